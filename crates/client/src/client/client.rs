@@ -18,15 +18,17 @@ use futures::Future;
 use tokio::runtime::current_thread::Runtime;
 
 use proto::error::ProtoError;
-use proto::xfer::{DnsRequestSender, DnsResponse};
+use proto::xfer::{DnsRequestSender, DnsResponse, RetryDnsHandle, RetryPolicy};
 
 #[cfg(feature = "dnssec")]
 use client::SecureClientHandle;
 use client::{BasicClientHandle, ClientConnection, ClientFuture, ClientHandle};
 use error::*;
+use op::{Message, UpdateBuilder};
 use rr::dnssec::Signer;
 #[cfg(feature = "dnssec")]
 use rr::dnssec::TrustAnchor;
+use rr::rdata::SOA;
 use rr::{DNSClass, Name, Record, RecordSet, RecordType};
 
 /// Client trait which implements basic DNS Client operations.
@@ -86,6 +88,17 @@ pub trait Client {
             .block_on(client.query(name.clone(), query_class, query_type))
     }
 
+    /// Sends the message as it is, see `ClientHandle::send_raw`
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - the message to send
+    fn send_raw(&self, message: Message) -> ClientResult<DnsResponse> {
+        let mut reactor = Runtime::new()?;
+        let (bg, mut client) = self.new_future();
+        reactor.spawn(bg).block_on(client.send_raw(message))
+    }
+
     /// Sends a NOTIFY message to the remote system
     ///
     /// # Arguments
@@ -383,6 +396,33 @@ pub trait Client {
             .spawn(bg)
             .block_on(client.delete_all(name_of_records, zone_origin, dns_class))
     }
+
+    /// Sends the update built with prerequisites and updates, see `UpdateBuilder`
+    ///
+    /// # Arguments
+    ///
+    /// * `update` - the prerequisites and updates of the zone
+    fn update(&self, update: UpdateBuilder) -> ClientResult<DnsResponse> {
+        let mut reactor = Runtime::new()?;
+        let (bg, mut client) = self.new_future();
+        reactor.spawn(bg).block_on(client.update(update))
+    }
+
+    /// Transfers the zone, AXFR, or the changes since the last SOA, IXFR
+    ///
+    /// See `ClientHandle::zone_transfer`, the transfer must be made over TCP.
+    ///
+    /// # Arguments
+    ///
+    /// * `zone_origin` - the zone name to transfer, i.e. SOA name
+    /// * `last_soa` - the SOA of the zone held by the client, for an IXFR, None for an AXFR
+    fn zone_transfer(&self, zone_origin: Name, last_soa: Option<SOA>) -> ClientResult<DnsResponse> {
+        let mut reactor = Runtime::new()?;
+        let (bg, mut client) = self.new_future();
+        reactor
+            .spawn(bg)
+            .block_on(client.zone_transfer(zone_origin, last_soa))
+    }
 }
 
 /// The Client is abstracted over either trust_dns::tcp::TcpClientConnection or
@@ -393,6 +433,7 @@ pub trait Client {
 pub struct SyncClient<CC> {
     conn: CC,
     signer: Option<Arc<Signer>>,
+    retry_policy: RetryPolicy,
 }
 
 impl<CC> SyncClient<CC>
@@ -405,7 +446,11 @@ where
     ///
     /// * `conn` - the [`ClientConnection`] to use for all communication
     pub fn new(conn: CC) -> Self {
-        SyncClient { conn, signer: None }
+        SyncClient {
+            conn,
+            signer: None,
+            retry_policy: RetryPolicy::new(0),
+        }
     }
 
     /// Creates a new DNS client with the specified connection type and a SIG0 signer.
@@ -420,8 +465,14 @@ where
         SyncClient {
             conn,
             signer: Some(Arc::new(signer)),
+            retry_policy: RetryPolicy::new(0),
         }
     }
+
+    /// Sets the policy with which failed requests are retried, by default they are not
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.retry_policy = retry_policy;
+    }
 }
 
 impl<CC> Client for SyncClient<CC>
@@ -431,7 +482,7 @@ where
     type Response = CC::Response;
     type Sender = CC::Sender;
     type SenderFuture = CC::SenderFuture;
-    type Handle = BasicClientHandle<CC::Response>;
+    type Handle = RetryDnsHandle<BasicClientHandle<CC::Response>>;
 
     #[allow(clippy::type_complexity)]
     fn new_future(
@@ -442,7 +493,11 @@ where
     ) {
         let stream = self.conn.new_stream(self.signer.clone());
 
-        ClientFuture::connect(stream)
+        let (background, handle) = ClientFuture::connect(stream);
+        (
+            background,
+            RetryDnsHandle::with_policy(handle, self.retry_policy),
+        )
     }
 }
 
@@ -451,6 +506,7 @@ where
 pub struct SecureSyncClient<CC> {
     conn: CC,
     signer: Option<Arc<Signer>>,
+    retry_policy: RetryPolicy,
 }
 
 #[cfg(feature = "dnssec")]
@@ -469,6 +525,7 @@ where
             conn,
             trust_anchor: None,
             signer: None,
+            retry_policy: RetryPolicy::new(0),
         }
     }
 
@@ -517,7 +574,7 @@ where
     type Response = CC::Response;
     type Sender = CC::Sender;
     type SenderFuture = CC::SenderFuture;
-    type Handle = SecureClientHandle<BasicClientHandle<Self::Response>>;
+    type Handle = SecureClientHandle<RetryDnsHandle<BasicClientHandle<Self::Response>>>;
 
     #[allow(clippy::type_complexity)]
     fn new_future(
@@ -529,6 +586,7 @@ where
         let stream = self.conn.new_stream(self.signer.clone());
 
         let (background, handle) = ClientFuture::connect(stream);
+        let handle = RetryDnsHandle::with_policy(handle, self.retry_policy);
         (background, SecureClientHandle::new(handle))
     }
 }
@@ -541,6 +599,7 @@ where
     conn: CC,
     trust_anchor: Option<TrustAnchor>,
     signer: Option<Arc<Signer>>,
+    retry_policy: RetryPolicy,
 }
 
 #[cfg(feature = "dnssec")]
@@ -571,10 +630,17 @@ where
         self
     }
 
+    /// The policy with which failed requests are retried, by default they are not
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
     pub fn build(self) -> SecureSyncClient<CC> {
         SecureSyncClient {
             conn: self.conn,
             signer: self.signer,
+            retry_policy: self.retry_policy,
         }
     }
 }
@@ -7,23 +7,27 @@
 
 use std::sync::Arc;
 use std::time::Duration;
+use std::vec;
 
-use futures::{Future, Poll};
+use futures::sync::mpsc::{self, UnboundedReceiver};
+use futures::{Async, Future, Poll, Stream};
 use proto::error::ProtoError;
 use proto::xfer::{
     BufDnsRequestStreamHandle, DnsClientStream, DnsExchange, DnsExchangeConnect, DnsHandle,
     DnsMultiplexer, DnsMultiplexerConnect, DnsMultiplexerSerialResponse, DnsRequest,
-    DnsRequestOptions, DnsRequestSender, DnsResponse, DnsStreamHandle, OneshotDnsResponseReceiver,
+    DnsRequestOptions, DnsRequestSender, DnsResponse, DnsStreamHandle, InFlightPolicy,
+    OneshotDnsResponseReceiver,
 };
 use rand;
 
 use error::*;
-use op::{Message, MessageType, OpCode, Query, update_message};
+use op::{Message, MessageType, OpCode, Query, UpdateBuilder, update_message};
 use rr::dnssec::Signer;
-use rr::{DNSClass, Name, Record, RecordSet, RecordType};
+use rr::rdata::SOA;
+use rr::{DNSClass, Name, RData, Record, RecordSet, RecordType};
 
 // TODO: this should be configurable
-pub const MAX_PAYLOAD_LEN: u16 = 1500 - 40 - 8; // 1500 (general MTU) - 40 (ipv6 header) - 8 (udp header)
+pub use proto::xfer::dns_request::MAX_PAYLOAD_LEN;
 
 // TODO: ClientFuture to ClientAsync or AsyncClient?
 /// A DNS Client implemented over futures-rs.
@@ -113,6 +117,26 @@ where
             },
         )
     }
+
+    /// Limits the requests in flight, sent and awaiting their response, see
+    ///  `DnsExchange::with_max_in_flight`
+    ///
+    /// # Arguments
+    ///
+    /// * `max` - the maximum number of requests in flight, at least 1
+    /// * `policy` - what to do with the requests over the limit
+    pub fn with_max_in_flight(self, max: usize, policy: InFlightPolicy) -> Self {
+        let inner = match self.inner {
+            InnerClientFuture::DnsExchangeConnect(connect) => {
+                InnerClientFuture::DnsExchangeConnect(connect.with_max_in_flight(max, policy))
+            }
+            InnerClientFuture::DnsExchange(exchange) => {
+                InnerClientFuture::DnsExchange(exchange.with_max_in_flight(max, policy))
+            }
+        };
+
+        Self { inner }
+    }
 }
 
 impl<SenderFuture, Sender, Response> Future for ClientFuture<SenderFuture, Sender, Response>
@@ -217,9 +241,95 @@ pub trait ClientHandle: 'static + Clone + DnsHandle + Send {
         query_class: DNSClass,
         query_type: RecordType,
     ) -> ClientResponse<<Self as DnsHandle>::Response> {
+        self.query_with_options(name, query_class, query_type, DnsRequestOptions::default())
+    }
+
+    /// A *classic* DNS query, with the EDNS and other options of the request
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the label to lookup
+    /// * `query_class` - most likely this should always be DNSClass::IN
+    /// * `query_type` - record type to lookup
+    /// * `options` - options of the request, e.g. `DnsRequestOptions::default().with_dnssec_ok(true)`
+    fn query_with_options(
+        &mut self,
+        name: Name,
+        query_class: DNSClass,
+        query_type: RecordType,
+        options: DnsRequestOptions,
+    ) -> ClientResponse<<Self as DnsHandle>::Response> {
+        let mut query = Query::query(name, query_type);
+        query.set_query_class(query_class);
+        ClientResponse(self.lookup(query, options))
+    }
+
+    /// Sends the message as it is, for testing the handling of unusual messages
+    ///
+    /// The id of the message is kept, the message is not signed, no EDNS is added, the counts of
+    ///  the header are emitted as they are set, see `Message::set_header`, and the request is not
+    ///  retried, see `DnsRequest::raw`.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - the message to send
+    fn send_raw(&mut self, message: Message) -> ClientResponse<<Self as DnsHandle>::Response> {
+        ClientResponse(self.send(DnsRequest::raw(message, DnsRequestOptions::default())))
+    }
+
+    /// A *classic* DNS query, yielding the answers as they are received, see `stream`
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the label to lookup
+    /// * `query_class` - most likely this should always be DNSClass::IN
+    /// * `query_type` - record type to lookup, e.g. ANY
+    /// * `options` - options of the request
+    fn query_stream(
+        &mut self,
+        name: Name,
+        query_class: DNSClass,
+        query_type: RecordType,
+        options: DnsRequestOptions,
+    ) -> ClientStreamingResponse<<Self as DnsHandle>::Response> {
         let mut query = Query::query(name, query_type);
         query.set_query_class(query_class);
-        ClientResponse(self.lookup(query, DnsRequestOptions::default()))
+
+        let mut message: Message = Message::new();
+        message
+            .set_id(rand::random())
+            .set_message_type(MessageType::Query)
+            .set_op_code(OpCode::Query)
+            .set_recursion_desired(true)
+            .add_query(query);
+        message.set_edns(options.query_edns());
+
+        self.stream(DnsRequest::new(message, options))
+    }
+
+    /// Sends the request, yielding the answers of each message of the response as it is received
+    ///
+    /// Unlike `send`, the messages of a multi-message response, e.g. `expects_multiple_responses`,
+    ///  are not all held until the request completes, see `DnsRequest::with_response_stream`.
+    ///  The answers of a message are yielded before the response completes, and so before a
+    ///  failure of the request, e.g. the verification of the TSIG of the last message, is known.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - the request to send
+    fn stream<R: Into<DnsRequest>>(
+        &mut self,
+        request: R,
+    ) -> ClientStreamingResponse<<Self as DnsHandle>::Response> {
+        let (sender, messages) = mpsc::unbounded();
+        let request = request.into().with_response_stream(sender);
+
+        ClientStreamingResponse {
+            messages,
+            response: Some(self.send(request)),
+            last: None,
+            records: Vec::new().into_iter(),
+        }
     }
 
     /// Sends a NOTIFY message to the remote system
@@ -614,6 +724,84 @@ pub trait ClientHandle: 'static + Clone + DnsHandle + Send {
 
         ClientResponse(self.send(message))
     }
+
+    /// Sends the update built with prerequisites and updates, see `UpdateBuilder`
+    ///
+    /// [RFC 2136](https://tools.ietf.org/html/rfc2136), DNS Update, April 1997
+    ///
+    /// # Arguments
+    ///
+    /// * `update` - the prerequisites and updates of the zone
+    ///
+    /// The update must go to a zone authority (i.e. the server used in the ClientConnection).
+    fn update(&mut self, update: UpdateBuilder) -> ClientResponse<<Self as DnsHandle>::Response> {
+        ClientResponse(self.send(update.build()))
+    }
+
+    /// Transfers the zone, AXFR, or the changes since the last SOA, IXFR
+    ///
+    /// [RFC 5936](https://tools.ietf.org/html/rfc5936), DNS Zone Transfer Protocol (AXFR), June 2010
+    /// [RFC 1995](https://tools.ietf.org/html/rfc1995), Incremental Zone Transfer in DNS, August 1996
+    ///
+    /// The transfer may span multiple messages, all are returned in the response. Transfers must
+    ///  be made over TCP, and are signed when the client has a TSIG signer, in which case the
+    ///  MACs of all the messages are verified.
+    ///
+    /// # Arguments
+    ///
+    /// * `zone_origin` - the zone name to transfer, i.e. SOA name
+    /// * `last_soa` - the SOA of the zone held by the client, for an IXFR, None for an AXFR
+    fn zone_transfer(
+        &mut self,
+        zone_origin: Name,
+        last_soa: Option<SOA>,
+    ) -> ClientResponse<<Self as DnsHandle>::Response> {
+        ClientResponse(self.send(zone_transfer_request(zone_origin, last_soa)))
+    }
+
+    /// Transfers the zone, AXFR, or the changes since the last SOA, IXFR, yielding the records
+    ///  as the messages are received
+    ///
+    /// See `zone_transfer`, the records of a large zone are not all held in memory. The records
+    ///  of a signed transfer are yielded before the MAC of the last message is verified, the
+    ///  stream fails after the records if the verification fails.
+    ///
+    /// # Arguments
+    ///
+    /// * `zone_origin` - the zone name to transfer, i.e. SOA name
+    /// * `last_soa` - the SOA of the zone held by the client, for an IXFR, None for an AXFR
+    fn zone_transfer_stream(
+        &mut self,
+        zone_origin: Name,
+        last_soa: Option<SOA>,
+    ) -> ClientStreamingResponse<<Self as DnsHandle>::Response> {
+        self.stream(zone_transfer_request(zone_origin, last_soa))
+    }
+}
+
+fn zone_transfer_request(zone_origin: Name, last_soa: Option<SOA>) -> DnsRequest {
+    let query_type = if last_soa.is_some() {
+        RecordType::IXFR
+    } else {
+        RecordType::AXFR
+    };
+
+    let mut message: Message = Message::new();
+    message
+        .set_id(rand::random())
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Query)
+        .add_query(Query::query(zone_origin.clone(), query_type));
+
+    // the SOA of the client is in the authority section, RFC 1995 section 3
+    if let Some(soa) = last_soa {
+        message.add_name_server(Record::from_rdata(zone_origin, 0, RData::SOA(soa)));
+    }
+
+    let mut options = DnsRequestOptions::default();
+    options.expects_multiple_responses = true;
+
+    DnsRequest::new(message, options)
 }
 
 /// A future result of a Client Request
@@ -632,3 +820,55 @@ where
         self.0.poll().map_err(ClientError::from)
     }
 }
+
+/// A stream of the answers of a Client Request, see `ClientHandle::stream`
+#[must_use = "streams do nothing unless polled"]
+pub struct ClientStreamingResponse<R>
+where
+    R: Future<Item = DnsResponse, Error = ProtoError> + Send + 'static,
+{
+    // the messages received before the last one
+    messages: UnboundedReceiver<Message>,
+    response: Option<R>,
+    // the completed response, yielded after the messages received before it
+    last: Option<DnsResponse>,
+    records: vec::IntoIter<Record>,
+}
+
+impl<R> Stream for ClientStreamingResponse<R>
+where
+    R: Future<Item = DnsResponse, Error = ProtoError> + Send + 'static,
+{
+    type Item = Record;
+    type Error = ClientError;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            if let Some(record) = self.records.next() {
+                return Ok(Async::Ready(Some(record)));
+            }
+
+            // the messages are all sent before the response completes
+            if let Ok(Async::Ready(Some(mut message))) = self.messages.poll() {
+                self.records = message.take_answers().into_iter();
+                continue;
+            }
+
+            if let Some(mut last) = self.last.take() {
+                self.records = last
+                    .messages_mut()
+                    .flat_map(Message::take_answers)
+                    .collect::<Vec<_>>()
+                    .into_iter();
+                continue;
+            }
+
+            let last = match self.response {
+                Some(ref mut response) => try_ready!(response.poll().map_err(ClientError::from)),
+                None => return Ok(Async::Ready(None)),
+            };
+            self.response = None;
+            self.last = Some(last);
+        }
+    }
+}
@@ -29,7 +29,9 @@ pub use self::client::SecureSyncClient;
 pub use self::client::{Client, SyncClient};
 pub use self::client_connection::ClientConnection;
 #[allow(deprecated)]
-pub use self::client_future::{BasicClientHandle, ClientFuture, ClientHandle, ClientResponse};
+pub use self::client_future::{
+    BasicClientHandle, ClientFuture, ClientHandle, ClientResponse, ClientStreamingResponse,
+};
 pub use self::memoize_client_handle::MemoizeClientHandle;
 
 /// This is an alias for [`trust_dns_proto::StreamHandle`]
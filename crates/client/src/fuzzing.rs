@@ -0,0 +1,57 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The fuzz targets of the client, see `trust_dns_proto::fuzzing` for the others
+
+use std::str;
+
+use proto::serialize::binary::{BinDecodable, BinEncodable};
+
+use rr::{Name, Record};
+use serialize::txt::{Lexer, Parser};
+
+/// Parses the input as a zone file of `example.com.`
+///
+/// Malformed zone files must fail to parse rather than panic, and the records of the zone must
+///  decode to the same records once encoded.
+pub fn zone_file(data: &[u8]) {
+    let text = match str::from_utf8(data) {
+        Ok(text) => text,
+        Err(_) => return,
+    };
+
+    let origin = Name::from_ascii("example.com.").expect("bad origin");
+    let records = match Parser::new().parse(Lexer::new(text), Some(origin)) {
+        Ok((_, records)) => records,
+        Err(_) => return,
+    };
+
+    for record in records
+        .values()
+        .flat_map(|rrset| rrset.records_without_rrsigs())
+    {
+        // records which don't fit in a message fail to encode
+        if let Ok(bytes) = record.to_bytes() {
+            let decoded = Record::from_bytes(&bytes).expect("failed to decode");
+            assert_eq!(&decoded, record);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zone_file() {
+        zone_file(b"@ 3600 IN SOA ns.example.com. admin.example.com. 1 2 3 4 5\nwww A 192.0.2.1\n");
+        zone_file(b"$INCLUDE other.zone\n");
+        zone_file(b"$TTL 99999999999\n");
+        zone_file(b"www 7102w IN A 192.0.2.1\n");
+        zone_file(b"www NULL\n");
+    }
+}
@@ -291,6 +291,8 @@ extern crate webpki;
 
 pub mod client;
 pub mod error;
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
 #[cfg(feature = "mdns")]
 pub mod multicast;
 pub mod op;
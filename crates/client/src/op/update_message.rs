@@ -550,3 +550,360 @@ pub fn delete_all(name_of_records: Name, zone_origin: Name, dns_class: DNSClass)
 
     message
 }
+
+/// Builds an update message from any combination of prerequisites and updates
+///
+/// [RFC 2136](https://tools.ietf.org/html/rfc2136), DNS Update, April 1997
+///
+/// The prerequisites must all hold for the server to apply the updates, which are applied
+///  atomically in order (atomicity depends on the server). The zone, prerequisite and update
+///  sections, and the class and TTL of each record, are set as required by the RFC.
+///
+/// ```rust
+/// use std::net::Ipv4Addr;
+/// use std::str::FromStr;
+///
+/// use trust_dns::op::UpdateBuilder;
+/// use trust_dns::rr::{Name, RData, Record, RecordType};
+///
+/// let origin = Name::from_str("example.com.").unwrap();
+/// let www = Name::from_str("www.example.com.").unwrap();
+///
+/// // replace the address of www, only if it has no CNAME
+/// let message = UpdateBuilder::new(origin)
+///     .require_absent(www.clone(), RecordType::CNAME)
+///     .delete_rrset(www.clone(), RecordType::A)
+///     .add(Record::from_rdata(www, 300, RData::A(Ipv4Addr::new(192, 0, 2, 1))))
+///     .build();
+/// ```
+#[derive(Debug, Clone)]
+pub struct UpdateBuilder {
+    zone_origin: Name,
+    dns_class: DNSClass,
+    prerequisites: Vec<Record>,
+    updates: Vec<Record>,
+}
+
+impl UpdateBuilder {
+    /// Creates an empty update of the zone, in the IN class
+    ///
+    /// # Arguments
+    ///
+    /// * `zone_origin` - the zone name to update, i.e. SOA name
+    pub fn new(zone_origin: Name) -> Self {
+        UpdateBuilder {
+            zone_origin,
+            dns_class: DNSClass::IN,
+            prerequisites: Vec::new(),
+            updates: Vec::new(),
+        }
+    }
+
+    /// Sets the class of the zone, records added to the zone must be of this class
+    pub fn dns_class(mut self, dns_class: DNSClass) -> Self {
+        self.dns_class = dns_class;
+        self
+    }
+
+    /// Requires at least one record of the name and type to exist
+    ///
+    /// ```text
+    /// 2.4.1 - RRset Exists (Value Independent)
+    ///
+    ///   At least one RR with a specified NAME and TYPE (in the zone and class
+    ///   specified in the Zone Section) must exist.
+    /// ```
+    pub fn require_exists(mut self, name: Name, record_type: RecordType) -> Self {
+        assert!(self.zone_origin.zone_of(&name));
+
+        let mut prerequisite = Record::with(name, record_type, 0);
+        prerequisite.set_dns_class(DNSClass::ANY);
+        self.prerequisites.push(prerequisite);
+        self
+    }
+
+    /// Requires the rrset to exist with exactly these records, the TTLs are ignored
+    ///
+    /// ```text
+    /// 2.4.2 - RRset Exists (Value Dependent)
+    ///
+    ///   A set of RRs with a specified NAME and TYPE exists and has the same
+    ///   members with the same RDATAs as the RRset specified here in this
+    ///   section.
+    /// ```
+    pub fn require_rrset<R: Into<RecordSet>>(mut self, rrset: R) -> Self {
+        let mut rrset = rrset.into();
+        assert!(self.zone_origin.zone_of(rrset.name()));
+
+        rrset.set_dns_class(self.dns_class);
+        rrset.set_ttl(0);
+        self.prerequisites.extend(rrset);
+        self
+    }
+
+    /// Requires no record of the name and type to exist
+    ///
+    /// ```text
+    /// 2.4.3 - RRset Does Not Exist
+    ///
+    ///   No RRs with a specified NAME and TYPE (in the zone and class denoted
+    ///   by the Zone Section) can exist.
+    /// ```
+    pub fn require_absent(mut self, name: Name, record_type: RecordType) -> Self {
+        assert!(self.zone_origin.zone_of(&name));
+
+        let mut prerequisite = Record::with(name, record_type, 0);
+        prerequisite.set_dns_class(DNSClass::NONE);
+        self.prerequisites.push(prerequisite);
+        self
+    }
+
+    /// Requires at least one record of any type to exist at the name
+    ///
+    /// ```text
+    /// 2.4.4 - Name Is In Use
+    ///
+    ///   Name is in use.  At least one RR with a specified NAME (in the zone
+    ///   and class specified by the Zone Section) must exist.
+    /// ```
+    pub fn require_name_in_use(self, name: Name) -> Self {
+        self.require_exists(name, RecordType::ANY)
+    }
+
+    /// Requires no record of any type to exist at the name
+    ///
+    /// ```text
+    /// 2.4.5 - Name Is Not In Use
+    ///
+    ///   Name is not in use.  No RR of any type is owned by a specified NAME.
+    /// ```
+    pub fn require_name_not_in_use(self, name: Name) -> Self {
+        self.require_absent(name, RecordType::ANY)
+    }
+
+    /// Adds the record to its rrset, duplicates are ignored by the server
+    ///
+    /// ```text
+    /// 2.5.1 - Add To An RRset
+    ///
+    ///    RRs are added to the Update Section whose NAME, TYPE, TTL, RDLENGTH
+    ///    and RDATA are those being added, and CLASS is the same as the zone
+    ///    class.
+    /// ```
+    #[allow(clippy::should_implement_trait)]
+    pub fn add(mut self, mut record: Record) -> Self {
+        assert!(self.zone_origin.zone_of(record.name()));
+
+        record.set_dns_class(self.dns_class);
+        self.updates.push(record);
+        self
+    }
+
+    /// Adds all the records of the rrset, see `add`
+    pub fn add_rrset<R: Into<RecordSet>>(mut self, rrset: R) -> Self {
+        for record in rrset.into() {
+            self = self.add(record);
+        }
+        self
+    }
+
+    /// Deletes the rrset of the name and type
+    ///
+    /// ```text
+    /// 2.5.2 - Delete An RRset
+    ///
+    ///   One RR is added to the Update Section whose NAME and TYPE are those
+    ///   of the RRset to be deleted.  TTL must be specified as zero (0) and is
+    ///   otherwise not used by the primary master.  CLASS must be specified as
+    ///   ANY.
+    /// ```
+    pub fn delete_rrset(mut self, name: Name, record_type: RecordType) -> Self {
+        assert!(self.zone_origin.zone_of(&name));
+
+        let mut record = Record::with(name, record_type, 0);
+        record.set_dns_class(DNSClass::ANY);
+        record.set_rdata(RData::NULL(NULL::new()));
+        self.updates.push(record);
+        self
+    }
+
+    /// Deletes the record, matching its name, type and rdata, from its rrset
+    ///
+    /// ```text
+    /// 2.5.4 - Delete An RR From An RRset
+    ///
+    ///   RRs to be deleted are added to the Update Section.  The NAME, TYPE,
+    ///   RDLENGTH and RDATA must match the RR being deleted.  TTL must be
+    ///   specified as zero (0) and will otherwise be ignored by the primary
+    ///   master.  CLASS must be specified as NONE to distinguish this from an
+    ///   RR addition.
+    /// ```
+    pub fn delete_record(mut self, mut record: Record) -> Self {
+        assert!(self.zone_origin.zone_of(record.name()));
+
+        record.set_dns_class(DNSClass::NONE);
+        record.set_ttl(0);
+        self.updates.push(record);
+        self
+    }
+
+    /// Deletes all the rrsets of the name
+    ///
+    /// ```text
+    /// 2.5.3 - Delete All RRsets From A Name
+    ///
+    ///   One RR is added to the Update Section whose NAME is that of the name
+    ///   to be cleansed of RRsets.  TYPE must be specified as ANY.  TTL must
+    ///   be specified as zero (0) and is otherwise not used by the primary
+    ///   master.  CLASS must be specified as ANY.
+    /// ```
+    pub fn delete_all(mut self, name: Name) -> Self {
+        assert!(self.zone_origin.zone_of(&name));
+
+        let mut record = Record::with(name, RecordType::ANY, 0);
+        record.set_dns_class(DNSClass::ANY);
+        self.updates.push(record);
+        self
+    }
+
+    /// The zone name to update
+    pub fn zone_origin(&self) -> &Name {
+        &self.zone_origin
+    }
+
+    /// The prerequisites, with the class and TTL set as required by the RFC
+    pub fn prerequisites(&self) -> &[Record] {
+        &self.prerequisites
+    }
+
+    /// The updates, with the class and TTL set as required by the RFC
+    pub fn updates(&self) -> &[Record] {
+        &self.updates
+    }
+
+    /// Returns the update message, with a random id
+    pub fn build(self) -> Message {
+        // for updates, the query section is used for the zone
+        let mut zone: Query = Query::new();
+        zone.set_name(self.zone_origin)
+            .set_query_class(self.dns_class)
+            .set_query_type(RecordType::SOA);
+
+        // build the message
+        let mut message: Message = Message::new();
+        message
+            .set_id(rand::random())
+            .set_message_type(MessageType::Query)
+            .set_op_code(OpCode::Update)
+            .set_recursion_desired(false);
+        message.add_zone(zone);
+        message.add_pre_requisites(self.prerequisites);
+        message.add_updates(self.updates);
+
+        // Extended dns
+        {
+            let edns = message.edns_mut();
+            edns.set_max_payload(MAX_PAYLOAD_LEN);
+            edns.set_version(0);
+        }
+
+        message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn test_update_builder_sections() {
+        let origin = Name::from_str("example.com.").unwrap();
+        let www = Name::from_str("www.example.com.").unwrap();
+        let a = Record::from_rdata(www.clone(), 300, RData::A(Ipv4Addr::new(192, 0, 2, 1)));
+
+        let message = UpdateBuilder::new(origin.clone())
+            .require_exists(www.clone(), RecordType::A)
+            .require_absent(www.clone(), RecordType::CNAME)
+            .require_name_in_use(www.clone())
+            .delete_rrset(www.clone(), RecordType::A)
+            .delete_record(a.clone())
+            .add(a.clone())
+            .delete_all(www.clone())
+            .build();
+
+        assert_eq!(message.op_code(), OpCode::Update);
+        assert_eq!(message.zones().len(), 1);
+        assert_eq!(message.zones()[0].name(), &origin);
+        assert_eq!(message.zones()[0].query_type(), RecordType::SOA);
+        assert_eq!(message.zones()[0].query_class(), DNSClass::IN);
+
+        let prerequisites = message.prerequisites();
+        assert_eq!(prerequisites.len(), 3);
+        assert_eq!(prerequisites[0].dns_class(), DNSClass::ANY);
+        assert_eq!(prerequisites[0].rr_type(), RecordType::A);
+        assert_eq!(prerequisites[1].dns_class(), DNSClass::NONE);
+        assert_eq!(prerequisites[1].rr_type(), RecordType::CNAME);
+        assert_eq!(prerequisites[2].dns_class(), DNSClass::ANY);
+        assert_eq!(prerequisites[2].rr_type(), RecordType::ANY);
+        assert!(prerequisites.iter().all(|r| r.ttl() == 0));
+
+        let updates = message.updates();
+        assert_eq!(updates.len(), 4);
+        assert_eq!(updates[0].dns_class(), DNSClass::ANY);
+        assert_eq!(updates[0].ttl(), 0);
+        assert_eq!(updates[1].dns_class(), DNSClass::NONE);
+        assert_eq!(updates[1].ttl(), 0);
+        assert_eq!(updates[1].rdata(), a.rdata());
+        assert_eq!(updates[2], a);
+        assert_eq!(updates[3].dns_class(), DNSClass::ANY);
+        assert_eq!(updates[3].rr_type(), RecordType::ANY);
+
+        // the message must survive the round trip, e.g. the empty rdatas
+        let bytes = message.to_vec().unwrap();
+        let decoded = Message::from_vec(&bytes).unwrap();
+        assert_eq!(decoded.prerequisites(), message.prerequisites());
+        assert_eq!(decoded.updates().len(), 4);
+    }
+
+    #[test]
+    fn test_update_builder_require_rrset() {
+        let origin = Name::from_str("example.com.").unwrap();
+        let www = Name::from_str("www.example.com.").unwrap();
+        let mut rrset = RecordSet::new(&www, RecordType::A, 0);
+        rrset.insert(
+            Record::from_rdata(www.clone(), 300, RData::A(Ipv4Addr::new(192, 0, 2, 1))),
+            0,
+        );
+        rrset.insert(
+            Record::from_rdata(www.clone(), 300, RData::A(Ipv4Addr::new(192, 0, 2, 2))),
+            0,
+        );
+
+        let message = UpdateBuilder::new(origin)
+            .require_rrset(rrset.clone())
+            .add_rrset(rrset)
+            .build();
+
+        assert_eq!(message.prerequisites().len(), 2);
+        assert!(message
+            .prerequisites()
+            .iter()
+            .all(|r| r.ttl() == 0 && r.dns_class() == DNSClass::IN));
+        assert_eq!(message.updates().len(), 2);
+        assert!(message.updates().iter().all(|r| r.ttl() == 300));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_update_builder_out_of_zone() {
+        UpdateBuilder::new(Name::from_str("example.com.").unwrap())
+            .add(Record::from_rdata(
+                Name::from_str("www.example.net.").unwrap(),
+                300,
+                RData::A(Ipv4Addr::new(192, 0, 2, 1)),
+            ));
+    }
+}
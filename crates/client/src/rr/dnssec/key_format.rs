@@ -1,5 +1,11 @@
 #[cfg(feature = "openssl")]
-use openssl::ec::EcKey;
+use openssl::bn::{BigNum, BigNumContext};
+#[cfg(feature = "openssl")]
+use openssl::ec::{EcGroup, EcKey, EcPoint};
+#[cfg(feature = "openssl")]
+use openssl::nid::Nid;
+#[cfg(feature = "openssl")]
+use openssl::pkey::PKey;
 #[cfg(feature = "openssl")]
 use openssl::rsa::Rsa;
 #[cfg(feature = "openssl")]
@@ -26,6 +32,8 @@ pub enum KeyFormat {
     Pem,
     /// Pkcs8, a pkcs8 formatted private key
     Pkcs8,
+    /// The `K<zone>+<algorithm>+<key tag>.private` files of the BIND `dnssec-keygen`
+    Bind,
 }
 
 impl KeyFormat {
@@ -58,9 +66,23 @@ impl KeyFormat {
                             format!("could not decode RSA from PEM, bad password?: {}", e)
                         })?
                     }
+                    KeyFormat::Bind => {
+                        let fields = BindFields::parse(bytes, algorithm)?;
+                        Rsa::from_private_components(
+                            fields.big_num("Modulus")?,
+                            fields.big_num("PublicExponent")?,
+                            fields.big_num("PrivateExponent")?,
+                            fields.big_num("Prime1")?,
+                            fields.big_num("Prime2")?,
+                            fields.big_num("Exponent1")?,
+                            fields.big_num("Exponent2")?,
+                            fields.big_num("Coefficient")?,
+                        )
+                        .map_err(|e| format!("error reading RSA from BIND: {}", e))?
+                    }
                     e => {
                         return Err(format!(
-                            "unsupported key format with RSA (DER or PEM only): \
+                            "unsupported key format with RSA (DER, PEM or BIND only): \
                              {:?}",
                             e
                         ).into())
@@ -89,6 +111,22 @@ impl KeyFormat {
                     Ok(KeyPair::from_ec_key(key)
                         .map_err(|e| format!("could not tranlate RSA to KeyPair: {}", e))?)
                 }
+                #[cfg(feature = "openssl")]
+                KeyFormat::Bind => {
+                    let fields = BindFields::parse(bytes, algorithm)?;
+                    let group = EcGroup::from_curve_name(ec_curve(algorithm))?;
+                    let private_key = fields.big_num("PrivateKey")?;
+
+                    // BIND only stores the private key, the public point is derived from it
+                    let context = BigNumContext::new()?;
+                    let mut public_key = EcPoint::new(&group)?;
+                    public_key.mul_generator(&group, &private_key, &context)?;
+                    let key = EcKey::from_private_components(&group, &private_key, &public_key)
+                        .map_err(|e| format!("error reading EC from BIND: {}", e))?;
+
+                    Ok(KeyPair::from_ec_key(key)
+                        .map_err(|e| format!("could not tranlate EC to KeyPair: {}", e))?)
+                }
                 #[cfg(feature = "ring")]
                 KeyFormat::Pkcs8 => {
                     let ring_algorithm = if algorithm == Algorithm::ECDSAP256SHA256 {
@@ -112,8 +150,16 @@ impl KeyFormat {
 
                     Ok(KeyPair::from_ed25519(key))
                 }
+                #[cfg(feature = "ring")]
+                KeyFormat::Bind => {
+                    let fields = BindFields::parse(bytes, algorithm)?;
+                    let seed = fields.get("PrivateKey")?;
+                    let key = Ed25519KeyPair::from_seed_unchecked(Input::from(&seed[..]))?;
+
+                    Ok(KeyPair::from_ed25519(key))
+                }
                 e => Err(format!(
-                    "unsupported key format with ED25519 (only Pkcs8 supported): {:?}",
+                    "unsupported key format with ED25519 (Pkcs8 or BIND only): {:?}",
                     e
                 ).into()),
             },
@@ -150,12 +196,16 @@ impl KeyFormat {
             Algorithm::RSASHA256 | Algorithm::RSASHA512 => KeyPair::generate(algorithm)?,
             Algorithm::ECDSAP256SHA256 | Algorithm::ECDSAP384SHA384 => match self {
                 #[cfg(feature = "openssl")]
-                KeyFormat::Der | KeyFormat::Pem => KeyPair::generate(algorithm)?,
+                KeyFormat::Der | KeyFormat::Pem | KeyFormat::Bind => KeyPair::generate(algorithm)?,
                 #[cfg(feature = "ring")]
                 KeyFormat::Pkcs8 => return KeyPair::generate_pkcs8(algorithm),
                 e => return Err(format!("unsupported key format with EC: {:?}", e).into()),
             }
             #[cfg(feature = "ring")]
+            Algorithm::ED25519 if self == KeyFormat::Bind => {
+                return Err("unsupported key format with ED25519 (Pkcs8 only)".into())
+            }
+            #[cfg(feature = "ring")]
             Algorithm::ED25519 => return KeyPair::generate_pkcs8(algorithm),
             #[cfg(not(all(feature = "openssl", feature = "ring")))]
             e => {
@@ -192,8 +242,15 @@ impl KeyFormat {
 
                         key.map_err(|e| format!("error writing key as PEM: {}", e).into())
                     }
+                    KeyFormat::Bind => {
+                        // BIND keys are never encrypted
+                        if password.is_some() {
+                            return Err(format!("Can only password protect PEM: {:?}", self).into());
+                        }
+                        encode_bind(algorithm, pkey)
+                    }
                     e => Err(format!(
-                        "unsupported key format with RSA or EC (DER or PEM \
+                        "unsupported key format with RSA or EC (DER, PEM or BIND \
                          only): {:?}",
                         e
                     ).into()),
@@ -265,6 +322,130 @@ impl KeyFormat {
     }
 }
 
+/// The fields of a BIND private key file, e.g.
+///
+/// ```text
+/// Private-key-format: v1.3
+/// Algorithm: 13 (ECDSAP256SHA256)
+/// PrivateKey: GU6SnQ/Ou+xC5RumuIUIuJZteXT2z0O/ok1s38Et6mQ=
+/// Created: 20190101000000
+/// ```
+#[cfg(any(feature = "openssl", feature = "ring"))]
+struct BindFields<'a>(Vec<(&'a str, &'a str)>);
+
+#[cfg(any(feature = "openssl", feature = "ring"))]
+impl<'a> BindFields<'a> {
+    /// Reads the fields of the file, verifying that it is a private key of the algorithm
+    fn parse(bytes: &'a [u8], algorithm: Algorithm) -> DnsSecResult<Self> {
+        let text = ::std::str::from_utf8(bytes)
+            .map_err(|e| format!("BIND private key is not UTF-8: {}", e))?;
+        let fields = BindFields(
+            text.lines()
+                .filter_map(|line| {
+                    let mut field = line.splitn(2, ':');
+                    Some((field.next()?.trim(), field.next()?.trim()))
+                })
+                .collect(),
+        );
+
+        let format = fields.value("Private-key-format")?;
+        if !format.starts_with("v1.") {
+            return Err(format!("unsupported BIND private key format: {}", format).into());
+        }
+
+        // the algorithm is the number followed by its mnemonic, e.g. `8 (RSASHA256)`
+        let key_algorithm = fields.value("Algorithm")?;
+        let key_algorithm = key_algorithm
+            .split_whitespace()
+            .next()
+            .and_then(|number| number.parse::<u8>().ok())
+            .ok_or_else(|| format!("invalid BIND algorithm: {}", key_algorithm))?;
+        if key_algorithm != u8::from(algorithm) {
+            return Err(format!(
+                "BIND private key algorithm {} is not {:?}",
+                key_algorithm, algorithm
+            )
+            .into());
+        }
+
+        Ok(fields)
+    }
+
+    fn value(&self, field: &str) -> DnsSecResult<&'a str> {
+        self.0
+            .iter()
+            .find(|&&(name, _)| name == field)
+            .map(|&(_, value)| value)
+            .ok_or_else(|| format!("BIND private key lacks the {} field", field).into())
+    }
+
+    /// The base64 decoded value of the field
+    fn get(&self, field: &str) -> DnsSecResult<Vec<u8>> {
+        ::data_encoding::BASE64
+            .decode(self.value(field)?.as_bytes())
+            .map_err(|e| format!("invalid BIND {} field: {}", field, e).into())
+    }
+
+    #[cfg(feature = "openssl")]
+    fn big_num(&self, field: &str) -> DnsSecResult<BigNum> {
+        Ok(BigNum::from_slice(&self.get(field)?)?)
+    }
+}
+
+#[cfg(feature = "openssl")]
+fn ec_curve(algorithm: Algorithm) -> Nid {
+    if algorithm == Algorithm::ECDSAP256SHA256 {
+        Nid::X9_62_PRIME256V1
+    } else {
+        Nid::SECP384R1
+    }
+}
+
+/// Writes the RSA or EC key as a BIND private key file
+#[cfg(feature = "openssl")]
+fn encode_bind(algorithm: Algorithm, pkey: &PKey<Private>) -> DnsSecResult<Vec<u8>> {
+    use data_encoding::BASE64;
+
+    let mut fields = vec![
+        ("Private-key-format", "v1.3".to_string()),
+        (
+            "Algorithm",
+            format!("{} ({})", u8::from(algorithm), algorithm.as_str()),
+        ),
+    ];
+
+    match algorithm {
+        Algorithm::ECDSAP256SHA256 | Algorithm::ECDSAP384SHA384 => {
+            // the private key is padded to the size of the curve, RFC 6605 section 4
+            let size = if algorithm == Algorithm::ECDSAP256SHA256 { 32 } else { 48 };
+            let private_key = pkey.ec_key()?.private_key().to_vec();
+            let mut padded = vec![0; size - private_key.len().min(size)];
+            padded.extend_from_slice(&private_key);
+
+            fields.push(("PrivateKey", BASE64.encode(&padded)));
+        }
+        _ => {
+            let rsa = pkey.rsa()?;
+            let missing = || DnsSecError::from("RSA key lacks its CRT components");
+
+            fields.push(("Modulus", BASE64.encode(&rsa.n().to_vec())));
+            fields.push(("PublicExponent", BASE64.encode(&rsa.e().to_vec())));
+            fields.push(("PrivateExponent", BASE64.encode(&rsa.d().to_vec())));
+            fields.push(("Prime1", BASE64.encode(&rsa.p().ok_or_else(missing)?.to_vec())));
+            fields.push(("Prime2", BASE64.encode(&rsa.q().ok_or_else(missing)?.to_vec())));
+            fields.push(("Exponent1", BASE64.encode(&rsa.dmp1().ok_or_else(missing)?.to_vec())));
+            fields.push(("Exponent2", BASE64.encode(&rsa.dmq1().ok_or_else(missing)?.to_vec())));
+            fields.push(("Coefficient", BASE64.encode(&rsa.iqmp().ok_or_else(missing)?.to_vec())));
+        }
+    }
+
+    Ok(fields
+        .into_iter()
+        .map(|(field, value)| format!("{}: {}\n", field, value))
+        .collect::<String>()
+        .into_bytes())
+}
+
 #[cfg(test)]
 mod tests {
     pub use super::*;
@@ -311,6 +492,46 @@ mod tests {
         encode_decode_with_format(KeyFormat::Pkcs8, algorithm, true, true);
     }
 
+    #[test]
+    #[cfg(feature = "openssl")]
+    fn test_rsa_encode_decode_bind() {
+        let algorithm = Algorithm::RSASHA256;
+        encode_decode_with_format(KeyFormat::Bind, algorithm, false, true);
+    }
+
+    #[test]
+    #[cfg(feature = "openssl")]
+    fn test_ec_encode_decode_bind() {
+        let algorithm = Algorithm::ECDSAP384SHA384;
+        encode_decode_with_format(KeyFormat::Bind, algorithm, false, true);
+    }
+
+    #[test]
+    #[cfg(feature = "ring")]
+    fn test_ed25519_decode_bind() {
+        let key = b"Private-key-format: v1.3
+Algorithm: 15 (ED25519)
+PrivateKey: AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=
+Created: 20190101000000
+";
+
+        let key_pair = KeyFormat::Bind
+            .decode_key(key, None, Algorithm::ED25519)
+            .expect("failed to decode");
+        assert_eq!(
+            ::data_encoding::BASE64.encode(&key_pair.to_public_bytes().unwrap()),
+            "A6EHv/POEL4dcN0Y50vAmWfk1jCbpQ1fHdyGZBJVMbg="
+        );
+
+        // the algorithm of the key must match
+        assert!(KeyFormat::Bind
+            .decode_key(key, None, Algorithm::ECDSAP256SHA256)
+            .is_err());
+        assert!(KeyFormat::Bind
+            .decode_key(b"Algorithm: 15 (ED25519)\n", None, Algorithm::ED25519)
+            .is_err());
+    }
+
     #[cfg(test)]
     fn encode_decode_with_format(
         key_format: KeyFormat,
@@ -20,6 +20,8 @@
 mod key_format;
 mod keypair;
 mod signer;
+#[cfg(feature = "dnssec")]
+mod tsig;
 
 use proto::rr::dnssec;
 
@@ -33,6 +35,8 @@ pub use self::dnssec::PublicKey;
 pub use self::dnssec::PublicKeyBuf;
 pub use self::dnssec::PublicKeyEnum;
 pub use self::signer::Signer;
+#[cfg(feature = "dnssec")]
+pub use self::tsig::TSigner;
 pub use self::dnssec::SupportedAlgorithms;
 pub use self::dnssec::TrustAnchor;
 pub use self::dnssec::tbs;
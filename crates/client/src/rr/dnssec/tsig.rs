@@ -0,0 +1,375 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! TSigner is a structure for signing and verifying messages with TSIG, a shared secret key
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use proto::error::{ProtoErrorKind, ProtoResult};
+use proto::rr::dnssec::rdata::tsig::{self, TsigAlgorithm, TSIG};
+
+use op::{Message, MessageFinalizer, MessageVerifier};
+use rr::rdata::DNSSECRData;
+use rr::{Name, RData, Record};
+
+/// The number of unsigned messages which must be accepted between the signed messages of a
+///  multi-message response, [RFC 8945 Section 5.3.1](https://tools.ietf.org/html/rfc8945#section-5.3.1)
+const MAX_UNSIGNED_MESSAGES: usize = 99;
+
+/// Signs and verifies messages with TSIG, [RFC 8945](https://tools.ietf.org/html/rfc8945)
+///
+/// As a `MessageFinalizer` all requests are signed, and the MACs of the responses are verified.
+///  For multi-message responses, e.g. AXFR, the MAC of each message covers the MAC of the
+///  previous signed message, such that the messages can't be reordered, dropped or replaced.
+#[derive(Clone)]
+pub struct TSigner {
+    key: Vec<u8>,
+    algorithm: TsigAlgorithm,
+    signer_name: Name,
+    fudge: u16,
+}
+
+impl TSigner {
+    /// Creates a new signer
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - the shared secret
+    /// * `algorithm` - the MAC algorithm, must be supported, see `TsigAlgorithm::is_supported`
+    /// * `signer_name` - the name of the key, e.g. `transfer-key.example.com.`
+    /// * `fudge` - seconds of clock skew permitted between the signer and the verifier, 300 is recommended
+    pub fn new(
+        key: Vec<u8>,
+        algorithm: TsigAlgorithm,
+        signer_name: Name,
+        fudge: u16,
+    ) -> ProtoResult<Self> {
+        if !algorithm.is_supported() {
+            return Err(
+                ProtoErrorKind::Msg(format!("unsupported tsig algorithm: {}", algorithm)).into(),
+            );
+        }
+
+        Ok(TSigner {
+            key,
+            algorithm,
+            signer_name,
+            fudge,
+        })
+    }
+
+    /// The MAC algorithm
+    pub fn algorithm(&self) -> &TsigAlgorithm {
+        &self.algorithm
+    }
+
+    /// The name of the key
+    pub fn signer_name(&self) -> &Name {
+        &self.signer_name
+    }
+
+    /// Seconds of clock skew permitted
+    pub fn fudge(&self) -> u16 {
+        self.fudge
+    }
+
+    /// Computes the MAC of the data, see `tsig::message_tbs`
+    pub fn sign(&self, tbs: &[u8]) -> ProtoResult<Vec<u8>> {
+        self.algorithm.mac_data(&self.key, tbs)
+    }
+
+    /// Verifies the MAC of the data, see `tsig::message_tbs`
+    pub fn verify(&self, tbs: &[u8], mac: &[u8]) -> ProtoResult<()> {
+        self.algorithm.verify_mac(&self.key, tbs, mac)
+    }
+
+    /// Returns an error if `time` is not within the fudge of `now`
+    pub fn verify_time(&self, time: u64, now: u64) -> ProtoResult<()> {
+        let skew = if time > now { time - now } else { now - time };
+        if skew > u64::from(self.fudge) {
+            return Err(ProtoErrorKind::Msg(format!(
+                "tsig time {} is outside of the fudge {} of {}",
+                time, self.fudge, now
+            ))
+            .into());
+        }
+
+        Ok(())
+    }
+
+    /// Signs the encoded message, appending the TSIG record to it
+    ///
+    /// # Arguments
+    ///
+    /// * `previous_mac` - the MAC of the request for the first message of a response, the MAC of
+    ///                    the previous message for the following messages of a multi-message response
+    /// * `message` - the encoded message, which must not be signed yet
+    /// * `first_message` - false for the following messages of a multi-message response
+    /// * `time` - seconds since the Unix epoch
+    /// * `error` - the TSIG error to return, e.g. BADTIME, or 0
+    ///
+    /// # Return
+    ///
+    /// The MAC of the message, to be passed as the `previous_mac` of the next message
+    pub fn sign_message_bytes(
+        &self,
+        previous_mac: Option<&[u8]>,
+        message: &mut Vec<u8>,
+        first_message: bool,
+        time: u64,
+        error: u16,
+    ) -> ProtoResult<Vec<u8>> {
+        if message.len() < 12 {
+            return Err(ProtoErrorKind::Message("message is too short to be signed").into());
+        }
+
+        let oid = u16::from(message[0]) << 8 | u16::from(message[1]);
+        let pre_tsig = TSIG::new(
+            self.algorithm.clone(),
+            time,
+            self.fudge,
+            Vec::new(),
+            oid,
+            error,
+            Vec::new(),
+        );
+
+        let tbs = tsig::message_tbs(
+            previous_mac,
+            message,
+            &pre_tsig,
+            &self.signer_name,
+            first_message,
+        )?;
+        let mac = self.sign(&tbs)?;
+
+        tsig::append_tsig_record(
+            message,
+            &tsig::make_tsig_record(self.signer_name.clone(), pre_tsig.set_mac(mac.clone())),
+        )?;
+
+        Ok(mac)
+    }
+}
+
+fn now() -> ProtoResult<u64> {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .map_err(|_| ProtoErrorKind::Message("Current time is before the Unix epoch.").into())
+}
+
+fn as_tsig(record: &Record) -> Option<&TSIG> {
+    match *record.rdata() {
+        RData::DNSSEC(DNSSECRData::TSIG(ref tsig)) => Some(tsig),
+        _ => None,
+    }
+}
+
+/// The state of the verification of the responses to a request
+struct ResponseVerifier {
+    signer: TSigner,
+    previous_mac: Vec<u8>,
+    // unsigned intermediate messages since the last signed message
+    unsigned: Vec<u8>,
+    unsigned_count: usize,
+    first_message: bool,
+}
+
+impl ResponseVerifier {
+    fn verify(&mut self, response: &[u8]) -> ProtoResult<bool> {
+        let (tbs, record) = match tsig::signed_bitmessage_to_buf(None, response, self.first_message)
+        {
+            Ok(signed) => signed,
+            Err(e) => {
+                if self.first_message || self.unsigned_count >= MAX_UNSIGNED_MESSAGES {
+                    return Err(e);
+                }
+
+                self.unsigned.extend_from_slice(response);
+                self.unsigned_count += 1;
+                return Ok(false);
+            }
+        };
+
+        let tsig = as_tsig(&record).expect("not a tsig record");
+        if record.name() != &self.signer.signer_name || tsig.algorithm() != &self.signer.algorithm {
+            return Err(ProtoErrorKind::Msg(format!(
+                "response is signed with another key: {} {}",
+                record.name(),
+                tsig.algorithm()
+            ))
+            .into());
+        }
+        if tsig.error() != 0 {
+            return Err(
+                ProtoErrorKind::Msg(format!("tsig error in response: {}", tsig.error())).into(),
+            );
+        }
+
+        let mut signed =
+            Vec::with_capacity(2 + self.previous_mac.len() + self.unsigned.len() + tbs.len());
+        signed.push((self.previous_mac.len() >> 8) as u8);
+        signed.push(self.previous_mac.len() as u8);
+        signed.extend_from_slice(&self.previous_mac);
+        signed.extend_from_slice(&self.unsigned);
+        signed.extend_from_slice(&tbs);
+
+        self.signer.verify(&signed, tsig.mac())?;
+        self.signer.verify_time(tsig.time(), now()?)?;
+
+        self.previous_mac = tsig.mac().to_vec();
+        self.unsigned.clear();
+        self.unsigned_count = 0;
+        self.first_message = false;
+        Ok(true)
+    }
+}
+
+impl MessageFinalizer for TSigner {
+    fn finalize_message(&self, message: &Message, current_time: u32) -> ProtoResult<Vec<Record>> {
+        debug!("signing message with tsig: {:?}", message);
+        let pre_tsig = TSIG::new(
+            self.algorithm.clone(),
+            u64::from(current_time),
+            self.fudge,
+            Vec::new(),
+            message.id(),
+            0,
+            Vec::new(),
+        );
+
+        let tbs = tsig::message_tbs(None, &message.to_vec()?, &pre_tsig, &self.signer_name, true)?;
+        let mac = self.sign(&tbs)?;
+
+        Ok(vec![tsig::make_tsig_record(
+            self.signer_name.clone(),
+            pre_tsig.set_mac(mac),
+        )])
+    }
+
+    /// All messages are signed
+    fn should_finalize_message(&self, _message: &Message) -> bool {
+        true
+    }
+
+    fn response_verifier(&self, message: &Message) -> Option<MessageVerifier> {
+        let request_mac = message
+            .sig0()
+            .iter()
+            .filter_map(as_tsig)
+            .last()?
+            .mac()
+            .to_vec();
+
+        let mut verifier = ResponseVerifier {
+            signer: self.clone(),
+            previous_mac: request_mac,
+            unsigned: Vec::new(),
+            unsigned_count: 0,
+            first_message: true,
+        };
+
+        Some(Box::new(move |response: &[u8]| verifier.verify(response)))
+    }
+}
+
+#[cfg(test)]
+#[cfg(any(feature = "openssl", feature = "ring"))]
+mod tests {
+    use op::{Message, MessageType, Query};
+    use rr::{Name, RecordType};
+
+    use super::*;
+
+    fn signer() -> TSigner {
+        TSigner::new(
+            b"0123456789abcdef0123456789abcdef".to_vec(),
+            TsigAlgorithm::HmacSha256,
+            Name::from_ascii("transfer-key.").unwrap(),
+            300,
+        )
+        .unwrap()
+    }
+
+    fn response(request: &Message, answers: u8) -> Vec<u8> {
+        let mut response = Message::new();
+        response
+            .set_id(request.id())
+            .set_message_type(MessageType::Response)
+            .add_query(request.queries()[0].clone());
+        for i in 0..answers {
+            response.add_answer(Record::from_rdata(
+                Name::from_ascii("www.example.com.").unwrap(),
+                0,
+                RData::A([192, 0, 2, i].into()),
+            ));
+        }
+        response.to_vec().unwrap()
+    }
+
+    #[test]
+    fn test_sign_and_verify_transfer() {
+        let signer = signer();
+        let now = now().unwrap();
+
+        let mut request = Message::new();
+        request.set_id(42).add_query(Query::query(
+            Name::from_ascii("example.com.").unwrap(),
+            RecordType::AXFR,
+        ));
+        let mut verifier = request
+            .finalize(&signer, now as u32)
+            .unwrap()
+            .expect("no verifier");
+        let request_mac = as_tsig(&request.sig0()[0]).unwrap().mac().to_vec();
+
+        // the server signs the first and last message, the second is unsigned
+        let mut first = response(&request, 1);
+        let mac = signer
+            .sign_message_bytes(Some(&request_mac), &mut first, true, now, 0)
+            .unwrap();
+        let second = response(&request, 2);
+        let mut third = response(&request, 3);
+        let mut signed = Vec::new();
+        signed.extend_from_slice(&second);
+        signed.extend_from_slice(&third);
+        let pre_tsig = TSIG::new(TsigAlgorithm::HmacSha256, now, 300, vec![], 42, 0, vec![]);
+        let tbs =
+            tsig::message_tbs(Some(&mac), &signed, &pre_tsig, signer.signer_name(), false).unwrap();
+        let tsig = pre_tsig.set_mac(signer.sign(&tbs).unwrap());
+        tsig::append_tsig_record(
+            &mut third,
+            &tsig::make_tsig_record(signer.signer_name().clone(), tsig),
+        )
+        .unwrap();
+
+        assert!(verifier(&first).unwrap());
+        assert!(!verifier(&second).unwrap());
+        assert!(verifier(&third).unwrap());
+    }
+
+    #[test]
+    fn test_verify_rejects_modified_message() {
+        let signer = signer();
+        let now = now().unwrap();
+
+        let mut request = Message::new();
+        request.set_id(42).add_query(Query::query(
+            Name::from_ascii("example.com.").unwrap(),
+            RecordType::AXFR,
+        ));
+        let mut verifier = request.finalize(&signer, now as u32).unwrap().unwrap();
+
+        // signed with the wrong request MAC
+        let mut response = response(&request, 1);
+        signer
+            .sign_message_bytes(Some(&[0; 32]), &mut response, true, now, 0)
+            .unwrap();
+        assert!(verifier(&response).is_err());
+    }
+}
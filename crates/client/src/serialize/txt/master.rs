@@ -13,10 +13,12 @@
  * See the License for the specific language governing permissions and
  * limitations under the License.
  */
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, VecDeque};
+use std::io::BufRead;
 use std::str::FromStr;
 
 use error::*;
+use rr::rdata::{DNSSECRData, DNSSECRecordType};
 use rr::{DNSClass, LowerName, Name, RData, Record, RecordSet, RecordType, RrKey};
 use serialize::txt::master_lex::{Lexer, Token};
 use serialize::txt::parse_rdata::RDataParser;
@@ -140,14 +142,56 @@ impl Parser {
         lexer: Lexer,
         origin: Option<Name>,
     ) -> ParseResult<(Name, BTreeMap<RrKey, RecordSet>)> {
-        let mut lexer = lexer;
         let mut records: BTreeMap<RrKey, RecordSet> = BTreeMap::new();
+        let mut rrsigs: Vec<Record> = Vec::new();
+        let mut context = Context::new(origin);
 
-        let mut origin: Option<Name> = origin;
-        let mut current_name: Option<Name> = None;
+        Self::parse_entries(lexer, &mut context, |record| {
+            Self::insert_record(&mut records, &mut rrsigs, record)
+        })?;
+        Self::insert_rrsigs(&mut records, rrsigs);
+
+        //
+        // build the Authority and return.
+        let origin = context.origin.ok_or_else(|| {
+            ParseError::from(ParseErrorKind::Message("$ORIGIN was not specified"))
+        })?;
+        Ok((origin, records))
+    }
+
+    /// Parse a file from the reader, one entry at a time, see `RecordReader`
+    ///
+    /// Unlike `parse`, the file is not held in memory, only its records.
+    ///
+    /// # Return
+    ///
+    /// A pair of the Zone origin name and a map of all Keys to RecordSets
+    pub fn parse_reader<R: BufRead>(
+        &mut self,
+        reader: R,
+        origin: Option<Name>,
+    ) -> ParseResult<(Name, BTreeMap<RrKey, RecordSet>)> {
+        let mut records: BTreeMap<RrKey, RecordSet> = BTreeMap::new();
+        let mut rrsigs: Vec<Record> = Vec::new();
+        let mut reader = RecordReader::new(reader, origin);
+
+        for record in &mut reader {
+            Self::insert_record(&mut records, &mut rrsigs, record?)?;
+        }
+        Self::insert_rrsigs(&mut records, rrsigs);
+
+        let origin = reader.context.origin.ok_or_else(|| {
+            ParseError::from(ParseErrorKind::Message("$ORIGIN was not specified"))
+        })?;
+        Ok((origin, records))
+    }
+
+    /// Parses the entries of the lexer, passing their records to `add`
+    fn parse_entries<F>(mut lexer: Lexer, context: &mut Context, mut add: F) -> ParseResult<()>
+    where
+        F: FnMut(Record) -> ParseResult<()>,
+    {
         let mut rtype: Option<RecordType> = None;
-        let mut ttl: Option<u32> = None;
-        let mut class: Option<DNSClass> = None;
         let mut state = State::StartLine;
 
         while let Some(t) = lexer.next_token()? {
@@ -158,19 +202,22 @@ impl Parser {
 
                     match t {
                         // if Dollar, then $INCLUDE or $ORIGIN
-                        Token::Include => unimplemented!(),
+                        Token::Include => {
+                            return Err(ParseErrorKind::Message("$INCLUDE is not supported").into())
+                        }
                         Token::Origin => State::Origin,
                         Token::Ttl => State::Ttl,
 
                         // if CharData, then Name then ttl_class_type
                         Token::CharData(data) => {
-                            current_name = Some(Name::parse(&data, origin.as_ref())?);
+                            context.current_name =
+                                Some(Name::parse(&data, context.origin.as_ref())?);
                             State::TtlClassType
                         }
 
                         // @ is a placeholder for specifying the current origin
                         Token::At => {
-                            current_name = origin.clone(); // TODO a COW or RC would reduce copies...
+                            context.current_name = context.origin.clone(); // TODO a COW or RC would reduce copies...
                             State::TtlClassType
                         }
 
@@ -182,7 +229,7 @@ impl Parser {
                 }
                 State::Ttl => match t {
                     Token::CharData(data) => {
-                        ttl = Some(Self::parse_time(&data)?);
+                        context.ttl = Some(Self::parse_time(&data)?);
                         State::StartLine
                     }
                     _ => return Err(ParseErrorKind::UnexpectedToken(t).into()),
@@ -191,29 +238,34 @@ impl Parser {
                     match t {
                         Token::CharData(data) => {
                             // TODO an origin was specified, should this be legal? definitely confusing...
-                            origin = Some(Name::parse(&data, None)?);
+                            context.origin = Some(Name::parse(&data, None)?);
                             State::StartLine
                         }
                         _ => return Err(ParseErrorKind::UnexpectedToken(t).into()),
                     }
                 }
-                State::Include => unimplemented!(),
+                State::Include => {
+                    return Err(ParseErrorKind::Message("$INCLUDE is not supported").into())
+                }
                 State::TtlClassType => {
                     match t {
                         // if number, TTL
                         // Token::Number(ref num) => ttl = Some(*num),
                         // One of Class or Type (these cannot be overlapping!)
                         Token::CharData(data) => {
-                            // if it's a number it's a ttl
+                            // if it's a number it's a ttl, a leading digit keeps types like DS
+                            //  from being read as a unit-only time
+                            let starts_with_digit =
+                                data.chars().next().map_or(false, |c| c.is_ascii_digit());
                             let result: ParseResult<u32> = Self::parse_time(&data);
-                            if result.is_ok() {
-                                ttl = result.ok();
+                            if starts_with_digit && result.is_ok() {
+                                context.ttl = result.ok();
                                 State::TtlClassType // hm, should this go to just ClassType?
                             } else {
                                 // if can parse DNSClass, then class
                                 let result = DNSClass::from_str(&data);
                                 if result.is_ok() {
-                                    class = result.ok();
+                                    context.class = result.ok();
                                     State::TtlClassType
                                 } else {
                                     // if can parse RecordType, then RecordType
@@ -234,15 +286,7 @@ impl Parser {
                     //  tokens to pass into the processor
                     match t {
                         Token::EOL => {
-                            Self::flush_record(
-                                record_parts,
-                                &origin,
-                                &current_name,
-                                rtype,
-                                &mut ttl,
-                                class,
-                                &mut records,
-                            )?;
+                            add(Self::flush_record(record_parts, context, rtype)?)?;
                             State::StartLine
                         }
                         Token::CharData(part) => {
@@ -264,34 +308,17 @@ impl Parser {
 
         //Extra flush at the end for the case of missing endline
         if let State::Record(record_parts) = state {
-            Self::flush_record(
-                record_parts,
-                &origin,
-                &current_name,
-                rtype,
-                &mut ttl,
-                class,
-                &mut records,
-            )?;
+            add(Self::flush_record(record_parts, context, rtype)?)?;
         }
 
-        //
-        // build the Authority and return.
-        let origin = origin.ok_or_else(|| {
-            ParseError::from(ParseErrorKind::Message("$ORIGIN was not specified"))
-        })?;
-        Ok((origin, records))
+        Ok(())
     }
 
     fn flush_record(
         record_parts: Vec<String>,
-        origin: &Option<Name>,
-        current_name: &Option<Name>,
+        context: &mut Context,
         rtype: Option<RecordType>,
-        ttl: &mut Option<u32>,
-        class: Option<DNSClass>,
-        records: &mut BTreeMap<RrKey, RecordSet>,
-    ) -> ParseResult<()> {
+    ) -> ParseResult<Record> {
         // call out to parsers for difference record types
         // all tokens as part of the Record should be chardata...
         let rdata = RData::parse(
@@ -299,7 +326,7 @@ impl Parser {
                 ParseError::from(ParseErrorKind::Message("record type not specified"))
             })?,
             record_parts.iter().map(AsRef::as_ref),
-            origin.as_ref(),
+            context.origin.as_ref(),
         )?;
 
         // verify that we have everything we need for the record
@@ -307,11 +334,11 @@ impl Parser {
         // TODO COW or RC would reduce mem usage, perhaps Name should have an intern()...
         //  might want to wait until RC.weak() stabilizes, as that would be needed for global
         //  memory where you want
-        record.set_name(current_name.clone().ok_or_else(|| {
+        record.set_name(context.current_name.clone().ok_or_else(|| {
             ParseError::from(ParseErrorKind::Message("record name not specified"))
         })?);
         record.set_rr_type(rtype.unwrap());
-        record.set_dns_class(class.ok_or_else(|| {
+        record.set_dns_class(context.class.ok_or_else(|| {
             ParseError::from(ParseErrorKind::Message("record class not specified"))
         })?);
 
@@ -324,15 +351,15 @@ impl Parser {
                 if let RData::SOA(ref soa) = rdata {
                     // TODO, this looks wrong, get_expire() should be get_minimum(), right?
                     record.set_ttl(soa.expire() as u32); // the spec seems a little inaccurate with u32 and i32
-                    if ttl.is_none() {
-                        *ttl = Some(soa.minimum());
+                    if context.ttl.is_none() {
+                        context.ttl = Some(soa.minimum());
                     } // TODO: should this only set it if it's not set?
                 } else {
                     panic!("Invalid RData here, expected SOA: {:?}", rdata);
                 }
             }
             _ => {
-                record.set_ttl(ttl.ok_or_else(|| {
+                record.set_ttl(context.ttl.ok_or_else(|| {
                     ParseError::from(ParseErrorKind::Message("record ttl not specified"))
                 })?);
             }
@@ -342,10 +369,20 @@ impl Parser {
 
         // move the rdata into record...
         record.set_rdata(rdata);
+        Ok(record)
+    }
 
+    fn insert_record(
+        records: &mut BTreeMap<RrKey, RecordSet>,
+        rrsigs: &mut Vec<Record>,
+        record: Record,
+    ) -> ParseResult<()> {
         // add to the map
         let key = RrKey::new(LowerName::new(record.name()), record.rr_type());
-        match rtype.unwrap() {
+        match record.rr_type() {
+            // the signatures of pre-signed zones are attached to the sets they cover once all the
+            //  records are read, inserting records into a set clears its signatures
+            RecordType::DNSSEC(DNSSECRecordType::RRSIG) => rrsigs.push(record),
             RecordType::SOA => {
                 let set = record.into();
                 if records.insert(key, set).is_some() {
@@ -363,6 +400,22 @@ impl Parser {
         Ok(())
     }
 
+    /// Attaches the RRSIG records to the record sets of the types they cover
+    fn insert_rrsigs(records: &mut BTreeMap<RrKey, RecordSet>, rrsigs: Vec<Record>) {
+        for rrsig in rrsigs {
+            let type_covered = match *rrsig.rdata() {
+                RData::DNSSEC(DNSSECRData::SIG(ref sig)) => sig.type_covered(),
+                _ => continue,
+            };
+
+            let key = RrKey::new(LowerName::new(rrsig.name()), type_covered);
+            records
+                .entry(key)
+                .or_insert_with(|| RecordSet::new(rrsig.name(), type_covered, 0))
+                .insert_rrsig(rrsig);
+        }
+    }
+
     /// parses the string following the rules from:
     ///  https://tools.ietf.org/html/rfc2308 (NXCaching RFC) and
     ///  http://www.zytrax.com/books/dns/apa/time.html
@@ -396,43 +449,57 @@ impl Parser {
     /// assert_eq!(Parser::parse_time("1w").unwrap(), 604800);
     /// assert_eq!(Parser::parse_time("1s2d3w4h2m").unwrap(), 1+2*86400+3*604800+4*3600+2*60);
     /// assert_eq!(Parser::parse_time("3w3w").unwrap(), 3*604800+3*604800);
+    /// assert!(Parser::parse_time("4294967296").is_err());
+    /// assert!(Parser::parse_time("7102w").is_err());
     /// ```
     pub fn parse_time(ttl_str: &str) -> ParseResult<u32> {
         let mut value: u32 = 0;
         let mut collect: u32 = 0;
 
+        // times which don't fit in a u32 fail to parse
+        let overflow = || ParseError::from(ParseErrorKind::ParseTime(ttl_str.to_string()));
+        let add_collected = |value: u32, collect: u32, unit: u32| {
+            collect
+                .checked_mul(unit)
+                .and_then(|collect| value.checked_add(collect))
+                .ok_or_else(overflow)
+        };
+
         for c in ttl_str.chars() {
             match c {
-                // TODO, should these all be checked operations?
                 '0'...'9' => {
-                    collect *= 10;
-                    collect += c.to_digit(10).ok_or_else(|| ParseErrorKind::CharToInt(c))?;
+                    let digit = c.to_digit(10).ok_or_else(|| ParseErrorKind::CharToInt(c))?;
+                    collect = collect
+                        .checked_mul(10)
+                        .and_then(|collect| collect.checked_add(digit))
+                        .ok_or_else(overflow)?;
                 }
                 'S' | 's' => {
-                    value += collect;
+                    value = add_collected(value, collect, 1)?;
                     collect = 0;
                 }
                 'M' | 'm' => {
-                    value += collect * 60;
+                    value = add_collected(value, collect, 60)?;
                     collect = 0;
                 }
                 'H' | 'h' => {
-                    value += collect * 3_600;
+                    value = add_collected(value, collect, 3_600)?;
                     collect = 0;
                 }
                 'D' | 'd' => {
-                    value += collect * 86_400;
+                    value = add_collected(value, collect, 86_400)?;
                     collect = 0;
                 }
                 'W' | 'w' => {
-                    value += collect * 604_800;
+                    value = add_collected(value, collect, 604_800)?;
                     collect = 0;
                 }
                 _ => return Err(ParseErrorKind::ParseTime(ttl_str.to_string()).into()),
             }
         }
 
-        Ok(value + collect) // collects the initial num, or 0 if it was already collected
+        // collects the initial num, or 0 if it was already collected
+        add_collected(value, collect, 1)
     }
 }
 
@@ -445,3 +512,148 @@ enum State {
     Include, // $INCLUDE <filename>
     Origin,
 }
+
+/// The origin and the defaults of the entries of a master file, which carry over to the next
+///  entries
+struct Context {
+    origin: Option<Name>,
+    current_name: Option<Name>,
+    ttl: Option<u32>,
+    class: Option<DNSClass>,
+}
+
+impl Context {
+    fn new(origin: Option<Name>) -> Self {
+        Context {
+            origin,
+            current_name: None,
+            ttl: None,
+            class: None,
+        }
+    }
+}
+
+/// Reads the records of a master file one entry at a time, see `Parser` for the format
+///
+/// Only the entry being parsed is held in memory, an entry is a line, or the lines of its list
+///  in parentheses or of its quoted text. Unlike `Parser::parse`, the records are not collected
+///  in record sets, they are returned in the order of the file, e.g. to load or transform zones
+///  larger than the memory.
+///
+/// ```
+/// use trust_dns::rr::{Name, RecordType};
+/// use trust_dns::serialize::txt::RecordReader;
+///
+/// let zone = "$ORIGIN example.com.\n$TTL 3600\nwww IN A 127.0.0.1\n    IN TXT (\"a\"\n \"b\")\n";
+/// let records = RecordReader::new(zone.as_bytes(), None)
+///     .collect::<Result<Vec<_>, _>>()
+///     .unwrap();
+///
+/// assert_eq!(records.len(), 2);
+/// assert_eq!(records[1].name(), &Name::from_ascii("www.example.com.").unwrap());
+/// assert_eq!(records[1].rr_type(), RecordType::TXT);
+/// ```
+pub struct RecordReader<R: BufRead> {
+    reader: R,
+    context: Context,
+    entry: String,
+    records: VecDeque<Record>,
+    finished: bool,
+}
+
+impl<R: BufRead> RecordReader<R> {
+    /// Returns a reader of the records of the master file
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - the master file, e.g. a `BufReader` of a `File`
+    /// * `origin` - the origin of the relative names, until the file sets `$ORIGIN`
+    pub fn new(reader: R, origin: Option<Name>) -> Self {
+        RecordReader {
+            reader,
+            context: Context::new(origin),
+            entry: String::new(),
+            records: VecDeque::new(),
+            finished: false,
+        }
+    }
+
+    /// The current origin of the relative names, the last `$ORIGIN` read
+    pub fn origin(&self) -> Option<&Name> {
+        self.context.origin.as_ref()
+    }
+
+    /// Reads the next entry, returns false at the end of the file
+    fn read_entry(&mut self) -> ParseResult<bool> {
+        self.entry.clear();
+        let mut depth = 0_usize;
+        let mut in_quote = false;
+
+        loop {
+            let start = self.entry.len();
+            if self.reader.read_line(&mut self.entry)? == 0 {
+                if in_quote || depth > 0 {
+                    return Err(ParseErrorKind::Message("unexpected end of the entry").into());
+                }
+                return Ok(!self.entry.is_empty());
+            }
+
+            // the entry continues on the next line in a list or a quote
+            let mut chars = self.entry[start..].chars();
+            while let Some(ch) = chars.next() {
+                match ch {
+                    '\\' => {
+                        chars.next();
+                    }
+                    '"' => in_quote = !in_quote,
+                    _ if in_quote => (),
+                    ';' => break,
+                    '(' => depth += 1,
+                    ')' => depth = depth.saturating_sub(1),
+                    _ => (),
+                }
+            }
+
+            if depth == 0 && !in_quote {
+                return Ok(true);
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for RecordReader<R> {
+    type Item = ParseResult<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(record) = self.records.pop_front() {
+                return Some(Ok(record));
+            }
+            if self.finished {
+                return None;
+            }
+
+            let parsed = self.read_entry().and_then(|more| {
+                if !more {
+                    return Ok(false);
+                }
+
+                let records = &mut self.records;
+                Parser::parse_entries(Lexer::new(&self.entry), &mut self.context, |record| {
+                    records.push_back(record);
+                    Ok(())
+                })
+                .map(|_| true)
+            });
+
+            match parsed {
+                Ok(true) => (),
+                Ok(false) => self.finished = true,
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
@@ -5,7 +5,6 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use std::char;
 use std::iter::Peekable;
 use std::str::Chars;
 
@@ -80,7 +79,7 @@ impl<'a> Lexer<'a> {
                         Some('"') => {
                             self.txt.next();
                             char_data = Some(String::new());
-                            self.state = State::Quote;
+                            self.state = State::Quote { is_list: false };
                         }
                         Some(';') => self.state = State::Comment { is_list: false },
                         Some(ch) if ch.is_whitespace() => {
@@ -117,19 +116,27 @@ impl<'a> Lexer<'a> {
                         }
                     }
                 }
-                State::Quote => {
+                State::Quote { is_list } => {
                     match ch {
                         // end and gobble the '"'
                         Some('"') => {
-                            self.state = State::RestOfLine;
                             self.txt.next();
-                            return Ok(Some(Token::CharData(
-                                char_data.take().unwrap_or_else(|| "".into()),
-                            )));
-                        }
-                        Some('\\') => {
-                            Self::push_to_str(&mut char_data, self.escape_seq()?)?;
+                            let char_data = char_data.take().unwrap_or_else(|| "".into());
+                            if is_list {
+                                char_data_vec
+                                    .as_mut()
+                                    .ok_or_else(|| {
+                                        LexerError::from(LexerErrorKind::IllegalState(
+                                            "char_data_vec is None",
+                                        ))
+                                    })?.push(char_data);
+                                self.state = State::List;
+                            } else {
+                                self.state = State::RestOfLine;
+                                return Ok(Some(Token::CharData(char_data)));
+                            }
                         }
+                        Some('\\') => self.escape_seq(&mut char_data)?,
                         Some(ch) => {
                             self.txt.next();
                             Self::push_to_str(&mut char_data, ch)?;
@@ -182,6 +189,11 @@ impl<'a> Lexer<'a> {
                                 LexerErrorKind::IllegalState("char_data_vec is None").into()
                             }).map(|v| Some(Token::List(v)));
                     }
+                    Some('"') => {
+                        self.txt.next();
+                        char_data = Some(String::new());
+                        self.state = State::Quote { is_list: true };
+                    }
                     Some(ch) if ch.is_whitespace() => {
                         self.txt.next();
                     }
@@ -221,8 +233,8 @@ impl<'a> Lexer<'a> {
                             let opt = result.map(|s| Some(Token::CharData(s)));
                             return opt;
                         },
-                        // TODO: this next one can be removed, but will keep unescaping for quoted strings
-                        //Some('\\') => { try!(Self::push_to_str(&mut char_data, try!(self.escape_seq()))); },
+                        // an escaped whitespace, ';', ')' or '"' is part of the data
+                        Some('\\') => self.escape_seq(&mut char_data)?,
                         Some(ch) if !ch.is_control() && !ch.is_whitespace() => {
                             self.txt.next();
                             Self::push_to_str(&mut char_data, ch)?;
@@ -276,54 +288,47 @@ impl<'a> Lexer<'a> {
             })
     }
 
-    fn escape_seq(&mut self) -> LexerResult<char> {
-        // escaped character, let's decode it.
+    /// Validates the escape sequence, `\X` or `\DDD` with a decimal value up to 255, RFC 1035
+    ///  section 5.1, and pushes it as is to the data
+    ///
+    /// The escapes are kept, names and character-strings are unescaped by their parsers, see
+    ///  `Name::parse` and `rdata_parsers::txt::parse_character_string`.
+    fn escape_seq(&mut self, char_data: &mut Option<String>) -> LexerResult<()> {
         self.txt.next(); // consume the escape
+        Self::push_to_str(char_data, '\\')?;
+
         let ch = self
             .peek()
             .ok_or_else(|| LexerError::from(LexerErrorKind::EOF))?;
+        if ch.is_control() {
+            return Err(LexerErrorKind::IllegalCharacter(ch).into());
+        }
 
-        if !ch.is_control() {
-            if ch.is_numeric() {
-                // in this case it's an excaped octal: \DDD
-                let d1: u32 = self
-                    .txt
-                    .next()
-                    .ok_or_else(|| LexerError::from(LexerErrorKind::EOF))
-                    .map(|c| {
-                        c.to_digit(10)
-                            .ok_or_else(|| LexerError::from(LexerErrorKind::IllegalCharacter(c)))
-                    })??; // gobble
-                let d2: u32 = self
-                    .txt
-                    .next()
-                    .ok_or_else(|| LexerError::from(LexerErrorKind::EOF))
-                    .map(|c| {
-                        c.to_digit(10)
-                            .ok_or_else(|| LexerError::from(LexerErrorKind::IllegalCharacter(c)))
-                    })??; // gobble
-                let d3: u32 = self
-                    .txt
-                    .next()
-                    .ok_or_else(|| LexerError::from(LexerErrorKind::EOF))
-                    .map(|c| {
-                        c.to_digit(10)
-                            .ok_or_else(|| LexerError::from(LexerErrorKind::IllegalCharacter(c)))
-                    })??; // gobble
-
-                let val: u32 = (d1 << 16) + (d2 << 8) + d3;
-                let ch: char = char::from_u32(val)
-                    .ok_or_else(|| LexerError::from(LexerErrorKind::UnrecognizedOctet(val)))?;
-
-                Ok(ch)
-            } else {
-                // this is an excaped char: \X
-                self.txt.next(); // gobble the char
-                Ok(ch)
-            }
-        } else {
-            Err(LexerErrorKind::IllegalCharacter(ch).into())
+        if !ch.is_ascii_digit() {
+            // this is an escaped char: \X
+            self.txt.next(); // gobble the char
+            return Self::push_to_str(char_data, ch);
+        }
+
+        // in this case it's an escaped decimal octet: \DDD
+        let mut val: u32 = 0;
+        for _ in 0..3 {
+            let digit = self
+                .txt
+                .next()
+                .ok_or_else(|| LexerError::from(LexerErrorKind::EOF))?;
+            val = val * 10
+                + digit
+                    .to_digit(10)
+                    .ok_or_else(|| LexerError::from(LexerErrorKind::IllegalCharacter(digit)))?;
+            Self::push_to_str(char_data, digit)?;
+        }
+
+        if val > 255 {
+            return Err(LexerErrorKind::UnrecognizedOctet(val).into());
         }
+
+        Ok(())
     }
 
     fn peek(&mut self) -> Option<char> {
@@ -342,7 +347,7 @@ pub enum State {
     //  Name,              // CharData + '.' + CharData
     Comment { is_list: bool }, // ;.*
     At,                        // @
-    Quote,                     // ".*"
+    Quote { is_list: bool },   // ".*"
     Dollar,                    // $
     EOL,                       // \n or \r\n
     EOF,
@@ -455,6 +460,22 @@ mod lex_test {
             Lexer::new("a\\077").next_token().unwrap().unwrap(),
             Token::CharData("a\\077".to_string())
         );
+        assert_eq!(
+            Lexer::new("a\\;b c").next_token().unwrap().unwrap(),
+            Token::CharData("a\\;b".to_string())
+        );
+        assert_eq!(
+            Lexer::new("a\\ b\\\"c").next_token().unwrap().unwrap(),
+            Token::CharData("a\\ b\\\"c".to_string())
+        );
+        assert_eq!(
+            Lexer::new("a\\\\").next_token().unwrap().unwrap(),
+            Token::CharData("a\\\\".to_string())
+        );
+
+        assert!(Lexer::new("a\\256").next_token().is_err());
+        assert!(Lexer::new("a\\1a3").next_token().is_err());
+        assert!(Lexer::new("a\\").next_token().is_err());
     }
 
     #[test]
@@ -469,22 +490,30 @@ mod lex_test {
         );
         assert_eq!(
             Lexer::new("\"some \\A\"").next_token().unwrap().unwrap(),
-            Token::CharData("some A".to_string())
+            Token::CharData("some \\A".to_string())
         );
         assert_eq!(
             Lexer::new("\"a\\Aa\"").next_token().unwrap().unwrap(),
-            Token::CharData("aAa".to_string())
+            Token::CharData("a\\Aa".to_string())
         );
         assert_eq!(
             Lexer::new("\"a\\$\"").next_token().unwrap().unwrap(),
-            Token::CharData("a$".to_string())
+            Token::CharData("a\\$".to_string())
         );
         assert_eq!(
             Lexer::new("\"a\\077\"").next_token().unwrap().unwrap(),
-            Token::CharData("a\u{707}".to_string())
+            Token::CharData("a\\077".to_string())
+        );
+        assert_eq!(
+            Lexer::new("\"say \\\"hi\\\"; bye\\\\\"")
+                .next_token()
+                .unwrap()
+                .unwrap(),
+            Token::CharData("say \\\"hi\\\"; bye\\\\".to_string())
         );
 
         assert!(Lexer::new("\"a\\\"").next_token().is_err());
+        assert!(Lexer::new("\"a\\256\"").next_token().is_err());
         assert!(Lexer::new("\"a\\0\"").next_token().is_err());
         assert!(Lexer::new("\"a\\07\"").next_token().is_err());
 
@@ -593,6 +622,19 @@ mod lex_test {
             Token::List(vec!["abc".to_string()])
         );
         assert_eq!(next_token(&mut lexer), None);
+
+        let mut lexer = Lexer::new("(\"a b\" \"c;\\\")\"\n\"\")");
+        assert_eq!(
+            next_token(&mut lexer).unwrap(),
+            Token::List(vec![
+                "a b".to_string(),
+                "c;\\\")".to_string(),
+                "".to_string(),
+            ])
+        );
+        assert_eq!(next_token(&mut lexer), None);
+
+        assert!(Lexer::new("(\"abc)").next_token().is_err());
     }
 
     #[test]
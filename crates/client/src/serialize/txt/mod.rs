@@ -21,6 +21,6 @@ mod master;
 mod parse_rdata;
 mod rdata_parsers;
 
-pub use self::master::Parser;
+pub use self::master::{Parser, RecordReader};
 pub use self::master_lex::Lexer;
 pub use self::master_lex::Token;
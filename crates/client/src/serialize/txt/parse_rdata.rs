@@ -17,7 +17,7 @@
 //! record data enum variants
 
 use error::*;
-use rr::rdata::DNSSECRecordType;
+use rr::rdata::{DNSSECRData, DNSSECRecordType};
 use rr::{Name, RData, RecordType};
 use serialize::txt::rdata_parsers::*;
 
@@ -60,25 +60,29 @@ impl RDataParser for RData {
             RecordType::TXT => RData::TXT(txt::parse(tokens)?),
             RecordType::DNSSEC(DNSSECRecordType::SIG) => panic!("parsing SIG doesn't make sense"), // valid panic, never should happen
             RecordType::DNSSEC(DNSSECRecordType::DNSKEY) => {
-                panic!("DNSKEY should be dynamically generated")
-            } // valid panic, never should happen
+                RData::DNSSEC(DNSSECRData::DNSKEY(dnskey::parse(tokens)?))
+            }
             RecordType::DNSSEC(DNSSECRecordType::KEY) => {
                 panic!("KEY should be dynamically generated")
             } // valid panic, never should happen
             RecordType::DNSSEC(DNSSECRecordType::DS) => {
-                panic!("DS should be dynamically generated")
-            } // valid panic, never should happen
+                RData::DNSSEC(DNSSECRData::DS(ds::parse(tokens)?))
+            }
             RecordType::DNSSEC(DNSSECRecordType::NSEC) => {
-                panic!("NSEC should be dynamically generated")
-            } // valid panic, never should happen
+                RData::DNSSEC(DNSSECRData::NSEC(nsec::parse(tokens, origin)?))
+            }
             RecordType::DNSSEC(DNSSECRecordType::NSEC3) => {
-                panic!("NSEC3 should be dynamically generated")
-            } // valid panic, never should happen
+                RData::DNSSEC(DNSSECRData::NSEC3(nsec3::parse(tokens)?))
+            }
             RecordType::DNSSEC(DNSSECRecordType::NSEC3PARAM) => {
-                panic!("NSEC3PARAM should be dynamically generated")
-            } // valid panic, never should happen
+                RData::DNSSEC(DNSSECRData::NSEC3PARAM(nsec3param::parse(tokens)?))
+            }
+            // RRSIG shares the rdata of SIG, RFC 4034 section 3
             RecordType::DNSSEC(DNSSECRecordType::RRSIG) => {
-                panic!("RRSIG should be dynamically generated")
+                RData::DNSSEC(DNSSECRData::SIG(rrsig::parse(tokens, origin)?))
+            }
+            RecordType::DNSSEC(DNSSECRecordType::TSIG) => {
+                panic!("TSIG is only valid in messages")
             } // valid panic, never should happen
             RecordType::DNSSEC(DNSSECRecordType::Unknown(code)) => {
                 panic!("Unknown dnssec record type, if you want to support this type, please file an issue against Trust-DNS: {}", code)
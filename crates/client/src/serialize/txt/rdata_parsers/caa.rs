@@ -22,6 +22,8 @@ use proto::rr::rdata::caa::{Property, Value};
 use error::*;
 use rr::rdata::CAA;
 
+use super::txt::parse_character_string;
+
 /// Parse the RData from a set of Tokens
 ///
 /// [RFC 6844, DNS Certification Authority Authorization, January 2013](https://tools.ietf.org/html/rfc6844#section-5.1)
@@ -75,18 +77,19 @@ pub fn parse<'i, I: Iterator<Item = &'i str>>(mut tokens: I) -> ParseResult<CAA>
     };
 
     // parse the value
+    let value_bytes = parse_character_string(value_str)?;
     let value = {
         // TODO: this is a slight dup of the match logic in caa::read_value(..)
         match tag {
             Property::Issue | Property::IssueWild => {
-                let value = caa::read_issuer(value_str.as_bytes())?;
+                let value = caa::read_issuer(&value_bytes)?;
                 Value::Issuer(value.0, value.1)
             }
             Property::Iodef => {
-                let url = caa::read_iodef(value_str.as_bytes())?;
+                let url = caa::read_iodef(&value_bytes)?;
                 Value::Url(url)
             }
-            Property::Unknown(_) => Value::Unknown(value_str.as_bytes().to_vec()),
+            Property::Unknown(_) => Value::Unknown(value_bytes),
         }
     };
 
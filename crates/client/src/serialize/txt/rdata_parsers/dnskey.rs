@@ -0,0 +1,109 @@
+// Copyright 2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! DNSKEY records of the public keys of signed zones
+
+use error::*;
+use rr::dnssec::Algorithm;
+use rr::rdata::DNSKEY;
+
+/// Parse the RData from a set of Tokens
+///
+/// [RFC 4034](https://tools.ietf.org/html/rfc4034#section-2.2)
+///
+/// ```text
+/// 2.2.  The DNSKEY RR Presentation Format
+///
+///    The Flag field MUST be represented as an unsigned decimal integer.
+///    Given the currently defined flags, the possible values are: 0, 256,
+///    and 257.
+///
+///    The Protocol Field MUST be represented as an unsigned decimal integer
+///    with a value of 3.
+///
+///    The Algorithm field MUST be represented either as an unsigned decimal
+///    integer or as an algorithm mnemonic as specified in Appendix A.1.
+///
+///    The Public Key field MUST be represented as a Base64 encoding of the
+///    Public Key.  Whitespace is allowed within the Base64 text.
+/// ```
+pub fn parse<'i, I: Iterator<Item = &'i str>>(mut tokens: I) -> ParseResult<DNSKEY> {
+    let flags: u16 = tokens
+        .next()
+        .ok_or_else(|| ParseErrorKind::Message("DNSKEY flags field is missing"))?
+        .parse()?;
+    let protocol: u8 = tokens
+        .next()
+        .ok_or_else(|| ParseErrorKind::Message("DNSKEY protocol field is missing"))?
+        .parse()?;
+    if protocol != 3 {
+        return Err(ParseErrorKind::Msg(format!("DNSKEY protocol must be 3: {}", protocol)).into());
+    }
+    let algorithm = parse_algorithm(
+        tokens
+            .next()
+            .ok_or_else(|| ParseErrorKind::Message("DNSKEY algorithm field is missing"))?,
+    )?;
+    let public_key = parse_base64(tokens)?;
+    if public_key.is_empty() {
+        return Err(ParseErrorKind::Message("DNSKEY public key field is missing").into());
+    }
+
+    // RFC 4034 section 2.1.1, zone key is bit 7, secure entry point bit 15, RFC 5011 revoke bit 8
+    Ok(DNSKEY::new(
+        flags & 0b0000_0001_0000_0000 != 0,
+        flags & 0b0000_0000_0000_0001 != 0,
+        flags & 0b0000_0000_1000_0000 != 0,
+        algorithm,
+        public_key,
+    ))
+}
+
+/// Parses an algorithm number, or its mnemonic, e.g. `RSASHA256`
+pub(crate) fn parse_algorithm(token: &str) -> ParseResult<Algorithm> {
+    if let Ok(value) = token.parse::<u8>() {
+        return Ok(Algorithm::from_u8(value)?);
+    }
+
+    match token.to_uppercase().as_str() {
+        "RSASHA1" => Ok(Algorithm::RSASHA1),
+        "RSASHA1-NSEC3-SHA1" | "NSEC3RSASHA1" => Ok(Algorithm::RSASHA1NSEC3SHA1),
+        "RSASHA256" => Ok(Algorithm::RSASHA256),
+        "RSASHA512" => Ok(Algorithm::RSASHA512),
+        "ECDSAP256SHA256" => Ok(Algorithm::ECDSAP256SHA256),
+        "ECDSAP384SHA384" => Ok(Algorithm::ECDSAP384SHA384),
+        "ED25519" => Ok(Algorithm::ED25519),
+        _ => Err(ParseErrorKind::Msg(format!("unknown algorithm: {}", token)).into()),
+    }
+}
+
+/// Decodes the base64 of the remaining tokens, which may be separated by whitespace
+pub(crate) fn parse_base64<'i, I: Iterator<Item = &'i str>>(tokens: I) -> ParseResult<Vec<u8>> {
+    let encoded = tokens.collect::<String>();
+    Ok(::data_encoding::BASE64.decode(encoded.as_bytes())?)
+}
+
+#[test]
+fn test_parsing() {
+    assert!(parse(::std::iter::empty()).is_err());
+    assert!(parse(vec!["257", "3", "8"].into_iter()).is_err());
+    assert!(parse(vec!["257", "2", "8", "AwEAAQ=="].into_iter()).is_err());
+    assert!(parse(vec!["257", "3", "99", "AwEAAQ=="].into_iter()).is_err());
+
+    let dnskey = parse(vec!["257", "3", "8", "AwEA", "AQ=="].into_iter()).expect("failed to parse");
+    assert!(dnskey.zone_key());
+    assert!(dnskey.secure_entry_point());
+    assert!(!dnskey.revoke());
+    assert_eq!(dnskey.algorithm(), Algorithm::RSASHA256);
+    assert_eq!(dnskey.public_key(), &[3, 1, 0, 1]);
+
+    let dnskey = parse(vec!["256", "3", "ECDSAP256SHA256", "AwEAAQ=="].into_iter())
+        .expect("failed to parse");
+    assert!(dnskey.zone_key());
+    assert!(!dnskey.secure_entry_point());
+    assert_eq!(dnskey.algorithm(), Algorithm::ECDSAP256SHA256);
+}
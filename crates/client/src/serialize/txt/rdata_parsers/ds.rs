@@ -0,0 +1,85 @@
+// Copyright 2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! DS records of the keys of delegated zones
+
+use error::*;
+use rr::dnssec::DigestType;
+use rr::rdata::DS;
+use serialize::txt::rdata_parsers::dnskey::parse_algorithm;
+
+const HEX: ::data_encoding::Encoding = new_encoding! {
+    symbols: "0123456789abcdef",
+    ignore: " \t\r\n",
+    translate_from: "ABCDEF",
+    translate_to: "abcdef",
+};
+
+/// Parse the RData from a set of Tokens
+///
+/// [RFC 4034](https://tools.ietf.org/html/rfc4034#section-5.3)
+///
+/// ```text
+/// 5.3.  The DS RR Presentation Format
+///
+///    The Key Tag field MUST be represented as an unsigned decimal integer.
+///
+///    The Algorithm field MUST be represented either as an unsigned decimal
+///    integer or as an algorithm mnemonic specified in Appendix A.1.
+///
+///    The Digest Type field MUST be represented as an unsigned decimal
+///    integer.
+///
+///    The Digest MUST be represented as a sequence of case-insensitive
+///    hexadecimal digits.  Whitespace is allowed within the hexadecimal
+///    text.
+/// ```
+pub fn parse<'i, I: Iterator<Item = &'i str>>(mut tokens: I) -> ParseResult<DS> {
+    let key_tag: u16 = tokens
+        .next()
+        .ok_or_else(|| ParseErrorKind::Message("DS key tag field is missing"))?
+        .parse()?;
+    let algorithm = parse_algorithm(
+        tokens
+            .next()
+            .ok_or_else(|| ParseErrorKind::Message("DS algorithm field is missing"))?,
+    )?;
+    let digest_type = DigestType::from_u8(
+        tokens
+            .next()
+            .ok_or_else(|| ParseErrorKind::Message("DS digest type field is missing"))?
+            .parse()?,
+    )?;
+    let digest = HEX.decode(tokens.collect::<String>().as_bytes())?;
+    if digest.is_empty() {
+        return Err(ParseErrorKind::Message("DS digest field is missing").into());
+    }
+
+    Ok(DS::new(key_tag, algorithm, digest_type, digest))
+}
+
+#[test]
+fn test_parsing() {
+    use rr::dnssec::Algorithm;
+
+    assert!(parse(::std::iter::empty()).is_err());
+    assert!(parse(vec!["60485", "5", "1"].into_iter()).is_err());
+    assert!(parse(vec!["60485", "5", "1", "xyz"].into_iter()).is_err());
+
+    let ds = parse(vec!["60485", "8", "2", "D4B7D520E7BB5F0F", "67674A0CCEB1E3E0"].into_iter())
+        .expect("failed to parse");
+    assert_eq!(ds.key_tag(), 60485);
+    assert_eq!(*ds.algorithm(), Algorithm::RSASHA256);
+    assert_eq!(ds.digest_type(), DigestType::SHA256);
+    assert_eq!(
+        ds.digest(),
+        &[
+            0xd4, 0xb7, 0xd5, 0x20, 0xe7, 0xbb, 0x5f, 0x0f, 0x67, 0x67, 0x4a, 0x0c, 0xce, 0xb1,
+            0xe3, 0xe0,
+        ]
+    );
+}
@@ -22,11 +22,17 @@
 pub mod a;
 pub mod aaaa;
 pub mod caa;
+pub mod dnskey;
+pub mod ds;
 pub mod mx;
 pub mod name;
 pub mod naptr;
+pub mod nsec;
+pub mod nsec3;
+pub mod nsec3param;
 pub mod null;
 pub mod openpgpkey;
+pub mod rrsig;
 pub mod soa;
 pub mod srv;
 pub mod sshfp;
@@ -0,0 +1,92 @@
+// Copyright 2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! NSEC records of the authenticated denial of existence
+
+use std::str::FromStr;
+
+use error::*;
+use rr::rdata::NSEC;
+use rr::{Name, RecordType};
+
+/// Parse the RData from a set of Tokens
+///
+/// [RFC 4034](https://tools.ietf.org/html/rfc4034#section-4.2)
+///
+/// ```text
+/// 4.2.  The NSEC RR Presentation Format
+///
+///    The Next Domain Name field is represented as a domain name.
+///
+///    The Type Bit Maps field is represented as a sequence of RR type
+///    mnemonics.  When the mnemonic is not known, the TYPE representation
+///    as described in Section 5 of [RFC3597] MUST be used.
+/// ```
+pub fn parse<'i, I: Iterator<Item = &'i str>>(
+    mut tokens: I,
+    origin: Option<&Name>,
+) -> ParseResult<NSEC> {
+    let next_domain_name = tokens
+        .next()
+        .ok_or_else(|| {
+            ParseError::from(ParseErrorKind::Message(
+                "NSEC next domain name field is missing",
+            ))
+        })
+        .and_then(|s| Name::parse(s, origin).map_err(ParseError::from))?;
+    let type_bit_maps = parse_type_bit_maps(tokens)?;
+
+    Ok(NSEC::new(next_domain_name, type_bit_maps))
+}
+
+/// Parses the record types of the type bit maps
+pub(crate) fn parse_type_bit_maps<'i, I: Iterator<Item = &'i str>>(
+    tokens: I,
+) -> ParseResult<Vec<RecordType>> {
+    tokens.map(parse_record_type).collect()
+}
+
+/// Parses a record type mnemonic, or `TYPE` followed by the value of the type, RFC 3597 section 5
+pub(crate) fn parse_record_type(token: &str) -> ParseResult<RecordType> {
+    let upper = token.to_uppercase();
+    if upper.starts_with("TYPE") {
+        if let Ok(value) = upper[4..].parse::<u16>() {
+            return Ok(RecordType::from(value));
+        }
+    }
+
+    Ok(RecordType::from_str(&upper)?)
+}
+
+#[test]
+fn test_parsing() {
+    use rr::rdata::DNSSECRecordType;
+
+    assert!(parse(::std::iter::empty(), None).is_err());
+    assert!(parse(vec!["host.example.com.", "A", "FOO"].into_iter(), None).is_err());
+
+    let origin = Name::from_str("example.com.").unwrap();
+    let nsec = parse(
+        vec!["host", "A", "MX", "RRSIG", "NSEC", "TYPE1234"].into_iter(),
+        Some(&origin),
+    )
+    .expect("failed to parse");
+    assert_eq!(
+        nsec.next_domain_name(),
+        &Name::from_str("host.example.com.").unwrap()
+    );
+    assert_eq!(
+        nsec.type_bit_maps(),
+        &[
+            RecordType::A,
+            RecordType::MX,
+            RecordType::DNSSEC(DNSSECRecordType::RRSIG),
+            RecordType::DNSSEC(DNSSECRecordType::NSEC),
+            RecordType::Unknown(1234),
+        ]
+    );
+}
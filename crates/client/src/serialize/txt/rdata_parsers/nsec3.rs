@@ -0,0 +1,88 @@
+// Copyright 2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! NSEC3 records of the hashed authenticated denial of existence
+
+use error::*;
+use rr::rdata::NSEC3;
+use serialize::txt::rdata_parsers::nsec::parse_type_bit_maps;
+use serialize::txt::rdata_parsers::nsec3param::parse_parameters;
+
+/// Parse the RData from a set of Tokens
+///
+/// [RFC 5155](https://tools.ietf.org/html/rfc5155#section-3.3)
+///
+/// ```text
+/// 3.3.  Presentation Format
+///
+///    The Hash Algorithm, Flags, Iterations and Salt fields are represented
+///    as in the NSEC3PARAM record.
+///
+///    The Next Hashed Owner Name field is represented as an unpadded
+///    sequence of case-insensitive base32 digits, without whitespace.
+///
+///    The Type Bit Maps field is represented as a sequence of RR type
+///    mnemonics.  When the mnemonic is not known, the TYPE representation
+///    as described in Section 5 of [RFC3597] MUST be used.
+/// ```
+pub fn parse<'i, I: Iterator<Item = &'i str>>(mut tokens: I) -> ParseResult<NSEC3> {
+    let (hash_algorithm, opt_out, iterations, salt) = parse_parameters("NSEC3", &mut tokens)?;
+    let next_hashed_owner_name = tokens
+        .next()
+        .ok_or_else(|| ParseErrorKind::Message("NSEC3 next hashed owner name field is missing"))?;
+    let next_hashed_owner_name = ::data_encoding::BASE32HEX_NOPAD
+        .decode(next_hashed_owner_name.to_uppercase().as_bytes())?;
+    let type_bit_maps = parse_type_bit_maps(tokens)?;
+
+    Ok(NSEC3::new(
+        hash_algorithm,
+        opt_out,
+        iterations,
+        salt,
+        next_hashed_owner_name,
+        type_bit_maps,
+    ))
+}
+
+#[test]
+fn test_parsing() {
+    use rr::dnssec::Nsec3HashAlgorithm;
+    use rr::rdata::DNSSECRecordType;
+    use rr::RecordType;
+
+    assert!(parse(::std::iter::empty()).is_err());
+    assert!(parse(vec!["1", "0", "10", "-"].into_iter()).is_err());
+    assert!(parse(vec!["1", "0", "10", "-", "not base32!"].into_iter()).is_err());
+
+    let nsec3 = parse(
+        vec![
+            "1",
+            "1",
+            "12",
+            "aabbccdd",
+            "2t7b4g4vsa5smi47k61mv5bv1a22bojr",
+            "MX",
+            "DNSKEY",
+            "NS",
+        ]
+        .into_iter(),
+    )
+    .expect("failed to parse");
+    assert_eq!(nsec3.hash_algorithm(), Nsec3HashAlgorithm::SHA1);
+    assert!(nsec3.opt_out());
+    assert_eq!(nsec3.iterations(), 12);
+    assert_eq!(nsec3.salt(), &[0xaa, 0xbb, 0xcc, 0xdd]);
+    assert_eq!(nsec3.next_hashed_owner_name().len(), 20);
+    assert_eq!(
+        nsec3.type_bit_maps(),
+        &[
+            RecordType::MX,
+            RecordType::DNSSEC(DNSSECRecordType::DNSKEY),
+            RecordType::NS,
+        ]
+    );
+}
@@ -0,0 +1,89 @@
+// Copyright 2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! NSEC3PARAM records of the parameters of the NSEC3 records of a zone
+
+use error::*;
+use rr::dnssec::Nsec3HashAlgorithm;
+use rr::rdata::NSEC3PARAM;
+
+const HEX: ::data_encoding::Encoding = new_encoding! {
+    symbols: "0123456789abcdef",
+    translate_from: "ABCDEF",
+    translate_to: "abcdef",
+};
+
+/// Parse the RData from a set of Tokens
+///
+/// [RFC 5155](https://tools.ietf.org/html/rfc5155#section-4.3)
+///
+/// ```text
+/// 4.3.  Presentation Format
+///
+///    The Hash Algorithm field is represented as an unsigned decimal
+///    integer.  The value has a maximum of 255.
+///
+///    The Flags field is represented as an unsigned decimal integer.
+///    The value has a maximum of 255.
+///
+///    The Iterations field is represented as an unsigned decimal
+///    integer.  The value is between 0 and 65535, inclusive.
+///
+///    The Salt Length field is not represented.
+///
+///    The Salt field is represented as a sequence of case-insensitive
+///    hexadecimal digits.  Whitespace is not allowed within the
+///    sequence.  This field is represented as "-" (without the quotes)
+///    when the Salt Length field is zero.
+/// ```
+pub fn parse<'i, I: Iterator<Item = &'i str>>(mut tokens: I) -> ParseResult<NSEC3PARAM> {
+    let (hash_algorithm, opt_out, iterations, salt) = parse_parameters("NSEC3PARAM", &mut tokens)?;
+
+    Some(NSEC3PARAM::new(hash_algorithm, opt_out, iterations, salt))
+        .filter(|_| tokens.next().is_none())
+        .ok_or_else(|| ParseErrorKind::Message("too many fields for NSEC3PARAM").into())
+}
+
+/// Parses the hash algorithm, flags, iterations and salt fields common to NSEC3 and NSEC3PARAM
+pub(crate) fn parse_parameters<'i, I: Iterator<Item = &'i str>>(
+    record_type: &str,
+    tokens: &mut I,
+) -> ParseResult<(Nsec3HashAlgorithm, bool, u16, Vec<u8>)> {
+    let mut next = |field: &str| {
+        tokens
+            .next()
+            .ok_or_else(|| ParseError::from(format!("{} {} field is missing", record_type, field)))
+    };
+
+    let hash_algorithm = Nsec3HashAlgorithm::from_u8(next("hash algorithm")?.parse()?)?;
+    let flags: u8 = next("flags")?.parse()?;
+    let iterations: u16 = next("iterations")?.parse()?;
+    let salt = match next("salt")? {
+        "-" => vec![],
+        salt => HEX.decode(salt.as_bytes())?,
+    };
+
+    // RFC 5155 section 3.1.2, the opt-out flag is the least significant bit
+    Ok((hash_algorithm, flags & 0b0000_0001 != 0, iterations, salt))
+}
+
+#[test]
+fn test_parsing() {
+    assert!(parse(::std::iter::empty()).is_err());
+    assert!(parse(vec!["1", "0", "10"].into_iter()).is_err());
+    assert!(parse(vec!["1", "0", "10", "-", "foo"].into_iter()).is_err());
+
+    let param = parse(vec!["1", "1", "12", "AABBCCDD"].into_iter()).expect("failed to parse");
+    assert_eq!(param.hash_algorithm(), Nsec3HashAlgorithm::SHA1);
+    assert!(param.opt_out());
+    assert_eq!(param.iterations(), 12);
+    assert_eq!(param.salt(), &[0xaa, 0xbb, 0xcc, 0xdd]);
+
+    let param = parse(vec!["1", "0", "0", "-"].into_iter()).expect("failed to parse");
+    assert!(!param.opt_out());
+    assert!(param.salt().is_empty());
+}
@@ -20,7 +20,8 @@ use error::*;
 use rr::rdata::NULL;
 
 /// Parse the RData from a set of Tokens
-#[allow(unused)]
-pub fn parse<'i, I: Iterator<Item = &'i str>>(mut tokens: I) -> ParseResult<NULL> {
-    unimplemented!()
+///
+/// NULL records have no text format, they always fail to parse
+pub fn parse<'i, I: Iterator<Item = &'i str>>(_tokens: I) -> ParseResult<NULL> {
+    Err(ParseErrorKind::Message("NULL records have no text format").into())
 }
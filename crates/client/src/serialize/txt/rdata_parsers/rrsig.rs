@@ -0,0 +1,152 @@
+// Copyright 2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! RRSIG records of the signatures of the record sets of signed zones
+
+use chrono::NaiveDateTime;
+
+use error::*;
+use rr::rdata::SIG;
+use rr::Name;
+use serialize::txt::rdata_parsers::dnskey::{parse_algorithm, parse_base64};
+use serialize::txt::rdata_parsers::nsec::parse_record_type;
+
+/// Parse the RData from a set of Tokens
+///
+/// [RFC 4034](https://tools.ietf.org/html/rfc4034#section-3.2)
+///
+/// ```text
+/// 3.2.  The RRSIG RR Presentation Format
+///
+///    The Type Covered field is represented as an RR type mnemonic.  When
+///    the mnemonic is not known, the TYPE representation as described in
+///    [RFC3597], Section 5, MUST be used.
+///
+///    The Algorithm field value MUST be represented either as an unsigned
+///    decimal integer or as an algorithm mnemonic, as specified in Appendix
+///    A.1.
+///
+///    The Labels field value MUST be represented as an unsigned decimal
+///    integer.
+///
+///    The Original TTL field value MUST be represented as an unsigned
+///    decimal integer.
+///
+///    The Signature Expiration Time and Inception Time field values MUST be
+///    represented either as an unsigned decimal integer indicating seconds
+///    since 1 January 1970 00:00:00 UTC, or in the form YYYYMMDDHHmmSS in
+///    UTC.
+///
+///    The Key Tag field MUST be represented as an unsigned decimal integer.
+///
+///    The Signer's Name field value MUST be represented as a domain name.
+///
+///    The Signature field is represented as a Base64 encoding of the
+///    signature.  Whitespace is allowed within the Base64 text.
+/// ```
+pub fn parse<'i, I: Iterator<Item = &'i str>>(
+    mut tokens: I,
+    origin: Option<&Name>,
+) -> ParseResult<SIG> {
+    let mut next = |field: &str| {
+        tokens
+            .next()
+            .ok_or_else(|| ParseError::from(format!("RRSIG {} field is missing", field)))
+    };
+
+    let type_covered = parse_record_type(next("type covered")?)?;
+    let algorithm = parse_algorithm(next("algorithm")?)?;
+    let num_labels: u8 = next("labels")?.parse()?;
+    let original_ttl: u32 = next("original TTL")?.parse()?;
+    let sig_expiration = parse_timestamp(next("signature expiration")?)?;
+    let sig_inception = parse_timestamp(next("signature inception")?)?;
+    let key_tag: u16 = next("key tag")?.parse()?;
+    let signer_name = Name::parse(next("signer's name")?, origin)?;
+
+    let sig = parse_base64(tokens)?;
+    if sig.is_empty() {
+        return Err(ParseErrorKind::Message("RRSIG signature field is missing").into());
+    }
+
+    Ok(SIG::new(
+        type_covered,
+        algorithm,
+        num_labels,
+        original_ttl,
+        sig_expiration,
+        sig_inception,
+        key_tag,
+        signer_name,
+        sig,
+    ))
+}
+
+/// The timestamps are YYYYMMDDHHmmSS in UTC, or seconds since the epoch, RFC 4034 section 3.2
+fn parse_timestamp(token: &str) -> ParseResult<u32> {
+    if token.len() == 14 && token.bytes().all(|b| b.is_ascii_digit()) {
+        let time = NaiveDateTime::parse_from_str(token, "%Y%m%d%H%M%S")
+            .map_err(|e| ParseError::from(format!("invalid RRSIG timestamp {}: {}", token, e)))?;
+
+        // the serial number arithmetic of RFC 1982 wraps the timestamps after 2106
+        return Ok(time.timestamp() as u32);
+    }
+
+    Ok(token.parse()?)
+}
+
+#[test]
+fn test_parsing() {
+    use std::str::FromStr;
+
+    use rr::dnssec::Algorithm;
+    use rr::RecordType;
+
+    assert!(parse(::std::iter::empty(), None).is_err());
+    assert!(parse(
+        vec![
+            "A",
+            "8",
+            "3",
+            "86400",
+            "20300101000000",
+            "20200101000000",
+            "2642"
+        ]
+        .into_iter(),
+        None
+    )
+    .is_err());
+
+    let origin = Name::from_str("example.com.").unwrap();
+    let rrsig = parse(
+        vec![
+            "A",
+            "8",
+            "3",
+            "86400",
+            "20300101000000",
+            "1577836800",
+            "2642",
+            "example.com.",
+            "AwEA",
+            "AQ==",
+        ]
+        .into_iter(),
+        Some(&origin),
+    )
+    .expect("failed to parse");
+
+    assert_eq!(rrsig.type_covered(), RecordType::A);
+    assert_eq!(rrsig.algorithm(), Algorithm::RSASHA256);
+    assert_eq!(rrsig.num_labels(), 3);
+    assert_eq!(rrsig.original_ttl(), 86400);
+    assert_eq!(rrsig.sig_expiration(), 1_893_456_000);
+    assert_eq!(rrsig.sig_inception(), 1_577_836_800);
+    assert_eq!(rrsig.key_tag(), 2642);
+    assert_eq!(rrsig.signer_name(), &origin);
+    assert_eq!(rrsig.sig(), &[3, 1, 0, 1]);
+}
@@ -21,6 +21,95 @@ use rr::rdata::TXT;
 
 /// Parse the RData from a set of Tokens
 pub fn parse<'i, I: Iterator<Item = &'i str>>(tokens: I) -> ParseResult<TXT> {
-    let txt_data: Vec<String> = tokens.map(ToString::to_string).collect();
-    Ok(TXT::new(txt_data))
+    let txt_data = tokens
+        .map(parse_character_string)
+        .collect::<ParseResult<Vec<Vec<u8>>>>()?;
+    Ok(TXT::from_bytes(txt_data.iter().map(|s| &s[..]).collect()))
+}
+
+/// Unescapes a `<character-string>` of the zone file, RFC 1035 section 5.1
+///
+/// ```text
+/// \X             where X is any character other than a digit (0-9), is
+///                 used to quote that character so that its special meaning
+///                 does not apply.  For example, "\." can be used to place
+///                 a dot character in a label.
+///
+/// \DDD           where each D is a digit is the octet corresponding to
+///                 the decimal number described by DDD.  The resulting
+///                 octet is assumed to be text and is not checked for
+///                 special meaning.
+/// ```
+pub fn parse_character_string(data: &str) -> ParseResult<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(data.len());
+    let mut chars = data.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+
+        match chars.next() {
+            Some(digit) if digit.is_ascii_digit() => {
+                let mut val = u32::from(digit as u8 - b'0');
+                for _ in 0..2 {
+                    let digit = chars
+                        .next()
+                        .and_then(|d| d.to_digit(10))
+                        .ok_or_else(|| ParseError::from(format!("bad escape in: {}", data)))?;
+                    val = val * 10 + digit;
+                }
+                if val > 255 {
+                    return Err(format!("escaped octet out of range in: {}", data).into());
+                }
+                bytes.push(val as u8);
+            }
+            Some(ch) => {
+                let mut buf = [0; 4];
+                bytes.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+            }
+            None => return Err(format!("unterminated escape in: {}", data).into()),
+        }
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_character_string() {
+        assert_eq!(parse_character_string("abc").unwrap(), b"abc");
+        assert_eq!(parse_character_string("").unwrap(), b"");
+        assert_eq!(
+            parse_character_string("say \\\"hi\\\"\\; \\\\").unwrap(),
+            b"say \"hi\"; \\"
+        );
+        assert_eq!(
+            parse_character_string("\\000\\0651\\255").unwrap(),
+            b"\x00A1\xff"
+        );
+        assert_eq!(parse_character_string("♥").unwrap(), "♥".as_bytes());
+
+        assert!(parse_character_string("\\256").is_err());
+        assert!(parse_character_string("\\07").is_err());
+        assert!(parse_character_string("\\0a7").is_err());
+        assert!(parse_character_string("a\\").is_err());
+    }
+
+    #[test]
+    fn test_parsing() {
+        let txt = parse(vec!["v=spf1 -all", "a\\\"b\\;", "\\255"].into_iter()).unwrap();
+        assert_eq!(
+            txt.txt_data(),
+            &[
+                b"v=spf1 -all".to_vec().into_boxed_slice(),
+                b"a\"b;".to_vec().into_boxed_slice(),
+                b"\xff".to_vec().into_boxed_slice(),
+            ]
+        );
+    }
 }
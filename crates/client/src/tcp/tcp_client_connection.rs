@@ -7,12 +7,12 @@
 
 //! TCP based DNS client connection for Client impls
 
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 use std::time::Duration;
 
 use tokio_tcp::TcpStream;
-use proto::tcp::{TcpClientConnect, TcpClientStream};
+use proto::tcp::{TcpClientConnect, TcpClientStream, TcpOptions};
 use proto::xfer::{DnsMultiplexer, DnsMultiplexerConnect, DnsRequestSender};
 
 use client::ClientConnection;
@@ -26,6 +26,7 @@ use rr::dnssec::Signer;
 pub struct TcpClientConnection {
     name_server: SocketAddr,
     timeout: Duration,
+    options: TcpOptions,
 }
 
 impl TcpClientConnection {
@@ -55,6 +56,30 @@ impl TcpClientConnection {
         Ok(TcpClientConnection {
             name_server,
             timeout,
+            options: TcpOptions::default(),
+        })
+    }
+
+    /// Creates a new client connection from the local address, e.g. for the zone transfers of a
+    ///  multi-homed host
+    ///
+    /// # Arguments
+    ///
+    /// * `name_server` - address of the name server to use for queries
+    /// * `bind_addr` - local address of the connections, with a random port
+    /// * `timeout` - connection timeout
+    pub fn with_bind_addr(
+        name_server: SocketAddr,
+        bind_addr: IpAddr,
+        timeout: Duration,
+    ) -> ClientResult<Self> {
+        Ok(TcpClientConnection {
+            name_server,
+            timeout,
+            options: TcpOptions {
+                bind_addr: Some(bind_addr),
+                ..TcpOptions::default()
+            },
         })
     }
 }
@@ -65,8 +90,11 @@ impl ClientConnection for TcpClientConnection {
     type SenderFuture = DnsMultiplexerConnect<TcpClientConnect, TcpClientStream<TcpStream>, Signer>;
 
     fn new_stream(&self, signer: Option<Arc<Signer>>) -> Self::SenderFuture {
-        let (tcp_client_stream, handle) =
-            TcpClientStream::<TcpStream>::with_timeout(self.name_server, self.timeout);
+        let (tcp_client_stream, handle) = TcpClientStream::<TcpStream>::with_options(
+            self.name_server,
+            self.timeout,
+            self.options,
+        );
         DnsMultiplexer::new(tcp_client_stream, handle, signer)
     }
 }
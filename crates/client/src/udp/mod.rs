@@ -17,8 +17,11 @@
 //! UDP protocol related components for DNS
 
 mod udp_client_connection;
+mod udp_multiplexed_client_connection;
 use proto::udp;
 
 pub use self::udp_client_connection::UdpClientConnection;
+pub use self::udp_multiplexed_client_connection::UdpMultiplexedClientConnection;
 pub use self::udp::UdpClientStream;
+pub use self::udp::UdpMultiplexedClientStream;
 pub use self::udp::UdpStream;
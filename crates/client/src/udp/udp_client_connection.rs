@@ -7,7 +7,7 @@
 
 //! UDP based DNS client connection for Client impls
 
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -24,6 +24,7 @@ use rr::dnssec::Signer;
 #[derive(Clone)]
 pub struct UdpClientConnection {
     name_server: SocketAddr,
+    bind_addr: Option<IpAddr>,
     timeout: Duration,
 }
 
@@ -39,7 +40,26 @@ impl UdpClientConnection {
 
     /// Allows a custom timeout
     pub fn with_timeout(name_server: SocketAddr, timeout: Duration) -> ClientResult<Self> {
-        Ok(UdpClientConnection {name_server, timeout})
+        Ok(UdpClientConnection {name_server, bind_addr: None, timeout})
+    }
+
+    /// Sends the requests from the local address, e.g. the zone transfers of a multi-homed host
+    ///
+    /// # Arguments
+    ///
+    /// * `name_server` - address of the name server to use for queries
+    /// * `bind_addr` - local address of the requests, each of them is sent from a random port
+    /// * `timeout` - time to wait for the responses
+    pub fn with_bind_addr(
+        name_server: SocketAddr,
+        bind_addr: IpAddr,
+        timeout: Duration,
+    ) -> ClientResult<Self> {
+        Ok(UdpClientConnection {
+            name_server,
+            bind_addr: Some(bind_addr),
+            timeout,
+        })
     }
 }
 
@@ -52,6 +72,11 @@ impl ClientConnection for UdpClientConnection {
         &self,
         signer: Option<Arc<Signer>>,
     ) -> Self::SenderFuture {
-        UdpClientStream::with_timeout_and_signer(self.name_server, self.timeout, signer)
+        UdpClientStream::with_bind_addr_and_signer(
+            self.name_server,
+            self.bind_addr,
+            self.timeout,
+            signer,
+        )
     }
 }
@@ -0,0 +1,64 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! UDP based DNS client connection over a single socket for Client impls
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use proto::udp::{UdpMultiplexedClientConnect, UdpMultiplexedClientStream};
+use proto::xfer::{DnsMultiplexer, DnsMultiplexerConnect, DnsRequestSender};
+
+use client::ClientConnection;
+use error::*;
+use rr::dnssec::Signer;
+
+/// UDP based DNS Client connection, all the requests are sent from one socket
+///
+/// `UdpClientConnection` binds a socket for every request, which makes the responses harder to
+///  spoof but uses a file descriptor per concurrent request. This connection sends all the
+///  requests from a single socket, the responses are matched to the requests by their ID, and
+///  each request times out on its own.
+///
+/// Use with `trust_dns::client::Client` impls
+#[derive(Clone)]
+pub struct UdpMultiplexedClientConnection {
+    name_server: SocketAddr,
+    timeout: Duration,
+}
+
+impl UdpMultiplexedClientConnection {
+    /// Creates a new client connection. With a default timeout of 5 seconds
+    ///
+    /// # Arguments
+    ///
+    /// * `name_server` - address of the name server to use for queries
+    pub fn new(name_server: SocketAddr) -> ClientResult<Self> {
+        Self::with_timeout(name_server, Duration::from_secs(5))
+    }
+
+    /// Allows a custom timeout, of each request
+    pub fn with_timeout(name_server: SocketAddr, timeout: Duration) -> ClientResult<Self> {
+        Ok(UdpMultiplexedClientConnection {
+            name_server,
+            timeout,
+        })
+    }
+}
+
+impl ClientConnection for UdpMultiplexedClientConnection {
+    type Sender = DnsMultiplexer<UdpMultiplexedClientStream, Signer>;
+    type Response = <Self::Sender as DnsRequestSender>::DnsResponseFuture;
+    type SenderFuture =
+        DnsMultiplexerConnect<UdpMultiplexedClientConnect, UdpMultiplexedClientStream, Signer>;
+
+    fn new_stream(&self, signer: Option<Arc<Signer>>) -> Self::SenderFuture {
+        let (udp_client_stream, handle) = UdpMultiplexedClientStream::new(self.name_server);
+        DnsMultiplexer::with_timeout(udp_client_stream, handle, self.timeout, signer)
+    }
+}
@@ -0,0 +1,90 @@
+/*
+ * Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+
+/*
+ * C interface to the lookups of the Trust-DNS Resolver, see src/lib.rs
+ *
+ * Lookups block the calling thread up to the timeout of the resolver. All results are owned by the
+ * caller, and must be released with the matching *_free function.
+ */
+
+#ifndef TRUST_DNS_H
+#define TRUST_DNS_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct TrustDnsResolver TrustDnsResolver;
+
+typedef enum TrustDnsStatus {
+    TRUST_DNS_OK = 0,
+    TRUST_DNS_INVALID_ARGUMENT = 1,
+    TRUST_DNS_NO_RECORDS_FOUND = 2,
+    TRUST_DNS_TIMEOUT = 3,
+    TRUST_DNS_IO = 4,
+    TRUST_DNS_PROTO = 5,
+    TRUST_DNS_OTHER = 6,
+} TrustDnsStatus;
+
+typedef struct TrustDnsIpAddr {
+    /* 4 for IPv4 or 6 for IPv6 */
+    int family;
+    /* network byte order, IPv4 addresses are in the first 4 bytes */
+    uint8_t octets[16];
+} TrustDnsIpAddr;
+
+typedef struct TrustDnsLookupIp {
+    TrustDnsIpAddr *addrs;
+    size_t len;
+} TrustDnsLookupIp;
+
+typedef struct TrustDnsRecord {
+    /* fully qualified name, NUL terminated */
+    char *name;
+    uint16_t record_type;
+    uint32_t ttl;
+    /* wire format, without compressed names */
+    uint8_t *rdata;
+    size_t rdata_len;
+} TrustDnsRecord;
+
+typedef struct TrustDnsLookup {
+    TrustDnsRecord *records;
+    size_t len;
+} TrustDnsLookup;
+
+/* uses the Google public name servers */
+TrustDnsResolver *trust_dns_resolver_new(void);
+/* uses the system configuration, e.g. /etc/resolv.conf */
+TrustDnsStatus trust_dns_resolver_from_system_conf(TrustDnsResolver **resolver);
+void trust_dns_resolver_free(TrustDnsResolver *resolver);
+
+TrustDnsStatus trust_dns_lookup_ip(const TrustDnsResolver *resolver,
+                                   const char *host,
+                                   TrustDnsLookupIp **lookup);
+void trust_dns_lookup_ip_free(TrustDnsLookupIp *lookup);
+
+TrustDnsStatus trust_dns_lookup(const TrustDnsResolver *resolver,
+                                const char *name,
+                                uint16_t record_type,
+                                TrustDnsLookup **lookup);
+void trust_dns_lookup_free(TrustDnsLookup *lookup);
+
+/* static, NUL terminated description of the status */
+const char *trust_dns_status_str(TrustDnsStatus status);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* TRUST_DNS_H */
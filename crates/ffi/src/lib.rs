@@ -0,0 +1,438 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! C interface to the lookups of the Trust-DNS Resolver
+//!
+//! The lookups are performed with the `StdResolver`, they block the calling thread up to the
+//!  timeout of the resolver and no background threads are started. The declarations for C are in
+//!  `include/trust_dns.h`.
+//!
+//! All the results returned through pointers are owned by the caller, and must be released with
+//!  the matching `*_free` function.
+
+#![warn(missing_docs)]
+
+extern crate trust_dns_proto;
+extern crate trust_dns_resolver;
+
+use std::ffi::{CStr, CString};
+use std::net::IpAddr;
+use std::os::raw::{c_char, c_int};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use trust_dns_proto::rr::{Record, RecordType};
+use trust_dns_proto::serialize::binary::BinEncoder;
+use trust_dns_resolver::error::{ResolveError, ResolveErrorKind};
+use trust_dns_resolver::StdResolver;
+
+/// A resolver, created with `trust_dns_resolver_new` and released with `trust_dns_resolver_free`
+pub struct TrustDnsResolver(StdResolver);
+
+/// The result of the functions of this library
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrustDnsStatus {
+    /// The call was successful
+    Ok = 0,
+    /// An argument was null, or the name was not valid UTF-8
+    InvalidArgument = 1,
+    /// The name exists, but no records of the requested type, or the name does not exist
+    NoRecordsFound = 2,
+    /// The name servers did not respond before the timeout
+    Timeout = 3,
+    /// An error of the sockets to the name servers
+    Io = 4,
+    /// A response could not be decoded or validated
+    Proto = 5,
+    /// Any other failure
+    Other = 6,
+}
+
+impl<'a> From<&'a ResolveError> for TrustDnsStatus {
+    fn from(error: &'a ResolveError) -> Self {
+        match *error.kind() {
+            ResolveErrorKind::NoRecordsFound { .. } => TrustDnsStatus::NoRecordsFound,
+            ResolveErrorKind::Timeout => TrustDnsStatus::Timeout,
+            ResolveErrorKind::Io => TrustDnsStatus::Io,
+            ResolveErrorKind::Proto => TrustDnsStatus::Proto,
+            _ => TrustDnsStatus::Other,
+        }
+    }
+}
+
+/// An IPv4 or IPv6 address
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TrustDnsIpAddr {
+    /// 4 for IPv4 or 6 for IPv6
+    pub family: c_int,
+    /// The address in network byte order, IPv4 addresses are in the first 4 bytes
+    pub octets: [u8; 16],
+}
+
+impl From<IpAddr> for TrustDnsIpAddr {
+    fn from(ip: IpAddr) -> Self {
+        let mut octets = [0u8; 16];
+        let family = match ip {
+            IpAddr::V4(ip) => {
+                octets[..4].copy_from_slice(&ip.octets());
+                4
+            }
+            IpAddr::V6(ip) => {
+                octets.copy_from_slice(&ip.octets());
+                6
+            }
+        };
+
+        TrustDnsIpAddr { family, octets }
+    }
+}
+
+/// The addresses of `trust_dns_lookup_ip`, released with `trust_dns_lookup_ip_free`
+#[repr(C)]
+#[derive(Debug)]
+pub struct TrustDnsLookupIp {
+    /// The addresses, in the order of preference
+    pub addrs: *mut TrustDnsIpAddr,
+    /// The number of addresses
+    pub len: usize,
+}
+
+/// A record of a lookup
+#[repr(C)]
+#[derive(Debug)]
+pub struct TrustDnsRecord {
+    /// The fully qualified name of the record, NUL terminated
+    pub name: *mut c_char,
+    /// The type of the record, e.g. 1 for A
+    pub record_type: u16,
+    /// The TTL of the record in seconds
+    pub ttl: u32,
+    /// The data of the record in wire format, without compressed names
+    pub rdata: *mut u8,
+    /// The length of `rdata`
+    pub rdata_len: usize,
+}
+
+/// The records of `trust_dns_lookup`, released with `trust_dns_lookup_free`
+#[repr(C)]
+#[derive(Debug)]
+pub struct TrustDnsLookup {
+    /// The records, including the CNAMEs followed to the requested type
+    pub records: *mut TrustDnsRecord,
+    /// The number of records
+    pub len: usize,
+}
+
+/// Creates a resolver with the default configuration, which uses the Google public name servers
+#[no_mangle]
+pub extern "C" fn trust_dns_resolver_new() -> *mut TrustDnsResolver {
+    Box::into_raw(Box::new(TrustDnsResolver(StdResolver::default())))
+}
+
+/// Creates a resolver with the system configuration, e.g. `/etc/resolv.conf`
+///
+/// On success `*resolver` is set to the new resolver.
+#[cfg(any(unix, target_os = "windows"))]
+#[no_mangle]
+pub unsafe extern "C" fn trust_dns_resolver_from_system_conf(
+    resolver: *mut *mut TrustDnsResolver,
+) -> TrustDnsStatus {
+    if resolver.is_null() {
+        return TrustDnsStatus::InvalidArgument;
+    }
+
+    match StdResolver::from_system_conf() {
+        Ok(std_resolver) => {
+            *resolver = Box::into_raw(Box::new(TrustDnsResolver(std_resolver)));
+            TrustDnsStatus::Ok
+        }
+        Err(_) => TrustDnsStatus::Io,
+    }
+}
+
+/// Releases a resolver, null is ignored
+#[no_mangle]
+pub unsafe extern "C" fn trust_dns_resolver_free(resolver: *mut TrustDnsResolver) {
+    if !resolver.is_null() {
+        drop(Box::from_raw(resolver));
+    }
+}
+
+/// Looks up the IPv4 and IPv6 addresses of `host`, a NUL terminated name or IP address
+///
+/// On success `*lookup` is set to the addresses, which must be released with
+///  `trust_dns_lookup_ip_free`.
+#[no_mangle]
+pub unsafe extern "C" fn trust_dns_lookup_ip(
+    resolver: *const TrustDnsResolver,
+    host: *const c_char,
+    lookup: *mut *mut TrustDnsLookupIp,
+) -> TrustDnsStatus {
+    let (resolver, host) = match args(resolver, host, lookup) {
+        Some(args) => args,
+        None => return TrustDnsStatus::InvalidArgument,
+    };
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| resolver.0.lookup_ip(host)));
+    match result {
+        Ok(Ok(lookup_ip)) => {
+            let addrs: Vec<TrustDnsIpAddr> = lookup_ip.iter().map(TrustDnsIpAddr::from).collect();
+            let (addrs, len) = into_raw_slice(addrs);
+            *lookup = Box::into_raw(Box::new(TrustDnsLookupIp { addrs, len }));
+            TrustDnsStatus::Ok
+        }
+        Ok(Err(ref error)) => error.into(),
+        Err(_) => TrustDnsStatus::Other,
+    }
+}
+
+/// Releases the addresses of `trust_dns_lookup_ip`, null is ignored
+#[no_mangle]
+pub unsafe extern "C" fn trust_dns_lookup_ip_free(lookup: *mut TrustDnsLookupIp) {
+    if lookup.is_null() {
+        return;
+    }
+
+    let lookup = Box::from_raw(lookup);
+    drop(from_raw_slice(lookup.addrs, lookup.len));
+}
+
+/// Looks up the records of `record_type` for `name`, a NUL terminated name
+///
+/// On success `*lookup` is set to the records, which must be released with
+///  `trust_dns_lookup_free`.
+#[no_mangle]
+pub unsafe extern "C" fn trust_dns_lookup(
+    resolver: *const TrustDnsResolver,
+    name: *const c_char,
+    record_type: u16,
+    lookup: *mut *mut TrustDnsLookup,
+) -> TrustDnsStatus {
+    let (resolver, name) = match args(resolver, name, lookup) {
+        Some(args) => args,
+        None => return TrustDnsStatus::InvalidArgument,
+    };
+
+    let record_type = RecordType::from(record_type);
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let lookup = resolver.0.lookup(name, record_type)?;
+        lookup
+            .record_iter()
+            .map(OwnedRecord::new)
+            .collect::<Result<Vec<_>, ResolveError>>()
+    }));
+
+    match result {
+        Ok(Ok(records)) => {
+            // the records are only given to the caller once all of them were converted
+            let records = records
+                .into_iter()
+                .map(OwnedRecord::into_raw)
+                .collect::<Vec<_>>();
+            let (records, len) = into_raw_slice(records);
+            *lookup = Box::into_raw(Box::new(TrustDnsLookup { records, len }));
+            TrustDnsStatus::Ok
+        }
+        Ok(Err(ref error)) => error.into(),
+        Err(_) => TrustDnsStatus::Other,
+    }
+}
+
+/// Releases the records of `trust_dns_lookup`, null is ignored
+#[no_mangle]
+pub unsafe extern "C" fn trust_dns_lookup_free(lookup: *mut TrustDnsLookup) {
+    if lookup.is_null() {
+        return;
+    }
+
+    let lookup = Box::from_raw(lookup);
+    for record in from_raw_slice(lookup.records, lookup.len).iter() {
+        drop(CString::from_raw(record.name));
+        drop(from_raw_slice(record.rdata, record.rdata_len));
+    }
+}
+
+/// Returns a static, NUL terminated description of the status
+#[no_mangle]
+pub extern "C" fn trust_dns_status_str(status: TrustDnsStatus) -> *const c_char {
+    let description: &'static [u8] = match status {
+        TrustDnsStatus::Ok => b"ok\0",
+        TrustDnsStatus::InvalidArgument => b"invalid argument\0",
+        TrustDnsStatus::NoRecordsFound => b"no records found\0",
+        TrustDnsStatus::Timeout => b"request timed out\0",
+        TrustDnsStatus::Io => b"io error\0",
+        TrustDnsStatus::Proto => b"proto error\0",
+        TrustDnsStatus::Other => b"unknown error\0",
+    };
+
+    description.as_ptr() as *const c_char
+}
+
+/// A record converted for a `TrustDnsRecord`, still owned, released if the lookup fails
+struct OwnedRecord {
+    name: CString,
+    record_type: u16,
+    ttl: u32,
+    rdata: Vec<u8>,
+}
+
+impl OwnedRecord {
+    fn new(record: &Record) -> Result<Self, ResolveError> {
+        let mut rdata = Vec::new();
+        {
+            let mut encoder = BinEncoder::new(&mut rdata);
+            encoder.set_canonical_names(true);
+            record.rdata().emit(&mut encoder)?;
+        }
+
+        let name = CString::new(record.name().to_string())
+            .map_err(|_| ResolveError::from("record name contains a NUL byte"))?;
+
+        Ok(OwnedRecord {
+            name,
+            record_type: record.record_type().into(),
+            ttl: record.ttl(),
+            rdata,
+        })
+    }
+
+    /// Gives the name and data to the caller, released by `trust_dns_lookup_free`
+    fn into_raw(self) -> TrustDnsRecord {
+        let (rdata, rdata_len) = into_raw_slice(self.rdata);
+
+        TrustDnsRecord {
+            name: self.name.into_raw(),
+            record_type: self.record_type,
+            ttl: self.ttl,
+            rdata,
+            rdata_len,
+        }
+    }
+}
+
+/// Checks the arguments of a lookup, and resets the result
+unsafe fn args<'a, T>(
+    resolver: *const TrustDnsResolver,
+    name: *const c_char,
+    lookup: *mut *mut T,
+) -> Option<(&'a TrustDnsResolver, &'a str)> {
+    if lookup.is_null() {
+        return None;
+    }
+    *lookup = ptr::null_mut();
+
+    if resolver.is_null() || name.is_null() {
+        return None;
+    }
+
+    let name = CStr::from_ptr(name).to_str().ok()?;
+    Some((&*resolver, name))
+}
+
+fn into_raw_slice<T>(values: Vec<T>) -> (*mut T, usize) {
+    let len = values.len();
+    let values = Box::into_raw(values.into_boxed_slice());
+    (values as *mut T, len)
+}
+
+unsafe fn from_raw_slice<T>(values: *mut T, len: usize) -> Box<[T]> {
+    Box::from_raw(slice::from_raw_parts_mut(values, len))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    fn lookup_ip(resolver: *const TrustDnsResolver, host: &[u8]) -> Vec<TrustDnsIpAddr> {
+        let mut lookup = ptr::null_mut();
+        let status = unsafe { trust_dns_lookup_ip(resolver, host.as_ptr() as _, &mut lookup) };
+        assert_eq!(status, TrustDnsStatus::Ok);
+
+        let addrs = unsafe { slice::from_raw_parts((*lookup).addrs, (*lookup).len).to_vec() };
+        unsafe { trust_dns_lookup_ip_free(lookup) };
+        addrs
+    }
+
+    #[test]
+    fn test_lookup_ip() {
+        let resolver = trust_dns_resolver_new();
+
+        let addrs = lookup_ip(resolver, b"10.1.0.2\0");
+        assert_eq!(addrs, vec![IpAddr::from(Ipv4Addr::new(10, 1, 0, 2)).into()]);
+        assert_eq!(addrs[0].family, 4);
+        assert_eq!(&addrs[0].octets[..4], &[10, 1, 0, 2]);
+
+        let addrs = lookup_ip(resolver, b"localhost.\0");
+        assert_eq!(addrs[0], IpAddr::from(Ipv4Addr::new(127, 0, 0, 1)).into());
+
+        unsafe { trust_dns_resolver_free(resolver) };
+    }
+
+    #[test]
+    fn test_lookup_records() {
+        let resolver = trust_dns_resolver_new();
+
+        let mut lookup = ptr::null_mut();
+        let status = unsafe {
+            trust_dns_lookup(
+                resolver,
+                b"localhost.\0".as_ptr() as _,
+                RecordType::A.into(),
+                &mut lookup,
+            )
+        };
+        assert_eq!(status, TrustDnsStatus::Ok);
+
+        let record = unsafe { &*(*lookup).records };
+        let name = unsafe { CStr::from_ptr(record.name) };
+        assert_eq!(name.to_str().unwrap(), "localhost.");
+        assert_eq!(record.record_type, 1);
+        let rdata = unsafe { slice::from_raw_parts(record.rdata, record.rdata_len) };
+        assert_eq!(rdata, &[127, 0, 0, 1]);
+
+        unsafe {
+            trust_dns_lookup_free(lookup);
+            trust_dns_resolver_free(resolver);
+        }
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        let resolver = trust_dns_resolver_new();
+
+        let mut lookup = ptr::null_mut();
+        let status = unsafe { trust_dns_lookup_ip(resolver, ptr::null(), &mut lookup) };
+        assert_eq!(status, TrustDnsStatus::InvalidArgument);
+        assert!(lookup.is_null());
+
+        let status = unsafe {
+            trust_dns_lookup_ip(resolver, b"localhost.\0".as_ptr() as _, ptr::null_mut())
+        };
+        assert_eq!(status, TrustDnsStatus::InvalidArgument);
+
+        let status =
+            unsafe { trust_dns_lookup_ip(ptr::null(), b"localhost.\0".as_ptr() as _, &mut lookup) };
+        assert_eq!(status, TrustDnsStatus::InvalidArgument);
+
+        unsafe {
+            trust_dns_lookup_ip_free(ptr::null_mut());
+            trust_dns_lookup_free(ptr::null_mut());
+            trust_dns_resolver_free(resolver);
+        }
+    }
+
+    #[test]
+    fn test_status_str() {
+        let description = unsafe { CStr::from_ptr(trust_dns_status_str(TrustDnsStatus::Timeout)) };
+        assert_eq!(description.to_str().unwrap(), "request timed out");
+    }
+}
@@ -9,6 +9,7 @@ use std::{fmt, io};
 
 use failure::{Backtrace, Context, Fail};
 use h2;
+use http::StatusCode;
 use trust_dns_proto::error::ProtoError;
 use typed_headers;
 
@@ -33,6 +34,14 @@ pub enum ErrorKind {
 
     #[fail(display = "h2: {}", _0)]
     H2(h2::Error),
+
+    /// The request was rejected, with the status of the response to it
+    #[fail(display = "{}: {}", _0, _1)]
+    Status(StatusCode, String),
+
+    /// An error reading or writing HTTP/1.1 messages
+    #[fail(display = "io error: {}", _0)]
+    Io(io::Error),
 }
 
 /// The error type for errors that get returned in the crate
@@ -46,6 +55,15 @@ impl Error {
     pub fn kind(&self) -> &ErrorKind {
         self.inner.get_context()
     }
+
+    /// The status of the HTTP response to the request which failed with this error
+    pub fn status(&self) -> StatusCode {
+        match *self.kind() {
+            ErrorKind::Status(status, _) => status,
+            ErrorKind::H2(_) | ErrorKind::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
 }
 
 impl Fail for Error {
@@ -108,6 +126,12 @@ impl From<h2::Error> for Error {
     }
 }
 
+impl From<io::Error> for Error {
+    fn from(msg: io::Error) -> Self {
+        ErrorKind::Io(msg).into()
+    }
+}
+
 impl From<Error> for io::Error {
     fn from(err: Error) -> Self {
         io::Error::new(io::ErrorKind::Other, format!("https: {}", err))
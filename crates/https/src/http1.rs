@@ -0,0 +1,265 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! HTTP/1.1 requests and responses, for reverse proxies which don't speak HTTP/2 to the backend
+
+use bytes::{BufMut, Bytes, BytesMut};
+use http::{header, Request, Response, StatusCode, Version};
+use httparse;
+use tokio_codec::{Decoder, Encoder};
+use typed_headers::{ContentLength, HeaderMapExt};
+
+use error::ErrorKind;
+use {HttpsError, HttpsResult};
+
+/// The most headers in a request
+const MAX_HEADERS: usize = 64;
+/// The longest request line and headers of a request
+const MAX_HEAD_LEN: usize = 8 * 1024;
+/// DNS messages are at most 65535 bytes long
+const MAX_BODY_LEN: usize = 65_535;
+
+/// Decodes HTTP/1.1 requests, of which the body must have a `Content-Length`, and encodes the
+///  responses to them
+///
+/// The version of the responses is always HTTP/1.1, regardless of their `version()`.
+#[derive(Debug, Default)]
+pub struct Http1Codec {
+    // the request of which the body is being received, and the length of that
+    head: Option<(Request<()>, usize)>,
+}
+
+impl Http1Codec {
+    /// Creates a codec for a new connection
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Decoder for Http1Codec {
+    type Item = Request<Bytes>;
+    type Error = HttpsError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> HttpsResult<Option<Request<Bytes>>> {
+        if self.head.is_none() {
+            let (request, head_len) = match decode_head(src)? {
+                Some(head) => head,
+                None => return Ok(None),
+            };
+
+            // the body is the message, which is not streamed, chunked messages are not supported
+            if request.headers().contains_key(header::TRANSFER_ENCODING) {
+                return Err(status(
+                    StatusCode::LENGTH_REQUIRED,
+                    "only bodies with a content length are supported",
+                ));
+            }
+
+            let content_length: Option<ContentLength> = request.headers().typed_get()?;
+            let length = content_length.map_or(0, |length| *length as usize);
+            if length > MAX_BODY_LEN {
+                return Err(status(
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    format!("message is too large: {}", length),
+                ));
+            }
+
+            src.split_to(head_len);
+            self.head = Some((request, length));
+        }
+
+        let length = self.head.as_ref().map_or(0, |head| head.1);
+        if src.len() < length {
+            src.reserve(length - src.len());
+            return Ok(None);
+        }
+
+        let body = src.split_to(length).freeze();
+        Ok(self.head.take().map(|(request, _)| request.map(|()| body)))
+    }
+}
+
+/// Decodes the request line and headers, returning the request and the length of those
+fn decode_head(src: &BytesMut) -> HttpsResult<Option<(Request<()>, usize)>> {
+    let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+    let mut parsed = httparse::Request::new(&mut headers);
+
+    let head_len = match parsed.parse(src) {
+        Ok(httparse::Status::Complete(head_len)) => head_len,
+        Ok(httparse::Status::Partial) if src.len() > MAX_HEAD_LEN => {
+            return Err(status(
+                StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+                "request is too large",
+            ))
+        }
+        Ok(httparse::Status::Partial) => return Ok(None),
+        Err(httparse::Error::TooManyHeaders) => {
+            return Err(status(
+                StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+                "too many headers",
+            ))
+        }
+        Err(e) => {
+            return Err(status(
+                StatusCode::BAD_REQUEST,
+                format!("bad request: {}", e),
+            ))
+        }
+    };
+
+    let mut request = Request::builder();
+    request
+        .method(parsed.method.unwrap_or_default())
+        .uri(parsed.path.unwrap_or_default())
+        .version(match parsed.version {
+            Some(1) => Version::HTTP_11,
+            _ => Version::HTTP_10,
+        });
+
+    for header in parsed.headers.iter() {
+        request.header(header.name, header.value);
+    }
+
+    let request = request
+        .body(())
+        .map_err(|e| status(StatusCode::BAD_REQUEST, format!("bad request: {}", e)))?;
+
+    Ok(Some((request, head_len)))
+}
+
+impl Encoder for Http1Codec {
+    type Item = Response<Bytes>;
+    type Error = HttpsError;
+
+    fn encode(&mut self, response: Response<Bytes>, dst: &mut BytesMut) -> HttpsResult<()> {
+        let (parts, body) = response.into_parts();
+
+        let status_line = format!(
+            "HTTP/1.1 {} {}\r\n",
+            parts.status.as_str(),
+            parts.status.canonical_reason().unwrap_or("")
+        );
+        dst.extend_from_slice(status_line.as_bytes());
+
+        for (name, value) in &parts.headers {
+            dst.extend_from_slice(name.as_str().as_bytes());
+            dst.extend_from_slice(b": ");
+            dst.extend_from_slice(value.as_bytes());
+            dst.extend_from_slice(b"\r\n");
+        }
+
+        dst.extend_from_slice(b"\r\n");
+        dst.reserve(body.len());
+        dst.put_slice(&body);
+
+        Ok(())
+    }
+}
+
+/// Returns true if the connection is kept open after the response to the request
+pub fn keep_alive<T>(request: &Request<T>) -> bool {
+    let close = request
+        .headers()
+        .get_all(header::CONNECTION)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|option| option.trim().eq_ignore_ascii_case("close"));
+
+    request.version() == Version::HTTP_11 && !close
+}
+
+fn status<S: Into<String>>(status: StatusCode, msg: S) -> HttpsError {
+    ErrorKind::Status(status, msg.into()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use http::Method;
+
+    use super::*;
+
+    #[test]
+    fn test_decode_get() {
+        let mut codec = Http1Codec::new();
+        let mut src = BytesMut::from(
+            &b"GET /dns-query?dns=AAEC HTTP/1.1\r\nHost: ns.example.com\r\n\r\nGET"[..],
+        );
+
+        let request = codec.decode(&mut src).unwrap().expect("no request");
+        assert_eq!(request.method(), Method::GET);
+        assert_eq!(request.uri(), "/dns-query?dns=AAEC");
+        assert_eq!(request.version(), Version::HTTP_11);
+        assert_eq!(request.headers()[header::HOST], "ns.example.com");
+        assert!(request.body().is_empty());
+        assert!(keep_alive(&request));
+
+        // the next request is incomplete
+        assert_eq!(&src[..], b"GET");
+        assert!(codec.decode(&mut src).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_decode_post() {
+        let mut codec = Http1Codec::new();
+        let mut src = BytesMut::from(
+            &b"POST /dns-query HTTP/1.1\r\nContent-Length: 3\r\nConnection: close\r\n\r\n\x00"[..],
+        );
+
+        assert!(codec.decode(&mut src).unwrap().is_none());
+        src.extend_from_slice(b"\x01\x02");
+
+        let request = codec.decode(&mut src).unwrap().expect("no request");
+        assert_eq!(request.method(), Method::POST);
+        assert_eq!(&request.body()[..], &[0, 1, 2]);
+        assert!(!keep_alive(&request));
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let decode_status = |src: &[u8]| {
+            Http1Codec::new()
+                .decode(&mut BytesMut::from(src))
+                .unwrap_err()
+                .status()
+        };
+
+        assert_eq!(
+            decode_status(b"GET\x00 / HTTP/1.1\r\n\r\n"),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            decode_status(b"POST /dns-query HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n"),
+            StatusCode::LENGTH_REQUIRED
+        );
+        assert_eq!(
+            decode_status(b"POST /dns-query HTTP/1.1\r\nContent-Length: 65536\r\n\r\n"),
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+        assert_eq!(
+            decode_status(&vec![b'a'; MAX_HEAD_LEN + 1]),
+            StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE
+        );
+    }
+
+    #[test]
+    fn test_encode() {
+        let response = Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_LENGTH, "3")
+            .body(Bytes::from(&[0_u8, 1, 2][..]))
+            .unwrap();
+
+        let mut dst = BytesMut::new();
+        Http1Codec::new().encode(response, &mut dst).unwrap();
+        assert_eq!(
+            &dst[..],
+            &b"HTTP/1.1 200 OK\r\ncontent-length: 3\r\n\r\n\x00\x01\x02"[..]
+        );
+    }
+}
@@ -59,10 +59,15 @@ impl DnsRequestSender for HttpsClientStream {
             panic!("can not send messages after stream is shutdown")
         }
 
-        // per the RFC, a zero id allows for the HTTP packet to be cached better
-        message.set_id(0);
-
-        let bytes = match message.to_vec() {
+        // per the RFC, a zero id allows for the HTTP packet to be cached better, raw requests are
+        //  sent as they are
+        let bytes = if message.is_raw() {
+            message.to_raw_vec()
+        } else {
+            message.set_id(0);
+            message.to_vec()
+        };
+        let bytes = match bytes {
             Ok(bytes) => bytes,
             Err(err) => {
                 return HttpsSerialResponse(HttpsSerialResponseInner::Errored(Some(err.into())))
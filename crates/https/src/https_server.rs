@@ -11,13 +11,18 @@ use std::borrow::Borrow;
 use std::fmt::Debug;
 use std::sync::Arc;
 
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
+use data_encoding::BASE64URL_NOPAD;
 use futures::{Async, Future, Poll, Stream};
 use h2;
-use http::{Method, Request};
+use http::{Method, Request, StatusCode, Uri};
 use typed_headers::{ContentLength, HeaderMapExt};
 
-use HttpsError;
+use error::ErrorKind;
+use {HttpsError, HttpsResult};
+
+/// DNS messages are at most 65535 bytes long
+const MAX_MESSAGE_LEN: usize = 65_535;
 
 /// Given an HTTP request, return a future that will result in the next sequence of bytes.
 ///
@@ -35,28 +40,83 @@ where
         Err(err) => return HttpsToMessageInner::HttpsError(Some(err)).into(),
     }
 
-    // attempt to get the content length
-    let content_length: Option<ContentLength> = match request.headers().typed_get() {
-        Ok(l) => l,
-        Err(err) => return HttpsToMessageInner::HttpsError(Some(err.into())).into(),
-    };
+    match *request.method() {
+        Method::GET => match message_from_query(request.uri()) {
+            Ok(bytes) => HttpsToMessageInner::FromGet(Some(bytes)).into(),
+            Err(err) => HttpsToMessageInner::HttpsError(Some(err)).into(),
+        },
+        _ => match content_length(&request) {
+            Ok(content_length) => message_from_post(request, content_length).into(),
+            Err(err) => HttpsToMessageInner::HttpsError(Some(err)).into(),
+        },
+    }
+}
 
-    let content_length: Option<usize> = content_length.map(|c| {
-        let length = *c as usize;
-        debug!("got message length: {}", length);
-        length
-    });
+/// Returns the bytes of the DNS message of a request of which the whole body was received, e.g.
+///  over HTTP/1.1
+pub fn message_from_bytes(this_server_name: &str, request: &Request<Bytes>) -> HttpsResult<Bytes> {
+    debug!("Received request: {:#?}", request);
+
+    ::request::verify(this_server_name, request)?;
 
     match *request.method() {
-        Method::GET => HttpsToMessageInner::HttpsError(Some(
-            format!("GET unimplemented: {}", request.method()).into(),
-        ))
-        .into(),
-        Method::POST => message_from_post(request, content_length).into(),
-        _ => HttpsToMessageInner::HttpsError(Some(
-            format!("bad method: {}", request.method()).into(),
-        ))
-        .into(),
+        Method::GET => message_from_query(request.uri()),
+        _ => {
+            if let Some(length) = content_length(request)? {
+                if request.body().len() != length {
+                    return Err("not all bytes received".into());
+                }
+            }
+
+            Ok(request.body().clone())
+        }
+    }
+}
+
+/// The message of a GET request is the `dns` variable of the query, encoded with base64url
+fn message_from_query(uri: &Uri) -> HttpsResult<Bytes> {
+    let dns = uri
+        .query()
+        .and_then(|query| {
+            query
+                .split('&')
+                .find(|param| param.starts_with("dns="))
+                .map(|param| &param[4..])
+        })
+        .ok_or_else(|| {
+            HttpsError::from(ErrorKind::Status(
+                StatusCode::BAD_REQUEST,
+                "no dns variable in the query".to_string(),
+            ))
+        })?;
+
+    BASE64URL_NOPAD
+        .decode(dns.as_bytes())
+        .map(Bytes::from)
+        .map_err(|e| {
+            ErrorKind::Status(
+                StatusCode::BAD_REQUEST,
+                format!("bad dns variable in the query: {}", e),
+            )
+            .into()
+        })
+}
+
+fn content_length<R>(request: &Request<R>) -> HttpsResult<Option<usize>> {
+    // attempt to get the content length
+    let content_length: Option<ContentLength> = request.headers().typed_get()?;
+
+    match content_length.map(|c| *c as usize) {
+        Some(length) if length > MAX_MESSAGE_LEN => Err(ErrorKind::Status(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("message is too large: {}", length),
+        )
+        .into()),
+        Some(length) => {
+            debug!("got message length: {}", length);
+            Ok(Some(length))
+        }
+        None => Ok(None),
     }
 }
 
@@ -91,6 +151,7 @@ where
 #[must_use = "futures do nothing unless polled"]
 enum HttpsToMessageInner<R> {
     FromPost(MessageFromPost<R>),
+    FromGet(Option<Bytes>),
     HttpsError(Option<HttpsError>),
 }
 
@@ -104,6 +165,9 @@ where
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
         match self {
             HttpsToMessageInner::FromPost(from_post) => from_post.poll(),
+            HttpsToMessageInner::FromGet(bytes) => Ok(Async::Ready(
+                bytes.take().expect("cannot poll after complete"),
+            )),
             HttpsToMessageInner::HttpsError(error) => {
                 Err(error.take().expect("cannot poll after complete"))
             }
@@ -116,6 +180,7 @@ fn message_from_post<R>(request: Request<R>, length: Option<usize>) -> MessageFr
     MessageFromPost {
         stream: body,
         length,
+        bytes: BytesMut::new(),
     }
 }
 
@@ -123,6 +188,7 @@ fn message_from_post<R>(request: Request<R>, length: Option<usize>) -> MessageFr
 struct MessageFromPost<R> {
     stream: R,
     length: Option<usize>,
+    bytes: BytesMut,
 }
 
 impl<R> Future for MessageFromPost<R>
@@ -141,21 +207,31 @@ where
                 Err(e) => return Err(e.into()),
             };
 
+            self.bytes.extend_from_slice(&bytes);
+
             let bytes = if let Some(length) = self.length {
                 // wait until we have all the bytes
-                if bytes.len() < length {
+                if self.bytes.len() < length {
                     continue;
                 }
 
                 // this will trim the bytes back to whatever we didn't consume
-                bytes.slice_to(length)
+                self.bytes.split_to(length)
             } else {
                 warn!("no content-length, assuming we have all the bytes");
-                bytes.slice_from(0)
+                self.bytes.take()
             };
 
+            if bytes.len() > MAX_MESSAGE_LEN {
+                return Err(ErrorKind::Status(
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    format!("message is too large: {}", bytes.len()),
+                )
+                .into());
+            }
+
             //let message = Message::from_vec(&bytes)?;
-            return Ok(Async::Ready(bytes));
+            return Ok(Async::Ready(bytes.freeze()));
         }
     }
 }
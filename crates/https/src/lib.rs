@@ -14,10 +14,12 @@ extern crate data_encoding;
 extern crate futures;
 extern crate h2;
 extern crate http;
+extern crate httparse;
 #[macro_use]
 extern crate log;
 extern crate failure;
 extern crate rustls;
+extern crate tokio_codec;
 extern crate tokio_executor;
 extern crate tokio_reactor;
 extern crate tokio_rustls;
@@ -36,6 +38,7 @@ const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VE
 
 //pub mod https_client_connection;
 mod error;
+pub mod http1;
 mod https_client_stream;
 pub mod https_server;
 pub mod request;
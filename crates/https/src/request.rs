@@ -9,14 +9,16 @@
 
 use std::str::FromStr;
 
-use http::{header, uri, Method, Request, Uri, Version};
+use data_encoding::BASE64URL_NOPAD;
+use http::{header, uri, Method, Request, StatusCode, Uri, Version};
 use typed_headers::{
     mime::Mime, Accept, ContentLength, ContentType, HeaderMapExt, Quality, QualityItem,
 };
 
 use trust_dns_proto::error::ProtoError;
 
-use HttpsResult;
+use error::ErrorKind;
+use {HttpsError, HttpsResult};
 
 /// Create a new Reqeust for an http/2 dns-message request
 ///
@@ -41,16 +43,10 @@ pub fn new(name_server_name: &str, message_len: usize) -> HttpsResult<Request<()
     //     .header(header::USER_AGENT, USER_AGENT)
     //     .body(());
 
-    let mut parts = uri::Parts::default();
-    parts.path_and_query = Some(uri::PathAndQuery::from_static(::DNS_QUERY_PATH));
-    parts.scheme = Some(uri::Scheme::HTTPS);
-    parts.authority = Some(
-        uri::Authority::from_str(&name_server_name)
-            .map_err(|e| ProtoError::from(format!("invalid authority: {}", e)))?,
-    );
-
-    let url =
-        Uri::from_parts(parts).map_err(|e| ProtoError::from(format!("uri parse error: {}", e)))?;
+    let url = url(
+        name_server_name,
+        uri::PathAndQuery::from_static(::DNS_QUERY_PATH),
+    )?;
 
     let accepts_dns = Mime::from_str(::MIME_APPLICATION_DNS).unwrap();
     let content_type = ContentType(accepts_dns.clone());
@@ -76,53 +72,133 @@ pub fn new(name_server_name: &str, message_len: usize) -> HttpsResult<Request<()
     Ok(request)
 }
 
+/// Create a new GET Request for an http/2 dns-message request
+///
+/// The message is the `dns` variable of the query, encoded with base64url. GET requests are
+///  larger than POST requests, but their responses may be cached by HTTP caches.
+pub fn new_get(name_server_name: &str, message: &[u8]) -> HttpsResult<Request<()>> {
+    let path_and_query = format!(
+        "{}?dns={}",
+        ::DNS_QUERY_PATH,
+        BASE64URL_NOPAD.encode(message)
+    );
+    let url = url(
+        name_server_name,
+        uri::PathAndQuery::from_str(&path_and_query)
+            .map_err(|e| ProtoError::from(format!("invalid query: {}", e)))?,
+    )?;
+
+    let accepts_dns = Mime::from_str(::MIME_APPLICATION_DNS).unwrap();
+    let accept = Accept(vec![QualityItem::new(accepts_dns, Quality::from_u16(1000))]);
+
+    let mut request = Request::get(url)
+        .header(header::USER_AGENT, ::USER_AGENT)
+        .version(Version::HTTP_2)
+        .body(())
+        .map_err(|e| ProtoError::from(format!("h2 stream errored: {}", e)))?;
+
+    request.headers_mut().typed_insert(&accept);
+
+    Ok(request)
+}
+
+fn url(name_server_name: &str, path_and_query: uri::PathAndQuery) -> HttpsResult<Uri> {
+    let mut parts = uri::Parts::default();
+    parts.path_and_query = Some(path_and_query);
+    parts.scheme = Some(uri::Scheme::HTTPS);
+    parts.authority = Some(
+        uri::Authority::from_str(name_server_name)
+            .map_err(|e| ProtoError::from(format!("invalid authority: {}", e)))?,
+    );
+
+    Ok(Uri::from_parts(parts).map_err(|e| ProtoError::from(format!("uri parse error: {}", e)))?)
+}
+
 /// Verifies the request is something we know what to deal with
+///
+/// Requests are rejected with the status of the response to them, for example
+///  `415 Unsupported Media Type` for a POST of anything but a DNS message, or `406 Not Acceptable`
+///  if the client doesn't accept DNS messages in response.
 pub fn verify<T>(name_server: &str, request: &Request<T>) -> HttpsResult<()> {
     // Verify all HTTP parameters
     let uri = request.uri();
 
     // validate path
     if uri.path() != ::DNS_QUERY_PATH {
-        return Err(format!("bad path: {}, expected: {}", uri.path(), ::DNS_QUERY_PATH).into());
+        return Err(status(
+            StatusCode::NOT_FOUND,
+            format!("bad path: {}, expected: {}", uri.path(), ::DNS_QUERY_PATH),
+        ));
     }
 
-    // we only accept HTTPS
-    if Some(&uri::Scheme::HTTPS) != uri.scheme_part() {
-        return Err("must be HTTPS scheme".into());
+    // HTTP/1.1 is supported for reverse proxies, which generally don't speak HTTP/2 to the backend
+    if request.version() != Version::HTTP_2 && request.version() != Version::HTTP_11 {
+        return Err(status(
+            StatusCode::HTTP_VERSION_NOT_SUPPORTED,
+            format!("unsupported version: {:?}", request.version()),
+        ));
     }
 
-    // the authority must match our nameserver name
-    if let Some(authority) = uri.authority_part() {
-        if authority.host() != name_server {
-            return Err("incorrect authority".into());
-        }
-    } else {
-        return Err("no authority in HTTPS request".into());
+    // we only accept HTTPS, HTTP/1.1 requests generally have no scheme in the request target
+    if uri
+        .scheme_part()
+        .map_or(false, |s| *s != uri::Scheme::HTTPS)
+    {
+        return Err(status(StatusCode::BAD_REQUEST, "must be HTTPS scheme"));
     }
 
-    let content_type: Option<ContentType> = request.headers().typed_get()?;
-    let accept: Option<Accept> = request.headers().typed_get()?;
+    // the authority must match our nameserver name, the Host header if it's not in the URI
+    let authority = match uri.authority_part() {
+        Some(authority) => authority.clone(),
+        None => request
+            .headers()
+            .get(header::HOST)
+            .and_then(|host| host.to_str().ok())
+            .and_then(|host| uri::Authority::from_str(host).ok())
+            .ok_or_else(|| status(StatusCode::BAD_REQUEST, "no authority in HTTPS request"))?,
+    };
 
-    // TODO: switch to mime::APPLICATION_DNS when that stabilizes
-    if !content_type
-        .map(|c| (c.type_() == ::MIME_APPLICATION && c.subtype() == ::MIME_DNS_BINARY))
-        .unwrap_or(true)
-    {
-        return Err("unsupported content type".into());
+    if authority.host() != name_server {
+        return Err(status(
+            StatusCode::MISDIRECTED_REQUEST,
+            format!("incorrect authority: {}", authority),
+        ));
     }
 
-    let accept = accept.ok_or_else(|| "Accept is unspecified")?;
+    if request.method() != Method::GET && request.method() != Method::POST {
+        return Err(status(
+            StatusCode::METHOD_NOT_ALLOWED,
+            format!("bad method: {}", request.method()),
+        ));
+    }
 
-    // TODO: switch to mime::APPLICATION_DNS when that stabilizes
-    if !accept
-        .iter()
-        .any(|q| (q.item.type_() == ::MIME_APPLICATION && q.item.subtype() == ::MIME_DNS_BINARY))
-    {
-        return Err("does not accept content type".into());
+    // only POST has content, the message of a GET is in the query
+    if request.method() == Method::POST {
+        let content_type: Option<ContentType> = request.headers().typed_get()?;
+
+        // TODO: switch to mime::APPLICATION_DNS when that stabilizes
+        if !content_type
+            .map(|c| c.type_() == ::MIME_APPLICATION && c.subtype() == ::MIME_DNS_BINARY)
+            .unwrap_or(true)
+        {
+            return Err(status(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "unsupported content type",
+            ));
+        }
     }
 
-    if request.version() != Version::HTTP_2 {
-        return Err("only HTTP/2 supported".into());
+    // no Accept header means that any media type is acceptable
+    let accept: Option<Accept> = request.headers().typed_get()?;
+    if !accept.map_or(true, |accept| {
+        accept
+            .iter()
+            .any(|q| q.quality.as_u16() > 0 && accepts_dns(&q.item))
+    }) {
+        return Err(status(
+            StatusCode::NOT_ACCEPTABLE,
+            "does not accept content type",
+        ));
     }
 
     debug!(
@@ -137,6 +213,20 @@ pub fn verify<T>(name_server: &str, request: &Request<T>) -> HttpsResult<()> {
     Ok(())
 }
 
+/// Returns true if the media range includes DNS messages, e.g. `application/*`
+fn accepts_dns(media_range: &Mime) -> bool {
+    // TODO: switch to mime::APPLICATION_DNS when that stabilizes
+    match (media_range.type_().as_str(), media_range.subtype().as_str()) {
+        ("*", "*") | (::MIME_APPLICATION, "*") => true,
+        (::MIME_APPLICATION, ::MIME_DNS_BINARY) => true,
+        _ => false,
+    }
+}
+
+fn status<S: Into<String>>(status: StatusCode, msg: S) -> HttpsError {
+    ErrorKind::Status(status, msg.into()).into()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -146,4 +236,93 @@ mod tests {
         let request = new("ns.example.com", 512).expect("error converting to http");
         assert!(verify("ns.example.com", &request).is_ok());
     }
+
+    #[test]
+    fn test_new_get_verify() {
+        let request = new_get("ns.example.com", &[0, 1, 2]).expect("error converting to http");
+        assert_eq!(request.uri().query(), Some("dns=AAEC"));
+        assert!(verify("ns.example.com", &request).is_ok());
+    }
+
+    fn verify_status(request: Request<()>) -> Option<StatusCode> {
+        verify("ns.example.com", &request).err().map(|e| e.status())
+    }
+
+    #[test]
+    fn test_verify_http11() {
+        let request = Request::get("/dns-query?dns=AAEC")
+            .header(header::HOST, "ns.example.com:8080")
+            .version(Version::HTTP_11)
+            .body(())
+            .unwrap();
+        assert_eq!(verify_status(request), None);
+
+        let request = Request::get("/dns-query?dns=AAEC")
+            .version(Version::HTTP_11)
+            .body(())
+            .unwrap();
+        assert_eq!(verify_status(request), Some(StatusCode::BAD_REQUEST));
+
+        let request = Request::get("/dns-query?dns=AAEC")
+            .header(header::HOST, "ns.example.com")
+            .version(Version::HTTP_10)
+            .body(())
+            .unwrap();
+        assert_eq!(
+            verify_status(request),
+            Some(StatusCode::HTTP_VERSION_NOT_SUPPORTED)
+        );
+    }
+
+    #[test]
+    fn test_verify_status() {
+        let mut request = new("ns.example.com", 512).unwrap();
+        *request.uri_mut() = "https://ns.example.com/resolve".parse().unwrap();
+        assert_eq!(verify_status(request), Some(StatusCode::NOT_FOUND));
+
+        let mut request = new("ns.example.com", 512).unwrap();
+        *request.method_mut() = Method::PUT;
+        assert_eq!(verify_status(request), Some(StatusCode::METHOD_NOT_ALLOWED));
+
+        let mut request = new("ns.example.com", 512).unwrap();
+        request.headers_mut().insert(
+            header::CONTENT_TYPE,
+            "application/dns-json".parse().unwrap(),
+        );
+        assert_eq!(
+            verify_status(request),
+            Some(StatusCode::UNSUPPORTED_MEDIA_TYPE)
+        );
+
+        let request = new("ns.example.com", 512).unwrap();
+        assert_eq!(
+            verify("ns.example.net", &request).unwrap_err().status(),
+            StatusCode::MISDIRECTED_REQUEST
+        );
+    }
+
+    #[test]
+    fn test_verify_accept() {
+        for (accept, status) in &[
+            ("application/dns-message", None),
+            ("*/*", None),
+            ("application/*;q=0.5, text/html", None),
+            ("application/dns-json", Some(StatusCode::NOT_ACCEPTABLE)),
+            (
+                "application/dns-message;q=0",
+                Some(StatusCode::NOT_ACCEPTABLE),
+            ),
+        ] {
+            let mut request = new_get("ns.example.com", &[0]).unwrap();
+            request
+                .headers_mut()
+                .insert(header::ACCEPT, accept.parse().unwrap());
+            assert_eq!(verify_status(request), *status, "accept: {}", accept);
+        }
+
+        // no Accept header accepts any media type
+        let mut request = new_get("ns.example.com", &[0]).unwrap();
+        request.headers_mut().remove(header::ACCEPT);
+        assert_eq!(verify_status(request), None);
+    }
 }
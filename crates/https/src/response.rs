@@ -9,10 +9,13 @@
 
 use std::str::FromStr;
 
+use http::header::{self, HeaderValue};
 use http::{Response, StatusCode, Version};
 use typed_headers::{mime::Mime, ContentLength, ContentType, HeaderMapExt};
 
 use trust_dns_proto::error::ProtoError;
+use trust_dns_proto::op::Message;
+use trust_dns_proto::rr::Record;
 
 use HttpsResult;
 
@@ -41,7 +44,12 @@ use HttpsResult;
 /// cannot generate a representation suitable for the client (HTTP status
 /// code 406, [RFC7231] Section 6.5.6), and so on.
 /// ```
-pub fn new(message_len: usize) -> HttpsResult<Response<()>> {
+///
+/// # Arguments
+///
+/// * `message_len` - the length of the DNS message of the response
+/// * `max_age` - for how long, in seconds, the response may be cached, see `max_age`
+pub fn new(message_len: usize, max_age: Option<u32>) -> HttpsResult<Response<()>> {
     let mut response = Response::builder();
     response.status(StatusCode::OK);
     response.version(Version::HTTP_2);
@@ -57,5 +65,112 @@ pub fn new(message_len: usize) -> HttpsResult<Response<()>> {
         .headers_mut()
         .typed_insert(&ContentLength(message_len as u64));
 
+    if let Some(max_age) = max_age {
+        response.headers_mut().insert(
+            header::CACHE_CONTROL,
+            HeaderValue::from_str(&format!("max-age={}", max_age))
+                .map_err(|e| ProtoError::from(format!("invalid cache control: {}", e)))?,
+        );
+    }
+
+    Ok(response)
+}
+
+/// Create a new Response for a request which was rejected with the `status`, it has no content
+pub fn error(status: StatusCode) -> HttpsResult<Response<()>> {
+    let mut response = Response::builder();
+    response.status(status);
+    response.version(Version::HTTP_2);
+    let mut response = response
+        .body(())
+        .map_err(|e| ProtoError::from(format!("invalid response: {}", e)))?;
+
+    response.headers_mut().typed_insert(&ContentLength(0));
+
     Ok(response)
 }
+
+/// The freshness lifetime of the response to the message, for the `Cache-Control` header
+///
+/// ```text
+/// https://tools.ietf.org/html/rfc8484#section-5.1
+/// The assigned freshness lifetime of a DoH HTTP response MUST be less
+/// than or equal to the smallest TTL in the Answer section of the DNS
+/// response.
+///
+/// [...] If the DNS response has no records in the Answer section, and the
+/// DNS response has an SOA record in the Authority section, the response
+/// freshness lifetime MUST NOT be greater than the MINIMUM field from that
+/// SOA record (see [RFC2308]).
+/// ```
+///
+/// Responses without either, e.g. with a SERVFAIL, have no freshness lifetime.
+pub fn max_age(message: &Message) -> Option<u32> {
+    if !message.answers().is_empty() {
+        return message.answers().iter().map(Record::ttl).min();
+    }
+
+    message
+        .name_servers()
+        .iter()
+        .filter_map(|record| {
+            record
+                .rdata()
+                .as_soa()
+                .map(|soa| record.ttl().min(soa.minimum()))
+        })
+        .min()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use trust_dns_proto::rr::rdata::SOA;
+    use trust_dns_proto::rr::{Name, RData};
+
+    use super::*;
+
+    #[test]
+    fn test_max_age_of_answers() {
+        let name = Name::from_ascii("www.example.com.").unwrap();
+        let mut message = Message::new();
+        message
+            .add_answer(Record::from_rdata(
+                name.clone(),
+                300,
+                RData::A(Ipv4Addr::new(127, 0, 0, 1)),
+            ))
+            .add_answer(Record::from_rdata(
+                name,
+                60,
+                RData::A(Ipv4Addr::new(127, 0, 0, 2)),
+            ));
+
+        assert_eq!(max_age(&message), Some(60));
+
+        let response = new(512, max_age(&message)).unwrap();
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "max-age=60");
+    }
+
+    #[test]
+    fn test_max_age_of_negative_response() {
+        let origin = Name::from_ascii("example.com.").unwrap();
+        let mut message = Message::new();
+        message.add_name_server(Record::from_rdata(
+            origin.clone(),
+            3600,
+            RData::SOA(SOA::new(origin.clone(), origin, 1, 7200, 3600, 86400, 900)),
+        ));
+
+        assert_eq!(max_age(&message), Some(900));
+    }
+
+    #[test]
+    fn test_no_max_age() {
+        assert_eq!(max_age(&Message::new()), None);
+
+        let response = new(512, None).unwrap();
+        assert!(!response.headers().contains_key(header::CACHE_CONTROL));
+    }
+}
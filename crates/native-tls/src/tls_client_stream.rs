@@ -17,7 +17,7 @@ use tokio_tcp::TcpStream as TokioTcpStream;
 use tokio_tls::TlsStream as TokioTlsStream;
 
 use trust_dns_proto::error::ProtoError;
-use trust_dns_proto::tcp::TcpClientStream;
+use trust_dns_proto::tcp::{TcpClientStream, TcpOptions};
 use trust_dns_proto::xfer::BufDnsStreamHandle;
 
 use TlsStreamBuilder;
@@ -49,6 +49,11 @@ impl TlsClientStreamBuilder {
         self.0.identity(pkcs12);
     }
 
+    /// Socket options of the TCP connection, e.g. TCP Fast Open
+    pub fn tcp_options(&mut self, tcp_options: TcpOptions) {
+        self.0.tcp_options(tcp_options);
+    }
+
     /// Creates a new TlsStream to the specified name_server
     ///
     /// # Arguments
@@ -17,7 +17,7 @@ use native_tls::{Certificate, Identity, TlsConnector};
 use tokio_tcp::TcpStream as TokioTcpStream;
 use tokio_tls::{TlsConnector as TokioTlsConnector, TlsStream as TokioTlsStream};
 
-use trust_dns_proto::tcp::TcpStream;
+use trust_dns_proto::tcp::{TcpOptions, TcpStream};
 use trust_dns_proto::xfer::BufStreamHandle;
 
 /// A TlsStream counterpart to the TcpStream which embeds a secure TlsStream
@@ -62,6 +62,7 @@ pub fn tls_from_stream(
 pub struct TlsStreamBuilder {
     ca_chain: Vec<Certificate>,
     identity: Option<Identity>,
+    tcp_options: TcpOptions,
 }
 
 impl TlsStreamBuilder {
@@ -70,6 +71,7 @@ impl TlsStreamBuilder {
         TlsStreamBuilder {
             ca_chain: vec![],
             identity: None,
+            tcp_options: TcpOptions::default(),
         }
     }
 
@@ -86,6 +88,11 @@ impl TlsStreamBuilder {
         self.identity = Some(identity);
     }
 
+    /// Socket options of the TCP connection, e.g. TCP Fast Open
+    pub fn tcp_options(&mut self, tcp_options: TcpOptions) {
+        self.tcp_options = tcp_options;
+    }
+
     /// Creates a new TlsStream to the specified name_server
     ///
     /// [RFC 7858](https://tools.ietf.org/html/rfc7858), DNS over TLS, May 2016
@@ -137,7 +144,7 @@ impl TlsStreamBuilder {
             }
         };
 
-        let tcp = TokioTcpStream::connect(&name_server);
+        let tcp = self.tcp_options.connect(name_server);
 
         // This set of futures collapses the next tcp socket into a stream which can be used for
         //  sending and receiving tcp packets.
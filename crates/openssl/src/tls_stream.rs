@@ -19,7 +19,7 @@ use openssl::x509::{X509Ref, X509};
 use tokio_openssl::{SslConnectorExt, SslStream as TokioTlsStream};
 use tokio_tcp::TcpStream as TokioTcpStream;
 
-use trust_dns_proto::tcp::TcpStream;
+use trust_dns_proto::tcp::{TcpOptions, TcpStream};
 use trust_dns_proto::xfer::BufStreamHandle;
 
 pub trait TlsIdentityExt {
@@ -130,6 +130,7 @@ pub fn tls_stream_from_existing_tls_stream(
 pub struct TlsStreamBuilder {
     ca_chain: Vec<X509>,
     identity: Option<ParsedPkcs12>,
+    tcp_options: TcpOptions,
 }
 
 impl TlsStreamBuilder {
@@ -138,6 +139,7 @@ impl TlsStreamBuilder {
         TlsStreamBuilder {
             ca_chain: vec![],
             identity: None,
+            tcp_options: TcpOptions::default(),
         }
     }
 
@@ -154,6 +156,11 @@ impl TlsStreamBuilder {
         self.identity = Some(pkcs12);
     }
 
+    /// Socket options of the TCP connection, e.g. TCP Fast Open
+    pub fn tcp_options(&mut self, tcp_options: TcpOptions) {
+        self.tcp_options = tcp_options;
+    }
+
     /// Creates a new TlsStream to the specified name_server
     ///
     /// [RFC 7858](https://tools.ietf.org/html/rfc7858), DNS over TLS, May 2016
@@ -205,7 +212,7 @@ impl TlsStreamBuilder {
             }
         };
 
-        let tcp = TokioTcpStream::connect(&name_server);
+        let tcp = self.tcp_options.connect(name_server);
 
         // This set of futures collapses the next tcp socket into a stream which can be used for
         //  sending and receiving tcp packets.
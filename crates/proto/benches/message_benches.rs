@@ -0,0 +1,135 @@
+#[macro_use]
+extern crate criterion;
+extern crate trust_dns_proto;
+
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::net::Ipv4Addr;
+
+use criterion::{black_box, Criterion};
+
+use trust_dns_proto::hash::FxBuildHasher;
+use trust_dns_proto::op::{Message, MessageType, Query};
+use trust_dns_proto::rr::{Name, RData, Record, RecordSet, RecordType};
+use trust_dns_proto::serialize::binary::{BinEncodable, BinEncoder};
+
+/// A response with `count` A records, all the names share the `example.com.` suffix
+fn response(count: u8) -> Message {
+    let origin = Name::from_ascii("example.com.").unwrap();
+    let name = Name::from_ascii("www").unwrap().append_domain(&origin);
+
+    let mut message = Message::new();
+    message
+        .set_id(10)
+        .set_message_type(MessageType::Response)
+        .add_query(Query::query(name.clone(), RecordType::A));
+
+    for i in 0..count {
+        message.add_answer(Record::from_rdata(
+            name.clone(),
+            86400,
+            RData::A(Ipv4Addr::new(93, 184, 216, i)),
+        ));
+
+        let host = Name::from_ascii(format!("host{}.sub", i))
+            .unwrap()
+            .append_domain(&origin);
+        message.add_additional(Record::from_rdata(
+            host,
+            86400,
+            RData::A(Ipv4Addr::new(10, 0, 0, i)),
+        ));
+    }
+
+    message
+}
+
+fn bench_message_parse(c: &mut Criterion) {
+    let bytes = response(4).to_vec().unwrap();
+
+    c.bench_function("message_parse", move |b| {
+        b.iter(|| Message::from_vec(black_box(&bytes)).unwrap())
+    });
+}
+
+fn bench_large_response_encode(c: &mut Criterion) {
+    let message = response(200);
+
+    c.bench_function("large_response_encode", move |b| {
+        b.iter(|| black_box(&message).to_vec().unwrap())
+    });
+}
+
+/// Encodes the response with the labels of the name pointers hashed by `S`
+fn bench_large_response_encode_hasher<S: BuildHasher + Default + Send + Sync + 'static>(
+    c: &mut Criterion,
+    id: &str,
+) {
+    let message = response(200);
+    let hasher = S::default();
+
+    c.bench_function(id, move |b| {
+        b.iter(|| {
+            let mut buffer = Vec::with_capacity(512);
+            {
+                let mut encoder = BinEncoder::new(&mut buffer);
+                encoder.set_name_hasher(Some(&hasher));
+                black_box(&message).emit(&mut encoder).unwrap();
+            }
+            buffer
+        })
+    });
+}
+
+fn bench_large_response_encode_fx(c: &mut Criterion) {
+    bench_large_response_encode_hasher::<FxBuildHasher>(c, "large_response_encode_fx");
+}
+
+fn bench_large_response_encode_sip(c: &mut Criterion) {
+    bench_large_response_encode_hasher::<RandomState>(c, "large_response_encode_sip");
+}
+
+fn bench_record_set_insert(c: &mut Criterion) {
+    let name = Name::from_ascii("www.example.com.").unwrap();
+    let records = (0..64)
+        .map(|i| Record::from_rdata(name.clone(), 86400, RData::A(Ipv4Addr::new(10, 0, 0, i))))
+        .collect::<Vec<_>>();
+
+    c.bench_function("record_set_insert", move |b| {
+        b.iter(|| {
+            let mut rr_set = RecordSet::new(&name, RecordType::A, 0);
+            for record in &records {
+                rr_set.insert(record.clone(), 0);
+            }
+            rr_set
+        })
+    });
+}
+
+fn bench_record_set_lookup(c: &mut Criterion) {
+    let name = Name::from_ascii("www.example.com.").unwrap();
+    let mut rr_set = RecordSet::new(&name, RecordType::A, 0);
+    for i in 0..64 {
+        rr_set.add_rdata(RData::A(Ipv4Addr::new(10, 0, 0, i)));
+    }
+    let last = RData::A(Ipv4Addr::new(10, 0, 0, 63));
+
+    c.bench_function("record_set_lookup", move |b| {
+        b.iter(|| {
+            rr_set
+                .records_without_rrsigs()
+                .any(|record| record.rdata() == black_box(&last))
+        })
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_message_parse,
+    bench_large_response_encode,
+    bench_large_response_encode_fx,
+    bench_large_response_encode_sip,
+    bench_record_set_insert,
+    bench_record_set_lookup
+);
+criterion_main!(benches);
@@ -0,0 +1,8 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate trust_dns;
+
+fuzz_target!(|data: &[u8]| {
+    trust_dns::fuzzing::zone_file(data);
+});
@@ -0,0 +1,630 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! `Arbitrary` names, record data, records and messages, for the property based tests of their
+//!  encoding
+//!
+//! The values are the ones which decode back to themselves: e.g. type bit maps are ordered and
+//!  client subnets have no address bits beyond their prefix. Names share a few suffixes, so that
+//!  they are compressed in messages.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use proptest::collection::vec;
+use proptest::prelude::*;
+
+use error::ProtoResult;
+use op::{Edns, Message, MessageType, OpCode, Query, ResponseCode};
+#[cfg(feature = "dnssec")]
+use rr::dnssec::rdata::DNSSECRecordType;
+use rr::domain::Label;
+use rr::rdata::caa::{KeyValue, Property, Value};
+use rr::rdata::opt::{ClientSubnet, Cookie, EdnsCode, EdnsOption, LongLivedQuery, UpdateLease};
+use rr::rdata::sshfp::{Algorithm as SshfpAlgorithm, FingerprintType};
+use rr::rdata::tlsa::{CertUsage, Matching, Selector};
+use rr::rdata::{CAA, MX, NAPTR, NULL, OPENPGPKEY, OPT, SOA, SRV, SSHFP, TLSA, TXT};
+use rr::{DNSClass, Name, RData, Record, RecordType};
+use serialize::binary::BinEncoder;
+
+/// The bytes of a label, mostly letters, digits and hyphens
+fn label() -> impl Strategy<Value = Label> {
+    let byte = prop_oneof![
+        8 => prop::sample::select(&b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789-"[..]),
+        1 => any::<u8>(),
+    ];
+
+    vec(byte, 1..=63).prop_map(|bytes| Label::from_raw_bytes(&bytes).expect("bad label"))
+}
+
+/// Names of any labels, of at most 255 bytes on the wire, under one of a few shared suffixes
+fn name() -> impl Strategy<Value = Name> {
+    let suffix = prop::sample::select(vec![".", "com.", "example.com.", "www.example.com."]);
+
+    (vec(label(), 0..4), suffix)
+        .prop_map(|(labels, suffix)| {
+            let suffix = Name::from_ascii(suffix).expect("bad suffix");
+            Name::from_labels(labels)
+                .expect("bad labels")
+                .append_domain(&suffix)
+        })
+        .prop_filter("names are at most 255 bytes", |name| name.len() <= 255)
+}
+
+/// Names in the letters, digits and hyphens of host names, which parse back from their text
+fn host_name() -> impl Strategy<Value = Name> {
+    vec("[a-z0-9]([a-z0-9-]{0,8}[a-z0-9])?", 1..4).prop_map(|labels| {
+        Name::from_labels(labels.iter().map(String::as_str)).expect("bad host name")
+    })
+}
+
+/// The values of the codes which decode
+fn known<T>(from_u8: fn(u8) -> ProtoResult<T>) -> Vec<T> {
+    (0..=u8::MAX)
+        .filter_map(|code| from_u8(code).ok())
+        .collect()
+}
+
+fn bytes(len: std::ops::Range<usize>) -> impl Strategy<Value = Vec<u8>> {
+    vec(any::<u8>(), len)
+}
+
+/// Character-strings, of at most 255 bytes
+fn text() -> impl Strategy<Value = Vec<u8>> {
+    bytes(0..256)
+}
+
+fn caa() -> impl Strategy<Value = CAA> {
+    let key_value =
+        ("[a-zA-Z0-9]{1,8}", "[a-zA-Z0-9]{0,8}").prop_map(|(key, value)| KeyValue::new(key, value));
+    let issuer = (
+        any::<bool>(),
+        prop::option::of(host_name()),
+        vec(key_value, 0..3),
+    );
+    let url = "[a-z]{1,10}".prop_map(|host| {
+        format!("https://{}.example/caa", host)
+            .parse()
+            .expect("bad url")
+    });
+
+    prop_oneof![
+        (any::<bool>(), issuer).prop_map(|(wild, (critical, name, key_values))| if wild {
+            CAA::new_issuewild(critical, name, key_values)
+        } else {
+            CAA::new_issue(critical, name, key_values)
+        }),
+        (any::<bool>(), url).prop_map(|(critical, url)| CAA::new_iodef(critical, url)),
+        (any::<bool>(), "[a-z0-9]{1,15}", bytes(0..32))
+            .prop_filter("known properties have their own values", |caa| {
+                Property::from(caa.1.clone()).is_unknown()
+            })
+            .prop_map(|(issuer_critical, tag, value)| CAA {
+                issuer_critical,
+                tag: Property::Unknown(tag),
+                value: Value::Unknown(value),
+            }),
+    ]
+}
+
+fn client_subnet() -> impl Strategy<Value = ClientSubnet> {
+    prop_oneof![
+        (any::<u32>(), 0u8..=32, 0u8..=32).prop_map(|(address, source, scope)| (
+            IpAddr::from(Ipv4Addr::from(address)),
+            source,
+            scope
+        )),
+        (any::<u128>(), 0u8..=128, 0u8..=128).prop_map(|(address, source, scope)| (
+            IpAddr::from(Ipv6Addr::from(address)),
+            source,
+            scope
+        )),
+    ]
+    .prop_map(|(address, source, scope)| {
+        ClientSubnet::new(address, source, scope).expect("bad client subnet")
+    })
+}
+
+fn edns_option() -> impl Strategy<Value = EdnsOption> {
+    let cookie = (any::<[u8; 8]>(), prop_oneof![Just(vec![]), bytes(8..33)])
+        .prop_map(|(client, server)| Cookie::new(client, server).expect("bad cookie"));
+
+    // the unassigned codes, without the ones of the parsers registered by the tests
+    let unknown =
+        (15u16..65_000, bytes(0..16)).prop_map(|(code, data)| EdnsOption::Unknown(code, data));
+
+    let options = prop_oneof![
+        (
+            any::<u16>(),
+            any::<u16>(),
+            any::<u16>(),
+            any::<u64>(),
+            any::<u32>()
+        )
+            .prop_map(|(version, opcode, error, id, lease)| EdnsOption::LLQ(
+                LongLivedQuery::new(version, opcode, error, id, lease)
+            )),
+        (any::<u32>(), prop::option::of(any::<u32>()))
+            .prop_map(|(lease, key_lease)| EdnsOption::UL(UpdateLease::new(lease, key_lease))),
+        bytes(0..16).prop_map(EdnsOption::NSID),
+        client_subnet().prop_map(EdnsOption::Subnet),
+        prop::option::of(any::<u32>()).prop_map(EdnsOption::Expire),
+        cookie.prop_map(EdnsOption::Cookie),
+        prop::option::of(any::<u16>()).prop_map(EdnsOption::Keepalive),
+        (0u16..512).prop_map(EdnsOption::Padding),
+        name().prop_map(EdnsOption::Chain),
+        vec(any::<u16>(), 0..4).prop_map(EdnsOption::KeyTag),
+        unknown,
+    ];
+
+    #[cfg(feature = "dnssec")]
+    let options = {
+        use rr::dnssec::SupportedAlgorithms;
+
+        let algorithms =
+            any::<u8>().prop_map(|bits| SupportedAlgorithms::from(&[bits & 0b0111_1111][..]));
+        prop_oneof![
+            10 => options,
+            1 => algorithms.clone().prop_map(EdnsOption::DAU),
+            1 => algorithms.clone().prop_map(EdnsOption::DHU),
+            1 => algorithms.prop_map(EdnsOption::N3U),
+        ]
+    };
+
+    options
+}
+
+fn opt() -> impl Strategy<Value = OPT> {
+    vec(edns_option(), 0..4).prop_map(|options| {
+        let options: HashMap<EdnsCode, EdnsOption> = options
+            .into_iter()
+            .map(|option| (EdnsCode::from(&option), option))
+            .collect();
+        OPT::new(options)
+    })
+}
+
+/// The record types of the type bit maps of NSEC and NSEC3, in the order in which they decode
+#[cfg(feature = "dnssec")]
+fn type_bit_maps() -> impl Strategy<Value = Vec<RecordType>> {
+    use proptest::collection::btree_set;
+
+    btree_set(any::<u16>(), 0..8)
+        .prop_map(|codes| codes.into_iter().map(RecordType::from).collect())
+}
+
+#[cfg(feature = "dnssec")]
+fn dnssec_rdata() -> impl Strategy<Value = RData> {
+    use rr::dnssec::rdata::key::{KeyTrust, KeyUsage, Protocol, UpdateScope};
+    use rr::dnssec::rdata::{
+        DNSSECRData, DNSSECRecordType, TsigAlgorithm, DNSKEY, DS, KEY, NSEC, NSEC3, NSEC3PARAM,
+        SIG, TSIG,
+    };
+    use rr::dnssec::{Algorithm, DigestType, Nsec3HashAlgorithm};
+
+    let algorithm = prop::sample::select(known(Algorithm::from_u8));
+    let digest_type = prop::sample::select(known(DigestType::from_u8));
+    let tsig_algorithm = prop_oneof![
+        Just(TsigAlgorithm::HmacMd5),
+        Just(TsigAlgorithm::HmacSha1),
+        Just(TsigAlgorithm::HmacSha224),
+        Just(TsigAlgorithm::HmacSha256),
+        Just(TsigAlgorithm::HmacSha384),
+        Just(TsigAlgorithm::HmacSha512),
+        name().prop_map(TsigAlgorithm::from_name),
+    ];
+
+    prop_oneof![
+        (
+            any::<bool>(),
+            any::<bool>(),
+            any::<bool>(),
+            algorithm.clone(),
+            bytes(0..64)
+        )
+            .prop_map(
+                |(zone_key, secure_entry_point, revoke, algorithm, public_key)| {
+                    DNSSECRData::DNSKEY(DNSKEY::new(
+                        zone_key,
+                        secure_entry_point,
+                        revoke,
+                        algorithm,
+                        public_key,
+                    ))
+                }
+            ),
+        (any::<u16>(), algorithm.clone(), digest_type, bytes(0..64)).prop_map(
+            |(key_tag, algorithm, digest_type, digest)| {
+                DNSSECRData::DS(DS::new(key_tag, algorithm, digest_type, digest))
+            }
+        ),
+        (any::<u16>(), any::<u8>(), algorithm.clone(), bytes(0..64)).prop_map(
+            |(flags, protocol, algorithm, public_key)| {
+                DNSSECRData::KEY(KEY::new(
+                    KeyTrust::from(flags),
+                    KeyUsage::from(flags),
+                    UpdateScope::from(flags),
+                    Protocol::from(protocol),
+                    algorithm,
+                    public_key,
+                ))
+            }
+        ),
+        (name(), type_bit_maps())
+            .prop_map(|(next, types)| DNSSECRData::NSEC(NSEC::new(next, types))),
+        (
+            any::<bool>(),
+            any::<u16>(),
+            bytes(0..256),
+            bytes(0..256),
+            type_bit_maps()
+        )
+            .prop_map(|(opt_out, iterations, salt, next, types)| {
+                DNSSECRData::NSEC3(NSEC3::new(
+                    Nsec3HashAlgorithm::SHA1,
+                    opt_out,
+                    iterations,
+                    salt,
+                    next,
+                    types,
+                ))
+            }),
+        (any::<bool>(), any::<u16>(), bytes(0..256)).prop_map(|(opt_out, iterations, salt)| {
+            DNSSECRData::NSEC3PARAM(NSEC3PARAM::new(
+                Nsec3HashAlgorithm::SHA1,
+                opt_out,
+                iterations,
+                salt,
+            ))
+        }),
+        (
+            any::<u16>(),
+            algorithm,
+            any::<u8>(),
+            any::<(u32, u32, u32, u16)>(),
+            name(),
+            bytes(0..64)
+        )
+            .prop_map(
+                |(
+                    type_covered,
+                    algorithm,
+                    num_labels,
+                    (ttl, expiration, inception, key_tag),
+                    signer,
+                    sig,
+                )| {
+                    DNSSECRData::SIG(SIG::new(
+                        RecordType::from(type_covered),
+                        algorithm,
+                        num_labels,
+                        ttl,
+                        expiration,
+                        inception,
+                        key_tag,
+                        signer,
+                        sig,
+                    ))
+                }
+            ),
+        (
+            tsig_algorithm,
+            0u64..(1 << 48),
+            any::<(u16, u16, u16)>(),
+            bytes(0..64),
+            bytes(0..16)
+        )
+            .prop_map(|(algorithm, time, (fudge, oid, error), mac, other)| {
+                DNSSECRData::TSIG(TSIG::new(algorithm, time, fudge, mac, oid, error, other))
+            }),
+        (any::<u16>(), bytes(1..32)).prop_map(|(code, anything)| DNSSECRData::Unknown {
+            code,
+            rdata: NULL::with(anything),
+        }),
+    ]
+    .prop_filter(
+        "unknown DNSSEC types are only read as such",
+        |rdata| match *rdata {
+            DNSSECRData::Unknown { code, .. } => {
+                RecordType::DNSSEC(DNSSECRecordType::Unknown(code)) == RecordType::from(code)
+            }
+            _ => true,
+        },
+    )
+    .prop_map(RData::DNSSEC)
+}
+
+impl Arbitrary for Name {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        name().boxed()
+    }
+}
+
+impl Arbitrary for RData {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        let rdata = prop_oneof![
+            any::<u32>().prop_map(|address| RData::A(address.into())),
+            any::<u128>().prop_map(|address| RData::AAAA(address.into())),
+            name().prop_map(RData::ANAME),
+            caa().prop_map(RData::CAA),
+            name().prop_map(RData::CNAME),
+            (any::<u16>(), name())
+                .prop_map(|(preference, exchange)| RData::MX(MX::new(preference, exchange))),
+            (
+                any::<u16>(),
+                any::<u16>(),
+                "[a-zA-Z0-9]{0,4}",
+                text(),
+                text(),
+                name()
+            )
+                .prop_map(
+                    |(order, preference, flags, services, regexp, replacement)| {
+                        RData::NAPTR(NAPTR::new(
+                            order,
+                            preference,
+                            flags.into_bytes().into_boxed_slice(),
+                            services.into_boxed_slice(),
+                            regexp.into_boxed_slice(),
+                            replacement,
+                        ))
+                    }
+                ),
+            bytes(1..64).prop_map(|anything| RData::NULL(NULL::with(anything))),
+            name().prop_map(RData::NS),
+            bytes(0..64).prop_map(|key| RData::OPENPGPKEY(OPENPGPKEY::new(key))),
+            opt().prop_map(RData::OPT),
+            name().prop_map(RData::PTR),
+            (name(), name(), any::<(u32, i32, i32, i32, u32)>()).prop_map(
+                |(mname, rname, (serial, refresh, retry, expire, minimum))| {
+                    RData::SOA(SOA::new(
+                        mname, rname, serial, refresh, retry, expire, minimum,
+                    ))
+                }
+            ),
+            (any::<(u16, u16, u16)>(), name()).prop_map(|((priority, weight, port), target)| {
+                RData::SRV(SRV::new(priority, weight, port, target))
+            }),
+            (any::<u8>(), any::<u8>(), bytes(0..64)).prop_map(|(algorithm, kind, fingerprint)| {
+                RData::SSHFP(SSHFP::new(
+                    SshfpAlgorithm::from(algorithm),
+                    FingerprintType::from(kind),
+                    fingerprint,
+                ))
+            }),
+            (any::<(u8, u8, u8)>(), bytes(0..64)).prop_map(
+                |((usage, selector, matching), data)| {
+                    RData::TLSA(TLSA::new(
+                        CertUsage::from(usage),
+                        Selector::from(selector),
+                        Matching::from(matching),
+                        data,
+                    ))
+                }
+            ),
+            vec(".{0,60}", 0..4).prop_map(|strings| RData::TXT(TXT::new(strings))),
+            (any::<u16>(), bytes(1..32))
+                .prop_filter("known types have their own data", |&(code, _)| {
+                    RecordType::from(code) == RecordType::Unknown(code)
+                })
+                .prop_map(|(code, anything)| RData::Unknown {
+                    code,
+                    rdata: NULL::with(anything),
+                }),
+            Just(RData::ZERO),
+        ];
+
+        #[cfg(feature = "dnssec")]
+        let rdata = prop_oneof![4 => rdata, 1 => dnssec_rdata()];
+
+        rdata.boxed()
+    }
+}
+
+impl Arbitrary for Record {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    /// Records of all the types but OPT, which are the EDNS of messages
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        let dns_class = prop_oneof![
+            8 => Just(DNSClass::IN),
+            1 => Just(DNSClass::CH),
+            1 => Just(DNSClass::HS),
+            1 => Just(DNSClass::NONE),
+            1 => Just(DNSClass::ANY),
+        ];
+
+        (name(), dns_class, any::<u32>(), any::<RData>())
+            .prop_filter("OPT records are the EDNS of messages", |record| {
+                record.3.to_record_type() != RecordType::OPT
+            })
+            .prop_map(|(name, dns_class, ttl, rdata)| {
+                let rr_type = rdata.to_record_type();
+
+                // empty record data is read as NULL, which is used in updates
+                let mut bytes = Vec::new();
+                rdata
+                    .emit(&mut BinEncoder::new(&mut bytes))
+                    .expect("failed to encode");
+                let rdata = if bytes.is_empty() {
+                    RData::NULL(NULL::new())
+                } else {
+                    rdata
+                };
+
+                let mut record = Record::from_rdata(name, ttl, rdata);
+                record.set_rr_type(rr_type);
+                record.set_dns_class(dns_class);
+                record
+            })
+            .boxed()
+    }
+}
+
+fn edns() -> impl Strategy<Value = Edns> {
+    (any::<u8>(), any::<u8>(), any::<bool>(), 512u16.., opt()).prop_map(
+        |(rcode_high, version, dnssec_ok, max_payload, options)| {
+            let mut edns = Edns::new();
+            edns.set_rcode_high(rcode_high);
+            edns.set_version(version);
+            edns.set_dnssec_ok(dnssec_ok);
+            edns.set_max_payload(max_payload);
+            for option in options.options().values() {
+                edns.set_option(option.clone());
+            }
+            edns
+        },
+    )
+}
+
+impl Arbitrary for Message {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    /// Messages of any header, sections and EDNS, without SIG(0) or TSIG signatures
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        let op_code = prop::sample::select(known(OpCode::from_u8));
+        let query = (name(), any::<u16>())
+            .prop_map(|(name, query_type)| Query::query(name, RecordType::from(query_type)));
+        let additional =
+            any::<Record>().prop_filter("signatures are the last records of messages", |record| {
+                match record.rr_type() {
+                    #[cfg(feature = "dnssec")]
+                    RecordType::DNSSEC(DNSSECRecordType::SIG)
+                    | RecordType::DNSSEC(DNSSECRecordType::TSIG) => false,
+                    _ => true,
+                }
+            });
+
+        (
+            (
+                any::<u16>(),
+                any::<bool>(),
+                op_code,
+                any::<[bool; 6]>(),
+                0u8..=10,
+            ),
+            vec(query, 0..3),
+            vec(any::<Record>(), 0..4),
+            vec(any::<Record>(), 0..3),
+            vec(additional, 0..3),
+            prop::option::of(edns()),
+        )
+            .prop_map(
+                |(
+                    (id, response, op_code, flags, rcode),
+                    queries,
+                    answers,
+                    name_servers,
+                    additionals,
+                    edns,
+                )| {
+                    let mut message = Message::new();
+                    message
+                        .set_id(id)
+                        .set_message_type(if response {
+                            MessageType::Response
+                        } else {
+                            MessageType::Query
+                        })
+                        .set_op_code(op_code)
+                        .set_authoritative(flags[0])
+                        .set_truncated(flags[1])
+                        .set_recursion_desired(flags[2])
+                        .set_recursion_available(flags[3])
+                        .set_authentic_data(flags[4])
+                        .set_checking_disabled(flags[5])
+                        .set_response_code(ResponseCode::from(0, rcode))
+                        .add_queries(queries)
+                        .add_answers(answers)
+                        .add_name_servers(name_servers);
+                    for additional in additionals {
+                        message.add_additional(additional);
+                    }
+                    if let Some(edns) = edns {
+                        message.set_edns(edns);
+                    }
+                    message.update_counts();
+                    message
+                },
+            )
+            .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serialize::binary::{BinDecodable, BinDecoder, BinEncodable, Restrict};
+
+    fn emit<E: BinEncodable>(value: &E) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = BinEncoder::new(&mut bytes);
+            value.emit(&mut encoder).expect("failed to encode");
+        }
+        bytes
+    }
+
+    proptest! {
+        #[test]
+        fn test_name_round_trip(name in any::<Name>()) {
+            let bytes = emit(&name);
+            let decoded = Name::from_bytes(&bytes).expect("failed to decode");
+            prop_assert!(decoded.eq_case(&name), "{:?} decoded to {:?}", name, decoded);
+            prop_assert_eq!(emit(&decoded), bytes);
+        }
+
+        #[test]
+        fn test_rdata_round_trip(rdata in any::<RData>()) {
+            let mut bytes = Vec::new();
+            {
+                let mut encoder = BinEncoder::new(&mut bytes);
+                rdata.emit(&mut encoder).expect("failed to encode");
+            }
+
+            let mut decoder = BinDecoder::new(&bytes);
+            let decoded = RData::read(
+                &mut decoder,
+                rdata.to_record_type(),
+                Restrict::new(bytes.len() as u16),
+            )
+            .expect("failed to decode");
+            prop_assert_eq!(&decoded, &rdata);
+
+            let mut encoded = Vec::new();
+            {
+                let mut encoder = BinEncoder::new(&mut encoded);
+                decoded.emit(&mut encoder).expect("failed to encode");
+            }
+            prop_assert_eq!(encoded, bytes);
+        }
+
+        #[test]
+        fn test_record_round_trip(record in any::<Record>()) {
+            let bytes = emit(&record);
+            let decoded = Record::from_bytes(&bytes).expect("failed to decode");
+            prop_assert_eq!(&decoded, &record);
+            prop_assert_eq!(emit(&decoded), bytes);
+        }
+
+        #[test]
+        fn test_message_round_trip(message in any::<Message>()) {
+            let bytes = message.to_vec().expect("failed to encode");
+            let decoded = Message::from_vec(&bytes).expect("failed to decode");
+            prop_assert_eq!(&decoded, &message);
+            prop_assert_eq!(decoded.to_vec().expect("failed to encode"), bytes);
+        }
+    }
+}
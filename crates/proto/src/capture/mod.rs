@@ -0,0 +1,285 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Capture of the exact bytes of the messages sent and received over UDP and TCP, to reproduce
+//!  protocol issues
+//!
+//! Captures are enabled for the whole process with `set_capture`, all the messages of the
+//!  `UdpClientStream` and the `DnsMultiplexer`, and so of the clients and resolvers built on them,
+//!  are then recorded. Captures are written and read in a text format, one message per line:
+//!
+//! ```text
+//! # comments and empty lines are ignored
+//! > 192.0.2.1:53 c3a90100000100000000000003777777076578616d706c6503636f6d0000010001
+//! < 192.0.2.1:53 c3a981800001000100000000037777770765...
+//! ```
+//!
+//! `>` are messages sent to the peer and `<` messages received from it, followed by the address
+//!  of the peer and the message in hex. The DNS messages of pcap files, e.g. attached to a bug
+//!  report, are read with `pcap::read_pcap`. Captured messages are replayed against the parser
+//!  with `CapturedMessage::to_message`.
+//!
+//! ```rust
+//! use trust_dns_proto::capture::{self, Capture};
+//!
+//! let capture = Capture::new();
+//! capture::set_capture(Some(capture.clone()));
+//!
+//! // ... send queries with a client or resolver
+//!
+//! capture::set_capture(None);
+//! let mut text = Vec::new();
+//! capture.write_to(&mut text).unwrap();
+//!
+//! for message in capture::read_captures(&text[..]).unwrap() {
+//!     println!("{:?}", message.to_message());
+//! }
+//! ```
+
+pub mod pcap;
+
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+use error::{ProtoError, ProtoResult};
+use op::Message;
+
+lazy_static! {
+    static ref CAPTURE: RwLock<Option<Capture>> = RwLock::new(None);
+}
+
+// avoids the lock of the capture for every message when there is none
+static CAPTURING: AtomicBool = AtomicBool::new(false);
+
+/// Whether a message was sent to or received from the peer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Sent to the peer, `>` in captures
+    Sent,
+    /// Received from the peer, `<` in captures
+    Received,
+}
+
+/// The bytes of a message sent to or received from a peer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapturedMessage {
+    direction: Direction,
+    peer: SocketAddr,
+    bytes: Vec<u8>,
+}
+
+impl CapturedMessage {
+    /// Creates a message sent to or received from the peer
+    pub fn new(direction: Direction, peer: SocketAddr, bytes: Vec<u8>) -> Self {
+        CapturedMessage {
+            direction,
+            peer,
+            bytes,
+        }
+    }
+
+    /// Whether the message was sent to or received from the peer
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// The address of the name server or client the message was exchanged with
+    pub fn peer(&self) -> SocketAddr {
+        self.peer
+    }
+
+    /// The message as it was sent or received
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Decodes the message, replaying it against the parser
+    pub fn to_message(&self) -> ProtoResult<Message> {
+        Message::from_vec(&self.bytes)
+    }
+}
+
+impl fmt::Display for CapturedMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let direction = match self.direction {
+            Direction::Sent => '>',
+            Direction::Received => '<',
+        };
+
+        write!(f, "{} {} ", direction, self.peer)?;
+        for byte in &self.bytes {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for CapturedMessage {
+    type Err = ProtoError;
+
+    fn from_str(line: &str) -> ProtoResult<Self> {
+        let mut fields = line.split_whitespace();
+
+        let direction = match fields.next() {
+            Some(">") => Direction::Sent,
+            Some("<") => Direction::Received,
+            _ => return Err(format!("expected > or < in captured message: {}", line).into()),
+        };
+        let peer = fields
+            .next()
+            .and_then(|peer| peer.parse().ok())
+            .ok_or_else(|| ProtoError::from(format!("bad peer in captured message: {}", line)))?;
+        let hex = fields
+            .next()
+            .ok_or_else(|| ProtoError::from(format!("no bytes in captured message: {}", line)))?;
+        if fields.next().is_some() || hex.len() % 2 != 0 {
+            return Err(format!("bad bytes in captured message: {}", line).into());
+        }
+
+        let bytes = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| ProtoError::from(format!("bad bytes in captured message: {}", line)))?;
+
+        Ok(CapturedMessage::new(direction, peer, bytes))
+    }
+}
+
+/// Records captured messages, clones record to the same messages
+#[derive(Clone, Debug, Default)]
+pub struct Capture {
+    messages: Arc<Mutex<Vec<CapturedMessage>>>,
+}
+
+impl Capture {
+    /// Creates an empty capture
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the message
+    pub fn record(&self, message: CapturedMessage) {
+        self.messages
+            .lock()
+            .expect("capture poisoned")
+            .push(message);
+    }
+
+    /// Returns the messages recorded so far, in the order in which they were recorded
+    pub fn messages(&self) -> Vec<CapturedMessage> {
+        self.messages.lock().expect("capture poisoned").clone()
+    }
+
+    /// Writes the messages recorded so far, one per line, see the module documentation
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        for message in self.messages.lock().expect("capture poisoned").iter() {
+            writeln!(writer, "{}", message)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads the messages of a capture written with `Capture::write_to`
+pub fn read_captures<R: BufRead>(reader: R) -> ProtoResult<Vec<CapturedMessage>> {
+    let mut messages = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        messages.push(line.parse()?);
+    }
+
+    Ok(messages)
+}
+
+/// Sets the capture in which all the messages sent and received are recorded, None stops the
+///  capture
+pub fn set_capture(capture: Option<Capture>) {
+    let mut current = CAPTURE.write().expect("capture poisoned");
+    CAPTURING.store(capture.is_some(), Ordering::SeqCst);
+    *current = capture;
+}
+
+/// Records the message in the capture, if one is set
+pub(crate) fn record(direction: Direction, peer: SocketAddr, bytes: &[u8]) {
+    if !CAPTURING.load(Ordering::Relaxed) {
+        return;
+    }
+
+    if let Some(ref capture) = *CAPTURE.read().expect("capture poisoned") {
+        capture.record(CapturedMessage::new(direction, peer, bytes.to_vec()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_captured_message_text() {
+        let message = CapturedMessage::new(
+            Direction::Sent,
+            "192.0.2.1:53".parse().unwrap(),
+            vec![0x12, 0x34, 0x01, 0x00],
+        );
+        assert_eq!(message.to_string(), "> 192.0.2.1:53 12340100");
+        assert_eq!(
+            message.to_string().parse::<CapturedMessage>().unwrap(),
+            message
+        );
+
+        let message = CapturedMessage::new(
+            Direction::Received,
+            "[2001:db8::1]:53".parse().unwrap(),
+            vec![0xab],
+        );
+        assert_eq!(message.to_string(), "< [2001:db8::1]:53 ab");
+        assert_eq!(
+            message.to_string().parse::<CapturedMessage>().unwrap(),
+            message
+        );
+    }
+
+    #[test]
+    fn test_bad_captured_message() {
+        assert!("".parse::<CapturedMessage>().is_err());
+        assert!("= 192.0.2.1:53 1234".parse::<CapturedMessage>().is_err());
+        assert!("> 192.0.2.1 1234".parse::<CapturedMessage>().is_err());
+        assert!("> 192.0.2.1:53".parse::<CapturedMessage>().is_err());
+        assert!("> 192.0.2.1:53 123".parse::<CapturedMessage>().is_err());
+        assert!("> 192.0.2.1:53 12zz".parse::<CapturedMessage>().is_err());
+        assert!("> 192.0.2.1:53 1234 56".parse::<CapturedMessage>().is_err());
+    }
+
+    #[test]
+    fn test_write_and_read_captures() {
+        let capture = Capture::new();
+        capture.record(CapturedMessage::new(
+            Direction::Sent,
+            "192.0.2.1:53".parse().unwrap(),
+            vec![1, 2],
+        ));
+        capture.record(CapturedMessage::new(
+            Direction::Received,
+            "192.0.2.1:53".parse().unwrap(),
+            vec![3, 4],
+        ));
+
+        let mut text = b"# a comment\n\n".to_vec();
+        capture.write_to(&mut text).unwrap();
+
+        assert_eq!(read_captures(&text[..]).unwrap(), capture.messages());
+    }
+}
@@ -0,0 +1,278 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Reading of the DNS messages of pcap files, e.g. written by tcpdump or Wireshark
+
+use std::io::Read;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian, NetworkEndian};
+
+use super::{CapturedMessage, Direction};
+use error::ProtoResult;
+
+const DNS_PORT: u16 = 53;
+
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+const LINKTYPE_IPV4: u32 = 228;
+const LINKTYPE_IPV6: u32 = 229;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86DD;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+
+const IP_PROTOCOL_TCP: u8 = 6;
+const IP_PROTOCOL_UDP: u8 = 17;
+
+/// Reads the DNS messages of a pcap file, exchanged with port 53
+///
+/// Messages to port 53 are `Direction::Sent` and messages from port 53 are `Direction::Received`,
+///  the peer is always the address on port 53. Ethernet, Linux cooked and raw IP captures are
+///  supported. Fragmented IP packets are skipped, as are TCP segments which don't hold exactly one
+///  length prefixed message, TCP streams are not reassembled.
+///
+/// # Errors
+///
+/// If the file isn't a pcap file, e.g. a pcapng file, or if it's truncated.
+pub fn read_pcap<R: Read>(mut reader: R) -> ProtoResult<Vec<CapturedMessage>> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+
+    if data.len() < 24 {
+        return Err("pcap file is too short for its header".into());
+    }
+
+    // microsecond and nanosecond resolution files, in the byte order of the writer
+    let big_endian = match NetworkEndian::read_u32(&data[0..4]) {
+        0xA1B2_C3D4 | 0xA1B2_3C4D => true,
+        0xD4C3_B2A1 | 0x4D3C_B2A1 => false,
+        _ => return Err("not a pcap file, pcapng files are not supported".into()),
+    };
+    let read_u32 = |bytes: &[u8]| {
+        if big_endian {
+            BigEndian::read_u32(bytes)
+        } else {
+            LittleEndian::read_u32(bytes)
+        }
+    };
+
+    // the upper bits may hold the FCS length
+    let link_type = read_u32(&data[20..24]) & 0xFFFF;
+
+    let mut messages = Vec::new();
+    let mut offset = 24;
+    while offset < data.len() {
+        if data.len() - offset < 16 {
+            return Err("pcap file is truncated in a record header".into());
+        }
+
+        let captured_len = read_u32(&data[offset + 8..offset + 12]) as usize;
+        let start = offset + 16;
+        let end = start + captured_len;
+        if end > data.len() {
+            return Err("pcap file is truncated in a record".into());
+        }
+
+        if let Some(message) = read_frame(link_type, &data[start..end]) {
+            messages.push(message);
+        }
+        offset = end;
+    }
+
+    Ok(messages)
+}
+
+fn read_frame(link_type: u32, frame: &[u8]) -> Option<CapturedMessage> {
+    let packet = match link_type {
+        LINKTYPE_ETHERNET => {
+            let mut ether_type = read_u16(frame, 12)?;
+            let mut header_len = 14;
+            if ether_type == ETHERTYPE_VLAN {
+                ether_type = read_u16(frame, 16)?;
+                header_len = 18;
+            }
+
+            if ether_type != ETHERTYPE_IPV4 && ether_type != ETHERTYPE_IPV6 {
+                return None;
+            }
+            frame.get(header_len..)?
+        }
+        LINKTYPE_LINUX_SLL => frame.get(16..)?,
+        LINKTYPE_RAW | LINKTYPE_IPV4 | LINKTYPE_IPV6 => frame,
+        _ => return None,
+    };
+
+    read_ip_packet(packet)
+}
+
+fn read_ip_packet(packet: &[u8]) -> Option<CapturedMessage> {
+    let (src, dst, protocol, payload): (IpAddr, IpAddr, u8, &[u8]) = match packet.first()? >> 4 {
+        4 => {
+            if packet.len() < 20 {
+                return None;
+            }
+
+            let header_len = ((packet[0] & 0x0F) as usize) * 4;
+            let total_len = read_u16(packet, 2)? as usize;
+
+            // fragments can't be decoded on their own
+            if read_u16(packet, 6)? & 0x3FFF != 0 {
+                return None;
+            }
+
+            let src = Ipv4Addr::new(packet[12], packet[13], packet[14], packet[15]);
+            let dst = Ipv4Addr::new(packet[16], packet[17], packet[18], packet[19]);
+            (
+                src.into(),
+                dst.into(),
+                packet[9],
+                packet.get(header_len..total_len.min(packet.len()))?,
+            )
+        }
+        6 => {
+            let payload_len = read_u16(packet, 4)? as usize;
+            let src = read_ipv6(packet.get(8..24)?);
+            let dst = read_ipv6(packet.get(24..40)?);
+
+            // extension headers are not supported, the next header must be UDP or TCP
+            (
+                src.into(),
+                dst.into(),
+                packet[6],
+                packet.get(40..(40 + payload_len).min(packet.len()))?,
+            )
+        }
+        _ => return None,
+    };
+
+    let src_port = read_u16(payload, 0)?;
+    let dst_port = read_u16(payload, 2)?;
+    let bytes = match protocol {
+        IP_PROTOCOL_UDP => {
+            let udp_len = read_u16(payload, 4)? as usize;
+            payload.get(8..udp_len.min(payload.len()))?
+        }
+        IP_PROTOCOL_TCP => {
+            let header_len = ((payload.get(12)? >> 4) as usize) * 4;
+            let segment = payload.get(header_len..)?;
+            let message_len = read_u16(segment, 0)? as usize;
+            if message_len != segment.len() - 2 {
+                return None;
+            }
+            &segment[2..]
+        }
+        _ => return None,
+    };
+
+    let (direction, peer) = if dst_port == DNS_PORT {
+        (Direction::Sent, SocketAddr::new(dst, dst_port))
+    } else if src_port == DNS_PORT {
+        (Direction::Received, SocketAddr::new(src, src_port))
+    } else {
+        return None;
+    };
+
+    Some(CapturedMessage::new(direction, peer, bytes.to_vec()))
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    bytes.get(offset..offset + 2).map(NetworkEndian::read_u16)
+}
+
+fn read_ipv6(bytes: &[u8]) -> Ipv6Addr {
+    let mut octets = [0u8; 16];
+    octets.copy_from_slice(bytes);
+    Ipv6Addr::from(octets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pcap(link_type: u32, frames: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = vec![0xD4, 0xC3, 0xB2, 0xA1, 2, 0, 4, 0];
+        data.extend_from_slice(&[0; 8]);
+        data.extend_from_slice(&[0xFF, 0xFF, 0, 0]);
+        data.extend_from_slice(&[link_type as u8, 0, 0, 0]);
+
+        for frame in frames {
+            let mut len = [0u8; 4];
+            LittleEndian::write_u32(&mut len, frame.len() as u32);
+            data.extend_from_slice(&[0; 8]);
+            data.extend_from_slice(&len);
+            data.extend_from_slice(&len);
+            data.extend_from_slice(frame);
+        }
+        data
+    }
+
+    fn ipv4_udp(src: [u8; 4], dst: [u8; 4], src_port: u16, dst_port: u16, bytes: &[u8]) -> Vec<u8> {
+        let total_len = 20 + 8 + bytes.len();
+        let mut packet = vec![0x45, 0];
+        packet.extend_from_slice(&(total_len as u16).to_be_bytes());
+        packet.extend_from_slice(&[0, 0, 0x40, 0, 64, IP_PROTOCOL_UDP, 0, 0]);
+        packet.extend_from_slice(&src);
+        packet.extend_from_slice(&dst);
+        packet.extend_from_slice(&src_port.to_be_bytes());
+        packet.extend_from_slice(&dst_port.to_be_bytes());
+        packet.extend_from_slice(&((8 + bytes.len()) as u16).to_be_bytes());
+        packet.extend_from_slice(&[0, 0]);
+        packet.extend_from_slice(bytes);
+        packet
+    }
+
+    fn ethernet(packet: Vec<u8>) -> Vec<u8> {
+        let mut frame = vec![0; 12];
+        frame.extend_from_slice(&ETHERTYPE_IPV4.to_be_bytes());
+        frame.extend(packet);
+        frame
+    }
+
+    #[test]
+    fn test_read_pcap() {
+        let query = ipv4_udp([192, 0, 2, 2], [192, 0, 2, 1], 40000, 53, &[1, 2, 3]);
+        let response = ipv4_udp([192, 0, 2, 1], [192, 0, 2, 2], 53, 40000, &[4, 5]);
+        let other = ipv4_udp([192, 0, 2, 1], [192, 0, 2, 2], 123, 40000, &[6]);
+
+        let data = pcap(
+            LINKTYPE_ETHERNET,
+            &[ethernet(query), ethernet(response), ethernet(other)],
+        );
+        let messages = read_pcap(&data[..]).unwrap();
+
+        let peer = "192.0.2.1:53".parse().unwrap();
+        assert_eq!(
+            messages,
+            vec![
+                CapturedMessage::new(Direction::Sent, peer, vec![1, 2, 3]),
+                CapturedMessage::new(Direction::Received, peer, vec![4, 5]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_raw_pcap() {
+        let query = ipv4_udp([192, 0, 2, 2], [192, 0, 2, 1], 40000, 53, &[1, 2, 3]);
+        let data = pcap(LINKTYPE_RAW, &[query]);
+
+        let messages = read_pcap(&data[..]).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].bytes(), &[1, 2, 3]);
+    }
+
+    #[test]
+    fn test_bad_pcap() {
+        assert!(read_pcap(&[0u8; 10][..]).is_err());
+        assert!(read_pcap(&[0u8; 24][..]).is_err());
+
+        let query = ipv4_udp([192, 0, 2, 2], [192, 0, 2, 1], 40000, 53, &[1, 2, 3]);
+        let data = pcap(LINKTYPE_RAW, &[query]);
+        assert!(read_pcap(&data[..data.len() - 1]).is_err());
+    }
+}
@@ -9,7 +9,14 @@
 
 #![deny(missing_docs)]
 
-use std::{fmt, io, sync};
+use std::error::Error;
+use std::net::SocketAddr;
+use std::fmt;
+use std::sync::Arc;
+#[cfg(feature = "std")]
+use std::{io, sync};
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
 
 use rr::{Name, RecordType};
 
@@ -22,8 +29,10 @@ use openssl::error::ErrorStack as SslErrorStack;
 #[cfg(feature = "ring")]
 use ring::error::Unspecified;
 
-use failure::{Backtrace, Context, Fail};
+use failure::Fail;
+#[cfg(feature = "std")]
 use tokio_executor::SpawnError;
+#[cfg(feature = "std")]
 use tokio_timer::Error as TimerError;
 
 /// An alias for results returned by functions of this crate
@@ -32,7 +41,12 @@ pub type ProtoResult<T> = ::std::result::Result<T, ProtoError>;
 /// The error kind for errors that get returned in the crate
 #[derive(Eq, PartialEq, Debug, Fail)]
 pub enum ProtoErrorKind {
+    /// The request was not sent, the maximum number of requests are in flight on the connection
+    #[fail(display = "too many requests in flight: {}", _0)]
+    Busy(usize),
+
     /// An error caused by a canceled future
+    #[cfg(feature = "std")]
     #[fail(display = "future was canceled: {:?}", _0)]
     Canceled(::futures::sync::oneshot::Canceled),
 
@@ -154,6 +168,23 @@ pub enum ProtoErrorKind {
     #[fail(display = "nsec3 flags should be 0b0000000*: {:b}", _0)]
     UnrecognizedNsec3Flags(u8),
 
+    /// The input ended before all of the data could be read
+    #[fail(
+        display = "unexpected end of input, needed: {} remaining: {}",
+        needed, remaining
+    )]
+    UnexpectedEnd {
+        /// Number of bytes which were needed
+        needed: usize,
+        /// Number of bytes which were left in the input
+        remaining: usize,
+    },
+
+    /// The name server is unreachable, the OS reported an error on the socket of the request,
+    ///  e.g. for an ICMP port or host unreachable, see the source of the error
+    #[fail(display = "name server unreachable: {}", _0)]
+    Unreachable(SocketAddr),
+
     // foreign
     /// An error got returned from IO
     #[fail(display = "io error")]
@@ -192,54 +223,149 @@ pub enum ProtoErrorKind {
     Utf8,
 }
 
+impl ProtoErrorKind {
+    /// Returns the stable class of this error, see `ProtoErrorCode`
+    pub fn code(&self) -> ProtoErrorCode {
+        use self::ProtoErrorKind::*;
+        match *self {
+            CharacterDataTooLong { .. }
+            | LabelOverlapsWithOther { .. }
+            | DnsKeyProtocolNot3(_)
+            | DomainNameTooLong(_)
+            | EdnsNameNotRoot(_)
+            | IncorrectRDataLengthRead { .. }
+            | LabelBytesTooLong(_)
+            | PointerNotPriorToLabel { .. }
+            | UnknownDnsClassStr(_)
+            | UnknownDnsClassValue(_)
+            | UnknownRecordTypeStr(_)
+            | UnknownRecordTypeValue(_)
+            | UnrecognizedLabelCode(_)
+            | UnrecognizedNsec3Flags(_)
+            | UrlParsing
+            | Utf8 => ProtoErrorCode::FormErr,
+            UnexpectedEnd { .. } => ProtoErrorCode::Truncated,
+            UnknownAlgorithmTypeValue(_) => ProtoErrorCode::UnsupportedAlgorithm,
+            RrsigsNotPresent { .. } => ProtoErrorCode::Insecure,
+            Timeout => ProtoErrorCode::Timeout,
+            Unreachable(_) => ProtoErrorCode::Unreachable,
+            Busy(_) => ProtoErrorCode::Busy,
+            #[cfg(feature = "std")]
+            Canceled(_) => ProtoErrorCode::Canceled,
+            Io => ProtoErrorCode::Io,
+            Ring | SSL => ProtoErrorCode::Crypto,
+            MaxBufferSizeExceeded(_) | NotAllRecordsWritten { .. } => ProtoErrorCode::BufferFull,
+            Message(_) | Msg(_) | NoError | Poisoned | SpawnError | Timer => ProtoErrorCode::Other,
+        }
+    }
+}
+
+/// The stable classes of errors, for matching on the cause of an error without depending on
+///  the details of `ProtoErrorKind`
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ProtoErrorCode {
+    /// The data is malformed, e.g. a message or record which failed to decode
+    FormErr,
+    /// The data ended before all of it could be read, e.g. a truncated message
+    Truncated,
+    /// An algorithm, e.g. of a DNSSEC key or digest, is unknown or not supported
+    UnsupportedAlgorithm,
+    /// Records could not be validated with DNSSEC
+    Insecure,
+    /// A request timed out
+    Timeout,
+    /// The name server of a request is unreachable
+    Unreachable,
+    /// The request was not sent, too many requests are in flight
+    Busy,
+    /// A request was canceled before it completed
+    Canceled,
+    /// An IO error, see the source of the error
+    Io,
+    /// An error of the cryptographic library, see the source of the error
+    Crypto,
+    /// The data doesn't fit in the buffer, or the maximum size, of the encoder
+    BufferFull,
+    /// Any other error
+    Other,
+}
+
 /// The error type for errors that get returned in the crate
-#[derive(Debug)]
+///
+/// The cause of the error is `kind`, or the coarser `code`. Errors decoding data have the
+///  `offset` in the data at which the error was found, and errors of other libraries, e.g. IO,
+///  are the `source` of the error.
+#[derive(Clone, Debug)]
 pub struct ProtoError {
-    inner: Context<ProtoErrorKind>,
+    kind: ProtoErrorKind,
+    offset: Option<usize>,
+    source: Option<Arc<Error + Send + Sync>>,
 }
 
 impl ProtoError {
     /// Get the kind of the error
     pub fn kind(&self) -> &ProtoErrorKind {
-        self.inner.get_context()
+        &self.kind
     }
-}
 
-impl Clone for ProtoError {
-    fn clone(&self) -> Self {
-        ProtoError {
-            inner: Context::new(self.inner.get_context().clone()),
+    /// Returns the stable class of the error
+    ///
+    /// Errors with a message, `ProtoErrorKind::Message` or `Msg`, are `FormErr` if they have an
+    ///  offset, i.e. they were found while decoding data.
+    pub fn code(&self) -> ProtoErrorCode {
+        match self.kind {
+            ProtoErrorKind::Message(_) | ProtoErrorKind::Msg(_) if self.offset.is_some() => {
+                ProtoErrorCode::FormErr
+            }
+            ref kind => kind.code(),
         }
     }
-}
 
-impl Fail for ProtoError {
-    fn cause(&self) -> Option<&Fail> {
-        self.inner.cause()
+    /// The offset of the byte at which an error decoding data was found
+    pub fn offset(&self) -> Option<usize> {
+        self.offset
+    }
+
+    /// Sets the error of another library which caused this error, see `Error::source`
+    pub fn with_source<E: Error + Send + Sync + 'static>(mut self, source: E) -> Self {
+        self.source = Some(Arc::new(source));
+        self
     }
 
-    fn backtrace(&self) -> Option<&Backtrace> {
-        self.inner.backtrace()
+    /// Sets the offset of the byte at which the error was found, unless it is already set by a
+    ///  more precise decoder
+    pub(crate) fn at_offset(mut self, offset: usize) -> Self {
+        self.offset = self.offset.or(Some(offset));
+        self
     }
 }
 
 impl fmt::Display for ProtoError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        fmt::Display::fmt(&self.inner, f)
+        fmt::Display::fmt(&self.kind, f)?;
+        if let Some(offset) = self.offset {
+            write!(f, ", at offset: {}", offset)?;
+        }
+        Ok(())
     }
 }
 
-impl From<ProtoErrorKind> for ProtoError {
-    fn from(kind: ProtoErrorKind) -> ProtoError {
-        ProtoError {
-            inner: Context::new(kind),
+impl Error for ProtoError {
+    fn source(&self) -> Option<&(Error + 'static)> {
+        match self.source {
+            Some(ref source) => Some(&**source),
+            None => None,
         }
     }
 }
 
-impl From<Context<ProtoErrorKind>> for ProtoError {
-    fn from(inner: Context<ProtoErrorKind>) -> ProtoError {
-        ProtoError { inner }
+impl From<ProtoErrorKind> for ProtoError {
+    fn from(kind: ProtoErrorKind) -> ProtoError {
+        ProtoError {
+            kind,
+            offset: None,
+            source: None,
+        }
     }
 }
 
@@ -255,45 +381,50 @@ impl From<String> for ProtoError {
     }
 }
 
+#[cfg(feature = "std")]
 impl From<io::Error> for ProtoError {
     fn from(e: io::Error) -> ProtoError {
         match e.kind() {
-            io::ErrorKind::TimedOut => e.context(ProtoErrorKind::Timeout).into(),
-            _ => e.context(ProtoErrorKind::Io).into(),
+            io::ErrorKind::TimedOut => ProtoError::from(ProtoErrorKind::Timeout).with_source(e),
+            _ => ProtoError::from(ProtoErrorKind::Io).with_source(e),
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl<T> From<sync::PoisonError<T>> for ProtoError {
     fn from(_e: sync::PoisonError<T>) -> ProtoError {
-        Context::new(ProtoErrorKind::Poisoned).into()
+        ProtoErrorKind::Poisoned.into()
     }
 }
 
 impl From<Unspecified> for ProtoError {
     fn from(e: Unspecified) -> ProtoError {
-        e.context(ProtoErrorKind::Ring).into()
+        ProtoError::from(ProtoErrorKind::Ring).with_source(e)
     }
 }
 
+#[cfg(feature = "std")]
 impl From<SpawnError> for ProtoError {
     fn from(e: SpawnError) -> ProtoError {
-        e.context(ProtoErrorKind::SpawnError).into()
+        ProtoError::from(ProtoErrorKind::SpawnError).with_source(e)
     }
 }
 
 impl From<SslErrorStack> for ProtoError {
     fn from(e: SslErrorStack) -> ProtoError {
-        e.context(ProtoErrorKind::SSL).into()
+        ProtoError::from(ProtoErrorKind::SSL).with_source(e)
     }
 }
 
+#[cfg(feature = "std")]
 impl From<TimerError> for ProtoError {
     fn from(e: TimerError) -> ProtoError {
-        e.context(ProtoErrorKind::Timer).into()
+        ProtoError::from(ProtoErrorKind::Timer).with_source(e)
     }
 }
 
+#[cfg(feature = "std")]
 impl From<tokio_timer::timeout::Error<ProtoError>> for ProtoError {
     fn from(e: tokio_timer::timeout::Error<ProtoError>) -> Self {
         if e.is_elapsed() {
@@ -315,15 +446,16 @@ impl From<tokio_timer::timeout::Error<ProtoError>> for ProtoError {
     }
 }
 
+#[cfg(feature = "std")]
 impl From<::url::ParseError> for ProtoError {
     fn from(e: ::url::ParseError) -> ProtoError {
-        e.context(ProtoErrorKind::UrlParsing).into()
+        ProtoError::from(ProtoErrorKind::UrlParsing).with_source(e)
     }
 }
 
 impl From<::std::str::Utf8Error> for ProtoError {
     fn from(e: ::std::str::Utf8Error) -> ProtoError {
-        e.context(ProtoErrorKind::Utf8).into()
+        ProtoError::from(ProtoErrorKind::Utf8).with_source(e)
     }
 }
 
@@ -371,11 +503,13 @@ pub mod not_ring {
     }
 }
 
+#[cfg(feature = "std")]
 impl From<ProtoError> for io::Error {
     fn from(e: ProtoError) -> Self {
         match *e.kind() {
-            ProtoErrorKind::Timeout => io::Error::new(io::ErrorKind::TimedOut, e.compat()),
-            _ => io::Error::new(io::ErrorKind::Other, e.compat()),
+            ProtoErrorKind::Timeout => io::Error::new(io::ErrorKind::TimedOut, e),
+            ProtoErrorKind::Unreachable(_) => io::Error::new(io::ErrorKind::ConnectionRefused, e),
+            _ => io::Error::new(io::ErrorKind::Other, e),
         }
     }
 }
@@ -390,6 +524,8 @@ impl Clone for ProtoErrorKind {
     fn clone(&self) -> Self {
         use self::ProtoErrorKind::*;
         match *self {
+            Busy(max) => Busy(max),
+            #[cfg(feature = "std")]
             Canceled(ref c) => Canceled(*c),
             CharacterDataTooLong { max, len } => CharacterDataTooLong { max, len },
             LabelOverlapsWithOther { label, other } => LabelOverlapsWithOther { label, other },
@@ -418,6 +554,8 @@ impl Clone for ProtoErrorKind {
             UnknownRecordTypeValue(value) => UnknownRecordTypeValue(value),
             UnrecognizedLabelCode(value) => UnrecognizedLabelCode(value),
             UnrecognizedNsec3Flags(flags) => UnrecognizedNsec3Flags(flags),
+            UnexpectedEnd { needed, remaining } => UnexpectedEnd { needed, remaining },
+            Unreachable(name_server) => Unreachable(name_server),
 
             // foreign
             Io => Io,
@@ -439,3 +577,72 @@ impl Clone for ProtoErrorKind {
 pub trait FromProtoError: From<ProtoError> + Fail + Clone {}
 
 impl<E> FromProtoError for E where E: From<ProtoError> + Fail + Clone {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use op::Message;
+
+    #[test]
+    fn test_truncated_message() {
+        // a header, and the first label of the query
+        let bytes = [0, 1, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0, 3, b'w', b'w'];
+        let error = Message::from_vec(&bytes).unwrap_err();
+
+        assert_eq!(error.code(), ProtoErrorCode::Truncated);
+        assert_eq!(
+            *error.kind(),
+            ProtoErrorKind::UnexpectedEnd {
+                needed: 3,
+                remaining: 2,
+            }
+        );
+        assert_eq!(error.offset(), Some(13));
+    }
+
+    #[test]
+    fn test_malformed_message() {
+        // an A record with 3 bytes of rdata
+        let bytes = [
+            0, 1, 0x81, 0x80, 0, 0, 0, 1, 0, 0, 0, 0, // header
+            0, 0, 1, 0, 1, 0, 0, 0, 60, 0, 3, 1, 2, 3, 4, // record
+        ];
+        let error = Message::from_vec(&bytes).unwrap_err();
+
+        assert_eq!(error.code(), ProtoErrorCode::FormErr);
+        assert_eq!(error.offset(), Some(23));
+        assert!(error.to_string().ends_with(", at offset: 23"));
+    }
+
+    #[test]
+    fn test_message_code() {
+        let error = ProtoError::from("not decoding");
+        assert_eq!(error.code(), ProtoErrorCode::Other);
+        assert_eq!(error.at_offset(1).code(), ProtoErrorCode::FormErr);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_source() {
+        let error = ProtoError::from(io::Error::new(io::ErrorKind::Other, "connection reset"));
+        assert_eq!(error.code(), ProtoErrorCode::Io);
+        assert_eq!(error.source().unwrap().to_string(), "connection reset");
+
+        // the source is shared by clones
+        let error = error.clone();
+        assert_eq!(error.source().unwrap().to_string(), "connection reset");
+
+        let error = ProtoError::from(io::Error::new(io::ErrorKind::TimedOut, "timed out"));
+        assert_eq!(error.code(), ProtoErrorCode::Timeout);
+        assert_eq!(io::Error::from(error).kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    #[cfg(feature = "dnssec")]
+    fn test_unsupported_algorithm() {
+        use rr::dnssec::Algorithm;
+
+        let error = Algorithm::from_u8(200).unwrap_err();
+        assert_eq!(error.code(), ProtoErrorCode::UnsupportedAlgorithm);
+    }
+}
@@ -0,0 +1,220 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::fmt::{self, Display};
+use std::sync::Arc;
+
+use futures::sync::oneshot;
+use futures::{future, Async, Future, Poll, Stream};
+use js_sys::{Promise, Uint8Array};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+use web_sys::{Request, RequestInit, Response};
+
+use error::{ProtoError, ProtoErrorKind};
+use op::Message;
+use xfer::{DnsRequest, DnsRequestSender, DnsResponse};
+
+const MIME_APPLICATION_DNS: &str = "application/dns-message";
+
+#[wasm_bindgen]
+extern "C" {
+    // the fetch of the global scope, of a window as well as of a worker
+    #[wasm_bindgen(js_name = fetch)]
+    fn fetch_with_request(input: &Request) -> Promise;
+}
+
+/// A DNS client for DNS-over-HTTPS, sending the requests with the fetch API of the host
+///
+/// The requests are `POST`ed to the endpoint as `application/dns-message`. The fetch is spawned
+///  on the JavaScript event loop, and its response sent back to the `FetchResponse`, which is
+///  `Send` as the `DnsRequestSender` requires.
+#[derive(Clone)]
+#[must_use = "futures do nothing unless polled"]
+pub struct FetchClientStream {
+    url: Arc<String>,
+    is_shutdown: bool,
+}
+
+impl FetchClientStream {
+    /// Constructs a stream sending the requests to a DNS-over-HTTPS endpoint
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - the URL of the endpoint, e.g. `https://cloudflare-dns.com/dns-query`
+    pub fn new(url: String) -> Self {
+        FetchClientStream {
+            url: Arc::new(url),
+            is_shutdown: false,
+        }
+    }
+}
+
+impl Display for FetchClientStream {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(formatter, "FETCH({})", self.url)
+    }
+}
+
+impl DnsRequestSender for FetchClientStream {
+    type DnsResponseFuture = FetchResponse;
+
+    fn send_message(&mut self, mut message: DnsRequest) -> Self::DnsResponseFuture {
+        if self.is_shutdown {
+            panic!("can not send messages after stream is shutdown")
+        }
+
+        // per the RFC, a zero id allows for the HTTP packet to be cached better, raw requests are
+        //  sent as they are
+        let bytes = if message.is_raw() {
+            message.to_raw_vec()
+        } else {
+            message.set_id(0);
+            message.to_vec()
+        };
+        let bytes = match bytes {
+            Ok(bytes) => bytes,
+            Err(err) => return Self::error_response(err),
+        };
+
+        // the fetch is driven to completion by the event loop, its promise isn't needed
+        let (complete, receiver) = oneshot::channel();
+        let _ = future_to_promise(fetch(&self.url, &bytes).then(move |response| {
+            if complete.send(response).is_err() {
+                debug!("the response of the fetch is no longer awaited");
+            }
+            Ok(JsValue::UNDEFINED)
+        }));
+
+        FetchResponse(FetchResponseInner::Receiving(receiver))
+    }
+
+    fn error_response(error: ProtoError) -> Self::DnsResponseFuture {
+        FetchResponse(FetchResponseInner::Errored(Some(error)))
+    }
+
+    fn shutdown(&mut self) {
+        self.is_shutdown = true;
+    }
+
+    fn is_shutdown(&self) -> bool {
+        self.is_shutdown
+    }
+}
+
+impl Stream for FetchClientStream {
+    type Item = ();
+    type Error = ProtoError;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        if self.is_shutdown {
+            return Ok(Async::Ready(None));
+        }
+
+        // the host manages the connections, a request can always be sent
+        Ok(Async::Ready(Some(())))
+    }
+}
+
+/// A future that will resolve to the DnsResponse of a fetch
+#[must_use = "futures do nothing unless polled"]
+pub struct FetchResponse(FetchResponseInner);
+
+enum FetchResponseInner {
+    Receiving(oneshot::Receiver<Result<DnsResponse, ProtoError>>),
+    Errored(Option<ProtoError>),
+}
+
+impl Future for FetchResponse {
+    type Item = DnsResponse;
+    type Error = ProtoError;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        match self.0 {
+            FetchResponseInner::Receiving(ref mut receiver) => match receiver.poll() {
+                Ok(Async::Ready(response)) => response.map(Async::Ready),
+                Ok(Async::NotReady) => Ok(Async::NotReady),
+                Err(canceled) => Err(ProtoErrorKind::Canceled(canceled).into()),
+            },
+            FetchResponseInner::Errored(ref mut error) => {
+                Err(error.take().expect("cannot poll after complete"))
+            }
+        }
+    }
+}
+
+/// Fetches the response of the request message in `bytes` from the endpoint at `url`
+fn fetch(url: &str, bytes: &[u8]) -> Box<dyn Future<Item = DnsResponse, Error = ProtoError>> {
+    let request = match new_request(url, bytes) {
+        Ok(request) => request,
+        Err(err) => return Box::new(future::err(js_error("invalid request", &err))),
+    };
+
+    Box::new(
+        JsFuture::from(fetch_with_request(&request))
+            .map_err(|err| js_error("fetch failed", &err))
+            .and_then(response_body)
+            .and_then(|body| {
+                JsFuture::from(body).map_err(|err| js_error("reading the body failed", &err))
+            })
+            .and_then(|body| {
+                Message::from_vec(&Uint8Array::new(&body).to_vec()).map(DnsResponse::from)
+            }),
+    )
+}
+
+fn new_request(url: &str, bytes: &[u8]) -> Result<Request, JsValue> {
+    let init = RequestInit::new();
+    init.set_method("POST");
+    init.set_body(&Uint8Array::from(bytes));
+
+    let request = Request::new_with_str_and_init(url, &init)?;
+    let headers = request.headers();
+    headers.set("Accept", MIME_APPLICATION_DNS)?;
+    headers.set("Content-Type", MIME_APPLICATION_DNS)?;
+
+    Ok(request)
+}
+
+/// Verifies the status and content type of the response, returning the promise of its body
+fn response_body(response: JsValue) -> Result<Promise, ProtoError> {
+    let response: Response = response
+        .dyn_into()
+        .map_err(|err| js_error("fetch resolved to no Response", &err))?;
+
+    if !response.ok() {
+        // TODO: make explicit error type
+        return Err(ProtoError::from(format!(
+            "http unsuccessful code: {}, message: {}",
+            response.status(),
+            response.status_text()
+        )));
+    }
+
+    // in the case that the ContentType is not specified, we assume it's the standard DNS format
+    let content_type = response
+        .headers()
+        .get("Content-Type")
+        .map_err(|err| js_error("ContentType header not a string", &err))?;
+    if let Some(content_type) = content_type {
+        if content_type != MIME_APPLICATION_DNS {
+            return Err(ProtoError::from(format!(
+                "ContentType unsupported (must be '{}'): '{}'",
+                MIME_APPLICATION_DNS, content_type
+            )));
+        }
+    }
+
+    response
+        .array_buffer()
+        .map_err(|err| js_error("reading the body failed", &err))
+}
+
+fn js_error(context: &str, err: &JsValue) -> ProtoError {
+    ProtoError::from(format!("{}: {:?}", context, err))
+}
@@ -0,0 +1,63 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! DNS-over-HTTPS with the fetch API, for `wasm32-unknown-unknown` targets
+//!
+//! The requests are sent by the `fetch` of the JavaScript global scope, the window of a browser
+//!  or the global scope of a worker, e.g. of Cloudflare Workers. TLS and HTTP are left to the
+//!  host, only the endpoint of the server is configured.
+//!
+//! `FetchClientStream` is a `DnsRequestSender`, wrapped by a `DnsExchange` it gives a `DnsHandle`
+//!  for typed lookups. The exchange must be spawned on the JavaScript event loop, e.g. with
+//!  `wasm_bindgen_futures::future_to_promise`. The `DnsMultiplexer` and the backoff of the
+//!  `RetryDnsHandle` read the clock of `tokio-timer`, which isn't available on these targets, and
+//!  are not used with this stream.
+//!
+//! The `trust-dns-resolver` does not build for these targets, its sockets and timers are those of
+//!  Tokio, and it has no `ConnectionProvider` over this stream.
+//!
+//! ```no_run
+//! # extern crate futures;
+//! # extern crate trust_dns_proto;
+//! # extern crate wasm_bindgen;
+//! # extern crate wasm_bindgen_futures;
+//! use futures::Future;
+//! use trust_dns_proto::fetch::FetchClientStream;
+//! use trust_dns_proto::op::Query;
+//! use trust_dns_proto::rr::{Name, RecordType};
+//! use trust_dns_proto::xfer::{BufDnsRequestStreamHandle, DnsExchange, DnsHandle};
+//! use wasm_bindgen::JsValue;
+//! use wasm_bindgen_futures::future_to_promise;
+//!
+//! # fn main() {
+//! let stream = FetchClientStream::new("https://cloudflare-dns.com/dns-query".to_string());
+//! let (exchange, handle) = DnsExchange::from_stream(stream);
+//! future_to_promise(
+//!     exchange
+//!         .map(|()| JsValue::UNDEFINED)
+//!         .map_err(|e| JsValue::from_str(&e.to_string())),
+//! );
+//!
+//! let mut handle = BufDnsRequestStreamHandle::new(handle);
+//! let query = Query::query(Name::from_ascii("www.example.com.").unwrap(), RecordType::A);
+//! future_to_promise(
+//!     handle
+//!         .lookup(query, Default::default())
+//!         .map(|response| {
+//!             for answer in response.answers() {
+//!                 println!("{:?}", answer.rdata());
+//!             }
+//!             JsValue::UNDEFINED
+//!         })
+//!         .map_err(|e| JsValue::from_str(&e.to_string())),
+//! );
+//! # }
+//! ```
+
+mod fetch_client_stream;
+
+pub use self::fetch_client_stream::{FetchClientStream, FetchResponse};
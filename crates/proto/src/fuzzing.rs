@@ -0,0 +1,127 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The fuzz targets of `fuzz/`, also run over their corpora by the fuzz regression tests
+//!
+//! Malformed inputs must fail to decode rather than panic, and what decodes must encode back to
+//!  the same value. Each function panics on an input which finds a bug.
+
+use std::str;
+
+use op::Message;
+use rr::Name;
+use serialize::binary::{BinDecodable, BinDecoder, BinEncodable, BinEncoder};
+
+/// Decodes the input as a `Message`
+pub fn message(data: &[u8]) {
+    if let Ok(message) = Message::from_vec(data) {
+        let encoded = message.to_vec().expect("failed to encode");
+        let decoded = Message::from_vec(&encoded).expect("failed to decode");
+        assert_eq!(decoded, message);
+    }
+}
+
+/// Decodes the input as a `Name` in the wire format, and parses it as a name in the text format
+pub fn name(data: &[u8]) {
+    let mut decoder = BinDecoder::new(data);
+    if let Ok(name) = Name::read(&mut decoder) {
+        let mut encoded = Vec::new();
+        {
+            let mut encoder = BinEncoder::new(&mut encoded);
+            name.emit(&mut encoder).expect("failed to encode");
+        }
+        let decoded = Name::from_bytes(&encoded).expect("failed to decode");
+        assert_eq!(decoded, name);
+
+        // TODO: not all the labels which decode parse back from the text format, e.g. with
+        //  control characters, they are only checked to not panic
+        let _ = name.to_ascii();
+    }
+
+    if let Ok(text) = str::from_utf8(data) {
+        if let Ok(name) = Name::from_ascii(text) {
+            let parsed = Name::from_ascii(name.to_ascii()).expect("failed to parse");
+            assert_eq!(parsed, name);
+        }
+
+        // IDNA labels are only checked to not panic, not all of them display as they were parsed
+        if let Ok(name) = Name::from_utf8(text) {
+            let _ = name.to_utf8();
+        }
+    }
+}
+
+/// Decodes the input as a `Message`, and verifies its RRSIGs with the DNSKEYs of the message
+#[cfg(feature = "dnssec")]
+pub fn dnssec_verify(data: &[u8]) {
+    use rr::dnssec::rdata::{DNSSECRData, DNSSECRecordType};
+    use rr::dnssec::Verifier;
+    use rr::{RData, Record, RecordType};
+
+    let message = match Message::from_vec(data) {
+        Ok(message) => message,
+        Err(_) => return,
+    };
+
+    let records: Vec<&Record> = message
+        .answers()
+        .iter()
+        .chain(message.name_servers())
+        .chain(message.additionals())
+        .collect();
+
+    let keys = records.iter().filter_map(|record| match *record.rdata() {
+        RData::DNSSEC(DNSSECRData::DNSKEY(ref key)) => Some(key),
+        _ => None,
+    });
+
+    for key in keys {
+        // keys which can't be encoded have no tag
+        let key_tag = match key.calculate_key_tag() {
+            Ok(key_tag) => key_tag,
+            Err(_) => continue,
+        };
+
+        for rrsig in records
+            .iter()
+            .filter(|record| record.rr_type() == RecordType::DNSSEC(DNSSECRecordType::RRSIG))
+        {
+            let sig = match *rrsig.rdata() {
+                RData::DNSSEC(DNSSECRData::SIG(ref sig)) if sig.key_tag() == key_tag => sig,
+                _ => continue,
+            };
+
+            let covered: Vec<Record> = records
+                .iter()
+                .filter(|record| {
+                    record.name() == rrsig.name()
+                        && record.dns_class() == rrsig.dns_class()
+                        && record.rr_type() == sig.type_covered()
+                })
+                .map(|record| (*record).clone())
+                .collect();
+
+            // forged signatures fail to verify, only panics are bugs
+            let _ = key.verify_rrsig(rrsig.name(), rrsig.dns_class(), sig, &covered);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name() {
+        name(b"\x03www\x07example\x03com\x00");
+        name(b"www.example.com.");
+        name("ü.example.com.".as_bytes());
+        name(b"a\\.b\\032c.example.");
+        name(b"\xc0\x00");
+        name(b"");
+    }
+}
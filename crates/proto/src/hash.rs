@@ -0,0 +1,146 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Hashers for the maps of names
+//!
+//! `FxHasher` is fast but not keyed, peers which choose the keys of a map can make them collide
+//!  and its operations linear. It's for maps whose keys are configured, e.g. the zones of a
+//!  server, which the names of requests are only looked up in. The maps with the names of
+//!  requests as keys, e.g. the caches of answers, keep the keyed SipHash of
+//!  `std::collections::hash_map::RandomState`.
+//!
+//! `NameHasher` is injected where the bytes of names are hashed, see
+//!  `BinEncoder::set_name_hasher` and `AsyncResolver::with_cache_hasher` of the resolver.
+
+use std::hash::{BuildHasher, Hasher};
+
+use byteorder::{ByteOrder, LittleEndian};
+
+/// Hashes the bytes of names, implemented by every `BuildHasher`
+pub trait NameHasher: Send + Sync {
+    /// Returns the hash of the bytes, equal bytes have equal hashes
+    fn hash_bytes(&self, bytes: &[u8]) -> u64;
+}
+
+impl<S: BuildHasher + Send + Sync> NameHasher for S {
+    fn hash_bytes(&self, bytes: &[u8]) -> u64 {
+        let mut hasher = self.build_hasher();
+        hasher.write(bytes);
+        hasher.finish()
+    }
+}
+
+/// Builds `FxHasher`s, for `HashMap::with_hasher` and `HashSet::with_hasher`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FxBuildHasher;
+
+impl BuildHasher for FxBuildHasher {
+    type Hasher = FxHasher;
+
+    fn build_hasher(&self) -> FxHasher {
+        FxHasher::default()
+    }
+}
+
+const SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+
+/// The hasher of rustc, Firefox's hash, a multiply and rotate of each word, not keyed
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FxHasher {
+    hash: u64,
+}
+
+impl FxHasher {
+    fn add_to_hash(&mut self, word: u64) {
+        self.hash = (self.hash.rotate_left(5) ^ word).wrapping_mul(SEED);
+    }
+}
+
+impl Hasher for FxHasher {
+    fn write(&mut self, mut bytes: &[u8]) {
+        while bytes.len() >= 8 {
+            self.add_to_hash(LittleEndian::read_u64(bytes));
+            bytes = &bytes[8..];
+        }
+        if bytes.len() >= 4 {
+            self.add_to_hash(u64::from(LittleEndian::read_u32(bytes)));
+            bytes = &bytes[4..];
+        }
+        for &byte in bytes {
+            self.add_to_hash(u64::from(byte));
+        }
+    }
+
+    fn write_u8(&mut self, i: u8) {
+        self.add_to_hash(u64::from(i));
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.add_to_hash(u64::from(i));
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.add_to_hash(u64::from(i));
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.add_to_hash(i);
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.add_to_hash(i as u64);
+    }
+
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::hash_map::RandomState;
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn test_fx_hash() {
+        // every length of the tail is hashed, and the hashes are stable
+        let bytes = b"\x03www\x07example\x03com\x00";
+        let fx_hash = |bytes: &[u8]| {
+            let mut hasher = FxHasher::default();
+            hasher.write(bytes);
+            hasher.finish()
+        };
+        let hashes = (0..bytes.len())
+            .map(|len| fx_hash(&bytes[..len]))
+            .collect::<Vec<_>>();
+        for (len, hash) in hashes.iter().enumerate() {
+            assert_eq!(*hash, fx_hash(&bytes[..len]));
+            assert!(hashes[len + 1..].iter().all(|other| other != hash));
+        }
+
+        let mut map = HashMap::with_hasher(FxBuildHasher::default());
+        map.insert("www.example.com.", 1);
+        assert_eq!(map.get("www.example.com."), Some(&1));
+    }
+
+    #[test]
+    fn test_name_hasher() {
+        let hashers: [&dyn NameHasher; 2] = [&FxBuildHasher, &RandomState::new()];
+        for hasher in &hashers {
+            assert_eq!(
+                hasher.hash_bytes(b"\x07example\x03com\x00"),
+                hasher.hash_bytes(b"\x07example\x03com\x00")
+            );
+            assert_ne!(
+                hasher.hash_bytes(b"\x07example\x03com\x00"),
+                hasher.hash_bytes(b"\x07example\x03net\x00")
+            );
+        }
+    }
+}
@@ -8,8 +8,23 @@
 
 #![warn(missing_docs)]
 #![recursion_limit = "2048"]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
 //! Trust-DNS Protocol library
+//!
+//! On `wasm32` targets the UDP, TCP and multicast streams are not available, the wire format,
+//!  record types and `xfer` handles can be used with a `DnsRequestSender` of the host, e.g. the
+//!  `fetch::FetchClientStream` of the `dns-over-https-fetch` feature.
+//!
+//! Without the default `std` feature only the wire format is built, the `BinEncoder` and
+//!  `BinDecoder`, the record types and `Message`, with `alloc` for embedded targets. IDNA,
+//!  `CAA` records, the registry of EDNS option parsers and the special use names of
+//!  `rr::domain::usage` need `std`, as does every other feature. This needs `core::net` and
+//!  `core::error`, i.e. Rust 1.81 or newer.
+
+#[cfg(not(any(feature = "std", test)))]
+#[macro_use]
+extern crate alloc;
 
 extern crate byteorder;
 #[cfg(feature = "dnssec")]
@@ -19,53 +34,118 @@ extern crate enum_as_inner;
 #[cfg(test)]
 extern crate env_logger;
 extern crate failure;
+#[cfg(feature = "std")]
 #[macro_use]
 extern crate futures;
+#[cfg(feature = "std")]
 extern crate idna;
+#[cfg(feature = "dns-over-https-fetch")]
+extern crate js_sys;
+#[cfg(feature = "std")]
 #[macro_use]
 extern crate lazy_static;
+#[cfg(all(feature = "std", target_os = "linux"))]
+extern crate libc;
 #[macro_use]
 extern crate log;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+extern crate mio;
 #[cfg(feature = "openssl")]
 extern crate openssl;
+#[cfg(feature = "std")]
 extern crate rand;
 #[cfg(feature = "ring")]
 extern crate ring;
 #[cfg(feature = "serde-config")]
+#[macro_use]
 extern crate serde;
+#[cfg(test)]
+extern crate proptest;
 extern crate smallvec;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
 extern crate socket2;
 #[cfg(test)]
 extern crate tokio;
+#[cfg(feature = "std")]
 extern crate tokio_executor;
+#[cfg(feature = "std")]
 #[macro_use]
 extern crate tokio_io;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
 extern crate tokio_reactor;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
 extern crate tokio_tcp;
+#[cfg(feature = "std")]
 extern crate tokio_timer;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
 extern crate tokio_udp;
+#[cfg(feature = "tracing-spans")]
+extern crate tracing;
 #[cfg(feature = "ring")]
 extern crate untrusted;
+#[cfg(feature = "std")]
 extern crate url;
+#[cfg(feature = "dns-over-https-fetch")]
+extern crate wasm_bindgen;
+#[cfg(feature = "dns-over-https-fetch")]
+extern crate wasm_bindgen_futures;
+#[cfg(feature = "dns-over-https-fetch")]
+extern crate web_sys;
+
+/// The parts of `std` used by the wire format, from `core` and `alloc` when built without `std`
+#[cfg(not(any(feature = "std", test)))]
+mod std {
+    pub use core::*;
+
+    pub use alloc::{borrow, collections, fmt, slice, str, sync, vec};
+
+    pub mod prelude {
+        pub mod v1 {
+            pub use alloc::borrow::ToOwned;
+            pub use alloc::boxed::Box;
+            pub use alloc::string::{String, ToString};
+            pub use alloc::vec::Vec;
+            pub use core::prelude::v1::*;
+        }
+    }
+}
 
+#[cfg(all(test, feature = "std"))]
+mod arbitrary;
+#[cfg(feature = "std")]
+pub mod capture;
 pub mod error;
-#[cfg(feature = "mdns")]
+#[cfg(feature = "dns-over-https-fetch")]
+pub mod fetch;
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
+pub mod hash;
+#[cfg(all(feature = "mdns", not(target_arch = "wasm32")))]
 pub mod multicast;
 pub mod op;
 pub mod rr;
 pub mod serialize;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
 pub mod tcp;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
 pub mod udp;
+#[cfg(feature = "std")]
 pub mod xfer;
 
 #[doc(hidden)]
+#[cfg(feature = "std")]
 pub use xfer::dns_handle::{BasicDnsHandle, DnsHandle, DnsStreamHandle, StreamHandle};
 #[doc(hidden)]
+#[cfg(feature = "std")]
 pub use xfer::dns_multiplexer::DnsMultiplexer;
 #[doc(hidden)]
-pub use xfer::retry_dns_handle::RetryDnsHandle;
+#[cfg(feature = "std")]
+pub use xfer::retry_dns_handle::{
+    BoxDelay, DelayProvider, RetryDnsHandle, RetryPolicy, TokioDelay,
+};
 #[doc(hidden)]
 #[cfg(feature = "dnssec")]
 pub use xfer::secure_dns_handle::SecureDnsHandle;
 #[doc(hidden)]
+#[cfg(feature = "std")]
 pub use xfer::{BufDnsStreamHandle, BufStreamHandle, MessageStreamHandle};
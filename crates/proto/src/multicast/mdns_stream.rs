@@ -33,6 +33,14 @@ lazy_static! {
     pub static ref MDNS_IPV6: SocketAddr = SocketAddr::new(Ipv6Addr::new(0xFF02, 0, 0, 0, 0, 0, 0, 0x00FB).into(), MDNS_PORT);
 }
 
+pub const LLMNR_PORT: u16 = 5355;
+lazy_static! {
+    /// LLMNR ipv4 address [rfc4795](https://tools.ietf.org/html/rfc4795#section-2)
+    pub static ref LLMNR_IPV4: SocketAddr = SocketAddr::new(Ipv4Addr::new(224,0,0,252).into(), LLMNR_PORT);
+    /// link-local LLMNR ipv6 address [rfc4795](https://tools.ietf.org/html/rfc4795#section-2)
+    pub static ref LLMNR_IPV6: SocketAddr = SocketAddr::new(Ipv6Addr::new(0xFF02, 0, 0, 0, 0, 0, 0x0001, 0x0003).into(), LLMNR_PORT);
+}
+
 /// A UDP stream of DNS binary packets
 #[must_use = "futures do nothing unless polled"]
 pub struct MdnsStream {
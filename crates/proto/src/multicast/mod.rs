@@ -11,7 +11,7 @@ mod mdns_client_stream;
 mod mdns_stream;
 
 pub use self::mdns_client_stream::{MdnsClientConnect, MdnsClientStream};
-pub use self::mdns_stream::{MdnsStream, MDNS_IPV4, MDNS_IPV6};
+pub use self::mdns_stream::{MdnsStream, LLMNR_IPV4, LLMNR_IPV6, MDNS_IPV4, MDNS_IPV6, MDNS_PORT};
 
 /// See [rfc6762](https://tools.ietf.org/html/rfc6762#section-5) details on these different types.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
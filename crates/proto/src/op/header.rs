@@ -55,7 +55,7 @@ use serialize::binary::*;
 ///
 /// ```
 ///
-#[derive(Clone, Debug, PartialEq, PartialOrd)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd)]
 pub struct Header {
     id: u16,
     message_type: MessageType,
@@ -74,7 +74,7 @@ pub struct Header {
 }
 
 /// Message types are either Query (also Update) or Response
-#[derive(Debug, PartialEq, PartialOrd, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Copy, Clone)]
 pub enum MessageType {
     /// Queries are Client requests, these are either Queries or Updates
     Query,
@@ -536,3 +536,18 @@ fn test_write() {
 
     assert_eq!(bytes, expect);
 }
+
+#[test]
+fn test_unknown_op_code() {
+    // 0b0000 1000, IQuery, obsolete
+    let byte_vec = vec![0x01, 0x10, 0x08, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+
+    let header = Header::read(&mut BinDecoder::new(&byte_vec)).unwrap();
+    assert_eq!(header.op_code(), OpCode::Unknown(1));
+    assert_eq!(header.query_count(), 1);
+
+    // the op_code is copied into the response
+    let mut bytes = Vec::with_capacity(512);
+    header.emit(&mut BinEncoder::new(&mut bytes)).unwrap();
+    assert_eq!(bytes, byte_vec);
+}
@@ -20,6 +20,8 @@ use std::iter;
 use std::mem;
 use std::ops::Deref;
 use std::sync::Arc;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
 
 use super::{Edns, Header, MessageType, OpCode, Query, ResponseCode};
 use error::*;
@@ -71,7 +73,7 @@ use rr::dnssec::rdata::DNSSECRecordType;
 ///
 /// By default Message is a Query. Use the Message::as_update() to create and update, or
 ///  Message::new_update()
-#[derive(Clone, Debug, PartialEq, Default)]
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
 pub struct Message {
     header: Header,
     queries: Vec<Query>,
@@ -326,16 +328,25 @@ impl Message {
         self
     }
 
-    /// Add a SIG0 record, i.e. sign this message
+    /// Add a SIG0 or TSIG record, i.e. sign this message
     ///
     /// This must be don't only after all records have been associated. Generally this will be handled by the client and not need to be used directly
     #[cfg(feature = "dnssec")]
     pub fn add_sig0(&mut self, record: Record) -> &mut Self {
-        assert_eq!(RecordType::DNSSEC(DNSSECRecordType::SIG), record.rr_type());
+        assert!(
+            record.rr_type() == RecordType::DNSSEC(DNSSECRecordType::SIG)
+                || record.rr_type() == RecordType::DNSSEC(DNSSECRecordType::TSIG)
+        );
         self.sig0.push(record);
         self
     }
 
+    /// Sets the header, the counts of the header are only emitted as they are by `to_raw_vec`
+    pub fn set_header(&mut self, header: Header) -> &mut Self {
+        self.header = header;
+        self
+    }
+
     /// Gets the header of the Message
     pub fn header(&self) -> &Header {
         &self.header
@@ -404,6 +415,11 @@ impl Message {
         &self.queries
     }
 
+    /// Removes all the queries from the Message
+    pub fn take_queries(&mut self) -> Vec<Query> {
+        mem::replace(&mut self.queries, vec![])
+    }
+
     /// ```text
     /// Answer          Carries RRs which directly answer the query.
     /// ```
@@ -522,11 +538,16 @@ impl Message {
         &self.sig0
     }
 
-    // TODO: only necessary in tests, should it be removed?
     /// this is necessary to match the counts in the header from the record sections
     ///  this happens implicitly on write_to, so no need to call before write_to
-    #[cfg(test)]
+    ///
+    /// The additional count includes the EDNS and SIG0 records, as they are emitted.
+    ///
+    /// # Panics
+    ///
+    /// If a section has more records than can be counted in the header.
     pub fn update_counts(&mut self) -> &mut Self {
+        let edns_count = if self.edns.is_some() { 1 } else { 0 };
         self.header = update_header_counts(
             &self.header,
             false,
@@ -534,7 +555,7 @@ impl Message {
                 query_count: self.queries.len(),
                 answer_count: self.answers.len(),
                 nameserver_count: self.name_servers.len(),
-                additional_count: self.additionals.len(),
+                additional_count: self.additionals.len() + edns_count + self.sig0.len(),
             },
         );
         self
@@ -577,7 +598,8 @@ impl Message {
             } else {
                 match record.rr_type() {
                     #[cfg(feature = "dnssec")]
-                    RecordType::DNSSEC(DNSSECRecordType::SIG) => {
+                    RecordType::DNSSEC(DNSSECRecordType::SIG)
+                    | RecordType::DNSSEC(DNSSECRecordType::TSIG) => {
                         saw_sig0 = true;
                         sig0s.push(record);
                     }
@@ -623,28 +645,56 @@ impl Message {
         Ok(buffer)
     }
 
+    /// Encodes the Message into a buffer, with the header as it is
+    ///
+    /// Unlike `to_vec`, the counts of the header are not updated from the sections, and so may
+    ///  not match the records which are emitted, see `update_counts` and `Header::set_query_count`
+    ///  et al. This is for testing the handling of malformed messages.
+    pub fn to_raw_vec(&self) -> ProtoResult<Vec<u8>> {
+        let mut buffer = Vec::with_capacity(512);
+        {
+            let mut encoder = BinEncoder::new(&mut buffer);
+            self.header.emit(&mut encoder)?;
+            encoder.emit_all(self.queries.iter())?;
+            encoder.emit_all(self.answers.iter())?;
+            encoder.emit_all(self.name_servers.iter())?;
+            encoder.emit_all(self.additionals.iter())?;
+            if let Some(ref edns) = self.edns {
+                Record::from(edns).emit(&mut encoder)?;
+            }
+            encoder.emit_all(self.sig0.iter())?;
+        }
+
+        Ok(buffer)
+    }
+
     /// Finalize the message prior to sending.
     ///
     /// Subsequent to calling this, the Message should not change.
+    ///
+    /// # Return
+    ///
+    /// The verifier of the responses to the message, if the finalizer requires them to be verified
     pub fn finalize<MF: MessageFinalizer>(
         &mut self,
         finalizer: &MF,
         inception_time: u32,
-    ) -> ProtoResult<()> {
+    ) -> ProtoResult<Option<MessageVerifier>> {
         debug!("finalizing message: {:?}", self);
         let finals: Vec<Record> = finalizer.finalize_message(self, inception_time)?;
 
         // append all records to message
         for fin in finals {
             match fin.rr_type() {
-                // SIG0's are special, and come at the very end of the message
+                // SIG0's and TSIG's are special, and come at the very end of the message
                 #[cfg(feature = "dnssec")]
-                RecordType::DNSSEC(DNSSECRecordType::SIG) => self.add_sig0(fin),
+                RecordType::DNSSEC(DNSSECRecordType::SIG)
+                | RecordType::DNSSEC(DNSSECRecordType::TSIG) => self.add_sig0(fin),
                 _ => self.add_additional(fin),
             };
         }
 
-        Ok(())
+        Ok(finalizer.response_verifier(self))
     }
 }
 
@@ -672,8 +722,29 @@ pub trait MessageFinalizer: Send + Sync + 'static {
     ///
     /// A vector to append to the additionals section of the message, sorted in the order as they should appear in the message.
     fn finalize_message(&self, message: &Message, current_time: u32) -> ProtoResult<Vec<Record>>;
+
+    /// Returns true if the message should be finalized before it is sent
+    ///
+    /// By default only updates are finalized, e.g. signed with SIG0
+    fn should_finalize_message(&self, message: &Message) -> bool {
+        message.op_code() == OpCode::Update
+    }
+
+    /// Returns the verifier for the responses to the finalized message, if any
+    ///
+    /// The verifier is called with each response message, in the order they are received, e.g.
+    ///  a TSIG signer verifies the MACs chained across the messages of a zone transfer.
+    fn response_verifier(&self, _message: &Message) -> Option<MessageVerifier> {
+        None
+    }
 }
 
+/// Verifies the encoded responses to a finalized message, see `MessageFinalizer::response_verifier`
+///
+/// Returns true if the response was verified, false if it's an unverified intermediate message of
+///  a multi-message response, which is only valid if a verified message follows it.
+pub type MessageVerifier = Box<dyn FnMut(&[u8]) -> ProtoResult<bool> + Send>;
+
 /// A MessageFinalizer which does nothing
 ///
 /// *WARNING* This should only be used in None context, it will panic in all cases where finalize is called.
@@ -789,7 +860,14 @@ impl BinEncodable for Message {
 }
 
 impl<'r> BinDecodable<'r> for Message {
+    /// Errors have the offset in the message at which they were found, see `ProtoError::offset`
     fn read(decoder: &mut BinDecoder<'r>) -> ProtoResult<Self> {
+        Self::read_message(decoder).map_err(|e| e.at_offset(decoder.index()))
+    }
+}
+
+impl Message {
+    fn read_message(decoder: &mut BinDecoder) -> ProtoResult<Self> {
         let header = Header::read(decoder)?;
 
         // TODO/FIXME: return just header, and in the case of the rest of message getting an error.
@@ -857,6 +935,24 @@ fn test_emit_and_read_query() {
     test_emit_and_read(message);
 }
 
+#[test]
+fn test_to_raw_vec() {
+    let mut message = Message::new();
+    message.set_id(10).add_query(Query::new()).update_counts();
+    assert_eq!(message.to_raw_vec().unwrap(), message.to_vec().unwrap());
+
+    // the counts of the header are emitted as they are, not matching the sections
+    let mut header = message.header().clone();
+    header.set_query_count(2).set_additional_count(1);
+    message.set_header(header);
+
+    let raw = message.to_raw_vec().unwrap();
+    assert_eq!(raw.len(), message.to_vec().unwrap().len());
+    assert_eq!(&raw[4..6], &[0, 2]);
+    assert_eq!(&raw[10..12], &[0, 1]);
+    assert!(Message::from_vec(&raw).is_err());
+}
+
 #[test]
 fn test_emit_and_read_records() {
     let mut message = Message::new();
@@ -932,3 +1028,27 @@ fn test_legit_message() {
 
     assert_eq!(message.id(), 4096);
 }
+
+#[test]
+fn test_fuzz_corpus() {
+    use std::fs;
+    use std::path::Path;
+
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus/message");
+    let entries = fs::read_dir(&corpus).expect("failed to read the fuzz corpus");
+
+    for entry in entries {
+        let path = entry.unwrap().path();
+        let bytes = fs::read(&path).unwrap();
+
+        // malformed messages must fail to decode rather than panic, valid ones must encode
+        if let Ok(message) = Message::from_vec(&bytes) {
+            let encoded = message
+                .to_vec()
+                .unwrap_or_else(|e| panic!("{}: failed to encode: {}", path.display(), e));
+            let decoded = Message::from_vec(&encoded)
+                .unwrap_or_else(|e| panic!("{}: failed to decode: {}", path.display(), e));
+            assert_eq!(decoded, message, "{}", path.display());
+        }
+    }
+}
@@ -0,0 +1,320 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! A builder for consistent query and response Messages
+
+use error::*;
+use op::{Edns, Message, MessageType, OpCode, Query, ResponseCode};
+use rr::{Record, RecordType};
+
+/// Builds a `Message` whose header is consistent with its sections
+///
+/// The header counts are those of the sections, OPT records can only be added with `edns`, and
+///  the high bits of extended response codes are set on the EDNS of the message.
+///
+/// ```rust
+/// use std::net::Ipv4Addr;
+/// use std::str::FromStr;
+///
+/// use trust_dns_proto::op::{MessageBuilder, Query};
+/// use trust_dns_proto::rr::{Name, Record, RecordType};
+///
+/// let name = Name::from_str("www.example.com.").unwrap();
+/// let request = MessageBuilder::query(Query::query(name.clone(), RecordType::A))
+///     .id(10)
+///     .build()
+///     .unwrap();
+///
+/// let response = MessageBuilder::response_to(&request)
+///     .recursion_available(true)
+///     .add_answer(Record::a(name, 300, Ipv4Addr::new(192, 0, 2, 1)))
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(response.id(), 10);
+/// assert_eq!(response.queries(), request.queries());
+/// assert_eq!(response.header().answer_count(), 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MessageBuilder {
+    message: Message,
+    response_code: ResponseCode,
+}
+
+impl MessageBuilder {
+    /// Creates an empty message, of the type and operation
+    pub fn new(message_type: MessageType, op_code: OpCode) -> Self {
+        let mut message = Message::new();
+        message.set_message_type(message_type).set_op_code(op_code);
+
+        MessageBuilder {
+            message,
+            response_code: ResponseCode::NoError,
+        }
+    }
+
+    /// Creates a standard query, for the `query` with recursion desired
+    pub fn query(query: Query) -> Self {
+        Self::new(MessageType::Query, OpCode::Query)
+            .recursion_desired(true)
+            .add_query(query)
+    }
+
+    /// Creates a response to the request
+    ///
+    /// The id, operation, queries and the recursion desired and checking disabled flags are those
+    ///  of the request. If the request has EDNS, the response has EDNS with the same DNSSEC OK
+    ///  flag.
+    pub fn response_to(request: &Message) -> Self {
+        let mut builder = Self::new(MessageType::Response, request.op_code())
+            .id(request.id())
+            .recursion_desired(request.recursion_desired())
+            .checking_disabled(request.checking_disabled())
+            .add_queries(request.queries().iter().cloned());
+
+        if let Some(request_edns) = request.edns() {
+            let mut edns = Edns::new();
+            edns.set_dnssec_ok(request_edns.dnssec_ok());
+            builder = builder.edns(edns);
+        }
+
+        builder
+    }
+
+    /// Sets the id of the message
+    pub fn id(mut self, id: u16) -> Self {
+        self.message.set_id(id);
+        self
+    }
+
+    /// see `Header::set_authoritative`
+    pub fn authoritative(mut self, authoritative: bool) -> Self {
+        self.message.set_authoritative(authoritative);
+        self
+    }
+
+    /// see `Header::set_recursion_desired`
+    pub fn recursion_desired(mut self, recursion_desired: bool) -> Self {
+        self.message.set_recursion_desired(recursion_desired);
+        self
+    }
+
+    /// see `Header::set_recursion_available`
+    pub fn recursion_available(mut self, recursion_available: bool) -> Self {
+        self.message.set_recursion_available(recursion_available);
+        self
+    }
+
+    /// see `Header::set_authentic_data`
+    pub fn authentic_data(mut self, authentic_data: bool) -> Self {
+        self.message.set_authentic_data(authentic_data);
+        self
+    }
+
+    /// see `Header::set_checking_disabled`
+    pub fn checking_disabled(mut self, checking_disabled: bool) -> Self {
+        self.message.set_checking_disabled(checking_disabled);
+        self
+    }
+
+    /// Sets the response code, extended response codes require the EDNS of the message, which is
+    ///  added if it isn't set
+    pub fn response_code(mut self, response_code: ResponseCode) -> Self {
+        self.response_code = response_code;
+        self
+    }
+
+    /// Sets the EDNS of the message, replacing any previous EDNS
+    pub fn edns(mut self, edns: Edns) -> Self {
+        self.message.set_edns(edns);
+        self
+    }
+
+    /// Adds a query to the question section
+    pub fn add_query(mut self, query: Query) -> Self {
+        self.message.add_query(query);
+        self
+    }
+
+    /// Adds the queries to the question section
+    pub fn add_queries<I: IntoIterator<Item = Query>>(mut self, queries: I) -> Self {
+        for query in queries {
+            self.message.add_query(query);
+        }
+        self
+    }
+
+    /// Adds a record to the answer section
+    pub fn add_answer(mut self, record: Record) -> Self {
+        self.message.add_answer(record);
+        self
+    }
+
+    /// Adds the records to the answer section
+    pub fn add_answers<I: IntoIterator<Item = Record>>(mut self, records: I) -> Self {
+        for record in records {
+            self.message.add_answer(record);
+        }
+        self
+    }
+
+    /// Adds a record to the authority section
+    pub fn add_name_server(mut self, record: Record) -> Self {
+        self.message.add_name_server(record);
+        self
+    }
+
+    /// Adds the records to the authority section
+    pub fn add_name_servers<I: IntoIterator<Item = Record>>(mut self, records: I) -> Self {
+        for record in records {
+            self.message.add_name_server(record);
+        }
+        self
+    }
+
+    /// Adds a record to the additional section, see `edns` for the OPT record
+    pub fn add_additional(mut self, record: Record) -> Self {
+        self.message.add_additional(record);
+        self
+    }
+
+    /// Adds the records to the additional section, see `edns` for the OPT record
+    pub fn add_additionals<I: IntoIterator<Item = Record>>(mut self, records: I) -> Self {
+        for record in records {
+            self.message.add_additional(record);
+        }
+        self
+    }
+
+    /// Returns the message, with the header counts of its sections
+    ///
+    /// # Errors
+    ///
+    /// If a section has an OPT record, or more records than can be counted in the header.
+    pub fn build(self) -> ProtoResult<Message> {
+        let MessageBuilder {
+            mut message,
+            response_code,
+        } = self;
+
+        let has_opt = message
+            .answers()
+            .iter()
+            .chain(message.name_servers())
+            .chain(message.additionals())
+            .any(|record| record.rr_type() == RecordType::OPT);
+        if has_opt {
+            return Err("OPT records must be set with MessageBuilder::edns".into());
+        }
+
+        message.set_response_code(response_code);
+        if response_code.high() > 0 || message.edns().is_some() {
+            message
+                .edns_mut()
+                .set_rcode_high(response_code.high() as u8);
+        }
+
+        // the EDNS record is counted in the additional section
+        let lens = [
+            message.queries().len(),
+            message.answers().len(),
+            message.name_servers().len(),
+            message.additionals().len() + 1,
+        ];
+        if let Some(len) = lens.iter().find(|len| **len > u16::MAX as usize) {
+            return Err(format!("too many records for a message: {}", len).into());
+        }
+
+        message.update_counts();
+        Ok(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+
+    use super::*;
+    use rr::Name;
+
+    fn query() -> Query {
+        Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A)
+    }
+
+    #[test]
+    fn test_query() {
+        let message = MessageBuilder::query(query()).id(10).build().unwrap();
+
+        assert_eq!(message.id(), 10);
+        assert_eq!(message.message_type(), MessageType::Query);
+        assert_eq!(message.op_code(), OpCode::Query);
+        assert!(message.recursion_desired());
+        assert_eq!(message.queries(), &[query()]);
+        assert_eq!(message.header().query_count(), 1);
+        assert_eq!(message.header().additional_count(), 0);
+
+        let bytes = message.to_vec().unwrap();
+        assert_eq!(Message::from_vec(&bytes).unwrap(), message);
+    }
+
+    #[test]
+    fn test_response_to() {
+        let mut edns = Edns::new();
+        edns.set_dnssec_ok(true);
+        let request = MessageBuilder::query(query())
+            .id(10)
+            .checking_disabled(true)
+            .edns(edns)
+            .build()
+            .unwrap();
+        assert_eq!(request.header().additional_count(), 1);
+
+        let record = Record::a(query().name().clone(), 300, Ipv4Addr::new(192, 0, 2, 1));
+        let response = MessageBuilder::response_to(&request)
+            .add_answer(record.clone())
+            .build()
+            .unwrap();
+
+        assert_eq!(response.id(), 10);
+        assert_eq!(response.message_type(), MessageType::Response);
+        assert!(response.recursion_desired());
+        assert!(response.checking_disabled());
+        assert_eq!(response.queries(), request.queries());
+        assert_eq!(response.answers(), &[record]);
+        assert!(response.edns().unwrap().dnssec_ok());
+        assert_eq!(response.header().answer_count(), 1);
+        assert_eq!(response.header().additional_count(), 1);
+
+        let bytes = response.to_vec().unwrap();
+        assert_eq!(Message::from_vec(&bytes).unwrap(), response);
+    }
+
+    #[test]
+    fn test_extended_response_code() {
+        let response = MessageBuilder::new(MessageType::Response, OpCode::Query)
+            .response_code(ResponseCode::BADKEY)
+            .build()
+            .unwrap();
+
+        assert_eq!(response.response_code(), ResponseCode::BADKEY);
+        assert_eq!(response.header().additional_count(), 1);
+
+        let bytes = response.to_vec().unwrap();
+        let response = Message::from_vec(&bytes).unwrap();
+        assert_eq!(response.response_code(), ResponseCode::BADKEY);
+    }
+
+    #[test]
+    fn test_opt_record_is_rejected() {
+        let opt = Record::with(Name::root(), RecordType::OPT, 0);
+        assert!(MessageBuilder::query(query())
+            .add_additional(opt)
+            .build()
+            .is_err());
+    }
+}
@@ -20,6 +20,7 @@
 mod edns;
 pub mod header;
 pub mod message;
+mod message_builder;
 pub mod op_code;
 pub mod query;
 pub mod response_code;
@@ -27,7 +28,8 @@ pub mod response_code;
 pub use self::edns::Edns;
 pub use self::header::Header;
 pub use self::header::MessageType;
-pub use self::message::{Message, MessageFinalizer, NoopMessageFinalizer};
+pub use self::message::{Message, MessageFinalizer, MessageVerifier, NoopMessageFinalizer};
+pub use self::message_builder::MessageBuilder;
 pub use self::op_code::OpCode;
 pub use self::query::Query;
 pub use self::response_code::ResponseCode;
@@ -37,7 +37,7 @@ use error::*;
 ///
 ///                 3-15            reserved for future use
 /// ```
-#[derive(Debug, PartialEq, PartialOrd, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Copy, Clone)]
 #[allow(dead_code)]
 pub enum OpCode {
     /// Query request [RFC 1035](https://tools.ietf.org/html/rfc1035)
@@ -51,6 +51,10 @@ pub enum OpCode {
 
     /// Update message [RFC 2136](https://tools.ietf.org/html/rfc2136)
     Update,
+
+    /// An unassigned or obsolete code, e.g. IQuery, which is decoded so that it can be answered
+    ///  with NotImp
+    Unknown(u8),
 }
 
 /// Convert from OpCode to u8
@@ -75,6 +79,7 @@ impl From<OpCode> for u8 {
             OpCode::Notify => 4,
             OpCode::Update => 5,
             // 6-15	Unassigned
+            OpCode::Unknown(value) => value,
         }
     }
 }
@@ -87,15 +92,22 @@ impl From<OpCode> for u8 {
 ///
 /// let var: OpCode = OpCode::from_u8(0).unwrap();
 /// assert_eq!(OpCode::Query, var);
+///
+/// let var: OpCode = OpCode::from_u8(1).unwrap();
+/// assert_eq!(OpCode::Unknown(1), var);
 /// ```
 impl OpCode {
     /// Decodes the binary value of the OpCode
+    ///
+    /// The unassigned codes are decoded as `Unknown`, only values which don't fit in the 4 bits of
+    ///  the header are errors.
     pub fn from_u8(value: u8) -> ProtoResult<Self> {
         match value {
             0 => Ok(OpCode::Query),
             2 => Ok(OpCode::Status),
             4 => Ok(OpCode::Notify),
             5 => Ok(OpCode::Update),
+            1 | 3 | 6..=15 => Ok(OpCode::Unknown(value)),
             _ => Err(format!("unknown OpCode: {}", value).into()),
         }
     }
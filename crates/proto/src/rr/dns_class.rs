@@ -12,6 +12,8 @@ use std::convert::From;
 use std::fmt;
 use std::fmt::{Display, Formatter};
 use std::str::FromStr;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
 
 use error::*;
 use serialize::binary::*;
@@ -26,6 +26,7 @@ pub mod nsec;
 pub mod nsec3;
 pub mod nsec3param;
 pub mod sig;
+pub mod tsig;
 
 use error::*;
 use rr::rdata::null;
@@ -39,6 +40,7 @@ pub use self::nsec::NSEC;
 pub use self::nsec3::NSEC3;
 pub use self::nsec3param::NSEC3PARAM;
 pub use self::sig::SIG;
+pub use self::tsig::{TsigAlgorithm, TSIG};
 
 /// The type of the resource record, for DNSSEC-specific records.
 #[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
@@ -66,6 +68,8 @@ pub enum DNSSECRecordType {
     /// for now, we enable/disable SIG(0) in exactly the same circumstances that
     /// we enable/disable DNSSEC. This may change in the future.
     SIG,
+    /// RFC 8945 Transaction signature, for secret key authentication of messages
+    TSIG,
     /// Unknown or not yet supported DNSSec record type
     Unknown(u16),
 }
@@ -81,6 +85,7 @@ impl From<u16> for DNSSECRecordType {
             51 => DNSSECRecordType::NSEC3PARAM,
             46 => DNSSECRecordType::RRSIG,
             24 => DNSSECRecordType::SIG,
+            250 => DNSSECRecordType::TSIG,
             _ => DNSSECRecordType::Unknown(value),
         }
     }
@@ -97,6 +102,7 @@ impl From<DNSSECRecordType> for &'static str {
             DNSSECRecordType::NSEC3PARAM => "NSEC3PARAM",
             DNSSECRecordType::RRSIG => "RRSIG",
             DNSSECRecordType::SIG => "SIG",
+            DNSSECRecordType::TSIG => "TSIG",
             DNSSECRecordType::Unknown(..) => "DnsSecUnknown",
         }
     }
@@ -113,6 +119,7 @@ impl From<DNSSECRecordType> for u16 {
             DNSSECRecordType::NSEC3PARAM => 51,
             DNSSECRecordType::RRSIG => 46,
             DNSSECRecordType::SIG => 24,
+            DNSSECRecordType::TSIG => 250,
             DNSSECRecordType::Unknown(value) => value,
         }
     }
@@ -446,6 +453,15 @@ pub enum DNSSECRData {
     /// ```
     SIG(SIG),
 
+    /// ```text
+    /// RFC 8945       Secret Key Transaction Authentication for DNS    November 2020
+    ///
+    /// 4.2.  TSIG Record Format
+    /// ```
+    ///
+    /// See `TSIG` for the wire format
+    TSIG(TSIG),
+
     /// Unknown or unsupported DNSSec record data
     Unknown {
         /// RecordType code
@@ -494,6 +510,10 @@ impl DNSSECRData {
                 debug!("reading SIG");
                 sig::read(decoder, rdata_length).map(DNSSECRData::SIG)
             }
+            DNSSECRecordType::TSIG => {
+                debug!("reading TSIG");
+                tsig::read(decoder, rdata_length).map(DNSSECRData::TSIG)
+            }
             DNSSECRecordType::Unknown(code) => {
                 debug!("reading unknown dnssec: {}", code);
                 null::read(decoder, rdata_length).map(|rdata| DNSSECRData::Unknown { code, rdata })
@@ -524,6 +544,9 @@ impl DNSSECRData {
             DNSSECRData::SIG(ref sig) => {
                 encoder.with_canonical_names(|encoder| sig::emit(encoder, sig))
             }
+            DNSSECRData::TSIG(ref tsig) => {
+                encoder.with_canonical_names(|encoder| tsig::emit(encoder, tsig))
+            }
             DNSSECRData::Unknown { ref rdata, .. } => {
                 encoder.with_canonical_names(|encoder| null::emit(encoder, rdata))
             }
@@ -539,6 +562,7 @@ impl DNSSECRData {
             DNSSECRData::NSEC3(..) => DNSSECRecordType::NSEC3,
             DNSSECRData::NSEC3PARAM(..) => DNSSECRecordType::NSEC3PARAM,
             DNSSECRData::SIG(..) => DNSSECRecordType::SIG,
+            DNSSECRData::TSIG(..) => DNSSECRecordType::TSIG,
             DNSSECRData::Unknown { code, .. } => DNSSECRecordType::Unknown(code),
         }
     }
@@ -357,20 +357,34 @@ pub(crate) fn decode_type_bit_maps(
     //  MUST be interpreted as zero octets.
     let mut record_types: Vec<RecordType> = Vec::new();
     let mut state: BitMapReadState = BitMapReadState::Window;
+    let mut last_window: Option<u8> = None;
 
     // loop through all the bytes in the bitmap
     for _ in 0..bit_map_len.unverified(/*bounded over any length of u16*/) {
         let current_byte = decoder.read_u8()?;
 
         state = match state {
-            BitMapReadState::Window => BitMapReadState::Len {
-                window: current_byte.unverified(/*window is any valid u8,*/),
-            },
-            BitMapReadState::Len { window } => BitMapReadState::RecordType {
-                window,
-                len: current_byte,
-                left: current_byte,
-            },
+            BitMapReadState::Window => {
+                // blocks are in increasing order, which also prevents duplicate windows
+                let window = current_byte
+                    .verify_unwrap(|window| last_window.map_or(true, |last| *window > last))
+                    .map_err(|_| ProtoError::from("window blocks out of order in NSEC(3)"))?;
+                last_window = Some(window);
+
+                BitMapReadState::Len { window }
+            }
+            BitMapReadState::Len { window } => {
+                let len = current_byte
+                    .verify_unwrap(|len| *len > 0 && *len <= 32)
+                    .map(Restrict::new)
+                    .map_err(|_| ProtoError::from("bitmap length not 1-32 in NSEC(3)"))?;
+
+                BitMapReadState::RecordType {
+                    window,
+                    len,
+                    left: len,
+                }
+            }
             BitMapReadState::RecordType { window, len, left } => {
                 // window is the Window Block # from above
                 // len is the Bitmap Length
@@ -410,7 +424,12 @@ pub(crate) fn decode_type_bit_maps(
         };
     }
 
-    Ok(record_types)
+    // the last window block must have all of its bitmap
+    if let BitMapReadState::Window = state {
+        Ok(record_types)
+    } else {
+        Err("type bit maps truncated in NSEC(3)".into())
+    }
 }
 
 enum BitMapReadState {
@@ -571,3 +590,31 @@ pub fn test_dups() {
     );
     assert_eq!(rdata_wo, read_rdata.unwrap());
 }
+
+#[test]
+pub fn test_malformed_bit_maps() {
+    let read_bit_maps = |bytes: &[u8]| {
+        let mut decoder: BinDecoder = BinDecoder::new(bytes);
+        decode_type_bit_maps(&mut decoder, Restrict::new(bytes.len()))
+    };
+
+    // window 0, 1 byte, A
+    assert_eq!(
+        read_bit_maps(&[0, 1, 0b0100_0000]).unwrap(),
+        vec![RecordType::A]
+    );
+
+    // windows must be increasing, which excludes duplicate windows
+    assert!(read_bit_maps(&[1, 1, 0b0100_0000, 0, 1, 0b0100_0000]).is_err());
+    assert!(read_bit_maps(&[0, 1, 0b0100_0000, 0, 1, 0b0010_0000]).is_err());
+
+    // bitmap lengths are 1-32
+    assert!(read_bit_maps(&[0, 0]).is_err());
+    let mut too_long = vec![0, 33];
+    too_long.extend_from_slice(&[0xFF; 33]);
+    assert!(read_bit_maps(&too_long).is_err());
+
+    // truncated windows
+    assert!(read_bit_maps(&[0]).is_err());
+    assert!(read_bit_maps(&[0, 2, 0b0100_0000]).is_err());
+}
@@ -0,0 +1,576 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! TSIG, secret key transaction authentication for DNS
+
+use std::fmt;
+
+use error::*;
+use op::{Header, Query};
+use rr::dnssec::rdata::{DNSSECRData, DNSSECRecordType};
+use rr::{DNSClass, Name, RData, Record, RecordType};
+use serialize::binary::*;
+
+/// [RFC 8945, Secret Key Transaction Authentication for DNS](https://tools.ietf.org/html/rfc8945#section-4.2)
+///
+/// ```text
+/// 4.2.  TSIG Record Format
+///
+///   The fields of the TSIG RR are described below.  All multi-octet
+///   integers in the record are sent in network byte order (see
+///   Section 2.3.2 of [RFC1035]).
+///
+///   NAME:  The name of the key used, in domain name syntax.  The name
+///      should reflect the names of the hosts and uniquely identify the
+///      key among a set of keys these two hosts may share at any given
+///      time.  For example, if hosts A.site.example and B.example.net
+///      share a key, possibilities for the key name include
+///      <id>.A.site.example, <id>.B.example.net, and
+///      <id>.A.site.example.B.example.net.  It should be possible for more
+///      than one key to be in simultaneous use among a set of interacting
+///      hosts.  This allows for periodic key rotation as per best
+///      operational practices, as well as algorithm agility as indicated
+///      by [RFC7696].
+///
+///      The name may be used as a local index to the key involved, but it
+///      is recommended that it be globally unique.  Where a key is just
+///      shared between two hosts, its name actually need only be
+///      meaningful to them, but it is recommended that the key name be
+///      mnemonic and incorporate the names of participating agents or
+///      resources as suggested above.
+///
+///   TYPE:  This MUST be TSIG (250: Transaction SIGnature).
+///
+///   CLASS:  This MUST be ANY.
+///
+///   TTL:  This MUST be 0.
+///
+///   RDLENGTH:  (variable)
+///
+///   RDATA:  The RDATA for a TSIG RR consists of a number of fields,
+///      described below:
+///
+///                            1 1 1 1 1 1 1 1 1 1 2 2 2 2 2 2 2 2 2 2 3 3
+///        0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+///       +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///       /                         Algorithm Name                        /
+///       +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///       |                                                               |
+///       |          Time Signed          +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///       |                               |            Fudge              |
+///       +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///       |          MAC Size             |                               /
+///       +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+             MAC               /
+///       /                                                               /
+///       +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///       |          Original ID          |            Error              |
+///       +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///       |          Other Len            |                               /
+///       +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+           Other Data          /
+///       /                                                               /
+///       +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct TSIG {
+    algorithm: TsigAlgorithm,
+    time: u64,
+    fudge: u16,
+    mac: Vec<u8>,
+    oid: u16,
+    error: u16,
+    other: Vec<u8>,
+}
+
+impl TSIG {
+    /// Constructs a new TSIG
+    ///
+    /// # Arguments
+    ///
+    /// * `algorithm` - the MAC algorithm
+    /// * `time` - seconds since the Unix epoch the message was signed at, only the low 48 bits are used
+    /// * `fudge` - seconds of error permitted in `time`
+    /// * `mac` - the Message Authentication Code
+    /// * `oid` - the original ID of the message
+    /// * `error` - the extended RCODE, e.g. BADSIG, or 0
+    /// * `other` - empty, except for BADTIME errors where it's the time of the server
+    pub fn new(
+        algorithm: TsigAlgorithm,
+        time: u64,
+        fudge: u16,
+        mac: Vec<u8>,
+        oid: u16,
+        error: u16,
+        other: Vec<u8>,
+    ) -> Self {
+        TSIG {
+            algorithm,
+            time,
+            fudge,
+            mac,
+            oid,
+            error,
+            other,
+        }
+    }
+
+    /// The MAC algorithm
+    pub fn algorithm(&self) -> &TsigAlgorithm {
+        &self.algorithm
+    }
+
+    /// Seconds since the Unix epoch the message was signed at
+    pub fn time(&self) -> u64 {
+        self.time
+    }
+
+    /// Seconds of error permitted in `time`
+    pub fn fudge(&self) -> u16 {
+        self.fudge
+    }
+
+    /// The Message Authentication Code
+    pub fn mac(&self) -> &[u8] {
+        &self.mac
+    }
+
+    /// The original ID of the message, before it was possibly changed by a forwarder
+    pub fn oid(&self) -> u16 {
+        self.oid
+    }
+
+    /// The extended RCODE, e.g. BADSIG, or 0
+    pub fn error(&self) -> u16 {
+        self.error
+    }
+
+    /// Other data, the time of the server for BADTIME errors
+    pub fn other(&self) -> &[u8] {
+        &self.other
+    }
+
+    /// Returns a copy of this TSIG with the MAC replaced
+    pub fn set_mac(self, mac: Vec<u8>) -> Self {
+        TSIG { mac, ..self }
+    }
+
+    /// Emits the TSIG variables of [RFC 8945 Section 4.3.3](https://tools.ietf.org/html/rfc8945#section-4.3.3),
+    ///  which are appended to the message to compute the MAC
+    ///
+    /// # Arguments
+    ///
+    /// * `encoder` - encoder to write the variables to
+    /// * `key_name` - name of the key, the owner of the TSIG record
+    /// * `first_message` - false for the following messages of a multi-message response, e.g. AXFR,
+    ///                     for which only the timers are included
+    pub fn emit_tsig_for_mac(
+        &self,
+        encoder: &mut BinEncoder,
+        key_name: &Name,
+        first_message: bool,
+    ) -> ProtoResult<()> {
+        if first_message {
+            key_name.emit_as_canonical(encoder, true)?;
+            DNSClass::ANY.emit(encoder)?;
+            encoder.emit_u32(0)?; // TTL
+            self.algorithm.emit(encoder)?;
+        }
+
+        emit_time(encoder, self.time)?;
+        encoder.emit_u16(self.fudge)?;
+
+        if first_message {
+            encoder.emit_u16(self.error)?;
+            encoder.emit_u16(self.other.len() as u16)?;
+            encoder.emit_vec(&self.other)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The MAC algorithm of a TSIG
+///
+/// [RFC 8945 Section 6](https://tools.ietf.org/html/rfc8945#section-6), only the SHA-2 HMACs are
+///  supported for signing and verification, the others are kept for interoperability, e.g. for
+///  responding with a BADKEY error.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum TsigAlgorithm {
+    /// HMAC-MD5.SIG-ALG.REG.INT, not supported
+    HmacMd5,
+    /// hmac-sha1, not supported
+    HmacSha1,
+    /// hmac-sha224, not supported
+    HmacSha224,
+    /// hmac-sha256
+    HmacSha256,
+    /// hmac-sha384
+    HmacSha384,
+    /// hmac-sha512
+    HmacSha512,
+    /// Unknown algorithm
+    Unknown(Name),
+}
+
+impl TsigAlgorithm {
+    /// Returns the name of the algorithm, as it is sent in the TSIG record
+    pub fn to_name(&self) -> Name {
+        let name = match *self {
+            TsigAlgorithm::HmacMd5 => "HMAC-MD5.SIG-ALG.REG.INT.",
+            TsigAlgorithm::HmacSha1 => "hmac-sha1.",
+            TsigAlgorithm::HmacSha224 => "hmac-sha224.",
+            TsigAlgorithm::HmacSha256 => "hmac-sha256.",
+            TsigAlgorithm::HmacSha384 => "hmac-sha384.",
+            TsigAlgorithm::HmacSha512 => "hmac-sha512.",
+            TsigAlgorithm::Unknown(ref name) => return name.clone(),
+        };
+
+        Name::from_ascii(name).expect("invalid algorithm name")
+    }
+
+    /// Returns the algorithm with the name, names are case insensitive
+    pub fn from_name(name: Name) -> Self {
+        let mut ascii = name.to_lowercase().to_ascii();
+        if !ascii.ends_with('.') {
+            ascii.push('.');
+        }
+
+        match ascii.as_str() {
+            "hmac-md5.sig-alg.reg.int." => TsigAlgorithm::HmacMd5,
+            "hmac-sha1." => TsigAlgorithm::HmacSha1,
+            "hmac-sha224." => TsigAlgorithm::HmacSha224,
+            "hmac-sha256." => TsigAlgorithm::HmacSha256,
+            "hmac-sha384." => TsigAlgorithm::HmacSha384,
+            "hmac-sha512." => TsigAlgorithm::HmacSha512,
+            _ => TsigAlgorithm::Unknown(name),
+        }
+    }
+
+    /// Returns true if messages can be signed and verified with this algorithm
+    pub fn is_supported(&self) -> bool {
+        match *self {
+            TsigAlgorithm::HmacSha256 | TsigAlgorithm::HmacSha384 | TsigAlgorithm::HmacSha512 => {
+                cfg!(any(feature = "ring", feature = "openssl"))
+            }
+            _ => false,
+        }
+    }
+
+    /// Computes the Message Authentication Code of the message with the key
+    pub fn mac_data(&self, key: &[u8], message: &[u8]) -> ProtoResult<Vec<u8>> {
+        self.hmac(key, message)
+    }
+
+    /// Verifies the Message Authentication Code of the message, comparing in constant time
+    pub fn verify_mac(&self, key: &[u8], message: &[u8], mac: &[u8]) -> ProtoResult<()> {
+        let expected = self.hmac(key, message)?;
+
+        if expected.len() == mac.len() && constant_time_eq(&expected, mac) {
+            Ok(())
+        } else {
+            Err("tsig mac does not match".into())
+        }
+    }
+
+    #[cfg(feature = "ring")]
+    fn hmac(&self, key: &[u8], message: &[u8]) -> ProtoResult<Vec<u8>> {
+        use ring::{digest, hmac};
+
+        let algorithm = match *self {
+            TsigAlgorithm::HmacSha256 => &digest::SHA256,
+            TsigAlgorithm::HmacSha384 => &digest::SHA384,
+            TsigAlgorithm::HmacSha512 => &digest::SHA512,
+            _ => return Err(format!("unsupported tsig algorithm: {}", self).into()),
+        };
+
+        let key = hmac::SigningKey::new(algorithm, key);
+        Ok(hmac::sign(&key, message).as_ref().to_vec())
+    }
+
+    #[cfg(all(not(feature = "ring"), feature = "openssl"))]
+    fn hmac(&self, key: &[u8], message: &[u8]) -> ProtoResult<Vec<u8>> {
+        use openssl::hash::MessageDigest;
+        use openssl::pkey::PKey;
+        use openssl::sign::Signer;
+
+        let digest = match *self {
+            TsigAlgorithm::HmacSha256 => MessageDigest::sha256(),
+            TsigAlgorithm::HmacSha384 => MessageDigest::sha384(),
+            TsigAlgorithm::HmacSha512 => MessageDigest::sha512(),
+            _ => return Err(format!("unsupported tsig algorithm: {}", self).into()),
+        };
+
+        let key = PKey::hmac(key)?;
+        let mut signer = Signer::new(digest, &key)?;
+        signer.update(message)?;
+        signer.sign_to_vec().map_err(|e| e.into())
+    }
+
+    #[cfg(not(any(feature = "ring", feature = "openssl")))]
+    fn hmac(&self, _key: &[u8], _message: &[u8]) -> ProtoResult<Vec<u8>> {
+        Err(format!("tsig requires the ring or openssl feature: {}", self).into())
+    }
+}
+
+impl fmt::Display for TsigAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_name())
+    }
+}
+
+impl BinEncodable for TsigAlgorithm {
+    fn emit(&self, encoder: &mut BinEncoder) -> ProtoResult<()> {
+        self.to_name().emit_as_canonical(encoder, true)
+    }
+}
+
+impl<'r> BinDecodable<'r> for TsigAlgorithm {
+    fn read(decoder: &mut BinDecoder<'r>) -> ProtoResult<Self> {
+        Name::read(decoder).map(TsigAlgorithm::from_name)
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+fn emit_time(encoder: &mut BinEncoder, time: u64) -> ProtoResult<()> {
+    encoder.emit_u16((time >> 32) as u16)?;
+    encoder.emit_u32(time as u32)
+}
+
+/// Read the RData from the given Decoder
+pub fn read(decoder: &mut BinDecoder, rdata_length: Restrict<u16>) -> ProtoResult<TSIG> {
+    let start_idx = decoder.index();
+
+    let algorithm = TsigAlgorithm::read(decoder)?;
+    let time_high = u64::from(decoder.read_u16()?.unverified(/*valid as any u16*/));
+    let time_low = u64::from(decoder.read_u32()?.unverified(/*valid as any u32*/));
+    let fudge = decoder.read_u16()?.unverified(/*valid as any u16*/);
+    let mac_len = decoder.read_u16()?.unverified(/*used only as length safely*/);
+    let mac = decoder
+        .read_vec(mac_len as usize)?
+        .unverified(/*will fail in verification if invalid*/);
+    let oid = decoder.read_u16()?.unverified(/*valid as any u16*/);
+    let error = decoder.read_u16()?.unverified(/*valid as any u16*/);
+    let other_len = decoder.read_u16()?.unverified(/*used only as length safely*/);
+    let other = decoder
+        .read_vec(other_len as usize)?
+        .unverified(/*only used for BADTIME*/);
+
+    rdata_length
+        .map(|u| u as usize)
+        .verify_unwrap(|u| *u == decoder.index() - start_idx)
+        .map_err(|_| ProtoError::from("invalid rdata length in TSIG"))?;
+
+    Ok(TSIG::new(
+        algorithm,
+        time_high << 32 | time_low,
+        fudge,
+        mac,
+        oid,
+        error,
+        other,
+    ))
+}
+
+/// Write the RData from the given Encoder
+pub fn emit(encoder: &mut BinEncoder, tsig: &TSIG) -> ProtoResult<()> {
+    tsig.algorithm.emit(encoder)?;
+    emit_time(encoder, tsig.time)?;
+    encoder.emit_u16(tsig.fudge)?;
+    encoder.emit_u16(tsig.mac.len() as u16)?;
+    encoder.emit_vec(&tsig.mac)?;
+    encoder.emit_u16(tsig.oid)?;
+    encoder.emit_u16(tsig.error)?;
+    encoder.emit_u16(tsig.other.len() as u16)?;
+    encoder.emit_vec(&tsig.other)?;
+    Ok(())
+}
+
+/// Returns the TSIG record to be appended to the additional section of a message
+pub fn make_tsig_record(key_name: Name, tsig: TSIG) -> Record {
+    let mut record = Record::from_rdata(key_name, 0, RData::DNSSEC(DNSSECRData::TSIG(tsig)));
+    record.set_dns_class(DNSClass::ANY);
+    record
+}
+
+/// Appends the TSIG record to the additional section of the encoded message
+pub fn append_tsig_record(message: &mut Vec<u8>, record: &Record) -> ProtoResult<()> {
+    if message.len() < 12 {
+        return Err("message is too short to be signed".into());
+    }
+
+    let additional_count = (u16::from(message[10]) << 8 | u16::from(message[11]))
+        .checked_add(1)
+        .ok_or_else(|| ProtoError::from("too many additional records"))?;
+
+    {
+        let offset = message.len() as u32;
+        let mut encoder = BinEncoder::with_offset(message, offset, EncodeMode::Normal);
+        record.emit(&mut encoder)?;
+    }
+
+    message[10..12].copy_from_slice(&additional_count.to_be_bytes());
+    Ok(())
+}
+
+/// Returns the data over which the MAC of a message is computed
+///
+/// # Arguments
+///
+/// * `previous_mac` - the MAC of the request for the first message of a response, the MAC of the
+///                    previous message for the following messages of a multi-message response
+/// * `message` - the encoded message, without the TSIG record
+/// * `pre_tsig` - the TSIG of the message, the MAC is ignored
+/// * `key_name` - name of the key
+/// * `first_message` - false for the following messages of a multi-message response
+pub fn message_tbs(
+    previous_mac: Option<&[u8]>,
+    message: &[u8],
+    pre_tsig: &TSIG,
+    key_name: &Name,
+    first_message: bool,
+) -> ProtoResult<Vec<u8>> {
+    let mut buf = Vec::with_capacity(message.len() + 128);
+    {
+        let mut encoder = BinEncoder::new(&mut buf);
+        if let Some(previous_mac) = previous_mac {
+            encoder.emit_u16(previous_mac.len() as u16)?;
+            encoder.emit_vec(previous_mac)?;
+        }
+        encoder.emit_vec(message)?;
+        pre_tsig.emit_tsig_for_mac(&mut encoder, key_name, first_message)?;
+    }
+
+    Ok(buf)
+}
+
+/// Returns the data over which the MAC of a received, TSIG signed, message is computed, and
+///  the TSIG record of the message
+///
+/// The TSIG record is removed from the message, and the ID is restored to the original ID. See
+///  `message_tbs` for the arguments. An error is returned if the final record of the message
+///  is not a TSIG.
+pub fn signed_bitmessage_to_buf(
+    previous_mac: Option<&[u8]>,
+    message: &[u8],
+    first_message: bool,
+) -> ProtoResult<(Vec<u8>, Record)> {
+    let mut decoder = BinDecoder::new(message);
+    let header = Header::read(&mut decoder)?;
+
+    if header.additional_count() == 0 {
+        return Err("message is not tsig signed".into());
+    }
+
+    for _ in 0..header.query_count() {
+        Query::read(&mut decoder)?;
+    }
+
+    let record_count = header.answer_count() as usize
+        + header.name_server_count() as usize
+        + header.additional_count() as usize;
+    for _ in 1..record_count {
+        Record::read(&mut decoder)?;
+    }
+
+    let start_tsig = decoder.index();
+    let record = Record::read(&mut decoder)?;
+    if !decoder.is_empty() {
+        return Err("tsig must be the final record of the message".into());
+    }
+
+    let tsig = match *record.rdata() {
+        RData::DNSSEC(DNSSECRData::TSIG(ref tsig))
+            if record.rr_type() == RecordType::DNSSEC(DNSSECRecordType::TSIG) =>
+        {
+            tsig
+        }
+        _ => return Err("message is not tsig signed".into()),
+    };
+
+    // the message as it was before the TSIG was added
+    let mut unsigned = message[..start_tsig].to_vec();
+    unsigned[0..2].copy_from_slice(&tsig.oid().to_be_bytes());
+    unsigned[10..12].copy_from_slice(&(header.additional_count() - 1).to_be_bytes());
+
+    let tbs = message_tbs(previous_mac, &unsigned, tsig, record.name(), first_message)?;
+
+    Ok((tbs, record))
+}
+
+#[test]
+fn test() {
+    use std::str::FromStr;
+
+    let rdata = TSIG::new(
+        TsigAlgorithm::HmacSha256,
+        0x0102_0304_0506,
+        300,
+        vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+        0x4242,
+        0,
+        vec![],
+    );
+
+    let mut bytes = Vec::new();
+    let mut encoder: BinEncoder = BinEncoder::new(&mut bytes);
+    assert!(emit(&mut encoder, &rdata).is_ok());
+    let bytes = encoder.into_bytes();
+
+    println!("bytes: {:?}", bytes);
+
+    let mut decoder: BinDecoder = BinDecoder::new(bytes);
+    let read_rdata = read(&mut decoder, Restrict::new(bytes.len() as u16));
+    assert!(
+        read_rdata.is_ok(),
+        format!("error decoding: {:?}", read_rdata.unwrap_err())
+    );
+    assert_eq!(rdata, read_rdata.unwrap());
+
+    assert_eq!(
+        TsigAlgorithm::from_name(Name::from_str("HMAC-SHA256").unwrap()),
+        TsigAlgorithm::HmacSha256
+    );
+    assert_eq!(
+        TsigAlgorithm::from_name(TsigAlgorithm::HmacMd5.to_name()),
+        TsigAlgorithm::HmacMd5
+    );
+}
+
+#[test]
+fn test_signed_bitmessage_to_buf() {
+    use op::Message;
+
+    let key_name = Name::from_ascii("tsig-key.").unwrap();
+    let mut message = Message::new();
+    message.set_id(0x1234).add_query(Query::query(
+        Name::from_ascii("example.com.").unwrap(),
+        RecordType::AXFR,
+    ));
+    let unsigned = message.to_vec().unwrap();
+
+    let tsig = TSIG::new(
+        TsigAlgorithm::HmacSha256,
+        42,
+        300,
+        vec![],
+        0x1234,
+        0,
+        vec![],
+    );
+    let expected = message_tbs(None, &unsigned, &tsig, &key_name, true).unwrap();
+
+    // the ID may have been changed by a forwarder, the original ID is signed
+    message.set_id(0x4321);
+    message.add_additional(make_tsig_record(key_name, tsig.set_mac(vec![1, 2, 3])));
+    let signed = message.to_vec().unwrap();
+
+    let (tbs, record) = signed_bitmessage_to_buf(None, &signed, true).unwrap();
+    assert_eq!(tbs, expected);
+    assert_eq!(record.dns_class(), DNSClass::ANY);
+}
@@ -1,4 +1,11 @@
 //! hash functions for DNSSec operations
+//!
+//! The to-be-signed data of a record set, `RRSIG_RDATA | RR(1) | RR(2)...` of
+//! [RFC 4034 section 3.1.8.1](https://tools.ietf.org/html/rfc4034#section-3.1.8.1), is the input
+//! of the signature of the RRSIG records. It is built by `rrset_tbs` from the fields of the
+//! RRSIG without its signature, and by `rrset_tbs_with_sig` and `RecordSet::tbs` from a SIG with
+//! an empty signature, which lets external signers, e.g. HSMs, sign the same bytes as the
+//! `Signer`.
 
 use super::rdata::{sig, DNSSECRData, SIG};
 use error::*;
@@ -7,6 +14,8 @@ use rr::{DNSClass, Name, RData, Record, RecordType};
 use serialize::binary::{BinEncodable, BinEncoder, EncodeMode};
 
 /// Data To Be Signed.
+///
+/// The bytes which are passed to the signing or verifying algorithm, see `AsRef<[u8]>`.
 pub struct TBS(Vec<u8>);
 
 impl<'a> From<&'a [u8]> for TBS {
@@ -58,6 +67,19 @@ pub fn message_tbs<M: BinEncodable>(message: &M, pre_sig0: &SIG) -> ProtoResult<
 
 /// Returns the to-be-signed serialization of the given record set.
 ///
+/// The records of `records` which do not match the `name`, `dns_class` and `type_covered` are
+/// skipped. The others are written in the canonical form of
+/// [RFC 4034 section 6.2](https://tools.ietf.org/html/rfc4034#section-6.2):
+///
+/// * the owner name is lowercased, and is the wildcard name when `num_labels` is less than the
+///   labels of `name`, RFC 4035 section 5.3.2
+/// * the TTL of each record is the `original_ttl`
+/// * the names in the RDATA of the types listed in RFC 4034 section 6.2 are lowercased, and no
+///   name is compressed
+///
+/// The records are ordered by their canonical RDATA and duplicate RDATA are only written once,
+/// RFC 4034 section 6.3.
+///
 /// # Arguments
 ///
 /// * `name` - RRset record name
@@ -75,7 +97,7 @@ pub fn message_tbs<M: BinEncodable>(message: &M, pre_sig0: &SIG) -> ProtoResult<
 ///
 /// the binary hash of the specified RRSet and associated information
 #[allow(clippy::too_many_arguments)]
-pub fn rrset_tbs(
+pub fn rrset_tbs<'r, R: IntoIterator<Item = &'r Record>>(
     name: &Name,
     dns_class: DNSClass,
     num_labels: u8,
@@ -86,26 +108,38 @@ pub fn rrset_tbs(
     sig_inception: u32,
     key_tag: u16,
     signer_name: &Name,
-    records: &[Record],
+    records: R,
 ) -> ProtoResult<TBS> {
-    // TODO: change this to a BTreeSet so that it's preordered, no sort necessary
-    let mut rrset: Vec<&Record> = Vec::new();
-
-    // collect only the records for this rrset
+    // collect the canonical RDATA of the records of this rrset
+    let mut rdatas: Vec<Vec<u8>> = Vec::new();
     for record in records {
-        if dns_class == record.dns_class()
-            && type_covered == record.rr_type()
-            && name == record.name()
+        if dns_class != record.dns_class()
+            || type_covered != record.rr_type()
+            || name != record.name()
+        {
+            continue;
+        }
+
+        let mut rdata_buf = Vec::new();
         {
-            rrset.push(record);
+            let mut rdata_encoder = BinEncoder::new(&mut rdata_buf);
+            rdata_encoder.set_canonical_names(true);
+            record.rdata().emit(&mut rdata_encoder)?;
         }
+
+        if rdata_buf.len() > usize::from(u16::max_value()) {
+            return Err(format!("rdata of {} is too long to sign", record.name()).into());
+        }
+        rdatas.push(rdata_buf);
     }
 
-    // put records in canonical order
-    rrset.sort();
+    // put records in canonical order, RFC 4034 section 6.3, which are the sorted RDATA without
+    //  duplicates as all the other fields are the same
+    rdatas.sort();
+    rdatas.dedup();
 
     let name: Name = if let Some(name) = determine_name(name, num_labels) {
-        name
+        name.to_lowercase()
     } else {
         return Err(format!("could not determine name from {}", name).into());
     };
@@ -124,52 +158,39 @@ pub fn rrset_tbs(
         //             RRSIG_RDATA is the wire format of the RRSIG RDATA fields
         //                with the Signature field excluded and the Signer's Name
         //                in canonical form.
-        assert!(
-            sig::emit_pre_sig(
-                &mut encoder,
-                type_covered,
-                algorithm,
-                name.num_labels(),
-                original_ttl,
-                sig_expiration,
-                sig_inception,
-                key_tag,
-                signer_name,
-            ).is_ok()
-        );
+        sig::emit_pre_sig(
+            &mut encoder,
+            type_covered,
+            algorithm,
+            name.num_labels(),
+            original_ttl,
+            sig_expiration,
+            sig_inception,
+            key_tag,
+            &signer_name.to_lowercase(),
+        )?;
 
         // construct the rrset signing data
-        for record in rrset {
+        for rdata in rdatas {
             //             RR(i) = name | type | class | OrigTTL | RDATA length | RDATA
             //
             //                name is calculated according to the function in the RFC 4035
-            assert!(
-                name.to_lowercase()
-                    .emit_as_canonical(&mut encoder, true)
-                    .is_ok()
-            );
+            name.emit_as_canonical(&mut encoder, true)?;
             //
             //                type is the RRset type and all RRs in the class
-            assert!(type_covered.emit(&mut encoder).is_ok());
+            type_covered.emit(&mut encoder)?;
             //
             //                class is the RRset's class
-            assert!(dns_class.emit(&mut encoder).is_ok());
+            dns_class.emit(&mut encoder)?;
             //
             //                OrigTTL is the value from the RRSIG Original TTL field
-            assert!(encoder.emit_u32(original_ttl).is_ok());
+            encoder.emit_u32(original_ttl)?;
             //
             //                RDATA length
-            // TODO: add support to the encoder to set a marker to go back and write the length
-            let mut rdata_buf = Vec::new();
-            {
-                let mut rdata_encoder = BinEncoder::new(&mut rdata_buf);
-                rdata_encoder.set_canonical_names(true);
-                assert!(record.rdata().emit(&mut rdata_encoder).is_ok());
-            }
-            assert!(encoder.emit_u16(rdata_buf.len() as u16).is_ok());
+            encoder.emit_u16(rdata.len() as u16)?;
             //
             //                All names in the RDATA field are in canonical form (set above)
-            assert!(encoder.emit_vec(&rdata_buf).is_ok());
+            encoder.emit_vec(&rdata)?;
         }
     }
 
@@ -187,7 +208,10 @@ pub fn rrset_tbs(
 /// # Return
 ///
 /// binary hash of the RRSet with the information from the RRSIG record
-pub fn rrset_tbs_with_rrsig(rrsig: &Record, records: &[Record]) -> ProtoResult<TBS> {
+pub fn rrset_tbs_with_rrsig<'r, R: IntoIterator<Item = &'r Record>>(
+    rrsig: &Record,
+    records: R,
+) -> ProtoResult<TBS> {
     if let RData::DNSSEC(DNSSECRData::SIG(ref sig)) = *rrsig.rdata() {
         rrset_tbs_with_sig(rrsig.name(), rrsig.dns_class(), sig, records)
     } else {
@@ -208,11 +232,11 @@ pub fn rrset_tbs_with_rrsig(rrsig: &Record, records: &[Record]) -> ProtoResult<T
 /// # Return
 ///
 /// binary hash of the RRSet with the information from the RRSIG record
-pub fn rrset_tbs_with_sig(
+pub fn rrset_tbs_with_sig<'r, R: IntoIterator<Item = &'r Record>>(
     name: &Name,
     dns_class: DNSClass,
     sig: &SIG,
-    records: &[Record],
+    records: R,
 ) -> ProtoResult<TBS> {
     rrset_tbs(
         name,
@@ -293,3 +317,107 @@ pub fn determine_name(name: &Name, num_labels: u8) -> Option<Name> {
     // TODO: this should be an error
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn test_rrset_tbs_canonical_form() {
+        let name = Name::from_str("WWW.Example.com.").unwrap();
+        let record = |address: [u8; 4], ttl: u32| {
+            Record::from_rdata(name.clone(), ttl, RData::A(Ipv4Addr::from(address)))
+        };
+
+        // out of order, a duplicate, other TTLs and a record of another type
+        let records = vec![
+            record([192, 0, 2, 2], 60),
+            record([192, 0, 2, 1], 7200),
+            record([192, 0, 2, 1], 3600),
+            Record::from_rdata(name.clone(), 3600, RData::CNAME(name.clone())),
+        ];
+
+        let tbs = rrset_tbs(
+            &name,
+            DNSClass::IN,
+            3,
+            RecordType::A,
+            Algorithm::RSASHA256,
+            3600,
+            2,
+            1,
+            0x1234,
+            &Name::from_str("Example.COM.").unwrap(),
+            &records,
+        )
+        .expect("failed to serialize");
+
+        let owner: &[u8] = b"\x03www\x07example\x03com\x00";
+        let mut expected: Vec<u8> = vec![
+            0, 1, // type covered
+            8, // algorithm
+            3, // labels
+            0, 0, 0x0e, 0x10, // original TTL
+            0, 0, 0, 2, // expiration
+            0, 0, 0, 1, // inception
+            0x12, 0x34, // key tag
+        ];
+        expected.extend_from_slice(b"\x07example\x03com\x00");
+        for last in &[1, 2] {
+            expected.extend_from_slice(owner);
+            expected.extend_from_slice(&[0, 1, 0, 1, 0, 0, 0x0e, 0x10, 0, 4, 192, 0, 2, *last]);
+        }
+
+        assert_eq!(tbs.as_ref(), &expected[..]);
+    }
+
+    #[test]
+    fn test_rrset_tbs_wildcard() {
+        let name = Name::from_str("a.b.example.com.").unwrap();
+        let records = vec![Record::from_rdata(
+            name.clone(),
+            3600,
+            RData::A(Ipv4Addr::new(192, 0, 2, 1)),
+        )];
+
+        // the signature of *.example.com. covers the expanded name
+        let tbs = rrset_tbs(
+            &name,
+            DNSClass::IN,
+            2,
+            RecordType::A,
+            Algorithm::RSASHA256,
+            3600,
+            2,
+            1,
+            0x1234,
+            &Name::from_str("example.com.").unwrap(),
+            &records,
+        )
+        .expect("failed to serialize");
+
+        let wildcard: &[u8] = b"\x01*\x07example\x03com\x00";
+        let rr_start = tbs.as_ref().len() - (wildcard.len() + 14);
+        assert_eq!(tbs.as_ref()[3], 2);
+        assert_eq!(&tbs.as_ref()[rr_start..rr_start + wildcard.len()], wildcard);
+
+        // more labels than the name are invalid
+        assert!(rrset_tbs(
+            &name,
+            DNSClass::IN,
+            5,
+            RecordType::A,
+            Algorithm::RSASHA256,
+            3600,
+            2,
+            1,
+            0x1234,
+            &Name::from_str("example.com.").unwrap(),
+            &records,
+        )
+        .is_err());
+    }
+}
@@ -9,6 +9,7 @@
 //!
 //! A label is stored internally as ascii, where all unicode characters are converted to punycode interenally.
 
+#[cfg(feature = "std")]
 #[allow(clippy::useless_attribute)]
 #[allow(unused)]
 #[allow(deprecated)]
@@ -18,12 +19,16 @@ use std::cmp::{Ordering, PartialEq};
 use std::fmt::{self, Debug, Display, Formatter, Write};
 use std::hash::{Hash, Hasher};
 use std::sync::Arc as Rc;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
 
+#[cfg(feature = "std")]
 use idna::uts46;
 
 use error::*;
 
 const WILDCARD: &[u8] = b"*";
+#[cfg(feature = "std")]
 const IDNA_PREFIX: &[u8] = b"xn--";
 
 /// Labels are always stored as ASCII, unicode characters must be encoded with punycode
@@ -43,6 +48,8 @@ impl Label {
     }
 
     /// Translates this string into IDNA safe name, encoding to punycode as necessary.
+    ///
+    /// Without the `std` feature there is no IDNA, the string must be ascii, see `from_ascii`.
     pub fn from_utf8(s: &str) -> ProtoResult<Self> {
         if s.as_bytes() == WILDCARD {
             return Ok(Label::wildcard());
@@ -53,6 +60,12 @@ impl Label {
             return Self::from_ascii(s);
         }
 
+        Self::from_idna(s)
+    }
+
+    /// Encodes the string to punycode
+    #[cfg(feature = "std")]
+    fn from_idna(s: &str) -> ProtoResult<Self> {
         match uts46::to_ascii(
             s,
             uts46::Flags {
@@ -173,14 +186,14 @@ impl Label {
             f: &mut W,
             is_first: bool,
         ) -> Result<(), fmt::Error> {
-            let to_triple_escape = |ch: u8| format!("\\{:03o}", ch);
-            let to_single_escape = |ch: char| format!("\\{}", ch);
-
             match char::from(byte) {
                 c if is_safe_ascii(c, is_first, true) => f.write_char(c)?,
                 // it's not a control and is printable as well as inside the standard ascii range
-                c if byte > b'\x20' && byte < b'\x7f' => f.write_str(&to_single_escape(c))?,
-                _ => f.write_str(&to_triple_escape(byte))?,
+                c if byte > b'\x20' && byte < b'\x7f' => {
+                    f.write_char('\\')?;
+                    f.write_char(c)?
+                }
+                _ => write!(f, "\\{:03o}", byte)?,
             }
 
             Ok(())
@@ -198,6 +211,73 @@ impl Label {
 
         Ok(())
     }
+
+    /// Writes this label for logs, IDNA labels are decoded to unicode within `[` and `]` if
+    ///  `decode_idna` is true, see `Name::escaped`
+    pub(crate) fn write_escaped<W: Write>(
+        &self,
+        f: &mut W,
+        decode_idna: bool,
+    ) -> Result<(), fmt::Error> {
+        if decode_idna {
+            if let Some(label) = self.to_unicode() {
+                f.write_char('[')?;
+                f.write_str(&label)?;
+                return f.write_char(']');
+            }
+        }
+
+        self.write_ascii(f)
+    }
+
+    /// Decodes an IDNA, punycode, label to unicode
+    ///
+    /// Returns `None` if this isn't an IDNA label, it fails to decode, or the unicode has
+    ///  characters which aren't printable, e.g. controls or bidirectional overrides.
+    #[cfg(feature = "std")]
+    fn to_unicode(&self) -> Option<String> {
+        if !self.as_bytes().starts_with(IDNA_PREFIX) {
+            return None;
+        }
+
+        // this should never be outside the ascii codes...
+        let label = String::from_utf8_lossy(self.borrow());
+        let (label, e) = uts46::to_unicode(
+            &label,
+            uts46::Flags {
+                use_std3_ascii_rules: false,
+                transitional_processing: false,
+                verify_dns_length: false,
+            },
+        );
+
+        if let Err(e) = e {
+            debug!(
+                "xn-- prefixed string did not translate via IDNA properly: {:?}",
+                e
+            );
+            return None;
+        }
+
+        if !label.chars().all(is_printable_unicode) {
+            debug!("xn-- prefixed string has unprintable characters");
+            return None;
+        }
+
+        Some(label)
+    }
+
+    /// Without std there is no IDNA, the string must be ascii
+    #[cfg(not(feature = "std"))]
+    fn from_idna(s: &str) -> ProtoResult<Self> {
+        Self::from_ascii(s)
+    }
+
+    /// Without std IDNA labels aren't decoded, they are written as ascii
+    #[cfg(not(feature = "std"))]
+    fn to_unicode(&self) -> Option<String> {
+        None
+    }
 }
 
 impl AsRef<[u8]> for Label {
@@ -224,36 +304,34 @@ fn is_safe_ascii(c: char, is_first: bool, for_encoding: bool) -> bool {
     }
 }
 
+/// Unicode which is displayed as is, without controls, invisible or bidirectional formatting
+///  characters, or characters which are escaped in ascii labels
+#[cfg(feature = "std")]
+fn is_printable_unicode(c: char) -> bool {
+    match c {
+        c if c.is_control() || c.is_whitespace() => false,
+        '\u{200B}'..='\u{200F}'
+        | '\u{202A}'..='\u{202E}'
+        | '\u{2060}'..='\u{2064}'
+        | '\u{2066}'..='\u{2069}'
+        | '\u{FEFF}' => false,
+        '.' | '\\' | '[' | ']' => false,
+        _ => true,
+    }
+}
+
 impl Display for Label {
     /// outputs characters in a safe string manner.
     ///
-    /// if the string is punycode, i.e. starts with `xn--`, otherwise it translates to a safe ascii string
-    ///   escaping characters as necessary.
+    /// if the string is punycode, i.e. starts with `xn--`, it is decoded to unicode unless it has
+    ///   unprintable characters, otherwise it translates to a safe ascii string escaping
+    ///   characters as necessary.
     fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
-        if self.as_bytes().starts_with(IDNA_PREFIX) {
-            // this should never be outside the ascii codes...
-            let label = String::from_utf8_lossy(self.borrow());
-            let (label, e) = uts46::to_unicode(
-                &label,
-                uts46::Flags {
-                    use_std3_ascii_rules: false,
-                    transitional_processing: false,
-                    verify_dns_length: false,
-                },
-            );
-
-            if e.is_ok() {
-                return f.write_str(&label);
-            } else {
-                debug!(
-                    "xn-- prefixed string did not translate via IDNA properly: {:?}",
-                    e
-                )
-            }
+        match self.to_unicode() {
+            Some(label) => f.write_str(&label),
+            // it wasn't known to be utf8
+            None => self.write_ascii(f),
         }
-
-        // it wasn't known to be utf8
-        self.write_ascii(f)
     }
 }
 
@@ -364,6 +442,7 @@ mod tests {
     use super::*;
 
     #[test]
+    #[cfg(feature = "std")]
     fn test_encoding() {
         assert_eq!(
             Label::from_utf8("abc").unwrap(),
@@ -391,6 +470,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "std")]
     fn test_decoding() {
         assert_eq!(Label::from_raw_bytes(b"abc").unwrap().to_string(), "abc");
         assert_eq!(
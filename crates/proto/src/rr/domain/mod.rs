@@ -10,8 +10,9 @@
 mod label;
 mod name;
 mod try_parse_ip;
+#[cfg(feature = "std")]
 pub mod usage;
 
 pub use self::label::{IntoLabel, Label};
-pub use self::name::{IntoName, Name};
+pub use self::name::{EscapedName, IntoName, Name};
 pub use self::try_parse_ip::TryParseIp;
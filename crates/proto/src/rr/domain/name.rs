@@ -16,9 +16,12 @@ use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::ops::Index;
 use std::slice::Iter;
 use std::str::FromStr;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
 
 use error::*;
 use rr::domain::label::{CaseInsensitive, CaseSensitive, IntoLabel, Label, LabelCmp};
+#[cfg(feature = "std")]
 use rr::domain::usage::LOCALHOST as LOCALHOST_usage;
 #[cfg(feature = "serde-config")]
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
@@ -462,6 +465,7 @@ impl Name {
     /// # Examples
     ///
     /// ```
+    /// # #[cfg(feature = "std")] {
     /// use std::str::FromStr;
     /// use trust_dns_proto::rr::Name;
     ///
@@ -473,6 +477,7 @@ impl Name {
     ///
     /// assert!(!bytes_name.eq_case(&utf8_name));
     /// assert!(lower_name.eq_case(&utf8_name));
+    /// # }
     /// ```
     pub fn from_utf8<S: AsRef<str>>(name: S) -> ProtoResult<Self> {
         Self::from_encoded_str::<LabelEncUtf8>(name.as_ref(), None)
@@ -491,6 +496,7 @@ impl Name {
     /// assert!(Name::from_utf8("_allows.example.com.").is_ok());
     ///
     /// // Error, underscore in the end
+    /// # #[cfg(feature = "std")]
     /// assert!(Name::from_utf8("dis_allowed.example.com.").is_err());
     ///
     /// // Ok, relaxed mode
@@ -718,20 +724,53 @@ impl Name {
         format!("{}", self)
     }
 
+    /// Returns a `Display` of the name which is safe to write to logs
+    ///
+    /// Names of requests are chosen by the client, all the bytes which aren't printable ascii are
+    ///  escaped, as with `to_ascii`, and the name is written to the formatter without allocating.
+    ///  See `EscapedName::decode_idna` to display the IDNA labels as unicode.
+    ///
+    /// ```
+    /// use trust_dns_proto::rr::domain::{Label, Name};
+    ///
+    /// let name = Name::from_labels(vec![Label::from_raw_bytes(b"www\x1b[2J").unwrap()]).unwrap();
+    /// assert_eq!(name.escaped().to_string(), "www\\033\\[2J.");
+    ///
+    /// # #[cfg(feature = "std")] {
+    /// let name = Name::from_utf8("www.bücher.de.").unwrap();
+    /// assert_eq!(name.escaped().to_string(), "www.xn--bcher-kva.de.");
+    /// assert_eq!(name.escaped().decode_idna(true).to_string(), "www.[bücher].de.");
+    /// # }
+    /// ```
+    pub fn escaped(&self) -> EscapedName {
+        EscapedName {
+            name: self,
+            decode_idna: false,
+        }
+    }
+
     fn write_labels<W: Write, E: LabelEnc>(&self, f: &mut W) -> Result<(), fmt::Error> {
+        self.write_labels_with(f, E::write_label)
+    }
+
+    fn write_labels_with<W, F>(&self, f: &mut W, write_label: F) -> Result<(), fmt::Error>
+    where
+        W: Write,
+        F: Fn(&mut W, &Label) -> Result<(), fmt::Error>,
+    {
         let mut iter = self.labels.iter();
         if let Some(label) = iter.next() {
-            E::write_label(f, label)?;
+            write_label(f, label)?;
         }
 
         for label in iter {
-            write!(f, ".")?;
-            E::write_label(f, label)?;
+            f.write_char('.')?;
+            write_label(f, label)?;
         }
 
         // if it was the root name
         if self.is_root() || self.is_fqdn() {
-            write!(f, ".")?;
+            f.write_char('.')?;
         }
         Ok(())
     }
@@ -753,6 +792,7 @@ impl Name {
     /// let name = Name::from_str("my.localhost.").unwrap();
     /// assert!(name.is_localhost());
     /// ```
+    #[cfg(feature = "std")]
     pub fn is_localhost(&self) -> bool {
         LOCALHOST_usage.zone_of(self)
     }
@@ -830,6 +870,33 @@ impl LabelEnc for LabelEncUtf8 {
     }
 }
 
+/// A `Display` of a `Name` which is safe to write to logs, see `Name::escaped`
+#[derive(Clone, Copy)]
+pub struct EscapedName<'a> {
+    name: &'a Name,
+    decode_idna: bool,
+}
+
+impl<'a> EscapedName<'a> {
+    /// If true, IDNA labels are displayed as unicode within `[` and `]`, which are always escaped
+    ///  in other labels
+    ///
+    /// Labels which fail to decode, or have unicode which isn't printable, e.g. bidirectional
+    ///  overrides, are displayed as ascii.
+    pub fn decode_idna(mut self, decode_idna: bool) -> Self {
+        self.decode_idna = decode_idna;
+        self
+    }
+}
+
+impl<'a> fmt::Display for EscapedName<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let decode_idna = self.decode_idna;
+        self.name
+            .write_labels_with(f, |f, label| label.write_escaped(f, decode_idna))
+    }
+}
+
 /// An iterator over labels in a name
 pub struct LabelIter<'a>(Iter<'a, Label>);
 
@@ -1246,18 +1313,24 @@ mod tests {
     use serialize::binary::*;
 
     fn get_data() -> Vec<(Name, Vec<u8>)> {
-        vec![
+        #[allow(unused_mut)]
+        let mut data = vec![
             (Name::new(), vec![0]),                           // base case, only the root
             (Name::from_str("a").unwrap(), vec![1, b'a', 0]), // a single 'a' label
             (
                 Name::from_str("a.bc").unwrap(),
                 vec![1, b'a', 2, b'b', b'c', 0],
             ), // two labels, 'a.bc'
-            (
-                Name::from_str("a.♥").unwrap(),
-                vec![1, b'a', 7, b'x', b'n', b'-', b'-', b'g', b'6', b'h', 0],
-            ), // two labels utf8, 'a.♥'
-        ]
+        ];
+
+        // two labels utf8, 'a.♥', IDNA needs std
+        #[cfg(feature = "std")]
+        data.push((
+            Name::from_str("a.♥").unwrap(),
+            vec![1, b'a', 7, b'x', b'n', b'-', b'-', b'g', b'6', b'h', 0],
+        ));
+
+        data
     }
 
     #[test]
@@ -1566,6 +1639,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "std")]
     fn test_from_utf8() {
         let bytes_name = Name::from_labels(vec![b"WWW" as &[u8], b"example", b"COM"]).unwrap();
         let utf8_name = Name::from_utf8("WWW.example.COM.").unwrap();
@@ -1597,6 +1671,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "std")]
     fn test_encoding() {
         assert_eq!(
             Name::from_ascii("WWW.example.COM.").unwrap().to_ascii(),
@@ -1642,4 +1717,58 @@ mod tests {
         Name::from_str_relaxed("mid_dle.example.com").expect("failed in the middle");
         Name::from_str_relaxed("end_.example.com").expect("failed at the end");
     }
+
+    #[test]
+    fn test_escaped() {
+        let name = Name::from_labels(vec![
+            Label::from_raw_bytes(b"a\x00b\n").unwrap(),
+            Label::from_raw_bytes(b"c.d").unwrap(),
+        ])
+        .unwrap();
+        assert_eq!(name.escaped().to_string(), "a\\000b\\012.c\\.d.");
+        assert_eq!(name.escaped().to_string(), name.to_ascii());
+
+        assert_eq!(Name::root().escaped().to_string(), ".");
+        assert_eq!(
+            Name::from_ascii("www.example.com.")
+                .unwrap()
+                .escaped()
+                .to_string(),
+            "www.example.com."
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_escaped_decode_idna() {
+        let name = Name::from_utf8("♥.example.com.").unwrap();
+        assert_eq!(name.escaped().to_string(), "xn--g6h.example.com.");
+        assert_eq!(
+            name.escaped().decode_idna(true).to_string(),
+            "[♥].example.com."
+        );
+
+        let name = Name::from_labels(vec![Label::from_raw_bytes(b"[a]").unwrap()]).unwrap();
+        assert_eq!(name.escaped().decode_idna(true).to_string(), "\\[a\\].");
+    }
+
+    #[test]
+    fn test_escaped_unprintable_idna() {
+        // punycode of a right-to-left override followed by "abc"
+        let label = Label::from_raw_bytes(b"xn--abc-4q0a").unwrap();
+        let name = Name::from_labels(vec![label]).unwrap();
+        assert_eq!(name.to_utf8(), "xn--abc-4q0a.");
+        assert_eq!(
+            name.escaped().decode_idna(true).to_string(),
+            "xn--abc-4q0a."
+        );
+
+        let label = Label::from_raw_bytes(b"xn--\x1b[31m-").unwrap();
+        let name = Name::from_labels(vec![label]).unwrap();
+        assert_eq!(name.to_utf8(), "xn--\\033\\[31m-.");
+        assert_eq!(
+            name.escaped().decode_idna(true).to_string(),
+            "xn--\\033\\[31m-."
+        );
+    }
 }
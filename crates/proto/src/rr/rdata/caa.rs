@@ -21,6 +21,8 @@
 //! ```
 
 use std::str;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
 
 use error::*;
 use rr::domain::Name;
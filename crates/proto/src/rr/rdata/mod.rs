@@ -21,6 +21,7 @@
 // each of these module's has the parser for that rdata embedded, to keep the file sizes down...
 pub mod a;
 pub mod aaaa;
+#[cfg(feature = "std")]
 pub mod caa;
 pub mod mx;
 pub mod name;
@@ -34,6 +35,7 @@ pub mod sshfp;
 pub mod tlsa;
 pub mod txt;
 
+#[cfg(feature = "std")]
 pub use self::caa::CAA;
 pub use self::mx::MX;
 pub use self::naptr::NAPTR;
@@ -44,4 +46,4 @@ pub use self::soa::SOA;
 pub use self::srv::SRV;
 pub use self::sshfp::SSHFP;
 pub use self::tlsa::TLSA;
-pub use self::txt::TXT;
+pub use self::txt::{DnsSdAttribute, TXT};
@@ -7,8 +7,13 @@
 
 //! Dynamic Delegation Discovery System
 
+use std::fmt;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
 use error::*;
 use rr::domain::Name;
+use rr::rdata::txt::write_character_string;
 use serialize::binary::*;
 
 /// [RFC 3403 DDDS DNS Database, October 2002](https://tools.ietf.org/html/rfc3403#section-4)
@@ -190,6 +195,23 @@ impl NAPTR {
     }
 }
 
+/// Writes the NAPTR data in the zone file format, the character-strings quoted and escaped
+///
+/// ```text
+/// 100 50 "s" "http+N2L+N2C+N2R" "" www.example.com.
+/// ```
+impl fmt::Display for NAPTR {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} ", self.order, self.preference)?;
+        write_character_string(f, &self.flags)?;
+        f.write_str(" ")?;
+        write_character_string(f, &self.services)?;
+        f.write_str(" ")?;
+        write_character_string(f, &self.regexp)?;
+        write!(f, " {}", self.replacement)
+    }
+}
+
 /// verifies that the flags are valid
 pub fn verify_flags(flags: &[u8]) -> bool {
     flags.iter().all(|c| match c {
@@ -286,3 +308,22 @@ pub fn test_bad_data() {
         "should have failed decoding with bad flag data"
     );
 }
+
+#[test]
+fn test_display() {
+    use std::str::FromStr;
+
+    let rdata = NAPTR::new(
+        100,
+        50,
+        b"s".to_vec().into_boxed_slice(),
+        b"http+N2L+N2C+N2R".to_vec().into_boxed_slice(),
+        b"!^.*$!\"\\1\"!".to_vec().into_boxed_slice(),
+        Name::from_str("www.example.com.").unwrap(),
+    );
+
+    assert_eq!(
+        rdata.to_string(),
+        r#"100 50 "s" "http+N2L+N2C+N2R" "!^.*$!\"\\1\"!" www.example.com."#
+    );
+}
@@ -16,6 +16,9 @@
 
 //! null record type, generally not used except as an internal tool for representing null data
 
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
 use error::*;
 use serialize::binary::*;
 
@@ -7,6 +7,9 @@
 
 //! OPENPGPKEY records for OpenPGP public keys
 
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
 use error::*;
 use serialize::binary::*;
 
@@ -16,9 +16,24 @@
 
 //! option record for passing protocol options between the client and server
 
+use std::any::Any;
+use std::cmp::Ordering;
+// without std the options are ordered by their code
+#[cfg(not(feature = "std"))]
+use std::collections::BTreeMap as HashMap;
+#[cfg(feature = "std")]
 use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::{Hash, Hasher};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::Arc;
+#[cfg(feature = "std")]
+use std::sync::RwLock;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
 
 use error::*;
+use rr::domain::Name;
 use serialize::binary::*;
 
 #[cfg(feature = "dnssec")]
@@ -219,13 +234,19 @@ pub fn read(decoder: &mut BinDecoder, rdata_length: Restrict<u16>) -> ProtoResul
                     .map(|u| u as usize)
                     .verify_unwrap(|u| *u <= rdata_length)
                     .map_err(|_| ProtoError::from("OPT value length exceeds rdata length"))?;
-                state = OptReadState::Data {
-                    code,
-                    length,
-                    // TODO: this cean be replaced with decoder.read_vec(), right?
-                    //  the current version allows for malformed opt to be skipped...
-                    collected: Vec::<u8>::with_capacity(length),
-                };
+                if length == 0 {
+                    // options without data, e.g. NSID and EXPIRE in queries
+                    options.insert(code, (code, &[] as &[u8]).into());
+                    state = OptReadState::ReadCode;
+                } else {
+                    state = OptReadState::Data {
+                        code,
+                        length,
+                        // TODO: this cean be replaced with decoder.read_vec(), right?
+                        //  the current version allows for malformed opt to be skipped...
+                        collected: Vec::<u8>::with_capacity(length),
+                    };
+                }
             }
             OptReadState::Data {
                 code,
@@ -261,7 +282,11 @@ pub fn read(decoder: &mut BinDecoder, rdata_length: Restrict<u16>) -> ProtoResul
 
 /// Write the RData from the given Decoder
 pub fn emit(encoder: &mut BinEncoder, opt: &OPT) -> ProtoResult<()> {
-    for (edns_code, edns_option) in opt.options().iter() {
+    // in the order of their codes, not of the map, for the same options to encode the same
+    let mut options: Vec<_> = opt.options().iter().collect();
+    options.sort_by_key(|&(edns_code, _)| u16::from(*edns_code));
+
+    for (edns_code, edns_option) in options {
         encoder.emit_u16(u16::from(*edns_code))?;
         encoder.emit_u16(edns_option.len())?;
         edns_option.emit(encoder)?
@@ -283,7 +308,7 @@ enum OptReadState {
 }
 
 /// The code of the EDNS data option
-#[derive(Hash, Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Hash, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum EdnsCode {
     /// [RFC 6891, Reserved](https://tools.ietf.org/html/rfc6891)
     Zero,
@@ -324,6 +349,9 @@ pub enum EdnsCode {
     /// [draft-ietf-dnsop-edns-chain-query](https://tools.ietf.org/html/draft-ietf-dnsop-edns-chain-query-07)
     Chain,
 
+    /// [RFC 8145, edns-key-tag](https://tools.ietf.org/html/rfc8145)
+    KeyTag,
+
     /// Unknown, used to deal with unknown or unsupported codes
     Unknown(u16),
 }
@@ -346,6 +374,7 @@ impl From<u16> for EdnsCode {
             11 => EdnsCode::Keepalive,
             12 => EdnsCode::Padding,
             13 => EdnsCode::Chain,
+            14 => EdnsCode::KeyTag,
             _ => EdnsCode::Unknown(value),
         }
     }
@@ -368,6 +397,7 @@ impl From<EdnsCode> for u16 {
             EdnsCode::Keepalive => 11,
             EdnsCode::Padding => 12,
             EdnsCode::Chain => 13,
+            EdnsCode::KeyTag => 14,
             EdnsCode::Unknown(value) => value,
         }
     }
@@ -375,11 +405,24 @@ impl From<EdnsCode> for u16 {
 
 /// options used to pass information about capabilities between client and server
 ///
-/// `note: Not all EdnsOptions are supported at this time.`
+/// The options with codes assigned by IANA are decoded into their variants. Options which fail to
+///  decode, and options with unassigned codes, are `Unknown` and are emitted unchanged. Parsers for
+///  other codes, e.g. of the local or experimental use range, are added with
+///  `register_option_parser`.
 ///
-/// http://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#dns-parameters-13
+/// http://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#dns-parameters-11
 #[derive(Debug, PartialOrd, PartialEq, Eq, Clone, Hash)]
 pub enum EdnsOption {
+    /// [LLQ On-hold](http://files.dns-sd.org/draft-sekar-dns-llq.txt)
+    LLQ(LongLivedQuery),
+
+    /// [UL On-hold](http://files.dns-sd.org/draft-sekar-dns-ul.txt)
+    UL(UpdateLease),
+
+    /// [RFC 5001, NSID](https://tools.ietf.org/html/rfc5001), the identifier of the name server,
+    ///  empty in queries
+    NSID(Vec<u8>),
+
     /// [RFC 6975, DNSSEC Algorithm Understood](https://tools.ietf.org/html/rfc6975)
     #[cfg(feature = "dnssec")]
     DAU(SupportedAlgorithms),
@@ -392,6 +435,33 @@ pub enum EdnsOption {
     #[cfg(feature = "dnssec")]
     N3U(SupportedAlgorithms),
 
+    /// [RFC 7871, Client Subnet](https://tools.ietf.org/html/rfc7871)
+    Subnet(ClientSubnet),
+
+    /// [RFC 7314, EDNS EXPIRE](https://tools.ietf.org/html/rfc7314), the expire timer of the zone
+    ///  in seconds, `None` in queries
+    Expire(Option<u32>),
+
+    /// [RFC 7873, DNS Cookies](https://tools.ietf.org/html/rfc7873)
+    Cookie(Cookie),
+
+    /// [RFC 7828, edns-tcp-keepalive](https://tools.ietf.org/html/rfc7828), the idle timeout in
+    ///  units of 100 milliseconds, `None` in queries
+    Keepalive(Option<u16>),
+
+    /// [RFC 7830, Padding](https://tools.ietf.org/html/rfc7830), the number of zero bytes of padding
+    Padding(u16),
+
+    /// [RFC 7901, CHAIN Query Requests](https://tools.ietf.org/html/rfc7901), the closest trust point
+    Chain(Name),
+
+    /// [RFC 8145, edns-key-tag](https://tools.ietf.org/html/rfc8145), the key tags of the trust
+    ///  anchors of the resolver
+    KeyTag(Vec<u16>),
+
+    /// An option decoded by a parser registered with `register_option_parser`
+    Custom(CustomOption),
+
     /// Unknown, used to deal with unknown or unsupported codes
     Unknown(u16, Vec<u8>),
 }
@@ -400,79 +470,598 @@ impl EdnsOption {
     /// Returns the length in bytes of the EdnsOption
     pub fn len(&self) -> u16 {
         match *self {
+            EdnsOption::LLQ(..) => 18,
+            EdnsOption::UL(ref lease) => 4 + lease.key_lease().map_or(0, |_| 4),
+            EdnsOption::NSID(ref data) => data.len() as u16,
             #[cfg(feature = "dnssec")]
             EdnsOption::DAU(ref algorithms)
             | EdnsOption::DHU(ref algorithms)
             | EdnsOption::N3U(ref algorithms) => algorithms.len(),
+            EdnsOption::Subnet(ref subnet) => 4 + subnet.address_len() as u16,
+            EdnsOption::Expire(expire) => expire.map_or(0, |_| 4),
+            EdnsOption::Cookie(ref cookie) => 8 + cookie.server().len() as u16,
+            EdnsOption::Keepalive(timeout) => timeout.map_or(0, |_| 2),
+            EdnsOption::Padding(len) => len,
+            EdnsOption::Chain(ref name) => {
+                name.iter().map(|label| label.len() as u16 + 1).sum::<u16>() + 1
+            }
+            EdnsOption::KeyTag(ref key_tags) => key_tags.len() as u16 * 2,
+            EdnsOption::Custom(ref custom) => custom.data().len() as u16,
             EdnsOption::Unknown(_, ref data) => data.len() as u16, // TODO: should we verify?
         }
     }
 
     /// Returns `true` if the length in bytes of the EdnsOption is 0
     pub fn is_empty(&self) -> bool {
-        match *self {
-            #[cfg(feature = "dnssec")]
-            EdnsOption::DAU(ref algorithms)
-            | EdnsOption::DHU(ref algorithms)
-            | EdnsOption::N3U(ref algorithms) => algorithms.is_empty(),
-            EdnsOption::Unknown(_, ref data) => data.is_empty(),
-        }
+        self.len() == 0
     }
 }
 
 impl BinEncodable for EdnsOption {
     fn emit(&self, encoder: &mut BinEncoder) -> ProtoResult<()> {
         match *self {
+            EdnsOption::LLQ(ref llq) => {
+                encoder.emit_u16(llq.version())?;
+                encoder.emit_u16(llq.opcode())?;
+                encoder.emit_u16(llq.error())?;
+                encoder.emit_u32((llq.id() >> 32) as u32)?;
+                encoder.emit_u32(llq.id() as u32)?;
+                encoder.emit_u32(llq.lease())
+            }
+            EdnsOption::UL(ref lease) => {
+                encoder.emit_u32(lease.lease())?;
+                if let Some(key_lease) = lease.key_lease() {
+                    encoder.emit_u32(key_lease)?;
+                }
+                Ok(())
+            }
+            EdnsOption::NSID(ref data) => encoder.emit_vec(data),
             #[cfg(feature = "dnssec")]
             EdnsOption::DAU(ref algorithms)
             | EdnsOption::DHU(ref algorithms)
             | EdnsOption::N3U(ref algorithms) => algorithms.emit(encoder),
+            EdnsOption::Subnet(ref subnet) => subnet.emit(encoder),
+            EdnsOption::Expire(Some(expire)) => encoder.emit_u32(expire),
+            EdnsOption::Cookie(ref cookie) => {
+                encoder.emit_vec(cookie.client())?;
+                encoder.emit_vec(cookie.server())
+            }
+            EdnsOption::Keepalive(Some(timeout)) => encoder.emit_u16(timeout),
+            EdnsOption::Expire(None) | EdnsOption::Keepalive(None) => Ok(()),
+            EdnsOption::Padding(len) => encoder.emit_vec(&vec![0; len as usize]),
+            // names in options are never compressed
+            EdnsOption::Chain(ref name) => name.emit_as_canonical(encoder, true),
+            EdnsOption::KeyTag(ref key_tags) => {
+                for key_tag in key_tags {
+                    encoder.emit_u16(*key_tag)?;
+                }
+                Ok(())
+            }
+            EdnsOption::Custom(ref custom) => encoder.emit_vec(custom.data()),
             EdnsOption::Unknown(_, ref data) => encoder.emit_vec(data), // gah, clone needed or make a crazy api.
         }
     }
 }
 
-/// only the supported extensions are listed right now.
+/// Options which fail to decode are `EdnsOption::Unknown`, with the data unchanged
 impl<'a> From<(EdnsCode, &'a [u8])> for EdnsOption {
     fn from(value: (EdnsCode, &'a [u8])) -> EdnsOption {
-        match value.0 {
-            #[cfg(feature = "dnssec")]
-            EdnsCode::DAU => EdnsOption::DAU(value.1.into()),
-            #[cfg(feature = "dnssec")]
-            EdnsCode::DHU => EdnsOption::DHU(value.1.into()),
-            #[cfg(feature = "dnssec")]
-            EdnsCode::N3U => EdnsOption::N3U(value.1.into()),
-            _ => EdnsOption::Unknown(value.0.into(), value.1.to_vec()),
+        match read_option(value.0, value.1) {
+            Ok(option) => option,
+            Err(err) => {
+                debug!("EDNS option {:?} is kept as unknown: {}", value.0, err);
+                EdnsOption::Unknown(value.0.into(), value.1.to_vec())
+            }
         }
     }
 }
 
+fn read_option(code: EdnsCode, data: &[u8]) -> ProtoResult<EdnsOption> {
+    if let Some(parser) = registered_parser(code) {
+        let value = parser(data)?;
+        return Ok(EdnsOption::Custom(CustomOption {
+            code: code.into(),
+            data: data.to_vec(),
+            value: Arc::from(value),
+        }));
+    }
+
+    let mut decoder = BinDecoder::new(data);
+    let option = match code {
+        EdnsCode::LLQ => {
+            let version = decoder.read_u16()?.unverified(/*any version is valid*/);
+            let opcode = decoder.read_u16()?.unverified(/*any opcode is valid*/);
+            let error = decoder.read_u16()?.unverified(/*any error is valid*/);
+            let id_high = decoder.read_u32()?.unverified(/*any id is valid*/);
+            let id_low = decoder.read_u32()?.unverified(/*any id is valid*/);
+            let lease = decoder.read_u32()?.unverified(/*any lease is valid*/);
+            EdnsOption::LLQ(LongLivedQuery::new(
+                version,
+                opcode,
+                error,
+                u64::from(id_high) << 32 | u64::from(id_low),
+                lease,
+            ))
+        }
+        EdnsCode::UL => {
+            let lease = decoder.read_u32()?.unverified(/*any lease is valid*/);
+            let key_lease = if decoder.is_empty() {
+                None
+            } else {
+                Some(decoder.read_u32()?.unverified(/*any lease is valid*/))
+            };
+            EdnsOption::UL(UpdateLease::new(lease, key_lease))
+        }
+        EdnsCode::NSID => {
+            EdnsOption::NSID(decoder.read_vec(data.len())?.unverified(/*opaque data*/))
+        }
+        #[cfg(feature = "dnssec")]
+        EdnsCode::DAU => return Ok(EdnsOption::DAU(data.into())),
+        #[cfg(feature = "dnssec")]
+        EdnsCode::DHU => return Ok(EdnsOption::DHU(data.into())),
+        #[cfg(feature = "dnssec")]
+        EdnsCode::N3U => return Ok(EdnsOption::N3U(data.into())),
+        EdnsCode::Subnet => EdnsOption::Subnet(ClientSubnet::read(&mut decoder)?),
+        EdnsCode::Expire => EdnsOption::Expire(if decoder.is_empty() {
+            None
+        } else {
+            Some(decoder.read_u32()?.unverified(/*any expire timer is valid*/))
+        }),
+        EdnsCode::Cookie => {
+            let mut client = [0; 8];
+            client.copy_from_slice(decoder.read_slice(8)?.unverified(/*any cookie is valid*/));
+            let server = decoder
+                .read_vec(data.len() - 8)?
+                .unverified(/*length is verified by Cookie::new*/);
+            EdnsOption::Cookie(Cookie::new(client, server)?)
+        }
+        EdnsCode::Keepalive => EdnsOption::Keepalive(if decoder.is_empty() {
+            None
+        } else {
+            Some(decoder.read_u16()?.unverified(/*any timeout is valid*/))
+        }),
+        EdnsCode::Padding => {
+            let padding = decoder
+                .read_slice(data.len())?
+                .verify_unwrap(|padding| padding.iter().all(|b| *b == 0))
+                .map_err(|_| ProtoError::from("padding is not zero"))?;
+            EdnsOption::Padding(padding.len() as u16)
+        }
+        EdnsCode::Chain => EdnsOption::Chain(Name::read(&mut decoder)?),
+        EdnsCode::KeyTag => {
+            let mut key_tags = Vec::with_capacity(data.len() / 2);
+            while !decoder.is_empty() {
+                key_tags.push(decoder.read_u16()?.unverified(/*any key tag is valid*/));
+            }
+            EdnsOption::KeyTag(key_tags)
+        }
+        _ => return Ok(EdnsOption::Unknown(code.into(), data.to_vec())),
+    };
+
+    if !decoder.is_empty() {
+        return Err(format!("{} bytes after the EDNS option data", decoder.len()).into());
+    }
+
+    Ok(option)
+}
+
 impl<'a> From<&'a EdnsOption> for Vec<u8> {
     fn from(value: &'a EdnsOption) -> Vec<u8> {
-        match *value {
-            #[cfg(feature = "dnssec")]
-            EdnsOption::DAU(ref algorithms)
-            | EdnsOption::DHU(ref algorithms)
-            | EdnsOption::N3U(ref algorithms) => algorithms.into(),
-            EdnsOption::Unknown(_, ref data) => data.clone(), // gah, clone needed or make a crazy api.
+        let mut bytes = Vec::with_capacity(value.len() as usize);
+        if let Err(err) = value.emit(&mut BinEncoder::new(&mut bytes)) {
+            warn!("failed to emit EDNS option: {}", err);
         }
+        bytes
     }
 }
 
 impl<'a> From<&'a EdnsOption> for EdnsCode {
     fn from(value: &'a EdnsOption) -> EdnsCode {
         match *value {
+            EdnsOption::LLQ(..) => EdnsCode::LLQ,
+            EdnsOption::UL(..) => EdnsCode::UL,
+            EdnsOption::NSID(..) => EdnsCode::NSID,
             #[cfg(feature = "dnssec")]
             EdnsOption::DAU(..) => EdnsCode::DAU,
             #[cfg(feature = "dnssec")]
             EdnsOption::DHU(..) => EdnsCode::DHU,
             #[cfg(feature = "dnssec")]
             EdnsOption::N3U(..) => EdnsCode::N3U,
+            EdnsOption::Subnet(..) => EdnsCode::Subnet,
+            EdnsOption::Expire(..) => EdnsCode::Expire,
+            EdnsOption::Cookie(..) => EdnsCode::Cookie,
+            EdnsOption::Keepalive(..) => EdnsCode::Keepalive,
+            EdnsOption::Padding(..) => EdnsCode::Padding,
+            EdnsOption::Chain(..) => EdnsCode::Chain,
+            EdnsOption::KeyTag(..) => EdnsCode::KeyTag,
+            EdnsOption::Custom(ref custom) => custom.code().into(),
             EdnsOption::Unknown(code, _) => code.into(),
         }
     }
 }
 
+/// The data of the long-lived query option, LLQ
+#[derive(Debug, PartialOrd, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct LongLivedQuery {
+    version: u16,
+    opcode: u16,
+    error: u16,
+    id: u64,
+    lease: u32,
+}
+
+impl LongLivedQuery {
+    /// Creates the data of an LLQ option
+    pub fn new(version: u16, opcode: u16, error: u16, id: u64, lease: u32) -> Self {
+        LongLivedQuery {
+            version,
+            opcode,
+            error,
+            id,
+            lease,
+        }
+    }
+
+    /// The version of the LLQ protocol
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
+    /// The operation, 1 for setup, 2 for refresh and 3 for an event
+    pub fn opcode(&self) -> u16 {
+        self.opcode
+    }
+
+    /// The error code, 0 for no error
+    pub fn error(&self) -> u16 {
+        self.error
+    }
+
+    /// The identifier of the LLQ, chosen by the server
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// The requested or granted lifetime of the LLQ in seconds
+    pub fn lease(&self) -> u32 {
+        self.lease
+    }
+}
+
+/// The data of the update lease option, UL
+#[derive(Debug, PartialOrd, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct UpdateLease {
+    lease: u32,
+    key_lease: Option<u32>,
+}
+
+impl UpdateLease {
+    /// Creates the data of an UL option
+    pub fn new(lease: u32, key_lease: Option<u32>) -> Self {
+        UpdateLease { lease, key_lease }
+    }
+
+    /// The requested or granted lifetime of the updated records in seconds
+    pub fn lease(&self) -> u32 {
+        self.lease
+    }
+
+    /// The lifetime of the KEY records of the update in seconds, if it differs from `lease`
+    pub fn key_lease(&self) -> Option<u32> {
+        self.key_lease
+    }
+}
+
+/// The data of the client subnet option
+///
+/// [RFC 7871](https://tools.ietf.org/html/rfc7871), Client Subnet in DNS Queries, May 2016
+///
+/// The bits of the address beyond the source prefix length are zero.
+#[derive(Debug, PartialOrd, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct ClientSubnet {
+    address: IpAddr,
+    source_prefix: u8,
+    scope_prefix: u8,
+}
+
+impl ClientSubnet {
+    /// Creates the data of a client subnet option, the address is truncated to the source prefix
+    ///
+    /// # Errors
+    ///
+    /// If a prefix length is longer than the address
+    pub fn new(address: IpAddr, source_prefix: u8, scope_prefix: u8) -> ProtoResult<Self> {
+        let max_prefix = if address.is_ipv4() { 32 } else { 128 };
+        if source_prefix > max_prefix || scope_prefix > max_prefix {
+            return Err(format!(
+                "prefix length of {} is longer than the address: {}/{}",
+                address, source_prefix, scope_prefix
+            )
+            .into());
+        }
+
+        Ok(ClientSubnet {
+            address: truncate(address, source_prefix),
+            source_prefix,
+            scope_prefix,
+        })
+    }
+
+    /// The address of the client, truncated to the source prefix
+    pub fn address(&self) -> IpAddr {
+        self.address
+    }
+
+    /// The length of the prefix of the address which is used in the query
+    pub fn source_prefix(&self) -> u8 {
+        self.source_prefix
+    }
+
+    /// The length of the prefix of the address which the response covers, 0 in queries
+    pub fn scope_prefix(&self) -> u8 {
+        self.scope_prefix
+    }
+
+    fn address_len(&self) -> usize {
+        address_len(self.source_prefix)
+    }
+
+    fn read(decoder: &mut BinDecoder) -> ProtoResult<Self> {
+        let family = decoder.read_u16()?.unverified(/*verified in the match*/);
+        let source_prefix = decoder.read_u8()?.unverified(/*verified by ClientSubnet::new*/);
+        let scope_prefix = decoder.read_u8()?.unverified(/*verified by ClientSubnet::new*/);
+        let address = decoder
+            .read_slice(decoder.len())?
+            .verify_unwrap(|address| address.len() == address_len(source_prefix))
+            .map_err(|address| {
+                ProtoError::from(format!(
+                    "{} address bytes for a source prefix of {}",
+                    address.len(),
+                    source_prefix
+                ))
+            })?;
+
+        let address = match family {
+            1 if address.len() <= 4 => {
+                let mut octets = [0u8; 4];
+                octets[..address.len()].copy_from_slice(address);
+                IpAddr::from(Ipv4Addr::from(octets))
+            }
+            2 if address.len() <= 16 => {
+                let mut octets = [0u8; 16];
+                octets[..address.len()].copy_from_slice(address);
+                IpAddr::from(Ipv6Addr::from(octets))
+            }
+            _ => return Err(format!("unsupported client subnet family: {}", family).into()),
+        };
+
+        let subnet = ClientSubnet::new(address, source_prefix, scope_prefix)?;
+        if subnet.address != address {
+            return Err("client subnet address bits beyond the source prefix are set".into());
+        }
+
+        Ok(subnet)
+    }
+
+    fn emit(&self, encoder: &mut BinEncoder) -> ProtoResult<()> {
+        let (family, octets) = match self.address {
+            IpAddr::V4(address) => (1, address.octets().to_vec()),
+            IpAddr::V6(address) => (2, address.octets().to_vec()),
+        };
+
+        encoder.emit_u16(family)?;
+        encoder.emit_u8(self.source_prefix)?;
+        encoder.emit_u8(self.scope_prefix)?;
+        encoder.emit_vec(&octets[..self.address_len()])
+    }
+}
+
+/// The number of bytes of an address with the prefix length
+fn address_len(prefix: u8) -> usize {
+    (usize::from(prefix) + 7) >> 3
+}
+
+/// Zeroes the bits of the address beyond the prefix length
+fn truncate(address: IpAddr, prefix: u8) -> IpAddr {
+    match address {
+        IpAddr::V4(address) => {
+            let mask = u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0);
+            IpAddr::from(Ipv4Addr::from(u32::from(address) & mask))
+        }
+        IpAddr::V6(address) => {
+            let mask = u128::MAX.checked_shl(128 - u32::from(prefix)).unwrap_or(0);
+            IpAddr::from(Ipv6Addr::from(u128::from(address) & mask))
+        }
+    }
+}
+
+/// The data of the cookie option
+///
+/// [RFC 7873](https://tools.ietf.org/html/rfc7873), Domain Name System (DNS) Cookies, May 2016
+#[derive(Debug, PartialOrd, PartialEq, Eq, Clone, Hash)]
+pub struct Cookie {
+    client: [u8; 8],
+    server: Vec<u8>,
+}
+
+impl Cookie {
+    /// Creates the data of a cookie option
+    ///
+    /// # Errors
+    ///
+    /// If the server cookie is not empty and not from 8 to 32 bytes long
+    pub fn new(client: [u8; 8], server: Vec<u8>) -> ProtoResult<Self> {
+        if !server.is_empty() && (server.len() < 8 || server.len() > 32) {
+            return Err(format!("server cookie of {} bytes", server.len()).into());
+        }
+
+        Ok(Cookie { client, server })
+    }
+
+    /// The client cookie
+    pub fn client(&self) -> &[u8; 8] {
+        &self.client
+    }
+
+    /// The server cookie, empty if the client doesn't know the cookie of the server
+    pub fn server(&self) -> &[u8] {
+        &self.server
+    }
+}
+
+/// The value of an option decoded by a registered parser, see `register_option_parser`
+pub trait EdnsOptionData: Debug + Send + Sync + 'static {
+    /// The data of the option, as emitted in the OPT record
+    fn to_bytes(&self) -> Vec<u8>;
+
+    /// Returns `self`, for `CustomOption::downcast_ref`
+    fn as_any(&self) -> &Any;
+}
+
+/// An option decoded by a registered parser, or created with `CustomOption::new`
+///
+/// Options are equal if their codes and data are equal, the data received is emitted unchanged.
+#[derive(Debug, Clone)]
+pub struct CustomOption {
+    code: u16,
+    data: Vec<u8>,
+    value: Arc<EdnsOptionData>,
+}
+
+impl CustomOption {
+    /// Creates the option with the code and value
+    pub fn new<T: EdnsOptionData>(code: u16, value: T) -> Self {
+        CustomOption {
+            code,
+            data: value.to_bytes(),
+            value: Arc::new(value),
+        }
+    }
+
+    /// The code of the option
+    pub fn code(&self) -> u16 {
+        self.code
+    }
+
+    /// The data of the option
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// The value of the option
+    pub fn value(&self) -> &EdnsOptionData {
+        &*self.value
+    }
+
+    /// The value of the option, if it is a `T`
+    pub fn downcast_ref<T: EdnsOptionData>(&self) -> Option<&T> {
+        self.value.as_any().downcast_ref()
+    }
+}
+
+impl PartialEq for CustomOption {
+    fn eq(&self, other: &Self) -> bool {
+        self.code == other.code && self.data == other.data
+    }
+}
+
+impl Eq for CustomOption {}
+
+impl PartialOrd for CustomOption {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        (self.code, &self.data).partial_cmp(&(other.code, &other.data))
+    }
+}
+
+impl Hash for CustomOption {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.code.hash(state);
+        self.data.hash(state);
+    }
+}
+
+/// Parses the data of an option into its value
+pub type EdnsOptionParser = fn(&[u8]) -> ProtoResult<Box<EdnsOptionData>>;
+
+#[cfg(feature = "std")]
+lazy_static! {
+    static ref OPTION_PARSERS: RwLock<HashMap<u16, EdnsOptionParser>> = RwLock::new(HashMap::new());
+}
+
+#[cfg(feature = "std")]
+fn registered_parser(code: EdnsCode) -> Option<EdnsOptionParser> {
+    OPTION_PARSERS
+        .read()
+        .expect("EDNS option parsers poisoned")
+        .get(&u16::from(code))
+        .cloned()
+}
+
+/// Without std there is no registry, only the options assigned by IANA are decoded
+#[cfg(not(feature = "std"))]
+fn registered_parser(_code: EdnsCode) -> Option<EdnsOptionParser> {
+    None
+}
+
+/// Registers the parser of the options with the code, for all the OPT records decoded after,
+///  returning the parser previously registered for the code
+///
+/// Registered parsers take precedence over the decoding of the options assigned by IANA, the
+///  options they parse are `EdnsOption::Custom`. Options which fail to parse are `Unknown`.
+///
+/// ```rust
+/// use std::any::Any;
+///
+/// use trust_dns_proto::error::ProtoResult;
+/// use trust_dns_proto::rr::rdata::opt::{self, EdnsCode, EdnsOption, EdnsOptionData};
+///
+/// #[derive(Debug)]
+/// struct DeviceId(Vec<u8>);
+///
+/// impl EdnsOptionData for DeviceId {
+///     fn to_bytes(&self) -> Vec<u8> {
+///         self.0.clone()
+///     }
+///
+///     fn as_any(&self) -> &Any {
+///         self
+///     }
+/// }
+///
+/// fn parse_device_id(data: &[u8]) -> ProtoResult<Box<EdnsOptionData>> {
+///     Ok(Box::new(DeviceId(data.to_vec())))
+/// }
+///
+/// opt::register_option_parser(EdnsCode::Unknown(65_001), parse_device_id);
+///
+/// let option = EdnsOption::from((EdnsCode::Unknown(65_001), &[1, 2, 3][..]));
+/// match option {
+///     EdnsOption::Custom(ref custom) => {
+///         assert_eq!(custom.downcast_ref::<DeviceId>().unwrap().0, vec![1, 2, 3])
+///     }
+///     _ => panic!("not a custom option: {:?}", option),
+/// }
+/// ```
+#[cfg(feature = "std")]
+pub fn register_option_parser(
+    code: EdnsCode,
+    parser: EdnsOptionParser,
+) -> Option<EdnsOptionParser> {
+    OPTION_PARSERS
+        .write()
+        .expect("EDNS option parsers poisoned")
+        .insert(code.into(), parser)
+}
+
+/// Removes the parser of the options with the code, returning it if it was registered
+#[cfg(feature = "std")]
+pub fn unregister_option_parser(code: EdnsCode) -> Option<EdnsOptionParser> {
+    OPTION_PARSERS
+        .write()
+        .expect("EDNS option parsers poisoned")
+        .remove(&code.into())
+}
+
 #[test]
 #[cfg(feature = "dnssec")]
 pub fn test() {
@@ -494,3 +1083,162 @@ pub fn test() {
     );
     assert_eq!(rdata, read_rdata.unwrap());
 }
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn round_trip(option: EdnsOption) {
+        let mut rdata = OPT::default();
+        rdata.insert(option.clone());
+
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = BinEncoder::new(&mut bytes);
+            emit(&mut encoder, &rdata).expect("failed to emit");
+        }
+        assert_eq!(bytes.len(), 4 + option.len() as usize);
+
+        let mut decoder = BinDecoder::new(&bytes);
+        let read_rdata =
+            read(&mut decoder, Restrict::new(bytes.len() as u16)).expect("failed to read");
+        assert_eq!(read_rdata.get((&option).into()), Some(&option));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        round_trip(EdnsOption::LLQ(LongLivedQuery::new(
+            1,
+            1,
+            0,
+            0x0102_0304_0506_0708,
+            3600,
+        )));
+        round_trip(EdnsOption::UL(UpdateLease::new(3600, None)));
+        round_trip(EdnsOption::UL(UpdateLease::new(3600, Some(7200))));
+        round_trip(EdnsOption::NSID(b"ns1".to_vec()));
+        round_trip(EdnsOption::NSID(vec![]));
+        round_trip(EdnsOption::Subnet(
+            ClientSubnet::new(IpAddr::from([192, 0, 2, 1]), 24, 0).unwrap(),
+        ));
+        round_trip(EdnsOption::Subnet(
+            ClientSubnet::new(IpAddr::from_str("2001:db8::1").unwrap(), 56, 48).unwrap(),
+        ));
+        round_trip(EdnsOption::Expire(None));
+        round_trip(EdnsOption::Expire(Some(86_400)));
+        round_trip(EdnsOption::Cookie(Cookie::new([1; 8], vec![]).unwrap()));
+        round_trip(EdnsOption::Cookie(
+            Cookie::new([1; 8], vec![2; 16]).unwrap(),
+        ));
+        round_trip(EdnsOption::Keepalive(None));
+        round_trip(EdnsOption::Keepalive(Some(300)));
+        round_trip(EdnsOption::Padding(12));
+        round_trip(EdnsOption::Chain(Name::from_str("example.com.").unwrap()));
+        round_trip(EdnsOption::KeyTag(vec![19036, 20326]));
+        round_trip(EdnsOption::Unknown(65_500, vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn test_client_subnet_is_truncated() {
+        let subnet = ClientSubnet::new(IpAddr::from([192, 0, 2, 129]), 25, 0).unwrap();
+        assert_eq!(subnet.address(), IpAddr::from([192, 0, 2, 128]));
+
+        let subnet = ClientSubnet::new(IpAddr::from([192, 0, 2, 1]), 0, 0).unwrap();
+        assert_eq!(subnet.address(), IpAddr::from([0, 0, 0, 0]));
+
+        assert!(ClientSubnet::new(IpAddr::from([192, 0, 2, 1]), 33, 0).is_err());
+    }
+
+    #[test]
+    fn test_invalid_options_are_unknown() {
+        // address bits beyond the source prefix
+        let data: &[u8] = &[0, 1, 24, 0, 192, 0, 2, 1];
+        assert_eq!(
+            EdnsOption::from((EdnsCode::Subnet, data)),
+            EdnsOption::Unknown(8, data.to_vec())
+        );
+
+        // server cookie too short
+        let data: &[u8] = &[1, 2, 3, 4, 5, 6, 7, 8, 9];
+        assert_eq!(
+            EdnsOption::from((EdnsCode::Cookie, data)),
+            EdnsOption::Unknown(10, data.to_vec())
+        );
+
+        // trailing data
+        let data: &[u8] = &[0, 0, 1, 44, 0];
+        assert_eq!(
+            EdnsOption::from((EdnsCode::Keepalive, data)),
+            EdnsOption::Unknown(11, data.to_vec())
+        );
+
+        // padding is not zero
+        let data: &[u8] = &[0, 1];
+        assert_eq!(
+            EdnsOption::from((EdnsCode::Padding, data)),
+            EdnsOption::Unknown(12, data.to_vec())
+        );
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Counter(u32);
+
+    impl EdnsOptionData for Counter {
+        fn to_bytes(&self) -> Vec<u8> {
+            vec![
+                (self.0 >> 24) as u8,
+                (self.0 >> 16) as u8,
+                (self.0 >> 8) as u8,
+                self.0 as u8,
+            ]
+        }
+
+        fn as_any(&self) -> &Any {
+            self
+        }
+    }
+
+    fn parse_counter(data: &[u8]) -> ProtoResult<Box<EdnsOptionData>> {
+        let mut decoder = BinDecoder::new(data);
+        let counter = decoder.read_u32()?.unverified();
+        if !decoder.is_empty() {
+            return Err("trailing data".into());
+        }
+        Ok(Box::new(Counter(counter)))
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_registered_parser() {
+        let code = EdnsCode::Unknown(65_010);
+        assert!(register_option_parser(code, parse_counter).is_none());
+
+        let option = EdnsOption::from((code, &[0, 0, 1, 0][..]));
+        match option {
+            EdnsOption::Custom(ref custom) => {
+                assert_eq!(custom.code(), 65_010);
+                assert_eq!(custom.downcast_ref::<Counter>(), Some(&Counter(256)));
+            }
+            _ => panic!("not a custom option: {:?}", option),
+        }
+        assert_eq!(
+            option,
+            EdnsOption::Custom(CustomOption::new(65_010, Counter(256)))
+        );
+        round_trip(option);
+
+        // failures to parse are unknown
+        assert_eq!(
+            EdnsOption::from((code, &[1, 2][..])),
+            EdnsOption::Unknown(65_010, vec![1, 2])
+        );
+
+        assert!(unregister_option_parser(code).is_some());
+        assert_eq!(
+            EdnsOption::from((code, &[0, 0, 1, 0][..])),
+            EdnsOption::Unknown(65_010, vec![0, 0, 1, 0])
+        );
+    }
+}
@@ -7,6 +7,9 @@
 
 //! SSHFP records for SSH public key fingerprints
 
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
 use error::*;
 use serialize::binary::*;
 
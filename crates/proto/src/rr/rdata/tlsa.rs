@@ -7,6 +7,9 @@
 
 //! TLSA records for storing TLS certificate validation information
 
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
 use error::*;
 use serialize::binary::*;
 
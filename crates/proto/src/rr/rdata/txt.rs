@@ -16,7 +16,10 @@
 
 //! text records for storing arbitrary data
 
+use std::fmt;
 use std::slice::Iter;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
 
 use error::*;
 use serialize::binary::*;
@@ -59,6 +62,25 @@ impl TXT {
         }
     }
 
+    /// Creates a new TXT record data from arbitrary bytes, e.g. from unescaped zone file data
+    ///
+    /// # Arguments
+    ///
+    /// * `txt_data` - the set of byte strings which make up the txt_data.
+    ///
+    /// # Return value
+    ///
+    /// The new TXT record data.
+    pub fn from_bytes(txt_data: Vec<&[u8]>) -> TXT {
+        TXT {
+            txt_data: txt_data
+                .into_iter()
+                .map(|s| s.to_vec().into_boxed_slice())
+                .collect::<Vec<_>>()
+                .into_boxed_slice(),
+        }
+    }
+
     /// ```text
     /// TXT-DATA        One or more <character-string>s.
     /// ```
@@ -70,6 +92,193 @@ impl TXT {
     pub fn iter(&self) -> Iter<Box<[u8]>> {
         self.txt_data.iter()
     }
+
+    /// Creates the TXT record data of a DNS-SD service, from its attributes, RFC 6763 section 6
+    ///
+    /// The attributes are emitted in order, e.g. `txtvers` should be first. Without any
+    ///  attributes the TXT record data is a single empty string.
+    ///
+    /// # Arguments
+    ///
+    /// * `attributes` - the keys and values of the attributes, the values of booleans are None
+    ///
+    /// # Return value
+    ///
+    /// An error if a key is empty, not printable US-ASCII, contains `=` or is used more than
+    ///  once, or if an attribute is longer than 255 bytes
+    pub fn from_dns_sd_attributes<I, K, V>(attributes: I) -> ProtoResult<TXT>
+    where
+        I: IntoIterator<Item = (K, Option<V>)>,
+        K: AsRef<str>,
+        V: AsRef<[u8]>,
+    {
+        let mut keys: Vec<String> = Vec::new();
+        let mut txt_data = Vec::new();
+        for (key, value) in attributes {
+            let key = key.as_ref();
+            if !is_dns_sd_key(key.as_bytes()) {
+                return Err(format!("bad DNS-SD attribute key: {:?}", key).into());
+            }
+
+            // keys are case insensitive
+            let lower_key = key.to_ascii_lowercase();
+            if keys.contains(&lower_key) {
+                return Err(format!("duplicate DNS-SD attribute key: {}", key).into());
+            }
+            keys.push(lower_key);
+
+            let mut attribute = key.as_bytes().to_vec();
+            if let Some(value) = value {
+                attribute.push(b'=');
+                attribute.extend_from_slice(value.as_ref());
+            }
+            if attribute.len() > 255 {
+                return Err(format!(
+                    "DNS-SD attribute too long: {}, {} bytes",
+                    key,
+                    attribute.len()
+                )
+                .into());
+            }
+
+            txt_data.push(attribute.into_boxed_slice());
+        }
+
+        // an empty TXT record is not allowed
+        if txt_data.is_empty() {
+            txt_data.push(Box::new([]) as Box<[u8]>);
+        }
+
+        Ok(TXT {
+            txt_data: txt_data.into_boxed_slice(),
+        })
+    }
+
+    /// The DNS-SD attributes of the TXT record data, `key=value` or the boolean `key`, in order,
+    ///  RFC 6763 section 6
+    ///
+    /// The strings without a valid key, e.g. empty or starting with `=`, are ignored, as are the
+    ///  later attributes with the same key, compared case insensitively.
+    pub fn dns_sd_attributes(&self) -> Vec<DnsSdAttribute<'_>> {
+        let mut attributes: Vec<DnsSdAttribute> = Vec::with_capacity(self.txt_data.len());
+        for data in self.txt_data.iter() {
+            let attribute = match DnsSdAttribute::from_bytes(data) {
+                Some(attribute) => attribute,
+                None => continue,
+            };
+
+            if attributes
+                .iter()
+                .all(|a| !a.key.eq_ignore_ascii_case(attribute.key))
+            {
+                attributes.push(attribute);
+            }
+        }
+
+        attributes
+    }
+
+    /// The DNS-SD attribute with the key, compared case insensitively, see `dns_sd_attributes`
+    pub fn dns_sd_attribute(&self, key: &str) -> Option<DnsSdAttribute<'_>> {
+        self.txt_data
+            .iter()
+            .filter_map(|data| DnsSdAttribute::from_bytes(data))
+            .find(|attribute| attribute.key.eq_ignore_ascii_case(key))
+    }
+}
+
+/// An attribute of the TXT record of a DNS-SD service, [RFC 6763, DNS-Based Service Discovery, February 2013](https://tools.ietf.org/html/rfc6763#section-6.4)
+///
+/// ```text
+/// 6.4.  Rules for Keys in DNS-SD Key/Value Pairs
+///
+///    The key MUST be at least one character.  DNS-SD TXT record strings
+///    beginning with an '=' character (i.e., the key is missing) MUST be
+///    silently ignored.
+///
+///    The key SHOULD be no more than nine characters long.
+///
+///    The characters of a key MUST be printable US-ASCII values (0x20-0x7E)
+///    [RFC20], excluding '=' (0x3D).
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DnsSdAttribute<'a> {
+    key: &'a str,
+    value: Option<&'a [u8]>,
+}
+
+impl<'a> DnsSdAttribute<'a> {
+    /// The key before the first `=`, None if it's not a valid key
+    fn from_bytes(data: &'a [u8]) -> Option<Self> {
+        let mut split = data.splitn(2, |b| *b == b'=');
+        let key = split.next()?;
+        if !is_dns_sd_key(key) {
+            return None;
+        }
+
+        Some(DnsSdAttribute {
+            // printable US-ASCII is UTF-8
+            key: ::std::str::from_utf8(key).ok()?,
+            value: split.next(),
+        })
+    }
+
+    /// The key of the attribute, the case is as it was received
+    pub fn key(&self) -> &'a str {
+        self.key
+    }
+
+    /// The value after the `=`, which may be empty, None for a boolean attribute
+    pub fn value(&self) -> Option<&'a [u8]> {
+        self.value
+    }
+
+    /// The value as UTF-8, None for a boolean attribute or a value which is not UTF-8
+    pub fn value_str(&self) -> Option<&'a str> {
+        self.value
+            .and_then(|value| ::std::str::from_utf8(value).ok())
+    }
+
+    /// True if this is a boolean attribute, only the key without any `=`
+    pub fn is_boolean(&self) -> bool {
+        self.value.is_none()
+    }
+}
+
+/// Writes the TXT data in the zone file format, each string quoted and escaped, see
+///  `write_character_string`
+impl fmt::Display for TXT {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, string) in self.txt_data.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write_character_string(f, string)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes a `<character-string>` in the zone file format, RFC 1035 section 5.1
+///
+/// The string is quoted, `"` and `\` are escaped with a `\` and the bytes which are not
+///  printable US-ASCII are escaped as `\DDD`, the decimal value of the byte.
+pub(crate) fn write_character_string(f: &mut fmt::Formatter, string: &[u8]) -> fmt::Result {
+    f.write_str("\"")?;
+    for b in string {
+        match *b {
+            b'"' | b'\\' => write!(f, "\\{}", *b as char)?,
+            0x20..=0x7E => write!(f, "{}", *b as char)?,
+            _ => write!(f, "\\{:03}", b)?,
+        }
+    }
+    f.write_str("\"")
+}
+
+/// At least one character, printable US-ASCII without `=`
+fn is_dns_sd_key(key: &[u8]) -> bool {
+    !key.is_empty() && key.iter().all(|b| *b >= 0x20 && *b <= 0x7E && *b != b'=')
 }
 
 /// Read the RData from the given Decoder
@@ -80,7 +289,9 @@ pub fn read(decoder: &mut BinDecoder, rdata_length: Restrict<u16>) -> ProtoResul
     // no unsafe usage of rdata length ofter this point
     let rdata_length = rdata_length.map(|u| u as usize).unverified(/*used as a higher bound, safely*/);
     while data_len - decoder.len() < rdata_length {
-        let string = decoder.read_character_data()?.unverified(/*any data should be validat in TXT usage*/);
+        // the string, after its length octet, must be within the rdata
+        let max_len = rdata_length - (data_len - decoder.len()) - 1;
+        let string = decoder.read_character_data_max(Some(max_len))?.unverified(/*any data should be validat in TXT usage*/);
         strings.push(string.to_vec().into_boxed_slice());
     }
     Ok(TXT {
@@ -116,3 +327,106 @@ fn test() {
     );
     assert_eq!(rdata, read_rdata.unwrap());
 }
+
+#[test]
+fn test_string_exceeds_rdata() {
+    // the second string claims 4 bytes, but only 2 are left in the rdata
+    let bytes = [1, b'a', 4, b'b', b'c', b'd', b'e'];
+
+    let mut decoder: BinDecoder = BinDecoder::new(&bytes);
+    assert!(read(&mut decoder, Restrict::new(5)).is_err());
+}
+
+#[test]
+fn test_dns_sd_attributes() {
+    let rdata = TXT {
+        txt_data: vec![
+            b"txtvers=1".to_vec().into_boxed_slice(),
+            b"PaperSize=A4".to_vec().into_boxed_slice(),
+            b"Color".to_vec().into_boxed_slice(),
+            b"path=/a=b".to_vec().into_boxed_slice(),
+            b"empty=".to_vec().into_boxed_slice(),
+            b"=no key".to_vec().into_boxed_slice(),
+            b"".to_vec().into_boxed_slice(),
+            b"papersize=Letter".to_vec().into_boxed_slice(),
+            b"bin=\xff\x00".to_vec().into_boxed_slice(),
+        ]
+        .into_boxed_slice(),
+    };
+
+    let attributes = rdata.dns_sd_attributes();
+    let keys = attributes
+        .iter()
+        .map(DnsSdAttribute::key)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        keys,
+        vec!["txtvers", "PaperSize", "Color", "path", "empty", "bin"]
+    );
+
+    // the first attribute with a key is used
+    let paper_size = rdata.dns_sd_attribute("papersize").unwrap();
+    assert_eq!(paper_size.value_str(), Some("A4"));
+    assert!(rdata.dns_sd_attribute("color").unwrap().is_boolean());
+    assert_eq!(rdata.dns_sd_attribute("color").unwrap().value(), None);
+    assert_eq!(
+        rdata.dns_sd_attribute("path").unwrap().value_str(),
+        Some("/a=b")
+    );
+    assert_eq!(
+        rdata.dns_sd_attribute("empty").unwrap().value(),
+        Some(&b""[..])
+    );
+    assert!(!rdata.dns_sd_attribute("empty").unwrap().is_boolean());
+    assert_eq!(
+        rdata.dns_sd_attribute("bin").unwrap().value(),
+        Some(&b"\xff\x00"[..])
+    );
+    assert_eq!(rdata.dns_sd_attribute("bin").unwrap().value_str(), None);
+    assert!(rdata.dns_sd_attribute("missing").is_none());
+}
+
+#[test]
+fn test_from_dns_sd_attributes() {
+    let rdata = TXT::from_dns_sd_attributes(vec![
+        ("txtvers", Some("1")),
+        ("Color", None),
+        ("path", Some("/a=b")),
+        ("empty", Some("")),
+    ])
+    .unwrap();
+    let strings = rdata.iter().map(|s| &s[..]).collect::<Vec<_>>();
+    assert_eq!(
+        strings,
+        vec![&b"txtvers=1"[..], b"Color", b"path=/a=b", b"empty="]
+    );
+    assert_eq!(rdata.dns_sd_attributes().len(), 4);
+
+    // no attributes is a single empty string
+    let rdata = TXT::from_dns_sd_attributes(Vec::<(&str, Option<&str>)>::new()).unwrap();
+    assert_eq!(rdata.txt_data().len(), 1);
+    assert!(rdata.txt_data()[0].is_empty());
+    assert!(rdata.dns_sd_attributes().is_empty());
+
+    assert!(TXT::from_dns_sd_attributes(vec![("", Some("a"))]).is_err());
+    assert!(TXT::from_dns_sd_attributes(vec![("a=b", Some("c"))]).is_err());
+    assert!(TXT::from_dns_sd_attributes(vec![("tab\t", None::<&str>)]).is_err());
+    assert!(TXT::from_dns_sd_attributes(vec![("a", Some("1")), ("A", None)]).is_err());
+    let long = "x".repeat(254);
+    assert!(TXT::from_dns_sd_attributes(vec![("a", Some(&long[..]))]).is_err());
+    assert!(TXT::from_dns_sd_attributes(vec![("a", Some(&long[1..]))]).is_ok());
+}
+
+#[test]
+fn test_display() {
+    let rdata = TXT::from_bytes(vec![
+        b"v=spf1 -all",
+        b"say \"hi\"; c:\\",
+        b"\x00\t\xff",
+        b"",
+    ]);
+    assert_eq!(
+        rdata.to_string(),
+        r#""v=spf1 -all" "say \"hi\"; c:\\" "\000\009\255" """#
+    );
+}
@@ -17,13 +17,17 @@
 //! record data enum variants
 
 use std::cmp::Ordering;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
 #[cfg(test)]
 use std::convert::From;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
 use super::domain::Name;
 use super::rdata;
-use super::rdata::{CAA, MX, NAPTR, NULL, OPENPGPKEY, OPT, SOA, SRV, SSHFP, TLSA, TXT};
+#[cfg(feature = "std")]
+use super::rdata::CAA;
+use super::rdata::{MX, NAPTR, NULL, OPENPGPKEY, OPT, SOA, SRV, SSHFP, TLSA, TXT};
 use super::record_type::RecordType;
 use error::*;
 use serialize::binary::*;
@@ -130,6 +134,7 @@ pub enum RData {
     /// remaining octets in the Value field (m = d - n - 2) where d is the
     /// length of the RDATA section.
     /// ```
+    #[cfg(feature = "std")]
     CAA(CAA),
 
     /// ```text
@@ -594,6 +599,19 @@ pub enum RData {
     ZERO,
 }
 
+macro_rules! into_fn {
+    ($f:ident, $v:ident, $t:ty) => {
+        /// Converts this into the inner value of the variant, or returns it unchanged if it is
+        ///  another variant. See the `as_*` functions for references to the inner values.
+        pub fn $f(self) -> Result<$t, Self> {
+            match self {
+                RData::$v(inner) => Ok(inner),
+                rdata => Err(rdata),
+            }
+        }
+    };
+}
+
 impl RData {
     fn to_bytes(&self) -> Vec<u8> {
         let mut buf: Vec<u8> = Vec::new();
@@ -630,10 +648,20 @@ impl RData {
             rt @ RecordType::ANY | rt @ RecordType::AXFR | rt @ RecordType::IXFR => {
                 return Err(ProtoErrorKind::UnknownRecordTypeValue(rt.into()).into());
             }
+            #[cfg(feature = "std")]
             RecordType::CAA => {
                 debug!("reading CAA");
                 rdata::caa::read(decoder, rdata_length).map(RData::CAA)
             }
+            // the iodef URLs need std, without it CAA records are kept as unknown
+            #[cfg(not(feature = "std"))]
+            rt @ RecordType::CAA => {
+                debug!("reading CAA as Unknown");
+                rdata::null::read(decoder, rdata_length).map(|rdata| RData::Unknown {
+                    code: rt.into(),
+                    rdata,
+                })
+            }
             RecordType::CNAME => {
                 debug!("reading CNAME");
                 rdata::name::read(decoder).map(RData::CNAME)
@@ -710,6 +738,7 @@ impl RData {
                     read,
                     len: rdata_length,
                 })
+                .at_offset(start_idx)
             })?;
 
         result
@@ -789,6 +818,7 @@ impl RData {
             RData::ANAME(ref name) => {
                 encoder.with_canonical_names(|encoder| rdata::name::emit(encoder, name))
             }
+            #[cfg(feature = "std")]
             RData::CAA(ref caa) => {
                 encoder.with_canonical_names(|encoder| rdata::caa::emit(encoder, caa))
             }
@@ -832,6 +862,7 @@ impl RData {
             RData::A(..) => RecordType::A,
             RData::AAAA(..) => RecordType::AAAA,
             RData::ANAME(..) => RecordType::ANAME,
+            #[cfg(feature = "std")]
             RData::CAA(..) => RecordType::CAA,
             RData::CNAME(..) => RecordType::CNAME,
             RData::MX(..) => RecordType::MX,
@@ -861,6 +892,27 @@ impl RData {
             _ => None,
         }
     }
+
+    into_fn!(into_a, A, Ipv4Addr);
+    into_fn!(into_aaaa, AAAA, Ipv6Addr);
+    into_fn!(into_aname, ANAME, Name);
+    #[cfg(feature = "std")]
+    into_fn!(into_caa, CAA, CAA);
+    into_fn!(into_cname, CNAME, Name);
+    into_fn!(into_mx, MX, MX);
+    into_fn!(into_naptr, NAPTR, NAPTR);
+    into_fn!(into_null, NULL, NULL);
+    into_fn!(into_ns, NS, Name);
+    into_fn!(into_openpgpkey, OPENPGPKEY, OPENPGPKEY);
+    into_fn!(into_opt, OPT, OPT);
+    into_fn!(into_ptr, PTR, Name);
+    into_fn!(into_soa, SOA, SOA);
+    into_fn!(into_srv, SRV, SRV);
+    into_fn!(into_sshfp, SSHFP, SSHFP);
+    into_fn!(into_tlsa, TLSA, TLSA);
+    into_fn!(into_txt, TXT, TXT);
+    #[cfg(feature = "dnssec")]
+    into_fn!(into_dnssec, DNSSEC, DNSSECRData);
 }
 
 impl PartialOrd<RData> for RData {
@@ -1077,6 +1129,7 @@ mod tests {
             RData::A(..) => RecordType::A,
             RData::AAAA(..) => RecordType::AAAA,
             RData::ANAME(..) => RecordType::ANAME,
+            #[cfg(feature = "std")]
             RData::CAA(..) => RecordType::CAA,
             RData::CNAME(..) => RecordType::CNAME,
             RData::MX(..) => RecordType::MX,
@@ -1102,4 +1155,17 @@ mod tests {
     fn test_write_to() {
         test_emit_data_set(get_data(), |e, d| d.emit(e));
     }
+
+    #[test]
+    fn test_as_and_into() {
+        let rdata = RData::A(Ipv4Addr::new(192, 168, 0, 1));
+        assert_eq!(rdata.as_a(), Some(&Ipv4Addr::new(192, 168, 0, 1)));
+        assert!(rdata.as_aaaa().is_none());
+
+        let rdata = rdata.into_cname().unwrap_err();
+        assert_eq!(rdata.into_a(), Ok(Ipv4Addr::new(192, 168, 0, 1)));
+
+        let mx = MX::new(10, Name::from_str("mail.example.com.").unwrap());
+        assert_eq!(RData::MX(mx.clone()).into_mx(), Ok(mx));
+    }
 }
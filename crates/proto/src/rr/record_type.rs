@@ -21,6 +21,8 @@ use std::convert::From;
 use std::fmt;
 use std::fmt::{Display, Formatter};
 use std::str::FromStr;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
 
 use error::*;
 use serialize::binary::*;
@@ -88,7 +90,6 @@ pub enum RecordType {
     //  TKEY,       //	249	RFC 2930	Secret key record
     /// RFC 6698 TLSA certificate association
     TLSA,
-    //  TSIG,       //	250	RFC 2845	Transaction Signature
     /// RFC 1035[1] Text record
     TXT,
 
@@ -168,6 +169,23 @@ impl FromStr for RecordType {
             "TXT" => Ok(RecordType::TXT),
             "ANY" | "*" => Ok(RecordType::ANY),
             "AXFR" => Ok(RecordType::AXFR),
+            "IXFR" => Ok(RecordType::IXFR),
+            #[cfg(feature = "dnssec")]
+            "DNSKEY" => Ok(RecordType::DNSSEC(DNSSECRecordType::DNSKEY)),
+            #[cfg(feature = "dnssec")]
+            "DS" => Ok(RecordType::DNSSEC(DNSSECRecordType::DS)),
+            #[cfg(feature = "dnssec")]
+            "KEY" => Ok(RecordType::DNSSEC(DNSSECRecordType::KEY)),
+            #[cfg(feature = "dnssec")]
+            "NSEC" => Ok(RecordType::DNSSEC(DNSSECRecordType::NSEC)),
+            #[cfg(feature = "dnssec")]
+            "NSEC3" => Ok(RecordType::DNSSEC(DNSSECRecordType::NSEC3)),
+            #[cfg(feature = "dnssec")]
+            "NSEC3PARAM" => Ok(RecordType::DNSSEC(DNSSECRecordType::NSEC3PARAM)),
+            #[cfg(feature = "dnssec")]
+            "RRSIG" => Ok(RecordType::DNSSEC(DNSSECRecordType::RRSIG)),
+            #[cfg(feature = "dnssec")]
+            "SIG" => Ok(RecordType::DNSSEC(DNSSECRecordType::SIG)),
             _ => Err(ProtoErrorKind::UnknownRecordTypeStr(str.to_string()).into()),
         }
     }
@@ -190,6 +208,7 @@ impl From<u16> for RecordType {
             65305 => RecordType::ANAME,
             255 => RecordType::ANY,
             252 => RecordType::AXFR,
+            251 => RecordType::IXFR,
             257 => RecordType::CAA,
             5 => RecordType::CNAME,
             0 => RecordType::ZERO,
@@ -213,7 +232,8 @@ impl From<u16> for RecordType {
             50/*NSEC3*/|
             51/*NSEC3PARAM*/|
             46/*RRSIG*/|
-            24/*SIG*/ => RecordType::DNSSEC(DNSSECRecordType::from(value)),
+            24/*SIG*/|
+            250/*TSIG*/ => RecordType::DNSSEC(DNSSECRecordType::from(value)),
             // all unknown record types
             _ => RecordType::Unknown(value),
         }
@@ -383,3 +403,24 @@ fn test_order() {
 
     assert_eq!(ordered, unordered);
 }
+
+#[test]
+#[cfg(feature = "dnssec")]
+fn test_from_str_dnssec() {
+    for record_type in &[
+        DNSSECRecordType::DNSKEY,
+        DNSSECRecordType::DS,
+        DNSSECRecordType::KEY,
+        DNSSECRecordType::NSEC,
+        DNSSECRecordType::NSEC3,
+        DNSSECRecordType::NSEC3PARAM,
+        DNSSECRecordType::RRSIG,
+        DNSSECRecordType::SIG,
+    ] {
+        let record_type = RecordType::DNSSEC(*record_type);
+        assert_eq!(
+            RecordType::from_str(&record_type.to_string()).unwrap(),
+            record_type
+        );
+    }
+}
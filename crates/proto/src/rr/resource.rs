@@ -17,10 +17,13 @@
 //! resource record implementation
 
 use std::cmp::Ordering;
+use std::net::{Ipv4Addr, Ipv6Addr};
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
 
 use error::*;
 use rr::dns_class::DNSClass;
-use rr::rdata::NULL;
+use rr::rdata::{MX, NULL, SRV, TXT};
 #[allow(deprecated)]
 use rr::IntoRecordSet;
 use rr::Name;
@@ -29,6 +32,17 @@ use rr::RecordSet;
 use rr::RecordType;
 use serialize::binary::*;
 
+#[cfg(feature = "mdns")]
+/// From [RFC 6762](https://tools.ietf.org/html/rfc6762#section-10.2)
+/// ```text
+/// The cache-flush bit is the most significant bit of the second
+/// 16-bit word of a resource record in a Resource Record Section of a
+/// Multicast DNS message (the field conventionally referred to as the
+/// rrclass field), and the actual resource record class is the least
+/// significant fifteen bits of this field.
+/// ```
+const MDNS_ENABLE_CACHE_FLUSH: u16 = 1 << 15;
+
 /// Resource records are storage value in DNS, into which all key/value pair data is stored.
 ///
 /// [RFC 1035](https://tools.ietf.org/html/rfc1035), DOMAIN NAMES - IMPLEMENTATION AND SPECIFICATION, November 1987
@@ -69,6 +83,8 @@ pub struct Record {
     dns_class: DNSClass,
     ttl: u32,
     rdata: RData,
+    #[cfg(feature = "mdns")]
+    mdns_cache_flush: bool,
 }
 
 impl Default for Record {
@@ -80,6 +96,8 @@ impl Default for Record {
             dns_class: DNSClass::IN,
             ttl: 0,
             rdata: RData::NULL(NULL::new()),
+            #[cfg(feature = "mdns")]
+            mdns_cache_flush: false,
         }
     }
 }
@@ -107,6 +125,8 @@ impl Record {
             dns_class: DNSClass::IN,
             ttl,
             rdata: RData::NULL(NULL::new()),
+            #[cfg(feature = "mdns")]
+            mdns_cache_flush: false,
         }
     }
 
@@ -124,9 +144,62 @@ impl Record {
             dns_class: DNSClass::IN,
             ttl,
             rdata,
+            #[cfg(feature = "mdns")]
+            mdns_cache_flush: false,
         }
     }
 
+    /// Creates an A record, the IPv4 `address` of `name`
+    pub fn a(name: Name, ttl: u32, address: Ipv4Addr) -> Record {
+        Self::from_rdata(name, ttl, RData::A(address))
+    }
+
+    /// Creates an AAAA record, the IPv6 `address` of `name`
+    pub fn aaaa(name: Name, ttl: u32, address: Ipv6Addr) -> Record {
+        Self::from_rdata(name, ttl, RData::AAAA(address))
+    }
+
+    /// Creates a CNAME record, `name` is an alias of `target`
+    pub fn cname(name: Name, ttl: u32, target: Name) -> Record {
+        Self::from_rdata(name, ttl, RData::CNAME(target))
+    }
+
+    /// Creates an NS record, `target` is a name server of the zone `name`
+    pub fn ns(name: Name, ttl: u32, target: Name) -> Record {
+        Self::from_rdata(name, ttl, RData::NS(target))
+    }
+
+    /// Creates a PTR record, `name` points to `target`
+    pub fn ptr(name: Name, ttl: u32, target: Name) -> Record {
+        Self::from_rdata(name, ttl, RData::PTR(target))
+    }
+
+    /// Creates an MX record, `exchange` accepts mail for `name` with the `preference`
+    pub fn mx(name: Name, ttl: u32, preference: u16, exchange: Name) -> Record {
+        Self::from_rdata(name, ttl, RData::MX(MX::new(preference, exchange)))
+    }
+
+    /// Creates a TXT record with the character-strings of `txt_data`
+    pub fn txt(name: Name, ttl: u32, txt_data: Vec<String>) -> Record {
+        Self::from_rdata(name, ttl, RData::TXT(TXT::new(txt_data)))
+    }
+
+    /// Creates an SRV record, the service `name` is available at the `port` of `target`
+    pub fn srv(
+        name: Name,
+        ttl: u32,
+        priority: u16,
+        weight: u16,
+        port: u16,
+        target: Name,
+    ) -> Record {
+        Self::from_rdata(
+            name,
+            ttl,
+            RData::SRV(SRV::new(priority, weight, port, target)),
+        )
+    }
+
     /// ```text
     /// NAME            a domain name to which this resource record pertains.
     /// ```
@@ -189,6 +262,16 @@ impl Record {
         self
     }
 
+    /// Changes mDNS cache-flush bit
+    /// See [RFC 6762](https://tools.ietf.org/html/rfc6762#section-10.2)
+    ///
+    /// The bit is only written in mDNS messages, see `BinEncoder::set_mdns`
+    #[cfg(feature = "mdns")]
+    pub fn set_mdns_cache_flush(&mut self, flag: bool) -> &mut Self {
+        self.mdns_cache_flush = flag;
+        self
+    }
+
     /// Returns the name of the record
     pub fn name(&self) -> &Name {
         &self.name_labels
@@ -229,6 +312,15 @@ impl Record {
     pub fn unwrap_rdata(self) -> RData {
         self.rdata
     }
+
+    /// Returns if the mDNS cache-flush bit is set or not
+    /// See [RFC 6762](https://tools.ietf.org/html/rfc6762#section-10.2)
+    ///
+    /// The bit is only read from mDNS messages, see `BinDecoder::set_mdns`
+    #[cfg(feature = "mdns")]
+    pub fn mdns_cache_flush(&self) -> bool {
+        self.mdns_cache_flush
+    }
 }
 
 #[allow(deprecated)]
@@ -242,8 +334,19 @@ impl BinEncodable for Record {
     fn emit(&self, encoder: &mut BinEncoder) -> ProtoResult<()> {
         self.name_labels.emit(encoder)?;
         self.rr_type.emit(encoder)?;
+
+        #[cfg(not(feature = "mdns"))]
         self.dns_class.emit(encoder)?;
 
+        #[cfg(feature = "mdns")]
+        {
+            if self.mdns_cache_flush && encoder.is_mdns() {
+                encoder.emit_u16(u16::from(self.dns_class) | MDNS_ENABLE_CACHE_FLUSH)?;
+            } else {
+                self.dns_class.emit(encoder)?;
+            }
+        }
+
         encoder.emit_u32(self.ttl)?;
 
         // place the RData length
@@ -273,6 +376,9 @@ impl<'r> BinDecodable<'r> for Record {
         // TYPE            two octets containing one of the RR TYPE codes.
         let record_type: RecordType = RecordType::read(decoder)?;
 
+        #[cfg(feature = "mdns")]
+        let mut mdns_cache_flush = false;
+
         // CLASS           two octets containing one of the RR CLASS codes.
         let class: DNSClass = if record_type == RecordType::OPT {
             // verify that the OPT record is Root
@@ -285,7 +391,22 @@ impl<'r> BinDecodable<'r> for Record {
                 decoder.read_u16()?.unverified(/*restricted to a min of 512 in for_opt*/),
             )
         } else {
-            DNSClass::read(decoder)?
+            #[cfg(not(feature = "mdns"))]
+            {
+                DNSClass::read(decoder)?
+            }
+
+            #[cfg(feature = "mdns")]
+            {
+                let dns_class_value =
+                    decoder.read_u16()?.unverified(/*DNSClass::from_u16 will verify the value*/);
+                if decoder.is_mdns() && dns_class_value & MDNS_ENABLE_CACHE_FLUSH > 0 {
+                    mdns_cache_flush = true;
+                    DNSClass::from_u16(dns_class_value & !MDNS_ENABLE_CACHE_FLUSH)?
+                } else {
+                    DNSClass::from_u16(dns_class_value)?
+                }
+            }
         };
 
         // TTL             a 32 bit signed integer that specifies the time interval
@@ -304,8 +425,12 @@ impl<'r> BinDecodable<'r> for Record {
         let rd_length: u16 = decoder
             .read_u16()?
             .verify_unwrap(|u| (*u as usize) <= decoder.len())
-            .map_err(|_| {
-                ProtoError::from("rdata length too large for remaining bytes, need: {} remain: {}")
+            .map_err(|u| {
+                ProtoError::from(ProtoErrorKind::UnexpectedEnd {
+                    needed: u as usize,
+                    remaining: decoder.len(),
+                })
+                .at_offset(decoder.index())
             })?;
 
         // this is to handle updates, RFC 2136, which uses 0 to indicate certain aspects of
@@ -327,6 +452,8 @@ impl<'r> BinDecodable<'r> for Record {
             dns_class: class,
             ttl,
             rdata,
+            #[cfg(feature = "mdns")]
+            mdns_cache_flush,
         })
     }
 }
@@ -483,4 +610,86 @@ mod tests {
             assert_eq!(r.cmp(g), Ordering::Less);
         }
     }
+
+    #[cfg(feature = "mdns")]
+    #[test]
+    fn test_mdns_cache_flush_bit_handling() {
+        const RR_CLASS_OFFSET: usize = 1 /* empty name */ +
+            std::mem::size_of::<u16>() /* rr_type */;
+
+        let mut record = Record::new();
+        record.set_mdns_cache_flush(true);
+
+        let mut vec_bytes: Vec<u8> = Vec::with_capacity(512);
+        {
+            let mut encoder = BinEncoder::new(&mut vec_bytes);
+            encoder.set_mdns(true);
+            record.emit(&mut encoder).unwrap();
+
+            let rr_class_slice = encoder.slice_of(RR_CLASS_OFFSET, RR_CLASS_OFFSET + 2);
+            assert_eq!(rr_class_slice, &[0x80, 0x01]);
+        }
+
+        let mut decoder = BinDecoder::new(&vec_bytes);
+        decoder.set_mdns(true);
+        let got = Record::read(&mut decoder).unwrap();
+
+        assert_eq!(got.dns_class(), DNSClass::IN);
+        assert!(got.mdns_cache_flush());
+    }
+
+    #[cfg(feature = "mdns")]
+    #[test]
+    fn test_mdns_cache_flush_bit_outside_mdns() {
+        const RR_CLASS_OFFSET: usize = 1 /* empty name */ +
+            std::mem::size_of::<u16>() /* rr_type */;
+
+        let mut record = Record::new();
+        record.set_mdns_cache_flush(true);
+
+        let mut vec_bytes: Vec<u8> = Vec::with_capacity(512);
+        {
+            let mut encoder = BinEncoder::new(&mut vec_bytes);
+            record.emit(&mut encoder).unwrap();
+
+            let rr_class_slice = encoder.slice_of(RR_CLASS_OFFSET, RR_CLASS_OFFSET + 2);
+            assert_eq!(rr_class_slice, &[0x00, 0x01]);
+        }
+
+        // the top bit is part of the class of standard DNS messages, 0x8001 is not a known class
+        vec_bytes[RR_CLASS_OFFSET] = 0x80;
+        let mut decoder = BinDecoder::new(&vec_bytes);
+        assert!(Record::read(&mut decoder).is_err());
+    }
+
+    #[test]
+    fn test_typed_constructors() {
+        let name = Name::from_str("www.example.com.").unwrap();
+        let target = Name::from_str("example.com.").unwrap();
+
+        let record = Record::a(name.clone(), 5, Ipv4Addr::new(192, 168, 0, 1));
+        assert_eq!(record.record_type(), RecordType::A);
+        assert_eq!(record.dns_class(), DNSClass::IN);
+        assert_eq!(record.ttl(), 5);
+        assert_eq!(record.rdata().as_a(), Some(&Ipv4Addr::new(192, 168, 0, 1)));
+
+        let record = Record::cname(name.clone(), 5, target.clone());
+        assert_eq!(record.record_type(), RecordType::CNAME);
+        assert_eq!(record.rdata().as_cname(), Some(&target));
+
+        let record = Record::txt(name.clone(), 5, vec!["v=spf1 -all".to_string()]);
+        assert_eq!(record.record_type(), RecordType::TXT);
+        assert_eq!(
+            record.unwrap_rdata().into_txt().unwrap().txt_data(),
+            &[b"v=spf1 -all".to_vec().into_boxed_slice()]
+        );
+
+        let record = Record::srv(name, 5, 1, 2, 443, target.clone());
+        assert_eq!(record.record_type(), RecordType::SRV);
+        let srv = record.unwrap_rdata().into_srv().unwrap();
+        assert_eq!(
+            (srv.priority(), srv.weight(), srv.port(), srv.target()),
+            (1, 2, 443, &target)
+        );
+    }
 }
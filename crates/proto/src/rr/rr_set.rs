@@ -4,24 +4,37 @@
 // http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
+use std::borrow::Borrow;
 use std::iter::Chain;
 use std::slice::Iter;
+use std::sync::Arc;
 use std::vec;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
 
 use rr::{DNSClass, Name, RData, Record, RecordType};
 
 #[cfg(feature = "dnssec")]
-use rr::dnssec::SupportedAlgorithms;
+use error::ProtoResult;
+#[cfg(feature = "dnssec")]
+use rr::dnssec::rdata::SIG;
+#[cfg(feature = "dnssec")]
+use rr::dnssec::{tbs, SupportedAlgorithms, TBS};
 
 /// Set of resource records associated to a name and type
+///
+/// The records are owned by default. A set of shared records, `RecordSet<Arc<Record>>`, or of
+/// borrowed records, `RecordSet<&Record>`, is cheap to clone, e.g. to share the same RRset between
+/// an authority, its signer and the responses being sent, see `into_shared` and `borrowed`. Only
+/// the sets of owned records can change the TTL and class of their records.
 #[derive(Clone, Debug, PartialEq)]
-pub struct RecordSet {
+pub struct RecordSet<R = Record> {
     name: Name,
     record_type: RecordType,
     dns_class: DNSClass,
     ttl: u32,
-    records: Vec<Record>,
-    rrsigs: Vec<Record>,
+    records: Vec<R>,
+    rrsigs: Vec<R>,
     serial: u32, // serial number at which this record was modified
 }
 
@@ -77,20 +90,6 @@ impl RecordSet {
         }
     }
 
-    /// # Return value
-    ///
-    /// Label of the Resource Record Set
-    pub fn name(&self) -> &Name {
-        &self.name
-    }
-
-    /// # Return value
-    ///
-    /// `RecordType` of the Resource Record Set
-    pub fn record_type(&self) -> RecordType {
-        self.record_type
-    }
-
     /// Sets the DNSClass to the specified value
     ///
     /// This will traverse every record and associate with it the specified dns_class
@@ -101,11 +100,6 @@ impl RecordSet {
         }
     }
 
-    /// Returns the `DNSClass` of the RecordSet
-    pub fn dns_class(&self) -> DNSClass {
-        self.dns_class
-    }
-
     /// Sets the TTL, in seconds, to the specified value
     ///
     /// This will traverse every record and associate with it the specified ttl
@@ -116,6 +110,69 @@ impl RecordSet {
         }
     }
 
+    /// Sets the owner name, e.g. the query name of a wildcard's records
+    ///
+    /// This will traverse every record and RRSIG and associate with it the specified name
+    pub fn set_name(&mut self, name: Name) {
+        for r in self.records.iter_mut().chain(self.rrsigs.iter_mut()) {
+            r.set_name(name.clone());
+        }
+        self.name = name;
+    }
+
+    /// creates a new Record as part of this RecordSet, adding the associated RData
+    ///
+    /// this interface may be deprecated in the future.
+    pub fn new_record(&mut self, rdata: &RData) -> &Record {
+        self.add_rdata(rdata.clone());
+
+        self.records
+            .iter()
+            .find(|r| r.rdata() == rdata)
+            .expect("insert failed")
+    }
+
+    /// creates a new Record as part of this RecordSet, adding the associated RData
+    pub fn add_rdata(&mut self, rdata: RData) -> bool {
+        debug_assert_eq!(self.record_type, rdata.to_record_type());
+
+        let mut record = Record::with(self.name.clone(), self.record_type, self.ttl);
+        record.set_rdata(rdata);
+        self.insert(record, 0)
+    }
+
+    /// Returns an iterator over the records in the set
+    #[deprecated(note = "see `records_without_rrsigs`")]
+    pub fn iter(&self) -> Iter<Record> {
+        self.records.iter()
+    }
+
+    /// Shares the records of this set, clones of the returned set do not clone the records
+    pub fn into_shared(self) -> RecordSet<Arc<Record>> {
+        self.map_records(Arc::new)
+    }
+}
+
+impl<R: Borrow<Record>> RecordSet<R> {
+    /// # Return value
+    ///
+    /// Label of the Resource Record Set
+    pub fn name(&self) -> &Name {
+        &self.name
+    }
+
+    /// # Return value
+    ///
+    /// `RecordType` of the Resource Record Set
+    pub fn record_type(&self) -> RecordType {
+        self.record_type
+    }
+
+    /// Returns the `DNSClass` of the RecordSet
+    pub fn dns_class(&self) -> DNSClass {
+        self.dns_class
+    }
+
     /// Returns the time-to-live for the record.
     ///
     /// # Return value
@@ -132,13 +189,14 @@ impl RecordSet {
     ///
     /// * `and_rrsigs` - if true, RRSIGs will be returned if they exist
     /// * `supported_algorithms` - the RRSIGs will be filtered by the set of supported_algorithms,
-    ///                            and then only the maximal RRSIG algorithm will be returned.
+    ///                            all those of the supported algorithms are returned, e.g. those
+    ///                            of each key of the zone during a key or algorithm rollover.
     #[cfg(feature = "dnssec")]
     pub fn records(
         &self,
         and_rrsigs: bool,
         supported_algorithms: SupportedAlgorithms,
-    ) -> RrsetRecords {
+    ) -> RrsetRecords<R> {
         if and_rrsigs {
             self.records_with_rrsigs(supported_algorithms)
         } else {
@@ -151,9 +209,13 @@ impl RecordSet {
     /// # Arguments
     ///
     /// * `supported_algorithms` - the RRSIGs will be filtered by the set of supported_algorithms,
-    ///                            and then only the maximal RRSIG algorithm will be returned.
+    ///                            all those of the supported algorithms are returned, e.g. those
+    ///                            of each key of the zone during a key or algorithm rollover.
     #[cfg(feature = "dnssec")]
-    pub fn records_with_rrsigs(&self, supported_algorithms: SupportedAlgorithms) -> RrsetRecords {
+    pub fn records_with_rrsigs(
+        &self,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> RrsetRecords<R> {
         if self.records.is_empty() {
             RrsetRecords::Empty
         } else {
@@ -166,7 +228,7 @@ impl RecordSet {
     }
 
     /// Returns a Vec of all records in the set, without any RRSIGs.
-    pub fn records_without_rrsigs(&self) -> RrsetRecords {
+    pub fn records_without_rrsigs(&self) -> RrsetRecords<R> {
         if self.records.is_empty() {
             RrsetRecords::Empty
         } else {
@@ -174,12 +236,6 @@ impl RecordSet {
         }
     }
 
-    /// Returns an iterator over the records in the set
-    #[deprecated(note = "see `records_without_rrsigs`")]
-    pub fn iter(&self) -> Iter<Record> {
-        self.records.iter()
-    }
-
     /// Returns true if there are no records in this set
     pub fn is_empty(&self) -> bool {
         self.records.is_empty()
@@ -191,7 +247,7 @@ impl RecordSet {
     }
 
     /// Returns a slice of all the Records signatures in the RecordSet
-    pub fn rrsigs(&self) -> &[Record] {
+    pub fn rrsigs(&self) -> &[R] {
         &self.rrsigs
     }
 
@@ -202,7 +258,7 @@ impl RecordSet {
     /// # Arguments
     ///
     /// * `rrsig` - A signature which covers the RecordSet.
-    pub fn insert_rrsig(&mut self, rrsig: Record) {
+    pub fn insert_rrsig(&mut self, rrsig: R) {
         self.rrsigs.push(rrsig)
     }
 
@@ -211,30 +267,35 @@ impl RecordSet {
         self.rrsigs.clear()
     }
 
-    fn updated(&mut self, serial: u32) {
-        self.serial = serial;
-        self.rrsigs.clear(); // on updates, the rrsigs are invalid
-    }
-
-    /// creates a new Record as part of this RecordSet, adding the associated RData
+    /// Returns the data to be signed for the RRSIG of this RecordSet, see `tbs::rrset_tbs`
     ///
-    /// this interface may be deprecated in the future.
-    pub fn new_record(&mut self, rdata: &RData) -> &Record {
-        self.add_rdata(rdata.clone());
+    /// # Arguments
+    ///
+    /// * `pre_sig` - the fields of the RRSIG, the signature is ignored and may be empty. Its type
+    ///               covered must be the type of this set.
+    #[cfg(feature = "dnssec")]
+    pub fn tbs(&self, pre_sig: &SIG) -> ProtoResult<TBS> {
+        if pre_sig.type_covered() != self.record_type {
+            return Err(format!(
+                "RRSIG covers {} not the {} of {}",
+                pre_sig.type_covered(),
+                self.record_type,
+                self.name
+            )
+            .into());
+        }
 
-        self.records
-            .iter()
-            .find(|r| r.rdata() == rdata)
-            .expect("insert failed")
+        tbs::rrset_tbs_with_sig(
+            &self.name,
+            self.dns_class,
+            pre_sig,
+            self.records.iter().map(<R as Borrow<Record>>::borrow),
+        )
     }
 
-    /// creates a new Record as part of this RecordSet, adding the associated RData
-    pub fn add_rdata(&mut self, rdata: RData) -> bool {
-        debug_assert_eq!(self.record_type, rdata.to_record_type());
-
-        let mut record = Record::with(self.name.clone(), self.record_type, self.ttl);
-        record.set_rdata(rdata);
-        self.insert(record, 0)
+    fn updated(&mut self, serial: u32) {
+        self.serial = serial;
+        self.rrsigs.clear(); // on updates, the rrsigs are invalid
     }
 
     /// Inserts a new Resource Record into the Set.
@@ -270,7 +331,33 @@ impl RecordSet {
     /// True if the record was inserted.
     ///
     /// TODO: make a default add without serial number for basic usage
-    pub fn insert(&mut self, record: Record, serial: u32) -> bool {
+    pub fn insert(&mut self, record: R, serial: u32) -> bool {
+        if !self.should_insert(record.borrow()) {
+            return false;
+        }
+
+        // the record replaces the one of the same rdata, unless they are identical (ttl, etc.)
+        let position = self
+            .records
+            .iter()
+            .position(|rr| rr.borrow().rdata() == record.borrow().rdata());
+        if let Some(i) = position {
+            if self.records[i].borrow() == record.borrow() {
+                return false;
+            }
+        }
+
+        self.ttl = record.borrow().ttl();
+        self.updated(serial);
+        match position {
+            Some(i) => self.records[i] = record,
+            None => self.records.push(record),
+        }
+        true
+    }
+
+    /// Checks the RFC 2136 restrictions on the record, clearing the set if the record replaces it
+    fn should_insert(&mut self, record: &Record) -> bool {
         assert_eq!(record.name(), &self.name);
         assert_eq!(record.rr_type(), self.record_type);
 
@@ -287,7 +374,7 @@ impl RecordSet {
                 assert!(self.records.len() <= 1);
 
                 if let Some(soa_record) = self.records.iter().next() {
-                    match soa_record.rdata() {
+                    match soa_record.borrow().rdata() {
                         &RData::SOA(ref existing_soa) => {
                             if let RData::SOA(ref new_soa) = *record.rdata() {
                                 if new_soa.serial() <= existing_soa.serial() {
@@ -341,38 +428,7 @@ impl RecordSet {
             _ => (),
         }
 
-        // collect any records to update based on rdata
-        let to_replace: Vec<usize> = self
-            .records
-            .iter()
-            .enumerate()
-            .filter(|&(_, rr)| rr.rdata() == record.rdata())
-            .map(|(i, _)| i)
-            .collect::<Vec<usize>>();
-
-        // if the Records are identical, ignore the update, update all that are not (ttl, etc.)
-        let mut replaced = false;
-        for i in to_replace {
-            if self.records[i] == record {
-                return false;
-            }
-
-            // TODO: this shouldn't really need a clone since there should only be one...
-            self.records.push(record.clone());
-            self.records.swap_remove(i);
-            self.ttl = record.ttl();
-            self.updated(serial);
-            replaced = true;
-        }
-
-        if !replaced {
-            self.ttl = record.ttl();
-            self.updated(serial);
-            self.records.push(record);
-            true
-        } else {
-            replaced
-        }
+        true
     }
 
     /// Removes the Resource Record if it exists.
@@ -413,7 +469,7 @@ impl RecordSet {
             .records
             .iter()
             .enumerate()
-            .filter(|&(_, rr)| rr.rdata() == record.rdata())
+            .filter(|&(_, rr)| rr.borrow().rdata() == record.rdata())
             .map(|(i, _)| i)
             .collect::<Vec<usize>>();
 
@@ -426,6 +482,61 @@ impl RecordSet {
 
         removed
     }
+
+    /// Returns the set with the records converted by `f`, e.g. `Arc::new` to share them
+    pub fn map_records<S, F: FnMut(R) -> S>(self, mut f: F) -> RecordSet<S> {
+        RecordSet {
+            name: self.name,
+            record_type: self.record_type,
+            dns_class: self.dns_class,
+            ttl: self.ttl,
+            records: self.records.into_iter().map(&mut f).collect(),
+            rrsigs: self.rrsigs.into_iter().map(&mut f).collect(),
+            serial: self.serial,
+        }
+    }
+
+    /// Returns a set borrowing the records of this set
+    pub fn borrowed(&self) -> RecordSet<&Record> {
+        RecordSet {
+            name: self.name.clone(),
+            record_type: self.record_type,
+            dns_class: self.dns_class,
+            ttl: self.ttl,
+            records: self
+                .records
+                .iter()
+                .map(<R as Borrow<Record>>::borrow)
+                .collect(),
+            rrsigs: self
+                .rrsigs
+                .iter()
+                .map(<R as Borrow<Record>>::borrow)
+                .collect(),
+            serial: self.serial,
+        }
+    }
+
+    /// Returns a set of owned copies of the records of this set
+    pub fn to_owned_records(&self) -> RecordSet {
+        RecordSet {
+            name: self.name.clone(),
+            record_type: self.record_type,
+            dns_class: self.dns_class,
+            ttl: self.ttl,
+            records: self
+                .records
+                .iter()
+                .map(|r| Record::clone(r.borrow()))
+                .collect(),
+            rrsigs: self
+                .rrsigs
+                .iter()
+                .map(|r| Record::clone(r.borrow()))
+                .collect(),
+            serial: self.serial,
+        }
+    }
 }
 
 impl From<Record> for RecordSet {
@@ -456,9 +567,9 @@ impl IntoRecordSet for RecordSet {
     }
 }
 
-impl IntoIterator for RecordSet {
-    type Item = Record;
-    type IntoIter = Chain<vec::IntoIter<Record>, vec::IntoIter<Record>>;
+impl<R> IntoIterator for RecordSet<R> {
+    type Item = R;
+    type IntoIter = Chain<vec::IntoIter<R>, vec::IntoIter<R>>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.records.into_iter().chain(self.rrsigs.into_iter())
@@ -468,32 +579,31 @@ impl IntoIterator for RecordSet {
 /// An iterator over all the records and their signatures
 #[cfg(feature = "dnssec")]
 #[derive(Debug)]
-pub struct RecordsAndRrsigsIter<'r>(Chain<Iter<'r, Record>, RrsigsByAlgorithms<'r>>);
+pub struct RecordsAndRrsigsIter<'r, R: 'r = Record>(Chain<Iter<'r, R>, RrsigsByAlgorithms<'r, R>>);
 
 #[cfg(feature = "dnssec")]
-impl<'r> Iterator for RecordsAndRrsigsIter<'r> {
+impl<'r, R: Borrow<Record>> Iterator for RecordsAndRrsigsIter<'r, R> {
     type Item = &'r Record;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.0.next()
+        self.0.next().map(<R as Borrow<Record>>::borrow)
     }
 }
 
 /// An iterator that limits the record signatures by SupportedAlgorithms
 #[cfg(feature = "dnssec")]
 #[derive(Debug)]
-pub struct RrsigsByAlgorithms<'r> {
-    rrsigs: Iter<'r, Record>,
+pub struct RrsigsByAlgorithms<'r, R: 'r = Record> {
+    rrsigs: Iter<'r, R>,
     supported_algorithms: SupportedAlgorithms,
 }
 
 #[cfg(feature = "dnssec")]
-impl<'r> Iterator for RrsigsByAlgorithms<'r> {
-    type Item = &'r Record;
+impl<'r, R: Borrow<Record>> Iterator for RrsigsByAlgorithms<'r, R> {
+    type Item = &'r R;
 
     fn next(&mut self) -> Option<Self::Item> {
         use rr::dnssec::rdata::DNSSECRData;
-        use rr::dnssec::Algorithm;
 
         let supported_algorithms = self.supported_algorithms;
 
@@ -501,39 +611,32 @@ impl<'r> Iterator for RrsigsByAlgorithms<'r> {
         if supported_algorithms.is_empty() {
             self.rrsigs.next()
         } else {
-            self.rrsigs
-                .by_ref()
-                .filter(|record| {
-                    if let RData::DNSSEC(DNSSECRData::SIG(ref rrsig)) = *record.rdata() {
-                        supported_algorithms.has(rrsig.algorithm())
-                    } else {
-                        false
-                    }
-                })
-                .max_by_key(|record| {
-                    if let RData::DNSSEC(DNSSECRData::SIG(ref rrsig)) = *record.rdata() {
-                        rrsig.algorithm()
-                    } else {
-                        Algorithm::RSASHA1
-                    }
-                })
+            // the signatures of all the keys are returned, the resolver may only trust some of them
+            self.rrsigs.by_ref().find(|record| {
+                let record: &Record = (*record).borrow();
+                if let RData::DNSSEC(DNSSECRData::SIG(ref rrsig)) = *record.rdata() {
+                    supported_algorithms.has(rrsig.algorithm())
+                } else {
+                    false
+                }
+            })
         }
     }
 }
 
 /// An iterator over the RecordSet data
 #[derive(Debug)]
-pub enum RrsetRecords<'r> {
+pub enum RrsetRecords<'r, R: 'r = Record> {
     /// There are no records in the record set
     Empty,
     /// The records associated with the record set
-    RecordsOnly(Iter<'r, Record>),
+    RecordsOnly(Iter<'r, R>),
     /// The records along with their signatures in the record set
     #[cfg(feature = "dnssec")]
-    RecordsAndRrsigs(RecordsAndRrsigsIter<'r>),
+    RecordsAndRrsigs(RecordsAndRrsigsIter<'r, R>),
 }
 
-impl<'r> RrsetRecords<'r> {
+impl<'r, R> RrsetRecords<'r, R> {
     /// This is a best effort emptyness check
     pub fn is_empty(&self) -> bool {
         match *self {
@@ -543,13 +646,13 @@ impl<'r> RrsetRecords<'r> {
     }
 }
 
-impl<'r> Iterator for RrsetRecords<'r> {
+impl<'r, R: Borrow<Record>> Iterator for RrsetRecords<'r, R> {
     type Item = &'r Record;
 
     fn next(&mut self) -> Option<Self::Item> {
         match self {
             RrsetRecords::Empty => None,
-            RrsetRecords::RecordsOnly(i) => i.next(),
+            RrsetRecords::RecordsOnly(i) => i.next().map(<R as Borrow<Record>>::borrow),
             #[cfg(feature = "dnssec")]
             RrsetRecords::RecordsAndRrsigs(i) => i.next(),
         }
@@ -930,5 +1033,142 @@ mod test {
                 false
             }
         }));
+
+        // the signatures of all the keys of the supported algorithms are returned, e.g. during
+        //  a rollover
+        let rsasha256_other_key = SIG::new(
+            RecordType::A,
+            Algorithm::RSASHA256,
+            0,
+            0,
+            0,
+            0,
+            1,
+            Name::root(),
+            vec![],
+        );
+        rrset.insert_rrsig(
+            Record::new()
+                .set_name(name.clone())
+                .set_ttl(3600)
+                .set_rr_type(RecordType::DNSSEC(DNSSECRecordType::RRSIG))
+                .set_dns_class(DNSClass::IN)
+                .set_rdata(RData::DNSSEC(DNSSECRData::SIG(rsasha256_other_key)))
+                .clone(),
+        );
+        let rrsigs = |supported_algorithms| {
+            rrset
+                .records_with_rrsigs(supported_algorithms)
+                .filter(|r| r.rr_type() == RecordType::DNSSEC(DNSSECRecordType::RRSIG))
+                .count()
+        };
+
+        let mut supported_algorithms = SupportedAlgorithms::new();
+        supported_algorithms.set(Algorithm::RSASHA256);
+        assert_eq!(rrsigs(supported_algorithms), 2);
+        supported_algorithms.set(Algorithm::ECDSAP256SHA256);
+        assert_eq!(rrsigs(supported_algorithms), 3);
+        assert_eq!(rrsigs(SupportedAlgorithms::all()), 5);
+        assert_eq!(rrsigs(SupportedAlgorithms::new()), 5);
+    }
+
+    #[test]
+    #[cfg(feature = "dnssec")]
+    fn test_tbs() {
+        use rr::dnssec::rdata::SIG;
+        use rr::dnssec::{tbs, Algorithm};
+
+        let name = Name::from_str("www.example.com.").unwrap();
+        let mut rrset = RecordSet::with_ttl(name.clone(), RecordType::A, 3600);
+        rrset.add_rdata(RData::A(Ipv4Addr::new(93, 184, 216, 24)));
+        rrset.add_rdata(RData::A(Ipv4Addr::new(93, 184, 216, 25)));
+
+        let pre_sig = SIG::new(
+            RecordType::A,
+            Algorithm::RSASHA256,
+            name.num_labels(),
+            3600,
+            2,
+            1,
+            0x1234,
+            Name::from_str("example.com.").unwrap(),
+            vec![],
+        );
+
+        let records = rrset.records_without_rrsigs().cloned().collect::<Vec<_>>();
+        assert_eq!(
+            rrset.tbs(&pre_sig).unwrap().as_ref(),
+            tbs::rrset_tbs_with_sig(&name, DNSClass::IN, &pre_sig, &records)
+                .unwrap()
+                .as_ref()
+        );
+
+        // the signature must cover the type of the set
+        let other_sig = SIG::new(
+            RecordType::AAAA,
+            Algorithm::RSASHA256,
+            name.num_labels(),
+            3600,
+            2,
+            1,
+            0x1234,
+            Name::from_str("example.com.").unwrap(),
+            vec![],
+        );
+        assert!(rrset.tbs(&other_sig).is_err());
+    }
+
+    #[test]
+    fn test_shared_and_borrowed_records() {
+        use std::sync::Arc;
+
+        let name = Name::from_str("www.example.com.").unwrap();
+        let record =
+            |address: Ipv4Addr, ttl: u32| Record::from_rdata(name.clone(), ttl, RData::A(address));
+
+        let mut rrset = RecordSet::new(&name, RecordType::A, 0);
+        rrset.insert(record(Ipv4Addr::new(93, 184, 216, 24), 86400), 0);
+        rrset.insert(record(Ipv4Addr::new(93, 184, 216, 25), 86400), 0);
+
+        let mut shared = rrset.clone().into_shared();
+        assert!(shared
+            .records_without_rrsigs()
+            .eq(rrset.records_without_rrsigs()));
+
+        // clones share the records
+        let cloned = shared.clone();
+        assert!(Arc::ptr_eq(&shared.records[0], &cloned.records[0]));
+
+        // the records of the shared set follow the same rules, the TTL is not compared
+        assert!(!shared.insert(Arc::new(record(Ipv4Addr::new(93, 184, 216, 24), 86400)), 1));
+        assert!(!shared.insert(Arc::new(record(Ipv4Addr::new(93, 184, 216, 24), 3600)), 1));
+        assert!(shared.insert(Arc::new(record(Ipv4Addr::new(93, 184, 216, 26), 3600)), 2));
+        assert_eq!(shared.records_without_rrsigs().count(), 3);
+        assert_eq!(shared.ttl(), 3600);
+        assert_eq!(shared.serial(), 2);
+        assert!(shared.remove(&record(Ipv4Addr::new(93, 184, 216, 26), 3600), 3));
+        assert_eq!(cloned.records_without_rrsigs().count(), 2);
+
+        let borrowed = rrset.borrowed();
+        assert!(borrowed
+            .records_without_rrsigs()
+            .eq(rrset.records_without_rrsigs()));
+        assert_eq!(borrowed.to_owned_records(), rrset);
+        assert_eq!(cloned.to_owned_records(), rrset);
+    }
+
+    #[test]
+    fn test_set_name() {
+        let name = Name::from_str("*.example.com.").unwrap();
+        let mut rrset = RecordSet::new(&name, RecordType::A, 0);
+        rrset.add_rdata(RData::A(Ipv4Addr::new(93, 184, 216, 24)));
+        rrset.add_rdata(RData::A(Ipv4Addr::new(93, 184, 216, 25)));
+
+        let new_name = Name::from_str("www.example.com.").unwrap();
+        rrset.set_name(new_name.clone());
+        assert_eq!(rrset.name(), &new_name);
+        assert!(rrset
+            .records_without_rrsigs()
+            .all(|record| record.name() == &new_name));
     }
 }
@@ -14,6 +14,9 @@
  * limitations under the License.
  */
 
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
 use byteorder::{ByteOrder, NetworkEndian};
 use error::{ProtoError, ProtoErrorKind, ProtoResult};
 use serialize::binary::Restrict;
@@ -28,6 +31,8 @@ use serialize::binary::Restrict;
 pub struct BinDecoder<'a> {
     buffer: &'a [u8],
     index: usize,
+    #[cfg(feature = "mdns")]
+    mdns: bool,
 }
 
 impl<'a> BinDecoder<'a> {
@@ -37,7 +42,25 @@ impl<'a> BinDecoder<'a> {
     ///
     /// * `buffer` - buffer from which all data will be read
     pub fn new(buffer: &'a [u8]) -> Self {
-        BinDecoder { buffer, index: 0 }
+        BinDecoder {
+            buffer,
+            index: 0,
+            #[cfg(feature = "mdns")]
+            mdns: false,
+        }
+    }
+
+    /// If set to true, the top bit of the class of records and queries is read as the mDNS
+    ///  cache-flush and unicast-response bit, see [RFC 6762](https://tools.ietf.org/html/rfc6762)
+    #[cfg(feature = "mdns")]
+    pub fn set_mdns(&mut self, mdns: bool) {
+        self.mdns = mdns;
+    }
+
+    /// Returns true if the decoder is reading an mDNS message
+    #[cfg(feature = "mdns")]
+    pub fn is_mdns(&self) -> bool {
+        self.mdns
     }
 
     /// Pop one byte from the buffer
@@ -47,7 +70,7 @@ impl<'a> BinDecoder<'a> {
             self.index += 1;
             Ok(Restrict::new(byte))
         } else {
-            Err("unexpected end of input reached".into())
+            Err(self.unexpected_end(1))
         }
     }
 
@@ -91,6 +114,8 @@ impl<'a> BinDecoder<'a> {
         BinDecoder {
             buffer: self.buffer,
             index: index_at as usize,
+            #[cfg(feature = "mdns")]
+            mdns: self.mdns,
         }
     }
 
@@ -130,6 +155,7 @@ impl<'a> BinDecoder<'a> {
                     max: max_len.unwrap_or_default(),
                     len: length,
                 })
+                .at_offset(self.index - 1)
             })?;
 
         self.read_slice(length)
@@ -161,15 +187,23 @@ impl<'a> BinDecoder<'a> {
         let end = self
             .index
             .checked_add(len)
-            .ok_or_else(|| ProtoError::from("invalid length for slice"))?;
+            .ok_or_else(|| self.unexpected_end(len))?;
         if end > self.buffer.len() {
-            return Err("buffer exhausted".into());
+            return Err(self.unexpected_end(len));
         }
         let slice: &'a [u8] = &self.buffer[self.index..end];
         self.index += len;
         Ok(Restrict::new(slice))
     }
 
+    fn unexpected_end(&self, needed: usize) -> ProtoError {
+        ProtoError::from(ProtoErrorKind::UnexpectedEnd {
+            needed,
+            remaining: self.len(),
+        })
+        .at_offset(self.index)
+    }
+
     /// Reads a slice from a previous index to the current
     pub fn slice_from(&self, index: usize) -> ProtoResult<&'a [u8]> {
         if index > self.index {
@@ -221,7 +255,7 @@ impl<'a> BinDecoder<'a> {
     }
 }
 
-#[cfg(tests)]
+#[cfg(test)]
 mod tests {
     use super::*;
 
@@ -231,13 +265,13 @@ mod tests {
         let mut decoder = BinDecoder::new(deadbeef);
 
         let read = decoder.read_slice(4).expect("failed to read dead");
-        assert_eq!(read, "dead");
+        assert_eq!(read.unverified(), b"dead");
 
         let read = decoder.read_slice(2).expect("failed to read be");
-        assert_eq!(read, "be");
+        assert_eq!(read.unverified(), b"be");
 
         let read = decoder.read_slice(0).expect("failed to read nothing");
-        assert_eq!(read, "");
+        assert_eq!(read.unverified(), b"");
 
         // this should fail
         assert!(decoder.read_slice(3).is_err());
@@ -248,20 +282,20 @@ mod tests {
         let deadbeef = b"deadbeef";
         let mut decoder = BinDecoder::new(deadbeef);
 
-        decoder.read_slice_from(4).expect("failed to read dead");
+        decoder.read_slice(4).expect("failed to read dead");
         let read = decoder.slice_from(0).expect("failed to get slice");
-        assert_eq!(read, "dead");
+        assert_eq!(read, b"dead");
 
         decoder.read_slice(2).expect("failed to read be");
         let read = decoder.slice_from(4).expect("failed to get slice");
-        assert_eq!(read, "be");
+        assert_eq!(read, b"be");
 
         decoder.read_slice(0).expect("failed to read nothing");
         let read = decoder.slice_from(4).expect("failed to get slice");
-        assert_eq!(read, "be");
+        assert_eq!(read, b"be");
 
         // this should fail
-        assert!(decoder.slice_from(6).is_err());
+        assert!(decoder.slice_from(7).is_err());
         assert!(decoder.slice_from(10).is_err());
     }
 }
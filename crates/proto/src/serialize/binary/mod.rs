@@ -28,6 +28,9 @@ pub use self::restrict::{Restrict, RestrictedMath, Verified};
 #[cfg(test)]
 pub mod bin_tests;
 
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
 use error::*;
 
 /// A type which can be encoded into a DNS binary format
@@ -53,9 +56,11 @@ pub trait BinDecodable<'r>: Sized {
     fn read(decoder: &mut BinDecoder<'r>) -> ProtoResult<Self>;
 
     /// Returns the object in binary form
+    ///
+    /// Errors have the offset in `bytes` at which they were found, see `ProtoError::offset`
     fn from_bytes(bytes: &'r [u8]) -> ProtoResult<Self> {
         let mut decoder = BinDecoder::new(bytes);
-        Self::read(&mut decoder)
+        Self::read(&mut decoder).map_err(|e| e.at_offset(decoder.index()))
     }
 }
 
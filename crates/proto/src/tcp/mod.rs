@@ -17,7 +17,9 @@
 //! TCP protocol related components for DNS
 
 mod tcp_client_stream;
+mod tcp_options;
 mod tcp_stream;
 
 pub use self::tcp_client_stream::{TcpClientConnect, TcpClientStream};
+pub use self::tcp_options::TcpOptions;
 pub use self::tcp_stream::TcpStream;
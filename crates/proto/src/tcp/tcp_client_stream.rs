@@ -14,7 +14,7 @@ use tokio_io::{AsyncRead, AsyncWrite};
 use tokio_tcp::TcpStream as TokioTcpStream;
 
 use error::ProtoError;
-use tcp::TcpStream;
+use tcp::{TcpOptions, TcpStream};
 use xfer::{DnsClientStream, SerialMessage};
 use BufDnsStreamHandle;
 use DnsStreamHandle;
@@ -50,7 +50,22 @@ impl TcpClientStream<TokioTcpStream> {
         name_server: SocketAddr,
         timeout: Duration,
     ) -> (TcpClientConnect, Box<DnsStreamHandle + Send>) {
-        let (stream_future, sender) = TcpStream::with_timeout(name_server, timeout);
+        Self::with_options(name_server, timeout, TcpOptions::default())
+    }
+
+    /// Constructs a new TcpStream for a client to the specified SocketAddr.
+    ///
+    /// # Arguments
+    ///
+    /// * `name_server` - the IP and Port of the DNS server to connect to
+    /// * `timeout` - connection timeout
+    /// * `options` - socket options of the connection, e.g. TCP Fast Open
+    pub fn with_options(
+        name_server: SocketAddr,
+        timeout: Duration,
+        options: TcpOptions,
+    ) -> (TcpClientConnect, Box<DnsStreamHandle + Send>) {
+        let (stream_future, sender) = TcpStream::with_options(name_server, timeout, options);
 
         let new_future = Box::new(
             stream_future
@@ -0,0 +1,236 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Socket options of the TCP connections of clients and servers
+
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use futures::{Future, IntoFuture};
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio_reactor::Handle;
+use tokio_tcp::{TcpListener, TcpStream as TokioTcpStream};
+
+/// The number of pending TCP Fast Open connections of listeners
+const FAST_OPEN_QUEUE_LEN: i32 = 256;
+
+/// Socket options of TCP connections
+///
+/// The defaults of the operating system delay small messages, e.g. the first query of a DNS over
+///  TLS connection waits for the acknowledgement of the handshake, Nagle's algorithm, and lost
+///  peers are only detected after several hours. All the options are disabled by default, which
+///  leaves the defaults of the operating system.
+///
+/// TCP Fast Open, [RFC 7413](https://tools.ietf.org/html/rfc7413), sends the first message with
+///  the SYN of the connection, saving a round trip when the client has a cookie of the server.
+///  Fast Open and the user timeout are only supported on Linux, they are ignored elsewhere.
+///
+/// The `bind_addr` selects the source address of the connections of multi-homed hosts, as the
+///  `transfer-source` of BIND, it is ignored by listeners.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde-config", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde-config", serde(default))]
+pub struct TcpOptions {
+    /// Sends messages without waiting for the acknowledgement of the previous ones, `TCP_NODELAY`
+    pub nodelay: bool,
+    /// Interval of the keepalive probes of idle connections, `SO_KEEPALIVE`, none to not send any
+    pub keepalive: Option<Duration>,
+    /// Time after which the connection is closed when sent data is not acknowledged,
+    ///  `TCP_USER_TIMEOUT`, none for the default of the operating system
+    pub user_timeout: Option<Duration>,
+    /// Sends the data with the SYN of connections, and accepts it on listeners, `TCP_FASTOPEN`
+    pub fast_open: bool,
+    /// The local address of the connections, with a random port, none for the address of the
+    ///  route to the name server
+    pub bind_addr: Option<IpAddr>,
+}
+
+impl TcpOptions {
+    /// Connects to the name server with these options
+    ///
+    /// The options are set before the connection is established, so that the first message can
+    ///  be sent with the SYN with Fast Open.
+    pub fn connect(
+        &self,
+        name_server: SocketAddr,
+    ) -> Box<Future<Item = TokioTcpStream, Error = io::Error> + Send> {
+        if *self == TcpOptions::default() {
+            return Box::new(TokioTcpStream::connect(&name_server));
+        }
+
+        let options = *self;
+        let domain = match name_server {
+            SocketAddr::V4(..) => Domain::ipv4(),
+            SocketAddr::V6(..) => Domain::ipv6(),
+        };
+
+        let connect = Socket::new(domain, Type::stream(), Some(Protocol::tcp()))
+            .and_then(|socket| {
+                options.set_socket_options(&socket)?;
+                if options.fast_open {
+                    sys::set_fast_open_connect(&socket)?;
+                }
+                if let Some(bind_addr) = options.bind_addr {
+                    socket.bind(&SocketAddr::new(bind_addr, 0).into())?;
+                }
+                Ok(socket)
+            })
+            .into_future()
+            .and_then(move |socket| {
+                TokioTcpStream::connect_std(
+                    socket.into_tcp_stream(),
+                    &name_server,
+                    &Handle::default(),
+                )
+            });
+
+        Box::new(connect)
+    }
+
+    /// Sets the options of a connected stream, e.g. accepted by a listener
+    pub fn set_stream_options(&self, stream: &TokioTcpStream) -> io::Result<()> {
+        if self.nodelay {
+            stream.set_nodelay(true)?;
+        }
+        if self.keepalive.is_some() {
+            stream.set_keepalive(self.keepalive)?;
+        }
+        if let Some(user_timeout) = self.user_timeout {
+            sys::set_user_timeout(stream, user_timeout)?;
+        }
+
+        Ok(())
+    }
+
+    /// Accepts the data sent with the SYN of the connections to the listener, if `fast_open`
+    ///
+    /// The other options are set on each of the accepted streams, see `set_stream_options`.
+    pub fn set_listener_options(&self, listener: &TcpListener) -> io::Result<()> {
+        if self.fast_open {
+            sys::set_fast_open(listener, FAST_OPEN_QUEUE_LEN)?;
+        }
+
+        Ok(())
+    }
+
+    fn set_socket_options(&self, socket: &Socket) -> io::Result<()> {
+        if self.nodelay {
+            socket.set_nodelay(true)?;
+        }
+        if self.keepalive.is_some() {
+            socket.set_keepalive(self.keepalive)?;
+        }
+        if let Some(user_timeout) = self.user_timeout {
+            sys::set_user_timeout(socket, user_timeout)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use std::io;
+    use std::mem;
+    use std::os::unix::io::AsRawFd;
+    use std::time::Duration;
+
+    use libc::{c_int, c_void, socklen_t, IPPROTO_TCP};
+
+    fn set_option<S: AsRawFd>(socket: &S, option: c_int, value: c_int) -> io::Result<()> {
+        let result = unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                IPPROTO_TCP,
+                option,
+                &value as *const c_int as *const c_void,
+                mem::size_of::<c_int>() as socklen_t,
+            )
+        };
+
+        if result == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    pub fn set_user_timeout<S: AsRawFd>(socket: &S, user_timeout: Duration) -> io::Result<()> {
+        let millis = user_timeout.as_secs() * 1_000 + u64::from(user_timeout.subsec_millis());
+        let millis = if millis > c_int::max_value() as u64 {
+            c_int::max_value()
+        } else {
+            millis as c_int
+        };
+
+        set_option(socket, libc::TCP_USER_TIMEOUT, millis)
+    }
+
+    pub fn set_fast_open<S: AsRawFd>(listener: &S, queue_len: c_int) -> io::Result<()> {
+        set_option(listener, libc::TCP_FASTOPEN, queue_len)
+    }
+
+    /// Fast Open of connect, since Linux 4.11, older kernels fail and connect without it
+    pub fn set_fast_open_connect<S: AsRawFd>(socket: &S) -> io::Result<()> {
+        if let Err(e) = set_option(socket, libc::TCP_FASTOPEN_CONNECT, 1) {
+            debug!("TCP Fast Open is not supported: {}", e);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod sys {
+    use std::io;
+    use std::time::Duration;
+
+    pub fn set_user_timeout<S>(_socket: &S, _user_timeout: Duration) -> io::Result<()> {
+        Ok(())
+    }
+
+    pub fn set_fast_open<S>(_listener: &S, _queue_len: i32) -> io::Result<()> {
+        Ok(())
+    }
+
+    pub fn set_fast_open_connect<S>(_socket: &S) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use tokio::runtime::current_thread::Runtime;
+
+    use super::*;
+
+    #[test]
+    fn test_connect_with_options() {
+        let mut runtime = Runtime::new().unwrap();
+        let listener = TcpListener::bind(&(Ipv4Addr::LOCALHOST, 0).into()).unwrap();
+        let options = TcpOptions {
+            nodelay: true,
+            keepalive: Some(Duration::from_secs(30)),
+            user_timeout: Some(Duration::from_secs(10)),
+            fast_open: true,
+            bind_addr: Some(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+        };
+        options.set_listener_options(&listener).unwrap();
+
+        let stream = runtime
+            .block_on(options.connect(listener.local_addr().unwrap()))
+            .expect("failed to connect");
+
+        assert_eq!(stream.local_addr().unwrap().ip(), Ipv4Addr::LOCALHOST);
+        assert!(stream.nodelay().unwrap());
+        assert_eq!(stream.keepalive().unwrap(), Some(Duration::from_secs(30)));
+        options.set_stream_options(&stream).unwrap();
+    }
+}
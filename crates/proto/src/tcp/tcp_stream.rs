@@ -20,6 +20,7 @@ use tokio_tcp::TcpStream as TokioTcpStream;
 use tokio_timer::Timeout;
 
 use error::*;
+use tcp::TcpOptions;
 use xfer::{BufStreamHandle, SerialMessage};
 
 /// Current state while writing to the remote of the TCP connection
@@ -111,13 +112,31 @@ impl TcpStream<TokioTcpStream> {
     ) -> (
         Box<Future<Item = TcpStream<TokioTcpStream>, Error = io::Error> + Send>,
         BufStreamHandle,
+    ) {
+        Self::with_options(name_server, timeout, TcpOptions::default())
+    }
+
+    /// Creates a new future of the eventually establish a IO stream connection or fail trying
+    ///
+    /// # Arguments
+    ///
+    /// * `name_server` - the IP and Port of the DNS server to connect to
+    /// * `timeout` - connection timeout
+    /// * `options` - socket options of the connection, e.g. TCP Fast Open
+    pub fn with_options(
+        name_server: SocketAddr,
+        timeout: Duration,
+        options: TcpOptions,
+    ) -> (
+        Box<Future<Item = TcpStream<TokioTcpStream>, Error = io::Error> + Send>,
+        BufStreamHandle,
     ) {
         let (message_sender, outbound_messages) = unbounded();
         let message_sender = BufStreamHandle::new(message_sender);
 
         // This set of futures collapses the next tcp socket into a stream which can be used for
         //  sending and receiving tcp packets.
-        let tcp = TokioTcpStream::connect(&name_server);
+        let tcp = options.connect(name_server);
         let stream = Timeout::new(tcp, timeout)
             .map_err(move |e| {
                 debug!("timed out connecting to: {}", name_server);
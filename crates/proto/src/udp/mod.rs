@@ -17,7 +17,13 @@
 //! UDP protocol related components for DNS
 
 mod udp_client_stream;
+mod udp_multiplexed_client_stream;
+mod udp_offload;
 mod udp_stream;
 
 pub use self::udp_client_stream::{UdpClientConnect, UdpClientStream, UdpResponse};
+pub use self::udp_multiplexed_client_stream::{
+    UdpMultiplexedClientConnect, UdpMultiplexedClientStream,
+};
+pub use self::udp_offload::UdpOffload;
 pub use self::udp_stream::UdpStream;
@@ -7,17 +7,20 @@
 
 use std::borrow::Borrow;
 use std::fmt::{self, Display};
-use std::net::SocketAddr;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use futures::{Async, Future, Poll, Stream};
+use tokio_reactor::Handle;
 use tokio_timer::Timeout;
 use tokio_udp;
 
-use error::ProtoError;
+use capture::{self, Direction};
+use error::{ProtoError, ProtoErrorKind};
 use op::message::NoopMessageFinalizer;
-use op::{Message, MessageFinalizer, OpCode};
+use op::{Message, MessageFinalizer, MessageVerifier};
 use udp::udp_stream::NextRandomUdpSocket;
 use xfer::{DnsRequest, DnsRequestSender, DnsResponse, SerialMessage};
 
@@ -31,6 +34,7 @@ where
     MF: MessageFinalizer,
 {
     name_server: SocketAddr,
+    bind_addr: Option<IpAddr>,
     timeout: Duration,
     is_shutdown: bool,
     signer: Option<Arc<MF>>,
@@ -62,6 +66,22 @@ impl UdpClientStream<NoopMessageFinalizer> {
     ) -> UdpClientConnect<NoopMessageFinalizer> {
         Self::with_timeout_and_signer(name_server, timeout, None)
     }
+
+    /// Constructs a new UdpStream for a client to the specified SocketAddr, from the local address.
+    ///
+    /// # Arguments
+    ///
+    /// * `name_server` - the IP and Port of the DNS server to connect to
+    /// * `bind_addr` - the local address of the requests, e.g. of a multi-homed host, each
+    ///                 request is sent from a random port
+    /// * `timeout` - connection timeout
+    pub fn with_bind_addr(
+        name_server: SocketAddr,
+        bind_addr: IpAddr,
+        timeout: Duration,
+    ) -> UdpClientConnect<NoopMessageFinalizer> {
+        Self::with_bind_addr_and_signer(name_server, Some(bind_addr), timeout, None)
+    }
 }
 
 impl<MF: MessageFinalizer> UdpClientStream<MF> {
@@ -75,9 +95,27 @@ impl<MF: MessageFinalizer> UdpClientStream<MF> {
         name_server: SocketAddr,
         timeout: Duration,
         signer: Option<Arc<MF>>,
+    ) -> UdpClientConnect<MF> {
+        Self::with_bind_addr_and_signer(name_server, None, timeout, signer)
+    }
+
+    /// Constructs a new UdpStream for a client to the specified SocketAddr, from the local address.
+    ///
+    /// # Arguments
+    ///
+    /// * `name_server` - the IP and Port of the DNS server to connect to
+    /// * `bind_addr` - the local address of the requests, none for any address
+    /// * `timeout` - connection timeout
+    /// * `signer` - signer of the requests, e.g. with TSIG
+    pub fn with_bind_addr_and_signer(
+        name_server: SocketAddr,
+        bind_addr: Option<IpAddr>,
+        timeout: Duration,
+        signer: Option<Arc<MF>>,
     ) -> UdpClientConnect<MF> {
         UdpClientConnect {
             name_server: Some(name_server),
+            bind_addr,
             timeout,
             signer,
         }
@@ -107,8 +145,11 @@ impl<MF: MessageFinalizer> DnsRequestSender for UdpClientStream<MF> {
         }
 
         // associated the ID for this request, b/c this connection is uniquw to socket port, the ID
-        //   does not need to be globally unique
-        message.set_id(random_query_id());
+        //   does not need to be globally unique, raw requests are sent as they are
+        let raw = message.is_raw();
+        if !raw {
+            message.set_id(random_query_id());
+        }
 
         let now = match SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -128,20 +169,28 @@ impl<MF: MessageFinalizer> DnsRequestSender for UdpClientStream<MF> {
         // TODO: truncates u64 to u32, error on overflow?
         let now = now as u32;
 
-        // TODO: move this logic into Message::finalize?
-        if let OpCode::Update = message.op_code() {
-            if let Some(ref signer) = self.signer {
-                if let Err(e) = message.finalize::<MF>(signer.borrow(), now) {
-                    debug!("could not sign message: {}", e);
-                    return UdpResponse(Timeout::new(
-                        SingleUseUdpSocket::Errored(Some(e)),
-                        self.timeout,
-                    ));
+        let mut verifier = None;
+        if let Some(ref signer) = self.signer {
+            if !raw && signer.should_finalize_message(&message) {
+                match message.finalize::<MF>(signer.borrow(), now) {
+                    Ok(answer_verifier) => verifier = answer_verifier,
+                    Err(e) => {
+                        debug!("could not sign message: {}", e);
+                        return UdpResponse(Timeout::new(
+                            SingleUseUdpSocket::Errored(Some(e)),
+                            self.timeout,
+                        ));
+                    }
                 }
             }
         }
 
-        let bytes = match message.to_vec() {
+        let bytes = if raw {
+            message.to_raw_vec()
+        } else {
+            message.to_vec()
+        };
+        let bytes = match bytes {
             Ok(bytes) => bytes,
             Err(err) => {
                 return UdpResponse(Timeout::new(
@@ -154,7 +203,7 @@ impl<MF: MessageFinalizer> DnsRequestSender for UdpClientStream<MF> {
         let message_id = message.id();
         let message = SerialMessage::new(bytes, self.name_server);
 
-        UdpResponse::new(message, message_id, self.timeout)
+        UdpResponse::new(message, self.bind_addr, message_id, verifier, self.timeout)
     }
 
     fn error_response(err: ProtoError) -> Self::DnsResponseFuture {
@@ -197,10 +246,18 @@ impl UdpResponse {
     /// # Arguments
     ///
     /// * `request` - Serialized message being sent
+    /// * `bind_addr` - local address from which the request is sent, none for any address
     /// * `message_id` - Id of the message that was encoded in the serial message
-    fn new(request: SerialMessage, message_id: u16, timeout: Duration) -> Self {
+    /// * `verifier` - verifier of the response, responses failing verification are dropped
+    fn new(
+        request: SerialMessage,
+        bind_addr: Option<IpAddr>,
+        message_id: u16,
+        verifier: Option<MessageVerifier>,
+        timeout: Duration,
+    ) -> Self {
         UdpResponse(Timeout::new(
-            SingleUseUdpSocket::StartSend(Some(request), message_id),
+            SingleUseUdpSocket::StartSend(Some(request), bind_addr, message_id, verifier),
             timeout,
         ))
     }
@@ -221,6 +278,7 @@ where
     MF: MessageFinalizer,
 {
     name_server: Option<SocketAddr>,
+    bind_addr: Option<IpAddr>,
     timeout: Duration,
     signer: Option<Arc<MF>>,
 }
@@ -235,6 +293,7 @@ impl<MF: MessageFinalizer> Future for UdpClientConnect<MF> {
                 .name_server
                 .take()
                 .expect("UdpClientConnect invalid state: name_server"),
+            bind_addr: self.bind_addr,
             is_shutdown: false,
             timeout: self.timeout,
             signer: self.signer.take(),
@@ -243,14 +302,64 @@ impl<MF: MessageFinalizer> Future for UdpClientConnect<MF> {
 }
 
 enum SingleUseUdpSocket {
-    StartSend(Option<SerialMessage>, u16),
-    Connect(Option<SerialMessage>, NextRandomUdpSocket, u16),
-    Send(Option<SerialMessage>, Option<tokio_udp::UdpSocket>, u16),
-    AwaitResponse(Option<SerialMessage>, tokio_udp::UdpSocket, u16),
+    StartSend(
+        Option<SerialMessage>,
+        Option<IpAddr>,
+        u16,
+        Option<MessageVerifier>,
+    ),
+    Connect(
+        Option<SerialMessage>,
+        NextRandomUdpSocket,
+        u16,
+        Option<MessageVerifier>,
+    ),
+    Send(
+        Option<SerialMessage>,
+        Option<ConnectedUdpSocket>,
+        u16,
+        Option<MessageVerifier>,
+    ),
+    AwaitResponse(
+        Option<SerialMessage>,
+        ConnectedUdpSocket,
+        u16,
+        Option<MessageVerifier>,
+    ),
     Response(Option<Message>),
     Errored(Option<ProtoError>),
 }
 
+/// A socket connected to the name server, the OS reports the ICMP errors of the name server on
+///  it, e.g. port unreachable, and drops the packets of other sources
+struct ConnectedUdpSocket {
+    socket: tokio_udp::UdpSocket,
+    // a clone of the socket, the errors don't make the tokio socket readable
+    errors: std::net::UdpSocket,
+}
+
+impl ConnectedUdpSocket {
+    fn connect(socket: std::net::UdpSocket, name_server: SocketAddr) -> io::Result<Self> {
+        socket.connect(name_server)?;
+        let errors = socket.try_clone()?;
+        let socket = tokio_udp::UdpSocket::from_std(socket, &Handle::default())?;
+
+        Ok(ConnectedUdpSocket { socket, errors })
+    }
+
+    /// Receives the response, or the error reported for the name server
+    fn poll_recv(&mut self, buf: &mut [u8]) -> Poll<usize, io::Error> {
+        match self.socket.poll_recv(buf)? {
+            Async::Ready(len) => Ok(Async::Ready(len)),
+            // the error may have been reported before the socket was polled
+            Async::NotReady => match self.errors.take_error()? {
+                Some(e) => Err(e),
+                None => Ok(Async::NotReady),
+            },
+        }
+    }
+}
+
 impl Future for SingleUseUdpSocket {
     type Item = DnsResponse;
     type Error = ProtoError;
@@ -258,35 +367,50 @@ impl Future for SingleUseUdpSocket {
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
         loop {
             *self = match *self {
-                SingleUseUdpSocket::StartSend(ref mut msg, msg_id) => {
+                SingleUseUdpSocket::StartSend(ref mut msg, bind_addr, msg_id, ref mut verifier) => {
                     // get a new socket to use
                     let msg = msg.take();
                     let name_server = msg
                         .as_ref()
                         .expect("SingleUseUdpSocket::StartSend invalid state: msg")
                         .addr();
-                    SingleUseUdpSocket::Connect(msg, NextRandomUdpSocket::new(&name_server), msg_id)
+                    let next_socket = match bind_addr {
+                        Some(bind_addr) => NextRandomUdpSocket::with_bind_addr(bind_addr),
+                        None => NextRandomUdpSocket::new(&name_server),
+                    };
+                    SingleUseUdpSocket::Connect(msg, next_socket, msg_id, verifier.take())
                 }
-                SingleUseUdpSocket::Connect(ref mut msg, ref mut future_socket, msg_id) => {
-                    let socket = try_ready!(future_socket.poll());
-                    // TODO: connect the socket here on merge into master
+                SingleUseUdpSocket::Connect(
+                    ref mut msg,
+                    ref mut future_socket,
+                    msg_id,
+                    ref mut verifier,
+                ) => {
+                    let socket = try_ready!(future_socket.poll_std());
+                    let name_server = msg
+                        .as_ref()
+                        .expect("SingleUseUdpSocket::Connect invalid state: msg")
+                        .addr();
+                    let socket = ConnectedUdpSocket::connect(socket, name_server)?;
 
                     // send the message, and then await the response
-                    SingleUseUdpSocket::Send(msg.take(), Some(socket), msg_id)
+                    SingleUseUdpSocket::Send(msg.take(), Some(socket), msg_id, verifier.take())
                 }
-                SingleUseUdpSocket::Send(ref mut msg, ref mut socket, msg_id) => {
+                SingleUseUdpSocket::Send(ref mut msg, ref mut socket, msg_id, ref mut verifier) => {
                     try_ready!(socket
                         .as_mut()
                         .expect("SingleUseUdpSocket::Send invalid state: socket1")
-                        .poll_send_to(
+                        .socket
+                        .poll_send(
                             msg.as_ref()
                                 .expect("SingleUseUdpSocket::Send invalid state: msg1")
-                                .bytes(),
-                            &msg.as_ref()
-                                .expect("SingleUseUdpSocket::Send invalid state: msg2")
-                                .addr()
+                                .bytes()
                         ));
 
+                    if let Some(ref msg) = *msg {
+                        capture::record(Direction::Sent, msg.addr(), msg.bytes());
+                    }
+
                     // message is sent, await the response
                     SingleUseUdpSocket::AwaitResponse(
                         msg.take(),
@@ -294,31 +418,37 @@ impl Future for SingleUseUdpSocket {
                             .take()
                             .expect("SingleUseUdpSocket::Send invalid state: socket2"),
                         msg_id,
+                        verifier.take(),
                     )
                 }
-                SingleUseUdpSocket::AwaitResponse(ref mut request, ref mut socket, msg_id) => {
+                SingleUseUdpSocket::AwaitResponse(
+                    ref mut request,
+                    ref mut socket,
+                    msg_id,
+                    ref mut verifier,
+                ) => {
                     // TODO: consider making this heap based? need to verify it matches EDNS settings
                     let mut buf = [0u8; 2048];
 
-                    let (len, src) = try_ready!(socket.poll_recv_from(&mut buf));
-                    let response = SerialMessage::new(buf.iter().take(len).cloned().collect(), src);
-
-                    // compare expected src to received packet
-                    let request_target = request
+                    // the socket is connected, only the packets of the name server are received
+                    let name_server = request
                         .as_ref()
                         .expect("SingleUseUdpSocket::AwaitResponse invalid state: msg")
                         .addr();
 
-                    if response.addr() != request_target {
-                        warn!(
-                            "ignoring response from {} because it does not match name_server: {}.",
-                            response.addr(),
-                            request_target
-                        );
-
-                        // await an answer from the correct NameServer
-                        continue;
-                    }
+                    // errors are those reported by ICMP, there is no need to await the timeout
+                    let len = match socket.poll_recv(&mut buf) {
+                        Ok(Async::Ready(len)) => len,
+                        Ok(Async::NotReady) => return Ok(Async::NotReady),
+                        Err(e) => {
+                            debug!("name server {} unreachable: {}", name_server, e);
+                            return Err(ProtoError::from(ProtoErrorKind::Unreachable(name_server))
+                                .with_source(e));
+                        }
+                    };
+                    let response =
+                        SerialMessage::new(buf.iter().take(len).cloned().collect(), name_server);
+                    capture::record(Direction::Received, name_server, response.bytes());
 
                     // TODO: match query strings from request and response?
 
@@ -326,6 +456,22 @@ impl Future for SingleUseUdpSocket {
                         Ok(message) => {
                             if msg_id == message.id() {
                                 debug!("received message id: {}", message.id());
+                                if let Some(ref mut verifier) = *verifier {
+                                    match verifier(response.bytes()) {
+                                        Ok(true) => (),
+                                        Ok(false) => {
+                                            warn!("dropped unverified message id: {}", msg_id);
+                                            continue;
+                                        }
+                                        Err(e) => {
+                                            warn!(
+                                                "dropped unverified message id: {} err: {}",
+                                                msg_id, e
+                                            );
+                                            continue;
+                                        }
+                                    }
+                                }
                                 SingleUseUdpSocket::Response(Some(message))
                             } else {
                                 // on wrong id, attempted poison?
@@ -370,11 +516,11 @@ impl Future for SingleUseUdpSocket {
     }
 }
 
+#[cfg(test)]
+use std::net::Ipv4Addr;
 #[cfg(not(target_os = "linux"))]
 #[cfg(test)]
 use std::net::Ipv6Addr;
-#[cfg(test)]
-use std::net::{IpAddr, Ipv4Addr};
 
 #[test]
 fn test_udp_client_stream_ipv4() {
@@ -387,6 +533,39 @@ fn test_udp_client_stream_ipv6() {
     udp_client_stream_test(IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1)))
 }
 
+#[test]
+fn test_udp_client_stream_unreachable() {
+    use op::Query;
+    use rr::{Name, RecordType};
+    use std::str::FromStr;
+    use std::time::Instant;
+    use tokio::runtime::current_thread::Runtime;
+
+    // a port on which nothing listens, answered with an ICMP port unreachable
+    let name_server = std::net::UdpSocket::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    let mut query = Message::new();
+    query.add_query(Query::query(
+        Name::from_str("dead.beef").unwrap(),
+        RecordType::NULL,
+    ));
+
+    let mut io_loop = Runtime::new().unwrap();
+    let stream = UdpClientStream::with_timeout(name_server, Duration::from_secs(5));
+    let mut stream: UdpClientStream = io_loop.block_on(stream).ok().unwrap();
+
+    let start = Instant::now();
+    let error = io_loop
+        .block_on(stream.send_message(DnsRequest::new(query, Default::default())))
+        .expect_err("no response expected");
+
+    assert_eq!(*error.kind(), ProtoErrorKind::Unreachable(name_server));
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
 #[cfg(test)]
 fn udp_client_stream_test(server_addr: IpAddr) {
     use op::Query;
@@ -0,0 +1,213 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::fmt::{self, Display};
+use std::net::SocketAddr;
+
+use futures::{Async, Future, Poll, Stream};
+
+use error::ProtoError;
+use udp::UdpStream;
+use xfer::{DnsClientStream, SerialMessage};
+use BufDnsStreamHandle;
+use DnsStreamHandle;
+
+/// A UDP client stream of DNS binary packets, over a single socket
+///
+/// Unlike `UdpClientStream`, which binds a new socket for every request, all the requests share
+///  one socket on a random port. Use with `DnsMultiplexer`, which matches the responses to the
+///  requests by their ID and times out each request on its own. This saves a file descriptor per
+///  request for clients with many concurrent queries, at the cost of the port randomization of
+///  each query: the responses are only protected from spoofing by the random IDs.
+#[must_use = "futures do nothing unless polled"]
+pub struct UdpMultiplexedClientStream {
+    name_server: SocketAddr,
+    udp_stream: UdpStream,
+}
+
+impl UdpMultiplexedClientStream {
+    /// Binds a socket on a random port for the requests to the name server
+    ///
+    /// # Arguments
+    ///
+    /// * `name_server` - the IP and Port of the DNS server to send the requests to
+    ///
+    /// # Return
+    ///
+    /// a tuple of a Future Stream which will handle sending and receiving messsages, and a
+    ///  handle which can be used to send messages into the stream.
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(
+        name_server: SocketAddr,
+    ) -> (UdpMultiplexedClientConnect, Box<DnsStreamHandle + Send>) {
+        let (stream_future, sender) = UdpStream::new(name_server);
+
+        let new_future = Box::new(
+            stream_future
+                .map(move |udp_stream| UdpMultiplexedClientStream {
+                    name_server,
+                    udp_stream,
+                })
+                .map_err(ProtoError::from),
+        );
+
+        let sender = Box::new(BufDnsStreamHandle::new(name_server, sender));
+
+        (UdpMultiplexedClientConnect(new_future), sender)
+    }
+}
+
+impl Display for UdpMultiplexedClientStream {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(formatter, "UDP({})", self.name_server)
+    }
+}
+
+impl DnsClientStream for UdpMultiplexedClientStream {
+    fn name_server_addr(&self) -> SocketAddr {
+        self.name_server
+    }
+}
+
+impl Stream for UdpMultiplexedClientStream {
+    type Item = SerialMessage;
+    type Error = ProtoError;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            match try_ready!(self.udp_stream.poll().map_err(ProtoError::from)) {
+                // the socket is not connected, anyone can send to it
+                Some(message) if message.addr() != self.name_server => warn!(
+                    "dropped response from {}, it does not match name_server: {}",
+                    message.addr(),
+                    self.name_server
+                ),
+                message => return Ok(Async::Ready(message)),
+            }
+        }
+    }
+}
+
+/// A future that resolves to an UdpMultiplexedClientStream
+pub struct UdpMultiplexedClientConnect(
+    Box<Future<Item = UdpMultiplexedClientStream, Error = ProtoError> + Send>,
+);
+
+impl Future for UdpMultiplexedClientConnect {
+    type Item = UdpMultiplexedClientStream;
+    type Error = ProtoError;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        self.0.poll()
+    }
+}
+
+#[test]
+fn test_udp_multiplexed_client_stream() {
+    use futures::future;
+    use op::message::NoopMessageFinalizer;
+    use op::{Message, Query};
+    use rr::rdata::NULL;
+    use rr::{Name, RData, Record, RecordType};
+    use std::collections::HashSet;
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::str::FromStr;
+    use std::time::Duration;
+    use tokio::runtime::current_thread::Runtime;
+    use xfer::{BufDnsRequestStreamHandle, DnsExchange, DnsHandle, DnsMultiplexer};
+
+    let localhost = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+    let server = std::net::UdpSocket::bind(localhost).unwrap();
+    server
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let server_addr = server.local_addr().unwrap();
+    let spoofer = std::net::UdpSocket::bind(localhost).unwrap();
+
+    let names = (0..10)
+        .map(|i| Name::from_str(&format!("{}.dead.beef.", i)).unwrap())
+        .collect::<Vec<_>>();
+
+    let expected = names.len();
+    let server_handle = std::thread::spawn(move || {
+        let mut buffer = [0_u8; 512];
+        let mut requests = Vec::new();
+        let mut sources = HashSet::new();
+
+        // receive all the queries before answering any of them
+        for _ in 0..expected {
+            let (len, addr) = server.recv_from(&mut buffer).expect("receive failed");
+            requests.push(Message::from_vec(&buffer[0..len]).expect("failed parse of request"));
+            sources.insert(addr);
+        }
+        assert_eq!(
+            sources.len(),
+            1,
+            "the queries were sent from more than one socket"
+        );
+        let client_addr = sources.into_iter().next().unwrap();
+
+        for request in requests.iter().rev() {
+            let name = request.queries()[0].name().clone();
+            let response = |data: &[u8]| {
+                let mut message = Message::new();
+                message.set_id(request.id());
+                message.add_queries(request.queries().to_vec());
+                message.add_answer(Record::from_rdata(
+                    name.clone(),
+                    0,
+                    RData::NULL(NULL::with(data.to_vec())),
+                ));
+                message.to_vec().unwrap()
+            };
+
+            // a response from another address is dropped, even with the right ID
+            spoofer.send_to(&response(b"SPOOFED"), client_addr).unwrap();
+            server
+                .send_to(&response(name.to_ascii().as_bytes()), client_addr)
+                .unwrap();
+        }
+    });
+
+    let mut io_loop = Runtime::new().unwrap();
+    let (stream, handle) = UdpMultiplexedClientStream::new(server_addr);
+    let multiplexer = DnsMultiplexer::<_, NoopMessageFinalizer>::with_timeout(
+        stream,
+        handle,
+        Duration::from_secs(5),
+        None,
+    );
+    let (exchange, handle) = DnsExchange::connect(multiplexer);
+    let handle = BufDnsRequestStreamHandle::new(handle);
+    io_loop.spawn(
+        exchange
+            .and_then(|exchange| exchange)
+            .map_err(|e| panic!("exchange failed: {}", e)),
+    );
+
+    let queries = names
+        .iter()
+        .map(|name| {
+            let mut query = Message::new();
+            query.add_query(Query::query(name.clone(), RecordType::NULL));
+            handle.clone().send(query)
+        })
+        .collect::<Vec<_>>();
+    let responses = io_loop.block_on(future::join_all(queries)).unwrap();
+    server_handle.join().expect("server thread failed");
+
+    for (name, response) in names.iter().zip(responses) {
+        let response = Message::from(response);
+        match *response.answers()[0].rdata() {
+            RData::NULL(ref null) => assert_eq!(
+                null.anything().expect("no bytes in NULL"),
+                name.to_ascii().as_bytes()
+            ),
+            _ => panic!("not a NULL response"),
+        }
+    }
+}
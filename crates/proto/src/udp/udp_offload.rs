@@ -0,0 +1,290 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Generic receive and segmentation offloads of UDP sockets, on Linux
+
+use std::io;
+use std::net::SocketAddr;
+
+use tokio_udp::UdpSocket;
+
+/// The most segments sent or received at once, `UDP_MAX_SEGMENTS` of Linux
+pub(crate) const MAX_SEGMENTS: usize = 64;
+/// The largest payload of the segments sent or received at once
+pub(crate) const MAX_SEGMENTS_LEN: usize = 65_507;
+
+/// The offloads enabled on a UDP socket
+///
+/// With the generic receive offload, GRO, the kernel coalesces the datagrams received from the
+///  same source into one buffer of equal sized segments, and with the generic segmentation
+///  offload, GSO, a buffer of equal sized segments is sent to a destination as one datagram per
+///  segment, which saves system calls at high packet rates. They are supported since Linux
+///  5.0 and 4.18, older kernels and other systems use one system call per datagram.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct UdpOffload {
+    gro: bool,
+    gso: bool,
+}
+
+impl UdpOffload {
+    /// Enables the offloads supported by the kernel on the socket, none of them on other systems
+    pub fn enable(socket: &UdpSocket) -> Self {
+        let offload = UdpOffload {
+            gro: sys::enable_gro(socket),
+            gso: sys::supports_gso(socket),
+        };
+
+        debug!("UDP offloads of {:?}: {:?}", socket.local_addr(), offload);
+        offload
+    }
+
+    /// Returns true if the datagrams received are coalesced
+    pub fn gro(&self) -> bool {
+        self.gro
+    }
+
+    /// Returns true if the datagrams sent to the same destination are segmented by the kernel
+    pub fn gso(&self) -> bool {
+        self.gso
+    }
+
+    /// Falls back to sending one datagram per system call, e.g. when the network device can't
+    ///  segment the datagrams
+    pub(crate) fn disable_gso(&mut self) {
+        self.gso = false;
+    }
+}
+
+/// Receives the coalesced datagrams into `buf`, returns their length, source, and the size of
+///  the segments if there is more than one datagram
+pub(crate) fn recv_segments(
+    socket: &UdpSocket,
+    buf: &mut [u8],
+) -> io::Result<(usize, SocketAddr, Option<usize>)> {
+    sys::recv_segments(socket, buf)
+}
+
+/// Sends `buf` to `dst`, in datagrams of `segment_size`, the last one may be shorter
+pub(crate) fn send_segments(
+    socket: &UdpSocket,
+    buf: &[u8],
+    dst: &SocketAddr,
+    segment_size: u16,
+) -> io::Result<usize> {
+    sys::send_segments(socket, buf, dst, segment_size)
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use std::io;
+    use std::mem;
+    use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+    use std::os::unix::io::AsRawFd;
+    use std::ptr;
+
+    use libc::{self, c_int, c_uint, c_void, socklen_t};
+    use tokio_udp::UdpSocket;
+
+    // from linux/udp.h, the options of the offloads
+    const UDP_SEGMENT: c_int = 103;
+    const UDP_GRO: c_int = 104;
+
+    pub fn enable_gro(socket: &UdpSocket) -> bool {
+        let enable: c_int = 1;
+        let result = unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                libc::SOL_UDP,
+                UDP_GRO,
+                &enable as *const c_int as *const c_void,
+                mem::size_of::<c_int>() as socklen_t,
+            )
+        };
+
+        result == 0
+    }
+
+    pub fn supports_gso(socket: &UdpSocket) -> bool {
+        let mut segment_size: c_int = 0;
+        let mut len = mem::size_of::<c_int>() as socklen_t;
+        let result = unsafe {
+            libc::getsockopt(
+                socket.as_raw_fd(),
+                libc::SOL_UDP,
+                UDP_SEGMENT,
+                &mut segment_size as *mut c_int as *mut c_void,
+                &mut len,
+            )
+        };
+
+        result == 0
+    }
+
+    pub fn recv_segments(
+        socket: &UdpSocket,
+        buf: &mut [u8],
+    ) -> io::Result<(usize, SocketAddr, Option<usize>)> {
+        let mut name: libc::sockaddr_storage = unsafe { mem::zeroed() };
+        let mut iov = libc::iovec {
+            iov_base: buf.as_mut_ptr() as *mut c_void,
+            iov_len: buf.len(),
+        };
+        // u64 for the alignment of the cmsghdr
+        let mut control = [0u64; 8];
+
+        let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+        msg.msg_name = &mut name as *mut libc::sockaddr_storage as *mut c_void;
+        msg.msg_namelen = mem::size_of::<libc::sockaddr_storage>() as socklen_t;
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut c_void;
+        msg.msg_controllen = mem::size_of_val(&control) as _;
+
+        let len = unsafe { libc::recvmsg(socket.as_raw_fd(), &mut msg, 0) };
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut segment_size = None;
+        unsafe {
+            let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+            while !cmsg.is_null() {
+                if (*cmsg).cmsg_level == libc::SOL_UDP && (*cmsg).cmsg_type == UDP_GRO {
+                    let size = ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const c_int);
+                    segment_size = Some(size as usize);
+                }
+                cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+            }
+        }
+
+        Ok((len as usize, socket_addr(&name)?, segment_size))
+    }
+
+    pub fn send_segments(
+        socket: &UdpSocket,
+        buf: &[u8],
+        dst: &SocketAddr,
+        segment_size: u16,
+    ) -> io::Result<usize> {
+        let (mut name, name_len) = sockaddr(dst);
+        let mut iov = libc::iovec {
+            iov_base: buf.as_ptr() as *mut c_void,
+            iov_len: buf.len(),
+        };
+        let mut control = [0u64; 4];
+
+        let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+        msg.msg_name = &mut name as *mut libc::sockaddr_storage as *mut c_void;
+        msg.msg_namelen = name_len;
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut c_void;
+
+        let len = unsafe {
+            msg.msg_controllen = libc::CMSG_SPACE(mem::size_of::<u16>() as c_uint) as _;
+            let cmsg = libc::CMSG_FIRSTHDR(&msg);
+            (*cmsg).cmsg_level = libc::SOL_UDP;
+            (*cmsg).cmsg_type = UDP_SEGMENT;
+            (*cmsg).cmsg_len = libc::CMSG_LEN(mem::size_of::<u16>() as c_uint) as _;
+            ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut u16, segment_size);
+
+            libc::sendmsg(socket.as_raw_fd(), &msg, 0)
+        };
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(len as usize)
+    }
+
+    fn socket_addr(name: &libc::sockaddr_storage) -> io::Result<SocketAddr> {
+        match c_int::from(name.ss_family) {
+            libc::AF_INET => {
+                let addr = unsafe { &*(name as *const _ as *const libc::sockaddr_in) };
+                let ip = Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr));
+                Ok(SocketAddrV4::new(ip, u16::from_be(addr.sin_port)).into())
+            }
+            libc::AF_INET6 => {
+                let addr = unsafe { &*(name as *const _ as *const libc::sockaddr_in6) };
+                let ip = Ipv6Addr::from(addr.sin6_addr.s6_addr);
+                Ok(SocketAddrV6::new(
+                    ip,
+                    u16::from_be(addr.sin6_port),
+                    addr.sin6_flowinfo,
+                    addr.sin6_scope_id,
+                )
+                .into())
+            }
+            family => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported address family: {}", family),
+            )),
+        }
+    }
+
+    fn sockaddr(addr: &SocketAddr) -> (libc::sockaddr_storage, socklen_t) {
+        let mut name: libc::sockaddr_storage = unsafe { mem::zeroed() };
+        let len = match *addr {
+            SocketAddr::V4(ref addr) => {
+                let sin = unsafe { &mut *(&mut name as *mut _ as *mut libc::sockaddr_in) };
+                sin.sin_family = libc::AF_INET as libc::sa_family_t;
+                sin.sin_port = addr.port().to_be();
+                sin.sin_addr.s_addr = u32::from(*addr.ip()).to_be();
+                mem::size_of::<libc::sockaddr_in>()
+            }
+            SocketAddr::V6(ref addr) => {
+                let sin6 = unsafe { &mut *(&mut name as *mut _ as *mut libc::sockaddr_in6) };
+                sin6.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+                sin6.sin6_port = addr.port().to_be();
+                sin6.sin6_flowinfo = addr.flowinfo();
+                sin6.sin6_addr.s6_addr = addr.ip().octets();
+                sin6.sin6_scope_id = addr.scope_id();
+                mem::size_of::<libc::sockaddr_in6>()
+            }
+        };
+
+        (name, len as socklen_t)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod sys {
+    use std::io;
+    use std::net::SocketAddr;
+
+    use tokio_udp::UdpSocket;
+
+    pub fn enable_gro(_socket: &UdpSocket) -> bool {
+        false
+    }
+
+    pub fn supports_gso(_socket: &UdpSocket) -> bool {
+        false
+    }
+
+    pub fn recv_segments(
+        _socket: &UdpSocket,
+        _buf: &mut [u8],
+    ) -> io::Result<(usize, SocketAddr, Option<usize>)> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "UDP offloads are only supported on Linux",
+        ))
+    }
+
+    pub fn send_segments(
+        _socket: &UdpSocket,
+        _buf: &[u8],
+        _dst: &SocketAddr,
+        _segment_size: u16,
+    ) -> io::Result<usize> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "UDP offloads are only supported on Linux",
+        ))
+    }
+}
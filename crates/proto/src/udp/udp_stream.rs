@@ -5,6 +5,8 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use std;
+use std::collections::VecDeque;
 use std::io;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 
@@ -12,10 +14,13 @@ use futures::stream::{Fuse, Peekable, Stream};
 use futures::sync::mpsc::{unbounded, UnboundedReceiver};
 use futures::task;
 use futures::{Async, Future, Poll};
+use mio::Ready;
 use rand;
 use rand::distributions::{uniform::Uniform, Distribution};
+use tokio_reactor::Handle;
 use tokio_udp;
 
+use udp::udp_offload::{self, UdpOffload, MAX_SEGMENTS, MAX_SEGMENTS_LEN};
 use xfer::{BufStreamHandle, SerialMessage};
 
 /// A UDP stream of DNS binary packets
@@ -23,6 +28,13 @@ use xfer::{BufStreamHandle, SerialMessage};
 pub struct UdpStream {
     socket: tokio_udp::UdpSocket,
     outbound_messages: Peekable<Fuse<UnboundedReceiver<SerialMessage>>>,
+    offload: UdpOffload,
+    /// Messages to the same destination, sent at once with GSO
+    segments: VecDeque<SerialMessage>,
+    /// Messages received at once with GRO, which are not yet returned
+    received: VecDeque<SerialMessage>,
+    /// The buffer of the datagrams received with GRO, allocated on the first receive
+    recv_buf: Vec<u8>,
 }
 
 impl UdpStream {
@@ -54,10 +66,9 @@ impl UdpStream {
 
         // This set of futures collapses the next udp socket into a stream which can be used for
         //  sending and receiving udp packets.
-        let stream = Box::new(next_socket.map(move |socket| UdpStream {
-            socket,
-            outbound_messages: outbound_messages.fuse().peekable(),
-        }));
+        let stream = Box::new(
+            next_socket.map(move |socket| UdpStream::from_parts(socket, outbound_messages)),
+        );
 
         (stream, message_sender)
     }
@@ -79,15 +90,25 @@ impl UdpStream {
         let (message_sender, outbound_messages) = unbounded();
         let message_sender = BufStreamHandle::new(message_sender);
 
-        let stream = UdpStream {
-            socket,
-            outbound_messages: outbound_messages.fuse().peekable(),
-        };
+        let stream = Self::from_parts(socket, outbound_messages);
+
+        (stream, message_sender)
+    }
+
+    /// Initialize the Stream with an already bound socket, with the UDP offloads supported by the
+    ///  kernel, see `UdpOffload`. The datagrams are sent and received one by one when they are
+    ///  not supported, as with `with_bound`.
+    ///
+    /// # Arguments
+    ///
+    /// * `socket` - an already bound UDP socket
+    pub fn with_offload(socket: tokio_udp::UdpSocket) -> (Self, BufStreamHandle) {
+        let (mut stream, message_sender) = Self::with_bound(socket);
+        stream.offload = UdpOffload::enable(&stream.socket);
 
         (stream, message_sender)
     }
 
-    #[allow(unused)]
     pub(crate) fn from_parts(
         socket: tokio_udp::UdpSocket,
         outbound_messages: UnboundedReceiver<SerialMessage>,
@@ -95,36 +116,157 @@ impl UdpStream {
         UdpStream {
             socket,
             outbound_messages: outbound_messages.fuse().peekable(),
+            offload: UdpOffload::default(),
+            segments: VecDeque::new(),
+            received: VecDeque::new(),
+            recv_buf: Vec::new(),
         }
     }
-}
 
-impl Stream for UdpStream {
-    type Item = SerialMessage;
-    type Error = io::Error;
+    /// Moves the next outbound messages to the segments, those to the same destination which can
+    ///  be sent at once with GSO: all the same size but the last one, which may be shorter
+    fn next_segments(&mut self) -> io::Result<()> {
+        let max_segments = if self.offload.gso() { MAX_SEGMENTS } else { 1 };
+        let mut segments_len: usize = self.segments.iter().map(|m| m.bytes().len()).sum();
 
-    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
-        // this will not accept incoming data while there is data to send
-        //  makes this self throttling.
-        loop {
-            // first try to send
-            match self
+        while self.segments.len() < max_segments {
+            let len = match self
                 .outbound_messages
                 .peek()
                 .map_err(|()| io::Error::new(io::ErrorKind::Other, "unknown"))?
             {
                 Async::Ready(Some(ref message)) => {
-                    // will return if the socket will block
-                    try_ready!(self.socket.poll_send_to(message.bytes(), &message.addr()));
+                    let len = message.bytes().len();
+                    let fits = match (self.segments.front(), self.segments.back()) {
+                        (Some(first), Some(last)) => {
+                            let segment_size = first.bytes().len();
+                            message.addr() == first.addr()
+                                && last.bytes().len() == segment_size
+                                && len <= segment_size
+                                && segments_len + len <= MAX_SEGMENTS_LEN
+                        }
+                        _ => true,
+                    };
+
+                    if !fits {
+                        break;
+                    }
+                    len
                 }
-                // now we get to drop through to the receives...
-                // TODO: should we also return None if there are no more messages to send?
                 Async::NotReady | Async::Ready(None) => break,
+            };
+
+            if let Async::Ready(Some(message)) = self.outbound_messages.poll().expect("Impossible")
+            {
+                segments_len += len;
+                self.segments.push_back(message);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sends the outbound messages, those to the same destination at once with GSO
+    fn poll_send(&mut self) -> Poll<(), io::Error> {
+        loop {
+            if self.segments.is_empty() {
+                self.next_segments()?;
+                if self.segments.is_empty() {
+                    return Ok(Async::Ready(()));
+                }
+            }
+
+            if self.segments.len() > 1 && self.offload.gso() {
+                try_ready!(self.socket.poll_write_ready());
+
+                let buf: Vec<u8> = self
+                    .segments
+                    .iter()
+                    .flat_map(|m| m.bytes().iter().cloned())
+                    .collect();
+                let dst = self.segments[0].addr();
+                let segment_size = self.segments[0].bytes().len() as u16;
+
+                match udp_offload::send_segments(&self.socket, &buf, &dst, segment_size) {
+                    Ok(_) => self.segments.clear(),
+                    // the readiness is cleared by sending the first segment alone, which
+                    //  registers the task to be notified when the socket is writable
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        try_ready!(self.socket.poll_send_to(self.segments[0].bytes(), &dst));
+                        self.segments.pop_front();
+                    }
+                    // e.g. EIO when the network device can't segment, the segments are sent one
+                    //  by one
+                    Err(e) => {
+                        warn!(
+                            "UDP segmentation offload disabled, sending to {}: {}",
+                            dst, e
+                        );
+                        self.offload.disable_gso();
+                    }
+                }
+            } else {
+                // will return if the socket will block
+                try_ready!(self
+                    .socket
+                    .poll_send_to(self.segments[0].bytes(), &self.segments[0].addr()));
+                self.segments.pop_front();
             }
+        }
+    }
+
+    /// Receives the coalesced datagrams with GRO, and queues them to be returned one by one
+    fn poll_recv_segments(&mut self) -> Poll<Option<SerialMessage>, io::Error> {
+        try_ready!(self.socket.poll_read_ready(Ready::readable()));
 
-            // now pop the request which is already sent
-            // If it were an Err, it was returned on peeking.
-            self.outbound_messages.poll().expect("Impossible");
+        // the buffer is reused, only the segments are copied to the messages
+        if self.recv_buf.is_empty() {
+            self.recv_buf = vec![0u8; MAX_SEGMENTS_LEN];
+        }
+        let buf = &mut self.recv_buf;
+
+        let (len, src, segment_size) = match udp_offload::recv_segments(&self.socket, buf) {
+            Ok(received) => received,
+            // the readiness is cleared by receiving a datagram alone, which registers the task to
+            //  be notified when the socket is readable
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                let (len, src) = try_ready!(self.socket.poll_recv_from(buf));
+                (len, src, None)
+            }
+            Err(e) => return Err(e),
+        };
+
+        let segment_size = segment_size.unwrap_or(len).max(1);
+        for segment in buf[..len].chunks(segment_size) {
+            self.received
+                .push_back(SerialMessage::new(segment.to_vec(), src));
+        }
+
+        // an empty datagram has no segments
+        Ok(Async::Ready(Some(
+            self.received
+                .pop_front()
+                .unwrap_or_else(|| SerialMessage::new(Vec::new(), src)),
+        )))
+    }
+}
+
+impl Stream for UdpStream {
+    type Item = SerialMessage;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        // this will not accept incoming data while there is data to send
+        //  makes this self throttling.
+        // TODO: should we also return None if there are no more messages to send?
+        try_ready!(self.poll_send());
+
+        // the datagrams already received at once are returned first
+        if let Some(message) = self.received.pop_front() {
+            return Ok(Async::Ready(Some(message)));
+        }
+        if self.offload.gro() {
+            return self.poll_recv_segments();
         }
 
         // For QoS, this will only accept one message and output that
@@ -155,20 +297,19 @@ impl NextRandomUdpSocket {
             SocketAddr::V6(..) => IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0)),
         };
 
-        NextRandomUdpSocket {
-            bind_address: zero_addr,
-        }
+        Self::with_bind_addr(zero_addr)
     }
-}
 
-impl Future for NextRandomUdpSocket {
-    type Item = tokio_udp::UdpSocket;
-    type Error = io::Error;
+    /// Creates a future for randomly binding to a port of the local address
+    pub(crate) fn with_bind_addr(bind_address: IpAddr) -> NextRandomUdpSocket {
+        NextRandomUdpSocket { bind_address }
+    }
 
-    /// polls until there is an available next random UDP port.
+    /// Polls until there is an available next random UDP port, with the std socket bound to it
     ///
-    /// if there is no port available after 10 attempts, returns NotReady
-    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+    /// The std socket can be cloned before it is registered with tokio, e.g. to read its errors.
+    ///  If there is no port available after 10 attempts, returns NotReady
+    pub(crate) fn poll_std(&mut self) -> Poll<std::net::UdpSocket, io::Error> {
         let rand_port_range = Uniform::new_inclusive(1025_u16, u16::max_value());
         let mut rand = rand::thread_rng();
 
@@ -177,7 +318,7 @@ impl Future for NextRandomUdpSocket {
             let zero_addr = SocketAddr::new(self.bind_address, port);
 
             // TODO: allow TTL to be adjusted...
-            match tokio_udp::UdpSocket::bind(&zero_addr) {
+            match std::net::UdpSocket::bind(zero_addr) {
                 Ok(socket) => {
                     debug!("created socket: {:?}", socket);
                     return Ok(Async::Ready(socket));
@@ -194,6 +335,19 @@ impl Future for NextRandomUdpSocket {
     }
 }
 
+impl Future for NextRandomUdpSocket {
+    type Item = tokio_udp::UdpSocket;
+    type Error = io::Error;
+
+    /// polls until there is an available next random UDP port.
+    ///
+    /// if there is no port available after 10 attempts, returns NotReady
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let socket = try_ready!(self.poll_std());
+        tokio_udp::UdpSocket::from_std(socket, &Handle::default()).map(Async::Ready)
+    }
+}
+
 #[test]
 fn test_next_random_socket() {
     use tokio::runtime::current_thread::Runtime;
@@ -218,6 +372,46 @@ fn test_udp_stream_ipv6() {
     udp_stream_test(IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1)))
 }
 
+#[test]
+fn test_udp_stream_offload() {
+    use tokio::runtime::current_thread::Runtime;
+
+    let mut io_loop = Runtime::new().unwrap();
+    let localhost = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 0);
+    let (client, client_sender) =
+        UdpStream::with_offload(tokio_udp::UdpSocket::bind(&localhost).unwrap());
+    let (mut server, _) = UdpStream::with_offload(tokio_udp::UdpSocket::bind(&localhost).unwrap());
+    let server_addr = server.socket.local_addr().unwrap();
+
+    // segments of the same size, and a shorter last one, sent at once if GSO is supported
+    let messages: Vec<&'static [u8]> = vec![b"DEADBEEF", b"BEEFDEAD", b"FEEDBEEF", b"DEAD"];
+    for message in &messages {
+        client_sender
+            .unbounded_send(SerialMessage::new(message.to_vec(), server_addr))
+            .unwrap();
+    }
+
+    // the client sends the messages as soon as its socket is writable
+    io_loop.spawn(
+        client
+            .for_each(|_| Ok(()))
+            .map_err(|e| panic!("send failed: {}", e)),
+    );
+
+    let mut recv_buf = None;
+    for expected in messages {
+        let (message, server_tmp) = io_loop.block_on(server.into_future()).ok().unwrap();
+        server = server_tmp;
+        assert_eq!(message.expect("no message received").bytes(), expected);
+
+        // the datagrams are received into the same buffer
+        if server.offload.gro() {
+            let buf = server.recv_buf.as_ptr();
+            assert_eq!(*recv_buf.get_or_insert(buf), buf);
+        }
+    }
+}
+
 #[cfg(test)]
 fn udp_stream_test(server_addr: IpAddr) {
     use tokio::runtime::current_thread::Runtime;
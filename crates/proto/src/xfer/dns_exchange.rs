@@ -12,7 +12,11 @@ use futures::sync::mpsc::{unbounded, UnboundedReceiver};
 use futures::{Async, Future, Poll};
 
 use error::*;
-use xfer::{DnsRequest, DnsRequestSender, DnsRequestStreamHandle, DnsResponse, OneshotDnsRequest};
+use xfer::in_flight::InFlight;
+use xfer::{
+    DnsRequest, DnsRequestSender, DnsRequestStreamHandle, DnsResponse, InFlightPolicy,
+    OneshotDnsRequest,
+};
 
 /// This is a generic Exchange implemented over multiplexed DNS connection providers.
 ///
@@ -25,6 +29,7 @@ where
 {
     io_stream: S,
     outbound_messages: Peekable<UnboundedReceiver<OneshotDnsRequest<R>>>,
+    in_flight: Option<InFlight>,
 }
 
 impl<S, R> DnsExchange<S, R>
@@ -56,9 +61,25 @@ where
         DnsExchange {
             io_stream: stream,
             outbound_messages: receiver.peekable(),
+            in_flight: None,
         }
     }
 
+    /// Limits the requests in flight, which were sent and are awaiting their response
+    ///
+    /// A burst of requests over the limit waits in the queue of the exchange, or fails, depending
+    ///  on the `policy`, instead of holding the state of each request, e.g. a socket per UDP
+    ///  request, until it completes.
+    ///
+    /// # Arguments
+    ///
+    /// * `max` - the maximum number of requests in flight, at least 1
+    /// * `policy` - what to do with the requests over the limit
+    pub fn with_max_in_flight(mut self, max: usize, policy: InFlightPolicy) -> Self {
+        self.in_flight = Some(InFlight::new(max.max(1), policy));
+        self
+    }
+
     /// Returns a future, which itself wraps a future which is awaiting connection.
     ///
     /// The connect_future should be lazy.
@@ -109,6 +130,19 @@ where
                 Err(err) => return Err(err),
             }
 
+            // a request may only be sent with a permit, if the requests in flight are limited
+            let (permit, is_busy) = match self.in_flight {
+                Some(ref in_flight) => match in_flight.try_acquire() {
+                    Some(permit) => (Some(permit), false),
+                    // the task is notified when a request completes
+                    None if in_flight.policy() == InFlightPolicy::Wait => {
+                        return Ok(Async::NotReady)
+                    }
+                    None => (None, true),
+                },
+                None => (None, false),
+            };
+
             // then see if there is more to send
             match self
                 .outbound_messages
@@ -120,16 +154,34 @@ where
                     // if there is no peer, this connection should die...
                     let (dns_request, serial_response): (DnsRequest, _) = dns_request.unwrap();
 
+                    // the request was dropped before it was sent, e.g. it was canceled or timed out
+                    if serial_response.is_canceled() {
+                        debug!("request canceled, not sending via: {}", self.io_stream);
+                        continue;
+                    }
+
+                    if is_busy {
+                        let max = self.in_flight.as_ref().map_or(0, InFlight::max);
+                        debug!(
+                            "too many requests in flight, failing request via: {}",
+                            self.io_stream
+                        );
+                        let error = ProtoError::from(ProtoErrorKind::Busy(max));
+                        serial_response
+                            .send_response(S::error_response(error), None)
+                            .ok();
+                        continue;
+                    }
+
                     debug!("sending message via: {}", self.io_stream);
 
-                    match serial_response.send_response(self.io_stream.send_message(dns_request)) {
-                        Ok(()) => (),
-                        Err(_) => {
-                            warn!("failed to associate send_message response to the sender");
-                            return Err(
-                                "failed to associate send_message response to the sender".into()
-                            );
-                        }
+                    // dropping the response future cancels the request if the sender is gone,
+                    //  this must not shutdown the exchange for the other requests
+                    if serial_response
+                        .send_response(self.io_stream.send_message(dns_request), permit)
+                        .is_err()
+                    {
+                        debug!("request canceled while sending via: {}", self.io_stream);
                     }
                 }
                 // On not ready, this is our time to return...
@@ -168,8 +220,22 @@ where
         DnsExchangeConnect(DnsExchangeConnectInner::Connecting {
             connect_future,
             outbound_messages: Some(outbound_messages),
+            max_in_flight: None,
         })
     }
+
+    /// Limits the requests in flight of the exchange once connected, see
+    ///  `DnsExchange::with_max_in_flight`
+    pub fn with_max_in_flight(mut self, max: usize, policy: InFlightPolicy) -> Self {
+        if let DnsExchangeConnectInner::Connecting {
+            ref mut max_in_flight,
+            ..
+        } = self.0
+        {
+            *max_in_flight = Some((max, policy));
+        }
+        self
+    }
 }
 
 impl<F, S, R> Future for DnsExchangeConnect<F, S, R>
@@ -195,6 +261,7 @@ where
     Connecting {
         connect_future: F,
         outbound_messages: Option<UnboundedReceiver<OneshotDnsRequest<R>>>,
+        max_in_flight: Option<(usize, InFlightPolicy)>,
     },
     FailAll {
         error: ProtoError,
@@ -218,16 +285,21 @@ where
                 DnsExchangeConnectInner::Connecting {
                     ref mut connect_future,
                     ref mut outbound_messages,
+                    ref max_in_flight,
                 } => {
                     match connect_future.poll() {
                         Ok(Async::Ready(stream)) => {
                             debug!("connection established: {}", stream);
-                            return Ok(Async::Ready(DnsExchange::from_stream_with_receiver(
+                            let mut exchange = DnsExchange::from_stream_with_receiver(
                                 stream,
                                 outbound_messages
                                     .take()
                                     .expect("cannot poll after complete"),
-                            )));
+                            );
+                            if let Some((max, policy)) = *max_in_flight {
+                                exchange = exchange.with_max_in_flight(max, policy);
+                            }
+                            return Ok(Async::Ready(exchange));
                         }
                         Ok(Async::NotReady) => return Ok(Async::NotReady),
                         Err(error) => {
@@ -252,7 +324,11 @@ where
                     } {
                         let response = S::error_response(error.clone());
                         // ignoring errors... best effort send...
-                        outbound_message.unwrap().1.send_response(response).ok();
+                        outbound_message
+                            .unwrap()
+                            .1
+                            .send_response(response, None)
+                            .ok();
                     }
 
                     return Err(error.clone());
@@ -263,3 +339,133 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fmt;
+    use std::sync::{Arc, Mutex};
+
+    use futures::sync::oneshot;
+    use futures::{future, Stream};
+
+    use super::*;
+    use op::Message;
+    use xfer::{BufDnsRequestStreamHandle, DnsHandle, OneshotDnsResponseReceiver};
+
+    type TestResponse = Box<Future<Item = DnsResponse, Error = ProtoError> + Send>;
+
+    /// Sends nothing, the responses are completed by the test
+    #[derive(Clone, Default)]
+    struct TestSender {
+        sent: Arc<Mutex<Vec<Option<oneshot::Sender<DnsResponse>>>>>,
+    }
+
+    impl TestSender {
+        fn sent(&self) -> usize {
+            self.sent.lock().unwrap().len()
+        }
+
+        fn respond(&self, idx: usize) {
+            let sender = self.sent.lock().unwrap()[idx].take().unwrap();
+            sender.send(Message::new().into()).unwrap();
+        }
+    }
+
+    impl fmt::Display for TestSender {
+        fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(formatter, "TestSender")
+        }
+    }
+
+    impl Stream for TestSender {
+        type Item = ();
+        type Error = ProtoError;
+
+        fn poll(&mut self) -> Poll<Option<()>, ProtoError> {
+            Ok(Async::NotReady)
+        }
+    }
+
+    impl DnsRequestSender for TestSender {
+        type DnsResponseFuture = TestResponse;
+
+        fn send_message(&mut self, _: DnsRequest) -> TestResponse {
+            let (sender, receiver) = oneshot::channel();
+            self.sent.lock().unwrap().push(Some(sender));
+            Box::new(receiver.map_err(|c| ProtoErrorKind::Canceled(c).into()))
+        }
+
+        fn error_response(error: ProtoError) -> TestResponse {
+            Box::new(future::err(error))
+        }
+
+        fn shutdown(&mut self) {}
+
+        fn is_shutdown(&self) -> bool {
+            false
+        }
+    }
+
+    type Responses = Vec<OneshotDnsResponseReceiver<TestResponse>>;
+
+    fn poll<F: Future>(future: &mut F) -> Poll<F::Item, F::Error> {
+        future::lazy(|| Ok::<_, ()>(future.poll())).wait().unwrap()
+    }
+
+    /// An exchange of at most 2 requests in flight, after 3 requests
+    fn exchange(
+        policy: InFlightPolicy,
+    ) -> (
+        TestSender,
+        DnsExchange<TestSender, TestResponse>,
+        BufDnsRequestStreamHandle<TestResponse>,
+        Responses,
+    ) {
+        let sender = TestSender::default();
+        let (exchange, handle) = DnsExchange::from_stream(sender.clone());
+        let mut exchange = exchange.with_max_in_flight(2, policy);
+        let mut handle = BufDnsRequestStreamHandle::new(handle);
+
+        let responses = (0..3).map(|_| handle.send(Message::new())).collect();
+        assert!(poll(&mut exchange).unwrap().is_not_ready());
+
+        (sender, exchange, handle, responses)
+    }
+
+    #[test]
+    fn test_max_in_flight_wait() {
+        let (sender, mut exchange, mut handle, mut responses) = exchange(InFlightPolicy::Wait);
+        assert_eq!(sender.sent(), 2);
+
+        // the third request is sent once the first completed
+        sender.respond(0);
+        assert!(poll(&mut responses[0]).unwrap().is_ready());
+        poll(&mut exchange).unwrap();
+        assert_eq!(sender.sent(), 3);
+
+        responses.push(handle.send(Message::new()));
+        poll(&mut exchange).unwrap();
+        assert_eq!(sender.sent(), 3);
+
+        // canceled requests are no longer in flight
+        responses.remove(1);
+        poll(&mut exchange).unwrap();
+        assert_eq!(sender.sent(), 4);
+    }
+
+    #[test]
+    fn test_max_in_flight_fail() {
+        let (sender, mut exchange, mut handle, mut responses) = exchange(InFlightPolicy::Fail);
+        assert_eq!(sender.sent(), 2);
+
+        let error = poll(&mut responses[2]).expect_err("request over the limit");
+        assert_eq!(*error.kind(), ProtoErrorKind::Busy(2));
+
+        sender.respond(0);
+        assert!(poll(&mut responses[0]).unwrap().is_ready());
+        responses.push(handle.send(Message::new()));
+        poll(&mut exchange).unwrap();
+        assert_eq!(sender.sent(), 3);
+        assert!(poll(&mut responses[3]).unwrap().is_not_ready());
+    }
+}
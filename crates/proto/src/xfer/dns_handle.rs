@@ -16,9 +16,6 @@ use error::*;
 use op::{Message, MessageType, OpCode, Query};
 use xfer::{ignore_send, DnsRequest, DnsRequestOptions, DnsResponse, SerialMessage};
 
-// TODO: this should be configurable
-const MAX_PAYLOAD_LEN: u16 = 1500 - 40 - 8; // 1500 (general MTU) - 40 (ipv6 header) - 8 (udp header)
-
 /// The StreamHandle is the general interface for communicating with the DnsMultiplexer
 pub struct StreamHandle {
     sender: UnboundedSender<Vec<u8>>,
@@ -145,12 +142,7 @@ pub trait DnsHandle: 'static + Clone + Send {
             .set_recursion_desired(true);
 
         // Extended dns
-        {
-            // TODO: this should really be configurable...
-            let edns = message.edns_mut();
-            edns.set_max_payload(MAX_PAYLOAD_LEN);
-            edns.set_version(0);
-        }
+        message.set_edns(options.query_edns());
 
         self.send(DnsRequest::new(message, options))
     }
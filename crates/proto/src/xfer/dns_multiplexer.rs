@@ -12,18 +12,21 @@ use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::fmt::{self, Display};
 use std::sync::Arc;
-use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use futures::stream::Stream;
+use futures::sync::mpsc::UnboundedSender;
 use futures::sync::oneshot;
 use futures::{task, Async, Future, Poll};
 use rand;
 use rand::distributions::{Distribution, Standard};
 use smallvec::SmallVec;
-use tokio_timer::Delay;
+use tokio_timer::{clock, Delay};
 
+use capture::{self, Direction};
 use error::*;
-use op::{Message, MessageFinalizer, OpCode};
+use op::{Message, MessageFinalizer, MessageVerifier, ResponseCode};
+use rr::RecordType;
 use xfer::{
     ignore_send, DnsClientStream, DnsRequest, DnsRequestOptions, DnsRequestSender, DnsResponse,
     SerialMessage,
@@ -40,9 +43,14 @@ struct ActiveRequest {
     // most requests pass a single Message response directly through to the completion
     //  this small vec will have no allocations, unless the requests is a DNS-SD request
     //  expecting more than one response
-    // TODO: change the completion above to a Stream, and don't hold messages...
     responses: SmallVec<[Message; 1]>,
+    // with a stream, only the last response is held, the previous ones are sent to the stream
+    response_stream: Option<UnboundedSender<Message>>,
     timeout: Delay,
+    verifier: Option<MessageVerifier>,
+    // AXFR or IXFR, for zone transfers which complete on the closing SOA
+    transfer_type: Option<RecordType>,
+    transfer: TransferProgress,
 }
 
 impl ActiveRequest {
@@ -51,6 +59,9 @@ impl ActiveRequest {
         request_id: u16,
         request_options: DnsRequestOptions,
         timeout: Delay,
+        verifier: Option<MessageVerifier>,
+        transfer_type: Option<RecordType>,
+        response_stream: Option<UnboundedSender<Message>>,
     ) -> Self {
         ActiveRequest {
             completion,
@@ -58,7 +69,11 @@ impl ActiveRequest {
             request_options,
             // request,
             responses: SmallVec::new(),
+            response_stream,
             timeout,
+            verifier,
+            transfer_type,
+            transfer: TransferProgress::default(),
         }
     }
 
@@ -74,9 +89,42 @@ impl ActiveRequest {
 
     /// Adds the response to the request such that it can be later sent to the client
     fn add_response(&mut self, message: Message) {
+        if self.transfer_type.is_some() {
+            self.transfer.add(&message);
+        }
+
+        if let Some(ref response_stream) = self.response_stream {
+            // the last response is held for the completion, which may be a timeout
+            if let Some(previous) = self.responses.pop() {
+                ignore_send(response_stream.unbounded_send(previous));
+            }
+        }
         self.responses.push(message);
     }
 
+    /// Verifies the encoded response with the verifier of the request, if any
+    ///
+    /// Returns false if the response is an unverified intermediate message of the responses
+    fn verify(&mut self, response: &[u8]) -> ProtoResult<bool> {
+        match self.verifier {
+            Some(ref mut verifier) => verifier(response),
+            None => Ok(true),
+        }
+    }
+
+    /// Returns true if all responses were received, with multiple responses only zone
+    ///  transfers complete before the timeout
+    fn is_complete(&self) -> bool {
+        if !self.request_options().expects_multiple_responses {
+            return true;
+        }
+
+        match self.transfer_type {
+            Some(transfer_type) => self.transfer.is_complete(transfer_type),
+            None => false,
+        }
+    }
+
     /// the request id of the message that was sent
     fn request_id(&self) -> u16 {
         self.request_id
@@ -105,6 +153,81 @@ impl ActiveRequest {
     }
 }
 
+/// The progress of a zone transfer, from the answers of the responses received so far
+///
+/// The responses are not held, so that they may be streamed, see
+///  `DnsRequest::with_response_stream`.
+#[derive(Default)]
+struct TransferProgress {
+    responses: usize,
+    failed: bool,
+    // the serial of the first answer, the SOA of the zone
+    serial: Option<u32>,
+    // the first answer is not an SOA
+    not_transfer: bool,
+    // the answers after the first one
+    answers: usize,
+    incremental: bool,
+    soa_count: usize,
+}
+
+impl TransferProgress {
+    fn add(&mut self, message: &Message) {
+        self.responses += 1;
+        self.failed |= message.response_code() != ResponseCode::NoError;
+
+        for record in message.answers() {
+            if self.not_transfer {
+                return;
+            }
+
+            let soa = record.rdata().as_soa();
+            let serial = match self.serial {
+                Some(serial) => serial,
+                None => {
+                    match soa {
+                        Some(soa) => self.serial = Some(soa.serial()),
+                        None => self.not_transfer = true,
+                    }
+                    self.soa_count = 1;
+                    continue;
+                }
+            };
+
+            if self.answers == 0 {
+                self.incremental = record.rr_type() == RecordType::SOA;
+            }
+            self.answers += 1;
+            if soa.map_or(false, |soa| soa.serial() == serial) {
+                self.soa_count += 1;
+            }
+        }
+    }
+
+    /// Returns true if the responses contained the closing SOA of the zone transfer, or an error
+    ///
+    /// [RFC 5936](https://tools.ietf.org/html/rfc5936#section-2.2), the answers of an AXFR are
+    ///  enclosed by the SOA of the zone. [RFC 1995](https://tools.ietf.org/html/rfc1995#section-4),
+    ///  the answers of an incremental IXFR start with the current SOA, and each difference starts
+    ///  with an SOA, such that the current SOA is present three times; an up to date IXFR is only
+    ///  the current SOA; and an IXFR which falls back to a full transfer is enclosed as an AXFR.
+    fn is_complete(&self, transfer_type: RecordType) -> bool {
+        // not a zone transfer response, there is nothing to wait for
+        if self.failed || self.not_transfer {
+            return true;
+        }
+        if self.serial.is_none() {
+            return false;
+        }
+
+        match transfer_type {
+            RecordType::IXFR if self.answers == 0 => self.responses == 1,
+            RecordType::IXFR if self.incremental => self.soa_count >= 3,
+            _ => self.soa_count >= 2,
+        }
+    }
+}
+
 /// A DNS Client implemented over futures-rs.
 ///
 /// This Client is generic and capable of wrapping UDP, TCP, and other underlying DNS protocol
@@ -311,23 +434,34 @@ where
 {
     type DnsResponseFuture = DnsMultiplexerSerialResponse;
 
-    fn send_message(&mut self, request: DnsRequest) -> Self::DnsResponseFuture {
+    fn send_message(&mut self, mut request: DnsRequest) -> Self::DnsResponseFuture {
         if self.is_shutdown {
             panic!("can not send messages after stream is shutdown")
         }
 
-        // get next query_id
-        let query_id: u16 = match self.next_random_query_id() {
-            Async::Ready(id) => id,
-            Async::NotReady => {
+        let raw = request.is_raw();
+        let response_stream = request.take_response_stream();
+        let (mut request, request_options) = request.unwrap();
+
+        if raw {
+            // the id of a raw request is kept, it must still be unique to match the response
+            if self.active_requests.contains_key(&request.id()) {
                 return DnsMultiplexerSerialResponseInner::Err(Some(ProtoError::from(
-                    "id space exhausted, consider filing an issue",
-                ))).into()
+                    "id of the raw request is in use",
+                ))).into();
             }
-        };
-
-        let (mut request, request_options) = request.unwrap();
-        request.set_id(query_id);
+        } else {
+            // get next query_id
+            let query_id: u16 = match self.next_random_query_id() {
+                Async::Ready(id) => id,
+                Async::NotReady => {
+                    return DnsMultiplexerSerialResponseInner::Err(Some(ProtoError::from(
+                        "id space exhausted, consider filing an issue",
+                    ))).into()
+                }
+            };
+            request.set_id(query_id);
+        }
 
         let now = match SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -340,28 +474,60 @@ where
         // TODO: truncates u64 to u32, error on overflow?
         let now = now as u32;
 
-        // update messages need to be signed.
-        if let OpCode::Update = request.op_code() {
-            if let Some(ref signer) = self.signer {
-                if let Err(e) = request.finalize::<MF>(signer.borrow(), now) {
-                    debug!("could not sign message: {}", e);
-                    return DnsMultiplexerSerialResponseInner::Err(Some(e)).into();
+        // update messages need to be signed, other messages depending on the signer, raw
+        //  messages are sent as they are.
+        let mut verifier = None;
+        if let Some(ref signer) = self.signer {
+            if !raw && signer.should_finalize_message(&request) {
+                match request.finalize::<MF>(signer.borrow(), now) {
+                    Ok(answer_verifier) => verifier = answer_verifier,
+                    Err(e) => {
+                        debug!("could not sign message: {}", e);
+                        return DnsMultiplexerSerialResponseInner::Err(Some(e)).into();
+                    }
                 }
             }
         }
 
+        let transfer_type = request
+            .queries()
+            .first()
+            .map(|q| q.query_type())
+            .filter(|t| *t == RecordType::AXFR || *t == RecordType::IXFR);
+
         // store a Timeout for this message before sending
-        let timeout = Delay::new(Instant::now() + self.timeout_duration);
+        let timeout = Delay::new(clock::now() + self.timeout_duration);
 
         let (complete, receiver) = oneshot::channel();
 
         // send the message
-        let active_request = ActiveRequest::new(complete, request.id(), request_options, timeout);
+        let active_request = ActiveRequest::new(
+            complete,
+            request.id(),
+            request_options,
+            timeout,
+            verifier,
+            transfer_type,
+            response_stream,
+        );
+
+        let serial_message = if raw {
+            request
+                .to_raw_vec()
+                .map(|bytes| SerialMessage::new(bytes, self.stream.name_server_addr()))
+        } else {
+            SerialMessage::from_message(&request, self.stream.name_server_addr())
+        };
 
-        match request.to_vec() {
-            Ok(buffer) => {
+        match serial_message {
+            Ok(serial_message) => {
                 debug!("sending message id: {}", active_request.request_id());
-                let serial_message = SerialMessage::new(buffer, self.stream.name_server_addr());
+
+                capture::record(
+                    Direction::Sent,
+                    serial_message.addr(),
+                    serial_message.bytes(),
+                );
 
                 // add to the map -after- the client send b/c we don't want to put it in the map if
                 //  we ended up returning an error from the send.
@@ -424,22 +590,39 @@ where
             match self.stream.poll()? {
                 Async::Ready(Some(buffer)) => {
                     messages_received = i;
+                    capture::record(Direction::Received, buffer.addr(), buffer.bytes());
 
                     //   deserialize or log decode_error
                     match buffer.to_message() {
                         Ok(message) => match self.active_requests.entry(message.id()) {
                             Entry::Occupied(mut request_entry) => {
+                                // verify the response, e.g. the TSIG, before accepting it
+                                let verified = match request_entry.get_mut().verify(buffer.bytes())
+                                {
+                                    Ok(verified) => verified,
+                                    Err(e) => {
+                                        warn!("response failed verification: {}", e);
+                                        request_entry.remove().complete_with_error(e);
+                                        continue;
+                                    }
+                                };
+
                                 // first add the response to the active_requests responses
                                 let complete = {
                                     let mut active_request = request_entry.get_mut();
                                     active_request.add_response(message);
 
                                     // determine if this is complete
-                                    !active_request.request_options().expects_multiple_responses
+                                    active_request.is_complete()
                                 };
 
                                 // now check if the request is complete
-                                if complete {
+                                if complete && !verified {
+                                    request_entry.remove().complete_with_error(
+                                        "final response failed verification, it is not signed"
+                                            .into(),
+                                    );
+                                } else if complete {
                                     let mut active_request = request_entry.remove();
                                     active_request.complete();
                                 }
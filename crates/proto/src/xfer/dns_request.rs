@@ -9,7 +9,18 @@
 
 use std::ops::{Deref, DerefMut};
 
-use op::Message;
+use futures::sync::mpsc::UnboundedSender;
+
+use op::{Edns, Message};
+use rr::rdata::opt::EdnsOption;
+
+/// The maximum UDP payload of requests, 1232, the size recommended by the
+///  [DNS Flag Day 2020](https://dnsflagday.net/2020/)
+///
+/// Responses of this size fit in a single packet on links with an MTU of 1280, the minimum of
+///  IPv6, with the 40 bytes of the IPv6 header and the 8 of the UDP header. Larger responses may
+///  be fragmented, and fragments are dropped by many networks.
+pub const MAX_PAYLOAD_LEN: u16 = 1280 - 40 - 8;
 
 /// A set of options for expressing options to how requests should be treated
 #[derive(Clone, Default)]
@@ -17,10 +28,51 @@ pub struct DnsRequestOptions {
     /// When true, the underlying DNS protocols will not return on the first response recieved.
     ///
     /// Setting this option will cause the underlying protocol to await the timeout, and then return all Responses.
+    ///  Zone transfers, AXFR and IXFR, are returned as soon as the closing SOA is received.
     pub expects_multiple_responses: bool,
     // /// If set, then the request will terminate early if all types have been received
     // pub expected_record_types: Option<SmallVec<[RecordType; 2]>>,
-    // TODO: add EDNS options here?
+    /// The EDNS of queries made with `DnsHandle::lookup`, when `None` the default of
+    ///  `DnsRequestOptions::default_edns` is used. See the `with_*` functions to configure it.
+    pub edns: Option<Edns>,
+}
+
+impl DnsRequestOptions {
+    /// The EDNS of queries without configured EDNS, version 0 with a max payload of
+    ///  `MAX_PAYLOAD_LEN`
+    pub fn default_edns() -> Edns {
+        let mut edns = Edns::new();
+        edns.set_max_payload(MAX_PAYLOAD_LEN);
+        edns.set_version(0);
+        edns
+    }
+
+    /// Returns the EDNS of queries with these options
+    pub fn query_edns(&self) -> Edns {
+        self.edns.clone().unwrap_or_else(Self::default_edns)
+    }
+
+    /// Sets the maximum UDP payload the responses may have, values lower than 512 are treated as 512
+    pub fn with_max_payload(mut self, max_payload: u16) -> Self {
+        self.edns_mut().set_max_payload(max_payload);
+        self
+    }
+
+    /// Sets the DNSSEC OK bit, requesting the DNSSEC records of the responses
+    pub fn with_dnssec_ok(mut self, dnssec_ok: bool) -> Self {
+        self.edns_mut().set_dnssec_ok(dnssec_ok);
+        self
+    }
+
+    /// Adds the option to the EDNS of the queries, replacing an option with the same code
+    pub fn with_edns_option(mut self, option: EdnsOption) -> Self {
+        self.edns_mut().set_option(option);
+        self
+    }
+
+    fn edns_mut(&mut self) -> &mut Edns {
+        self.edns.get_or_insert_with(Self::default_edns)
+    }
 }
 
 /// A DNS reqeust object
@@ -30,12 +82,37 @@ pub struct DnsRequestOptions {
 pub struct DnsRequest {
     message: Message,
     options: DnsRequestOptions,
+    response_stream: Option<UnboundedSender<Message>>,
+    raw: bool,
 }
 
 impl DnsRequest {
     /// Returns a new DnsRequest object
     pub fn new(message: Message, options: DnsRequestOptions) -> Self {
-        DnsRequest { message, options }
+        DnsRequest {
+            message,
+            options,
+            response_stream: None,
+            raw: false,
+        }
+    }
+
+    /// Returns a new DnsRequest which is sent as it is
+    ///
+    /// The id of the message is not replaced, the message is not signed, the counts of the header
+    ///  are not updated from the sections, see `Message::to_raw_vec`, and the request is not
+    ///  retried. The response is still matched to the request by the id, which must not be in use
+    ///  by another request on the connection.
+    pub fn raw(message: Message, options: DnsRequestOptions) -> Self {
+        DnsRequest {
+            raw: true,
+            ..Self::new(message, options)
+        }
+    }
+
+    /// Returns true if the request is sent as it is, see `raw`
+    pub fn is_raw(&self) -> bool {
+        self.raw
     }
 
     /// Get the set of request options associated with this request
@@ -43,6 +120,23 @@ impl DnsRequest {
         &self.options
     }
 
+    /// Sends the messages of the responses to the stream as they are received, with
+    ///  `expects_multiple_responses`, instead of holding them until the request completes
+    ///
+    /// The last message received is always the response of the request, the stream only
+    ///  receives the previous messages, in order, before the request completes. This bounds the
+    ///  memory of large zone transfers. Protocols which don't stream the messages return them
+    ///  all in the response.
+    pub fn with_response_stream(mut self, response_stream: UnboundedSender<Message>) -> Self {
+        self.response_stream = Some(response_stream);
+        self
+    }
+
+    /// Takes the stream of the messages of the responses, see `with_response_stream`
+    pub fn take_response_stream(&mut self) -> Option<UnboundedSender<Message>> {
+        self.response_stream.take()
+    }
+
     /// Unwraps the raw message
     pub fn unwrap(self) -> (Message, DnsRequestOptions) {
         (self.message, self.options)
@@ -67,3 +161,35 @@ impl Into<DnsRequest> for Message {
         DnsRequest::new(self, DnsRequestOptions::default())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rr::rdata::opt::EdnsCode;
+
+    #[test]
+    fn test_default_edns() {
+        let edns = DnsRequestOptions::default().query_edns();
+
+        assert_eq!(edns.max_payload(), MAX_PAYLOAD_LEN);
+        assert_eq!(edns.version(), 0);
+        assert!(!edns.dnssec_ok());
+    }
+
+    #[test]
+    fn test_with_edns() {
+        let options = DnsRequestOptions::default()
+            .with_max_payload(4096)
+            .with_dnssec_ok(true)
+            .with_edns_option(EdnsOption::Unknown(10, vec![1, 2, 3, 4, 5, 6, 7, 8]));
+        let edns = options.query_edns();
+
+        assert_eq!(edns.max_payload(), 4096);
+        assert_eq!(edns.version(), 0);
+        assert!(edns.dnssec_ok());
+        assert_eq!(
+            edns.option(EdnsCode::Cookie),
+            Some(&EdnsOption::Unknown(10, vec![1, 2, 3, 4, 5, 6, 7, 8]))
+        );
+    }
+}
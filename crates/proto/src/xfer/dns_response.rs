@@ -20,28 +20,43 @@ use op::Message;
 ///
 /// For Most DNS requests, only one response is expected, the exception is a multicast request.
 #[derive(Clone, Debug)]
-pub struct DnsResponse(SmallVec<[Message; 1]>);
+pub struct DnsResponse {
+    messages: SmallVec<[Message; 1]>,
+    tcp_fallback: bool,
+}
 
 // TODO: when `impl Trait` lands in stable, remove this, and expose FlatMap over answers, et al.
 impl DnsResponse {
     /// Get all the messages in the Response
     pub fn messages(&self) -> Iter<Message> {
-        self.0.as_slice().iter()
+        self.messages.as_slice().iter()
     }
 
     /// Get all the messages in the Response
     pub fn messages_mut(&mut self) -> IterMut<Message> {
-        self.0.as_mut_slice().iter_mut()
+        self.messages.as_mut_slice().iter_mut()
     }
 
     /// returns the number of messages in the response
     pub fn len(&self) -> usize {
-        self.0.len()
+        self.messages.len()
     }
 
     /// returns the number of messages in the response
     pub fn is_empty(&self) -> bool {
-        self.0.is_empty()
+        self.messages.is_empty()
+    }
+
+    /// Returns true if the request was sent again over TCP, after a truncated response or a
+    ///  failure over UDP, and this is the response received over TCP
+    pub fn tcp_fallback(&self) -> bool {
+        self.tcp_fallback
+    }
+
+    /// Sets whether this response was received over TCP after falling back from UDP
+    pub fn set_tcp_fallback(&mut self, tcp_fallback: bool) -> &mut Self {
+        self.tcp_fallback = tcp_fallback;
+        self
     }
 }
 
@@ -49,25 +64,28 @@ impl Deref for DnsResponse {
     type Target = Message;
 
     fn deref(&self) -> &Self::Target {
-        &self.0[0]
+        &self.messages[0]
     }
 }
 
 impl DerefMut for DnsResponse {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0[0]
+        &mut self.messages[0]
     }
 }
 
 impl From<DnsResponse> for Message {
     fn from(mut response: DnsResponse) -> Message {
-        response.0.remove(0)
+        response.messages.remove(0)
     }
 }
 
 impl From<Message> for DnsResponse {
     fn from(message: Message) -> DnsResponse {
-        DnsResponse(SmallVec::from([message]))
+        DnsResponse {
+            messages: SmallVec::from([message]),
+            tcp_fallback: false,
+        }
     }
 }
 
@@ -77,6 +95,9 @@ impl From<SmallVec<[Message; 1]>> for DnsResponse {
             !messages.is_empty(),
             "There should be at least one message in any DnsResponse"
         );
-        DnsResponse(messages)
+        DnsResponse {
+            messages,
+            tcp_fallback: false,
+        }
     }
 }
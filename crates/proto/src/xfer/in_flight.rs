@@ -0,0 +1,86 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Limit of the requests in flight of a `DnsExchange`
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use futures::task::AtomicTask;
+
+/// What a `DnsExchange` does with the requests over its limit of requests in flight
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum InFlightPolicy {
+    /// The requests wait for earlier requests to complete, they are sent in the order they were
+    ///  made, whichever handle they were made on
+    Wait,
+    /// The requests fail immediately with `ProtoErrorKind::Busy`
+    Fail,
+}
+
+/// Counts the requests in flight of a `DnsExchange`
+pub(crate) struct InFlight {
+    max: usize,
+    policy: InFlightPolicy,
+    state: Arc<InFlightState>,
+}
+
+struct InFlightState {
+    count: AtomicUsize,
+    // the task of the exchange, notified when a request completes
+    task: AtomicTask,
+}
+
+impl InFlight {
+    pub fn new(max: usize, policy: InFlightPolicy) -> Self {
+        InFlight {
+            max,
+            policy,
+            state: Arc::new(InFlightState {
+                count: AtomicUsize::new(0),
+                task: AtomicTask::new(),
+            }),
+        }
+    }
+
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    pub fn policy(&self) -> InFlightPolicy {
+        self.policy
+    }
+
+    /// Returns a permit for one more request in flight, None if the maximum are in flight
+    ///
+    /// The current task is notified when a permit is released. Only the exchange acquires
+    ///  permits, they are released from the tasks awaiting the responses.
+    pub fn try_acquire(&self) -> Option<InFlightPermit> {
+        self.state.task.register();
+        if self.state.count.load(Ordering::SeqCst) >= self.max {
+            return None;
+        }
+
+        self.state.count.fetch_add(1, Ordering::SeqCst);
+        Some(InFlightPermit {
+            state: Arc::clone(&self.state),
+        })
+    }
+}
+
+/// A request in flight on a `DnsExchange` with a limit, the request is no longer in flight once
+///  the permit is dropped
+pub struct InFlightPermit {
+    state: Arc<InFlightState>,
+}
+
+impl Drop for InFlightPermit {
+    fn drop(&mut self) {
+        self.state.count.fetch_sub(1, Ordering::SeqCst);
+        self.state.task.notify();
+    }
+}
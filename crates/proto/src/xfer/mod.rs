@@ -10,7 +10,7 @@ use std::net::SocketAddr;
 use error::*;
 use futures::sync::mpsc::{SendError, UnboundedSender};
 use futures::sync::oneshot;
-use futures::{Future, Poll, Stream};
+use futures::{Async, Future, Poll, Stream};
 use op::Message;
 
 mod dns_exchange;
@@ -18,10 +18,12 @@ pub mod dns_handle;
 pub mod dns_multiplexer;
 pub mod dns_request;
 pub mod dns_response;
+mod in_flight;
 pub mod retry_dns_handle;
 #[cfg(feature = "dnssec")]
 pub mod secure_dns_handle;
 mod serial_message;
+pub mod tcp_fallback_handle;
 
 pub use self::dns_exchange::{DnsExchange, DnsExchangeConnect};
 pub use self::dns_handle::{BasicDnsHandle, DnsHandle, DnsStreamHandle, StreamHandle};
@@ -30,10 +32,14 @@ pub use self::dns_multiplexer::{
 };
 pub use self::dns_request::{DnsRequest, DnsRequestOptions};
 pub use self::dns_response::DnsResponse;
-pub use self::retry_dns_handle::RetryDnsHandle;
+pub use self::in_flight::{InFlightPermit, InFlightPolicy};
+pub use self::retry_dns_handle::{
+    BoxDelay, DelayProvider, RetryDnsHandle, RetryPolicy, TokioDelay,
+};
 #[cfg(feature = "dnssec")]
 pub use self::secure_dns_handle::SecureDnsHandle;
 pub use self::serial_message::SerialMessage;
+pub use self::tcp_fallback_handle::TcpFallbackHandle;
 
 /// Ignores the result of a send operation and logs and ignores errors
 fn ignore_send<M, E: Debug>(result: Result<M, E>) {
@@ -243,14 +249,19 @@ where
     F: Future<Item = DnsResponse, Error = ProtoError> + Send,
 {
     dns_request: DnsRequest,
-    sender_for_response: oneshot::Sender<F>,
+    sender_for_response: oneshot::Sender<(F, Option<InFlightPermit>)>,
 }
 
 impl<F> OneshotDnsRequest<F>
 where
     F: Future<Item = DnsResponse, Error = ProtoError> + Send,
 {
-    fn oneshot(dns_request: DnsRequest) -> (OneshotDnsRequest<F>, oneshot::Receiver<F>) {
+    fn oneshot(
+        dns_request: DnsRequest,
+    ) -> (
+        OneshotDnsRequest<F>,
+        oneshot::Receiver<(F, Option<InFlightPermit>)>,
+    ) {
         let (sender_for_response, receiver) = oneshot::channel();
 
         (
@@ -270,7 +281,7 @@ where
     }
 }
 
-struct OneshotDnsResponse<F>(oneshot::Sender<F>)
+struct OneshotDnsResponse<F>(oneshot::Sender<(F, Option<InFlightPermit>)>)
 where
     F: Future<Item = DnsResponse, Error = ProtoError> + Send;
 
@@ -278,8 +289,17 @@ impl<F> OneshotDnsResponse<F>
 where
     F: Future<Item = DnsResponse, Error = ProtoError> + Send,
 {
-    fn send_response(self, serial_response: F) -> Result<(), F> {
-        self.0.send(serial_response)
+    /// Sends the response future, with the permit of the request if the exchange has a limit
+    fn send_response(
+        self,
+        serial_response: F,
+        permit: Option<InFlightPermit>,
+    ) -> Result<(), (F, Option<InFlightPermit>)> {
+        self.0.send((serial_response, permit))
+    }
+
+    fn is_canceled(&self) -> bool {
+        self.0.is_canceled()
     }
 }
 
@@ -289,9 +309,10 @@ where
     F: Future<Item = DnsResponse, Error = ProtoError> + Send,
 {
     /// The receiver
-    Receiver(oneshot::Receiver<F>),
-    /// The future once received
-    Received(F),
+    Receiver(oneshot::Receiver<(F, Option<InFlightPermit>)>),
+    /// The future once received, and the permit of the request which is released once it
+    ///  completes
+    Received(F, Option<InFlightPermit>),
     /// Error during the send operation
     Err(Option<ProtoError>),
 }
@@ -305,14 +326,25 @@ where
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
         loop {
-            let future;
+            let (future, permit);
             match self {
                 OneshotDnsResponseReceiver::Receiver(ref mut receiver) => {
-                    future = try_ready!(receiver
+                    let received = try_ready!(receiver
                         .poll()
                         .map_err(|_| ProtoError::from("receiver was canceled")));
+                    future = received.0;
+                    permit = received.1;
+                }
+                OneshotDnsResponseReceiver::Received(ref mut future, ref mut permit) => {
+                    let poll = future.poll();
+                    // the request is no longer in flight once it completed, even if this is not
+                    //  dropped yet
+                    match poll {
+                        Ok(Async::NotReady) => (),
+                        _ => drop(permit.take()),
+                    }
+                    return poll;
                 }
-                OneshotDnsResponseReceiver::Received(ref mut future) => return future.poll(),
                 OneshotDnsResponseReceiver::Err(err) => {
                     return Err(err
                         .take()
@@ -320,7 +352,7 @@ where
                 }
             }
 
-            *self = OneshotDnsResponseReceiver::Received(future);
+            *self = OneshotDnsResponseReceiver::Received(future, permit);
         }
     }
 }
@@ -7,12 +7,101 @@
 
 //! `RetryDnsHandle` allows for DnsQueries to be reattempted on failure
 
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
 use futures::{Future, Poll};
+use rand::{self, Rng};
+use tokio_timer::{self, clock, Delay};
 
 use error::ProtoError;
 use xfer::{DnsRequest, DnsResponse};
 use DnsHandle;
 
+/// How failed requests are retried, see `RetryDnsHandle`
+///
+/// The delay before each retry grows by the `backoff_factor` from the `base_delay`, up to the
+///  `max_delay`. With `jitter` each delay is a random duration between half of it and all of it, so
+///  that clients which failed at the same time don't retry at the same time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Number of retries once the first attempt failed
+    pub attempts: usize,
+    /// Delay before the first retry, the default is to retry immediately
+    pub base_delay: Duration,
+    /// Factor by which the delay grows with each retry, the default is 2
+    pub backoff_factor: u32,
+    /// Maximum delay before a retry, the default is 5 seconds
+    pub max_delay: Duration,
+    /// Randomize the delays, the default is false
+    pub jitter: bool,
+}
+
+impl RetryPolicy {
+    /// A policy of `attempts` immediate retries
+    pub fn new(attempts: usize) -> Self {
+        RetryPolicy {
+            attempts,
+            ..RetryPolicy::default()
+        }
+    }
+
+    /// The delay before the retry, the first retry is `0`
+    pub fn delay(&self, retry: usize) -> Duration {
+        let mut delay = self.base_delay;
+        for _ in 0..retry {
+            if delay >= self.max_delay {
+                break;
+            }
+            delay = delay
+                .checked_mul(self.backoff_factor)
+                .unwrap_or(self.max_delay);
+        }
+        let delay = delay.min(self.max_delay);
+
+        if !self.jitter || delay == Duration::from_secs(0) {
+            return delay;
+        }
+
+        let millis = delay.as_secs() * 1000 + u64::from(delay.subsec_millis());
+        let half = millis / 2;
+        Duration::from_millis(half + rand::thread_rng().gen_range(0, millis - half + 1))
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            attempts: 2,
+            base_delay: Duration::from_secs(0),
+            backoff_factor: 2,
+            max_delay: Duration::from_secs(5),
+            jitter: false,
+        }
+    }
+}
+
+/// A future which is ready at its deadline, see `DelayProvider`
+pub type BoxDelay = Box<Future<Item = (), Error = ProtoError> + Send>;
+
+/// The timers of the runtime which polls the futures, e.g. the backoff of the `RetryDnsHandle`
+pub trait DelayProvider: Send + Sync {
+    /// Returns a future which is ready at `deadline`
+    fn delay(&self, deadline: Instant) -> BoxDelay;
+}
+
+/// The timers of Tokio, the default `DelayProvider`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TokioDelay;
+
+impl DelayProvider for TokioDelay {
+    fn delay(&self, deadline: Instant) -> BoxDelay {
+        Box::new(
+            Delay::new(deadline).map_err(ProtoError::from as fn(tokio_timer::Error) -> ProtoError),
+        )
+    }
+}
+
 /// Can be used to reattempt a queries if they fail
 ///
 /// *note* Current value of this is not clear, it may be removed
@@ -20,7 +109,8 @@ use DnsHandle;
 #[must_use = "queries can only be sent through a ClientHandle"]
 pub struct RetryDnsHandle<H: DnsHandle> {
     handle: H,
-    attempts: usize,
+    retry_policy: RetryPolicy,
+    delay_provider: Arc<DelayProvider>,
 }
 
 impl<H: DnsHandle> RetryDnsHandle<H> {
@@ -31,7 +121,23 @@ impl<H: DnsHandle> RetryDnsHandle<H> {
     /// * `handle` - handle to the dns connection
     /// * `attempts` - number of attempts before failing
     pub fn new(handle: H, attempts: usize) -> Self {
-        RetryDnsHandle { handle, attempts }
+        Self::with_policy(handle, RetryPolicy::new(attempts))
+    }
+
+    /// Creates a new Client handler for reattempting requests on failures, after the delays of the
+    ///  `retry_policy`
+    pub fn with_policy(handle: H, retry_policy: RetryPolicy) -> Self {
+        RetryDnsHandle {
+            handle,
+            retry_policy,
+            delay_provider: Arc::new(TokioDelay),
+        }
+    }
+
+    /// Waits the delays of the retry policy with the timers of the provider, Tokio by default
+    pub fn with_delay_provider(mut self, delay_provider: Arc<DelayProvider>) -> Self {
+        self.delay_provider = delay_provider;
+        self
     }
 }
 
@@ -48,11 +154,21 @@ where
         //  obviously it would be nice to be lazy about this...
         let future = self.handle.send(request.clone());
 
+        // raw requests are sent once, as they are
+        let retry_policy = if request.is_raw() {
+            RetryPolicy::new(0)
+        } else {
+            self.retry_policy
+        };
+
         Box::new(RetrySendFuture {
             request,
             handle: self.handle.clone(),
             future,
-            remaining_attempts: self.attempts,
+            retry_policy,
+            retries: 0,
+            delay_provider: Arc::clone(&self.delay_provider),
+            backoff: None,
         })
     }
 }
@@ -62,7 +178,10 @@ struct RetrySendFuture<H: DnsHandle> {
     request: DnsRequest,
     handle: H,
     future: <H as DnsHandle>::Response,
-    remaining_attempts: usize,
+    retry_policy: RetryPolicy,
+    retries: usize,
+    delay_provider: Arc<DelayProvider>,
+    backoff: Option<BoxDelay>,
 }
 
 impl<H: DnsHandle> Future for RetrySendFuture<H> {
@@ -73,19 +192,39 @@ impl<H: DnsHandle> Future for RetrySendFuture<H> {
         // loop over the future, on errors, spawn a new future
         //  on ready and not ready return.
         loop {
-            match self.future.poll() {
-                r @ Ok(_) => return r,
-                Err(e) => {
-                    if self.remaining_attempts == 0 {
-                        return Err(e);
-                    }
+            if let Some(ref mut backoff) = self.backoff {
+                try_ready!(backoff.poll());
+            }
 
-                    self.remaining_attempts -= 1;
-                    // FIXME: if the "sent" Message is part of the error result,
-                    //  then we can just reuse it... and no clone necessary
-                    self.future = self.handle.send(self.request.clone());
+            if self.backoff.take().is_none() {
+                match self.future.poll() {
+                    r @ Ok(_) => return r,
+                    Err(e) => {
+                        if self.retries >= self.retry_policy.attempts {
+                            return Err(e);
+                        }
+
+                        let delay = self.retry_policy.delay(self.retries);
+                        self.retries += 1;
+                        #[cfg(feature = "tracing-spans")]
+                        ::tracing::debug!(
+                            attempt = self.retries as u64,
+                            delay = ?delay,
+                            error = %e,
+                            "retransmitting request"
+                        );
+                        if delay > Duration::from_secs(0) {
+                            debug!("retrying request in {:?} after: {}", delay, e);
+                            self.backoff = Some(self.delay_provider.delay(clock::now() + delay));
+                            continue;
+                        }
+                    }
                 }
             }
+
+            // FIXME: if the "sent" Message is part of the error result,
+            //  then we can just reuse it... and no clone necessary
+            self.future = self.handle.send(self.request.clone());
         }
     }
 }
@@ -151,4 +290,113 @@ mod test {
         let test1 = Message::new();
         assert!(client.send(test1).wait().is_err());
     }
+
+    #[test]
+    fn test_raw_not_retried() {
+        let mut client = RetryDnsHandle::new(
+            TestClient {
+                last_succeed: true,
+                retries: 1,
+                attempts: Cell::new(0),
+            },
+            2,
+        );
+        let request = DnsRequest::raw(Message::new(), Default::default());
+        assert!(client.send(request).wait().is_err());
+    }
+
+    #[test]
+    fn test_retry_policy_delay() {
+        let policy = RetryPolicy {
+            attempts: 5,
+            base_delay: Duration::from_millis(100),
+            backoff_factor: 2,
+            max_delay: Duration::from_millis(500),
+            jitter: false,
+        };
+
+        assert_eq!(policy.delay(0), Duration::from_millis(100));
+        assert_eq!(policy.delay(1), Duration::from_millis(200));
+        assert_eq!(policy.delay(2), Duration::from_millis(400));
+        assert_eq!(policy.delay(3), Duration::from_millis(500));
+        assert_eq!(policy.delay(usize::max_value()), Duration::from_millis(500));
+
+        let policy = RetryPolicy {
+            jitter: true,
+            ..policy
+        };
+        for _ in 0..100 {
+            let delay = policy.delay(1);
+            assert!(delay >= Duration::from_millis(100));
+            assert!(delay <= Duration::from_millis(200));
+        }
+
+        assert_eq!(RetryPolicy::new(3).delay(2), Duration::from_secs(0));
+    }
+
+    #[test]
+    fn test_retry_backoff() {
+        use std::time::Instant;
+        use tokio::runtime::current_thread::Runtime;
+
+        let mut handle = RetryDnsHandle::with_policy(
+            TestClient {
+                last_succeed: true,
+                retries: 2,
+                attempts: Cell::new(0),
+            },
+            RetryPolicy {
+                base_delay: Duration::from_millis(10),
+                ..RetryPolicy::default()
+            },
+        );
+
+        let start = Instant::now();
+        let result = Runtime::new()
+            .unwrap()
+            .block_on(handle.send(Message::new()))
+            .expect("should have succeeded");
+
+        assert_eq!(result.id(), 2);
+        // the retries are after 10 and 20 milliseconds
+        assert!(start.elapsed() >= Duration::from_millis(30));
+    }
+
+    #[test]
+    fn test_retry_backoff_delay_provider() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::time::Instant;
+
+        #[derive(Default)]
+        struct CountingDelay(AtomicUsize);
+
+        impl DelayProvider for CountingDelay {
+            fn delay(&self, _deadline: Instant) -> BoxDelay {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                Box::new(finished(()))
+            }
+        }
+
+        let delays = Arc::new(CountingDelay::default());
+        let mut handle = RetryDnsHandle::with_policy(
+            TestClient {
+                last_succeed: true,
+                retries: 2,
+                attempts: Cell::new(0),
+            },
+            RetryPolicy {
+                base_delay: Duration::from_secs(60),
+                ..RetryPolicy::default()
+            },
+        )
+        .with_delay_provider(delays.clone());
+
+        // the provider's delays are ready at once, no Tokio timer is needed
+        let result = handle
+            .send(Message::new())
+            .wait()
+            .expect("should have succeeded");
+        assert_eq!(result.id(), 2);
+        assert_eq!(delays.0.load(Ordering::SeqCst), 2);
+    }
 }
@@ -8,19 +8,19 @@
 //! The `SecureDnsHandle` is used to validate all DNS responses for correct DNSSec signatures.
 
 use std::clone::Clone;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::mem;
 use std::sync::Arc;
 
 use futures::*;
 
 use error::*;
-use op::{OpCode, Query};
+use op::{Message, MessageType, OpCode, Query};
 use rr::dnssec::rdata::{DNSSECRData, DNSSECRecordType, DNSKEY, SIG};
 #[cfg(feature = "dnssec")]
 use rr::dnssec::Verifier;
 use rr::dnssec::{Algorithm, SupportedAlgorithms, TrustAnchor};
-use rr::rdata::opt::EdnsOption;
+use rr::rdata::opt::{EdnsCode, EdnsOption};
 use rr::{DNSClass, Name, RData, Record, RecordType};
 use xfer::{DnsRequest, DnsRequestOptions, DnsResponse};
 use DnsHandle;
@@ -49,6 +49,9 @@ where
     request_depth: usize,
     minimum_key_len: usize,
     minimum_algorithm: Algorithm, // used to prevent down grade attacks...
+    chain_query: Option<Name>,
+    /// the DS and DNSKEY rrsets, with their RRSIGs, of the chain returned to a CHAIN query
+    chain: Arc<HashMap<(Name, RecordType), Vec<Record>>>,
 }
 
 impl<H> SecureDnsHandle<H>
@@ -79,9 +82,24 @@ where
             request_depth: 0,
             minimum_key_len: 0,
             minimum_algorithm: Algorithm::RSASHA256,
+            chain_query: None,
+            chain: Arc::new(HashMap::new()),
         }
     }
 
+    /// Requests the DNSSEC chain of the responses, from the closest trust point down, with the
+    ///  EDNS CHAIN option, [RFC 7901](https://tools.ietf.org/html/rfc7901)
+    ///
+    /// The DS and DNSKEY records of the chain are validated without querying them. Servers only
+    ///  return the chain over TCP, or other stream protocols, otherwise they are queried as usual.
+    ///
+    /// # Arguments
+    /// * `closest_trust_point` - the closest name to the queries with a trusted key, e.g. the root
+    pub fn with_chain_query(mut self, closest_trust_point: Name) -> Self {
+        self.chain_query = Some(closest_trust_point);
+        self
+    }
+
     /// An internal function used to clone the handle, but maintain some information back to the
     ///  original handle, such as the request_depth such that infinite recurssion does
     ///  not occur.
@@ -92,8 +110,26 @@ where
             request_depth: self.request_depth + 1,
             minimum_key_len: self.minimum_key_len,
             minimum_algorithm: self.minimum_algorithm,
+            chain_query: self.chain_query.clone(),
+            chain: Arc::clone(&self.chain),
         }
     }
+
+    /// Responds with the records of the chain to the queries of the DS and DNSKEY records in it
+    fn chain_response(&self, request: &DnsRequest, query: &Query) -> Option<DnsResponse> {
+        let records = self
+            .chain
+            .get(&(query.name().clone(), query.query_type()))?;
+
+        let mut message = Message::new();
+        message
+            .set_id(request.id())
+            .set_message_type(MessageType::Response)
+            .set_op_code(OpCode::Query)
+            .add_query(query.clone());
+        message.insert_answers(records.clone());
+        Some(message.into())
+    }
 }
 
 impl<H: DnsHandle> DnsHandle for SecureDnsHandle<H> {
@@ -107,6 +143,11 @@ impl<H: DnsHandle> DnsHandle for SecureDnsHandle<H> {
     fn send<R: Into<DnsRequest>>(&mut self, request: R) -> Self::Response {
         let mut request = request.into();
 
+        // raw requests are sent as they are, and their responses are not validated
+        if request.is_raw() {
+            return Box::new(self.handle.send(request));
+        }
+
         // backstop, this might need to be configurable at some point
         if self.request_depth > 20 {
             return Box::new(failed(ProtoError::from("exceeded max validation depth")));
@@ -147,6 +188,19 @@ impl<H: DnsHandle> DnsHandle for SecureDnsHandle<H> {
                 edns.set_option(dhu);
             }
 
+            // only the original query requests the chain, the records of which are then used for
+            //  the validation of its response
+            let chain_query = if self.request_depth == 0 {
+                self.chain_query.clone()
+            } else {
+                None
+            };
+            if let Some(ref closest_trust_point) = chain_query {
+                request
+                    .edns_mut()
+                    .set_option(EdnsOption::Chain(closest_trust_point.clone()));
+            }
+
             request.set_authentic_data(true);
             request.set_checking_disabled(false);
             let dns_class = request
@@ -154,10 +208,22 @@ impl<H: DnsHandle> DnsHandle for SecureDnsHandle<H> {
                 .first()
                 .map_or(DNSClass::IN, Query::query_class);
 
+            let response: Self::Response = match self.chain_response(&request, &query) {
+                Some(response) => {
+                    debug!("responding from the chain to: {}", query);
+                    Box::new(finished(response))
+                }
+                None => Box::new(self.handle.send(request)),
+            };
+
             return Box::new(
-                self.handle
-                    .send(request)
-                    .and_then(move |message_response| {
+                response
+                    .and_then(move |mut message_response| {
+                        let mut handle = handle;
+                        if chain_query.is_some() {
+                            handle.chain = Arc::new(take_chain(&mut message_response));
+                        }
+
                         // group the record sets by name and type
                         //  each rrset type needs to validated independently
                         debug!("validating message_response: {}", message_response.id());
@@ -319,6 +385,48 @@ fn is_dnssec(rr: &Record, dnssec_type: DNSSECRecordType) -> bool {
     rr.rr_type() == RecordType::DNSSEC(dnssec_type)
 }
 
+/// Takes the DS and DNSKEY rrsets, and the RRSIGs covering them, of the chain returned to a CHAIN
+///  query from the authority section of the response
+fn take_chain(response: &mut DnsResponse) -> HashMap<(Name, RecordType), Vec<Record>> {
+    let mut chain = HashMap::new();
+    if response
+        .edns()
+        .and_then(|edns| edns.option(EdnsCode::Chain))
+        .is_none()
+    {
+        // the server did not return the chain
+        return chain;
+    }
+
+    let (chain_records, name_servers): (Vec<Record>, Vec<Record>) = response
+        .take_name_servers()
+        .into_iter()
+        .partition(|rr| match *rr.rdata() {
+            RData::DNSSEC(DNSSECRData::SIG(ref rrsig)) => is_chain_type(rrsig.type_covered()),
+            _ => is_chain_type(rr.rr_type()),
+        });
+    response.insert_name_servers(name_servers);
+
+    for record in chain_records {
+        let record_type = match *record.rdata() {
+            RData::DNSSEC(DNSSECRData::SIG(ref rrsig)) => rrsig.type_covered(),
+            _ => record.rr_type(),
+        };
+
+        chain
+            .entry((record.name().clone(), record_type))
+            .or_insert_with(Vec::new)
+            .push(record);
+    }
+
+    chain
+}
+
+fn is_chain_type(record_type: RecordType) -> bool {
+    record_type == RecordType::DNSSEC(DNSSECRecordType::DS)
+        || record_type == RecordType::DNSSEC(DNSSECRecordType::DNSKEY)
+}
+
 impl Future for VerifyRrsetsFuture {
     type Item = DnsResponse;
     type Error = ProtoError;
@@ -678,7 +786,8 @@ where
 
                         if rrset.records.iter().any(|r| {
                             if let RData::DNSSEC(DNSSECRData::DNSKEY(ref dnskey)) = *r.rdata() {
-                                verify_rrset_with_dnskey(dnskey, &sig, &rrset).is_ok()
+                                is_key_of_signature(dnskey, &sig)
+                                    && verify_rrset_with_dnskey(dnskey, &sig, &rrset).is_ok()
                             } else {
                                 panic!("expected a DNSKEY here: {:?}", r.rdata());
                             }
@@ -730,7 +839,9 @@ where
                                              .filter(|r| is_dnssec(r, DNSSECRecordType::DNSKEY))
                                              .find(|r|
                                                if let RData::DNSSEC(DNSSECRData::DNSKEY(ref dnskey)) = *r.rdata() {
-                                                 verify_rrset_with_dnskey(dnskey, &sig, &rrset).is_ok()
+                                                 // all the keys with the tag are tried, tags may collide
+                                                 is_key_of_signature(dnskey, &sig) &&
+                                                   verify_rrset_with_dnskey(dnskey, &sig, &rrset).is_ok()
                                                } else {
                                                  panic!("expected a DNSKEY here: {:?}", r.rdata());
                                                }
@@ -760,6 +871,18 @@ where
     Box::new(select)
 }
 
+/// Returns true if the DNSKEY may have made the SIG, it has the key tag and algorithm of the SIG
+///
+/// Key tags are not unique, several keys of a zone may have the same tag. The signature is then
+///  verified with each of them, see RFC 4035 section 5.3.1.
+fn is_key_of_signature(dnskey: &DNSKEY, sig: &SIG) -> bool {
+    dnskey.algorithm() == sig.algorithm()
+        && dnskey
+            .calculate_key_tag()
+            .map(|key_tag| key_tag == sig.key_tag())
+            .unwrap_or(false)
+}
+
 /// Verifies the given SIG of the RRSET with the DNSKEY.
 #[cfg(feature = "dnssec")]
 fn verify_rrset_with_dnskey(dnskey: &DNSKEY, sig: &SIG, rrset: &Rrset) -> ProtoResult<()> {
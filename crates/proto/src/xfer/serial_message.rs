@@ -9,6 +9,7 @@ use std::net::SocketAddr;
 
 use error::ProtoResult;
 use op::Message;
+use serialize::binary::{BinDecodable, BinDecoder, BinEncodable, BinEncoder};
 
 /// A DNS message in serialized form, with either the target address or source address
 pub struct SerialMessage {
@@ -38,8 +39,59 @@ impl SerialMessage {
         (self.message, self.addr)
     }
 
+    /// Serializes the Message, to be sent to the address
+    ///
+    /// Messages to the mDNS port are written as mDNS messages, with the cache-flush and
+    ///  unicast-response bits of the records and queries.
+    pub fn from_message(message: &Message, addr: SocketAddr) -> ProtoResult<Self> {
+        let mut bytes = Vec::with_capacity(512);
+        {
+            let mut encoder = BinEncoder::new(&mut bytes);
+            #[cfg(all(feature = "mdns", not(target_arch = "wasm32")))]
+            encoder.set_mdns(is_mdns(addr));
+            message.emit(&mut encoder)?;
+        }
+
+        Ok(SerialMessage::new(bytes, addr))
+    }
+
     /// Deserializes the inner data into a Message
+    ///
+    /// Messages from the mDNS port are read as mDNS messages, see `from_message`.
     pub fn to_message(&self) -> ProtoResult<Message> {
-        Message::from_vec(&self.message)
+        let mut decoder = BinDecoder::new(&self.message);
+        #[cfg(all(feature = "mdns", not(target_arch = "wasm32")))]
+        decoder.set_mdns(is_mdns(self.addr));
+        Message::read(&mut decoder)
+    }
+}
+
+/// mDNS messages are sent to and from the mDNS port, the legacy unicast messages of one-shot
+///  queriers use other ports and are standard DNS messages, RFC 6762 section 6.7
+#[cfg(all(feature = "mdns", not(target_arch = "wasm32")))]
+fn is_mdns(addr: SocketAddr) -> bool {
+    addr.port() == ::multicast::MDNS_PORT
+}
+
+#[cfg(all(test, feature = "mdns", not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use multicast::MDNS_IPV4;
+    use op::Query;
+
+    #[test]
+    fn test_mdns_context() {
+        let mut query = Query::new();
+        query.set_mdns_unicast_response(true);
+        let mut message = Message::new();
+        message.add_query(query);
+
+        let mdns = SerialMessage::from_message(&message, *MDNS_IPV4).unwrap();
+        assert!(mdns.to_message().unwrap().queries()[0].mdns_unicast_response());
+
+        let unicast =
+            SerialMessage::from_message(&message, "127.0.0.1:53".parse().unwrap()).unwrap();
+        assert!(!unicast.to_message().unwrap().queries()[0].mdns_unicast_response());
+        assert_ne!(mdns.bytes(), unicast.bytes());
     }
 }
@@ -0,0 +1,155 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! `TcpFallbackHandle` sends requests again over TCP when their responses over UDP are truncated
+
+use futures::{future, Future};
+
+use error::ProtoError;
+use xfer::{DnsRequest, DnsResponse};
+use DnsHandle;
+
+/// Sends requests over a datagram connection, and again over a stream connection if the response
+///  is truncated
+///
+/// Responses received over the stream connection have `DnsResponse::tcp_fallback` set. Failures
+///  over the datagram connection are returned as is, e.g. to be retried by a `RetryDnsHandle`.
+#[derive(Clone)]
+#[must_use = "queries can only be sent through a ClientHandle"]
+pub struct TcpFallbackHandle<D: DnsHandle, S: DnsHandle> {
+    datagram: D,
+    stream: S,
+    accept_truncated: bool,
+}
+
+impl<D: DnsHandle, S: DnsHandle> TcpFallbackHandle<D, S> {
+    /// Creates a new handle over the connections to the same name server
+    ///
+    /// # Arguments
+    ///
+    /// * `datagram` - handle to the UDP connection, on which requests are first sent
+    /// * `stream` - handle to the TCP connection, used after truncated responses
+    pub fn new(datagram: D, stream: S) -> Self {
+        TcpFallbackHandle {
+            datagram,
+            stream,
+            accept_truncated: false,
+        }
+    }
+
+    /// If true, truncated responses are returned without sending the request over TCP, e.g. when
+    ///  the partial answers are enough. Defaults to false
+    pub fn accept_truncated(mut self, accept_truncated: bool) -> Self {
+        self.accept_truncated = accept_truncated;
+        self
+    }
+}
+
+impl<D, S> DnsHandle for TcpFallbackHandle<D, S>
+where
+    D: DnsHandle + 'static,
+    S: DnsHandle + 'static,
+{
+    type Response = Box<Future<Item = DnsResponse, Error = ProtoError> + Send>;
+
+    fn send<R: Into<DnsRequest>>(&mut self, request: R) -> Self::Response {
+        let request = request.into();
+        let mut stream = self.stream.clone();
+        let accept_truncated = self.accept_truncated;
+
+        // the request is kept to be sent again over TCP
+        let stream_request = request.clone();
+
+        Box::new(self.datagram.send(request).and_then(move |response| {
+            if !response.truncated() || accept_truncated {
+                return future::Either::A(future::ok(response));
+            }
+
+            debug!("truncated response over UDP, sending the request over TCP");
+            future::Either::B(stream.send(stream_request).map(|mut response| {
+                response.set_tcp_fallback(true);
+                response
+            }))
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use futures::future;
+
+    use super::*;
+    use op::Message;
+
+    #[derive(Clone)]
+    struct TestHandle {
+        truncated: bool,
+        sends: Arc<AtomicUsize>,
+    }
+
+    impl TestHandle {
+        fn new(truncated: bool) -> Self {
+            TestHandle {
+                truncated,
+                sends: Arc::new(AtomicUsize::new(0)),
+            }
+        }
+    }
+
+    impl DnsHandle for TestHandle {
+        type Response = Box<Future<Item = DnsResponse, Error = ProtoError> + Send>;
+
+        fn send<R: Into<DnsRequest>>(&mut self, _: R) -> Self::Response {
+            self.sends.fetch_add(1, Ordering::SeqCst);
+
+            let mut message = Message::new();
+            message.set_truncated(self.truncated);
+            Box::new(future::ok(message.into()))
+        }
+    }
+
+    #[test]
+    fn test_not_truncated() {
+        let datagram = TestHandle::new(false);
+        let stream = TestHandle::new(false);
+        let mut handle = TcpFallbackHandle::new(datagram.clone(), stream.clone());
+
+        let response = handle.send(Message::new()).wait().unwrap();
+        assert!(!response.tcp_fallback());
+        assert_eq!(datagram.sends.load(Ordering::SeqCst), 1);
+        assert_eq!(stream.sends.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_truncated() {
+        let datagram = TestHandle::new(true);
+        let stream = TestHandle::new(false);
+        let mut handle = TcpFallbackHandle::new(datagram.clone(), stream.clone());
+
+        let response = handle.send(Message::new()).wait().unwrap();
+        assert!(response.tcp_fallback());
+        assert!(!response.truncated());
+        assert_eq!(datagram.sends.load(Ordering::SeqCst), 1);
+        assert_eq!(stream.sends.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_accept_truncated() {
+        let datagram = TestHandle::new(true);
+        let stream = TestHandle::new(false);
+        let mut handle =
+            TcpFallbackHandle::new(datagram.clone(), stream.clone()).accept_truncated(true);
+
+        let response = handle.send(Message::new()).wait().unwrap();
+        assert!(!response.tcp_fallback());
+        assert!(response.truncated());
+        assert_eq!(stream.sends.load(Ordering::SeqCst), 0);
+    }
+}
@@ -0,0 +1,81 @@
+#[macro_use]
+extern crate criterion;
+extern crate futures;
+extern crate trust_dns_resolver;
+
+use std::collections::hash_map::RandomState;
+use std::net::Ipv4Addr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use criterion::{black_box, Criterion};
+use futures::{future, Future};
+
+use trust_dns_resolver::lookup_state::CachingClient;
+use trust_dns_resolver::proto::error::ProtoError;
+use trust_dns_resolver::proto::hash::{FxBuildHasher, NameHasher};
+use trust_dns_resolver::proto::op::{Message, Query};
+use trust_dns_resolver::proto::rr::{Name, RData, Record, RecordType};
+use trust_dns_resolver::proto::xfer::{DnsHandle, DnsRequest, DnsRequestOptions, DnsResponse};
+
+/// Answers every query with an A record, counting the requests which weren't answered by the cache
+#[derive(Clone, Default)]
+struct CountingHandle(Arc<AtomicUsize>);
+
+impl DnsHandle for CountingHandle {
+    type Response = future::FutureResult<DnsResponse, ProtoError>;
+
+    fn send<R: Into<DnsRequest>>(&mut self, request: R) -> Self::Response {
+        self.0.fetch_add(1, Ordering::Relaxed);
+
+        let request = request.into();
+        let mut message = Message::new();
+        for query in request.queries() {
+            message.add_answer(Record::from_rdata(
+                query.name().clone(),
+                86400,
+                RData::A(Ipv4Addr::new(93, 184, 216, 34)),
+            ));
+        }
+
+        future::ok(message.into())
+    }
+}
+
+/// Looks up the same query, answered by the cache with its queries hashed by `hasher`
+fn bench_cache_hit_hasher(c: &mut Criterion, id: &str, hasher: Arc<dyn NameHasher>) {
+    let handle = CountingHandle::default();
+    let mut client = CachingClient::with_hasher(64, handle.clone(), hasher);
+    let query = Query::query(Name::from_ascii("www.example.com.").unwrap(), RecordType::A);
+
+    client
+        .lookup(query.clone(), DnsRequestOptions::default())
+        .wait()
+        .expect("lookup failed");
+
+    c.bench_function(id, move |b| {
+        b.iter(|| {
+            client
+                .lookup(black_box(query.clone()), DnsRequestOptions::default())
+                .wait()
+                .expect("lookup failed")
+        })
+    });
+
+    assert_eq!(
+        handle.0.load(Ordering::Relaxed),
+        1,
+        "the lookups missed the cache"
+    );
+}
+
+fn bench_cache_hit(c: &mut Criterion) {
+    bench_cache_hit_hasher(c, "cache_hit", Arc::new(RandomState::new()));
+}
+
+fn bench_cache_hit_fx(c: &mut Criterion) {
+    bench_cache_hit_hasher(c, "cache_hit_fx", Arc::new(FxBuildHasher));
+}
+
+criterion_group!(benches, bench_cache_hit, bench_cache_hit_fx);
+criterion_main!(benches);
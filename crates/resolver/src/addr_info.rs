@@ -0,0 +1,528 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! A `getaddrinfo()` style interface, for code migrating from the libc resolver
+//!
+//! ```rust,no_run
+//! # extern crate futures;
+//! # extern crate tokio;
+//! # extern crate trust_dns_resolver;
+//! # fn main() {
+//! use tokio::runtime::Runtime;
+//! use trust_dns_resolver::AsyncResolver;
+//! use trust_dns_resolver::addr_info::{AddrInfoHandle, AddrInfoHints, SocketType};
+//!
+//! let mut runtime = Runtime::new().unwrap();
+//! let (resolver, background) = AsyncResolver::from_system_conf().unwrap();
+//! runtime.spawn(background);
+//!
+//! let hints = AddrInfoHints {
+//!     socket_type: Some(SocketType::Stream),
+//!     ..AddrInfoHints::default()
+//! };
+//!
+//! let addr_infos = runtime
+//!     .block_on(resolver.getaddrinfo(Some("www.example.com."), Some("https"), hints))
+//!     .unwrap();
+//! for addr_info in addr_infos {
+//!     println!("{}", addr_info.socket_addr());
+//! }
+//! # }
+//! ```
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::Path;
+
+use futures::{future, Future, Poll};
+
+use proto::rr::rdata::SRV;
+use proto::rr::Name;
+
+use async_resolver::AsyncResolver;
+use error::*;
+use runtime::RuntimeProvider;
+
+lazy_static! {
+    static ref SERVICES: Services = Services::new();
+}
+
+/// The address family of the results, `AF_INET` or `AF_INET6`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AddressFamily {
+    /// IPv4 addresses
+    Inet,
+    /// IPv6 addresses
+    Inet6,
+}
+
+/// The type of socket the results are for, `SOCK_STREAM` or `SOCK_DGRAM`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SocketType {
+    /// TCP sockets
+    Stream,
+    /// UDP sockets
+    Datagram,
+}
+
+impl SocketType {
+    /// The protocol of the socket type, as used in the services file and SRV names
+    pub fn protocol(self) -> &'static str {
+        match self {
+            SocketType::Stream => "tcp",
+            SocketType::Datagram => "udp",
+        }
+    }
+}
+
+/// Restricts the results of `getaddrinfo`, like the `hints` argument of the libc function
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AddrInfoHints {
+    /// Only return addresses of this family, `None` for both (`AF_UNSPEC`)
+    pub family: Option<AddressFamily>,
+    /// Only return results for this socket type, `None` for both
+    pub socket_type: Option<SocketType>,
+    /// Without a host, return the unspecified addresses for binding rather than loopback (`AI_PASSIVE`)
+    pub passive: bool,
+    /// Look up the SRV records of named services, `_service._protocol.host`, before using the port
+    ///  of the services file. SRV targets are ordered by priority, then by highest weight.
+    pub use_srv: bool,
+}
+
+/// An address to connect or bind to, the result of `getaddrinfo`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct AddrInfo {
+    socket_addr: SocketAddr,
+    socket_type: SocketType,
+}
+
+impl AddrInfo {
+    /// The address and port
+    pub fn socket_addr(&self) -> SocketAddr {
+        self.socket_addr
+    }
+
+    /// The type of socket for the address
+    pub fn socket_type(&self) -> SocketType {
+        self.socket_type
+    }
+}
+
+/// An extension for the Resolver for `getaddrinfo()` style lookups
+pub trait AddrInfoHandle {
+    /// Returns the addresses of `host` with the port of `service`, for each socket type
+    ///
+    /// Like libc, results are ordered by address, then by socket type.
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - a name or IP address, without a host the loopback or unspecified (see
+    ///   `AddrInfoHints::passive`) addresses are returned
+    /// * `service` - a port number or a name of the services file, e.g. `/etc/services`, without a
+    ///   service the port is 0
+    /// * `hints` - restrictions of the results
+    fn getaddrinfo(
+        &self,
+        host: Option<&str>,
+        service: Option<&str>,
+        hints: AddrInfoHints,
+    ) -> AddrInfoFuture;
+}
+
+impl<R: RuntimeProvider> AddrInfoHandle for AsyncResolver<R> {
+    fn getaddrinfo(
+        &self,
+        host: Option<&str>,
+        service: Option<&str>,
+        hints: AddrInfoHints,
+    ) -> AddrInfoFuture {
+        let ports = match service_ports(service, hints.socket_type, &SERVICES) {
+            Ok(ports) => ports,
+            Err(err) => return AddrInfoFuture(Box::new(future::err(err))),
+        };
+
+        let host = match host {
+            Some(host) => host,
+            None => {
+                let addr_infos = addr_infos(local_addrs(hints.passive), &ports, hints.family);
+                return AddrInfoFuture(Box::new(future::result(non_empty(addr_infos))));
+            }
+        };
+
+        let family = hints.family;
+        let addr_infos: Box<Future<Item = Vec<AddrInfo>, Error = ResolveError> + Send> =
+            match service {
+                Some(service) if hints.use_srv && service.parse::<u16>().is_err() => {
+                    let lookups = ports
+                        .into_iter()
+                        .map(|port| srv_addr_infos(self, host, service, port, family))
+                        .collect::<Vec<_>>();
+
+                    Box::new(future::join_all(lookups).map(|addr_infos| {
+                        addr_infos.into_iter().flat_map(Vec::into_iter).collect()
+                    }))
+                }
+                _ => Box::new(
+                    self.lookup_ip(host)
+                        .map(move |lookup_ip| addr_infos(lookup_ip.iter(), &ports, family)),
+                ),
+            };
+
+        AddrInfoFuture(Box::new(addr_infos.and_then(non_empty)))
+    }
+}
+
+/// A future of the results of `AddrInfoHandle::getaddrinfo`
+pub struct AddrInfoFuture(Box<Future<Item = Vec<AddrInfo>, Error = ResolveError> + Send>);
+
+impl Future for AddrInfoFuture {
+    type Item = Vec<AddrInfo>;
+    type Error = ResolveError;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        self.0.poll()
+    }
+}
+
+/// The ports of the named services of the system services file, e.g. `/etc/services`
+#[derive(Debug, Default)]
+pub struct Services {
+    /// (name, protocol) -> port map, including the aliases of the services
+    by_name: HashMap<(String, SocketType), u16>,
+}
+
+impl Services {
+    /// Creates a new configuration from the system services file,
+    /// only works for Windows and Unix-like OSes,
+    /// will return empty configuration on others
+    pub fn new() -> Self {
+        read_services_conf(services_path()).unwrap_or_default()
+    }
+
+    /// Returns the port of the service for the protocol of the socket type
+    pub fn port(&self, name: &str, socket_type: SocketType) -> Option<u16> {
+        self.by_name.get(&(name.to_string(), socket_type)).cloned()
+    }
+
+    /// Inserts the port of a service for the protocol of the socket type
+    pub fn insert(&mut self, name: String, socket_type: SocketType, port: u16) {
+        self.by_name.insert((name, socket_type), port);
+    }
+}
+
+#[cfg(unix)]
+fn services_path() -> &'static str {
+    "/etc/services"
+}
+
+#[cfg(windows)]
+fn services_path() -> std::path::PathBuf {
+    let system_root =
+        std::env::var_os("SystemRoot").expect("Environtment variable SystemRoot not found");
+    let system_root = Path::new(&system_root);
+    system_root.join("System32\\drivers\\etc\\services")
+}
+
+#[cfg(not(any(unix, windows)))]
+fn services_path() -> &'static str {
+    ""
+}
+
+/// parse configuration from `path`
+#[cfg(any(unix, windows))]
+pub fn read_services_conf<P: AsRef<Path>>(path: P) -> io::Result<Services> {
+    use std::fs::File;
+    use std::io::{BufRead, BufReader};
+
+    let mut services = Services::default();
+
+    // lines in the file have the form `name port/protocol alias1 alias2 ...`,
+    // anything after a `#` is a comment, protocols other than tcp and udp are ignored
+    let file = File::open(path)?;
+
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let mut fields = line.split_whitespace();
+        let name = fields.next();
+        let port_protocol = fields.next().map(|field| field.splitn(2, '/'));
+        let (name, port, protocol) = match (name, port_protocol) {
+            (Some(name), Some(mut port_protocol)) => (
+                name,
+                port_protocol
+                    .next()
+                    .and_then(|port| port.parse::<u16>().ok()),
+                port_protocol.next(),
+            ),
+            _ => continue,
+        };
+
+        let socket_type = match protocol {
+            Some("tcp") => SocketType::Stream,
+            Some("udp") => SocketType::Datagram,
+            _ => continue,
+        };
+        let port = match port {
+            Some(port) => port,
+            None => {
+                warn!("could not parse a port from services file: {}", line);
+                continue;
+            }
+        };
+
+        for name in Some(name).into_iter().chain(fields) {
+            services.insert(name.to_string(), socket_type, port);
+        }
+    }
+
+    Ok(services)
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn read_services_conf<P: AsRef<Path>>(_path: P) -> io::Result<Services> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "Only Windows or Unix-like services file is supported".to_string(),
+    ))
+}
+
+/// Returns the port of the service for each of the socket types of the hints
+fn service_ports(
+    service: Option<&str>,
+    socket_type: Option<SocketType>,
+    services: &Services,
+) -> ResolveResult<Vec<(SocketType, u16)>> {
+    let socket_types = match socket_type {
+        Some(socket_type) => vec![socket_type],
+        None => vec![SocketType::Stream, SocketType::Datagram],
+    };
+
+    let service = match service {
+        Some(service) => service,
+        None => return Ok(socket_types.into_iter().map(|t| (t, 0)).collect()),
+    };
+
+    if let Ok(port) = service.parse::<u16>() {
+        return Ok(socket_types.into_iter().map(|t| (t, port)).collect());
+    }
+
+    let ports = socket_types
+        .into_iter()
+        .filter_map(|t| services.port(service, t).map(|port| (t, port)))
+        .collect::<Vec<_>>();
+
+    if ports.is_empty() {
+        return Err(format!("unknown service: {}", service).into());
+    }
+
+    Ok(ports)
+}
+
+fn local_addrs(passive: bool) -> Vec<IpAddr> {
+    if passive {
+        vec![
+            Ipv4Addr::new(0, 0, 0, 0).into(),
+            Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0).into(),
+        ]
+    } else {
+        vec![
+            Ipv4Addr::new(127, 0, 0, 1).into(),
+            Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1).into(),
+        ]
+    }
+}
+
+fn addr_infos<I: IntoIterator<Item = IpAddr>>(
+    ips: I,
+    ports: &[(SocketType, u16)],
+    family: Option<AddressFamily>,
+) -> Vec<AddrInfo> {
+    ips.into_iter()
+        .filter(|ip| {
+            let ip_family = match *ip {
+                IpAddr::V4(..) => AddressFamily::Inet,
+                IpAddr::V6(..) => AddressFamily::Inet6,
+            };
+            family.is_none() || family == Some(ip_family)
+        })
+        .flat_map(|ip| {
+            ports.iter().map(move |&(socket_type, port)| AddrInfo {
+                socket_addr: SocketAddr::new(ip, port),
+                socket_type,
+            })
+        })
+        .collect()
+}
+
+/// Looks up the targets of the SRV records of the service, or the host if there are none
+fn srv_addr_infos<R: RuntimeProvider>(
+    resolver: &AsyncResolver<R>,
+    host: &str,
+    service: &str,
+    (socket_type, port): (SocketType, u16),
+    family: Option<AddressFamily>,
+) -> Box<Future<Item = Vec<AddrInfo>, Error = ResolveError> + Send> {
+    let name = format!("_{}._{}.{}", service, socket_type.protocol(), host);
+    let resolver = resolver.clone();
+    let host = host.to_string();
+
+    Box::new(resolver.lookup_srv(name).then(move |srv_lookup| {
+        let targets: Vec<(Name, u16)> = match srv_lookup {
+            Ok(srv_lookup) => {
+                let mut srvs = srv_lookup.iter().cloned().collect::<Vec<SRV>>();
+                srvs.sort_by_key(|srv| (srv.priority(), Reverse(srv.weight())));
+
+                // a target of `.` means the service is not available, https://tools.ietf.org/html/rfc2782
+                srvs.into_iter()
+                    .filter(|srv| !srv.target().is_root())
+                    .map(|srv| (srv.target().clone(), srv.port()))
+                    .collect()
+            }
+            // without SRV records the service is on the host, at the port of the services file
+            Err(err) => match *err.kind() {
+                ResolveErrorKind::NoRecordsFound { .. } => {
+                    let lookup = resolver.lookup_ip(host.as_str()).map(move |lookup_ip| {
+                        addr_infos(lookup_ip.iter(), &[(socket_type, port)], family)
+                    });
+                    return Box::new(lookup)
+                        as Box<Future<Item = Vec<AddrInfo>, Error = ResolveError> + Send>;
+                }
+                _ => return Box::new(future::err(err)),
+            },
+        };
+
+        // a failing target is skipped, the others may still be available
+        let lookups = targets.into_iter().map(move |(target, port)| {
+            resolver.lookup_ip(target).then(move |lookup_ip| {
+                Ok(lookup_ip
+                    .map(|lookup_ip| addr_infos(lookup_ip.iter(), &[(socket_type, port)], family))
+                    .unwrap_or_default())
+            })
+        });
+
+        Box::new(
+            future::join_all(lookups.collect::<Vec<_>>())
+                .map(|addr_infos| addr_infos.into_iter().flat_map(Vec::into_iter).collect()),
+        )
+    }))
+}
+
+fn non_empty(addr_infos: Vec<AddrInfo>) -> ResolveResult<Vec<AddrInfo>> {
+    if addr_infos.is_empty() {
+        Err("no addresses found for the hints".into())
+    } else {
+        Ok(addr_infos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+
+    use tokio::runtime::current_thread::Runtime;
+
+    use config::{ResolverConfig, ResolverOpts};
+
+    fn tests_dir() -> String {
+        let server_path = env::var("TDNS_SERVER_SRC_ROOT").unwrap_or_else(|_| ".".to_owned());
+        format! {"{}/../resolver/tests", server_path}
+    }
+
+    #[cfg(any(unix, windows))]
+    #[test]
+    fn test_read_services_conf() {
+        let path = format!("{}/services", tests_dir());
+        let services = read_services_conf(&path).unwrap();
+
+        assert_eq!(services.port("http", SocketType::Stream), Some(80));
+        assert_eq!(services.port("www", SocketType::Stream), Some(80));
+        assert_eq!(services.port("http", SocketType::Datagram), None);
+        assert_eq!(services.port("domain", SocketType::Datagram), Some(53));
+        assert_eq!(services.port("ddp-only", SocketType::Stream), None);
+        assert_eq!(services.port("broken", SocketType::Stream), None);
+    }
+
+    #[test]
+    fn test_service_ports() {
+        let mut services = Services::default();
+        services.insert("domain".to_string(), SocketType::Stream, 53);
+        services.insert("domain".to_string(), SocketType::Datagram, 53);
+        services.insert("http".to_string(), SocketType::Stream, 80);
+
+        assert_eq!(
+            service_ports(None, None, &services).unwrap(),
+            vec![(SocketType::Stream, 0), (SocketType::Datagram, 0)]
+        );
+        assert_eq!(
+            service_ports(Some("8080"), Some(SocketType::Stream), &services).unwrap(),
+            vec![(SocketType::Stream, 8080)]
+        );
+        assert_eq!(
+            service_ports(Some("http"), None, &services).unwrap(),
+            vec![(SocketType::Stream, 80)]
+        );
+        assert!(service_ports(Some("http"), Some(SocketType::Datagram), &services).is_err());
+        assert!(service_ports(Some("unknown"), None, &services).is_err());
+    }
+
+    #[test]
+    fn test_getaddrinfo() {
+        let mut io_loop = Runtime::new().unwrap();
+        let (resolver, bg) = AsyncResolver::new(ResolverConfig::default(), ResolverOpts::default());
+        io_loop.spawn(bg);
+
+        let addr_infos = io_loop
+            .block_on(resolver.getaddrinfo(Some("10.1.0.2"), Some("8080"), Default::default()))
+            .unwrap();
+        assert_eq!(
+            addr_infos,
+            vec![
+                AddrInfo {
+                    socket_addr: "10.1.0.2:8080".parse().unwrap(),
+                    socket_type: SocketType::Stream,
+                },
+                AddrInfo {
+                    socket_addr: "10.1.0.2:8080".parse().unwrap(),
+                    socket_type: SocketType::Datagram,
+                },
+            ]
+        );
+
+        let hints = AddrInfoHints {
+            family: Some(AddressFamily::Inet6),
+            socket_type: Some(SocketType::Stream),
+            passive: true,
+            use_srv: false,
+        };
+        let addr_infos = io_loop
+            .block_on(resolver.getaddrinfo(None, Some("443"), hints))
+            .unwrap();
+        assert_eq!(
+            addr_infos,
+            vec![AddrInfo {
+                socket_addr: "[::]:443".parse().unwrap(),
+                socket_type: SocketType::Stream,
+            }]
+        );
+
+        let hints = AddrInfoHints {
+            family: Some(AddressFamily::Inet6),
+            ..AddrInfoHints::default()
+        };
+        assert!(io_loop
+            .block_on(resolver.getaddrinfo(Some("10.1.0.2"), None, hints))
+            .is_err());
+    }
+}
@@ -0,0 +1,364 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Destination address selection, [RFC 6724](https://tools.ietf.org/html/rfc6724)
+//!
+//! The addresses of a `LookupIp` are sorted such that connections should first be attempted to
+//!  the addresses most likely to be reachable from the local addresses, see
+//!  `ResolverConfig::set_address_policy`.
+
+use std::cmp::Ordering;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::sync::Arc;
+
+use proto::rr::{RData, Record};
+
+use lookup::Lookup;
+
+/// An entry of the policy table, [RFC 6724 Section 2.1](https://tools.ietf.org/html/rfc6724#section-2.1)
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde-config", derive(Serialize, Deserialize))]
+pub struct PolicyEntry {
+    /// The prefix matched by the entry, IPv4 addresses are matched as IPv4-mapped addresses
+    pub prefix: Ipv6Addr,
+    /// The length of the prefix in bits
+    pub prefix_len: u8,
+    /// Addresses with a higher precedence are preferred
+    pub precedence: u8,
+    /// Destinations with the same label as the source address are preferred
+    pub label: u8,
+}
+
+impl PolicyEntry {
+    fn matches(&self, addr: &Ipv6Addr) -> bool {
+        common_prefix_len(&self.prefix, addr) >= u32::from(self.prefix_len)
+    }
+}
+
+/// The policy table used to sort the addresses, the longest matching prefix of an address is used
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde-config", derive(Serialize, Deserialize))]
+pub struct PolicyTable(Vec<PolicyEntry>);
+
+impl PolicyTable {
+    /// Creates a policy table with the entries, the table should have an entry for `::/0`
+    pub fn new(entries: Vec<PolicyEntry>) -> Self {
+        PolicyTable(entries)
+    }
+
+    /// The entries of the table
+    pub fn entries(&self) -> &[PolicyEntry] {
+        &self.0
+    }
+
+    /// Returns the precedence and label of the address, 0 for both if no entry matches it
+    pub fn lookup(&self, addr: IpAddr) -> (u8, u8) {
+        let addr = to_ipv6(addr);
+        self.0
+            .iter()
+            .filter(|entry| entry.matches(&addr))
+            .max_by_key(|entry| entry.prefix_len)
+            .map_or((0, 0), |entry| (entry.precedence, entry.label))
+    }
+}
+
+impl Default for PolicyTable {
+    /// The default policy table of RFC 6724
+    fn default() -> Self {
+        let entry = |prefix: [u16; 8], prefix_len, precedence, label| PolicyEntry {
+            prefix: Ipv6Addr::new(
+                prefix[0], prefix[1], prefix[2], prefix[3], prefix[4], prefix[5], prefix[6],
+                prefix[7],
+            ),
+            prefix_len,
+            precedence,
+            label,
+        };
+
+        PolicyTable(vec![
+            entry([0, 0, 0, 0, 0, 0, 0, 1], 128, 50, 0),
+            entry([0, 0, 0, 0, 0, 0, 0, 0], 0, 40, 1),
+            entry([0, 0, 0, 0, 0, 0xffff, 0, 0], 96, 35, 4),
+            entry([0x2002, 0, 0, 0, 0, 0, 0, 0], 16, 30, 2),
+            entry([0x2001, 0, 0, 0, 0, 0, 0, 0], 32, 5, 5),
+            entry([0xfc00, 0, 0, 0, 0, 0, 0, 0], 7, 3, 13),
+            entry([0, 0, 0, 0, 0, 0, 0, 0], 96, 1, 3),
+            entry([0xfec0, 0, 0, 0, 0, 0, 0, 0], 10, 1, 11),
+            entry([0x3ffe, 0, 0, 0, 0, 0, 0, 0], 16, 1, 12),
+        ])
+    }
+}
+
+/// Returns the lookup with its addresses sorted, other records, e.g. CNAMEs, are kept first
+pub(crate) fn sort_lookup(lookup: &Lookup, policy: &PolicyTable) -> Lookup {
+    let (ips, mut records): (Vec<Record>, Vec<Record>) =
+        lookup
+            .record_iter()
+            .cloned()
+            .partition(|record| match *record.rdata() {
+                RData::A(..) | RData::AAAA(..) => true,
+                _ => false,
+            });
+
+    let ips = ips
+        .into_iter()
+        .filter_map(|record| {
+            let ip = record.rdata().to_ip_addr()?;
+            Some((record, ip, source_address(ip)))
+        })
+        .collect();
+    records.extend(
+        sort_with_sources(ips, policy)
+            .into_iter()
+            .map(|(record, _, _)| record),
+    );
+
+    lookup.with_records(Arc::new(records))
+}
+
+/// The address the system would use as the source to connect to `destination`
+///
+/// The UDP socket is only connected, no packets are sent.
+fn source_address(destination: IpAddr) -> Option<IpAddr> {
+    let bind: SocketAddr = match destination {
+        IpAddr::V4(..) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        IpAddr::V6(..) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+
+    let socket = UdpSocket::bind(bind).ok()?;
+    socket.connect((destination, 9)).ok()?;
+    socket.local_addr().ok().map(|addr| addr.ip())
+}
+
+/// Sorts the destinations with their source addresses, None if they are unreachable
+///
+/// The rules of [RFC 6724 Section 6](https://tools.ietf.org/html/rfc6724#section-6) are applied,
+///  except those requiring knowledge of the local addresses: deprecated addresses (3), home
+///  addresses (4) and native transport (7). As recommended, the longest matching prefix (9) is only
+///  applied to IPv6 addresses. Destinations which are equal by all rules keep their order.
+fn sort_with_sources<T>(
+    mut destinations: Vec<(T, IpAddr, Option<IpAddr>)>,
+    policy: &PolicyTable,
+) -> Vec<(T, IpAddr, Option<IpAddr>)> {
+    destinations.sort_by(|&(_, da, sa), &(_, db, sb)| compare(da, sa, db, sb, policy));
+    destinations
+}
+
+/// Returns Less if `da` is preferred over `db`
+fn compare(
+    da: IpAddr,
+    sa: Option<IpAddr>,
+    db: IpAddr,
+    sb: Option<IpAddr>,
+    policy: &PolicyTable,
+) -> Ordering {
+    // Rule 1: Avoid unusable destinations
+    let (sa, sb) = match (sa, sb) {
+        (Some(sa), Some(sb)) => (sa, sb),
+        (Some(..), None) => return Ordering::Less,
+        (None, Some(..)) => return Ordering::Greater,
+        (None, None) => return Ordering::Equal,
+    };
+
+    let (precedence_a, label_a) = policy.lookup(da);
+    let (precedence_b, label_b) = policy.lookup(db);
+
+    // Rule 2: Prefer matching scope
+    let matching_scope_a = scope(da) == scope(sa);
+    let matching_scope_b = scope(db) == scope(sb);
+    // Rule 5: Prefer matching label
+    let matching_label_a = label_a == policy.lookup(sa).1;
+    let matching_label_b = label_b == policy.lookup(sb).1;
+
+    matching_scope_b
+        .cmp(&matching_scope_a)
+        .then(matching_label_b.cmp(&matching_label_a))
+        // Rule 6: Prefer higher precedence
+        .then(precedence_b.cmp(&precedence_a))
+        // Rule 8: Prefer smaller scope
+        .then(scope(da).cmp(&scope(db)))
+        // Rule 9: Use longest matching prefix
+        .then_with(|| match (da, sa, db, sb) {
+            (IpAddr::V6(da), IpAddr::V6(sa), IpAddr::V6(db), IpAddr::V6(sb)) => {
+                // only up to the length of the prefix of the source, assumed to be a /64
+                let prefix_a = common_prefix_len(&da, &sa).min(64);
+                let prefix_b = common_prefix_len(&db, &sb).min(64);
+                prefix_b.cmp(&prefix_a)
+            }
+            _ => Ordering::Equal,
+        })
+}
+
+/// The scope of the address, [RFC 6724 Section 3.1](https://tools.ietf.org/html/rfc6724#section-3.1)
+fn scope(addr: IpAddr) -> u8 {
+    const LINK_LOCAL: u8 = 0x2;
+    const SITE_LOCAL: u8 = 0x5;
+    const GLOBAL: u8 = 0xe;
+
+    match addr {
+        IpAddr::V4(addr) => {
+            if addr.is_loopback() || addr.is_link_local() {
+                LINK_LOCAL
+            } else {
+                GLOBAL
+            }
+        }
+        IpAddr::V6(addr) => {
+            let segments = addr.segments();
+            if addr.is_multicast() {
+                (segments[0] & 0x000f) as u8
+            } else if addr.is_loopback() || segments[0] & 0xffc0 == 0xfe80 {
+                LINK_LOCAL
+            } else if segments[0] & 0xffc0 == 0xfec0 {
+                SITE_LOCAL
+            } else {
+                GLOBAL
+            }
+        }
+    }
+}
+
+fn to_ipv6(addr: IpAddr) -> Ipv6Addr {
+    match addr {
+        IpAddr::V4(addr) => addr.to_ipv6_mapped(),
+        IpAddr::V6(addr) => addr,
+    }
+}
+
+fn common_prefix_len(a: &Ipv6Addr, b: &Ipv6Addr) -> u32 {
+    let a = u128::from(*a);
+    let b = u128::from(*b);
+    (a ^ b).leading_zeros()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    fn sorted(destinations: &[(&str, Option<&str>)]) -> Vec<IpAddr> {
+        let destinations = destinations
+            .iter()
+            .map(|&(d, s)| ((), ip(d), s.map(ip)))
+            .collect();
+        sort_with_sources(destinations, &PolicyTable::default())
+            .into_iter()
+            .map(|(_, d, _)| d)
+            .collect()
+    }
+
+    #[test]
+    fn test_policy_lookup() {
+        let policy = PolicyTable::default();
+
+        assert_eq!(policy.lookup(ip("::1")), (50, 0));
+        assert_eq!(policy.lookup(ip("2001:db8::1")), (40, 1));
+        assert_eq!(policy.lookup(ip("192.0.2.1")), (35, 4));
+        assert_eq!(policy.lookup(ip("2002:c000:0201::1")), (30, 2));
+        assert_eq!(policy.lookup(ip("2001::1")), (5, 5));
+        assert_eq!(policy.lookup(ip("fd00::1")), (3, 13));
+    }
+
+    #[test]
+    fn test_scope() {
+        assert_eq!(scope(ip("127.0.0.1")), 2);
+        assert_eq!(scope(ip("169.254.1.1")), 2);
+        assert_eq!(scope(ip("192.0.2.1")), 14);
+        assert_eq!(scope(ip("fe80::1")), 2);
+        assert_eq!(scope(ip("fec0::1")), 5);
+        assert_eq!(scope(ip("2001:db8::1")), 14);
+        assert_eq!(scope(ip("ff05::1")), 5);
+    }
+
+    // the examples of RFC 6724 Section 10.2
+    #[test]
+    fn test_rfc_examples() {
+        // prefer matching scope
+        assert_eq!(
+            sorted(&[
+                ("2001:db8:1::1", Some("fe80::1")),
+                ("198.51.100.121", Some("198.51.100.117"))
+            ]),
+            vec![ip("198.51.100.121"), ip("2001:db8:1::1")]
+        );
+
+        // prefer higher precedence
+        assert_eq!(
+            sorted(&[
+                ("198.51.100.121", Some("198.51.100.117")),
+                ("2001:db8:1::1", Some("2001:db8:1::2"))
+            ]),
+            vec![ip("2001:db8:1::1"), ip("198.51.100.121")]
+        );
+
+        // prefer smaller scope
+        assert_eq!(
+            sorted(&[
+                ("2001:db8:1::1", Some("2001:db8:1::2")),
+                ("fe80::1", Some("fe80::2"))
+            ]),
+            vec![ip("fe80::1"), ip("2001:db8:1::1")]
+        );
+
+        // prefer matching label, over 6to4
+        assert_eq!(
+            sorted(&[
+                ("2002:c633:6401::1", Some("2002:c633:6401::2")),
+                ("2001:db8:1::1", Some("2002:c633:6401::2"))
+            ]),
+            vec![ip("2002:c633:6401::1"), ip("2001:db8:1::1")]
+        );
+
+        // use longest matching prefix
+        assert_eq!(
+            sorted(&[
+                ("2001:db8:1::1", Some("2001:db8:3::2")),
+                ("2001:db8:3::1", Some("2001:db8:3::2"))
+            ]),
+            vec![ip("2001:db8:3::1"), ip("2001:db8:1::1")]
+        );
+    }
+
+    #[test]
+    fn test_unusable_last() {
+        assert_eq!(
+            sorted(&[
+                ("2001:db8:1::1", None),
+                ("192.0.2.1", Some("192.0.2.100")),
+                ("192.0.2.2", Some("192.0.2.100"))
+            ]),
+            vec![ip("192.0.2.1"), ip("192.0.2.2"), ip("2001:db8:1::1")]
+        );
+    }
+
+    #[test]
+    fn test_sort_lookup_keeps_other_records() {
+        use proto::op::Query;
+        use proto::rr::{Name, RecordType};
+
+        let name = Name::from_ascii("www.example.com.").unwrap();
+        let records = vec![
+            Record::from_rdata(name.clone(), 300, RData::A(Ipv4Addr::new(192, 0, 2, 1))),
+            Record::from_rdata(name.clone(), 300, RData::CNAME(name.clone())),
+        ];
+        let lookup = Lookup::new_with_max_ttl(Query::query(name, RecordType::A), Arc::new(records));
+
+        let sorted = sort_lookup(&lookup, &PolicyTable::default());
+        assert_eq!(sorted.query(), lookup.query());
+        assert_eq!(sorted.valid_until(), lookup.valid_until());
+        assert_eq!(
+            sorted.iter().cloned().collect::<Vec<_>>(),
+            vec![
+                RData::CNAME(lookup.query().name().clone()),
+                RData::A(Ipv4Addr::new(192, 0, 2, 1)),
+            ]
+        );
+    }
+}
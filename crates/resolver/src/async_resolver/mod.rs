@@ -6,25 +6,33 @@
 // copied, modified, or distributed except according to those terms.
 
 //! Structs for creating and using a AsyncResolver
+use std::collections::hash_map::RandomState;
 use std::fmt;
 use std::net::IpAddr;
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 use futures::{
     self, future,
     sync::{mpsc, oneshot},
-    Future, Poll,
+    Async, Future, Poll,
 };
-use proto::error::ProtoResult;
+use proto::error::{ProtoError, ProtoResult};
+use proto::hash::NameHasher;
 use proto::rr::domain::TryParseIp;
 use proto::rr::{IntoName, Name, RData, RecordType};
 use proto::xfer::DnsRequestOptions;
+use tokio_timer::clock;
 
 use config::{ResolverConfig, ResolverOpts};
-use dns_lru::{self, DnsLru};
+use dns_lru::{self, CacheEntry, CacheStats, DnsLru};
 use error::*;
-use lookup::{self, LookupFuture};
+use events::ResolverEvents;
+use lookup::{self, LookupEither, LookupFuture};
 use lookup_ip::LookupIpFuture;
+use name_server::ConnectionProvider;
+use runtime::{RuntimeProvider, TokioRuntime};
+use validation_exceptions::ValidationStats;
 
 mod background;
 
@@ -55,9 +63,15 @@ mod background;
 /// continue to do so as long as there are still any [`AsyncResolver`] handle
 /// linked to it. When all of its [`AsyncResolver`]s have been dropped, the
 /// background future will finish.
+///
+/// Dropping a lookup future cancels the lookup, its outstanding requests and retries are dropped
+/// along with it. See `Background::with_deadline` for lookups which must complete by a deadline.
+///
+/// The connections and timers are those of the `RuntimeProvider`, by default the `TokioRuntime`.
 #[derive(Clone)]
-pub struct AsyncResolver {
-    request_tx: mpsc::UnboundedSender<Request>,
+pub struct AsyncResolver<R: RuntimeProvider = TokioRuntime> {
+    request_tx: mpsc::UnboundedSender<Request<R>>,
+    lru: Arc<Mutex<DnsLru>>,
 }
 
 /// A future that represents sending a request to a background task,
@@ -72,11 +86,25 @@ where
     inner: BgInner<G::Item, F, G>,
 }
 
+/// A lookup which fails with `ResolveErrorKind::Timeout` if it's not complete by its deadline
+///
+/// The lookup is canceled at the deadline, or whenever this future is dropped.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless polled"]
+pub struct LookupDeadline<F, D = <TokioRuntime as RuntimeProvider>::Delay> {
+    lookup: F,
+    deadline: D,
+}
+
+/// The client of the lookups of an `AsyncResolver` with the `RuntimeProvider`
+pub type RuntimeClient<R = TokioRuntime> = LookupEither<<R as ConnectionProvider>::ConnHandle, R>;
+
 /// Future returned by `LookupIp` requests to the background task.
-pub type BackgroundLookupIp = Background<LookupIpFuture>;
+pub type BackgroundLookupIp<R = TokioRuntime> = Background<LookupIpFuture<RuntimeClient<R>>>;
 
 /// Future returned by lookup requests to the background task.
-pub type BackgroundLookup<F = LookupFuture> = Background<LookupFuture, F>;
+pub type BackgroundLookup<F = LookupFuture, R = TokioRuntime> =
+    Background<LookupFuture<RuntimeClient<R>>, F>;
 
 /// Type alias for the complex inner part of a `Background` future.
 type BgInner<T, F, G> = future::Either<BgSend<F, G>, future::FutureResult<T, ResolveError>>;
@@ -91,24 +119,29 @@ type BgSend<F, G> = futures::AndThen<
 
 /// Used by `AsyncResolver` for communicating with the background resolver task.
 #[allow(clippy::large_enum_variant)]
-enum Request {
+enum Request<R: RuntimeProvider> {
     /// Requests a lookup of the specified `RecordType`.
     Lookup {
         name: Name,
         record_type: RecordType,
         options: DnsRequestOptions,
-        tx: oneshot::Sender<LookupFuture>,
+        tx: oneshot::Sender<LookupFuture<RuntimeClient<R>>>,
     },
     /// Requests an IP lookup for a name or IP address.
     Ip {
         maybe_name: ProtoResult<Name>,
         maybe_ip: Option<RData>,
-        tx: oneshot::Sender<LookupIpFuture>,
+        tx: oneshot::Sender<LookupIpFuture<RuntimeClient<R>>>,
+    },
+    /// Replaces the configuration of the lookups
+    Reload {
+        config: ResolverConfig,
+        options: ResolverOpts,
     },
 }
 
 macro_rules! lookup_fn {
-    ($p:ident, $f:ty, $r:path) => {
+    ($p:ident, $f:ident, $r:path) => {
 /// Performs a lookup for the associated type.
 ///
 /// *hint* queries that end with a '.' are fully qualified names and are cheaper lookups
@@ -116,7 +149,7 @@ macro_rules! lookup_fn {
 /// # Arguments
 ///
 /// * `query` - a string which parses to a domain name, failure to parse will return an error
-pub fn $p<N: IntoName>(&self, query: N) -> BackgroundLookup<$f> {
+pub fn $p<N: IntoName>(&self, query: N) -> BackgroundLookup<lookup::$f<RuntimeClient<R>>, R> {
     let name = match query.into_name() {
         Ok(name) => name,
         Err(err) => {
@@ -127,13 +160,13 @@ pub fn $p<N: IntoName>(&self, query: N) -> BackgroundLookup<$f> {
     self.inner_lookup(name, $r, DnsRequestOptions::default(),)
 }
     };
-    ($p:ident, $f:ty, $r:path, $t:ty) => {
+    ($p:ident, $f:ident, $r:path, $t:ty) => {
 /// Performs a lookup for the associated type.
 ///
 /// # Arguments
 ///
 /// * `query` - a type which can be converted to `Name` via `From`.
-pub fn $p(&self, query: $t) -> BackgroundLookup<$f> {
+pub fn $p(&self, query: $t) -> BackgroundLookup<lookup::$f<RuntimeClient<R>>, R> {
     let name = Name::from(query);
     self.inner_lookup(name, $r, DnsRequestOptions::default(),)
 }
@@ -158,10 +191,66 @@ impl AsyncResolver {
         config: ResolverConfig,
         options: ResolverOpts,
     ) -> (Self, impl Future<Item = (), Error = ()>) {
-        let lru = DnsLru::new(options.cache_size, dns_lru::TtlConfig::from_opts(&options));
+        Self::with_runtime(config, options, TokioRuntime)
+    }
+
+    /// Constructs a new Resolver with the system configuration.
+    ///
+    /// This will use `/etc/resolv.conf` on Unix OSes and the registry on Windows.
+    #[cfg(any(unix, target_os = "windows"))]
+    pub fn from_system_conf() -> ResolveResult<(Self, impl Future<Item = (), Error = ()>)> {
+        let (config, options) = super::system_conf::read_system_conf()?;
+        Ok(Self::new(config, options))
+    }
+}
+
+impl<R: RuntimeProvider> AsyncResolver<R> {
+    /// Construct a new `AsyncResolver` with the provided configuration, on the `runtime`.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - configuration, name_servers, etc. for the Resolver
+    /// * `options` - basic lookup options for the resolver
+    /// * `runtime` - makes the connections to the name servers, and provides the timers
+    ///
+    /// # Returns
+    ///
+    /// A tuple containing the new `AsyncResolver` and a future that drives the
+    /// background task that runs resolutions for the `AsyncResolver`. The
+    /// background future, and the lookup futures, must be run on the `runtime`.
+    pub fn with_runtime(
+        config: ResolverConfig,
+        options: ResolverOpts,
+        runtime: R,
+    ) -> (Self, impl Future<Item = (), Error = ()>) {
+        Self::with_cache_hasher(config, options, runtime, Arc::new(RandomState::new()))
+    }
+
+    /// Construct a new `AsyncResolver` on the `runtime`, with the hasher of the queries of its cache
+    ///
+    /// The queries are hashed with the keyed SipHash of `RandomState` by `with_runtime`, the names
+    ///  looked up may be chosen by peers of the application. An unkeyed hasher, e.g.
+    ///  `proto::hash::FxBuildHasher`, is faster but only for names which are trusted, see
+    ///  `proto::hash`.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - configuration, name_servers, etc. for the Resolver
+    /// * `options` - basic lookup options for the resolver
+    /// * `runtime` - makes the connections to the name servers, and provides the timers
+    /// * `hasher` - hashes the queries of the cache
+    pub fn with_cache_hasher(
+        config: ResolverConfig,
+        options: ResolverOpts,
+        runtime: R,
+        hasher: Arc<dyn NameHasher>,
+    ) -> (Self, impl Future<Item = (), Error = ()>) {
+        let lru = DnsLru::new(options.cache_size, dns_lru::TtlConfig::new(&config, &options))
+            .with_max_bytes(options.cache_max_bytes)
+            .with_hasher(hasher);
         let lru = Arc::new(Mutex::new(lru));
 
-        Self::with_cache(config, options, lru)
+        Self::with_cache(config, options, lru, runtime)
     }
 
     /// Construct a new `AsyncResolver` with the associated Client and configuration.
@@ -171,6 +260,7 @@ impl AsyncResolver {
     /// * `config` - configuration, name_servers, etc. for the Resolver
     /// * `options` - basic lookup options for the resolver
     /// * `lru` - the cache to be used with the resolver
+    /// * `runtime` - makes the connections to the name servers
     ///
     /// # Returns
     ///
@@ -182,22 +272,14 @@ impl AsyncResolver {
         config: ResolverConfig,
         options: ResolverOpts,
         lru: Arc<Mutex<DnsLru>>,
+        runtime: R,
     ) -> (Self, impl Future<Item = (), Error = ()>) {
         let (request_tx, request_rx) = mpsc::unbounded();
-        let background = background::task(config, options, lru, request_rx);
-        let handle = Self { request_tx };
+        let background = background::task(config, options, Arc::clone(&lru), runtime, request_rx);
+        let handle = Self { request_tx, lru };
         (handle, background)
     }
 
-    /// Constructs a new Resolver with the system configuration.
-    ///
-    /// This will use `/etc/resolv.conf` on Unix OSes and the registry on Windows.
-    #[cfg(any(unix, target_os = "windows"))]
-    pub fn from_system_conf() -> ResolveResult<(Self, impl Future<Item = (), Error = ()>)> {
-        let (config, options) = super::system_conf::read_system_conf()?;
-        Ok(Self::new(config, options))
-    }
-
     /// Generic lookup for any RecordType
     ///
     /// *WARNING* this interface may change in the future, see if one of the specializations would be better.
@@ -210,7 +292,11 @@ impl AsyncResolver {
     /// # Returns
     ///
     //  A future for the returned Lookup RData
-    pub fn lookup<N: IntoName>(&self, name: N, record_type: RecordType) -> BackgroundLookup {
+    pub fn lookup<N: IntoName>(
+        &self,
+        name: N,
+        record_type: RecordType,
+    ) -> BackgroundLookup<LookupFuture<RuntimeClient<R>>, R> {
         let name = match name.into_name() {
             Ok(name) => name,
             Err(err) => return err.into(),
@@ -219,19 +305,115 @@ impl AsyncResolver {
         self.inner_lookup(name, record_type, DnsRequestOptions::default())
     }
 
+    /// Generic lookup for any RecordType, with the EDNS and other options of the queries
+    ///
+    /// Responses are cached by query, a cached response may have been received with other options.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - name of the record to lookup, if name is not a valid domain name, an error will be returned
+    /// * `record_type` - type of record to lookup, all RecordData responses will be filtered to this type
+    /// * `options` - options of the queries, e.g. `DnsRequestOptions::default().with_max_payload(4096)`
+    pub fn lookup_with_options<N: IntoName>(
+        &self,
+        name: N,
+        record_type: RecordType,
+        options: DnsRequestOptions,
+    ) -> BackgroundLookup<LookupFuture<RuntimeClient<R>>, R> {
+        let name = match name.into_name() {
+            Ok(name) => name,
+            Err(err) => return err.into(),
+        };
+
+        self.inner_lookup(name, record_type, options)
+    }
+
     fn oneshot_canceled(_: oneshot::Canceled) -> ResolveError {
         ResolveErrorKind::Message("oneshot canceled unexpectedly, this is a bug").into()
     }
 
+    /// A snapshot of the entries of the cache, for debugging
+    ///
+    /// The entries which expired are not returned, the others are returned from the least to the
+    ///  most recently used, with the time they are still cached for.
+    pub fn cache_entries(&self) -> Vec<CacheEntry> {
+        self.lru
+            .lock()
+            .expect("cache poisoned")
+            .entries(clock::now())
+    }
+
+    /// The statistics of the cache, its entries and their estimated memory, and the entries
+    ///  evicted so far, see `ResolverOpts::cache_max_bytes`
+    pub fn cache_stats(&self) -> CacheStats {
+        self.lru.lock().expect("cache poisoned").stats()
+    }
+
+    /// The queries which were not validated so far, for the exceptions of
+    ///  `ResolverConfig::add_validation_exception`
+    pub fn validation_stats(&self) -> ValidationStats {
+        self.lru.lock().expect("cache poisoned").validation_stats()
+    }
+
+    /// Removes the cached answers for the names under the suffix, e.g. after they were changed
+    ///
+    /// # Arguments
+    ///
+    /// * `suffix` - the names to flush, `Name::root()` flushes all the names
+    /// * `record_type` - if specified, only the answers for this type are flushed
+    ///
+    /// # Returns
+    ///
+    /// The number of answers removed from the cache
+    pub fn flush_cache(&self, suffix: &Name, record_type: Option<RecordType>) -> usize {
+        self.lru
+            .lock()
+            .expect("cache poisoned")
+            .flush(suffix, record_type)
+    }
+
+    /// Replaces the configuration of the resolver, e.g. after the system configuration changed
+    ///
+    /// The lookups sent after this use the new name servers and options, the lookups in progress
+    ///  complete with the previous ones. The cache and its entries are kept, only its TTLs and
+    ///  `ResolverOpts::cache_max_bytes` are updated, the `cache_size` is that of the
+    ///  configuration the resolver was created with. `ResolverEvent::ConfigReload` is sent once
+    ///  the configuration is replaced.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - configuration, name_servers, etc. for the Resolver
+    /// * `options` - basic lookup options for the resolver
+    pub fn reload(&self, config: ResolverConfig, options: ResolverOpts) {
+        if self
+            .request_tx
+            .unbounded_send(Request::Reload { config, options })
+            .is_err()
+        {
+            warn!("failed to reload the resolver, its background task is gone");
+        }
+    }
+
+    /// Returns a stream of the events of the resolver, e.g. name servers which are down, from now on
+    ///
+    /// Any number of streams may be subscribed, see `ResolverEvent` for the events.
+    pub fn events(&self) -> ResolverEvents {
+        self.lru
+            .lock()
+            .expect("cache poisoned")
+            .events()
+            .subscribe()
+    }
+
     pub(crate) fn inner_lookup<F>(
         &self,
         name: Name,
         record_type: RecordType,
         options: DnsRequestOptions,
-    ) -> BackgroundLookup<F>
+    ) -> BackgroundLookup<F, R>
     where
         F: Future<Error = ResolveError>,
-        F: From<LookupFuture>,
+        F: From<LookupFuture<RuntimeClient<R>>>,
     {
         let (tx, rx) = oneshot::channel();
         let request = Request::Lookup {
@@ -243,7 +425,7 @@ impl AsyncResolver {
         if self.request_tx.unbounded_send(request).is_err() {
             return ResolveErrorKind::Message("background resolver gone, this is a bug").into();
         }
-        let f: BgSend<LookupFuture, F> = rx
+        let f: BgSend<LookupFuture<RuntimeClient<R>>, F> = rx
             .map_err(Self::oneshot_canceled as fn(oneshot::Canceled) -> ResolveError)
             .and_then(F::from);
         BackgroundLookup::from(f)
@@ -255,7 +437,7 @@ impl AsyncResolver {
     ///
     /// # Arguments
     /// * `host` - string hostname, if this is an invalid hostname, an error will be returned.
-    pub fn lookup_ip<N: IntoName + TryParseIp>(&self, host: N) -> BackgroundLookupIp {
+    pub fn lookup_ip<N: IntoName + TryParseIp>(&self, host: N) -> BackgroundLookupIp<R> {
         let (tx, rx) = oneshot::channel();
         let maybe_ip = host.try_parse_ip();
         let request = Request::Ip {
@@ -269,7 +451,7 @@ impl AsyncResolver {
             // probably be okay to just `expect` the unbounded send to be successful.
             return ResolveErrorKind::Message("background resolver gone, this is a bug").into();
         }
-        let f: BgSend<LookupIpFuture, LookupIpFuture> = rx
+        let f: BgSend<LookupIpFuture<RuntimeClient<R>>, LookupIpFuture<RuntimeClient<R>>> = rx
             .map_err(Self::oneshot_canceled as fn(oneshot::Canceled) -> ResolveError)
             .and_then(LookupIpFuture::from);
         BackgroundLookupIp::from(f)
@@ -290,13 +472,16 @@ impl AsyncResolver {
         service: &str,
         protocol: &str,
         name: &str,
-    ) -> BackgroundLookup<lookup::SrvLookupFuture> {
+    ) -> BackgroundLookup<lookup::SrvLookupFuture<RuntimeClient<R>>, R> {
         let name = format!("_{}._{}.{}", service, protocol, name);
         self.srv_lookup(name)
     }
 
     /// Lookup an SRV record.
-    pub fn lookup_srv<N: IntoName>(&self, name: N) -> BackgroundLookup<lookup::SrvLookupFuture> {
+    pub fn lookup_srv<N: IntoName>(
+        &self,
+        name: N,
+    ) -> BackgroundLookup<lookup::SrvLookupFuture<RuntimeClient<R>>, R> {
         let name = match name.into_name() {
             Ok(name) => name,
             Err(err) => return err.into(),
@@ -305,21 +490,16 @@ impl AsyncResolver {
         self.inner_lookup(name, RecordType::SRV, DnsRequestOptions::default())
     }
 
-    lookup_fn!(
-        reverse_lookup,
-        lookup::ReverseLookupFuture,
-        RecordType::PTR,
-        IpAddr
-    );
-    lookup_fn!(ipv4_lookup, lookup::Ipv4LookupFuture, RecordType::A);
-    lookup_fn!(ipv6_lookup, lookup::Ipv6LookupFuture, RecordType::AAAA);
-    lookup_fn!(mx_lookup, lookup::MxLookupFuture, RecordType::MX);
+    lookup_fn!(reverse_lookup, ReverseLookupFuture, RecordType::PTR, IpAddr);
+    lookup_fn!(ipv4_lookup, Ipv4LookupFuture, RecordType::A);
+    lookup_fn!(ipv6_lookup, Ipv6LookupFuture, RecordType::AAAA);
+    lookup_fn!(mx_lookup, MxLookupFuture, RecordType::MX);
     #[deprecated(note = "use lookup_srv instead, this interface is none ideal")]
-    lookup_fn!(srv_lookup, lookup::SrvLookupFuture, RecordType::SRV);
-    lookup_fn!(txt_lookup, lookup::TxtLookupFuture, RecordType::TXT);
+    lookup_fn!(srv_lookup, SrvLookupFuture, RecordType::SRV);
+    lookup_fn!(txt_lookup, TxtLookupFuture, RecordType::TXT);
 }
 
-impl fmt::Debug for AsyncResolver {
+impl<R: RuntimeProvider> fmt::Debug for AsyncResolver<R> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("AsyncResolver")
             // We probably don't want to print out the `fmt::Debug` output
@@ -332,6 +512,32 @@ impl fmt::Debug for AsyncResolver {
 
 // ===== impl Background =====
 
+impl<F, G> Background<F, G>
+where
+    F: Future<Error = ResolveError>,
+    G: Future<Error = ResolveError>,
+{
+    /// Fails the lookup with `ResolveErrorKind::Timeout` if it's not complete by `deadline`
+    ///
+    /// This bounds the lookup as a whole, i.e. all of the names searched and all of the retries,
+    ///  unlike the `timeout` of the `ResolverOpts` which is per request.
+    pub fn with_deadline(self, deadline: Instant) -> LookupDeadline<Self> {
+        self.with_delay(TokioRuntime.delay(deadline))
+    }
+
+    /// Fails the lookup with `ResolveErrorKind::Timeout` if it's not complete once `delay` is,
+    ///  e.g. with the `RuntimeProvider::delay` of another runtime
+    pub fn with_delay<D>(self, delay: D) -> LookupDeadline<Self, D>
+    where
+        D: Future<Item = (), Error = ProtoError>,
+    {
+        LookupDeadline {
+            lookup: self,
+            deadline: delay,
+        }
+    }
+}
+
 impl<F, G> Future for Background<F, G>
 where
     F: Future<Error = ResolveError>,
@@ -372,19 +578,46 @@ where
     }
 }
 
+// ===== impl LookupDeadline =====
+
+impl<F, D> Future for LookupDeadline<F, D>
+where
+    F: Future<Error = ResolveError>,
+    D: Future<Item = (), Error = ProtoError>,
+{
+    type Item = F::Item;
+    type Error = ResolveError;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        if let Async::Ready(lookup) = self.lookup.poll()? {
+            return Ok(Async::Ready(lookup));
+        }
+
+        match self.deadline.poll()? {
+            Async::Ready(()) => {
+                debug!("lookup deadline elapsed");
+                Err(ResolveErrorKind::Timeout.into())
+            }
+            Async::NotReady => Ok(Async::NotReady),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     extern crate env_logger;
     extern crate tokio;
 
     use failure::Fail;
+    use futures::Stream;
     use std::net::*;
     use std::str::FromStr;
 
     use self::tokio::runtime::current_thread::Runtime;
     use proto::xfer::DnsRequest;
 
-    use config::{LookupIpStrategy, NameServerConfig};
+    use config::{LookupIpStrategy, NameServerConfig, Protocol};
+    use events::ResolverEvent;
 
     use super::*;
 
@@ -409,6 +642,7 @@ mod tests {
         assert!(is_send_t::<DnsRequest>());
         assert!(is_send_t::<LookupIpFuture>());
         assert!(is_send_t::<LookupFuture>());
+        assert!(is_send_t::<LookupDeadline<BackgroundLookupIp>>());
     }
 
     fn lookup_test(config: ResolverConfig) {
@@ -480,6 +714,55 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_lookup_deadline() {
+        use std::time::Duration;
+
+        let mut io_loop = Runtime::new().unwrap();
+        // the background task isn't spawned, so the lookup is never complete
+        let (resolver, _bg) =
+            AsyncResolver::new(ResolverConfig::default(), ResolverOpts::default());
+
+        let deadline = Instant::now() + Duration::from_millis(50);
+        let lookup = resolver
+            .lookup_ip("www.example.com.")
+            .with_deadline(deadline);
+        let error = io_loop
+            .block_on(lookup)
+            .expect_err("lookup should have timed out");
+
+        assert_eq!(*error.kind(), ResolveErrorKind::Timeout);
+        assert!(Instant::now() >= deadline);
+    }
+
+    #[test]
+    fn test_with_runtime() {
+        let mut io_loop = Runtime::new().unwrap();
+        let (resolver, bg) = AsyncResolver::with_runtime(
+            ResolverConfig::default(),
+            ResolverOpts::default(),
+            TokioRuntime,
+        );
+        io_loop.spawn(bg);
+
+        let response = io_loop
+            .block_on(resolver.lookup_ip("127.0.0.1"))
+            .expect("failed to run lookup");
+        assert_eq!(
+            response.iter().collect::<Vec<_>>(),
+            vec![IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1))]
+        );
+
+        // the delay of another runtime's timer, which is already elapsed
+        let lookup = resolver
+            .lookup_ip("www.example.com.")
+            .with_delay(future::ok::<(), ProtoError>(()));
+        let error = io_loop
+            .block_on(lookup)
+            .expect_err("lookup should have timed out");
+        assert_eq!(*error.kind(), ResolveErrorKind::Timeout);
+    }
+
     #[test]
     fn test_ip_lookup_across_threads() {
         // Test ensuring that running the background task on a separate Tokio
@@ -847,6 +1130,43 @@ mod tests {
         assert!(response.iter().next().is_some());
     }
 
+    #[test]
+    fn test_reload() {
+        let mut io_loop = Runtime::new().unwrap();
+        let (resolver, bg) = AsyncResolver::new(ResolverConfig::default(), ResolverOpts::default());
+        io_loop.spawn(bg);
+        let events = resolver.events();
+
+        let name_server = SocketAddr::from(([127, 0, 0, 1], 5353));
+        let config = ResolverConfig::from_parts(
+            None,
+            vec![],
+            vec![NameServerConfig {
+                socket_addr: name_server,
+                protocol: Protocol::Udp,
+                tls_dns_name: None,
+            }],
+        );
+        resolver.reload(config, ResolverOpts::default());
+
+        let (event, _) = io_loop
+            .block_on(events.into_future())
+            .map_err(|_| "events failed")
+            .unwrap();
+        match event.expect("no event") {
+            ResolverEvent::ConfigReload { name_servers } => {
+                assert_eq!(name_servers, vec![name_server])
+            }
+            event => panic!("unexpected event: {:?}", event),
+        }
+
+        // the lookups are still answered after the reload
+        let response = io_loop
+            .block_on(resolver.lookup_ip("localhost"))
+            .expect("failed to run lookup");
+        assert!(response.iter().next().is_some());
+    }
+
     #[test]
     fn test_localhost_ipv4() {
         let mut io_loop = Runtime::new().unwrap();
@@ -11,6 +11,11 @@ use std::ops::{Deref, DerefMut};
 use std::time::Duration;
 
 use proto::rr::Name;
+use proto::tcp::TcpOptions;
+use proto::xfer::dns_request::MAX_PAYLOAD_LEN;
+use proto::xfer::RetryPolicy;
+
+use address_selection::PolicyTable;
 
 /// Configuration for the upstream nameservers to use for resolution
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -22,6 +27,15 @@ pub struct ResolverConfig {
     search: Vec<Name>,
     // nameservers to use for resolution.
     name_servers: NameServerConfigGroup,
+    // the policy to sort the addresses of LookupIp with, they are unsorted if None
+    #[cfg_attr(feature = "serde-config", serde(default))]
+    address_policy: Option<PolicyTable>,
+    // the TTLs of the cached answers for the names under each suffix
+    #[cfg_attr(feature = "serde-config", serde(default))]
+    ttl_overrides: Vec<TtlOverride>,
+    // the names under each suffix which are not validated, or queried without the DO bit
+    #[cfg_attr(feature = "serde-config", serde(default))]
+    validation_exceptions: Vec<ValidationException>,
 }
 
 impl ResolverConfig {
@@ -32,6 +46,9 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::new(),
+            address_policy: None,
+            ttl_overrides: vec![],
+            validation_exceptions: vec![],
         }
     }
 
@@ -46,6 +63,9 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::google(),
+            address_policy: None,
+            ttl_overrides: vec![],
+            validation_exceptions: vec![],
         }
     }
 
@@ -60,6 +80,9 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::cloudflare(),
+            address_policy: None,
+            ttl_overrides: vec![],
+            validation_exceptions: vec![],
         }
     }
 
@@ -75,6 +98,9 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::cloudflare_tls(),
+            address_policy: None,
+            ttl_overrides: vec![],
+            validation_exceptions: vec![],
         }
     }
 
@@ -90,6 +116,9 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::cloudflare_https(),
+            address_policy: None,
+            ttl_overrides: vec![],
+            validation_exceptions: vec![],
         }
     }
 
@@ -104,6 +133,9 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::quad9(),
+            address_policy: None,
+            ttl_overrides: vec![],
+            validation_exceptions: vec![],
         }
     }
 
@@ -119,6 +151,9 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::quad9_tls(),
+            address_policy: None,
+            ttl_overrides: vec![],
+            validation_exceptions: vec![],
         }
     }
 
@@ -138,6 +173,9 @@ impl ResolverConfig {
             domain,
             search,
             name_servers: name_servers.into(),
+            address_policy: None,
+            ttl_overrides: vec![],
+            validation_exceptions: vec![],
         }
     }
 
@@ -176,6 +214,82 @@ impl ResolverConfig {
     pub fn name_servers(&self) -> &[NameServerConfig] {
         &self.name_servers
     }
+
+    /// Returns the policy with which the addresses of `LookupIp` are sorted, if any
+    pub fn address_policy(&self) -> Option<&PolicyTable> {
+        self.address_policy.as_ref()
+    }
+
+    /// Sort the addresses of `LookupIp` with the policy, see `address_selection`
+    ///
+    /// By default the addresses are in the order of the responses, `PolicyTable::default()` sorts
+    ///  them as defined in [RFC 6724](https://tools.ietf.org/html/rfc6724), based on the source
+    ///  addresses the system would connect to them with.
+    pub fn set_address_policy(&mut self, policy: PolicyTable) {
+        self.address_policy = Some(policy);
+    }
+
+    /// Returns the TTL overrides of the cached answers
+    pub fn ttl_overrides(&self) -> &[TtlOverride] {
+        &self.ttl_overrides
+    }
+
+    /// Cache the answers for `suffix` and the names under it for `ttl` seconds, whatever the TTLs
+    ///  received, and the minimum and maximum TTLs of the `ResolverOpts`
+    ///
+    /// The override of the longest matching suffix is used, e.g. to re-resolve the names of an
+    ///  internal domain more often, or to cache the answers of an upstream with zero TTLs.
+    pub fn add_ttl_override(&mut self, mut suffix: Name, ttl: u32) {
+        suffix.set_fqdn(true);
+        self.ttl_overrides.push(TtlOverride { suffix, ttl });
+    }
+
+    /// Returns the exceptions to the DNSSEC validation of `ResolverOpts::validate`
+    pub fn validation_exceptions(&self) -> &[ValidationException] {
+        &self.validation_exceptions
+    }
+
+    /// Do not validate the answers for `suffix` and the names under it, as `policy`
+    ///
+    /// With `ResolverOpts::validate`, the answers for unsigned zones which are not delegated
+    ///  securely from the root, e.g. internal domains only known to the NameServers, fail to
+    ///  validate. The exception of the longest matching suffix is used, see `ValidationStats`
+    ///  for how often they are.
+    pub fn add_validation_exception(&mut self, mut suffix: Name, policy: ValidationPolicy) {
+        suffix.set_fqdn(true);
+        self.validation_exceptions
+            .push(ValidationException { suffix, policy });
+    }
+}
+
+/// The TTL of the cached answers for the names under a suffix, see `ResolverConfig::add_ttl_override`
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-config", derive(Serialize, Deserialize))]
+pub struct TtlOverride {
+    /// The suffix, the override is for the name itself and all the names under it
+    pub suffix: Name,
+    /// The TTL of the answers, in seconds
+    pub ttl: u32,
+}
+
+/// The DNSSEC validation of the names under a suffix, see `ResolverConfig::add_validation_exception`
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-config", derive(Serialize, Deserialize))]
+pub struct ValidationException {
+    /// The suffix, the exception is for the name itself and all the names under it
+    pub suffix: Name,
+    /// How the names are queried
+    pub policy: ValidationPolicy,
+}
+
+/// How the names of a `ValidationException` are queried, instead of being validated
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-config", derive(Serialize, Deserialize))]
+pub enum ValidationPolicy {
+    /// The DNSSEC OK bit is set, the DNSSEC records are returned but not validated
+    Insecure,
+    /// The DNSSEC OK bit is not set, e.g. for NameServers which fail on DNSSEC queries
+    NoDnssecOk,
 }
 
 impl Default for ResolverConfig {
@@ -204,6 +318,9 @@ pub enum Protocol {
     /// mDNS protocol for performing multicast lookups
     #[cfg(feature = "mdns")]
     Mdns,
+    /// LLMNR protocol for performing link-local multicast lookups of single label names
+    #[cfg(feature = "llmnr")]
+    Llmnr,
 }
 
 impl Protocol {
@@ -218,6 +335,8 @@ impl Protocol {
             Protocol::Https => false,
             #[cfg(feature = "mdns")]
             Protocol::Mdns => true,
+            #[cfg(feature = "llmnr")]
+            Protocol::Llmnr => true,
         }
     }
 
@@ -237,6 +356,8 @@ impl Protocol {
             Protocol::Https => true,
             #[cfg(feature = "mdns")]
             Protocol::Mdns => false,
+            #[cfg(feature = "llmnr")]
+            Protocol::Llmnr => false,
         }
     }
 }
@@ -486,6 +607,8 @@ pub enum LookupIpStrategy {
     Ipv6thenIpv4,
     /// Query for Ipv4 if that fails, query for Ipv6 (default)
     Ipv4thenIpv6,
+    /// Query for the families in the order, concurrency and timeouts of the `IpPreference`
+    Preference(IpPreference),
 }
 
 impl Default for LookupIpStrategy {
@@ -495,9 +618,117 @@ impl Default for LookupIpStrategy {
     }
 }
 
+/// A family of IP addresses, queried with A or AAAA records
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-config", derive(Serialize, Deserialize))]
+pub enum IpFamily {
+    /// IPv4 addresses, of A records
+    Ipv4,
+    /// IPv6 addresses, of AAAA records
+    Ipv6,
+}
+
+/// The order, concurrency and timeouts of the A and AAAA queries of
+///  `LookupIpStrategy::Preference`
+///
+/// The answer of a family is usable when it has at least the minimum number of addresses of the
+///  family. The other family is only queried when the answer of the `first` family is not
+///  usable, unless they are `concurrent`. The addresses of both families are returned when they
+///  are queried, those of the `first` family first, and the failure of one family is ignored
+///  when the other has addresses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-config", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde-config", serde(default))]
+pub struct IpPreference {
+    /// The family which is queried, and whose addresses are returned, first. The default is Ipv4
+    pub first: IpFamily,
+    /// Query both families at once, instead of the other family only when the answer of the
+    ///  first one is not usable. The default is false
+    pub concurrent: bool,
+    /// With `concurrent`, return the addresses of the first usable answer, of either family,
+    ///  without waiting for the other. The default is false
+    pub first_usable: bool,
+    /// The fewest addresses of a usable IPv4 answer, the default is 1
+    pub ipv4_min_answers: usize,
+    /// The fewest addresses of a usable IPv6 answer, the default is 1
+    pub ipv6_min_answers: usize,
+    /// The time after which the A query fails, independently of the AAAA query, the default is
+    ///  the `timeout` and `attempts` of the requests to the NameServers
+    pub ipv4_timeout: Option<Duration>,
+    /// The time after which the AAAA query fails, independently of the A query, the default is
+    ///  the `timeout` and `attempts` of the requests to the NameServers
+    pub ipv6_timeout: Option<Duration>,
+}
+
+impl IpPreference {
+    /// Queries for A records, and for AAAA records only when there are none, as `Ipv4thenIpv6`
+    pub fn ipv4_then_ipv6() -> Self {
+        IpPreference {
+            first: IpFamily::Ipv4,
+            concurrent: false,
+            first_usable: false,
+            ipv4_min_answers: 1,
+            ipv6_min_answers: 1,
+            ipv4_timeout: None,
+            ipv6_timeout: None,
+        }
+    }
+
+    /// Queries for AAAA records, and for A records only when there are none, as `Ipv6thenIpv4`
+    pub fn ipv6_then_ipv4() -> Self {
+        IpPreference {
+            first: IpFamily::Ipv6,
+            ..Self::ipv4_then_ipv6()
+        }
+    }
+
+    /// The fewest addresses of a usable answer of the family
+    pub fn min_answers(&self, family: IpFamily) -> usize {
+        match family {
+            IpFamily::Ipv4 => self.ipv4_min_answers,
+            IpFamily::Ipv6 => self.ipv6_min_answers,
+        }
+    }
+
+    /// The time after which the query of the family fails, if any
+    pub fn timeout(&self, family: IpFamily) -> Option<Duration> {
+        match family {
+            IpFamily::Ipv4 => self.ipv4_timeout,
+            IpFamily::Ipv6 => self.ipv6_timeout,
+        }
+    }
+}
+
+impl Default for IpPreference {
+    fn default() -> Self {
+        Self::ipv4_then_ipv6()
+    }
+}
+
+/// How truncated responses to requests over UDP are handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-config", derive(Serialize, Deserialize))]
+pub enum TruncationStrategy {
+    /// Send the request again over TCP (default)
+    SwitchToTcp,
+    /// The truncated response is a failure, the request is retried over UDP as configured in
+    ///  `ResolverOpts`, e.g. with `attempts` and `retry_delay`
+    Retry,
+    /// The truncated response is returned, with the records which fit in it
+    Accept,
+}
+
+impl Default for TruncationStrategy {
+    /// Returns SwitchToTcp as the default.
+    fn default() -> Self {
+        TruncationStrategy::SwitchToTcp
+    }
+}
+
 /// Configuration for the Resolver
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 #[cfg_attr(feature = "serde-config", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde-config", serde(default))]
 #[allow(dead_code)] // TODO: remove after all params are supported
 pub struct ResolverOpts {
     /// Sets the number of dots that must appear (unless it's a final dot representing the root)
@@ -505,10 +736,54 @@ pub struct ResolverOpts {
     ///  means that `www` would never be assumed to be a TLD, and would always be appended to either
     ///  the search
     pub ndots: usize,
+    /// Names with at least one dot, e.g. `www.example`, are also tried appended with the domain and
+    ///  the search list, as `ndots` allows. If false, they are only queried as is, which avoids the
+    ///  storms of queries of a high `ndots`, e.g. the `ndots:5` of Kubernetes pods. The default is true
+    pub search_dotted_names: bool,
+    /// The most candidate names which are queried for a name, the name as is is always one of them
+    ///  and the names of the search list are dropped from the last. The default is no limit
+    pub max_candidate_names: Option<usize>,
+    /// Names are always queried as is first, before the domain and the search list, regardless of
+    ///  `ndots`. The default is false
+    pub absolute_first: bool,
     /// Specify the timeout for a request. Defaults to 5 seconds
     pub timeout: Duration,
     /// Number of attempts before giving up. Defaults to 2
     pub attempts: usize,
+    /// Delay before the first retry of a failed request, the default is to retry immediately
+    pub retry_delay: Duration,
+    /// Factor by which the delay grows with each retry, up to the `timeout`. Defaults to 2
+    pub retry_backoff_factor: u32,
+    /// Randomize the delays before retries, between half of the delay and all of it, so that
+    ///  resolvers which failed at the same time don't retry at the same time. Defaults to false
+    pub retry_jitter: bool,
+    /// How truncated responses over UDP are handled, see `TruncationStrategy`
+    pub truncation_strategy: TruncationStrategy,
+    /// The UDP payload advertised in the EDNS of queries, i.e. the size of the largest response
+    ///  over UDP. The default is 1232, as recommended by the DNS Flag Day 2020, which avoids the
+    ///  fragmentation of responses. Queries configured with their own EDNS keep their payload
+    pub max_udp_payload: u16,
+    /// After consecutive timeouts of UDP queries which advertised more than 512 bytes, e.g.
+    ///  because their fragmented responses were dropped, 512 is advertised to the NameServer for
+    ///  this delay, then `max_udp_payload` is probed again. The default is 5 minutes, None never
+    ///  downgrades the payload
+    pub udp_payload_downgrade: Option<Duration>,
+    /// The most requests in flight, sent and awaiting their response, on each connection to a
+    ///  NameServer. The requests over it wait for earlier requests to complete, in the order they
+    ///  were made, or fail with `fail_when_busy`. The default is no limit
+    pub max_in_flight: Option<usize>,
+    /// The requests over `max_in_flight` fail immediately with `ProtoErrorKind::Busy`, instead of
+    ///  waiting. The default is false
+    pub fail_when_busy: bool,
+    /// Randomize the case of the letters of the names of UDP queries, 0x20 encoding, and only
+    ///  accept the responses which echo it, as protection against spoofed responses. A query is
+    ///  sent again when its response doesn't, and the case is not randomized anymore for the
+    ///  NameServers which normalize it. The default is false
+    pub case_randomization: bool,
+    /// Socket options of the TCP connections, over TCP and TLS, e.g. `nodelay` and TCP Fast
+    ///  Open to not delay the first queries of new connections. The default leaves the options
+    ///  of the operating system
+    pub tcp_options: TcpOptions,
     /// Rotate through the resource records in the response (if there is more than one for a given name)
     pub(crate) rotate: bool,
     /// Validate the names in the response, not implemented don't really see the point unless you need to support
@@ -518,12 +793,23 @@ pub struct ResolverOpts {
     pub(crate) edns0: bool,
     /// Use DNSSec to validate the request
     pub validate: bool,
+    /// Request the DNSSEC chain of the responses from the root with the EDNS CHAIN option,
+    ///  [RFC 7901](https://tools.ietf.org/html/rfc7901), to `validate` them without querying the
+    ///  DS and DNSKEY records. Only responses over TCP, or other stream protocols, return the
+    ///  chain. The default is false
+    pub chain_queries: bool,
     /// The ip_strategy for the Resolver to use when lookup Ipv4 or Ipv6 addresses
     pub ip_strategy: LookupIpStrategy,
     /// Cache size is in number of records (some records can be large)
     pub cache_size: usize,
+    /// The most memory used by the cache, estimated from its entries and their records, in
+    ///  bytes. The least recently used entries are evicted over it, as over `cache_size`, and
+    ///  answers which alone are larger are not cached. The default is no limit
+    pub cache_max_bytes: Option<usize>,
     /// Check /ect/hosts file before dns requery (only works for unix like OS)
     pub use_hosts_file: bool,
+    /// Use the host aliases file of the `HOSTALIASES` environment variable, see `search::Aliases`, the default is true
+    pub use_host_aliases: bool,
     /// Optional minimum TTL for positive responses.
     ///
     /// If this is set, any positive responses with a TTL lower than this value will have a TTL of
@@ -556,6 +842,29 @@ pub struct ResolverOpts {
     ///
     /// 0 or 1 will configure this to execute all requests serially
     pub num_concurrent_reqs: usize,
+    /// Hedge the requests, sending them to the NameServers one after the other instead of all at
+    ///  once: to the next NameServer when there is no response within this delay, or when all the
+    ///  requests sent have failed. The first response is used, and at most
+    ///  `num_concurrent_reqs` requests are outstanding. The default is no hedging
+    pub hedge_delay: Option<Duration>,
+    /// Single label names, e.g. `printer`, which fail to resolve with the NameServers are looked
+    ///  up with LLMNR on the local link, [rfc4795](https://tools.ietf.org/html/rfc4795), the default is true
+    #[cfg(feature = "llmnr")]
+    pub use_llmnr: bool,
+}
+
+impl ResolverOpts {
+    /// The policy with which failed requests are retried, of the `attempts` and the `retry_*`
+    ///  options
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            attempts: self.attempts,
+            base_delay: self.retry_delay,
+            backoff_factor: self.retry_backoff_factor,
+            max_delay: self.timeout,
+            jitter: self.retry_jitter,
+        }
+    }
 }
 
 impl Default for ResolverOpts {
@@ -565,21 +874,40 @@ impl Default for ResolverOpts {
     fn default() -> Self {
         ResolverOpts {
             ndots: 1,
+            search_dotted_names: true,
+            max_candidate_names: None,
+            absolute_first: false,
             timeout: Duration::from_secs(5),
             attempts: 2,
+            retry_delay: Duration::from_secs(0),
+            retry_backoff_factor: 2,
+            retry_jitter: false,
+            truncation_strategy: TruncationStrategy::default(),
+            max_udp_payload: MAX_PAYLOAD_LEN,
+            udp_payload_downgrade: Some(Duration::from_secs(300)),
+            max_in_flight: None,
+            fail_when_busy: false,
+            case_randomization: false,
+            tcp_options: TcpOptions::default(),
             rotate: false,
             check_names: true,
             edns0: false,
             validate: false,
+            chain_queries: false,
             ip_strategy: LookupIpStrategy::default(),
             cache_size: 32,
+            cache_max_bytes: None,
             use_hosts_file: true,
+            use_host_aliases: true,
             positive_min_ttl: None,
             negative_min_ttl: None,
             positive_max_ttl: None,
             negative_max_ttl: None,
             distrust_nx_responses: true,
             num_concurrent_reqs: 2,
+            hedge_delay: None,
+            #[cfg(feature = "llmnr")]
+            use_llmnr: true,
         }
     }
 }
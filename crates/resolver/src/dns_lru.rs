@@ -7,38 +7,160 @@
 
 //! An LRU cache designed for work with DNS lookups
 
+use std::cmp::Reverse;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::mem;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-use proto::op::Query;
-use proto::rr::Record;
+use proto::hash::NameHasher;
+use proto::op::{Message, Query, ResponseCode};
+use proto::rr::{Name, Record, RecordType};
+use proto::serialize::binary::BinEncoder;
 
-use config;
+#[cfg(feature = "dnssec")]
+use config::ValidationPolicy;
+use config::{self, TtlOverride};
 use error::*;
+use events::{EventSender, ResolverEvent};
 use lookup::Lookup;
 use lru_cache::LruCache;
+use smallvec::SmallVec;
+use validation_exceptions::ValidationStats;
 
 /// Maximum TTL as defined in https://tools.ietf.org/html/rfc2181, 2147483647
 ///   Setting this to a value of 1 day, in seconds
 pub const MAX_TTL: u32 = 86400_u32;
 
+/// The heap allocated for each label of a name, the pointer in the `Name` and the counts of the `Rc`
+const LABEL_OVERHEAD: usize = 4 * mem::size_of::<usize>();
+
 #[derive(Debug)]
 struct LruValue {
     // In the None case, this represents an NXDomain
     lookup: Option<Lookup>,
     valid_until: Instant,
+    // the estimated memory of the entry, with its query, see `LruValue::estimate_size`
+    size: usize,
 }
 
 impl LruValue {
+    fn new(query: &Query, lookup: Option<Lookup>, valid_until: Instant) -> Self {
+        let mut value = LruValue {
+            lookup,
+            valid_until,
+            size: 0,
+        };
+        value.size = value.estimate_size(query);
+        value
+    }
+
     /// Returns true if this set of ips is still valid
     fn is_current(&self, now: Instant) -> bool {
         now <= self.valid_until
     }
+
+    /// Estimates the memory used by the entry, the entry itself and the heap of its query, records
+    ///  and response
+    ///
+    /// The records shared with other entries, e.g. by `DnsLru::duplicate`, are counted for each of
+    ///  them, so that the estimate is never below the memory actually used.
+    fn estimate_size(&self, query: &Query) -> usize {
+        let mut size =
+            mem::size_of::<Query>() + mem::size_of::<LruValue>() + name_size(query.name());
+
+        if let Some(ref lookup) = self.lookup {
+            size += records_size(lookup.record_iter());
+            if let Some(response) = lookup.response() {
+                size += mem::size_of::<Message>();
+                for query in response.queries() {
+                    size += mem::size_of::<Query>() + name_size(query.name());
+                }
+                size += records_size(response.answers().iter());
+                size += records_size(response.name_servers().iter());
+                size += records_size(response.additionals().iter());
+            }
+        }
+
+        size
+    }
+}
+
+/// The heap used by the labels of the name
+fn name_size(name: &Name) -> usize {
+    usize::from(name.num_labels()) * LABEL_OVERHEAD + name.len()
+}
+
+/// The memory used by the records, with their names and the heap of their rdata
+fn records_size<'r, I: Iterator<Item = &'r Record>>(records: I) -> usize {
+    records
+        .map(|record| mem::size_of::<Record>() + name_size(record.name()) + rdata_size(record))
+        .sum()
+}
+
+/// The heap of the rdata, estimated with the length of its wire format
+fn rdata_size(record: &Record) -> usize {
+    let mut buf = Vec::new();
+    let mut encoder = BinEncoder::new(&mut buf);
+    // the names of the rdata are held uncompressed
+    encoder.set_canonical_names(true);
+    match record.rdata().emit(&mut encoder) {
+        Ok(()) => buf.len(),
+        Err(_) => 0,
+    }
+}
+
+/// Builds the hashers of the queries of the cache, with the injected `NameHasher`
+#[derive(Clone)]
+struct QueryHasher(Arc<dyn NameHasher>);
+
+impl BuildHasher for QueryHasher {
+    type Hasher = BufferedHasher;
+
+    fn build_hasher(&self) -> BufferedHasher {
+        BufferedHasher {
+            hasher: Arc::clone(&self.0),
+            bytes: SmallVec::new(),
+        }
+    }
+}
+
+impl fmt::Debug for QueryHasher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("QueryHasher")
+    }
+}
+
+/// Collects the bytes of a query, they're hashed at once by the `NameHasher`
+struct BufferedHasher {
+    hasher: Arc<dyn NameHasher>,
+    // a name is at most 255 bytes, the queries of most of them aren't spilled on the heap
+    bytes: SmallVec<[u8; 128]>,
+}
+
+impl Hasher for BufferedHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        self.hasher.hash_bytes(&self.bytes)
+    }
 }
 
 #[derive(Debug)]
 pub(crate) struct DnsLru {
-    cache: LruCache<Query, LruValue>,
+    /// The queries are chosen by the users of the resolver, they're hashed with the keyed SipHash
+    ///  of `RandomState` unless another hasher is set, see `proto::hash`
+    cache: LruCache<Query, LruValue, QueryHasher>,
+    /// The most memory used by the entries, estimated, entries are evicted over it
+    max_bytes: Option<usize>,
+    /// The memory used by the entries, estimated
+    bytes: usize,
+    /// The evictions and expirations so far
+    stats: CacheStats,
     /// A minimum TTL value for positive responses.
     ///
     /// Positive responses with TTLs under `positive_max_ttl` will use
@@ -75,6 +197,88 @@ pub(crate) struct DnsLru {
     ///
     /// [`MAX_TTL`]: const.MAX_TTL.html
     negative_max_ttl: Duration,
+    /// The TTLs of the answers for the names under the suffixes, the longest suffixes first
+    ///
+    /// These replace the TTLs received, and the minimum and maximum TTLs.
+    overrides: Vec<TtlOverride>,
+    /// The events of the resolver, shared by all the users of the cache
+    events: EventSender,
+    /// The queries which were not validated for their `ValidationException`
+    validation: ValidationStats,
+}
+
+/// An entry of the cache of a resolver, as it was when the snapshot of the cache was taken
+///
+/// See `AsyncResolver::cache_entries`.
+#[derive(Clone, Debug)]
+pub struct CacheEntry {
+    query: Query,
+    // None for the negative responses
+    lookup: Option<Lookup>,
+    ttl: Duration,
+}
+
+impl CacheEntry {
+    /// The query which was answered
+    pub fn query(&self) -> &Query {
+        &self.query
+    }
+
+    /// The answer, None for a negative response, i.e. NXDOMAIN or no records found
+    pub fn lookup(&self) -> Option<&Lookup> {
+        self.lookup.as_ref()
+    }
+
+    /// The time the entry was still cached for when the snapshot was taken
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+}
+
+/// The statistics of the cache of a resolver, see `AsyncResolver::cache_stats`
+///
+/// The memory of the entries is an estimate of the entries themselves and of the heap of their
+///  queries, records and responses, see `ResolverOpts::cache_max_bytes`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    entries: usize,
+    bytes: usize,
+    evictions: u64,
+    evicted_bytes: u64,
+    expirations: u64,
+    oversized: u64,
+}
+
+impl CacheStats {
+    /// The entries in the cache, including those which expired and were not removed yet
+    pub fn entries(&self) -> usize {
+        self.entries
+    }
+
+    /// The estimated memory used by the entries in the cache, in bytes
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// The least recently used entries which were evicted, for the entry count or memory limits
+    pub fn evictions(&self) -> u64 {
+        self.evictions
+    }
+
+    /// The estimated memory of the evicted entries, in bytes
+    pub fn evicted_bytes(&self) -> u64 {
+        self.evicted_bytes
+    }
+
+    /// The expired entries which were removed when they were looked up
+    pub fn expirations(&self) -> u64 {
+        self.expirations
+    }
+
+    /// The answers which were not cached as they alone would exceed the memory limit
+    pub fn oversized(&self) -> u64 {
+        self.oversized
+    }
 }
 
 /// The time-to-live, TTL, configuration for use by the cache.
@@ -84,7 +288,7 @@ pub(crate) struct DnsLru {
 ///   than the DNS standard. Generally a Duration greater than u32::MAX_VALUE
 ///   shouldn't cause any issue as this will never be used in serialization,
 ///   but understand that this would be outside the standard range.
-#[derive(Copy, Clone, Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub(crate) struct TtlConfig {
     /// An optional minimum TTL value for positive responses.
     ///
@@ -106,48 +310,175 @@ pub(crate) struct TtlConfig {
     /// `NXDOMAIN` responses with TTLs over `negative_max_ttl` will use
     /// `negative_max_ttl` instead.
     pub negative_max_ttl: Option<Duration>,
+    /// The TTLs of the answers for the names under the suffixes, see
+    ///  `ResolverConfig::add_ttl_override`
+    pub overrides: Vec<TtlOverride>,
 }
 
 impl TtlConfig {
-    pub(crate) fn from_opts(opts: &config::ResolverOpts) -> TtlConfig {
+    pub(crate) fn new(config: &config::ResolverConfig, opts: &config::ResolverOpts) -> TtlConfig {
         TtlConfig {
             positive_min_ttl: opts.positive_min_ttl,
             negative_min_ttl: opts.negative_min_ttl,
             positive_max_ttl: opts.positive_max_ttl,
             negative_max_ttl: opts.negative_max_ttl,
+            overrides: config.ttl_overrides().to_vec(),
         }
     }
 }
 
 impl DnsLru {
     pub(crate) fn new(capacity: usize, ttl_cfg: TtlConfig) -> Self {
+        let cache = LruCache::with_hasher(capacity, QueryHasher(Arc::new(RandomState::new())));
+
+        let mut lru = Self {
+            cache,
+            max_bytes: None,
+            bytes: 0,
+            stats: CacheStats::default(),
+            positive_min_ttl: Duration::from_secs(0),
+            negative_min_ttl: Duration::from_secs(0),
+            positive_max_ttl: Duration::from_secs(u64::from(MAX_TTL)),
+            negative_max_ttl: Duration::from_secs(u64::from(MAX_TTL)),
+            overrides: Vec::new(),
+            events: EventSender::default(),
+            validation: ValidationStats::default(),
+        };
+        lru.set_ttl_config(ttl_cfg);
+        lru
+    }
+
+    /// Sets the TTLs of the answers inserted from now on, e.g. on a reload of the configuration
+    pub(crate) fn set_ttl_config(&mut self, ttl_cfg: TtlConfig) {
         let TtlConfig {
             positive_min_ttl,
             negative_min_ttl,
             positive_max_ttl,
             negative_max_ttl,
+            mut overrides,
         } = ttl_cfg;
-        let cache = LruCache::new(capacity);
-        Self {
-            cache,
-            positive_min_ttl: positive_min_ttl.unwrap_or_else(|| Duration::from_secs(0)),
-            negative_min_ttl: negative_min_ttl.unwrap_or_else(|| Duration::from_secs(0)),
-            positive_max_ttl: positive_max_ttl
-                .unwrap_or_else(|| Duration::from_secs(u64::from(MAX_TTL))),
-            negative_max_ttl: negative_max_ttl
-                .unwrap_or_else(|| Duration::from_secs(u64::from(MAX_TTL))),
+        overrides.sort_by_key(|o| Reverse(o.suffix.num_labels()));
+
+        self.positive_min_ttl = positive_min_ttl.unwrap_or_else(|| Duration::from_secs(0));
+        self.negative_min_ttl = negative_min_ttl.unwrap_or_else(|| Duration::from_secs(0));
+        self.positive_max_ttl =
+            positive_max_ttl.unwrap_or_else(|| Duration::from_secs(u64::from(MAX_TTL)));
+        self.negative_max_ttl =
+            negative_max_ttl.unwrap_or_else(|| Duration::from_secs(u64::from(MAX_TTL)));
+        self.overrides = overrides;
+    }
+
+    /// Limits the estimated memory used by the entries, see `ResolverOpts::cache_max_bytes`
+    pub(crate) fn with_max_bytes(mut self, max_bytes: Option<usize>) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Limits the estimated memory used by the entries from now on, the entries over it are
+    ///  evicted as new ones are inserted
+    pub(crate) fn set_max_bytes(&mut self, max_bytes: Option<usize>) {
+        self.max_bytes = max_bytes;
+    }
+
+    /// Sets the hasher of the queries, the entries of the cache are cleared
+    pub(crate) fn with_hasher(mut self, hasher: Arc<dyn NameHasher>) -> Self {
+        self.cache = LruCache::with_hasher(self.cache.capacity(), QueryHasher(hasher));
+        self.bytes = 0;
+        self
+    }
+
+    /// The events of the resolver using this cache
+    pub(crate) fn events(&self) -> &EventSender {
+        &self.events
+    }
+
+    /// The queries which were not validated so far, for the exceptions of the resolver
+    pub(crate) fn validation_stats(&self) -> ValidationStats {
+        self.validation
+    }
+
+    /// Counts a query which is not validated, for its exception
+    #[cfg(feature = "dnssec")]
+    pub(crate) fn count_validation_exception(&mut self, policy: ValidationPolicy) {
+        self.validation.count(policy);
+    }
+
+    /// The statistics of the cache, with its current entries and memory
+    pub(crate) fn stats(&self) -> CacheStats {
+        CacheStats {
+            entries: self.cache.len(),
+            bytes: self.bytes,
+            ..self.stats
+        }
+    }
+
+    /// Inserts the value, the least recently used entries are evicted while the cache is full or
+    ///  over its memory limit
+    fn insert_value(&mut self, query: Query, value: LruValue) {
+        self.remove(&query);
+
+        if self.cache.capacity() == 0 {
+            return;
+        }
+        if self.max_bytes.map(|max| value.size > max).unwrap_or(false) {
+            debug!(
+                "not caching {:?}, {} bytes over the limit",
+                query, value.size
+            );
+            self.stats.oversized += 1;
+            return;
         }
+
+        while self.cache.len() >= self.cache.capacity()
+            || self
+                .max_bytes
+                .map(|max| self.bytes + value.size > max)
+                .unwrap_or(false)
+        {
+            let (evicted, evicted_value) = match self.cache.remove_lru() {
+                Some(entry) => entry,
+                None => break,
+            };
+            self.bytes -= evicted_value.size;
+            self.stats.evictions += 1;
+            self.stats.evicted_bytes += evicted_value.size as u64;
+            self.events
+                .send(|| ResolverEvent::CacheEviction { query: evicted });
+        }
+
+        self.bytes += value.size;
+        self.cache.insert(query, value);
+    }
+
+    /// Removes the entry of the query, returns true if there was one
+    fn remove(&mut self, query: &Query) -> bool {
+        match self.cache.remove(query) {
+            Some(value) => {
+                self.bytes -= value.size;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The TTL of the answers for the name, of the longest suffix of it with an override
+    fn ttl_override(&self, name: &Name) -> Option<Duration> {
+        self.overrides
+            .iter()
+            .find(|o| o.suffix.zone_of(name))
+            .map(|o| Duration::from_secs(u64::from(o.ttl)))
     }
 
     pub(crate) fn insert(
         &mut self,
         query: Query,
         records_and_ttl: Vec<(Record, u32)>,
+        response: Option<Arc<Message>>,
         now: Instant,
     ) -> Lookup {
         let len = records_and_ttl.len();
         // collapse the values, we're going to take the Minimum TTL as the correct one
-        let (records, ttl): (Vec<Record>, Duration) = records_and_ttl.into_iter().fold(
+        let (mut records, ttl): (Vec<Record>, Duration) = records_and_ttl.into_iter().fold(
             (Vec::with_capacity(len), self.positive_max_ttl),
             |(mut records, mut min_ttl), (record, ttl)| {
                 records.push(record);
@@ -160,17 +491,34 @@ impl DnsLru {
         // If the cache was configured with a minimum TTL, and that value is higher
         // than the minimum TTL in the values, use it instead.
         let ttl = self.positive_min_ttl.max(ttl);
+
+        // the TTLs of the records are rewritten, so that they are answered with those of the cache
+        let ttl = match self.ttl_override(query.name()) {
+            Some(ttl) => {
+                for record in &mut records {
+                    record.set_ttl(ttl.as_secs() as u32);
+                }
+                ttl
+            }
+            None => {
+                let min_ttl = self.positive_min_ttl.as_secs();
+                let max_ttl = self.positive_max_ttl.as_secs();
+                for record in &mut records {
+                    let record_ttl = u64::from(record.ttl()).max(min_ttl).min(max_ttl);
+                    record.set_ttl(record_ttl as u32);
+                }
+                ttl
+            }
+        };
         let valid_until = now + ttl;
 
         // insert into the LRU
-        let lookup = Lookup::new_with_deadline(query.clone(), Arc::new(records), valid_until);
-        self.cache.insert(
-            query,
-            LruValue {
-                lookup: Some(lookup.clone()),
-                valid_until,
-            },
-        );
+        let mut lookup = Lookup::new_with_deadline(query.clone(), Arc::new(records), valid_until);
+        if let Some(response) = response {
+            lookup = lookup.with_response(response);
+        }
+        let value = LruValue::new(&query, Some(lookup.clone()), valid_until);
+        self.insert_value(query, value);
 
         lookup
     }
@@ -183,25 +531,30 @@ impl DnsLru {
         ttl: u32,
         now: Instant,
     ) -> Lookup {
-        let ttl = Duration::from_secs(u64::from(ttl));
+        let ttl = self
+            .ttl_override(query.name())
+            .unwrap_or_else(|| Duration::from_secs(u64::from(ttl)));
         let valid_until = now + ttl;
 
-        self.cache.insert(
-            query,
-            LruValue {
-                lookup: Some(lookup.clone()),
-                valid_until,
-            },
-        );
+        let value = LruValue::new(&query, Some(lookup.clone()), valid_until);
+        self.insert_value(query, value);
 
         lookup
     }
 
+    /// The error of a query without records, e.g. of an invalid name, without a response
     pub(crate) fn nx_error(query: Query, valid_until: Option<Instant>) -> ResolveError {
-        ResolveErrorKind::NoRecordsFound { query, valid_until }.into()
+        ResolveErrorKind::NoRecordsFound {
+            query,
+            response_code: ResponseCode::NXDomain,
+            authority: None,
+            valid_until,
+        }
+        .into()
     }
 
-    pub(crate) fn negative(&mut self, query: Query, ttl: u32, now: Instant) -> ResolveError {
+    /// Caches the negative response to the query, returns the `Instant` at which it expires
+    pub(crate) fn negative(&mut self, query: Query, ttl: u32, now: Instant) -> Instant {
         // TODO: if we are getting a negative response, should we instead fallback to cache?
         //   this would cache indefinitely, probably not correct
 
@@ -210,17 +563,13 @@ impl DnsLru {
             // minimum and maximum TTLs for negative responses.
             .max(self.negative_min_ttl)
             .min(self.negative_max_ttl);
+        let ttl = self.ttl_override(query.name()).unwrap_or(ttl);
         let valid_until = now + ttl;
 
-        self.cache.insert(
-            query.clone(),
-            LruValue {
-                lookup: None,
-                valid_until,
-            },
-        );
+        let value = LruValue::new(&query, None, valid_until);
+        self.insert_value(query, value);
 
-        Self::nx_error(query, Some(valid_until))
+        valid_until
     }
 
     /// This needs to be mut b/c it's an LRU, meaning the ordering of elements will potentially change on retrieval...
@@ -229,7 +578,7 @@ impl DnsLru {
         let lookup = self.cache.get_mut(query).and_then(|value| {
             if value.is_current(now) {
                 out_of_date = false;
-                value.lookup.clone()
+                value.lookup.clone().map(Lookup::cached)
             } else {
                 out_of_date = true;
                 None
@@ -239,12 +588,47 @@ impl DnsLru {
         // in this case, we can preemtively remove out of data elements
         // this assumes time is always moving forward, this would only not be true in contrived situations where now
         //  is not current time, like tests...
-        if out_of_date {
-            self.cache.remove(query);
+        if out_of_date && self.remove(query) {
+            self.stats.expirations += 1;
         }
 
         lookup
     }
+
+    /// The entries which are current at `now`, from the least to the most recently used
+    pub(crate) fn entries(&self, now: Instant) -> Vec<CacheEntry> {
+        self.cache
+            .iter()
+            .filter(|(_, value)| value.is_current(now))
+            .map(|(query, value)| CacheEntry {
+                query: query.clone(),
+                lookup: value.lookup.clone(),
+                ttl: value.valid_until - now,
+            })
+            .collect()
+    }
+
+    /// Removes the entries of the names under the suffix, only those of the type if specified
+    ///
+    /// # Return
+    ///
+    /// The number of entries removed
+    pub(crate) fn flush(&mut self, suffix: &Name, record_type: Option<RecordType>) -> usize {
+        let flushed = self
+            .cache
+            .iter()
+            .map(|(query, _)| query)
+            .filter(|query| suffix.zone_of(query.name()))
+            .filter(|query| record_type.is_none() || record_type == Some(query.query_type()))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for query in &flushed {
+            self.remove(query);
+        }
+
+        flushed.len()
+    }
 }
 
 // see also the lookup_tests.rs in integration-tests crate
@@ -269,6 +653,7 @@ mod tests {
         let value = LruValue {
             lookup: None,
             valid_until: future,
+            size: 0,
         };
 
         assert!(value.is_current(now));
@@ -297,7 +682,7 @@ mod tests {
         };
         let mut lru = DnsLru::new(1, ttls);
 
-        let rc_ips = lru.insert(query.clone(), ips_ttl, now);
+        let rc_ips = lru.insert(query.clone(), ips_ttl, None, now);
         assert_eq!(*rc_ips.iter().next().unwrap(), ips[0]);
         // the returned lookup should use the cache's min TTL, since the
         // query's TTL was below the minimum.
@@ -309,7 +694,7 @@ mod tests {
             3,
         )];
 
-        let rc_ips = lru.insert(query.clone(), ips_ttl, now);
+        let rc_ips = lru.insert(query.clone(), ips_ttl, None, now);
         assert_eq!(*rc_ips.iter().next().unwrap(), ips[0]);
         // the returned lookup should use the record's TTL, since it's
         // greater than the cache's minimum.
@@ -330,27 +715,15 @@ mod tests {
         let mut lru = DnsLru::new(1, ttls);
 
         // neg response should have TTL of 1 seconds.
-        let nx_error = lru.negative(name.clone(), 1, now);
-        match nx_error.kind() {
-            &ResolveErrorKind::NoRecordsFound { valid_until, .. } => {
-                let valid_until = valid_until.expect("resolve error should have a deadline");
-                // the error's `valid_until` field should have been limited to 2 seconds.
-                assert_eq!(valid_until, now + Duration::from_secs(2));
-            }
-            other => panic!("expected ResolveErrorKind::NoRecordsFound, got {:?}", other),
-        }
+        let valid_until = lru.negative(name.clone(), 1, now);
+        // the deadline should have been limited to 2 seconds.
+        assert_eq!(valid_until, now + Duration::from_secs(2));
 
         // neg response should have TTL of 3 seconds.
-        let nx_error = lru.negative(name.clone(), 3, now);
-        match nx_error.kind() {
-            &ResolveErrorKind::NoRecordsFound { valid_until, .. } => {
-                let valid_until = valid_until.expect("ResolveError should have a deadline");
-                // the error's `valid_until` field should not have been limited, as it was
-                // over the min TTL.
-                assert_eq!(valid_until, now + Duration::from_secs(3));
-            }
-            other => panic!("expected ResolveErrorKind::NoRecordsFound, got {:?}", other),
-        }
+        let valid_until = lru.negative(name.clone(), 3, now);
+        // the deadline should not have been limited, as it was
+        // over the min TTL.
+        assert_eq!(valid_until, now + Duration::from_secs(3));
     }
 
     #[test]
@@ -373,7 +746,7 @@ mod tests {
         };
         let mut lru = DnsLru::new(1, ttls);
 
-        let rc_ips = lru.insert(query.clone(), ips_ttl, now);
+        let rc_ips = lru.insert(query.clone(), ips_ttl, None, now);
         assert_eq!(*rc_ips.iter().next().unwrap(), ips[0]);
         // the returned lookup should use the cache's min TTL, since the
         // query's TTL was above the maximum.
@@ -385,7 +758,7 @@ mod tests {
             59,
         )];
 
-        let rc_ips = lru.insert(query.clone(), ips_ttl, now);
+        let rc_ips = lru.insert(query.clone(), ips_ttl, None, now);
         assert_eq!(*rc_ips.iter().next().unwrap(), ips[0]);
         // the returned lookup should use the record's TTL, since it's
         // below than the cache's maximum.
@@ -406,27 +779,15 @@ mod tests {
         let mut lru = DnsLru::new(1, ttls);
 
         // neg response should have TTL of 62 seconds.
-        let nx_error = lru.negative(name.clone(), 62, now);
-        match nx_error.kind() {
-            &ResolveErrorKind::NoRecordsFound { valid_until, .. } => {
-                let valid_until = valid_until.expect("resolve error should have a deadline");
-                // the error's `valid_until` field should have been limited to 60 seconds.
-                assert_eq!(valid_until, now + Duration::from_secs(60));
-            }
-            other => panic!("expected ResolveErrorKind::NoRecordsFound, got {:?}", other),
-        }
+        let valid_until = lru.negative(name.clone(), 62, now);
+        // the deadline should have been limited to 60 seconds.
+        assert_eq!(valid_until, now + Duration::from_secs(60));
 
         // neg response should have TTL of 59 seconds.
-        let nx_error = lru.negative(name.clone(), 59, now);
-        match nx_error.kind() {
-            &ResolveErrorKind::NoRecordsFound { valid_until, .. } => {
-                let valid_until = valid_until.expect("resolve error should have a deadline");
-                // the error's `valid_until` field should not have been limited, as it was
-                // under the max TTL.
-                assert_eq!(valid_until, now + Duration::from_secs(59));
-            }
-            other => panic!("expected ResolveErrorKind::NoRecordsFound, got {:?}", other),
-        }
+        let valid_until = lru.negative(name.clone(), 59, now);
+        // the deadline should not have been limited, as it was
+        // under the max TTL.
+        assert_eq!(valid_until, now + Duration::from_secs(59));
     }
 
     #[test]
@@ -442,13 +803,40 @@ mod tests {
         let ips = vec![RData::A(Ipv4Addr::new(127, 0, 0, 1))];
         let mut lru = DnsLru::new(1, TtlConfig::default());
 
-        let rc_ips = lru.insert(query.clone(), ips_ttl, now);
+        let rc_ips = lru.insert(query.clone(), ips_ttl, None, now);
         assert_eq!(*rc_ips.iter().next().unwrap(), ips[0]);
 
         let rc_ips = lru.get(&query, now).unwrap();
         assert_eq!(*rc_ips.iter().next().unwrap(), ips[0]);
     }
 
+    #[test]
+    fn test_insert_hasher() {
+        use proto::hash::FxBuildHasher;
+
+        let now = Instant::now();
+        let name = Name::from_str("www.example.com.").unwrap();
+        let query = Query::query(name.clone(), RecordType::A);
+        let ips_ttl = vec![(
+            Record::from_rdata(name.clone(), 1, RData::A(Ipv4Addr::new(127, 0, 0, 1))),
+            1,
+        )];
+        let mut lru = DnsLru::new(2, TtlConfig::default()).with_hasher(Arc::new(FxBuildHasher));
+
+        lru.insert(query.clone(), ips_ttl, None, now);
+        let rc_ips = lru.get(&query, now).unwrap();
+        assert_eq!(
+            *rc_ips.iter().next().unwrap(),
+            RData::A(Ipv4Addr::new(127, 0, 0, 1))
+        );
+
+        // the names of the queries are hashed case insensitively
+        let upper = Query::query(Name::from_str("WWW.EXAMPLE.COM.").unwrap(), RecordType::A);
+        assert!(lru.get(&upper, now).is_some());
+        let other = Query::query(Name::from_str("www.example.net.").unwrap(), RecordType::A);
+        assert!(lru.get(&other, now).is_none());
+    }
+
     #[test]
     fn test_insert_ttl() {
         let now = Instant::now();
@@ -471,7 +859,7 @@ mod tests {
         ];
         let mut lru = DnsLru::new(1, TtlConfig::default());
 
-        lru.insert(query.clone(), ips_ttl, now);
+        lru.insert(query.clone(), ips_ttl, None, now);
 
         // still valid
         let rc_ips = lru.get(&query, now + Duration::from_secs(1)).unwrap();
@@ -510,7 +898,7 @@ mod tests {
             ..Default::default()
         };
         let mut lru = DnsLru::new(1, ttls);
-        lru.insert(query.clone(), ips_ttl, now);
+        lru.insert(query.clone(), ips_ttl, None, now);
 
         // still valid
         let rc_ips = lru.get(&query, now + Duration::from_secs(1)).unwrap();
@@ -561,7 +949,7 @@ mod tests {
             ..Default::default()
         };
         let mut lru = DnsLru::new(1, ttls);
-        lru.insert(query.clone(), ips_ttl, now);
+        lru.insert(query.clone(), ips_ttl, None, now);
 
         // still valid
         let rc_ips = lru.get(&query, now + Duration::from_secs(1)).unwrap();
@@ -578,4 +966,199 @@ mod tests {
         let rc_ips = lru.get(&query, now + Duration::from_secs(3));
         assert!(rc_ips.is_none());
     }
+
+    #[test]
+    fn test_records_ttls_are_clamped() {
+        let now = Instant::now();
+
+        let name = Name::from_str("www.example.com.").unwrap();
+        let query = Query::query(name.clone(), RecordType::A);
+        let ips_ttl = vec![
+            (
+                Record::from_rdata(name.clone(), 0, RData::A(Ipv4Addr::new(127, 0, 0, 1))),
+                0,
+            ),
+            (
+                Record::from_rdata(name.clone(), 120, RData::A(Ipv4Addr::new(127, 0, 0, 2))),
+                120,
+            ),
+        ];
+
+        let ttls = TtlConfig {
+            positive_min_ttl: Some(Duration::from_secs(30)),
+            positive_max_ttl: Some(Duration::from_secs(60)),
+            ..Default::default()
+        };
+        let mut lru = DnsLru::new(1, ttls);
+
+        let rc_ips = lru.insert(query.clone(), ips_ttl, None, now);
+        assert_eq!(rc_ips.valid_until(), now + Duration::from_secs(30));
+        let ttls: Vec<u32> = rc_ips.record_iter().map(Record::ttl).collect();
+        assert_eq!(ttls, vec![30, 60]);
+    }
+
+    #[test]
+    fn test_ttl_overrides() {
+        let now = Instant::now();
+
+        let mut config = config::ResolverConfig::new();
+        config.add_ttl_override(Name::from_str("example.com").unwrap(), 300);
+        config.add_ttl_override(Name::from_str("corp.example.com.").unwrap(), 5);
+        let options = config::ResolverOpts {
+            positive_max_ttl: Some(Duration::from_secs(60)),
+            ..Default::default()
+        };
+        let mut lru = DnsLru::new(2, TtlConfig::new(&config, &options));
+
+        // the override of the longest suffix is used, and the TTLs of the records are rewritten
+        let name = Name::from_str("db.corp.example.com.").unwrap();
+        let query = Query::query(name.clone(), RecordType::A);
+        let ips_ttl = vec![(
+            Record::from_rdata(name.clone(), 3600, RData::A(Ipv4Addr::new(10, 0, 0, 1))),
+            3600,
+        )];
+        let rc_ips = lru.insert(query.clone(), ips_ttl, None, now);
+        assert_eq!(rc_ips.valid_until(), now + Duration::from_secs(5));
+        assert_eq!(rc_ips.record_iter().next().unwrap().ttl(), 5);
+
+        // overrides are not bounded by the maximum TTL
+        let name = Name::from_str("www.example.com.").unwrap();
+        let query = Query::query(name.clone(), RecordType::A);
+        let ips_ttl = vec![(
+            Record::from_rdata(name.clone(), 0, RData::A(Ipv4Addr::new(127, 0, 0, 1))),
+            0,
+        )];
+        let rc_ips = lru.insert(query.clone(), ips_ttl, None, now);
+        assert_eq!(rc_ips.valid_until(), now + Duration::from_secs(300));
+
+        // negative responses are overriden as well
+        let query = Query::query(Name::from_str("nx.example.com.").unwrap(), RecordType::A);
+        assert_eq!(lru.negative(query, 1, now), now + Duration::from_secs(300));
+
+        // names outside of the suffixes are not overriden
+        let query = Query::query(Name::from_str("www.example.net.").unwrap(), RecordType::A);
+        assert_eq!(lru.negative(query, 1, now), now + Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_entries_and_flush() {
+        let now = Instant::now();
+        let mut lru = DnsLru::new(10, TtlConfig::default());
+
+        let insert = |lru: &mut DnsLru, name: &str, record_type: RecordType, ttl: u32| {
+            let name = Name::from_str(name).unwrap();
+            let record = Record::from_rdata(name.clone(), ttl, RData::A(Ipv4Addr::LOCALHOST));
+            lru.insert(
+                Query::query(name, record_type),
+                vec![(record, ttl)],
+                None,
+                now,
+            );
+        };
+        insert(&mut lru, "www.example.com.", RecordType::A, 60);
+        insert(&mut lru, "www.example.com.", RecordType::AAAA, 60);
+        insert(&mut lru, "mail.example.com.", RecordType::A, 60);
+        insert(&mut lru, "www.example.net.", RecordType::A, 1);
+        lru.negative(
+            Query::query(Name::from_str("nx.example.com.").unwrap(), RecordType::A),
+            60,
+            now,
+        );
+
+        // expired entries are not in the snapshot
+        let later = now + Duration::from_secs(10);
+        let entries = lru.entries(later);
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[0].query().name().to_string(), "www.example.com.");
+        assert_eq!(entries[0].ttl(), Duration::from_secs(50));
+        assert!(entries[0].lookup().is_some());
+        assert!(entries[3].lookup().is_none());
+
+        let example_com = Name::from_str("example.com.").unwrap();
+        assert_eq!(lru.flush(&example_com, Some(RecordType::AAAA)), 1);
+        assert_eq!(lru.flush(&example_com, Some(RecordType::AAAA)), 0);
+        assert_eq!(lru.flush(&example_com, None), 3);
+        assert_eq!(lru.entries(now).len(), 1);
+        assert_eq!(lru.flush(&Name::root(), None), 1);
+        assert!(lru.entries(now).is_empty());
+    }
+
+    #[test]
+    fn test_memory_limit() {
+        let now = Instant::now();
+        let query = |name: &str| Query::query(Name::from_str(name).unwrap(), RecordType::A);
+        let negative_size = LruValue::new(&query("www.example.com."), None, now).size;
+
+        let mut lru = DnsLru::new(10, TtlConfig::default()).with_max_bytes(Some(2 * negative_size));
+        lru.negative(query("www.example.com."), 60, now);
+        lru.negative(query("ftp.example.com."), 60, now);
+        assert_eq!(lru.stats().bytes(), 2 * negative_size);
+        assert_eq!(lru.stats().evictions(), 0);
+
+        // the cache is not full, but over its memory limit
+        lru.negative(query("web.example.com."), 60, now);
+        let stats = lru.stats();
+        assert_eq!(stats.entries(), 2);
+        assert_eq!(stats.bytes(), 2 * negative_size);
+        assert_eq!(stats.evictions(), 1);
+        assert_eq!(stats.evicted_bytes(), negative_size as u64);
+        assert!(lru.get(&query("www.example.com."), now).is_none());
+
+        // the records are counted, an answer larger than the limit is not cached
+        let records = (0..10)
+            .map(|i| {
+                let record = Record::from_rdata(
+                    Name::from_str("www.example.com.").unwrap(),
+                    60,
+                    RData::A(Ipv4Addr::new(127, 0, 0, i)),
+                );
+                (record, 60)
+            })
+            .collect();
+        lru.insert(query("www.example.com."), records, None, now);
+        assert_eq!(lru.stats().oversized(), 1);
+        assert_eq!(lru.stats().entries(), 2);
+        assert!(lru.get(&query("www.example.com."), now).is_none());
+
+        // expired and flushed entries are not counted anymore
+        let later = now + Duration::from_secs(61);
+        assert!(lru.get(&query("ftp.example.com."), later).is_none());
+        assert_eq!(lru.stats().expirations(), 1);
+        assert_eq!(lru.stats().bytes(), negative_size);
+        assert_eq!(lru.flush(&Name::root(), None), 1);
+        assert_eq!(lru.stats().bytes(), 0);
+    }
+
+    #[test]
+    fn test_eviction_events() {
+        use futures::{future, Async, Future, Stream};
+
+        use events::ResolverEvent;
+
+        future::lazy(|| {
+            let now = Instant::now();
+            let mut lru = DnsLru::new(2, TtlConfig::default());
+            let mut events = lru.events().subscribe();
+
+            let query = |name: &str| Query::query(Name::from_str(name).unwrap(), RecordType::A);
+            lru.negative(query("www.example.com."), 60, now);
+            lru.negative(query("mail.example.com."), 60, now);
+            // replacing an answer doesn't evict another
+            lru.negative(query("www.example.com."), 60, now);
+            assert!(events.poll().unwrap().is_not_ready());
+
+            lru.negative(query("ftp.example.com."), 60, now);
+            match events.poll() {
+                Ok(Async::Ready(Some(ResolverEvent::CacheEviction { query: evicted }))) => {
+                    assert_eq!(evicted, query("mail.example.com."))
+                }
+                other => panic!("expected an eviction: {:?}", other.map(|_| ())),
+            }
+            assert!(events.poll().unwrap().is_not_ready());
+
+            Ok::<(), ()>(())
+        })
+        .wait()
+        .unwrap();
+    }
 }
@@ -13,7 +13,7 @@ use std::collections::HashMap;
 
 use futures::{Async, Future, Poll};
 
-use proto::rr::rdata::TXT;
+use proto::rr::rdata::{DnsSdAttribute, TXT};
 use proto::rr::{IntoName, Name, RecordType};
 use proto::xfer::DnsRequestOptions;
 
@@ -40,6 +40,7 @@ impl DnsSdHandle for AsyncResolver {
     fn list_services<N: IntoName>(&self, name: N) -> ListServicesFuture {
         let options = DnsRequestOptions {
             expects_multiple_responses: true,
+            ..DnsRequestOptions::default()
         };
 
         let name: Name = match name.into_name() {
@@ -122,22 +123,20 @@ impl ServiceInfo {
     ///
     /// This converts the DNS-SD TXT record into a map following the rules specified in https://tools.ietf.org/html/rfc6763#section-6.4
     pub fn to_map<'s>(&'s self) -> HashMap<Cow<'s, str>, Option<Cow<'s, str>>> {
-        self.0
-            .iter()
-            .flat_map(TXT::iter)
-            .filter_map(|bytes| {
-                let mut split = bytes.split(|byte| *byte == b'=');
-
-                let key = split.next().map(String::from_utf8_lossy);
-                let value = split.next().map(String::from_utf8_lossy);
-
-                if let Some(key) = key {
-                    Some((key, value))
-                } else {
-                    None
-                }
-            })
-            .collect()
+        let mut map = HashMap::new();
+        for attribute in self.attributes() {
+            map.entry(Cow::Borrowed(attribute.key()))
+                .or_insert_with(|| attribute.value().map(String::from_utf8_lossy));
+        }
+
+        map
+    }
+
+    /// The attributes of all the TXT records, in order, see `TXT::dns_sd_attributes`
+    ///
+    /// The values are the bytes received, the boolean attributes have no value.
+    pub fn attributes(&self) -> Vec<DnsSdAttribute<'_>> {
+        self.0.iter().flat_map(TXT::dns_sd_attributes).collect()
     }
 }
 
@@ -7,10 +7,38 @@
 
 //! Error types for the crate
 
-use failure::{Backtrace, Context, Fail};
-use std::{fmt, io, sync, time::Instant};
-use proto::error::{ProtoError, ProtoErrorKind};
-use proto::op::Query;
+use failure::Fail;
+use std::{fmt, io, net::SocketAddr, sync::{self, Arc}, time::Instant};
+use proto::error::{ProtoError, ProtoErrorCode, ProtoErrorKind};
+use proto::op::{Query, ResponseCode};
+use proto::rr::Record;
+
+/// The records of the authority section of a negative response, see RFC 2308
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NegativeAuthority {
+    soa: Option<Record>,
+    negative_proofs: Vec<Record>,
+}
+
+impl NegativeAuthority {
+    pub(crate) fn new(soa: Option<Record>, negative_proofs: Vec<Record>) -> Self {
+        NegativeAuthority {
+            soa,
+            negative_proofs,
+        }
+    }
+
+    /// The SOA record of the zone, its minimum TTL bounds the caching of the negative response
+    pub fn soa(&self) -> Option<&Record> {
+        self.soa.as_ref()
+    }
+
+    /// The NSEC and NSEC3 records, and their RRSIGs, proving the negative response, only
+    /// collected when validating DNSSEC
+    pub fn negative_proofs(&self) -> &[Record] {
+        &self.negative_proofs
+    }
+}
 
 /// An alias for results returned by functions of this crate
 pub type ResolveResult<T> = ::std::result::Result<T, ResolveError>;
@@ -31,11 +59,21 @@ pub enum ResolveErrorKind {
     NoRecordsFound {
         /// The query for which no records were found.
         query: Query,
+        /// `NXDomain` if the name does not exist, `NoError` if it exists without records of the
+        /// type of the query, i.e. NODATA
+        response_code: ResponseCode,
+        /// The SOA record and the negative proofs from the authority section of the response,
+        /// if it had any
+        authority: Option<Box<NegativeAuthority>>,
         /// A deadline after which the the `NXDOMAIN` response is no longer
         /// valid, and the nameserver should be queried again.
         valid_until: Option<Instant>
     },
 
+    /// The name server answered the query with an error, e.g. `Refused` or `ServFail`
+    #[fail(display = "error response: {}", _0)]
+    ResponseCode(ResponseCode),
+
     // foreign
     /// An error got returned from IO
     #[fail(display = "io error")]
@@ -56,10 +94,18 @@ impl Clone for ResolveErrorKind {
         match *self {
             Message(msg) => Message(msg),
             Msg(ref msg) => Msg(msg.clone()),
-            NoRecordsFound { ref query, valid_until } => NoRecordsFound {
+            NoRecordsFound {
+                ref query,
+                response_code,
+                ref authority,
+                valid_until,
+            } => NoRecordsFound {
                 query: query.clone(),
+                response_code,
+                authority: authority.clone(),
                 valid_until,
             },
+            ResponseCode(response_code) => ResponseCode(response_code),
 
             // foreign
             Io => Io,
@@ -69,57 +115,161 @@ impl Clone for ResolveErrorKind {
     }
 }
 
+impl ResolveErrorKind {
+    /// Returns the stable class of this error, see `ResolveErrorCode`
+    ///
+    /// `Proto` errors are `Other`, the class of their source is only known to the `ResolveError`.
+    pub fn code(&self) -> ResolveErrorCode {
+        match *self {
+            ResolveErrorKind::NoRecordsFound {
+                response_code: ResponseCode::NXDomain,
+                ..
+            } => ResolveErrorCode::NXDomain,
+            ResolveErrorKind::NoRecordsFound { .. } => ResolveErrorCode::NoData,
+            ResolveErrorKind::ResponseCode(ResponseCode::Refused) => ResolveErrorCode::Refused,
+            ResolveErrorKind::ResponseCode(ResponseCode::ServFail) => ResolveErrorCode::ServFail,
+            ResolveErrorKind::Timeout => ResolveErrorCode::Timeout,
+            ResolveErrorKind::Io => ResolveErrorCode::Network,
+            ResolveErrorKind::Message(_)
+            | ResolveErrorKind::Msg(_)
+            | ResolveErrorKind::ResponseCode(_)
+            | ResolveErrorKind::Proto => ResolveErrorCode::Other,
+        }
+    }
+}
+
+/// The stable classes of errors, for matching on the outcome of a lookup without depending on
+///  the details of `ResolveErrorKind`
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ResolveErrorCode {
+    /// The name does not exist, NXDOMAIN
+    NXDomain,
+    /// The name exists, but has no records of the type of the query, NODATA
+    NoData,
+    /// The name server refused to answer the query
+    Refused,
+    /// The name server failed to answer the query, SERVFAIL
+    ServFail,
+    /// The records failed DNSSEC validation, or their algorithms are not supported
+    Bogus,
+    /// The name servers did not answer before the timeout
+    Timeout,
+    /// The name servers are unreachable, or the connections to them failed
+    Network,
+    /// Any other error
+    Other,
+}
+
 /// The error type for errors that get returned in the crate
-#[derive(Debug)]
+///
+/// The cause of the error is `kind`, or the coarser `code`. Errors of lookups have the `query`
+///  which failed, and the `name_servers` it was sent to when none of them answered it. Errors
+///  of other libraries, e.g. `ProtoError`, are the `cause` of the error.
+#[derive(Clone, Debug)]
 pub struct ResolveError {
-    inner: Context<ResolveErrorKind>,
+    kind: ResolveErrorKind,
+    query: Option<Box<Query>>,
+    name_servers: Vec<SocketAddr>,
+    source: Option<Arc<Fail>>,
 }
 
 impl ResolveError {
     /// Get the kind of the error
     pub fn kind(&self) -> &ResolveErrorKind {
-        self.inner.get_context()
+        &self.kind
     }
-}
 
-impl Clone for ResolveError {
-    fn clone(&self) -> Self {
-        ResolveError {
-            inner: Context::new(self.inner.get_context().clone()),
+    /// Returns the stable class of the error
+    ///
+    /// `Proto` errors are classified by the `ProtoErrorCode` of their cause, e.g. `Insecure`
+    ///  records are `Bogus`.
+    pub fn code(&self) -> ResolveErrorCode {
+        let proto_code = self
+            .source
+            .as_ref()
+            .and_then(|source| source.downcast_ref::<ProtoError>())
+            .map(ProtoError::code);
+
+        match (&self.kind, proto_code) {
+            (&ResolveErrorKind::Proto, Some(proto_code)) => match proto_code {
+                ProtoErrorCode::Timeout => ResolveErrorCode::Timeout,
+                ProtoErrorCode::Unreachable
+                | ProtoErrorCode::Busy
+                | ProtoErrorCode::Canceled
+                | ProtoErrorCode::Io => ResolveErrorCode::Network,
+                ProtoErrorCode::Insecure
+                | ProtoErrorCode::UnsupportedAlgorithm
+                | ProtoErrorCode::Crypto => ResolveErrorCode::Bogus,
+                ProtoErrorCode::FormErr
+                | ProtoErrorCode::Truncated
+                | ProtoErrorCode::BufferFull
+                | ProtoErrorCode::Other => ResolveErrorCode::Other,
+            },
+            (kind, _) => kind.code(),
+        }
+    }
+
+    /// The query which failed, for a CNAME chain the last query of the chain
+    pub fn query(&self) -> Option<&Query> {
+        match self.kind {
+            ResolveErrorKind::NoRecordsFound { ref query, .. } => Some(query),
+            _ => self.query.as_ref().map(Box::as_ref),
+        }
+    }
+
+    /// The name servers the query was sent to, if none of them answered it
+    pub fn name_servers(&self) -> &[SocketAddr] {
+        &self.name_servers
+    }
+
+    /// Sets the query which failed, unless it is already set by a lookup further down a CNAME chain
+    pub(crate) fn with_query(mut self, query: Query) -> Self {
+        if self.query().is_none() {
+            self.query = Some(Box::new(query));
         }
+        self
+    }
+
+    /// Sets the name servers the query was sent to, if the error is that none of them answered
+    pub(crate) fn with_name_servers(mut self, name_servers: &[SocketAddr]) -> Self {
+        let code = self.code();
+        if self.name_servers.is_empty()
+            && (code == ResolveErrorCode::Timeout || code == ResolveErrorCode::Network)
+        {
+            self.name_servers = name_servers.to_vec();
+        }
+        self
+    }
+
+    fn with_source<F: Fail>(mut self, source: F) -> Self {
+        self.source = Some(Arc::new(source));
+        self
     }
 }
 
 impl Fail for ResolveError {
     fn cause(&self) -> Option<&Fail> {
-        self.inner.cause()
-    }
-
-    fn backtrace(&self) -> Option<&Backtrace> {
-        self.inner.backtrace()
+        self.source.as_ref().map(Arc::as_ref)
     }
 }
 
 impl fmt::Display for ResolveError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        fmt::Display::fmt(&self.inner, f)
+        fmt::Display::fmt(&self.kind, f)
     }
 }
 
 impl From<ResolveErrorKind> for ResolveError {
     fn from(kind: ResolveErrorKind) -> ResolveError {
         ResolveError {
-            inner: Context::new(kind),
+            kind,
+            query: None,
+            name_servers: vec![],
+            source: None,
         }
     }
 }
 
-impl From<Context<ResolveErrorKind>> for ResolveError {
-    fn from(inner: Context<ResolveErrorKind>) -> ResolveError {
-        ResolveError { inner }
-    }
-}
-
 impl From<&'static str> for ResolveError {
     fn from(msg: &'static str) -> ResolveError {
         ResolveErrorKind::Message(msg).into()
@@ -129,8 +279,7 @@ impl From<&'static str> for ResolveError {
 #[cfg(target_os = "windows")]
 impl From<::ipconfig::error::Error> for ResolveError {
     fn from(e: ::ipconfig::error::Error) -> ResolveError {
-        e.context(ResolveErrorKind::Message("failed to read from registry"))
-            .into()
+        ResolveErrorKind::Msg(format!("failed to read from registry: {}", e)).into()
     }
 }
 
@@ -143,8 +292,8 @@ impl From<String> for ResolveError {
 impl From<io::Error> for ResolveError {
     fn from(e: io::Error) -> ResolveError {
         match e.kind() {
-            io::ErrorKind::TimedOut => e.context(ResolveErrorKind::Timeout).into(),
-            _ => e.context(ResolveErrorKind::Io).into(),
+            io::ErrorKind::TimedOut => ResolveError::from(ResolveErrorKind::Timeout).with_source(e),
+            _ => ResolveError::from(ResolveErrorKind::Io).with_source(e),
         }
     }
 }
@@ -152,8 +301,8 @@ impl From<io::Error> for ResolveError {
 impl From<ProtoError> for ResolveError {
     fn from(e: ProtoError) -> ResolveError {
         match *e.kind() {
-            ProtoErrorKind::Timeout => e.context(ResolveErrorKind::Timeout).into(),
-            _ => e.context(ResolveErrorKind::Proto).into(),
+            ProtoErrorKind::Timeout => ResolveError::from(ResolveErrorKind::Timeout).with_source(e),
+            _ => ResolveError::from(ResolveErrorKind::Proto).with_source(e),
         }
     }
 }
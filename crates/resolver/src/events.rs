@@ -0,0 +1,202 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Events of the health of a resolver, see `AsyncResolver::events`
+
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use futures::sync::mpsc;
+use futures::{Poll, Stream};
+
+use proto::error::ProtoError;
+use proto::op::Query;
+
+use config::Protocol;
+
+/// The events buffered for each subscriber, later events are dropped until the subscriber catches up
+const EVENTS_BUFFER: usize = 64;
+
+/// An event of the resolver, for applications to monitor its health
+#[derive(Clone, Debug)]
+pub enum ResolverEvent {
+    /// A request to the name server failed, it's tried after the others until it succeeds again
+    ///
+    /// Only the first failure is reported, until the name server is up again.
+    NameServerDown {
+        /// The address of the name server
+        name_server: SocketAddr,
+        /// The protocol of the connection to the name server
+        protocol: Protocol,
+        /// The error of the failed request, e.g. a timeout
+        error: ProtoError,
+    },
+    /// A name server which was down answered a request
+    NameServerUp {
+        /// The address of the name server
+        name_server: SocketAddr,
+        /// The protocol of the connection to the name server
+        protocol: Protocol,
+    },
+    /// The least recently used answer was removed from the full cache, for the answer of another
+    ///  query, see `ResolverOpts::cache_size` and `ResolverOpts::cache_max_bytes`
+    CacheEviction {
+        /// The query of the removed answer
+        query: Query,
+    },
+    /// The records of a query failed DNSSEC validation, see `ResolverOpts::validate`
+    ValidationFailure {
+        /// The query which failed
+        query: Query,
+        /// The error of the validation
+        error: ProtoError,
+    },
+    /// The configuration of the resolver was replaced, see `AsyncResolver::reload`
+    ///
+    /// The lookups started after the event use the new configuration.
+    ConfigReload {
+        /// The addresses of the name servers of the new configuration
+        name_servers: Vec<SocketAddr>,
+    },
+}
+
+/// A stream of the events of a resolver, from the time it was subscribed
+///
+/// The events are buffered until they are polled, if the stream falls too far behind the
+///  latest events are dropped. The stream ends when the resolver is dropped.
+#[must_use = "streams do nothing unless polled"]
+pub struct ResolverEvents(mpsc::Receiver<ResolverEvent>);
+
+impl Stream for ResolverEvents {
+    type Item = ResolverEvent;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        self.0.poll()
+    }
+}
+
+/// Sends the events of a resolver to all of its subscribers
+#[derive(Clone, Default)]
+pub(crate) struct EventSender(Arc<Mutex<Vec<mpsc::Sender<ResolverEvent>>>>);
+
+impl EventSender {
+    /// Returns a new stream of all the events sent after this
+    pub(crate) fn subscribe(&self) -> ResolverEvents {
+        let (sender, receiver) = mpsc::channel(EVENTS_BUFFER);
+        self.0.lock().expect("events poisoned").push(sender);
+
+        ResolverEvents(receiver)
+    }
+
+    /// Sends the event to all the subscribers, the event is only built if there are any
+    pub(crate) fn send<F: FnOnce() -> ResolverEvent>(&self, event: F) {
+        let mut subscribers = self.0.lock().expect("events poisoned");
+        if subscribers.is_empty() {
+            return;
+        }
+
+        let event = event();
+        debug!("resolver event: {:?}", event);
+        // the senders are not cloned, each clone of a sender would have a slot of its own
+        let live = subscribers
+            .drain(..)
+            .filter_map(|mut subscriber| match subscriber.try_send(event.clone()) {
+                Ok(()) => Some(subscriber),
+                Err(ref e) if e.is_full() => {
+                    warn!(
+                        "resolver events subscriber is full, dropped event: {:?}",
+                        event
+                    );
+                    Some(subscriber)
+                }
+                // the stream was dropped
+                Err(_) => None,
+            })
+            .collect();
+        *subscribers = live;
+    }
+}
+
+impl fmt::Debug for EventSender {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let len = self
+            .0
+            .lock()
+            .map(|subscribers| subscribers.len())
+            .unwrap_or(0);
+        write!(f, "EventSender({})", len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use futures::{Async, Future, Stream};
+
+    use proto::rr::{Name, RecordType};
+
+    use super::*;
+
+    fn eviction(name: &str) -> ResolverEvent {
+        ResolverEvent::CacheEviction {
+            query: Query::query(Name::from_str(name).unwrap(), RecordType::A),
+        }
+    }
+
+    fn next_name(events: &mut ResolverEvents) -> Option<String> {
+        match events.poll() {
+            Ok(Async::Ready(Some(ResolverEvent::CacheEviction { query }))) => {
+                Some(query.name().to_ascii())
+            }
+            Ok(Async::NotReady) => None,
+            other => panic!("unexpected event: {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_subscribers() {
+        ::futures::future::lazy(|| {
+            let sender = EventSender::default();
+            // not built without subscribers
+            sender.send(|| panic!("event built without subscribers"));
+
+            let mut first = sender.subscribe();
+            sender.send(|| eviction("www.example.com."));
+            let mut second = sender.subscribe();
+            sender.send(|| eviction("ftp.example.com."));
+
+            assert_eq!(next_name(&mut first).unwrap(), "www.example.com.");
+            assert_eq!(next_name(&mut first).unwrap(), "ftp.example.com.");
+            assert_eq!(next_name(&mut first), None);
+            assert_eq!(next_name(&mut second).unwrap(), "ftp.example.com.");
+            assert_eq!(next_name(&mut second), None);
+
+            // dropped subscribers are removed
+            drop(first);
+            sender.send(|| eviction("mail.example.com."));
+            assert_eq!(sender.0.lock().unwrap().len(), 1);
+
+            // a full subscriber drops the latest events
+            for _ in 0..EVENTS_BUFFER * 2 {
+                sender.send(|| eviction("mail.example.com."));
+            }
+            let mut received = 0;
+            while next_name(&mut second).is_some() {
+                received += 1;
+            }
+            assert!(received <= EVENTS_BUFFER + 1);
+            assert!(received > 0);
+
+            Ok::<(), ()>(())
+        })
+        .wait()
+        .unwrap();
+    }
+}
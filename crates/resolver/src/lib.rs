@@ -63,6 +63,21 @@
 //! # }
 //! ```
 //!
+//! ## Using the Resolver without an async runtime
+//!
+//! The `StdResolver` has the same lookup functions, it sends the requests over the blocking sockets of `std::net` on the calling thread, without the `tokio` feature or a reactor.
+//!
+//! ```rust,no_run
+//! # extern crate trust_dns_resolver;
+//! # fn main() {
+//! use trust_dns_resolver::StdResolver;
+//! use trust_dns_resolver::config::*;
+//!
+//! let resolver = StdResolver::new(ResolverConfig::default(), ResolverOpts::default());
+//! let response = resolver.lookup_ip("www.example.com.").unwrap();
+//! # }
+//! ```
+//!
 //! ## Using the host system config
 //!
 //! On Unix systems, the `/etc/resolv.conf` can be used for configuration. Not all options specified in the host systems `resolv.conf` are applicable or compatible with this software. In addition there may be additional options supported which the host system does not. Example:
@@ -181,12 +196,18 @@
 #![warn(missing_docs)]
 #![recursion_limit = "128"]
 
+#[cfg(feature = "async-std")]
+extern crate async_io;
+#[cfg(feature = "async-std")]
+extern crate async_std;
 #[cfg(feature = "dns-over-tls")]
 #[macro_use]
 extern crate cfg_if;
 extern crate failure;
 #[macro_use]
 extern crate futures;
+#[cfg(feature = "async-std")]
+extern crate futures03;
 #[cfg(target_os = "windows")]
 extern crate ipconfig;
 #[macro_use]
@@ -194,6 +215,7 @@ extern crate lazy_static;
 #[macro_use]
 extern crate log;
 extern crate lru_cache;
+extern crate rand;
 extern crate resolv_conf;
 #[cfg(feature = "serde-config")]
 #[macro_use]
@@ -202,6 +224,11 @@ extern crate smallvec;
 #[cfg(any(feature = "tokio", test))]
 extern crate tokio;
 extern crate tokio_executor;
+extern crate tokio_timer;
+#[cfg(feature = "tracing-spans")]
+extern crate tracing;
+#[cfg(feature = "tracing-spans")]
+extern crate tracing_futures;
 #[cfg(feature = "dns-over-https")]
 extern crate trust_dns_https;
 #[cfg(feature = "dns-over-native-tls")]
@@ -212,32 +239,51 @@ pub extern crate trust_dns_proto as proto;
 #[cfg(feature = "dns-over-rustls")]
 extern crate trust_dns_rustls;
 
+pub mod addr_info;
+pub mod address_selection;
 mod async_resolver;
 pub mod config;
 mod dns_lru;
 pub mod dns_sd;
 pub mod error;
+pub mod events;
 mod hosts;
 #[cfg(feature = "dns-over-https")]
 mod https;
 pub mod lookup;
+mod lookup_context;
 pub mod lookup_ip;
 pub mod lookup_state;
 #[doc(hidden)]
 pub mod name_server;
 #[cfg(any(feature = "tokio", test))]
 mod resolver;
+pub mod runtime;
+pub mod search;
+mod std_resolver;
 pub mod system_conf;
+#[cfg(feature = "testing")]
+pub mod testing;
 #[cfg(feature = "dns-over-tls")]
 mod tls;
+mod validation_exceptions;
 
 // reexports from proto
 pub use self::proto::rr::{IntoName, Name, TryParseIp};
 
-pub use async_resolver::{AsyncResolver, Background, BackgroundLookup, BackgroundLookupIp};
+pub use async_resolver::{
+    AsyncResolver, Background, BackgroundLookup, BackgroundLookupIp, LookupDeadline, RuntimeClient,
+};
+pub use dns_lru::{CacheEntry, CacheStats};
+pub use events::{ResolverEvent, ResolverEvents};
 pub use hosts::Hosts;
 #[cfg(any(feature = "tokio", test))]
 pub use resolver::Resolver;
+#[cfg(feature = "async-std")]
+pub use runtime::AsyncStdRuntime;
+pub use runtime::{RuntimeProvider, TokioRuntime};
+pub use std_resolver::StdResolver;
+pub use validation_exceptions::ValidationStats;
 
 /// This is an alias for [`AsyncResolver`], which replaced the type previously
 /// called `ResolverFuture`.
@@ -15,9 +15,10 @@ use std::time::{Duration, Instant};
 use std::vec::IntoIter;
 
 use futures::{future, Async, Future, Poll};
+use tokio_timer::clock;
 
 use proto::error::ProtoError;
-use proto::op::Query;
+use proto::op::{Message, Query};
 use proto::rr::rdata;
 use proto::rr::{Name, RData, Record, RecordType};
 use proto::xfer::{DnsRequest, DnsRequestOptions, DnsResponse};
@@ -30,6 +31,8 @@ use error::*;
 use lookup_ip::LookupIpIter;
 use lookup_state::CachingClient;
 use name_server::{ConnectionHandle, ConnectionProvider, NameServerPool, StandardConnection};
+#[cfg(feature = "dnssec")]
+use validation_exceptions::ValidationExceptions;
 
 /// Result of a DNS query when querying for any record type supported by the Trust-DNS Proto library.
 ///
@@ -39,6 +42,9 @@ pub struct Lookup {
     query: Query,
     records: Arc<Vec<Record>>,
     valid_until: Instant,
+    received: Instant,
+    source: LookupSource,
+    response: Option<Arc<Message>>,
 }
 
 impl Lookup {
@@ -49,16 +55,24 @@ impl Lookup {
     }
 
     /// Return new instance with given records and the maximum TTL.
+    ///
+    /// The source of the lookup is `LookupSource::Static`.
     pub fn new_with_max_ttl(query: Query, records: Arc<Vec<Record>>) -> Self {
-        let valid_until = Instant::now() + Duration::from_secs(u64::from(MAX_TTL));
+        let received = clock::now();
+        let valid_until = received + Duration::from_secs(u64::from(MAX_TTL));
         Lookup {
             query,
             records,
             valid_until,
+            received,
+            source: LookupSource::Static,
+            response: None,
         }
     }
 
     /// Return a new instance with the given records and deadline.
+    ///
+    /// The source of the lookup is `LookupSource::Network`, received now.
     pub fn new_with_deadline(
         query: Query,
         records: Arc<Vec<Record>>,
@@ -68,9 +82,18 @@ impl Lookup {
             query,
             records,
             valid_until,
+            received: clock::now(),
+            source: LookupSource::Network,
+            response: None,
         }
     }
 
+    /// Returns the same lookup with the response message from which its records were taken
+    pub(crate) fn with_response(mut self, response: Arc<Message>) -> Self {
+        self.response = Some(response);
+        self
+    }
+
     /// Returns a reference to the Query that was used to produce this result.
     pub fn query(&self) -> &Query {
         &self.query
@@ -87,10 +110,65 @@ impl Lookup {
     }
 
     /// Returns the `Instant` at which this `Lookup` is no longer valid.
+    ///
+    /// This is the minimum TTL of the records from when they were received, within the minimum
+    ///  and maximum TTLs of the `ResolverOpts`, unless the `ResolverConfig` overrides the TTL.
     pub fn valid_until(&self) -> Instant {
         self.valid_until
     }
 
+    /// Returns the `Instant` at which the records were received, or cached for static records
+    pub fn received(&self) -> Instant {
+        self.received
+    }
+
+    /// Returns where the records of this `Lookup` came from
+    pub fn source(&self) -> LookupSource {
+        self.source
+    }
+
+    /// Returns the response message of the name server, with its header flags, e.g. AD and TC,
+    ///  its authority and additional sections and its EDNS options
+    ///
+    /// The records of the message are those received, before the TTL limits of the cache. For
+    ///  CNAME chains, this is the response to the query of the last name, and for lookups of
+    ///  several record types, e.g. of the A and AAAA records, the response of the first. Static
+    ///  lookups, e.g. from the hosts file, have no response.
+    pub fn response(&self) -> Option<&Message> {
+        self.response.as_ref().map(Arc::as_ref)
+    }
+
+    /// Returns an iterator over the records with their original and remaining TTLs
+    pub fn record_ttls(&self) -> RecordTtlIter {
+        let elapsed = clock::now().duration_since(self.received).as_secs();
+        let elapsed = if elapsed > u64::from(u32::max_value()) {
+            u32::max_value()
+        } else {
+            elapsed as u32
+        };
+
+        RecordTtlIter {
+            records: self.records.iter(),
+            elapsed,
+        }
+    }
+
+    /// Returns the same lookup with other records, e.g. reordered
+    pub(crate) fn with_records(&self, records: Arc<Vec<Record>>) -> Self {
+        Lookup {
+            records,
+            ..self.clone()
+        }
+    }
+
+    /// Marks the lookup as returned from the cache
+    pub(crate) fn cached(mut self) -> Self {
+        if self.source == LookupSource::Network {
+            self.source = LookupSource::Cache;
+        }
+        self
+    }
+
     #[doc(hidden)]
     pub fn is_empty(&self) -> bool {
         self.records.is_empty()
@@ -113,7 +191,77 @@ impl Lookup {
 
         // Choose the sooner deadline of the two lookups.
         let valid_until = min(self.valid_until(), other.valid_until());
-        Self::new_with_deadline(self.query.clone(), Arc::new(records), valid_until)
+        // the result is from the cache if any of the two lookups is
+        let source = match (self.source, other.source) {
+            (LookupSource::Cache, _) | (_, LookupSource::Cache) => LookupSource::Cache,
+            (LookupSource::Network, _) | (_, LookupSource::Network) => LookupSource::Network,
+            _ => LookupSource::Static,
+        };
+
+        Lookup {
+            query: self.query.clone(),
+            records: Arc::new(records),
+            valid_until,
+            received: min(self.received, other.received),
+            source,
+            response: self.response.clone().or(other.response),
+        }
+    }
+}
+
+/// Where the records of a `Lookup` came from
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LookupSource {
+    /// Received from a name server for this lookup
+    Network,
+    /// Returned from the cache, received from a name server for a previous lookup
+    Cache,
+    /// Static records, e.g. from the hosts file or for `localhost`, with the maximum TTL
+    Static,
+}
+
+/// A record of a `Lookup` with its TTLs
+#[derive(Clone, Copy, Debug)]
+pub struct RecordTtl<'a> {
+    record: &'a Record,
+    remaining: u32,
+}
+
+impl<'a> RecordTtl<'a> {
+    /// The record
+    pub fn record(&self) -> &'a Record {
+        self.record
+    }
+
+    /// The TTL of the record when it was received, in seconds
+    ///
+    /// This is within the minimum and maximum TTLs of the `ResolverOpts`, or the TTL override of
+    ///  the `ResolverConfig` for its name.
+    pub fn original(&self) -> u32 {
+        self.record.ttl()
+    }
+
+    /// The TTL of the record remaining now, in seconds
+    pub fn remaining(&self) -> u32 {
+        self.remaining
+    }
+}
+
+/// Borrowed view of the records of a Lookup with their TTLs
+pub struct RecordTtlIter<'a> {
+    records: Iter<'a, Record>,
+    elapsed: u32,
+}
+
+impl<'a> Iterator for RecordTtlIter<'a> {
+    type Item = RecordTtl<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let elapsed = self.elapsed;
+        self.records.next().map(|record| RecordTtl {
+            record,
+            remaining: record.ttl().saturating_sub(elapsed),
+        })
     }
 }
 
@@ -184,7 +332,10 @@ impl Iterator for LookupIntoIter {
 pub enum LookupEither<C: DnsHandle + 'static, P: ConnectionProvider<ConnHandle = C> + 'static> {
     Retry(RetryDnsHandle<NameServerPool<C, P>>),
     #[cfg(feature = "dnssec")]
-    Secure(SecureDnsHandle<RetryDnsHandle<NameServerPool<C, P>>>),
+    Secure(
+        SecureDnsHandle<RetryDnsHandle<NameServerPool<C, P>>>,
+        ValidationExceptions<RetryDnsHandle<NameServerPool<C, P>>>,
+    ),
 }
 
 impl<C: DnsHandle, P: ConnectionProvider<ConnHandle = C>> DnsHandle for LookupEither<C, P> {
@@ -194,15 +345,39 @@ impl<C: DnsHandle, P: ConnectionProvider<ConnHandle = C>> DnsHandle for LookupEi
         match *self {
             LookupEither::Retry(ref c) => c.is_verifying_dnssec(),
             #[cfg(feature = "dnssec")]
-            LookupEither::Secure(ref c) => c.is_verifying_dnssec(),
+            LookupEither::Secure(ref c, _) => c.is_verifying_dnssec(),
         }
     }
 
     fn send<R: Into<DnsRequest>>(&mut self, request: R) -> Self::Response {
         match *self {
             LookupEither::Retry(ref mut c) => c.send(request),
-            #[cfg(feature = "dnssec")]
-            LookupEither::Secure(ref mut c) => c.send(request),
+            #[cfg(all(feature = "dnssec", not(feature = "tracing-spans")))]
+            LookupEither::Secure(ref mut c, ref mut exceptions) => {
+                match exceptions.send(request.into()) {
+                    // the names of the exceptions are not validated
+                    Ok(response) => response,
+                    Err(request) => c.send(request),
+                }
+            }
+            #[cfg(all(feature = "dnssec", feature = "tracing-spans"))]
+            LookupEither::Secure(ref mut c, ref mut exceptions) => {
+                let request = match exceptions.send(request.into()) {
+                    Ok(response) => {
+                        ::tracing::debug!(validation = "exception");
+                        return response;
+                    }
+                    Err(request) => request,
+                };
+
+                Box::new(c.send(request).then(|result| {
+                    match result {
+                        Ok(_) => ::tracing::debug!(validation = "secure"),
+                        Err(ref e) => ::tracing::debug!(validation = "failed", error = %e),
+                    }
+                    result
+                }))
+            }
         }
     }
 }
@@ -374,15 +549,17 @@ impl Iterator for SrvLookupIntoIter {
 }
 
 /// A Future while resolves to the Lookup type
-pub struct SrvLookupFuture(LookupFuture);
+pub struct SrvLookupFuture<C = LookupEither<ConnectionHandle, StandardConnection>>(LookupFuture<C>)
+where
+    C: DnsHandle + 'static;
 
-impl From<LookupFuture> for SrvLookupFuture {
-    fn from(lookup_future: LookupFuture) -> Self {
+impl<C: DnsHandle + 'static> From<LookupFuture<C>> for SrvLookupFuture<C> {
+    fn from(lookup_future: LookupFuture<C>) -> Self {
         SrvLookupFuture(lookup_future)
     }
 }
 
-impl Future for SrvLookupFuture {
+impl<C: DnsHandle + 'static> Future for SrvLookupFuture<C> {
     type Item = SrvLookup;
     type Error = ResolveError;
 
@@ -412,6 +589,16 @@ macro_rules! lookup_type {
             pub fn query(&self) -> &Query {
                 self.0.query()
             }
+
+            /// Returns the `Instant` at which this lookup is no longer valid.
+            pub fn valid_until(&self) -> Instant {
+                self.0.valid_until()
+            }
+
+            /// Returns the inner `Lookup`, e.g. for the TTLs of the records
+            pub fn as_lookup(&self) -> &Lookup {
+                &self.0
+            }
         }
 
         impl From<Lookup> for $l {
@@ -464,15 +651,17 @@ macro_rules! lookup_type {
         }
 
         /// A Future while resolves to the Lookup type
-        pub struct $f(LookupFuture);
+        pub struct $f<C = LookupEither<ConnectionHandle, StandardConnection>>(LookupFuture<C>)
+        where
+            C: DnsHandle + 'static;
 
-        impl From<LookupFuture> for $f {
-            fn from(lookup_future: LookupFuture) -> Self {
+        impl<C: DnsHandle + 'static> From<LookupFuture<C>> for $f<C> {
+            fn from(lookup_future: LookupFuture<C>) -> Self {
                 $f(lookup_future)
             }
         }
 
-        impl Future for $f {
+        impl<C: DnsHandle + 'static> Future for $f<C> {
             type Item = $l;
             type Error = ResolveError;
 
@@ -538,7 +727,7 @@ pub mod tests {
     use futures::{future, Future};
 
     use proto::error::{ProtoErrorKind, ProtoResult};
-    use proto::op::Message;
+    use proto::op::{Message, ResponseCode};
     use proto::rr::{Name, RData, Record, RecordType};
     use proto::xfer::{DnsRequest, DnsRequestOptions};
 
@@ -645,6 +834,8 @@ pub mod tests {
             .kind(),
             ResolveErrorKind::NoRecordsFound {
                 query: Query::query(Name::root(), RecordType::A),
+                response_code: ResponseCode::NoError,
+                authority: None,
                 valid_until: None,
             }
         );
@@ -708,4 +899,41 @@ pub mod tests {
         );
         assert_eq!(lookup.next(), None);
     }
+
+    #[test]
+    fn test_record_ttls_and_source() {
+        let name = Name::from_str("www.example.com.").unwrap();
+        let query = Query::query(name.clone(), RecordType::A);
+        let records = vec![
+            Record::from_rdata(name.clone(), 300, RData::A(Ipv4Addr::new(127, 0, 0, 1))),
+            Record::from_rdata(name.clone(), 60, RData::A(Ipv4Addr::new(127, 0, 0, 2))),
+        ];
+        let valid_until = Instant::now() + Duration::from_secs(60);
+        let lookup = Lookup::new_with_deadline(query.clone(), Arc::new(records), valid_until);
+
+        assert_eq!(lookup.source(), LookupSource::Network);
+        assert_eq!(lookup.valid_until(), valid_until);
+        let ttls = lookup
+            .record_ttls()
+            .map(|ttl| (ttl.original(), ttl.remaining()))
+            .collect::<Vec<_>>();
+        assert_eq!(ttls.len(), 2);
+        assert_eq!(ttls[0].0, 300);
+        assert!(ttls[0].1 <= 300 && ttls[0].1 >= 299);
+        assert_eq!(ttls[1].0, 60);
+        assert!(ttls[1].1 <= 60 && ttls[1].1 >= 59);
+
+        let cached = lookup.clone().cached();
+        assert_eq!(cached.source(), LookupSource::Cache);
+        assert_eq!(cached.received(), lookup.received());
+
+        let static_lookup = Lookup::from_rdata(query, RData::A(Ipv4Addr::new(127, 0, 0, 3)));
+        assert_eq!(static_lookup.source(), LookupSource::Static);
+        assert_eq!(
+            static_lookup.clone().cached().source(),
+            LookupSource::Static
+        );
+        assert_eq!(static_lookup.append(lookup).source(), LookupSource::Network);
+        assert_eq!(static_lookup.append(cached).source(), LookupSource::Cache);
+    }
 }
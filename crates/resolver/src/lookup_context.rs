@@ -0,0 +1,261 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The state shared by the lookups of a resolver, independent of how the connections are made
+
+use std::sync::{Arc, Mutex};
+
+#[cfg(feature = "llmnr")]
+use futures::future;
+use futures::Future;
+#[cfg(feature = "dnssec")]
+use proto::SecureDnsHandle;
+use proto::{
+    error::ProtoResult,
+    op::Query,
+    rr::{Name, RData, Record, RecordType},
+    xfer::{DelayProvider, DnsHandle, DnsRequestOptions, RetryDnsHandle},
+};
+
+use address_selection::PolicyTable;
+use config::{ResolverConfig, ResolverOpts};
+use dns_lru::{self, DnsLru};
+use error::ResolveError;
+use hosts::Hosts;
+use lookup::{Lookup, LookupEither, LookupFuture};
+#[cfg(feature = "llmnr")]
+use lookup_ip::strategic_lookup;
+use lookup_ip::LookupIpFuture;
+use lookup_state::CachingClient;
+#[cfg(feature = "llmnr")]
+use name_server::LlmnrHandle;
+use name_server::{ConnectionProvider, NameServerPool};
+use search::{self, Aliases};
+#[cfg(feature = "dnssec")]
+use validation_exceptions::ValidationExceptions;
+
+/// Builds the lookup futures of a resolver, with the connections of the `ConnectionProvider`
+pub(crate) struct LookupContext<C, P>
+where
+    C: DnsHandle + 'static,
+    P: ConnectionProvider<ConnHandle = C> + 'static,
+{
+    config: ResolverConfig,
+    options: ResolverOpts,
+    client_cache: CachingClient<LookupEither<C, P>>,
+    #[cfg(feature = "llmnr")]
+    llmnr_cache: Option<CachingClient<LlmnrHandle<C, P>>>,
+    hosts: Option<Arc<Hosts>>,
+    aliases: Option<Arc<Aliases>>,
+    address_policy: Option<Arc<PolicyTable>>,
+    delay_provider: Arc<DelayProvider>,
+}
+
+impl<C, P> LookupContext<C, P>
+where
+    C: DnsHandle + 'static,
+    P: ConnectionProvider<ConnHandle = C> + 'static,
+{
+    pub(crate) fn new(
+        config: ResolverConfig,
+        options: ResolverOpts,
+        lru: Arc<Mutex<DnsLru>>,
+        conn_provider: P,
+        delay_provider: Arc<DelayProvider>,
+    ) -> Self {
+        let events = lru.lock().expect("cache poisoned").events().clone();
+        let pool = NameServerPool::from_config_with_provider(
+            &config,
+            &options,
+            &events,
+            conn_provider.clone(),
+        )
+        .with_delay_provider(Arc::clone(&delay_provider));
+        let either;
+        let client = RetryDnsHandle::with_policy(pool, options.retry_policy())
+            .with_delay_provider(Arc::clone(&delay_provider));
+        if options.validate {
+            #[cfg(feature = "dnssec")]
+            {
+                let exceptions = ValidationExceptions::new(
+                    config.validation_exceptions(),
+                    client.clone(),
+                    Arc::clone(&lru),
+                );
+                let mut secure = SecureDnsHandle::new(client);
+                if options.chain_queries {
+                    secure = secure.with_chain_query(Name::root());
+                }
+                either = LookupEither::Secure(secure, exceptions);
+            }
+
+            #[cfg(not(feature = "dnssec"))]
+            {
+                // TODO: should this just be a panic, or a pinned error?
+                warn!("validate option is only available with 'dnssec' feature");
+                either = LookupEither::Retry(client);
+            }
+        } else {
+            either = LookupEither::Retry(client);
+        }
+
+        let hosts = if options.use_hosts_file {
+            Some(Arc::new(Hosts::new()))
+        } else {
+            None
+        };
+
+        let address_policy = config.address_policy().cloned().map(Arc::new);
+        let aliases = if options.use_host_aliases {
+            Some(Arc::new(Aliases::new()))
+        } else {
+            None
+        };
+
+        // LLMNR answers are cached separately, the negative responses of the NameServers for the
+        //  same names would otherwise be returned from the shared cache
+        #[cfg(feature = "llmnr")]
+        let llmnr_cache = if options.use_llmnr {
+            Some(CachingClient::new(
+                options.cache_size,
+                LlmnrHandle::new(options, conn_provider),
+            ))
+        } else {
+            None
+        };
+
+        // the UDP and TCP connections to a name server share its address
+        let mut name_servers = Vec::new();
+        for name_server in config.name_servers() {
+            if !name_servers.contains(&name_server.socket_addr) {
+                name_servers.push(name_server.socket_addr);
+            }
+        }
+
+        LookupContext {
+            config,
+            options,
+            client_cache: CachingClient::with_cache(lru, either).with_name_servers(name_servers),
+            #[cfg(feature = "llmnr")]
+            llmnr_cache,
+            hosts,
+            aliases,
+            address_policy,
+            delay_provider,
+        }
+    }
+
+    pub(crate) fn lookup(
+        &self,
+        name: Name,
+        record_type: RecordType,
+        options: DnsRequestOptions,
+    ) -> LookupFuture<LookupEither<C, P>> {
+        let names = self.build_names(name);
+        LookupFuture::lookup(names, record_type, options, self.client_cache.clone())
+    }
+
+    pub(crate) fn lookup_ip(
+        &self,
+        maybe_name: ProtoResult<Name>,
+        maybe_ip: Option<RData>,
+    ) -> LookupIpFuture<LookupEither<C, P>> {
+        let mut finally_ip_addr: Option<Record> = None;
+
+        // if host is a ip address, return directly.
+        if let Some(ip_addr) = maybe_ip {
+            let name = maybe_name.clone().unwrap_or_default();
+            let record = Record::from_rdata(name.clone(), dns_lru::MAX_TTL, ip_addr.clone());
+
+            // if ndots are greater than 4, then we can't assume the name is an IpAddr
+            //   this accepts IPv6 as well, b/c IPv6 can take the form: 2001:db8::198.51.100.35
+            //   but `:` is not a valid DNS character, so techinically this will fail parsing.
+            //   TODO: should we always do search before returning this?
+            if self.options.ndots > 4 {
+                finally_ip_addr = Some(record);
+            } else {
+                let query = Query::query(name, ip_addr.to_record_type());
+                let lookup = Lookup::new_with_max_ttl(query, Arc::new(vec![record]));
+                return LookupIpFuture::ok(self.client_cache.clone(), lookup);
+            }
+        }
+
+        let name = match (maybe_name, finally_ip_addr.as_ref()) {
+            (Ok(name), _) => name,
+            (Err(_), Some(ip_addr)) => {
+                // it was a valid IP, return that...
+                let query = Query::query(ip_addr.name().clone(), ip_addr.record_type());
+                let lookup = Lookup::new_with_max_ttl(query, Arc::new(vec![ip_addr.clone()]));
+                return LookupIpFuture::ok(self.client_cache.clone(), lookup);
+            }
+            (Err(err), None) => {
+                return LookupIpFuture::error(self.client_cache.clone(), err);
+            }
+        };
+
+        let llmnr_fallback = self.llmnr_lookup(&name);
+        let names = self.build_names(name);
+        let hosts = self.hosts.as_ref().cloned();
+
+        LookupIpFuture::lookup(
+            names,
+            self.options.ip_strategy,
+            self.client_cache.clone(),
+            DnsRequestOptions::default(),
+            hosts,
+            finally_ip_addr.map(Record::unwrap_rdata),
+        )
+        .with_fallback(llmnr_fallback)
+        .with_address_policy(self.address_policy.clone())
+        .with_delay_provider(Arc::clone(&self.delay_provider))
+    }
+
+    /// Returns the LLMNR lookup of a single label name, performed only once it's polled
+    #[cfg(feature = "llmnr")]
+    fn llmnr_lookup(
+        &self,
+        name: &Name,
+    ) -> Option<Box<Future<Item = Lookup, Error = ResolveError> + Send>> {
+        let client = self.llmnr_cache.as_ref()?.clone();
+        if name.num_labels() != 1 {
+            return None;
+        }
+
+        let mut name = name.clone();
+        name.set_fqdn(true);
+        let strategy = self.options.ip_strategy;
+        let delay_provider = Arc::clone(&self.delay_provider);
+
+        Some(Box::new(future::lazy(move || {
+            strategic_lookup(
+                name,
+                strategy,
+                client,
+                DnsRequestOptions::default(),
+                None,
+                delay_provider,
+            )
+        })))
+    }
+
+    #[cfg(not(feature = "llmnr"))]
+    fn llmnr_lookup(
+        &self,
+        _name: &Name,
+    ) -> Option<Box<Future<Item = Lookup, Error = ResolveError> + Send>> {
+        None
+    }
+
+    fn build_names(&self, name: Name) -> Vec<Name> {
+        search::search_stack(
+            &name,
+            &self.config,
+            &self.options,
+            self.aliases.as_ref().map(AsRef::as_ref),
+        )
+    }
+}
@@ -11,17 +11,20 @@
 
 use std::net::IpAddr;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use failure::Fail;
 
+use futures::future::Either;
 use futures::{future, Async, Future, Poll};
+use tokio_timer::clock;
 
 use proto::op::Query;
 use proto::rr::{Name, RData, Record, RecordType};
-use proto::xfer::{DnsHandle, DnsRequestOptions};
+use proto::xfer::{DelayProvider, DnsHandle, DnsRequestOptions, TokioDelay};
 
-use config::LookupIpStrategy;
+use address_selection::{self, PolicyTable};
+use config::{IpFamily, IpPreference, LookupIpStrategy};
 use dns_lru::MAX_TTL;
 use error::*;
 use hosts::Hosts;
@@ -50,6 +53,11 @@ impl LookupIp {
     pub fn valid_until(&self) -> Instant {
         self.0.valid_until()
     }
+
+    /// Returns the inner `Lookup`, e.g. for the TTLs of the records
+    pub fn as_lookup(&self) -> &Lookup {
+        &self.0
+    }
 }
 
 impl From<Lookup> for LookupIp {
@@ -117,6 +125,9 @@ where
     query: Box<Future<Item = Lookup, Error = ResolveError> + Send>,
     hosts: Option<Arc<Hosts>>,
     finally_ip_addr: Option<RData>,
+    fallback: Option<Box<Future<Item = Lookup, Error = ResolveError> + Send>>,
+    address_policy: Option<Arc<PolicyTable>>,
+    delay_provider: Arc<DelayProvider>,
 }
 
 impl<C: DnsHandle + 'static> Future for LookupIpFuture<C> {
@@ -150,10 +161,24 @@ impl<C: DnsHandle + 'static> Future for LookupIpFuture<C> {
                         self.client_cache.clone(),
                         self.options.clone(),
                         self.hosts.clone(),
+                        Arc::clone(&self.delay_provider),
                     );
                     // Continue looping with the new query. It will be polled
                     // on the next iteration of the loop.
                     continue;
+                } else if let Some(fallback) = self.fallback.take() {
+                    // All names failed, try the fallback lookup. If that fails as well, the
+                    // result of the last name is returned, which is more meaningful.
+                    let last = match query {
+                        Ok(Async::Ready(lookup)) => Ok(lookup),
+                        Ok(Async::NotReady) => unreachable!("NotReady is returned above"),
+                        Err(e) => Err(e),
+                    };
+                    self.query = Box::new(fallback.then(move |result| match result {
+                        Ok(ref lookup) if !lookup.is_empty() => result,
+                        _ => last,
+                    }));
+                    continue;
                 } else if let Some(ip_addr) = self.finally_ip_addr.take() {
                     // Otherwise, if there's an IP address to fall back to,
                     // we'll return it.
@@ -166,7 +191,12 @@ impl<C: DnsHandle + 'static> Future for LookupIpFuture<C> {
             // If we didn't have to retry the query, or we weren't able to
             // retry because we've exhausted the names to search and have no
             // fallback IP address, return the current query.
-            return query.map(|async| async.map(LookupIp::from));
+            return query.map(|async| {
+                async.map(|lookup| match self.address_policy {
+                    Some(ref policy) => address_selection::sort_lookup(&lookup, policy).into(),
+                    None => LookupIp::from(lookup),
+                })
+            });
             // If we skipped retrying the  query, this will return the
             // successful lookup, otherwise, if the retry failed, this will
             // return the last  query result --- either an empty lookup or the
@@ -206,9 +236,33 @@ where
             options,
             hosts,
             finally_ip_addr,
+            fallback: None,
+            address_policy: None,
+            delay_provider: Arc::new(TokioDelay),
         }
     }
 
+    /// Sets the lookup to perform when all names fail to resolve, e.g. over LLMNR
+    pub(crate) fn with_fallback(
+        mut self,
+        fallback: Option<Box<Future<Item = Lookup, Error = ResolveError> + Send>>,
+    ) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    /// Sets the policy with which the addresses of the result are sorted
+    pub(crate) fn with_address_policy(mut self, address_policy: Option<Arc<PolicyTable>>) -> Self {
+        self.address_policy = address_policy;
+        self
+    }
+
+    /// Waits the timeouts of the families with the timers of the provider, Tokio by default
+    pub(crate) fn with_delay_provider(mut self, delay_provider: Arc<DelayProvider>) -> Self {
+        self.delay_provider = delay_provider;
+        self
+    }
+
     pub(crate) fn error<E: Fail>(client_cache: CachingClient<C>, error: E) -> Self {
         LookupIpFuture {
             // errors on names don't need to be cheap... i.e. this clone is unfortunate in this case.
@@ -221,6 +275,9 @@ where
             )),
             hosts: None,
             finally_ip_addr: None,
+            fallback: None,
+            address_policy: None,
+            delay_provider: Arc::new(TokioDelay),
         }
     }
 
@@ -233,16 +290,20 @@ where
             query: Box::new(future::ok(lp)),
             hosts: None,
             finally_ip_addr: None,
+            fallback: None,
+            address_policy: None,
+            delay_provider: Arc::new(TokioDelay),
         }
     }
 }
 /// returns a new future for lookup
-fn strategic_lookup<C: DnsHandle + 'static>(
+pub(crate) fn strategic_lookup<C: DnsHandle + 'static>(
     name: Name,
     strategy: LookupIpStrategy,
     client: CachingClient<C>,
     options: DnsRequestOptions,
     hosts: Option<Arc<Hosts>>,
+    delay_provider: Arc<DelayProvider>,
 ) -> Box<Future<Item = Lookup, Error = ResolveError> + Send> {
     match strategy {
         LookupIpStrategy::Ipv4Only => ipv4_only(name, client, options, hosts),
@@ -250,6 +311,9 @@ fn strategic_lookup<C: DnsHandle + 'static>(
         LookupIpStrategy::Ipv4AndIpv6 => ipv4_and_ipv6(name, client, options, hosts),
         LookupIpStrategy::Ipv6thenIpv4 => ipv6_then_ipv4(name, client, options, hosts),
         LookupIpStrategy::Ipv4thenIpv6 => ipv4_then_ipv6(name, client, options, hosts),
+        LookupIpStrategy::Preference(preference) => {
+            preference_lookup(name, preference, client, options, hosts, delay_provider)
+        }
     }
 }
 
@@ -414,6 +478,159 @@ fn rt_then_swap<C: DnsHandle + 'static>(
     )
 }
 
+/// The lookup of the addresses of a family, which succeeds with its result, so that the result of
+///  each family is kept while the other is queried
+type FamilyLookup = Box<Future<Item = ResolveResult<Lookup>, Error = ResolveError> + Send>;
+
+/// queries for the families as configured, the addresses of the first family first
+fn preference_lookup<C: DnsHandle + 'static>(
+    name: Name,
+    preference: IpPreference,
+    client: CachingClient<C>,
+    options: DnsRequestOptions,
+    hosts: Option<Arc<Hosts>>,
+    delay_provider: Arc<DelayProvider>,
+) -> Box<Future<Item = Lookup, Error = ResolveError> + Send> {
+    let (first, second) = match preference.first {
+        IpFamily::Ipv4 => (IpFamily::Ipv4, IpFamily::Ipv6),
+        IpFamily::Ipv6 => (IpFamily::Ipv6, IpFamily::Ipv4),
+    };
+    let (first_min, second_min) = (
+        preference.min_answers(first),
+        preference.min_answers(second),
+    );
+
+    let first_lookup = family_lookup(
+        name.clone(),
+        first,
+        preference.timeout(first),
+        client.clone(),
+        options.clone(),
+        hosts.clone(),
+        Arc::clone(&delay_provider),
+    );
+    let second_lookup = future::lazy(move || {
+        family_lookup(
+            name,
+            second,
+            preference.timeout(second),
+            client,
+            options,
+            hosts,
+            delay_provider,
+        )
+    });
+
+    if !preference.concurrent {
+        return Box::new(first_lookup.and_then(move |first_result| {
+            if is_usable(&first_result, first_min) {
+                Either::A(future::result(first_result))
+            } else {
+                Either::B(
+                    second_lookup.and_then(move |second_result| merge(first_result, second_result)),
+                )
+            }
+        }));
+    }
+
+    // the second lookup is polled, and so sent, with the first
+    let second_lookup = Box::new(second_lookup) as FamilyLookup;
+    if !preference.first_usable {
+        return Box::new(
+            first_lookup
+                .join(second_lookup)
+                .and_then(|(first_result, second_result)| merge(first_result, second_result)),
+        );
+    }
+
+    Box::new(
+        first_lookup
+            .select2(second_lookup)
+            .map_err(|e| e.split().0)
+            .and_then(move |either| -> FamilyLookup {
+                match either {
+                    Either::A((first_result, second_lookup)) => {
+                        if is_usable(&first_result, first_min) {
+                            Box::new(future::ok::<_, ResolveError>(first_result))
+                        } else {
+                            Box::new(
+                                second_lookup
+                                    .map(move |second_result| merge(first_result, second_result)),
+                            )
+                        }
+                    }
+                    Either::B((second_result, first_lookup)) => {
+                        if is_usable(&second_result, second_min) {
+                            Box::new(future::ok::<_, ResolveError>(second_result))
+                        } else {
+                            Box::new(
+                                first_lookup
+                                    .map(move |first_result| merge(first_result, second_result)),
+                            )
+                        }
+                    }
+                }
+            })
+            .and_then(future::result),
+    )
+}
+
+/// queries for the addresses of the family, the future always succeeds with the result
+fn family_lookup<C: DnsHandle + 'static>(
+    name: Name,
+    family: IpFamily,
+    timeout: Option<Duration>,
+    client: CachingClient<C>,
+    options: DnsRequestOptions,
+    hosts: Option<Arc<Hosts>>,
+    delay_provider: Arc<DelayProvider>,
+) -> FamilyLookup {
+    let lookup = match family {
+        IpFamily::Ipv4 => ipv4_only(name, client, options, hosts),
+        IpFamily::Ipv6 => ipv6_only(name, client, options, hosts),
+    };
+
+    match timeout {
+        Some(timeout) => {
+            let delay = delay_provider.delay(clock::now() + timeout);
+            Box::new(lookup.select2(delay).then(|result| {
+                let result = match result {
+                    Ok(Either::A((lookup, _))) => Ok(lookup),
+                    Err(Either::A((e, _))) => Err(e),
+                    // the deadline passed, or the timer failed
+                    Ok(Either::B(_)) | Err(Either::B(_)) => Err(ResolveErrorKind::Timeout.into()),
+                };
+                Ok::<_, ResolveError>(result)
+            }))
+        }
+        None => Box::new(lookup.then(Ok::<_, ResolveError>)),
+    }
+}
+
+/// true if the lookup has at least the minimum number of addresses
+fn is_usable(result: &ResolveResult<Lookup>, min_answers: usize) -> bool {
+    match *result {
+        Ok(ref lookup) => {
+            let addresses = lookup
+                .iter()
+                .filter(|rdata| rdata.to_ip_addr().is_some())
+                .count();
+            addresses > 0 && addresses >= min_answers
+        }
+        Err(_) => false,
+    }
+}
+
+/// the addresses of both families, the failure of one is ignored, the error of the second family
+///  is returned if both failed
+fn merge(first: ResolveResult<Lookup>, second: ResolveResult<Lookup>) -> ResolveResult<Lookup> {
+    match (first, second) {
+        (Ok(first), Ok(second)) => Ok(first.append(second)),
+        (Ok(first), Err(_)) => Ok(first),
+        (Err(_), second) => second,
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
     use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
@@ -701,4 +918,156 @@ pub mod tests {
             vec![Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1)]
         );
     }
+
+    #[test]
+    fn test_fallback_lookup() {
+        let names = vec![Name::from_ascii("printer.").unwrap()];
+        let record = Record::from_rdata(
+            Name::from_ascii("printer.").unwrap(),
+            30,
+            RData::A(Ipv4Addr::new(192, 168, 0, 2)),
+        );
+
+        // the name fails to resolve, the fallback succeeds
+        let fallback = future::ok(Lookup::new_with_max_ttl(
+            Query::query(Name::from_ascii("printer.").unwrap(), RecordType::A),
+            Arc::new(vec![record]),
+        ));
+        assert_eq!(
+            LookupIpFuture::lookup(
+                names.clone(),
+                LookupIpStrategy::Ipv4Only,
+                CachingClient::new(0, mock(vec![error()])),
+                Default::default(),
+                None,
+                None,
+            )
+            .with_fallback(Some(Box::new(fallback)))
+            .wait()
+            .unwrap()
+            .iter()
+            .collect::<Vec<IpAddr>>(),
+            vec![Ipv4Addr::new(192, 168, 0, 2)]
+        );
+
+        // both fail, the error of the name is returned
+        let fallback = future::err(ResolveErrorKind::Message("no llmnr responder").into());
+        let error = LookupIpFuture::lookup(
+            names,
+            LookupIpStrategy::Ipv4Only,
+            CachingClient::new(0, mock(vec![error()])),
+            Default::default(),
+            None,
+            None,
+        )
+        .with_fallback(Some(Box::new(fallback)))
+        .wait()
+        .unwrap_err();
+        assert!(format!("{:?}", error).contains("forced test failure"));
+    }
+
+    fn preference_ips<H: DnsHandle>(preference: IpPreference, handle: H) -> Vec<IpAddr> {
+        preference_lookup(
+            Name::root(),
+            preference,
+            CachingClient::new(0, handle),
+            Default::default(),
+            None,
+            Arc::new(TokioDelay),
+        )
+        .wait()
+        .unwrap()
+        .iter()
+        .map(|r| r.to_ip_addr().unwrap())
+        .collect()
+    }
+
+    #[test]
+    fn test_preference_strategy() {
+        let v4 = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+        let v6 = IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1));
+
+        // the other family is not queried
+        let preference = IpPreference::ipv6_then_ipv4();
+        let handle = mock(vec![v4_message(), v6_message()]);
+        assert_eq!(preference_ips(preference, handle.clone()), vec![v6]);
+        assert_eq!(handle.messages.lock().unwrap().len(), 1);
+
+        // too few ipv4 addresses, ipv6 is queried
+        let preference = IpPreference {
+            ipv4_min_answers: 2,
+            ..IpPreference::ipv4_then_ipv6()
+        };
+        let handle = mock(vec![v6_message(), v4_message()]);
+        assert_eq!(preference_ips(preference, handle), vec![v4, v6]);
+
+        // both families, the first family first
+        let preference = IpPreference {
+            concurrent: true,
+            ..IpPreference::ipv6_then_ipv4()
+        };
+        let handle = mock(vec![v4_message(), v6_message()]);
+        assert_eq!(preference_ips(preference, handle), vec![v6, v4]);
+
+        // the failure of a family is ignored
+        let handle = mock(vec![v4_message(), error()]);
+        assert_eq!(preference_ips(preference, handle), vec![v4]);
+
+        // the first usable answer
+        let preference = IpPreference {
+            concurrent: true,
+            first_usable: true,
+            ..IpPreference::ipv4_then_ipv6()
+        };
+        let handle = mock(vec![v6_message(), v4_message()]);
+        assert_eq!(preference_ips(preference, handle), vec![v4]);
+
+        // the first answer is not usable, the other is waited for
+        let handle = mock(vec![v6_message(), empty()]);
+        assert_eq!(preference_ips(preference, handle), vec![v6]);
+    }
+
+    /// Answers the A queries, never the AAAA queries
+    #[derive(Clone)]
+    struct Ipv4Only;
+
+    impl DnsHandle for Ipv4Only {
+        type Response = Box<Future<Item = DnsResponse, Error = ProtoError> + Send>;
+
+        fn send<R: Into<DnsRequest>>(&mut self, request: R) -> Self::Response {
+            let request = request.into();
+            match request.queries()[0].query_type() {
+                RecordType::A => Box::new(future::result(v4_message())),
+                _ => Box::new(future::empty::<DnsResponse, ProtoError>()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_preference_timeout() {
+        use tokio::runtime::current_thread::Runtime;
+
+        let preference = IpPreference {
+            concurrent: true,
+            ipv6_timeout: Some(Duration::from_millis(50)),
+            ..IpPreference::ipv6_then_ipv4()
+        };
+        let lookup = preference_lookup(
+            Name::root(),
+            preference,
+            CachingClient::new(0, Ipv4Only),
+            Default::default(),
+            None,
+            Arc::new(TokioDelay),
+        );
+
+        let lookup = Runtime::new().unwrap().block_on(lookup).unwrap();
+        assert_eq!(
+            lookup
+                .iter()
+                .map(|r| r.to_ip_addr().unwrap())
+                .collect::<Vec<_>>(),
+            vec![IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1))]
+        );
+    }
 }
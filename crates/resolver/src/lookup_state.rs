@@ -9,13 +9,18 @@
 
 use std::borrow::Cow;
 use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
 use std::mem;
-use std::net::{Ipv4Addr, Ipv6Addr};
-use std::sync::{Arc, Mutex, TryLockError};
-use std::time::Instant;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex, TryLockError, Weak};
 
+use futures::future::Shared;
 use futures::{future, task, Async, Future, Poll};
+use tokio_timer::clock;
 
+use proto::error::{ProtoError, ProtoErrorCode};
+use proto::hash::NameHasher;
 use proto::op::{Message, Query, ResponseCode};
 use proto::rr::domain::usage::{
     ResolverUsage, DEFAULT, INVALID, IN_ADDR_ARPA_127, IP6_ARPA_1, LOCAL,
@@ -27,6 +32,7 @@ use proto::xfer::{DnsHandle, DnsRequestOptions, DnsResponse};
 use dns_lru;
 use dns_lru::DnsLru;
 use error::*;
+use events::ResolverEvent;
 use lookup::Lookup;
 
 const MAX_QUERY_DEPTH: u8 = 7; // arbitrarily chosen number...
@@ -49,6 +55,63 @@ pub struct CachingClient<C: DnsHandle> {
     // TODO: switch to FuturesMutex (Mutex will have some undesireable locking)
     lru: Arc<Mutex<DnsLru>>,
     client: C,
+    in_flight: Arc<InFlight>,
+    name_servers: Arc<Vec<SocketAddr>>,
+}
+
+type SharedLookup = Shared<Box<Future<Item = Lookup, Error = ResolveError> + Send>>;
+
+/// The lookups in progress, concurrent identical lookups share the same future
+///
+/// Each of the lookups holds a reference to the token of the shared lookup, it's canceled once
+///  all of them are dropped.
+#[derive(Default)]
+struct InFlight(Mutex<HashMap<(Query, bool), (SharedLookup, Weak<()>)>>);
+
+/// One of the lookups sharing an in flight lookup, cloning its result
+struct Waiter {
+    key: (Query, bool),
+    lookup: SharedLookup,
+    in_flight: Arc<InFlight>,
+    token: Option<Arc<()>>,
+}
+
+impl Future for Waiter {
+    type Item = Lookup;
+    type Error = ResolveError;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        match self.lookup.poll() {
+            Ok(Async::Ready(lookup)) => Ok(Async::Ready((*lookup).clone())),
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Err(e) => Err((*e).clone()),
+        }
+    }
+}
+
+impl Drop for Waiter {
+    fn drop(&mut self) {
+        drop(self.token.take());
+
+        // the last of the lookups to be dropped cancels the shared lookup, i.e. its requests and retries
+        if let Ok(mut in_flight) = self.in_flight.0.lock() {
+            let canceled = in_flight
+                .get(&self.key)
+                .map_or(false, |&(_, ref token)| token.upgrade().is_none());
+
+            if canceled {
+                debug!("canceling in flight lookup: {}", self.key.0);
+                in_flight.remove(&self.key);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for InFlight {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let len = self.0.lock().map(|in_flight| in_flight.len()).unwrap_or(0);
+        write!(f, "InFlight({})", len)
+    }
 }
 
 impl<C: DnsHandle + 'static> CachingClient<C> {
@@ -60,15 +123,115 @@ impl<C: DnsHandle + 'static> CachingClient<C> {
         )
     }
 
+    #[doc(hidden)]
+    pub fn with_hasher(max_size: usize, client: C, hasher: Arc<dyn NameHasher>) -> Self {
+        Self::with_cache(
+            Arc::new(Mutex::new(
+                DnsLru::new(max_size, Default::default()).with_hasher(hasher),
+            )),
+            client,
+        )
+    }
+
     pub(crate) fn with_cache(lru: Arc<Mutex<DnsLru>>, client: C) -> Self {
-        CachingClient { lru, client }
+        CachingClient {
+            lru,
+            client,
+            in_flight: Arc::new(InFlight::default()),
+            name_servers: Arc::new(vec![]),
+        }
+    }
+
+    /// Sets the name servers of the client, on the errors of the lookups none of them answered
+    pub(crate) fn with_name_servers(mut self, name_servers: Vec<SocketAddr>) -> Self {
+        self.name_servers = Arc::new(name_servers);
+        self
     }
 
     /// Perform a lookup against this caching client, looking first in the cache for a result
+    ///
+    /// Concurrent lookups of the same query, from any clone of this client, share the
+    ///  same request and its result. Lookups with configured EDNS are never shared.
+    ///
+    /// With the `tracing-spans` feature, the lookup is in a `query` span of its name and type.
     pub fn lookup(
         &mut self,
         query: Query,
         options: DnsRequestOptions,
+    ) -> Box<Future<Item = Lookup, Error = ResolveError> + Send> {
+        #[cfg(feature = "tracing-spans")]
+        let span = ::tracing::info_span!(
+            "query",
+            name = %query.name(),
+            query_type = %query.query_type()
+        );
+
+        let lookup = self.coalesced_lookup(query, options);
+
+        #[cfg(feature = "tracing-spans")]
+        let lookup = Box::new(::tracing_futures::Instrument::instrument(lookup, span));
+
+        lookup
+    }
+
+    fn coalesced_lookup(
+        &mut self,
+        query: Query,
+        options: DnsRequestOptions,
+    ) -> Box<Future<Item = Lookup, Error = ResolveError> + Send> {
+        if options.edns.is_some() {
+            return Box::new(self.uncoalesced_lookup(query, options));
+        }
+
+        let key = (query.clone(), options.expects_multiple_responses);
+        let in_flight_lookups = Arc::clone(&self.in_flight);
+        let mut in_flight = in_flight_lookups
+            .0
+            .lock()
+            .expect("in flight lookups poisoned");
+
+        if let Some(&(ref shared, ref token)) = in_flight.get(&key) {
+            if let Some(token) = token.upgrade() {
+                debug!("joining in flight lookup: {}", query);
+                return Box::new(Waiter {
+                    key,
+                    lookup: shared.clone(),
+                    in_flight: Arc::clone(&in_flight_lookups),
+                    token: Some(token),
+                });
+            }
+        }
+
+        let remove_key = key.clone();
+        let remove_from = Arc::clone(&in_flight_lookups);
+        let lookup: Box<Future<Item = Lookup, Error = ResolveError> + Send> =
+            Box::new(self.uncoalesced_lookup(query, options).then(move |result| {
+                remove_from
+                    .0
+                    .lock()
+                    .expect("in flight lookups poisoned")
+                    .remove(&remove_key);
+                result
+            }));
+        let shared = lookup.shared();
+        let token = Arc::new(());
+        in_flight.insert(key.clone(), (shared.clone(), Arc::downgrade(&token)));
+
+        Box::new(Waiter {
+            key,
+            lookup: shared,
+            in_flight: Arc::clone(&in_flight_lookups),
+            token: Some(token),
+        })
+    }
+
+    /// Perform a lookup which isn't shared with concurrent lookups of the same query
+    ///
+    /// This is used for the CNAME chains of lookups, which must not wait on themselves.
+    fn uncoalesced_lookup(
+        &mut self,
+        query: Query,
+        options: DnsRequestOptions,
     ) -> Box<Future<Item = Lookup, Error = ResolveError> + Send> {
         // see https://tools.ietf.org/html/rfc6761
         //
@@ -125,12 +288,11 @@ impl<C: DnsHandle + 'static> CachingClient<C> {
             }
         }
 
-        Box::new(QueryState::lookup(
-            query,
-            options,
-            &mut self.client,
-            self.lru.clone(),
-        ))
+        let name_servers = Arc::clone(&self.name_servers);
+        Box::new(
+            QueryState::lookup(query.clone(), options, &mut self.client, self.lru.clone())
+                .map_err(move |error| error.with_query(query).with_name_servers(&name_servers)),
+        )
     }
 }
 
@@ -156,7 +318,7 @@ impl Future for FromCache {
             Err(TryLockError::Poisoned(poison)) => {
                 Err(ResolveErrorKind::Msg(format!("poisoned: {}", poison)).into())
             }
-            Ok(mut lru) => Ok(Async::Ready(lru.get(&self.query, Instant::now()))),
+            Ok(mut lru) => Ok(Async::Ready(lru.get(&self.query, clock::now()))),
         }
     }
 }
@@ -173,10 +335,14 @@ struct QueryFuture<C: DnsHandle + 'static> {
 }
 
 enum Records {
-    /// The records exists, a vec of rdata with ttl
-    Exists(Vec<(Record, u32)>),
-    /// Records do not exist, ttl for negative caching
-    NoData { ttl: Option<u32> },
+    /// The records exists, a vec of rdata with ttl, and the response they were taken from
+    Exists(Vec<(Record, u32)>, Arc<Message>),
+    /// Records do not exist, ttl for negative caching, with the SOA and the proofs of the response
+    NoData {
+        ttl: Option<u32>,
+        response_code: ResponseCode,
+        authority: Option<Box<NegativeAuthority>>,
+    },
     /// Future lookup for recursive cname records
     CnameChain {
         next: Box<Future<Item = Lookup, Error = ResolveError> + Send>,
@@ -196,7 +362,7 @@ impl<C: DnsHandle + 'static> QueryFuture<C> {
             QUERY_DEPTH.with(|c| *c.borrow_mut() += 1);
 
             Records::CnameChain {
-                next: self.client.lookup(query, self.options.clone()),
+                next: self.client.uncoalesced_lookup(query, self.options.clone()),
                 min_ttl: cname_ttl,
             }
         }
@@ -206,6 +372,9 @@ impl<C: DnsHandle + 'static> QueryFuture<C> {
         // initial ttl is what CNAMES for min usage
         const INITIAL_TTL: u32 = dns_lru::MAX_TTL;
 
+        // the records are taken from the response, it is kept whole for the lookup
+        let message = Arc::new(Message::clone(&response));
+
         // seek out CNAMES, this is only performed if the query is not a CNAME, ANY, or SRV
         let (search_name, cname_ttl, was_cname) = {
             // this will only search for CNAMEs if the request was not meant to be for one of the triggers for recursion
@@ -291,7 +460,7 @@ impl<C: DnsHandle + 'static> QueryFuture<C> {
                 .collect::<Vec<_>>();
 
             if !records.is_empty() {
-                return Ok(Async::Ready(Records::Exists(records)));
+                return Ok(Async::Ready(Records::Exists(records, message)));
             }
 
             (search_name.into_owned(), cname_ttl, was_cname)
@@ -327,23 +496,41 @@ impl<C: DnsHandle + 'static> QueryFuture<C> {
     /// * `message` - message to extract SOA, etc, from for caching failed requests
     /// * `valid_nsec` - species that in DNSSec mode, this request is safe to cache
     fn handle_nxdomain(&self, mut message: DnsResponse, valid_nsec: bool) -> Records {
-        if valid_nsec || !self.dnssec {
-            //  if there were validated NSEC records
-            let soa = message
-                .take_name_servers()
-                .into_iter()
-                .find(|r| r.rr_type() == RecordType::SOA);
-
-            let ttl = if let Some(RData::SOA(soa)) = soa.map(Record::unwrap_rdata) {
+        let response_code = message.response_code();
+        let authority = message.take_name_servers();
+        let soa = authority
+            .iter()
+            .find(|r| r.rr_type() == RecordType::SOA)
+            .cloned();
+
+        //  if there were validated NSEC records
+        let ttl = if valid_nsec || !self.dnssec {
+            if let Some(RData::SOA(ref soa)) = soa.as_ref().map(|soa| soa.rdata()) {
                 Some(soa.minimum())
             } else {
                 // TODO: figure out a looping lookup to get SOA
                 None
-            };
+            }
+        } else {
+            None
+        };
 
-            Records::NoData { ttl }
+        let negative_proofs = if self.dnssec {
+            authority.into_iter().filter(is_negative_proof).collect()
         } else {
-            Records::NoData { ttl: None }
+            vec![]
+        };
+
+        let authority = if soa.is_some() || !negative_proofs.is_empty() {
+            Some(Box::new(NegativeAuthority::new(soa, negative_proofs)))
+        } else {
+            None
+        };
+
+        Records::NoData {
+            ttl,
+            response_code,
+            authority,
         }
     }
 }
@@ -363,15 +550,58 @@ impl<C: DnsHandle + 'static> Future for QueryFuture<C> {
                         message, false, /* false b/c DNSSec should not cache NXDomain */
                     ))),
                     ResponseCode::NoError => self.handle_noerror(message),
-                    r => Err(ResolveErrorKind::Msg(format!("DNS Error: {}", r)).into()),
+                    r => Err(ResolveErrorKind::ResponseCode(r).into()),
                 }
             }
             Ok(Async::NotReady) => Ok(Async::NotReady),
-            Err(err) => Err(err.into()),
+            Err(err) => {
+                if self.dnssec && is_validation_failure(&err) {
+                    if let Ok(cache) = self.cache.lock() {
+                        cache.events().send(|| ResolverEvent::ValidationFailure {
+                            query: self.query.clone(),
+                            error: err.clone(),
+                        });
+                    }
+                }
+
+                Err(err.into())
+            }
         }
     }
 }
 
+/// True if the record is an NSEC or NSEC3 record, or the RRSIG of one
+#[cfg(feature = "dnssec")]
+fn is_negative_proof(record: &Record) -> bool {
+    use proto::rr::dnssec::rdata::{DNSSECRData, DNSSECRecordType};
+
+    let is_nsec = |rtype| {
+        rtype == RecordType::DNSSEC(DNSSECRecordType::NSEC)
+            || rtype == RecordType::DNSSEC(DNSSECRecordType::NSEC3)
+    };
+    if let RData::DNSSEC(DNSSECRData::SIG(ref sig)) = *record.rdata() {
+        is_nsec(sig.type_covered())
+    } else {
+        is_nsec(record.rr_type())
+    }
+}
+
+/// Without DNSSEC the responses are not validated, they have no proofs
+#[cfg(not(feature = "dnssec"))]
+fn is_negative_proof(_record: &Record) -> bool {
+    false
+}
+
+/// True if the error of a validating client is a failure to validate the records, and not of the
+///  connections to the name servers
+fn is_validation_failure(error: &ProtoError) -> bool {
+    let code = error.code();
+    code == ProtoErrorCode::Insecure
+        || code == ProtoErrorCode::UnsupportedAlgorithm
+        || code == ProtoErrorCode::Crypto
+        || code == ProtoErrorCode::Other
+}
+
 struct InsertCache {
     rdatas: Records,
     query: Query,
@@ -397,12 +627,22 @@ impl Future for InsertCache {
             Ok(mut lru) => {
                 // this will put this object into an inconsistent state, but no one should call poll again...
                 let query = mem::replace(&mut self.query, Query::new());
-                let rdata = mem::replace(&mut self.rdatas, Records::NoData { ttl: None });
+                let rdata = mem::replace(
+                    &mut self.rdatas,
+                    Records::NoData {
+                        ttl: None,
+                        response_code: ResponseCode::NoError,
+                        authority: None,
+                    },
+                );
 
                 match rdata {
-                    Records::Exists(rdata) => {
-                        Ok(Async::Ready(lru.insert(query, rdata, Instant::now())))
-                    }
+                    Records::Exists(rdata, response) => Ok(Async::Ready(lru.insert(
+                        query,
+                        rdata,
+                        Some(response),
+                        clock::now(),
+                    ))),
                     Records::Chained {
                         cached: lookup,
                         min_ttl: ttl,
@@ -410,14 +650,24 @@ impl Future for InsertCache {
                         query,
                         lookup,
                         ttl,
-                        Instant::now(),
+                        clock::now(),
                     ))),
-                    Records::NoData { ttl: Some(ttl) } => {
-                        Err(lru.negative(query, ttl, Instant::now()))
-                    }
-                    Records::NoData { ttl: None } | Records::CnameChain { .. } => {
-                        Err(DnsLru::nx_error(query, None))
+                    Records::NoData {
+                        ttl,
+                        response_code,
+                        authority,
+                    } => {
+                        let valid_until =
+                            ttl.map(|ttl| lru.negative(query.clone(), ttl, clock::now()));
+                        Err(ResolveErrorKind::NoRecordsFound {
+                            query,
+                            response_code,
+                            authority,
+                            valid_until,
+                        }
+                        .into())
                     }
+                    Records::CnameChain { .. } => Err(DnsLru::nx_error(query, None)),
                 }
             }
         }
@@ -580,8 +830,15 @@ impl<C: DnsHandle + 'static> Future for QueryState<C> {
             QueryState::FromCache(ref mut from_cache, ..) => {
                 match from_cache.poll() {
                     // need to query since it wasn't in the cache
-                    Ok(Async::Ready(None)) => (), // handled below
-                    Ok(Async::Ready(Some(ips))) => return Ok(Async::Ready(ips)),
+                    Ok(Async::Ready(None)) => {
+                        #[cfg(feature = "tracing-spans")]
+                        ::tracing::debug!(cache = "miss");
+                    } // handled below
+                    Ok(Async::Ready(Some(ips))) => {
+                        #[cfg(feature = "tracing-spans")]
+                        ::tracing::debug!(cache = "hit");
+                        return Ok(Async::Ready(ips));
+                    }
                     Ok(Async::NotReady) => return Ok(Async::NotReady),
                     Err(error) => return Err(error),
                 };
@@ -655,6 +912,7 @@ impl<C: DnsHandle + 'static> Future for QueryState<C> {
 mod tests {
     use std::net::*;
     use std::str::FromStr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
     use std::time::*;
 
     use futures::future;
@@ -663,10 +921,101 @@ mod tests {
     use proto::op::{Message, Query};
     use proto::rr::rdata::SRV;
     use proto::rr::{Name, Record};
+    use proto::xfer::DnsRequest;
 
     use super::*;
     use lookup_ip::tests::*;
 
+    #[derive(Clone, Default)]
+    struct CountingDnsHandle {
+        sent: Arc<Mutex<usize>>,
+    }
+
+    impl DnsHandle for CountingDnsHandle {
+        type Response = Box<Future<Item = DnsResponse, Error = ProtoError> + Send>;
+
+        fn send<R: Into<DnsRequest>>(&mut self, _: R) -> Self::Response {
+            *self.sent.lock().unwrap() += 1;
+            Box::new(future::err(ProtoError::from("forced test failure")))
+        }
+    }
+
+    #[test]
+    fn test_concurrent_lookups_are_coalesced() {
+        let handle = CountingDnsHandle::default();
+        let mut client = CachingClient::new(1, handle.clone());
+        let mut other_client = client.clone();
+        let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+
+        let first = client.lookup(query.clone(), Default::default());
+        let second = other_client.lookup(query.clone(), Default::default());
+        let results = first
+            .then(Ok::<_, ()>)
+            .join(second.then(Ok::<_, ()>))
+            .wait()
+            .unwrap();
+
+        assert!(results.0.is_err());
+        assert!(results.1.is_err());
+        assert_eq!(*handle.sent.lock().unwrap(), 1);
+
+        // the completed lookup is no longer shared
+        assert!(client.lookup(query, Default::default()).wait().is_err());
+        assert_eq!(*handle.sent.lock().unwrap(), 2);
+    }
+
+    /// Counts the requests which are outstanding, i.e. which haven't been dropped
+    #[derive(Clone, Default)]
+    struct PendingDnsHandle {
+        outstanding: Arc<AtomicUsize>,
+    }
+
+    struct Outstanding(Arc<AtomicUsize>);
+
+    impl Drop for Outstanding {
+        fn drop(&mut self) {
+            self.0.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    impl DnsHandle for PendingDnsHandle {
+        type Response = Box<Future<Item = DnsResponse, Error = ProtoError> + Send>;
+
+        fn send<R: Into<DnsRequest>>(&mut self, _: R) -> Self::Response {
+            self.outstanding.fetch_add(1, Ordering::SeqCst);
+            let outstanding = Outstanding(Arc::clone(&self.outstanding));
+            Box::new(future::empty().map(move |response| {
+                drop(outstanding);
+                response
+            }))
+        }
+    }
+
+    #[test]
+    fn test_dropped_lookups_are_canceled() {
+        let handle = PendingDnsHandle::default();
+        let mut client = CachingClient::new(1, handle.clone());
+        let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+
+        let mut first = client.lookup(query.clone(), Default::default());
+        let mut second = client.lookup(query.clone(), Default::default());
+        future::lazy(|| {
+            assert!(first.poll().unwrap().is_not_ready());
+            assert!(second.poll().unwrap().is_not_ready());
+            Ok::<_, ()>(())
+        })
+        .wait()
+        .unwrap();
+        assert_eq!(handle.outstanding.load(Ordering::SeqCst), 1);
+
+        // the request is outstanding until all of the lookups sharing it are dropped
+        drop(first);
+        assert_eq!(handle.outstanding.load(Ordering::SeqCst), 1);
+        drop(second);
+        assert_eq!(handle.outstanding.load(Ordering::SeqCst), 0);
+        assert_eq!(format!("{:?}", client.in_flight), "InFlight(0)");
+    }
+
     #[test]
     fn test_empty_cache() {
         let cache = Arc::new(Mutex::new(DnsLru::new(1, dns_lru::TtlConfig::default())));
@@ -679,11 +1028,109 @@ mod tests {
                 .kind(),
             ResolveErrorKind::NoRecordsFound {
                 query: Query::new(),
+                response_code: ResponseCode::NoError,
+                authority: None,
                 valid_until: None,
             }
         );
     }
 
+    #[test]
+    fn test_negative_soa() {
+        use proto::rr::rdata::SOA;
+
+        let soa = Record::from_rdata(
+            Name::from_str("example.com.").unwrap(),
+            3600,
+            RData::SOA(SOA::new(
+                Name::from_str("ns.example.com.").unwrap(),
+                Name::from_str("root.example.com.").unwrap(),
+                1,
+                7200,
+                3600,
+                1_209_600,
+                300,
+            )),
+        );
+        let negative = |response_code: ResponseCode| {
+            let mut message = Message::new();
+            message.set_response_code(response_code);
+            message.add_name_server(soa.clone());
+            Ok(message.into())
+        };
+        let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+
+        for &response_code in &[ResponseCode::NXDomain, ResponseCode::NoError] {
+            let cache = Arc::new(Mutex::new(DnsLru::new(1, dns_lru::TtlConfig::default())));
+            let mut client = mock(vec![negative(response_code)]);
+
+            let error = QueryState::lookup(query.clone(), Default::default(), &mut client, cache)
+                .wait()
+                .unwrap_err();
+            match *error.kind() {
+                ResolveErrorKind::NoRecordsFound {
+                    response_code: ref code,
+                    authority: Some(ref authority),
+                    valid_until: Some(_),
+                    ..
+                } => {
+                    assert_eq!(*code, response_code);
+                    assert_eq!(authority.soa(), Some(&soa));
+                    assert!(authority.negative_proofs().is_empty());
+                }
+                ref kind => panic!("expected NoRecordsFound with the SOA: {:?}", kind),
+            }
+
+            let code = if response_code == ResponseCode::NXDomain {
+                ResolveErrorCode::NXDomain
+            } else {
+                ResolveErrorCode::NoData
+            };
+            assert_eq!(error.code(), code);
+            assert_eq!(error.query(), Some(&query));
+        }
+    }
+
+    #[test]
+    fn test_error_codes() {
+        use proto::error::ProtoErrorKind;
+
+        let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+        let name_server = SocketAddr::from(([192, 0, 2, 53], 53));
+        let lookup = |response: ProtoResult<DnsResponse>| {
+            CachingClient::new(0, mock(vec![response]))
+                .with_name_servers(vec![name_server])
+                .lookup(query.clone(), Default::default())
+                .wait()
+                .unwrap_err()
+        };
+
+        let mut refused = Message::new();
+        refused.set_response_code(ResponseCode::Refused);
+        let error = lookup(Ok(refused.into()));
+        assert_eq!(
+            *error.kind(),
+            ResolveErrorKind::ResponseCode(ResponseCode::Refused)
+        );
+        assert_eq!(error.code(), ResolveErrorCode::Refused);
+        assert_eq!(error.query(), Some(&query));
+        assert!(error.name_servers().is_empty());
+
+        let error = lookup(Err(ProtoErrorKind::Timeout.into()));
+        assert_eq!(error.code(), ResolveErrorCode::Timeout);
+        assert_eq!(error.query(), Some(&query));
+        assert_eq!(error.name_servers(), &[name_server]);
+
+        let error = lookup(Err(ProtoErrorKind::RrsigsNotPresent {
+            name: query.name().clone(),
+            record_type: RecordType::A,
+        }
+        .into()));
+        assert_eq!(*error.kind(), ResolveErrorKind::Proto);
+        assert_eq!(error.code(), ResolveErrorCode::Bogus);
+        assert!(error.name_servers().is_empty());
+    }
+
     #[test]
     fn test_from_cache() {
         let cache = Arc::new(Mutex::new(DnsLru::new(1, dns_lru::TtlConfig::default())));
@@ -698,6 +1145,7 @@ mod tests {
                 ),
                 u32::max_value(),
             )],
+            None,
             Instant::now(),
         );
 
@@ -741,6 +1189,35 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_lookup_response() {
+        let cache = Arc::new(Mutex::new(DnsLru::new(1, dns_lru::TtlConfig::default())));
+        let mut message = v4_message().unwrap();
+        message.set_authentic_data(true);
+        message.add_name_server(Record::from_rdata(
+            Name::root(),
+            86400,
+            RData::NS(Name::from_str("ns.example.com.").unwrap()),
+        ));
+        let mut client = mock(vec![Ok(message)]);
+
+        let lookup =
+            QueryState::lookup(Query::new(), Default::default(), &mut client, cache.clone())
+                .wait()
+                .unwrap();
+        let response = lookup.response().expect("no response");
+        assert!(response.authentic_data());
+        assert_eq!(response.answers().len(), 1);
+        assert_eq!(response.name_servers().len(), 1);
+
+        // cached with the response
+        let mut client = mock(vec![empty()]);
+        let cached = QueryState::lookup(Query::new(), Default::default(), &mut client, cache)
+            .wait()
+            .unwrap();
+        assert_eq!(cached.response(), Some(response));
+    }
+
     pub fn cname_message() -> ProtoResult<DnsResponse> {
         let mut message = Message::new();
         message.insert_answers(vec![Record::from_rdata(
@@ -961,7 +1438,7 @@ mod tests {
 
         assert!(poll.is_ready());
         if let Async::Ready(records) = poll {
-            if let Records::Exists(records) = records {
+            if let Records::Exists(records, _) = records {
                 assert!(records.iter().all(|&(_, ttl)| ttl == 1));
             } else {
                 panic!("records don't exist");
@@ -981,7 +1458,7 @@ mod tests {
     fn test_early_return_localhost() {
         let cache = Arc::new(Mutex::new(DnsLru::new(0, dns_lru::TtlConfig::default())));
         let client = mock(vec![empty()]);
-        let mut client = CachingClient { lru: cache, client };
+        let mut client = CachingClient::with_cache(cache, client);
 
         {
             let query = Query::query(Name::from_ascii("localhost.").unwrap(), RecordType::A);
@@ -1070,7 +1547,7 @@ mod tests {
     fn test_early_return_invalid() {
         let cache = Arc::new(Mutex::new(DnsLru::new(0, dns_lru::TtlConfig::default())));
         let client = mock(vec![empty()]);
-        let mut client = CachingClient { lru: cache, client };
+        let mut client = CachingClient::with_cache(cache, client);
 
         assert!(client
             .lookup(
@@ -1096,7 +1573,7 @@ mod tests {
         ));
 
         let client = mock(vec![error(), Ok(message)]);
-        let mut client = CachingClient { lru: cache, client };
+        let mut client = CachingClient::with_cache(cache, client);
 
         assert!(client
             .lookup(
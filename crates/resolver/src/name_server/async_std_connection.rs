@@ -0,0 +1,524 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Connections over the sockets of the async-std reactor, which don't require Tokio
+
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream, UdpSocket};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use async_io::Async;
+use async_std;
+use futures03::compat::Compat;
+use futures03::future::{self, BoxFuture, FutureExt};
+use rand;
+use rand::distributions::{Distribution, Standard};
+
+use proto::error::{ProtoErrorKind, ProtoResult};
+use proto::op::Message;
+use proto::xfer::{DnsHandle, DnsRequest, DnsResponse};
+
+use config::{NameServerConfig, Protocol, ResolverOpts};
+use name_server::std_connection::bind_random_port;
+use name_server::ConnectionProvider;
+
+/// Makes connections over the sockets of async-std, see `AsyncStdRuntime`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AsyncStdConnection;
+
+impl ConnectionProvider for AsyncStdConnection {
+    type ConnHandle = AsyncStdConnectionHandle;
+
+    fn new_connection(
+        &self,
+        config: &NameServerConfig,
+        options: &ResolverOpts,
+    ) -> Self::ConnHandle {
+        AsyncStdConnectionHandle {
+            socket_addr: config.socket_addr,
+            protocol: config.protocol,
+            timeout: options.timeout,
+        }
+    }
+}
+
+/// Sends each request on a new socket, its response is awaited up to the timeout
+#[derive(Clone, Debug)]
+pub struct AsyncStdConnectionHandle {
+    socket_addr: SocketAddr,
+    protocol: Protocol,
+    timeout: Duration,
+}
+
+impl DnsHandle for AsyncStdConnectionHandle {
+    type Response = Compat<BoxFuture<'static, ProtoResult<DnsResponse>>>;
+
+    fn send<R: Into<DnsRequest>>(&mut self, request: R) -> Self::Response {
+        let exchange = match self.exchange(request.into()) {
+            Ok(exchange) => exchange,
+            Err(e) => return Compat::new(future::err(e).boxed()),
+        };
+
+        let response = async_std::future::timeout(self.timeout, exchange).map(|response| {
+            response
+                .unwrap_or_else(|_| Err(ProtoErrorKind::Timeout.into()))
+                .map(DnsResponse::from)
+        });
+        Compat::new(response.boxed())
+    }
+}
+
+impl AsyncStdConnectionHandle {
+    #[allow(unreachable_patterns)]
+    fn exchange(
+        &self,
+        request: DnsRequest,
+    ) -> ProtoResult<BoxFuture<'static, ProtoResult<Message>>> {
+        let (mut message, _) = request.unwrap();
+
+        // each request is on its own socket, so the ID doesn't need to be globally unique
+        message.set_id(Standard.sample(&mut rand::thread_rng()));
+        let id = message.id();
+        let bytes = message.to_vec()?;
+
+        match self.protocol {
+            Protocol::Udp => Ok(UdpExchange::new(self.socket_addr, bytes, id, false)?.boxed()),
+            Protocol::Tcp => Ok(TcpExchange::new(self.socket_addr, &bytes, id)?.boxed()),
+            #[cfg(feature = "mdns")]
+            Protocol::Mdns => Ok(UdpExchange::new(self.socket_addr, bytes, id, true)?.boxed()),
+            #[cfg(feature = "llmnr")]
+            Protocol::Llmnr => Ok(UdpExchange::new(self.socket_addr, bytes, id, true)?.boxed()),
+            protocol => {
+                Err(format!("{:?} is not supported by async-std connections", protocol).into())
+            }
+        }
+    }
+}
+
+/// Retries the non-blocking operation each time the socket is ready, until it doesn't block
+fn poll_io<T, F, R>(cx: &mut Context, mut operation: F, mut ready: R) -> Poll<io::Result<T>>
+where
+    F: FnMut() -> io::Result<T>,
+    R: FnMut(&mut Context) -> Poll<io::Result<()>>,
+{
+    loop {
+        match operation() {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => match ready(cx) {
+                Poll::Ready(Ok(())) => continue,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            },
+            result => return Poll::Ready(result),
+        }
+    }
+}
+
+/// Sends the request, the response of a multicast request is accepted from any address
+struct UdpExchange {
+    socket: Async<UdpSocket>,
+    name_server: SocketAddr,
+    bytes: Vec<u8>,
+    id: u16,
+    multicast: bool,
+    is_sent: bool,
+}
+
+impl UdpExchange {
+    fn new(name_server: SocketAddr, bytes: Vec<u8>, id: u16, multicast: bool) -> io::Result<Self> {
+        let socket = bind_random_port(&name_server)?;
+        if multicast && name_server.is_ipv4() {
+            // multicast requests are link-local
+            socket.set_multicast_ttl_v4(1)?;
+        }
+
+        Ok(UdpExchange {
+            socket: Async::new(socket)?,
+            name_server,
+            bytes,
+            id,
+            multicast,
+            is_sent: false,
+        })
+    }
+}
+
+impl ::std::future::Future for UdpExchange {
+    type Output = ProtoResult<Message>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let socket = &this.socket;
+
+        if !this.is_sent {
+            let (bytes, name_server) = (&this.bytes, this.name_server);
+            match poll_io(
+                cx,
+                || socket.get_ref().send_to(bytes, name_server),
+                |cx| socket.poll_writable(cx),
+            ) {
+                Poll::Ready(Ok(_)) => this.is_sent = true,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e.into())),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+
+        // TODO: consider making this heap based? need to verify it matches EDNS settings
+        let mut buf = [0u8; 2048];
+        loop {
+            let (len, src) = match poll_io(
+                cx,
+                || socket.get_ref().recv_from(&mut buf),
+                |cx| socket.poll_readable(cx),
+            ) {
+                Poll::Ready(Ok(received)) => received,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e.into())),
+                Poll::Pending => return Poll::Pending,
+            };
+
+            if !this.multicast && src != this.name_server {
+                warn!(
+                    "ignoring response from {} because it does not match name_server: {}.",
+                    src, this.name_server
+                );
+                continue;
+            }
+
+            match Message::from_vec(&buf[..len]) {
+                Ok(message) if message.id() == this.id => return Poll::Ready(Ok(message)),
+                Ok(message) => warn!(
+                    "ignoring response with id {} from {}, expected id: {}",
+                    message.id(),
+                    src,
+                    this.id
+                ),
+                Err(e) => warn!("ignoring malformed response from {}: {}", src, e),
+            }
+        }
+    }
+}
+
+/// Sends the request prefixed with its length, and reads the response of the same length prefix
+struct TcpExchange {
+    connect: Option<BoxFuture<'static, io::Result<Async<TcpStream>>>>,
+    stream: Option<Async<TcpStream>>,
+    send_buf: Vec<u8>,
+    sent: usize,
+    recv_buf: Vec<u8>,
+    id: u16,
+}
+
+impl TcpExchange {
+    fn new(name_server: SocketAddr, bytes: &[u8], id: u16) -> ProtoResult<Self> {
+        if bytes.len() > u16::MAX as usize {
+            return Err("message is too long for TCP".into());
+        }
+
+        // messages are prefixed with their length, see https://tools.ietf.org/html/rfc1035#section-4.2.2
+        let len = bytes.len() as u16;
+        let mut send_buf = Vec::with_capacity(bytes.len() + 2);
+        send_buf.extend_from_slice(&[(len >> 8) as u8, len as u8]);
+        send_buf.extend_from_slice(bytes);
+
+        Ok(TcpExchange {
+            connect: Some(Async::<TcpStream>::connect(name_server).boxed()),
+            stream: None,
+            send_buf,
+            sent: 0,
+            recv_buf: Vec::new(),
+            id,
+        })
+    }
+
+    /// The response, once the length prefix and that many bytes are received
+    fn response(&self) -> Option<ProtoResult<Message>> {
+        if self.recv_buf.len() < 2 {
+            return None;
+        }
+
+        let len = (u16::from(self.recv_buf[0]) << 8 | u16::from(self.recv_buf[1])) as usize;
+        let bytes = self.recv_buf.get(2..2 + len)?;
+
+        Some(Message::from_vec(bytes).and_then(|message| {
+            if message.id() != self.id {
+                return Err(format!(
+                    "response id {} does not match request id: {}",
+                    message.id(),
+                    self.id
+                )
+                .into());
+            }
+            Ok(message)
+        }))
+    }
+}
+
+impl ::std::future::Future for TcpExchange {
+    type Output = ProtoResult<Message>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        if let Some(mut connect) = this.connect.take() {
+            match connect.poll_unpin(cx) {
+                Poll::Ready(Ok(stream)) => this.stream = Some(stream),
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e.into())),
+                Poll::Pending => {
+                    this.connect = Some(connect);
+                    return Poll::Pending;
+                }
+            }
+        }
+
+        let stream = this.stream.as_ref().expect("polled after completion");
+        while this.sent < this.send_buf.len() {
+            let bytes = &this.send_buf[this.sent..];
+            match poll_io(
+                cx,
+                || stream.get_ref().write(bytes),
+                |cx| stream.poll_writable(cx),
+            ) {
+                Poll::Ready(Ok(0)) => {
+                    return Poll::Ready(Err(io::Error::from(io::ErrorKind::WriteZero).into()))
+                }
+                Poll::Ready(Ok(len)) => this.sent += len,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e.into())),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+
+        let mut buf = [0u8; 2048];
+        loop {
+            if let Some(response) = this.response() {
+                return Poll::Ready(response);
+            }
+
+            match poll_io(
+                cx,
+                || stream.get_ref().read(&mut buf),
+                |cx| stream.poll_readable(cx),
+            ) {
+                Poll::Ready(Ok(0)) => {
+                    return Poll::Ready(Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()))
+                }
+                Poll::Ready(Ok(len)) => this.recv_buf.extend_from_slice(&buf[..len]),
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e.into())),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, UdpSocket};
+    use std::str::FromStr;
+    use std::thread;
+
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use futures03::compat::Future01CompatExt;
+
+    use proto::op::{MessageType, Query};
+    use proto::rr::{Name, RData, Record, RecordType};
+
+    use config::{IpPreference, LookupIpStrategy, NameServerConfigGroup, ResolverConfig};
+    use runtime::AsyncStdRuntime;
+    use AsyncResolver;
+
+    use super::*;
+
+    fn handle(socket_addr: SocketAddr, protocol: Protocol) -> AsyncStdConnectionHandle {
+        AsyncStdConnectionHandle {
+            socket_addr,
+            protocol,
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    fn lookup(mut handle: AsyncStdConnectionHandle) -> ProtoResult<DnsResponse> {
+        let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+        async_std::task::block_on(handle.lookup(query, Default::default()).compat())
+    }
+
+    #[test]
+    fn test_udp_exchange() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server_addr = server.local_addr().unwrap();
+
+        let responder = thread::spawn(move || {
+            let mut buf = [0u8; 512];
+            let (len, src) = server.recv_from(&mut buf).unwrap();
+            let mut message = Message::from_vec(&buf[..len]).unwrap();
+            message.set_message_type(MessageType::Response);
+
+            // a response with the wrong id is ignored
+            let id = message.id();
+            message.set_id(id.wrapping_add(1));
+            server.send_to(&message.to_vec().unwrap(), src).unwrap();
+            message.set_id(id);
+            server.send_to(&message.to_vec().unwrap(), src).unwrap();
+        });
+
+        let response = lookup(handle(server_addr, Protocol::Udp)).unwrap();
+        assert_eq!(response.message_type(), MessageType::Response);
+        responder.join().unwrap();
+    }
+
+    #[test]
+    fn test_udp_timeout() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut handle = handle(server.local_addr().unwrap(), Protocol::Udp);
+        handle.timeout = Duration::from_millis(50);
+
+        let error = lookup(handle).unwrap_err();
+        assert_eq!(*error.kind(), ProtoErrorKind::Timeout);
+    }
+
+    #[test]
+    fn test_tcp_exchange() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let server_addr = listener.local_addr().unwrap();
+
+        let responder = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut len = [0u8; 2];
+            stream.read_exact(&mut len).unwrap();
+            let mut buf = vec![0; (u16::from(len[0]) << 8 | u16::from(len[1])) as usize];
+            stream.read_exact(&mut buf).unwrap();
+
+            let mut message = Message::from_vec(&buf).unwrap();
+            message.set_message_type(MessageType::Response);
+            let bytes = message.to_vec().unwrap();
+
+            // the response is received in more than one read
+            stream.write_all(&[0, bytes.len() as u8]).unwrap();
+            stream.flush().unwrap();
+            thread::sleep(Duration::from_millis(10));
+            stream.write_all(&bytes).unwrap();
+        });
+
+        let response = lookup(handle(server_addr, Protocol::Tcp)).unwrap();
+        assert_eq!(response.message_type(), MessageType::Response);
+        responder.join().unwrap();
+    }
+
+    #[test]
+    fn test_async_resolver_lookup_ip() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server_addr = server.local_addr().unwrap();
+
+        let responder = thread::spawn(move || {
+            let mut buf = [0u8; 512];
+            let (len, src) = server.recv_from(&mut buf).unwrap();
+            let mut message = Message::from_vec(&buf[..len]).unwrap();
+            let name = message.queries()[0].name().clone();
+            message
+                .set_message_type(MessageType::Response)
+                .add_answer(Record::from_rdata(
+                    name,
+                    300,
+                    RData::A(Ipv4Addr::new(127, 0, 0, 2)),
+                ));
+            server.send_to(&message.to_vec().unwrap(), src).unwrap();
+        });
+
+        let mut config = ResolverConfig::new();
+        for name_server in
+            NameServerConfigGroup::from_ips_clear(&[server_addr.ip()], server_addr.port()).iter()
+        {
+            if name_server.protocol == Protocol::Udp {
+                config.add_name_server(name_server.clone());
+            }
+        }
+        let options = ResolverOpts {
+            ip_strategy: LookupIpStrategy::Ipv4Only,
+            ..ResolverOpts::default()
+        };
+
+        // no Tokio runtime, the resolver and its connections are run by async-std
+        let (resolver, background) = AsyncResolver::with_runtime(config, options, AsyncStdRuntime);
+        async_std::task::spawn(background.compat().map(|_| ()));
+
+        let lookup = async_std::task::block_on(resolver.lookup_ip("www.example.com.").compat())
+            .expect("lookup failed");
+        assert_eq!(
+            lookup.iter().collect::<Vec<_>>(),
+            vec![IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2))]
+        );
+        responder.join().unwrap();
+    }
+
+    #[test]
+    fn test_async_resolver_delays() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server_addr = server.local_addr().unwrap();
+
+        // the first A query and the AAAA queries are not answered
+        let responder = thread::spawn(move || {
+            let mut buf = [0u8; 512];
+            let mut ignored = false;
+            loop {
+                let (len, src) = server.recv_from(&mut buf).unwrap();
+                let mut message = Message::from_vec(&buf[..len]).unwrap();
+                let query = message.queries()[0].clone();
+                if query.query_type() != RecordType::A {
+                    continue;
+                }
+                if !ignored {
+                    ignored = true;
+                    continue;
+                }
+
+                message
+                    .set_message_type(MessageType::Response)
+                    .add_answer(Record::from_rdata(
+                        query.name().clone(),
+                        300,
+                        RData::A(Ipv4Addr::new(127, 0, 0, 2)),
+                    ));
+                server.send_to(&message.to_vec().unwrap(), src).unwrap();
+                return;
+            }
+        });
+
+        let mut config = ResolverConfig::new();
+        for name_server in
+            NameServerConfigGroup::from_ips_clear(&[server_addr.ip()], server_addr.port()).iter()
+        {
+            if name_server.protocol == Protocol::Udp {
+                config.add_name_server(name_server.clone());
+            }
+        }
+        let options = ResolverOpts {
+            timeout: Duration::from_millis(100),
+            attempts: 2,
+            retry_delay: Duration::from_millis(50),
+            hedge_delay: Some(Duration::from_millis(20)),
+            ip_strategy: LookupIpStrategy::Preference(IpPreference {
+                concurrent: true,
+                ipv6_timeout: Some(Duration::from_millis(50)),
+                ..IpPreference::ipv4_then_ipv6()
+            }),
+            ..ResolverOpts::default()
+        };
+
+        // the retry is after the backoff, and the ipv6 lookup times out, without a Tokio timer
+        let (resolver, background) = AsyncResolver::with_runtime(config, options, AsyncStdRuntime);
+        async_std::task::spawn(background.compat().map(|_| ()));
+
+        let lookup = async_std::task::block_on(resolver.lookup_ip("www.example.com.").compat())
+            .expect("lookup failed");
+        assert_eq!(
+            lookup.iter().collect::<Vec<_>>(),
+            vec![IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2))]
+        );
+        responder.join().unwrap();
+    }
+}
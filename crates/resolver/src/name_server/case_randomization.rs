@@ -0,0 +1,230 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use futures::future::{self, Either};
+use futures::Future;
+use rand::{self, Rng};
+
+use proto::error::ProtoError;
+use proto::op::{Message, Query};
+use proto::rr::{Name, Record};
+use proto::xfer::{DnsHandle, DnsRequest, DnsResponse};
+
+/// The 0x20 encoding of the queries to a NameServer, see
+///  [draft-vixie-dnsext-dns0x20](https://tools.ietf.org/html/draft-vixie-dnsext-dns0x20-00)
+///
+/// The letters of the names of the queries are randomly upper or lower case, and responses must
+///  echo the exact case of the query, which an off-path attacker spoofing responses would have
+///  to guess. A response which doesn't is discarded and the query is sent again with another
+///  case, if that response doesn't either, the NameServer normalizes the case of the queries and
+///  they are not randomized anymore.
+pub(crate) struct CaseRandomization {
+    enabled: AtomicBool,
+}
+
+impl CaseRandomization {
+    pub fn new(enabled: bool) -> Self {
+        CaseRandomization {
+            enabled: AtomicBool::new(enabled),
+        }
+    }
+
+    /// True if the case of the queries is randomized
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Stops randomizing the case of the queries, once the NameServer normalized it
+    pub fn disable(&self) {
+        self.enabled.store(false, Ordering::Relaxed)
+    }
+}
+
+/// Sends the request with the case of its names randomized, see `CaseRandomization`
+///
+/// The names of the response are restored to the case of the request.
+pub(crate) fn send_randomized<C: DnsHandle>(
+    client: &mut C,
+    request: DnsRequest,
+    case_randomization: Arc<CaseRandomization>,
+    name_server: SocketAddr,
+) -> impl Future<Item = DnsResponse, Error = ProtoError> + Send {
+    let original = request.queries().to_vec();
+    let mut retry_client = client.clone();
+
+    let (first, first_queries) = randomize_queries(request.clone());
+    client.send(first).and_then(move |response| {
+        if is_echoed(&first_queries, &response) {
+            return Either::A(future::ok(restore_queries(response, &original)));
+        }
+
+        debug!(
+            "response from {} did not echo the case of {:?}, querying again",
+            name_server, first_queries
+        );
+        let (second, second_queries) = randomize_queries(request);
+        Either::B(retry_client.send(second).map(move |response| {
+            if !is_echoed(&second_queries, &response) {
+                warn!(
+                    "{} normalizes the case of queries, not randomizing it anymore",
+                    name_server
+                );
+                case_randomization.disable();
+            }
+
+            restore_queries(response, &original)
+        }))
+    })
+}
+
+/// Returns the name with the case of each of its ASCII letters randomized
+fn randomize_case(name: &Name) -> Name {
+    let mut rng = rand::thread_rng();
+    let labels = name
+        .iter()
+        .map(|label| {
+            label
+                .iter()
+                .map(|b| match b {
+                    b'a'..=b'z' | b'A'..=b'Z' if rng.gen() => b ^ 0x20,
+                    _ => *b,
+                })
+                .collect::<Vec<u8>>()
+        })
+        .collect::<Vec<_>>();
+
+    let mut randomized = Name::from_labels(labels).unwrap_or_else(|_| name.clone());
+    randomized.set_fqdn(name.is_fqdn());
+    randomized
+}
+
+/// Randomizes the case of the names of the queries of the request, returns them with it
+fn randomize_queries(mut request: DnsRequest) -> (DnsRequest, Vec<Query>) {
+    let queries = request
+        .take_queries()
+        .into_iter()
+        .map(|mut query| {
+            let name = randomize_case(query.name());
+            query.set_name(name);
+            query
+        })
+        .collect::<Vec<_>>();
+
+    request.add_queries(queries.clone());
+    (request, queries)
+}
+
+/// True if the queries of the response have the exact case of the queries sent
+fn is_echoed(queries: &[Query], response: &Message) -> bool {
+    queries.len() == response.queries().len()
+        && queries
+            .iter()
+            .zip(response.queries())
+            .all(|(sent, received)| sent.name().eq_case(received.name()))
+}
+
+/// Restores the names of the queries of the response, and of the records for them, to the case of
+///  the original queries
+fn restore_queries(mut response: DnsResponse, original: &[Query]) -> DnsResponse {
+    let restore = |records: Vec<Record>| -> Vec<Record> {
+        records
+            .into_iter()
+            .map(|mut record| {
+                // names are compared case insensitively
+                if let Some(query) = original.iter().find(|q| q.name() == record.name()) {
+                    record.set_name(query.name().clone());
+                }
+                record
+            })
+            .collect()
+    };
+
+    for message in response.messages_mut() {
+        if message.queries().len() == original.len() {
+            message.take_queries();
+            message.add_queries(original.to_vec());
+        }
+
+        let answers = restore(message.take_answers());
+        message.insert_answers(answers);
+        let name_servers = restore(message.take_name_servers());
+        message.insert_name_servers(name_servers);
+        let additionals = restore(message.take_additionals());
+        message.insert_additionals(additionals);
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use proto::rr::{RData, RecordType};
+
+    use super::*;
+
+    #[test]
+    fn test_randomize_case() {
+        let name = Name::from_str("www.example.com.").unwrap();
+        let randomized = (0..32)
+            .map(|_| randomize_case(&name))
+            .inspect(|randomized| {
+                assert_eq!(*randomized, name);
+                assert!(randomized.is_fqdn());
+            })
+            .filter(|randomized| !randomized.eq_case(&name))
+            .count();
+        assert!(randomized > 0);
+
+        // only letters are changed
+        let name = Name::from_str("1-2_3.").unwrap();
+        assert!(randomize_case(&name).eq_case(&name));
+    }
+
+    #[test]
+    fn test_echo_and_restore() {
+        let name = Name::from_str("www.example.com.").unwrap();
+        let query = Query::query(name.clone(), RecordType::A);
+        let (request, queries) = randomize_queries(DnsRequest::new(
+            {
+                let mut message = Message::new();
+                message.add_query(query.clone());
+                message
+            },
+            Default::default(),
+        ));
+        assert_eq!(request.queries(), &queries[..]);
+
+        let mut message = Message::new();
+        message.add_queries(queries.clone());
+        message.add_answer(Record::from_rdata(
+            queries[0].name().clone(),
+            86400,
+            RData::A([127, 0, 0, 1].into()),
+        ));
+        assert!(is_echoed(&queries, &message));
+
+        let response = restore_queries(message.clone().into(), ::std::slice::from_ref(&query));
+        assert!(response.queries()[0].name().eq_case(&name));
+        assert!(response.answers()[0].name().eq_case(&name));
+
+        // a normalized response
+        let sent = vec![Query::query(
+            Name::from_ascii("wWw.ExAmPle.cOm.").unwrap(),
+            RecordType::A,
+        )];
+        let mut normalized = Message::new();
+        normalized.add_query(Query::query(name.to_lowercase(), RecordType::A));
+        assert!(!is_echoed(&sent, &normalized));
+        assert!(!is_echoed(&queries, &Message::new()));
+    }
+}
@@ -10,26 +10,26 @@ use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use futures::{Future, Poll};
-use tokio_executor::{DefaultExecutor, Executor};
 
 use proto;
-#[cfg(feature = "mdns")]
+#[cfg(any(feature = "mdns", feature = "llmnr"))]
 use proto::multicast::{MdnsClientStream, MdnsQueryType};
 use proto::op::NoopMessageFinalizer;
-use proto::tcp::TcpClientStream;
+use proto::tcp::{TcpClientStream, TcpOptions};
 use proto::udp::{UdpClientStream, UdpResponse};
 use proto::xfer::{
     self, BufDnsRequestStreamHandle, DnsExchange, DnsHandle, DnsMultiplexer,
-    DnsMultiplexerSerialResponse, DnsRequest, DnsResponse,
+    DnsMultiplexerSerialResponse, DnsRequest, DnsRequestSender, DnsResponse, InFlightPolicy,
 };
 #[cfg(feature = "dns-over-https")]
 use trust_dns_https;
 
 use config::{NameServerConfig, Protocol, ResolverOpts};
+use runtime::RuntimeProvider;
 
 /// A type to allow for custom ConnectionProviders. Needed mainly for mocking purposes.
 pub trait ConnectionProvider: 'static + Clone + Send + Sync {
-    type ConnHandle;
+    type ConnHandle: DnsHandle;
 
     /// The returned handle should
     fn new_connection(&self, config: &NameServerConfig, options: &ResolverOpts)
@@ -37,6 +37,9 @@ pub trait ConnectionProvider: 'static + Clone + Send + Sync {
 }
 
 /// Standard connection implements the default mechanism for creating new Connections
+///
+/// This is the `TokioRuntime`, the futures of the `AsyncResolver` must be run by a Tokio executor,
+///  with its reactor and timer.
 #[derive(Clone)]
 pub struct StandardConnection;
 
@@ -50,35 +53,48 @@ impl ConnectionProvider for StandardConnection {
         config: &NameServerConfig,
         options: &ResolverOpts,
     ) -> Self::ConnHandle {
-        let dns_handle = match config.protocol {
-            Protocol::Udp => ConnectionHandleInner::Connect(Some(ConnectionHandleConnect::Udp {
+        let connect = match config.protocol {
+            Protocol::Udp => ConnectionHandleConnect::Udp {
                 socket_addr: config.socket_addr,
                 timeout: options.timeout,
-            })),
-            Protocol::Tcp => ConnectionHandleInner::Connect(Some(ConnectionHandleConnect::Tcp {
+            },
+            Protocol::Tcp => ConnectionHandleConnect::Tcp {
                 socket_addr: config.socket_addr,
                 timeout: options.timeout,
-            })),
+                tcp_options: options.tcp_options,
+            },
             #[cfg(feature = "dns-over-tls")]
-            Protocol::Tls => ConnectionHandleInner::Connect(Some(ConnectionHandleConnect::Tls {
+            Protocol::Tls => ConnectionHandleConnect::Tls {
                 socket_addr: config.socket_addr,
                 timeout: options.timeout,
+                tcp_options: options.tcp_options,
                 tls_dns_name: config.tls_dns_name.clone().unwrap_or_default(),
-            })),
+            },
             #[cfg(feature = "dns-over-https")]
-            Protocol::Https => {
-                ConnectionHandleInner::Connect(Some(ConnectionHandleConnect::Https {
-                    socket_addr: config.socket_addr,
-                    timeout: options.timeout,
-                    tls_dns_name: config.tls_dns_name.clone().unwrap_or_default(),
-                }))
-            }
+            Protocol::Https => ConnectionHandleConnect::Https {
+                socket_addr: config.socket_addr,
+                timeout: options.timeout,
+                tls_dns_name: config.tls_dns_name.clone().unwrap_or_default(),
+            },
             #[cfg(feature = "mdns")]
-            Protocol::Mdns => ConnectionHandleInner::Connect(Some(ConnectionHandleConnect::Mdns {
+            Protocol::Mdns => ConnectionHandleConnect::Mdns {
                 socket_addr: config.socket_addr,
                 timeout: options.timeout,
-            })),
+            },
+            #[cfg(feature = "llmnr")]
+            Protocol::Llmnr => ConnectionHandleConnect::Llmnr {
+                socket_addr: config.socket_addr,
+                timeout: options.timeout,
+            },
+        };
+
+        let in_flight_policy = if options.fail_when_busy {
+            InFlightPolicy::Fail
+        } else {
+            InFlightPolicy::Wait
         };
+        let max_in_flight = options.max_in_flight.map(|max| (max, in_flight_policy));
+        let dns_handle = ConnectionHandleInner::Connect(Some(connect), max_in_flight);
 
         ConnectionHandle(Arc::new(Mutex::new(dns_handle)))
     }
@@ -94,11 +110,13 @@ pub(crate) enum ConnectionHandleConnect {
     Tcp {
         socket_addr: SocketAddr,
         timeout: Duration,
+        tcp_options: TcpOptions,
     },
     #[cfg(feature = "dns-over-tls")]
     Tls {
         socket_addr: SocketAddr,
         timeout: Duration,
+        tcp_options: TcpOptions,
         tls_dns_name: String,
     },
     #[cfg(feature = "dns-over-https")]
@@ -112,12 +130,22 @@ pub(crate) enum ConnectionHandleConnect {
         socket_addr: SocketAddr,
         timeout: Duration,
     },
+    #[cfg(feature = "llmnr")]
+    Llmnr {
+        socket_addr: SocketAddr,
+        timeout: Duration,
+    },
 }
 
 impl ConnectionHandleConnect {
     /// Establishes the connection, this is allowed to perform network operations,
     ///   suchas tokio::spawns of background tasks, etc.
-    fn connect(self) -> Result<ConnectionHandleConnected, proto::error::ProtoError> {
+    ///
+    /// `max_in_flight` limits the requests in flight of the connection, see `ResolverOpts`
+    fn connect(
+        self,
+        max_in_flight: Option<(usize, InFlightPolicy)>,
+    ) -> Result<ConnectionHandleConnected, proto::error::ProtoError> {
         use self::ConnectionHandleConnect::*;
 
         debug!("connecting: {:?}", self);
@@ -129,19 +157,24 @@ impl ConnectionHandleConnect {
                 let stream = UdpClientStream::with_timeout(socket_addr, timeout);
                 let (stream, handle) = DnsExchange::connect(stream);
 
-                let stream = stream.and_then(|stream| stream).map_err(|e| {
-                    debug!("udp connection shutting down: {}", e);
-                });
+                let stream = stream
+                    .map(move |exchange| limit_in_flight(exchange, max_in_flight))
+                    .and_then(|stream| stream)
+                    .map_err(|e| {
+                        debug!("udp connection shutting down: {}", e);
+                    });
                 let handle = BufDnsRequestStreamHandle::new(handle);
 
-                DefaultExecutor::current().spawn(Box::new(stream))?;
+                StandardConnection.spawn_bg(stream)?;
                 Ok(ConnectionHandleConnected::Udp(handle))
             }
             Tcp {
                 socket_addr,
                 timeout,
+                tcp_options,
             } => {
-                let (stream, handle) = TcpClientStream::with_timeout(socket_addr, timeout);
+                let (stream, handle) =
+                    TcpClientStream::with_options(socket_addr, timeout, tcp_options);
                 // TODO: need config for Signer...
                 let dns_conn = DnsMultiplexer::with_timeout(
                     Box::new(stream),
@@ -151,21 +184,26 @@ impl ConnectionHandleConnect {
                 );
 
                 let (stream, handle) = DnsExchange::connect(dns_conn);
-                let stream = stream.and_then(|stream| stream).map_err(|e| {
-                    debug!("tcp connection shutting down: {}", e);
-                });
+                let stream = stream
+                    .map(move |exchange| limit_in_flight(exchange, max_in_flight))
+                    .and_then(|stream| stream)
+                    .map_err(|e| {
+                        debug!("tcp connection shutting down: {}", e);
+                    });
                 let handle = BufDnsRequestStreamHandle::new(handle);
 
-                DefaultExecutor::current().spawn(Box::new(stream))?;
+                StandardConnection.spawn_bg(stream)?;
                 Ok(ConnectionHandleConnected::Tcp(handle))
             }
             #[cfg(feature = "dns-over-tls")]
             Tls {
                 socket_addr,
                 timeout,
+                tcp_options,
                 tls_dns_name,
             } => {
-                let (stream, handle) = ::tls::new_tls_stream(socket_addr, tls_dns_name);
+                let (stream, handle) =
+                    ::tls::new_tls_stream(socket_addr, tls_dns_name, tcp_options);
                 let dns_conn = DnsMultiplexer::with_timeout(
                     stream,
                     Box::new(handle),
@@ -174,12 +212,15 @@ impl ConnectionHandleConnect {
                 );
 
                 let (stream, handle) = DnsExchange::connect(dns_conn);
-                let stream = stream.and_then(|stream| stream).map_err(|e| {
-                    debug!("tls connection shutting down: {}", e);
-                });
+                let stream = stream
+                    .map(move |exchange| limit_in_flight(exchange, max_in_flight))
+                    .and_then(|stream| stream)
+                    .map_err(|e| {
+                        debug!("tls connection shutting down: {}", e);
+                    });
                 let handle = BufDnsRequestStreamHandle::new(handle);
 
-                DefaultExecutor::current().spawn(Box::new(stream))?;
+                StandardConnection.spawn_bg(stream)?;
                 Ok(ConnectionHandleConnected::Tcp(handle))
             }
             #[cfg(feature = "dns-over-https")]
@@ -191,11 +232,14 @@ impl ConnectionHandleConnect {
             } => {
                 let (stream, handle) = ::https::new_https_stream(socket_addr, tls_dns_name);
 
-                let stream = stream.and_then(|stream| stream).map_err(|e| {
-                    debug!("https connection shutting down: {}", e);
-                });
+                let stream = stream
+                    .map(move |exchange| limit_in_flight(exchange, max_in_flight))
+                    .and_then(|stream| stream)
+                    .map_err(|e| {
+                        debug!("https connection shutting down: {}", e);
+                    });
 
-                DefaultExecutor::current().spawn(Box::new(stream))?;
+                StandardConnection.spawn_bg(stream)?;
                 Ok(ConnectionHandleConnected::Https(handle))
             }
             #[cfg(feature = "mdns")]
@@ -214,18 +258,63 @@ impl ConnectionHandleConnect {
                 );
 
                 let (stream, handle) = DnsExchange::connect(dns_conn);
-                let stream = stream.and_then(|stream| stream).map_err(|e| {
-                    debug!("mdns connection shutting down: {}", e);
-                });
+                let stream = stream
+                    .map(move |exchange| limit_in_flight(exchange, max_in_flight))
+                    .and_then(|stream| stream)
+                    .map_err(|e| {
+                        debug!("mdns connection shutting down: {}", e);
+                    });
                 let handle = BufDnsRequestStreamHandle::new(handle);
 
-                DefaultExecutor::current().spawn(Box::new(stream))?;
+                StandardConnection.spawn_bg(stream)?;
+                Ok(ConnectionHandleConnected::Tcp(handle))
+            }
+            #[cfg(feature = "llmnr")]
+            Llmnr {
+                socket_addr,
+                timeout,
+            } => {
+                // LLMNR queries must not leave the link, rfc4795 section 2.5
+                let (stream, handle) =
+                    MdnsClientStream::new(socket_addr, MdnsQueryType::OneShot, Some(1), None, None);
+                let dns_conn = DnsMultiplexer::with_timeout(
+                    stream,
+                    handle,
+                    timeout,
+                    NoopMessageFinalizer::new(),
+                );
+
+                let (stream, handle) = DnsExchange::connect(dns_conn);
+                let stream = stream
+                    .map(move |exchange| limit_in_flight(exchange, max_in_flight))
+                    .and_then(|stream| stream)
+                    .map_err(|e| {
+                        debug!("llmnr connection shutting down: {}", e);
+                    });
+                let handle = BufDnsRequestStreamHandle::new(handle);
+
+                StandardConnection.spawn_bg(stream)?;
                 Ok(ConnectionHandleConnected::Tcp(handle))
             }
         }
     }
 }
 
+/// Limits the requests in flight of the exchange of a connection, if `max_in_flight` is set
+fn limit_in_flight<S, R>(
+    exchange: DnsExchange<S, R>,
+    max_in_flight: Option<(usize, InFlightPolicy)>,
+) -> DnsExchange<S, R>
+where
+    S: DnsRequestSender<DnsResponseFuture = R>,
+    R: Future<Item = DnsResponse, Error = proto::error::ProtoError> + 'static + Send,
+{
+    match max_in_flight {
+        Some((max, policy)) => exchange.with_max_in_flight(max, policy),
+        None => exchange,
+    }
+}
+
 /// A representation of an established connection
 #[derive(Clone)]
 enum ConnectionHandleConnected {
@@ -256,7 +345,11 @@ impl DnsHandle for ConnectionHandleConnected {
 
 /// Allows us to wrap a connection that is either pending or already connected
 enum ConnectionHandleInner {
-    Connect(Option<ConnectionHandleConnect>),
+    /// the connection, and the limit of its requests in flight
+    Connect(
+        Option<ConnectionHandleConnect>,
+        Option<(usize, InFlightPolicy)>,
+    ),
     Connected(ConnectionHandleConnected),
 }
 
@@ -266,9 +359,10 @@ impl ConnectionHandleInner {
             let connected: Result<ConnectionHandleConnected, proto::error::ProtoError> = match self
             {
                 // still need to connect, drop through
-                ConnectionHandleInner::Connect(conn) => {
-                    conn.take().expect("already connected?").connect()
-                }
+                ConnectionHandleInner::Connect(conn, max_in_flight) => conn
+                    .take()
+                    .expect("already connected?")
+                    .connect(*max_in_flight),
                 ConnectionHandleInner::Connected(conn) => return conn.send(request),
             };
 
@@ -0,0 +1,95 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Link-Local Multicast Name Resolution, [RFC 4795](https://tools.ietf.org/html/rfc4795)
+
+use futures::Future;
+
+use proto::error::ProtoError;
+use proto::multicast::LLMNR_IPV4;
+use proto::xfer::{DnsHandle, DnsRequest, DnsResponse};
+
+use config::{NameServerConfig, Protocol, ResolverOpts};
+use name_server::{ConnectionProvider, NameServer};
+
+/// Sends queries to the LLMNR responders of the local link
+///
+/// LLMNR is only meant for single label names, e.g. `printer.`, the caller is responsible for
+///  only sending queries for those names.
+#[derive(Clone)]
+pub(crate) struct LlmnrHandle<C: DnsHandle, P: ConnectionProvider<ConnHandle = C>> {
+    name_server: NameServer<C, P>,
+}
+
+impl<C, P> LlmnrHandle<C, P>
+where
+    C: DnsHandle,
+    P: ConnectionProvider<ConnHandle = C>,
+{
+    // TODO: also query the ipv6 group, LLMNR_IPV6, once IPv6 is better understood
+    pub(crate) fn new(options: ResolverOpts, conn_provider: P) -> Self {
+        let config = NameServerConfig {
+            socket_addr: *LLMNR_IPV4,
+            protocol: Protocol::Llmnr,
+            tls_dns_name: None,
+        };
+
+        LlmnrHandle {
+            name_server: NameServer::new_with_provider(config, options, conn_provider),
+        }
+    }
+}
+
+impl<C, P> DnsHandle for LlmnrHandle<C, P>
+where
+    C: DnsHandle,
+    P: ConnectionProvider<ConnHandle = C>,
+{
+    type Response = Box<Future<Item = DnsResponse, Error = ProtoError> + Send>;
+
+    fn send<R: Into<DnsRequest>>(&mut self, request: R) -> Self::Response {
+        self.name_server.send(llmnr_request(request.into()))
+    }
+}
+
+/// LLMNR responders only answer for their own names, recursion must not be desired,
+///  [RFC 4795 Section 2.1.1](https://tools.ietf.org/html/rfc4795#section-2.1.1)
+fn llmnr_request(request: DnsRequest) -> DnsRequest {
+    let (mut message, options) = request.unwrap();
+    message.set_recursion_desired(false);
+
+    DnsRequest::new(message, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use proto::op::{Message, Query};
+    use proto::rr::{Name, RecordType};
+
+    use super::*;
+
+    #[test]
+    fn test_llmnr_request() {
+        let mut message = Message::new();
+        message
+            .set_id(10)
+            .set_recursion_desired(true)
+            .add_query(Query::query(
+                Name::parse("printer.", None).unwrap(),
+                RecordType::A,
+            ));
+
+        let request = llmnr_request(DnsRequest::new(message, Default::default()));
+
+        assert_eq!(request.id(), 10);
+        assert!(!request.recursion_desired());
+        assert_eq!(
+            request.queries()[0].name(),
+            &Name::parse("printer.", None).unwrap()
+        );
+    }
+}
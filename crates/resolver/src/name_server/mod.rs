@@ -5,18 +5,32 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+#[cfg(feature = "async-std")]
+mod async_std_connection;
+mod case_randomization;
 mod connection_provider;
+#[cfg(feature = "llmnr")]
+mod llmnr;
 mod name_server_pool;
 mod name_server_state;
 mod name_server_stats;
+mod std_connection;
+mod udp_payload;
 #[allow(clippy::module_inception)]
 mod name_server;
 
+use self::case_randomization::CaseRandomization;
 use self::name_server_state::NameServerState;
 use self::name_server_stats::NameServerStats;
+use self::udp_payload::UdpPayload;
 pub use self::name_server_pool::NameServerPool;
+#[cfg(feature = "async-std")]
+pub use self::async_std_connection::{AsyncStdConnection, AsyncStdConnectionHandle};
 pub use self::connection_provider::ConnectionProvider;
-pub(crate) use self::connection_provider::{StandardConnection, ConnectionHandle};
+pub use self::connection_provider::{ConnectionHandle, StandardConnection};
+pub(crate) use self::std_connection::{StdConnection, StdConnectionHandle};
 pub use self::name_server::NameServer;
+#[cfg(feature = "llmnr")]
+pub(crate) use self::llmnr::LlmnrHandle;
 #[cfg(feature = "mdns")]
 pub(crate) use self::name_server::mdns_nameserver;
\ No newline at end of file
@@ -7,22 +7,26 @@
 
 use std::cmp::Ordering;
 use std::fmt::{self, Debug, Formatter};
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
 use std::sync::Arc;
-use std::time::Instant;
 
-use futures::{future, Future};
+use futures::future::{self, Either};
+use futures::Future;
+use tokio_timer::clock;
 
-use proto::error::{ProtoError, ProtoResult};
+use proto::error::{ProtoError, ProtoErrorKind, ProtoResult};
 #[cfg(feature = "mdns")]
 use proto::multicast::MDNS_IPV4;
 use proto::op::ResponseCode;
 use proto::xfer::{DnsHandle, DnsRequest, DnsResponse};
 
-#[cfg(feature = "mdns")]
-use config::Protocol;
-use config::{NameServerConfig, ResolverOpts};
+use config::{NameServerConfig, Protocol, ResolverOpts};
+use events::{EventSender, ResolverEvent, ResolverEvents};
+use name_server::case_randomization::send_randomized;
+use name_server::CaseRandomization;
 use name_server::NameServerState;
 use name_server::NameServerStats;
+use name_server::UdpPayload;
 use name_server::{ConnectionHandle, ConnectionProvider, StandardConnection};
 
 /// Specifies the details of a remote NameServer used for lookups
@@ -33,7 +37,12 @@ pub struct NameServer<C: DnsHandle, P: ConnectionProvider<ConnHandle = C>> {
     client: Option<C>,
     state: Arc<NameServerState>,
     stats: Arc<NameServerStats>,
+    udp_payload: Arc<UdpPayload>,
+    case_randomization: Arc<CaseRandomization>,
     conn_provider: P,
+    events: EventSender,
+    /// the NameServerDown event was sent, and not yet the NameServerUp
+    down: Arc<AtomicBool>,
 }
 
 impl<C: DnsHandle, P: ConnectionProvider<ConnHandle = C>> Debug for NameServer<C, P> {
@@ -60,7 +69,14 @@ impl<C: DnsHandle, P: ConnectionProvider<ConnHandle = C>> NameServer<C, P> {
             client: None,
             state: Arc::new(NameServerState::init(None)),
             stats: Arc::new(NameServerStats::default()),
+            udp_payload: Arc::new(UdpPayload::new(
+                options.max_udp_payload,
+                options.udp_payload_downgrade,
+            )),
+            case_randomization: Arc::new(CaseRandomization::new(options.case_randomization)),
             conn_provider,
+            events: EventSender::default(),
+            down: Arc::new(AtomicBool::new(false)),
         }
     }
 
@@ -77,10 +93,29 @@ impl<C: DnsHandle, P: ConnectionProvider<ConnHandle = C>> NameServer<C, P> {
             client: Some(client),
             state: Arc::new(NameServerState::init(None)),
             stats: Arc::new(NameServerStats::default()),
+            udp_payload: Arc::new(UdpPayload::new(
+                options.max_udp_payload,
+                options.udp_payload_downgrade,
+            )),
+            case_randomization: Arc::new(CaseRandomization::new(options.case_randomization)),
             conn_provider,
+            events: EventSender::default(),
+            down: Arc::new(AtomicBool::new(false)),
         }
     }
 
+    /// Sends the events of this NameServer to the subscribers of the resolver
+    pub(crate) fn with_events(mut self, events: EventSender) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Returns a stream of the events of this NameServer, when it's down and up again
+    #[doc(hidden)]
+    pub fn events(&self) -> ResolverEvents {
+        self.events.subscribe()
+    }
+
     /// This will return a mutable client to allows for sending messages.
     ///
     /// If the connection is in a failed state, then this will establish a new connection
@@ -127,6 +162,40 @@ where
         let stats2 = self.stats.clone();
         let state1 = self.state.clone();
         let state2 = self.state.clone();
+        let (events1, events2) = (self.events.clone(), self.events.clone());
+        let (down1, down2) = (self.down.clone(), self.down.clone());
+        let (name_server, protocol) = (self.config.socket_addr, self.config.protocol);
+
+        // over UDP, advertise the payload which avoids fragmentation with this NameServer
+        let mut request: DnsRequest = request.into();
+        let udp_payload = if self.config.protocol.is_datagram() && request.edns().is_some() {
+            let requested = if request.options().edns.is_some() {
+                Some(request.max_payload())
+            } else {
+                None
+            };
+            let payload = self.udp_payload.payload_at(requested, clock::now());
+            request.edns_mut().set_max_payload(payload);
+            Some((self.udp_payload.clone(), payload))
+        } else {
+            None
+        };
+        let udp_payload1 = udp_payload.clone();
+
+        // 0x20 encoding, only UDP responses can be spoofed by an off-path attacker
+        let case_randomization =
+            if self.config.protocol == Protocol::Udp && self.case_randomization.is_enabled() {
+                Some(self.case_randomization.clone())
+            } else {
+                None
+            };
+
+        #[cfg(feature = "tracing-spans")]
+        ::tracing::debug!(
+            upstream = %self.config.socket_addr,
+            protocol = ?self.config.protocol,
+            "sending request"
+        );
 
         // if state is failed, return future::err(), unless retry delay expired...
         let client = match self.connected_mut_client() {
@@ -135,9 +204,18 @@ where
         };
 
         // Becuase a Poisoned lock error could have occured, make sure to create a new Mutex...
+        let response = match case_randomization {
+            Some(case_randomization) => Either::A(send_randomized(
+                client,
+                request,
+                case_randomization,
+                name_server,
+            )),
+            None => Either::B(client.send(request)),
+        };
+
         Box::new(
-            client
-                .send(request)
+            response
                 .and_then(move |response| {
                     // first we'll evaluate if the message succeeded
                     //   see https://github.com/bluejekyll/trust-dns/issues/606
@@ -160,20 +238,48 @@ where
                     // take the remote edns options and store them
                     state1.establish(remote_edns);
 
+                    if let Some((udp_payload, payload)) = udp_payload1 {
+                        udp_payload.response(payload);
+                    }
+
                     // record the success
                     stats1.next_success();
 
+                    if down1.swap(false, AtomicOrdering::SeqCst) {
+                        events1.send(|| ResolverEvent::NameServerUp {
+                            name_server,
+                            protocol,
+                        });
+                    }
+
                     future::ok(response)
                 })
                 .or_else(move |error| {
                     debug!("name_server connection failure: {}", error);
+                    #[cfg(feature = "tracing-spans")]
+                    ::tracing::debug!(error = %error, "upstream failed");
 
                     // this transitions the state to failure
-                    state2.fail(Instant::now());
+                    state2.fail(clock::now());
 
                     // recrod the failure
                     stats2.next_failure();
 
+                    if !down2.swap(true, AtomicOrdering::SeqCst) {
+                        events2.send(|| ResolverEvent::NameServerDown {
+                            name_server,
+                            protocol,
+                            error: error.clone(),
+                        });
+                    }
+
+                    // large responses which time out may have been fragmented
+                    if let (Some((udp_payload, payload)), ProtoErrorKind::Timeout) =
+                        (udp_payload, error.kind())
+                    {
+                        udp_payload.timeout(payload, clock::now());
+                    }
+
                     // These are connection failures, not lookup failures, that is handled in the resolver layer
                     future::err(error)
                 }),
@@ -5,17 +5,21 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use std::collections::VecDeque;
 use std::sync::{Arc, Mutex, TryLockError};
+use std::time::Duration;
 
 use futures::future::Loop;
 use futures::{future, task, Async, Future, IntoFuture, Poll};
 use smallvec::SmallVec;
+use tokio_timer::clock;
 
 use proto::error::ProtoError;
 use proto::op::ResponseCode;
-use proto::xfer::{DnsHandle, DnsRequest, DnsResponse};
+use proto::xfer::{BoxDelay, DelayProvider, DnsHandle, DnsRequest, DnsResponse, TokioDelay};
 
-use config::{ResolverConfig, ResolverOpts};
+use config::{ResolverConfig, ResolverOpts, TruncationStrategy};
+use events::EventSender;
 use name_server::{NameServer, ConnectionHandle, ConnectionProvider, StandardConnection};
 #[cfg(feature = "mdns")]
 use name_server;
@@ -32,11 +36,17 @@ pub struct NameServerPool<C: DnsHandle + 'static, P: ConnectionProvider<ConnHand
     mdns_conns: NameServer<C, P>, /* All NameServers must be the same type */
     options: ResolverOpts,
     conn_provider: P,
+    delay_provider: Arc<DelayProvider>,
 }
 
 impl NameServerPool<ConnectionHandle, StandardConnection> {
     pub(crate) fn from_config(config: &ResolverConfig, options: &ResolverOpts) -> Self {
-        Self::from_config_with_provider(config, options, StandardConnection)
+        Self::from_config_with_provider(
+            config,
+            options,
+            &EventSender::default(),
+            StandardConnection,
+        )
     }
 }
 
@@ -44,6 +54,7 @@ impl<C: DnsHandle + 'static, P: ConnectionProvider<ConnHandle = C> + 'static> Na
     pub(crate) fn from_config_with_provider(
         config: &ResolverConfig,
         options: &ResolverOpts,
+        events: &EventSender,
         conn_provider: P,
     ) -> NameServerPool<C, P> {
         let datagram_conns: Vec<NameServer<C, P>> = config
@@ -56,6 +67,7 @@ impl<C: DnsHandle + 'static, P: ConnectionProvider<ConnHandle = C> + 'static> Na
                     *options,
                     conn_provider.clone(),
                 )
+                .with_events(events.clone())
             }).collect();
 
         let stream_conns: Vec<NameServer<C, P>> = config
@@ -68,18 +80,27 @@ impl<C: DnsHandle + 'static, P: ConnectionProvider<ConnHandle = C> + 'static> Na
                     *options,
                     conn_provider.clone(),
                 )
+                .with_events(events.clone())
             }).collect();
 
         NameServerPool {
             datagram_conns: Arc::new(Mutex::new(datagram_conns)),
             stream_conns: Arc::new(Mutex::new(stream_conns)),
             #[cfg(feature = "mdns")]
-            mdns_conns: name_server::mdns_nameserver(*options, conn_provider.clone()),
+            mdns_conns: name_server::mdns_nameserver(*options, conn_provider.clone())
+                .with_events(events.clone()),
             options: *options,
             conn_provider,
+            delay_provider: Arc::new(TokioDelay),
         }
     }
 
+    /// Waits the hedge delay with the timers of the provider, Tokio by default
+    pub(crate) fn with_delay_provider(mut self, delay_provider: Arc<DelayProvider>) -> Self {
+        self.delay_provider = delay_provider;
+        self
+    }
+
     #[doc(hidden)]
     #[cfg(not(feature = "mdns"))]
     pub fn from_nameservers(
@@ -93,6 +114,7 @@ impl<C: DnsHandle + 'static, P: ConnectionProvider<ConnHandle = C> + 'static> Na
             stream_conns: Arc::new(Mutex::new(stream_conns.into_iter().collect())),
             options: *options,
             conn_provider,
+            delay_provider: Arc::new(TokioDelay),
         }
     }
 
@@ -111,6 +133,7 @@ impl<C: DnsHandle + 'static, P: ConnectionProvider<ConnHandle = C> + 'static> Na
             mdns_conns,
             options: *options,
             conn_provider,
+            delay_provider: Arc::new(TokioDelay),
         }
     }
 
@@ -118,13 +141,29 @@ impl<C: DnsHandle + 'static, P: ConnectionProvider<ConnHandle = C> + 'static> Na
         opts: ResolverOpts,
         conns: Arc<Mutex<Vec<NameServer<C, P>>>>,
         request: DnsRequest,
+        delay_provider: Arc<DelayProvider>,
     ) -> TrySend<C, P> {
         TrySend::Lock {
             opts,
             conns,
             request: Some(request),
+            delay_provider,
         }
     }
+
+    /// Sends the request over the stream connections, after a truncated response or a failure
+    ///  over the datagram connections
+    fn try_send_tcp(
+        opts: ResolverOpts,
+        conns: Arc<Mutex<Vec<NameServer<C, P>>>>,
+        request: DnsRequest,
+        delay_provider: Arc<DelayProvider>,
+    ) -> impl Future<Item = DnsResponse, Error = ProtoError> {
+        Self::try_send(opts, conns, request, delay_provider).map(|mut response| {
+            response.set_tcp_fallback(true);
+            response
+        })
+    }
 }
 
 impl<C, P> DnsHandle for NameServerPool<C, P>
@@ -138,11 +177,11 @@ where
         let opts = self.options;
         let request = request.into();
         let datagram_conns = Arc::clone(&self.datagram_conns);
-        let stream_conns1 = Arc::clone(&self.stream_conns);
-        let stream_conns2 = Arc::clone(&self.stream_conns);
+        let stream_conns = Arc::clone(&self.stream_conns);
+        let delay = Arc::clone(&self.delay_provider);
+        let tcp_delay = Arc::clone(&self.delay_provider);
         // TODO: remove this clone, return the Message in the error?
-        let tcp_message1 = request.clone();
-        let tcp_message2 = request.clone();
+        let tcp_message = request.clone();
 
         // if it's a .local. query, then we *only* query mDNS, these should never be sent on to upstream resolvers
         #[cfg(feature = "mdns")]
@@ -163,19 +202,40 @@ where
         let request = mdns.take_request();
         Box::new(
             // First try the UDP connections
-            Self::try_send(opts, datagram_conns, request)
-                .and_then(move |response| {
-                    // handling promotion from datagram to stream base on truncation in message
-                    if ResponseCode::NoError == response.response_code() && response.truncated() {
+            Self::try_send(opts, datagram_conns, request, delay).then(move |result| match result {
+                // handling promotion from datagram to stream base on truncation in message
+                Ok(ref response)
+                    if ResponseCode::NoError == response.response_code()
+                        && response.truncated()
+                        && opts.truncation_strategy != TruncationStrategy::Accept =>
+                {
+                    match opts.truncation_strategy {
                         // TCP connections should not truncate
-                        future::Either::A(Self::try_send(opts, stream_conns1, tcp_message1))
-                    } else {
-                        // Return the result from the UDP connection
-                        future::Either::B(future::ok(response))
+                        TruncationStrategy::SwitchToTcp => future::Either::A(Self::try_send_tcp(
+                            opts,
+                            stream_conns,
+                            tcp_message,
+                            tcp_delay,
+                        )),
+                        // retried by the RetryDnsHandle
+                        TruncationStrategy::Retry => future::Either::B(future::err(
+                            ProtoError::from("truncated response over UDP"),
+                        )),
+                        TruncationStrategy::Accept => {
+                            unreachable!("truncated responses are accepted by the next arm")
+                        }
                     }
-                })
+                }
+                // Return the result from the UDP connection
+                Ok(response) => future::Either::B(future::ok(response)),
                 // if UDP fails, try TCP
-                .or_else(move |_| Self::try_send(opts, stream_conns2, tcp_message2)),
+                Err(_) => future::Either::A(Self::try_send_tcp(
+                    opts,
+                    stream_conns,
+                    tcp_message,
+                    tcp_delay,
+                )),
+            }),
         )
     }
 }
@@ -186,6 +246,7 @@ enum TrySend<C: DnsHandle + 'static, P: ConnectionProvider<ConnHandle = C> + 'st
         opts: ResolverOpts,
         conns: Arc<Mutex<Vec<NameServer<C, P>>>>,
         request: Option<DnsRequest>,
+        delay_provider: Arc<DelayProvider>,
     },
     DoSend(Box<Future<Item = DnsResponse, Error = ProtoError> + Send>),
 }
@@ -200,13 +261,14 @@ where
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
         // TODO: this resolves an odd unsized issue with the loop_fn future
-        let future;
+        let future: Box<Future<Item = DnsResponse, Error = ProtoError> + Send>;
 
         match *self {
             TrySend::Lock {
                 ref opts,
                 ref conns,
                 ref mut request,
+                ref delay_provider,
             } => {
                 // pull a lock on the shared connections, lock releases at the end of the method
                 let conns = conns.try_lock();
@@ -234,49 +296,60 @@ where
                         let mut conns: Vec<NameServer<C, P>> = conns.clone();
                         let request = request.take();
                         let request = request.expect("bad state, mesage should never be None");
-                        let request_loop = request.clone();
 
-                        let loop_future = future::loop_fn(
-                            (
+                        if let Some(hedge_delay) = opts.hedge_delay {
+                            future = Box::new(HedgedSend::new(
                                 conns,
-                                request_loop,
-                                ProtoError::from("No connections available"),
-                            ),
-                            move |(mut conns, request, err)| {
-                                let request_cont = request.clone();
-
-                                // construct the parallel requests, 2 is the default
-                                let mut par_conns = SmallVec::<[NameServer<C, P>; 2]>::new();
-                                let count = conns.len().min(opts.num_concurrent_reqs.max(1));
-                                for conn in conns.drain(..count) {
-                                    par_conns.push(conn);
-                                }
-
-                                // construct the requests to send
-                                let requests = if par_conns.is_empty() {
-                                    None
-                                } else {
-                                    Some(
-                                        par_conns
-                                            .into_iter()
-                                            .map(move |mut conn| conn.send(request.clone())),
+                                request,
+                                hedge_delay,
+                                opts.num_concurrent_reqs.max(1),
+                                Arc::clone(delay_provider),
+                            ));
+                        } else {
+                            let request_loop = request.clone();
+
+                            let loop_future = future::loop_fn(
+                                (
+                                    conns,
+                                    request_loop,
+                                    ProtoError::from("No connections available"),
+                                ),
+                                move |(mut conns, request, err)| {
+                                    let request_cont = request.clone();
+
+                                    // construct the parallel requests, 2 is the default
+                                    let mut par_conns = SmallVec::<[NameServer<C, P>; 2]>::new();
+                                    let count = conns.len().min(opts.num_concurrent_reqs.max(1));
+                                    for conn in conns.drain(..count) {
+                                        par_conns.push(conn);
+                                    }
+
+                                    // construct the requests to send
+                                    let requests = if par_conns.is_empty() {
+                                        None
+                                    } else {
+                                        Some(
+                                            par_conns
+                                                .into_iter()
+                                                .map(move |mut conn| conn.send(request.clone())),
+                                        )
+                                    };
+
+                                    // execute all the requests
+                                    requests.ok_or_else(move || err).into_future().and_then(
+                                        |requests| {
+                                            futures::select_ok(requests)
+                                                .and_then(|(sent, _)| Ok(Loop::Break(sent)))
+                                                .or_else(move |err| {
+                                                    Ok(Loop::Continue((conns, request_cont, err)))
+                                                })
+                                        },
                                     )
-                                };
-
-                                // execute all the requests
-                                requests.ok_or_else(move || err).into_future().and_then(
-                                    |requests| {
-                                        futures::select_ok(requests)
-                                            .and_then(|(sent, _)| Ok(Loop::Break(sent)))
-                                            .or_else(move |err| {
-                                                Ok(Loop::Continue((conns, request_cont, err)))
-                                            })
-                                    },
-                                )
-                            },
-                        );
-
-                        future = Box::new(loop_future);
+                                },
+                            );
+
+                            future = Box::new(loop_future);
+                        }
                     }
                 }
             }
@@ -291,6 +364,102 @@ where
     }
 }
 
+/// Sends the request to the NameServers one after the other, the first response is used
+///
+/// The request is sent to the next NameServer once the hedge delay passes without a response, or
+///  immediately when all the requests sent have failed.
+#[must_use = "futures do nothing unless polled"]
+struct HedgedSend<C: DnsHandle + 'static, P: ConnectionProvider<ConnHandle = C> + 'static> {
+    conns: VecDeque<NameServer<C, P>>,
+    request: DnsRequest,
+    hedge_delay: Duration,
+    max_outstanding: usize,
+    delay_provider: Arc<DelayProvider>,
+    delay: Option<BoxDelay>,
+    outstanding: Vec<Box<Future<Item = DnsResponse, Error = ProtoError> + Send>>,
+    err: ProtoError,
+}
+
+impl<C, P> HedgedSend<C, P>
+where
+    C: DnsHandle + 'static,
+    P: ConnectionProvider<ConnHandle = C> + 'static,
+{
+    fn new(
+        conns: Vec<NameServer<C, P>>,
+        request: DnsRequest,
+        hedge_delay: Duration,
+        max_outstanding: usize,
+        delay_provider: Arc<DelayProvider>,
+    ) -> Self {
+        HedgedSend {
+            conns: conns.into(),
+            request,
+            hedge_delay,
+            max_outstanding,
+            delay_provider,
+            delay: None,
+            outstanding: Vec::with_capacity(max_outstanding),
+            err: ProtoError::from("No connections available"),
+        }
+    }
+}
+
+impl<C, P> Future for HedgedSend<C, P>
+where
+    C: DnsHandle + 'static,
+    P: ConnectionProvider<ConnHandle = C> + 'static,
+{
+    type Item = DnsResponse;
+    type Error = ProtoError;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            let mut i = 0;
+            while i < self.outstanding.len() {
+                match self.outstanding[i].poll() {
+                    Ok(Async::Ready(response)) => return Ok(Async::Ready(response)),
+                    Ok(Async::NotReady) => i += 1,
+                    Err(err) => {
+                        self.outstanding.swap_remove(i);
+                        self.err = err;
+                    }
+                }
+            }
+
+            if self.conns.is_empty() {
+                if self.outstanding.is_empty() {
+                    return Err(self.err.clone());
+                }
+                return Ok(Async::NotReady);
+            }
+
+            if !self.outstanding.is_empty() {
+                if self.outstanding.len() >= self.max_outstanding {
+                    return Ok(Async::NotReady);
+                }
+
+                if let Some(ref mut delay) = self.delay {
+                    match delay.poll() {
+                        Ok(Async::NotReady) => return Ok(Async::NotReady),
+                        Ok(Async::Ready(())) => (),
+                        Err(e) => {
+                            // hedging is only an optimization, wait for the outstanding requests
+                            warn!("hedge delay failed: {}", e);
+                            return Ok(Async::NotReady);
+                        }
+                    }
+                }
+            }
+
+            let mut conn = self.conns.pop_front().expect("conns is not empty");
+            debug!("sending request to the next name server");
+            self.outstanding.push(conn.send(self.request.clone()));
+            self.delay = Some(self.delay_provider.delay(clock::now() + self.hedge_delay));
+        }
+    }
+}
+
 #[cfg(feature = "mdns")]
 mod mdns {
     use super::*;
@@ -298,7 +467,7 @@ mod mdns {
     use proto::rr::domain::usage;
     use proto::DnsHandle;
 
-    /// Returns true
+    /// Returns a Local::ResolveFuture if the request is for a .local. name, sent over mDNS
     pub fn maybe_local<C, P>(name_server: &mut NameServer<C, P>, request: DnsRequest) -> Local
     where
         C: DnsHandle + 'static,
@@ -309,11 +478,29 @@ mod mdns {
             .iter()
             .any(|query| usage::LOCAL.name().zone_of(query.name()))
         {
-            Local::ResolveFuture(name_server.send(request))
+            Local::ResolveFuture(name_server.send(one_shot_request(request)))
         } else {
             Local::NotMdns(request)
         }
     }
+
+    /// Sets the flags of a one-shot mDNS query, [RFC 6762](https://tools.ietf.org/html/rfc6762#section-5.1)
+    ///
+    /// Recursion is not desired of the responders, and unicast responses are requested with the
+    ///  unicast-response bit of the queries, such that the responses are sent back to the port of
+    ///  the query rather than to the multicast group.
+    pub fn one_shot_request(request: DnsRequest) -> DnsRequest {
+        let (mut message, options) = request.unwrap();
+        let queries = message.take_queries();
+
+        message.set_recursion_desired(false);
+        for mut query in queries {
+            query.set_mdns_unicast_response(true);
+            message.add_query(query);
+        }
+
+        DnsRequest::new(message, options)
+    }
 }
 
 pub enum Local {
@@ -441,4 +628,28 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    #[cfg(feature = "mdns")]
+    fn test_mdns_one_shot_request() {
+        use proto::op::Message;
+        use proto::xfer::DnsRequest;
+
+        let mut message = Message::new();
+        message
+            .set_id(10)
+            .set_recursion_desired(true)
+            .add_query(Query::query(
+                Name::parse("host.local.", None).unwrap(),
+                RecordType::A,
+            ));
+
+        let request = mdns::one_shot_request(DnsRequest::new(message, Default::default()));
+
+        assert_eq!(request.id(), 10);
+        assert!(!request.recursion_desired());
+        assert_eq!(request.queries().len(), 1);
+        assert_eq!(request.queries()[0].name(), &Name::parse("host.local.", None).unwrap());
+        assert!(request.queries()[0].mdns_unicast_response());
+    }
 }
@@ -0,0 +1,261 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Blocking connections over the sockets of `std::net`, which don't require a reactor
+
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, UdpSocket};
+use std::time::{Duration, Instant};
+
+use futures::future::{self, FutureResult};
+use rand;
+use rand::distributions::{uniform::Uniform, Distribution, Standard};
+
+use proto::error::{ProtoError, ProtoErrorKind, ProtoResult};
+use proto::op::Message;
+use proto::xfer::{DnsHandle, DnsRequest, DnsResponse};
+
+use config::{NameServerConfig, Protocol, ResolverOpts};
+use name_server::ConnectionProvider;
+
+/// Makes connections which block on `std::net` sockets, the requests are sent when the
+///  `DnsHandle` is called and their responses are immediately ready.
+#[derive(Clone)]
+pub(crate) struct StdConnection;
+
+impl ConnectionProvider for StdConnection {
+    type ConnHandle = StdConnectionHandle;
+
+    fn new_connection(
+        &self,
+        config: &NameServerConfig,
+        options: &ResolverOpts,
+    ) -> Self::ConnHandle {
+        StdConnectionHandle {
+            socket_addr: config.socket_addr,
+            protocol: config.protocol,
+            timeout: options.timeout,
+        }
+    }
+}
+
+/// Sends each request on a new socket, waiting up to the timeout for its response
+#[derive(Clone)]
+pub(crate) struct StdConnectionHandle {
+    socket_addr: SocketAddr,
+    protocol: Protocol,
+    timeout: Duration,
+}
+
+impl DnsHandle for StdConnectionHandle {
+    type Response = FutureResult<DnsResponse, ProtoError>;
+
+    fn send<R: Into<DnsRequest>>(&mut self, request: R) -> Self::Response {
+        future::result(self.exchange(request.into()))
+    }
+}
+
+impl StdConnectionHandle {
+    #[allow(unreachable_patterns)]
+    fn exchange(&self, request: DnsRequest) -> ProtoResult<DnsResponse> {
+        let (mut message, _) = request.unwrap();
+
+        // each request is on its own socket, so the ID doesn't need to be globally unique
+        message.set_id(Standard.sample(&mut rand::thread_rng()));
+        let bytes = message.to_vec()?;
+
+        let response = match self.protocol {
+            Protocol::Udp => self.udp_exchange(&bytes, message.id(), false)?,
+            Protocol::Tcp => self.tcp_exchange(&bytes, message.id())?,
+            #[cfg(feature = "mdns")]
+            Protocol::Mdns => self.udp_exchange(&bytes, message.id(), true)?,
+            #[cfg(feature = "llmnr")]
+            Protocol::Llmnr => self.udp_exchange(&bytes, message.id(), true)?,
+            protocol => {
+                return Err(
+                    format!("{:?} is not supported by blocking connections", protocol).into(),
+                )
+            }
+        };
+
+        Ok(DnsResponse::from(response))
+    }
+
+    /// Sends the request, the response of a multicast request is accepted from any address
+    fn udp_exchange(&self, bytes: &[u8], id: u16, multicast: bool) -> ProtoResult<Message> {
+        let socket = bind_random_port(&self.socket_addr)?;
+        if multicast && self.socket_addr.is_ipv4() {
+            // multicast requests are link-local
+            socket.set_multicast_ttl_v4(1)?;
+        }
+
+        socket.send_to(bytes, self.socket_addr)?;
+
+        let deadline = Instant::now() + self.timeout;
+        // TODO: consider making this heap based? need to verify it matches EDNS settings
+        let mut buf = [0u8; 2048];
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(ProtoErrorKind::Timeout.into());
+            }
+
+            socket.set_read_timeout(Some(deadline - now))?;
+            let (len, src) = socket.recv_from(&mut buf).map_err(timeout_error)?;
+
+            if !multicast && src != self.socket_addr {
+                warn!(
+                    "ignoring response from {} because it does not match name_server: {}.",
+                    src, self.socket_addr
+                );
+                continue;
+            }
+
+            match Message::from_vec(&buf[..len]) {
+                Ok(message) if message.id() == id => return Ok(message),
+                Ok(message) => warn!(
+                    "ignoring response with id {} from {}, expected id: {}",
+                    message.id(),
+                    src,
+                    id
+                ),
+                Err(e) => warn!("ignoring malformed response from {}: {}", src, e),
+            }
+        }
+    }
+
+    fn tcp_exchange(&self, bytes: &[u8], id: u16) -> ProtoResult<Message> {
+        if bytes.len() > u16::MAX as usize {
+            return Err("message is too long for TCP".into());
+        }
+
+        let mut stream =
+            TcpStream::connect_timeout(&self.socket_addr, self.timeout).map_err(timeout_error)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+
+        // messages are prefixed with their length, see https://tools.ietf.org/html/rfc1035#section-4.2.2
+        let len = bytes.len() as u16;
+        stream
+            .write_all(&[(len >> 8) as u8, len as u8])
+            .and_then(|()| stream.write_all(bytes))
+            .map_err(timeout_error)?;
+
+        let mut len_bytes = [0u8; 2];
+        stream.read_exact(&mut len_bytes).map_err(timeout_error)?;
+        let len = (u16::from(len_bytes[0]) << 8 | u16::from(len_bytes[1])) as usize;
+
+        let mut buf = vec![0; len];
+        stream.read_exact(&mut buf).map_err(timeout_error)?;
+
+        let message = Message::from_vec(&buf)?;
+        if message.id() != id {
+            return Err(format!(
+                "response id {} does not match request id: {}",
+                message.id(),
+                id
+            )
+            .into());
+        }
+
+        Ok(message)
+    }
+}
+
+/// Binds a random port, like `UdpClientStream`, to reduce the chance of cache poisoning
+pub(super) fn bind_random_port(name_server: &SocketAddr) -> io::Result<UdpSocket> {
+    let zero_addr: IpAddr = match *name_server {
+        SocketAddr::V4(..) => IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)),
+        SocketAddr::V6(..) => IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0)),
+    };
+
+    let rand_port_range = Uniform::new_inclusive(1025_u16, u16::MAX);
+    let mut rand = rand::thread_rng();
+
+    for attempt in 0..10 {
+        let port = rand_port_range.sample(&mut rand);
+        match UdpSocket::bind(SocketAddr::new(zero_addr, port)) {
+            Ok(socket) => return Ok(socket),
+            Err(err) => debug!("unable to bind port, attempt: {}: {}", attempt, err),
+        }
+    }
+
+    // fallback to a port of the OS
+    UdpSocket::bind(SocketAddr::new(zero_addr, 0))
+}
+
+/// Socket timeouts are reported as `WouldBlock` on some platforms, and `TimedOut` on others
+fn timeout_error(e: io::Error) -> ProtoError {
+    match e.kind() {
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => ProtoErrorKind::Timeout.into(),
+        _ => e.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::UdpSocket;
+    use std::str::FromStr;
+    use std::thread;
+
+    use futures::Future;
+
+    use proto::op::{MessageType, Query};
+    use proto::rr::{Name, RecordType};
+
+    use super::*;
+
+    fn handle(socket_addr: SocketAddr, protocol: Protocol) -> StdConnectionHandle {
+        StdConnectionHandle {
+            socket_addr,
+            protocol,
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    #[test]
+    fn test_udp_exchange() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server_addr = server.local_addr().unwrap();
+
+        let responder = thread::spawn(move || {
+            let mut buf = [0u8; 512];
+            let (len, src) = server.recv_from(&mut buf).unwrap();
+            let mut message = Message::from_vec(&buf[..len]).unwrap();
+            message.set_message_type(MessageType::Response);
+
+            // a response with the wrong id is ignored
+            let id = message.id();
+            message.set_id(id.wrapping_add(1));
+            server.send_to(&message.to_vec().unwrap(), src).unwrap();
+            message.set_id(id);
+            server.send_to(&message.to_vec().unwrap(), src).unwrap();
+        });
+
+        let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+        let response = handle(server_addr, Protocol::Udp)
+            .lookup(query.clone(), Default::default())
+            .wait()
+            .unwrap();
+
+        assert_eq!(response.message_type(), MessageType::Response);
+        assert_eq!(response.queries(), &[query]);
+        responder.join().unwrap();
+    }
+
+    #[test]
+    fn test_udp_timeout() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut handle = handle(server.local_addr().unwrap(), Protocol::Udp);
+        handle.timeout = Duration::from_millis(50);
+
+        let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+        let error = handle.lookup(query, Default::default()).wait().unwrap_err();
+
+        assert_eq!(*error.kind(), ProtoErrorKind::Timeout);
+    }
+}
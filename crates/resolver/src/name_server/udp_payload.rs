@@ -0,0 +1,147 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The payload of responses which are never fragmented, see RFC 1035
+pub(crate) const MIN_PAYLOAD_LEN: u16 = 512;
+
+/// Consecutive timeouts of large queries after which the payload is downgraded
+const TIMEOUTS_BEFORE_DOWNGRADE: usize = 2;
+
+/// The UDP payload advertised in the EDNS of the queries to a NameServer
+///
+/// Responses larger than the MTU of the path are fragmented, and firewalls and NATs often drop
+///  fragments, those queries time out. After consecutive timeouts of queries advertising more
+///  than 512 bytes, 512 is advertised: the larger responses are truncated and retried over TCP.
+///  Once the downgrade delay has passed, the configured payload is probed again, a single timeout
+///  of the probe downgrades it again.
+pub(crate) struct UdpPayload {
+    max_payload: u16,
+    downgrade_delay: Option<Duration>,
+    state: Mutex<UdpPayloadState>,
+}
+
+#[derive(Default)]
+struct UdpPayloadState {
+    /// consecutive timeouts of queries advertising more than 512 bytes
+    timeouts: usize,
+    /// the end of the downgrade, the payload is probed after it
+    downgraded_until: Option<Instant>,
+}
+
+impl UdpPayload {
+    /// # Arguments
+    ///
+    /// * `max_payload` - the payload advertised when not downgraded, at least 512
+    /// * `downgrade_delay` - how long the payload stays downgraded, None to never downgrade it
+    pub fn new(max_payload: u16, downgrade_delay: Option<Duration>) -> Self {
+        UdpPayload {
+            max_payload: max_payload.max(MIN_PAYLOAD_LEN),
+            downgrade_delay,
+            state: Mutex::new(UdpPayloadState::default()),
+        }
+    }
+
+    /// The payload to advertise in a query sent at `now`
+    ///
+    /// `requested` is the payload of a query configured with its own EDNS, otherwise the
+    ///  configured payload is advertised. Both are downgraded to 512.
+    pub fn payload_at(&self, requested: Option<u16>, now: Instant) -> u16 {
+        let state = self.state.lock().expect("udp payload poisoned");
+        match state.downgraded_until {
+            Some(until) if now < until => MIN_PAYLOAD_LEN,
+            _ => requested.unwrap_or(self.max_payload),
+        }
+    }
+
+    /// Records a response to a query which advertised `payload`
+    pub fn response(&self, payload: u16) {
+        if payload <= MIN_PAYLOAD_LEN {
+            return;
+        }
+
+        let mut state = self.state.lock().expect("udp payload poisoned");
+        if state.downgraded_until.is_some() {
+            debug!("udp payload of {} probed successfully", payload);
+        }
+        state.timeouts = 0;
+        state.downgraded_until = None;
+    }
+
+    /// Records the timeout at `now` of a query which advertised `payload`
+    pub fn timeout(&self, payload: u16, now: Instant) {
+        let delay = match self.downgrade_delay {
+            Some(delay) if payload > MIN_PAYLOAD_LEN => delay,
+            _ => return,
+        };
+
+        let mut state = self.state.lock().expect("udp payload poisoned");
+        state.timeouts += 1;
+
+        // a probe after a downgrade is not given a second chance
+        if state.timeouts >= TIMEOUTS_BEFORE_DOWNGRADE || state.downgraded_until.is_some() {
+            debug!(
+                "udp payload of {} downgraded to {} for {:?} after {} timeouts",
+                payload, MIN_PAYLOAD_LEN, delay, state.timeouts
+            );
+            state.timeouts = 0;
+            state.downgraded_until = Some(now + delay);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_downgrade_and_probe() {
+        let delay = Duration::from_secs(60);
+        let payload = UdpPayload::new(1232, Some(delay));
+        let now = Instant::now();
+        assert_eq!(payload.payload_at(None, now), 1232);
+        assert_eq!(payload.payload_at(Some(4096), now), 4096);
+
+        // a single timeout can be a lost packet
+        payload.timeout(1232, now);
+        assert_eq!(payload.payload_at(None, now), 1232);
+        payload.timeout(1232, now);
+        assert_eq!(payload.payload_at(None, now), 512);
+        assert_eq!(payload.payload_at(Some(4096), now), 512);
+
+        // timeouts of small queries are not related to the payload
+        payload.timeout(512, now);
+        payload.response(512);
+        assert_eq!(payload.payload_at(None, now + delay / 2), 512);
+
+        // the probe fails
+        let probe = now + delay;
+        assert_eq!(payload.payload_at(None, probe), 1232);
+        payload.timeout(1232, probe);
+        assert_eq!(payload.payload_at(None, probe), 512);
+
+        // the next one succeeds
+        let probe = probe + delay;
+        assert_eq!(payload.payload_at(None, probe), 1232);
+        payload.response(1232);
+        payload.timeout(1232, probe);
+        assert_eq!(payload.payload_at(None, probe), 1232);
+    }
+
+    #[test]
+    fn test_no_downgrade() {
+        let payload = UdpPayload::new(4096, None);
+        let now = Instant::now();
+
+        for _ in 0..TIMEOUTS_BEFORE_DOWNGRADE * 2 {
+            payload.timeout(4096, now);
+        }
+        assert_eq!(payload.payload_at(None, now), 4096);
+    }
+}
@@ -10,14 +10,17 @@ use std::io;
 use std::net::IpAddr;
 use std::sync::Mutex;
 
-use proto::rr::RecordType;
+use proto::rr::{Name, RecordType};
 use tokio::runtime::{self, Runtime};
 
 use config::{ResolverConfig, ResolverOpts};
+use dns_lru::{CacheEntry, CacheStats};
 use error::*;
+use events::ResolverEvents;
 use lookup;
 use lookup::Lookup;
 use lookup_ip::LookupIp;
+use validation_exceptions::ValidationStats;
 use AsyncResolver;
 
 /// The Resolver is used for performing DNS queries.
@@ -160,6 +163,36 @@ impl Resolver {
         self.runtime.lock()?.block_on(lookup)
     }
 
+    /// A snapshot of the entries of the cache, see `AsyncResolver::cache_entries`
+    pub fn cache_entries(&self) -> Vec<CacheEntry> {
+        self.async_resolver.cache_entries()
+    }
+
+    /// The statistics of the cache, see `AsyncResolver::cache_stats`
+    pub fn cache_stats(&self) -> CacheStats {
+        self.async_resolver.cache_stats()
+    }
+
+    /// The queries which were not validated so far, see `AsyncResolver::validation_stats`
+    pub fn validation_stats(&self) -> ValidationStats {
+        self.async_resolver.validation_stats()
+    }
+
+    /// Removes the cached answers for the names under the suffix, see `AsyncResolver::flush_cache`
+    pub fn flush_cache(&self, suffix: &Name, record_type: Option<RecordType>) -> usize {
+        self.async_resolver.flush_cache(suffix, record_type)
+    }
+
+    /// Replaces the configuration of the resolver, see `AsyncResolver::reload`
+    pub fn reload(&self, config: ResolverConfig, options: ResolverOpts) {
+        self.async_resolver.reload(config, options)
+    }
+
+    /// Returns a stream of the events of the resolver, see `AsyncResolver::events`
+    pub fn events(&self) -> ResolverEvents {
+        self.async_resolver.events()
+    }
+
     lookup_fn!(reverse_lookup, lookup::ReverseLookup, IpAddr);
     lookup_fn!(ipv4_lookup, lookup::Ipv4Lookup);
     lookup_fn!(ipv6_lookup, lookup::Ipv6Lookup);
@@ -0,0 +1,135 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The runtime specific parts of the `AsyncResolver`
+//!
+//! The `AsyncResolver` is generic over its `RuntimeProvider`, which creates the connections to the
+//!  name servers, spawns their background work, and provides the timers. `TokioRuntime` is the
+//!  default. Another runtime implements `ConnectionProvider` for its sockets, the handles of the
+//!  connections are `DnsHandle`s, e.g. with the `DnsExchange` of a `DnsRequestSender` over them.
+//!
+//! With the `async-std` feature, `AsyncStdRuntime` runs the `AsyncResolver` on async-std, see
+//!  its documentation. Other runtimes, e.g. smol, are not provided by this crate.
+
+#[cfg(feature = "async-std")]
+use std::time::Duration;
+use std::time::Instant;
+
+#[cfg(feature = "async-std")]
+use async_std;
+use futures::{future, Future};
+#[cfg(feature = "async-std")]
+use futures03::compat::{Compat, Future01CompatExt};
+#[cfg(feature = "async-std")]
+use futures03::future::{BoxFuture, FutureExt};
+use tokio_executor::{DefaultExecutor, Executor};
+use tokio_timer::{self, Delay};
+
+use proto::error::{ProtoError, ProtoResult};
+use proto::xfer::{BoxDelay, DelayProvider};
+
+#[cfg(feature = "async-std")]
+use name_server::AsyncStdConnection;
+use name_server::{ConnectionProvider, StandardConnection};
+
+/// The connections, background tasks and timers of a runtime
+pub trait RuntimeProvider: ConnectionProvider {
+    /// A future which is ready at its deadline, see `delay`
+    type Delay: Future<Item = (), Error = ProtoError> + Send + 'static;
+
+    /// Spawns the background work of a connection on the runtime, e.g. its `DnsExchange`
+    fn spawn_bg<F>(&self, future: F) -> ProtoResult<()>
+    where
+        F: Future<Item = (), Error = ()> + Send + 'static;
+
+    /// Returns a future which is ready at `deadline`
+    fn delay(&self, deadline: Instant) -> Self::Delay;
+}
+
+/// The timers of the runtime for the retries, the hedging and the timeouts of the lookups
+pub(crate) struct RuntimeDelay<R: RuntimeProvider>(pub(crate) R);
+
+impl<R: RuntimeProvider> DelayProvider for RuntimeDelay<R> {
+    fn delay(&self, deadline: Instant) -> BoxDelay {
+        Box::new(self.0.delay(deadline))
+    }
+}
+
+/// The Tokio runtime, with the UDP, TCP and other streams of `trust-dns-proto`
+pub use name_server::StandardConnection as TokioRuntime;
+
+impl RuntimeProvider for StandardConnection {
+    type Delay = future::MapErr<Delay, fn(tokio_timer::Error) -> ProtoError>;
+
+    fn spawn_bg<F>(&self, future: F) -> ProtoResult<()>
+    where
+        F: Future<Item = (), Error = ()> + Send + 'static,
+    {
+        DefaultExecutor::current()
+            .spawn(Box::new(future))
+            .map_err(ProtoError::from)
+    }
+
+    fn delay(&self, deadline: Instant) -> Self::Delay {
+        Delay::new(deadline).map_err(ProtoError::from as fn(tokio_timer::Error) -> ProtoError)
+    }
+}
+
+/// The async-std runtime, with UDP and TCP connections over the sockets of its reactor
+///
+/// The futures of the `AsyncResolver` are futures 0.1, they are run by async-std through the
+///  `compat` layer of futures 0.3, e.g. `async_std::task::spawn(background.compat())`. The
+///  delays of the retries, the hedging and the family timeouts are `async_std::task::sleep`.
+///
+/// ```
+/// # extern crate async_std;
+/// # extern crate futures03;
+/// # extern crate trust_dns_resolver;
+/// use futures03::compat::Future01CompatExt;
+/// use futures03::future::FutureExt;
+/// use trust_dns_resolver::config::{ResolverConfig, ResolverOpts};
+/// use trust_dns_resolver::{AsyncResolver, AsyncStdRuntime};
+///
+/// # fn main() {
+/// let (resolver, background) = AsyncResolver::with_runtime(
+///     ResolverConfig::default(),
+///     ResolverOpts::default(),
+///     AsyncStdRuntime,
+/// );
+/// async_std::task::spawn(background.compat().map(|_| ()));
+///
+/// # let _ = || {
+/// let response = async_std::task::block_on(resolver.lookup_ip("www.example.com.").compat());
+/// # };
+/// # }
+/// ```
+#[cfg(feature = "async-std")]
+pub use name_server::AsyncStdConnection as AsyncStdRuntime;
+
+#[cfg(feature = "async-std")]
+impl RuntimeProvider for AsyncStdConnection {
+    type Delay = Compat<BoxFuture<'static, ProtoResult<()>>>;
+
+    fn spawn_bg<F>(&self, future: F) -> ProtoResult<()>
+    where
+        F: Future<Item = (), Error = ()> + Send + 'static,
+    {
+        async_std::task::spawn(future.compat().map(|_| ()));
+        Ok(())
+    }
+
+    fn delay(&self, deadline: Instant) -> Self::Delay {
+        let now = Instant::now();
+        let duration = if deadline > now {
+            deadline - now
+        } else {
+            Duration::from_secs(0)
+        };
+
+        Compat::new(async_std::task::sleep(duration).map(Ok).boxed())
+    }
+}
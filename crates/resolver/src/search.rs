@@ -0,0 +1,329 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Canonicalization of the names to lookup into the candidate names to query
+//!
+//! The candidates are generated like `getaddrinfo` does, from the search list and domain of the
+//!  `ResolverConfig`, the `ndots` of the `ResolverOpts`, and the host aliases file. Which of the
+//!  candidates resolved is the name of the `query()` of the `Lookup` or `LookupIp`.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+use std::str::FromStr;
+
+use proto::rr::Name;
+
+use config::{ResolverConfig, ResolverOpts};
+
+/// The environment variable with the path of the host aliases file, see `hostname(7)`
+pub const HOSTALIASES: &str = "HOSTALIASES";
+
+/// Aliases of single label names, from the host aliases file
+///
+/// Lines of the file have the form `alias name`, e.g. `printer printer-3.office.example.com`. An
+///  alias is only matched by a single label name, which isn't fully qualified. The name of the
+///  alias is then the only candidate, it's queried as an FQDN without the search list.
+#[derive(Clone, Debug, Default)]
+pub struct Aliases {
+    by_alias: HashMap<Name, Name>,
+}
+
+impl Aliases {
+    /// Reads the aliases from the file of the `HOSTALIASES` environment variable,
+    /// the aliases are empty if it's not set or the file can't be read
+    pub fn new() -> Self {
+        env::var_os(HOSTALIASES)
+            .and_then(|path| match read_aliases_conf(&path) {
+                Ok(aliases) => Some(aliases),
+                Err(e) => {
+                    warn!("could not read host aliases from {:?}: {}", path, e);
+                    None
+                }
+            })
+            .unwrap_or_default()
+    }
+
+    /// Adds the alias of `name`, replacing any previous one
+    pub fn insert(&mut self, alias: Name, mut name: Name) {
+        name.set_fqdn(true);
+        self.by_alias.insert(Self::key(alias), name);
+    }
+
+    /// Returns the name of the alias, if `alias` is a single label name which isn't fully qualified
+    pub fn lookup(&self, alias: &Name) -> Option<&Name> {
+        if alias.is_fqdn() || alias.num_labels() != 1 {
+            return None;
+        }
+
+        self.by_alias.get(&Self::key(alias.clone()))
+    }
+
+    fn key(mut alias: Name) -> Name {
+        alias.set_fqdn(false);
+        alias
+    }
+}
+
+/// parse the host aliases from `path`
+pub fn read_aliases_conf<P: AsRef<Path>>(path: P) -> io::Result<Aliases> {
+    let mut aliases = Aliases::default();
+
+    // lines in the file should have the form `alias name`,
+    // anything following a `#` is a comment, empty and malformed lines are ignored
+    let file = File::open(path)?;
+
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let fields: Vec<_> = line.split_whitespace().collect();
+        if fields.len() != 2 {
+            warn!("could not parse host alias: {}", line);
+            continue;
+        }
+
+        match (Name::from_str(fields[0]), Name::from_str(fields[1])) {
+            (Ok(alias), Ok(name)) => aliases.insert(alias, name),
+            _ => warn!("could not parse host alias: {}", line),
+        }
+    }
+
+    Ok(aliases)
+}
+
+/// Returns the names to query for `name`, in the order in which they are tried
+///
+/// * an FQDN, i.e. ending with a final `.`, is the only candidate
+/// * a single label name with an alias, the name of the alias is the only candidate
+/// * a name with more labels than `ndots` is tried as is first, otherwise last
+/// * the name appended with the domain, and then each of the search list
+/// * `search_dotted_names`, `max_candidate_names` and `absolute_first` of the `ResolverOpts`
+///   restrict or reorder those
+///
+/// # Arguments
+///
+/// * `name` - the name to lookup
+/// * `config` - the domain and search list
+/// * `options` - the `ndots`, and the other search options
+/// * `aliases` - the host aliases, if they are used
+pub fn candidate_names(
+    name: &Name,
+    config: &ResolverConfig,
+    options: &ResolverOpts,
+    aliases: Option<&Aliases>,
+) -> Vec<Name> {
+    let mut names = search_stack(name, config, options, aliases);
+    names.reverse();
+    names
+}
+
+/// The candidate names, in reverse order of precedence for stack semantics, i.e. the first is `names.pop()`
+pub(crate) fn search_stack(
+    name: &Name,
+    config: &ResolverConfig,
+    options: &ResolverOpts,
+    aliases: Option<&Aliases>,
+) -> Vec<Name> {
+    // if it's fully qualified, we can short circuit the lookup logic
+    if name.is_fqdn() {
+        return vec![name.clone()];
+    }
+
+    if let Some(alias) = aliases.and_then(|aliases| aliases.lookup(name)) {
+        debug!("using host alias {} for {}", alias, name);
+        return vec![alias.clone()];
+    }
+
+    // a name with a dot might only be queried as is, without the search list
+    if !options.search_dotted_names && name.num_labels() > 1 {
+        return vec![name.clone()];
+    }
+
+    // Otherwise we have to build the search list
+    let mut search = Vec::<Name>::with_capacity(1 /*DOMAIN*/ + config.search().len());
+
+    for name_search in config.search().iter().rev() {
+        let name_search = name.clone().append_domain(name_search);
+        push_name(name_search, &mut search);
+    }
+
+    if let Some(domain) = config.domain() {
+        let name_search = name.clone().append_domain(domain);
+        push_name(name_search, &mut search);
+    }
+
+    // the last of the search list are dropped to make room for the raw name
+    if let Some(max_candidate_names) = options.max_candidate_names {
+        let max_search = max_candidate_names.saturating_sub(1);
+        if search.len() > max_search {
+            let dropped = search.len() - max_search;
+            search.drain(..dropped);
+        }
+    }
+
+    // unless absolute first, if not meeting ndots the raw name is the final lookup, or it's a localhost...
+    let raw_name_first: bool =
+        options.absolute_first || name.num_labels() as usize > options.ndots || name.is_localhost();
+
+    let mut names = Vec::<Name>::with_capacity(1 /*FQDN*/ + search.len());
+
+    // if not meeting ndots, we always do the raw name in the final lookup
+    if !raw_name_first {
+        names.push(name.clone());
+    }
+
+    names.extend(search);
+
+    // this is the direct name lookup
+    if raw_name_first {
+        // adding the name as though it's an FQDN for lookup
+        names.push(name.clone());
+    }
+
+    names
+}
+
+fn push_name(name: Name, names: &mut Vec<Name>) {
+    if !names.contains(&name) {
+        names.push(name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tests_dir() -> String {
+        let server_path = env::var("TDNS_SERVER_SRC_ROOT").unwrap_or_else(|_| ".".to_owned());
+        format! {"{}/../resolver/tests", server_path}
+    }
+
+    fn name(name: &str) -> Name {
+        Name::from_str(name).unwrap()
+    }
+
+    #[test]
+    fn test_read_aliases_conf() {
+        let aliases = read_aliases_conf(format!("{}/host.aliases", tests_dir())).unwrap();
+
+        assert_eq!(
+            aliases.lookup(&name("printer")),
+            Some(&name("printer-3.office.example.com."))
+        );
+        assert_eq!(
+            aliases.lookup(&name("wiki")),
+            Some(&name("wiki.example.com."))
+        );
+        assert_eq!(aliases.lookup(&name("printer.")), None);
+        assert_eq!(aliases.lookup(&name("broken")), None);
+    }
+
+    #[test]
+    fn test_candidate_names() {
+        let mut config = ResolverConfig::new();
+        config.set_domain(name("example.com."));
+        config.add_search(name("corp.example.com."));
+        let options = ResolverOpts::default();
+
+        assert_eq!(
+            candidate_names(&name("www.example.com."), &config, &options, None),
+            vec![name("www.example.com.")]
+        );
+        assert_eq!(
+            candidate_names(&name("www"), &config, &options, None),
+            vec![
+                name("www.example.com."),
+                name("www.corp.example.com."),
+                name("www")
+            ]
+        );
+        assert_eq!(
+            candidate_names(&name("www.example"), &config, &options, None),
+            vec![
+                name("www.example"),
+                name("www.example.example.com."),
+                name("www.example.corp.example.com."),
+            ]
+        );
+
+        let mut aliases = Aliases::default();
+        aliases.insert(name("www"), name("web.example.net"));
+        assert_eq!(
+            candidate_names(&name("www"), &config, &options, Some(&aliases)),
+            vec![name("web.example.net.")]
+        );
+        assert_eq!(
+            candidate_names(&name("www.example"), &config, &options, Some(&aliases)).len(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_candidate_names_kubernetes() {
+        let mut config = ResolverConfig::new();
+        config.add_search(name("default.svc.cluster.local."));
+        config.add_search(name("svc.cluster.local."));
+        config.add_search(name("cluster.local."));
+        let mut options = ResolverOpts {
+            ndots: 5,
+            ..ResolverOpts::default()
+        };
+
+        assert_eq!(
+            candidate_names(&name("www.example.com"), &config, &options, None),
+            vec![
+                name("www.example.com.default.svc.cluster.local."),
+                name("www.example.com.svc.cluster.local."),
+                name("www.example.com.cluster.local."),
+                name("www.example.com"),
+            ]
+        );
+
+        options.absolute_first = true;
+        assert_eq!(
+            candidate_names(&name("www.example.com"), &config, &options, None)[0],
+            name("www.example.com")
+        );
+
+        options.absolute_first = false;
+        options.max_candidate_names = Some(2);
+        assert_eq!(
+            candidate_names(&name("www.example.com"), &config, &options, None),
+            vec![
+                name("www.example.com.default.svc.cluster.local."),
+                name("www.example.com"),
+            ]
+        );
+        options.max_candidate_names = Some(0);
+        assert_eq!(
+            candidate_names(&name("www.example.com"), &config, &options, None),
+            vec![name("www.example.com")]
+        );
+
+        options.max_candidate_names = None;
+        options.search_dotted_names = false;
+        assert_eq!(
+            candidate_names(&name("www.example.com"), &config, &options, None),
+            vec![name("www.example.com")]
+        );
+        assert_eq!(
+            candidate_names(&name("kubernetes"), &config, &options, None),
+            vec![
+                name("kubernetes.default.svc.cluster.local."),
+                name("kubernetes.svc.cluster.local."),
+                name("kubernetes.cluster.local."),
+                name("kubernetes"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,278 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Structs for creating and using a StdResolver
+use std::fmt;
+use std::io;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+
+use futures::Future;
+use proto::rr::domain::TryParseIp;
+use proto::rr::{IntoName, Name, RecordType};
+use proto::xfer::{DnsRequestOptions, TokioDelay};
+use tokio_timer::clock;
+
+use config::{ResolverConfig, ResolverOpts};
+use dns_lru::{self, CacheEntry, CacheStats, DnsLru};
+use error::*;
+use events::{ResolverEvent, ResolverEvents};
+use lookup::{self, Lookup};
+use lookup_context::LookupContext;
+use lookup_ip::LookupIp;
+use name_server::{StdConnection, StdConnectionHandle};
+use validation_exceptions::ValidationStats;
+
+/// A blocking Resolver for DNS records, which doesn't require an async runtime.
+///
+/// Requests are sent over the blocking sockets of `std::net` on the calling thread, each of them
+///  waits up to the `timeout` of the `ResolverOpts`. Unlike the `Resolver`, no reactor or
+///  background thread is started, it's meant for small tools and build scripts.
+///
+/// DNS over TLS and HTTPS are not supported, name servers with those protocols fail all requests.
+pub struct StdResolver {
+    context: LookupContext<StdConnectionHandle, StdConnection>,
+    lru: Arc<Mutex<DnsLru>>,
+}
+
+macro_rules! lookup_fn {
+    ($p:ident, $l:ty, $r:path) => {
+        /// Performs a lookup for the associated type.
+        ///
+        /// *hint* queries that end with a '.' are fully qualified names and are cheaper lookups
+        ///
+        /// # Arguments
+        ///
+        /// * `query` - a string which parses to a domain name, failure to parse will return an error
+        pub fn $p<N: IntoName>(&self, query: N) -> ResolveResult<$l> {
+            let name = query.into_name()?;
+            self.inner_lookup(name, $r).map(<$l>::from)
+        }
+    };
+    ($p:ident, $l:ty, $r:path, $t:ty) => {
+        /// Performs a lookup for the associated type.
+        ///
+        /// # Arguments
+        ///
+        /// * `query` - a type which can be converted to `Name` via `From`.
+        pub fn $p(&self, query: $t) -> ResolveResult<$l> {
+            let name = Name::from(query);
+            self.inner_lookup(name, $r).map(<$l>::from)
+        }
+    };
+}
+
+impl StdResolver {
+    /// Constructs a new StdResolver with the specified configuration.
+    ///
+    /// # Arguments
+    /// * `config` - configuration for the resolver
+    /// * `options` - resolver options for performing lookups
+    pub fn new(config: ResolverConfig, options: ResolverOpts) -> Self {
+        let lru = DnsLru::new(
+            options.cache_size,
+            dns_lru::TtlConfig::new(&config, &options),
+        )
+        .with_max_bytes(options.cache_max_bytes);
+        let lru = Arc::new(Mutex::new(lru));
+
+        StdResolver {
+            context: LookupContext::new(
+                config,
+                options,
+                Arc::clone(&lru),
+                StdConnection,
+                Arc::new(TokioDelay),
+            ),
+            lru,
+        }
+    }
+
+    /// Constructs a new StdResolver with the system configuration.
+    ///
+    /// This will use `/etc/resolv.conf` on Unix OSes and the registry on Windows.
+    #[cfg(any(unix, target_os = "windows"))]
+    pub fn from_system_conf() -> io::Result<Self> {
+        let (config, options) = super::system_conf::read_system_conf()?;
+        Ok(Self::new(config, options))
+    }
+
+    /// Generic lookup for any RecordType
+    ///
+    /// *WARNING* this interface may change in the future, please use `lookup_ip` or another variant for more stable interfaces.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - name of the record to lookup, if name is not a valid domain name, an error will be returned
+    /// * `record_type` - type of record to lookup
+    pub fn lookup<N: IntoName>(&self, name: N, record_type: RecordType) -> ResolveResult<Lookup> {
+        let name = name.into_name()?;
+        self.inner_lookup(name, record_type)
+    }
+
+    /// Generic lookup for any RecordType, with the EDNS and other options of the queries
+    ///
+    /// See `AsyncResolver::lookup_with_options`
+    pub fn lookup_with_options<N: IntoName>(
+        &self,
+        name: N,
+        record_type: RecordType,
+        options: DnsRequestOptions,
+    ) -> ResolveResult<Lookup> {
+        let name = name.into_name()?;
+        self.context.lookup(name, record_type, options).wait()
+    }
+
+    /// A snapshot of the entries of the cache, see `AsyncResolver::cache_entries`
+    pub fn cache_entries(&self) -> Vec<CacheEntry> {
+        self.lru
+            .lock()
+            .expect("cache poisoned")
+            .entries(clock::now())
+    }
+
+    /// The statistics of the cache, see `AsyncResolver::cache_stats`
+    pub fn cache_stats(&self) -> CacheStats {
+        self.lru.lock().expect("cache poisoned").stats()
+    }
+
+    /// The queries which were not validated so far, see `AsyncResolver::validation_stats`
+    pub fn validation_stats(&self) -> ValidationStats {
+        self.lru.lock().expect("cache poisoned").validation_stats()
+    }
+
+    /// Removes the cached answers for the names under the suffix, see `AsyncResolver::flush_cache`
+    pub fn flush_cache(&self, suffix: &Name, record_type: Option<RecordType>) -> usize {
+        self.lru
+            .lock()
+            .expect("cache poisoned")
+            .flush(suffix, record_type)
+    }
+
+    /// Replaces the configuration of the resolver, see `AsyncResolver::reload`
+    pub fn reload(&mut self, config: ResolverConfig, options: ResolverOpts) {
+        let name_servers = config
+            .name_servers()
+            .iter()
+            .map(|name_server| name_server.socket_addr)
+            .collect::<Vec<_>>();
+
+        {
+            let mut lru = self.lru.lock().expect("cache poisoned");
+            lru.set_ttl_config(dns_lru::TtlConfig::new(&config, &options));
+            lru.set_max_bytes(options.cache_max_bytes);
+        }
+        self.context = LookupContext::new(
+            config,
+            options,
+            Arc::clone(&self.lru),
+            StdConnection,
+            Arc::new(TokioDelay),
+        );
+
+        self.lru
+            .lock()
+            .expect("cache poisoned")
+            .events()
+            .send(|| ResolverEvent::ConfigReload { name_servers });
+    }
+
+    /// Returns a stream of the events of the resolver, see `AsyncResolver::events`
+    ///
+    /// Without a runtime, the events may be received with `Stream::wait`.
+    pub fn events(&self) -> ResolverEvents {
+        self.lru
+            .lock()
+            .expect("cache poisoned")
+            .events()
+            .subscribe()
+    }
+
+    fn inner_lookup(&self, name: Name, record_type: RecordType) -> ResolveResult<Lookup> {
+        self.context
+            .lookup(name, record_type, DnsRequestOptions::default())
+            .wait()
+    }
+
+    /// Performs a dual-stack DNS lookup for the IP for the given hostname.
+    ///
+    /// See the configuration and options parameters for controlling the way in which A(Ipv4) and AAAA(Ipv6) lookups will be performed. For the least expensive query a fully-qualified-domain-name, FQDN, which ends in a final `.`, e.g. `www.example.com.`, will only issue one query. Anything else will always incur the cost of querying the `ResolverConfig::domain` and `ResolverConfig::search`.
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - string hostname, if this is an invalid hostname, an error will be returned.
+    pub fn lookup_ip<N: IntoName + TryParseIp>(&self, host: N) -> ResolveResult<LookupIp> {
+        let maybe_ip = host.try_parse_ip();
+        self.context.lookup_ip(host.into_name(), maybe_ip).wait()
+    }
+
+    /// Lookup an SRV record.
+    pub fn lookup_srv<N: IntoName>(&self, name: N) -> ResolveResult<lookup::SrvLookup> {
+        let name = name.into_name()?;
+        self.inner_lookup(name, RecordType::SRV)
+            .map(lookup::SrvLookup::from)
+    }
+
+    lookup_fn!(
+        reverse_lookup,
+        lookup::ReverseLookup,
+        RecordType::PTR,
+        IpAddr
+    );
+    lookup_fn!(ipv4_lookup, lookup::Ipv4Lookup, RecordType::A);
+    lookup_fn!(ipv6_lookup, lookup::Ipv6Lookup, RecordType::AAAA);
+    lookup_fn!(mx_lookup, lookup::MxLookup, RecordType::MX);
+    lookup_fn!(txt_lookup, lookup::TxtLookup, RecordType::TXT);
+}
+
+impl Default for StdResolver {
+    /// Constructs a new StdResolver with default config and default options.
+    ///
+    /// See [`ResolverConfig::default`] and [`ResolverOpts::default`] for more information.
+    fn default() -> Self {
+        Self::new(ResolverConfig::default(), ResolverOpts::default())
+    }
+}
+
+impl fmt::Debug for StdResolver {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StdResolver").finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::*;
+
+    use super::*;
+
+    fn require_send_sync<S: Send + Sync>() {}
+
+    #[test]
+    fn test_std_resolver_sendable() {
+        require_send_sync::<StdResolver>();
+    }
+
+    #[test]
+    fn test_lookup_without_runtime() {
+        let resolver = StdResolver::default();
+
+        let response = resolver.lookup_ip("localhost.").unwrap();
+        assert_eq!(
+            response.iter().next(),
+            Some(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)))
+        );
+
+        let response = resolver.lookup_ip("10.1.0.2").unwrap();
+        assert_eq!(
+            response.iter().collect::<Vec<_>>(),
+            vec![IpAddr::V4(Ipv4Addr::new(10, 1, 0, 2))]
+        );
+
+        assert!(resolver.lookup_ip("www.example.com.invalid.").is_err());
+    }
+}
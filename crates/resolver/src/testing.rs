@@ -0,0 +1,394 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! A deterministic simulation of the resolver, with virtual time and a virtual network
+//!
+//! The futures run on a `Simulation` are timed by a virtual clock, which jumps to the next timer
+//!  when there is nothing else to do, such that timeouts, retries and cache expirations are tested
+//!  without waiting for them. The `VirtualNetwork` is the `RuntimeProvider` of the
+//!  `AsyncResolver`, its name servers answer from functions over links with latency, jitter,
+//!  which reorders the responses, and loss, drawn from a seeded generator.
+//!
+//! ```rust
+//! # extern crate trust_dns_resolver;
+//! # use std::time::Duration;
+//! use trust_dns_resolver::config::{NameServerConfigGroup, ResolverConfig, ResolverOpts};
+//! use trust_dns_resolver::proto::op::{Message, ResponseCode};
+//! use trust_dns_resolver::proto::rr::RecordType;
+//! use trust_dns_resolver::testing::{Link, Simulation, VirtualNetwork};
+//! use trust_dns_resolver::AsyncResolver;
+//!
+//! fn nx_domain(request: &Message) -> Message {
+//!     Message::error_msg(request.id(), request.op_code(), ResponseCode::NXDomain)
+//! }
+//!
+//! # fn main() {
+//! let addr = "10.0.0.1:53".parse().unwrap();
+//! let network = VirtualNetwork::new(1)
+//!     .with_server(addr, nx_domain)
+//!     .with_link(addr, Link::new(Duration::from_millis(20)).with_loss(0.5));
+//!
+//! let config = ResolverConfig::from_parts(
+//!     None,
+//!     vec![],
+//!     NameServerConfigGroup::from_ips_clear(&[addr.ip()], addr.port()),
+//! );
+//! let (resolver, background) =
+//!     AsyncResolver::with_runtime(config, ResolverOpts::default(), network.clone());
+//!
+//! let mut simulation = Simulation::new();
+//! simulation.spawn(background);
+//! let lookup = simulation.block_on(resolver.lookup("www.example.com.", RecordType::A));
+//! assert!(lookup.is_err());
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::{future, Future};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use tokio::executor::current_thread::{CurrentThread, TaskExecutor};
+use tokio_executor::park::{Park, Unpark};
+use tokio_executor::{self, DefaultExecutor, Executor};
+use tokio_timer::clock::{self, Clock, Now};
+use tokio_timer::timer::{self, Timer};
+use tokio_timer::{Delay, Timeout};
+
+use proto::error::{ProtoError, ProtoErrorKind, ProtoResult};
+use proto::op::Message;
+use proto::xfer::{DnsHandle, DnsRequest, DnsResponse};
+
+use config::{NameServerConfig, Protocol, ResolverOpts};
+use name_server::ConnectionProvider;
+use runtime::RuntimeProvider;
+
+/// A clock which only advances when the `Simulation` has nothing to do before its next timer
+#[derive(Clone)]
+struct VirtualClock(Arc<Mutex<Instant>>);
+
+impl VirtualClock {
+    fn advance(&self, duration: Duration) {
+        *self.0.lock().expect("virtual clock poisoned") += duration;
+    }
+}
+
+impl Now for VirtualClock {
+    fn now(&self) -> Instant {
+        *self.0.lock().expect("virtual clock poisoned")
+    }
+}
+
+/// Parks the `Simulation` by advancing the virtual clock, instead of sleeping
+struct VirtualPark(VirtualClock);
+
+struct VirtualUnpark;
+
+impl Unpark for VirtualUnpark {
+    fn unpark(&self) {}
+}
+
+impl Park for VirtualPark {
+    type Unpark = VirtualUnpark;
+    type Error = ();
+
+    fn unpark(&self) -> Self::Unpark {
+        VirtualUnpark
+    }
+
+    // there are no timers, only tasks of the simulation could be woken
+    fn park(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn park_timeout(&mut self, duration: Duration) -> Result<(), Self::Error> {
+        self.0.advance(duration);
+        Ok(())
+    }
+}
+
+/// An executor of futures in virtual time, see the module documentation
+///
+/// Within `block_on`, `tokio_timer::clock::now` is the virtual time, and the timers of
+///  `tokio_timer` are ready at their virtual deadlines. Futures which wait on anything other than
+///  the timers or the tasks of the simulation, e.g. real sockets, never complete.
+pub struct Simulation {
+    clock: VirtualClock,
+    clock_handle: Clock,
+    timer_handle: timer::Handle,
+    executor: CurrentThread<Timer<VirtualPark, Clock>>,
+}
+
+impl Simulation {
+    /// A simulation starting at the current time
+    pub fn new() -> Self {
+        let clock = VirtualClock(Arc::new(Mutex::new(Instant::now())));
+        let timer = Timer::new_with_now(
+            VirtualPark(clock.clone()),
+            Clock::new_with_now(clock.clone()),
+        );
+
+        Simulation {
+            clock_handle: Clock::new_with_now(clock.clone()),
+            timer_handle: timer.handle(),
+            executor: CurrentThread::new_with_park(timer),
+            clock,
+        }
+    }
+
+    /// The virtual time of the simulation
+    pub fn now(&self) -> Instant {
+        self.clock.now()
+    }
+
+    /// Spawns the future, e.g. the background future of an `AsyncResolver`, run by `block_on`
+    pub fn spawn<F>(&mut self, future: F) -> &mut Self
+    where
+        F: Future<Item = (), Error = ()> + 'static,
+    {
+        self.executor.spawn(future);
+        self
+    }
+
+    /// Runs the future, and the spawned futures, until it completes
+    pub fn block_on<F: Future>(&mut self, future: F) -> Result<F::Item, F::Error> {
+        let Simulation {
+            ref clock_handle,
+            ref timer_handle,
+            ref mut executor,
+            ..
+        } = *self;
+
+        let mut enter = tokio_executor::enter().expect("multiple executors at once");
+        clock::with_default(clock_handle, &mut enter, |enter| {
+            timer::with_default(timer_handle, enter, |enter| {
+                let mut default_executor = TaskExecutor::current();
+                tokio_executor::with_default(&mut default_executor, enter, |enter| {
+                    executor.enter(enter).block_on(future)
+                })
+            })
+        })
+        .map_err(|e| e.into_inner().expect("the simulation failed"))
+    }
+
+    /// Advances the virtual time by the duration, running the spawned futures
+    pub fn advance(&mut self, duration: Duration) {
+        let deadline = self.now() + duration;
+        self.block_on(Delay::new(deadline))
+            .expect("the simulation timer failed");
+    }
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The conditions of the link to a name server of the `VirtualNetwork`
+#[derive(Clone, Copy, Debug)]
+pub struct Link {
+    latency: Duration,
+    jitter: Duration,
+    loss: f64,
+}
+
+impl Link {
+    /// A link on which the responses are received after the latency
+    pub fn new(latency: Duration) -> Self {
+        Link {
+            latency,
+            jitter: Duration::from_secs(0),
+            loss: 0.0,
+        }
+    }
+
+    /// Adds a random delay of up to `jitter` to each response, such that they may be reordered
+    pub fn with_jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// The probability, from `0.0` to `1.0`, that a request or its response is lost
+    pub fn with_loss(mut self, loss: f64) -> Self {
+        self.loss = loss;
+        self
+    }
+}
+
+impl Default for Link {
+    fn default() -> Self {
+        Link::new(Duration::from_millis(0))
+    }
+}
+
+type Responder = Box<Fn(&Message) -> Message + Send + Sync>;
+
+struct Network {
+    rng: StdRng,
+    servers: HashMap<SocketAddr, Responder>,
+    links: HashMap<SocketAddr, Link>,
+    requests: Vec<(SocketAddr, Protocol, Message)>,
+}
+
+impl Network {
+    /// Returns the response and its delay, None if it's lost
+    fn exchange(
+        &mut self,
+        addr: SocketAddr,
+        protocol: Protocol,
+        request: &Message,
+    ) -> Option<(Message, Duration)> {
+        self.requests.push((addr, protocol, request.clone()));
+
+        let link = self.links.get(&addr).cloned().unwrap_or_default();
+        // the draws are made for each request, such that they only depend on the seed and the
+        //  order of the requests
+        let lost = self.rng.gen::<f64>() < link.loss;
+        let jitter = link.jitter.as_secs() * 1_000_000_000 + u64::from(link.jitter.subsec_nanos());
+        let jitter = Duration::from_nanos((jitter as f64 * self.rng.gen::<f64>()) as u64);
+
+        if lost {
+            return None;
+        }
+
+        let responder = self.servers.get(&addr)?;
+        let mut response = responder(request);
+        response.set_id(request.id());
+
+        Some((response, link.latency + jitter))
+    }
+}
+
+/// A network of name servers, in virtual time, see the module documentation
+///
+/// The clones of the network share its servers, links and requests.
+#[derive(Clone)]
+pub struct VirtualNetwork(Arc<Mutex<Network>>);
+
+impl VirtualNetwork {
+    /// A network without name servers, the generator of the links is seeded with `seed`
+    pub fn new(seed: u64) -> Self {
+        VirtualNetwork(Arc::new(Mutex::new(Network {
+            rng: StdRng::seed_from_u64(seed),
+            servers: HashMap::new(),
+            links: HashMap::new(),
+            requests: Vec::new(),
+        })))
+    }
+
+    /// Adds a name server at the address, for all protocols, which responds to each request with
+    ///  the message of `respond`, the id of the request is set on the response
+    pub fn with_server<F>(self, addr: SocketAddr, respond: F) -> Self
+    where
+        F: Fn(&Message) -> Message + Send + Sync + 'static,
+    {
+        self.lock().servers.insert(addr, Box::new(respond));
+        self
+    }
+
+    /// Sets the link to the name server at the address, without a link the responses are immediate
+    pub fn with_link(self, addr: SocketAddr, link: Link) -> Self {
+        self.set_link(addr, link);
+        self
+    }
+
+    /// Changes the link to the name server at the address, e.g. to fail it during a simulation
+    pub fn set_link(&self, addr: SocketAddr, link: Link) {
+        self.lock().links.insert(addr, link);
+    }
+
+    /// The requests sent, in order, with the address and protocol of the name server
+    pub fn requests(&self) -> Vec<(SocketAddr, Protocol, Message)> {
+        self.lock().requests.clone()
+    }
+
+    fn lock(&self) -> ::std::sync::MutexGuard<Network> {
+        self.0.lock().expect("virtual network poisoned")
+    }
+}
+
+impl ConnectionProvider for VirtualNetwork {
+    type ConnHandle = VirtualConnection;
+
+    fn new_connection(
+        &self,
+        config: &NameServerConfig,
+        options: &ResolverOpts,
+    ) -> Self::ConnHandle {
+        VirtualConnection {
+            network: self.clone(),
+            addr: config.socket_addr,
+            protocol: config.protocol,
+            timeout: options.timeout,
+        }
+    }
+}
+
+impl RuntimeProvider for VirtualNetwork {
+    type Delay = future::MapErr<Delay, fn(::tokio_timer::Error) -> ProtoError>;
+
+    fn spawn_bg<F>(&self, future: F) -> ProtoResult<()>
+    where
+        F: Future<Item = (), Error = ()> + Send + 'static,
+    {
+        DefaultExecutor::current()
+            .spawn(Box::new(future))
+            .map_err(ProtoError::from)
+    }
+
+    fn delay(&self, deadline: Instant) -> Self::Delay {
+        Delay::new(deadline).map_err(ProtoError::from as fn(::tokio_timer::Error) -> ProtoError)
+    }
+}
+
+/// A connection to a name server of the `VirtualNetwork`, which times out after the timeout of
+///  the `ResolverOpts`
+#[derive(Clone)]
+pub struct VirtualConnection {
+    network: VirtualNetwork,
+    addr: SocketAddr,
+    protocol: Protocol,
+    timeout: Duration,
+}
+
+impl DnsHandle for VirtualConnection {
+    type Response = Box<Future<Item = DnsResponse, Error = ProtoError> + Send>;
+
+    fn send<R: Into<DnsRequest>>(&mut self, request: R) -> Self::Response {
+        let (request, _) = request.into().unwrap();
+        let exchange = self
+            .network
+            .lock()
+            .exchange(self.addr, self.protocol, &request);
+
+        let response: Self::Response = match exchange {
+            Some((response, delay)) => Box::new(
+                Delay::new(clock::now() + delay)
+                    .map_err(ProtoError::from)
+                    .map(move |()| response.into()),
+            ),
+            None => Box::new(future::empty()),
+        };
+
+        Box::new(Timeout::new(response, self.timeout).map_err(|e| {
+            if e.is_elapsed() {
+                ProtoError::from(ProtoErrorKind::Timeout)
+            } else if e.is_inner() {
+                e.into_inner().expect("inner error")
+            } else {
+                e.into_timer().map_or_else(
+                    || ProtoError::from("virtual timer failed"),
+                    ProtoError::from,
+                )
+            }
+        }))
+    }
+}
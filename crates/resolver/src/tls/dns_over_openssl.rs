@@ -14,15 +14,18 @@ use futures::Future;
 
 use trust_dns_openssl::{TlsClientStream, TlsClientStreamBuilder};
 use proto::error::ProtoError;
+use proto::tcp::TcpOptions;
 use proto::BufDnsStreamHandle;
 
 pub(crate) fn new_tls_stream(
     socket_addr: SocketAddr,
     dns_name: String,
+    tcp_options: TcpOptions,
 ) -> (
     Box<Future<Item = TlsClientStream, Error = ProtoError> + Send>,
     BufDnsStreamHandle,
 ) {
-    let tls_builder = TlsClientStreamBuilder::new();
+    let mut tls_builder = TlsClientStreamBuilder::new();
+    tls_builder.tcp_options(tcp_options);
     tls_builder.build(socket_addr, dns_name)
 }
@@ -18,8 +18,9 @@ use self::rustls::{ClientConfig, ProtocolVersion, RootCertStore};
 use futures::Future;
 
 use proto::error::ProtoError;
+use proto::tcp::TcpOptions;
 use proto::BufDnsStreamHandle;
-use trust_dns_rustls::{tls_client_connect, TlsClientStream};
+use trust_dns_rustls::{tls_client_connect_with_options, TlsClientStream};
 
 lazy_static! {
     // using the mozilla default root store
@@ -39,10 +40,12 @@ lazy_static! {
 pub(crate) fn new_tls_stream(
     socket_addr: SocketAddr,
     dns_name: String,
+    tcp_options: TcpOptions,
 ) -> (
     Box<Future<Item = TlsClientStream, Error = ProtoError> + Send>,
     BufDnsStreamHandle,
 ) {
-    let (stream, handle) = tls_client_connect(socket_addr, dns_name, CLIENT_CONFIG.clone());
+    let (stream, handle) =
+        tls_client_connect_with_options(socket_addr, dns_name, CLIENT_CONFIG.clone(), tcp_options);
     (Box::new(stream), handle)
 }
@@ -0,0 +1,192 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The names which are not validated by a validating resolver, see
+//!  `ResolverConfig::add_validation_exception`
+
+#[cfg(feature = "dnssec")]
+use std::cmp::Reverse;
+#[cfg(feature = "dnssec")]
+use std::sync::{Arc, Mutex};
+
+#[cfg(feature = "dnssec")]
+use proto::op::OpCode;
+#[cfg(feature = "dnssec")]
+use proto::rr::Name;
+#[cfg(feature = "dnssec")]
+use proto::xfer::{DnsHandle, DnsRequest};
+
+#[cfg(feature = "dnssec")]
+use config::{ValidationException, ValidationPolicy};
+#[cfg(feature = "dnssec")]
+use dns_lru::DnsLru;
+
+/// The queries which were not validated for an exception, see `AsyncResolver::validation_stats`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ValidationStats {
+    insecure: u64,
+    no_dnssec_ok: u64,
+}
+
+impl ValidationStats {
+    /// The queries sent with the DNSSEC OK bit, whose answers were not validated
+    pub fn insecure(&self) -> u64 {
+        self.insecure
+    }
+
+    /// The queries sent without the DNSSEC OK bit
+    pub fn no_dnssec_ok(&self) -> u64 {
+        self.no_dnssec_ok
+    }
+
+    #[cfg(feature = "dnssec")]
+    pub(crate) fn count(&mut self, policy: ValidationPolicy) {
+        match policy {
+            ValidationPolicy::Insecure => self.insecure += 1,
+            ValidationPolicy::NoDnssecOk => self.no_dnssec_ok += 1,
+        }
+    }
+}
+
+/// Sends the queries for the names of the exceptions with the client which doesn't validate
+#[cfg(feature = "dnssec")]
+#[derive(Clone)]
+#[doc(hidden)]
+pub struct ValidationExceptions<H: DnsHandle> {
+    /// The exceptions, the longest suffixes first
+    exceptions: Arc<Vec<ValidationException>>,
+    client: H,
+    lru: Arc<Mutex<DnsLru>>,
+}
+
+#[cfg(feature = "dnssec")]
+impl<H: DnsHandle> ValidationExceptions<H> {
+    pub(crate) fn new(
+        exceptions: &[ValidationException],
+        client: H,
+        lru: Arc<Mutex<DnsLru>>,
+    ) -> Self {
+        let mut exceptions = exceptions.to_vec();
+        exceptions.sort_by_key(|e| Reverse(e.suffix.num_labels()));
+
+        ValidationExceptions {
+            exceptions: Arc::new(exceptions),
+            client,
+            lru,
+        }
+    }
+
+    /// The policy of the name, of the longest suffix of it with an exception
+    pub(crate) fn policy(&self, name: &Name) -> Option<ValidationPolicy> {
+        self.exceptions
+            .iter()
+            .find(|e| e.suffix.zone_of(name))
+            .map(|e| e.policy)
+    }
+
+    /// Sends the request without validating its response if its name has an exception, otherwise
+    ///  returns the request to be validated
+    pub(crate) fn send(&mut self, mut request: DnsRequest) -> Result<H::Response, DnsRequest> {
+        if self.exceptions.is_empty() || request.op_code() != OpCode::Query {
+            return Err(request);
+        }
+
+        let policy = match request
+            .queries()
+            .first()
+            .and_then(|q| self.policy(q.name()))
+        {
+            Some(policy) => policy,
+            None => return Err(request),
+        };
+
+        debug!("not validating {:?}: {:?}", request.queries(), policy);
+        let dnssec_ok = policy == ValidationPolicy::Insecure;
+        if dnssec_ok || request.edns().is_some() {
+            request.edns_mut().set_dnssec_ok(dnssec_ok);
+        }
+        self.lru
+            .lock()
+            .expect("cache poisoned")
+            .count_validation_exception(policy);
+
+        Ok(self.client.send(request))
+    }
+}
+
+#[cfg(all(test, feature = "dnssec"))]
+mod tests {
+    use std::str::FromStr;
+
+    use futures::{future, Future};
+    use proto::error::ProtoError;
+    use proto::op::{Message, Query};
+    use proto::rr::RecordType;
+    use proto::xfer::{DnsRequestOptions, DnsResponse};
+
+    use dns_lru::TtlConfig;
+
+    use super::*;
+
+    /// Returns the requests it's sent as their responses
+    #[derive(Clone)]
+    struct Echo;
+
+    impl DnsHandle for Echo {
+        type Response = Box<Future<Item = DnsResponse, Error = ProtoError> + Send>;
+
+        fn send<R: Into<DnsRequest>>(&mut self, request: R) -> Self::Response {
+            let (message, _) = request.into().unwrap();
+            Box::new(future::ok(message.into()))
+        }
+    }
+
+    fn request(name: &str) -> DnsRequest {
+        let mut message = Message::new();
+        message.add_query(Query::query(Name::from_str(name).unwrap(), RecordType::A));
+        DnsRequest::new(message, DnsRequestOptions::default())
+    }
+
+    fn send(exceptions: &mut ValidationExceptions<Echo>, request: DnsRequest) -> DnsResponse {
+        match exceptions.send(request) {
+            Ok(response) => response.wait().unwrap(),
+            Err(_) => panic!("not an exception"),
+        }
+    }
+
+    fn exception(suffix: &str, policy: ValidationPolicy) -> ValidationException {
+        ValidationException {
+            suffix: Name::from_str(suffix).unwrap(),
+            policy,
+        }
+    }
+
+    #[test]
+    fn test_validation_exceptions() {
+        let lru = DnsLru::new(0, TtlConfig::default());
+        let lru = Arc::new(Mutex::new(lru));
+        let exceptions = vec![
+            exception("corp.example.com.", ValidationPolicy::Insecure),
+            exception("lab.corp.example.com.", ValidationPolicy::NoDnssecOk),
+        ];
+        let mut exceptions = ValidationExceptions::new(&exceptions, Echo, Arc::clone(&lru));
+
+        assert!(exceptions.send(request("www.example.com.")).is_err());
+
+        let response = send(&mut exceptions, request("www.corp.example.com."));
+        assert!(response.edns().unwrap().dnssec_ok());
+
+        let mut lab = request("host.lab.corp.example.com.");
+        lab.edns_mut().set_dnssec_ok(true);
+        let response = send(&mut exceptions, lab);
+        assert!(!response.edns().unwrap().dnssec_ok());
+
+        let stats = lru.lock().unwrap().validation_stats();
+        assert_eq!(stats.insecure(), 1);
+        assert_eq!(stats.no_dnssec_ok(), 1);
+    }
+}
@@ -31,8 +31,10 @@ pub mod tls_client_stream;
 pub mod tls_server;
 pub mod tls_stream;
 
-pub use self::tls_client_stream::{TlsClientStream, tls_client_connect};
-pub use self::tls_stream::{tls_from_stream, TlsStream, tls_connect};
+pub use self::tls_client_stream::{
+    tls_client_connect, tls_client_connect_with_options, TlsClientStream,
+};
+pub use self::tls_stream::{tls_connect, tls_connect_with_options, tls_from_stream, TlsStream};
 
 #[cfg(test)]
 mod tests;
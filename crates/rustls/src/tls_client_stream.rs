@@ -14,10 +14,10 @@ use tokio_rustls::TlsStream as TokioTlsStream;
 use tokio_tcp::TcpStream as TokioTcpStream;
 
 use trust_dns_proto::error::ProtoError;
-use trust_dns_proto::tcp::TcpClientStream;
+use trust_dns_proto::tcp::{TcpClientStream, TcpOptions};
 use trust_dns_proto::xfer::BufDnsStreamHandle;
 
-use tls_stream::tls_connect;
+use tls_stream::tls_connect_with_options;
 
 pub type TlsClientStream = TcpClientStream<TokioTlsStream<TokioTcpStream, ClientSession>>;
 
@@ -35,7 +35,28 @@ pub fn tls_client_connect(
     Box<Future<Item = TlsClientStream, Error = ProtoError> + Send>,
     BufDnsStreamHandle,
 ) {
-    let (stream_future, sender) = tls_connect(name_server, dns_name, client_config);
+    tls_client_connect_with_options(name_server, dns_name, client_config, TcpOptions::default())
+}
+
+/// Creates a new TlsStream to the specified name_server, with the socket options of the TCP
+///  connection
+///
+/// # Arguments
+///
+/// * `name_server` - IP and Port for the remote DNS resolver
+/// * `dns_name` - The DNS name, Subject Public Key Info (SPKI) name, as associated to a certificate
+/// * `tcp_options` - socket options of the TCP connection, e.g. TCP Fast Open
+pub fn tls_client_connect_with_options(
+    name_server: SocketAddr,
+    dns_name: String,
+    client_config: Arc<ClientConfig>,
+    tcp_options: TcpOptions,
+) -> (
+    Box<Future<Item = TlsClientStream, Error = ProtoError> + Send>,
+    BufDnsStreamHandle,
+) {
+    let (stream_future, sender) =
+        tls_connect_with_options(name_server, dns_name, client_config, tcp_options);
 
     let new_future = Box::new(
         stream_future
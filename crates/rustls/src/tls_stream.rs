@@ -16,7 +16,7 @@ use tokio_rustls::{TlsConnector, TlsStream as TokioTlsStream};
 use tokio_tcp::TcpStream as TokioTcpStream;
 use webpki::{DNSName, DNSNameRef};
 
-use trust_dns_proto::tcp::TcpStream;
+use trust_dns_proto::tcp::{TcpOptions, TcpStream};
 use trust_dns_proto::xfer::BufStreamHandle;
 
 pub type TlsStream<S> = TcpStream<TokioTlsStream<TokioTcpStream, S>>;
@@ -68,12 +68,32 @@ pub fn tls_connect(
 ) -> (
     Box<Future<Item = TlsStream<ClientSession>, Error = io::Error> + Send>,
     BufStreamHandle,
+) {
+    tls_connect_with_options(name_server, dns_name, client_config, TcpOptions::default())
+}
+
+/// Creates a new TlsStream to the specified name_server, with the socket options of the TCP
+///  connection, see `tls_connect`
+///
+/// # Arguments
+///
+/// * `name_server` - IP and Port for the remote DNS resolver
+/// * `dns_name` - The DNS name,  Subject Public Key Info (SPKI) name, as associated to a certificate
+/// * `tcp_options` - socket options of the TCP connection, e.g. TCP Fast Open
+pub fn tls_connect_with_options(
+    name_server: SocketAddr,
+    dns_name: String,
+    client_config: Arc<ClientConfig>,
+    tcp_options: TcpOptions,
+) -> (
+    Box<Future<Item = TlsStream<ClientSession>, Error = io::Error> + Send>,
+    BufStreamHandle,
 ) {
     let (message_sender, outbound_messages) = unbounded();
     let message_sender = BufStreamHandle::new(message_sender);
 
     let tls_connector = TlsConnector::from(client_config);
-    let tcp = TokioTcpStream::connect(&name_server);
+    let tcp = tcp_options.connect(name_server);
 
     // This set of futures collapses the next tcp socket into a stream which can be used for
     //  sending and receiving tcp packets.
@@ -0,0 +1,73 @@
+#[macro_use]
+extern crate criterion;
+extern crate trust_dns_proto;
+extern crate trust_dns_server;
+
+use std::net::Ipv4Addr;
+
+use criterion::Criterion;
+
+use trust_dns_proto::op::{Message, MessageType, Query};
+use trust_dns_proto::rr::{Name, RData, Record, RecordType};
+use trust_dns_proto::serialize::binary::{BinDecodable, BinDecoder};
+use trust_dns_server::authority::MessageRequest;
+use trust_dns_server::server::{ArenaScope, ArenaStats, RequestArena};
+
+#[global_allocator]
+static ALLOCATOR: RequestArena = RequestArena;
+
+/// Decodes the request and encodes a response with its records, as the server handles a query
+fn handle(request: &[u8], records: &[Record]) -> Vec<u8> {
+    let mut decoder = BinDecoder::new(request);
+    let request = MessageRequest::read(&mut decoder).expect("bad request");
+
+    let mut response = Message::new();
+    response
+        .set_id(request.id())
+        .set_message_type(MessageType::Response)
+        .add_queries(
+            request
+                .queries()
+                .iter()
+                .map(|query| query.original().clone()),
+        )
+        .add_answers(records.iter().cloned());
+    response.to_vec().expect("failed to encode the response")
+}
+
+fn bench_request(c: &mut Criterion) {
+    let name = Name::from_ascii("www.example.com.").unwrap();
+    let mut request = Message::new();
+    request
+        .set_id(10)
+        .add_query(Query::query(name.clone(), RecordType::A));
+    let request = request.to_vec().unwrap();
+    let records = (0..10)
+        .map(|i| Record::from_rdata(name.clone(), 86400, RData::A(Ipv4Addr::new(127, 0, 0, i))))
+        .collect::<Vec<_>>();
+
+    let system_request = request.clone();
+    let system_records = records.clone();
+    c.bench_function("request_system_allocator", move |b| {
+        b.iter(|| handle(&system_request, &system_records).len())
+    });
+
+    let start = ArenaStats::of_thread();
+    c.bench_function("request_arena", move |b| {
+        b.iter(|| {
+            let _arena = ArenaScope::enter();
+            handle(&request, &records).len()
+        })
+    });
+
+    // the benches run on this thread, the misses are the allocations left to the system allocator
+    let stats = ArenaStats::of_thread();
+    println!(
+        "request_arena: {} arena hits, {} arena misses",
+        stats.hits - start.hits,
+        stats.misses - start.misses
+    );
+}
+
+criterion_group!(benches, bench_request);
+criterion_main!(benches);
@@ -0,0 +1,170 @@
+#[macro_use]
+extern crate criterion;
+extern crate futures;
+extern crate trust_dns;
+extern crate trust_dns_proto;
+extern crate trust_dns_server;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::net::Ipv4Addr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use criterion::Criterion;
+use futures::Future;
+
+use trust_dns::rr::dnssec::SupportedAlgorithms;
+use trust_dns::rr::rdata::SOA;
+use trust_dns::rr::{LowerName, Name, RData, Record, RecordType};
+use trust_dns::serialize::binary::BinEncoder;
+use trust_dns_proto::op::{Header, Message, MessageType};
+use trust_dns_server::authority::{Authority, MessageResponseBuilder, ZoneType};
+use trust_dns_server::store::in_memory::InMemoryAuthority;
+
+/// Counts the allocations, to compare those of the responses sharing the records of the zone with
+///  those copying them
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn create_authority() -> InMemoryAuthority {
+    let origin = Name::from_ascii("example.com.").unwrap();
+    let mut authority = InMemoryAuthority::empty(origin.clone(), ZoneType::Master, false);
+
+    authority.upsert(
+        Record::from_rdata(
+            origin.clone(),
+            3600,
+            RData::SOA(SOA::new(
+                Name::from_ascii("ns.example.com.").unwrap(),
+                Name::from_ascii("root.example.com.").unwrap(),
+                1,
+                3600,
+                600,
+                86400,
+                300,
+            )),
+        ),
+        1,
+    );
+
+    for name in &["www.example.com.", "*.wild.example.com."] {
+        for i in 0..10 {
+            authority.upsert(
+                Record::from_rdata(
+                    Name::from_ascii(name).unwrap(),
+                    86400,
+                    RData::A(Ipv4Addr::new(127, 0, 0, i)),
+                ),
+                1,
+            );
+        }
+    }
+
+    authority
+}
+
+fn header() -> Header {
+    let mut header = Header::new();
+    header.set_id(10).set_message_type(MessageType::Response);
+    header
+}
+
+/// Looks up the name and encodes the response with copies of the records, into a `Message`
+fn respond_copied(authority: &InMemoryAuthority, name: &LowerName) -> Vec<u8> {
+    let lookup = authority
+        .lookup(name, RecordType::A, false, SupportedAlgorithms::new())
+        .wait()
+        .expect("lookup failed");
+
+    let mut response = Message::new();
+    response
+        .set_id(10)
+        .set_message_type(MessageType::Response)
+        .add_answers(lookup.iter().cloned());
+    response.to_vec().expect("failed to encode the response")
+}
+
+/// Looks up the name and encodes the response with the records of the lookup, as the `Catalog`
+fn respond_shared(authority: &InMemoryAuthority, name: &LowerName) -> Vec<u8> {
+    let lookup = authority
+        .lookup(name, RecordType::A, false, SupportedAlgorithms::new())
+        .wait()
+        .expect("lookup failed");
+
+    let none = Vec::<&Record>::new();
+    let response = MessageResponseBuilder::new(None).build(
+        header(),
+        lookup.iter(),
+        none.clone(),
+        none.clone(),
+        none,
+    );
+
+    let mut buf = Vec::with_capacity(512);
+    response
+        .destructive_emit(&mut BinEncoder::new(&mut buf))
+        .expect("failed to encode the response");
+    buf
+}
+
+/// The number of allocations of a response
+fn allocations<F: Fn() -> Vec<u8>>(respond: F) -> usize {
+    let start = ALLOCATIONS.load(Ordering::Relaxed);
+    respond();
+    ALLOCATIONS.load(Ordering::Relaxed) - start
+}
+
+fn bench_response(c: &mut Criterion, id: &str, name: &str) {
+    let name = LowerName::from(Name::from_ascii(name).unwrap());
+
+    let authority = create_authority();
+    let copied_name = name.clone();
+    println!(
+        "{}_copied: {} allocations per response",
+        id,
+        allocations(|| respond_copied(&authority, &copied_name))
+    );
+    c.bench_function(&format!("{}_copied", id), move |b| {
+        b.iter(|| respond_copied(&authority, &copied_name).len())
+    });
+
+    let authority = create_authority();
+    println!(
+        "{}_shared: {} allocations per response",
+        id,
+        allocations(|| respond_shared(&authority, &name))
+    );
+    c.bench_function(&format!("{}_shared", id), move |b| {
+        b.iter(|| respond_shared(&authority, &name).len())
+    });
+}
+
+fn bench_exact_response(c: &mut Criterion) {
+    bench_response(c, "exact_response", "www.example.com.");
+}
+
+fn bench_wildcard_response(c: &mut Criterion) {
+    bench_response(c, "wildcard_response", "host.wild.example.com.");
+}
+
+criterion_group!(benches, bench_exact_response, bench_wildcard_response);
+criterion_main!(benches);
@@ -0,0 +1,136 @@
+#[macro_use]
+extern crate criterion;
+extern crate futures;
+extern crate tokio;
+extern crate trust_dns;
+extern crate trust_dns_proto;
+extern crate trust_dns_server;
+
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::net::UdpSocket;
+use std::time::Duration;
+
+use criterion::{black_box, Criterion};
+use futures::future;
+use tokio::runtime::Runtime;
+
+use trust_dns::rr::LowerName;
+use trust_dns_proto::hash::FxBuildHasher;
+use trust_dns_proto::op::{Header, Message, MessageType, Query};
+use trust_dns_proto::rr::{Name, RecordType};
+use trust_dns_server::authority::MessageResponseBuilder;
+use trust_dns_server::server::{Request, RequestHandler, ResponseHandler};
+use trust_dns_server::ServerFuture;
+
+/// Responds to every request with its queries, without any records
+struct EchoHandler;
+
+impl RequestHandler for EchoHandler {
+    type ResponseFuture = future::FutureResult<(), ()>;
+
+    fn handle_request<R: ResponseHandler>(
+        &self,
+        request: Request,
+        response_handle: R,
+    ) -> Self::ResponseFuture {
+        let mut header = Header::new();
+        header
+            .set_id(request.message.id())
+            .set_message_type(MessageType::Response)
+            .set_op_code(request.message.op_code());
+
+        let response = MessageResponseBuilder::new(Some(request.message.raw_queries()));
+        future::result(
+            response_handle
+                .send_response(response.build_no_records(header))
+                .map_err(|e| panic!("failed to send response: {}", e)),
+        )
+    }
+}
+
+fn bench_udp_echo(c: &mut Criterion) {
+    let server_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let server_addr = server_socket.local_addr().unwrap();
+
+    // the server is running on the runtime until it's dropped, after the benchmark
+    let mut runtime = Runtime::new().unwrap();
+    let server = ServerFuture::new(EchoHandler);
+    runtime
+        .block_on(future::lazy(move || {
+            server.register_socket_std(server_socket);
+            future::ok::<(), ()>(())
+        }))
+        .unwrap();
+
+    let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+    client
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    client.connect(server_addr).unwrap();
+
+    let mut request = Message::new();
+    request.set_id(10).add_query(Query::query(
+        Name::from_ascii("www.example.com.").unwrap(),
+        RecordType::A,
+    ));
+    let request = request.to_vec().unwrap();
+
+    c.bench_function("udp_echo", move |b| {
+        let mut buf = [0_u8; 512];
+        b.iter(|| {
+            client.send(&request).expect("send failed");
+            let len = client.recv(&mut buf).expect("no response from the server");
+            Message::from_vec(&buf[..len]).expect("bad response")
+        })
+    });
+
+    drop(runtime);
+}
+
+/// Finds the zone of the name, as the `Catalog`, in zones hashed by `S`
+fn find_zone<S: BuildHasher>(
+    zones: &HashMap<LowerName, usize, S>,
+    name: &LowerName,
+) -> Option<usize> {
+    let mut name = name.clone();
+    loop {
+        if let Some(zone) = zones.get(&name) {
+            return Some(*zone);
+        }
+        if name.is_root() {
+            return None;
+        }
+        name = name.base_name();
+    }
+}
+
+fn bench_zone_lookup<S: BuildHasher + Default>(c: &mut Criterion, id: &str) {
+    let mut zones = HashMap::with_hasher(S::default());
+    for i in 0..100 {
+        let zone = Name::from_ascii(format!("zone{}.example.com.", i)).unwrap();
+        zones.insert(LowerName::from(zone), i);
+    }
+    let name = LowerName::from(Name::from_ascii("www.host.zone42.example.com.").unwrap());
+
+    c.bench_function(id, move |b| {
+        b.iter(|| find_zone(&zones, black_box(&name)).unwrap())
+    });
+}
+
+fn bench_zone_lookup_sip(c: &mut Criterion) {
+    bench_zone_lookup::<RandomState>(c, "zone_lookup_sip");
+}
+
+fn bench_zone_lookup_fx(c: &mut Criterion) {
+    bench_zone_lookup::<FxBuildHasher>(c, "zone_lookup_fx");
+}
+
+criterion_group!(
+    benches,
+    bench_udp_echo,
+    bench_zone_lookup_sip,
+    bench_zone_lookup_fx
+);
+criterion_main!(benches);
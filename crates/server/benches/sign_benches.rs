@@ -0,0 +1,80 @@
+#[macro_use]
+extern crate criterion;
+extern crate rayon;
+extern crate trust_dns;
+extern crate trust_dns_server;
+
+use std::net::Ipv4Addr;
+
+use criterion::Criterion;
+use rayon::ThreadPoolBuilder;
+
+use trust_dns::rr::rdata::SOA;
+use trust_dns::rr::{Name, RData, Record};
+use trust_dns_server::authority::{Authority, ZoneType};
+use trust_dns_server::config::dnssec::KeyConfig;
+use trust_dns_server::store::in_memory::InMemoryAuthority;
+
+/// A zone of 1000 names with an A record, signed with an RSA key
+fn zone() -> InMemoryAuthority {
+    let origin = Name::from_ascii("example.com.").unwrap();
+    let mut authority = InMemoryAuthority::empty(origin.clone(), ZoneType::Master, false);
+    authority.upsert(
+        Record::from_rdata(
+            origin.clone(),
+            3600,
+            RData::SOA(SOA::new(
+                Name::from_ascii("ns.example.com.").unwrap(),
+                Name::from_ascii("root.example.com.").unwrap(),
+                1,
+                7200,
+                3600,
+                86400,
+                300,
+            )),
+        ),
+        1,
+    );
+    for i in 0..1000 {
+        let name = Name::from_ascii(&format!("host-{}.example.com.", i)).unwrap();
+        let address = Ipv4Addr::new(10, 0, (i / 256) as u8, (i % 256) as u8);
+        authority.upsert(Record::from_rdata(name, 3600, RData::A(address)), 1);
+    }
+
+    let key_config = KeyConfig {
+        key_path: "tests/named_test_configs/dnssec/rsa_2048.pem".to_string(),
+        password: Some("123456".to_string()),
+        algorithm: "RSASHA256".to_string(),
+        signer_name: Some(origin.to_string()),
+        is_zone_signing_key: Some(true),
+        is_zone_update_auth: Some(false),
+    };
+    let signer = key_config
+        .try_into_signer(origin)
+        .expect("failed to read key_config");
+    authority
+        .add_zone_signing_key(signer)
+        .expect("failed to add signer to zone");
+    authority
+}
+
+fn bench_sign_zone(c: &mut Criterion) {
+    // a pool of one thread signs the record sets one after the other
+    let serial_pool = ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+    let mut serial_zone = zone();
+    c.bench_function("sign_zone_serial", move |b| {
+        b.iter(|| serial_pool.install(|| serial_zone.secure_zone().expect("failed to sign")))
+    });
+
+    let mut parallel_zone = zone();
+    c.bench_function("sign_zone_parallel", move |b| {
+        b.iter(|| parallel_zone.secure_zone().expect("failed to sign"))
+    });
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = bench_sign_zone
+}
+criterion_main!(benches);
@@ -0,0 +1,177 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use trust_dns::op::{Edns, LowerQuery};
+use trust_dns::rr::rdata::opt::{EdnsCode, EdnsOption};
+use trust_dns::rr::Record;
+
+use authority::{LookupObject, MessageRequest};
+
+/// Selects the records returned in the answer section of a response, per request
+///
+/// Selectors are registered per zone on the `Catalog`, see `Catalog::set_answer_selector`. This
+///  allows embedders to reorder or filter the answers based on the client, e.g. by subnet, or on
+///  external state, e.g. the health or latency of backends, a simple GSLB.
+///
+/// Selectors are called synchronously while building the response and must not block.
+pub trait AnswerSelector: Send + Sync {
+    /// Returns the records to return in the answer section, in order
+    ///
+    /// # Arguments
+    ///
+    /// * `context` - the request which is being answered
+    /// * `answers` - the answers found by the authority, additional records are not included
+    ///
+    /// # Return
+    ///
+    /// The records to answer with, returning no records results in an empty, NODATA, answer
+    fn select(&self, context: &SelectionContext, answers: Vec<Record>) -> Vec<Record>;
+}
+
+impl<F> AnswerSelector for F
+where
+    F: Fn(&SelectionContext, Vec<Record>) -> Vec<Record> + Send + Sync,
+{
+    fn select(&self, context: &SelectionContext, answers: Vec<Record>) -> Vec<Record> {
+        self(context, answers)
+    }
+}
+
+/// The request being answered, passed to the `AnswerSelector`
+pub struct SelectionContext<'a> {
+    src: Option<SocketAddr>,
+    query: &'a LowerQuery,
+    request: &'a MessageRequest,
+}
+
+impl<'a> SelectionContext<'a> {
+    pub(crate) fn new(
+        src: Option<SocketAddr>,
+        query: &'a LowerQuery,
+        request: &'a MessageRequest,
+    ) -> Self {
+        SelectionContext {
+            src,
+            query,
+            request,
+        }
+    }
+
+    /// The source address of the client, None if the request was passed directly to `Catalog::lookup`
+    pub fn src(&self) -> Option<SocketAddr> {
+        self.src
+    }
+
+    /// The query being answered
+    pub fn query(&self) -> &LowerQuery {
+        self.query
+    }
+
+    /// The EDNS options of the request, e.g. for the client subnet option
+    pub fn edns(&self) -> Option<&Edns> {
+        self.request.edns()
+    }
+
+    /// The full request
+    pub fn request(&self) -> &MessageRequest {
+        self.request
+    }
+
+    /// The address and source prefix length of the EDNS client subnet option, if present
+    ///
+    /// [RFC 7871](https://tools.ietf.org/html/rfc7871), Client Subnet in DNS Queries, May 2016
+    ///
+    /// The address bits beyond the prefix length are zero.
+    pub fn client_subnet(&self) -> Option<(IpAddr, u8)> {
+        match self.edns()?.option(EdnsCode::Subnet)? {
+            EdnsOption::Subnet(subnet) => Some((subnet.address(), subnet.source_prefix())),
+            // options which aren't strictly valid, e.g. with extra address bytes
+            EdnsOption::Unknown(_, data) => parse_client_subnet(data),
+            _ => None,
+        }
+    }
+
+    /// The address of the client, from the client subnet if present, otherwise the source address
+    pub fn client_addr(&self) -> Option<IpAddr> {
+        self.client_subnet()
+            .map(|(addr, _)| addr)
+            .or_else(|| self.src.map(|src| src.ip()))
+    }
+}
+
+/// Parses the option data, `FAMILY`, `SOURCE PREFIX-LENGTH`, `SCOPE PREFIX-LENGTH` and `ADDRESS`
+fn parse_client_subnet(data: &[u8]) -> Option<(IpAddr, u8)> {
+    if data.len() < 4 {
+        return None;
+    }
+
+    let family = u16::from(data[0]) << 8 | u16::from(data[1]);
+    let source_prefix = data[2];
+    let address = &data[4..];
+
+    let addr = match family {
+        1 if source_prefix <= 32 && address.len() <= 4 => {
+            let mut octets = [0u8; 4];
+            octets[..address.len()].copy_from_slice(address);
+            IpAddr::from(Ipv4Addr::from(octets))
+        }
+        2 if source_prefix <= 128 && address.len() <= 16 => {
+            let mut octets = [0u8; 16];
+            octets[..address.len()].copy_from_slice(address);
+            IpAddr::from(Ipv6Addr::from(octets))
+        }
+        _ => return None,
+    };
+
+    Some((addr, source_prefix))
+}
+
+/// The answers returned from an `AnswerSelector`
+pub(crate) struct SelectedAnswers(pub(crate) Vec<Record>);
+
+impl LookupObject for SelectedAnswers {
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a Record> + Send + 'a> {
+        Box::new(self.0.iter())
+    }
+
+    fn take_additionals(&mut self) -> Option<Box<dyn LookupObject>> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_client_subnet() {
+        assert_eq!(
+            parse_client_subnet(&[0, 1, 24, 0, 192, 0, 2]),
+            Some((IpAddr::from(Ipv4Addr::new(192, 0, 2, 0)), 24))
+        );
+        assert_eq!(
+            parse_client_subnet(&[0, 2, 48, 0, 0x20, 0x01, 0x0d, 0xb8, 0, 1]),
+            Some((
+                IpAddr::from("2001:db8:1::".parse::<Ipv6Addr>().unwrap()),
+                48
+            ))
+        );
+        assert_eq!(
+            parse_client_subnet(&[0, 1, 0, 0]),
+            Some((IpAddr::from(Ipv4Addr::new(0, 0, 0, 0)), 0))
+        );
+        assert_eq!(parse_client_subnet(&[0, 1, 24]), None);
+        assert_eq!(parse_client_subnet(&[0, 1, 24, 0, 1, 2, 3, 4, 5]), None);
+        assert_eq!(parse_client_subnet(&[0, 3, 24, 0, 1, 2, 3]), None);
+    }
+}
@@ -8,8 +8,9 @@
 use std::iter::Chain;
 use std::slice::Iter;
 use std::sync::Arc;
+use std::vec;
 
-use authority::LookupObject;
+use authority::{LookupObject, RecordOrder};
 use proto::rr::dnssec::SupportedAlgorithms;
 use proto::rr::{Record, RecordSet, RecordType, RrsetRecords};
 use trust_dns::rr::LowerName;
@@ -303,6 +304,8 @@ pub enum LookupRecords {
         supported_algorithms: SupportedAlgorithms,
         /// the records found based on the query
         records: Arc<RecordSet>,
+        /// the order in which the records are returned
+        order: RecordOrder,
     },
     /// Vec of disjoint record sets
     ManyRecords(bool, SupportedAlgorithms, Vec<Arc<RecordSet>>),
@@ -322,9 +325,19 @@ impl LookupRecords {
             is_secure,
             supported_algorithms,
             records,
+            order: RecordOrder::Stored,
         }
     }
 
+    /// Sets the order in which the records are returned, this only applies to a single RecordSet
+    pub fn with_order(mut self, record_order: RecordOrder) -> Self {
+        if let LookupRecords::Records { ref mut order, .. } = self {
+            *order = record_order;
+        }
+
+        self
+    }
+
     /// Construct a new LookupRecords over a set of ResordSets
     pub fn many(
         is_secure: bool,
@@ -366,7 +379,24 @@ impl<'a> IntoIterator for &'a LookupRecords {
                 is_secure,
                 supported_algorithms,
                 records,
+                order: RecordOrder::Stored,
             } => LookupRecordsIter::RecordsIter(records.records(*is_secure, *supported_algorithms)),
+            LookupRecords::Records {
+                is_secure,
+                supported_algorithms,
+                records,
+                order,
+            } => {
+                let mut ordered = records
+                    .records(*is_secure, *supported_algorithms)
+                    .collect::<Vec<_>>();
+
+                // only the records are reordered, any RRSIGs follow them
+                let len = records.records_without_rrsigs().count();
+                order.apply(&mut ordered[..len]);
+
+                LookupRecordsIter::OrderedIter(ordered.into_iter())
+            }
             LookupRecords::ManyRecords(is_secure, supported_algorithms, r) => {
                 LookupRecordsIter::ManyRecordsIter(
                     r.iter()
@@ -386,6 +416,8 @@ pub enum LookupRecordsIter<'r> {
     AnyRecordsIter(AnyRecordsIter<'r>),
     /// An iteration over a single RecordSet
     RecordsIter(RrsetRecords<'r>),
+    /// An iteration over a single, reordered, RecordSet
+    OrderedIter(vec::IntoIter<&'r Record>),
     /// An iteration over many rrsets
     ManyRecordsIter(Vec<RrsetRecords<'r>>, Option<RrsetRecords<'r>>),
     /// An empty set
@@ -406,6 +438,7 @@ impl<'r> Iterator for LookupRecordsIter<'r> {
             LookupRecordsIter::Empty => None,
             LookupRecordsIter::AnyRecordsIter(current) => current.next(),
             LookupRecordsIter::RecordsIter(current) => current.next(),
+            LookupRecordsIter::OrderedIter(current) => current.next(),
             LookupRecordsIter::ManyRecordsIter(set, ref mut current) => loop {
                 if let Some(o) = current.as_mut().and_then(Iterator::next) {
                     return Some(o);
@@ -7,14 +7,18 @@
 
 //! All authority related types
 
+use std::time::Duration;
+
 use futures::Future;
 
-use trust_dns::op::LowerQuery;
+use trust_dns::op::{LowerQuery, ResponseCode, UpdateBuilder};
 use trust_dns::proto::rr::dnssec::rdata::key::KEY;
 use trust_dns::rr::dnssec::{DnsSecError, DnsSecResult, Signer, SupportedAlgorithms};
+use trust_dns::rr::rdata::opt::ClientSubnet;
 use trust_dns::rr::{LowerName, Name, RecordType};
 
 use authority::{LookupError, MessageRequest, UpdateResult, ZoneType};
+use store::in_memory::ZoneDiff;
 
 /// Authority implementations can be used with a `Catalog`
 pub trait Authority: Send {
@@ -30,7 +34,30 @@ pub trait Authority: Send {
     fn is_axfr_allowed(&self) -> bool;
 
     /// Perform a dynamic update of a zone
-    fn update(&mut self, update: &MessageRequest) -> UpdateResult<bool>;
+    ///
+    /// Updates run concurrently with the lookups of the zone, these should not wait on the update
+    fn update(&self, update: &MessageRequest) -> UpdateResult<bool>;
+
+    /// Perform a dynamic update of a zone, see `update`, returning the records it changed
+    ///
+    /// The diff of the update is audited by the `Catalog`. Authorities which don't track the
+    ///  changes of their updates return an empty diff.
+    fn update_with_diff(&self, update: &MessageRequest) -> UpdateResult<ZoneDiff> {
+        self.update(update).map(|_| ZoneDiff::default())
+    }
+
+    /// Applies the prerequisites and updates of the builder atomically, without an UPDATE message
+    ///
+    /// This is for the programmatic changes of embedders, the requestor is not authorized. Either
+    ///  all the prerequisites are satisfied and all the updates are applied, or the zone is left
+    ///  unchanged and the error of the first failed check is returned, as for `update`.
+    ///
+    /// # Return value
+    ///
+    /// true if any record was added, updated or deleted
+    fn apply_update(&self, _update: &UpdateBuilder) -> UpdateResult<bool> {
+        Err(ResponseCode::NotImp)
+    }
 
     /// Get the origin of this zone, i.e. example.com is the origin for www.example.com
     fn origin(&self) -> &LowerName;
@@ -75,6 +102,21 @@ pub trait Authority: Send {
         supported_algorithms: SupportedAlgorithms,
     ) -> Box<Future<Item = Self::Lookup, Error = LookupError> + Send>;
 
+    /// Performs the query on behalf of a client, see `search`
+    ///
+    /// The `client_subnet` is the client subnet option of the request, otherwise the source
+    ///  address of the client with its full length, e.g. for a forwarder to send a truncated
+    ///  subnet to its upstream. Authorities which don't answer per client ignore it.
+    fn search_with_client(
+        &self,
+        query: &LowerQuery,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+        _client_subnet: Option<ClientSubnet>,
+    ) -> Box<Future<Item = Self::Lookup, Error = LookupError> + Send> {
+        self.search(query, is_secure, supported_algorithms)
+    }
+
     /// Get the NS, NameServer, record for the zone
     fn ns(&self, is_secure: bool, supported_algorithms: SupportedAlgorithms) -> Self::LookupFuture {
         self.lookup(
@@ -142,6 +184,14 @@ pub trait Authority: Send {
         ))
     }
 
+    /// Spreads the expirations of the signatures over the jitter, their validity is shorter by a
+    ///  random time within it, so that they aren't all signed again at the same time
+    fn set_signature_jitter(&mut self, _jitter: Duration) -> DnsSecResult<()> {
+        Err(DnsSecError::from(
+            "zone signing not supported by this Authority type",
+        ))
+    }
+
     /// Sign the zone for DNSSEC
     fn secure_zone(&mut self) -> DnsSecResult<()> {
         Err(DnsSecError::from(
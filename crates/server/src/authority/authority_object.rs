@@ -7,14 +7,18 @@
 
 //! All authority related types
 
+use std::time::Duration;
+
 use futures::{future, Future, Poll};
 
-use trust_dns::op::LowerQuery;
+use trust_dns::op::{LowerQuery, UpdateBuilder};
 use trust_dns::proto::rr::dnssec::rdata::key::KEY;
 use trust_dns::rr::dnssec::{DnsSecError, DnsSecResult, Signer, SupportedAlgorithms};
+use trust_dns::rr::rdata::opt::ClientSubnet;
 use trust_dns::rr::{LowerName, Name, Record, RecordType};
 
 use authority::{Authority, LookupError, MessageRequest, UpdateResult, ZoneType};
+use store::in_memory::ZoneDiff;
 
 /// An Object safe Authority
 pub trait AuthorityObject: Send + Sync {
@@ -25,7 +29,17 @@ pub trait AuthorityObject: Send + Sync {
     fn is_axfr_allowed(&self) -> bool;
 
     /// Perform a dynamic update of a zone
-    fn update(&mut self, update: &MessageRequest) -> UpdateResult<bool>;
+    ///
+    /// Updates run concurrently with the lookups of the zone, these should not wait on the update
+    fn update(&self, update: &MessageRequest) -> UpdateResult<bool>;
+
+    /// Perform a dynamic update of a zone, returning the records it changed, see
+    ///  `Authority::update_with_diff`
+    fn update_with_diff(&self, update: &MessageRequest) -> UpdateResult<ZoneDiff>;
+
+    /// Applies the prerequisites and updates of the builder atomically, see
+    ///  `Authority::apply_update`
+    fn apply_update(&self, update: &UpdateBuilder) -> UpdateResult<bool>;
 
     /// Get the origin of this zone, i.e. example.com is the origin for www.example.com
     fn origin(&self) -> &LowerName;
@@ -70,6 +84,15 @@ pub trait AuthorityObject: Send + Sync {
         supported_algorithms: SupportedAlgorithms,
     ) -> BoxedLookupFuture;
 
+    /// Performs the query on behalf of a client, see `Authority::search_with_client`
+    fn search_with_client(
+        &self,
+        query: &LowerQuery,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+        client_subnet: Option<ClientSubnet>,
+    ) -> BoxedLookupFuture;
+
     /// Get the NS, NameServer, record for the zone
     fn ns(&self, is_secure: bool, supported_algorithms: SupportedAlgorithms) -> BoxedLookupFuture {
         self.lookup(
@@ -137,6 +160,14 @@ pub trait AuthorityObject: Send + Sync {
         ))
     }
 
+    /// Spreads the expirations of the signatures over the jitter, see
+    ///  `Authority::set_signature_jitter`
+    fn set_signature_jitter(&mut self, _jitter: Duration) -> DnsSecResult<()> {
+        Err(DnsSecError::from(
+            "zone signing not supported by this Authority type",
+        ))
+    }
+
     /// Sign the zone for DNSSEC
     fn secure_zone(&mut self) -> DnsSecResult<()> {
         Err(DnsSecError::from(
@@ -162,10 +193,20 @@ where
     }
 
     /// Perform a dynamic update of a zone
-    fn update(&mut self, update: &MessageRequest) -> UpdateResult<bool> {
+    fn update(&self, update: &MessageRequest) -> UpdateResult<bool> {
         Authority::update(self, update)
     }
 
+    /// Perform a dynamic update of a zone, returning the records it changed
+    fn update_with_diff(&self, update: &MessageRequest) -> UpdateResult<ZoneDiff> {
+        Authority::update_with_diff(self, update)
+    }
+
+    /// Applies the prerequisites and updates of the builder atomically
+    fn apply_update(&self, update: &UpdateBuilder) -> UpdateResult<bool> {
+        Authority::apply_update(self, update)
+    }
+
     /// Get the origin of this zone, i.e. example.com is the origin for www.example.com
     fn origin(&self) -> &LowerName {
         Authority::origin(self)
@@ -217,6 +258,24 @@ where
         BoxedLookupFuture::from(lookup.map(|l| Box::new(l) as Box<dyn LookupObject>))
     }
 
+    /// Performs the query on behalf of a client, see `Authority::search_with_client`
+    fn search_with_client(
+        &self,
+        query: &LowerQuery,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+        client_subnet: Option<ClientSubnet>,
+    ) -> BoxedLookupFuture {
+        let lookup = Authority::search_with_client(
+            self,
+            query,
+            is_secure,
+            supported_algorithms,
+            client_subnet,
+        );
+        BoxedLookupFuture::from(lookup.map(|l| Box::new(l) as Box<dyn LookupObject>))
+    }
+
     /// Return the NSEC records based on the given name
     ///
     /// # Arguments
@@ -242,6 +301,10 @@ where
         Authority::add_zone_signing_key(self, signer)
     }
 
+    fn set_signature_jitter(&mut self, jitter: Duration) -> DnsSecResult<()> {
+        Authority::set_signature_jitter(self, jitter)
+    }
+
     fn secure_zone(&mut self) -> DnsSecResult<()> {
         Authority::secure_zone(self)
     }
@@ -19,23 +19,96 @@
 use std::borrow::Borrow;
 use std::collections::HashMap;
 use std::io;
+use std::net::SocketAddr;
 use std::sync::{Arc, RwLock};
 
+use futures::future::join_all;
 use futures::{Async, Future, Poll};
 
-use server::{Request, RequestHandler, ResponseHandler};
+use proto::hash::FxBuildHasher;
+use server::{CookieSecrets, HandleOpCode, Protocol, Request, RequestHandler, ResponseHandler};
 use trust_dns::op::{Edns, Header, LowerQuery, MessageType, OpCode, ResponseCode};
 use trust_dns::rr::dnssec::{Algorithm, SupportedAlgorithms};
-use trust_dns::rr::rdata::opt::{EdnsCode, EdnsOption};
-use trust_dns::rr::{LowerName, RecordType};
+use trust_dns::rr::rdata::opt::{ClientSubnet, EdnsCode, EdnsOption};
+use trust_dns::rr::rdata::DNSSECRecordType;
+use trust_dns::rr::{LowerName, Name, Record, RecordSet, RecordType};
+use trust_dns::serialize::binary::BinEncodable;
 
-use authority::{AuthLookup, MessageRequest, MessageResponse, MessageResponseBuilder, ZoneType};
-use authority::{AuthorityObject, BoxedLookupFuture, LookupError, LookupObject};
+use authority::answer_selector::SelectedAnswers;
+use authority::{AnswerSelector, SelectionContext, ZoneChange, ZoneListener};
+use authority::{AuthLookup, LookupRecords, MessageRequest, MessageResponse};
+use authority::{MessageResponseBuilder, ZoneType};
+use authority::{AuthorityObject, BoxedLookupFuture, LookupError, LookupObject, MinimalResponses};
+use authority::{UpdateAudit, UpdateAuditSink, UpdateSigner};
+use store::in_memory::ZoneDiff;
+
+/// The maximum size of each message of a zone transfer
+const TRANSFER_MESSAGE_SIZE: usize = 16_384;
+
+/// Space reserved in each message of a zone transfer for the header, query and EDNS
+const TRANSFER_MESSAGE_OVERHEAD: usize = 512;
 
 /// Set of authorities, zones, available to this server.
-#[derive(Default)]
+///
+/// Clones of the catalog share the same zones, a clone can be kept to add, replace and remove
+///  zones at runtime after the catalog was passed to the `ServerFuture`. Changes are copy-on-write,
+///  requests see all zones as they were when the request was received, and requests in progress
+///  on a replaced or removed zone complete with the previous authority.
+#[derive(Clone, Default)]
 pub struct Catalog {
-    authorities: HashMap<LowerName, Arc<RwLock<Box<dyn AuthorityObject>>>>,
+    zones: Arc<RwLock<Arc<Zones>>>,
+    listeners: Arc<RwLock<Vec<Arc<dyn ZoneListener>>>>,
+    audit_sinks: Arc<RwLock<Vec<Arc<dyn UpdateAuditSink>>>>,
+    cookie_secrets: Arc<RwLock<Option<Arc<CookieSecrets>>>>,
+}
+
+/// A snapshot of the zones of the `Catalog`
+///
+/// The zones are configured, the names of the requests are only looked up, so they're hashed
+///  with the faster `FxHasher`.
+#[derive(Clone, Default)]
+struct Zones {
+    authorities: HashMap<LowerName, Arc<RwLock<Box<dyn AuthorityObject>>>, FxBuildHasher>,
+    answer_selectors: HashMap<LowerName, Arc<dyn AnswerSelector>, FxBuildHasher>,
+    minimal_responses: MinimalResponses,
+    zone_minimal_responses: HashMap<LowerName, MinimalResponses, FxBuildHasher>,
+}
+
+impl Zones {
+    /// Recursively searches for a matching authority
+    fn find(&self, name: &LowerName) -> Option<&Arc<RwLock<Box<dyn AuthorityObject>>>> {
+        debug!("searching authorities for: {}", name);
+        self.authorities.get(name).or_else(|| {
+            if !name.is_root() {
+                let name = name.base_name();
+                self.find(&name)
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// The signer name of the first SIG(0) record of the update
+#[cfg(feature = "dnssec")]
+fn sig0_signer(update: &MessageRequest) -> Option<Name> {
+    use trust_dns::rr::rdata::DNSSECRData;
+    use trust_dns::rr::RData;
+
+    update
+        .sig0()
+        .iter()
+        .filter_map(|sig0| match *sig0.rdata() {
+            RData::DNSSEC(DNSSECRData::SIG(ref sig)) => Some(sig.signer_name().clone()),
+            _ => None,
+        })
+        .next()
+}
+
+/// SIG(0) records are not decoded without DNSSEC
+#[cfg(not(feature = "dnssec"))]
+fn sig0_signer(_update: &MessageRequest) -> Option<Name> {
+    None
 }
 
 fn send_response<R: ResponseHandler>(
@@ -79,6 +152,9 @@ impl RequestHandler for Catalog {
         response_handle: R,
     ) -> Self::ResponseFuture {
         let request_message = request.message;
+        let src = request.src;
+        let protocol = request.protocol;
+        let tsig_key = request.tsig_key;
         trace!("request: {:?}", request_message);
 
         let response_edns: Option<Edns>;
@@ -94,17 +170,31 @@ impl RequestHandler for Catalog {
             // check our version against the request
             // TODO: what version are we?
             let our_version = 0;
-            resp_edns.set_dnssec_ok(true);
+            resp_edns.set_dnssec_ok(req_edns.dnssec_ok());
             resp_edns.set_max_payload(req_edns.max_payload().max(512));
             resp_edns.set_version(our_version);
 
+            if let Some(EdnsOption::Cookie(cookie)) = req_edns.option(EdnsCode::Cookie) {
+                let secrets = self
+                    .cookie_secrets
+                    .read()
+                    .expect("cookie secrets poisoned")
+                    .clone();
+                if let Some(secrets) = secrets {
+                    let cookie = secrets.response_cookie(cookie, src.ip());
+                    resp_edns.set_option(EdnsOption::Cookie(cookie));
+                }
+            }
+
             if req_edns.version() > our_version {
                 warn!(
                     "request edns version greater than {}: {}",
                     our_version,
                     req_edns.version()
                 );
+                // the high bits of the extended response code are those of the EDNS
                 response_header.set_response_code(ResponseCode::BADVERS);
+                resp_edns.set_rcode_high(ResponseCode::BADVERS.high() as u8);
                 response.edns(resp_edns);
 
                 // TODO: should ResponseHandle consume self?
@@ -122,18 +212,47 @@ impl RequestHandler for Catalog {
             // TODO think about threading query lookups for multiple lookups, this could be a huge improvement
             //  especially for recursive lookups
             MessageType::Query => match request_message.op_code() {
+                // the behavior of more than one question is undefined, and no question can't be
+                //  answered, see RFC 9619
+                OpCode::Query if request_message.queries().len() != 1 => {
+                    warn!(
+                        "query with {} questions from id: {}",
+                        request_message.queries().len(),
+                        request_message.id()
+                    );
+                    let response = MessageResponseBuilder::new(Some(request_message.raw_queries()));
+                    let result = response_handle.send_response(response.error_msg(
+                        request_message.id(),
+                        request_message.op_code(),
+                        ResponseCode::FormErr,
+                    ));
+                    HandleRequest::result(result)
+                }
                 OpCode::Query => {
                     debug!("query received: {}", request_message.id());
-                    let lookup = self.lookup(request_message, response_edns, response_handle);
+                    let lookup = self.lookup_from(
+                        request_message,
+                        Some(src),
+                        Some(protocol),
+                        response_edns,
+                        response_handle,
+                    );
                     HandleRequest::lookup(lookup)
                 }
                 OpCode::Update => {
                     debug!("update received: {}", request_message.id());
                     // TODO: this should be a future
-                    let result = self.update(&request_message, response_edns, response_handle);
+                    let result = self.update_from(
+                        &request_message,
+                        Some(src),
+                        tsig_key,
+                        response_edns,
+                        response_handle,
+                    );
                     HandleRequest::result(result)
                 }
-                c => {
+                // NOTIFY of the primaries is not acted upon, STATUS was never defined
+                c @ OpCode::Notify | c @ OpCode::Status | c @ OpCode::Unknown(_) => {
                     warn!("unimplemented op_code: {:?}", c);
                     let response = MessageResponseBuilder::new(Some(request_message.raw_queries()));
                     let result = response_handle.send_response(response.error_msg(
@@ -160,6 +279,15 @@ impl RequestHandler for Catalog {
             }
         }
     }
+
+    /// Updates are handled by `handle_request`, with the EDNS of the response
+    fn handle_update<R: ResponseHandler>(
+        &self,
+        request: Request,
+        response_handle: R,
+    ) -> HandleOpCode<Self::ResponseFuture> {
+        HandleOpCode::Handler(self.handle_request(request, response_handle))
+    }
 }
 
 /// Future response to handle a request
@@ -199,9 +327,7 @@ impl Future for HandleRequest {
 impl Catalog {
     /// Constructs a new Catalog
     pub fn new() -> Self {
-        Catalog {
-            authorities: HashMap::new(),
-        }
+        Catalog::default()
     }
 
     /// Insert or update a zone authority
@@ -211,14 +337,162 @@ impl Catalog {
     /// * `name` - zone name, e.g. example.com.
     /// * `authority` - the zone data
     pub fn upsert(&mut self, name: LowerName, authority: Box<dyn AuthorityObject>) {
-        // self.authorities.insert(name, Arc::new(RwLock::new(authority)));
-        self.authorities
-            .insert(name, Arc::new(RwLock::new(authority)));
+        self.swap_zone(name, authority);
     }
 
     /// Remove a zone from the catalog
     pub fn remove(&mut self, name: &LowerName) -> Option<Arc<RwLock<Box<dyn AuthorityObject>>>> {
-        self.authorities.remove(name)
+        self.remove_zone(name)
+    }
+
+    /// Atomically add a zone, or replace the authority of an existing zone
+    ///
+    /// The answer selector of an existing zone is kept. Requests which were received before the
+    ///  swap are answered by the previous authority.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - zone name, e.g. example.com.
+    /// * `authority` - the zone data
+    ///
+    /// # Return
+    ///
+    /// The previous authority of the zone, if any
+    pub fn swap_zone(
+        &self,
+        name: LowerName,
+        authority: Box<dyn AuthorityObject>,
+    ) -> Option<Arc<RwLock<Box<dyn AuthorityObject>>>> {
+        let previous = self.modify(|zones| {
+            zones
+                .authorities
+                .insert(name.clone(), Arc::new(RwLock::new(authority)))
+        });
+
+        let change = if previous.is_some() {
+            ZoneChange::Replaced
+        } else {
+            ZoneChange::Added
+        };
+        info!("zone {:?}: {}", change, name);
+        self.notify(&name, change);
+
+        previous
+    }
+
+    /// Atomically remove a zone, its answer selector and its minimal responses
+    ///
+    /// Requests which were received before the removal are still answered by the zone.
+    ///
+    /// # Return
+    ///
+    /// The authority of the zone, None if the zone was not in the catalog
+    pub fn remove_zone(&self, name: &LowerName) -> Option<Arc<RwLock<Box<dyn AuthorityObject>>>> {
+        let removed = self.modify(|zones| {
+            let removed = zones.authorities.remove(name)?;
+            zones.answer_selectors.remove(name);
+            zones.zone_minimal_responses.remove(name);
+            Some(removed)
+        });
+
+        if removed.is_some() {
+            info!("zone {:?}: {}", ZoneChange::Removed, name);
+            self.notify(name, ZoneChange::Removed);
+        }
+
+        removed
+    }
+
+    /// Notify the listeners that the zone of a secondary is up to date with its masters
+    pub fn zone_refreshed(&self, name: &LowerName) {
+        debug!("zone {:?}: {}", ZoneChange::Refreshed, name);
+        self.notify(name, ZoneChange::Refreshed);
+    }
+
+    /// The names of all zones in the catalog
+    pub fn zone_names(&self) -> Vec<LowerName> {
+        self.snapshot().authorities.keys().cloned().collect()
+    }
+
+    /// Register a listener to be notified of all subsequent changes to the zones
+    pub fn add_zone_listener(&self, listener: Arc<dyn ZoneListener>) {
+        self.listeners
+            .write()
+            .expect("listeners poisoned")
+            .push(listener);
+    }
+
+    /// Register a sink receiving the audit of all subsequent UPDATE requests, accepted or rejected
+    pub fn add_update_audit_sink(&self, sink: Arc<dyn UpdateAuditSink>) {
+        self.audit_sinks
+            .write()
+            .expect("audit sinks poisoned")
+            .push(sink);
+    }
+
+    /// Set the secrets of the server cookies of the responses to requests with a DNS cookie
+    ///
+    /// Without secrets the cookies of the requests are ignored, and the responses have none.
+    pub fn set_cookie_secrets(&self, secrets: Arc<CookieSecrets>) {
+        *self
+            .cookie_secrets
+            .write()
+            .expect("cookie secrets poisoned") = Some(secrets);
+    }
+
+    /// Returns the current zones, these are not affected by subsequent changes
+    fn snapshot(&self) -> Arc<Zones> {
+        Arc::clone(&*self.zones.read().expect("zones poisoned"))
+    }
+
+    /// Applies the change to a copy of the zones, if any snapshots are in use, and publishes it
+    fn modify<T, F: FnOnce(&mut Zones) -> T>(&self, f: F) -> T {
+        let mut zones = self.zones.write().expect("zones poisoned");
+        f(Arc::make_mut(&mut *zones))
+    }
+
+    fn notify(&self, name: &LowerName, change: ZoneChange) {
+        let listeners = self.listeners.read().expect("listeners poisoned").clone();
+        for listener in listeners {
+            listener.zone_changed(name, change);
+        }
+    }
+
+    /// Set the selector of the answers for all queries answered by the zone
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - zone name, e.g. example.com., this is matched against the origin of the zone
+    /// * `selector` - reorders or filters the answers per request, replaces any existing selector
+    pub fn set_answer_selector(&mut self, name: LowerName, selector: Arc<dyn AnswerSelector>) {
+        self.modify(|zones| zones.answer_selectors.insert(name, selector));
+    }
+
+    /// Remove the selector of the answers for the zone, answers are returned as found in the zone
+    pub fn remove_answer_selector(&mut self, name: &LowerName) -> Option<Arc<dyn AnswerSelector>> {
+        self.modify(|zones| zones.answer_selectors.remove(name))
+    }
+
+    /// Set the optional records omitted from the answers of all zones, see `MinimalResponses`
+    ///
+    /// By default all the records are returned, zones may override it with
+    ///  `set_zone_minimal_responses`.
+    pub fn set_minimal_responses(&mut self, minimal_responses: MinimalResponses) {
+        self.modify(|zones| zones.minimal_responses = minimal_responses);
+    }
+
+    /// Set the optional records omitted from the answers of the zone, instead of those of all zones
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - zone name, e.g. example.com., this is matched against the origin of the zone
+    /// * `minimal_responses` - the records omitted from the answers of the zone
+    pub fn set_zone_minimal_responses(
+        &mut self,
+        name: LowerName,
+        minimal_responses: MinimalResponses,
+    ) {
+        self.modify(|zones| zones.zone_minimal_responses.insert(name, minimal_responses));
     }
 
     /// Update the zone given the Update request.
@@ -275,6 +549,19 @@ impl Catalog {
         update: &'q MessageRequest,
         response_edns: Option<Edns>,
         response_handle: R,
+    ) -> io::Result<()> {
+        self.update_from(update, None, None, response_edns, response_handle)
+    }
+
+    /// Update the zone given the Update request, see `update`, auditing it with the source of the
+    ///  request and the TSIG key which signed it
+    fn update_from<R: ResponseHandler + 'static>(
+        &self,
+        update: &MessageRequest,
+        src: Option<SocketAddr>,
+        tsig_key: Option<Name>,
+        response_edns: Option<Edns>,
+        response_handle: R,
     ) -> io::Result<()> {
         let response = MessageResponseBuilder::new(None);
         let mut response_header = Header::default();
@@ -282,6 +569,39 @@ impl Catalog {
         response_header.set_op_code(OpCode::Update);
         response_header.set_message_type(MessageType::Response);
 
+        let zones: &[LowerQuery] = update.queries();
+        let (response_code, diff) = self.apply_update_request(update);
+        response_header.set_response_code(response_code);
+
+        let signer = tsig_key
+            .map(UpdateSigner::Tsig)
+            .or_else(|| sig0_signer(update).map(UpdateSigner::Sig0));
+        let audit = UpdateAudit::new(
+            update.id(),
+            zones.first().map(|zone| Name::from(zone.name())),
+            src,
+            signer,
+            response_code,
+            diff,
+        );
+        let audit_sinks = self
+            .audit_sinks
+            .read()
+            .expect("audit sinks poisoned")
+            .clone();
+        for sink in audit_sinks {
+            sink.audit(&audit);
+        }
+
+        send_response(
+            response_edns,
+            response.build_no_records(response_header),
+            response_handle,
+        )
+    }
+
+    /// Applies the update to its zone, returning the response code and the records it changed
+    fn apply_update_request(&self, update: &MessageRequest) -> (ResponseCode, ZoneDiff) {
         let zones: &[LowerQuery] = update.queries();
 
         // 2.3 - Zone Section
@@ -300,13 +620,7 @@ impl Catalog {
                 zones.len(),
                 ztype
             );
-            response_header.set_response_code(ResponseCode::FormErr);
-
-            return send_response(
-                response_edns,
-                response.build_no_records(response_header),
-                response_handle,
-            );
+            return (ResponseCode::FormErr, ZoneDiff::default());
         }
 
         if let Some(authority) = zones
@@ -314,54 +628,22 @@ impl Catalog {
             .map(LowerQuery::name)
             .and_then(|name| self.find(name))
         {
-            let mut authority = authority.write().unwrap(); // poison errors should panic...
+            // the authority applies the update itself, without blocking the lookups of the zone
+            let authority = authority.read().unwrap(); // poison errors should panic...
             match authority.zone_type() {
                 ZoneType::Slave => {
                     error!("slave forwarding for update not yet implemented");
-                    response_header.set_response_code(ResponseCode::NotImp);
-
-                    return send_response(
-                        response_edns,
-                        response.build_no_records(response_header),
-                        response_handle,
-                    );
-                }
-                ZoneType::Master => {
-                    let update_result = authority.update(update);
-                    match update_result {
-                        // successful update
-                        Ok(..) => {
-                            response_header.set_response_code(ResponseCode::NoError);
-                        }
-                        Err(response_code) => {
-                            response_header.set_response_code(response_code);
-                        }
-                    }
-
-                    return send_response(
-                        response_edns,
-                        response.build_no_records(response_header),
-                        response_handle,
-                    );
-                }
-                _ => {
-                    response_header.set_response_code(ResponseCode::NotAuth);
-
-                    return send_response(
-                        response_edns,
-                        response.build_no_records(response_header),
-                        response_handle,
-                    );
+                    (ResponseCode::NotImp, ZoneDiff::default())
                 }
+                ZoneType::Master => match authority.update_with_diff(update) {
+                    // successful update
+                    Ok(diff) => (ResponseCode::NoError, diff),
+                    Err(response_code) => (response_code, ZoneDiff::default()),
+                },
+                _ => (ResponseCode::NotAuth, ZoneDiff::default()),
             }
         } else {
-            response_header.set_response_code(ResponseCode::NXDomain);
-
-            return send_response(
-                response_edns,
-                response.build_no_records(response_header),
-                response_handle,
-            );
+            (ResponseCode::NXDomain, ZoneDiff::default())
         }
     }
 
@@ -375,11 +657,14 @@ impl Catalog {
     /// If you do not know the exact domain name to use or you actually
     /// want to use the authority it contains, use `find` instead.
     pub fn contains(&self, name: &LowerName) -> bool {
-        self.authorities.contains_key(name)
+        self.snapshot().authorities.contains_key(name)
     }
 
     /// Given the requested query, lookup and return any matching results.
     ///
+    /// The source of the request is unknown, `AnswerSelector`s are passed None as the `src`, and
+    ///  zone transfers are allowed as if the request was received over TCP.
+    ///
     /// # Arguments
     ///
     /// * `request` - the query message.
@@ -389,12 +674,53 @@ impl Catalog {
         request: MessageRequest,
         response_edns: Option<Edns>,
         response_handle: R,
+    ) -> LookupFuture<R> {
+        self.lookup_from(request, None, None, response_edns, response_handle)
+    }
+
+    fn lookup_from<R: ResponseHandler>(
+        &self,
+        request: MessageRequest,
+        src: Option<SocketAddr>,
+        protocol: Option<Protocol>,
+        response_edns: Option<Edns>,
+        response_handle: R,
     ) -> LookupFuture<R> {
         let request = Arc::new(request);
         let response_edns = response_edns.map(Arc::new);
+        let zones = self.snapshot();
 
-        // TODO: the spec is very unclear on what to do with multiple queries
-        //  we will search for each, in the future, maybe make this threaded to respond even faster.
+        // zone transfers may require more than one response message, see RFC 5936 section 4.2
+        let is_transfer = request
+            .queries()
+            .iter()
+            .any(|q| q.query_type() == RecordType::AXFR);
+        if let Some(datagram) = protocol.filter(|p| is_transfer && !p.is_stream()) {
+            warn!("request: {} refusing AXFR over {}", request.id(), datagram);
+            let response = MessageResponseBuilder::new(Some(request.raw_queries()));
+            send_response(
+                response_edns
+                    .as_ref()
+                    .map(|arc| Borrow::<Edns>::borrow(arc).clone()),
+                response.error_msg(request.id(), request.op_code(), ResponseCode::Refused),
+                response_handle.clone(),
+            )
+            .map_err(|e| error!("failed to send response: {}", e))
+            .ok();
+
+            return LookupFuture::new(
+                request,
+                src,
+                protocol,
+                zones,
+                response_edns,
+                response_handle,
+                vec![],
+            );
+        }
+
+        // the requests received by the server have exactly one query, see `handle_request`, those
+        //  passed to `lookup` are searched for each query.
         //  the current impl will return on the first query result
 
         // collect all the queries and lookups
@@ -403,8 +729,23 @@ impl Catalog {
             .iter()
             .enumerate()
             .filter_map(|(idx, q)| {
-                self.find(q.name())
-                    .map(|authority| (idx, Arc::clone(authority)))
+                zones.find(q.name()).map(|authority| {
+                    let authority_read = authority.read().expect("authority poisoned");
+                    let origin = authority_read.origin();
+                    let answer_selector = zones.answer_selectors.get(origin).cloned();
+                    let minimal_responses = zones
+                        .zone_minimal_responses
+                        .get(origin)
+                        .cloned()
+                        .unwrap_or(zones.minimal_responses);
+
+                    (
+                        idx,
+                        Arc::clone(authority),
+                        answer_selector,
+                        minimal_responses,
+                    )
+                })
             })
             .collect::<Vec<_>>();
 
@@ -425,6 +766,9 @@ impl Catalog {
 
         LookupFuture::new(
             request,
+            src,
+            protocol,
+            zones,
             response_edns,
             response_handle,
             queries_and_authorities,
@@ -432,27 +776,31 @@ impl Catalog {
     }
 
     /// Recursively searches the catalog for a matching authority
-    pub fn find(&self, name: &LowerName) -> Option<&Arc<RwLock<Box<dyn AuthorityObject>>>> {
-        debug!("searching authorities for: {}", name);
-        self.authorities.get(name).or_else(|| {
-            if !name.is_root() {
-                let name = name.base_name();
-                self.find(&name)
-            } else {
-                None
-            }
-        })
+    pub fn find(&self, name: &LowerName) -> Option<Arc<RwLock<Box<dyn AuthorityObject>>>> {
+        self.snapshot().find(name).cloned()
     }
 }
 
+/// The index of a query, the authority for the query, the selector of its answers and the
+///  records omitted from them
+type QueryAndAuthority = (
+    usize,
+    Arc<RwLock<Box<dyn AuthorityObject>>>,
+    Option<Arc<dyn AnswerSelector>>,
+    MinimalResponses,
+);
+
 /// A Future that runs the lookups and responds to the requests
 #[allow(clippy::type_complexity)]
 #[must_use = "futures do nothing unless polled"]
 pub struct LookupFuture<R: ResponseHandler> {
     request: Arc<MessageRequest>,
+    src: Option<SocketAddr>,
+    protocol: Option<Protocol>,
+    zones: Arc<Zones>,
     response_edns: Option<Arc<Edns>>,
     response_handle: R,
-    queries_and_authorities: Vec<(usize, Arc<RwLock<Box<dyn AuthorityObject>>>)>,
+    queries_and_authorities: Vec<QueryAndAuthority>,
     lookup: Option<AuthorityLookup<R>>,
 }
 
@@ -460,18 +808,115 @@ impl<R: ResponseHandler> LookupFuture<R> {
     #[allow(clippy::type_complexity)]
     fn new(
         request: Arc<MessageRequest>,
+        src: Option<SocketAddr>,
+        protocol: Option<Protocol>,
+        zones: Arc<Zones>,
         response_edns: Option<Arc<Edns>>,
         response_handle: R,
-        queries_and_authorities: Vec<(usize, Arc<RwLock<Box<dyn AuthorityObject>>>)>,
+        queries_and_authorities: Vec<QueryAndAuthority>,
     ) -> Self {
         LookupFuture {
             request,
+            src,
+            protocol,
+            zones,
             response_edns,
             response_handle,
             queries_and_authorities,
             lookup: None,
         }
     }
+
+    /// Looks up the DS and DNSKEY records of the zones from the closest trust point of a CHAIN
+    ///  query down to the query name, [RFC 7901](https://tools.ietf.org/html/rfc7901)
+    ///
+    /// The chain is only returned to DNSSEC requests, with the DO bit, received over TCP or other
+    ///  stream protocols: over UDP the large responses would amplify spoofed requests. The chain
+    ///  is omitted if the closest trust point is not an ancestor of the query name.
+    fn chain(
+        &self,
+        query: &LowerQuery,
+        is_dnssec: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Option<Chain> {
+        let closest_trust_point = match self
+            .request
+            .edns()
+            .and_then(|edns| edns.option(EdnsCode::Chain))
+        {
+            Some(EdnsOption::Chain(closest_trust_point)) => closest_trust_point.clone(),
+            _ => return None,
+        };
+
+        let is_stream = self.protocol.map(|p| p.is_stream()).unwrap_or(true);
+        if !is_dnssec || !is_stream {
+            debug!(
+                "request: {} CHAIN ignored, not DNSSEC or not over a stream",
+                self.request.id()
+            );
+            return None;
+        }
+
+        let name = Name::from(query.name());
+        if !closest_trust_point.zone_of(&name) {
+            debug!(
+                "request: {} CHAIN closest trust point {} is not an ancestor of {}",
+                self.request.id(),
+                closest_trust_point,
+                name
+            );
+            return None;
+        }
+
+        // from the zone below the closest trust point down
+        let mut lookups = Vec::new();
+        for num_labels in closest_trust_point.num_labels() + 1..=name.num_labels() {
+            let zone = LowerName::from(name.trim_to(num_labels as usize));
+            let authority = match self.zones.find(&zone) {
+                Some(authority) => authority.read().expect("authority poisoned"),
+                None => continue,
+            };
+
+            // the cuts of forwarded zones are not known, all the names are looked up
+            if authority.zone_type().is_authoritative() && *authority.origin() != zone {
+                continue;
+            }
+
+            // the DS records of a zone are in its parent
+            if let Some(parent) = self.zones.find(&zone.base_name()) {
+                lookups.push(parent.read().expect("authority poisoned").lookup(
+                    &zone,
+                    RecordType::DNSSEC(DNSSECRecordType::DS),
+                    true,
+                    supported_algorithms,
+                ));
+            }
+            lookups.push(authority.lookup(
+                &zone,
+                RecordType::DNSSEC(DNSSECRecordType::DNSKEY),
+                true,
+                supported_algorithms,
+            ));
+        }
+
+        // the records are shared with the authorities, they're not copied into the response
+        let records = join_all(lookups.into_iter().map(|lookup| {
+            // missing records are not an error, the chain may be partial
+            lookup.then(|lookup| Ok(lookup.ok()))
+        }))
+        .map(|lookups| lookups.into_iter().flatten().collect());
+
+        Some(Chain {
+            closest_trust_point,
+            records: Box::new(records),
+        })
+    }
+}
+
+/// The lookup of the DNSSEC chain of a CHAIN query, see `LookupFuture::chain`
+struct Chain {
+    closest_trust_point: Name,
+    records: Box<dyn Future<Item = Vec<Box<dyn LookupObject>>, Error = ()> + Send>,
 }
 
 impl<R: ResponseHandler> Future for LookupFuture<R> {
@@ -492,12 +937,13 @@ impl<R: ResponseHandler> Future for LookupFuture<R> {
             self.lookup = None;
 
             // get next query
-            let (query_idx, ref_authority) = if let Some(q_a) = self.queries_and_authorities.pop() {
-                q_a
-            } else {
-                // all lookups are complete, finish request
-                return Ok(Async::Ready(()));
-            };
+            let (query_idx, ref_authority, answer_selector, minimal_responses) =
+                if let Some(q_a) = self.queries_and_authorities.pop() {
+                    q_a
+                } else {
+                    // all lookups are complete, finish request
+                    return Ok(Async::Ready(()));
+                };
 
             let query = if let Some(query) = self.request.queries().get(query_idx) {
                 query
@@ -513,6 +959,13 @@ impl<R: ResponseHandler> Future for LookupFuture<R> {
                 self.request.id(),
                 authority.origin()
             );
+            #[cfg(feature = "tracing-spans")]
+            ::tracing::debug!(
+                authority = %authority.origin(),
+                zone_type = ?authority.zone_type(),
+                query = %query,
+                "found authority"
+            );
 
             let mut response_header = Header::new();
             response_header.set_id(self.request.id());
@@ -545,13 +998,26 @@ impl<R: ResponseHandler> Future for LookupFuture<R> {
             }
 
             debug!("performing {} on {}", query, authority.origin());
-            let lookup_future = authority.search(query, is_dnssec, supported_algorithms);
+            let lookup_future = authority.search_with_client(
+                query,
+                is_dnssec,
+                supported_algorithms,
+                client_subnet(&self.request, self.src),
+            );
+            let chain = if query.query_type() == RecordType::AXFR {
+                None
+            } else {
+                self.chain(query, is_dnssec, supported_algorithms)
+            };
 
             let request_params = RequestParams {
                 is_dnssec,
                 supported_algorithms,
                 query: query.clone(),
                 request: Arc::clone(&self.request),
+                src: self.src,
+                answer_selector,
+                minimal_responses,
             };
             let response_params = ResponseParams {
                 response_edns: self.response_edns.clone(),
@@ -566,15 +1032,17 @@ impl<R: ResponseHandler> Future for LookupFuture<R> {
                         response_params,
                         request_params,
                         lookup_future,
+                        chain,
                         Arc::clone(&ref_authority),
                     ));
                 }
-                ZoneType::Forward | ZoneType::Hint => {
+                ZoneType::Forward | ZoneType::Hint | ZoneType::Stub => {
                     self.lookup = Some(AuthorityLookup::resolve(
                         self.request.id(),
                         response_params,
                         request_params,
                         lookup_future,
+                        chain,
                         Arc::clone(&ref_authority),
                     ));
                 }
@@ -588,6 +1056,9 @@ struct RequestParams {
     supported_algorithms: SupportedAlgorithms,
     query: LowerQuery,
     request: Arc<MessageRequest>,
+    src: Option<SocketAddr>,
+    answer_selector: Option<Arc<dyn AnswerSelector>>,
+    minimal_responses: MinimalResponses,
 }
 
 struct ResponseParams<R: ResponseHandler> {
@@ -603,6 +1074,9 @@ struct AuthorityLookup<R: ResponseHandler> {
     request_params: RequestParams,
     authority: Arc<RwLock<Box<dyn AuthorityObject>>>,
     state: AuthOrResolve,
+    /// the sections of the response, once looked up
+    sections: Option<LookupSections>,
+    chain: Option<Chain>,
 }
 
 impl<R: ResponseHandler> AuthorityLookup<R> {
@@ -611,6 +1085,7 @@ impl<R: ResponseHandler> AuthorityLookup<R> {
         response_params: ResponseParams<R>,
         request_params: RequestParams,
         record_lookup: BoxedLookupFuture,
+        chain: Option<Chain>,
         authority: Arc<RwLock<Box<dyn AuthorityObject>>>,
     ) -> Self {
         debug!("handling authoritative request: {}", request_id);
@@ -622,6 +1097,8 @@ impl<R: ResponseHandler> AuthorityLookup<R> {
             state: AuthOrResolve::AuthorityLookupState(AuthorityLookupState::Records {
                 record_lookup,
             }),
+            sections: None,
+            chain,
         }
     }
 
@@ -630,6 +1107,7 @@ impl<R: ResponseHandler> AuthorityLookup<R> {
         response_params: ResponseParams<R>,
         request_params: RequestParams,
         record_lookup: BoxedLookupFuture,
+        chain: Option<Chain>,
         authority: Arc<RwLock<Box<dyn AuthorityObject>>>,
     ) -> Self {
         debug!("handling forwarded resolve: {}", request_id);
@@ -639,6 +1117,8 @@ impl<R: ResponseHandler> AuthorityLookup<R> {
             request_params,
             authority,
             state: AuthOrResolve::ResolveLookupState(ResolveLookupState::Records { record_lookup }),
+            sections: None,
+            chain,
         }
     }
 }
@@ -648,15 +1128,45 @@ impl<R: ResponseHandler> Future for AuthorityLookup<R> {
     type Error = ();
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        let sections = try_ready!(self.state.poll(
-            &self.request_params,
-            self.response_params
-                .as_mut()
-                .expect("bad state, response_params should not be none here"),
-            &self.authority
-        ));
-
-        let records = sections.answers;
+        if self.sections.is_none() {
+            self.sections = Some(try_ready!(self.state.poll(
+                &self.request_params,
+                self.response_params
+                    .as_mut()
+                    .expect("bad state, response_params should not be none here"),
+                &self.authority
+            )));
+        }
+
+        // the DNSSEC chain of a CHAIN query is added to the authority section
+        let chain = match self.chain {
+            Some(ref mut chain) => Some((
+                chain.closest_trust_point.clone(),
+                try_ready!(chain.records.poll()),
+            )),
+            None => None,
+        };
+        let sections = self
+            .sections
+            .take()
+            .expect("AuthorityLookup already complete");
+
+        let is_transfer = self.request_params.query.query_type() == RecordType::AXFR;
+        let records = match self.request_params.answer_selector {
+            // answers of zone transfers are not selected, secondaries require the full zone
+            Some(ref answer_selector) if !is_transfer && !sections.answers.is_empty() => {
+                let context = SelectionContext::new(
+                    self.request_params.src,
+                    &self.request_params.query,
+                    &self.request_params.request,
+                );
+                let answers = sections.answers.iter().cloned().collect();
+
+                Box::new(SelectedAnswers(answer_selector.select(&context, answers)))
+                    as Box<dyn LookupObject>
+            }
+            _ => sections.answers,
+        };
         let soa = sections.soa;
         let ns = sections.ns;
         let additionals = sections.additionals;
@@ -665,11 +1175,44 @@ impl<R: ResponseHandler> Future for AuthorityLookup<R> {
             .response_params
             .take()
             .expect("AuthorityLookup already complete");
-        let response_edns = response_params.response_edns;
+        let mut response_edns = response_params.response_edns;
         let response = MessageResponseBuilder::new(Some(self.request_params.request.raw_queries()));
         let response_header = response_params.response_header;
         let response_handle = response_params.response_handle;
 
+        if is_transfer && !records.is_empty() {
+            send_transfer(
+                response_edns
+                    .as_ref()
+                    .map(|arc| Borrow::<Edns>::borrow(arc)),
+                &self.request_params.request,
+                response_header,
+                records.iter(),
+                response_handle,
+            )
+            .map_err(|e| error!("error sending transfer: {}", e))
+            .ok();
+
+            return Ok(Async::Ready(()));
+        }
+
+        let chain_lookups = match chain {
+            Some((closest_trust_point, chain_lookups)) => {
+                // the option is echoed when the chain is returned
+                response_edns = response_edns.map(|edns| {
+                    let mut edns = Borrow::<Edns>::borrow(&edns).clone();
+                    edns.set_option(EdnsOption::Chain(closest_trust_point));
+                    Arc::new(edns)
+                });
+                chain_lookups
+            }
+            None => vec![],
+        };
+        let chain_records = chain_lookups
+            .iter()
+            .flat_map(|lookup| lookup.iter())
+            .collect::<Vec<_>>();
+
         send_response(
             response_edns
                 .as_ref()
@@ -677,7 +1220,8 @@ impl<R: ResponseHandler> Future for AuthorityLookup<R> {
             response.build(
                 response_header,
                 records.iter(),
-                ns.iter(),
+                Box::new(ns.iter().chain(chain_records.into_iter()))
+                    as Box<dyn Iterator<Item = &Record> + Send>,
                 soa.iter(),
                 additionals.iter(),
             ),
@@ -690,6 +1234,104 @@ impl<R: ResponseHandler> Future for AuthorityLookup<R> {
     }
 }
 
+/// The client subnet option of the request, otherwise the source address with its full length
+fn client_subnet(request: &MessageRequest, src: Option<SocketAddr>) -> Option<ClientSubnet> {
+    let option = request
+        .edns()
+        .and_then(|edns| edns.option(EdnsCode::Subnet));
+    if let Some(&EdnsOption::Subnet(subnet)) = option {
+        return Some(subnet);
+    }
+
+    let ip = src?.ip();
+    let prefix = if ip.is_ipv4() { 32 } else { 128 };
+    ClientSubnet::new(ip, prefix, 0).ok()
+}
+
+/// Sends the records of a zone transfer in as many messages as required
+///
+/// The records, which start and end with the SOA, are sent in order. Only the first message
+///  contains the query, all messages contain the EDNS of the response.
+fn send_transfer<'a, R: ResponseHandler, I: Iterator<Item = &'a Record>>(
+    response_edns: Option<&Edns>,
+    request: &MessageRequest,
+    response_header: Header,
+    records: I,
+    response_handle: R,
+) -> io::Result<()> {
+    let chunks = transfer_chunks(records)?;
+    let empty = Box::new(AuthLookup::default()) as Box<dyn LookupObject>;
+    debug!(
+        "request: {} sending transfer in {} messages",
+        request.id(),
+        chunks.len()
+    );
+
+    for (idx, chunk) in chunks.iter().enumerate() {
+        let queries = if idx == 0 {
+            Some(request.raw_queries())
+        } else {
+            None
+        };
+        let answers: Box<dyn Iterator<Item = &Record> + Send> =
+            Box::new(chunk.iter().map(|record| &**record));
+
+        send_response(
+            response_edns.cloned(),
+            MessageResponseBuilder::new(queries).build(
+                response_header.clone(),
+                answers,
+                empty.iter(),
+                empty.iter(),
+                empty.iter(),
+            ),
+            response_handle.clone(),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Splits the records into the messages of a zone transfer
+///
+/// Records are sized without name compression, so that each message is within
+///  `TRANSFER_MESSAGE_SIZE`. A record larger than that is sent alone.
+fn transfer_chunks<'a, I: Iterator<Item = &'a Record>>(
+    records: I,
+) -> io::Result<Vec<Vec<&'a Record>>> {
+    let max_size = TRANSFER_MESSAGE_SIZE - TRANSFER_MESSAGE_OVERHEAD;
+    let mut chunks = Vec::new();
+    let mut chunk = Vec::new();
+    let mut chunk_size = 0;
+
+    for record in records {
+        let size = record
+            .to_bytes()
+            .map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("error encoding record: {}", e),
+                )
+            })?
+            .len();
+
+        if !chunk.is_empty() && chunk_size + size > max_size {
+            chunks.push(chunk);
+            chunk = Vec::new();
+            chunk_size = 0;
+        }
+
+        chunk.push(record);
+        chunk_size += size;
+    }
+
+    if !chunk.is_empty() {
+        chunks.push(chunk);
+    }
+
+    Ok(chunks)
+}
+
 struct LookupSections {
     answers: Box<dyn LookupObject>,
     soa: Box<dyn LookupObject>,
@@ -771,23 +1413,44 @@ impl AuthorityLookupState {
                                 .set_response_code(ResponseCode::NoError);
                             response_params.response_header.set_authoritative(true);
 
-                            // This was a successful authoritative lookup:
-                            //   get the NS records
-                            let ns_lookup = authority.read().expect("authority poisoned").ns(
-                                request_params.is_dnssec,
-                                request_params.supported_algorithms,
-                            );
-                            AuthorityLookupState::LookupNs {
-                                ns_lookup,
-                                records: Some(records),
+                            // the NS records are optional, see `MinimalResponses`
+                            if request_params.minimal_responses.omit_ns() {
+                                let ns = Box::new(AuthLookup::default()) as Box<dyn LookupObject>;
+                                let soa = Box::new(AuthLookup::default()) as Box<dyn LookupObject>;
+                                AuthorityLookupState::Complete {
+                                    records: Some(records),
+                                    soa: Some(soa),
+                                    ns: Some(ns),
+                                }
+                            } else {
+                                // This was a successful authoritative lookup:
+                                //   get the NS records
+                                let ns_lookup = authority.read().expect("authority poisoned").ns(
+                                    request_params.is_dnssec,
+                                    request_params.supported_algorithms,
+                                );
+                                AuthorityLookupState::LookupNs {
+                                    ns_lookup,
+                                    records: Some(records),
+                                }
                             }
                         }
-                        // This request was refused
-                        // TODO: there are probably other error cases that should just drop through (FormErr, ServFail)
-                        Err(LookupError::ResponseCode(ResponseCode::Refused)) => {
+                        // This request was refused, or failed in the backend, there are no records
+                        //   for the response
+                        Err(ref e) if !e.is_nx_domain() && !e.is_name_exists() => {
+                            if e.is_refused() {
+                                debug!("request: {} refused", request_params.request.id());
+                            } else {
+                                warn!(
+                                    "request: {} lookup failed: {}",
+                                    request_params.request.id(),
+                                    e
+                                );
+                            }
+
                             response_params
                                 .response_header
-                                .set_response_code(ResponseCode::Refused);
+                                .set_response_code(e.response_code());
 
                             let ns = Box::new(AuthLookup::default()) as Box<dyn LookupObject>;
                             let soa = Box::new(AuthLookup::default()) as Box<dyn LookupObject>;
@@ -803,15 +1466,9 @@ impl AuthorityLookupState {
                         //   if the name is in this zone, etc.
                         // see https://tools.ietf.org/html/rfc2308 for proper response construct
                         Err(e) => {
-                            if e.is_nx_domain() {
-                                response_params
-                                    .response_header
-                                    .set_response_code(ResponseCode::NXDomain);
-                            } else if e.is_name_exists() {
-                                response_params
-                                    .response_header
-                                    .set_response_code(ResponseCode::NoError);
-                            };
+                            response_params
+                                .response_header
+                                .set_response_code(e.response_code());
 
                             // in the dnssec case, nsec records should exist, we return NoError + NoData + NSec...
                             let nsec_lookup = if request_params.is_dnssec {
@@ -905,7 +1562,13 @@ impl AuthorityLookupState {
                     let mut records = records
                         .take()
                         .expect("AuthorityLookupState already complete");
-                    let additionals = records.take_additionals();
+                    let additionals = records.take_additionals().filter(|_| {
+                        // the records of a CNAME chain answer the query, see `MinimalResponses`
+                        !request_params.minimal_responses.omit_additionals()
+                            || records
+                                .iter()
+                                .any(|record| record.record_type() == RecordType::CNAME)
+                    });
 
                     let sections = LookupSections {
                         answers: records,
@@ -948,16 +1611,57 @@ impl ResolveLookupState {
                 //
                 // On Errors, the transition depends on the type of error.
                 ResolveLookupState::Records { record_lookup } => {
-                    let records = try_ready!(record_lookup
-                        .poll()
-                        .map_err(|e| error!("error resolving: {}", e)));
+                    let (records, soa) = match record_lookup.poll() {
+                        Ok(Async::NotReady) => return Ok(Async::NotReady),
+                        Ok(Async::Ready(records)) => {
+                            (records, Box::new(AuthLookup::default()) as Box<dyn LookupObject>)
+                        }
+                        // the answer was determined locally, e.g. the name is blocked
+                        Err(LookupError::ResponseCode(response_code)) => {
+                            response_params
+                                .response_header
+                                .set_response_code(response_code);
+                            (
+                                Box::new(AuthLookup::default()) as Box<dyn LookupObject>,
+                                Box::new(AuthLookup::default()) as Box<dyn LookupObject>,
+                            )
+                        }
+                        // NXDomain and NODATA of the upstream are passed on with its SOA, RFC 2308,
+                        //  if the upstream or the backend failed the client should not wait for a timeout
+                        Err(e) => {
+                            let soa = match e.soa() {
+                                Some(soa) => {
+                                    debug!("negative response resolving: {}", e);
+                                    AuthLookup::answers(
+                                        LookupRecords::new(
+                                            false,
+                                            SupportedAlgorithms::new(),
+                                            Arc::new(RecordSet::from(soa.clone())),
+                                        ),
+                                        None,
+                                    )
+                                }
+                                None => {
+                                    error!("error resolving: {}", e);
+                                    AuthLookup::default()
+                                }
+                            };
+                            response_params
+                                .response_header
+                                .set_response_code(e.response_code());
+                            (
+                                Box::new(AuthLookup::default()) as Box<dyn LookupObject>,
+                                Box::new(soa) as Box<dyn LookupObject>,
+                            )
+                        }
+                    };
                     // need to clone the result codes...
 
                     response_params.response_header.set_authoritative(false);
 
                     let sections = LookupSections {
                         answers: records,
-                        soa: Box::new(AuthLookup::default()) as Box<dyn LookupObject>,
+                        soa,
                         ns: Box::new(AuthLookup::default()) as Box<dyn LookupObject>,
                         additionals: Box::new(AuthLookup::default()) as Box<dyn LookupObject>,
                     };
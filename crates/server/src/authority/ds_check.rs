@@ -0,0 +1,357 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Check of the DS records of the parent against the DNSKEY records published by a zone
+
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, RwLock};
+
+use trust_dns::proto::rr::dnssec::rdata::{DNSSECRData, DNSKEY, DS};
+use trust_dns::rr::{Name, RData, Record};
+
+/// The DS records of the parent which diverge from the DNSKEY records published by the zone
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DsMismatch {
+    zone: Name,
+    unmatched_ds: Vec<u16>,
+    unsigned_keys: Vec<u16>,
+    broken: bool,
+}
+
+impl DsMismatch {
+    /// The zone whose delegation diverges
+    pub fn zone(&self) -> &Name {
+        &self.zone
+    }
+
+    /// The key tags of the DS records which match no published DNSKEY
+    pub fn unmatched_ds(&self) -> &[u16] {
+        &self.unmatched_ds
+    }
+
+    /// The key tags of the published keys which should have a DS record but have none
+    pub fn unsigned_keys(&self) -> &[u16] {
+        &self.unsigned_keys
+    }
+
+    /// True if the parent has DS records and none matches a published DNSKEY, validating
+    ///  resolvers fail to resolve the zone
+    pub fn is_broken(&self) -> bool {
+        self.broken
+    }
+}
+
+impl fmt::Display for DsMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "DS of {} diverge from its DNSKEY", self.zone)?;
+        if self.broken {
+            write!(f, ", delegation is broken")?;
+        }
+        if !self.unmatched_ds.is_empty() {
+            write!(f, ", DS without DNSKEY: {:?}", self.unmatched_ds)?;
+        }
+        if !self.unsigned_keys.is_empty() {
+            write!(f, ", DNSKEY without DS: {:?}", self.unsigned_keys)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Compares the DS records of the parent with the DNSKEY records of the zone
+///
+/// Revoked keys are ignored. The secure entry point keys must each have a DS record, if the zone
+///  publishes none, at least one of its keys must have a DS record. A zone without DS records is
+///  an insecure delegation, which diverges from a signed zone.
+///
+/// # Return
+///
+/// The mismatch, None if the DS records match the keys
+pub fn compare_ds(zone: &Name, dnskeys: &[DNSKEY], ds: &[DS]) -> Option<DsMismatch> {
+    let keys = dnskeys
+        .iter()
+        .filter(|key| key.zone_key() && !key.revoke())
+        .filter_map(|key| key.calculate_key_tag().ok().map(|tag| (tag, key)))
+        .collect::<Vec<_>>();
+
+    let covers = |ds: &DS, tag: u16, key: &DNSKEY| {
+        ds.key_tag() == tag
+            && *ds.algorithm() == key.algorithm()
+            && ds.covers(zone, key).unwrap_or(false)
+    };
+
+    let unmatched_ds = ds
+        .iter()
+        .filter(|ds| !keys.iter().any(|&(tag, key)| covers(ds, tag, key)))
+        .map(DS::key_tag)
+        .collect::<Vec<_>>();
+
+    let has_ds = |&&(tag, key): &&(u16, &DNSKEY)| ds.iter().any(|ds| covers(ds, tag, key));
+    let has_sep = keys.iter().any(|&(_, key)| key.secure_entry_point());
+    let unsigned_keys = if has_sep {
+        keys.iter()
+            .filter(|&&(_, key)| key.secure_entry_point())
+            .filter(|key| !has_ds(key))
+            .map(|&(tag, _)| tag)
+            .collect::<Vec<_>>()
+    } else if !keys.iter().any(|key| has_ds(&key)) {
+        keys.iter().map(|&(tag, _)| tag).collect::<Vec<_>>()
+    } else {
+        vec![]
+    };
+
+    if unmatched_ds.is_empty() && unsigned_keys.is_empty() {
+        return None;
+    }
+
+    Some(DsMismatch {
+        zone: zone.clone(),
+        broken: !ds.is_empty() && unmatched_ds.len() == ds.len(),
+        unmatched_ds,
+        unsigned_keys,
+    })
+}
+
+/// Returns the DNSKEY data of the records
+pub(crate) fn dnskeys<'r, I: IntoIterator<Item = &'r Record>>(records: I) -> Vec<DNSKEY> {
+    records
+        .into_iter()
+        .filter_map(|record| match *record.rdata() {
+            RData::DNSSEC(DNSSECRData::DNSKEY(ref key)) => Some(key.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the DS data of the records
+pub(crate) fn ds_records<'r, I: IntoIterator<Item = &'r Record>>(records: I) -> Vec<DS> {
+    records
+        .into_iter()
+        .filter_map(|record| match *record.rdata() {
+            RData::DNSSEC(DNSSECRData::DS(ref ds)) => Some(ds.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Receives the changes of the result of the DS checks of a zone
+///
+/// Sinks are called from the task of the check, and must not block.
+pub trait DsCheckSink: Send + Sync {
+    /// The result of the check changed, `mismatch` is None when the DS records match the keys again
+    fn changed(&self, zone: &Name, mismatch: Option<&DsMismatch>);
+}
+
+impl<F> DsCheckSink for F
+where
+    F: Fn(&Name, Option<&DsMismatch>) + Send + Sync,
+{
+    fn changed(&self, zone: &Name, mismatch: Option<&DsMismatch>) {
+        self(zone, mismatch)
+    }
+}
+
+/// The number of DS checks of a zone, and their outcome
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DsCheckMetrics {
+    /// the checks which compared the DS records with the keys
+    pub checks: usize,
+    /// the checks which found a mismatch
+    pub mismatches: usize,
+    /// the checks which failed to lookup the DS or DNSKEY records
+    pub failures: usize,
+}
+
+/// The DS checks of a zone, with the sinks notified when their result changes
+///
+/// The zone is assumed to match its DS records until a check finds otherwise.
+pub struct DsChecker {
+    zone: Name,
+    sinks: RwLock<Vec<Box<dyn DsCheckSink>>>,
+    mismatch: Mutex<Option<DsMismatch>>,
+    checks: AtomicUsize,
+    mismatches: AtomicUsize,
+    failures: AtomicUsize,
+}
+
+impl DsChecker {
+    /// Creates the checker of the zone, without sinks
+    pub fn new(zone: Name) -> Self {
+        DsChecker {
+            zone,
+            sinks: RwLock::new(Vec::new()),
+            mismatch: Mutex::new(None),
+            checks: AtomicUsize::new(0),
+            mismatches: AtomicUsize::new(0),
+            failures: AtomicUsize::new(0),
+        }
+    }
+
+    /// The checked zone
+    pub fn zone(&self) -> &Name {
+        &self.zone
+    }
+
+    /// Adds a sink notified of the changes of the result of the checks
+    pub fn add_sink(&self, sink: Box<dyn DsCheckSink>) {
+        self.sinks.write().expect("sinks poisoned").push(sink);
+    }
+
+    /// The mismatch found by the last check, None if the DS records matched the keys
+    pub fn mismatch(&self) -> Option<DsMismatch> {
+        self.mismatch.lock().expect("mismatch poisoned").clone()
+    }
+
+    /// The checks so far, and their outcome
+    pub fn metrics(&self) -> DsCheckMetrics {
+        DsCheckMetrics {
+            checks: self.checks.load(Ordering::Relaxed),
+            mismatches: self.mismatches.load(Ordering::Relaxed),
+            failures: self.failures.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Compares the DS records with the keys, the sinks are notified if the result changed
+    pub fn check(&self, dnskeys: &[DNSKEY], ds: &[DS]) -> Option<DsMismatch> {
+        let mismatch = compare_ds(&self.zone, dnskeys, ds);
+
+        self.checks.fetch_add(1, Ordering::Relaxed);
+        if mismatch.is_some() {
+            self.mismatches.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let mut previous = self.mismatch.lock().expect("mismatch poisoned");
+        if *previous != mismatch {
+            match mismatch {
+                Some(ref mismatch) => warn!("{}", mismatch),
+                None => info!("DS of {} match its DNSKEY", self.zone),
+            }
+
+            for sink in self.sinks.read().expect("sinks poisoned").iter() {
+                sink.changed(&self.zone, mismatch.as_ref());
+            }
+            *previous = mismatch.clone();
+        }
+
+        mismatch
+    }
+
+    /// Counts a check which failed to lookup the records, the result of the checks is unchanged
+    pub fn failed(&self, error: &dyn fmt::Display) {
+        debug!("DS check of {} failed: {}", self.zone, error);
+        self.failures.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(feature = "trust-dns-resolver")]
+pub use self::background::check_ds;
+
+#[cfg(feature = "trust-dns-resolver")]
+mod background {
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    use futures::future::{self, Future};
+    use futures::Stream;
+    use tokio_timer::Interval;
+
+    use trust_dns::rr::dnssec::SupportedAlgorithms;
+    use trust_dns::rr::rdata::DNSSECRecordType;
+    use trust_dns::rr::{LowerName, RecordType};
+    use trust_dns_resolver::config::{NameServerConfigGroup, ResolverConfig, ResolverOpts};
+    use trust_dns_resolver::error::ResolveErrorKind;
+    use trust_dns_resolver::AsyncResolver;
+
+    use super::{dnskeys, ds_records, DsChecker};
+    use authority::Catalog;
+    use config::DsCheckConfig;
+
+    /// Returns a future which checks the DS records of the zone on every interval, it never
+    ///  completes
+    ///
+    /// The DNSKEY records are looked up in the zone of the catalog, the DS records are resolved
+    ///  with the name servers of the configuration, or of the system.
+    pub fn check_ds(
+        checker: Arc<DsChecker>,
+        catalog: Catalog,
+        config: &DsCheckConfig,
+    ) -> Result<impl Future<Item = (), Error = ()>, String> {
+        let (resolver_config, mut options) = if config.name_servers.is_empty() {
+            ::trust_dns_resolver::system_conf::read_system_conf()
+                .map_err(|e| format!("failed to read the system resolver config: {}", e))?
+        } else {
+            let name_servers = NameServerConfigGroup::from_ips_clear(&config.name_servers, 53);
+            (
+                ResolverConfig::from_parts(None, vec![], name_servers),
+                ResolverOpts::default(),
+            )
+        };
+
+        // a cached answer would hide the changes of the parent
+        options.cache_size = 0;
+        let (resolver, bg) = AsyncResolver::new(resolver_config, options);
+        let origin = LowerName::from(checker.zone());
+
+        let checks = Interval::new(Instant::now(), config.get_interval())
+            .map_err(|e| error!("DS check timer failed: {}", e))
+            .for_each(move |_| {
+                let checker = Arc::clone(&checker);
+
+                let authority = match catalog.find(&origin) {
+                    Some(ref authority)
+                        if authority.read().expect("authority poisoned").origin() == &origin =>
+                    {
+                        Arc::clone(authority)
+                    }
+                    _ => {
+                        checker.failed(&"the zone is not in the catalog");
+                        return future::Either::A(future::ok(()));
+                    }
+                };
+
+                let keys = authority
+                    .read()
+                    .expect("authority poisoned")
+                    .lookup(
+                        &origin,
+                        RecordType::DNSSEC(DNSSECRecordType::DNSKEY),
+                        false,
+                        SupportedAlgorithms::new(),
+                    )
+                    .map(|lookup| dnskeys(lookup.iter()))
+                    .map_err(|e| e.to_string());
+
+                let ds = resolver
+                    .lookup(
+                        checker.zone().clone(),
+                        RecordType::DNSSEC(DNSSECRecordType::DS),
+                    )
+                    .then(|result| match result {
+                        Ok(lookup) => Ok(ds_records(lookup.record_iter())),
+                        Err(e) => match *e.kind() {
+                            // an insecure delegation
+                            ResolveErrorKind::NoRecordsFound { .. } => Ok(vec![]),
+                            _ => Err(e.to_string()),
+                        },
+                    });
+
+                future::Either::B(keys.join(ds).then(move |result| {
+                    match result {
+                        Ok((keys, ds)) => {
+                            checker.check(&keys, &ds);
+                        }
+                        Err(e) => checker.failed(&e),
+                    }
+
+                    Ok(())
+                }))
+            });
+
+        Ok(bg.join(checks).map(|_| ()))
+    }
+}
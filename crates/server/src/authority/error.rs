@@ -13,8 +13,9 @@ use std::io;
 use failure::{Compat, Fail};
 
 use trust_dns::op::ResponseCode;
+use trust_dns::rr::Record;
 #[cfg(feature = "trust-dns-resolver")]
-use trust_dns_resolver::error::ResolveError;
+use trust_dns_resolver::error::{ResolveError, ResolveErrorKind};
 
 // TODO: should this implement Failure?
 /// A query could not be fullfilled
@@ -60,6 +61,39 @@ impl LookupError {
             _ => false,
         }
     }
+
+    /// The response code with which a request failing with this error is answered
+    ///
+    /// Negative answers of the upstream, i.e. `NXDomain` and NODATA, and its error responses are
+    /// passed on. Failures of the backend, e.g. timeouts or IO errors, are a `ServFail`.
+    pub fn response_code(&self) -> ResponseCode {
+        match *self {
+            LookupError::NameExists => ResponseCode::NoError,
+            LookupError::ResponseCode(response_code) => response_code,
+            #[cfg(feature = "trust-dns-resolver")]
+            LookupError::ResolveError(ref e) => match *e.get_ref().kind() {
+                ResolveErrorKind::NoRecordsFound { response_code, .. } => response_code,
+                ResolveErrorKind::ResponseCode(response_code) => response_code,
+                _ => ResponseCode::ServFail,
+            },
+            LookupError::Io(_) => ResponseCode::ServFail,
+        }
+    }
+
+    /// The SOA of the zone from the authority section of a negative answer of the upstream
+    pub fn soa(&self) -> Option<&Record> {
+        match *self {
+            #[cfg(feature = "trust-dns-resolver")]
+            LookupError::ResolveError(ref e) => match *e.get_ref().kind() {
+                ResolveErrorKind::NoRecordsFound {
+                    authority: Some(ref authority),
+                    ..
+                } => authority.soa(),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for LookupError {
@@ -125,3 +159,61 @@ impl From<LookupError> for io::Error {
 
 /// Result of a Lookup in the Catalog and Authority
 pub type LookupResult<T> = Result<T, LookupError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_response_code() {
+        assert_eq!(
+            LookupError::for_name_exists().response_code(),
+            ResponseCode::NoError
+        );
+        assert_eq!(
+            LookupError::from(ResponseCode::NXDomain).response_code(),
+            ResponseCode::NXDomain
+        );
+        assert_eq!(
+            LookupError::from(ResponseCode::Refused).response_code(),
+            ResponseCode::Refused
+        );
+        assert_eq!(
+            LookupError::from(io::Error::new(io::ErrorKind::Other, "backend")).response_code(),
+            ResponseCode::ServFail
+        );
+    }
+
+    #[cfg(feature = "trust-dns-resolver")]
+    #[test]
+    fn test_resolve_error_response_code() {
+        use trust_dns::op::Query;
+
+        assert_eq!(
+            LookupError::from(ResolveError::from("no connections")).response_code(),
+            ResponseCode::ServFail
+        );
+        assert_eq!(
+            LookupError::from(ResolveError::from(ResolveErrorKind::Timeout)).response_code(),
+            ResponseCode::ServFail
+        );
+        assert_eq!(
+            LookupError::from(ResolveError::from(ResolveErrorKind::ResponseCode(
+                ResponseCode::Refused
+            )))
+            .response_code(),
+            ResponseCode::Refused
+        );
+
+        for response_code in &[ResponseCode::NXDomain, ResponseCode::NoError] {
+            let error = LookupError::from(ResolveError::from(ResolveErrorKind::NoRecordsFound {
+                query: Query::new(),
+                response_code: *response_code,
+                authority: None,
+                valid_until: None,
+            }));
+            assert_eq!(error.response_code(), *response_code);
+            assert!(error.soa().is_none());
+        }
+    }
+}
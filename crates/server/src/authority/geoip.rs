@@ -0,0 +1,243 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! GeoIP based selection of answers, the group of addresses closest to the client is returned
+
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+#[cfg(feature = "geoip")]
+use std::path::{Path, PathBuf};
+
+use trust_dns::rr::{LowerName, Name, Record};
+
+use authority::{AnswerSelector, SelectionContext};
+
+/// The region matching all clients, for groups which are used when no other group matches
+pub const DEFAULT_REGION: &str = "default";
+
+/// Configuration for GeoIP selection of the answers of a zone
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct GeoIpConfig {
+    /// path to the MaxMind GeoIP2 or GeoLite2 Country or City database
+    pub database: String,
+    /// the groups of addresses, tagged with the regions they serve
+    pub groups: Vec<GeoIpGroupConfig>,
+}
+
+/// A group of the addresses of a name, answered to the clients in the regions
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct GeoIpGroupConfig {
+    /// the name of the A and AAAA records of the group
+    pub name: Name,
+    /// ISO 3166 country codes, e.g. `DE`, or continent codes, e.g. `EU`, or `default`
+    pub regions: Vec<String>,
+    /// the addresses of the group, these must be records of the name in the zone
+    pub addresses: Vec<IpAddr>,
+}
+
+/// The location of a client
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct GeoLocation {
+    /// ISO 3166 country code, e.g. `DE`
+    pub country: Option<String>,
+    /// continent code, e.g. `EU`
+    pub continent: Option<String>,
+}
+
+/// A database of the location of addresses
+pub trait GeoIpDatabase: Send + Sync {
+    /// Returns the location of the address, None if it's unknown
+    fn locate(&self, address: IpAddr) -> Option<GeoLocation>;
+}
+
+/// A MaxMind GeoIP2 or GeoLite2 database, either Country or City
+#[cfg(feature = "geoip")]
+pub struct MaxMindDatabase(::maxminddb::Reader<Vec<u8>>);
+
+#[cfg(feature = "geoip")]
+impl MaxMindDatabase {
+    /// Reads the database at the path
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        ::maxminddb::Reader::open_readfile(path.as_ref())
+            .map(MaxMindDatabase)
+            .map_err(|e| {
+                format!(
+                    "failed to open GeoIP database {}: {}",
+                    path.as_ref().display(),
+                    e
+                )
+            })
+    }
+}
+
+#[cfg(feature = "geoip")]
+impl GeoIpDatabase for MaxMindDatabase {
+    fn locate(&self, address: IpAddr) -> Option<GeoLocation> {
+        use maxminddb::geoip2::Country;
+
+        // City databases are a superset of Country databases
+        let country: Country = match self.0.lookup(address) {
+            Ok(country) => country,
+            Err(e) => {
+                debug!("no GeoIP location for {}: {:?}", address, e);
+                return None;
+            }
+        };
+
+        Some(GeoLocation {
+            country: country.country.and_then(|c| c.iso_code),
+            continent: country.continent.and_then(|c| c.code),
+        })
+    }
+}
+
+struct Group {
+    regions: Vec<String>,
+    addresses: HashSet<IpAddr>,
+}
+
+/// Selects the addresses of the group matching the location of the client
+///
+/// The client address is taken from the EDNS client subnet option when present, otherwise the
+///  source address of the request. A group matching the country of the client is preferred over
+///  one matching the continent, over a `default` group. If no group matches, the addresses which
+///  are in no group are answered, if there are none all addresses are.
+///
+/// Records other than A and AAAA, and names without groups, are always answered unchanged. The
+///  scope of the client subnet option is not returned in responses.
+pub struct GeoIpSelector {
+    database: Box<dyn GeoIpDatabase>,
+    groups: HashMap<LowerName, Vec<Group>>,
+}
+
+impl GeoIpSelector {
+    /// Creates a new selector with no groups
+    pub fn new(database: Box<dyn GeoIpDatabase>) -> Self {
+        GeoIpSelector {
+            database,
+            groups: HashMap::new(),
+        }
+    }
+
+    /// Opens the MaxMind database and adds all the groups from the configuration
+    ///
+    /// # Arguments
+    ///
+    /// * `root_dir` - the directory the database path is relative to
+    /// * `config` - the database and groups
+    #[cfg(feature = "geoip")]
+    pub fn try_from_config(root_dir: Option<&Path>, config: &GeoIpConfig) -> Result<Self, String> {
+        let path = root_dir
+            .map(PathBuf::from)
+            .unwrap_or_default()
+            .join(&config.database);
+
+        let mut selector = GeoIpSelector::new(Box::new(MaxMindDatabase::open(&path)?));
+        for group in &config.groups {
+            selector.add_group(&group.name, &group.regions, &group.addresses);
+        }
+
+        Ok(selector)
+    }
+
+    /// Adds the group of addresses of the name, answered to clients in the regions
+    pub fn add_group(&mut self, name: &Name, regions: &[String], addresses: &[IpAddr]) {
+        let group = Group {
+            regions: regions
+                .iter()
+                .map(|r| {
+                    if r.eq_ignore_ascii_case(DEFAULT_REGION) {
+                        DEFAULT_REGION.to_string()
+                    } else {
+                        r.to_ascii_uppercase()
+                    }
+                })
+                .collect(),
+            addresses: addresses.iter().cloned().collect(),
+        };
+
+        let mut name = name.clone();
+        name.set_fqdn(true);
+
+        self.groups
+            .entry(LowerName::from(name))
+            .or_default()
+            .push(group);
+    }
+
+    fn find_group<'g>(groups: &'g [Group], location: Option<&GeoLocation>) -> Option<&'g Group> {
+        let country = location.and_then(|l| l.country.as_ref());
+        let continent = location.and_then(|l| l.continent.as_ref());
+
+        let find = |region: Option<&String>| {
+            let region = region?;
+            groups.iter().find(|g| g.regions.contains(region))
+        };
+
+        find(country).or_else(|| find(continent)).or_else(|| {
+            groups
+                .iter()
+                .find(|g| g.regions.iter().any(|r| r == DEFAULT_REGION))
+        })
+    }
+}
+
+impl AnswerSelector for GeoIpSelector {
+    fn select(&self, context: &SelectionContext, answers: Vec<Record>) -> Vec<Record> {
+        let is_grouped = |record: &Record| {
+            record.rdata().to_ip_addr().is_some()
+                && self.groups.contains_key(&LowerName::from(record.name()))
+        };
+
+        if !answers.iter().any(is_grouped) {
+            return answers;
+        }
+
+        let client_addr = context.client_addr();
+        let location = client_addr.and_then(|addr| self.database.locate(addr));
+        debug!("client {:?} located: {:?}", client_addr, location);
+
+        // answers may contain the records of more than one name, e.g. following a CNAME
+        let keep = |record: &Record| {
+            let address = match record.rdata().to_ip_addr() {
+                Some(address) => address,
+                None => return true,
+            };
+            let groups = match self.groups.get(&LowerName::from(record.name())) {
+                Some(groups) => groups,
+                None => return true,
+            };
+
+            match Self::find_group(groups, location.as_ref()) {
+                Some(group) => group.addresses.contains(&address),
+                None => !groups.iter().any(|g| g.addresses.contains(&address)),
+            }
+        };
+
+        let selected = answers
+            .iter()
+            .filter(|r| keep(r))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        // the group has no addresses of the queried type, rather than no answer return all of them
+        let has_address = |name: &Name| {
+            selected
+                .iter()
+                .any(|r| r.name() == name && r.rdata().to_ip_addr().is_some())
+        };
+        if answers
+            .iter()
+            .filter(|r| is_grouped(r))
+            .all(|r| has_address(r.name()))
+        {
+            selected
+        } else {
+            answers
+        }
+    }
+}
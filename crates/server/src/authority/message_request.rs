@@ -162,6 +162,16 @@ impl MessageRequest {
         }
     }
 
+    /// Lowers the max payload of the EDNS section to the largest honored by the server, which is
+    ///  then advertised in the EDNS section of the response
+    pub(crate) fn limit_max_payload(&mut self, max_payload: u16) {
+        if let Some(ref mut edns) = self.edns {
+            if edns.max_payload() > max_payload {
+                edns.set_max_payload(max_payload);
+            }
+        }
+    }
+
     /// # Return value
     ///
     /// the version as defined in the EDNS record
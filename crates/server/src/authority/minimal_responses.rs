@@ -0,0 +1,41 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+/// The optional records omitted from the responses, as the `minimal-responses` option of BIND
+///
+/// The NS records of the zone in the authority section of answers, and the additional records,
+///  e.g. the addresses of the targets of MX records, are not required by resolvers. Omitting them
+///  reduces the size of the responses, and so the amplification of spoofed requests. The SOA and
+///  NSEC records of negative responses are always returned.
+#[derive(Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+pub enum MinimalResponses {
+    /// All the records are returned
+    No,
+    /// The NS records of the zone are omitted from the authority section of answers
+    NoAuth,
+    /// The NS records of answers and the additional records are omitted, except the records at
+    ///  the end of a CNAME chain, which answer the query
+    Yes,
+}
+
+impl MinimalResponses {
+    /// Returns true if the NS records of the zone are omitted from the answers
+    pub fn omit_ns(self) -> bool {
+        self != MinimalResponses::No
+    }
+
+    /// Returns true if the additional records are omitted from the answers which aren't CNAMEs
+    pub fn omit_additionals(self) -> bool {
+        self == MinimalResponses::Yes
+    }
+}
+
+impl Default for MinimalResponses {
+    fn default() -> Self {
+        MinimalResponses::No
+    }
+}
@@ -12,23 +12,44 @@ use trust_dns::op::ResponseCode;
 /// Result of an Update operation
 pub type UpdateResult<T> = Result<T, ResponseCode>;
 
+mod answer_selector;
 mod auth_lookup;
 #[allow(clippy::module_inception)]
 mod authority;
 pub(crate) mod authority_object;
 mod catalog;
+#[cfg(feature = "dnssec")]
+pub(crate) mod ds_check;
 mod error;
+pub mod geoip;
 pub(crate) mod message_request;
 mod message_response;
+mod minimal_responses;
+mod response_order;
+mod update_audit;
+mod zone_listener;
+mod zone_transfer;
 mod zone_type;
 
+pub use self::answer_selector::{AnswerSelector, SelectionContext};
 pub use self::auth_lookup::{
     AnyRecords, AuthLookup, AuthLookupIter, LookupRecords, LookupRecordsIter,
 };
 pub use self::authority::Authority;
 pub use self::authority_object::{AuthorityObject, BoxedLookupFuture, LookupObject};
 pub use self::catalog::Catalog;
+#[cfg(all(feature = "dnssec", feature = "trust-dns-resolver"))]
+pub use self::ds_check::check_ds;
+#[cfg(feature = "dnssec")]
+pub use self::ds_check::{compare_ds, DsCheckMetrics, DsCheckSink, DsChecker, DsMismatch};
 pub use self::error::{LookupError, LookupResult};
 pub use self::message_request::{MessageRequest, Queries, UpdateRequest};
 pub use self::message_response::{MessageResponse, MessageResponseBuilder};
+pub use self::minimal_responses::MinimalResponses;
+pub use self::response_order::{RecordOrder, ResponseOrder, ResponseOrdering};
+#[cfg(unix)]
+pub use self::update_audit::SyslogAuditSink;
+pub use self::update_audit::{FileAuditSink, UpdateAudit, UpdateAuditSink, UpdateSigner};
+pub use self::zone_listener::{ZoneChange, ZoneListener};
+pub use self::zone_transfer::{refresh_secondary, ZoneTransfer};
 pub use self::zone_type::ZoneType;
@@ -0,0 +1,110 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rand::rngs::SmallRng;
+use rand::seq::SliceRandom;
+use rand::{self, SeedableRng};
+
+use trust_dns::rr::RecordType;
+
+/// The order in which the A and AAAA records of an RRset are returned in responses
+///
+/// Many clients only use the first address in a response, reordering the records between
+///  responses spreads those clients across all the addresses.
+#[derive(Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+pub enum ResponseOrder {
+    /// Records are returned in the order in which they are stored in the zone
+    Fixed,
+    /// Records are rotated by one position on each response
+    RoundRobin,
+    /// Records are randomly shuffled on each response
+    Random,
+}
+
+impl Default for ResponseOrder {
+    fn default() -> Self {
+        ResponseOrder::Fixed
+    }
+}
+
+/// The state of a `ResponseOrder` across the responses of an Authority
+#[derive(Debug, Default)]
+pub struct ResponseOrdering {
+    order: ResponseOrder,
+    responses: AtomicUsize,
+}
+
+impl ResponseOrdering {
+    /// Creates the ordering, round robin rotation starts with the records in stored order
+    pub fn new(order: ResponseOrder) -> Self {
+        ResponseOrdering {
+            order,
+            responses: AtomicUsize::new(0),
+        }
+    }
+
+    /// The configured order
+    pub fn order(&self) -> ResponseOrder {
+        self.order
+    }
+
+    /// Returns the order of the records for the next response containing records of `record_type`
+    pub fn next(&self, record_type: RecordType) -> RecordOrder {
+        match record_type {
+            RecordType::A | RecordType::AAAA => (),
+            _ => return RecordOrder::Stored,
+        }
+
+        match self.order {
+            ResponseOrder::Fixed => RecordOrder::Stored,
+            ResponseOrder::RoundRobin => {
+                RecordOrder::Rotate(self.responses.fetch_add(1, Ordering::Relaxed))
+            }
+            ResponseOrder::Random => RecordOrder::Shuffle(rand::random()),
+        }
+    }
+}
+
+/// The order of the records in a single lookup, see `ResponseOrder`
+///
+/// The order is fixed for the lifetime of the lookup, i.e. all iterations over the records
+///  produce the same order.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RecordOrder {
+    /// The order in which the records are stored
+    Stored,
+    /// Rotate the records left by the number of positions
+    Rotate(usize),
+    /// Shuffle the records with the seed
+    Shuffle(u64),
+}
+
+impl RecordOrder {
+    /// Reorders the records in place
+    pub fn apply<T>(self, records: &mut [T]) {
+        if records.is_empty() {
+            return;
+        }
+
+        match self {
+            RecordOrder::Stored => (),
+            RecordOrder::Rotate(count) => {
+                let len = records.len();
+                records.rotate_left(count % len)
+            }
+            RecordOrder::Shuffle(seed) => records.shuffle(&mut SmallRng::seed_from_u64(seed)),
+        }
+    }
+}
+
+impl Default for RecordOrder {
+    fn default() -> Self {
+        RecordOrder::Stored
+    }
+}
@@ -0,0 +1,241 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Audit of the dynamic updates received by the `Catalog`
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::net::SocketAddr;
+#[cfg(unix)]
+use std::os::unix::net::UnixDatagram;
+use std::path::Path;
+use std::sync::Mutex;
+
+use trust_dns::op::ResponseCode;
+use trust_dns::rr::{Name, Record};
+
+use store::in_memory::ZoneDiff;
+
+/// The identity which signed an update
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UpdateSigner {
+    /// The name of the TSIG key which signed the request, verified by the server
+    Tsig(Name),
+    /// The signer name of the SIG(0) record of the request, only verified if it was accepted
+    Sig0(Name),
+}
+
+impl fmt::Display for UpdateSigner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            UpdateSigner::Tsig(ref name) => write!(f, "TSIG {}", name),
+            UpdateSigner::Sig0(ref name) => write!(f, "SIG(0) {}", name),
+        }
+    }
+}
+
+/// An UPDATE request accepted or rejected by the `Catalog`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UpdateAudit {
+    id: u16,
+    zone: Option<Name>,
+    src: Option<SocketAddr>,
+    signer: Option<UpdateSigner>,
+    response_code: ResponseCode,
+    diff: ZoneDiff,
+}
+
+impl UpdateAudit {
+    pub(crate) fn new(
+        id: u16,
+        zone: Option<Name>,
+        src: Option<SocketAddr>,
+        signer: Option<UpdateSigner>,
+        response_code: ResponseCode,
+        diff: ZoneDiff,
+    ) -> Self {
+        UpdateAudit {
+            id,
+            zone,
+            src,
+            signer,
+            response_code,
+            diff,
+        }
+    }
+
+    /// The id of the request
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+
+    /// The zone of the update, None if the request has no zone section
+    pub fn zone(&self) -> Option<&Name> {
+        self.zone.as_ref()
+    }
+
+    /// The address of the client, None if the update was not received by the server
+    pub fn src(&self) -> Option<SocketAddr> {
+        self.src
+    }
+
+    /// The identity which signed the update, if it was signed
+    pub fn signer(&self) -> Option<&UpdateSigner> {
+        self.signer.as_ref()
+    }
+
+    /// The response code of the update, `NoError` if it was accepted
+    pub fn response_code(&self) -> ResponseCode {
+        self.response_code
+    }
+
+    /// True if the update was accepted, even if it changed no record
+    pub fn is_accepted(&self) -> bool {
+        self.response_code == ResponseCode::NoError
+    }
+
+    /// The records deleted from and added to the zone, empty if the update was rejected
+    pub fn diff(&self) -> &ZoneDiff {
+        &self.diff
+    }
+}
+
+/// Formats the audit on one line, with the deleted and added records of the diff
+impl fmt::Display for UpdateAudit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let outcome = if self.is_accepted() {
+            "accepted"
+        } else {
+            "rejected"
+        };
+        write!(f, "update {} {}: {}", self.id, outcome, self.response_code)?;
+
+        if let Some(ref zone) = self.zone {
+            write!(f, " zone: {}", zone)?;
+        }
+        if let Some(src) = self.src {
+            write!(f, " src: {}", src)?;
+        }
+        if let Some(ref signer) = self.signer {
+            write!(f, " signer: {}", signer)?;
+        }
+        for record in self.diff.deleted() {
+            write!(f, " -[{}]", AuditRecord(record))?;
+        }
+        for record in self.diff.added() {
+            write!(f, " +[{}]", AuditRecord(record))?;
+        }
+
+        Ok(())
+    }
+}
+
+struct AuditRecord<'a>(&'a Record);
+
+impl<'a> fmt::Display for AuditRecord<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let record = self.0;
+        write!(
+            f,
+            "{} {} {} {:?}",
+            record.name(),
+            record.ttl(),
+            record.dns_class(),
+            record.rdata()
+        )
+    }
+}
+
+/// Receives the audit of each UPDATE request handled by the `Catalog`
+///
+/// Sinks are called synchronously once the response code of the update is known, before the
+///  response is sent, and must not block.
+pub trait UpdateAuditSink: Send + Sync {
+    /// Records the audit of an update
+    fn audit(&self, audit: &UpdateAudit);
+}
+
+impl<F> UpdateAuditSink for F
+where
+    F: Fn(&UpdateAudit) + Send + Sync,
+{
+    fn audit(&self, audit: &UpdateAudit) {
+        self(audit)
+    }
+}
+
+/// Appends the audits to a file, one line per update
+pub struct FileAuditSink {
+    file: Mutex<File>,
+}
+
+impl FileAuditSink {
+    /// Opens the file for appending, it is created if it doesn't exist
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(FileAuditSink {
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl UpdateAuditSink for FileAuditSink {
+    fn audit(&self, audit: &UpdateAudit) {
+        let mut file = self.file.lock().expect("audit file poisoned");
+        if let Err(error) = writeln!(file, "{}", audit) {
+            error!(
+                "failed to write the audit of update {}: {}",
+                audit.id(),
+                error
+            );
+        }
+    }
+}
+
+/// Sends the audits to the local syslog daemon, with the auth facility
+#[cfg(unix)]
+pub struct SyslogAuditSink {
+    socket: UnixDatagram,
+}
+
+#[cfg(unix)]
+impl SyslogAuditSink {
+    /// The socket of the local syslog daemon
+    pub const DEFAULT_PATH: &'static str = "/dev/log";
+
+    /// Facility auth (4) and severity notice (5), see RFC 5424
+    const PRIORITY: u8 = 4 * 8 + 5;
+
+    /// Connects to the syslog daemon at `/dev/log`
+    pub fn connect() -> io::Result<Self> {
+        Self::connect_to(Self::DEFAULT_PATH)
+    }
+
+    /// Connects to the syslog daemon listening on the unix datagram socket at `path`
+    pub fn connect_to<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(path)?;
+
+        Ok(SyslogAuditSink { socket })
+    }
+}
+
+#[cfg(unix)]
+impl UpdateAuditSink for SyslogAuditSink {
+    fn audit(&self, audit: &UpdateAudit) {
+        let message = format!("<{}>trust-dns-server: {}", Self::PRIORITY, audit);
+        if let Err(error) = self.socket.send(message.as_bytes()) {
+            error!(
+                "failed to send the audit of update {}: {}",
+                audit.id(),
+                error
+            );
+        }
+    }
+}
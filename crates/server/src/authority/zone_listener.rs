@@ -0,0 +1,46 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use trust_dns::rr::LowerName;
+
+/// A change to the set of zones in the `Catalog`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ZoneChange {
+    /// The zone was not in the catalog and was added
+    Added,
+    /// The authority of an existing zone was replaced
+    Replaced,
+    /// The zone was removed from the catalog
+    Removed,
+    /// The zone of a secondary was checked against its masters and is up to date, its authority
+    ///  is unchanged
+    Refreshed,
+}
+
+/// Notified of zones being added, replaced, refreshed or removed from the `Catalog` at runtime
+///
+/// This allows dependent subsystems, e.g. DNSSEC signers or secondaries sending NOTIFY, to follow
+///  the zones being served. Listeners are called synchronously after the change is visible to
+///  new requests, and must not block.
+pub trait ZoneListener: Send + Sync {
+    /// Called after the zone was changed
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - zone name, as passed to the catalog, e.g. example.com.
+    /// * `change` - what happened to the zone
+    fn zone_changed(&self, name: &LowerName, change: ZoneChange);
+}
+
+impl<F> ZoneListener for F
+where
+    F: Fn(&LowerName, ZoneChange) + Send + Sync,
+{
+    fn zone_changed(&self, name: &LowerName, change: ZoneChange) {
+        self(name, change)
+    }
+}
@@ -0,0 +1,390 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Transfers of secondary zones from their masters
+
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::future::{self, Either, Loop};
+use futures::Future;
+use tokio_executor;
+use tokio_timer::Delay;
+
+use proto::tcp::TcpOptions;
+use proto::xfer::{DnsMultiplexer, DnsResponse};
+use trust_dns::client::{ClientFuture, ClientHandle};
+use trust_dns::op::ResponseCode;
+#[cfg(not(feature = "dnssec"))]
+use trust_dns::rr::dnssec::Signer;
+#[cfg(feature = "dnssec")]
+use trust_dns::rr::dnssec::TSigner;
+use trust_dns::rr::rdata::SOA;
+use trust_dns::rr::{DNSClass, LowerName, Name, Record, RecordType};
+use trust_dns::tcp::TcpClientStream;
+
+use authority::{Authority, Catalog, ZoneType};
+use store::in_memory::InMemoryAuthority;
+
+/// Time before the next transfer when the zone has no SOA, e.g. the first transfer failed
+const DEFAULT_RETRY: Duration = Duration::from_secs(60);
+
+/// The signer of the SOA queries and the AXFRs, only TSIG is supported
+#[cfg(feature = "dnssec")]
+type TransferSigner = TSigner;
+#[cfg(not(feature = "dnssec"))]
+type TransferSigner = Signer;
+
+/// The transfers of a secondary zone from its masters, with AXFR over TCP
+///
+/// The serial of the SOA of a master is queried first, the zone is only transferred if it is
+///  newer than that of the zone held by the secondary. With a TSIG key, see `with_tsig_signer`,
+///  the queries are signed and the responses verified.
+#[derive(Clone)]
+pub struct ZoneTransfer {
+    origin: Name,
+    masters: Vec<SocketAddr>,
+    transfer_source: Option<IpAddr>,
+    is_axfr_allowed: bool,
+    timeout: Duration,
+    signer: Option<Arc<TransferSigner>>,
+}
+
+impl fmt::Debug for ZoneTransfer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ZoneTransfer")
+            .field("origin", &self.origin)
+            .field("masters", &self.masters)
+            .field("transfer_source", &self.transfer_source)
+            .field("is_axfr_allowed", &self.is_axfr_allowed)
+            .field("timeout", &self.timeout)
+            .field("signed", &self.signer.is_some())
+            .finish()
+    }
+}
+
+impl ZoneTransfer {
+    /// Creates the transfers of a zone
+    ///
+    /// # Arguments
+    ///
+    /// * `origin` - the name of the zone
+    /// * `masters` - the masters of the zone, tried in order until one answers
+    /// * `transfer_source` - local address of the connections to the masters, none for that of
+    ///   the route to the master, as the transfer-source of BIND
+    /// * `is_axfr_allowed` - whether the transferred zone can itself be transferred
+    pub fn new(
+        origin: Name,
+        masters: Vec<SocketAddr>,
+        transfer_source: Option<IpAddr>,
+        is_axfr_allowed: bool,
+    ) -> Self {
+        ZoneTransfer {
+            origin,
+            masters,
+            transfer_source,
+            is_axfr_allowed,
+            timeout: Duration::from_secs(30),
+            signer: None,
+        }
+    }
+
+    /// Signs the SOA queries and the AXFRs with the TSIG key, the masters must sign all the
+    ///  messages of their responses with it, or the transfer fails
+    #[cfg(feature = "dnssec")]
+    pub fn with_tsig_signer(mut self, signer: TSigner) -> Self {
+        self.signer = Some(Arc::new(signer));
+        self
+    }
+
+    /// The name of the zone
+    pub fn origin(&self) -> &Name {
+        &self.origin
+    }
+
+    /// Transfers the zone from the first master which answers, if its serial is newer
+    ///
+    /// Resolves to none if the zone of the master is not newer than `serial`.
+    ///
+    /// # Arguments
+    ///
+    /// * `serial` - the serial of the zone held by the secondary, none to always transfer it
+    pub fn transfer(
+        &self,
+        serial: Option<u32>,
+    ) -> Box<dyn Future<Item = Option<InMemoryAuthority>, Error = String> + Send> {
+        let this = self.clone();
+
+        Box::new(future::loop_fn(
+            (0, Vec::new()),
+            move |(index, mut errors): (usize, Vec<String>)| {
+                let master = match this.masters.get(index) {
+                    Some(master) => *master,
+                    None => {
+                        return Either::A(future::err(format!(
+                            "transfer of {} failed: {}",
+                            this.origin,
+                            errors.join(", ")
+                        )));
+                    }
+                };
+
+                Either::B(
+                    this.transfer_from(master, serial)
+                        .then(move |result| match result {
+                            Ok(zone) => Ok(Loop::Break(zone)),
+                            Err(error) => {
+                                warn!("transfer from {} failed: {}", master, error);
+                                errors.push(format!("{}: {}", master, error));
+                                Ok(Loop::Continue((index + 1, errors)))
+                            }
+                        }),
+                )
+            },
+        ))
+    }
+
+    fn transfer_from(
+        &self,
+        master: SocketAddr,
+        serial: Option<u32>,
+    ) -> impl Future<Item = Option<InMemoryAuthority>, Error = String> + Send {
+        let options = TcpOptions {
+            bind_addr: self.transfer_source,
+            ..TcpOptions::default()
+        };
+        let timeout = self.timeout;
+        let origin = self.origin.clone();
+        let is_axfr_allowed = self.is_axfr_allowed;
+        let signer = self.signer.clone();
+
+        // connects once polled, on the executor the connection is spawned on
+        future::lazy(move || {
+            let (stream, handle) = TcpClientStream::with_options(master, timeout, options);
+            let multiplexer = DnsMultiplexer::with_timeout(stream, handle, timeout, signer);
+            let (bg, mut client) = ClientFuture::connect(multiplexer);
+            // the connection is closed once the client is dropped
+            tokio_executor::spawn(bg);
+
+            let mut transfer_client = client.clone();
+            client
+                .query(origin.clone(), DNSClass::IN, RecordType::SOA)
+                .map_err(|e| format!("SOA query failed: {}", e))
+                .and_then(move |response| {
+                    let master_serial = match soa_serial(&origin, &response) {
+                        Ok(master_serial) => master_serial,
+                        Err(error) => return Either::A(future::err(error)),
+                    };
+
+                    if let Some(serial) = serial {
+                        if !is_newer(master_serial, serial) {
+                            debug!("{} is up to date, serial: {}", origin, serial);
+                            return Either::A(future::ok(None));
+                        }
+                    }
+
+                    info!(
+                        "transferring {} from {}, serial: {}",
+                        origin, master, master_serial
+                    );
+                    Either::B(
+                        transfer_client
+                            .zone_transfer(origin.clone(), None)
+                            .map_err(|e| format!("AXFR failed: {}", e))
+                            .and_then(move |response| {
+                                axfr_zone(&origin, &response, is_axfr_allowed).map(Some)
+                            }),
+                    )
+                })
+        })
+    }
+}
+
+/// Returns a future which transfers the zone into the catalog, and again on every refresh of its
+///  SOA if the masters have a newer serial, it never completes
+///
+/// The first transfer adds the zone to the catalog, unless it was already loaded, e.g. from its
+///  file. After a failed transfer the next one is attempted after the retry of the SOA. Once the
+///  expire of the SOA passed without a successful refresh, the zone is removed from the catalog,
+///  it's served again once it is transferred.
+pub fn refresh_secondary(
+    transfer: ZoneTransfer,
+    catalog: Catalog,
+) -> impl Future<Item = (), Error = ()> + Send {
+    let origin = LowerName::from(transfer.origin());
+
+    // the zone already held, e.g. loaded from its file, expires as if it was just refreshed
+    future::loop_fn(Instant::now(), move |refreshed| {
+        let current = current_soa(&catalog, &origin);
+        let catalog = catalog.clone();
+        let origin = origin.clone();
+
+        transfer
+            .transfer(current.as_ref().map(SOA::serial))
+            .then(move |result| {
+                let (next, refreshed) = match result {
+                    Ok(Some(authority)) => {
+                        let soa = current_soa_of(&authority);
+                        info!("zone transferred: {}", origin);
+                        catalog.swap_zone(origin, Box::new(authority));
+                        (soa.map(|soa| refresh_of(&soa)), Instant::now())
+                    }
+                    Ok(None) => {
+                        catalog.zone_refreshed(&origin);
+                        (current.map(|soa| refresh_of(&soa)), Instant::now())
+                    }
+                    Err(error) => {
+                        error!("{}", error);
+                        let next = current.map(|soa| {
+                            let expire = expire_of(&soa).checked_sub(refreshed.elapsed());
+                            match expire {
+                                // retried once more when the zone expires
+                                Some(expire) if expire > Duration::from_secs(0) => {
+                                    retry_of(&soa).min(expire)
+                                }
+                                _ => {
+                                    error!("zone expired, it's not served: {}", origin);
+                                    catalog.remove_zone(&origin);
+                                    retry_of(&soa)
+                                }
+                            }
+                        });
+                        (next, refreshed)
+                    }
+                };
+
+                Delay::new(Instant::now() + next.unwrap_or(DEFAULT_RETRY))
+                    .map_err(|e| error!("zone transfer timer failed: {}", e))
+                    .map(move |()| Loop::Continue(refreshed))
+            })
+    })
+}
+
+/// The serial of the SOA of the zone in the response of the master
+fn soa_serial(origin: &Name, response: &DnsResponse) -> Result<u32, String> {
+    if response.response_code() != ResponseCode::NoError {
+        return Err(format!("SOA query failed: {}", response.response_code()));
+    }
+
+    response
+        .answers()
+        .iter()
+        .filter(|record| record.name() == origin)
+        .filter_map(|record| record.rdata().as_soa())
+        .map(SOA::serial)
+        .next()
+        .ok_or_else(|| format!("no SOA for {}", origin))
+}
+
+/// Returns true if the serial is newer than the current one, RFC 1982 serial number arithmetic
+fn is_newer(serial: u32, current: u32) -> bool {
+    serial != current && serial.wrapping_sub(current) < 1 << 31
+}
+
+/// The zone of the records of the AXFR, which must start and end with the SOA of the zone
+fn axfr_zone(
+    origin: &Name,
+    response: &DnsResponse,
+    is_axfr_allowed: bool,
+) -> Result<InMemoryAuthority, String> {
+    if let Some(message) = response
+        .messages()
+        .find(|message| message.response_code() != ResponseCode::NoError)
+    {
+        return Err(format!("AXFR refused: {}", message.response_code()));
+    }
+
+    let records = response
+        .messages()
+        .flat_map(|message| message.answers().iter())
+        .collect::<Vec<&Record>>();
+
+    let soa_serial = |record: Option<&&Record>| {
+        record
+            .filter(|record| record.name() == origin)
+            .and_then(|record| record.rdata().as_soa())
+            .map(SOA::serial)
+    };
+    let serial = match (soa_serial(records.first()), soa_serial(records.last())) {
+        (Some(first), Some(last)) if records.len() > 1 && first == last => first,
+        _ => {
+            return Err(format!(
+                "incomplete AXFR of {}, {} records",
+                origin,
+                records.len()
+            ))
+        }
+    };
+
+    let mut authority = InMemoryAuthority::empty(origin.clone(), ZoneType::Slave, is_axfr_allowed);
+
+    // the closing SOA is the same as the first
+    for record in &records[..records.len() - 1] {
+        if record.dns_class() != DNSClass::IN || !origin.zone_of(record.name()) {
+            debug!("ignoring record outside of {}: {}", origin, record.name());
+            continue;
+        }
+
+        authority.upsert((*record).clone(), serial);
+    }
+
+    Ok(authority)
+}
+
+/// The SOA of the zone held by the catalog, if it is a zone of its own
+fn current_soa(catalog: &Catalog, origin: &LowerName) -> Option<SOA> {
+    let authority = catalog.find(origin)?;
+    let authority = authority.read().expect("authority poisoned");
+    if authority.origin() != origin {
+        return None;
+    }
+
+    let soa = authority.soa().wait().ok()?;
+    let soa = soa
+        .iter()
+        .filter_map(|record| record.rdata().as_soa())
+        .next();
+    soa.cloned()
+}
+
+fn current_soa_of(authority: &InMemoryAuthority) -> Option<SOA> {
+    let soa = authority.soa().wait().ok()?;
+    let soa = soa
+        .iter()
+        .filter_map(|record| record.rdata().as_soa())
+        .next();
+    soa.cloned()
+}
+
+fn refresh_of(soa: &SOA) -> Duration {
+    Duration::from_secs(soa.refresh().max(1) as u64)
+}
+
+fn retry_of(soa: &SOA) -> Duration {
+    Duration::from_secs(soa.retry().max(1) as u64)
+}
+
+fn expire_of(soa: &SOA) -> Duration {
+    Duration::from_secs(soa.expire().max(1) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_newer() {
+        assert!(is_newer(2, 1));
+        assert!(!is_newer(1, 1));
+        assert!(!is_newer(1, 2));
+        // the serial wraps around
+        assert!(is_newer(1, 0xFFFF_FFFF));
+        assert!(!is_newer(0xFFFF_FFFF, 1));
+    }
+}
@@ -16,6 +16,9 @@ pub enum ZoneType {
     Hint,
     /// A cached zone where all requests are forwarded to another Resolver
     Forward,
+    /// A cached zone where requests are resolved by the authoritative name servers of the zone,
+    ///  learned from its NS records
+    Stub,
 }
 
 impl ZoneType {
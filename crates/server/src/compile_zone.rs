@@ -0,0 +1,136 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The `compile-zone` binary for compiling master files, which `named` then loads without parsing
+//!
+//! ```text
+//! Usage: compile-zone [options] --origin=NAME --output=FILE ZONE_FILE
+//!       compile-zone (-h | --help | --version)
+//!
+//! Options:
+//!    -h, --help              Show this message
+//!    -v, --version           Show the version of trust-dns
+//!    -n NAME, --origin=NAME  The origin of the zone, unless the zone file sets $ORIGIN
+//!    -o FILE, --output=FILE  Path of the compiled zone
+//! ```
+//!
+//! The compiled zone replaces the master file in the `zone_file_path`, or `file`, of the zone in
+//!  the configuration of `named`.
+
+#[macro_use]
+extern crate clap;
+#[macro_use]
+extern crate log;
+extern crate trust_dns;
+extern crate trust_dns_server;
+
+use std::fs;
+use std::path::Path;
+use std::process;
+use std::str::FromStr;
+
+use clap::{Arg, ArgMatches};
+
+use trust_dns::rr::Name;
+use trust_dns_server::authority::{Authority, ZoneType};
+use trust_dns_server::logger;
+use trust_dns_server::store::file::{compile_zone, FileAuthority, FileConfig};
+
+// argument name constants for the CLI options
+const ORIGIN_ARG: &str = "origin";
+const OUTPUT_ARG: &str = "output";
+const ZONE_FILE_ARG: &str = "ZONE_FILE";
+
+/// Args struct for all options
+struct Args {
+    pub flag_origin: String,
+    pub flag_output: String,
+    pub arg_zone_file: String,
+}
+
+impl<'a> From<ArgMatches<'a>> for Args {
+    fn from(matches: ArgMatches<'a>) -> Args {
+        Args {
+            flag_origin: matches
+                .value_of(ORIGIN_ARG)
+                .map(ToString::to_string)
+                .expect("origin is required"),
+            flag_output: matches
+                .value_of(OUTPUT_ARG)
+                .map(ToString::to_string)
+                .expect("output is required"),
+            arg_zone_file: matches
+                .value_of(ZONE_FILE_ARG)
+                .map(ToString::to_string)
+                .expect("zone file is required"),
+        }
+    }
+}
+
+fn compile(args: &Args) -> Result<(), String> {
+    let origin = Name::from_str(&args.flag_origin)
+        .map_err(|e| format!("bad origin {}: {}", args.flag_origin, e))?;
+    let config = FileConfig {
+        zone_file_path: args.arg_zone_file.clone(),
+    };
+
+    // the zone is loaded as named loads it, so that it's checked as it would be
+    let authority = FileAuthority::try_from_config(origin, ZoneType::Master, false, None, &config)?;
+    let origin = Name::from(authority.origin());
+    let records = authority.records();
+    let compiled = compile_zone(&origin, &records)
+        .map_err(|e| format!("failed to compile {}: {}", args.arg_zone_file, e))?;
+
+    let output = Path::new(&args.flag_output);
+    fs::write(output, &compiled)
+        .map_err(|e| format!("failed to write {}: {}", output.display(), e))?;
+
+    info!(
+        "compiled zone {} with {} rrsets to {}: {} bytes",
+        origin,
+        records.len(),
+        output.display(),
+        compiled.len()
+    );
+    Ok(())
+}
+
+/// Main method for compiling a zone file
+pub fn main() {
+    let args = app_from_crate!()
+        .arg(
+            Arg::with_name(ORIGIN_ARG)
+                .long(ORIGIN_ARG)
+                .short("n")
+                .help("The origin of the zone, unless the zone file sets $ORIGIN")
+                .value_name("NAME")
+                .required(true),
+        )
+        .arg(
+            Arg::with_name(OUTPUT_ARG)
+                .long(OUTPUT_ARG)
+                .short("o")
+                .help("Path of the compiled zone")
+                .value_name("FILE")
+                .required(true),
+        )
+        .arg(
+            Arg::with_name(ZONE_FILE_ARG)
+                .help("Path of the master file of the zone")
+                .required(true)
+                .index(1),
+        )
+        .get_matches();
+
+    let args: Args = args.into();
+    logger::default();
+
+    if let Err(error) = compile(&args) {
+        error!("{}", error);
+        process::exit(1);
+    }
+}
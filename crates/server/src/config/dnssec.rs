@@ -90,6 +90,7 @@ impl KeyConfig {
             Some("key") => Ok(KeyFormat::Pem), // TODO: deprecate this...
             Some("pem") => Ok(KeyFormat::Pem),
             Some("pk8") => Ok(KeyFormat::Pkcs8),
+            Some("private") => Ok(KeyFormat::Bind),
             e => Err(ParseErrorKind::Msg(format!(
                 "extension not understood, '{:?}': {:?}",
                 e,
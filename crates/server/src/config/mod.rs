@@ -11,7 +11,7 @@ pub mod dnssec;
 
 use std::fs::File;
 use std::io::Read;
-use std::net::{Ipv4Addr, Ipv6Addr};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::time::Duration;
@@ -20,10 +20,14 @@ use log;
 use toml;
 
 use proto::error::ProtoResult;
+use proto::tcp::TcpOptions;
+use proto::xfer::dns_request::MAX_PAYLOAD_LEN;
 use trust_dns::rr::Name;
 
-use authority::ZoneType;
+use authority::geoip::GeoIpConfig;
+use authority::{MinimalResponses, ResponseOrder, ZoneType};
 use error::{ConfigError, ConfigResult};
+use server::{CookieSecrets, Protocol, ProtocolPolicy};
 use store::StoreConfig;
 
 static DEFAULT_PATH: &'static str = "/var/named"; // TODO what about windows (do I care? ;)
@@ -41,14 +45,39 @@ pub struct Config {
     /// This list of IPv6 addresses to listen on
     #[serde(default)]
     listen_addrs_ipv6: Vec<String>,
+    /// The addresses to listen on, with their protocols and IPv6 options
+    #[serde(default)]
+    listen: Vec<ListenConfig>,
     /// Port on which to listen (associated to all IPs)
     listen_port: Option<u16>,
     /// Secure port to listen on
     tls_listen_port: Option<u16>,
     /// HTTPS port to listen on
     https_listen_port: Option<u16>,
+    /// HTTP port to listen on, for a reverse proxy which terminates the HTTPS connections
+    http_listen_port: Option<u16>,
+    /// Serve the `/healthz` and `/readyz` endpoints on the HTTPS and HTTP listeners
+    health_endpoints: Option<bool>,
     /// Timeout associated to a request before it is closed.
     tcp_request_timeout: Option<u64>,
+    /// Time in seconds within which a request must be handled, otherwise it is answered with ServFail
+    request_deadline: Option<u64>,
+    /// The largest response over UDP, larger responses are truncated, default is 1232
+    max_udp_payload: Option<u16>,
+    /// Refuse the ANY queries over UDP, default is false
+    refuse_any_over_udp: Option<bool>,
+    /// The largest EDNS payload honored over TLS and HTTPS, by default it is not limited
+    max_encrypted_payload: Option<u16>,
+    /// Disable Nagle's algorithm on the TCP connections, TLS and HTTPS included, default is false
+    tcp_nodelay: Option<bool>,
+    /// Interval in seconds of the keepalive probes of idle TCP connections, default is none
+    tcp_keepalive: Option<u64>,
+    /// Time in seconds after which TCP connections with unacknowledged data are closed
+    tcp_user_timeout: Option<u64>,
+    /// Accept the data sent with the SYN of TCP connections, TCP Fast Open, default is false
+    tcp_fast_open: Option<bool>,
+    /// Receive and send the UDP datagrams in batches, with GRO and GSO on Linux, default is false
+    udp_offload: Option<bool>,
     /// Level at which to log, default is INFO
     log_level: Option<String>,
     /// Base configuration directory, i.e. root path for zones
@@ -58,6 +87,12 @@ pub struct Config {
     zones: Vec<ZoneConfig>,
     /// Certificate to associate to TLS connections (currently the same is used for HTTPS and TLS)
     tls_cert: Option<dnssec::TlsCertConfig>,
+    /// Audit of the dynamic updates, accepted or rejected
+    update_audit: Option<UpdateAuditConfig>,
+    /// Server cookies of the responses to requests with a DNS cookie
+    cookies: Option<CookieConfig>,
+    /// Optional records omitted from the answers of all zones, defaults to No
+    minimal_responses: Option<MinimalResponses>,
 }
 
 impl Config {
@@ -85,6 +120,36 @@ impl Config {
             .collect()
     }
 
+    /// the addresses to listen on, those of `listen` followed by the `listen_addrs_ipv4` and
+    ///  `listen_addrs_ipv6`, by default all the IPv4 addresses
+    ///
+    /// The IPv6 addresses of `listen_addrs_ipv6` only accept IPv6 connections if IPv4 addresses
+    ///  are also listened on, so that `0.0.0.0` and `::` can both be bound.
+    pub fn get_listen(&self) -> Vec<ListenConfig> {
+        let v4addrs = self.get_listen_addrs_ipv4();
+        let v6only = if v4addrs.is_empty() { None } else { Some(true) };
+
+        let mut listen = self.listen.clone();
+        listen.extend(
+            v4addrs
+                .into_iter()
+                .map(|ip| ListenConfig::new(IpAddr::V4(ip), None))
+                .chain(
+                    self.get_listen_addrs_ipv6()
+                        .into_iter()
+                        .map(|ip| ListenConfig::new(IpAddr::V6(ip), v6only)),
+                ),
+        );
+
+        if listen.is_empty() {
+            listen.push(ListenConfig::new(
+                IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)),
+                None,
+            ));
+        }
+        listen
+    }
+
     /// port on which to listen for connections on specified addresses
     pub fn get_listen_port(&self) -> u16 {
         self.listen_port.unwrap_or(DEFAULT_PORT)
@@ -100,6 +165,17 @@ impl Config {
         self.https_listen_port.unwrap_or(DEFAULT_HTTPS_PORT)
     }
 
+    /// port on which to listen for HTTP connections from a reverse proxy, by default there is none
+    pub fn get_http_listen_port(&self) -> Option<u16> {
+        self.http_listen_port
+    }
+
+    /// true if the HTTPS and HTTP listeners serve the `/healthz` and `/readyz` endpoints, by
+    ///  default they don't
+    pub fn get_health_endpoints(&self) -> bool {
+        self.health_endpoints.unwrap_or(false)
+    }
+
     /// default timeout for all TCP connections before forceably shutdown
     pub fn get_tcp_request_timeout(&self) -> Duration {
         Duration::from_secs(
@@ -108,6 +184,50 @@ impl Config {
         )
     }
 
+    /// time within which a request must be handled before it is answered with a ServFail, by
+    ///  default there is none
+    pub fn get_request_deadline(&self) -> Option<Duration> {
+        self.request_deadline.map(Duration::from_secs)
+    }
+
+    /// the size to which responses over UDP are truncated, by default 1232 as recommended by the
+    ///  DNS Flag Day 2020, so that they are not fragmented
+    pub fn get_max_udp_payload(&self) -> u16 {
+        self.max_udp_payload.unwrap_or(MAX_PAYLOAD_LEN)
+    }
+
+    /// the answers depending on the protocol of the requests, which refuse ANY queries over UDP
+    ///  if `refuse_any_over_udp`, and honor EDNS payloads larger than `max_udp_payload` over TLS
+    ///  and HTTPS only
+    pub fn get_protocol_policy(&self) -> ProtocolPolicy {
+        let mut policy = ProtocolPolicy::new();
+        policy
+            .set_refuse_any_over_udp(self.refuse_any_over_udp.unwrap_or(false))
+            .set_max_udp_payload(self.get_max_udp_payload());
+        if let Some(max_encrypted_payload) = self.max_encrypted_payload {
+            policy.set_max_encrypted_payload(max_encrypted_payload);
+        }
+        policy
+    }
+
+    /// the socket options of the TCP, TLS and HTTPS listeners and their connections, by default
+    ///  those of the operating system
+    pub fn get_tcp_options(&self) -> TcpOptions {
+        TcpOptions {
+            nodelay: self.tcp_nodelay.unwrap_or(false),
+            keepalive: self.tcp_keepalive.map(Duration::from_secs),
+            user_timeout: self.tcp_user_timeout.map(Duration::from_secs),
+            fast_open: self.tcp_fast_open.unwrap_or(false),
+            bind_addr: None,
+        }
+    }
+
+    /// receive and send the datagrams of the UDP sockets in batches, with the offloads supported
+    ///  by the kernel, by default one system call per datagram
+    pub fn get_udp_offload(&self) -> bool {
+        self.udp_offload.unwrap_or(false)
+    }
+
     /// specify the log level which should be used, ["Trace", "Debug", "Info", "Warn", "Error"]
     pub fn get_log_level(&self) -> log::Level {
         if let Some(ref level_str) = self.log_level {
@@ -140,6 +260,22 @@ impl Config {
     pub fn get_tls_cert(&self) -> Option<&dnssec::TlsCertConfig> {
         self.tls_cert.as_ref()
     }
+
+    /// the sinks of the audit of the dynamic updates, None if updates are not audited
+    pub fn get_update_audit(&self) -> Option<&UpdateAuditConfig> {
+        self.update_audit.as_ref()
+    }
+
+    /// the secrets of the server cookies, None if the responses have no server cookies
+    pub fn get_cookies(&self) -> Option<&CookieConfig> {
+        self.cookies.as_ref()
+    }
+
+    /// the optional records omitted from the answers, e.g. the NS records of the zone, by default
+    ///  all the records are returned
+    pub fn get_minimal_responses(&self) -> MinimalResponses {
+        self.minimal_responses.unwrap_or_default()
+    }
 }
 
 impl FromStr for Config {
@@ -150,6 +286,122 @@ impl FromStr for Config {
     }
 }
 
+/// Configuration of an address to listen on, `[[listen]]`
+#[derive(Deserialize, PartialEq, Debug, Clone)]
+pub struct ListenConfig {
+    /// IP address, or socket address to listen on a port other than `listen_port`, e.g. `[::]:53`
+    addr: String,
+    /// Only accept IPv6 connections on an IPv6 address, by default the setting of the system
+    v6only: Option<bool>,
+    /// The protocols served on the address, by default all of those configured
+    protocols: Option<Vec<Protocol>>,
+}
+
+impl ListenConfig {
+    fn new(ip: IpAddr, v6only: Option<bool>) -> Self {
+        ListenConfig {
+            addr: ip.to_string(),
+            v6only,
+            protocols: None,
+        }
+    }
+
+    /// the IP address to listen on
+    pub fn get_ip(&self) -> IpAddr {
+        self.get_socket_addr()
+            .map(|addr| addr.ip())
+            .unwrap_or_else(|| self.addr.parse().unwrap())
+    }
+
+    /// the port of the UDP and TCP listeners given with the address, otherwise `listen_port`
+    ///  applies, TLS and HTTPS listen on their own ports
+    pub fn get_port(&self) -> Option<u16> {
+        self.get_socket_addr().map(|addr| addr.port())
+    }
+
+    fn get_socket_addr(&self) -> Option<SocketAddr> {
+        self.addr.parse::<SocketAddr>().ok()
+    }
+
+    /// true if only IPv6 connections are accepted, false if IPv4 connections are accepted on
+    ///  IPv6 addresses as IPv4-mapped addresses, by default the setting of the system,
+    ///  `net.ipv6.bindv6only` on Linux. Ignored on IPv4 addresses.
+    pub fn get_v6only(&self) -> Option<bool> {
+        match self.get_ip() {
+            IpAddr::V4(..) => None,
+            IpAddr::V6(..) => self.v6only,
+        }
+    }
+
+    /// true if the protocol is served on this address, TLS and HTTPS only with a `tls_cert`
+    pub fn serves(&self, protocol: Protocol) -> bool {
+        self.protocols
+            .as_ref()
+            .map_or(true, |protocols| protocols.contains(&protocol))
+    }
+}
+
+/// Configuration of the audit of the dynamic updates, see `UpdateAuditSink`
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct UpdateAuditConfig {
+    /// File to which the audits are appended, relative to the directory
+    file: Option<String>,
+    /// Send the audits to the local syslog daemon, with the auth facility
+    #[serde(default)]
+    syslog: bool,
+}
+
+impl UpdateAuditConfig {
+    /// path of the file to which the audits are appended, relative to the directory
+    pub fn get_file(&self) -> Option<&Path> {
+        self.file.as_ref().map(Path::new)
+    }
+
+    /// true if the audits are sent to the local syslog daemon
+    pub fn get_syslog(&self) -> bool {
+        self.syslog
+    }
+}
+
+/// Configuration of the secrets of the server cookies, see `CookieSecrets`
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct CookieConfig {
+    /// File in which the secrets are persisted across restarts, relative to the directory
+    secret_file: Option<String>,
+    /// The first secret, 16 bytes in hex, random by default
+    secret: Option<String>,
+    /// Seconds between rotations of the secret, default is a day
+    rotation_interval: Option<u64>,
+    /// Seconds after a rotation during which the previous secret is valid, default is an hour
+    overlap: Option<u64>,
+}
+
+impl CookieConfig {
+    /// path of the file in which the secrets are persisted, relative to the directory
+    pub fn get_secret_file(&self) -> Option<&Path> {
+        self.secret_file.as_ref().map(Path::new)
+    }
+
+    /// the first secret, in hex, used if the secret file doesn't exist yet
+    pub fn get_secret(&self) -> Option<&str> {
+        self.secret.as_ref().map(|secret| &secret[..])
+    }
+
+    /// the interval between rotations of the secret
+    pub fn get_rotation_interval(&self) -> Duration {
+        self.rotation_interval
+            .map(Duration::from_secs)
+            .unwrap_or(CookieSecrets::DEFAULT_ROTATION_INTERVAL)
+    }
+
+    /// the time after a rotation during which cookies of the previous secret are valid
+    pub fn get_overlap(&self) -> Duration {
+        self.overlap
+            .map(Duration::from_secs)
+            .unwrap_or(CookieSecrets::DEFAULT_OVERLAP)
+    }
+}
+
 /// Configuration for a zone
 #[derive(Deserialize, PartialEq, Debug)]
 pub struct ZoneConfig {
@@ -159,6 +411,13 @@ pub struct ZoneConfig {
     pub zone_type: ZoneType,
     /// location of the file (short for StoreConfig::FileConfig{zone_file_path})
     pub file: Option<String>,
+    /// masters of a Slave zone, it is transferred from the first which answers, on every refresh
+    ///  of its SOA
+    #[serde(default)]
+    pub masters: Vec<SocketAddr>,
+    /// local address of the transfers of a Slave zone, as the transfer-source of BIND, by
+    ///  default that of the route to the master
+    pub transfer_source: Option<IpAddr>,
     /// Deprecated allow_update, this is a Store option
     pub allow_update: Option<bool>,
     /// Allow AXFR (TODO: need auth)
@@ -168,6 +427,16 @@ pub struct ZoneConfig {
     /// Keys for use by the zone
     #[serde(default)]
     pub keys: Vec<dnssec::KeyConfig>,
+    /// Order of the A and AAAA records in responses, defaults to Fixed
+    pub response_order: Option<ResponseOrder>,
+    /// GeoIP selection of the A and AAAA answers, requires the `geoip` feature
+    pub geoip: Option<GeoIpConfig>,
+    /// Time in seconds over which the expirations of the signatures of the zone are spread
+    pub signature_jitter: Option<u64>,
+    /// Periodic check of the DS records of the parent against the DNSKEY records of the zone
+    pub ds_check: Option<DsCheckConfig>,
+    /// Optional records omitted from the answers of the zone, overrides that of the server
+    pub minimal_responses: Option<MinimalResponses>,
     /// Store configurations, TODO: allow chained Stores
     #[serde(default)]
     pub stores: Option<StoreConfig>,
@@ -198,10 +467,17 @@ impl ZoneConfig {
             zone,
             zone_type,
             file: Some(file),
+            masters: vec![],
+            transfer_source: None,
             allow_update,
             allow_axfr,
             enable_dnssec,
             keys,
+            response_order: None,
+            geoip: None,
+            signature_jitter: None,
+            ds_check: None,
+            minimal_responses: None,
             stores: None,
         }
     }
@@ -241,8 +517,60 @@ impl ZoneConfig {
         self.enable_dnssec.unwrap_or(false)
     }
 
+    /// the order in which A and AAAA records are returned in responses
+    pub fn get_response_order(&self) -> ResponseOrder {
+        self.response_order.unwrap_or_default()
+    }
+
+    /// the window over which the expirations of the signatures are spread, each signature expires
+    ///  earlier by a random time within it so that they aren't all signed again at once, by
+    ///  default there is none
+    pub fn get_signature_jitter(&self) -> Duration {
+        Duration::from_secs(self.signature_jitter.unwrap_or(0))
+    }
+
     /// the configuration for the keys used for auth and/or dnssec zone signing.
     pub fn get_keys(&self) -> &[dnssec::KeyConfig] {
         &self.keys
     }
+
+    /// the check of the DS records of the parent, if any
+    pub fn get_ds_check(&self) -> Option<&DsCheckConfig> {
+        self.ds_check.as_ref()
+    }
+
+    /// the optional records omitted from the answers of the zone, if it differs from the server
+    pub fn get_minimal_responses(&self) -> Option<MinimalResponses> {
+        self.minimal_responses
+    }
+
+    /// the masters from which a Slave zone is transferred, none if it is only loaded from its file
+    pub fn get_masters(&self) -> &[SocketAddr] {
+        &self.masters
+    }
+
+    /// the local address of the transfers, none for that of the route to the master
+    pub fn get_transfer_source(&self) -> Option<IpAddr> {
+        self.transfer_source
+    }
+}
+
+/// Configuration of the periodic check of the DS records of a signed zone, see `DsChecker`
+///
+/// The DS records are resolved from the parent, requires the `trust-dns-resolver` feature.
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct DsCheckConfig {
+    /// seconds between each check, defaults to an hour
+    pub interval: Option<u64>,
+    /// addresses of the recursive name servers resolving the DS records, on port 53, by default
+    ///  those of the system
+    #[serde(default)]
+    pub name_servers: Vec<IpAddr>,
+}
+
+impl DsCheckConfig {
+    /// Time between each check of the DS records
+    pub fn get_interval(&self) -> Duration {
+        Duration::from_secs(self.interval.unwrap_or(3600))
+    }
 }
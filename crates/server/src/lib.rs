@@ -37,6 +37,11 @@ extern crate failure;
 extern crate futures;
 #[macro_use]
 extern crate log;
+#[cfg(feature = "geoip")]
+extern crate maxminddb;
+extern crate rand;
+#[cfg(feature = "dnssec")]
+extern crate rayon;
 extern crate rusqlite;
 #[macro_use]
 extern crate serde;
@@ -46,7 +51,7 @@ extern crate h2;
 extern crate http;
 #[cfg(feature = "dns-over-openssl")]
 extern crate openssl;
-#[cfg(feature = "dns-over-rustls")]
+#[cfg(feature = "rustls")]
 extern crate rustls;
 extern crate time;
 extern crate tokio;
@@ -59,6 +64,10 @@ extern crate tokio_tcp;
 extern crate tokio_timer;
 extern crate tokio_udp;
 extern crate toml;
+#[cfg(feature = "tracing-spans")]
+extern crate tracing;
+#[cfg(feature = "tracing-spans")]
+extern crate tracing_futures;
 extern crate trust_dns;
 #[cfg(feature = "dns-over-https")]
 extern crate trust_dns_https;
@@ -69,6 +78,10 @@ pub extern crate trust_dns_proto as proto;
 extern crate trust_dns_resolver;
 #[cfg(feature = "dns-over-rustls")]
 extern crate trust_dns_rustls;
+#[cfg(feature = "blocklist-https")]
+extern crate webpki;
+#[cfg(feature = "blocklist-https")]
+extern crate webpki_roots;
 
 pub mod authority;
 pub mod config;
@@ -76,6 +89,8 @@ pub mod error;
 pub mod logger;
 pub mod server;
 pub mod store;
+#[cfg(feature = "testing")]
+pub mod testing;
 
 pub use self::server::ServerFuture;
 
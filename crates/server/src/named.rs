@@ -31,6 +31,7 @@ extern crate futures;
 extern crate log;
 #[cfg(feature = "dns-over-rustls")]
 extern crate rustls;
+extern crate socket2;
 extern crate tokio;
 extern crate tokio_executor;
 extern crate tokio_tcp;
@@ -43,11 +44,15 @@ extern crate trust_dns_rustls;
 extern crate trust_dns_server;
 
 use std::io;
-use std::net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs};
+use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 
 use clap::{Arg, ArgMatches};
 use futures::{future, Future};
+use socket2::{Domain, Socket, Type};
+use tokio::reactor::Handle;
 use tokio::runtime::Runtime;
 use tokio::runtime::TaskExecutor;
 use tokio_tcp::TcpListener;
@@ -56,23 +61,48 @@ use tokio_udp::UdpSocket;
 #[cfg(feature = "dnssec")]
 use trust_dns::rr::rdata::key::KeyUsage;
 use trust_dns::rr::Name;
-use trust_dns_server::authority::{AuthorityObject, Catalog, ZoneType};
+#[cfg(unix)]
+use trust_dns_server::authority::SyslogAuditSink;
+use trust_dns_server::authority::{
+    self, AnswerSelector, AuthorityObject, Catalog, FileAuditSink, ZoneTransfer, ZoneType,
+};
 #[cfg(any(feature = "dns-over-tls", feature = "dnssec"))]
 use trust_dns_server::config::dnssec::{self, TlsCertConfig};
-use trust_dns_server::config::{Config, ZoneConfig};
+use trust_dns_server::config::{Config, CookieConfig, ListenConfig, UpdateAuditConfig, ZoneConfig};
 use trust_dns_server::logger;
-use trust_dns_server::server::ServerFuture;
+#[cfg(feature = "request-arena")]
+use trust_dns_server::server::RequestArena;
+use trust_dns_server::server::{CookieSecrets, Health, Protocol, ServerFuture};
+#[cfg(feature = "trust-dns-resolver")]
+use trust_dns_server::store::aname::AnameAuthority;
+use trust_dns_server::store::auto_ptr::AutoPtrAuthority;
+#[cfg(feature = "trust-dns-resolver")]
+use trust_dns_server::store::blocklist::BlocklistAuthority;
+use trust_dns_server::store::dhcp::DhcpAuthority;
 use trust_dns_server::store::file::{FileAuthority, FileConfig};
 #[cfg(feature = "trust-dns-resolver")]
 use trust_dns_server::store::forwarder::ForwardAuthority;
+use trust_dns_server::store::load_balancer::LoadBalancerAuthority;
+#[cfg(feature = "trust-dns-resolver")]
+use trust_dns_server::store::local_data::LocalDataAuthority;
 use trust_dns_server::store::sqlite::{SqliteAuthority, SqliteConfig};
+#[cfg(feature = "trust-dns-resolver")]
+use trust_dns_server::store::stub::StubAuthority;
+use trust_dns_server::store::synth_ptr::SynthPtrAuthority;
 use trust_dns_server::store::StoreConfig;
 
+/// The transient allocations of the requests are made from the arena of their thread
+#[cfg(feature = "request-arena")]
+#[global_allocator]
+static ALLOCATOR: RequestArena = RequestArena;
+
 #[cfg_attr(not(feature = "dnssec"), allow(unused_mut, unused))]
 fn load_zone(
     zone_dir: &Path,
     zone_config: &ZoneConfig,
     executor: &TaskExecutor,
+    health: &Health,
+    catalog: &Catalog,
 ) -> Result<Box<dyn AuthorityObject>, String> {
     use std::path::PathBuf;
 
@@ -84,6 +114,7 @@ fn load_zone(
     let zone_type: ZoneType = zone_config.get_zone_type();
     let is_axfr_allowed = zone_config.is_axfr_allowed();
     let is_dnssec_enabled = zone_config.is_dnssec_enabled();
+    let response_order = zone_config.get_response_order();
 
     if zone_config.is_update_allowed() {
         warn!("allow_update is deprecated in [[zones]] section, it belongs in [[zones.stores]]");
@@ -96,34 +127,92 @@ fn load_zone(
                 warn!("ignoring [[zones.file]] instead using [[zones.stores.zone_file_path]]");
             }
 
-            SqliteAuthority::try_from_config(
+            let mut authority = SqliteAuthority::try_from_config(
                 zone_name,
                 zone_type,
                 is_axfr_allowed,
                 is_dnssec_enabled,
                 Some(zone_dir),
                 config,
-            )
-            .map(Box::new)?
+            )?;
+            authority.set_response_order(response_order);
+
+            Box::new(authority)
         }
         Some(StoreConfig::File(ref config)) => {
             if zone_path.is_some() {
                 warn!("ignoring [[zones.file]] instead using [[zones.stores.zone_file_path]]");
             }
-            FileAuthority::try_from_config(
+            let mut authority = FileAuthority::try_from_config(
                 zone_name,
                 zone_type,
                 is_axfr_allowed,
                 Some(zone_dir),
                 config,
-            )
-            .map(Box::new)?
+            )?;
+            authority.set_response_order(response_order);
+
+            Box::new(authority)
+        }
+        Some(StoreConfig::LoadBalancer(ref config)) => {
+            use futures::future::Executor;
+
+            let (mut authority, bg) = LoadBalancerAuthority::try_from_config(
+                zone_name,
+                zone_type,
+                is_axfr_allowed,
+                Some(zone_dir),
+                config,
+            )?;
+            authority.set_response_order(response_order);
+
+            executor
+                .execute(bg)
+                .expect("failed to background health checks");
+
+            Box::new(authority)
+        }
+        Some(StoreConfig::AutoPtr(ref config)) => {
+            use futures::future::Executor;
+
+            let (authority, bg) = AutoPtrAuthority::try_from_config(
+                zone_name,
+                zone_type,
+                is_axfr_allowed,
+                Some(zone_dir),
+                catalog.clone(),
+                config,
+            )?;
+
+            executor
+                .execute(bg)
+                .expect("failed to background PTR generation");
+
+            Box::new(authority)
+        }
+        Some(StoreConfig::Dhcp(ref config)) => {
+            use futures::future::Executor;
+
+            let (authority, bg) =
+                DhcpAuthority::try_from_config(zone_name, zone_type, Some(zone_dir), config)?;
+
+            executor
+                .execute(bg)
+                .expect("failed to background lease file watch");
+
+            Box::new(authority)
+        }
+        Some(StoreConfig::SynthPtr(ref config)) => {
+            let authority = SynthPtrAuthority::try_from_config(zone_name, zone_type, config)?;
+            Box::new(authority)
         }
         #[cfg(feature = "trust-dns-resolver")]
         Some(StoreConfig::Forward(ref config)) => {
             use futures::future::Executor;
 
-            let (forwarder, bg) = ForwardAuthority::try_from_config(zone_name, zone_type, config)?;
+            let (forwarder, bg) =
+                ForwardAuthority::try_from_config(zone_name.clone(), zone_type, config)?;
+            add_forwarder(health, zone_name, &forwarder);
 
             executor
                 .execute(bg)
@@ -131,6 +220,70 @@ fn load_zone(
 
             Box::new(forwarder)
         }
+        #[cfg(feature = "trust-dns-resolver")]
+        Some(StoreConfig::Blocklist(ref config)) => {
+            use futures::future::Executor;
+
+            let (blocklist, bg) = BlocklistAuthority::try_from_config(
+                zone_name.clone(),
+                zone_type,
+                Some(zone_dir),
+                config,
+            )?;
+            add_forwarder(health, zone_name, blocklist.forwarder());
+
+            executor
+                .execute(bg)
+                .expect("failed to background blocklist");
+
+            Box::new(blocklist)
+        }
+        #[cfg(feature = "trust-dns-resolver")]
+        Some(StoreConfig::LocalData(ref config)) => {
+            use futures::future::Executor;
+
+            let (local_data, bg) =
+                LocalDataAuthority::try_from_config(zone_name.clone(), zone_type, config)?;
+            add_forwarder(health, zone_name, local_data.forwarder());
+
+            executor
+                .execute(bg)
+                .expect("failed to background local data forwarder");
+
+            Box::new(local_data)
+        }
+        #[cfg(feature = "trust-dns-resolver")]
+        Some(StoreConfig::Stub(ref config)) => {
+            use futures::future::Executor;
+
+            let (stub, bg) = StubAuthority::try_from_config(zone_name, zone_type, config)?;
+
+            executor
+                .execute(bg)
+                .expect("failed to background stub resolver");
+
+            Box::new(stub)
+        }
+        #[cfg(feature = "trust-dns-resolver")]
+        Some(StoreConfig::Aname(ref config)) => {
+            use futures::future::Executor;
+
+            let (mut authority, bg) = AnameAuthority::try_from_config(
+                zone_name.clone(),
+                zone_type,
+                is_axfr_allowed,
+                Some(zone_dir),
+                config,
+            )?;
+            authority.set_response_order(response_order);
+            add_forwarder(health, zone_name, authority.forwarder());
+
+            executor
+                .execute(bg)
+                .expect("failed to background ANAME forwarder");
+
+            Box::new(authority)
+        }
         None if zone_config.is_update_allowed() => {
             warn!(
                 "using deprecated SQLite load configuration, please move to [[zones.stores]] form"
@@ -149,29 +302,33 @@ fn load_zone(
                 allow_update: zone_config.is_update_allowed(),
             };
 
-            SqliteAuthority::try_from_config(
+            let mut authority = SqliteAuthority::try_from_config(
                 zone_name,
                 zone_type,
                 is_axfr_allowed,
                 is_dnssec_enabled,
                 Some(zone_dir),
                 &config,
-            )
-            .map(Box::new)?
+            )?;
+            authority.set_response_order(response_order);
+
+            Box::new(authority)
         }
         None => {
             let config = FileConfig {
                 zone_file_path: zone_path
                     .ok_or_else(|| "file is a necessary parameter of zone_config")?,
             };
-            FileAuthority::try_from_config(
+            let mut authority = FileAuthority::try_from_config(
                 zone_name,
                 zone_type,
                 is_axfr_allowed,
                 Some(zone_dir),
                 &config,
-            )
-            .map(Box::new)?
+            )?;
+            authority.set_response_order(response_order);
+
+            Box::new(authority)
         }
     };
 
@@ -213,6 +370,10 @@ fn load_zone(
                 }
             }
 
+            authority
+                .set_signature_jitter(zone_config.get_signature_jitter())
+                .map_err(|e| format!("failed to set the signature jitter: {}", e))?;
+
             info!("signing zone: {}", zone_config.get_zone().unwrap());
             authority.secure_zone().expect("failed to sign zone");
         }
@@ -238,6 +399,118 @@ fn load_zone(
     Ok(authority)
 }
 
+/// Adds the checked upstream name servers of the forwarder to the readiness of the server
+#[cfg(feature = "trust-dns-resolver")]
+fn add_forwarder(health: &Health, zone_name: Name, forwarder: &ForwardAuthority) {
+    if let Some(upstream) = forwarder.upstream_health() {
+        health.add_forwarder(zone_name.into(), Arc::clone(upstream));
+    }
+}
+
+/// The transfers of a secondary zone from its masters, if it has any
+fn zone_transfer(zone_config: &ZoneConfig) -> Option<ZoneTransfer> {
+    if zone_config.get_zone_type() != ZoneType::Slave || zone_config.get_masters().is_empty() {
+        return None;
+    }
+
+    Some(ZoneTransfer::new(
+        zone_config.get_zone().expect("bad zone name"),
+        zone_config.get_masters().to_vec(),
+        zone_config.get_transfer_source(),
+        zone_config.is_axfr_allowed(),
+    ))
+}
+
+/// The SOA expire of a secondary zone, after which its records are stale unless refreshed
+fn secondary_expire(authority: &dyn AuthorityObject) -> Option<Duration> {
+    if authority.zone_type() != ZoneType::Slave {
+        return None;
+    }
+
+    let soa = authority.soa().wait().ok()?;
+    let expire = soa
+        .iter()
+        .filter_map(|record| record.rdata().as_soa())
+        .map(|soa| Duration::from_secs(soa.expire().max(0) as u64))
+        .next();
+    expire
+}
+
+/// Checks the DS records of the parent against the keys of the zone periodically, if configured
+#[cfg(all(feature = "dnssec", feature = "trust-dns-resolver"))]
+fn check_ds(
+    zone_config: &ZoneConfig,
+    executor: &TaskExecutor,
+    catalog: &Catalog,
+) -> Result<(), String> {
+    use futures::future::Executor;
+    use trust_dns_server::authority::{self, DsChecker};
+
+    let config = match zone_config.get_ds_check() {
+        Some(config) => config,
+        None => return Ok(()),
+    };
+    if !zone_config.is_dnssec_enabled() {
+        return Err("ds_check is configured, but the zone is not signed".to_string());
+    }
+
+    let zone_name = zone_config
+        .get_zone()
+        .map_err(|e| format!("bad zone name: {}", e))?;
+    let checker = Arc::new(DsChecker::new(zone_name.clone()));
+    let check = authority::check_ds(checker, catalog.clone(), config)?;
+
+    info!("checking the DS records of: {}", zone_name);
+    executor
+        .execute(check)
+        .expect("failed to background DS check");
+    Ok(())
+}
+
+#[cfg(not(all(feature = "dnssec", feature = "trust-dns-resolver")))]
+fn check_ds(
+    zone_config: &ZoneConfig,
+    _executor: &TaskExecutor,
+    _catalog: &Catalog,
+) -> Result<(), String> {
+    match zone_config.get_ds_check() {
+        Some(_) => Err(
+            "ds_check is configured, but the dnssec and trust-dns-resolver features are not enabled"
+                .to_string(),
+        ),
+        None => Ok(()),
+    }
+}
+
+/// Loads the GeoIP selector of the answers of the zone, if configured
+#[cfg(feature = "geoip")]
+fn load_answer_selector(
+    zone_dir: &Path,
+    zone_config: &ZoneConfig,
+) -> Result<Option<Arc<dyn AnswerSelector>>, String> {
+    use trust_dns_server::authority::geoip::GeoIpSelector;
+
+    match zone_config.geoip {
+        Some(ref config) => {
+            let selector = GeoIpSelector::try_from_config(Some(zone_dir), config)?;
+            info!("GeoIP selection enabled: {}", zone_config.zone);
+            Ok(Some(Arc::new(selector)))
+        }
+        None => Ok(None),
+    }
+}
+
+#[cfg(not(feature = "geoip"))]
+fn load_answer_selector(
+    _zone_dir: &Path,
+    zone_config: &ZoneConfig,
+) -> Result<Option<Arc<dyn AnswerSelector>>, String> {
+    match zone_config.geoip {
+        Some(_) => Err("geoip is configured, but the geoip feature is not enabled".to_string()),
+        None => Ok(None),
+    }
+}
+
 // argument name constants for the CLI options
 const QUIET_ARG: &str = "quiet";
 const DEBUG_ARG: &str = "debug";
@@ -367,49 +640,98 @@ pub fn main() {
     let mut io_loop = Runtime::new().expect("error when creating tokio Runtime");
     let executor = io_loop.executor();
     let mut catalog: Catalog = Catalog::new();
+    let health = Arc::new(Health::new());
+    let mut transfers = Vec::new();
     // configure our server based on the config_path
     for zone in config.get_zones() {
         let zone_name = zone
             .get_zone()
             .unwrap_or_else(|_| panic!("bad zone name in {:?}", config_path));
 
-        match load_zone(&zone_dir, zone, &executor) {
-            Ok(authority) => catalog.upsert(zone_name.into(), authority),
+        if let Some(transfer) = zone_transfer(zone) {
+            transfers.push(transfer);
+
+            // without a file the zone is served once it is transferred from its masters
+            if zone.file.is_none() && zone.stores.is_none() {
+                health.zone_loading(zone_name.clone().into());
+                continue;
+            }
+        }
+
+        match load_zone(&zone_dir, zone, &executor, &health, &catalog) {
+            Ok(authority) => {
+                health.zone_loaded(zone_name.clone().into(), secondary_expire(&*authority));
+                catalog.upsert(zone_name.clone().into(), authority)
+            }
+            Err(error) => panic!("could not load zone {}: {}", zone_name, error),
+        }
+
+        if let Err(error) = check_ds(zone, &executor, &catalog) {
+            panic!("could not check the DS of zone {}: {}", zone_name, error);
+        }
+
+        if let Some(minimal_responses) = zone.get_minimal_responses() {
+            catalog.set_zone_minimal_responses(zone_name.clone().into(), minimal_responses);
+        }
+
+        match load_answer_selector(&zone_dir, zone) {
+            Ok(Some(selector)) => catalog.set_answer_selector(zone_name.into(), selector),
+            Ok(None) => (),
             Err(error) => panic!("could not load zone {}: {}", zone_name, error),
         }
     }
+    catalog.set_minimal_responses(config.get_minimal_responses());
 
-    // TODO: support all the IPs asked to listen on...
-    // TODO:, there should be the option to listen on any port, IP and protocol option...
-    let v4addr = config.get_listen_addrs_ipv4();
-    let v6addr = config.get_listen_addrs_ipv6();
-    let mut listen_addrs: Vec<IpAddr> = v4addr
-        .into_iter()
-        .map(IpAddr::V4)
-        .chain(v6addr.into_iter().map(IpAddr::V6))
-        .collect();
+    let listen = config.get_listen();
     let listen_port: u16 = args.flag_port.unwrap_or_else(|| config.get_listen_port());
     let tcp_request_timeout = config.get_tcp_request_timeout();
 
-    if listen_addrs.is_empty() {
-        listen_addrs.push(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)));
-    }
-    let sockaddrs: Vec<SocketAddr> = listen_addrs
-        .iter()
-        .flat_map(|x| (*x, listen_port).to_socket_addrs().unwrap())
-        .collect();
-    let udp_sockets: Vec<UdpSocket> = sockaddrs
+    // the port of an address, e.g. `[::]:5353`, applies unless overridden on the command line
+    let port = |l: &ListenConfig| {
+        args.flag_port
+            .or_else(|| l.get_port())
+            .unwrap_or(listen_port)
+    };
+    let udp_sockets: Vec<UdpSocket> = listen
         .iter()
-        .map(|x| UdpSocket::bind(x).unwrap_or_else(|_| panic!("could not bind to udp: {}", x)))
+        .filter(|l| l.serves(Protocol::Udp))
+        .map(|l| bind_udp(l, port(l)))
         .collect();
-    let tcp_listeners: Vec<TcpListener> = sockaddrs
+    let tcp_listeners: Vec<TcpListener> = listen
         .iter()
-        .map(|x| TcpListener::bind(x).unwrap_or_else(|_| panic!("could not bind to tcp: {}", x)))
+        .filter(|l| l.serves(Protocol::Tcp))
+        .map(|l| bind_tcp(l, port(l), Protocol::Tcp))
         .collect();
 
+    if let Some(update_audit) = config.get_update_audit() {
+        if let Err(error) = add_update_audit_sinks(&zone_dir, update_audit, &catalog) {
+            panic!("could not audit updates: {}", error);
+        }
+    }
+
+    if let Some(cookies) = config.get_cookies() {
+        match cookie_secrets(&zone_dir, cookies) {
+            Ok(secrets) => catalog.set_cookie_secrets(Arc::new(secrets)),
+            Err(error) => panic!("could not load the cookie secrets: {}", error),
+        }
+    }
+
     // now, run the server, based on the config
-    #[cfg_attr(not(feature = "dns-over-tls"), allow(unused_mut))]
+    catalog.add_zone_listener(health.clone());
+    for transfer in transfers {
+        info!("transferring zone {} from its masters", transfer.origin());
+        executor.spawn(authority::refresh_secondary(transfer, catalog.clone()));
+    }
     let mut server = ServerFuture::new(catalog);
+    if let Some(request_deadline) = config.get_request_deadline() {
+        server.set_request_deadline(request_deadline);
+    }
+    server.set_protocol_policy(config.get_protocol_policy());
+    server.set_tcp_options(config.get_tcp_options());
+    server.set_udp_offload(config.get_udp_offload());
+    if config.get_health_endpoints() {
+        server.set_health(health);
+    }
 
     let server_future: Box<Future<Item = (), Error = ()> + Send> =
         Box::new(future::lazy(move || {
@@ -441,7 +763,7 @@ pub fn main() {
                     &config,
                     _tls_cert_config,
                     &zone_dir,
-                    &listen_addrs,
+                    &listen,
                 );
 
                 // setup HTTPS listeners
@@ -452,7 +774,7 @@ pub fn main() {
                     &config,
                     _tls_cert_config,
                     &zone_dir,
-                    &listen_addrs,
+                    &listen,
                 );
             }
 
@@ -480,6 +802,61 @@ pub fn main() {
     info!("Trust-DNS {} stopping", trust_dns::version());
 }
 
+/// Registers the sinks of the audit of the dynamic updates on the catalog
+fn add_update_audit_sinks(
+    zone_dir: &Path,
+    update_audit: &UpdateAuditConfig,
+    catalog: &Catalog,
+) -> Result<(), String> {
+    if let Some(file) = update_audit.get_file() {
+        let path = zone_dir.join(file);
+        let sink = FileAuditSink::open(&path)
+            .map_err(|e| format!("failed to open {}: {}", path.display(), e))?;
+
+        info!("auditing updates to: {}", path.display());
+        catalog.add_update_audit_sink(Arc::new(sink));
+    }
+
+    if update_audit.get_syslog() {
+        #[cfg(unix)]
+        {
+            let sink = SyslogAuditSink::connect()
+                .map_err(|e| format!("failed to connect to syslog: {}", e))?;
+
+            info!("auditing updates to syslog");
+            catalog.add_update_audit_sink(Arc::new(sink));
+        }
+
+        #[cfg(not(unix))]
+        return Err("syslog is only supported on unix".to_string());
+    }
+
+    Ok(())
+}
+
+fn cookie_secrets(zone_dir: &Path, cookies: &CookieConfig) -> Result<CookieSecrets, String> {
+    let secret = match cookies.get_secret() {
+        Some(secret) => Some(
+            CookieSecrets::parse_secret(secret)
+                .map_err(|e| format!("invalid cookie secret: {}", e))?,
+        ),
+        None => None,
+    };
+
+    let mut secrets = match cookies.get_secret_file() {
+        Some(file) => {
+            let path = zone_dir.join(file);
+            info!("cookie secrets persisted in: {}", path.display());
+            CookieSecrets::open(&path, secret)
+                .map_err(|e| format!("failed to open {}: {}", path.display(), e))?
+        }
+        None => secret.map_or_else(CookieSecrets::random, CookieSecrets::new),
+    };
+
+    secrets.set_rotation(cookies.get_rotation_interval(), cookies.get_overlap());
+    Ok(secrets)
+}
+
 #[cfg(feature = "dns-over-tls")]
 fn config_tls(
     args: &Args,
@@ -487,18 +864,15 @@ fn config_tls(
     config: &Config,
     tls_cert_config: &TlsCertConfig,
     zone_dir: &Path,
-    listen_addrs: &[IpAddr],
+    listen: &[ListenConfig],
 ) {
     let tls_listen_port: u16 = args
         .flag_tls_port
         .unwrap_or_else(|| config.get_tls_listen_port());
-    let tls_sockaddrs: Vec<SocketAddr> = listen_addrs
+    let tls_listeners: Vec<TcpListener> = listen
         .iter()
-        .flat_map(|x| (*x, tls_listen_port).to_socket_addrs().unwrap())
-        .collect();
-    let tls_listeners: Vec<TcpListener> = tls_sockaddrs
-        .iter()
-        .map(|x| TcpListener::bind(x).unwrap_or_else(|_| panic!("could not bind to tls: {}", x)))
+        .filter(|l| l.serves(Protocol::Tls))
+        .map(|l| bind_tcp(l, tls_listen_port, Protocol::Tls))
         .collect();
     if tls_listeners.is_empty() {
         warn!("a tls certificate was specified, but no TLS addresses configured to listen on");
@@ -527,18 +901,15 @@ fn config_https(
     config: &Config,
     tls_cert_config: &TlsCertConfig,
     zone_dir: &Path,
-    listen_addrs: &[IpAddr],
+    listen: &[ListenConfig],
 ) {
     let https_listen_port: u16 = args
         .flag_https_port
         .unwrap_or_else(|| config.get_https_listen_port());
-    let https_sockaddrs: Vec<SocketAddr> = listen_addrs
-        .iter()
-        .flat_map(|x| (*x, https_listen_port).to_socket_addrs().unwrap())
-        .collect();
-    let https_listeners: Vec<TcpListener> = https_sockaddrs
+    let https_listeners: Vec<TcpListener> = listen
         .iter()
-        .map(|x| TcpListener::bind(x).unwrap_or_else(|_| panic!("could not bind to tls: {}", x)))
+        .filter(|l| l.serves(Protocol::Https))
+        .map(|l| bind_tcp(l, https_listen_port, Protocol::Https))
         .collect();
     if https_listeners.is_empty() {
         warn!("a tls certificate was specified, but no HTTPS addresses configured to listen on");
@@ -564,6 +935,84 @@ fn config_https(
             )
             .expect("could not register TLS listener");
     }
+
+    // the reverse proxy sends the name of the certificate as the authority of the requests
+    if let Some(http_listen_port) = config.get_http_listen_port() {
+        let http_listeners: Vec<TcpListener> = listen
+            .iter()
+            .filter(|l| l.serves(Protocol::Https))
+            .map(|l| bind_tcp(l, http_listen_port, Protocol::Https))
+            .collect();
+
+        for http_listener in http_listeners {
+            info!("listening for HTTP on {:?}", http_listener);
+            server
+                .register_http_listener(
+                    http_listener,
+                    config.get_tcp_request_timeout(),
+                    tls_cert_config.get_endpoint_name().to_string(),
+                )
+                .expect("could not register HTTP listener");
+        }
+    }
+}
+
+/// Creates a socket for the address, only accepting IPv6 connections if `v6only`
+fn new_socket(
+    addr: &SocketAddr,
+    ty: Type,
+    protocol: socket2::Protocol,
+    v6only: Option<bool>,
+) -> io::Result<Socket> {
+    let domain = match *addr {
+        SocketAddr::V4(..) => Domain::ipv4(),
+        SocketAddr::V6(..) => Domain::ipv6(),
+    };
+
+    let socket = Socket::new(domain, ty, Some(protocol))?;
+    if let Some(v6only) = v6only {
+        socket.set_only_v6(v6only)?;
+    }
+
+    Ok(socket)
+}
+
+/// Binds a UDP socket on the address, with its V6ONLY option
+fn bind_udp(listen: &ListenConfig, port: u16) -> UdpSocket {
+    let addr = SocketAddr::new(listen.get_ip(), port);
+    let bind = || -> io::Result<UdpSocket> {
+        let socket = new_socket(
+            &addr,
+            Type::dgram(),
+            socket2::Protocol::udp(),
+            listen.get_v6only(),
+        )?;
+        socket.bind(&addr.into())?;
+        UdpSocket::from_std(socket.into_udp_socket(), &Handle::default())
+    };
+
+    bind().unwrap_or_else(|e| panic!("could not bind to udp: {}: {}", addr, e))
+}
+
+/// Binds a TCP listener for the protocol on the address, with its V6ONLY option
+fn bind_tcp(listen: &ListenConfig, port: u16, protocol: Protocol) -> TcpListener {
+    let addr = SocketAddr::new(listen.get_ip(), port);
+    let bind = || -> io::Result<TcpListener> {
+        let socket = new_socket(
+            &addr,
+            Type::stream(),
+            socket2::Protocol::tcp(),
+            listen.get_v6only(),
+        )?;
+        // as std, for restarts not to wait for the connections in TIME_WAIT
+        #[cfg(unix)]
+        socket.set_reuse_address(true)?;
+        socket.bind(&addr.into())?;
+        socket.listen(128)?;
+        TcpListener::from_std(socket.into_tcp_listener(), &Handle::default())
+    };
+
+    bind().unwrap_or_else(|e| panic!("could not bind to {}: {}: {}", protocol, addr, e))
 }
 
 fn banner() {
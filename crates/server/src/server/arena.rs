@@ -0,0 +1,428 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! A bump allocator for the transient allocations of each request
+//!
+//! The `RequestArena` must be registered as the global allocator of the binary, as `named` does
+//!  with the `request-arena` feature:
+//!
+//! ```rust,ignore
+//! #[global_allocator]
+//! static ALLOCATOR: RequestArena = RequestArena;
+//! ```
+//!
+//! The `ServerFuture` enters an `ArenaScope` while it decodes, handles and answers each request,
+//!  the allocations of the thread in the scope are bumped from a chunk owned by the thread, and
+//!  their deallocations only count down the live allocations of the chunk. The chunk is reset
+//!  when the next request enters a scope, once all its allocations were freed. Allocations which
+//!  outlive their request, e.g. the records added by an update, keep the chunk from being reset,
+//!  it's then left to be reclaimed once they are freed. All other allocations, and those which
+//!  don't fit in the chunk, are made by the system allocator. The chunk of a thread is released
+//!  when the thread exits, to be claimed by another thread once its allocations are freed.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::marker::PhantomData;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+
+/// The size of the chunk of each thread
+const CHUNK_SIZE: usize = 64 * 1024;
+/// The chunks shared by all the threads, the threads without a chunk use the system allocator
+const CHUNKS: usize = 64;
+/// The alignment of the chunks, larger alignments are left to the system allocator
+const CHUNK_ALIGN: usize = 4096;
+
+struct Chunk {
+    /// A thread allocates from the chunk
+    claimed: AtomicBool,
+    /// The allocations of the chunk which were not freed yet
+    live: AtomicUsize,
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const FREE_CHUNK: Chunk = Chunk {
+    claimed: AtomicBool::new(false),
+    live: AtomicUsize::new(0),
+};
+
+static CHUNK_STATES: [Chunk; CHUNKS] = [FREE_CHUNK; CHUNKS];
+/// The memory of all the chunks, allocated on the first request
+static REGION: AtomicPtr<u8> = AtomicPtr::new(ptr::null_mut());
+
+/// The chunk of the thread and the scopes it entered
+#[derive(Clone, Copy)]
+struct Local {
+    chunk: Option<usize>,
+    offset: usize,
+    depth: usize,
+    // the thread is exiting, its chunk was released
+    released: bool,
+    hits: usize,
+    misses: usize,
+}
+
+thread_local! {
+    // without a destructor, so that it's usable by the allocator until the thread exits
+    static LOCAL: Cell<Local> = const {
+        Cell::new(Local {
+            chunk: None,
+            offset: 0,
+            depth: 0,
+            released: false,
+            hits: 0,
+            misses: 0,
+        })
+    };
+    // registered by the first scope of the thread, not by the allocator
+    static RELEASE: ReleaseOnExit = const { ReleaseOnExit };
+}
+
+/// Releases the chunk of the thread when it exits
+struct ReleaseOnExit;
+
+impl Drop for ReleaseOnExit {
+    fn drop(&mut self) {
+        let _ = LOCAL.try_with(|local| {
+            let mut state = local.get();
+            if let Some(chunk) = state.chunk.take() {
+                CHUNK_STATES[chunk].claimed.store(false, Ordering::Release);
+            }
+            state.released = true;
+            local.set(state);
+        });
+    }
+}
+
+fn region_layout() -> Layout {
+    Layout::from_size_align(CHUNK_SIZE * CHUNKS, CHUNK_ALIGN).expect("invalid arena layout")
+}
+
+/// The memory of the chunks, null if it couldn't be allocated
+fn region() -> *mut u8 {
+    let region = REGION.load(Ordering::Acquire);
+    if !region.is_null() {
+        return region;
+    }
+
+    let new_region = unsafe { System.alloc(region_layout()) };
+    if new_region.is_null() {
+        return new_region;
+    }
+    match REGION.compare_exchange(
+        ptr::null_mut(),
+        new_region,
+        Ordering::AcqRel,
+        Ordering::Acquire,
+    ) {
+        Ok(_) => new_region,
+        Err(region) => {
+            unsafe { System.dealloc(new_region, region_layout()) };
+            region
+        }
+    }
+}
+
+/// The chunk and the offset in it of the memory, None if it's not from the arena
+fn chunk_of(ptr: *mut u8) -> Option<(usize, usize)> {
+    let region = REGION.load(Ordering::Acquire) as usize;
+    let ptr = ptr as usize;
+    if region == 0 || ptr < region || ptr >= region + CHUNK_SIZE * CHUNKS {
+        return None;
+    }
+
+    let offset = ptr - region;
+    Some((offset / CHUNK_SIZE, offset % CHUNK_SIZE))
+}
+
+/// Claims a chunk without live allocations, which isn't used by another thread
+fn claim_chunk() -> Option<usize> {
+    for (index, chunk) in CHUNK_STATES.iter().enumerate() {
+        if chunk.claimed.load(Ordering::Relaxed)
+            || chunk
+                .claimed
+                .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_err()
+        {
+            continue;
+        }
+
+        // the allocations of a previous thread may still be live
+        if chunk.live.load(Ordering::Acquire) == 0 {
+            return Some(index);
+        }
+        chunk.claimed.store(false, Ordering::Release);
+    }
+
+    None
+}
+
+/// Allocates from the chunk of the thread, None outside of a scope or if it doesn't fit
+fn bump(layout: Layout) -> Option<*mut u8> {
+    LOCAL
+        .try_with(|local| {
+            let mut state = local.get();
+            if state.depth == 0 {
+                return None;
+            }
+
+            let ptr = bump_chunk(&mut state, layout);
+            if ptr.is_some() {
+                state.hits += 1;
+            } else {
+                state.misses += 1;
+            }
+            local.set(state);
+            ptr
+        })
+        .ok()
+        .and_then(|ptr| ptr)
+}
+
+fn bump_chunk(state: &mut Local, layout: Layout) -> Option<*mut u8> {
+    if layout.align() > CHUNK_ALIGN || state.released {
+        return None;
+    }
+
+    let region = region();
+    if region.is_null() {
+        return None;
+    }
+    let chunk = match state.chunk {
+        Some(chunk) => chunk,
+        None => {
+            let chunk = claim_chunk()?;
+            state.chunk = Some(chunk);
+            state.offset = 0;
+            chunk
+        }
+    };
+
+    // the chunks are aligned, the offset is aligned as the address
+    let start = (state.offset + layout.align() - 1) & !(layout.align() - 1);
+    let end = start.checked_add(layout.size())?;
+    if end > CHUNK_SIZE {
+        return None;
+    }
+
+    CHUNK_STATES[chunk].live.fetch_add(1, Ordering::Relaxed);
+    state.offset = end;
+    Some(unsafe { region.add(chunk * CHUNK_SIZE + start) })
+}
+
+/// Resizes the last allocation of the chunk of the thread in place, returns false otherwise
+fn resize_last(chunk: usize, offset: usize, size: usize, new_size: usize) -> bool {
+    LOCAL
+        .try_with(|local| {
+            let mut state = local.get();
+            if state.chunk != Some(chunk)
+                || offset + size != state.offset
+                || offset + new_size > CHUNK_SIZE
+            {
+                return false;
+            }
+
+            state.offset = offset + new_size;
+            local.set(state);
+            true
+        })
+        .unwrap_or(false)
+}
+
+/// The global allocator which allocates from the chunk of the thread in an `ArenaScope`
+///
+/// Outside of a scope, it's the system allocator.
+pub struct RequestArena;
+
+unsafe impl GlobalAlloc for RequestArena {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        match bump(layout) {
+            Some(ptr) => ptr,
+            None => System.alloc(layout),
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        match chunk_of(ptr) {
+            Some((chunk, _)) => {
+                CHUNK_STATES[chunk].live.fetch_sub(1, Ordering::Release);
+            }
+            None => System.dealloc(ptr, layout),
+        }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let (chunk, offset) = match chunk_of(ptr) {
+            Some(position) => position,
+            None => return System.realloc(ptr, layout, new_size),
+        };
+
+        // e.g. a growing buffer, the last allocation of the thread is extended
+        if resize_last(chunk, offset, layout.size(), new_size) {
+            return ptr;
+        }
+
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+        let new_ptr = self.alloc(new_layout);
+        if !new_ptr.is_null() {
+            ptr::copy_nonoverlapping(ptr, new_ptr, layout.size().min(new_size));
+            self.dealloc(ptr, layout);
+        }
+        new_ptr
+    }
+}
+
+/// The allocations of the thread are made from its chunk, see `RequestArena`, until it's dropped
+///
+/// Scopes may be nested, the chunk is only reset when the outermost scope is entered.
+pub struct ArenaScope {
+    // the scope is of the thread
+    _thread: PhantomData<*const ()>,
+}
+
+impl ArenaScope {
+    /// Enters the scope of a request
+    pub fn enter() -> Self {
+        // the chunk claimed in the scope is released when the thread exits
+        let _ = RELEASE.try_with(|_| ());
+        let _ = LOCAL.try_with(|local| {
+            let mut state = local.get();
+            if let (0, Some(chunk)) = (state.depth, state.chunk) {
+                let chunk_state = &CHUNK_STATES[chunk];
+                if chunk_state.live.load(Ordering::Acquire) == 0 {
+                    state.offset = 0;
+                } else if state.offset > CHUNK_SIZE / 2 {
+                    // allocations outlived their requests, the chunk is reclaimed once they're
+                    //  freed, another chunk is claimed for the next allocation
+                    chunk_state.claimed.store(false, Ordering::Release);
+                    state.chunk = None;
+                }
+            }
+
+            state.depth += 1;
+            local.set(state);
+        });
+
+        ArenaScope {
+            _thread: PhantomData,
+        }
+    }
+}
+
+impl Drop for ArenaScope {
+    fn drop(&mut self) {
+        let _ = LOCAL.try_with(|local| {
+            let mut state = local.get();
+            state.depth = state.depth.saturating_sub(1);
+            local.set(state);
+        });
+    }
+}
+
+/// The allocations made in the scopes of the thread, see `ArenaStats::of_thread`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ArenaStats {
+    /// The allocations made from the chunk of the thread
+    pub hits: usize,
+    /// The allocations left to the system allocator, e.g. those which didn't fit in the chunk
+    pub misses: usize,
+}
+
+impl ArenaStats {
+    /// The allocations made in the scopes of the current thread since it started
+    pub fn of_thread() -> Self {
+        LOCAL
+            .try_with(|local| {
+                let state = local.get();
+                ArenaStats {
+                    hits: state.hits,
+                    misses: state.misses,
+                }
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    fn layout(size: usize) -> Layout {
+        Layout::from_size_align(size, 8).unwrap()
+    }
+
+    #[test]
+    fn test_arena_is_reset_when_freed() {
+        unsafe {
+            let outside = RequestArena.alloc(layout(16));
+            assert!(chunk_of(outside).is_none());
+            RequestArena.dealloc(outside, layout(16));
+
+            let scope = ArenaScope::enter();
+            let first = RequestArena.alloc(layout(16));
+            assert!(chunk_of(first).is_some());
+            let second = RequestArena.alloc(layout(16));
+            assert_eq!(second as usize, first as usize + 16);
+
+            // the last allocation grows in place
+            let second = RequestArena.realloc(second, layout(16), 64);
+            assert_eq!(second as usize, first as usize + 16);
+            // allocations which don't fit are made by the system allocator
+            let large = RequestArena.alloc(layout(CHUNK_SIZE));
+            assert!(chunk_of(large).is_none());
+            RequestArena.dealloc(large, layout(CHUNK_SIZE));
+            drop(scope);
+
+            // the first allocation outlives the request, the chunk is not reset
+            RequestArena.dealloc(second, layout(64));
+            let scope = ArenaScope::enter();
+            let third = RequestArena.alloc(layout(16));
+            assert_eq!(third as usize, first as usize + 80);
+            RequestArena.dealloc(third, layout(16));
+            drop(scope);
+
+            RequestArena.dealloc(first, layout(16));
+            let _scope = ArenaScope::enter();
+            let reused = RequestArena.alloc(layout(16));
+            assert_eq!(reused, first);
+            RequestArena.dealloc(reused, layout(16));
+        }
+    }
+
+    #[test]
+    fn test_chunk_is_released_when_its_thread_exits() {
+        // the allocation outlives its thread, and pins the chunk
+        let pinned = thread::spawn(|| unsafe {
+            let _scope = ArenaScope::enter();
+            let pinned = RequestArena.alloc(layout(16));
+            assert_eq!(ArenaStats::of_thread(), ArenaStats { hits: 1, misses: 0 });
+            pinned as usize
+        })
+        .join()
+        .unwrap() as *mut u8;
+
+        let (chunk, _) = chunk_of(pinned).expect("not allocated from the arena");
+        assert!(!CHUNK_STATES[chunk].claimed.load(Ordering::Acquire));
+        assert_eq!(CHUNK_STATES[chunk].live.load(Ordering::Acquire), 1);
+
+        // the chunk is not claimed by another thread while it's pinned
+        thread::spawn(move || unsafe {
+            let _scope = ArenaScope::enter();
+            let ptr = RequestArena.alloc(layout(16));
+            assert_ne!(chunk_of(ptr).map(|(other, _)| other), Some(chunk));
+            RequestArena.dealloc(ptr, layout(16));
+        })
+        .join()
+        .unwrap();
+
+        // once freed, the chunk can be claimed again
+        unsafe { RequestArena.dealloc(pinned, layout(16)) };
+        assert_eq!(CHUNK_STATES[chunk].live.load(Ordering::Acquire), 0);
+    }
+}
@@ -0,0 +1,487 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Server cookies, RFC 7873, generated and verified with rotating secrets
+
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rand;
+use toml;
+
+use trust_dns::rr::rdata::opt::Cookie;
+
+/// The version of the server cookies, RFC 9018
+const VERSION: u8 = 1;
+
+/// Server cookies older than this are not valid
+const MAX_AGE: i64 = 3600;
+
+/// Server cookies from further in the future than this are not valid, for clock skew
+const MAX_SKEW: i64 = 300;
+
+/// Valid server cookies younger than this are returned to the client unchanged
+const REFRESH_AGE: i64 = 1800;
+
+#[derive(Deserialize, Serialize)]
+struct SecretState {
+    /// When the current secret replaced the previous one, in seconds since the unix epoch
+    rotated: u64,
+    /// The secret of the new cookies, in hex
+    current: String,
+    /// The secret replaced by the current one, in hex
+    previous: Option<String>,
+}
+
+struct Secrets {
+    rotated: u64,
+    current: [u8; 16],
+    previous: Option<[u8; 16]>,
+}
+
+/// The secrets with which the server cookies of the responses are generated and verified
+///
+/// Server cookies are interoperable, RFC 9018, servers sharing the secrets accept each other's
+///  cookies. The secret is replaced every rotation interval, cookies of the previous secret remain
+///  valid during the overlap after a rotation. When opened from a file, the secrets and the time
+///  of the last rotation are written back to the file at each rotation, so that restarts of the
+///  server don't invalidate the cookies of the clients.
+pub struct CookieSecrets {
+    path: Option<PathBuf>,
+    rotation_interval: Duration,
+    overlap: Duration,
+    secrets: RwLock<Secrets>,
+}
+
+impl CookieSecrets {
+    /// The secret is replaced daily by default
+    pub const DEFAULT_ROTATION_INTERVAL: Duration = Duration::from_secs(86_400);
+
+    /// By default the previous secret is valid for an hour, the age of the oldest valid cookie
+    pub const DEFAULT_OVERLAP: Duration = Duration::from_secs(MAX_AGE as u64);
+
+    /// Creates secrets starting from the secret, which are not persisted
+    pub fn new(secret: [u8; 16]) -> Self {
+        CookieSecrets {
+            path: None,
+            rotation_interval: Self::DEFAULT_ROTATION_INTERVAL,
+            overlap: Self::DEFAULT_OVERLAP,
+            secrets: RwLock::new(Secrets {
+                rotated: unix_now(),
+                current: secret,
+                previous: None,
+            }),
+        }
+    }
+
+    /// Creates secrets starting from a random secret, which are not persisted
+    pub fn random() -> Self {
+        Self::new(rand::random())
+    }
+
+    /// Opens the secrets persisted in the file
+    ///
+    /// If the file doesn't exist it is created, starting from `secret`, or a random secret if
+    ///  None.
+    pub fn open<P: AsRef<Path>>(path: P, secret: Option<[u8; 16]>) -> io::Result<Self> {
+        let path = path.as_ref();
+
+        let secrets = match File::open(path) {
+            Ok(mut file) => {
+                let mut state = String::new();
+                file.read_to_string(&mut state)?;
+                let state: SecretState = toml::from_str(&state).map_err(invalid_data)?;
+
+                Secrets {
+                    rotated: state.rotated,
+                    current: Self::parse_secret(&state.current)?,
+                    previous: match state.previous {
+                        Some(ref previous) => Some(Self::parse_secret(previous)?),
+                        None => None,
+                    },
+                }
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                let secrets = Secrets {
+                    rotated: unix_now(),
+                    current: secret.unwrap_or_else(rand::random),
+                    previous: None,
+                };
+                persist(path, &secrets)?;
+                secrets
+            }
+            Err(e) => return Err(e),
+        };
+
+        Ok(CookieSecrets {
+            path: Some(path.to_path_buf()),
+            rotation_interval: Self::DEFAULT_ROTATION_INTERVAL,
+            overlap: Self::DEFAULT_OVERLAP,
+            secrets: RwLock::new(secrets),
+        })
+    }
+
+    /// Sets the interval at which the secret is replaced, and the time after a rotation during
+    ///  which the cookies of the previous secret are still valid
+    pub fn set_rotation(&mut self, rotation_interval: Duration, overlap: Duration) {
+        self.rotation_interval = rotation_interval;
+        self.overlap = overlap;
+    }
+
+    /// Parses a secret of 16 bytes in hex, e.g. from the configuration
+    pub fn parse_secret(hex: &str) -> io::Result<[u8; 16]> {
+        let bytes = from_hex(hex)?;
+        if bytes.len() != 16 {
+            return Err(invalid_data(format!(
+                "cookie secret of {} bytes, not 16",
+                bytes.len()
+            )));
+        }
+
+        let mut secret = [0; 16];
+        secret.copy_from_slice(&bytes);
+        Ok(secret)
+    }
+
+    /// Replaces the current secret with a random one, keeping it as the previous secret
+    pub fn rotate(&self) -> io::Result<()> {
+        let mut secrets = self.secrets.write().expect("cookie secrets poisoned");
+        self.rotate_secrets(&mut secrets, unix_now())
+    }
+
+    /// The server cookie of the response to a request with the cookie, from the client address
+    ///
+    /// A valid server cookie of the request is returned unchanged if it's less than half an hour
+    ///  old, otherwise a new one is generated with the current secret.
+    pub fn response_cookie(&self, cookie: &Cookie, client: IpAddr) -> Cookie {
+        self.response_cookie_at(cookie, client, unix_now())
+    }
+
+    /// True if the server cookie was generated for the client cookie and address, with the
+    ///  current secret or the previous one during the overlap, within the last hour
+    pub fn is_valid(&self, cookie: &Cookie, client: IpAddr) -> bool {
+        self.is_valid_at(cookie, client, unix_now())
+    }
+
+    fn response_cookie_at(&self, cookie: &Cookie, client: IpAddr, now: u64) -> Cookie {
+        if self.is_valid_at(cookie, client, now) && age(cookie, now) < REFRESH_AGE {
+            return cookie.clone();
+        }
+
+        let rotated = self
+            .secrets
+            .read()
+            .expect("cookie secrets poisoned")
+            .rotated;
+        if self.rotation_due(rotated, now) {
+            let mut secrets = self.secrets.write().expect("cookie secrets poisoned");
+            // another request may have rotated the secret in the meantime
+            if self.rotation_due(secrets.rotated, now) {
+                if let Err(e) = self.rotate_secrets(&mut secrets, now) {
+                    warn!("failed to persist the rotated cookie secret: {}", e);
+                }
+            }
+        }
+
+        let secret = self
+            .secrets
+            .read()
+            .expect("cookie secrets poisoned")
+            .current;
+        let server = server_cookie(cookie.client(), now as u32, client, &secret);
+        Cookie::new(*cookie.client(), server).expect("server cookies are 16 bytes")
+    }
+
+    fn is_valid_at(&self, cookie: &Cookie, client: IpAddr, now: u64) -> bool {
+        let server = cookie.server();
+        if server.len() != 16 || server[0] != VERSION {
+            return false;
+        }
+
+        let age = age(cookie, now);
+        if !(-MAX_SKEW..=MAX_AGE).contains(&age) {
+            return false;
+        }
+        let timestamp = (now as u32).wrapping_sub(age as u32);
+
+        let secrets = self.secrets.read().expect("cookie secrets poisoned");
+        let previous = secrets
+            .previous
+            .filter(|_| now < secrets.rotated + self.overlap.as_secs());
+
+        Some(secrets.current)
+            .into_iter()
+            .chain(previous)
+            .any(|secret| server_cookie(cookie.client(), timestamp, client, &secret) == server)
+    }
+
+    fn rotation_due(&self, rotated: u64, now: u64) -> bool {
+        now >= rotated + self.rotation_interval.as_secs()
+    }
+
+    fn rotate_secrets(&self, secrets: &mut Secrets, now: u64) -> io::Result<()> {
+        info!("rotating the cookie secret");
+        secrets.previous = Some(secrets.current);
+        secrets.current = rand::random();
+        secrets.rotated = now;
+
+        match self.path {
+            Some(ref path) => persist(path, secrets),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The age in seconds of a server cookie of 16 bytes, negative if it is from the future
+///
+/// The timestamp is a serial number, RFC 1982, it wraps around in 2106.
+fn age(cookie: &Cookie, now: u64) -> i64 {
+    let server = cookie.server();
+    let timestamp = u32::from(server[4]) << 24
+        | u32::from(server[5]) << 16
+        | u32::from(server[6]) << 8
+        | u32::from(server[7]);
+
+    i64::from((now as u32).wrapping_sub(timestamp) as i32)
+}
+
+/// Version, reserved, timestamp and the SipHash-2-4 of the client cookie, the first 8 bytes and
+///  the client address, RFC 9018 section 4
+fn server_cookie(
+    client_cookie: &[u8; 8],
+    timestamp: u32,
+    client: IpAddr,
+    secret: &[u8; 16],
+) -> Vec<u8> {
+    let mut server = Vec::with_capacity(16);
+    server.push(VERSION);
+    server.extend_from_slice(&[0; 3]);
+    server.extend_from_slice(&[
+        (timestamp >> 24) as u8,
+        (timestamp >> 16) as u8,
+        (timestamp >> 8) as u8,
+        timestamp as u8,
+    ]);
+
+    let mut message = Vec::with_capacity(8 + 8 + 16);
+    message.extend_from_slice(client_cookie);
+    message.extend_from_slice(&server);
+    match client {
+        IpAddr::V4(client) => message.extend_from_slice(&client.octets()),
+        IpAddr::V6(client) => message.extend_from_slice(&client.octets()),
+    }
+
+    let hash = siphash24(secret, &message);
+    for i in 0..8 {
+        server.push((hash >> (8 * i)) as u8);
+    }
+    server
+}
+
+/// SipHash-2-4 of the message with the key
+fn siphash24(key: &[u8; 16], message: &[u8]) -> u64 {
+    fn read_u64(bytes: &[u8]) -> u64 {
+        bytes
+            .iter()
+            .enumerate()
+            .fold(0, |word, (i, byte)| word | u64::from(*byte) << (8 * i))
+    }
+
+    fn round(v: &mut [u64; 4]) {
+        v[0] = v[0].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(13) ^ v[0];
+        v[0] = v[0].rotate_left(32);
+        v[2] = v[2].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(16) ^ v[2];
+        v[0] = v[0].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(21) ^ v[0];
+        v[2] = v[2].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(17) ^ v[2];
+        v[2] = v[2].rotate_left(32);
+    }
+
+    let k0 = read_u64(&key[..8]);
+    let k1 = read_u64(&key[8..]);
+    let mut v = [
+        k0 ^ 0x736f_6d65_7073_6575,
+        k1 ^ 0x646f_7261_6e64_6f6d,
+        k0 ^ 0x6c79_6765_6e65_7261,
+        k1 ^ 0x7465_6462_7974_6573,
+    ];
+
+    // the last word has the remaining bytes, and the length of the message in its high byte
+    let mut last = [0; 8];
+    let remaining = message.chunks(8).last().filter(|chunk| chunk.len() < 8);
+    if let Some(remaining) = remaining {
+        last[..remaining.len()].copy_from_slice(remaining);
+    }
+    last[7] = message.len() as u8;
+
+    let words = message
+        .chunks(8)
+        .filter(|chunk| chunk.len() == 8)
+        .map(read_u64)
+        .chain(Some(read_u64(&last)));
+    for word in words {
+        v[3] ^= word;
+        round(&mut v);
+        round(&mut v);
+        v[0] ^= word;
+    }
+
+    v[2] ^= 0xff;
+    for _ in 0..4 {
+        round(&mut v);
+    }
+    v[0] ^ v[1] ^ v[2] ^ v[3]
+}
+
+fn persist(path: &Path, secrets: &Secrets) -> io::Result<()> {
+    let state = SecretState {
+        rotated: secrets.rotated,
+        current: to_hex(&secrets.current),
+        previous: secrets.previous.as_ref().map(|previous| to_hex(previous)),
+    };
+    let state = toml::to_string(&state).map_err(invalid_data)?;
+
+    // replaced at once, the secrets are never partially written
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    fs::write(&tmp_path, state)?;
+    fs::rename(&tmp_path, path)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn invalid_data<E: ToString>(error: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error.to_string())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(hex: &str) -> io::Result<Vec<u8>> {
+    let digits = hex
+        .chars()
+        .map(|digit| digit.to_digit(16).map(|digit| digit as u8))
+        .collect::<Option<Vec<u8>>>()
+        .ok_or_else(|| invalid_data(format!("invalid hex: {}", hex)))?;
+
+    digits
+        .chunks(2)
+        .map(|pair| match *pair {
+            [high, low] => Ok(high << 4 | low),
+            _ => Err(invalid_data(format!("odd number of hex digits: {}", hex))),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    fn client_cookie(client: &str) -> Cookie {
+        let mut client_cookie = [0; 8];
+        client_cookie.copy_from_slice(&from_hex(client).unwrap());
+        Cookie::new(client_cookie, vec![]).unwrap()
+    }
+
+    fn secret(hex: &str) -> [u8; 16] {
+        CookieSecrets::parse_secret(hex).unwrap()
+    }
+
+    #[test]
+    fn test_siphash24() {
+        // the test vectors of the SipHash paper, for messages of 0 to 8 bytes
+        let mut key = [0; 16];
+        for (i, byte) in key.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        let message = (0..16).collect::<Vec<u8>>();
+
+        assert_eq!(siphash24(&key, &message[..0]), 0x726f_db47_dd0e_0e31);
+        assert_eq!(siphash24(&key, &message[..1]), 0x74f8_39c5_93dc_67fd);
+        assert_eq!(siphash24(&key, &message[..7]), 0xab02_00f5_8b01_d137);
+        assert_eq!(siphash24(&key, &message[..8]), 0x93f5_f579_9a93_2462);
+        assert_eq!(siphash24(&key, &message[..15]), 0xa129_ca61_49be_45e5);
+    }
+
+    #[test]
+    fn test_rfc9018_cookie() {
+        // RFC 9018 appendix A.1, learning a new server cookie
+        let secrets = CookieSecrets::new(secret("e5e973e5a6b2a43f48e7dc849e37bfcf"));
+        let client = IpAddr::V4(Ipv4Addr::new(198, 51, 100, 100));
+        let now = 1_559_731_985;
+
+        let response = secrets.response_cookie_at(&client_cookie("2464c4abcf10c957"), client, now);
+        assert_eq!(
+            to_hex(response.server()),
+            "010000005cf79f111f8130c3eee29480"
+        );
+        assert!(secrets.is_valid_at(&response, client, now + 60));
+
+        // returned unchanged while it's less than half an hour old
+        let later = secrets.response_cookie_at(&response, client, now + 60);
+        assert_eq!(later, response);
+
+        // not valid for other clients, or after an hour
+        assert!(!secrets.is_valid_at(&response, IpAddr::V4(Ipv4Addr::new(198, 51, 100, 101)), now));
+        assert!(!secrets.is_valid_at(&response, client, now + MAX_AGE as u64 + 1));
+    }
+
+    #[test]
+    fn test_rotation_overlap() {
+        let mut secrets = CookieSecrets::random();
+        secrets.set_rotation(Duration::from_secs(600), Duration::from_secs(60));
+        let client = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        let start = secrets.secrets.read().unwrap().rotated;
+
+        let cookie = secrets.response_cookie_at(&client_cookie("0102030405060708"), client, start);
+
+        // the secret is rotated by the first cookie generated after the rotation interval
+        let fresh =
+            secrets.response_cookie_at(&client_cookie("0807060504030201"), client, start + 600);
+        assert!(secrets.secrets.read().unwrap().previous.is_some());
+        assert!(secrets.is_valid_at(&fresh, client, start + 630));
+
+        // cookies of the previous secret are only valid during the overlap
+        assert!(secrets.is_valid_at(&cookie, client, start + 630));
+        assert!(!secrets.is_valid_at(&cookie, client, start + 660));
+    }
+
+    #[test]
+    fn test_persisted_secrets() {
+        let path = PathBuf::from("target/tests/cookie_secrets.toml");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::remove_file(&path).ok();
+
+        let secret = secret("e5e973e5a6b2a43f48e7dc849e37bfcf");
+        let client = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        let secrets = CookieSecrets::open(&path, Some(secret)).unwrap();
+        let cookie = secrets.response_cookie(&client_cookie("0102030405060708"), client);
+        secrets.rotate().ok();
+
+        // a restarted server accepts the cookies of the current and previous secrets
+        let restarted = CookieSecrets::open(&path, None).unwrap();
+        assert!(restarted.is_valid(&cookie, client));
+        assert_eq!(restarted.secrets.read().unwrap().previous, Some(secret));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,250 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use trust_dns::rr::LowerName;
+
+use authority::{ZoneChange, ZoneListener};
+use store::load_balancer::TargetHealth;
+
+/// The status of a zone of the server
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ZoneStatus {
+    /// The records of the zone are being loaded
+    Loading,
+    /// The zone is being served
+    Loaded,
+    /// The zone failed to load, with the error
+    Failed(String),
+}
+
+#[derive(Debug)]
+struct ZoneHealth {
+    status: ZoneStatus,
+    // when the records were last loaded or transferred
+    refreshed: Instant,
+    // the SOA expire of a secondary zone, its records are stale if not refreshed within it
+    expire: Option<Duration>,
+}
+
+/// The health of the server, reported by the `/healthz` and `/readyz` endpoints
+///
+/// The server is live as long as it answers. It is ready when all its zones are loaded, its
+///  secondary zones were refreshed within the expire of their SOA, and the upstream name servers
+///  of its forwarders are reachable.
+///
+/// Registered as a `ZoneListener` of the `Catalog`, zones added or replaced at runtime are loaded
+///  and refreshed, and removed zones are no longer reported.
+#[derive(Debug, Default)]
+pub struct Health {
+    zones: RwLock<BTreeMap<LowerName, ZoneHealth>>,
+    forwarders: RwLock<BTreeMap<LowerName, Arc<TargetHealth>>>,
+}
+
+/// Whether the server is ready, and why
+#[derive(Debug)]
+pub struct Readiness {
+    /// true if all the zones and forwarders are ready
+    pub ready: bool,
+    /// the state of each zone and forwarder, one per line
+    pub report: String,
+}
+
+impl Health {
+    /// Creates the health of a server without zones
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The zone is being loaded, the server is not ready until it is
+    pub fn zone_loading(&self, name: LowerName) {
+        self.set_status(name, ZoneStatus::Loading);
+    }
+
+    /// The zone was loaded
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - zone name, e.g. example.com.
+    /// * `expire` - the SOA expire of a secondary zone, `None` for all other zones
+    pub fn zone_loaded(&self, name: LowerName, expire: Option<Duration>) {
+        self.zones.write().expect("health poisoned").insert(
+            name,
+            ZoneHealth {
+                status: ZoneStatus::Loaded,
+                refreshed: Instant::now(),
+                expire,
+            },
+        );
+    }
+
+    /// The zone failed to load, the server is not ready
+    pub fn zone_failed(&self, name: LowerName, error: String) {
+        self.set_status(name, ZoneStatus::Failed(error));
+    }
+
+    /// The records of the zone were transferred, or loaded again
+    pub fn zone_refreshed(&self, name: &LowerName) {
+        if let Some(zone) = self.zones.write().expect("health poisoned").get_mut(name) {
+            zone.status = ZoneStatus::Loaded;
+            zone.refreshed = Instant::now();
+        }
+    }
+
+    /// The status of the zone, if it is known
+    pub fn zone_status(&self, name: &LowerName) -> Option<ZoneStatus> {
+        self.zones
+            .read()
+            .expect("health poisoned")
+            .get(name)
+            .map(|zone| zone.status.clone())
+    }
+
+    /// Adds the health of the upstream name servers of the forwarder of the zone
+    pub fn add_forwarder(&self, name: LowerName, upstream: Arc<TargetHealth>) {
+        self.forwarders
+            .write()
+            .expect("health poisoned")
+            .insert(name, upstream);
+    }
+
+    /// Returns the readiness of the server, with the state of all the zones and forwarders
+    pub fn readiness(&self) -> Readiness {
+        let mut ready = true;
+        let mut report = String::new();
+
+        for (name, zone) in self.zones.read().expect("health poisoned").iter() {
+            let state = match (&zone.status, zone.expire) {
+                (ZoneStatus::Loading, _) => "loading".to_string(),
+                (ZoneStatus::Failed(ref error), _) => format!("failed: {}", error),
+                (ZoneStatus::Loaded, Some(expire)) if zone.refreshed.elapsed() >= expire => {
+                    format!(
+                        "stale: not refreshed for {}s, expire is {}s",
+                        zone.refreshed.elapsed().as_secs(),
+                        expire.as_secs()
+                    )
+                }
+                (ZoneStatus::Loaded, _) => {
+                    report.push_str(&format!("zone {}: ok\n", name));
+                    continue;
+                }
+            };
+
+            ready = false;
+            report.push_str(&format!("zone {}: {}\n", name, state));
+        }
+
+        for (name, upstream) in self.forwarders.read().expect("health poisoned").iter() {
+            if upstream.is_healthy() {
+                report.push_str(&format!("forwarder {}: ok\n", name));
+            } else {
+                ready = false;
+                report.push_str(&format!("forwarder {}: upstream unreachable\n", name));
+            }
+        }
+
+        Readiness { ready, report }
+    }
+
+    fn set_status(&self, name: LowerName, status: ZoneStatus) {
+        let mut zones = self.zones.write().expect("health poisoned");
+        match zones.get_mut(&name) {
+            Some(zone) => zone.status = status,
+            None => {
+                zones.insert(
+                    name,
+                    ZoneHealth {
+                        status,
+                        refreshed: Instant::now(),
+                        expire: None,
+                    },
+                );
+            }
+        }
+    }
+}
+
+impl ZoneListener for Health {
+    fn zone_changed(&self, name: &LowerName, change: ZoneChange) {
+        match change {
+            ZoneChange::Added => self.zone_loaded(name.clone(), None),
+            ZoneChange::Replaced | ZoneChange::Refreshed => self.zone_refreshed(name),
+            ZoneChange::Removed => {
+                self.zones.write().expect("health poisoned").remove(name);
+                self.forwarders
+                    .write()
+                    .expect("health poisoned")
+                    .remove(name);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn name(name: &str) -> LowerName {
+        LowerName::from_str(name).unwrap()
+    }
+
+    #[test]
+    fn test_zone_status() {
+        let health = Health::new();
+        assert!(health.readiness().ready);
+
+        health.zone_loading(name("example.com."));
+        let readiness = health.readiness();
+        assert!(!readiness.ready);
+        assert_eq!(readiness.report, "zone example.com.: loading\n");
+
+        health.zone_loaded(name("example.com."), None);
+        health.zone_failed(name("example.net."), "no such file".to_string());
+        let readiness = health.readiness();
+        assert!(!readiness.ready);
+        assert_eq!(
+            readiness.report,
+            "zone example.com.: ok\nzone example.net.: failed: no such file\n"
+        );
+
+        health.zone_changed(&name("example.net."), ZoneChange::Removed);
+        assert!(health.readiness().ready);
+    }
+
+    #[test]
+    fn test_secondary_freshness() {
+        let health = Health::new();
+
+        health.zone_loaded(name("example.com."), Some(Duration::from_secs(3600)));
+        assert!(health.readiness().ready);
+
+        health.zone_loaded(name("example.net."), Some(Duration::from_secs(0)));
+        let readiness = health.readiness();
+        assert!(!readiness.ready);
+        assert!(readiness
+            .report
+            .contains("zone example.net.: stale: not refreshed for 0s, expire is 0s\n"));
+    }
+
+    #[test]
+    fn test_forwarder_reachability() {
+        let health = Health::new();
+        let upstream = Arc::new(TargetHealth::new());
+        health.add_forwarder(name("."), Arc::clone(&upstream));
+        assert!(health.readiness().ready);
+
+        upstream.set_healthy(false);
+        let readiness = health.readiness();
+        assert!(!readiness.ready);
+        assert_eq!(readiness.report, "forwarder .: upstream unreachable\n");
+    }
+}
@@ -8,23 +8,40 @@
 use std::io;
 use std::net::SocketAddr;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use futures::{Future, Stream};
+use bytes::Bytes;
+use futures::future::{self, Either, Loop};
+use futures::sync::oneshot;
+use futures::{Future, Sink, Stream};
 use h2::server;
-use proto::serialize::binary::BinDecodable;
+use http::{header, Method, Request, Response, StatusCode};
+use proto::op::Message;
+use proto::serialize::binary::{BinDecodable, BinEncoder};
+use tokio::codec::Framed;
 use tokio_io::{AsyncRead, AsyncWrite};
-use trust_dns_https::https_server;
+use trust_dns_https::http1::{self, Http1Codec};
+use trust_dns_https::{https_server, response, HttpsError};
 
-use authority::MessageResponse;
+use authority::{MessageRequest, MessageResponse};
 use server::request_handler::RequestHandler;
 use server::response_handler::ResponseHandler;
-use server::server_future;
+use server::server_future::{self, RequestDeadline};
+use server::{Health, Protocol};
+
+/// The path of the liveness endpoint
+const HEALTHZ_PATH: &str = "/healthz";
+/// The path of the readiness endpoint
+const READYZ_PATH: &str = "/readyz";
 
 pub fn h2_handler<T, I>(
     handler: Arc<Mutex<T>>,
     io: I,
     src_addr: SocketAddr,
     dns_hostname: Arc<String>,
+    request_deadline: Option<Duration>,
+    max_payload: u16,
+    health: Option<Arc<Health>>,
 ) -> impl Future<Item = (), Error = ()>
 where
     T: RequestHandler,
@@ -35,6 +52,7 @@ where
         .map_err(|e| warn!("h2 handshake error: {}", e))
         .and_then(move |h2| {
             let dns_hostname = dns_hostname.clone();
+            let health = health.clone();
             // Accept all inbound HTTP/2.0 streams sent over the
             // connection.
             h2.map_err(|e| warn!("h2 failed to receive message: {}", e))
@@ -44,46 +62,294 @@ where
                     let handler = handler.clone();
                     let responder = HttpsResponseHandle(Arc::new(Mutex::new(respond)));
 
-                    https_server::message_from(dns_hostname, request)
-                        .map_err(|e| warn!("h2 failed to receive message: {}", e))
-                        .and_then(|bytes| {
-                            BinDecodable::from_bytes(&bytes)
-                                .map_err(|e| warn!("could not decode message: {}", e))
-                        })
-                        .and_then(move |message| {
-                            debug!("reieved message: {:?}", message);
-
-                            server_future::handle_request(
-                                message,
+                    if let Some(response) = health
+                        .as_ref()
+                        .and_then(|health| health_response(health, &request))
+                    {
+                        responder
+                            .send_http(response)
+                            .unwrap_or_else(|e| warn!("failed to send health: {}", e));
+                        return Either::B(future::ok(()));
+                    }
+
+                    Either::A(https_server::message_from(dns_hostname, request).then(
+                        move |bytes| {
+                            let bytes = match bytes {
+                                Ok(bytes) => bytes,
+                                Err(e) => {
+                                    warn!("h2 failed to receive message: {}", e);
+                                    responder
+                                        .send_error(e.status())
+                                        .unwrap_or_else(|e| warn!("failed to send error: {}", e));
+                                    return Either::B(future::ok(()));
+                                }
+                            };
+
+                            Either::A(handle_message(
+                                bytes,
                                 src_addr,
-                                handler.clone(),
+                                handler,
                                 responder,
-                            )
-                        })
+                                request_deadline,
+                                max_payload,
+                            ))
+                        },
+                    ))
                 })
         })
         .map_err(|_| warn!("error in h2 handler"))
 }
 
+/// Handles the HTTP/1.1 requests of a connection, one at a time
+pub fn h1_handler<T, I>(
+    handler: Arc<Mutex<T>>,
+    io: I,
+    src_addr: SocketAddr,
+    dns_hostname: Arc<String>,
+    request_deadline: Option<Duration>,
+    max_payload: u16,
+    health: Option<Arc<Health>>,
+) -> impl Future<Item = (), Error = ()>
+where
+    T: RequestHandler,
+    I: AsyncRead + AsyncWrite + Send + 'static,
+{
+    type Connection<I> = Framed<I, Http1Codec>;
+    type Next<I> = Box<Future<Item = Loop<(), Connection<I>>, Error = ()> + Send>;
+
+    // sends the response, and continues with the next request if the connection is kept alive
+    fn send<I: AsyncRead + AsyncWrite + Send + 'static>(
+        connection: Connection<I>,
+        mut response: Response<Bytes>,
+        keep_alive: bool,
+    ) -> Next<I> {
+        if !keep_alive {
+            response.headers_mut().insert(
+                header::CONNECTION,
+                header::HeaderValue::from_static("close"),
+            );
+        }
+
+        Box::new(
+            connection
+                .send(response)
+                .map_err(|e| warn!("failed to send HTTP/1.1 response: {}", e))
+                .map(move |connection| {
+                    if keep_alive {
+                        Loop::Continue(connection)
+                    } else {
+                        Loop::Break(())
+                    }
+                }),
+        )
+    }
+
+    future::loop_fn(Framed::new(io, Http1Codec::new()), move |connection| {
+        let handler = handler.clone();
+        let dns_hostname = dns_hostname.clone();
+        let health = health.clone();
+
+        connection.into_future().then(move |next| -> Next<I> {
+            let (request, connection) = match next {
+                Ok((Some(request), connection)) => (request, connection),
+                // the connection was closed
+                Ok((None, _)) => return Box::new(future::ok(Loop::Break(()))),
+                // the rest of the requests can't be decoded after a bad one
+                Err((e, connection)) => {
+                    warn!("bad HTTP/1.1 request: {}", e);
+                    return send(connection, error_response(e.status()), false);
+                }
+            };
+
+            let keep_alive = http1::keep_alive(&request);
+            if let Some(response) = health
+                .as_ref()
+                .and_then(|health| health_response(health, &request))
+            {
+                return send(connection, response, keep_alive);
+            }
+
+            let bytes = match https_server::message_from_bytes(&dns_hostname, &request) {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    warn!("HTTP/1.1 failed to receive message: {}", e);
+                    return send(connection, error_response(e.status()), keep_alive);
+                }
+            };
+
+            let (sender, receiver) = oneshot::channel();
+            let responder = Http1ResponseHandle(Arc::new(Mutex::new(Some(sender))));
+
+            // the response is sent before the request is handled, unless the handler failed
+            let response = handle_message(
+                bytes,
+                src_addr,
+                handler,
+                responder,
+                request_deadline,
+                max_payload,
+            )
+            .then(move |_| receiver)
+            .then(move |response| {
+                let response =
+                    response.unwrap_or_else(|_| error_response(StatusCode::INTERNAL_SERVER_ERROR));
+                send(connection, response, keep_alive)
+            });
+
+            Box::new(response)
+        })
+    })
+}
+
+fn handle_message<T, R>(
+    bytes: Bytes,
+    src_addr: SocketAddr,
+    handler: Arc<Mutex<T>>,
+    responder: R,
+    request_deadline: Option<Duration>,
+    max_payload: u16,
+) -> impl Future<Item = (), Error = ()>
+where
+    T: RequestHandler,
+    R: ResponseHandler,
+{
+    match MessageRequest::from_bytes(&bytes) {
+        Ok(mut message) => {
+            debug!("reieved message: {:?}", message);
+            message.limit_max_payload(max_payload);
+
+            let handle_request = server_future::handle_request(
+                message,
+                src_addr,
+                Protocol::Https,
+                None,
+                handler,
+                responder.clone(),
+            );
+            Either::A(RequestDeadline::new(
+                handle_request,
+                request_deadline,
+                &bytes,
+                responder,
+            ))
+        }
+        Err(e) => {
+            let e = server_future::send_form_err(&bytes, e, responder);
+            warn!("could not decode message: {}", e);
+            Either::B(future::ok(()))
+        }
+    }
+}
+
+/// Encodes the response, returning the bytes and for how long the response may be cached
+fn encode_response(response: MessageResponse) -> io::Result<(Bytes, Option<u32>)> {
+    let mut bytes = Vec::with_capacity(512);
+    // mut block
+    {
+        let mut encoder = BinEncoder::new(&mut bytes);
+        response.destructive_emit(&mut encoder)?;
+    };
+
+    // the records of the response were consumed by the encoding, they are decoded for their TTLs
+    let max_age = Message::from_vec(&bytes)
+        .map(|message| response::max_age(&message))
+        .unwrap_or_else(|e| {
+            warn!("failed to decode response for its TTLs: {}", e);
+            None
+        });
+
+    Ok((Bytes::from(bytes), max_age))
+}
+
+/// Answers the requests of the health endpoints, `None` if the request is for another path
+fn health_response<T>(health: &Health, request: &Request<T>) -> Option<Response<Bytes>> {
+    let (status, body) = match request.uri().path() {
+        HEALTHZ_PATH => (StatusCode::OK, "ok\n".to_string()),
+        READYZ_PATH => {
+            let readiness = health.readiness();
+            if readiness.ready {
+                (StatusCode::OK, format!("ready\n{}", readiness.report))
+            } else {
+                let report = format!("not ready\n{}", readiness.report);
+                (StatusCode::SERVICE_UNAVAILABLE, report)
+            }
+        }
+        _ => return None,
+    };
+
+    if request.method() != Method::GET {
+        return Some(error_response(StatusCode::METHOD_NOT_ALLOWED));
+    }
+
+    let content_length = body.len();
+    let mut response = Response::new(Bytes::from(body));
+    *response.status_mut() = status;
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("text/plain; charset=utf-8"),
+    );
+    headers.insert(
+        header::CACHE_CONTROL,
+        header::HeaderValue::from_static("no-store"),
+    );
+    headers.insert(
+        header::CONTENT_LENGTH,
+        header::HeaderValue::from(content_length),
+    );
+
+    Some(response)
+}
+
+fn error_response(status: StatusCode) -> Response<Bytes> {
+    let mut response = Response::new(Bytes::new());
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        header::CONTENT_LENGTH,
+        header::HeaderValue::from_static("0"),
+    );
+    response
+}
+
 #[derive(Clone)]
 struct HttpsResponseHandle(Arc<Mutex<::h2::server::SendResponse<::bytes::Bytes>>>);
 
-impl ResponseHandler for HttpsResponseHandle {
-    fn send_response(&self, response: MessageResponse) -> io::Result<()> {
-        use bytes::Bytes;
+impl HttpsResponseHandle {
+    /// Responds with the status, and no DNS message, to a request which was rejected
+    fn send_error(&self, status: StatusCode) -> io::Result<()> {
+        let response = response::error(status)?;
+
+        debug!("sending error response: {:#?}", response);
+        self.0
+            .lock()
+            .expect("https poisoned")
+            .send_response(response, true)
+            .map_err(HttpsError::from)?;
+
+        Ok(())
+    }
 
-        use proto::serialize::binary::BinEncoder;
-        use trust_dns_https::response;
-        use trust_dns_https::HttpsError;
+    /// Responds with a response which is not a DNS message, e.g. of the health endpoints
+    fn send_http(&self, response: Response<Bytes>) -> io::Result<()> {
+        let (parts, body) = response.into_parts();
+        let response = Response::from_parts(parts, ());
 
-        let mut bytes = Vec::with_capacity(512);
-        // mut block
-        {
-            let mut encoder = BinEncoder::new(&mut bytes);
-            response.destructive_emit(&mut encoder)?;
-        };
-        let bytes = Bytes::from(bytes);
-        let response = response::new(bytes.len())?;
+        let mut respond = self.0.lock().expect("https poisoned");
+        let mut stream = respond
+            .send_response(response, body.is_empty())
+            .map_err(HttpsError::from)?;
+        if !body.is_empty() {
+            stream.send_data(body, true).map_err(HttpsError::from)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ResponseHandler for HttpsResponseHandle {
+    fn send_response(&self, response: MessageResponse) -> io::Result<()> {
+        let (bytes, max_age) = encode_response(response)?;
+        let response = response::new(bytes.len(), max_age)?;
 
         debug!("sending response: {:#?}", response);
         let mut stream = self
@@ -97,3 +363,23 @@ impl ResponseHandler for HttpsResponseHandle {
         Ok(())
     }
 }
+
+/// Hands the response over to the connection, which sends it after the request was handled
+#[derive(Clone)]
+struct Http1ResponseHandle(Arc<Mutex<Option<oneshot::Sender<Response<Bytes>>>>>);
+
+impl ResponseHandler for Http1ResponseHandle {
+    fn send_response(&self, response: MessageResponse) -> io::Result<()> {
+        let (bytes, max_age) = encode_response(response)?;
+        let response = response::new(bytes.len(), max_age)?.map(|()| bytes);
+
+        debug!("sending response: {:#?}", response);
+        self.0
+            .lock()
+            .expect("https poisoned")
+            .take()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "response was already sent"))?
+            .send(response)
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "connection was closed"))
+    }
+}
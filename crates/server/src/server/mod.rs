@@ -7,14 +7,30 @@
 
 //! `Server` component for hosting a domain name servers operations.
 
+#[cfg(feature = "request-arena")]
+mod arena;
+mod cookie;
+mod health;
 #[cfg(feature = "dns-over-https")]
 mod https_handler;
+mod protocol;
+mod protocol_policy;
+mod quota;
 mod request_handler;
 mod response_handler;
 mod server_future;
 mod timeout_stream;
+mod tsig;
 
-pub use self::request_handler::{Request, RequestHandler};
+#[cfg(feature = "request-arena")]
+pub use self::arena::{ArenaScope, ArenaStats, RequestArena};
+pub use self::cookie::CookieSecrets;
+pub use self::health::{Health, Readiness, ZoneStatus};
+pub use self::protocol::Protocol;
+pub use self::protocol_policy::ProtocolPolicy;
+pub use self::quota::{IdentityQuotas, Quota, QuotaKind, QuotaMetrics};
+pub use self::request_handler::{HandleOpCode, Request, RequestHandler};
 pub use self::response_handler::{ResponseHandle, ResponseHandler};
 pub use self::server_future::ServerFuture;
 pub use self::timeout_stream::TimeoutStream;
+pub use self::tsig::TsigKeyring;
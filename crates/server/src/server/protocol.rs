@@ -0,0 +1,44 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::fmt;
+
+/// The protocol on which a request was received
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Protocol {
+    /// UDP
+    Udp,
+    /// TCP
+    Tcp,
+    /// DNS over TLS
+    Tls,
+    /// DNS over HTTPS
+    Https,
+}
+
+impl Protocol {
+    /// Returns true if more than one response message can be sent for a request, e.g. for AXFR
+    pub fn is_stream(self) -> bool {
+        match self {
+            Protocol::Tcp | Protocol::Tls => true,
+            Protocol::Udp | Protocol::Https => false,
+        }
+    }
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let protocol = match *self {
+            Protocol::Udp => "udp",
+            Protocol::Tcp => "tcp",
+            Protocol::Tls => "tls",
+            Protocol::Https => "https",
+        };
+
+        f.write_str(protocol)
+    }
+}
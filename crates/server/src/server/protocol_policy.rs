@@ -0,0 +1,157 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use proto::rr::RecordType;
+use proto::xfer::dns_request::MAX_PAYLOAD_LEN;
+
+use authority::MessageRequest;
+use server::Protocol;
+
+/// The answers of the server depending on the protocol on which the requests are received
+///
+/// Responses over UDP can be amplified by spoofing the source of the requests, so they are
+///  limited: ANY queries may be refused, and the responses larger than `max_udp_payload` are
+///  truncated, with the TC bit set, for the client to retry over TCP rather than receive a
+///  fragmented response. The EDNS payload honored over UDP and TCP is also limited to
+///  `max_udp_payload`, only the encrypted transports, TLS and HTTPS, allow larger payloads.
+///
+/// AXFR requests are always refused over UDP, as the transfer may need more than one message.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ProtocolPolicy {
+    refuse_any_over_udp: bool,
+    max_udp_payload: u16,
+    max_encrypted_payload: u16,
+}
+
+impl ProtocolPolicy {
+    /// Returns the default policy, which answers ANY queries over UDP, truncates the responses
+    ///  over UDP to 1232 bytes, and does not limit the payload over TLS and HTTPS
+    pub fn new() -> Self {
+        ProtocolPolicy {
+            refuse_any_over_udp: false,
+            max_udp_payload: MAX_PAYLOAD_LEN,
+            max_encrypted_payload: u16::max_value(),
+        }
+    }
+
+    /// Refuses the ANY queries received over UDP, see [RFC 8482](https://tools.ietf.org/html/rfc8482)
+    pub fn set_refuse_any_over_udp(&mut self, refuse_any_over_udp: bool) -> &mut Self {
+        self.refuse_any_over_udp = refuse_any_over_udp;
+        self
+    }
+
+    /// Sets the size above which the responses over UDP are truncated, and the largest EDNS
+    ///  payload honored over UDP and TCP
+    pub fn set_max_udp_payload(&mut self, max_udp_payload: u16) -> &mut Self {
+        self.max_udp_payload = max_udp_payload;
+        self
+    }
+
+    /// Sets the largest EDNS payload honored over TLS and HTTPS
+    pub fn set_max_encrypted_payload(&mut self, max_encrypted_payload: u16) -> &mut Self {
+        self.max_encrypted_payload = max_encrypted_payload;
+        self
+    }
+
+    /// Returns true if the request must be refused on this protocol
+    pub fn refuses(&self, request: &MessageRequest, protocol: Protocol) -> bool {
+        protocol == Protocol::Udp
+            && self.refuse_any_over_udp
+            && request
+                .queries()
+                .iter()
+                .any(|q| q.query_type() == RecordType::ANY)
+    }
+
+    /// Returns the size to which the response to the request is truncated on this protocol, if any
+    pub fn max_response_size(&self, request: &MessageRequest, protocol: Protocol) -> Option<u16> {
+        // the response must fit in the payload of the requester, and avoid fragmentation
+        if protocol == Protocol::Udp {
+            Some(request.max_payload().min(self.max_udp_payload))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the largest EDNS payload honored on this protocol
+    pub fn max_payload(&self, protocol: Protocol) -> u16 {
+        match protocol {
+            Protocol::Udp | Protocol::Tcp => self.max_udp_payload,
+            Protocol::Tls | Protocol::Https => self.max_encrypted_payload,
+        }
+    }
+}
+
+impl Default for ProtocolPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proto::op::{Edns, Message, Query};
+    use proto::rr::Name;
+    use proto::serialize::binary::BinDecodable;
+
+    use super::*;
+
+    fn request(query_type: RecordType, max_payload: u16) -> MessageRequest {
+        let mut edns = Edns::new();
+        edns.set_max_payload(max_payload);
+
+        let mut message = Message::new();
+        message
+            .add_query(Query::query(
+                Name::from_ascii("www.example.com.").unwrap(),
+                query_type,
+            ))
+            .set_edns(edns);
+
+        MessageRequest::from_bytes(&message.to_vec().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_refuses() {
+        let any = request(RecordType::ANY, 4096);
+        let a = request(RecordType::A, 4096);
+
+        let mut policy = ProtocolPolicy::new();
+        assert!(!policy.refuses(&any, Protocol::Udp));
+
+        policy.set_refuse_any_over_udp(true);
+        assert!(policy.refuses(&any, Protocol::Udp));
+        assert!(!policy.refuses(&any, Protocol::Tcp));
+        assert!(!policy.refuses(&any, Protocol::Tls));
+        assert!(!policy.refuses(&a, Protocol::Udp));
+    }
+
+    #[test]
+    fn test_max_response_size() {
+        let small = request(RecordType::A, 512);
+        let large = request(RecordType::A, 4096);
+
+        let mut policy = ProtocolPolicy::new();
+        assert_eq!(policy.max_response_size(&small, Protocol::Udp), Some(512));
+        assert_eq!(policy.max_response_size(&large, Protocol::Udp), Some(1232));
+        assert_eq!(policy.max_response_size(&large, Protocol::Tcp), None);
+
+        policy.set_max_udp_payload(4000);
+        assert_eq!(policy.max_response_size(&large, Protocol::Udp), Some(4000));
+    }
+
+    #[test]
+    fn test_max_payload() {
+        let mut policy = ProtocolPolicy::new();
+        policy.set_max_encrypted_payload(8192);
+
+        assert_eq!(policy.max_payload(Protocol::Udp), 1232);
+        assert_eq!(policy.max_payload(Protocol::Tcp), 1232);
+        assert_eq!(policy.max_payload(Protocol::Tls), 8192);
+        assert_eq!(policy.max_payload(Protocol::Https), 8192);
+    }
+}
@@ -0,0 +1,257 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Quotas of the requests signed by each TSIG key
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use trust_dns::op::OpCode;
+use trust_dns::rr::{LowerName, Name, RecordType};
+
+use authority::MessageRequest;
+
+/// The kinds of requests limited by a `Quota`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum QuotaKind {
+    /// Queries, of any type but AXFR and IXFR
+    Query,
+    /// Dynamic updates
+    Update,
+    /// Zone transfers, AXFR and IXFR queries
+    Transfer,
+}
+
+impl QuotaKind {
+    /// The kind of the request
+    pub fn of(request: &MessageRequest) -> Self {
+        if request.op_code() == OpCode::Update {
+            return QuotaKind::Update;
+        }
+
+        match request.queries().first().map(|query| query.query_type()) {
+            Some(RecordType::AXFR) | Some(RecordType::IXFR) => QuotaKind::Transfer,
+            _ => QuotaKind::Query,
+        }
+    }
+
+    fn index(self) -> usize {
+        match self {
+            QuotaKind::Query => 0,
+            QuotaKind::Update => 1,
+            QuotaKind::Transfer => 2,
+        }
+    }
+}
+
+/// The number of requests of each kind allowed to an identity, None for no limit
+///
+/// Requests are allowed in bursts of up to the limit, the allowance is then replenished evenly
+///  over the period, e.g. a query every 100ms for 10 queries per second.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Quota {
+    /// The queries allowed per second
+    pub queries_per_second: Option<u32>,
+    /// The dynamic updates allowed per minute
+    pub updates_per_minute: Option<u32>,
+    /// The zone transfers allowed per hour
+    pub transfers_per_hour: Option<u32>,
+}
+
+impl Quota {
+    fn limit(&self, kind: QuotaKind) -> Option<(u32, Duration)> {
+        match kind {
+            QuotaKind::Query => self
+                .queries_per_second
+                .map(|limit| (limit, Duration::from_secs(1))),
+            QuotaKind::Update => self
+                .updates_per_minute
+                .map(|limit| (limit, Duration::from_secs(60))),
+            QuotaKind::Transfer => self
+                .transfers_per_hour
+                .map(|limit| (limit, Duration::from_secs(3600))),
+        }
+    }
+}
+
+/// The number of requests of an identity allowed and refused by its quota
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QuotaMetrics {
+    allowed: [u64; 3],
+    refused: [u64; 3],
+}
+
+impl QuotaMetrics {
+    /// The requests of the kind which were allowed, including those without a limit
+    pub fn allowed(&self, kind: QuotaKind) -> u64 {
+        self.allowed[kind.index()]
+    }
+
+    /// The requests of the kind which were refused
+    pub fn refused(&self, kind: QuotaKind) -> u64 {
+        self.refused[kind.index()]
+    }
+}
+
+#[derive(Default)]
+struct Usage {
+    // the remaining allowance of each kind, and when it was last replenished
+    allowance: [Option<(f64, Instant)>; 3],
+    metrics: QuotaMetrics,
+}
+
+/// The quotas of the requests of each identity, the TSIG key with which they are signed
+///
+/// Requests exceeding the quota of their key are answered with REFUSED, signed with the key, and
+///  aren't passed to the handler. Unsigned requests are not limited, neither are the keys without
+///  a quota unless there is a default quota.
+#[derive(Default)]
+pub struct IdentityQuotas {
+    quotas: HashMap<LowerName, Quota>,
+    default_quota: Option<Quota>,
+    usage: Mutex<HashMap<LowerName, Usage>>,
+}
+
+impl IdentityQuotas {
+    /// Creates quotas without any limit
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the quota of the requests signed by the key, replacing any previous quota
+    pub fn set_quota(&mut self, key_name: &Name, quota: Quota) {
+        self.quotas.insert(LowerName::from(key_name), quota);
+    }
+
+    /// Sets the quota of the keys without a quota of their own
+    pub fn set_default_quota(&mut self, quota: Quota) {
+        self.default_quota = Some(quota);
+    }
+
+    /// The requests of the key allowed and refused so far
+    pub fn metrics(&self, key_name: &Name) -> QuotaMetrics {
+        self.usage
+            .lock()
+            .expect("quotas poisoned")
+            .get(&LowerName::from(key_name))
+            .map(|usage| usage.metrics)
+            .unwrap_or_default()
+    }
+
+    /// Counts the request of the key, returns false if it exceeds the quota of the key
+    pub fn check(&self, key_name: &Name, kind: QuotaKind) -> bool {
+        self.check_at(key_name, kind, Instant::now())
+    }
+
+    fn check_at(&self, key_name: &Name, kind: QuotaKind, now: Instant) -> bool {
+        let key_name = LowerName::from(key_name);
+        let limit = self
+            .quotas
+            .get(&key_name)
+            .or(self.default_quota.as_ref())
+            .and_then(|quota| quota.limit(kind));
+
+        let mut usage = self.usage.lock().expect("quotas poisoned");
+        let usage = usage.entry(key_name).or_default();
+
+        let allowed = match limit {
+            Some((limit, period)) => {
+                let limit = f64::from(limit);
+                let (allowance, replenished) =
+                    usage.allowance[kind.index()].get_or_insert((limit, now));
+
+                let elapsed = now.duration_since(*replenished);
+                let elapsed = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1e9;
+                *allowance = (*allowance + elapsed * limit / period.as_secs() as f64).min(limit);
+                *replenished = now;
+
+                if *allowance >= 1.0 {
+                    *allowance -= 1.0;
+                    true
+                } else {
+                    false
+                }
+            }
+            None => true,
+        };
+
+        if allowed {
+            usage.metrics.allowed[kind.index()] += 1;
+        } else {
+            usage.metrics.refused[kind.index()] += 1;
+        }
+        allowed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn key(name: &str) -> Name {
+        Name::from_str(name).unwrap()
+    }
+
+    #[test]
+    fn test_quota_is_replenished() {
+        let mut quotas = IdentityQuotas::new();
+        quotas.set_quota(
+            &key("update-key."),
+            Quota {
+                updates_per_minute: Some(2),
+                ..Quota::default()
+            },
+        );
+
+        let start = Instant::now();
+        let update = |secs| {
+            quotas.check_at(
+                &key("Update-Key."),
+                QuotaKind::Update,
+                start + Duration::from_secs(secs),
+            )
+        };
+        assert!(update(0));
+        assert!(update(0));
+        assert!(!update(0));
+        assert!(!update(29));
+        assert!(update(31));
+        assert!(!update(31));
+
+        // the allowance is never more than the limit
+        assert!(update(600));
+        assert!(update(600));
+        assert!(!update(600));
+
+        let metrics = quotas.metrics(&key("update-key."));
+        assert_eq!(metrics.allowed(QuotaKind::Update), 5);
+        assert_eq!(metrics.refused(QuotaKind::Update), 4);
+        assert_eq!(metrics.allowed(QuotaKind::Query), 0);
+    }
+
+    #[test]
+    fn test_default_quota() {
+        let mut quotas = IdentityQuotas::new();
+        quotas.set_quota(&key("transfer-key."), Quota::default());
+        quotas.set_default_quota(Quota {
+            transfers_per_hour: Some(1),
+            ..Quota::default()
+        });
+
+        let now = Instant::now();
+        assert!(quotas.check_at(&key("other-key."), QuotaKind::Transfer, now));
+        assert!(!quotas.check_at(&key("other-key."), QuotaKind::Transfer, now));
+        assert!(quotas.check_at(&key("other-key."), QuotaKind::Query, now));
+
+        // keys with their own quota are not limited by the default one
+        assert!(quotas.check_at(&key("transfer-key."), QuotaKind::Transfer, now));
+        assert!(quotas.check_at(&key("transfer-key."), QuotaKind::Transfer, now));
+    }
+}
@@ -7,12 +7,15 @@
 
 //! Request Handler for incoming requests
 
+use std::io;
 use std::net::SocketAddr;
 
-use futures::Future;
+use futures::{Async, Future, Poll};
+use trust_dns::op::{OpCode, ResponseCode};
+use trust_dns::rr::Name;
 
-use authority::MessageRequest;
-use server::ResponseHandler;
+use authority::{MessageRequest, MessageResponseBuilder};
+use server::{Protocol, ResponseHandler};
 
 /// An incoming request to the DNS catalog
 pub struct Request {
@@ -20,9 +23,24 @@ pub struct Request {
     pub message: MessageRequest,
     /// Source address of the Client
     pub src: SocketAddr,
+    /// Protocol on which the request was received
+    pub protocol: Protocol,
+    /// Name of the TSIG key with which the request was signed and verified, see `TsigKeyring`
+    pub tsig_key: Option<Name>,
+}
+
+impl Request {
+    /// The op code of the request, see `RequestHandler` for the methods handling each of them
+    pub fn op_code(&self) -> OpCode {
+        self.message.op_code()
+    }
 }
 
 /// Trait for handling incoming requests, and providing a message response.
+///
+/// The server routes the requests by their op code: NOTIFY, STATUS and UPDATE requests to
+///  `handle_notify`, `handle_status` and `handle_update`, which answer `NotImp` unless they are
+///  implemented, and all the others, e.g. queries, to `handle_request`.
 pub trait RequestHandler: Send + 'static {
     /// A future for execution of the request
     type ResponseFuture: Future<Item = (), Error = ()> + Send + 'static;
@@ -38,4 +56,213 @@ pub trait RequestHandler: Send + 'static {
         request: Request,
         response_handle: R,
     ) -> Self::ResponseFuture;
+
+    /// Handles a NOTIFY of a change of a zone, [RFC 1996](https://tools.ietf.org/html/rfc1996)
+    ///
+    /// By default the request is answered with `NotImp`.
+    fn handle_notify<R: ResponseHandler>(
+        &self,
+        request: Request,
+        response_handle: R,
+    ) -> HandleOpCode<Self::ResponseFuture> {
+        HandleOpCode::not_imp(&request, response_handle)
+    }
+
+    /// Handles a STATUS request, the op code is reserved but its behavior was never defined
+    ///
+    /// By default the request is answered with `NotImp`.
+    fn handle_status<R: ResponseHandler>(
+        &self,
+        request: Request,
+        response_handle: R,
+    ) -> HandleOpCode<Self::ResponseFuture> {
+        HandleOpCode::not_imp(&request, response_handle)
+    }
+
+    /// Handles a dynamic UPDATE, [RFC 2136](https://tools.ietf.org/html/rfc2136)
+    ///
+    /// By default the request is answered with `NotImp`.
+    fn handle_update<R: ResponseHandler>(
+        &self,
+        request: Request,
+        response_handle: R,
+    ) -> HandleOpCode<Self::ResponseFuture> {
+        HandleOpCode::not_imp(&request, response_handle)
+    }
+
+    /// Routes the request to the method handling its op code
+    fn handle_op_code<R: ResponseHandler>(
+        &self,
+        request: Request,
+        response_handle: R,
+    ) -> HandleOpCode<Self::ResponseFuture> {
+        match request.op_code() {
+            OpCode::Notify => self.handle_notify(request, response_handle),
+            OpCode::Status => self.handle_status(request, response_handle),
+            OpCode::Update => self.handle_update(request, response_handle),
+            OpCode::Query | OpCode::Unknown(_) => {
+                HandleOpCode::Handler(self.handle_request(request, response_handle))
+            }
+        }
+    }
+}
+
+/// The future of a request routed by its op code, see `RequestHandler::handle_op_code`
+#[must_use = "futures do nothing unless polled"]
+pub enum HandleOpCode<F: Future<Item = (), Error = ()>> {
+    /// The request is handled by the future of the handler
+    Handler(F),
+    /// The request was already answered, e.g. with `NotImp`
+    Answered(Option<io::Error>),
+}
+
+impl<F: Future<Item = (), Error = ()>> HandleOpCode<F> {
+    /// Answers the request with `NotImp`, its op code is not implemented by the handler
+    pub fn not_imp<R: ResponseHandler>(request: &Request, response_handle: R) -> Self {
+        warn!(
+            "request: {} unimplemented op_code: {:?}",
+            request.message.id(),
+            request.op_code()
+        );
+        let response = MessageResponseBuilder::new(Some(request.message.raw_queries()));
+        let result = response_handle.send_response(response.error_msg(
+            request.message.id(),
+            request.op_code(),
+            ResponseCode::NotImp,
+        ));
+
+        HandleOpCode::Answered(result.err())
+    }
+}
+
+impl<F: Future<Item = (), Error = ()>> Future for HandleOpCode<F> {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        match self {
+            HandleOpCode::Handler(handle_request) => handle_request.poll(),
+            HandleOpCode::Answered(error) => {
+                if let Some(error) = error.take() {
+                    warn!("failed to send response: {}", error);
+                }
+                Ok(Async::Ready(()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::sync::{Arc, Mutex};
+
+    use futures::future;
+    use proto::op::{Message, Query};
+    use proto::serialize::binary::BinDecodable;
+    use trust_dns::rr::RecordType;
+
+    use authority::MessageResponse;
+
+    use super::*;
+
+    /// Records the response codes of the responses
+    #[derive(Clone, Default)]
+    struct TestResponseHandler(Arc<Mutex<Vec<ResponseCode>>>);
+
+    impl ResponseHandler for TestResponseHandler {
+        fn send_response(&self, response: MessageResponse) -> io::Result<()> {
+            // the high bits of the code are in the EDNS of the response, none are set here
+            let response_code = ResponseCode::from(0, response.header().response_code());
+            self.0.lock().unwrap().push(response_code);
+            Ok(())
+        }
+    }
+
+    /// Handles queries and NOTIFY, answering them with `NoError`
+    struct NotifyHandler;
+
+    impl NotifyHandler {
+        fn answer<R: ResponseHandler>(
+            request: &Request,
+            response_handle: R,
+        ) -> future::FutureResult<(), ()> {
+            let response = MessageResponseBuilder::new(Some(request.message.raw_queries()));
+            future::result(
+                response_handle
+                    .send_response(response.error_msg(
+                        request.message.id(),
+                        request.op_code(),
+                        ResponseCode::NoError,
+                    ))
+                    .map_err(|_| ()),
+            )
+        }
+    }
+
+    impl RequestHandler for NotifyHandler {
+        type ResponseFuture = future::FutureResult<(), ()>;
+
+        fn handle_request<R: ResponseHandler>(
+            &self,
+            request: Request,
+            response_handle: R,
+        ) -> Self::ResponseFuture {
+            Self::answer(&request, response_handle)
+        }
+
+        fn handle_notify<R: ResponseHandler>(
+            &self,
+            request: Request,
+            response_handle: R,
+        ) -> HandleOpCode<Self::ResponseFuture> {
+            HandleOpCode::Handler(Self::answer(&request, response_handle))
+        }
+    }
+
+    fn request(op_code: OpCode) -> Request {
+        let mut message = Message::new();
+        message
+            .set_id(10)
+            .set_op_code(op_code)
+            .add_query(Query::query(
+                Name::from_ascii("example.com.").unwrap(),
+                RecordType::SOA,
+            ));
+
+        Request {
+            message: MessageRequest::from_bytes(&message.to_vec().unwrap()).unwrap(),
+            src: SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 53),
+            protocol: Protocol::Udp,
+            tsig_key: None,
+        }
+    }
+
+    #[test]
+    fn test_handle_op_code() {
+        let response_handle = TestResponseHandler::default();
+        for op_code in &[
+            OpCode::Query,
+            OpCode::Notify,
+            OpCode::Status,
+            OpCode::Update,
+        ] {
+            let request = request(*op_code);
+            assert_eq!(request.op_code(), *op_code);
+            NotifyHandler
+                .handle_op_code(request, response_handle.clone())
+                .wait()
+                .unwrap();
+        }
+
+        assert_eq!(
+            *response_handle.0.lock().unwrap(),
+            vec![
+                ResponseCode::NoError,
+                ResponseCode::NoError,
+                ResponseCode::NotImp,
+                ResponseCode::NotImp,
+            ]
+        );
+    }
 }
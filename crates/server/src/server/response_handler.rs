@@ -7,12 +7,16 @@
 
 use std::io;
 use std::net::SocketAddr;
+#[cfg(feature = "dnssec")]
+use std::sync::Arc;
 
 use proto::xfer::SerialMessage;
 use trust_dns::serialize::binary::BinEncoder;
 use trust_dns::BufStreamHandle;
 
 use authority::MessageResponse;
+#[cfg(feature = "dnssec")]
+use server::tsig::ResponseSigner;
 
 /// A handler for send a response to a client
 pub trait ResponseHandler: Clone + Send + 'static {
@@ -31,12 +35,26 @@ pub trait ResponseHandler: Clone + Send + 'static {
 pub struct ResponseHandle {
     dst: SocketAddr,
     stream_handle: BufStreamHandle,
+    max_size: Option<u16>,
+    #[cfg(feature = "dnssec")]
+    pub(crate) tsig: Option<Arc<ResponseSigner>>,
 }
 
 impl ResponseHandle {
     /// Returns a new `ResponseHandle` for sending a response message
     pub fn new(dst: SocketAddr, stream_handle: BufStreamHandle) -> Self {
-        ResponseHandle { dst, stream_handle }
+        ResponseHandle {
+            dst,
+            stream_handle,
+            max_size: None,
+            #[cfg(feature = "dnssec")]
+            tsig: None,
+        }
+    }
+
+    /// Sets the size to which responses are truncated, e.g. the max payload of UDP requests
+    pub fn set_max_size(&mut self, max_size: u16) {
+        self.max_size = Some(max_size);
     }
 }
 
@@ -53,6 +71,9 @@ impl ResponseHandler for ResponseHandle {
         let mut buffer = Vec::with_capacity(512);
         let encode_result = {
             let mut encoder: BinEncoder = BinEncoder::new(&mut buffer);
+            if let Some(max_size) = self.max_size {
+                encoder.set_max_size(max_size);
+            }
             response.destructive_emit(&mut encoder)
         };
 
@@ -63,6 +84,18 @@ impl ResponseHandler for ResponseHandle {
             )
         })?;
 
+        #[cfg(feature = "dnssec")]
+        {
+            if let Some(ref tsig) = self.tsig {
+                tsig.sign(&mut buffer).map_err(|e| {
+                    io::Error::new(
+                        io::ErrorKind::Other,
+                        format!("error signing message: {}", e),
+                    )
+                })?;
+            }
+        }
+
         self.stream_handle
             .unbounded_send(SerialMessage::new(buffer, self.dst))
             .map_err(|_| io::Error::new(io::ErrorKind::Other, "unknown"))
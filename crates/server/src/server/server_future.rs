@@ -8,7 +8,7 @@ use std;
 use std::io;
 use std::net::SocketAddr;
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use futures::{Async, Future, Poll, Stream};
 
@@ -17,25 +17,40 @@ use rustls::{Certificate, PrivateKey};
 use tokio_executor;
 use tokio_reactor::Handle;
 use tokio_tcp;
+use tokio_timer::Delay;
 use tokio_udp;
 
-use proto::op::Edns;
+use proto::error::ProtoError;
+use proto::op::{Edns, Header, MessageType, ResponseCode};
+use proto::rr::Name;
 use proto::serialize::binary::{BinDecodable, BinDecoder};
-use proto::tcp::TcpStream;
+use proto::tcp::{TcpOptions, TcpStream};
 use proto::udp::UdpStream;
 use proto::xfer::SerialMessage;
 use proto::BufStreamHandle;
 #[cfg(all(feature = "dns-over-openssl", not(feature = "dns-over-rustls")))]
 use trust_dns_openssl::tls_server::*;
 
-use authority::MessageRequest;
-use server::{Request, RequestHandler, ResponseHandle, ResponseHandler, TimeoutStream};
+use authority::{MessageRequest, MessageResponseBuilder};
+#[cfg(feature = "request-arena")]
+use server::ArenaScope;
+use server::{
+    HandleOpCode, Health, IdentityQuotas, Protocol, ProtocolPolicy, QuotaKind, Request,
+    RequestHandler, ResponseHandle, ResponseHandler, TimeoutStream, TsigKeyring,
+};
 
 // TODO, would be nice to have a Slab for buffers here...
 
 /// A Futures based implementation of a DNS server
 pub struct ServerFuture<T: RequestHandler> {
     handler: Arc<Mutex<T>>,
+    tsig_keyring: Arc<TsigKeyring>,
+    quotas: Option<Arc<IdentityQuotas>>,
+    request_deadline: Option<Duration>,
+    policy: ProtocolPolicy,
+    tcp_options: TcpOptions,
+    udp_offload: bool,
+    health: Option<Arc<Health>>,
 }
 
 impl<T: RequestHandler> ServerFuture<T> {
@@ -43,17 +58,98 @@ impl<T: RequestHandler> ServerFuture<T> {
     pub fn new(handler: T) -> ServerFuture<T> {
         ServerFuture {
             handler: Arc::new(Mutex::new(handler)),
+            tsig_keyring: Arc::new(TsigKeyring::new()),
+            quotas: None,
+            request_deadline: None,
+            policy: ProtocolPolicy::new(),
+            tcp_options: TcpOptions::default(),
+            udp_offload: false,
+            health: None,
         }
     }
 
+    /// Sets the TSIG keys with which requests are verified, and their responses signed
+    ///
+    /// Only applies to sockets and listeners registered after this call.
+    pub fn set_tsig_keyring(&mut self, tsig_keyring: TsigKeyring) {
+        self.tsig_keyring = Arc::new(tsig_keyring);
+    }
+
+    /// Sets the quotas of the requests signed by each key of the TSIG keyring, by default the
+    ///  requests are not limited
+    ///
+    /// Requests exceeding their quota are answered with REFUSED, the quotas keep the metrics of
+    ///  the requests allowed and refused. Only applies to sockets and listeners registered after
+    ///  this call.
+    pub fn set_quotas(&mut self, quotas: Arc<IdentityQuotas>) {
+        self.quotas = Some(quotas);
+    }
+
+    /// Sets the time within which a request must be handled, otherwise it is answered with a
+    ///  `ServFail`, e.g. when the upstream of a forwarder does not respond
+    ///
+    /// By default there is no deadline. Only applies to sockets and listeners registered after
+    ///  this call.
+    pub fn set_request_deadline(&mut self, request_deadline: Duration) {
+        self.request_deadline = Some(request_deadline);
+    }
+
+    /// Sets the largest response sent over UDP, larger responses are truncated
+    ///
+    /// Responses are also truncated to the payload advertised by the requester, or to 512 bytes
+    ///  without EDNS. The default is 1232, as recommended by the DNS Flag Day 2020, larger
+    ///  responses may be fragmented and the fragments dropped. Only applies to sockets registered
+    ///  after this call.
+    pub fn set_max_udp_payload(&mut self, max_udp_payload: u16) {
+        self.policy.set_max_udp_payload(max_udp_payload);
+    }
+
+    /// Sets the answers depending on the protocol of the requests, e.g. refusing ANY queries over
+    ///  UDP, see `ProtocolPolicy`. Only applies to sockets and listeners registered after this call.
+    pub fn set_protocol_policy(&mut self, policy: ProtocolPolicy) {
+        self.policy = policy;
+    }
+
+    /// Sets the socket options of the TCP connections, e.g. TCP Fast Open on the listeners, and
+    ///  `nodelay` for the responses over TLS not to wait for the acknowledgement of the previous
+    ///  ones. Only applies to listeners registered after this call.
+    pub fn set_tcp_options(&mut self, tcp_options: TcpOptions) {
+        self.tcp_options = tcp_options;
+    }
+
+    /// Receives and sends the datagrams of the UDP sockets in batches, with the generic receive
+    ///  and segmentation offloads of Linux, see `UdpOffload`
+    ///
+    /// The offloads not supported by the kernel are not used. Disabled by default, only applies
+    ///  to sockets registered after this call.
+    pub fn set_udp_offload(&mut self, udp_offload: bool) {
+        self.udp_offload = udp_offload;
+    }
+
+    /// Sets the health reported by the `/healthz` and `/readyz` endpoints of the HTTPS and HTTP
+    ///  listeners, by default there are no such endpoints
+    ///
+    /// Only applies to listeners registered after this call.
+    pub fn set_health(&mut self, health: Arc<Health>) {
+        self.health = Some(health);
+    }
+
     /// Register a UDP socket. Should be bound before calling this function.
     pub fn register_socket(&self, socket: tokio_udp::UdpSocket) {
         debug!("registered udp: {:?}", socket);
 
         // create the new UdpStream
-        let (buf_stream, stream_handle) = UdpStream::with_bound(socket);
+        let (buf_stream, stream_handle) = if self.udp_offload {
+            UdpStream::with_offload(socket)
+        } else {
+            UdpStream::with_bound(socket)
+        };
         //let request_stream = RequestStream::new(buf_stream, stream_handle);
         let handler = self.handler.clone();
+        let tsig_keyring = self.tsig_keyring.clone();
+        let quotas = self.quotas.clone();
+        let request_deadline = self.request_deadline;
+        let policy = self.policy;
 
         // this spawns a ForEach future which handles all the requests into a Handler.
         tokio_executor::spawn(
@@ -62,7 +158,16 @@ impl<T: RequestHandler> ServerFuture<T> {
                 .for_each(move |message| {
                     let src_addr = message.addr();
                     debug!("received udp request from: {}", src_addr);
-                    self::handle_raw_request(message, handler.clone(), stream_handle.clone())
+                    self::handle_raw_request(
+                        message,
+                        Protocol::Udp,
+                        tsig_keyring.clone(),
+                        quotas.clone(),
+                        request_deadline,
+                        policy,
+                        handler.clone(),
+                        stream_handle.clone(),
+                    )
                 }),
         );
     }
@@ -92,6 +197,12 @@ impl<T: RequestHandler> ServerFuture<T> {
         timeout: Duration,
     ) -> io::Result<()> {
         let handler = self.handler.clone();
+        let tsig_keyring = self.tsig_keyring.clone();
+        let quotas = self.quotas.clone();
+        let request_deadline = self.request_deadline;
+        let policy = self.policy;
+        let tcp_options = self.tcp_options;
+        tcp_options.set_listener_options(&listener)?;
         debug!("registered tcp: {:?}", listener);
 
         // for each incoming request...
@@ -101,11 +212,16 @@ impl<T: RequestHandler> ServerFuture<T> {
                 .for_each(move |tcp_stream| {
                     let src_addr = tcp_stream.peer_addr().unwrap();
                     debug!("accepted request from: {}", src_addr);
+                    if let Err(e) = tcp_options.set_stream_options(&tcp_stream) {
+                        warn!("failed to set the TCP options of {}: {}", src_addr, e);
+                    }
                     // take the created stream...
                     let (buf_stream, stream_handle) = TcpStream::from_stream(tcp_stream, src_addr);
                     let timeout_stream = TimeoutStream::new(buf_stream, timeout);
                     //let request_stream = RequestStream::new(timeout_stream, stream_handle);
                     let handler = handler.clone();
+                    let tsig_keyring = tsig_keyring.clone();
+                    let quotas = quotas.clone();
 
                     // and spawn to the io_loop
                     tokio_executor::spawn(
@@ -119,6 +235,11 @@ impl<T: RequestHandler> ServerFuture<T> {
                             .for_each(move |message| {
                                 self::handle_raw_request(
                                     message,
+                                    Protocol::Tcp,
+                                    tsig_keyring.clone(),
+                                    quotas.clone(),
+                                    request_deadline,
+                                    policy,
                                     handler.clone(),
                                     stream_handle.clone(),
                                 )
@@ -181,6 +302,12 @@ impl<T: RequestHandler> ServerFuture<T> {
 
         let ((cert, chain), key) = certificate_and_key;
         let handler = self.handler.clone();
+        let tsig_keyring = self.tsig_keyring.clone();
+        let quotas = self.quotas.clone();
+        let request_deadline = self.request_deadline;
+        let policy = self.policy;
+        let tcp_options = self.tcp_options;
+        tcp_options.set_listener_options(&listener)?;
         debug!("registered tcp: {:?}", listener);
 
         let tls_acceptor = tls_server::new_acceptor(cert, chain, key)?;
@@ -192,7 +319,12 @@ impl<T: RequestHandler> ServerFuture<T> {
                 .for_each(move |tcp_stream| {
                     let src_addr = tcp_stream.peer_addr().unwrap();
                     debug!("accepted request from: {}", src_addr);
+                    if let Err(e) = tcp_options.set_stream_options(&tcp_stream) {
+                        warn!("failed to set the TCP options of {}: {}", src_addr, e);
+                    }
                     let handler = handler.clone();
+                    let tsig_keyring = tsig_keyring.clone();
+                    let quotas = quotas.clone();
 
                     // take the created stream...
                     tls_acceptor
@@ -209,6 +341,8 @@ impl<T: RequestHandler> ServerFuture<T> {
                             let timeout_stream = TimeoutStream::new(buf_stream, timeout);
                             //let request_stream = RequestStream::new(timeout_stream, stream_handle);
                             let handler = handler.clone();
+                            let tsig_keyring = tsig_keyring.clone();
+                            let quotas = quotas.clone();
 
                             // and spawn to the io_loop
                             tokio_executor::spawn(
@@ -222,6 +356,11 @@ impl<T: RequestHandler> ServerFuture<T> {
                                     .for_each(move |message| {
                                         self::handle_raw_request(
                                             message,
+                                            Protocol::Tls,
+                                            tsig_keyring.clone(),
+                                            quotas.clone(),
+                                            request_deadline,
+                                            policy,
                                             handler.clone(),
                                             stream_handle.clone(),
                                         )
@@ -296,16 +435,26 @@ impl<T: RequestHandler> ServerFuture<T> {
         use trust_dns_rustls::{tls_from_stream, tls_server};
 
         let handler = self.handler.clone();
+        let tsig_keyring = self.tsig_keyring.clone();
+        let quotas = self.quotas.clone();
+        let request_deadline = self.request_deadline;
+        let policy = self.policy;
 
+        let tcp_options = self.tcp_options;
+        tcp_options.set_listener_options(&listener)?;
         debug!("registered tcp: {:?}", listener);
 
-        let tls_acceptor = tls_server::new_acceptor(certificate_and_key.0, certificate_and_key.1)
-            .map_err(|e| {
-            io::Error::new(
-                io::ErrorKind::Other,
-                format!("error creating TLS acceptor: {}", e),
-            )
-        })?;
+        let mut tls_acceptor =
+            tls_server::new_acceptor(certificate_and_key.0, certificate_and_key.1).map_err(
+                |e| {
+                    io::Error::new(
+                        io::ErrorKind::Other,
+                        format!("error creating TLS acceptor: {}", e),
+                    )
+                },
+            )?;
+        // HTTP/1.1 is for the reverse proxies which don't forward HTTP/2
+        tls_acceptor.set_protocols(&[b"h2".to_vec(), b"http/1.1".to_vec()]);
         let tls_acceptor = TlsAcceptor::from(Arc::new(tls_acceptor));
 
         // for each incoming request...
@@ -315,7 +464,12 @@ impl<T: RequestHandler> ServerFuture<T> {
                 .for_each(move |tcp_stream| {
                     let src_addr = tcp_stream.peer_addr().unwrap();
                     debug!("accepted request from: {}", src_addr);
+                    if let Err(e) = tcp_options.set_stream_options(&tcp_stream) {
+                        warn!("failed to set the TCP options of {}: {}", src_addr, e);
+                    }
                     let handler = handler.clone();
+                    let tsig_keyring = tsig_keyring.clone();
+                    let quotas = quotas.clone();
 
                     // TODO: need to consider timeout of total connect...
                     // take the created stream...
@@ -332,6 +486,8 @@ impl<T: RequestHandler> ServerFuture<T> {
                             let timeout_stream = TimeoutStream::new(buf_stream, timeout);
                             //let request_stream = RequestStream::new(timeout_stream, stream_handle);
                             let handler = handler.clone();
+                            let tsig_keyring = tsig_keyring.clone();
+                            let quotas = quotas.clone();
 
                             // and spawn to the io_loop
                             tokio_executor::spawn(
@@ -345,6 +501,11 @@ impl<T: RequestHandler> ServerFuture<T> {
                                     .for_each(move |message| {
                                         self::handle_raw_request(
                                             message,
+                                            Protocol::Tls,
+                                            tsig_keyring.clone(),
+                                            quotas.clone(),
+                                            request_deadline,
+                                            policy,
                                             handler.clone(),
                                             stream_handle.clone(),
                                         )
@@ -367,6 +528,63 @@ impl<T: RequestHandler> ServerFuture<T> {
         Ok(())
     }
 
+    /// Register a TcpListener for DNS over HTTP/1.1, without TLS, to the Server. This is for a
+    ///  reverse proxy which terminates the HTTPS connections of the clients, and should not be
+    ///  reachable by them directly.
+    ///
+    /// # Arguments
+    /// * `listener` - a bound TCP (needs to be on a different port from standard TCP connections) socket
+    /// * `timeout` - timeout duration of incoming requests, any connection that does not send
+    ///               requests within this time period will be closed.
+    /// * `dns_hostname` - the name of the server, which must be the authority of the requests
+    #[cfg(feature = "dns-over-https")]
+    pub fn register_http_listener(
+        &self,
+        listener: tokio_tcp::TcpListener,
+        // TODO: need to set a timeout between requests.
+        _timeout: Duration,
+        dns_hostname: String,
+    ) -> io::Result<()> {
+        use server::https_handler::h1_handler;
+
+        let dns_hostname = Arc::new(dns_hostname);
+        let handler = self.handler.clone();
+        let request_deadline = self.request_deadline;
+        let max_payload = self.policy.max_payload(Protocol::Https);
+        let health = self.health.clone();
+        let tcp_options = self.tcp_options;
+        tcp_options.set_listener_options(&listener)?;
+        debug!("registered http: {:?}", listener);
+
+        // for each incoming request...
+        tokio_executor::spawn(
+            listener
+                .incoming()
+                .for_each(move |tcp_stream| {
+                    let src_addr = tcp_stream.peer_addr()?;
+                    debug!("accepted request from: {}", src_addr);
+                    if let Err(e) = tcp_options.set_stream_options(&tcp_stream) {
+                        warn!("failed to set the TCP options of {}: {}", src_addr, e);
+                    }
+
+                    tokio_executor::spawn(h1_handler(
+                        handler.clone(),
+                        tcp_stream,
+                        src_addr,
+                        dns_hostname.clone(),
+                        request_deadline,
+                        max_payload,
+                        health.clone(),
+                    ));
+
+                    Ok(())
+                })
+                .map_err(|e| panic!("error in inbound http_stream: {}", e)),
+        );
+
+        Ok(())
+    }
+
     /// Register a TlsListener to the Server. The TlsListener should already be bound to either an
     /// IPv6 or an IPv4 address.
     ///
@@ -415,23 +633,34 @@ impl<T: RequestHandler> ServerFuture<T> {
         certificate_and_key: (Vec<Certificate>, PrivateKey),
         dns_hostname: String,
     ) -> io::Result<()> {
-        use futures::{future, Stream};
+        use futures::future::{self, Either};
+        use futures::Stream;
+        use rustls::Session;
         use tokio_rustls::TlsAcceptor;
 
-        use server::https_handler::h2_handler;
+        use server::https_handler::{h1_handler, h2_handler};
         use trust_dns_rustls::tls_server;
 
         let dns_hostname = Arc::new(dns_hostname);
         let handler = self.handler.clone();
+        let request_deadline = self.request_deadline;
+        let max_payload = self.policy.max_payload(Protocol::Https);
+        let health = self.health.clone();
+        let tcp_options = self.tcp_options;
+        tcp_options.set_listener_options(&listener)?;
         debug!("registered tcp: {:?}", listener);
 
-        let tls_acceptor = tls_server::new_acceptor(certificate_and_key.0, certificate_and_key.1)
-            .map_err(|e| {
-            io::Error::new(
-                io::ErrorKind::Other,
-                format!("error creating TLS acceptor: {}", e),
-            )
-        })?;
+        let mut tls_acceptor =
+            tls_server::new_acceptor(certificate_and_key.0, certificate_and_key.1).map_err(
+                |e| {
+                    io::Error::new(
+                        io::ErrorKind::Other,
+                        format!("error creating TLS acceptor: {}", e),
+                    )
+                },
+            )?;
+        // HTTP/1.1 is for the reverse proxies which don't forward HTTP/2
+        tls_acceptor.set_protocols(&[b"h2".to_vec(), b"http/1.1".to_vec()]);
         let tls_acceptor = TlsAcceptor::from(Arc::new(tls_acceptor));
 
         // for each incoming request...
@@ -445,21 +674,50 @@ impl<T: RequestHandler> ServerFuture<T> {
                 .for_each(move |tcp_stream| {
                     let src_addr = tcp_stream.peer_addr().unwrap();
                     debug!("accepted request from: {}", src_addr);
+                    if let Err(e) = tcp_options.set_stream_options(&tcp_stream) {
+                        warn!("failed to set the TCP options of {}: {}", src_addr, e);
+                    }
                     let handler = handler.clone();
                     let dns_hostname = dns_hostname.clone();
+                    let health = health.clone();
 
                     // TODO: need to consider timeout of total connect...
                     // take the created stream...
-                    tls_acceptor
-                        .accept(tcp_stream)
-                        .map_err(|e| warn!("tls error: {}", e))
-                        .and_then(move |tls_stream| {
-                            h2_handler(handler, tls_stream, src_addr, dns_hostname)
-                        })
-                    // FIXME: need to map this error to Ok, otherwise this is a DOS potential
-                    // .map_err(move |e| {
-                    //     debug!("error HTTPS handshake: {:?} error: {:?}", src_addr, e)
-                    // })
+                    tokio_executor::spawn(
+                        tls_acceptor
+                            .accept(tcp_stream)
+                            .map_err(move |e| {
+                                debug!("error HTTPS handshake: {:?} error: {}", src_addr, e)
+                            })
+                            .and_then(move |tls_stream| {
+                                let h2 =
+                                    tls_stream.get_ref().1.get_alpn_protocol() == Some(&b"h2"[..]);
+
+                                if h2 {
+                                    Either::A(h2_handler(
+                                        handler,
+                                        tls_stream,
+                                        src_addr,
+                                        dns_hostname,
+                                        request_deadline,
+                                        max_payload,
+                                        health,
+                                    ))
+                                } else {
+                                    Either::B(h1_handler(
+                                        handler,
+                                        tls_stream,
+                                        src_addr,
+                                        dns_hostname,
+                                        request_deadline,
+                                        max_payload,
+                                        health,
+                                    ))
+                                }
+                            }),
+                    );
+
+                    Ok(())
                 })
                 .map_err(|_| panic!("error in inbound https_stream"))
         }));
@@ -468,13 +726,23 @@ impl<T: RequestHandler> ServerFuture<T> {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn handle_raw_request<T: RequestHandler>(
     message: SerialMessage,
+    protocol: Protocol,
+    tsig_keyring: Arc<TsigKeyring>,
+    quotas: Option<Arc<IdentityQuotas>>,
+    request_deadline: Option<Duration>,
+    policy: ProtocolPolicy,
     request_handler: Arc<Mutex<T>>,
     response_handler: BufStreamHandle,
-) -> HandleRawRequest<T::ResponseFuture> {
+) -> HandleRawRequest<RequestDeadline<HandleRequest<HandleOpCode<T::ResponseFuture>>, ResponseHandle>>
+{
+    #[cfg(feature = "request-arena")]
+    let _arena = ArenaScope::enter();
+
     let src_addr = message.addr();
-    let response_handler = ResponseHandle::new(message.addr(), response_handler);
+    let mut response_handler = ResponseHandle::new(message.addr(), response_handler);
 
     // TODO: rather than decoding the message here, this RequestStream should instead
     //       forward the request to another sender such that we could pull serialization off
@@ -482,29 +750,140 @@ pub(crate) fn handle_raw_request<T: RequestHandler>(
     // decode any messages that are ready
     let mut decoder = BinDecoder::new(message.bytes());
     match MessageRequest::read(&mut decoder) {
-        Ok(message) => {
-            let handle_request =
-                self::handle_request(message, src_addr, request_handler, response_handler);
-            HandleRawRequest::HandleRequest(handle_request)
+        Ok(mut request) => {
+            request.limit_max_payload(policy.max_payload(protocol));
+            if let Some(max_size) = policy.max_response_size(&request, protocol) {
+                response_handler.set_max_size(max_size);
+            }
+
+            if policy.refuses(&request, protocol) {
+                warn!("request: {} refusing ANY over {}", request.id(), protocol);
+                let response = MessageResponseBuilder::new(Some(request.raw_queries()));
+                let result = response_handler.send_response(response.error_msg(
+                    request.id(),
+                    request.op_code(),
+                    ResponseCode::Refused,
+                ));
+
+                return HandleRawRequest::Result(result.err().unwrap_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::Other,
+                        format!("ANY refused over {} from: {}", protocol, src_addr),
+                    )
+                }));
+            }
+
+            let tsig_key =
+                match tsig_keyring.verify(&request, message.bytes(), &mut response_handler) {
+                    Ok(tsig_key) => tsig_key,
+                    Err(response_code) => {
+                        let response = MessageResponseBuilder::new(Some(request.raw_queries()));
+                        let result = response_handler.send_response(response.error_msg(
+                            request.id(),
+                            request.op_code(),
+                            response_code,
+                        ));
+
+                        return HandleRawRequest::Result(result.err().unwrap_or_else(|| {
+                            io::Error::new(
+                                io::ErrorKind::Other,
+                                format!("tsig verification failed from: {}", src_addr),
+                            )
+                        }));
+                    }
+                };
+
+            if let (Some(quotas), Some(tsig_key)) = (quotas, tsig_key.as_ref()) {
+                let kind = QuotaKind::of(&request);
+                if !quotas.check(tsig_key, kind) {
+                    warn!(
+                        "request: {} refused, {:?} quota of {} exceeded",
+                        request.id(),
+                        kind,
+                        tsig_key
+                    );
+                    let response = MessageResponseBuilder::new(Some(request.raw_queries()));
+                    let result = response_handler.send_response(response.error_msg(
+                        request.id(),
+                        request.op_code(),
+                        ResponseCode::Refused,
+                    ));
+
+                    return HandleRawRequest::Result(result.err().unwrap_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::Other,
+                            format!("quota of {} exceeded from: {}", tsig_key, src_addr),
+                        )
+                    }));
+                }
+            }
+
+            let handle_request = self::handle_request(
+                request,
+                src_addr,
+                protocol,
+                tsig_key,
+                request_handler,
+                response_handler.clone(),
+            );
+            HandleRawRequest::HandleRequest(RequestDeadline::new(
+                handle_request,
+                request_deadline,
+                message.bytes(),
+                response_handler,
+            ))
         }
-        Err(e) => HandleRawRequest::Result(e.into()),
+        Err(e) => HandleRawRequest::Result(send_form_err(message.bytes(), e, response_handler)),
+    }
+}
+
+/// Answers a request which could not be decoded with a `FormErr`, so that the client need not
+///  wait for a timeout
+///
+/// Nothing is sent if the header of the request can not be read, or if the message is a response,
+///  returning the decoding `error`.
+pub(crate) fn send_form_err<R: ResponseHandler>(
+    bytes: &[u8],
+    error: ProtoError,
+    response_handler: R,
+) -> io::Error {
+    let header = match Header::read(&mut BinDecoder::new(bytes)) {
+        Ok(header) => header,
+        Err(_) => return error.into(),
+    };
+    if header.message_type() == MessageType::Response {
+        return error.into();
     }
+
+    let response = MessageResponseBuilder::new(None);
+    let result = response_handler.send_response(response.error_msg(
+        header.id(),
+        header.op_code(),
+        ResponseCode::FormErr,
+    ));
+
+    result.err().unwrap_or_else(|| error.into())
 }
 
 pub(crate) fn handle_request<R: ResponseHandler, T: RequestHandler>(
     message: MessageRequest,
     src_addr: SocketAddr,
+    protocol: Protocol,
+    tsig_key: Option<Name>,
     request_handler: Arc<Mutex<T>>,
     response_handler: R,
-) -> T::ResponseFuture {
+) -> HandleRequest<HandleOpCode<T::ResponseFuture>> {
     let request = Request {
         message,
         src: src_addr,
+        protocol,
+        tsig_key,
     };
 
     info!(
-        "request: {} type: {:?} op_code: {:?} dnssec: {} {}",
+        "request: {} protocol: {} type: {:?} op_code: {:?} dnssec: {} {}",
         request.message.id(),
+        request.protocol,
         request.message.message_type(),
         request.message.op_code(),
         request.message.edns().map_or(false, Edns::dnssec_ok),
@@ -512,14 +891,133 @@ pub(crate) fn handle_request<R: ResponseHandler, T: RequestHandler>(
             .message
             .queries()
             .first()
-            .map(|q| q.original().to_string())
+            .map(|q| {
+                // names are written escaped, they are chosen by the client
+                let q = q.original();
+                format!(
+                    "name: {} type: {} class: {}",
+                    q.name().escaped(),
+                    q.query_type(),
+                    q.query_class()
+                )
+            })
             .unwrap_or_else(|| "empty_queries".to_string()),
     );
 
-    request_handler
+    #[cfg(feature = "tracing-spans")]
+    let span = ::tracing::info_span!(
+        "request",
+        id = request.message.id(),
+        src = %request.src,
+        protocol = %request.protocol
+    );
+    #[cfg(feature = "tracing-spans")]
+    let _enter = span.enter();
+
+    let handle_request = request_handler
         .lock()
         .expect("poisoned lock")
-        .handle_request(request, response_handler)
+        .handle_op_code(request, response_handler);
+
+    #[cfg(feature = "tracing-spans")]
+    let handle_request = ::tracing_futures::Instrument::instrument(handle_request, span.clone());
+
+    handle_request
+}
+
+/// The future handling a request, with the `tracing-spans` feature it is in the `request` span
+#[cfg(feature = "tracing-spans")]
+pub(crate) type HandleRequest<F> = ::tracing_futures::Instrumented<F>;
+/// The future handling a request
+#[cfg(not(feature = "tracing-spans"))]
+pub(crate) type HandleRequest<F> = F;
+
+/// Answers the request with a `ServFail` if it is not handled before the deadline, dropping the
+///  future handling it
+#[must_use = "futures do nothing unless polled"]
+pub(crate) struct RequestDeadline<F: Future<Item = (), Error = ()>, R: ResponseHandler> {
+    handle_request: F,
+    deadline: Option<(Delay, Vec<u8>, R)>,
+}
+
+impl<F: Future<Item = (), Error = ()>, R: ResponseHandler> RequestDeadline<F, R> {
+    /// Returns a new RequestDeadline
+    ///
+    /// # Arguments
+    ///
+    /// * `handle_request` - future handling the request
+    /// * `request_deadline` - time within which the request must be handled, none for no deadline
+    /// * `request` - the request, for the queries of the response
+    /// * `response_handler` - handle to which the response is sent at the deadline
+    pub(crate) fn new(
+        handle_request: F,
+        request_deadline: Option<Duration>,
+        request: &[u8],
+        response_handler: R,
+    ) -> Self {
+        let deadline = request_deadline.map(|request_deadline| {
+            (
+                Delay::new(Instant::now() + request_deadline),
+                request.to_vec(),
+                response_handler,
+            )
+        });
+
+        RequestDeadline {
+            handle_request,
+            deadline,
+        }
+    }
+}
+
+impl<F: Future<Item = (), Error = ()>, R: ResponseHandler> Future for RequestDeadline<F, R> {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        if let Async::Ready(()) = self.handle_request.poll()? {
+            return Ok(Async::Ready(()));
+        }
+
+        let (mut delay, request, response_handler) = match self.deadline.take() {
+            Some(deadline) => deadline,
+            None => return Ok(Async::NotReady),
+        };
+
+        match delay.poll() {
+            Ok(Async::NotReady) => {
+                self.deadline = Some((delay, request, response_handler));
+                return Ok(Async::NotReady);
+            }
+            Ok(Async::Ready(())) => (),
+            Err(e) => {
+                // the request is still handled, just without a deadline
+                warn!("request deadline failed: {}", e);
+                return Ok(Async::NotReady);
+            }
+        }
+
+        // the request was decoded before it was handled
+        let request = match MessageRequest::from_bytes(&request) {
+            Ok(request) => request,
+            Err(e) => {
+                warn!("failed to decode request at its deadline: {}", e);
+                return Ok(Async::Ready(()));
+            }
+        };
+
+        warn!("request: {} not handled before its deadline", request.id());
+        let response = MessageResponseBuilder::new(Some(request.raw_queries()));
+        if let Err(e) = response_handler.send_response(response.error_msg(
+            request.id(),
+            request.op_code(),
+            ResponseCode::ServFail,
+        )) {
+            warn!("failed to send response at the deadline: {}", e);
+        }
+
+        Ok(Async::Ready(()))
+    }
 }
 
 #[must_use = "futures do nothing unless polled"]
@@ -533,6 +1031,10 @@ impl<F: Future<Item = (), Error = ()>> Future for HandleRawRequest<F> {
     type Error = ();
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        // the handler and the response are in the scope of the request, as its decoding
+        #[cfg(feature = "request-arena")]
+        let _arena = ArenaScope::enter();
+
         match self {
             HandleRawRequest::HandleRequest(f) => f.poll(),
             HandleRawRequest::Result(res) => {
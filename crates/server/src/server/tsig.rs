@@ -0,0 +1,220 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! TSIG verification of requests and signing of their responses
+
+#[cfg(feature = "dnssec")]
+use std::collections::HashMap;
+#[cfg(feature = "dnssec")]
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "dnssec")]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use trust_dns::op::ResponseCode;
+#[cfg(feature = "dnssec")]
+use trust_dns::proto::error::ProtoResult;
+#[cfg(feature = "dnssec")]
+use trust_dns::proto::rr::dnssec::rdata::tsig::{self, TSIG};
+#[cfg(feature = "dnssec")]
+use trust_dns::rr::dnssec::TSigner;
+#[cfg(feature = "dnssec")]
+use trust_dns::rr::rdata::DNSSECRData;
+use trust_dns::rr::Name;
+#[cfg(feature = "dnssec")]
+use trust_dns::rr::{LowerName, RData};
+
+use authority::MessageRequest;
+use server::ResponseHandle;
+
+/// The TSIG keys accepted by the server
+///
+/// Requests signed with a key of the keyring are verified, and all their response messages are
+///  signed, e.g. every message of an AXFR. Requests failing verification are answered with
+///  NOTAUTH and the TSIG error, e.g. BADSIG. Unsigned requests are handled as before, the key
+///  which signed a request is passed to the handler in `Request::tsig_key`.
+///
+/// Signed requests are only verified over UDP, TCP and TLS, not over HTTPS.
+#[derive(Clone, Default)]
+pub struct TsigKeyring {
+    #[cfg(feature = "dnssec")]
+    keys: HashMap<LowerName, TSigner>,
+}
+
+impl TsigKeyring {
+    /// Creates an empty keyring
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the key, replacing any key with the same name
+    #[cfg(feature = "dnssec")]
+    pub fn add_key(&mut self, signer: TSigner) {
+        self.keys
+            .insert(LowerName::from(signer.signer_name()), signer);
+    }
+
+    /// Verifies the TSIG of the request, if it's signed the responses will be signed as well
+    ///
+    /// # Return
+    ///
+    /// The name of the key which signed the request, None if it's not signed, or the error to
+    ///  respond with, the TSIG error is then returned by the `response_handle`
+    #[cfg(feature = "dnssec")]
+    pub(crate) fn verify(
+        &self,
+        request: &MessageRequest,
+        raw_request: &[u8],
+        response_handle: &mut ResponseHandle,
+    ) -> Result<Option<Name>, ResponseCode> {
+        let (key_name, request_tsig) = match request.sig0().last().map(|r| (r.name(), r.rdata())) {
+            Some((name, RData::DNSSEC(DNSSECRData::TSIG(tsig)))) => (name.clone(), tsig.clone()),
+            _ => return Ok(None),
+        };
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        let signer = match self.keys.get(&LowerName::from(&key_name)) {
+            Some(signer) if signer.algorithm() == request_tsig.algorithm() => signer,
+            _ => {
+                warn!("request signed with unknown tsig key: {}", key_name);
+                response_handle.set_tsig(ResponseSigner::unsigned(
+                    key_name,
+                    &request_tsig,
+                    ResponseCode::BADKEY,
+                ));
+                return Err(ResponseCode::NotAuth);
+            }
+        };
+
+        let verified = tsig::signed_bitmessage_to_buf(None, raw_request, true)
+            .and_then(|(tbs, _)| signer.verify(&tbs, request_tsig.mac()));
+        if let Err(e) = verified {
+            warn!("request failed tsig verification: {}: {}", key_name, e);
+            response_handle.set_tsig(ResponseSigner::unsigned(
+                key_name,
+                &request_tsig,
+                ResponseCode::BADSIG,
+            ));
+            return Err(ResponseCode::NotAuth);
+        }
+
+        let mut responder = ResponseSigner::signed(signer.clone(), request_tsig.mac().to_vec());
+        if let Err(e) = signer.verify_time(request_tsig.time(), now) {
+            warn!("request failed tsig verification: {}: {}", key_name, e);
+            responder.error = ResponseCode::BADTIME;
+            response_handle.set_tsig(responder);
+            return Err(ResponseCode::NotAuth);
+        }
+
+        debug!("request verified with tsig key: {}", key_name);
+        response_handle.set_tsig(responder);
+        Ok(Some(key_name))
+    }
+
+    /// Without DNSSEC support TSIG records are not parsed, all requests are unsigned
+    #[cfg(not(feature = "dnssec"))]
+    pub(crate) fn verify(
+        &self,
+        _request: &MessageRequest,
+        _raw_request: &[u8],
+        _response_handle: &mut ResponseHandle,
+    ) -> Result<Option<Name>, ResponseCode> {
+        Ok(None)
+    }
+}
+
+/// Appends the TSIG to each response message of a TSIG signed request
+#[cfg(feature = "dnssec")]
+pub(crate) struct ResponseSigner {
+    // None when the key of the request is unknown or its MAC is invalid, responses are unsigned
+    signer: Option<TSigner>,
+    key_name: Name,
+    request_tsig: Option<TSIG>,
+    error: ResponseCode,
+    // the MAC of the previous message, the request MAC for the first response
+    previous_mac: Mutex<(Vec<u8>, bool)>,
+}
+
+#[cfg(feature = "dnssec")]
+impl ResponseSigner {
+    fn signed(signer: TSigner, request_mac: Vec<u8>) -> Self {
+        ResponseSigner {
+            key_name: signer.signer_name().clone(),
+            signer: Some(signer),
+            request_tsig: None,
+            error: ResponseCode::NoError,
+            previous_mac: Mutex::new((request_mac, true)),
+        }
+    }
+
+    fn unsigned(key_name: Name, request_tsig: &TSIG, error: ResponseCode) -> Self {
+        ResponseSigner {
+            signer: None,
+            key_name,
+            request_tsig: Some(request_tsig.clone()),
+            error,
+            previous_mac: Mutex::new((Vec::new(), true)),
+        }
+    }
+
+    /// Appends the TSIG record to the encoded response
+    pub(crate) fn sign(&self, response: &mut Vec<u8>) -> ProtoResult<()> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        match self.signer {
+            Some(ref signer) => {
+                let mut previous_mac = self.previous_mac.lock().expect("poisoned lock");
+                let (ref mut mac, ref mut first_message) = *previous_mac;
+
+                *mac = signer.sign_message_bytes(
+                    Some(&mac[..]),
+                    response,
+                    *first_message,
+                    now,
+                    self.error.into(),
+                )?;
+                *first_message = false;
+                Ok(())
+            }
+            None => {
+                let request_tsig = self
+                    .request_tsig
+                    .as_ref()
+                    .expect("unsigned responses must have the request tsig");
+                let oid = u16::from(response[0]) << 8 | u16::from(response[1]);
+                let tsig = TSIG::new(
+                    request_tsig.algorithm().clone(),
+                    now,
+                    request_tsig.fudge(),
+                    Vec::new(),
+                    oid,
+                    self.error.into(),
+                    Vec::new(),
+                );
+
+                tsig::append_tsig_record(
+                    response,
+                    &tsig::make_tsig_record(self.key_name.clone(), tsig),
+                )
+            }
+        }
+    }
+}
+
+#[cfg(feature = "dnssec")]
+impl ResponseHandle {
+    /// Signs all responses sent with this handle
+    pub(crate) fn set_tsig(&mut self, signer: ResponseSigner) {
+        self.tsig = Some(Arc::new(signer));
+    }
+}
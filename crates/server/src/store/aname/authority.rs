@@ -0,0 +1,327 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::future::{self, FutureResult};
+use futures::{Async, Future, Poll};
+
+use trust_dns::op::LowerQuery;
+use trust_dns::proto::rr::dnssec::rdata::key::KEY;
+use trust_dns::rr::dnssec::{DnsSecResult, Signer, SupportedAlgorithms};
+use trust_dns::rr::{LowerName, Name, Record, RecordSet, RecordType};
+use trust_dns_resolver::error::ResolveErrorKind;
+
+use authority::{
+    AuthLookup, Authority, LookupError, LookupObject, LookupRecords, MessageRequest, RecordOrder,
+    UpdateResult, ZoneType,
+};
+use store::aname::AnameConfig;
+use store::file::{FileAuthority, FileConfig};
+use store::forwarder::{ForwardAuthority, ForwardLookupFuture};
+use store::in_memory::InMemoryAuthority;
+
+/// An authority for a zone file, where the ANAME records with targets outside of the zone are
+///  flattened into the A and AAAA records of their target
+///
+/// ANAME records, [draft-ietf-dnsop-aname](https://tools.ietf.org/html/draft-ietf-dnsop-aname),
+///  are the CNAME-like records allowed at the apex of a zone. The zone answers the A and AAAA
+///  queries of their names with the addresses of the target, resolved upstream, and the ANAME
+///  record in the additional section. The TTL of the addresses is the lowest of the ANAME record
+///  and of the upstream records, which the upstream cache counts down, so the flattened answers
+///  expire with the upstream ones.
+///
+/// ANAME records with a target in the zone are flattened by the zone itself. The flattened
+///  answers of upstream addresses are not DNSSEC signed.
+pub struct AnameAuthority {
+    zone: InMemoryAuthority,
+    forwarder: ForwardAuthority,
+}
+
+impl AnameAuthority {
+    /// Creates a new authority for the zone, resolving the ANAME targets with the `forwarder`
+    pub fn new(zone: InMemoryAuthority, forwarder: ForwardAuthority) -> Self {
+        AnameAuthority { zone, forwarder }
+    }
+
+    /// Read the Authority for the origin from the specified configuration
+    ///
+    /// The returned future must be run in the background, it drives the forwarder.
+    pub fn try_from_config(
+        origin: Name,
+        zone_type: ZoneType,
+        allow_axfr: bool,
+        root_dir: Option<&Path>,
+        config: &AnameConfig,
+    ) -> Result<(Self, impl Future<Item = (), Error = ()>), String> {
+        let zone = FileAuthority::try_from_config(
+            origin,
+            zone_type,
+            allow_axfr,
+            root_dir,
+            &FileConfig {
+                zone_file_path: config.zone_file_path.clone(),
+            },
+        )?;
+
+        // the targets can be anywhere
+        let (forwarder, bg) =
+            ForwardAuthority::try_from_config(Name::root(), ZoneType::Forward, &config.forward)?;
+
+        Ok((AnameAuthority::new(zone.unwrap(), forwarder), bg))
+    }
+
+    /// The forwarder resolving the targets of the ANAME records
+    pub fn forwarder(&self) -> &ForwardAuthority {
+        &self.forwarder
+    }
+
+    /// Unwrap the InMemoryAuthority, ANAME records are no longer resolved upstream
+    pub fn unwrap(self) -> InMemoryAuthority {
+        self.zone
+    }
+}
+
+impl Deref for AnameAuthority {
+    type Target = InMemoryAuthority;
+
+    fn deref(&self) -> &Self::Target {
+        &self.zone
+    }
+}
+
+impl DerefMut for AnameAuthority {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.zone
+    }
+}
+
+impl Authority for AnameAuthority {
+    type Lookup = AuthLookup;
+    type LookupFuture = AnameLookupFuture;
+
+    /// What type is this zone
+    fn zone_type(&self) -> ZoneType {
+        self.zone.zone_type()
+    }
+
+    /// Return true if AXFR is allowed
+    fn is_axfr_allowed(&self) -> bool {
+        self.zone.is_axfr_allowed()
+    }
+
+    /// Dynamic updates are not supported
+    fn update(&self, _update: &MessageRequest) -> UpdateResult<bool> {
+        use proto::op::ResponseCode;
+        Err(ResponseCode::NotImp)
+    }
+
+    /// Get the origin of this zone, i.e. example.com is the origin for www.example.com
+    fn origin(&self) -> &LowerName {
+        self.zone.origin()
+    }
+
+    /// A and AAAA lookups of ANAME records with targets outside of the zone are resolved
+    ///  upstream, all others are answered from the zone
+    fn lookup(
+        &self,
+        name: &LowerName,
+        rtype: RecordType,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Self::LookupFuture {
+        // the zone lookup is ready immediately
+        let lookup = self
+            .zone
+            .lookup(name, rtype, is_secure, supported_algorithms)
+            .wait();
+
+        if rtype != RecordType::A && rtype != RecordType::AAAA {
+            return AnameLookupFuture::Zone(future::result(lookup));
+        }
+
+        // the zone answers with the ANAME record itself when it doesn't have the target
+        let aname = match lookup {
+            Ok(ref lookup) => lookup
+                .iter()
+                .next()
+                .filter(|record| record.rr_type() == RecordType::ANAME)
+                .cloned(),
+            Err(_) => None,
+        };
+        let target = match aname.as_ref().and_then(|r| r.rdata().as_aname()) {
+            Some(target) if !self.origin().zone_of(&LowerName::from(target)) => target.clone(),
+            _ => return AnameLookupFuture::Zone(future::result(lookup)),
+        };
+
+        debug!("resolving ANAME target: {} {}", target, rtype);
+        AnameLookupFuture::Flatten {
+            aname: aname.expect("ANAME record checked above"),
+            order: self.zone.record_order(rtype),
+            lookup: self.forwarder.lookup(
+                &LowerName::from(target),
+                rtype,
+                is_secure,
+                supported_algorithms,
+            ),
+            rtype,
+            supported_algorithms,
+        }
+    }
+
+    fn search(
+        &self,
+        query: &LowerQuery,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Box<dyn Future<Item = Self::Lookup, Error = LookupError> + Send> {
+        match query.query_type() {
+            RecordType::A | RecordType::AAAA => Box::new(self.lookup(
+                query.name(),
+                query.query_type(),
+                is_secure,
+                supported_algorithms,
+            )),
+            _ => self.zone.search(query, is_secure, supported_algorithms),
+        }
+    }
+
+    /// Get the NS, NameServer, record for the zone
+    fn ns(&self, is_secure: bool, supported_algorithms: SupportedAlgorithms) -> Self::LookupFuture {
+        AnameLookupFuture::Zone(self.zone.ns(is_secure, supported_algorithms))
+    }
+
+    /// Return the NSEC records based on the given name
+    fn get_nsec_records(
+        &self,
+        name: &LowerName,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Self::LookupFuture {
+        AnameLookupFuture::Zone(
+            self.zone
+                .get_nsec_records(name, is_secure, supported_algorithms),
+        )
+    }
+
+    /// Returns the SOA of the authority.
+    fn soa(&self) -> Self::LookupFuture {
+        AnameLookupFuture::Zone(self.zone.soa())
+    }
+
+    /// Returns the SOA record for the zone
+    fn soa_secure(
+        &self,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Self::LookupFuture {
+        AnameLookupFuture::Zone(self.zone.soa_secure(is_secure, supported_algorithms))
+    }
+
+    /// Add a (Sig0) key that is authorized to perform updates against this authority
+    fn add_update_auth_key(&mut self, name: Name, key: KEY) -> DnsSecResult<()> {
+        self.zone.add_update_auth_key(name, key)
+    }
+
+    /// Add Signer
+    fn add_zone_signing_key(&mut self, signer: Signer) -> DnsSecResult<()> {
+        self.zone.add_zone_signing_key(signer)
+    }
+
+    /// Spreads the expirations of the signatures over the jitter
+    fn set_signature_jitter(&mut self, jitter: Duration) -> DnsSecResult<()> {
+        self.zone.set_signature_jitter(jitter)
+    }
+
+    /// Sign the zone for DNSSEC
+    fn secure_zone(&mut self) -> DnsSecResult<()> {
+        Authority::secure_zone(&mut self.zone)
+    }
+}
+
+/// A future which resolves to the `AuthLookup` of an `AnameAuthority`
+#[allow(clippy::large_enum_variant)]
+pub enum AnameLookupFuture {
+    /// Answered from the zone, ready immediately
+    Zone(FutureResult<AuthLookup, LookupError>),
+    /// The upstream lookup of the target of an ANAME record, flattened into its name
+    Flatten {
+        /// the ANAME record, returned in the additional section
+        aname: Record,
+        /// the type of the query, A or AAAA
+        rtype: RecordType,
+        /// the order of the flattened records
+        order: RecordOrder,
+        /// the algorithms supported by the request
+        supported_algorithms: SupportedAlgorithms,
+        /// the upstream lookup of the target
+        lookup: ForwardLookupFuture,
+    },
+}
+
+impl Future for AnameLookupFuture {
+    type Item = AuthLookup;
+    type Error = LookupError;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        match self {
+            AnameLookupFuture::Zone(lookup) => lookup.poll(),
+            AnameLookupFuture::Flatten {
+                aname,
+                rtype,
+                order,
+                supported_algorithms,
+                lookup,
+            } => {
+                let resolved = match lookup.poll() {
+                    Ok(Async::Ready(resolved)) => resolved,
+                    Ok(Async::NotReady) => return Ok(Async::NotReady),
+                    Err(e) => {
+                        if let LookupError::ResolveError(ref error) = e {
+                            let kind = error.get_ref().kind();
+                            if let ResolveErrorKind::NoRecordsFound { .. } = *kind {
+                                // the ANAME exists, the target has no addresses of the type
+                                return Err(LookupError::NameExists);
+                            }
+                        }
+
+                        warn!("failed to resolve ANAME target of {}: {}", aname.name(), e);
+                        return Err(e);
+                    }
+                };
+
+                // the upstream answer may start with CNAMEs, only the addresses are flattened
+                let mut ttl = aname.ttl();
+                let mut rrset = RecordSet::new(aname.name(), *rtype, 0);
+                for record in resolved.iter().filter(|r| r.rr_type() == *rtype) {
+                    ttl = ttl.min(record.ttl());
+                    rrset.add_rdata(record.rdata().clone());
+                }
+
+                if rrset.is_empty() {
+                    return Err(LookupError::NameExists);
+                }
+                rrset.set_ttl(ttl);
+
+                let mut aname_rrset = RecordSet::new(aname.name(), RecordType::ANAME, 0);
+                aname_rrset.insert(aname.clone(), 0);
+
+                let answers = LookupRecords::new(false, *supported_algorithms, Arc::new(rrset))
+                    .with_order(*order);
+                let additionals =
+                    LookupRecords::new(false, *supported_algorithms, Arc::new(aname_rrset));
+                Ok(Async::Ready(AuthLookup::answers(
+                    answers,
+                    Some(additionals),
+                )))
+            }
+        }
+    }
+}
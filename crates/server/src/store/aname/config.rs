@@ -0,0 +1,18 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use store::forwarder::ForwardConfig;
+
+/// Configuration for zones with ANAME records, the targets outside of the zone are resolved
+///  upstream
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct AnameConfig {
+    /// path to the master file, all names are answered from the zone
+    pub zone_file_path: String,
+    /// upstream configuration resolving the targets of the ANAME records outside of the zone
+    pub forward: ForwardConfig,
+}
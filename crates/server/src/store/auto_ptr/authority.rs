@@ -0,0 +1,311 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::future::{self, Future};
+use futures::Stream;
+use tokio_timer::Interval;
+
+use trust_dns::op::{LowerQuery, ResponseCode};
+use trust_dns::rr::dnssec::SupportedAlgorithms;
+use trust_dns::rr::{LowerName, Name, RData, Record, RecordSet, RecordType, RrKey};
+
+use authority::{Authority, Catalog, LookupError, MessageRequest, UpdateResult, ZoneType};
+use store::auto_ptr::AutoPtrConfig;
+use store::file::{FileAuthority, FileConfig};
+use store::in_memory::InMemoryAuthority;
+
+/// A generated PTR record: its name, the name it points to and its TTL
+type Ptr = (Name, Name, u32);
+
+/// An authority for a reverse zone, with PTR records generated from the A and AAAA records of
+///  forward zones
+///
+/// The forward zones are looked up in the `Catalog`, so they may be of any type, and the PTR
+///  records follow the dynamic updates of the forward zones and the zones replaced at runtime.
+///  The forward zones are checked periodically, the PTR records are regenerated and the serial
+///  of the reverse zone incremented when the addresses changed.
+///
+/// Only the addresses within the reverse zone get PTR records, they have the TTL of their A or
+///  AAAA record. The records of the zone file, e.g. its SOA and NS records, are always served.
+pub struct AutoPtrAuthority {
+    zone: Arc<InMemoryAuthority>,
+}
+
+impl AutoPtrAuthority {
+    /// Creates a new authority for the reverse zone, with the records of the zone
+    ///
+    /// The returned future must be run in the background, it generates the PTR records.
+    ///
+    /// # Arguments
+    ///
+    /// * `zone` - the reverse zone, e.g. 0.168.192.in-addr.arpa.
+    /// * `catalog` - the catalog in which the forward zones are looked up
+    /// * `forward_zones` - the zones whose A and AAAA records get PTR records
+    /// * `refresh` - the time between each check of the forward zones
+    pub fn new(
+        zone: InMemoryAuthority,
+        catalog: Catalog,
+        forward_zones: Vec<LowerName>,
+        refresh: Duration,
+    ) -> (Self, impl Future<Item = (), Error = ()>) {
+        let zone = Arc::new(zone);
+        let generate = generate_ptrs(Arc::clone(&zone), catalog, forward_zones, refresh);
+
+        (AutoPtrAuthority { zone }, generate)
+    }
+
+    /// Read the Authority for the origin from the specified configuration
+    ///
+    /// The returned future must be run in the background, it generates the PTR records.
+    pub fn try_from_config(
+        origin: Name,
+        zone_type: ZoneType,
+        allow_axfr: bool,
+        root_dir: Option<&Path>,
+        catalog: Catalog,
+        config: &AutoPtrConfig,
+    ) -> Result<(Self, impl Future<Item = (), Error = ()>), String> {
+        let zone = FileAuthority::try_from_config(
+            origin.clone(),
+            zone_type,
+            allow_axfr,
+            root_dir,
+            &FileConfig {
+                zone_file_path: config.zone_file_path.clone(),
+            },
+        )?;
+
+        let forward_zones = config
+            .forward_zones
+            .iter()
+            .map(LowerName::from)
+            .collect::<Vec<_>>();
+        info!(
+            "auto PTR zone {} loaded, from zones: {:?}",
+            origin, forward_zones
+        );
+
+        Ok(AutoPtrAuthority::new(
+            zone.unwrap(),
+            catalog,
+            forward_zones,
+            config.get_refresh(),
+        ))
+    }
+}
+
+/// Returns a future which generates the PTR records on every refresh, it never completes
+fn generate_ptrs(
+    zone: Arc<InMemoryAuthority>,
+    catalog: Catalog,
+    forward_zones: Vec<LowerName>,
+    refresh: Duration,
+) -> impl Future<Item = (), Error = ()> {
+    // the PTR records are generated over the records of the zone file
+    let base = zone.records();
+
+    Interval::new(Instant::now(), refresh)
+        .map_err(|e| error!("auto PTR timer failed: {}", e))
+        .fold(Vec::new(), move |previous, _| {
+            let zone = Arc::clone(&zone);
+            let base = Arc::clone(&base);
+
+            lookup_ptrs(zone.origin(), &catalog, &forward_zones).map(move |ptrs| {
+                if ptrs != previous {
+                    update_zone(&zone, &base, &ptrs);
+                }
+
+                ptrs
+            })
+        })
+        .map(|_| ())
+}
+
+/// Looks up the A and AAAA records of the forward zones, returning the sorted PTR records
+fn lookup_ptrs(
+    origin: &LowerName,
+    catalog: &Catalog,
+    forward_zones: &[LowerName],
+) -> impl Future<Item = Vec<Ptr>, Error = ()> {
+    let lookups = forward_zones
+        .iter()
+        .filter_map(|forward_zone| {
+            let authority = catalog
+                .find(forward_zone)
+                .filter(|a| a.read().expect("authority poisoned").origin() == forward_zone);
+            let authority = match authority {
+                Some(authority) => authority,
+                None => {
+                    debug!("auto PTR forward zone not in the catalog: {}", forward_zone);
+                    return None;
+                }
+            };
+
+            let forward_zone = forward_zone.clone();
+            let origin = origin.clone();
+            let lookup = authority.read().expect("authority poisoned").lookup(
+                &forward_zone,
+                RecordType::AXFR,
+                false,
+                SupportedAlgorithms::new(),
+            );
+
+            Some(lookup.then(move |lookup| {
+                let lookup = match lookup {
+                    Ok(lookup) => lookup,
+                    Err(e) => {
+                        warn!("failed to lookup the records of {}: {}", forward_zone, e);
+                        return Ok(vec![]);
+                    }
+                };
+
+                let ptrs = lookup
+                    .iter()
+                    .filter(|record| !record.name().is_wildcard())
+                    .filter_map(|record| {
+                        let address = match *record.rdata() {
+                            RData::A(ip) => IpAddr::V4(ip),
+                            RData::AAAA(ip) => IpAddr::V6(ip),
+                            _ => return None,
+                        };
+
+                        Some((Name::from(address), record.name().clone(), record.ttl()))
+                    })
+                    .filter(|(name, _, _)| origin.zone_of(&LowerName::from(name)))
+                    .collect::<Vec<_>>();
+
+                Ok(ptrs)
+            }))
+        })
+        .collect::<Vec<_>>();
+
+    future::join_all(lookups).map(|ptrs: Vec<Vec<Ptr>>| {
+        let mut ptrs = ptrs.into_iter().flatten().collect::<Vec<_>>();
+        ptrs.sort();
+        ptrs.dedup();
+        ptrs
+    })
+}
+
+/// Replaces the PTR records of the zone, incrementing its serial
+fn update_zone(zone: &InMemoryAuthority, base: &BTreeMap<RrKey, Arc<RecordSet>>, ptrs: &[Ptr]) {
+    let soa_key = RrKey::new(zone.origin().clone(), RecordType::SOA);
+    let mut update = zone.begin_update();
+
+    // the serial keeps increasing from the current SOA, not from the one of the zone file
+    let soa = update.records().get(&soa_key).cloned();
+    *update.records_mut() = base.clone();
+    if let Some(soa) = soa {
+        update.records_mut().insert(soa_key, soa);
+    }
+
+    let serial = update.increment_soa_serial();
+    for &(ref name, ref target, ttl) in ptrs {
+        let record = Record::from_rdata(name.clone(), ttl, RData::PTR(target.clone()));
+        if !update.upsert(record, serial) {
+            warn!("failed to add PTR record {} to {}", name, target);
+        }
+    }
+
+    update.commit();
+    info!(
+        "auto PTR zone {} updated: {} records, serial {}",
+        zone.origin(),
+        ptrs.len(),
+        serial
+    );
+}
+
+impl Deref for AutoPtrAuthority {
+    type Target = InMemoryAuthority;
+
+    fn deref(&self) -> &Self::Target {
+        &self.zone
+    }
+}
+
+impl Authority for AutoPtrAuthority {
+    type Lookup = <InMemoryAuthority as Authority>::Lookup;
+    type LookupFuture = <InMemoryAuthority as Authority>::LookupFuture;
+
+    /// What type is this zone
+    fn zone_type(&self) -> ZoneType {
+        self.zone.zone_type()
+    }
+
+    /// Return true if AXFR is allowed
+    fn is_axfr_allowed(&self) -> bool {
+        self.zone.is_axfr_allowed()
+    }
+
+    /// Dynamic updates are not supported, the records are generated from the forward zones
+    fn update(&self, _update: &MessageRequest) -> UpdateResult<bool> {
+        Err(ResponseCode::NotImp)
+    }
+
+    /// Get the origin of this zone, i.e. example.com is the origin for www.example.com
+    fn origin(&self) -> &LowerName {
+        self.zone.origin()
+    }
+
+    fn lookup(
+        &self,
+        name: &LowerName,
+        rtype: RecordType,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Self::LookupFuture {
+        self.zone
+            .lookup(name, rtype, is_secure, supported_algorithms)
+    }
+
+    fn search(
+        &self,
+        query: &LowerQuery,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Box<dyn Future<Item = Self::Lookup, Error = LookupError> + Send> {
+        self.zone.search(query, is_secure, supported_algorithms)
+    }
+
+    /// Get the NS, NameServer, record for the zone
+    fn ns(&self, is_secure: bool, supported_algorithms: SupportedAlgorithms) -> Self::LookupFuture {
+        self.zone.ns(is_secure, supported_algorithms)
+    }
+
+    /// Return the NSEC records based on the given name
+    fn get_nsec_records(
+        &self,
+        name: &LowerName,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Self::LookupFuture {
+        self.zone
+            .get_nsec_records(name, is_secure, supported_algorithms)
+    }
+
+    /// Returns the SOA of the authority.
+    fn soa(&self) -> Self::LookupFuture {
+        self.zone.soa()
+    }
+
+    /// Returns the SOA record for the zone
+    fn soa_secure(
+        &self,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Self::LookupFuture {
+        self.zone.soa_secure(is_secure, supported_algorithms)
+    }
+}
@@ -0,0 +1,29 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::time::Duration;
+
+use trust_dns::rr::Name;
+
+/// Configuration for reverse zones generated from forward zones
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct AutoPtrConfig {
+    /// path to the master file of the reverse zone, e.g. with its SOA and NS records, the PTR
+    ///  records are added to those of the file
+    pub zone_file_path: String,
+    /// the zones of the catalog whose A and AAAA records within the reverse zone get PTR records
+    pub forward_zones: Vec<Name>,
+    /// seconds between each check of the forward zones for changed records, defaults to 5
+    pub refresh: Option<u64>,
+}
+
+impl AutoPtrConfig {
+    /// Time between each check of the forward zones
+    pub fn get_refresh(&self) -> Duration {
+        Duration::from_secs(self.refresh.unwrap_or(5))
+    }
+}
@@ -0,0 +1,405 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::fmt::{self, Display};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use futures::sync::oneshot;
+use futures::{future, Async, Future, Poll, Stream};
+use tokio_timer::Interval;
+
+use trust_dns::op::LowerQuery;
+use trust_dns::op::ResponseCode;
+use trust_dns::rr::dnssec::SupportedAlgorithms;
+use trust_dns::rr::{LowerName, Name, RData, Record, RecordType};
+
+use authority::{Authority, LookupError, LookupObject, MessageRequest, UpdateResult, ZoneType};
+use store::blocklist::fetch::ListUrl;
+use store::blocklist::{BlockAction, BlocklistConfig, DomainList};
+use store::forwarder::{ForwardAuthority, ForwardLookup, ForwardLookupFuture};
+
+static DEFAULT_BLOCK_TTL: u32 = 60;
+
+/// The block and allow lists, shared with the background refresh
+#[derive(Debug, Default)]
+struct Lists {
+    blocked: DomainList,
+    allowed: DomainList,
+}
+
+impl Lists {
+    /// Reads the files and downloads the URLs of the lists, this blocks on the disk and network
+    fn read(block_lists: &[ListSource], allow_lists: &[ListSource]) -> io::Result<Self> {
+        Ok(Lists {
+            blocked: read_lists(block_lists)?,
+            allowed: read_lists(allow_lists)?,
+        })
+    }
+
+    fn is_blocked(&self, name: &LowerName) -> bool {
+        self.blocked.contains(name) && !self.allowed.contains(name)
+    }
+}
+
+fn read_lists(sources: &[ListSource]) -> io::Result<DomainList> {
+    let mut list = DomainList::new();
+    for source in sources {
+        let read = source.read().map_err(|e| {
+            io::Error::new(e.kind(), format!("failed to read list {}: {}", source, e))
+        })?;
+
+        list.extend(read);
+    }
+
+    Ok(list)
+}
+
+/// Where a list is read from
+#[derive(Clone, Debug)]
+enum ListSource {
+    /// A local file
+    File(PathBuf),
+    /// A list downloaded from an http or https URL
+    Url(ListUrl),
+}
+
+impl ListSource {
+    /// The source of a list, a URL or the path of a file relative to the `root_dir`
+    fn new(list: &str, root_dir: Option<&Path>) -> Result<Self, String> {
+        let is_url = list.find("://").map_or(false, |end| {
+            let scheme = &list[..end];
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.')
+        });
+
+        if is_url {
+            return ListUrl::parse(list).map(ListSource::Url);
+        }
+
+        Ok(ListSource::File(
+            root_dir.map_or_else(|| PathBuf::from(list), |d| d.join(list)),
+        ))
+    }
+
+    fn read(&self) -> io::Result<DomainList> {
+        match *self {
+            ListSource::File(ref path) => DomainList::from_path(path),
+            ListSource::Url(ref url) => url.fetch(),
+        }
+    }
+}
+
+impl Display for ListSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ListSource::File(ref path) => write!(f, "{}", path.display()),
+            ListSource::Url(ref url) => write!(f, "{}", url),
+        }
+    }
+}
+
+/// Reads the lists on a thread of its own, the reads of the files and the downloads block
+///
+/// The future never fails, a failure to read the lists is its result.
+fn read_in_background(
+    block_lists: Vec<ListSource>,
+    allow_lists: Vec<ListSource>,
+) -> impl Future<Item = io::Result<Lists>, Error = ()> {
+    let (tx, rx) = oneshot::channel();
+    let spawned = thread::Builder::new()
+        .name("trust-dns-blocklist".to_string())
+        .spawn(move || {
+            // the refresh may have been dropped, e.g. when the server shuts down
+            tx.send(Lists::read(&block_lists, &allow_lists)).ok();
+        });
+
+    if let Err(e) = spawned {
+        return future::Either::A(future::ok(Err(e)));
+    }
+
+    future::Either::B(rx.or_else(|_| {
+        Ok(Err(io::Error::new(
+            io::ErrorKind::Other,
+            "the blocklist refresh thread panicked",
+        )))
+    }))
+}
+
+/// An authority which answers queries for blocked names locally, and forwards all other queries
+///
+/// This is a Pi-hole style filtering resolver, names are blocked based on hosts-format or
+///  domain-list files, local or downloaded from http and https URLs. The lists can be
+///  periodically re-read to pick up changes, the previous lists are kept when a refresh fails.
+pub struct BlocklistAuthority {
+    lists: Arc<RwLock<Lists>>,
+    block_action: BlockAction,
+    ttl: u32,
+    forwarder: ForwardAuthority,
+}
+
+impl BlocklistAuthority {
+    /// Creates a new authority which blocks names in `blocked`, except those in `allowed`
+    ///
+    /// # Arguments
+    ///
+    /// * `blocked` - names which will be answered according to the `block_action`
+    /// * `allowed` - names which are never blocked, even if they are in `blocked`
+    /// * `block_action` - how queries for blocked names are answered
+    /// * `ttl` - the TTL of the records synthesized for blocked names
+    /// * `forwarder` - the authority to which all queries for non-blocked names are forwarded
+    pub fn new(
+        blocked: DomainList,
+        allowed: DomainList,
+        block_action: BlockAction,
+        ttl: u32,
+        forwarder: ForwardAuthority,
+    ) -> Self {
+        BlocklistAuthority {
+            lists: Arc::new(RwLock::new(Lists { blocked, allowed })),
+            block_action,
+            ttl,
+            forwarder,
+        }
+    }
+
+    /// Read the Authority for the origin from the specified configuration
+    ///
+    /// The lists are read, and downloaded, before this returns, a list which fails to be read is
+    ///  an error. The returned future must be run in the background, it drives the forwarder
+    ///  and, if configured, the refresh of the lists.
+    pub fn try_from_config(
+        origin: Name,
+        zone_type: ZoneType,
+        root_dir: Option<&Path>,
+        config: &BlocklistConfig,
+    ) -> Result<(Self, impl Future<Item = (), Error = ()>), String> {
+        info!("loading blocklist config: {}", origin);
+
+        let to_source = |list: &String| ListSource::new(list, root_dir);
+        let block_lists = config
+            .block_lists
+            .iter()
+            .map(to_source)
+            .collect::<Result<Vec<ListSource>, String>>()?;
+        let allow_lists = config
+            .allow_lists
+            .iter()
+            .map(to_source)
+            .collect::<Result<Vec<ListSource>, String>>()?;
+
+        let lists = Lists::read(&block_lists, &allow_lists).map_err(|e| e.to_string())?;
+        info!(
+            "blocklist loaded: {} blocked, {} allowed",
+            lists.blocked.len(),
+            lists.allowed.len()
+        );
+
+        let (forwarder, forward_bg) =
+            ForwardAuthority::try_from_config(origin, zone_type, &config.forward)?;
+
+        let authority = BlocklistAuthority {
+            lists: Arc::new(RwLock::new(lists)),
+            block_action: config.block_action,
+            ttl: config.ttl.unwrap_or(DEFAULT_BLOCK_TTL),
+            forwarder,
+        };
+
+        let refresh = if let Some(interval) = config.refresh_interval {
+            let interval = Duration::from_secs(interval);
+            let lists = Arc::clone(&authority.lists);
+
+            future::Either::A(
+                Interval::new(Instant::now() + interval, interval)
+                    .map_err(|e| error!("blocklist refresh timer failed: {}", e))
+                    .for_each(move |_| {
+                        let lists = Arc::clone(&lists);
+
+                        // the next tick is only taken once this refresh is complete
+                        read_in_background(block_lists.clone(), allow_lists.clone()).map(
+                            move |read| match read {
+                                Ok(read) => {
+                                    debug!(
+                                        "blocklist refreshed: {} blocked, {} allowed",
+                                        read.blocked.len(),
+                                        read.allowed.len()
+                                    );
+                                    *lists.write().expect("blocklist poisoned") = read;
+                                }
+                                // keep serving the previous lists
+                                Err(e) => warn!("blocklist refresh failed: {}", e),
+                            },
+                        )
+                    }),
+            )
+        } else {
+            future::Either::B(future::ok(()))
+        };
+
+        Ok((authority, forward_bg.join(refresh).map(|_| ())))
+    }
+
+    /// Returns true if queries for the name are being blocked
+    pub fn is_blocked(&self, name: &LowerName) -> bool {
+        self.lists
+            .read()
+            .expect("blocklist poisoned")
+            .is_blocked(name)
+    }
+
+    /// The forwarder of the names which are not blocked
+    pub fn forwarder(&self) -> &ForwardAuthority {
+        &self.forwarder
+    }
+
+    fn blocked(&self, name: &LowerName, rtype: RecordType) -> BlocklistLookupFuture {
+        if self.block_action == BlockAction::NxDomain {
+            return BlocklistLookupFuture::Blocked(Some(Err(LookupError::from(
+                ResponseCode::NXDomain,
+            ))));
+        }
+
+        let rdata = match rtype {
+            RecordType::A => self.block_action.ipv4().map(RData::A),
+            RecordType::AAAA => self.block_action.ipv6().map(RData::AAAA),
+            _ => None,
+        };
+
+        let records = rdata
+            .map(|rdata| Record::from_rdata(name.into(), self.ttl, rdata))
+            .into_iter()
+            .collect();
+
+        BlocklistLookupFuture::Blocked(Some(Ok(BlocklistLookup::Blocked(records))))
+    }
+}
+
+impl Authority for BlocklistAuthority {
+    type Lookup = BlocklistLookup;
+    type LookupFuture = BlocklistLookupFuture;
+
+    /// Always Forward, only blocked names are answered locally
+    fn zone_type(&self) -> ZoneType {
+        ZoneType::Forward
+    }
+
+    /// Always false for Blocklist zones
+    fn is_axfr_allowed(&self) -> bool {
+        false
+    }
+
+    fn update(&self, _update: &MessageRequest) -> UpdateResult<bool> {
+        Err(ResponseCode::NotImp)
+    }
+
+    /// Get the origin of this zone, this is the origin of the forwarder
+    fn origin(&self) -> &LowerName {
+        self.forwarder.origin()
+    }
+
+    /// Answers the lookup locally if the name is blocked, otherwise forwards it
+    fn lookup(
+        &self,
+        name: &LowerName,
+        rtype: RecordType,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Self::LookupFuture {
+        if self.is_blocked(name) {
+            info!("blocked lookup: {} {}", name, rtype);
+            return self.blocked(name, rtype);
+        }
+
+        BlocklistLookupFuture::Forward(self.forwarder.lookup(
+            name,
+            rtype,
+            is_secure,
+            supported_algorithms,
+        ))
+    }
+
+    fn search(
+        &self,
+        query: &LowerQuery,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Box<dyn Future<Item = Self::Lookup, Error = LookupError> + Send> {
+        Box::new(self.lookup(
+            query.name(),
+            query.query_type(),
+            is_secure,
+            supported_algorithms,
+        ))
+    }
+
+    fn get_nsec_records(
+        &self,
+        _name: &LowerName,
+        _is_secure: bool,
+        _supported_algorithms: SupportedAlgorithms,
+    ) -> Self::LookupFuture {
+        BlocklistLookupFuture::Blocked(Some(Ok(BlocklistLookup::Blocked(vec![]))))
+    }
+}
+
+/// The result of a lookup against a `BlocklistAuthority`
+pub enum BlocklistLookup {
+    /// Records synthesized for a blocked name, may be empty
+    Blocked(Vec<Record>),
+    /// The upstream answer for a name which is not blocked
+    Forward(ForwardLookup),
+}
+
+impl LookupObject for BlocklistLookup {
+    fn is_empty(&self) -> bool {
+        match self {
+            BlocklistLookup::Blocked(records) => records.is_empty(),
+            BlocklistLookup::Forward(lookup) => lookup.is_empty(),
+        }
+    }
+
+    fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a Record> + Send + 'a> {
+        match self {
+            BlocklistLookup::Blocked(records) => Box::new(records.iter()),
+            BlocklistLookup::Forward(lookup) => lookup.iter(),
+        }
+    }
+
+    fn take_additionals(&mut self) -> Option<Box<dyn LookupObject>> {
+        None
+    }
+}
+
+/// A future which resolves to the `BlocklistLookup`
+pub enum BlocklistLookupFuture {
+    /// The blocked response, ready immediately
+    Blocked(Option<Result<BlocklistLookup, LookupError>>),
+    /// The pending upstream lookup
+    Forward(ForwardLookupFuture),
+}
+
+impl Future for BlocklistLookupFuture {
+    type Item = BlocklistLookup;
+    type Error = LookupError;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        match self {
+            BlocklistLookupFuture::Blocked(result) => result
+                .take()
+                .expect("BlocklistLookupFuture already complete")
+                .map(Async::Ready),
+            BlocklistLookupFuture::Forward(lookup) => Ok(Async::Ready(BlocklistLookup::Forward(
+                try_ready!(lookup.poll()),
+            ))),
+        }
+    }
+}
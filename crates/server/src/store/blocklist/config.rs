@@ -0,0 +1,79 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use store::forwarder::ForwardConfig;
+
+/// Configuration for blocklist zones, names not blocked are forwarded upstream
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct BlocklistConfig {
+    /// paths to hosts-format or domain-list files, every name in these lists is blocked
+    ///
+    /// The lists are local files, relative to the zone directory, or http URLs, https URLs
+    ///  require the `blocklist-https` feature. All lists are read again every
+    ///  `refresh_interval`.
+    pub block_lists: Vec<String>,
+    /// paths or URLs of hosts-format or domain-list files, names in these lists are never blocked
+    #[serde(default)]
+    pub allow_lists: Vec<String>,
+    /// how queries for blocked names are answered, defaults to NXDOMAIN
+    #[serde(default)]
+    pub block_action: BlockAction,
+    /// TTL for the synthesized answers to blocked names
+    pub ttl: Option<u32>,
+    /// seconds between each re-read of the lists, if not specified they are only read at startup
+    ///
+    /// The previous lists are kept if any list fails to be read or downloaded.
+    pub refresh_interval: Option<u64>,
+    /// upstream configuration for all names which are not blocked
+    pub forward: ForwardConfig,
+}
+
+/// The answer given for a blocked name
+#[derive(Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[serde(tag = "type")]
+#[serde(rename_all = "lowercase")]
+pub enum BlockAction {
+    /// Respond with NXDOMAIN
+    NxDomain,
+    /// Respond with `0.0.0.0` to A queries and `::` to AAAA queries
+    Unspecified,
+    /// Respond with the specified addresses, a query for a missing family is answered with no records
+    Address {
+        /// address returned to A queries
+        ipv4: Option<Ipv4Addr>,
+        /// address returned to AAAA queries
+        ipv6: Option<Ipv6Addr>,
+    },
+}
+
+impl BlockAction {
+    /// The address to return to A queries, None if no record should be returned
+    pub fn ipv4(&self) -> Option<Ipv4Addr> {
+        match *self {
+            BlockAction::NxDomain => None,
+            BlockAction::Unspecified => Some(Ipv4Addr::UNSPECIFIED),
+            BlockAction::Address { ipv4, .. } => ipv4,
+        }
+    }
+
+    /// The address to return to AAAA queries, None if no record should be returned
+    pub fn ipv6(&self) -> Option<Ipv6Addr> {
+        match *self {
+            BlockAction::NxDomain => None,
+            BlockAction::Unspecified => Some(Ipv6Addr::UNSPECIFIED),
+            BlockAction::Address { ipv6, .. } => ipv6,
+        }
+    }
+}
+
+impl Default for BlockAction {
+    fn default() -> Self {
+        BlockAction::NxDomain
+    }
+}
@@ -0,0 +1,179 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+use std::str::FromStr;
+
+use proto::rr::domain::TryParseIp;
+use trust_dns::rr::{LowerName, Name};
+
+/// A set of names, and wildcards of names, read from hosts-format or domain-list files
+///
+/// Both formats may be mixed in the same file:
+///
+/// ```text
+/// # hosts format, the address is ignored
+/// 0.0.0.0 ads.example.com tracker.example.com
+/// # domain-list format, one name per line
+/// metrics.example.net
+/// # wildcards match all names below the wildcard, but not the name itself
+/// *.doubleclick.example
+/// ```
+#[derive(Debug, Default)]
+pub struct DomainList {
+    names: HashSet<LowerName>,
+    wildcards: HashSet<LowerName>,
+}
+
+impl DomainList {
+    /// Creates an empty list
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads all the names from the file at `path` into a new list
+    pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut list = Self::new();
+        list.read_from(BufReader::new(File::open(path)?))?;
+        Ok(list)
+    }
+
+    /// Reads all names from the reader into this list
+    ///
+    /// Lines starting with `#` are comments, entries which are not valid names are skipped.
+    pub fn read_from<R: BufRead>(&mut self, reader: R) -> io::Result<()> {
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut fields = line.split_whitespace().peekable();
+
+            // hosts format, the first field is the address which is being mapped to
+            if fields.peek().and_then(|f| f.try_parse_ip()).is_some() {
+                fields.next();
+            }
+
+            for field in fields {
+                if field.try_parse_ip().is_some() {
+                    continue;
+                }
+
+                match Name::from_str(field) {
+                    // hosts files generally map localhost, which should never be blocked
+                    Ok(ref name) if name.is_localhost() => continue,
+                    Ok(mut name) => {
+                        name.set_fqdn(true);
+                        self.insert(name);
+                    }
+                    Err(e) => warn!("skipping invalid name in domain list: {}: {}", field, e),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Adds the name to the list, a wildcard name matches all names below its base name
+    pub fn insert(&mut self, name: Name) {
+        if name.is_wildcard() {
+            self.wildcards.insert(LowerName::from(name.base_name()));
+        } else {
+            self.names.insert(LowerName::from(name));
+        }
+    }
+
+    /// Adds all the names from the other list to this one
+    pub fn extend(&mut self, other: DomainList) {
+        self.names.extend(other.names);
+        self.wildcards.extend(other.wildcards);
+    }
+
+    /// Returns true if the name, or a wildcard covering the name, is in this list
+    pub fn contains(&self, name: &LowerName) -> bool {
+        if self.names.contains(name) {
+            return true;
+        }
+
+        if self.wildcards.is_empty() {
+            return false;
+        }
+
+        let mut name = name.clone();
+        while !name.is_root() {
+            name = name.base_name();
+            if self.wildcards.contains(&name) {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// The number of names and wildcards in the list
+    pub fn len(&self) -> usize {
+        self.names.len() + self.wildcards.len()
+    }
+
+    /// Returns true if there are no names in the list
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(data: &str) -> DomainList {
+        let mut list = DomainList::new();
+        list.read_from(data.as_bytes())
+            .expect("failed to read list");
+        list
+    }
+
+    #[test]
+    fn test_hosts_format() {
+        let list = list(
+            "# comment\n\
+             127.0.0.1 localhost\n\
+             0.0.0.0 ads.example.com Tracker.Example.com # trailing comment\n\
+             ::1 ip6-localhost\n",
+        );
+
+        assert_eq!(list.len(), 3);
+        assert!(list.contains(&LowerName::from_str("ads.example.com.").unwrap()));
+        assert!(list.contains(&LowerName::from_str("tracker.example.com.").unwrap()));
+        assert!(!list.contains(&LowerName::from_str("localhost.").unwrap()));
+        assert!(!list.contains(&LowerName::from_str("www.example.com.").unwrap()));
+    }
+
+    #[test]
+    fn test_domain_list_format() {
+        let list = list("ads.example.com\n\n  metrics.example.net.  \n");
+
+        assert_eq!(list.len(), 2);
+        assert!(list.contains(&LowerName::from_str("ads.example.com.").unwrap()));
+        assert!(list.contains(&LowerName::from_str("metrics.example.net.").unwrap()));
+        assert!(!list.contains(&LowerName::from_str("www.ads.example.com.").unwrap()));
+    }
+
+    #[test]
+    fn test_wildcards() {
+        let list = list("*.ads.example.com\n");
+
+        assert!(list.contains(&LowerName::from_str("www.ads.example.com.").unwrap()));
+        assert!(list.contains(&LowerName::from_str("a.b.ads.example.com.").unwrap()));
+        assert!(!list.contains(&LowerName::from_str("ads.example.com.").unwrap()));
+        assert!(!list.contains(&LowerName::from_str("example.com.").unwrap()));
+    }
+}
@@ -0,0 +1,274 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Downloads of the lists published at http and https URLs
+//!
+//! The download is a blocking `GET` over `std::net`, it is never run on the reactor, see the
+//!  refresh of the `BlocklistAuthority`.
+
+use std::fmt::{self, Display};
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use store::blocklist::DomainList;
+
+/// Timeout of the connection, and of each read and write of the download
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The largest list which is downloaded, larger responses are an error
+const MAX_LIST_LEN: u64 = 64 * 1024 * 1024;
+
+/// A list published at an http or https URL
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ListUrl {
+    url: String,
+    https: bool,
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl ListUrl {
+    /// Parses the URL of a list, only the `http` and `https` schemes are supported
+    pub fn parse(url: &str) -> Result<Self, String> {
+        let scheme_end = url
+            .find("://")
+            .ok_or_else(|| format!("blocklist {} is not a URL", url))?;
+        let https = match url[..scheme_end].to_ascii_lowercase().as_str() {
+            "http" => false,
+            "https" => true,
+            scheme => {
+                return Err(format!(
+                    "blocklist {} has an unsupported scheme {}, only http and https lists are \
+                     downloaded",
+                    url, scheme
+                ))
+            }
+        };
+
+        if https && !cfg!(feature = "blocklist-https") {
+            return Err(format!(
+                "blocklist {} is an https URL, which requires the blocklist-https feature",
+                url
+            ));
+        }
+
+        let rest = &url[scheme_end + 3..];
+        let rest = &rest[..rest.find('#').unwrap_or(rest.len())];
+        let (authority, path) = match rest.find('/') {
+            Some(slash) => (&rest[..slash], &rest[slash..]),
+            None => (rest, "/"),
+        };
+        if authority.contains('@') {
+            return Err(format!(
+                "blocklist {} has user info, which is not supported",
+                url
+            ));
+        }
+
+        let default_port = if https { 443 } else { 80 };
+        let (host, port) = match (authority.rfind(':'), authority.rfind(']')) {
+            // the colon of the port, not one of an ipv6 address
+            (Some(colon), bracket) if bracket.map_or(true, |b| b < colon) => {
+                let port = authority[colon + 1..]
+                    .parse::<u16>()
+                    .map_err(|e| format!("blocklist {} has an invalid port: {}", url, e))?;
+                (&authority[..colon], port)
+            }
+            _ => (authority, default_port),
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if host.is_empty() {
+            return Err(format!("blocklist {} has no host", url));
+        }
+
+        Ok(ListUrl {
+            url: url.to_string(),
+            https,
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+
+    /// Downloads the list and reads its names
+    pub fn fetch(&self) -> io::Result<DomainList> {
+        let body = self.get()?;
+
+        let mut list = DomainList::new();
+        list.read_from(&body[..])?;
+        Ok(list)
+    }
+
+    /// Returns the body of a `GET` of the URL, redirects are not followed
+    fn get(&self) -> io::Result<Vec<u8>> {
+        let stream = self.connect()?;
+        stream.set_read_timeout(Some(FETCH_TIMEOUT))?;
+        stream.set_write_timeout(Some(FETCH_TIMEOUT))?;
+
+        // HTTP/1.0, the response is not chunked and ends with the connection
+        let request = format!(
+            "GET {} HTTP/1.0\r\nHost: {}\r\nAccept: text/plain, */*\r\nUser-Agent: trust-dns\r\n\
+             Connection: close\r\n\r\n",
+            self.path, self.host
+        );
+
+        let response = if self.https {
+            exchange(tls::connect(&self.host, stream)?, request.as_bytes())?
+        } else {
+            exchange(stream, request.as_bytes())?
+        };
+
+        parse_response(&response)
+            .map(|body| body.to_vec())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn connect(&self) -> io::Result<TcpStream> {
+        let mut error = io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no addresses for {}", self.host),
+        );
+        for addr in (self.host.as_str(), self.port).to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, FETCH_TIMEOUT) {
+                Ok(stream) => return Ok(stream),
+                Err(e) => error = e,
+            }
+        }
+
+        Err(error)
+    }
+}
+
+impl Display for ListUrl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.url)
+    }
+}
+
+/// Sends the request and reads the whole response, up to the `MAX_LIST_LEN`
+fn exchange<S: Read + Write>(mut stream: S, request: &[u8]) -> io::Result<Vec<u8>> {
+    stream.write_all(request)?;
+    stream.flush()?;
+
+    let mut response = Vec::new();
+    stream.take(MAX_LIST_LEN + 1).read_to_end(&mut response)?;
+    if response.len() as u64 > MAX_LIST_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("list is larger than {} bytes", MAX_LIST_LEN),
+        ));
+    }
+
+    Ok(response)
+}
+
+/// Returns the body of a `200 OK` response
+fn parse_response(response: &[u8]) -> Result<&[u8], String> {
+    let head_end = response
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or("incomplete HTTP response")?;
+    let head = String::from_utf8_lossy(&response[..head_end]);
+
+    let status_line = head.lines().next().unwrap_or_default();
+    let mut fields = status_line.splitn(3, ' ');
+    let version = fields.next().unwrap_or_default();
+    let status = fields.next().unwrap_or_default();
+    if !version.starts_with("HTTP/") {
+        return Err(format!("invalid HTTP status line: {}", status_line));
+    }
+    if status != "200" {
+        return Err(format!("HTTP request failed: {}", status_line));
+    }
+
+    Ok(&response[head_end + 4..])
+}
+
+#[cfg(feature = "blocklist-https")]
+mod tls {
+    use std::io;
+    use std::net::TcpStream;
+    use std::sync::Arc;
+
+    use rustls::{ClientConfig, ClientSession, StreamOwned};
+    use webpki::DNSNameRef;
+    use webpki_roots;
+
+    pub fn connect(
+        host: &str,
+        stream: TcpStream,
+    ) -> io::Result<StreamOwned<ClientSession, TcpStream>> {
+        let mut config = ClientConfig::new();
+        config
+            .root_store
+            .add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
+
+        let name = DNSNameRef::try_from_ascii_str(host).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid TLS name: {}", host),
+            )
+        })?;
+        let session = ClientSession::new(&Arc::new(config), name);
+
+        Ok(StreamOwned::new(session, stream))
+    }
+}
+
+#[cfg(not(feature = "blocklist-https"))]
+mod tls {
+    use std::io;
+    use std::net::TcpStream;
+
+    pub fn connect(_host: &str, _stream: TcpStream) -> io::Result<TcpStream> {
+        unreachable!("https URLs are rejected without the blocklist-https feature")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_url() {
+        let url = ListUrl::parse("http://127.0.0.1:8080/lists/ads.hosts").unwrap();
+        assert!(!url.https);
+        assert_eq!(url.host, "127.0.0.1");
+        assert_eq!(url.port, 8080);
+        assert_eq!(url.path, "/lists/ads.hosts");
+
+        let url = ListUrl::parse("HTTP://example.com").unwrap();
+        assert_eq!(url.host, "example.com");
+        assert_eq!(url.port, 80);
+        assert_eq!(url.path, "/");
+
+        let url = ListUrl::parse("http://[::1]/hosts?v=1#top").unwrap();
+        assert_eq!(url.host, "::1");
+        assert_eq!(url.port, 80);
+        assert_eq!(url.path, "/hosts?v=1");
+
+        assert!(ListUrl::parse("ftp://example.com/hosts").is_err());
+        assert!(ListUrl::parse("http://user@example.com/hosts").is_err());
+        assert!(ListUrl::parse("http://example.com:http/hosts").is_err());
+        assert_eq!(
+            ListUrl::parse("https://example.com/hosts").is_ok(),
+            cfg!(feature = "blocklist-https")
+        );
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let body = parse_response(b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nads.").unwrap();
+        assert_eq!(body, b"ads.");
+
+        let error = parse_response(b"HTTP/1.1 301 Moved Permanently\r\n\r\n").unwrap_err();
+        assert!(error.contains("301"), "{}", error);
+        assert!(parse_response(b"HTTP/1.1 200 OK\r\n").is_err());
+    }
+}
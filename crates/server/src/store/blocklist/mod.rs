@@ -0,0 +1,19 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+#![cfg(feature = "trust-dns-resolver")]
+
+//! Blocklist based filtering, with all other names forwarded upstream
+
+mod authority;
+mod config;
+mod domain_list;
+mod fetch;
+
+pub use self::authority::{BlocklistAuthority, BlocklistLookup, BlocklistLookupFuture};
+pub use self::config::{BlockAction, BlocklistConfig};
+pub use self::domain_list::DomainList;
@@ -7,10 +7,22 @@
 
 //! Configuration for the stores
 
+#[cfg(feature = "trust-dns-resolver")]
+use store::aname::AnameConfig;
+use store::auto_ptr::AutoPtrConfig;
+#[cfg(feature = "trust-dns-resolver")]
+use store::blocklist::BlocklistConfig;
+use store::dhcp::DhcpConfig;
 use store::file::FileConfig;
 #[cfg(feature = "trust-dns-resolver")]
 use store::forwarder::ForwardConfig;
+use store::load_balancer::LoadBalancerConfig;
+#[cfg(feature = "trust-dns-resolver")]
+use store::local_data::LocalDataConfig;
 use store::sqlite::SqliteConfig;
+#[cfg(feature = "trust-dns-resolver")]
+use store::stub::StubConfig;
+use store::synth_ptr::SynthPtrConfig;
 
 /// Enumeration over all Store configurations
 #[derive(Deserialize, PartialEq, Debug)]
@@ -21,7 +33,33 @@ pub enum StoreConfig {
     File(FileConfig),
     /// Sqlite based configuration file
     Sqlite(SqliteConfig),
+    /// File based configuration, with health checked pools of addresses
+    #[serde(rename = "load_balancer")]
+    LoadBalancer(LoadBalancerConfig),
+    /// Reverse zone file, with the PTR records generated from the addresses of forward zones
+    #[serde(rename = "auto_ptr")]
+    AutoPtr(AutoPtrConfig),
+    /// A and PTR records of the active leases of a DHCP server lease file
+    Dhcp(DhcpConfig),
+    /// PTR records of the addresses of an ip6.arpa zone, and AAAA records of their names,
+    ///  synthesized from a template
+    #[serde(rename = "synth_ptr")]
+    SynthPtr(SynthPtrConfig),
     /// Forwarder, aka Resolver
     #[cfg(feature = "trust-dns-resolver")]
     Forward(ForwardConfig),
+    /// Blocklist, forwarding all names which are not blocked
+    #[cfg(feature = "trust-dns-resolver")]
+    Blocklist(BlocklistConfig),
+    /// Local records overriding the upstream answers, forwarding all other names
+    #[cfg(feature = "trust-dns-resolver")]
+    #[serde(rename = "local_data")]
+    LocalData(LocalDataConfig),
+    /// Stub zone, resolved by the authoritative name servers learned from its NS records
+    #[cfg(feature = "trust-dns-resolver")]
+    Stub(StubConfig),
+    /// File based configuration, with the targets of ANAME records outside of the zone resolved
+    ///  upstream
+    #[cfg(feature = "trust-dns-resolver")]
+    Aname(AnameConfig),
 }
@@ -0,0 +1,321 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::fs;
+use std::net::IpAddr;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use futures::{Future, Stream};
+use tokio_timer::Interval;
+
+use trust_dns::op::{LowerQuery, ResponseCode};
+use trust_dns::rr::dnssec::SupportedAlgorithms;
+use trust_dns::rr::rdata::SOA;
+use trust_dns::rr::{LowerName, Name, RData, Record, RecordType, RrKey};
+
+use authority::{Authority, LookupError, MessageRequest, UpdateResult, ZoneType};
+use store::dhcp::{read_leases_from_path, DhcpConfig, Lease, LeaseFormat};
+use store::in_memory::InMemoryAuthority;
+
+/// An authority for the hosts which were leased an address by a DHCP server
+///
+/// The active leases with a hostname are answered with an A record of the hostname in the
+///  domain, e.g. laptop.lan., and a PTR record of the address to that name. Only the records
+///  within the zone are served, so a forward zone, e.g. lan., and a reverse zone, e.g.
+///  1.168.192.in-addr.arpa., can be served from the same lease file.
+///
+/// The lease file is checked periodically, it is read again when it was modified. The zone has a
+///  generated SOA record, its serial is incremented when the records changed, e.g. when a lease
+///  was added or ended.
+pub struct DhcpAuthority {
+    zone: Arc<InMemoryAuthority>,
+    domain: Name,
+    ttl: u32,
+}
+
+impl DhcpAuthority {
+    /// Creates a new authority without leases
+    ///
+    /// # Arguments
+    ///
+    /// * `origin` - the zone, the domain itself or a reverse zone
+    /// * `domain` - the domain of the hostnames, e.g. lan.
+    /// * `ttl` - TTL of the records
+    pub fn new(origin: Name, domain: Name, ttl: u32) -> Self {
+        let mut zone = InMemoryAuthority::empty(origin.clone(), ZoneType::Master, false);
+        let soa = SOA::new(
+            origin.clone(),
+            Name::from_ascii("hostmaster")
+                .expect("bad hostmaster label")
+                .append_domain(&origin),
+            1,
+            3600,
+            600,
+            86400,
+            ttl,
+        );
+        zone.upsert(Record::from_rdata(origin, ttl, RData::SOA(soa)), 1);
+
+        DhcpAuthority {
+            zone: Arc::new(zone),
+            domain,
+            ttl,
+        }
+    }
+
+    /// Read the Authority for the origin from the specified configuration
+    ///
+    /// The returned future must be run in the background, it watches the lease file.
+    pub fn try_from_config(
+        origin: Name,
+        _zone_type: ZoneType,
+        root_dir: Option<&Path>,
+        config: &DhcpConfig,
+    ) -> Result<(Self, impl Future<Item = (), Error = ()>), String> {
+        let domain = config.domain.clone().unwrap_or_else(|| origin.clone());
+        let path = root_dir.map_or_else(
+            || PathBuf::from(&config.lease_file_path),
+            |d| d.join(&config.lease_file_path),
+        );
+        info!(
+            "loading DHCP leases of {} from {} into {}",
+            domain,
+            path.display(),
+            origin
+        );
+
+        let authority = DhcpAuthority::new(origin, domain, config.get_ttl());
+        let watch = watch_leases(
+            authority.clone_shared(),
+            path,
+            config.get_format(),
+            config.get_watch_interval(),
+        );
+
+        Ok((authority, watch))
+    }
+
+    /// Replaces the records of the zone with those of the leases active at `now`
+    ///
+    /// # Arguments
+    ///
+    /// * `leases` - the leases, e.g. as read by `read_leases`
+    /// * `now` - seconds since the unix epoch
+    ///
+    /// # Return
+    ///
+    /// The records of the zone, without the SOA
+    pub fn update_leases(&self, leases: &[Lease], now: u64) -> Vec<Record> {
+        let records = self.records_of(leases, now);
+        let soa_key = RrKey::new(self.zone.origin().clone(), RecordType::SOA);
+
+        let mut update = self.zone.begin_update();
+        update.records_mut().retain(|key, _| *key == soa_key);
+        let serial = update.increment_soa_serial();
+        for record in &records {
+            if !update.upsert(record.clone(), serial) {
+                warn!("failed to add lease record: {}", record.name());
+            }
+        }
+        update.commit();
+
+        debug!(
+            "DHCP zone {} updated: {} records, serial {}",
+            self.zone.origin(),
+            records.len(),
+            serial
+        );
+        records
+    }
+
+    /// The A and PTR records of the active leases within the zone, sorted
+    fn records_of(&self, leases: &[Lease], now: u64) -> Vec<Record> {
+        let origin = self.zone.origin();
+        let mut records = Vec::new();
+
+        for lease in leases.iter().filter(|lease| lease.is_active_at(now)) {
+            let name = match host_name(&lease.hostname, &self.domain) {
+                Some(name) => name,
+                None => {
+                    debug!("skipping lease of bad hostname: {}", lease.hostname);
+                    continue;
+                }
+            };
+
+            let ptr_name = Name::from(IpAddr::V4(lease.address));
+            if origin.zone_of(&LowerName::from(&name)) {
+                let rdata = RData::A(lease.address);
+                records.push(Record::from_rdata(name.clone(), self.ttl, rdata));
+            }
+            if origin.zone_of(&LowerName::from(&ptr_name)) {
+                let rdata = RData::PTR(name);
+                records.push(Record::from_rdata(ptr_name, self.ttl, rdata));
+            }
+        }
+
+        records.sort();
+        records.dedup();
+        records
+    }
+
+    /// A handle to the same zone, for the background watch of the lease file
+    fn clone_shared(&self) -> Self {
+        DhcpAuthority {
+            zone: Arc::clone(&self.zone),
+            domain: self.domain.clone(),
+            ttl: self.ttl,
+        }
+    }
+}
+
+/// The name of the host in the domain, from the first label of the hostname sent by the client
+fn host_name(hostname: &str, domain: &Name) -> Option<Name> {
+    let label = hostname.split('.').next().unwrap_or_default();
+    let is_valid = |c: char| c.is_ascii_alphanumeric() || c == '-';
+    if label.is_empty() || !label.chars().all(is_valid) {
+        return None;
+    }
+
+    let name = Name::from_ascii(label).ok()?;
+    Some(name.to_lowercase().append_domain(domain))
+}
+
+/// Returns a future which reads the lease file on every interval if it was modified, and updates
+///  the records of the zone if they changed, it never completes
+fn watch_leases(
+    authority: DhcpAuthority,
+    path: PathBuf,
+    format: LeaseFormat,
+    interval: Duration,
+) -> impl Future<Item = (), Error = ()> {
+    // the modification time of the lease file when it was read, its leases, and the records
+    let state: (Option<SystemTime>, Vec<Lease>, Option<Vec<Record>>) = (None, vec![], None);
+
+    Interval::new(Instant::now(), interval)
+        .map_err(|e| error!("DHCP lease watch timer failed: {}", e))
+        .fold(state, move |(modified, leases, records), _| {
+            let (modified, leases) = match fs::metadata(&path).and_then(|m| m.modified()) {
+                Ok(now_modified) if Some(now_modified) == modified => (modified, leases),
+                Ok(now_modified) => match read_leases_from_path(format, &path) {
+                    Ok(read) => {
+                        info!("read {} DHCP leases from {}", read.len(), path.display());
+                        (Some(now_modified), read)
+                    }
+                    // keep serving the previous leases
+                    Err(e) => {
+                        warn!("failed to read leases from {}: {}", path.display(), e);
+                        (modified, leases)
+                    }
+                },
+                Err(e) => {
+                    warn!("failed to check lease file {}: {}", path.display(), e);
+                    (modified, leases)
+                }
+            };
+
+            // leases also end while the file is not modified
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|now| now.as_secs())
+                .unwrap_or_default();
+            let current = authority.records_of(&leases, now);
+            let records = if records.as_ref() != Some(&current) {
+                Some(authority.update_leases(&leases, now))
+            } else {
+                records
+            };
+
+            Ok((modified, leases, records))
+        })
+        .map(|_| ())
+}
+
+impl Deref for DhcpAuthority {
+    type Target = InMemoryAuthority;
+
+    fn deref(&self) -> &Self::Target {
+        &self.zone
+    }
+}
+
+impl Authority for DhcpAuthority {
+    type Lookup = <InMemoryAuthority as Authority>::Lookup;
+    type LookupFuture = <InMemoryAuthority as Authority>::LookupFuture;
+
+    /// What type is this zone
+    fn zone_type(&self) -> ZoneType {
+        self.zone.zone_type()
+    }
+
+    /// Return true if AXFR is allowed
+    fn is_axfr_allowed(&self) -> bool {
+        self.zone.is_axfr_allowed()
+    }
+
+    /// Dynamic updates are not supported, the records are those of the lease file
+    fn update(&self, _update: &MessageRequest) -> UpdateResult<bool> {
+        Err(ResponseCode::NotImp)
+    }
+
+    /// Get the origin of this zone, i.e. example.com is the origin for www.example.com
+    fn origin(&self) -> &LowerName {
+        self.zone.origin()
+    }
+
+    fn lookup(
+        &self,
+        name: &LowerName,
+        rtype: RecordType,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Self::LookupFuture {
+        self.zone
+            .lookup(name, rtype, is_secure, supported_algorithms)
+    }
+
+    fn search(
+        &self,
+        query: &LowerQuery,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Box<dyn Future<Item = Self::Lookup, Error = LookupError> + Send> {
+        self.zone.search(query, is_secure, supported_algorithms)
+    }
+
+    /// Get the NS, NameServer, record for the zone
+    fn ns(&self, is_secure: bool, supported_algorithms: SupportedAlgorithms) -> Self::LookupFuture {
+        self.zone.ns(is_secure, supported_algorithms)
+    }
+
+    /// Return the NSEC records based on the given name
+    fn get_nsec_records(
+        &self,
+        name: &LowerName,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Self::LookupFuture {
+        self.zone
+            .get_nsec_records(name, is_secure, supported_algorithms)
+    }
+
+    /// Returns the SOA of the authority.
+    fn soa(&self) -> Self::LookupFuture {
+        self.zone.soa()
+    }
+
+    /// Returns the SOA record for the zone
+    fn soa_secure(
+        &self,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Self::LookupFuture {
+        self.zone.soa_secure(is_secure, supported_algorithms)
+    }
+}
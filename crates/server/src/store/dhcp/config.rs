@@ -0,0 +1,44 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::time::Duration;
+
+use trust_dns::rr::Name;
+
+use store::dhcp::LeaseFormat;
+
+/// Configuration for zones served from the lease file of a DHCP server
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct DhcpConfig {
+    /// path to the lease file, e.g. /var/lib/dhcp/dhcpd.leases
+    pub lease_file_path: String,
+    /// the format of the lease file, isc or kea, defaults to isc
+    pub format: Option<LeaseFormat>,
+    /// the domain of the hosts, e.g. lan., defaults to the zone, it must be set for reverse zones
+    pub domain: Option<Name>,
+    /// TTL of the records, defaults to 60
+    pub ttl: Option<u32>,
+    /// seconds between each check of the lease file for changes, defaults to 5
+    pub watch_interval: Option<u64>,
+}
+
+impl DhcpConfig {
+    /// The format of the lease file
+    pub fn get_format(&self) -> LeaseFormat {
+        self.format.unwrap_or(LeaseFormat::Isc)
+    }
+
+    /// TTL of the records of the leases
+    pub fn get_ttl(&self) -> u32 {
+        self.ttl.unwrap_or(60)
+    }
+
+    /// Time between each check of the lease file
+    pub fn get_watch_interval(&self) -> Duration {
+        Duration::from_secs(self.watch_interval.unwrap_or(5))
+    }
+}
@@ -0,0 +1,309 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::net::Ipv4Addr;
+use std::path::Path;
+use std::str::FromStr;
+
+use chrono::NaiveDateTime;
+
+/// The format of a lease file
+#[derive(Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum LeaseFormat {
+    /// ISC dhcpd `dhcpd.leases`
+    Isc,
+    /// Kea memfile `kea-leases4.csv`
+    Kea,
+}
+
+/// A lease of an address to a host
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lease {
+    /// the leased address
+    pub address: Ipv4Addr,
+    /// the hostname sent by the client
+    pub hostname: String,
+    /// the end of the lease, in seconds since the unix epoch, None if it never ends
+    pub ends: Option<u64>,
+}
+
+impl Lease {
+    /// Returns true if the lease has not ended at `now`, in seconds since the unix epoch
+    pub fn is_active_at(&self, now: u64) -> bool {
+        match self.ends {
+            Some(ends) => ends > now,
+            None => true,
+        }
+    }
+}
+
+/// Reads the leases from the file at `path`
+pub fn read_leases_from_path<P: AsRef<Path>>(
+    format: LeaseFormat,
+    path: P,
+) -> io::Result<Vec<Lease>> {
+    read_leases(format, BufReader::new(File::open(path)?))
+}
+
+/// Reads the leases which are bound to a host with a hostname, sorted by address
+///
+/// Lease files are journals, the last entry for an address is its current state. Leases which
+///  were released, expired or declined when they were written are not returned, those which ended
+///  since are, see `Lease::is_active_at`. Entries which can't be parsed are skipped.
+pub fn read_leases<R: BufRead>(format: LeaseFormat, reader: R) -> io::Result<Vec<Lease>> {
+    let leases = match format {
+        LeaseFormat::Isc => read_isc(reader)?,
+        LeaseFormat::Kea => read_kea(reader)?,
+    };
+
+    Ok(leases
+        .values()
+        .flatten()
+        .filter(|lease| !lease.hostname.is_empty())
+        .cloned()
+        .collect())
+}
+
+/// The state of each address, None if it is not bound
+type Leases = BTreeMap<Ipv4Addr, Option<Lease>>;
+
+/// Reads the `lease <address> { ... }` declarations of dhcpd
+///
+/// ```text
+/// lease 192.168.1.10 {
+///   starts 4 2019/08/01 10:00:00;
+///   ends 4 2019/08/01 22:00:00;
+///   binding state active;
+///   next binding state free;
+///   hardware ethernet 00:11:22:33:44:55;
+///   client-hostname "laptop";
+/// }
+/// ```
+fn read_isc<R: BufRead>(reader: R) -> io::Result<Leases> {
+    let mut leases = Leases::new();
+    // the address, the binding state, and the lease being read
+    let mut current: Option<(Ipv4Addr, bool, Lease)> = None;
+
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.split('#').next().unwrap_or_default().trim();
+
+        // each statement is a keyword and its arguments
+        let mut words = line.trim_end_matches(';').splitn(2, ' ');
+        let (keyword, arguments) = match (words.next(), words.next()) {
+            (Some(keyword), Some(arguments)) => (keyword, arguments.trim()),
+            (Some(keyword), None) => (keyword, ""),
+            _ => continue,
+        };
+
+        if keyword == "lease" && arguments.ends_with('{') {
+            let address = arguments.trim_end_matches('{').trim();
+            current = Ipv4Addr::from_str(address).ok().map(|address| {
+                let lease = Lease {
+                    address,
+                    hostname: String::new(),
+                    ends: None,
+                };
+                (address, false, lease)
+            });
+
+            if current.is_none() {
+                debug!("skipping lease of bad address: {}", address);
+            }
+            continue;
+        }
+
+        if keyword == "}" {
+            if let Some((address, active, lease)) = current.take() {
+                leases.insert(address, if active { Some(lease) } else { None });
+            }
+            continue;
+        }
+
+        let (active, lease) = match current {
+            Some((_, ref mut active, ref mut lease)) => (active, lease),
+            None => continue,
+        };
+
+        match keyword {
+            "binding" => *active = arguments == "state active",
+            "ends" => lease.ends = parse_isc_time(arguments),
+            "client-hostname" => lease.hostname = arguments.trim_matches('"').to_string(),
+            _ => (),
+        }
+    }
+
+    Ok(leases)
+}
+
+/// Parses `never`, `epoch <seconds>` or `<weekday> <yyyy/mm/dd> <hh:mm:ss>` in UTC
+fn parse_isc_time(time: &str) -> Option<u64> {
+    let mut words = time.splitn(2, ' ');
+    let ends = match (words.next(), words.next()) {
+        (Some("never"), None) => return None,
+        (Some("epoch"), Some(seconds)) => u64::from_str(seconds.trim()).ok(),
+        // the weekday is redundant
+        (Some(_), Some(date)) => NaiveDateTime::parse_from_str(date.trim(), "%Y/%m/%d %H:%M:%S")
+            .ok()
+            .filter(|date| date.timestamp() >= 0)
+            .map(|date| date.timestamp() as u64),
+        _ => None,
+    };
+
+    if ends.is_none() {
+        debug!("bad lease end: {}", time);
+    }
+
+    // a lease with a bad end is considered ended
+    Some(ends.unwrap_or_default())
+}
+
+/// Reads the CSV lease file of the Kea memfile backend
+///
+/// ```text
+/// address,hwaddr,client_id,valid_lifetime,expire,subnet_id,fqdn_fwd,fqdn_rev,hostname,state
+/// 192.168.1.10,00:11:22:33:44:55,,3600,1564696800,1,0,0,laptop,0
+/// ```
+fn read_kea<R: BufRead>(reader: R) -> io::Result<Leases> {
+    let mut leases = Leases::new();
+    let mut lines = reader.lines();
+
+    let header = match lines.next() {
+        Some(header) => header?,
+        None => return Ok(leases),
+    };
+    let columns = header.trim().split(',').collect::<Vec<_>>();
+    let column = |name: &str| {
+        columns.iter().position(|c| *c == name).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("lease file has no {} column", name),
+            )
+        })
+    };
+
+    let address_column = column("address")?;
+    let valid_lifetime_column = column("valid_lifetime")?;
+    let expire_column = column("expire")?;
+    let hostname_column = column("hostname")?;
+    let state_column = column("state")?;
+
+    for line in lines {
+        let line = line?;
+        let fields = line.trim().split(',').collect::<Vec<_>>();
+        let field = |column: usize| fields.get(column).cloned().unwrap_or_default();
+
+        let address = match Ipv4Addr::from_str(field(address_column)) {
+            Ok(address) => address,
+            Err(_) => {
+                debug!("skipping lease of bad address: {}", field(address_column));
+                continue;
+            }
+        };
+
+        // a valid lifetime of 0 records the deletion of the lease, states other than 0 are
+        //  declined or expired and reclaimed leases
+        let active = field(valid_lifetime_column) != "0" && field(state_column) == "0";
+        let lease = if active {
+            Some(Lease {
+                address,
+                hostname: field(hostname_column).replace("&#x2c", ","),
+                ends: Some(u64::from_str(field(expire_column)).unwrap_or_default()),
+            })
+        } else {
+            None
+        };
+
+        leases.insert(address, lease);
+    }
+
+    Ok(leases)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_isc_leases() {
+        let leases = read_leases(
+            LeaseFormat::Isc,
+            "# The format of this file is documented in the dhcpd.leases(5) manual page.\n\
+             lease 192.168.1.10 {\n\
+               starts 4 2019/08/01 10:00:00;\n\
+               ends 4 2019/08/01 22:00:00;\n\
+               binding state active;\n\
+               next binding state free;\n\
+               client-hostname \"laptop\";\n\
+             }\n\
+             lease 192.168.1.11 {\n\
+               ends epoch 1564696800; # Thu Aug 01 22:00:00 2019\n\
+               binding state active;\n\
+               client-hostname \"phone\";\n\
+             }\n\
+             lease 192.168.1.11 {\n\
+               binding state free;\n\
+             }\n\
+             lease 192.168.1.12 {\n\
+               ends never;\n\
+               binding state active;\n\
+               client-hostname \"printer\";\n\
+             }\n\
+             lease 192.168.1.13 {\n\
+               binding state active;\n\
+             }\n"
+            .as_bytes(),
+        )
+        .expect("failed to read leases");
+
+        assert_eq!(
+            leases,
+            vec![
+                Lease {
+                    address: Ipv4Addr::new(192, 168, 1, 10),
+                    hostname: "laptop".to_string(),
+                    ends: Some(1_564_696_800),
+                },
+                Lease {
+                    address: Ipv4Addr::new(192, 168, 1, 12),
+                    hostname: "printer".to_string(),
+                    ends: None,
+                },
+            ]
+        );
+        assert!(leases[0].is_active_at(1_564_696_799));
+        assert!(!leases[0].is_active_at(1_564_696_800));
+        assert!(leases[1].is_active_at(4_102_444_800));
+    }
+
+    #[test]
+    fn test_kea_leases() {
+        let leases = read_leases(
+            LeaseFormat::Kea,
+            "address,hwaddr,client_id,valid_lifetime,expire,subnet_id,fqdn_fwd,fqdn_rev,hostname,state,user_context\n\
+             192.168.1.10,00:11:22:33:44:55,,3600,1564696800,1,0,0,laptop,0,\n\
+             192.168.1.11,00:11:22:33:44:56,,3600,1564696800,1,0,0,phone,0,\n\
+             192.168.1.11,00:11:22:33:44:56,,0,1564693200,1,0,0,phone,0,\n\
+             192.168.1.12,00:11:22:33:44:57,,3600,1564696800,1,0,0,tv,2,\n"
+                .as_bytes(),
+        )
+        .expect("failed to read leases");
+
+        assert_eq!(
+            leases,
+            vec![Lease {
+                address: Ipv4Addr::new(192, 168, 1, 10),
+                hostname: "laptop".to_string(),
+                ends: Some(1_564_696_800),
+            }]
+        );
+    }
+}
@@ -0,0 +1,16 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Zones with the A and PTR records of the active leases of a DHCP server
+
+mod authority;
+mod config;
+mod leases;
+
+pub use self::authority::DhcpAuthority;
+pub use self::config::DhcpConfig;
+pub use self::leases::{read_leases, read_leases_from_path, Lease, LeaseFormat};
@@ -10,6 +10,7 @@
 use std::collections::BTreeMap;
 use std::ops::{Deref, DerefMut};
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use futures::future::Future;
 
@@ -19,7 +20,7 @@ use trust_dns::rr::dnssec::{DnsSecResult, Signer, SupportedAlgorithms};
 use trust_dns::rr::{LowerName, Name, RecordSet, RecordType, RrKey};
 
 use authority::{Authority, LookupError, MessageRequest, UpdateResult, ZoneType};
-use store::file::FileConfig;
+use store::file::{is_compiled_zone, read_compiled_zone, FileConfig};
 use store::in_memory::InMemoryAuthority;
 
 /// FileAuthority is responsible for storing the resource records for a particular zone.
@@ -62,8 +63,8 @@ impl FileAuthority {
         config: &FileConfig,
     ) -> Result<Self, String> {
         use std::fs::File;
-        use std::io::Read;
-        use trust_dns::serialize::txt::{Lexer, Parser};
+        use std::io::{BufRead, BufReader, Read};
+        use trust_dns::serialize::txt::Parser;
 
         let zone_path = root_dir
             .map(PathBuf::from)
@@ -72,18 +73,45 @@ impl FileAuthority {
 
         info!("loading zone file: {:?}", zone_path);
 
-        let mut file = File::open(&zone_path)
+        let file = File::open(&zone_path)
             .map_err(|e| format!("error opening {}: {:?}", zone_path.display(), e))?;
+        let mut file = BufReader::new(file);
 
-        let mut buf = String::new();
-
-        // TODO: this should really use something to read line by line or some other method to
-        //  keep the usage down. and be a custom lexer...
-        file.read_to_string(&mut buf)
+        // compiled zones, see `compile_zone`, are loaded without parsing
+        let is_compiled = file
+            .fill_buf()
+            .map(is_compiled_zone)
             .map_err(|e| format!("failed to read {}: {:?}", zone_path.display(), e))?;
-        let lexer = Lexer::new(&buf);
+        if is_compiled {
+            let mut buf = Vec::new();
+            file.read_to_end(&mut buf)
+                .map_err(|e| format!("failed to read {}: {:?}", zone_path.display(), e))?;
+            let (compiled_origin, records, index) = read_compiled_zone(&buf)
+                .map_err(|e| format!("failed to read {}: {}", zone_path.display(), e))?;
+            if compiled_origin != origin {
+                return Err(format!(
+                    "{} is compiled for {}, not for the zone {}",
+                    zone_path.display(),
+                    compiled_origin,
+                    origin
+                ));
+            }
+
+            info!(
+                "compiled zone loaded: {} with {} records",
+                origin,
+                records.len()
+            );
+
+            return InMemoryAuthority::from_records_with_index(
+                origin, records, index, zone_type, allow_axfr,
+            )
+            .map(Self);
+        }
+
+        // the file is parsed one entry at a time, only its records are held in memory
         let (origin, records) = Parser::new()
-            .parse(lexer, Some(origin))
+            .parse_reader(file, Some(origin))
             .map_err(|e| format!("failed to parse {}: {:?}", zone_path.display(), e))?;
 
         info!(
@@ -130,7 +158,7 @@ impl Authority for FileAuthority {
     }
 
     /// Perform a dynamic update of a zone
-    fn update(&mut self, _update: &MessageRequest) -> UpdateResult<bool> {
+    fn update(&self, _update: &MessageRequest) -> UpdateResult<bool> {
         use proto::op::ResponseCode;
         Err(ResponseCode::NotImp)
     }
@@ -233,6 +261,11 @@ impl Authority for FileAuthority {
         self.0.add_zone_signing_key(signer)
     }
 
+    /// Spreads the expirations of the signatures over the jitter
+    fn set_signature_jitter(&mut self, jitter: Duration) -> DnsSecResult<()> {
+        self.0.set_signature_jitter(jitter)
+    }
+
     /// Sign the zone for DNSSEC
     fn secure_zone(&mut self) -> DnsSecResult<()> {
         Authority::secure_zone(&mut self.0)
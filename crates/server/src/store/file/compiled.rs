@@ -0,0 +1,272 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The compiled format of zones, the parsed records of a zone which load without parsing a
+//!  master file
+//!
+//! ```text
+//! magic       "TDNSZONE"
+//! version     u8
+//! origin      name
+//! rrsets      u32, then for each rrset in the order of their keys:
+//!   name        name
+//!   type        u16
+//!   class       u16
+//!   ttl         u32
+//!   serial      u32
+//!   records     u32, then for each record: ttl u32, rdlength u16, rdata
+//!   rrsigs      u32, then for each signature: ttl u32, rdlength u16, rdata
+//! index       the `NameIndex` of the names of the rrsets
+//! ```
+//!
+//! All integers are in network order and names are not compressed. The index of the names is
+//!  read as it was stored, it's not built again from the names of the rrsets.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use proto::error::{ProtoError, ProtoResult};
+use proto::serialize::binary::{BinDecodable, BinDecoder, BinEncodable, BinEncoder, Restrict};
+use trust_dns::rr::rdata::{DNSSECRecordType, NULL};
+use trust_dns::rr::{DNSClass, Name, RData, Record, RecordSet, RecordType, RrKey};
+
+use store::in_memory::NameIndex;
+
+/// The first bytes of a compiled zone
+pub const COMPILED_ZONE_MAGIC: &[u8] = b"TDNSZONE";
+/// The version of the format, zones compiled with another version must be compiled again
+const COMPILED_ZONE_VERSION: u8 = 2;
+
+/// Returns true if the bytes are those of a compiled zone, and not of a master file
+pub fn is_compiled_zone(bytes: &[u8]) -> bool {
+    bytes.starts_with(COMPILED_ZONE_MAGIC)
+}
+
+/// Compiles the records of the zone, see the module documentation for the format
+///
+/// The signatures of the rrsets are kept, a signed zone is served without signing it again.
+pub fn compile_zone(
+    origin: &Name,
+    records: &BTreeMap<RrKey, Arc<RecordSet>>,
+) -> ProtoResult<Vec<u8>> {
+    let mut buf = Vec::new();
+    {
+        let mut encoder = BinEncoder::new(&mut buf);
+        encoder.emit_vec(COMPILED_ZONE_MAGIC)?;
+        encoder.emit_u8(COMPILED_ZONE_VERSION)?;
+        origin.emit_as_canonical(&mut encoder, true)?;
+
+        encoder.emit_u32(records.len() as u32)?;
+        for rrset in records.values() {
+            rrset.name().emit_as_canonical(&mut encoder, true)?;
+            rrset.record_type().emit(&mut encoder)?;
+            rrset.dns_class().emit(&mut encoder)?;
+            encoder.emit_u32(rrset.ttl())?;
+            encoder.emit_u32(rrset.serial())?;
+
+            let records = rrset.records_without_rrsigs().collect::<Vec<_>>();
+            emit_records(&mut encoder, &records)?;
+            let rrsigs = rrset.rrsigs().iter().collect::<Vec<_>>();
+            emit_records(&mut encoder, &rrsigs)?;
+        }
+
+        NameIndex::from_keys(records.keys()).emit(&mut encoder)?;
+    }
+
+    Ok(buf)
+}
+
+/// Reads the origin, the records and the index of the names of a compiled zone
+pub fn read_compiled_zone(
+    bytes: &[u8],
+) -> ProtoResult<(Name, BTreeMap<RrKey, Arc<RecordSet>>, NameIndex)> {
+    if !is_compiled_zone(bytes) {
+        return Err("not a compiled zone".into());
+    }
+
+    let mut decoder = BinDecoder::new(&bytes[COMPILED_ZONE_MAGIC.len()..]);
+    let version = decoder.read_u8()?.unverified(/*checked below*/);
+    if version != COMPILED_ZONE_VERSION {
+        return Err(format!(
+            "unsupported version of compiled zone: {}, compile the zone again",
+            version
+        )
+        .into());
+    }
+
+    let origin = Name::read(&mut decoder)?;
+    let rrsets = decoder.read_u32()?.unverified(/*bounded by the length of the bytes*/);
+
+    let mut records = BTreeMap::new();
+    for _ in 0..rrsets {
+        let name = Name::read(&mut decoder)?;
+        let record_type = RecordType::read(&mut decoder)?;
+        let dns_class = DNSClass::read(&mut decoder)?;
+        let ttl = decoder.read_u32()?.unverified(/*any u32 is valid*/);
+        let serial = decoder.read_u32()?.unverified(/*any u32 is valid*/);
+
+        let mut rrset = RecordSet::new(&name, record_type, serial);
+        rrset.set_dns_class(dns_class);
+        for record in read_records(&mut decoder, &name, record_type, dns_class)? {
+            rrset.insert(record, serial);
+        }
+        for rrsig in read_records(
+            &mut decoder,
+            &name,
+            RecordType::DNSSEC(DNSSECRecordType::RRSIG),
+            dns_class,
+        )? {
+            rrset.insert_rrsig(rrsig);
+        }
+        // the records set the ttl as they're inserted, as they did when the zone was compiled
+        if rrset.is_empty() {
+            rrset.set_ttl(ttl);
+        }
+
+        records.insert(RrKey::new(name.into(), record_type), Arc::new(rrset));
+    }
+
+    let index = NameIndex::read(&mut decoder)?;
+    if index.len() != records.len() {
+        return Err(format!(
+            "the name index has {} names and types for {} rrsets",
+            index.len(),
+            records.len()
+        )
+        .into());
+    }
+
+    if !decoder.is_empty() {
+        return Err("trailing bytes after the compiled zone".into());
+    }
+
+    Ok((origin, records, index))
+}
+
+fn emit_records(encoder: &mut BinEncoder, records: &[&Record]) -> ProtoResult<()> {
+    encoder.emit_u32(records.len() as u32)?;
+    for record in records {
+        encoder.emit_u32(record.ttl())?;
+
+        // names in the rdata are compressed against the rdata only, so that it's read alone
+        let mut rdata = Vec::new();
+        record.rdata().emit(&mut BinEncoder::new(&mut rdata))?;
+        if rdata.len() > 0xFFFF {
+            return Err(format!("rdata too long: {}", record.name()).into());
+        }
+        encoder.emit_u16(rdata.len() as u16)?;
+        encoder.emit_vec(&rdata)?;
+    }
+
+    Ok(())
+}
+
+fn read_records(
+    decoder: &mut BinDecoder,
+    name: &Name,
+    record_type: RecordType,
+    dns_class: DNSClass,
+) -> ProtoResult<Vec<Record>> {
+    let count = decoder.read_u32()?.unverified(/*bounded by the length of the bytes*/);
+
+    let mut records = Vec::new();
+    for _ in 0..count {
+        let ttl = decoder.read_u32()?.unverified(/*any u32 is valid*/);
+        let rd_length = decoder.read_u16()?.unverified(/*checked by read_slice*/);
+        let rdata = decoder.read_slice(rd_length as usize)?.unverified(/*read as rdata*/);
+
+        let rdata = if rd_length == 0 {
+            RData::NULL(NULL::new())
+        } else {
+            RData::read(
+                &mut BinDecoder::new(rdata),
+                record_type,
+                Restrict::new(rd_length),
+            )
+            .map_err(|e| {
+                ProtoError::from(format!("bad rdata of {} {}: {}", name, record_type, e))
+            })?
+        };
+
+        let mut record = Record::with(name.clone(), record_type, ttl);
+        record.set_dns_class(dns_class).set_rdata(rdata);
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+
+    use trust_dns::rr::rdata::SOA;
+    use trust_dns::rr::LowerName;
+
+    use super::*;
+
+    #[test]
+    fn test_compiled_zone_round_trip() {
+        let origin = Name::from_str("example.com.").unwrap();
+        let www = Name::from_str("www.example.com.").unwrap();
+        let alias = Name::from_str("alias.example.com.").unwrap();
+
+        let mut soa = RecordSet::new(&origin, RecordType::SOA, 2);
+        soa.insert(
+            Record::from_rdata(
+                origin.clone(),
+                3600,
+                RData::SOA(SOA::new(
+                    Name::from_str("ns.example.com.").unwrap(),
+                    Name::from_str("root.example.com.").unwrap(),
+                    2,
+                    7200,
+                    3600,
+                    86400,
+                    300,
+                )),
+            ),
+            2,
+        );
+        let mut a = RecordSet::new(&www, RecordType::A, 2);
+        a.insert(Record::a(www.clone(), 300, Ipv4Addr::new(127, 0, 0, 1)), 2);
+        a.insert(Record::a(www.clone(), 300, Ipv4Addr::new(127, 0, 0, 2)), 2);
+        let mut cname = RecordSet::new(&alias.clone(), RecordType::CNAME, 2);
+        cname.insert(Record::cname(alias.clone(), 60, www.clone()), 2);
+
+        let records = vec![soa, a, cname]
+            .into_iter()
+            .map(|rrset| {
+                (
+                    RrKey::new(rrset.name().into(), rrset.record_type()),
+                    Arc::new(rrset),
+                )
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        let compiled = compile_zone(&origin, &records).expect("failed to compile");
+        assert!(is_compiled_zone(&compiled));
+
+        let (read_origin, read_records, index) =
+            read_compiled_zone(&compiled).expect("failed to read");
+        assert_eq!(read_origin, origin);
+        assert_eq!(read_records, records);
+        assert_eq!(index.len(), 3);
+        assert!(index.contains(&LowerName::from(&alias)));
+        let read_www = &read_records[&RrKey::new(www.into(), RecordType::A)];
+        assert_eq!(read_www.ttl(), 300);
+        assert_eq!(read_www.records_without_rrsigs().count(), 2);
+
+        // truncated and unknown versions are errors
+        assert!(read_compiled_zone(&compiled[..compiled.len() - 1]).is_err());
+        let mut other_version = compiled.clone();
+        other_version[COMPILED_ZONE_MAGIC.len()] = COMPILED_ZONE_VERSION + 1;
+        assert!(read_compiled_zone(&other_version).is_err());
+        assert!(read_compiled_zone(b"$ORIGIN example.com.").is_err());
+    }
+}
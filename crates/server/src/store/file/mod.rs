@@ -8,7 +8,9 @@
 //! Master file based serving with Dynamic DNS and journaling support
 
 mod authority;
+mod compiled;
 mod config;
 
 pub use self::authority::FileAuthority;
+pub use self::compiled::{compile_zone, is_compiled_zone, read_compiled_zone, COMPILED_ZONE_MAGIC};
 pub use self::config::FileConfig;
@@ -5,18 +5,27 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use futures::{Async, Future, Poll};
+use std::sync::Arc;
+use std::time::Instant;
+
+use futures::future::{self, Either};
+use futures::{Async, Future, Poll, Stream};
+use tokio_timer::{Interval, Timeout};
 
 use trust_dns::op::LowerQuery;
 use trust_dns::op::ResponseCode;
+use trust_dns::proto::xfer::DnsRequestOptions;
 use trust_dns::rr::dnssec::SupportedAlgorithms;
+use trust_dns::rr::rdata::opt::{ClientSubnet, EdnsOption};
 use trust_dns::rr::{LowerName, Name, Record, RecordType};
-use trust_dns_resolver::config::ResolverConfig;
+use trust_dns_resolver::config::{ResolverConfig, ResolverOpts};
+use trust_dns_resolver::error::ResolveErrorKind;
 use trust_dns_resolver::lookup::Lookup as ResolverLookup;
-use trust_dns_resolver::{AsyncResolver, BackgroundLookup};
+use trust_dns_resolver::{AsyncResolver, BackgroundLookup, ValidationStats};
 
 use authority::{Authority, LookupError, LookupObject, MessageRequest, UpdateResult, ZoneType};
-use store::forwarder::ForwardConfig;
+use store::forwarder::{ClientSubnetConfig, ForwardConfig, UpstreamCheckConfig};
+use store::load_balancer::TargetHealth;
 
 /// An authority that will forward resolutions to upstream resolvers.
 ///
@@ -24,6 +33,8 @@ use store::forwarder::ForwardConfig;
 pub struct ForwardAuthority {
     origin: LowerName,
     resolver: AsyncResolver,
+    upstream: Option<Arc<TargetHealth>>,
+    client_subnet: Option<ClientSubnetConfig>,
 }
 
 impl ForwardAuthority {
@@ -38,10 +49,15 @@ impl ForwardAuthority {
         ForwardAuthority {
             origin: Name::root().into(),
             resolver,
+            upstream: None,
+            client_subnet: None,
         }
     }
 
     /// Read the Authority for the origin from the specified configuration
+    ///
+    /// The returned future must be run in the background, it drives the resolver and the checks of
+    ///  the upstream name servers.
     pub fn try_from_config(
         origin: Name,
         _zone_type: ZoneType,
@@ -51,9 +67,31 @@ impl ForwardAuthority {
 
         let name_servers = config.name_servers.clone();
         let options = config.options.unwrap_or_default();
-        let config = ResolverConfig::from_parts(None, vec![], name_servers);
+        let mut resolver_config = ResolverConfig::from_parts(None, vec![], name_servers);
+        for ttl_override in config.ttl_overrides.iter().flatten() {
+            resolver_config.add_ttl_override(ttl_override.suffix.clone(), ttl_override.ttl);
+        }
+        for exception in config.validation_exceptions.iter().flatten() {
+            resolver_config.add_validation_exception(exception.suffix.clone(), exception.policy);
+        }
 
-        let (resolver, bg) = AsyncResolver::new(config, options);
+        let (upstream, check) = match config.health_check {
+            Some(ref health_check) => {
+                let upstream = Arc::new(TargetHealth::new());
+                let check = check_upstream(
+                    origin.clone(),
+                    resolver_config.clone(),
+                    options,
+                    Arc::clone(&upstream),
+                    health_check,
+                );
+
+                (Some(upstream), Either::A(check))
+            }
+            None => (None, Either::B(future::ok(()))),
+        };
+
+        let (resolver, bg) = AsyncResolver::new(resolver_config, options);
 
         info!("forward resolver configured: {}: ", origin);
 
@@ -61,10 +99,78 @@ impl ForwardAuthority {
             ForwardAuthority {
                 origin: origin.into(),
                 resolver,
+                upstream,
+                client_subnet: config.client_subnet,
             },
-            bg,
+            bg.join(check).map(|_| ()),
         ))
     }
+
+    /// The reachability of the upstream name servers, if they are checked
+    pub fn upstream_health(&self) -> Option<&Arc<TargetHealth>> {
+        self.upstream.as_ref()
+    }
+
+    /// The queries which were not validated so far, for the `validation_exceptions`
+    pub fn validation_stats(&self) -> ValidationStats {
+        self.resolver.validation_stats()
+    }
+}
+
+/// Returns a future which checks the upstream name servers on every interval, it never completes
+fn check_upstream(
+    origin: Name,
+    config: ResolverConfig,
+    mut options: ResolverOpts,
+    health: Arc<TargetHealth>,
+    check: &UpstreamCheckConfig,
+) -> impl Future<Item = (), Error = ()> {
+    let timeout = check.get_timeout();
+    let rise = check.get_rise();
+    let fall = check.get_fall();
+
+    // responses from the cache would hide unreachable name servers
+    options.cache_size = 0;
+    let (resolver, bg) = AsyncResolver::new(config, options);
+
+    let checks = Interval::new(Instant::now(), check.get_interval())
+        .map_err(|e| error!("upstream check timer failed: {}", e))
+        .for_each(move |_| {
+            let health = Arc::clone(&health);
+            let origin = origin.clone();
+
+            Timeout::new(resolver.lookup(origin.clone(), RecordType::NS), timeout).then(
+                move |result| {
+                    let reachable = match result {
+                        Ok(_) => true,
+                        Err(e) => match e.into_inner().map(|e| e.kind().clone()) {
+                            // a negative response is still a response
+                            Some(ResolveErrorKind::NoRecordsFound { .. }) => true,
+                            Some(ref kind) => {
+                                debug!("upstream check failed for {}: {}", origin, kind);
+                                false
+                            }
+                            None => {
+                                debug!("upstream check timed out for {}", origin);
+                                false
+                            }
+                        },
+                    };
+
+                    if health.record(reachable, rise, fall) {
+                        if health.is_healthy() {
+                            info!("upstream reachable: {}", origin);
+                        } else {
+                            warn!("upstream unreachable: {}", origin);
+                        }
+                    }
+
+                    Ok(())
+                },
+            )
+        });
+
+    bg.join(checks).map(|_| ())
 }
 
 impl Authority for ForwardAuthority {
@@ -81,7 +187,7 @@ impl Authority for ForwardAuthority {
         false
     }
 
-    fn update(&mut self, _update: &MessageRequest) -> UpdateResult<bool> {
+    fn update(&self, _update: &MessageRequest) -> UpdateResult<bool> {
         Err(ResponseCode::NotImp)
     }
 
@@ -123,6 +229,36 @@ impl Authority for ForwardAuthority {
         ))
     }
 
+    /// Forwards the query with the truncated subnet of the client, if configured
+    fn search_with_client(
+        &self,
+        query: &LowerQuery,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+        client_subnet: Option<ClientSubnet>,
+    ) -> Box<Future<Item = Self::Lookup, Error = LookupError> + Send> {
+        let subnet = match (self.client_subnet, client_subnet) {
+            (Some(config), Some(client_subnet)) => config.truncate(&client_subnet),
+            _ => return self.search(query, is_secure, supported_algorithms),
+        };
+
+        let name = query.name();
+        let rtype = query.query_type();
+        assert!(self.origin.zone_of(name));
+
+        info!(
+            "forwarding lookup: {} {} for {}/{}",
+            name,
+            rtype,
+            subnet.address(),
+            subnet.source_prefix()
+        );
+        let options = DnsRequestOptions::default().with_edns_option(EdnsOption::Subnet(subnet));
+        Box::new(ForwardLookupFuture(
+            self.resolver.lookup_with_options(name, rtype, options),
+        ))
+    }
+
     fn get_nsec_records(
         &self,
         _name: &LowerName,
@@ -133,15 +269,17 @@ impl Authority for ForwardAuthority {
     }
 }
 
-pub struct ForwardLookup(ResolverLookup);
+/// The result of a forwarded lookup, empty by default
+#[derive(Default)]
+pub struct ForwardLookup(Option<ResolverLookup>);
 
 impl LookupObject for ForwardLookup {
     fn is_empty(&self) -> bool {
-        self.0.is_empty()
+        self.0.as_ref().map_or(true, ResolverLookup::is_empty)
     }
 
     fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a Record> + Send + 'a> {
-        Box::new(self.0.record_iter())
+        Box::new(self.0.iter().flat_map(ResolverLookup::record_iter))
     }
 
     fn take_additionals(&mut self) -> Option<Box<dyn LookupObject>> {
@@ -149,7 +287,8 @@ impl LookupObject for ForwardLookup {
     }
 }
 
-pub struct ForwardLookupFuture(BackgroundLookup);
+/// A future which resolves to the `ForwardLookup`
+pub struct ForwardLookupFuture(pub(crate) BackgroundLookup);
 
 impl Future for ForwardLookupFuture {
     type Item = ForwardLookup;
@@ -157,7 +296,7 @@ impl Future for ForwardLookupFuture {
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
         match self.0.poll() {
-            Ok(Async::Ready(f)) => Ok(Async::Ready(ForwardLookup(f))),
+            Ok(Async::Ready(f)) => Ok(Async::Ready(ForwardLookup(Some(f)))),
             Ok(Async::NotReady) => Ok(Async::NotReady),
             Err(e) => Err(e.into()),
         }
@@ -5,13 +5,107 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use trust_dns_resolver::config::{NameServerConfigGroup, ResolverOpts};
+use std::net::IpAddr;
+use std::time::Duration;
+
+use trust_dns::rr::rdata::opt::ClientSubnet;
+use trust_dns_resolver::config::{
+    NameServerConfigGroup, ResolverOpts, TtlOverride, ValidationException,
+};
 
 /// Configuration for master file based zones
 #[derive(Deserialize, PartialEq, Debug)]
 pub struct ForwardConfig {
     /// upstream name_server configurations
     pub name_servers: NameServerConfigGroup,
-    /// Resolver options
+    /// Resolver options, e.g. the minimum and maximum TTLs of the cached answers
     pub options: Option<ResolverOpts>,
+    /// the TTLs of the cached answers for the names under suffixes, e.g. for internal domains
+    ///  which must be resolved again more often
+    pub ttl_overrides: Option<Vec<TtlOverride>>,
+    /// the names under suffixes which are not validated with the `validate` option, e.g. unsigned
+    ///  internal domains, and whether they are queried with the DNSSEC OK bit
+    pub validation_exceptions: Option<Vec<ValidationException>>,
+    /// the periodic check of the reachability of the upstream name servers, reported by the
+    ///  readiness of the server, by default they are not checked
+    pub health_check: Option<UpstreamCheckConfig>,
+    /// the client subnet sent upstream, truncated to privacy preserving prefixes, by default the
+    ///  upstream name servers receive no information about the clients
+    pub client_subnet: Option<ClientSubnetConfig>,
+}
+
+/// Configuration of the periodic check of the upstream name servers of a forwarder
+///
+/// The NS records of the zone are looked up, without caching, any response from the upstream
+///  name servers is a successful check.
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct UpstreamCheckConfig {
+    /// seconds between each check, defaults to 10
+    pub interval: Option<u64>,
+    /// seconds before a check is considered failed, defaults to 2
+    pub timeout: Option<u64>,
+    /// number of consecutive successful checks before the upstream is reachable again, defaults to 2
+    pub rise: Option<usize>,
+    /// number of consecutive failed checks before the upstream is unreachable, defaults to 3
+    pub fall: Option<usize>,
+}
+
+impl UpstreamCheckConfig {
+    /// Time between each check of the upstream
+    pub fn get_interval(&self) -> Duration {
+        Duration::from_secs(self.interval.unwrap_or(10))
+    }
+
+    /// Time before a check is considered failed
+    pub fn get_timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout.unwrap_or(2))
+    }
+
+    /// Consecutive successful checks required to mark the upstream reachable
+    pub fn get_rise(&self) -> usize {
+        self.rise.unwrap_or(2).max(1)
+    }
+
+    /// Consecutive failed checks required to mark the upstream unreachable
+    pub fn get_fall(&self) -> usize {
+        self.fall.unwrap_or(3).max(1)
+    }
+}
+
+/// Configuration of the client subnet option, [RFC 7871](https://tools.ietf.org/html/rfc7871),
+///  sent by a forwarder to its upstream name servers
+///
+/// The client subnet of the request, or the source address of the client without one, is
+///  truncated to the prefix lengths so that the upstream name servers, e.g. public resolvers, can
+///  answer for the network of the client without identifying it. The address of the client is
+///  never sent as is. The answers are cached for all clients, whatever their subnet.
+#[derive(Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+pub struct ClientSubnetConfig {
+    /// the longest prefix of the IPv4 subnets sent upstream, defaults to 24
+    pub ipv4_prefix: Option<u8>,
+    /// the longest prefix of the IPv6 subnets sent upstream, defaults to 56
+    pub ipv6_prefix: Option<u8>,
+}
+
+impl ClientSubnetConfig {
+    /// The longest prefix of the IPv4 subnets sent upstream
+    pub fn get_ipv4_prefix(&self) -> u8 {
+        self.ipv4_prefix.unwrap_or(24).min(32)
+    }
+
+    /// The longest prefix of the IPv6 subnets sent upstream
+    pub fn get_ipv6_prefix(&self) -> u8 {
+        self.ipv6_prefix.unwrap_or(56).min(128)
+    }
+
+    /// Truncates the subnet of the client to the configured prefix, the scope of queries is 0
+    pub fn truncate(&self, subnet: &ClientSubnet) -> ClientSubnet {
+        let max_prefix = match subnet.address() {
+            IpAddr::V4(..) => self.get_ipv4_prefix(),
+            IpAddr::V6(..) => self.get_ipv6_prefix(),
+        };
+
+        ClientSubnet::new(subnet.address(), subnet.source_prefix().min(max_prefix), 0)
+            .expect("prefix within the address length")
+    }
 }
@@ -12,5 +12,5 @@
 mod authority;
 mod config;
 
-pub use self::authority::ForwardAuthority;
-pub use self::config::ForwardConfig;
+pub use self::authority::{ForwardAuthority, ForwardLookup, ForwardLookupFuture};
+pub use self::config::{ClientSubnetConfig, ForwardConfig, UpstreamCheckConfig};
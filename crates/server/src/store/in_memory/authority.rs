@@ -8,8 +8,10 @@
 //! All authority related types
 
 use std::borrow::Borrow;
-use std::collections::BTreeMap;
-use std::sync::Arc;
+use std::collections::{BTreeMap, BTreeSet};
+use std::mem;
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+use std::time::Duration;
 
 use futures::future::{self, Future, FutureResult, IntoFuture};
 
@@ -24,25 +26,45 @@ use trust_dns::rr::{DNSClass, LowerName, Name, RData, Record, RecordSet, RecordT
 
 use authority::{
     AnyRecords, AuthLookup, Authority, LookupError, LookupRecords, LookupResult, MessageRequest,
-    UpdateResult, ZoneType,
+    RecordOrder, ResponseOrder, ResponseOrdering, UpdateResult, ZoneType,
 };
+use store::in_memory::{NameIndex, NameMatch, ZoneDiff};
+
+type Records = BTreeMap<RrKey, Arc<RecordSet>>;
+
+/// The published records, and the index of their names, which is rebuilt on the first lookup
+///  after the records are changed in place
+#[derive(Default)]
+struct Snapshot {
+    records: Arc<Records>,
+    names: Option<Arc<NameIndex>>,
+}
 
 /// InMemoryAuthority is responsible for storing the resource records for a particular zone.
 ///
 /// Authorities default to DNSClass IN. The ZoneType specifies if this should be treated as the
 /// start of authority for the zone, is a slave, or a cached zone.
+///
+/// The records are copy-on-write, each lookup is answered from the records as they were when it
+///  started, and updates, see `begin_update`, publish all their changes at once. Lookups never
+///  wait on an update or the signing of the zone.
 pub struct InMemoryAuthority {
     origin: LowerName,
     class: DNSClass,
-    records: BTreeMap<RrKey, Arc<RecordSet>>,
+    records: RwLock<Snapshot>,
+    // only one update is applied at a time, to the latest records
+    updates: Mutex<()>,
     zone_type: ZoneType,
     allow_axfr: bool,
+    response_ordering: ResponseOrdering,
     // Private key mapped to the Record of the DNSKey
     //  TODO: these private_keys should be stored securely. Ideally, we have keys only stored per
     //   server instance, but that requires requesting updates from the parent zone, which may or
     //   may not support dynamic updates to register the new key... Trust-DNS will provide support
     //   for this, in some form, perhaps alternate root zones...
     secure_keys: Vec<Signer>,
+    // the expirations of the signatures are spread over the jitter, see `set_signature_jitter`
+    signature_jitter: Duration,
 }
 
 impl InMemoryAuthority {
@@ -98,6 +120,48 @@ impl InMemoryAuthority {
         Ok(this)
     }
 
+    /// Creates a new Authority with the record sets as they are
+    ///
+    /// Unlike `new`, the records aren't inserted one by one, and the signatures of the record
+    ///  sets are kept. The index of the names is built here, not on the first lookup.
+    pub fn from_records(
+        origin: Name,
+        records: BTreeMap<RrKey, Arc<RecordSet>>,
+        zone_type: ZoneType,
+        allow_axfr: bool,
+    ) -> Result<Self, String> {
+        let index = NameIndex::from_keys(records.keys());
+        Self::from_records_with_index(origin, records, index, zone_type, allow_axfr)
+    }
+
+    /// Creates an authority from the records of the zone and the index of their names, e.g. as
+    ///  they were stored by `compile_zone`
+    ///
+    /// The index must be that of the keys of the records, see `NameIndex::from_keys`.
+    pub fn from_records_with_index(
+        origin: Name,
+        records: BTreeMap<RrKey, Arc<RecordSet>>,
+        index: NameIndex,
+        zone_type: ZoneType,
+        allow_axfr: bool,
+    ) -> Result<Self, String> {
+        // SOA must be present
+        if !records
+            .iter()
+            .any(|(key, rrset)| key.record_type == RecordType::SOA && !rrset.is_empty())
+        {
+            return Err(format!("SOA record must be present: {}", origin));
+        }
+
+        let mut this = Self::empty(origin, zone_type, allow_axfr);
+        *this.records.get_mut().expect("records poisoned") = Snapshot {
+            names: Some(Arc::new(index)),
+            records: Arc::new(records),
+        };
+
+        Ok(this)
+    }
+
     /// Creates an empty Authority
     ///
     /// # Warning
@@ -107,16 +171,19 @@ impl InMemoryAuthority {
         Self {
             origin: LowerName::new(&origin),
             class: DNSClass::IN,
-            records: BTreeMap::new(),
+            records: RwLock::new(Snapshot::default()),
+            updates: Mutex::new(()),
             zone_type,
             allow_axfr,
+            response_ordering: ResponseOrdering::default(),
             secure_keys: Vec::new(),
+            signature_jitter: Duration::from_secs(0),
         }
     }
 
     /// Clears all records (including SOA, etc)
     pub fn clear(&mut self) {
-        self.records.clear()
+        self.records_mut().clear()
     }
 
     /// Get the DNSClass of the zone
@@ -129,33 +196,115 @@ impl InMemoryAuthority {
         self.allow_axfr = allow_axfr;
     }
 
+    /// Sets the order in which A and AAAA records are returned in responses
+    pub fn set_response_order(&mut self, response_order: ResponseOrder) {
+        self.response_ordering = ResponseOrdering::new(response_order);
+    }
+
+    /// The order in which A and AAAA records are returned in responses
+    pub fn response_order(&self) -> ResponseOrder {
+        self.response_ordering.order()
+    }
+
+    /// The order of the records in the next answer of the `record_type`, see `ResponseOrder`
+    pub fn record_order(&self, record_type: RecordType) -> RecordOrder {
+        self.response_ordering.next(record_type)
+    }
+
     /// Retrieve the Signer, which contains the private keys, for this zone
     pub fn secure_keys(&self) -> &[Signer] {
         &self.secure_keys
     }
 
-    /// Get all the records
-    pub fn records(&self) -> &BTreeMap<RrKey, Arc<RecordSet>> {
-        &self.records
+    /// The window over which the expirations of the signatures are spread, see
+    ///  `Authority::set_signature_jitter`
+    pub fn signature_jitter(&self) -> Duration {
+        self.signature_jitter
+    }
+
+    /// Get all the records, these are not affected by subsequent updates
+    pub fn records(&self) -> Arc<BTreeMap<RrKey, Arc<RecordSet>>> {
+        Arc::clone(&self.records.read().expect("records poisoned").records)
     }
 
     /// Get a mutable reference to the records
     pub fn records_mut(&mut self) -> &mut BTreeMap<RrKey, Arc<RecordSet>> {
-        &mut self.records
+        let snapshot = self.records.get_mut().expect("records poisoned");
+        snapshot.names = None;
+        Arc::make_mut(&mut snapshot.records)
     }
 
-    fn inner_soa(&self) -> Option<&SOA> {
+    /// The changes from the records of this authority to those of `newer`, e.g. a reloaded zone
+    ///
+    /// To diff versions of the same authority, see `ZoneDiff::new` with the `records` taken
+    ///  before and after an update.
+    pub fn diff(&self, newer: &InMemoryAuthority) -> ZoneDiff {
+        ZoneDiff::new(&self.records(), &newer.records())
+    }
+
+    /// The records, and the index of their names, building the index if the records changed
+    fn snapshot(&self) -> (Arc<Records>, Arc<NameIndex>) {
+        {
+            let snapshot = self.records.read().expect("records poisoned");
+            if let Some(ref names) = snapshot.names {
+                return (Arc::clone(&snapshot.records), Arc::clone(names));
+            }
+        }
+
+        let records = self.records();
+        let names = Arc::new(NameIndex::from_keys(records.keys()));
+
+        let mut snapshot = self.records.write().expect("records poisoned");
+        if Arc::ptr_eq(&snapshot.records, &records) {
+            snapshot.names = Some(Arc::clone(&names));
+        }
+
+        (records, names)
+    }
+
+    /// Begins an update of the records, waiting for the update in progress, if any, to complete
+    ///
+    /// The changes of the update are made to a copy of the records, in the meantime lookups are
+    ///  answered from the records as they were before the update.
+    pub fn begin_update(&self) -> InMemoryUpdate {
+        let updating = self.updates.lock().expect("updates poisoned");
+
+        let (records, names) = self.snapshot();
+        InMemoryUpdate {
+            authority: self,
+            records,
+            names,
+            changed: Some(BTreeSet::new()),
+            _updating: updating,
+        }
+    }
+
+    /// Changes the records in place, no lookup or update can be in progress as self is borrowed
+    fn with_records_mut<T, F: FnOnce(&Self, &mut Records) -> T>(&mut self, f: F) -> T {
+        let mut records = mem::replace(
+            &mut self.records.get_mut().expect("records poisoned").records,
+            Arc::default(),
+        );
+        let result = f(self, Arc::make_mut(&mut records));
+
+        *self.records.get_mut().expect("records poisoned") = Snapshot {
+            records,
+            names: None,
+        };
+        result
+    }
+
+    fn inner_soa<'r>(&self, records: &'r Records) -> Option<&'r SOA> {
         let rr_key = RrKey::new(self.origin.clone(), RecordType::SOA);
 
-        self.records
+        records
             .get(&rr_key)
             .and_then(|rrset| rrset.records_without_rrsigs().next())
             .and_then(|record| record.rdata().as_soa())
     }
 
-    /// Returns the minimum ttl (as used in the SOA record)
-    pub fn minimum_ttl(&self) -> u32 {
-        let soa = self.inner_soa();
+    fn inner_minimum_ttl(&self, records: &Records) -> u32 {
+        let soa = self.inner_soa(records);
 
         let soa = match soa {
             Some(soa) => soa,
@@ -168,9 +317,8 @@ impl InMemoryAuthority {
         soa.minimum()
     }
 
-    /// get the current serial number for the zone.
-    pub fn serial(&self) -> u32 {
-        let soa = self.inner_soa();
+    fn inner_serial(&self, records: &Records) -> u32 {
+        let soa = self.inner_soa(records);
 
         let soa = match soa {
             Some(soa) => soa,
@@ -183,90 +331,101 @@ impl InMemoryAuthority {
         soa.serial()
     }
 
+    /// Returns the minimum ttl (as used in the SOA record)
+    pub fn minimum_ttl(&self) -> u32 {
+        self.inner_minimum_ttl(&self.records())
+    }
+
+    /// get the current serial number for the zone.
+    pub fn serial(&self) -> u32 {
+        self.inner_serial(&self.records())
+    }
+
     fn inner_lookup(
         &self,
+        records: &Records,
+        names: &NameIndex,
         name: &LowerName,
         record_type: RecordType,
-        and_rrsigs: bool,
-        supported_algorithms: SupportedAlgorithms,
     ) -> Option<Arc<RecordSet>> {
-        // this range covers all the records for any of the RecordTypes at a given label.
-        let start_range_key = RrKey::new(name.clone(), RecordType::Unknown(u16::min_value()));
-        let end_range_key = RrKey::new(name.clone(), RecordType::Unknown(u16::max_value()));
-
-        fn aname_covers_type(key_type: RecordType, query_type: RecordType) -> bool {
-            (query_type == RecordType::A || query_type == RecordType::AAAA)
-                && key_type == RecordType::ANAME
-        }
-
-        let lookup = self
-            .records
-            .range(&start_range_key..&end_range_key)
-            // remember CNAME can be the only record at a particular label
-            .find(|(key, _)| {
-                key.record_type == record_type
-                    || key.record_type == RecordType::CNAME
-                    || aname_covers_type(key.record_type, record_type)
-            })
-            .map(|(_key, rr_set)| rr_set);
-
-        // TODO: maybe unwrap this recursion.
-        match lookup {
-            None => self.inner_lookup_wildcard(name, record_type, and_rrsigs, supported_algorithms),
-            l => l.cloned(),
+        match names.lookup(name) {
+            NameMatch::Exact(record_types) => {
+                Self::lookup_rrset(records, record_types, name, record_type)
+            }
+            // the wildcard only matches names which don't exist, RFC 4592
+            NameMatch::Closest {
+                wildcard: Some((ref wildcard, record_types)),
+                ..
+            } if !name.is_wildcard() => {
+                Self::lookup_rrset(records, record_types, wildcard, record_type)
+                    .map(|rrset| Self::synthesize_wildcard(name, &rrset))
+            }
+            NameMatch::Closest { .. } => None,
         }
     }
 
-    fn inner_lookup_wildcard(
-        &self,
+    /// Finds the record set of the `record_type` at the name, of which the index has the
+    ///  `record_types`
+    fn lookup_rrset(
+        records: &Records,
+        record_types: &[RecordType],
         name: &LowerName,
         record_type: RecordType,
-        and_rrsigs: bool,
-        supported_algorithms: SupportedAlgorithms,
     ) -> Option<Arc<RecordSet>> {
-        let wildcard = if name.is_wildcard() {
-            return None;
-        } else {
-            name.clone().into_wildcard()
-        };
+        // remember CNAME can be the only record at a particular label
+        fn covers_type(key_type: RecordType, query_type: RecordType) -> bool {
+            key_type == query_type
+                || key_type == RecordType::CNAME
+                || ((query_type == RecordType::A || query_type == RecordType::AAAA)
+                    && key_type == RecordType::ANAME)
+        }
 
-        self.inner_lookup(&wildcard, record_type, and_rrsigs, supported_algorithms)
-            // we need to change the name to the query name in the result set since this was a whildcard
-            .map(|rrset| {
-                let mut new_answer =
-                    RecordSet::new(name.borrow(), rrset.record_type(), rrset.ttl());
+        // most of the lookups of names without records of the type end here, without a search
+        if !record_types
+            .iter()
+            .any(|key_type| covers_type(*key_type, record_type))
+        {
+            return None;
+        }
 
-                let (records, _rrsigs): (Vec<&Record>, Vec<&Record>) = rrset
-                    .records(and_rrsigs, supported_algorithms)
-                    .partition(|r| r.record_type() != RecordType::DNSSEC(DNSSECRecordType::RRSIG));
+        // this range covers all the records for any of the RecordTypes at a given label.
+        let start_range_key = RrKey::new(name.clone(), RecordType::Unknown(u16::min_value()));
+        let end_range_key = RrKey::new(name.clone(), RecordType::Unknown(u16::max_value()));
 
-                for record in records {
-                    new_answer.add_rdata(record.rdata().clone());
-                }
+        records
+            .range(&start_range_key..&end_range_key)
+            .find(|(key, _)| covers_type(key.record_type, record_type))
+            .map(|(_key, rr_set)| rr_set)
+            .cloned()
+    }
 
-                #[cfg(feature = "dnssec")]
-                for rrsig in _rrsigs {
-                    new_answer.insert_rrsig(rrsig.clone())
-                }
+    /// The records of the wildcard's record set, with the query name
+    ///
+    /// This is the only copy of the records of a lookup, the RRSIGs are copied along and filtered
+    ///  by the lookup as those of any other set.
+    fn synthesize_wildcard(name: &LowerName, rrset: &RecordSet) -> Arc<RecordSet> {
+        let mut new_answer = rrset.clone();
+        new_answer.set_name(Name::from(name));
 
-                Arc::new(new_answer)
-            })
+        Arc::new(new_answer)
     }
 
     /// Search for additional records to include in the response
     ///
     /// # Arguments
     ///
+    /// * records - the records of the zone as of the start of the lookup
+    /// * names - the index of the names of the records
     /// * query_type - original type in the request query
     /// * next_name - the name from the CNAME, ANAME, MX, etc. record that is being searched
     /// * search_type - the root search type, ANAME, CNAME, MX, i.e. the begging of the chain
     fn additional_search(
         &self,
+        records: &Records,
+        names: &NameIndex,
         query_type: RecordType,
         next_name: LowerName,
         _search_type: RecordType,
-        and_rrsigs: bool,
-        supported_algorithms: SupportedAlgorithms,
     ) -> Option<Vec<Arc<RecordSet>>> {
         let mut additionals: Vec<Arc<RecordSet>> = vec![];
 
@@ -284,8 +443,7 @@ impl InMemoryAuthority {
             // loop and collect any additional records to send
             let mut next_name = Some(next_name.clone());
             while let Some(search) = next_name.take() {
-                let additional =
-                    self.inner_lookup(&search, *query_type, and_rrsigs, supported_algorithms);
+                let additional = self.inner_lookup(records, names, &search, *query_type);
                 let mut continue_name = None;
 
                 if let Some(additional) = additional {
@@ -309,11 +467,10 @@ impl InMemoryAuthority {
         }
     }
 
-    pub(crate) fn increment_soa_serial(&mut self) -> u32 {
+    fn inner_increment_soa_serial(&self, records: &mut Records) -> u32 {
         // we'll remove the SOA and then replace it
         let rr_key = RrKey::new(self.origin.clone(), RecordType::SOA);
-        let record = self
-            .records
+        let record = records
             .remove(&rr_key)
             // TODO: there should be an unwrap on rrset, but it's behind Arc
             .and_then(|rrset| rrset.records_without_rrsigs().next().cloned());
@@ -332,7 +489,7 @@ impl InMemoryAuthority {
             panic!("This was not an SOA record"); // valid panic, never should happen
         };
 
-        self.upsert(record, serial);
+        self.inner_upsert(records, record, serial);
         serial
     }
 
@@ -349,6 +506,10 @@ impl InMemoryAuthority {
     ///
     /// true if the value was inserted, false otherwise
     pub fn upsert(&mut self, record: Record, serial: u32) -> bool {
+        self.with_records_mut(|this, records| this.inner_upsert(records, record, serial))
+    }
+
+    fn inner_upsert(&self, records: &mut Records, record: Record, serial: u32) -> bool {
         assert_eq!(self.class, record.dns_class());
 
         #[cfg(feature = "dnssec")]
@@ -384,8 +545,7 @@ impl InMemoryAuthority {
             RrKey::new(record.name().into(), RecordType::Unknown(u16::min_value()));
         let end_range_key = RrKey::new(record.name().into(), RecordType::Unknown(u16::max_value()));
 
-        let multiple_records_at_label_disallowed = self
-            .records
+        let multiple_records_at_label_disallowed = records
             .range(&start_range_key..&end_range_key)
             // remember CNAME can be the only record at a particular label
             .any(|(key, _)| {
@@ -403,8 +563,7 @@ impl InMemoryAuthority {
         }
 
         let rr_key = RrKey::new(record.name().into(), record.rr_type());
-        let records: &mut Arc<RecordSet> = self
-            .records
+        let records: &mut Arc<RecordSet> = records
             .entry(rr_key)
             .or_insert_with(|| Arc::new(RecordSet::new(record.name(), record.rr_type(), serial)));
 
@@ -421,55 +580,67 @@ impl InMemoryAuthority {
     /// (Re)generates the nsec records, increments the serial number nad signs the zone
     #[cfg(feature = "dnssec")]
     pub fn secure_zone(&mut self) -> DnsSecResult<()> {
+        self.with_records_mut(|this, records| this.inner_secure_zone(records))
+            .map(|_| ())
+    }
+
+    /// (Re)generates the nsec records, increments the serial number nad signs the zone
+    #[cfg(not(feature = "dnssec"))]
+    pub fn secure_zone(&mut self) -> Result<(), &str> {
+        Err("DNSSEC was not enabled during compilation.")
+    }
+
+    /// Returns the keys of the nsec records which were added, replaced or removed
+    #[cfg(feature = "dnssec")]
+    fn inner_secure_zone(&self, records: &mut Records) -> DnsSecResult<Vec<RrKey>> {
         // TODO: only call nsec_zone after adds/deletes
         // needs to be called before incrementing the soa serial, to make sur IXFR works properly
-        self.nsec_zone();
+        let nsec_keys = self.nsec_zone(records);
 
         // need to resign any records at the current serial number and bump the number.
         // first bump the serial number on the SOA, so that it is resigned with the new serial.
-        self.increment_soa_serial();
+        self.inner_increment_soa_serial(records);
 
         // TODO: should we auto sign here? or maybe up a level...
-        self.sign_zone()
+        self.sign_zone(records).map(|_| nsec_keys)
     }
 
-    /// (Re)generates the nsec records, increments the serial number nad signs the zone
-    #[cfg(not(feature = "dnssec"))]
-    pub fn secure_zone(&mut self) -> Result<(), &str> {
-        Err("DNSSEC was not enabled during compilation.")
+    /// Like `inner_secure_zone`, but only the record sets changed by an update are signed
+    #[cfg(feature = "dnssec")]
+    fn inner_secure_changes(&self, records: &mut Records) -> DnsSecResult<Vec<RrKey>> {
+        let serial = self.inner_serial(records);
+
+        let nsec_keys = self.nsec_zone(records);
+        self.inner_increment_soa_serial(records);
+        self.sign_changes(records, serial).map(|_| nsec_keys)
     }
 
-    /// Dummy implementation for when DNSSEC is disabled.
+    /// (Re)generates the NSEC records of the zone
+    ///
+    /// The NSEC records which are unchanged keep their signatures, only those of the names which
+    ///  were added or whose types changed, and of the names before them in the chain, are
+    ///  replaced and then signed by `sign_zone` or `sign_changes`. Returns the keys of the NSEC
+    ///  records which were added, replaced or removed.
     #[cfg(feature = "dnssec")]
-    fn nsec_zone(&mut self) {
+    fn nsec_zone(&self, records: &mut Records) -> Vec<RrKey> {
         use trust_dns::rr::rdata::{DNSSECRData, DNSSECRecordType, NSEC};
 
         // only create nsec records for secure zones
         if self.secure_keys.is_empty() {
-            return;
+            return vec![];
         }
         debug!("generating nsec records: {}", self.origin);
 
-        // first remove all existing nsec records
-        let delete_keys: Vec<RrKey> = self
-            .records
-            .keys()
-            .filter(|k| k.record_type == RecordType::DNSSEC(DNSSECRecordType::NSEC))
-            .cloned()
-            .collect();
-
-        for key in delete_keys {
-            self.records.remove(&key);
-        }
-
-        // now go through and generate the nsec records
-        let ttl = self.minimum_ttl();
-        let serial = self.serial();
-        let mut records: Vec<Record> = vec![];
+        // now go through and generate the nsec records, of all the names but those with only
+        //  an existing nsec record
+        let nsec_type = RecordType::DNSSEC(DNSSECRecordType::NSEC);
+        let ttl = self.inner_minimum_ttl(records);
+        let serial = self.inner_serial(records);
+        let mut nsec_records: BTreeMap<RrKey, Record> = BTreeMap::new();
 
         {
             let mut nsec_info: Option<(&Name, Vec<RecordType>)> = None;
-            for key in self.records.keys() {
+            for key in records.keys().filter(|k| k.record_type != nsec_type) {
                 match nsec_info {
                     None => nsec_info = Some((key.name.borrow(), vec![key.record_type])),
                     Some((name, ref mut vec)) if LowerName::new(name) == key.name => {
@@ -477,14 +648,10 @@ impl InMemoryAuthority {
                     }
                     Some((name, vec)) => {
                         // names aren't equal, create the NSEC record
-                        let mut record = Record::with(
-                            name.clone(),
-                            RecordType::DNSSEC(DNSSECRecordType::NSEC),
-                            ttl,
-                        );
+                        let mut record = Record::with(name.clone(), nsec_type, ttl);
                         let rdata = NSEC::new_cover_self(key.name.clone().into(), vec);
                         record.set_rdata(RData::DNSSEC(DNSSECRData::NSEC(rdata)));
-                        records.push(record);
+                        nsec_records.insert(RrKey::new(name.into(), nsec_type), record);
 
                         // new record...
                         nsec_info = Some((&key.name.borrow(), vec![key.record_type]))
@@ -495,22 +662,51 @@ impl InMemoryAuthority {
             // the last record
             if let Some((name, vec)) = nsec_info {
                 // names aren't equal, create the NSEC record
-                let mut record = Record::with(
-                    name.clone(),
-                    RecordType::DNSSEC(DNSSECRecordType::NSEC),
-                    ttl,
-                );
+                let mut record = Record::with(name.clone(), nsec_type, ttl);
                 let rdata = NSEC::new_cover_self(Authority::origin(self).clone().into(), vec);
                 record.set_rdata(RData::DNSSEC(DNSSECRData::NSEC(rdata)));
-                records.push(record);
+                nsec_records.insert(RrKey::new(name.into(), nsec_type), record);
             }
         }
 
-        // insert all the nsec records
-        for record in records {
-            let upserted = self.upsert(record, serial);
-            debug_assert!(upserted);
+        // remove the nsec records of the names which were removed
+        let removed_keys: Vec<RrKey> = records
+            .keys()
+            .filter(|k| k.record_type == nsec_type && !nsec_records.contains_key(k))
+            .cloned()
+            .collect();
+        for key in &removed_keys {
+            records.remove(key);
         }
+
+        // insert the changed nsec records at once, there's a single NSEC record for each name
+        let changed_rrsets = nsec_records
+            .into_iter()
+            .filter(|(key, record)| {
+                let rrset = match records.get(key) {
+                    Some(rrset) => rrset,
+                    None => return true,
+                };
+                let mut existing = rrset.records_without_rrsigs();
+                match (existing.next(), existing.next()) {
+                    (Some(existing), None) => {
+                        existing.rdata() != record.rdata() || existing.ttl() != record.ttl()
+                    }
+                    _ => true,
+                }
+            })
+            .map(|(key, mut record)| {
+                record.set_dns_class(self.class);
+                let mut rrset = RecordSet::new(record.name(), nsec_type, serial);
+                rrset.insert(record, serial);
+                (key, Arc::new(rrset))
+            })
+            .collect::<Vec<_>>();
+
+        let mut changed_keys = removed_keys;
+        changed_keys.extend(changed_rrsets.iter().map(|(key, _)| key.clone()));
+        records.extend(changed_rrsets);
+        changed_keys
     }
 
     /// Signs an RecordSet, and stores the RRSIGs in the RecordSet
@@ -523,18 +719,27 @@ impl InMemoryAuthority {
     /// * `secure_keys` - Set of keys to use to sign the RecordSet, see `self.signers()`
     /// * `zone_ttl` - the zone TTL, see `self.minimum_ttl()`
     /// * `zone_class` - DNSClass of the zone, see `self.zone_class()`
+    /// * `signature_jitter` - the signatures expire earlier by a random time within the jitter,
+    ///                        see `self.signature_jitter()`
     #[cfg(feature = "dnssec")]
     fn sign_rrset(
         rr_set: &mut RecordSet,
         secure_keys: &[Signer],
         zone_ttl: u32,
         zone_class: DNSClass,
+        signature_jitter: Duration,
     ) -> DnsSecResult<()> {
-        use chrono::Utc;
+        use chrono::{self, Utc};
+        use rand::{self, Rng};
         use trust_dns::rr::dnssec::tbs;
         use trust_dns::rr::rdata::{DNSSECRData, DNSSECRecordType, SIG};
 
         let inception = Utc::now();
+        // the signatures of the rrset expire together, those of the other rrsets at other times
+        let jitter = match signature_jitter.as_secs() {
+            0 => chrono::Duration::zero(),
+            jitter => chrono::Duration::seconds(rand::thread_rng().gen_range(0, jitter + 1) as i64),
+        };
 
         rr_set.clear_rrsigs();
 
@@ -543,7 +748,6 @@ impl InMemoryAuthority {
             RecordType::DNSSEC(DNSSECRecordType::RRSIG),
             zone_ttl,
         );
-
         for signer in secure_keys {
             debug!(
                 "signing rr_set: {}, {} with: {}",
@@ -552,7 +756,8 @@ impl InMemoryAuthority {
                 signer.algorithm(),
             );
 
-            let expiration = inception + signer.sig_duration();
+            let expiration =
+                inception + (signer.sig_duration() - jitter).max(chrono::Duration::zero());
 
             let tbs = tbs::rrset_tbs(
                 rr_set.name(),
@@ -565,12 +770,7 @@ impl InMemoryAuthority {
                 inception.timestamp() as u32,
                 signer.calculate_key_tag()?,
                 signer.signer_name(),
-                // TODO: this is a nasty clone... the issue is that the vec
-                //  from records is of Vec<&R>, but we really want &[R]
-                &rr_set
-                    .records_without_rrsigs()
-                    .cloned()
-                    .collect::<Vec<Record>>(),
+                rr_set.records_without_rrsigs(),
             );
 
             // TODO, maybe chain these with some ETL operations instead?
@@ -620,13 +820,17 @@ impl InMemoryAuthority {
     }
 
     /// Signs any records in the zone that have serial numbers greater than or equal to `serial`
+    ///
+    /// The record sets are signed in parallel on the global rayon thread pool, see
+    ///  `RAYON_NUM_THREADS` for its size.
     #[cfg(feature = "dnssec")]
-    fn sign_zone(&mut self) -> DnsSecResult<()> {
+    fn sign_zone(&self, records: &mut Records) -> DnsSecResult<()> {
+        use rayon::prelude::*;
+
         debug!("signing zone: {}", self.origin);
 
-        let minimum_ttl = self.minimum_ttl();
+        let minimum_ttl = self.inner_minimum_ttl(records);
         let secure_keys = &self.secure_keys;
-        let records = &mut self.records;
 
         // TODO: should this be an error?
         if secure_keys.is_empty() {
@@ -634,14 +838,173 @@ impl InMemoryAuthority {
         }
 
         // sign all record_sets, as of 0.12.1 this includes DNSKEY
-        for mut rr_set_orig in records.values_mut() {
+        let class = self.class;
+        let signature_jitter = self.signature_jitter;
+        records.par_iter_mut().try_for_each(|(_, rr_set_orig)| {
             // becuase the rrset is an Arc, it must be cloned before mutated
             let rr_set = Arc::make_mut(rr_set_orig);
-            Self::sign_rrset(rr_set, secure_keys, minimum_ttl, self.class)?;
+            Self::sign_rrset(rr_set, secure_keys, minimum_ttl, class, signature_jitter)
+        })
+    }
+
+    /// Signs the record sets changed at or after `serial`, and those without signatures
+    ///
+    /// The changes of an update are made at the serial of the zone before it's incremented, see
+    ///  `RecordSet::insert`, the other record sets keep their signatures.
+    #[cfg(feature = "dnssec")]
+    fn sign_changes(&self, records: &mut Records, serial: u32) -> DnsSecResult<()> {
+        use rayon::prelude::*;
+
+        debug!("signing changes of zone: {} since: {}", self.origin, serial);
+
+        let minimum_ttl = self.inner_minimum_ttl(records);
+        let secure_keys = &self.secure_keys;
+        let class = self.class;
+        let signature_jitter = self.signature_jitter;
+        records
+            .par_iter_mut()
+            .filter(|(_, rr_set)| rr_set.serial() >= serial || rr_set.rrsigs().is_empty())
+            .try_for_each(|(_, rr_set_orig)| {
+                let rr_set = Arc::make_mut(rr_set_orig);
+                Self::sign_rrset(rr_set, secure_keys, minimum_ttl, class, signature_jitter)
+            })
+    }
+}
+
+/// An update of the records of an `InMemoryAuthority`, see `InMemoryAuthority::begin_update`
+///
+/// The changes are made to a copy of the records, lookups see none of them until the update is
+///  committed, and all of them after. Dropping the update discards the changes.
+pub struct InMemoryUpdate<'a> {
+    authority: &'a InMemoryAuthority,
+    records: Arc<Records>,
+    // the index of the records before the update, and the keys the update added or removed,
+    //  unknown once the records were changed through `records_mut`
+    names: Arc<NameIndex>,
+    changed: Option<BTreeSet<RrKey>>,
+    _updating: MutexGuard<'a, ()>,
+}
+
+impl<'a> InMemoryUpdate<'a> {
+    /// The records, with the changes of the update
+    pub fn records(&self) -> &BTreeMap<RrKey, Arc<RecordSet>> {
+        &self.records
+    }
+
+    /// Get a mutable reference to the records, they are copied on the first change
+    ///
+    /// The index of the names is then rebuilt from all the records when the update is
+    ///  committed, see `remove`, `get_mut` and `clear` for the usual changes.
+    pub fn records_mut(&mut self) -> &mut BTreeMap<RrKey, Arc<RecordSet>> {
+        self.changed = None;
+        Arc::make_mut(&mut self.records)
+    }
+
+    /// Removes the record set of the key, returning it if it was present
+    pub fn remove(&mut self, key: &RrKey) -> Option<Arc<RecordSet>> {
+        self.changed(key);
+        Arc::make_mut(&mut self.records).remove(key)
+    }
+
+    /// Get a mutable reference to the record set of the key, to change its records
+    pub fn get_mut(&mut self, key: &RrKey) -> Option<&mut Arc<RecordSet>> {
+        Arc::make_mut(&mut self.records).get_mut(key)
+    }
+
+    /// Removes all the records
+    pub fn clear(&mut self) {
+        self.names = Arc::new(NameIndex::new());
+        self.changed = Some(BTreeSet::new());
+        self.records = Arc::new(Records::new());
+    }
+
+    fn changed(&mut self, key: &RrKey) {
+        if let Some(ref mut changed) = self.changed {
+            changed.insert(key.clone());
         }
+    }
 
+    /// The serial number of the zone, with the changes of the update
+    pub fn serial(&self) -> u32 {
+        self.authority.inner_serial(&self.records)
+    }
+
+    /// Inserts or updates a `Record`, see `InMemoryAuthority::upsert`
+    pub fn upsert(&mut self, record: Record, serial: u32) -> bool {
+        self.changed(&RrKey::new(record.name().into(), record.rr_type()));
+        self.authority
+            .inner_upsert(Arc::make_mut(&mut self.records), record, serial)
+    }
+
+    /// Increments the serial number of the SOA record, returning the new serial
+    pub fn increment_soa_serial(&mut self) -> u32 {
+        self.authority
+            .inner_increment_soa_serial(Arc::make_mut(&mut self.records))
+    }
+
+    /// (Re)generates the nsec records, increments the serial number and signs the zone
+    #[cfg(feature = "dnssec")]
+    pub fn secure_zone(&mut self) -> DnsSecResult<()> {
+        let nsec_keys = self
+            .authority
+            .inner_secure_zone(Arc::make_mut(&mut self.records))?;
+        for key in &nsec_keys {
+            self.changed(key);
+        }
         Ok(())
     }
+
+    /// (Re)generates the nsec records, increments the serial number and signs the zone
+    #[cfg(not(feature = "dnssec"))]
+    pub fn secure_zone(&mut self) -> Result<(), &str> {
+        Err("DNSSEC was not enabled during compilation.")
+    }
+
+    /// Updates the nsec records, increments the serial number and signs the changes of the update
+    ///
+    /// Only the record sets changed by the update, at the serial number of the zone before it's
+    ///  incremented, and their nsec records are signed, see `secure_zone` to sign all of them.
+    #[cfg(feature = "dnssec")]
+    pub fn secure_changes(&mut self) -> DnsSecResult<()> {
+        let nsec_keys = self
+            .authority
+            .inner_secure_changes(Arc::make_mut(&mut self.records))?;
+        for key in &nsec_keys {
+            self.changed(key);
+        }
+        Ok(())
+    }
+
+    /// Updates the nsec records, increments the serial number and signs the changes of the update
+    #[cfg(not(feature = "dnssec"))]
+    pub fn secure_changes(&mut self) -> Result<(), &str> {
+        Err("DNSSEC was not enabled during compilation.")
+    }
+
+    /// Publishes the changes, subsequent lookups are answered from the updated records
+    pub fn commit(self) {
+        // the names are indexed before publishing, the lookups right after the update don't wait
+        let names = match self.changed {
+            Some(ref changed) if changed.is_empty() => self.names,
+            Some(ref changed) => {
+                let mut names = NameIndex::clone(&self.names);
+                for key in changed {
+                    if self.records.contains_key(key) {
+                        names.insert(key.name(), key.record_type);
+                    } else {
+                        names.remove(key.name(), key.record_type);
+                    }
+                }
+                Arc::new(names)
+            }
+            None => Arc::new(NameIndex::from_keys(self.records.keys())),
+        };
+
+        *self.authority.records.write().expect("records poisoned") = Snapshot {
+            records: self.records,
+            names: Some(names),
+        };
+    }
 }
 
 /// Gets the next search name, and returns the RecordType that it originated from
@@ -764,7 +1127,7 @@ impl Authority for InMemoryAuthority {
     ///
     /// true if any of additions, updates or deletes were made to the zone, false otherwise. Err is
     ///  returned in the case of bad data, etc.
-    fn update(&mut self, _update: &MessageRequest) -> UpdateResult<bool> {
+    fn update(&self, _update: &MessageRequest) -> UpdateResult<bool> {
         Err(ResponseCode::NotImp)
     }
 
@@ -794,6 +1157,9 @@ impl Authority for InMemoryAuthority {
         is_secure: bool,
         supported_algorithms: SupportedAlgorithms,
     ) -> Self::LookupFuture {
+        // the records as of the start of the lookup, updates in the meantime don't affect it
+        let (records, names) = self.snapshot();
+
         // Collect the records from each rr_set
         let (result, additionals): (LookupResult<LookupRecords>, Option<LookupRecords>) =
             match query_type {
@@ -801,7 +1167,7 @@ impl Authority for InMemoryAuthority {
                     let result = AnyRecords::new(
                         is_secure,
                         supported_algorithms,
-                        self.records.values().cloned().collect(),
+                        records.values().cloned().collect(),
                         query_type,
                         name.clone(),
                     );
@@ -809,8 +1175,7 @@ impl Authority for InMemoryAuthority {
                 }
                 _ => {
                     // perform the lookup
-                    let answer =
-                        self.inner_lookup(name, query_type, is_secure, supported_algorithms);
+                    let answer = self.inner_lookup(&records, &names, name, query_type);
 
                     // evaluate any cnames for additional inclusion
                     let additionals_root_chain_type: Option<(_, _)> = answer
@@ -818,11 +1183,11 @@ impl Authority for InMemoryAuthority {
                         .and_then(|a| maybe_next_name(&*a, query_type))
                         .and_then(|(search_name, search_type)| {
                             self.additional_search(
+                                &records,
+                                &names,
                                 query_type,
                                 search_name,
                                 search_type,
-                                is_secure,
-                                supported_algorithms,
                             )
                             .map(|adds| (adds, search_type))
                         });
@@ -886,6 +1251,7 @@ impl Authority for InMemoryAuthority {
                                             self.secure_keys(),
                                             self.minimum_ttl(),
                                             self.class(),
+                                            self.signature_jitter(),
                                         )
                                         // rather than failing the request, we'll just warn
                                         .map_err(|e| warn!("failed to sign ANAME record: {}", e))
@@ -909,11 +1275,9 @@ impl Authority for InMemoryAuthority {
                     // map the answer to a result
                     let answer =
                         answer.map_or(Err(LookupError::from(ResponseCode::NXDomain)), |rr_set| {
-                            Ok(LookupRecords::new(
-                                is_secure,
-                                supported_algorithms,
-                                rr_set.clone(),
-                            ))
+                            let order = self.record_order(rr_set.record_type());
+                            Ok(LookupRecords::new(is_secure, supported_algorithms, rr_set)
+                                .with_order(order))
                         });
 
                     let additionals = additionals
@@ -930,11 +1294,15 @@ impl Authority for InMemoryAuthority {
         // TODO: can we get rid of this?
         let result = match result {
             Err(LookupError::ResponseCode(ResponseCode::NXDomain)) => {
-                if self
-                    .records
-                    .keys()
-                    .any(|key| key.name() == name || name.zone_of(key.name()))
-                {
+                // the name exists if there are records at or below it, or it matches a wildcard
+                let exists = match names.lookup(name) {
+                    NameMatch::Exact(..) => true,
+                    NameMatch::Closest { wildcard, .. } => {
+                        wildcard.is_some() && !name.is_wildcard()
+                    }
+                };
+
+                if exists {
                     return Err(LookupError::NameExists).into_future();
                 } else {
                     return Err(LookupError::from(ResponseCode::NXDomain)).into_future();
@@ -1027,8 +1395,8 @@ impl Authority for InMemoryAuthority {
 
         // TODO: need a BorrowdRrKey
         let rr_key = RrKey::new(name.clone(), RecordType::DNSSEC(DNSSECRecordType::NSEC));
-        let no_data = self
-            .records
+        let (records, names) = self.snapshot();
+        let no_data = records
             .get(&rr_key)
             .map(|rr_set| LookupRecords::new(is_secure, supported_algorithms, rr_set.clone()));
 
@@ -1037,7 +1405,7 @@ impl Authority for InMemoryAuthority {
         }
 
         let get_closest_nsec = |name: &LowerName| -> Option<Arc<RecordSet>> {
-            self.records
+            records
                 .values()
                 .rev()
                 .filter(|rr_set| is_nsec_rrset(rr_set))
@@ -1063,19 +1431,24 @@ impl Authority for InMemoryAuthority {
 
         let closest_proof = get_closest_nsec(name);
 
-        // we need the wildcard proof, but make sure that it's still part of the zone.
-        let wildcard = name.base_name();
-        let wildcard = if self.origin().zone_of(&wildcard) {
-            wildcard
-        } else {
-            self.origin().clone()
-        };
+        // we need the proof that there is no wildcard of the closest encloser, if the name doesn't
+        //  exist, but make sure that it's still part of the zone.
+        let wildcard_proof = match names.lookup(name) {
+            NameMatch::Closest { encloser, .. } => {
+                let encloser = if self.origin().zone_of(&encloser) {
+                    encloser
+                } else {
+                    self.origin().clone()
+                };
+                let encloser: &Name = encloser.borrow();
 
-        // don't duplicate the record...
-        let wildcard_proof = if wildcard != *name {
-            get_closest_nsec(&wildcard)
-        } else {
-            None
+                get_closest_nsec(&LowerName::from(
+                    Name::from_labels(Some("*"))
+                        .expect("wildcard is a valid label")
+                        .append_domain(encloser),
+                ))
+            }
+            NameMatch::Exact(..) => None,
         };
 
         let proofs = match (closest_proof, wildcard_proof) {
@@ -1159,19 +1532,23 @@ impl Authority for InMemoryAuthority {
         Err("DNSSEC was not enabled during compilation.".into())
     }
 
-    /// (Re)generates the nsec records, increments the serial number nad signs the zone
+    /// The signatures made from now on expire earlier by a random time within the jitter
     #[cfg(feature = "dnssec")]
-    fn secure_zone(&mut self) -> DnsSecResult<()> {
-        // TODO: only call nsec_zone after adds/deletes
-        // needs to be called before incrementing the soa serial, to make sur IXFR works properly
-        self.nsec_zone();
+    fn set_signature_jitter(&mut self, jitter: Duration) -> DnsSecResult<()> {
+        self.signature_jitter = jitter;
+        Ok(())
+    }
 
-        // need to resign any records at the current serial number and bump the number.
-        // first bump the serial number on the SOA, so that it is resigned with the new serial.
-        self.increment_soa_serial();
+    /// This will fail, the dnssec feature must be enabled
+    #[cfg(not(feature = "dnssec"))]
+    fn set_signature_jitter(&mut self, _jitter: Duration) -> DnsSecResult<()> {
+        Err("DNSSEC was not enabled during compilation.".into())
+    }
 
-        // TODO: should we auto sign here? or maybe up a level...
-        self.sign_zone()
+    /// (Re)generates the nsec records, increments the serial number nad signs the zone
+    #[cfg(feature = "dnssec")]
+    fn secure_zone(&mut self) -> DnsSecResult<()> {
+        InMemoryAuthority::secure_zone(self)
     }
 
     /// (Re)generates the nsec records, increments the serial number nad signs the zone
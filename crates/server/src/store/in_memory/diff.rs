@@ -0,0 +1,200 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, BTreeSet};
+
+use trust_dns::rr::{Name, RData, Record, RecordSet, RecordType, RrKey};
+
+/// The changes of the records of a zone between two of its versions
+///
+/// The versions are maps of the record sets of the zone, e.g. `InMemoryAuthority::records`
+///  taken before and after an update, or the records of two versions of a zone file as returned
+///  by the `Parser`. A record is changed if its rdata or its TTL changed, a changed record is
+///  deleted and added again, as in [RFC 1995](https://tools.ietf.org/html/rfc1995), IXFR.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ZoneDiff {
+    old_soa: Option<Record>,
+    new_soa: Option<Record>,
+    // sorted by key, only the record sets which changed
+    rrsets: Vec<RrsetDiff>,
+}
+
+/// The deleted and added records of one record set, see `ZoneDiff`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RrsetDiff {
+    key: RrKey,
+    deleted: Vec<Record>,
+    added: Vec<Record>,
+}
+
+impl ZoneDiff {
+    /// Computes the changes from the `old` records to the `new` records
+    ///
+    /// The records of each record set are compared with their RRSIGs, so the new signatures of a
+    ///  resigned zone are part of the diff.
+    pub fn new<O, N>(old: &BTreeMap<RrKey, O>, new: &BTreeMap<RrKey, N>) -> Self
+    where
+        O: Borrow<RecordSet>,
+        N: Borrow<RecordSet>,
+    {
+        let keys = old.keys().chain(new.keys()).collect::<BTreeSet<_>>();
+        let rrsets = keys
+            .into_iter()
+            .filter_map(|key| {
+                RrsetDiff::new(
+                    key,
+                    old.get(key).map(Borrow::borrow),
+                    new.get(key).map(Borrow::borrow),
+                )
+            })
+            .collect();
+
+        ZoneDiff {
+            old_soa: soa_of(old),
+            new_soa: soa_of(new),
+            rrsets,
+        }
+    }
+
+    /// Returns true if no record changed, there is nothing to transfer or to notify
+    pub fn is_empty(&self) -> bool {
+        self.rrsets.is_empty()
+    }
+
+    /// The changed record sets, sorted by name and type
+    pub fn rrsets(&self) -> &[RrsetDiff] {
+        &self.rrsets
+    }
+
+    /// All the deleted records, in the order of their record sets
+    pub fn deleted(&self) -> impl Iterator<Item = &Record> {
+        self.rrsets.iter().flat_map(|rrset| rrset.deleted.iter())
+    }
+
+    /// All the added records, in the order of their record sets
+    pub fn added(&self) -> impl Iterator<Item = &Record> {
+        self.rrsets.iter().flat_map(|rrset| rrset.added.iter())
+    }
+
+    /// The serial of the SOA of the old records, None if they have no SOA
+    pub fn old_serial(&self) -> Option<u32> {
+        self.old_soa.as_ref().and_then(serial_of)
+    }
+
+    /// The serial of the SOA of the new records, None if they have no SOA
+    pub fn new_serial(&self) -> Option<u32> {
+        self.new_soa.as_ref().and_then(serial_of)
+    }
+
+    /// The records of the difference sequence of an IXFR response
+    ///
+    /// ```text
+    /// old SOA, deleted records, new SOA, added records
+    /// ```
+    ///
+    /// # Return
+    ///
+    /// None if either version has no SOA or if the serial did not change, the secondaries can't
+    ///  tell the versions apart then.
+    pub fn ixfr_records(&self) -> Option<Vec<Record>> {
+        let (old_soa, new_soa) = match (&self.old_soa, &self.new_soa) {
+            (Some(old_soa), Some(new_soa)) => (old_soa, new_soa),
+            _ => return None,
+        };
+        if self.old_serial() == self.new_serial() {
+            return None;
+        }
+
+        let not_soa = |record: &&Record| record.rr_type() != RecordType::SOA;
+        let mut records = vec![old_soa.clone()];
+        records.extend(self.deleted().filter(not_soa).cloned());
+        records.push(new_soa.clone());
+        records.extend(self.added().filter(not_soa).cloned());
+
+        Some(records)
+    }
+}
+
+impl RrsetDiff {
+    /// The changes of a record set, None if it did not change
+    fn new(key: &RrKey, old: Option<&RecordSet>, new: Option<&RecordSet>) -> Option<Self> {
+        let old = old.map(records_of).unwrap_or_default();
+        let new = new.map(records_of).unwrap_or_default();
+
+        // records are equal regardless of their TTL, a changed TTL is a change of the record
+        let is_in = |record: &Record, records: &[Record]| {
+            records
+                .iter()
+                .any(|r| r == record && r.ttl() == record.ttl())
+        };
+        let deleted = old
+            .iter()
+            .filter(|record| !is_in(record, &new))
+            .cloned()
+            .collect::<Vec<_>>();
+        let added = new
+            .iter()
+            .filter(|record| !is_in(record, &old))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if deleted.is_empty() && added.is_empty() {
+            return None;
+        }
+
+        Some(RrsetDiff {
+            key: key.clone(),
+            deleted,
+            added,
+        })
+    }
+
+    /// The name of the record set
+    pub fn name(&self) -> Name {
+        Name::from(self.key.name())
+    }
+
+    /// The type of the record set
+    pub fn record_type(&self) -> RecordType {
+        self.key.record_type
+    }
+
+    /// The records of the old version which are not in the new one
+    pub fn deleted(&self) -> &[Record] {
+        &self.deleted
+    }
+
+    /// The records of the new version which are not in the old one
+    pub fn added(&self) -> &[Record] {
+        &self.added
+    }
+}
+
+/// The records of the record set, with its RRSIGs
+fn records_of(rrset: &RecordSet) -> Vec<Record> {
+    rrset
+        .records_without_rrsigs()
+        .chain(rrset.rrsigs())
+        .cloned()
+        .collect()
+}
+
+/// The SOA record of the records, i.e. the one at the apex of the zone
+fn soa_of<R: Borrow<RecordSet>>(records: &BTreeMap<RrKey, R>) -> Option<Record> {
+    records
+        .iter()
+        .find(|(key, _)| key.record_type == RecordType::SOA)
+        .and_then(|(_, rrset)| rrset.borrow().records_without_rrsigs().next().cloned())
+}
+
+fn serial_of(soa: &Record) -> Option<u32> {
+    match *soa.rdata() {
+        RData::SOA(ref soa) => Some(soa.serial()),
+        _ => None,
+    }
+}
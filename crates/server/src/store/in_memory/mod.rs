@@ -8,5 +8,9 @@
 //! Master file based serving with Dynamic DNS and journaling support
 
 mod authority;
+mod diff;
+mod name_index;
 
-pub use self::authority::InMemoryAuthority;
+pub use self::authority::{InMemoryAuthority, InMemoryUpdate};
+pub use self::diff::{RrsetDiff, ZoneDiff};
+pub use self::name_index::{NameIndex, NameMatch};
@@ -0,0 +1,377 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::borrow::Borrow;
+use std::collections::BTreeMap;
+
+use proto::error::ProtoResult;
+use proto::serialize::binary::{BinDecodable, BinDecoder, BinEncodable, BinEncoder};
+use trust_dns::rr::{LowerName, Name, RecordType, RrKey};
+
+const WILDCARD: &[u8] = b"*";
+
+/// An index of the names of a zone, a trie of their labels starting from the root
+///
+/// Every name with records, and every ancestor of those, is a node of the trie. A lookup finds
+///  the name, or otherwise its closest encloser and the wildcard of that, in a single traversal,
+///  see [RFC 4592](https://tools.ietf.org/html/rfc4592).
+#[derive(Clone, Debug, Default)]
+pub struct NameIndex {
+    root: Node,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Node {
+    // sorted, empty for the empty non-terminals
+    record_types: Vec<RecordType>,
+    children: BTreeMap<Box<[u8]>, Node>,
+}
+
+/// The result of the lookup of a name in a `NameIndex`
+#[derive(Debug, PartialEq)]
+pub enum NameMatch<'i> {
+    /// The name exists, with records of these types, none if it is an empty non-terminal
+    Exact(&'i [RecordType]),
+    /// The name does not exist
+    Closest {
+        /// The closest encloser, the longest existing ancestor of the name
+        encloser: LowerName,
+        /// The wildcard of the closest encloser, and the types of its records, if it exists
+        wildcard: Option<(LowerName, &'i [RecordType])>,
+    },
+}
+
+impl NameIndex {
+    /// Creates an empty index
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates the index of the names of all the keys, i.e. of all the records of a zone
+    pub fn from_keys<'k, I: IntoIterator<Item = &'k RrKey>>(keys: I) -> Self {
+        let mut index = Self::new();
+        for key in keys {
+            index.insert(key.name(), key.record_type);
+        }
+
+        index
+    }
+
+    /// Adds the name, with a record of the `record_type`
+    pub fn insert(&mut self, name: &LowerName, record_type: RecordType) {
+        let name: &Name = name.borrow();
+        let node = name.iter().rev().fold(&mut self.root, |node, label| {
+            node.children
+                .entry(Box::from(label))
+                .or_insert_with(Node::default)
+        });
+
+        if let Err(i) = node.record_types.binary_search(&record_type) {
+            node.record_types.insert(i, record_type);
+        }
+    }
+
+    /// Removes the `record_type` of the name, and the nodes left without records or descendants
+    pub fn remove(&mut self, name: &LowerName, record_type: RecordType) {
+        let name: &Name = name.borrow();
+        self.root.remove(&mut name.iter().rev(), record_type);
+    }
+
+    /// Looks up the name, or the longest match of its ancestors if it does not exist
+    pub fn lookup(&self, name: &LowerName) -> NameMatch {
+        let labels: &Name = name.borrow();
+
+        let mut node = &self.root;
+        for (depth, label) in labels.iter().rev().enumerate() {
+            match node.children.get(label) {
+                Some(child) => node = child,
+                None => {
+                    return NameMatch::Closest {
+                        encloser: LowerName::new(&labels.trim_to(depth)),
+                        wildcard: node.children.get(WILDCARD).map(|wildcard| {
+                            (
+                                LowerName::new(&labels.trim_to(depth + 1).into_wildcard()),
+                                &wildcard.record_types[..],
+                            )
+                        }),
+                    }
+                }
+            }
+        }
+
+        NameMatch::Exact(&node.record_types)
+    }
+
+    /// Returns true if there are records at, or below, the name
+    pub fn contains(&self, name: &LowerName) -> bool {
+        match self.lookup(name) {
+            NameMatch::Exact(..) => true,
+            NameMatch::Closest { .. } => false,
+        }
+    }
+
+    /// Returns true if there are no names in the index
+    pub fn is_empty(&self) -> bool {
+        self.root.children.is_empty() && self.root.record_types.is_empty()
+    }
+
+    /// The number of names and types in the index, i.e. of the keys it was built from
+    pub fn len(&self) -> usize {
+        self.root.len()
+    }
+}
+
+impl Node {
+    fn len(&self) -> usize {
+        self.record_types.len() + self.children.values().map(Node::len).sum::<usize>()
+    }
+
+    /// Returns true if the node is left without records and children
+    fn remove<'l, I: Iterator<Item = &'l [u8]>>(
+        &mut self,
+        labels: &mut I,
+        record_type: RecordType,
+    ) -> bool {
+        match labels.next() {
+            Some(label) => {
+                let is_empty = match self.children.get_mut(label) {
+                    Some(child) => child.remove(labels, record_type),
+                    None => return false,
+                };
+                if is_empty {
+                    self.children.remove(label);
+                }
+            }
+            None => {
+                if let Ok(i) = self.record_types.binary_search(&record_type) {
+                    self.record_types.remove(i);
+                }
+            }
+        }
+
+        self.record_types.is_empty() && self.children.is_empty()
+    }
+}
+
+/// The nodes are written from the root, each with its sorted types and then its children in
+///  the order of their labels:
+///
+/// ```text
+/// types       u16, then each type u16
+/// children    u32, then for each child: label length u8, label, node
+/// ```
+impl BinEncodable for NameIndex {
+    fn emit(&self, encoder: &mut BinEncoder) -> ProtoResult<()> {
+        self.root.emit(encoder)
+    }
+}
+
+impl<'r> BinDecodable<'r> for NameIndex {
+    fn read(decoder: &mut BinDecoder<'r>) -> ProtoResult<Self> {
+        Ok(NameIndex {
+            root: Node::read(decoder)?,
+        })
+    }
+}
+
+impl BinEncodable for Node {
+    fn emit(&self, encoder: &mut BinEncoder) -> ProtoResult<()> {
+        encoder.emit_u16(self.record_types.len() as u16)?;
+        for record_type in &self.record_types {
+            record_type.emit(encoder)?;
+        }
+
+        encoder.emit_u32(self.children.len() as u32)?;
+        for (label, child) in &self.children {
+            encoder.emit_character_data(label)?;
+            child.emit(encoder)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'r> BinDecodable<'r> for Node {
+    fn read(decoder: &mut BinDecoder<'r>) -> ProtoResult<Self> {
+        let types = decoder.read_u16()?.unverified(/*bounded by the length of the bytes*/);
+        let mut record_types = Vec::with_capacity(types as usize);
+        for _ in 0..types {
+            let record_type = RecordType::read(decoder)?;
+            // the lookups search the types
+            if record_types.last().map_or(false, |last| *last >= record_type) {
+                return Err("record types of the name index are not sorted".into());
+            }
+            record_types.push(record_type);
+        }
+
+        let count = decoder.read_u32()?.unverified(/*bounded by the length of the bytes*/);
+        let mut children = BTreeMap::new();
+        for _ in 0..count {
+            let label = decoder
+                .read_character_data()?
+                .verify_unwrap(|label| !label.is_empty())
+                .map_err(|_| "empty label in the name index")?;
+            children.insert(Box::from(label), Node::read(decoder)?);
+        }
+
+        Ok(Node {
+            record_types,
+            children,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn name(name: &str) -> LowerName {
+        LowerName::from_str(name).unwrap()
+    }
+
+    fn index() -> NameIndex {
+        let mut index = NameIndex::new();
+        index.insert(&name("example.com."), RecordType::SOA);
+        index.insert(&name("example.com."), RecordType::NS);
+        index.insert(&name("www.example.com."), RecordType::A);
+        index.insert(&name("WWW.example.com."), RecordType::AAAA);
+        index.insert(&name("a.b.c.example.com."), RecordType::TXT);
+        index.insert(&name("*.wild.example.com."), RecordType::CNAME);
+        index
+    }
+
+    #[test]
+    fn test_emit_and_read() {
+        let index = index();
+        assert_eq!(index.len(), 6);
+
+        let bytes = index.to_bytes().unwrap();
+        let read = NameIndex::read(&mut BinDecoder::new(&bytes)).unwrap();
+        assert_eq!(read.len(), 6);
+        assert_eq!(read.to_bytes().unwrap(), bytes);
+        assert_eq!(
+            read.lookup(&name("www.wild.example.com.")),
+            index.lookup(&name("www.wild.example.com."))
+        );
+
+        assert!(NameIndex::read(&mut BinDecoder::new(&bytes[..bytes.len() - 1])).is_err());
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut index = index();
+
+        index.remove(&name("www.example.com."), RecordType::A);
+        assert_eq!(
+            index.lookup(&name("www.example.com.")),
+            NameMatch::Exact(&[RecordType::AAAA])
+        );
+
+        // the empty non-terminals of the name are removed with it
+        index.remove(&name("A.b.c.example.com."), RecordType::TXT);
+        assert!(!index.contains(&name("a.b.c.example.com.")));
+        assert!(!index.contains(&name("c.example.com.")));
+        assert!(index.contains(&name("example.com.")));
+
+        // removing what is not there does nothing
+        index.remove(&name("ftp.example.com."), RecordType::A);
+        index.remove(&name("example.com."), RecordType::MX);
+        assert_eq!(index.len(), 4);
+
+        index.remove(&name("example.com."), RecordType::SOA);
+        index.remove(&name("example.com."), RecordType::NS);
+        index.remove(&name("www.example.com."), RecordType::AAAA);
+        index.remove(&name("*.wild.example.com."), RecordType::CNAME);
+        assert!(index.is_empty());
+    }
+
+    #[test]
+    fn test_exact() {
+        let index = index();
+
+        assert_eq!(
+            index.lookup(&name("example.com.")),
+            NameMatch::Exact(&[RecordType::NS, RecordType::SOA])
+        );
+        assert_eq!(
+            index.lookup(&name("Www.Example.Com.")),
+            NameMatch::Exact(&[RecordType::A, RecordType::AAAA])
+        );
+        assert_eq!(
+            index.lookup(&name("*.wild.example.com.")),
+            NameMatch::Exact(&[RecordType::CNAME])
+        );
+    }
+
+    #[test]
+    fn test_empty_non_terminal() {
+        let index = index();
+
+        assert_eq!(
+            index.lookup(&name("b.c.example.com.")),
+            NameMatch::Exact(&[])
+        );
+        assert_eq!(
+            index.lookup(&name("wild.example.com.")),
+            NameMatch::Exact(&[])
+        );
+        assert!(index.contains(&name("c.example.com.")));
+        assert!(index.contains(&name("com.")));
+    }
+
+    #[test]
+    fn test_closest_encloser() {
+        let index = index();
+
+        assert_eq!(
+            index.lookup(&name("x.b.c.example.com.")),
+            NameMatch::Closest {
+                encloser: name("b.c.example.com."),
+                wildcard: None,
+            }
+        );
+        assert_eq!(
+            index.lookup(&name("ftp.example.com.")),
+            NameMatch::Closest {
+                encloser: name("example.com."),
+                wildcard: None,
+            }
+        );
+        assert!(!index.contains(&name("ftp.example.com.")));
+        assert!(!index.contains(&name("example.net.")));
+    }
+
+    #[test]
+    fn test_wildcard() {
+        let index = index();
+
+        assert_eq!(
+            index.lookup(&name("www.wild.example.com.")),
+            NameMatch::Closest {
+                encloser: name("wild.example.com."),
+                wildcard: Some((name("*.wild.example.com."), &[RecordType::CNAME][..])),
+            }
+        );
+        // the wildcard also matches the names more than one label below the closest encloser
+        assert_eq!(
+            index.lookup(&name("a.b.wild.example.com.")),
+            NameMatch::Closest {
+                encloser: name("wild.example.com."),
+                wildcard: Some((name("*.wild.example.com."), &[RecordType::CNAME][..])),
+            }
+        );
+        assert_eq!(
+            index.lookup(&name("x.www.example.com.")),
+            NameMatch::Closest {
+                encloser: name("www.example.com."),
+                wildcard: None,
+            }
+        );
+    }
+}
@@ -0,0 +1,361 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::future::{self, Future};
+
+use trust_dns::op::LowerQuery;
+use trust_dns::proto::rr::dnssec::rdata::key::KEY;
+use trust_dns::rr::dnssec::{DnsSecResult, Signer, SupportedAlgorithms};
+use trust_dns::rr::{LowerName, Name, RData, Record, RecordSet, RecordType};
+
+use authority::{
+    AuthLookup, Authority, LookupError, LookupRecords, MessageRequest, UpdateResult, ZoneType,
+};
+use store::file::{FileAuthority, FileConfig};
+use store::in_memory::InMemoryAuthority;
+use store::load_balancer::health_check::{self, TargetHealth};
+use store::load_balancer::{LoadBalancerConfig, PoolConfig};
+
+static DEFAULT_POOL_TTL: u32 = 30;
+
+struct Target {
+    address: IpAddr,
+    health: Arc<TargetHealth>,
+}
+
+struct Pool {
+    ttl: u32,
+    min_answers: usize,
+    targets: Vec<Target>,
+}
+
+impl Pool {
+    /// The addresses to answer with, all healthy targets topped up to `min_answers` with unhealthy
+    fn addresses(&self, record_type: RecordType) -> Vec<IpAddr> {
+        let family = self
+            .targets
+            .iter()
+            .filter(|t| match record_type {
+                RecordType::A => t.address.is_ipv4(),
+                RecordType::AAAA => t.address.is_ipv6(),
+                _ => false,
+            })
+            .collect::<Vec<_>>();
+
+        let mut addresses = family
+            .iter()
+            .filter(|t| t.health.is_healthy())
+            .map(|t| t.address)
+            .collect::<Vec<_>>();
+
+        if addresses.len() < self.min_answers {
+            let missing = self.min_answers - addresses.len();
+            if !family.is_empty() {
+                debug!("not enough healthy targets, adding {} unhealthy", missing);
+            }
+
+            addresses.extend(
+                family
+                    .iter()
+                    .filter(|t| !t.health.is_healthy())
+                    .take(missing)
+                    .map(|t| t.address),
+            );
+        }
+
+        addresses
+    }
+}
+
+/// An authority for a zone file, where pools of addresses are answered based on their health
+///
+/// Each pool is a name in the zone which is answered with the A and AAAA records of its healthy
+///  targets. Targets are periodically checked with a TCP connect or HTTP GET, and are withheld
+///  from answers after consecutive failures, until consecutive checks succeed again. To avoid
+///  withholding all answers, e.g. when the checks themselves are failing, a pool always returns
+///  at least its `min_answers` addresses per family.
+///
+/// The pool records are also inserted in the zone, so they are returned in AXFR and ANY queries
+///  regardless of their health. Answers from pools are not DNSSEC signed.
+pub struct LoadBalancerAuthority {
+    zone: InMemoryAuthority,
+    pools: HashMap<LowerName, Pool>,
+}
+
+impl LoadBalancerAuthority {
+    /// Creates a new authority for the zone, with no pools
+    pub fn new(zone: InMemoryAuthority) -> Self {
+        LoadBalancerAuthority {
+            zone,
+            pools: HashMap::new(),
+        }
+    }
+
+    /// Read the Authority for the origin from the specified configuration
+    ///
+    /// The returned future must be run in the background, it performs the health checks.
+    pub fn try_from_config(
+        origin: Name,
+        zone_type: ZoneType,
+        allow_axfr: bool,
+        root_dir: Option<&Path>,
+        config: &LoadBalancerConfig,
+    ) -> Result<(Self, impl Future<Item = (), Error = ()>), String> {
+        let zone = FileAuthority::try_from_config(
+            origin,
+            zone_type,
+            allow_axfr,
+            root_dir,
+            &FileConfig {
+                zone_file_path: config.zone_file_path.clone(),
+            },
+        )?;
+
+        let mut authority = LoadBalancerAuthority::new(zone.unwrap());
+        let mut checks = Vec::new();
+
+        for pool in &config.pools {
+            checks.extend(authority.insert_pool(pool)?);
+        }
+
+        info!(
+            "load balancer loaded: {} pools, {} health checks",
+            authority.pools.len(),
+            checks.len()
+        );
+        Ok((authority, future::join_all(checks).map(|_| ())))
+    }
+
+    /// Adds the pool to the zone, replacing any existing pool with the same name
+    ///
+    /// # Return
+    ///
+    /// The health checks of the targets in the pool, these must be run in the background
+    pub fn insert_pool(
+        &mut self,
+        config: &PoolConfig,
+    ) -> Result<Vec<Box<dyn Future<Item = (), Error = ()> + Send>>, String> {
+        let mut pool_name = config.name.clone();
+        pool_name.set_fqdn(true);
+
+        let name = LowerName::from(&pool_name);
+        if !self.origin().zone_of(&name) {
+            return Err(format!(
+                "pool {} is not in zone {}",
+                config.name,
+                self.origin()
+            ));
+        }
+
+        let ttl = config.ttl.unwrap_or(DEFAULT_POOL_TTL);
+        let serial = self.zone.serial();
+        let mut pool = Pool {
+            ttl,
+            min_answers: config.min_answers.unwrap_or(1),
+            targets: Vec::with_capacity(config.targets.len()),
+        };
+        let mut checks: Vec<Box<dyn Future<Item = (), Error = ()> + Send>> = Vec::new();
+
+        for address in &config.targets {
+            let rdata = match *address {
+                IpAddr::V4(ip) => RData::A(ip),
+                IpAddr::V6(ip) => RData::AAAA(ip),
+            };
+            self.zone
+                .upsert(Record::from_rdata(pool_name.clone(), ttl, rdata), serial);
+
+            let health = Arc::new(TargetHealth::new());
+            if let Some(ref health_check) = config.health_check {
+                checks.push(Box::new(health_check::check_target(
+                    *address,
+                    Arc::clone(&health),
+                    health_check,
+                )));
+            }
+
+            pool.targets.push(Target {
+                address: *address,
+                health,
+            });
+        }
+
+        self.pools.insert(name, pool);
+        Ok(checks)
+    }
+
+    /// Returns the health of the target in the pool, None if there is no such target
+    pub fn target_health(&self, name: &LowerName, address: IpAddr) -> Option<&TargetHealth> {
+        self.pools
+            .get(name)?
+            .targets
+            .iter()
+            .find(|t| t.address == address)
+            .map(|t| &*t.health)
+    }
+
+    /// Unwrap the InMemoryAuthority, all pools are removed
+    pub fn unwrap(self) -> InMemoryAuthority {
+        self.zone
+    }
+
+    fn lookup_pool(
+        &self,
+        name: &LowerName,
+        rtype: RecordType,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Option<AuthLookup> {
+        let pool = self.pools.get(name)?;
+        let addresses = pool.addresses(rtype);
+        if addresses.is_empty() {
+            return None;
+        }
+
+        let mut rrset = RecordSet::with_ttl(name.into(), rtype, pool.ttl);
+        for address in addresses {
+            let rdata = match address {
+                IpAddr::V4(ip) => RData::A(ip),
+                IpAddr::V6(ip) => RData::AAAA(ip),
+            };
+            rrset.add_rdata(rdata);
+        }
+
+        let answers = LookupRecords::new(is_secure, supported_algorithms, Arc::new(rrset))
+            .with_order(self.zone.record_order(rtype));
+        Some(AuthLookup::answers(answers, None))
+    }
+}
+
+impl Deref for LoadBalancerAuthority {
+    type Target = InMemoryAuthority;
+
+    fn deref(&self) -> &Self::Target {
+        &self.zone
+    }
+}
+
+impl DerefMut for LoadBalancerAuthority {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.zone
+    }
+}
+
+impl Authority for LoadBalancerAuthority {
+    type Lookup = <InMemoryAuthority as Authority>::Lookup;
+    type LookupFuture = <InMemoryAuthority as Authority>::LookupFuture;
+
+    /// What type is this zone
+    fn zone_type(&self) -> ZoneType {
+        self.zone.zone_type()
+    }
+
+    /// Return true if AXFR is allowed
+    fn is_axfr_allowed(&self) -> bool {
+        self.zone.is_axfr_allowed()
+    }
+
+    /// Dynamic updates are not supported, the pools are defined by configuration
+    fn update(&self, _update: &MessageRequest) -> UpdateResult<bool> {
+        use proto::op::ResponseCode;
+        Err(ResponseCode::NotImp)
+    }
+
+    /// Get the origin of this zone, i.e. example.com is the origin for www.example.com
+    fn origin(&self) -> &LowerName {
+        self.zone.origin()
+    }
+
+    /// A and AAAA lookups of pools are answered with the healthy targets, all others from the zone
+    fn lookup(
+        &self,
+        name: &LowerName,
+        rtype: RecordType,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Self::LookupFuture {
+        match self.lookup_pool(name, rtype, is_secure, supported_algorithms) {
+            Some(lookup) => future::ok(lookup),
+            None => self
+                .zone
+                .lookup(name, rtype, is_secure, supported_algorithms),
+        }
+    }
+
+    fn search(
+        &self,
+        query: &LowerQuery,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Box<dyn Future<Item = Self::Lookup, Error = LookupError> + Send> {
+        match self.lookup_pool(
+            query.name(),
+            query.query_type(),
+            is_secure,
+            supported_algorithms,
+        ) {
+            Some(lookup) => Box::new(future::ok(lookup)),
+            None => self.zone.search(query, is_secure, supported_algorithms),
+        }
+    }
+
+    /// Get the NS, NameServer, record for the zone
+    fn ns(&self, is_secure: bool, supported_algorithms: SupportedAlgorithms) -> Self::LookupFuture {
+        self.zone.ns(is_secure, supported_algorithms)
+    }
+
+    /// Return the NSEC records based on the given name
+    fn get_nsec_records(
+        &self,
+        name: &LowerName,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Self::LookupFuture {
+        self.zone
+            .get_nsec_records(name, is_secure, supported_algorithms)
+    }
+
+    /// Returns the SOA of the authority.
+    fn soa(&self) -> Self::LookupFuture {
+        self.zone.soa()
+    }
+
+    /// Returns the SOA record for the zone
+    fn soa_secure(
+        &self,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Self::LookupFuture {
+        self.zone.soa_secure(is_secure, supported_algorithms)
+    }
+
+    /// Add a (Sig0) key that is authorized to perform updates against this authority
+    fn add_update_auth_key(&mut self, name: Name, key: KEY) -> DnsSecResult<()> {
+        self.zone.add_update_auth_key(name, key)
+    }
+
+    /// Add Signer
+    fn add_zone_signing_key(&mut self, signer: Signer) -> DnsSecResult<()> {
+        self.zone.add_zone_signing_key(signer)
+    }
+
+    /// Spreads the expirations of the signatures over the jitter
+    fn set_signature_jitter(&mut self, jitter: Duration) -> DnsSecResult<()> {
+        self.zone.set_signature_jitter(jitter)
+    }
+
+    /// Sign the zone for DNSSEC
+    fn secure_zone(&mut self) -> DnsSecResult<()> {
+        Authority::secure_zone(&mut self.zone)
+    }
+}
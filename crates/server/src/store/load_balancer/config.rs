@@ -0,0 +1,97 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::net::IpAddr;
+use std::time::Duration;
+
+use trust_dns::rr::Name;
+
+/// Configuration for load balanced zones
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct LoadBalancerConfig {
+    /// path to the master file, all names other than the pools are answered from the zone
+    pub zone_file_path: String,
+    /// the names which are answered with the healthy addresses of the pool
+    pub pools: Vec<PoolConfig>,
+}
+
+/// A name which is answered with the healthy addresses of its targets
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct PoolConfig {
+    /// the name of the pool, must be in the zone
+    pub name: Name,
+    /// TTL of the A and AAAA records, low values allow clients to pick up changes quickly
+    pub ttl: Option<u32>,
+    /// the addresses of the pool
+    pub targets: Vec<IpAddr>,
+    /// the minimum number of addresses per family returned, if there are not enough healthy
+    ///  targets unhealthy targets are returned as well, defaults to 1
+    pub min_answers: Option<usize>,
+    /// the health check of the targets, without a health check all targets are always returned
+    pub health_check: Option<HealthCheckConfig>,
+}
+
+/// Configuration of the periodic health check of each target in a pool
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct HealthCheckConfig {
+    /// the check performed against each target
+    #[serde(flatten)]
+    pub probe: Probe,
+    /// seconds between each check, defaults to 10
+    pub interval: Option<u64>,
+    /// seconds before a check is considered failed, defaults to 2
+    pub timeout: Option<u64>,
+    /// number of consecutive successful checks before an unhealthy target is restored, defaults to 2
+    pub rise: Option<usize>,
+    /// number of consecutive failed checks before a healthy target is withheld, defaults to 3
+    pub fall: Option<usize>,
+}
+
+impl HealthCheckConfig {
+    /// Time between each check of a target
+    pub fn get_interval(&self) -> Duration {
+        Duration::from_secs(self.interval.unwrap_or(10))
+    }
+
+    /// Time before a check is considered failed
+    pub fn get_timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout.unwrap_or(2))
+    }
+
+    /// Consecutive successful checks required to mark a target healthy
+    pub fn get_rise(&self) -> usize {
+        self.rise.unwrap_or(2).max(1)
+    }
+
+    /// Consecutive failed checks required to mark a target unhealthy
+    pub fn get_fall(&self) -> usize {
+        self.fall.unwrap_or(3).max(1)
+    }
+}
+
+/// The check performed against a target
+#[derive(Deserialize, PartialEq, Eq, Debug, Clone)]
+#[serde(tag = "type")]
+#[serde(rename_all = "lowercase")]
+pub enum Probe {
+    /// The target is healthy if a TCP connection can be established
+    Tcp {
+        /// port on the target to connect to
+        port: u16,
+    },
+    /// The target is healthy if an HTTP GET returns the expected status
+    Http {
+        /// port on the target to connect to
+        port: u16,
+        /// path requested, defaults to `/`
+        path: Option<String>,
+        /// value of the Host header, defaults to the address of the target
+        host: Option<String>,
+        /// the status expected in the response, by default any 2xx or 3xx status is healthy
+        expected_status: Option<u16>,
+    },
+}
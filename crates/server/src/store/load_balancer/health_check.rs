@@ -0,0 +1,219 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::{Future, Stream};
+use tokio_io::io::{read, write_all};
+use tokio_tcp::TcpStream;
+use tokio_timer::{Interval, Timeout};
+
+use store::load_balancer::{HealthCheckConfig, Probe};
+
+/// The health of a single target, with hysteresis between the states
+///
+/// Targets start healthy, so that answers are not withheld before the first checks complete.
+#[derive(Debug)]
+pub struct TargetHealth {
+    healthy: AtomicBool,
+    successes: AtomicUsize,
+    failures: AtomicUsize,
+}
+
+impl TargetHealth {
+    /// Creates a new healthy target
+    pub fn new() -> Self {
+        TargetHealth {
+            healthy: AtomicBool::new(true),
+            successes: AtomicUsize::new(0),
+            failures: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns true if the target is considered healthy
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Acquire)
+    }
+
+    /// Overrides the state of the target, the counts of consecutive checks are reset
+    pub fn set_healthy(&self, healthy: bool) {
+        self.successes.store(0, Ordering::Relaxed);
+        self.failures.store(0, Ordering::Relaxed);
+        self.healthy.store(healthy, Ordering::Release);
+    }
+
+    /// Records the result of a check, returns true if this changed the state of the target
+    ///
+    /// # Arguments
+    ///
+    /// * `success` - the result of the check
+    /// * `rise` - consecutive successes required for an unhealthy target to become healthy
+    /// * `fall` - consecutive failures required for a healthy target to become unhealthy
+    pub fn record(&self, success: bool, rise: usize, fall: usize) -> bool {
+        let (count, other, threshold) = if success {
+            (&self.successes, &self.failures, rise)
+        } else {
+            (&self.failures, &self.successes, fall)
+        };
+
+        other.store(0, Ordering::Relaxed);
+        let count = count.fetch_add(1, Ordering::Relaxed) + 1;
+
+        if count >= threshold && self.is_healthy() != success {
+            self.healthy.store(success, Ordering::Release);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl Default for TargetHealth {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns a future which checks the target on every interval, it never completes
+pub(crate) fn check_target(
+    address: IpAddr,
+    health: Arc<TargetHealth>,
+    config: &HealthCheckConfig,
+) -> impl Future<Item = (), Error = ()> {
+    let probe = config.probe.clone();
+    let timeout = config.get_timeout();
+    let rise = config.get_rise();
+    let fall = config.get_fall();
+
+    Interval::new(Instant::now(), config.get_interval())
+        .map_err(move |e| error!("health check timer failed for {}: {}", address, e))
+        .for_each(move |_| {
+            let health = Arc::clone(&health);
+
+            run_probe(address, &probe, timeout).then(move |result| {
+                if let Err(ref e) = result {
+                    debug!("health check failed for {}: {}", address, e);
+                }
+
+                if health.record(result.is_ok(), rise, fall) {
+                    if health.is_healthy() {
+                        info!("target restored: {}", address);
+                    } else {
+                        warn!("target unhealthy: {}", address);
+                    }
+                }
+
+                Ok(())
+            })
+        })
+}
+
+fn run_probe(
+    address: IpAddr,
+    probe: &Probe,
+    timeout: Duration,
+) -> Box<dyn Future<Item = (), Error = io::Error> + Send> {
+    let probe: Box<dyn Future<Item = (), Error = io::Error> + Send> = match *probe {
+        Probe::Tcp { port } => {
+            Box::new(TcpStream::connect(&SocketAddr::new(address, port)).map(|_| ()))
+        }
+        Probe::Http {
+            port,
+            ref path,
+            ref host,
+            expected_status,
+        } => {
+            let request = format!(
+                "GET {} HTTP/1.0\r\nHost: {}\r\nUser-Agent: trust-dns\r\nConnection: close\r\n\r\n",
+                path.as_ref().map_or("/", String::as_str),
+                host.clone().unwrap_or_else(|| address.to_string()),
+            );
+
+            Box::new(
+                TcpStream::connect(&SocketAddr::new(address, port))
+                    .and_then(move |stream| write_all(stream, request))
+                    .and_then(|(stream, _)| read(stream, vec![0; 512]))
+                    .and_then(move |(_, buf, len)| check_http_status(&buf[..len], expected_status)),
+            )
+        }
+    };
+
+    Box::new(Timeout::new(probe, timeout).map_err(|e| {
+        if e.is_elapsed() {
+            io::Error::new(io::ErrorKind::TimedOut, "health check timed out")
+        } else if let Some(e) = e.into_inner() {
+            e
+        } else {
+            io::Error::new(io::ErrorKind::Other, "health check timer failed")
+        }
+    }))
+}
+
+/// Parses the status line, e.g. `HTTP/1.1 200 OK`, of the response
+fn check_http_status(response: &[u8], expected_status: Option<u16>) -> io::Result<()> {
+    let status = response
+        .split(|b| *b == b'\n')
+        .next()
+        .and_then(|line| ::std::str::from_utf8(line).ok())
+        .filter(|line| line.starts_with("HTTP/"))
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|status| status.parse::<u16>().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid HTTP response"))?;
+
+    let healthy = match expected_status {
+        Some(expected) => status == expected,
+        None => (200..400).contains(&status),
+    };
+
+    if healthy {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("unexpected HTTP status: {}", status),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hysteresis() {
+        let health = TargetHealth::new();
+        assert!(health.is_healthy());
+
+        assert!(!health.record(false, 2, 3));
+        assert!(!health.record(false, 2, 3));
+        // a success resets the count of failures
+        assert!(!health.record(true, 2, 3));
+        assert!(!health.record(false, 2, 3));
+        assert!(!health.record(false, 2, 3));
+        assert!(health.is_healthy());
+        assert!(health.record(false, 2, 3));
+        assert!(!health.is_healthy());
+
+        assert!(!health.record(true, 2, 3));
+        assert!(!health.is_healthy());
+        assert!(health.record(true, 2, 3));
+        assert!(health.is_healthy());
+    }
+
+    #[test]
+    fn test_http_status() {
+        assert!(check_http_status(b"HTTP/1.1 200 OK\r\n", None).is_ok());
+        assert!(check_http_status(b"HTTP/1.0 301 Moved\r\n", None).is_ok());
+        assert!(check_http_status(b"HTTP/1.1 503 Unavailable\r\n", None).is_err());
+        assert!(check_http_status(b"HTTP/1.1 204 No Content\r\n", Some(200)).is_err());
+        assert!(check_http_status(b"SSH-2.0-OpenSSH\r\n", None).is_err());
+    }
+}
@@ -0,0 +1,16 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Master file based zones with health checked pools of addresses, for DNS load balancing
+
+mod authority;
+mod config;
+mod health_check;
+
+pub use self::authority::LoadBalancerAuthority;
+pub use self::config::{HealthCheckConfig, LoadBalancerConfig, PoolConfig, Probe};
+pub use self::health_check::TargetHealth;
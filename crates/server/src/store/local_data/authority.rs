@@ -0,0 +1,272 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::HashMap;
+
+use futures::{Async, Future, Poll};
+
+use trust_dns::op::LowerQuery;
+use trust_dns::op::ResponseCode;
+use trust_dns::rr::dnssec::SupportedAlgorithms;
+use trust_dns::rr::{LowerName, Name, Record, RecordType};
+
+use authority::{Authority, LookupError, LookupObject, MessageRequest, UpdateResult, ZoneType};
+use store::forwarder::{ForwardAuthority, ForwardLookup, ForwardLookupFuture};
+use store::local_data::LocalDataConfig;
+
+static DEFAULT_LOCAL_TTL: u32 = 300;
+
+/// The maximum number of local CNAMEs which will be followed for a single lookup
+const MAX_CNAME_CHAIN: usize = 8;
+
+/// An authority which answers from locally defined records, and forwards all other queries
+///
+/// Any name with local records is answered entirely from those records, i.e. a query for a type
+///  which is not defined locally results in an empty answer, rather than being forwarded. This
+///  is the equivalent of the dnsmasq `address=/host/ip` configuration.
+pub struct LocalDataAuthority {
+    records: HashMap<LowerName, Vec<Record>>,
+    forwarder: ForwardAuthority,
+}
+
+impl LocalDataAuthority {
+    /// Creates a new authority with no local records, forwarding all queries to `forwarder`
+    pub fn new(forwarder: ForwardAuthority) -> Self {
+        LocalDataAuthority {
+            records: HashMap::new(),
+            forwarder,
+        }
+    }
+
+    /// Read the Authority for the origin from the specified configuration
+    ///
+    /// The returned future must be run in the background, it drives the forwarder.
+    pub fn try_from_config(
+        origin: Name,
+        zone_type: ZoneType,
+        config: &LocalDataConfig,
+    ) -> Result<(Self, impl Future<Item = (), Error = ()>), String> {
+        info!("loading local data config: {}", origin);
+
+        let (forwarder, bg) =
+            ForwardAuthority::try_from_config(origin, zone_type, &config.forward)?;
+        let mut authority = LocalDataAuthority::new(forwarder);
+
+        let ttl = config.ttl.unwrap_or(DEFAULT_LOCAL_TTL);
+        for record in &config.records {
+            let record = record.to_record(ttl);
+            if !authority.origin().zone_of(&record.name().into()) {
+                return Err(format!(
+                    "local record {} is not in zone {}",
+                    record.name(),
+                    authority.origin()
+                ));
+            }
+
+            authority.insert(record);
+        }
+
+        info!("local data loaded: {} names", authority.records.len());
+        Ok((authority, bg))
+    }
+
+    /// Adds a local record, all queries for the name of the record will no longer be forwarded
+    pub fn insert(&mut self, record: Record) {
+        self.records
+            .entry(LowerName::new(record.name()))
+            .or_default()
+            .push(record);
+    }
+
+    /// Removes all local records for the name, queries for it will be forwarded again
+    pub fn remove(&mut self, name: &LowerName) -> Option<Vec<Record>> {
+        self.records.remove(name)
+    }
+
+    /// Returns the local records for the name
+    pub fn get(&self, name: &LowerName) -> Option<&[Record]> {
+        self.records.get(name).map(Vec::as_slice)
+    }
+
+    /// The forwarder of the names without local records
+    pub fn forwarder(&self) -> &ForwardAuthority {
+        &self.forwarder
+    }
+}
+
+impl Authority for LocalDataAuthority {
+    type Lookup = LocalDataLookup;
+    type LookupFuture = LocalDataLookupFuture;
+
+    /// Always Forward, only names with local records are answered locally
+    fn zone_type(&self) -> ZoneType {
+        ZoneType::Forward
+    }
+
+    /// Always false for LocalData zones
+    fn is_axfr_allowed(&self) -> bool {
+        false
+    }
+
+    fn update(&self, _update: &MessageRequest) -> UpdateResult<bool> {
+        Err(ResponseCode::NotImp)
+    }
+
+    /// Get the origin of this zone, this is the origin of the forwarder
+    fn origin(&self) -> &LowerName {
+        self.forwarder.origin()
+    }
+
+    /// Answers from the local records for the name, following local CNAMEs, otherwise forwards
+    fn lookup(
+        &self,
+        name: &LowerName,
+        rtype: RecordType,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Self::LookupFuture {
+        let mut answers = Vec::new();
+        let mut name = name.clone();
+
+        for _ in 0..MAX_CNAME_CHAIN {
+            let local = match self.records.get(&name) {
+                Some(local) => local,
+                // the name is not local, if it's in this zone the upstream answers the rest
+                None if self.origin().zone_of(&name) => {
+                    debug!("forwarding lookup: {} {}", name, rtype);
+                    return LocalDataLookupFuture::Forward {
+                        local: answers,
+                        lookup: self.forwarder.lookup(
+                            &name,
+                            rtype,
+                            is_secure,
+                            supported_algorithms,
+                        ),
+                    };
+                }
+                None => return LocalDataLookupFuture::Local(Some(answers)),
+            };
+
+            debug!("local lookup: {} {}", name, rtype);
+            let matched = local
+                .iter()
+                .filter(|r| rtype == RecordType::ANY || r.rr_type() == rtype)
+                .cloned()
+                .collect::<Vec<_>>();
+
+            if !matched.is_empty() {
+                answers.extend(matched);
+                return LocalDataLookupFuture::Local(Some(answers));
+            }
+
+            // no records of the type, an alias for the name is followed, otherwise it's NODATA
+            let target = match local
+                .iter()
+                .find(|r| r.rr_type() == RecordType::CNAME)
+                .and_then(|cname| cname.rdata().as_cname().map(|t| (cname, t)))
+            {
+                Some((cname, target)) => {
+                    answers.push(cname.clone());
+                    LowerName::new(target)
+                }
+                None => return LocalDataLookupFuture::Local(Some(answers)),
+            };
+
+            name = target;
+        }
+
+        warn!("local CNAME chain too long at: {}", name);
+        LocalDataLookupFuture::Local(Some(answers))
+    }
+
+    fn search(
+        &self,
+        query: &LowerQuery,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Box<dyn Future<Item = Self::Lookup, Error = LookupError> + Send> {
+        Box::new(self.lookup(
+            query.name(),
+            query.query_type(),
+            is_secure,
+            supported_algorithms,
+        ))
+    }
+
+    fn get_nsec_records(
+        &self,
+        _name: &LowerName,
+        _is_secure: bool,
+        _supported_algorithms: SupportedAlgorithms,
+    ) -> Self::LookupFuture {
+        LocalDataLookupFuture::Local(Some(vec![]))
+    }
+}
+
+/// The result of a lookup against a `LocalDataAuthority`
+pub struct LocalDataLookup {
+    local: Vec<Record>,
+    forwarded: Option<ForwardLookup>,
+}
+
+impl LookupObject for LocalDataLookup {
+    fn is_empty(&self) -> bool {
+        let forwarded_is_empty = match self.forwarded {
+            Some(ref forwarded) => forwarded.is_empty(),
+            None => true,
+        };
+
+        self.local.is_empty() && forwarded_is_empty
+    }
+
+    fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a Record> + Send + 'a> {
+        match self.forwarded {
+            Some(ref forwarded) => Box::new(self.local.iter().chain(forwarded.iter())),
+            None => Box::new(self.local.iter()),
+        }
+    }
+
+    fn take_additionals(&mut self) -> Option<Box<dyn LookupObject>> {
+        None
+    }
+}
+
+/// A future which resolves to the `LocalDataLookup`
+pub enum LocalDataLookupFuture {
+    /// Answered entirely from local records, ready immediately
+    Local(Option<Vec<Record>>),
+    /// The local records, i.e. CNAMEs, followed by the pending upstream lookup
+    Forward {
+        /// the local records which precede the forwarded answer
+        local: Vec<Record>,
+        /// the upstream lookup
+        lookup: ForwardLookupFuture,
+    },
+}
+
+impl Future for LocalDataLookupFuture {
+    type Item = LocalDataLookup;
+    type Error = LookupError;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        match self {
+            LocalDataLookupFuture::Local(local) => Ok(Async::Ready(LocalDataLookup {
+                local: local
+                    .take()
+                    .expect("LocalDataLookupFuture already complete"),
+                forwarded: None,
+            })),
+            LocalDataLookupFuture::Forward { local, lookup } => {
+                let forwarded = try_ready!(lookup.poll());
+                Ok(Async::Ready(LocalDataLookup {
+                    local: local.split_off(0),
+                    forwarded: Some(forwarded),
+                }))
+            }
+        }
+    }
+}
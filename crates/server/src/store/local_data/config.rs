@@ -0,0 +1,89 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use trust_dns::rr::rdata::TXT;
+use trust_dns::rr::{Name, RData, Record};
+
+use store::forwarder::ForwardConfig;
+
+/// Configuration for local data zones, names without local records are forwarded upstream
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct LocalDataConfig {
+    /// records which are answered locally, instead of being forwarded
+    #[serde(default)]
+    pub records: Vec<LocalRecordConfig>,
+    /// default TTL for the local records
+    pub ttl: Option<u32>,
+    /// upstream configuration for all names without local records
+    pub forward: ForwardConfig,
+}
+
+/// A single locally defined record
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct LocalRecordConfig {
+    /// name of the record, e.g. `host.example.com`
+    pub name: Name,
+    /// TTL of the record, defaults to the TTL of the `LocalDataConfig`
+    pub ttl: Option<u32>,
+    /// the data of the record
+    #[serde(flatten)]
+    pub rdata: LocalRData,
+}
+
+impl LocalRecordConfig {
+    /// Converts this into a `Record`, using `default_ttl` if no TTL was specified
+    pub fn to_record(&self, default_ttl: u32) -> Record {
+        let mut name = self.name.clone();
+        name.set_fqdn(true);
+
+        Record::from_rdata(name, self.ttl.unwrap_or(default_ttl), self.rdata.to_rdata())
+    }
+}
+
+/// The supported types of locally defined records
+#[derive(Deserialize, PartialEq, Eq, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum LocalRData {
+    /// An IPv4 address
+    A {
+        /// the address
+        address: Ipv4Addr,
+    },
+    /// An IPv6 address
+    AAAA {
+        /// the address
+        address: Ipv6Addr,
+    },
+    /// An alias to another name, which is resolved locally if possible, otherwise forwarded
+    CNAME {
+        /// the canonical name
+        target: Name,
+    },
+    /// Text data
+    TXT {
+        /// each string is stored as a separate character-string
+        text: Vec<String>,
+    },
+}
+
+impl LocalRData {
+    /// Converts into the `RData` to serve
+    pub fn to_rdata(&self) -> RData {
+        match *self {
+            LocalRData::A { address } => RData::A(address),
+            LocalRData::AAAA { address } => RData::AAAA(address),
+            LocalRData::CNAME { ref target } => {
+                let mut target = target.clone();
+                target.set_fqdn(true);
+                RData::CNAME(target)
+            }
+            LocalRData::TXT { ref text } => RData::TXT(TXT::new(text.clone())),
+        }
+    }
+}
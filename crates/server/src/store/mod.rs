@@ -7,11 +7,19 @@
 
 //! All persistent store implementations
 
+pub mod aname;
+pub mod auto_ptr;
+pub mod blocklist;
 mod config;
+pub mod dhcp;
 pub mod file;
 pub mod forwarder;
 pub mod in_memory;
+pub mod load_balancer;
+pub mod local_data;
 pub mod sqlite;
+pub mod stub;
+pub mod synth_ptr;
 
 // TODO: add a dynamic library option?
 
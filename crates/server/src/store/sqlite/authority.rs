@@ -10,11 +10,13 @@
 use std::ops::{Deref, DerefMut};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
 use futures::future::Future;
 
 use trust_dns::op::LowerQuery;
 use trust_dns::op::ResponseCode;
+use trust_dns::op::UpdateBuilder;
 use trust_dns::proto::rr::dnssec::rdata::key::KEY;
 use trust_dns::rr::dnssec::{DnsSecResult, Signer, SupportedAlgorithms};
 use trust_dns::rr::{DNSClass, LowerName, Name, RData, Record, RecordSet, RecordType, RrKey};
@@ -22,7 +24,7 @@ use trust_dns::rr::{DNSClass, LowerName, Name, RData, Record, RecordSet, RecordT
 #[cfg(feature = "dnssec")]
 use authority::UpdateRequest;
 use authority::{Authority, LookupError, MessageRequest, UpdateResult, ZoneType};
-use store::in_memory::InMemoryAuthority;
+use store::in_memory::{InMemoryAuthority, InMemoryUpdate, ZoneDiff};
 use store::sqlite::{Journal, SqliteConfig};
 
 use error::{PersistenceErrorKind, PersistenceResult};
@@ -148,17 +150,19 @@ impl SqliteAuthority {
         );
 
         info!("recovering from journal");
+        let mut zone = self.in_memory.begin_update();
         for record in journal.iter() {
             // AXFR is special, it is used to mark the dump of a full zone.
             //  when recovering, if an AXFR is encountered, we should remove all the records in the
             //  authority.
             if record.rr_type() == RecordType::AXFR {
-                self.in_memory.clear();
-            } else if let Err(error) = self.update_records(&[record], false) {
+                zone.clear();
+            } else if let Err(error) = self.apply_records(&mut zone, &[record], false) {
                 return Err(PersistenceErrorKind::Recovery(error.to_str()).into());
             }
         }
 
+        zone.commit();
         Ok(())
     }
 
@@ -647,12 +651,52 @@ impl SqliteAuthority {
     /// * `auto_signing_and_increment` - if true, the zone will sign and increment the SOA, this
     ///                                  should be disabled during recovery.
     pub fn update_records(
-        &mut self,
+        &self,
+        records: &[Record],
+        auto_signing_and_increment: bool,
+    ) -> UpdateResult<bool> {
+        let mut zone = self.in_memory.begin_update();
+        let updated = self.apply_records(&mut zone, records, auto_signing_and_increment)?;
+
+        zone.commit();
+        Ok(updated)
+    }
+
+    /// Verifies the prerequisites and applies the updates, either all of them or none
+    ///
+    /// This is shared by the UPDATE messages and `apply_update`, once the requestor is authorized.
+    ///  The diff has all the changes of the update, including the new SOA serial and signatures.
+    fn apply_transaction(
+        &self,
+        prerequisites: &[Record],
+        updates: &[Record],
+    ) -> UpdateResult<ZoneDiff> {
+        // updates are applied one at a time, the prerequisites are verified against the zone as it
+        //  is before this update, and lookups are answered from it until the update is committed
+        let mut zone = self.in_memory.begin_update();
+        let records = self.in_memory.records();
+
+        self.verify_prerequisites(prerequisites)?;
+        self.pre_scan(updates)?;
+
+        if !self.apply_records(&mut zone, updates, true)? {
+            return Ok(ZoneDiff::default());
+        }
+
+        let diff = ZoneDiff::new(&records, zone.records());
+        zone.commit();
+        Ok(diff)
+    }
+
+    /// Applies the records to the zone being updated, see `update_records`
+    fn apply_records(
+        &self,
+        zone: &mut InMemoryUpdate,
         records: &[Record],
         auto_signing_and_increment: bool,
     ) -> UpdateResult<bool> {
         let mut updated = false;
-        let serial: u32 = self.serial();
+        let serial: u32 = zone.serial();
 
         // the persistence act as a write-ahead log. The WAL will also be used for recovery of a zone
         //  subsequent to a failure of the server.
@@ -721,7 +765,7 @@ impl SqliteAuthority {
 
                     // zone     rrset    rr       Add to an RRset
                     info!("upserting record: {:?}", rr);
-                    updated = self.upsert(rr.clone(), serial) || updated;
+                    updated = zone.upsert(rr.clone(), serial) || updated;
                 }
                 DNSClass::ANY => {
                     // This is a delete of entire RRSETs, either many or one. In either case, the spec is clear:
@@ -742,7 +786,7 @@ impl SqliteAuthority {
                                 "deleting all records at name (not SOA or NS at origin): {:?}",
                                 rr_name
                             );
-                            let to_delete = self
+                            let to_delete = zone
                                 .records()
                                 .keys()
                                 .filter(|k| {
@@ -754,7 +798,7 @@ impl SqliteAuthority {
                                 .cloned()
                                 .collect::<Vec<RrKey>>();
                             for delete in to_delete {
-                                self.records_mut().remove(&delete);
+                                zone.remove(&delete);
                                 updated = true;
                             }
                         }
@@ -766,7 +810,7 @@ impl SqliteAuthority {
 
                             // ANY      rrset    empty    Delete an RRset
                             if let RData::NULL(..) = *rr.rdata() {
-                                let deleted = self.records_mut().remove(&rr_key);
+                                let deleted = zone.remove(&rr_key);
                                 info!("deleted rrset: {:?}", deleted);
                                 updated = updated || deleted.is_some();
                             } else {
@@ -779,7 +823,7 @@ impl SqliteAuthority {
                 DNSClass::NONE => {
                     info!("deleting specific record: {:?}", rr);
                     // NONE     rrset    rr       Delete an RR from an RRset
-                    if let Some(mut rrset) = self.records_mut().get_mut(&rr_key) {
+                    if let Some(mut rrset) = zone.get_mut(&rr_key) {
                         // b/c this is an Arc, we need to clone, then remove, and replace the node.
                         let mut rrset_clone: RecordSet = RecordSet::clone(&*rrset);
                         let deleted = rrset_clone.remove(rr, serial);
@@ -801,14 +845,15 @@ impl SqliteAuthority {
         // update the serial...
         if updated && auto_signing_and_increment {
             if self.is_dnssec_enabled {
-                self.secure_zone().map_err(|e| {
+                // only the changes of the update are signed
+                zone.secure_changes().map_err(|e| {
                     error!("failure securing zone: {}", e);
                     ResponseCode::ServFail
                 })?
             } else {
-                // the secure_zone() function increments the SOA during it's operation, if we're not
-                //  dnssec, then we need to do it here...
-                self.increment_soa_serial();
+                // the secure_changes() function increments the SOA during it's operation, if
+                //  we're not dnssec, then we need to do it here...
+                zone.increment_soa_serial();
             }
         }
 
@@ -901,22 +946,42 @@ impl Authority for SqliteAuthority {
     ///
     /// true if any of additions, updates or deletes were made to the zone, false otherwise. Err is
     ///  returned in the case of bad data, etc.
+    fn update(&self, update: &MessageRequest) -> UpdateResult<bool> {
+        self.update_with_diff(update).map(|diff| !diff.is_empty())
+    }
+
+    /// Performs the dynamic update, see `update`, returning the records it changed
     #[cfg(feature = "dnssec")]
-    fn update(&mut self, update: &MessageRequest) -> UpdateResult<bool> {
+    fn update_with_diff(&self, update: &MessageRequest) -> UpdateResult<ZoneDiff> {
         // the spec says to authorize after prereqs, seems better to auth first.
         self.authorize(update)?;
-        self.verify_prerequisites(update.prerequisites())?;
-        self.pre_scan(update.updates())?;
-
-        self.update_records(update.updates(), true)
+        self.apply_transaction(update.prerequisites(), update.updates())
     }
 
     /// Always fail when DNSSEC is disabled.
     #[cfg(not(feature = "dnssec"))]
-    fn update(&mut self, _update: &MessageRequest) -> UpdateResult<bool> {
+    fn update_with_diff(&self, _update: &MessageRequest) -> UpdateResult<ZoneDiff> {
         Err(ResponseCode::NotImp)
     }
 
+    /// Applies the prerequisites and updates of the builder atomically, as for an UPDATE message
+    ///
+    /// The updates are applied even if the zone does not allow dynamic updates, that only
+    ///  concerns the UPDATE messages of the requestors.
+    fn apply_update(&self, update: &UpdateBuilder) -> UpdateResult<bool> {
+        if LowerName::from(update.zone_origin()) != *self.origin() {
+            warn!(
+                "update of {} attempted on Authority: {}",
+                update.zone_origin(),
+                self.origin()
+            );
+            return Err(ResponseCode::NotAuth);
+        }
+
+        self.apply_transaction(update.prerequisites(), update.updates())
+            .map(|diff| !diff.is_empty())
+    }
+
     /// Get the origin of this zone, i.e. example.com is the origin for www.example.com
     fn origin(&self) -> &LowerName {
         self.in_memory.origin()
@@ -987,6 +1052,11 @@ impl Authority for SqliteAuthority {
         self.in_memory.add_zone_signing_key(signer)
     }
 
+    /// Spreads the expirations of the signatures over the jitter
+    fn set_signature_jitter(&mut self, jitter: Duration) -> DnsSecResult<()> {
+        self.in_memory.set_signature_jitter(jitter)
+    }
+
     /// (Re)generates the nsec records, increments the serial number nad signs the zone
     fn secure_zone(&mut self) -> DnsSecResult<()> {
         Authority::secure_zone(&mut self.in_memory)
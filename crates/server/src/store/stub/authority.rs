@@ -0,0 +1,221 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::net::IpAddr;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use futures::future::{self, Either, FutureResult};
+use futures::{Future, Stream};
+use tokio_timer::Interval;
+
+use trust_dns::op::LowerQuery;
+use trust_dns::op::ResponseCode;
+use trust_dns::rr::dnssec::SupportedAlgorithms;
+use trust_dns::rr::{LowerName, Name, RecordType};
+use trust_dns_resolver::config::{NameServerConfigGroup, ResolverConfig, ResolverOpts};
+use trust_dns_resolver::error::ResolveError;
+use trust_dns_resolver::AsyncResolver;
+
+use authority::{Authority, LookupError, MessageRequest, UpdateResult, ZoneType};
+use store::forwarder::{ForwardLookup, ForwardLookupFuture};
+use store::stub::StubConfig;
+
+/// An authority for a stub zone, the names of the zone are resolved by querying its
+///  authoritative name servers directly.
+///
+/// The authoritative name servers are learned from the NS records of the zone, looked up through
+///  the configured masters on every refresh. Until they are learned, or if they can't be, the
+///  masters answer the lookups.
+pub struct StubAuthority {
+    origin: LowerName,
+    resolver: Arc<RwLock<AsyncResolver>>,
+}
+
+impl StubAuthority {
+    /// Read the Authority for the origin from the specified configuration
+    ///
+    /// The returned future must be run in the background, it drives the resolver and the refreshes
+    ///  of the authoritative name servers, it never completes.
+    pub fn try_from_config(
+        origin: Name,
+        _zone_type: ZoneType,
+        config: &StubConfig,
+    ) -> Result<(Self, impl Future<Item = (), Error = ()>), String> {
+        info!("loading stub config: {}", origin);
+
+        let options = config.options.unwrap_or_default();
+        let masters = ResolverConfig::from_parts(None, vec![], config.masters.clone());
+        let (resolver, bg) = AsyncResolver::new(masters.clone(), options);
+        let resolver = Arc::new(RwLock::new(resolver));
+
+        let refresh = refresh_name_servers(
+            origin.clone(),
+            masters,
+            options,
+            config.get_port(),
+            config.get_refresh(),
+            Arc::clone(&resolver),
+        );
+
+        Ok((
+            StubAuthority {
+                origin: origin.into(),
+                resolver,
+            },
+            bg.join(refresh).map(|_| ()),
+        ))
+    }
+}
+
+/// Returns a future which looks up the authoritative name servers of the zone through the masters
+///  on every refresh, and replaces the resolver with one querying them, it never completes
+fn refresh_name_servers(
+    origin: Name,
+    masters: ResolverConfig,
+    options: ResolverOpts,
+    port: u16,
+    refresh: Duration,
+    resolver: Arc<RwLock<AsyncResolver>>,
+) -> impl Future<Item = (), Error = ()> {
+    // the name servers must be learned again on every refresh, not from the cache
+    let mut masters_options = options;
+    masters_options.cache_size = 0;
+    let (masters, bg) = AsyncResolver::new(masters, masters_options);
+
+    let refreshes = Interval::new(Instant::now(), refresh)
+        .map_err(|e| error!("stub refresh timer failed: {}", e))
+        .for_each(move |_| {
+            let origin = origin.clone();
+            let resolver = Arc::clone(&resolver);
+
+            lookup_name_servers(origin.clone(), masters.clone()).then(move |result| {
+                let addresses = match result {
+                    Ok(ref addresses) if addresses.is_empty() => {
+                        warn!("no addresses of the name servers of stub: {}", origin);
+                        return Ok(());
+                    }
+                    Ok(addresses) => addresses,
+                    Err(e) => {
+                        warn!(
+                            "failed to lookup the name servers of stub: {}: {}",
+                            origin, e
+                        );
+                        return Ok(());
+                    }
+                };
+
+                info!("stub name servers of {}: {:?}", origin, addresses);
+                let name_servers = NameServerConfigGroup::from_ips_clear(&addresses, port);
+                let config = ResolverConfig::from_parts(None, vec![], name_servers);
+                let (authoritative, bg) = AsyncResolver::new(config, options);
+
+                // the previous resolver stops once its pending lookups are done
+                ::tokio_executor::spawn(bg);
+                *resolver.write().expect("stub resolver poisoned") = authoritative;
+
+                Ok(())
+            })
+        });
+
+    bg.join(refreshes).map(|_| ())
+}
+
+/// Looks up the NS records of the zone, and the addresses of the name servers
+fn lookup_name_servers(
+    origin: Name,
+    masters: AsyncResolver,
+) -> impl Future<Item = Vec<IpAddr>, Error = ResolveError> {
+    masters
+        .lookup(origin, RecordType::NS)
+        .and_then(move |lookup| {
+            let addresses = lookup
+                .iter()
+                .filter_map(|rdata| rdata.as_ns())
+                .map(|name| {
+                    let name = name.clone();
+                    masters.lookup_ip(name.clone()).then(move |result| {
+                        let addresses = match result {
+                            Ok(lookup) => lookup.iter().collect(),
+                            Err(e) => {
+                                debug!("failed to lookup the addresses of {}: {}", name, e);
+                                vec![]
+                            }
+                        };
+
+                        Ok(addresses)
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            future::join_all(addresses)
+        })
+        .map(|addresses: Vec<Vec<IpAddr>>| addresses.into_iter().flatten().collect())
+}
+
+impl Authority for StubAuthority {
+    type Lookup = ForwardLookup;
+    type LookupFuture = Either<ForwardLookupFuture, FutureResult<ForwardLookup, LookupError>>;
+
+    /// Always Stub
+    fn zone_type(&self) -> ZoneType {
+        ZoneType::Stub
+    }
+
+    /// Always false for Stub zones
+    fn is_axfr_allowed(&self) -> bool {
+        false
+    }
+
+    fn update(&self, _update: &MessageRequest) -> UpdateResult<bool> {
+        Err(ResponseCode::NotImp)
+    }
+
+    /// Get the origin of this zone, i.e. example.com is the origin for www.example.com
+    fn origin(&self) -> &LowerName {
+        &self.origin
+    }
+
+    /// Resolves the lookup with the authoritative name servers of the zone
+    fn lookup(
+        &self,
+        name: &LowerName,
+        rtype: RecordType,
+        _is_secure: bool,
+        _supported_algorithms: SupportedAlgorithms,
+    ) -> Self::LookupFuture {
+        assert!(self.origin.zone_of(name));
+
+        info!("stub lookup: {} {}", name, rtype);
+        let resolver = self.resolver.read().expect("stub resolver poisoned");
+        Either::A(ForwardLookupFuture(resolver.lookup(name, rtype)))
+    }
+
+    fn search(
+        &self,
+        query: &LowerQuery,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Box<Future<Item = Self::Lookup, Error = LookupError> + Send> {
+        Box::new(self.lookup(
+            query.name(),
+            query.query_type(),
+            is_secure,
+            supported_algorithms,
+        ))
+    }
+
+    /// Always empty, a stub zone has no records of its own to prove the denials with
+    fn get_nsec_records(
+        &self,
+        _name: &LowerName,
+        _is_secure: bool,
+        _supported_algorithms: SupportedAlgorithms,
+    ) -> Self::LookupFuture {
+        Either::B(future::ok(ForwardLookup::default()))
+    }
+}
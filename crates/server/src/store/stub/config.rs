@@ -0,0 +1,36 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::time::Duration;
+
+use trust_dns_resolver::config::{NameServerConfigGroup, ResolverOpts};
+
+/// Configuration for stub zones
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct StubConfig {
+    /// name servers queried for the NS records of the zone, they answer the lookups until the
+    ///  authoritative name servers are learned, or if they can't be
+    pub masters: NameServerConfigGroup,
+    /// port of the authoritative name servers learned from the NS records, defaults to 53
+    pub port: Option<u16>,
+    /// seconds between each lookup of the NS records of the zone, defaults to 3600
+    pub refresh: Option<u64>,
+    /// Resolver options, e.g. the minimum and maximum TTLs of the cached answers
+    pub options: Option<ResolverOpts>,
+}
+
+impl StubConfig {
+    /// Port of the authoritative name servers
+    pub fn get_port(&self) -> u16 {
+        self.port.unwrap_or(53)
+    }
+
+    /// Time between each lookup of the NS records
+    pub fn get_refresh(&self) -> Duration {
+        Duration::from_secs(self.refresh.unwrap_or(3600))
+    }
+}
@@ -0,0 +1,418 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::net::{IpAddr, Ipv6Addr};
+use std::ops::Deref;
+use std::str::{self, FromStr};
+use std::sync::Arc;
+
+use futures::future::{self, FutureResult};
+use futures::Future;
+
+use proto::rr::domain::usage::IP6_ARPA;
+use trust_dns::op::{LowerQuery, ResponseCode};
+use trust_dns::rr::dnssec::SupportedAlgorithms;
+use trust_dns::rr::rdata::SOA;
+use trust_dns::rr::{LowerName, Name, RData, Record, RecordSet, RecordType};
+
+use authority::{
+    AuthLookup, Authority, LookupError, LookupRecords, MessageRequest, UpdateResult, ZoneType,
+};
+use store::in_memory::InMemoryAuthority;
+use store::synth_ptr::SynthPtrConfig;
+
+/// The placeholder of the address in a `NameTemplate`
+const PLACEHOLDER: &str = "{ip}";
+
+/// The longest address label, eight segments of four digits and their separators
+const MAX_ADDRESS_LABEL: usize = 39;
+
+/// An authority for the IPv6 addresses of an ip6.arpa zone, with PTR records to names from a
+///  template, and AAAA records for those names
+///
+/// Reverse zones of IPv6 networks can't have a record for each of their addresses, the records
+///  are synthesized when they are looked up. In the reverse zone, e.g. 8.b.d.0.1.0.0.2.ip6.arpa.,
+///  the names of the addresses have PTR records to the name of the template, e.g.
+///  2001-db8--1.dyn.example.com., and the zone of the template has the AAAA records of those
+///  names, so that the reverse and forward lookups of the addresses match. The AAAA records are
+///  only synthesized for the addresses of the reverse zone.
+///
+/// The zone has a generated SOA record. The synthesized records are not DNSSEC signed.
+pub struct SynthPtrAuthority {
+    zone: InMemoryAuthority,
+    template: NameTemplate,
+    reverse_zone: LowerName,
+    ttl: u32,
+}
+
+impl SynthPtrAuthority {
+    /// Creates a new authority synthesizing the records of the addresses of the reverse zone
+    ///
+    /// # Arguments
+    ///
+    /// * `origin` - the zone, the reverse zone itself or the zone of the template
+    /// * `template` - the names of the addresses
+    /// * `reverse_zone` - the ip6.arpa zone of the addresses, e.g. 8.b.d.0.1.0.0.2.ip6.arpa.
+    /// * `ttl` - TTL of the records
+    pub fn new(origin: Name, template: NameTemplate, reverse_zone: Name, ttl: u32) -> Self {
+        let mut zone = InMemoryAuthority::empty(origin.clone(), ZoneType::Master, false);
+        let soa = SOA::new(
+            origin.clone(),
+            Name::from_ascii("hostmaster")
+                .expect("bad hostmaster label")
+                .append_domain(&origin),
+            1,
+            3600,
+            600,
+            86400,
+            ttl,
+        );
+        zone.upsert(Record::from_rdata(origin, ttl, RData::SOA(soa)), 1);
+
+        SynthPtrAuthority {
+            zone,
+            template,
+            reverse_zone: LowerName::from(reverse_zone),
+            ttl,
+        }
+    }
+
+    /// Read the Authority for the origin from the specified configuration
+    pub fn try_from_config(
+        origin: Name,
+        _zone_type: ZoneType,
+        config: &SynthPtrConfig,
+    ) -> Result<Self, String> {
+        let template = NameTemplate::from_str(&config.template)?;
+        let reverse_zone = config
+            .reverse_zone
+            .clone()
+            .unwrap_or_else(|| origin.clone());
+
+        if !IP6_ARPA.zone_of(&reverse_zone) {
+            return Err(format!("not an ip6.arpa zone: {}", reverse_zone));
+        }
+        if !origin.zone_of(&reverse_zone) && !origin.zone_of(template.domain()) {
+            return Err(format!(
+                "{} is neither the zone of {} nor of the template {}",
+                origin, reverse_zone, config.template
+            ));
+        }
+
+        info!(
+            "synthesizing PTR records of {} to {} in {}",
+            reverse_zone, config.template, origin
+        );
+        Ok(SynthPtrAuthority::new(
+            origin,
+            template,
+            reverse_zone,
+            config.get_ttl(),
+        ))
+    }
+
+    /// The template of the names of the addresses
+    pub fn template(&self) -> &NameTemplate {
+        &self.template
+    }
+
+    /// The record synthesized at the name, the PTR record of an address in the reverse zone or the
+    ///  AAAA record of a name of the template
+    fn synthesized(&self, name: &LowerName) -> Option<Record> {
+        let name = Name::from(name);
+
+        if self.reverse_zone.zone_of(&LowerName::from(&name)) {
+            let address = reverse_address(&name)?;
+            let target = self.template.name_of(address);
+            return Some(Record::from_rdata(name, self.ttl, RData::PTR(target)));
+        }
+
+        let address = self.template.address_of(&name)?;
+        let reverse = LowerName::from(Name::from(IpAddr::V6(address)));
+        if !self.reverse_zone.zone_of(&reverse) {
+            return None;
+        }
+
+        Some(Record::from_rdata(name, self.ttl, RData::AAAA(address)))
+    }
+
+    /// True if the name is in the reverse zone and above the names of the addresses, these names
+    ///  exist, e.g. resolvers stop at the NXDOMAIN of a name, RFC 8020
+    fn is_reverse_ancestor(&self, name: &LowerName) -> bool {
+        let name = Name::from(name);
+        if !self.reverse_zone.zone_of(&LowerName::from(&name)) {
+            return false;
+        }
+
+        let nibbles = name.num_labels().saturating_sub(IP6_ARPA.num_labels());
+        nibbles < 32 && name.iter().take(nibbles as usize).all(is_nibble)
+    }
+}
+
+/// The address of the name of a reverse zone, e.g. of
+///  1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa.
+fn reverse_address(name: &Name) -> Option<Ipv6Addr> {
+    if name.num_labels() != 32 + IP6_ARPA.num_labels() || !IP6_ARPA.zone_of(name) {
+        return None;
+    }
+
+    // the least significant nibble first
+    let nibbles = name.iter().take(32).collect::<Vec<_>>();
+    let mut address = 0_u128;
+    for label in nibbles.into_iter().rev() {
+        if !is_nibble(label) {
+            return None;
+        }
+
+        let nibble = (label[0] as char).to_digit(16)?;
+        address = address << 4 | u128::from(nibble);
+    }
+
+    Some(Ipv6Addr::from(address))
+}
+
+/// True if the label is a single hexadecimal digit
+fn is_nibble(label: &[u8]) -> bool {
+    label.len() == 1 && (label[0] as char).is_ascii_hexdigit()
+}
+
+/// The address in a label, the RFC 5952 text representation with `-` for `:`, e.g. 2001-db8--1
+fn address_label(address: Ipv6Addr) -> String {
+    let segments = address.segments();
+
+    // the first of the longest runs of at least two zero segments is compressed
+    let (mut start, mut len) = (0, 0);
+    let mut i = 0;
+    while i < segments.len() {
+        let run = segments[i..].iter().take_while(|s| **s == 0).count();
+        if run > len {
+            start = i;
+            len = run;
+        }
+        i += run.max(1);
+    }
+
+    let hex = |segments: &[u16]| {
+        segments
+            .iter()
+            .map(|s| format!("{:x}", s))
+            .collect::<Vec<_>>()
+            .join("-")
+    };
+    if len < 2 {
+        hex(&segments)
+    } else {
+        format!(
+            "{}--{}",
+            hex(&segments[..start]),
+            hex(&segments[start + len..])
+        )
+    }
+}
+
+/// The names of the addresses, a domain and a first label with the address
+///
+/// The template is a name with `{ip}` in its first label, e.g. `{ip}.dyn.example.com.` or
+///  `host-{ip}.example.com.`, it is replaced by the address with `-` for `:`, e.g.
+///  `2001-db8--1.dyn.example.com.`
+#[derive(Clone, Debug, PartialEq)]
+pub struct NameTemplate {
+    prefix: String,
+    suffix: String,
+    domain: Name,
+}
+
+impl NameTemplate {
+    /// The domain of the names, e.g. dyn.example.com.
+    pub fn domain(&self) -> &Name {
+        &self.domain
+    }
+
+    /// The name of the address
+    pub fn name_of(&self, address: Ipv6Addr) -> Name {
+        // the label starts with a - for the addresses starting with ::, it is not a hostname
+        let label = format!("{}{}{}", self.prefix, address_label(address), self.suffix);
+        Name::from_labels(vec![label.into_bytes()])
+            .expect("template label checked when parsed")
+            .append_domain(&self.domain)
+    }
+
+    /// The address of the name, if it is one of the template
+    ///
+    /// Only the names of the template with the exact label of the address match, e.g.
+    ///  2001-0db8--1.dyn.example.com. doesn't, so that an address has a single name.
+    pub fn address_of(&self, name: &Name) -> Option<Ipv6Addr> {
+        if name.num_labels() != self.domain.num_labels() + 1 || !self.domain.zone_of(name) {
+            return None;
+        }
+
+        let label = str::from_utf8(name.iter().next()?).ok()?.to_lowercase();
+        if !label.starts_with(&self.prefix) || !label.ends_with(&self.suffix) {
+            return None;
+        }
+        let end = label.len().checked_sub(self.suffix.len())?;
+        let address = label.get(self.prefix.len()..end)?;
+
+        let parsed = Ipv6Addr::from_str(&address.replace('-', ":")).ok()?;
+        if address_label(parsed) != address {
+            return None;
+        }
+
+        Some(parsed)
+    }
+}
+
+impl FromStr for NameTemplate {
+    type Err = String;
+
+    fn from_str(template: &str) -> Result<Self, String> {
+        let (label, domain) = match template.find('.') {
+            Some(dot) => (&template[..dot], &template[dot + 1..]),
+            None => (template, "."),
+        };
+        let placeholder = label
+            .find(PLACEHOLDER)
+            .ok_or_else(|| format!("no {} in the first label of {}", PLACEHOLDER, template))?;
+
+        let prefix = label[..placeholder].to_lowercase();
+        let suffix = label[placeholder + PLACEHOLDER.len()..].to_lowercase();
+        let is_valid = |s: &str| s.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+        if !is_valid(&prefix) || !is_valid(&suffix) {
+            return Err(format!("bad first label of template {}", template));
+        }
+        if prefix.len() + MAX_ADDRESS_LABEL + suffix.len() > 63 {
+            return Err(format!("first label of template {} too long", template));
+        }
+
+        // the domain of the template is absolute
+        let mut domain = Name::from_str(domain)
+            .map_err(|e| format!("bad domain of template {}: {}", template, e))?;
+        domain.set_fqdn(true);
+
+        Ok(NameTemplate {
+            prefix,
+            suffix,
+            domain,
+        })
+    }
+}
+
+impl Deref for SynthPtrAuthority {
+    type Target = InMemoryAuthority;
+
+    fn deref(&self) -> &Self::Target {
+        &self.zone
+    }
+}
+
+impl Authority for SynthPtrAuthority {
+    type Lookup = AuthLookup;
+    type LookupFuture = FutureResult<Self::Lookup, LookupError>;
+
+    /// What type is this zone
+    fn zone_type(&self) -> ZoneType {
+        self.zone.zone_type()
+    }
+
+    /// Return true if AXFR is allowed
+    fn is_axfr_allowed(&self) -> bool {
+        self.zone.is_axfr_allowed()
+    }
+
+    /// Dynamic updates are not supported, the records are synthesized
+    fn update(&self, _update: &MessageRequest) -> UpdateResult<bool> {
+        Err(ResponseCode::NotImp)
+    }
+
+    /// Get the origin of this zone, i.e. example.com is the origin for www.example.com
+    fn origin(&self) -> &LowerName {
+        self.zone.origin()
+    }
+
+    /// The names not in the zone, e.g. other than its apex, are answered with the synthesized
+    ///  records
+    fn lookup(
+        &self,
+        name: &LowerName,
+        rtype: RecordType,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Self::LookupFuture {
+        // the zone lookup is ready immediately
+        let lookup = self
+            .zone
+            .lookup(name, rtype, is_secure, supported_algorithms)
+            .wait();
+        // ANY lookups of missing names are empty
+        let is_missing = match lookup {
+            Err(ref e) => e.is_nx_domain() || e.is_name_exists(),
+            Ok(ref lookup) => lookup.is_empty(),
+        };
+        if !is_missing {
+            return future::result(lookup);
+        }
+
+        let record = match self.synthesized(name) {
+            Some(record) => record,
+            None if self.is_reverse_ancestor(name) => return future::err(LookupError::NameExists),
+            None => return future::result(lookup),
+        };
+        if rtype != record.rr_type() && rtype != RecordType::ANY {
+            return future::err(LookupError::NameExists);
+        }
+
+        debug!("synthesized {} {}", record.rr_type(), record.name());
+        let mut rrset = RecordSet::new(record.name(), record.rr_type(), 0);
+        rrset.insert(record, 0);
+        let answers = LookupRecords::new(is_secure, supported_algorithms, Arc::new(rrset));
+        future::ok(AuthLookup::answers(answers, None))
+    }
+
+    fn search(
+        &self,
+        query: &LowerQuery,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Box<dyn Future<Item = Self::Lookup, Error = LookupError> + Send> {
+        match query.query_type() {
+            RecordType::AXFR | RecordType::SOA => {
+                self.zone.search(query, is_secure, supported_algorithms)
+            }
+            rtype => Box::new(self.lookup(query.name(), rtype, is_secure, supported_algorithms)),
+        }
+    }
+
+    /// Get the NS, NameServer, record for the zone
+    fn ns(&self, is_secure: bool, supported_algorithms: SupportedAlgorithms) -> Self::LookupFuture {
+        self.zone.ns(is_secure, supported_algorithms)
+    }
+
+    /// Return the NSEC records based on the given name
+    fn get_nsec_records(
+        &self,
+        name: &LowerName,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Self::LookupFuture {
+        self.zone
+            .get_nsec_records(name, is_secure, supported_algorithms)
+    }
+
+    /// Returns the SOA of the authority.
+    fn soa(&self) -> Self::LookupFuture {
+        self.zone.soa()
+    }
+
+    /// Returns the SOA record for the zone
+    fn soa_secure(
+        &self,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Self::LookupFuture {
+        self.zone.soa_secure(is_secure, supported_algorithms)
+    }
+}
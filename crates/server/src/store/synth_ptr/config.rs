@@ -0,0 +1,28 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use trust_dns::rr::Name;
+
+/// Configuration for zones with records synthesized for the addresses of an ip6.arpa zone
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct SynthPtrConfig {
+    /// template of the names of the addresses, `{ip}` in its first label is replaced by the
+    ///  address with `-` for `:`, e.g. `{ip}.dyn.example.com.` for `2001-db8--1.dyn.example.com.`
+    pub template: String,
+    /// the ip6.arpa zone of the addresses, defaults to the zone, it must be set for the zone of
+    ///  the template
+    pub reverse_zone: Option<Name>,
+    /// TTL of the records, defaults to 3600
+    pub ttl: Option<u32>,
+}
+
+impl SynthPtrConfig {
+    /// TTL of the synthesized records
+    pub fn get_ttl(&self) -> u32 {
+        self.ttl.unwrap_or(3600)
+    }
+}
@@ -0,0 +1,14 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! IPv6 reverse zones with PTR records, and matching AAAA records, synthesized from a template
+
+mod authority;
+mod config;
+
+pub use self::authority::{NameTemplate, SynthPtrAuthority};
+pub use self::config::SynthPtrConfig;
@@ -0,0 +1,393 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! An in-process server of canned responses, for the tests of the handling of failures by clients
+//!
+//! The server listens on UDP and TCP on an ephemeral port of the loopback, on its own thread, until
+//!  its handle is dropped.
+//!
+//! ```rust,no_run
+//! # extern crate trust_dns_server;
+//! # use std::time::Duration;
+//! use trust_dns_server::proto::op::{Query, ResponseCode};
+//! use trust_dns_server::proto::rr::{Name, RecordType};
+//! use trust_dns_server::testing::{MockResponse, MockServer};
+//!
+//! # fn main() {
+//! let query = Query::query(Name::from_ascii("www.example.com.").unwrap(), RecordType::A);
+//! let server = MockServer::new()
+//!     .respond(
+//!         query,
+//!         MockResponse::response_code(ResponseCode::ServFail).with_delay(Duration::from_secs(1)),
+//!     )
+//!     .start()
+//!     .expect("failed to start the server");
+//!
+//! println!("listening on: {}", server.addr());
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use futures::sync::oneshot;
+use futures::{future, Future, Stream};
+use tokio::runtime::current_thread::{self, Runtime};
+use tokio_reactor::Handle;
+use tokio_tcp;
+use tokio_timer::Delay;
+use tokio_udp;
+
+use proto::op::{Message, MessageType, Query, ResponseCode};
+use proto::rr::Record;
+use proto::tcp::TcpStream;
+use proto::udp::UdpStream;
+use proto::xfer::SerialMessage;
+use proto::BufStreamHandle;
+#[cfg(feature = "trust-dns-resolver")]
+use trust_dns_resolver::config::{NameServerConfigGroup, ResolverConfig};
+
+use server::Protocol;
+
+/// A canned response of the `MockServer`
+#[derive(Clone, Debug)]
+pub struct MockResponse {
+    response_code: ResponseCode,
+    answers: Vec<Record>,
+    delay: Option<Duration>,
+    truncated: bool,
+    dropped: usize,
+}
+
+impl MockResponse {
+    /// A `NoError` response with the answers
+    pub fn answers(answers: Vec<Record>) -> Self {
+        MockResponse {
+            response_code: ResponseCode::NoError,
+            answers,
+            delay: None,
+            truncated: false,
+            dropped: 0,
+        }
+    }
+
+    /// A response of the code without answers, e.g. `ServFail`
+    pub fn response_code(response_code: ResponseCode) -> Self {
+        MockResponse {
+            response_code,
+            ..Self::answers(Vec::new())
+        }
+    }
+
+    /// Sends the response after the delay
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+
+    /// Sends the response over UDP truncated, without answers, such that clients retry over TCP
+    pub fn with_truncated(mut self, truncated: bool) -> Self {
+        self.truncated = truncated;
+        self
+    }
+
+    /// Doesn't respond to the first `dropped` requests, such that clients time out
+    pub fn with_dropped(mut self, dropped: usize) -> Self {
+        self.dropped = dropped;
+        self
+    }
+}
+
+/// A server of canned responses to queries, see `start`
+pub struct MockServer {
+    responses: HashMap<Query, MockResponse>,
+    default: MockResponse,
+}
+
+impl MockServer {
+    /// A server which responds `Refused` to all queries
+    pub fn new() -> Self {
+        MockServer {
+            responses: HashMap::new(),
+            default: MockResponse::response_code(ResponseCode::Refused),
+        }
+    }
+
+    /// Responds to the query with the response
+    pub fn respond(mut self, query: Query, response: MockResponse) -> Self {
+        self.responses.insert(query, response);
+        self
+    }
+
+    /// The response to the queries without a response, `Refused` by default
+    pub fn with_default(mut self, response: MockResponse) -> Self {
+        self.default = response;
+        self
+    }
+
+    /// Starts the server on an ephemeral port of the IPv4 loopback, for both UDP and TCP
+    pub fn start(self) -> io::Result<MockServerHandle> {
+        let (udp, tcp) = bind_loopback()?;
+        let addr = udp.local_addr()?;
+
+        let state = Arc::new(Mutex::new(State {
+            server: self,
+            counts: HashMap::new(),
+            requests: Vec::new(),
+        }));
+
+        let (shutdown, signal) = oneshot::channel::<()>();
+        let thread_state = Arc::clone(&state);
+        let thread = thread::Builder::new()
+            .name("trust-dns-mock-server".to_string())
+            .spawn(move || {
+                let mut runtime = Runtime::new().expect("failed to create the mock server runtime");
+                runtime.spawn(future::lazy(move || {
+                    serve_udp(udp, Arc::clone(&thread_state));
+                    serve_tcp(tcp, thread_state);
+                    Ok(())
+                }));
+
+                // the sender is dropped with the handle
+                runtime.block_on(signal).ok();
+            })?;
+
+        Ok(MockServerHandle {
+            addr,
+            state,
+            shutdown: Some(shutdown),
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Default for MockServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A running `MockServer`, which is stopped when the handle is dropped
+pub struct MockServerHandle {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    shutdown: Option<oneshot::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl MockServerHandle {
+    /// The address of the server, for both UDP and TCP
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// The requests received, in order, with the protocol over which they were received
+    pub fn requests(&self) -> Vec<(Protocol, Message)> {
+        self.state
+            .lock()
+            .expect("mock server state poisoned")
+            .requests
+            .clone()
+    }
+
+    /// A resolver configuration of the server, over UDP and TCP
+    #[cfg(feature = "trust-dns-resolver")]
+    pub fn resolver_config(&self) -> ResolverConfig {
+        ResolverConfig::from_parts(
+            None,
+            vec![],
+            NameServerConfigGroup::from_ips_clear(&[self.addr.ip()], self.addr.port()),
+        )
+    }
+}
+
+impl Drop for MockServerHandle {
+    fn drop(&mut self) {
+        self.shutdown.take();
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+struct State {
+    server: MockServer,
+    // the requests of each query, for the dropped requests
+    counts: HashMap<Query, usize>,
+    requests: Vec<(Protocol, Message)>,
+}
+
+impl State {
+    /// Returns the response to the request and its delay, None if the request is dropped
+    fn response(
+        &mut self,
+        protocol: Protocol,
+        request: &Message,
+    ) -> Option<(Message, Option<Duration>)> {
+        self.requests.push((protocol, request.clone()));
+
+        let canned = match request.queries().first() {
+            Some(query) => {
+                let count = self.counts.entry(query.clone()).or_insert(0);
+                *count += 1;
+
+                let canned = self
+                    .server
+                    .responses
+                    .get(query)
+                    .unwrap_or(&self.server.default);
+                if *count <= canned.dropped {
+                    debug!("mock server dropped request {} of: {}", count, query);
+                    return None;
+                }
+                canned
+            }
+            None => &self.server.default,
+        };
+
+        let mut response = Message::new();
+        response
+            .set_id(request.id())
+            .set_message_type(MessageType::Response)
+            .set_op_code(request.op_code())
+            .set_recursion_desired(request.recursion_desired())
+            .set_recursion_available(true)
+            .set_response_code(canned.response_code)
+            .add_queries(request.queries().to_vec());
+
+        // the truncation is set after the counts, see `Message::to_raw_vec`
+        let truncated = canned.truncated && protocol == Protocol::Udp;
+        if !truncated {
+            response.insert_answers(canned.answers.clone());
+        }
+        response.update_counts().set_truncated(truncated);
+
+        Some((response, canned.delay))
+    }
+}
+
+/// Binds UDP and TCP to the same ephemeral port, retrying on the port of UDP being in use by TCP
+fn bind_loopback() -> io::Result<(std::net::UdpSocket, std::net::TcpListener)> {
+    let mut attempts = 0;
+    loop {
+        let udp = std::net::UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))?;
+        match std::net::TcpListener::bind(udp.local_addr()?) {
+            Ok(tcp) => return Ok((udp, tcp)),
+            Err(ref e) if e.kind() == io::ErrorKind::AddrInUse && attempts < 10 => attempts += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn serve_udp(socket: std::net::UdpSocket, state: Arc<Mutex<State>>) {
+    let socket = tokio_udp::UdpSocket::from_std(socket, &Handle::default())
+        .expect("failed to register the mock server UDP socket");
+    let (buf_stream, stream_handle) = UdpStream::with_bound(socket);
+
+    current_thread::spawn(
+        buf_stream
+            .map_err(|e| warn!("error in mock server UDP stream: {}", e))
+            .for_each(move |message| {
+                current_thread::spawn(respond(&state, Protocol::Udp, message, &stream_handle));
+                Ok(())
+            }),
+    );
+}
+
+fn serve_tcp(listener: std::net::TcpListener, state: Arc<Mutex<State>>) {
+    let listener = tokio_tcp::TcpListener::from_std(listener, &Handle::default())
+        .expect("failed to register the mock server TCP listener");
+
+    current_thread::spawn(
+        listener
+            .incoming()
+            .map_err(|e| warn!("error in mock server TCP listener: {}", e))
+            .for_each(move |tcp_stream| {
+                let src_addr = match tcp_stream.peer_addr() {
+                    Ok(src_addr) => src_addr,
+                    Err(e) => {
+                        warn!("error in mock server TCP stream: {}", e);
+                        return Ok(());
+                    }
+                };
+                let (buf_stream, stream_handle) = TcpStream::from_stream(tcp_stream, src_addr);
+                let state = Arc::clone(&state);
+
+                current_thread::spawn(
+                    buf_stream
+                        .map_err(|e| debug!("error in mock server TCP stream: {}", e))
+                        .for_each(move |message| {
+                            current_thread::spawn(respond(
+                                &state,
+                                Protocol::Tcp,
+                                message,
+                                &stream_handle,
+                            ));
+                            Ok(())
+                        }),
+                );
+                Ok(())
+            }),
+    );
+}
+
+fn respond(
+    state: &Mutex<State>,
+    protocol: Protocol,
+    request: SerialMessage,
+    stream_handle: &BufStreamHandle,
+) -> Box<Future<Item = (), Error = ()>> {
+    let src_addr = request.addr();
+    let request = match request.to_message() {
+        Ok(request) => request,
+        Err(e) => {
+            warn!(
+                "mock server failed to decode request from {}: {}",
+                src_addr, e
+            );
+            return Box::new(future::ok(()));
+        }
+    };
+
+    let response = state
+        .lock()
+        .expect("mock server state poisoned")
+        .response(protocol, &request);
+    let (response, delay) = match response {
+        Some(response) => response,
+        None => return Box::new(future::ok(())),
+    };
+
+    let stream_handle = stream_handle.clone();
+    let send = move || match response.to_raw_vec() {
+        Ok(bytes) => {
+            if stream_handle
+                .unbounded_send(SerialMessage::new(bytes, src_addr))
+                .is_err()
+            {
+                debug!("mock server connection closed: {}", src_addr);
+            }
+        }
+        Err(e) => warn!("mock server failed to encode response: {}", e),
+    };
+
+    match delay {
+        Some(delay) => Box::new(
+            Delay::new(Instant::now() + delay)
+                .map_err(|e| warn!("error in mock server delay: {}", e))
+                .map(move |()| send()),
+        ),
+        None => {
+            send();
+            Box::new(future::ok(()))
+        }
+    }
+}
@@ -5,4 +5,4 @@ pub mod basic;
 #[macro_use]
 pub mod dnssec;
 #[macro_use]
-pub mod dynamic_update;
\ No newline at end of file
+pub mod dynamic_update;
@@ -16,15 +16,22 @@
 extern crate log;
 extern crate trust_dns;
 extern crate trust_dns_proto;
+#[cfg(feature = "trust-dns-resolver")]
+extern crate trust_dns_resolver;
 extern crate trust_dns_server;
 
 use std::env;
-use std::net::{Ipv4Addr, Ipv6Addr};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::time::Duration;
 
-use trust_dns_server::authority::ZoneType;
+use trust_dns::rr::Name;
+use trust_dns_proto::tcp::TcpOptions;
+use trust_dns_server::authority::{MinimalResponses, ResponseOrder, ZoneType};
 use trust_dns_server::config::*;
+use trust_dns_server::server::{Protocol, ProtocolPolicy};
+use trust_dns_server::store::StoreConfig;
 
 #[test]
 fn test_read_config() {
@@ -41,7 +48,14 @@ fn test_read_config() {
     assert_eq!(config.get_listen_port(), 53);
     assert_eq!(config.get_listen_addrs_ipv4(), Vec::<Ipv4Addr>::new());
     assert_eq!(config.get_listen_addrs_ipv6(), Vec::<Ipv6Addr>::new());
+    assert_eq!(config.get_listen().len(), 1);
+    assert_eq!(
+        config.get_listen()[0].get_ip(),
+        IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0))
+    );
     assert_eq!(config.get_tcp_request_timeout(), Duration::from_secs(5));
+    assert_eq!(config.get_request_deadline(), None);
+    assert_eq!(config.get_max_udp_payload(), 1232);
     assert_eq!(config.get_log_level(), log::Level::Info);
     assert_eq!(config.get_directory(), Path::new("/var/named"));
     assert_eq!(
@@ -141,14 +155,217 @@ fn test_parse_toml() {
         ]
     );
 
+    let config: Config = "listen_addrs_ipv4 = [\"0.0.0.0\"]\nlisten_addrs_ipv6 = [\"::\"]"
+        .parse()
+        .unwrap();
+    let listen = config.get_listen();
+    assert_eq!(listen.len(), 2);
+    assert_eq!(listen[0].get_ip(), IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)));
+    assert_eq!(listen[0].get_v6only(), None);
+    assert_eq!(
+        listen[1].get_ip(),
+        IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0))
+    );
+    assert_eq!(listen[1].get_v6only(), Some(true));
+
+    let config: Config = "
+        [[listen]]
+        addr = \"[::]:5353\"
+        v6only = false
+
+        [[listen]]
+        addr = \"127.0.0.1\"
+        protocols = [\"Tcp\", \"Tls\"]
+    "
+    .parse()
+    .unwrap();
+    let listen = config.get_listen();
+    assert_eq!(listen.len(), 2);
+    assert_eq!(
+        listen[0].get_ip(),
+        IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0))
+    );
+    assert_eq!(listen[0].get_port(), Some(5353));
+    assert_eq!(listen[0].get_v6only(), Some(false));
+    assert!(listen[0].serves(Protocol::Udp));
+    assert_eq!(listen[1].get_ip(), IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)));
+    assert_eq!(listen[1].get_port(), None);
+    assert!(!listen[1].serves(Protocol::Udp));
+    assert!(listen[1].serves(Protocol::Tcp));
+    assert!(listen[1].serves(Protocol::Tls));
+
     let config: Config = "tcp_request_timeout = 25".parse().unwrap();
     assert_eq!(config.get_tcp_request_timeout(), Duration::from_secs(25));
 
+    let config: Config = "request_deadline = 3".parse().unwrap();
+    assert_eq!(config.get_request_deadline(), Some(Duration::from_secs(3)));
+
+    let config: Config = "max_udp_payload = 4096".parse().unwrap();
+    assert_eq!(config.get_max_udp_payload(), 4096);
+
+    let config: Config = "refuse_any_over_udp = true\nmax_encrypted_payload = 8192"
+        .parse()
+        .unwrap();
+    let mut policy = ProtocolPolicy::new();
+    policy
+        .set_refuse_any_over_udp(true)
+        .set_max_encrypted_payload(8192);
+    assert_eq!(config.get_protocol_policy(), policy);
+
+    let config: Config = "tcp_nodelay = true\ntcp_keepalive = 60\ntcp_fast_open = true"
+        .parse()
+        .unwrap();
+    assert_eq!(
+        config.get_tcp_options(),
+        TcpOptions {
+            nodelay: true,
+            keepalive: Some(Duration::from_secs(60)),
+            user_timeout: None,
+            fast_open: true,
+            bind_addr: None,
+        }
+    );
+
+    let config: Config = "udp_offload = true".parse().unwrap();
+    assert!(config.get_udp_offload());
+
     let config: Config = "log_level = \"Debug\"".parse().unwrap();
     assert_eq!(config.get_log_level(), log::Level::Debug);
 
     let config: Config = "directory = \"/dev/null\"".parse().unwrap();
     assert_eq!(config.get_directory(), Path::new("/dev/null"));
+
+    let config: Config = "update_audit = { file = \"update_audit.log\" }"
+        .parse()
+        .unwrap();
+    let update_audit = config.get_update_audit().expect("update_audit");
+    assert_eq!(update_audit.get_file(), Some(Path::new("update_audit.log")));
+    assert!(!update_audit.get_syslog());
+
+    let config: Config =
+        "cookies = { secret_file = \"cookie_secrets.toml\", rotation_interval = 600 }"
+            .parse()
+            .unwrap();
+    let cookies = config.get_cookies().expect("cookies");
+    assert_eq!(
+        cookies.get_secret_file(),
+        Some(Path::new("cookie_secrets.toml"))
+    );
+    assert_eq!(cookies.get_secret(), None);
+    assert_eq!(cookies.get_rotation_interval(), Duration::from_secs(600));
+    assert_eq!(cookies.get_overlap(), Duration::from_secs(3600));
+}
+
+#[test]
+fn test_parse_response_order() {
+    let config: Config = "[[zones]]
+zone = \"example.com\"
+zone_type = \"Master\"
+file = \"example.com.zone\"
+response_order = \"RoundRobin\"
+"
+    .parse()
+    .unwrap();
+    assert_eq!(
+        config.get_zones()[0].get_response_order(),
+        ResponseOrder::RoundRobin
+    );
+
+    let config: Config = "[[zones]]
+zone = \"example.com\"
+zone_type = \"Master\"
+file = \"example.com.zone\"
+"
+    .parse()
+    .unwrap();
+    assert_eq!(
+        config.get_zones()[0].get_response_order(),
+        ResponseOrder::Fixed
+    );
+}
+
+#[test]
+fn test_parse_minimal_responses() {
+    let config: Config = "minimal_responses = \"NoAuth\"
+
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Master\"
+file = \"example.com.zone\"
+minimal_responses = \"Yes\"
+
+[[zones]]
+zone = \"example.net\"
+zone_type = \"Master\"
+file = \"example.net.zone\"
+"
+    .parse()
+    .unwrap();
+    assert_eq!(config.get_minimal_responses(), MinimalResponses::NoAuth);
+    assert_eq!(
+        config.get_zones()[0].get_minimal_responses(),
+        Some(MinimalResponses::Yes)
+    );
+    assert_eq!(config.get_zones()[1].get_minimal_responses(), None);
+
+    let config: Config = "".parse().unwrap();
+    assert_eq!(config.get_minimal_responses(), MinimalResponses::No);
+}
+
+#[test]
+fn test_parse_signature_jitter() {
+    let config: Config = "[[zones]]
+zone = \"example.com\"
+zone_type = \"Master\"
+file = \"example.com.zone\"
+signature_jitter = 86400
+"
+    .parse()
+    .unwrap();
+    assert_eq!(
+        config.get_zones()[0].get_signature_jitter(),
+        Duration::from_secs(86400)
+    );
+
+    let config: Config = "[[zones]]
+zone = \"example.com\"
+zone_type = \"Master\"
+file = \"example.com.zone\"
+"
+    .parse()
+    .unwrap();
+    assert_eq!(
+        config.get_zones()[0].get_signature_jitter(),
+        Duration::from_secs(0)
+    );
+}
+
+#[test]
+fn test_parse_ds_check() {
+    let config: Config = "[[zones]]
+zone = \"example.com\"
+zone_type = \"Master\"
+file = \"example.com.zone\"
+enable_dnssec = true
+ds_check = { interval = 600, name_servers = [\"192.0.2.53\"] }
+"
+    .parse()
+    .unwrap();
+    let ds_check = config.get_zones()[0].get_ds_check().expect("no ds_check");
+    assert_eq!(ds_check.get_interval(), Duration::from_secs(600));
+    assert_eq!(
+        ds_check.name_servers,
+        vec![IpAddr::V4(Ipv4Addr::new(192, 0, 2, 53))]
+    );
+
+    let config: Config = "[[zones]]
+zone = \"example.com\"
+zone_type = \"Master\"
+file = \"example.com.zone\"
+"
+    .parse()
+    .unwrap();
+    assert!(config.get_zones()[0].get_ds_check().is_none());
 }
 
 #[cfg(feature = "dnssec")]
@@ -253,6 +470,129 @@ tls_listen_port = 8853
     );
 }
 
+#[test]
+fn test_parse_https() {
+    // defaults
+    let config: Config = "".parse().unwrap();
+
+    assert_eq!(config.get_https_listen_port(), 443);
+    assert_eq!(config.get_http_listen_port(), None);
+    assert!(!config.get_health_endpoints());
+
+    let config: Config = "
+https_listen_port = 8443
+http_listen_port = 8080
+health_endpoints = true
+  "
+    .parse()
+    .unwrap();
+
+    assert_eq!(config.get_https_listen_port(), 8443);
+    assert_eq!(config.get_http_listen_port(), Some(8080));
+    assert!(config.get_health_endpoints());
+}
+
+#[test]
+fn test_parse_secondary_masters() {
+    let config: Config = "
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Slave\"
+masters = [\"192.0.2.53:53\", \"[2001:db8::53]:53\"]
+transfer_source = \"192.0.2.1\"
+
+[[zones]]
+zone = \"example.net\"
+zone_type = \"Slave\"
+file = \"example.net.zone\"
+  "
+    .parse()
+    .unwrap();
+
+    let zone = &config.get_zones()[0];
+    assert_eq!(
+        zone.get_masters(),
+        &[
+            "192.0.2.53:53".parse().unwrap(),
+            "[2001:db8::53]:53".parse().unwrap()
+        ]
+    );
+    assert_eq!(
+        zone.get_transfer_source(),
+        Some(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)))
+    );
+
+    // defaults
+    let zone = &config.get_zones()[1];
+    assert!(zone.get_masters().is_empty());
+    assert_eq!(zone.get_transfer_source(), None);
+}
+
+#[test]
+fn test_parse_forward_ttl_overrides() {
+    let config: Config = "
+[[zones]]
+zone = \".\"
+zone_type = \"Forward\"
+
+[zones.stores]
+type = \"forward\"
+name_servers = [{ socket_addr = \"8.8.8.8:53\", protocol = \"Udp\" }]
+ttl_overrides = [{ suffix = \"corp.example.com.\", ttl = 5 }]
+  "
+    .parse()
+    .unwrap();
+
+    match config.get_zones()[0].stores {
+        Some(StoreConfig::Forward(ref forward)) => {
+            let ttl_overrides = forward.ttl_overrides.as_ref().expect("no ttl_overrides");
+            assert_eq!(ttl_overrides.len(), 1);
+            assert_eq!(
+                ttl_overrides[0].suffix,
+                Name::from_str("corp.example.com.").unwrap()
+            );
+            assert_eq!(ttl_overrides[0].ttl, 5);
+        }
+        ref stores => panic!("expected a forward store: {:?}", stores),
+    }
+}
+
+#[test]
+#[cfg(feature = "trust-dns-resolver")]
+fn test_parse_forward_validation_exceptions() {
+    use trust_dns_resolver::config::ValidationPolicy;
+
+    let config: Config = "
+[[zones]]
+zone = \".\"
+zone_type = \"Forward\"
+
+[zones.stores]
+type = \"forward\"
+name_servers = [{ socket_addr = \"8.8.8.8:53\", protocol = \"Udp\" }]
+options = { validate = true }
+validation_exceptions = [{ suffix = \"corp.example.com.\", policy = \"NoDnssecOk\" }]
+  "
+    .parse()
+    .unwrap();
+
+    match config.get_zones()[0].stores {
+        Some(StoreConfig::Forward(ref forward)) => {
+            let exceptions = forward
+                .validation_exceptions
+                .as_ref()
+                .expect("no validation_exceptions");
+            assert_eq!(exceptions.len(), 1);
+            assert_eq!(
+                exceptions[0].suffix,
+                Name::from_str("corp.example.com.").unwrap()
+            );
+            assert_eq!(exceptions[0].policy, ValidationPolicy::NoDnssecOk);
+        }
+        ref stores => panic!("expected a forward store: {:?}", stores),
+    }
+}
+
 fn test_config(path: &str) {
     let path = PathBuf::from("tests/named_test_configs")
         .join(path)
@@ -275,9 +615,15 @@ define_test_config!(all_supported_dnssec);
 define_test_config!(dns_over_https);
 define_test_config!(dns_over_tls_rustls_and_openssl);
 define_test_config!(dns_over_tls);
+define_test_config!(dual_stack);
 define_test_config!(dnssec_with_update);
 define_test_config!(dnssec_with_update_deprecated);
 define_test_config!(example);
+define_test_config!(example_geoip);
+define_test_config!(example_load_balancer);
+define_test_config!(example_auto_ptr);
+define_test_config!(example_dhcp);
+define_test_config!(example_synth_ptr);
 define_test_config!(ipv4_and_ipv6);
 define_test_config!(ipv4_only);
 define_test_config!(ipv6_only);
@@ -285,3 +631,11 @@ define_test_config!(openssl_dnssec);
 define_test_config!(ring_dnssec);
 #[cfg(feature = "trust-dns-resolver")]
 define_test_config!(example_forwarder);
+#[cfg(feature = "trust-dns-resolver")]
+define_test_config!(example_blocklist);
+#[cfg(feature = "trust-dns-resolver")]
+define_test_config!(example_local_data);
+#[cfg(feature = "trust-dns-resolver")]
+define_test_config!(example_stub);
+#[cfg(feature = "trust-dns-resolver")]
+define_test_config!(example_aname);
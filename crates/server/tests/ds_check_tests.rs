@@ -0,0 +1,122 @@
+#![cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+
+extern crate trust_dns;
+extern crate trust_dns_server;
+
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use trust_dns::proto::rr::dnssec::rdata::{DNSKEY, DS};
+use trust_dns::rr::dnssec::{Algorithm, DigestType};
+use trust_dns::rr::Name;
+use trust_dns_server::authority::{compare_ds, DsChecker, DsMismatch};
+
+fn zone() -> Name {
+    Name::from_str("example.com.").unwrap()
+}
+
+fn key(secure_entry_point: bool, public_key: &[u8]) -> DNSKEY {
+    DNSKEY::new(
+        true,
+        secure_entry_point,
+        false,
+        Algorithm::RSASHA256,
+        public_key.to_vec(),
+    )
+}
+
+fn ds(key: &DNSKEY) -> DS {
+    let digest = key
+        .to_digest(&zone(), DigestType::SHA256)
+        .expect("failed to digest the key");
+
+    DS::new(
+        key.calculate_key_tag().unwrap(),
+        key.algorithm(),
+        DigestType::SHA256,
+        digest.as_ref().to_vec(),
+    )
+}
+
+fn tag(key: &DNSKEY) -> u16 {
+    key.calculate_key_tag().unwrap()
+}
+
+#[test]
+fn test_matching_ds() {
+    let ksk = key(true, b"key signing key");
+    let zsk = key(false, b"zone signing key");
+
+    assert!(compare_ds(&zone(), &[ksk.clone(), zsk.clone()], &[ds(&ksk)]).is_none());
+
+    // without a secure entry point, any key may have the DS
+    let other = key(false, b"other zone signing key");
+    assert!(compare_ds(&zone(), &[zsk.clone(), other], &[ds(&zsk)]).is_none());
+}
+
+#[test]
+fn test_unmatched_ds() {
+    let ksk = key(true, b"key signing key");
+    let removed = key(true, b"removed key signing key");
+
+    // the DS of a removed key breaks the delegation
+    let mismatch = compare_ds(&zone(), &[ksk.clone()], &[ds(&removed)]).expect("no mismatch");
+    assert!(mismatch.is_broken());
+    assert_eq!(mismatch.unmatched_ds(), &[tag(&removed)]);
+    assert_eq!(mismatch.unsigned_keys(), &[tag(&ksk)]);
+
+    // a stale DS alongside a valid one
+    let mismatch =
+        compare_ds(&zone(), &[ksk.clone()], &[ds(&ksk), ds(&removed)]).expect("no mismatch");
+    assert!(!mismatch.is_broken());
+    assert_eq!(mismatch.unmatched_ds(), &[tag(&removed)]);
+    assert!(mismatch.unsigned_keys().is_empty());
+}
+
+#[test]
+fn test_unsigned_keys() {
+    let ksk = key(true, b"key signing key");
+    let new_ksk = key(true, b"new key signing key");
+
+    let mismatch =
+        compare_ds(&zone(), &[ksk.clone(), new_ksk.clone()], &[ds(&ksk)]).expect("no mismatch");
+    assert!(!mismatch.is_broken());
+    assert!(mismatch.unmatched_ds().is_empty());
+    assert_eq!(mismatch.unsigned_keys(), &[tag(&new_ksk)]);
+
+    // an insecure delegation of a signed zone
+    let mismatch = compare_ds(&zone(), &[ksk.clone()], &[]).expect("no mismatch");
+    assert!(!mismatch.is_broken());
+    assert_eq!(mismatch.unsigned_keys(), &[tag(&ksk)]);
+}
+
+#[test]
+fn test_checker_notifies_changes() {
+    let ksk = key(true, b"key signing key");
+    let removed = key(true, b"removed key signing key");
+
+    let changes: Arc<Mutex<Vec<Option<DsMismatch>>>> = Arc::new(Mutex::new(vec![]));
+    let checker = DsChecker::new(zone());
+    let sink_changes = Arc::clone(&changes);
+    checker.add_sink(Box::new(move |_: &Name, mismatch: Option<&DsMismatch>| {
+        sink_changes.lock().unwrap().push(mismatch.cloned())
+    }));
+
+    assert!(checker.check(&[ksk.clone()], &[ds(&ksk)]).is_none());
+    assert!(checker.check(&[ksk.clone()], &[ds(&removed)]).is_some());
+    assert!(checker.check(&[ksk.clone()], &[ds(&removed)]).is_some());
+    checker.failed(&"timed out");
+    assert!(checker.check(&[ksk.clone()], &[ds(&ksk)]).is_none());
+
+    // only the changes are notified
+    let changes = changes.lock().unwrap();
+    assert_eq!(changes.len(), 2);
+    assert!(changes[0].as_ref().expect("no mismatch").is_broken());
+    assert!(changes[1].is_none());
+
+    let metrics = checker.metrics();
+    assert_eq!(metrics.checks, 4);
+    assert_eq!(metrics.mismatches, 2);
+    assert_eq!(metrics.failures, 1);
+    assert!(checker.mismatch().is_none());
+}
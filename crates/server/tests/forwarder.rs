@@ -2,17 +2,32 @@
 #![cfg(feature = "trust-dns-resolver")]
 
 extern crate futures;
+extern crate tokio;
 extern crate trust_dns;
+extern crate trust_dns_resolver;
 extern crate trust_dns_server;
 
-use std::net::Ipv4Addr;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, UdpSocket};
 use std::str::FromStr;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use futures::future::Future;
+use futures::future::{self, Future};
+use tokio::runtime::current_thread::Runtime as CurrentThreadRuntime;
+use tokio::runtime::Runtime;
 
-use trust_dns::rr::{Name, RecordType};
-use trust_dns_server::authority::{Authority, LookupObject};
-use trust_dns_server::store::forwarder::ForwardAuthority;
+use trust_dns::op::{LowerQuery, Message, MessageType, Query, ResponseCode};
+use trust_dns::rr::rdata::opt::{ClientSubnet, EdnsCode, EdnsOption};
+use trust_dns::rr::rdata::SOA;
+use trust_dns::rr::{Name, RData, Record, RecordType};
+use trust_dns::serialize::binary::BinDecodable;
+use trust_dns_resolver::config::{NameServerConfigGroup, ResolverOpts};
+use trust_dns_server::authority::{Authority, Catalog, LookupObject, ZoneType};
+use trust_dns_server::store::forwarder::{
+    ClientSubnetConfig, ForwardAuthority, ForwardConfig, UpstreamCheckConfig,
+};
+use trust_dns_server::ServerFuture;
 
 #[ignore]
 #[test]
@@ -33,3 +48,431 @@ fn test_lookup() {
     let address = address.rdata().as_a().expect("not an A record");
     assert_eq!(*address, Ipv4Addr::new(93, 184, 216, 34));
 }
+
+#[test]
+fn test_upstream_unreachable() {
+    // the upstream never answers
+    let upstream = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = upstream.local_addr().unwrap().port();
+
+    let config = ForwardConfig {
+        name_servers: NameServerConfigGroup::from_ips_clear(
+            &[IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1))],
+            port,
+        ),
+        options: None,
+        ttl_overrides: None,
+        validation_exceptions: None,
+        health_check: Some(UpstreamCheckConfig {
+            interval: Some(1),
+            timeout: Some(1),
+            rise: None,
+            fall: Some(1),
+        }),
+        client_subnet: None,
+    };
+
+    let (forwarder, bg) =
+        ForwardAuthority::try_from_config(Name::root(), ZoneType::Forward, &config).unwrap();
+    let upstream_health = forwarder
+        .upstream_health()
+        .expect("upstream not checked")
+        .clone();
+    assert!(upstream_health.is_healthy());
+
+    let mut io_loop = Runtime::new().unwrap();
+    io_loop.spawn(bg);
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while upstream_health.is_healthy() {
+        assert!(Instant::now() < deadline, "upstream still reachable");
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+/// Answers the A queries to the returned port, with the name of the question as is or lowercased,
+///  sends the names of the questions received
+fn case_upstream(normalize: bool) -> (u16, mpsc::Receiver<Name>) {
+    let upstream = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = upstream.local_addr().unwrap().port();
+    let (names, received) = mpsc::channel();
+
+    thread::spawn(move || {
+        let mut buf = [0_u8; 512];
+        loop {
+            let (len, src) = upstream.recv_from(&mut buf).unwrap();
+            let request = Message::from_bytes(&buf[..len]).unwrap();
+            let query = request.queries()[0].clone();
+            if names.send(query.name().clone()).is_err() {
+                return;
+            }
+
+            let name = if normalize {
+                query.name().to_lowercase()
+            } else {
+                query.name().clone()
+            };
+            let mut response = Message::new();
+            response
+                .set_id(request.id())
+                .set_message_type(MessageType::Response)
+                .set_recursion_desired(true)
+                .set_recursion_available(true)
+                .add_query(Query::query(name.clone(), query.query_type()))
+                .add_answer(Record::from_rdata(
+                    name,
+                    86400,
+                    RData::A(Ipv4Addr::new(127, 0, 0, 1)),
+                ));
+            upstream.send_to(&response.to_vec().unwrap(), src).unwrap();
+        }
+    });
+
+    (port, received)
+}
+
+fn case_randomization_forwarder(port: u16) -> (ForwardAuthority, CurrentThreadRuntime) {
+    let mut options = ResolverOpts::default();
+    options.case_randomization = true;
+    options.cache_size = 0;
+
+    let config = ForwardConfig {
+        name_servers: NameServerConfigGroup::from_ips_clear(
+            &[IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1))],
+            port,
+        ),
+        options: Some(options),
+        ttl_overrides: None,
+        validation_exceptions: None,
+        health_check: None,
+        client_subnet: None,
+    };
+
+    let (forwarder, bg) =
+        ForwardAuthority::try_from_config(Name::root(), ZoneType::Forward, &config).unwrap();
+    let mut io_loop = CurrentThreadRuntime::new().unwrap();
+    io_loop.spawn(bg);
+
+    (forwarder, io_loop)
+}
+
+// long enough that a randomized case is never all lower case
+const CASE_NAME: &str = "a-name-long-enough-for-case-randomization.example.com.";
+
+#[test]
+fn test_case_randomization() {
+    let (port, received) = case_upstream(false);
+    let (forwarder, mut io_loop) = case_randomization_forwarder(port);
+    let name = Name::from_str(CASE_NAME).unwrap();
+
+    for _ in 0..2 {
+        let lookup = io_loop
+            .block_on(forwarder.lookup(
+                &name.clone().into(),
+                RecordType::A,
+                false,
+                Default::default(),
+            ))
+            .unwrap();
+
+        // the answers have the case of the query
+        let record = lookup.iter().next().expect("no answer");
+        assert!(record.name().eq_case(&name));
+
+        // a single query, with a randomized case
+        let sent = received.recv().unwrap();
+        assert_eq!(sent, name);
+        assert!(!sent.eq_case(&name));
+        assert!(received.try_recv().is_err());
+    }
+}
+
+#[test]
+fn test_case_randomization_normalized() {
+    let (port, received) = case_upstream(true);
+    let (forwarder, mut io_loop) = case_randomization_forwarder(port);
+    let name = Name::from_str(CASE_NAME).unwrap();
+
+    let lookup = io_loop
+        .block_on(forwarder.lookup(
+            &name.clone().into(),
+            RecordType::A,
+            false,
+            Default::default(),
+        ))
+        .unwrap();
+    assert!(lookup
+        .iter()
+        .next()
+        .expect("no answer")
+        .name()
+        .eq_case(&name));
+
+    // the query is sent again with another case, which isn't echoed either
+    assert!(!received.recv().unwrap().eq_case(&name));
+    assert!(!received.recv().unwrap().eq_case(&name));
+    assert!(received.try_recv().is_err());
+
+    // the case is not randomized anymore
+    io_loop
+        .block_on(forwarder.lookup(
+            &name.clone().into(),
+            RecordType::A,
+            false,
+            Default::default(),
+        ))
+        .unwrap();
+    assert!(received.recv().unwrap().eq_case(&name));
+    assert!(received.try_recv().is_err());
+}
+
+/// Answers the A queries to the returned port, sends the client subnets of the queries received
+fn client_subnet_upstream() -> (u16, mpsc::Receiver<Option<ClientSubnet>>) {
+    let upstream = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = upstream.local_addr().unwrap().port();
+    let (subnets, received) = mpsc::channel();
+
+    thread::spawn(move || {
+        let mut buf = [0_u8; 512];
+        loop {
+            let (len, src) = upstream.recv_from(&mut buf).unwrap();
+            let request = Message::from_bytes(&buf[..len]).unwrap();
+            let subnet = match request
+                .edns()
+                .and_then(|edns| edns.option(EdnsCode::Subnet))
+            {
+                Some(&EdnsOption::Subnet(subnet)) => Some(subnet),
+                _ => None,
+            };
+            if subnets.send(subnet).is_err() {
+                return;
+            }
+
+            let query = request.queries()[0].clone();
+            let mut response = Message::new();
+            response
+                .set_id(request.id())
+                .set_message_type(MessageType::Response)
+                .set_recursion_desired(true)
+                .set_recursion_available(true)
+                .add_query(query.clone())
+                .add_answer(Record::from_rdata(
+                    query.name().clone(),
+                    86400,
+                    RData::A(Ipv4Addr::new(127, 0, 0, 1)),
+                ));
+            upstream.send_to(&response.to_vec().unwrap(), src).unwrap();
+        }
+    });
+
+    (port, received)
+}
+
+fn client_subnet_forwarder(
+    port: u16,
+    client_subnet: Option<ClientSubnetConfig>,
+) -> (ForwardAuthority, CurrentThreadRuntime) {
+    let mut options = ResolverOpts::default();
+    options.cache_size = 0;
+
+    let config = ForwardConfig {
+        name_servers: NameServerConfigGroup::from_ips_clear(
+            &[IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1))],
+            port,
+        ),
+        options: Some(options),
+        ttl_overrides: None,
+        validation_exceptions: None,
+        health_check: None,
+        client_subnet,
+    };
+
+    let (forwarder, bg) =
+        ForwardAuthority::try_from_config(Name::root(), ZoneType::Forward, &config).unwrap();
+    let mut io_loop = CurrentThreadRuntime::new().unwrap();
+    io_loop.spawn(bg);
+
+    (forwarder, io_loop)
+}
+
+#[test]
+fn test_client_subnet_truncated() {
+    let (port, received) = client_subnet_upstream();
+    let config = ClientSubnetConfig {
+        ipv4_prefix: None,
+        ipv6_prefix: None,
+    };
+    let (forwarder, mut io_loop) = client_subnet_forwarder(port, Some(config));
+    let query = LowerQuery::from(Query::query(
+        Name::from_str("www.example.com.").unwrap(),
+        RecordType::A,
+    ));
+
+    let clients = vec![
+        (
+            ClientSubnet::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 77)), 32, 0).unwrap(),
+            ClientSubnet::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 0)), 24, 0).unwrap(),
+        ),
+        (
+            ClientSubnet::new(
+                IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 1, 2, 3, 0, 0, 1)),
+                128,
+                0,
+            )
+            .unwrap(),
+            ClientSubnet::new(
+                IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 0)),
+                56,
+                0,
+            )
+            .unwrap(),
+        ),
+        // shorter prefixes of the clients are kept
+        (
+            ClientSubnet::new(IpAddr::V4(Ipv4Addr::new(198, 51, 0, 0)), 16, 0).unwrap(),
+            ClientSubnet::new(IpAddr::V4(Ipv4Addr::new(198, 51, 0, 0)), 16, 0).unwrap(),
+        ),
+    ];
+
+    for (client, sent) in clients {
+        io_loop
+            .block_on(forwarder.search_with_client(&query, false, Default::default(), Some(client)))
+            .unwrap();
+        assert_eq!(received.recv().unwrap(), Some(sent));
+    }
+}
+
+#[test]
+fn test_client_subnet_stripped() {
+    let (port, received) = client_subnet_upstream();
+    let (forwarder, mut io_loop) = client_subnet_forwarder(port, None);
+    let query = LowerQuery::from(Query::query(
+        Name::from_str("www.example.com.").unwrap(),
+        RecordType::A,
+    ));
+    let client = ClientSubnet::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 77)), 32, 0).unwrap();
+
+    io_loop
+        .block_on(forwarder.search_with_client(&query, false, Default::default(), Some(client)))
+        .unwrap();
+    assert_eq!(received.recv().unwrap(), None);
+}
+
+/// Answers the queries to the returned port without records, with the response code and the SOA
+///  of example.com. in the authority section
+fn negative_upstream(response_code: ResponseCode) -> u16 {
+    let upstream = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = upstream.local_addr().unwrap().port();
+
+    thread::spawn(move || {
+        let mut buf = [0_u8; 512];
+        loop {
+            let (len, src) = upstream.recv_from(&mut buf).unwrap();
+            let request = Message::from_bytes(&buf[..len]).unwrap();
+
+            let mut response = Message::new();
+            response
+                .set_id(request.id())
+                .set_message_type(MessageType::Response)
+                .set_response_code(response_code)
+                .set_recursion_desired(true)
+                .set_recursion_available(true)
+                .add_query(request.queries()[0].clone())
+                .add_name_server(negative_soa());
+            upstream.send_to(&response.to_vec().unwrap(), src).unwrap();
+        }
+    });
+
+    port
+}
+
+fn negative_soa() -> Record {
+    let origin = Name::from_str("example.com.").unwrap();
+    Record::from_rdata(
+        origin.clone(),
+        3600,
+        RData::SOA(SOA::new(
+            Name::from_str("ns.example.com.").unwrap(),
+            Name::from_str("root.example.com.").unwrap(),
+            1,
+            7200,
+            600,
+            86400,
+            300,
+        )),
+    )
+}
+
+/// Serves a catalog forwarding to the upstream, and sends it the query of the name
+fn forwarded_response(upstream_port: u16, name: &str) -> Message {
+    let mut options = ResolverOpts::default();
+    options.cache_size = 0;
+
+    let config = ForwardConfig {
+        name_servers: NameServerConfigGroup::from_ips_clear(
+            &[IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1))],
+            upstream_port,
+        ),
+        options: Some(options),
+        ttl_overrides: None,
+        validation_exceptions: None,
+        health_check: None,
+        client_subnet: None,
+    };
+
+    let (forwarder, bg) =
+        ForwardAuthority::try_from_config(Name::root(), ZoneType::Forward, &config).unwrap();
+    let mut catalog = Catalog::new();
+    catalog.upsert(Name::root().into(), Box::new(forwarder));
+
+    let mut io_loop = Runtime::new().unwrap();
+    io_loop.spawn(bg);
+    let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let server_addr = server.local_addr().unwrap();
+    io_loop
+        .block_on(future::lazy(move || {
+            ServerFuture::new(catalog).register_socket_std(server);
+            future::ok::<(), ()>(())
+        }))
+        .unwrap();
+
+    let mut request = Message::new();
+    request
+        .set_id(1234)
+        .set_recursion_desired(true)
+        .add_query(Query::query(Name::from_str(name).unwrap(), RecordType::A));
+
+    let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+    client
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    client
+        .send_to(&request.to_vec().unwrap(), server_addr)
+        .unwrap();
+
+    let mut buf = [0_u8; 512];
+    let len = client.recv(&mut buf).expect("no response");
+    Message::from_bytes(&buf[..len]).unwrap()
+}
+
+#[test]
+fn test_forward_nx_domain() {
+    let port = negative_upstream(ResponseCode::NXDomain);
+    let response = forwarded_response(port, "nx.example.com.");
+
+    assert_eq!(response.id(), 1234);
+    assert_eq!(response.response_code(), ResponseCode::NXDomain);
+    assert!(response.answers().is_empty());
+    assert_eq!(response.name_servers(), &[negative_soa()]);
+}
+
+#[test]
+fn test_forward_no_data() {
+    let port = negative_upstream(ResponseCode::NoError);
+    let response = forwarded_response(port, "www.example.com.");
+
+    assert_eq!(response.id(), 1234);
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert!(response.answers().is_empty());
+    assert_eq!(response.name_servers(), &[negative_soa()]);
+}
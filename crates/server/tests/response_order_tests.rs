@@ -0,0 +1,114 @@
+extern crate futures;
+extern crate trust_dns;
+extern crate trust_dns_server;
+
+use std::net::Ipv4Addr;
+use std::str::FromStr;
+
+use futures::Future;
+
+use trust_dns::rr::rdata::SOA;
+use trust_dns::rr::{LowerName, Name, RData, Record, RecordType};
+use trust_dns_server::authority::{Authority, ResponseOrder, ZoneType};
+use trust_dns_server::store::in_memory::InMemoryAuthority;
+
+fn create_authority(response_order: ResponseOrder) -> InMemoryAuthority {
+    let origin = Name::from_str("example.com.").unwrap();
+    let mut authority = InMemoryAuthority::empty(origin.clone(), ZoneType::Master, false);
+    authority.set_response_order(response_order);
+
+    authority.upsert(
+        Record::from_rdata(
+            origin.clone(),
+            3600,
+            RData::SOA(SOA::new(
+                Name::from_str("ns.example.com.").unwrap(),
+                Name::from_str("root.example.com.").unwrap(),
+                1,
+                7200,
+                3600,
+                1_209_600,
+                3600,
+            )),
+        ),
+        0,
+    );
+
+    let www = Name::from_str("www.example.com.").unwrap();
+    for i in 1..=3 {
+        authority.upsert(
+            Record::from_rdata(www.clone(), 86400, RData::A(Ipv4Addr::new(127, 0, 0, i))),
+            0,
+        );
+    }
+
+    authority.upsert(
+        Record::from_rdata(
+            www.clone(),
+            86400,
+            RData::TXT(trust_dns::rr::rdata::TXT::new(vec!["one".to_string()])),
+        ),
+        0,
+    );
+
+    authority
+}
+
+fn lookup_addresses(authority: &InMemoryAuthority) -> Vec<Ipv4Addr> {
+    let lookup = authority
+        .lookup(
+            &LowerName::from_str("www.example.com.").unwrap(),
+            RecordType::A,
+            false,
+            Default::default(),
+        )
+        .wait()
+        .expect("lookup failed");
+
+    lookup
+        .iter()
+        .map(|r| *r.rdata().as_a().expect("not an A record"))
+        .collect()
+}
+
+#[test]
+fn test_fixed_order() {
+    let authority = create_authority(ResponseOrder::Fixed);
+
+    let first = lookup_addresses(&authority);
+    assert_eq!(first.len(), 3);
+    assert_eq!(first, lookup_addresses(&authority));
+}
+
+#[test]
+fn test_round_robin_order() {
+    let authority = create_authority(ResponseOrder::RoundRobin);
+
+    let first = lookup_addresses(&authority);
+    let second = lookup_addresses(&authority);
+    let third = lookup_addresses(&authority);
+    let fourth = lookup_addresses(&authority);
+
+    assert_eq!(first.len(), 3);
+    assert_eq!(second[..], [first[1], first[2], first[0]]);
+    assert_eq!(third[..], [first[2], first[0], first[1]]);
+    assert_eq!(fourth, first);
+}
+
+#[test]
+fn test_random_order() {
+    let authority = create_authority(ResponseOrder::Random);
+
+    let fixed = lookup_addresses(&create_authority(ResponseOrder::Fixed));
+    for _ in 0..16 {
+        let mut addresses = lookup_addresses(&authority);
+        assert_eq!(addresses.len(), 3);
+
+        addresses.sort();
+        assert_eq!(addresses, {
+            let mut fixed = fixed.clone();
+            fixed.sort();
+            fixed
+        });
+    }
+}
@@ -0,0 +1,155 @@
+#![cfg(feature = "trust-dns-resolver")]
+
+extern crate futures;
+extern crate tokio;
+extern crate trust_dns;
+extern crate trust_dns_resolver;
+extern crate trust_dns_server;
+
+use std::net::{IpAddr, Ipv4Addr, UdpSocket};
+use std::path::Path;
+use std::str::FromStr;
+use std::thread;
+
+use tokio::runtime::current_thread::Runtime;
+
+use trust_dns::op::{Message, MessageType, Query};
+use trust_dns::rr::{LowerName, Name, RData, Record, RecordType};
+use trust_dns::serialize::binary::BinDecodable;
+use trust_dns_resolver::config::{NameServerConfigGroup, ResolverOpts};
+use trust_dns_server::authority::{AuthLookup, Authority, LookupError, ZoneType};
+use trust_dns_server::store::aname::{AnameAuthority, AnameConfig};
+use trust_dns_server::store::forwarder::ForwardConfig;
+
+/// An upstream with the A record of www.example.org, and no AAAA records
+fn upstream() -> u16 {
+    let upstream = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = upstream.local_addr().unwrap().port();
+
+    thread::spawn(move || {
+        let mut buf = [0_u8; 512];
+        loop {
+            let (len, src) = upstream.recv_from(&mut buf).unwrap();
+            let request = Message::from_bytes(&buf[..len]).unwrap();
+            let query = request.queries()[0].clone();
+
+            let mut response = Message::new();
+            response
+                .set_id(request.id())
+                .set_message_type(MessageType::Response)
+                .set_recursion_desired(true)
+                .set_recursion_available(true)
+                .add_query(Query::query(query.name().clone(), query.query_type()));
+            if query.query_type() == RecordType::A {
+                response.add_answer(Record::from_rdata(
+                    query.name().clone(),
+                    60,
+                    RData::A(Ipv4Addr::new(192, 0, 2, 1)),
+                ));
+            }
+            upstream.send_to(&response.to_vec().unwrap(), src).unwrap();
+        }
+    });
+
+    port
+}
+
+fn aname_authority() -> (AnameAuthority, Runtime) {
+    let mut options = ResolverOpts::default();
+    options.cache_size = 0;
+
+    let config = AnameConfig {
+        zone_file_path: "example.net.zone".to_string(),
+        forward: ForwardConfig {
+            name_servers: NameServerConfigGroup::from_ips_clear(
+                &[IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1))],
+                upstream(),
+            ),
+            options: Some(options),
+            ttl_overrides: None,
+            validation_exceptions: None,
+            health_check: None,
+            client_subnet: None,
+        },
+    };
+
+    let (authority, bg) = AnameAuthority::try_from_config(
+        Name::from_str("example.net.").unwrap(),
+        ZoneType::Master,
+        false,
+        Some(Path::new("tests/named_test_configs")),
+        &config,
+    )
+    .expect("failed to load ANAME zone");
+
+    let mut io_loop = Runtime::new().unwrap();
+    io_loop.spawn(bg);
+    (authority, io_loop)
+}
+
+fn aname_lookup(
+    authority: &AnameAuthority,
+    io_loop: &mut Runtime,
+    name: &str,
+    rtype: RecordType,
+) -> Result<AuthLookup, LookupError> {
+    io_loop.block_on(authority.lookup(
+        &LowerName::from_str(name).unwrap(),
+        rtype,
+        false,
+        Default::default(),
+    ))
+}
+
+#[test]
+fn test_apex_flattened() {
+    let (authority, mut io_loop) = aname_authority();
+    let apex = Name::from_str("example.net.").unwrap();
+
+    let mut lookup = aname_lookup(&authority, &mut io_loop, "example.net.", RecordType::A)
+        .expect("lookup failed");
+    let answers = lookup.iter().collect::<Vec<_>>();
+    assert_eq!(answers.len(), 1);
+    assert_eq!(*answers[0].name(), apex);
+    assert_eq!(answers[0].rdata(), &RData::A(Ipv4Addr::new(192, 0, 2, 1)));
+    // the lowest of the ANAME and of the upstream TTL
+    assert_eq!(answers[0].ttl(), 60);
+
+    let additionals = lookup.take_additionals().expect("no additionals");
+    let additionals = additionals.iter().collect::<Vec<_>>();
+    assert_eq!(additionals.len(), 1);
+    assert_eq!(
+        additionals[0].rdata(),
+        &RData::ANAME(Name::from_str("www.example.org.").unwrap())
+    );
+
+    // the target has no AAAA records
+    let error = aname_lookup(&authority, &mut io_loop, "example.net.", RecordType::AAAA)
+        .expect_err("expected no records");
+    assert!(error.is_name_exists());
+
+    // other types are answered from the zone
+    let lookup = aname_lookup(&authority, &mut io_loop, "example.net.", RecordType::NS)
+        .expect("lookup failed");
+    assert_eq!(lookup.iter().count(), 1);
+}
+
+#[test]
+fn test_target_in_zone() {
+    let (authority, mut io_loop) = aname_authority();
+
+    let lookup = aname_lookup(
+        &authority,
+        &mut io_loop,
+        "local.example.net.",
+        RecordType::A,
+    )
+    .expect("lookup failed");
+    let answers = lookup.iter().collect::<Vec<_>>();
+    assert_eq!(answers.len(), 1);
+    assert_eq!(
+        *answers[0].name(),
+        Name::from_str("local.example.net.").unwrap()
+    );
+    assert_eq!(answers[0].rdata(), &RData::A(Ipv4Addr::new(127, 0, 0, 2)));
+}
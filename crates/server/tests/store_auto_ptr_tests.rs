@@ -0,0 +1,126 @@
+extern crate futures;
+extern crate tokio;
+extern crate trust_dns;
+extern crate trust_dns_server;
+
+use std::net::Ipv4Addr;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use futures::Future;
+use tokio::runtime::Runtime;
+
+use trust_dns::rr::rdata::SOA;
+use trust_dns::rr::{LowerName, Name, RData, Record, RecordType};
+use trust_dns_server::authority::{Authority, Catalog, ZoneType};
+use trust_dns_server::store::auto_ptr::AutoPtrAuthority;
+use trust_dns_server::store::in_memory::InMemoryAuthority;
+
+fn zone(origin: &str, records: Vec<Record>) -> InMemoryAuthority {
+    let origin = Name::from_str(origin).unwrap();
+    let mut authority = InMemoryAuthority::empty(origin.clone(), ZoneType::Master, false);
+
+    authority.upsert(
+        Record::from_rdata(
+            origin.clone(),
+            3600,
+            RData::SOA(SOA::new(
+                Name::from_str("ns.example.com.").unwrap(),
+                Name::from_str("root.example.com.").unwrap(),
+                1,
+                7200,
+                3600,
+                1_209_600,
+                3600,
+            )),
+        ),
+        1,
+    );
+    for record in records {
+        authority.upsert(record, 1);
+    }
+
+    authority
+}
+
+fn a(name: &str, ttl: u32, address: Ipv4Addr) -> Record {
+    Record::from_rdata(Name::from_str(name).unwrap(), ttl, RData::A(address))
+}
+
+fn ptrs(authority: &AutoPtrAuthority, name: &str) -> Vec<(Name, u32)> {
+    authority
+        .lookup(
+            &LowerName::from_str(name).unwrap(),
+            RecordType::PTR,
+            false,
+            Default::default(),
+        )
+        .wait()
+        .map(|lookup| {
+            lookup
+                .iter()
+                .map(|record| (record.rdata().as_ptr().unwrap().clone(), record.ttl()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn wait_for<F: Fn() -> bool>(condition: F) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !condition() {
+        assert!(Instant::now() < deadline, "PTR records not generated");
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+#[test]
+fn test_ptrs_follow_forward_zone() {
+    let catalog = Catalog::new();
+    catalog.swap_zone(
+        LowerName::from_str("example.com.").unwrap(),
+        Box::new(zone(
+            "example.com.",
+            vec![
+                a("www.example.com.", 300, Ipv4Addr::new(192, 168, 0, 10)),
+                a("*.example.com.", 300, Ipv4Addr::new(192, 168, 0, 11)),
+                a("out.example.com.", 300, Ipv4Addr::new(10, 0, 0, 1)),
+            ],
+        )),
+    );
+
+    let (authority, bg) = AutoPtrAuthority::new(
+        zone("0.168.192.in-addr.arpa.", vec![]),
+        catalog.clone(),
+        vec![LowerName::from_str("example.com.").unwrap()],
+        Duration::from_millis(100),
+    );
+    let mut io_loop = Runtime::new().unwrap();
+    io_loop.spawn(bg);
+
+    wait_for(|| !ptrs(&authority, "10.0.168.192.in-addr.arpa.").is_empty());
+    assert_eq!(
+        ptrs(&authority, "10.0.168.192.in-addr.arpa."),
+        vec![(Name::from_str("www.example.com.").unwrap(), 300)]
+    );
+    // wildcards don't get PTR records
+    assert!(ptrs(&authority, "11.0.168.192.in-addr.arpa.").is_empty());
+    let serial = authority.serial();
+    assert!(serial > 1);
+
+    // the forward zone is replaced, with another address
+    catalog.swap_zone(
+        LowerName::from_str("example.com.").unwrap(),
+        Box::new(zone(
+            "example.com.",
+            vec![a("www.example.com.", 60, Ipv4Addr::new(192, 168, 0, 20))],
+        )),
+    );
+
+    wait_for(|| ptrs(&authority, "10.0.168.192.in-addr.arpa.").is_empty());
+    assert_eq!(
+        ptrs(&authority, "20.0.168.192.in-addr.arpa."),
+        vec![(Name::from_str("www.example.com.").unwrap(), 60)]
+    );
+    assert!(authority.serial() > serial);
+}
@@ -0,0 +1,236 @@
+#![cfg(feature = "trust-dns-resolver")]
+
+extern crate futures;
+extern crate tokio;
+extern crate trust_dns;
+extern crate trust_dns_server;
+
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, Ipv6Addr, TcpListener};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use futures::Future;
+use tokio::runtime::Runtime;
+
+use trust_dns::op::ResponseCode;
+use trust_dns::rr::{LowerName, Name, RecordType};
+use trust_dns_server::authority::{Authority, LookupError, LookupObject, ZoneType};
+use trust_dns_server::store::blocklist::{BlockAction, BlocklistAuthority, BlocklistConfig};
+use trust_dns_server::store::forwarder::ForwardConfig;
+
+fn config(block_lists: Vec<String>, block_action: BlockAction) -> BlocklistConfig {
+    BlocklistConfig {
+        block_lists,
+        allow_lists: vec![],
+        block_action,
+        ttl: None,
+        refresh_interval: None,
+        forward: ForwardConfig {
+            name_servers: From::from(vec![]),
+            options: None,
+            ttl_overrides: None,
+            validation_exceptions: None,
+            health_check: None,
+            client_subnet: None,
+        },
+    }
+}
+
+fn blocklist(block_action: BlockAction) -> BlocklistAuthority {
+    let config = config(vec!["blocklist.hosts".to_string()], block_action);
+
+    BlocklistAuthority::try_from_config(
+        Name::root(),
+        ZoneType::Forward,
+        Some(Path::new("tests/named_test_configs")),
+        &config,
+    )
+    .expect("failed to load blocklist")
+    .0
+}
+
+#[test]
+fn test_blocked_nxdomain() {
+    let authority = blocklist(BlockAction::NxDomain);
+
+    let name = LowerName::from_str("ads.example.com.").unwrap();
+    assert!(authority.is_blocked(&name));
+
+    let result = authority
+        .lookup(&name, RecordType::A, false, Default::default())
+        .wait();
+
+    match result {
+        Err(LookupError::ResponseCode(ResponseCode::NXDomain)) => (),
+        Err(e) => panic!("wrong error: {}", e),
+        Ok(_) => panic!("blocked name was resolved"),
+    }
+}
+
+#[test]
+fn test_blocked_address() {
+    let authority = blocklist(BlockAction::Address {
+        ipv4: Some(Ipv4Addr::new(127, 0, 0, 1)),
+        ipv6: None,
+    });
+
+    let name = LowerName::from_str("www.doubleclick.example.").unwrap();
+    let lookup = authority
+        .lookup(&name, RecordType::A, false, Default::default())
+        .wait()
+        .expect("lookup failed");
+
+    let record = lookup.iter().next().expect("no records returned");
+    assert_eq!(*record.rdata().as_a().unwrap(), Ipv4Addr::new(127, 0, 0, 1));
+
+    // no ipv6 address configured
+    let lookup = authority
+        .lookup(&name, RecordType::AAAA, false, Default::default())
+        .wait()
+        .expect("lookup failed");
+    assert!(lookup.is_empty());
+}
+
+#[test]
+fn test_blocked_unspecified() {
+    let authority = blocklist(BlockAction::Unspecified);
+
+    let name = LowerName::from_str("metrics.example.net.").unwrap();
+    let lookup = authority
+        .lookup(&name, RecordType::AAAA, false, Default::default())
+        .wait()
+        .expect("lookup failed");
+
+    let record = lookup.iter().next().expect("no records returned");
+    assert_eq!(*record.rdata().as_aaaa().unwrap(), Ipv6Addr::UNSPECIFIED);
+}
+
+#[test]
+fn test_not_blocked() {
+    let authority = blocklist(BlockAction::NxDomain);
+
+    assert!(!authority.is_blocked(&LowerName::from_str("www.example.com.").unwrap()));
+    assert!(!authority.is_blocked(&LowerName::from_str("doubleclick.example.").unwrap()));
+    assert!(!authority.is_blocked(&LowerName::from_str("localhost.").unwrap()));
+}
+
+/// Serves the responses in order, one per connection, each served request is sent to the channel
+fn serve_lists(responses: Vec<&'static str>) -> (String, mpsc::Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!(
+        "http://{}/lists/blocklist.hosts",
+        listener.local_addr().unwrap()
+    );
+
+    let (served, requests) = mpsc::channel();
+    thread::spawn(move || {
+        for response in responses {
+            let (mut stream, _) = listener.accept().unwrap();
+
+            let mut request = Vec::new();
+            let mut buf = [0u8; 512];
+            while !request.ends_with(b"\r\n\r\n") {
+                let len = stream.read(&mut buf).unwrap();
+                assert!(len > 0, "incomplete request");
+                request.extend_from_slice(&buf[..len]);
+            }
+
+            stream.write_all(response.as_bytes()).unwrap();
+            drop(stream);
+            served.send(String::from_utf8(request).unwrap()).unwrap();
+        }
+    });
+
+    (url, requests)
+}
+
+fn url_config(url: String, refresh_interval: Option<u64>) -> BlocklistConfig {
+    BlocklistConfig {
+        refresh_interval,
+        ..config(vec![url], BlockAction::NxDomain)
+    }
+}
+
+#[test]
+fn test_url_list() {
+    let (url, requests) = serve_lists(vec![
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n\r\n0.0.0.0 ads.example.org\n*.tracker.example.org\n",
+    ]);
+
+    let (authority, _) = BlocklistAuthority::try_from_config(
+        Name::root(),
+        ZoneType::Forward,
+        None,
+        &url_config(url, None),
+    )
+    .expect("failed to load blocklist");
+
+    let request = requests.recv().unwrap();
+    assert!(
+        request.starts_with("GET /lists/blocklist.hosts HTTP/1.0\r\n"),
+        "{}",
+        request
+    );
+    assert!(authority.is_blocked(&LowerName::from_str("ads.example.org.").unwrap()));
+    assert!(authority.is_blocked(&LowerName::from_str("www.tracker.example.org.").unwrap()));
+    assert!(!authority.is_blocked(&LowerName::from_str("www.example.org.").unwrap()));
+}
+
+#[test]
+fn test_url_list_failed() {
+    let (url, _requests) = serve_lists(vec!["HTTP/1.1 404 Not Found\r\n\r\n"]);
+
+    match BlocklistAuthority::try_from_config(
+        Name::root(),
+        ZoneType::Forward,
+        None,
+        &url_config(url, None),
+    ) {
+        Err(e) => assert!(e.contains("404"), "wrong error: {}", e),
+        Ok(_) => panic!("failed download was read as a list"),
+    }
+}
+
+#[test]
+fn test_url_list_refresh() {
+    let (url, requests) = serve_lists(vec![
+        "HTTP/1.1 200 OK\r\n\r\nads.example.org\n",
+        "HTTP/1.1 500 Internal Server Error\r\n\r\n",
+        "HTTP/1.1 200 OK\r\n\r\ntracker.example.org\n",
+    ]);
+
+    let (authority, background) = BlocklistAuthority::try_from_config(
+        Name::root(),
+        ZoneType::Forward,
+        None,
+        &url_config(url, Some(1)),
+    )
+    .expect("failed to load blocklist");
+    requests.recv().unwrap();
+
+    let mut runtime = Runtime::new().unwrap();
+    runtime.spawn(background);
+
+    let ads = LowerName::from_str("ads.example.org.").unwrap();
+    let tracker = LowerName::from_str("tracker.example.org.").unwrap();
+
+    // the failed refresh keeps the previous list
+    requests.recv_timeout(Duration::from_secs(5)).unwrap();
+    thread::sleep(Duration::from_millis(100));
+    assert!(authority.is_blocked(&ads));
+    assert!(!authority.is_blocked(&tracker));
+
+    requests.recv_timeout(Duration::from_secs(5)).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !authority.is_blocked(&tracker) {
+        assert!(Instant::now() < deadline, "list was not refreshed");
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(!authority.is_blocked(&ads));
+
+    drop(runtime);
+}
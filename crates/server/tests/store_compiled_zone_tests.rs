@@ -0,0 +1,93 @@
+extern crate futures;
+extern crate trust_dns;
+extern crate trust_dns_server;
+
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use trust_dns::rr::Name;
+use trust_dns_server::authority::{Authority, ZoneType};
+use trust_dns_server::store::file::{compile_zone, is_compiled_zone, FileAuthority, FileConfig};
+
+#[macro_use]
+mod authority_battery;
+
+fn load(zone_file_path: &str) -> FileAuthority {
+    let config = FileConfig {
+        zone_file_path: zone_file_path.to_string(),
+    };
+
+    FileAuthority::try_from_config(
+        Name::from_str("example.com.").unwrap(),
+        ZoneType::Master,
+        false,
+        None,
+        &config,
+    )
+    .expect("failed to load file")
+}
+
+fn compiled(master_file_path: &str, module: &str, test_name: &str) -> FileAuthority {
+    let compiled_path = PathBuf::from("target/tests")
+        .join(module)
+        .join(test_name)
+        .join("example.com.zone.compiled");
+    fs::create_dir_all(compiled_path.parent().unwrap()).ok();
+
+    let authority = load(master_file_path);
+    let compiled = compile_zone(&Name::from(authority.origin()), &authority.records())
+        .expect("failed to compile");
+    fs::write(&compiled_path, &compiled).expect("failed to write");
+
+    load(compiled_path.to_str().unwrap())
+}
+
+basic_battery!(compiled);
+#[cfg(feature = "dnssec")]
+dnssec_battery!(compiled);
+
+#[test]
+fn test_compiled_zone_has_all_records() {
+    let authority = load("tests/named_test_configs/example.com.zone");
+    let compiled = compile_zone(&Name::from(authority.origin()), &authority.records())
+        .expect("failed to compile");
+    assert!(is_compiled_zone(&compiled));
+
+    let compiled_authority = self::compiled(
+        "tests/named_test_configs/example.com.zone",
+        module_path!(),
+        "test_compiled_zone_has_all_records",
+    );
+    assert_eq!(compiled_authority.origin(), authority.origin());
+    assert_eq!(compiled_authority.records(), authority.records());
+}
+
+#[test]
+fn test_compiled_zone_other_origin() {
+    let compiled_path = PathBuf::from("target/tests")
+        .join(module_path!())
+        .join("test_compiled_zone_other_origin")
+        .join("example.com.zone.compiled");
+    fs::create_dir_all(compiled_path.parent().unwrap()).ok();
+
+    let authority = load("tests/named_test_configs/example.com.zone");
+    let compiled = compile_zone(&Name::from(authority.origin()), &authority.records())
+        .expect("failed to compile");
+    fs::write(&compiled_path, &compiled).expect("failed to write");
+
+    // the zone compiled for example.com. is not served as example.net.
+    let config = FileConfig {
+        zone_file_path: compiled_path.to_str().unwrap().to_string(),
+    };
+    let error = FileAuthority::try_from_config(
+        Name::from_str("example.net.").unwrap(),
+        ZoneType::Master,
+        false,
+        None,
+        &config,
+    )
+    .err()
+    .expect("the compiled zone was loaded for another origin");
+    assert!(error.contains("compiled for example.com."), "{}", error);
+}
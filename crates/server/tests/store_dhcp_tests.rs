@@ -0,0 +1,116 @@
+extern crate futures;
+extern crate tokio;
+extern crate trust_dns;
+extern crate trust_dns_server;
+
+use std::env;
+use std::fs;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use futures::Future;
+use tokio::runtime::Runtime;
+
+use trust_dns::rr::{LowerName, Name, RData, RecordType};
+use trust_dns_server::authority::{Authority, ZoneType};
+use trust_dns_server::store::dhcp::{DhcpAuthority, DhcpConfig, LeaseFormat};
+
+const LAPTOP: &str = "lease 192.168.1.10 {
+  ends never;
+  binding state active;
+  client-hostname \"Laptop\";
+}
+";
+
+const PHONE: &str = "lease 192.168.1.11 {
+  ends never;
+  binding state active;
+  client-hostname \"phone.home\";
+}
+";
+
+fn lease_file(name: &str, leases: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("trust-dns-{}-{}.leases", name, std::process::id()));
+    fs::write(&path, leases).expect("failed to write leases");
+    path
+}
+
+fn dhcp(origin: &str, path: &Path, io_loop: &mut Runtime) -> DhcpAuthority {
+    let config = DhcpConfig {
+        lease_file_path: path.to_str().unwrap().to_string(),
+        format: Some(LeaseFormat::Isc),
+        domain: Some(Name::from_str("lan.").unwrap()),
+        ttl: Some(30),
+        watch_interval: Some(1),
+    };
+
+    let (authority, bg) = DhcpAuthority::try_from_config(
+        Name::from_str(origin).unwrap(),
+        ZoneType::Master,
+        None,
+        &config,
+    )
+    .expect("failed to load leases");
+    io_loop.spawn(bg);
+
+    authority
+}
+
+fn lookup(authority: &DhcpAuthority, name: &str, rtype: RecordType) -> Vec<RData> {
+    authority
+        .lookup(
+            &LowerName::from_str(name).unwrap(),
+            rtype,
+            false,
+            Default::default(),
+        )
+        .wait()
+        .map(|lookup| lookup.iter().map(|r| r.rdata().clone()).collect())
+        .unwrap_or_default()
+}
+
+fn wait_for<F: Fn() -> bool>(condition: F) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !condition() {
+        assert!(Instant::now() < deadline, "leases not loaded");
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+#[test]
+fn test_leases_are_served_and_watched() {
+    let mut io_loop = Runtime::new().unwrap();
+    let path = lease_file("watched", LAPTOP);
+
+    let forward = dhcp("lan.", &path, &mut io_loop);
+    let reverse = dhcp("1.168.192.in-addr.arpa.", &path, &mut io_loop);
+
+    wait_for(|| !lookup(&forward, "laptop.lan.", RecordType::A).is_empty());
+    assert_eq!(
+        lookup(&forward, "laptop.lan.", RecordType::A),
+        vec![RData::A(Ipv4Addr::new(192, 168, 1, 10))]
+    );
+    // the records are only served in their zone
+    assert!(lookup(&forward, "10.1.168.192.in-addr.arpa.", RecordType::PTR).is_empty());
+
+    wait_for(|| !lookup(&reverse, "10.1.168.192.in-addr.arpa.", RecordType::PTR).is_empty());
+    assert_eq!(
+        lookup(&reverse, "10.1.168.192.in-addr.arpa.", RecordType::PTR),
+        vec![RData::PTR(Name::from_str("laptop.lan.").unwrap())]
+    );
+    let serial = forward.serial();
+
+    // a new lease is picked up from the modified file
+    fs::write(&path, format!("{}{}", LAPTOP, PHONE)).expect("failed to write leases");
+    wait_for(|| !lookup(&forward, "phone.lan.", RecordType::A).is_empty());
+    assert_eq!(
+        lookup(&forward, "phone.lan.", RecordType::A),
+        vec![RData::A(Ipv4Addr::new(192, 168, 1, 11))]
+    );
+    assert!(forward.serial() > serial);
+
+    fs::remove_file(&path).expect("failed to remove leases");
+}
@@ -51,3 +51,44 @@ fn test_all_lines_are_loaded() {
     };
     assert!(authority.records().get(&rrkey).is_some())
 }
+
+#[cfg(feature = "dnssec-openssl")]
+#[test]
+fn test_signature_jitter() {
+    use std::time::Duration;
+
+    use trust_dns::rr::rdata::DNSSECRData;
+    use trust_dns::rr::RData;
+    use trust_dns_server::authority::Authority;
+
+    let jitter = 86400;
+    let mut authority = file(
+        "tests/named_test_configs/example.com.zone",
+        module_path!(),
+        "test_signature_jitter",
+    );
+    authority
+        .set_signature_jitter(Duration::from_secs(jitter))
+        .expect("failed to set the jitter");
+    authority_battery::dnssec::add_signers(&mut authority);
+
+    // the signatures are valid for 52 weeks, less up to the jitter
+    let duration = 52 * 7 * 86400;
+    let validities = authority
+        .records()
+        .values()
+        .flat_map(|rrset| rrset.rrsigs())
+        .map(|rrsig| match rrsig.rdata() {
+            RData::DNSSEC(DNSSECRData::SIG(sig)) => {
+                u64::from(sig.sig_expiration() - sig.sig_inception())
+            }
+            rdata => panic!("not a signature: {:?}", rdata),
+        })
+        .collect::<Vec<_>>();
+
+    assert!(validities.len() > 10);
+    assert!(validities
+        .iter()
+        .all(|validity| *validity <= duration && *validity >= duration - jitter));
+    assert!(validities.iter().any(|validity| *validity != validities[0]));
+}
@@ -0,0 +1,360 @@
+extern crate futures;
+extern crate trust_dns;
+extern crate trust_dns_server;
+
+use std::net::Ipv4Addr;
+use std::str::FromStr;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+
+use futures::Future;
+
+use trust_dns::rr::dnssec::SupportedAlgorithms;
+use trust_dns::rr::rdata::{SOA, TXT};
+use trust_dns::rr::{LowerName, Name, RData, Record, RecordType, RrKey};
+use trust_dns::serialize::txt::{Lexer, Parser};
+use trust_dns_server::authority::{AuthLookup, Authority, LookupError, ZoneType};
+use trust_dns_server::store::in_memory::{InMemoryAuthority, ZoneDiff};
+
+fn create_authority() -> InMemoryAuthority {
+    let origin = Name::from_str("example.com.").unwrap();
+    let mut authority = InMemoryAuthority::empty(origin.clone(), ZoneType::Master, false);
+
+    authority.upsert(
+        Record::from_rdata(
+            origin.clone(),
+            3600,
+            RData::SOA(SOA::new(
+                Name::from_str("ns.example.com.").unwrap(),
+                Name::from_str("root.example.com.").unwrap(),
+                1,
+                7200,
+                3600,
+                1_209_600,
+                3600,
+            )),
+        ),
+        1,
+    );
+    authority.upsert(www(1), 1);
+
+    authority
+}
+
+fn www(host: u8) -> Record {
+    Record::from_rdata(
+        Name::from_str("www.example.com.").unwrap(),
+        86400,
+        RData::A(Ipv4Addr::new(127, 0, 0, host)),
+    )
+}
+
+fn lookup_addresses(authority: &InMemoryAuthority) -> Vec<Ipv4Addr> {
+    let lookup = authority
+        .lookup(
+            &LowerName::from_str("www.example.com.").unwrap(),
+            RecordType::A,
+            false,
+            SupportedAlgorithms::new(),
+        )
+        .wait()
+        .unwrap();
+
+    let mut addresses = lookup
+        .iter()
+        .map(|record| *record.rdata().as_a().unwrap())
+        .collect::<Vec<_>>();
+    addresses.sort();
+    addresses
+}
+
+#[test]
+fn test_update_is_published_on_commit() {
+    let authority = create_authority();
+
+    let mut update = authority.begin_update();
+    assert!(update.upsert(www(2), 1));
+    assert_eq!(update.increment_soa_serial(), 2);
+
+    assert_eq!(
+        lookup_addresses(&authority),
+        vec![Ipv4Addr::new(127, 0, 0, 1)]
+    );
+    assert_eq!(authority.serial(), 1);
+
+    update.commit();
+
+    assert_eq!(
+        lookup_addresses(&authority),
+        vec![Ipv4Addr::new(127, 0, 0, 1), Ipv4Addr::new(127, 0, 0, 2)]
+    );
+    assert_eq!(authority.serial(), 2);
+}
+
+#[test]
+fn test_dropped_update_is_discarded() {
+    let authority = create_authority();
+
+    {
+        let mut update = authority.begin_update();
+        update.upsert(www(2), 1);
+        update.records_mut().clear();
+    }
+
+    assert_eq!(
+        lookup_addresses(&authority),
+        vec![Ipv4Addr::new(127, 0, 0, 1)]
+    );
+    assert_eq!(authority.records().len(), 2);
+}
+
+#[test]
+fn test_lookups_do_not_wait_on_update() {
+    let authority = Arc::new(create_authority());
+    let (started_tx, started_rx) = mpsc::channel();
+    let (done_tx, done_rx) = mpsc::channel::<()>();
+
+    let updater = {
+        let authority = Arc::clone(&authority);
+        thread::spawn(move || {
+            let mut update = authority.begin_update();
+            update.upsert(www(2), 1);
+            started_tx.send(()).unwrap();
+
+            // the update stays in progress until the lookups are done
+            done_rx.recv().unwrap();
+            update.commit();
+        })
+    };
+
+    started_rx.recv().unwrap();
+    for _ in 0..10 {
+        assert_eq!(
+            lookup_addresses(&authority),
+            vec![Ipv4Addr::new(127, 0, 0, 1)]
+        );
+    }
+
+    done_tx.send(()).unwrap();
+    updater.join().unwrap();
+
+    // the next update starts from the committed records
+    let mut update = authority.begin_update();
+    update.upsert(www(3), 1);
+    update.commit();
+
+    assert_eq!(
+        lookup_addresses(&authority),
+        vec![
+            Ipv4Addr::new(127, 0, 0, 1),
+            Ipv4Addr::new(127, 0, 0, 2),
+            Ipv4Addr::new(127, 0, 0, 3),
+        ]
+    );
+}
+
+fn lookup(
+    authority: &InMemoryAuthority,
+    name: &str,
+    record_type: RecordType,
+) -> Result<AuthLookup, LookupError> {
+    authority
+        .lookup(
+            &LowerName::from_str(name).unwrap(),
+            record_type,
+            false,
+            SupportedAlgorithms::new(),
+        )
+        .wait()
+}
+
+fn create_wildcard_authority() -> InMemoryAuthority {
+    let mut authority = create_authority();
+    authority.upsert(
+        Record::from_rdata(
+            Name::from_str("*.wild.example.com.").unwrap(),
+            86400,
+            RData::A(Ipv4Addr::new(127, 0, 1, 1)),
+        ),
+        1,
+    );
+    authority.upsert(
+        Record::from_rdata(
+            Name::from_str("host.wild.example.com.").unwrap(),
+            86400,
+            RData::TXT(TXT::new(vec!["host".to_string()])),
+        ),
+        1,
+    );
+    authority.upsert(
+        Record::from_rdata(
+            Name::from_str("a.b.example.com.").unwrap(),
+            86400,
+            RData::A(Ipv4Addr::new(127, 0, 2, 1)),
+        ),
+        1,
+    );
+
+    authority
+}
+
+#[test]
+fn test_wildcard_of_closest_encloser() {
+    let authority = create_wildcard_authority();
+
+    for name in &["www.wild.example.com.", "a.b.wild.example.com."] {
+        let lookup = lookup(&authority, name, RecordType::A).expect("wildcard did not match");
+        let records = lookup.iter().collect::<Vec<_>>();
+
+        assert_eq!(records.len(), 1);
+        assert_eq!(*records[0].name(), Name::from_str(name).unwrap());
+        assert_eq!(*records[0].rdata(), RData::A(Ipv4Addr::new(127, 0, 1, 1)));
+    }
+
+    // the wildcard has no records of the type
+    assert!(
+        lookup(&authority, "www.wild.example.com.", RecordType::AAAA)
+            .unwrap_err()
+            .is_name_exists()
+    );
+}
+
+#[test]
+fn test_wildcard_does_not_match_existing_names() {
+    let authority = create_wildcard_authority();
+
+    assert!(lookup(&authority, "host.wild.example.com.", RecordType::A)
+        .unwrap_err()
+        .is_name_exists());
+    assert!(
+        lookup(&authority, "www.host.wild.example.com.", RecordType::A)
+            .unwrap_err()
+            .is_nx_domain()
+    );
+}
+
+#[test]
+fn test_empty_non_terminal() {
+    let authority = create_wildcard_authority();
+
+    assert!(lookup(&authority, "b.example.com.", RecordType::A)
+        .unwrap_err()
+        .is_name_exists());
+    assert!(lookup(&authority, "c.example.com.", RecordType::A)
+        .unwrap_err()
+        .is_nx_domain());
+}
+
+#[test]
+fn test_names_of_update() {
+    let authority = create_authority();
+    let deep = Record::from_rdata(
+        Name::from_str("a.b.example.com.").unwrap(),
+        86400,
+        RData::A(Ipv4Addr::new(127, 0, 0, 4)),
+    );
+
+    let mut update = authority.begin_update();
+    assert!(update.upsert(deep.clone(), 1));
+    update.commit();
+
+    assert!(lookup(&authority, "a.b.example.com.", RecordType::A).is_ok());
+    assert!(lookup(&authority, "b.example.com.", RecordType::A)
+        .unwrap_err()
+        .is_name_exists());
+
+    // removing the only record set of the name removes its empty non-terminal too
+    let mut update = authority.begin_update();
+    assert!(update
+        .remove(&RrKey::new(deep.name().into(), RecordType::A))
+        .is_some());
+    update.commit();
+
+    assert!(lookup(&authority, "a.b.example.com.", RecordType::A)
+        .unwrap_err()
+        .is_nx_domain());
+    assert!(lookup(&authority, "b.example.com.", RecordType::A)
+        .unwrap_err()
+        .is_nx_domain());
+    assert_eq!(
+        lookup_addresses(&authority),
+        vec![Ipv4Addr::new(127, 0, 0, 1)]
+    );
+}
+
+#[test]
+fn test_diff_of_update() {
+    let authority = create_authority();
+    let before = authority.records();
+
+    let mut update = authority.begin_update();
+    update.upsert(www(2), 1);
+    update.increment_soa_serial();
+    update.commit();
+
+    let diff = ZoneDiff::new(&before, &authority.records());
+    assert_eq!(diff.old_serial(), Some(1));
+    assert_eq!(diff.new_serial(), Some(2));
+    assert_eq!(diff.added().count(), 2);
+    assert_eq!(diff.deleted().count(), 1);
+
+    // the SOAs frame the deleted and the added records
+    let ixfr = diff.ixfr_records().expect("no IXFR records");
+    let types = ixfr.iter().map(Record::rr_type).collect::<Vec<_>>();
+    assert_eq!(types, vec![RecordType::SOA, RecordType::SOA, RecordType::A]);
+    assert_eq!(ixfr[2], www(2));
+
+    assert!(authority.diff(&authority).is_empty());
+}
+
+#[test]
+fn test_diff_of_zone_files() {
+    let parse = |zone: &str| {
+        Parser::new()
+            .parse(
+                Lexer::new(zone),
+                Some(Name::from_str("example.com.").unwrap()),
+            )
+            .expect("failed to parse zone")
+            .1
+    };
+    let old = parse(
+        "@ 3600 IN SOA ns.example.com. root.example.com. 1 7200 3600 1209600 3600\n\
+         www 300 IN A 127.0.0.1\n\
+         old 300 IN A 127.0.0.2\n\
+         ttl 300 IN A 127.0.0.3\n",
+    );
+    let new = parse(
+        "@ 3600 IN SOA ns.example.com. root.example.com. 2 7200 3600 1209600 3600\n\
+         www 300 IN A 127.0.0.1\n\
+         new 300 IN A 127.0.0.2\n\
+         ttl 60 IN A 127.0.0.3\n",
+    );
+
+    let diff = ZoneDiff::new(&old, &new);
+    let changes = diff
+        .rrsets()
+        .iter()
+        .map(|rrset| {
+            (
+                rrset.name().to_string(),
+                rrset.record_type(),
+                rrset.deleted().len(),
+                rrset.added().len(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    // a changed TTL is a changed record
+    assert_eq!(
+        changes,
+        vec![
+            ("example.com.".to_string(), RecordType::SOA, 1, 1),
+            ("new.example.com.".to_string(), RecordType::A, 0, 1),
+            ("old.example.com.".to_string(), RecordType::A, 1, 0),
+            ("ttl.example.com.".to_string(), RecordType::A, 1, 1),
+        ]
+    );
+    assert!(ZoneDiff::new(&new, &new).is_empty());
+}
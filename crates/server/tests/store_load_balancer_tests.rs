@@ -0,0 +1,198 @@
+extern crate futures;
+extern crate tokio;
+extern crate trust_dns;
+extern crate trust_dns_server;
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, TcpListener};
+use std::path::Path;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use futures::Future;
+
+use trust_dns::rr::{LowerName, Name, RecordType};
+use trust_dns_server::authority::{Authority, ZoneType};
+use trust_dns_server::config::Config;
+use trust_dns_server::store::load_balancer::{
+    HealthCheckConfig, LoadBalancerAuthority, PoolConfig, Probe,
+};
+use trust_dns_server::store::StoreConfig;
+
+fn load_balancer() -> LoadBalancerAuthority {
+    let config = Config::read_config(Path::new(
+        "tests/named_test_configs/example_load_balancer.toml",
+    ))
+    .expect("failed to read config");
+    let zone = config.get_zones().last().expect("no zones");
+
+    let config = match zone.stores {
+        Some(StoreConfig::LoadBalancer(ref config)) => config,
+        ref other => panic!("unexpected store config: {:?}", other),
+    };
+
+    // the health checks are not run, all targets stay healthy until changed
+    LoadBalancerAuthority::try_from_config(
+        Name::from_str("example.com.").unwrap(),
+        ZoneType::Master,
+        false,
+        Some(Path::new("tests/named_test_configs")),
+        config,
+    )
+    .expect("failed to load load balancer")
+    .0
+}
+
+fn lookup(authority: &LoadBalancerAuthority, name: &str, rtype: RecordType) -> Vec<IpAddr> {
+    let lookup = authority
+        .lookup(
+            &LowerName::from_str(name).unwrap(),
+            rtype,
+            false,
+            Default::default(),
+        )
+        .wait()
+        .expect("lookup failed");
+
+    let mut addresses = lookup
+        .iter()
+        .filter_map(|r| r.rdata().to_ip_addr())
+        .collect::<Vec<_>>();
+    addresses.sort();
+    addresses
+}
+
+#[test]
+fn test_unhealthy_targets_withheld() {
+    let authority = load_balancer();
+    let lb = LowerName::from_str("lb.example.com.").unwrap();
+    let target = |ip: [u8; 4]| IpAddr::from(Ipv4Addr::from(ip));
+
+    assert_eq!(
+        lookup(&authority, "lb.example.com.", RecordType::A),
+        vec![
+            target([192, 0, 2, 1]),
+            target([192, 0, 2, 2]),
+            target([192, 0, 2, 3])
+        ]
+    );
+
+    authority
+        .target_health(&lb, target([192, 0, 2, 2]))
+        .expect("target not found")
+        .set_healthy(false);
+    assert_eq!(
+        lookup(&authority, "lb.example.com.", RecordType::A),
+        vec![target([192, 0, 2, 1]), target([192, 0, 2, 3])]
+    );
+
+    // when all targets are unhealthy, min_answers are still returned
+    authority
+        .target_health(&lb, target([192, 0, 2, 1]))
+        .unwrap()
+        .set_healthy(false);
+    authority
+        .target_health(&lb, target([192, 0, 2, 3]))
+        .unwrap()
+        .set_healthy(false);
+    assert_eq!(
+        lookup(&authority, "lb.example.com.", RecordType::A).len(),
+        1
+    );
+
+    authority
+        .target_health(&lb, target([192, 0, 2, 3]))
+        .unwrap()
+        .set_healthy(true);
+    assert_eq!(
+        lookup(&authority, "lb.example.com.", RecordType::A),
+        vec![target([192, 0, 2, 3])]
+    );
+
+    // the families are independent
+    assert_eq!(
+        lookup(&authority, "lb.example.com.", RecordType::AAAA),
+        vec![IpAddr::from(Ipv6Addr::from_str("2001:db8::1").unwrap())]
+    );
+}
+
+#[test]
+fn test_zone_records() {
+    let authority = load_balancer();
+
+    assert_eq!(
+        lookup(&authority, "www.example.com.", RecordType::A),
+        vec![IpAddr::from(Ipv4Addr::new(127, 0, 0, 1))]
+    );
+
+    // the pool names exist in the zone, other types have no data
+    let lookup = authority
+        .lookup(
+            &LowerName::from_str("tcp.example.com.").unwrap(),
+            RecordType::AAAA,
+            false,
+            Default::default(),
+        )
+        .wait();
+    match lookup {
+        Err(e) => assert!(!e.is_nx_domain()),
+        Ok(lookup) => assert!(lookup.iter().next().is_none()),
+    }
+}
+
+#[test]
+fn test_tcp_health_check() {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind");
+    let port = listener.local_addr().unwrap().port();
+    let healthy = IpAddr::from(Ipv4Addr::new(127, 0, 0, 1));
+    let unhealthy = IpAddr::from(Ipv4Addr::new(127, 0, 0, 2));
+
+    let mut authority = load_balancer();
+    let checks = authority
+        .insert_pool(&PoolConfig {
+            name: Name::from_str("checked.example.com.").unwrap(),
+            ttl: None,
+            targets: vec![healthy, unhealthy],
+            min_answers: None,
+            health_check: Some(HealthCheckConfig {
+                probe: Probe::Tcp { port },
+                interval: Some(1),
+                timeout: Some(1),
+                rise: Some(1),
+                fall: Some(1),
+            }),
+        })
+        .expect("failed to insert pool");
+    assert_eq!(checks.len(), 2);
+
+    let mut runtime = tokio::runtime::Runtime::new().expect("failed to create runtime");
+    for check in checks {
+        runtime.spawn(check);
+    }
+
+    let checked = LowerName::from_str("checked.example.com.").unwrap();
+    let start = Instant::now();
+    while authority
+        .target_health(&checked, unhealthy)
+        .unwrap()
+        .is_healthy()
+    {
+        assert!(
+            start.elapsed() < Duration::from_secs(10),
+            "target never became unhealthy"
+        );
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    assert!(authority
+        .target_health(&checked, healthy)
+        .unwrap()
+        .is_healthy());
+    assert_eq!(
+        lookup(&authority, "checked.example.com.", RecordType::A),
+        vec![healthy]
+    );
+
+    drop(listener);
+    runtime.shutdown_now().wait().ok();
+}
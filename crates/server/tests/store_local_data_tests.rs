@@ -0,0 +1,111 @@
+#![cfg(feature = "trust-dns-resolver")]
+
+extern crate futures;
+extern crate trust_dns;
+extern crate trust_dns_server;
+
+use std::net::Ipv4Addr;
+use std::path::Path;
+use std::str::FromStr;
+
+use futures::Future;
+
+use trust_dns::rr::{LowerName, Name, RecordType};
+use trust_dns_server::authority::{Authority, LookupObject, ZoneType};
+use trust_dns_server::config::Config;
+use trust_dns_server::store::local_data::LocalDataAuthority;
+use trust_dns_server::store::StoreConfig;
+
+fn local_data() -> LocalDataAuthority {
+    let config = Config::read_config(Path::new(
+        "tests/named_test_configs/example_local_data.toml",
+    ))
+    .expect("failed to read config");
+    let zone = config.get_zones().last().expect("no zones");
+
+    let config = match zone.stores {
+        Some(StoreConfig::LocalData(ref config)) => config,
+        ref other => panic!("unexpected store config: {:?}", other),
+    };
+
+    LocalDataAuthority::try_from_config(Name::root(), ZoneType::Forward, config)
+        .expect("failed to load local data")
+        .0
+}
+
+#[test]
+fn test_local_answer() {
+    let authority = local_data();
+
+    let lookup = authority
+        .lookup(
+            &LowerName::from_str("router.lan.").unwrap(),
+            RecordType::A,
+            false,
+            Default::default(),
+        )
+        .wait()
+        .expect("lookup failed");
+
+    let records = lookup.iter().collect::<Vec<_>>();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].ttl(), 300);
+    assert_eq!(
+        *records[0].rdata().as_a().unwrap(),
+        Ipv4Addr::new(192, 168, 1, 1)
+    );
+}
+
+#[test]
+fn test_local_cname() {
+    let authority = local_data();
+
+    let lookup = authority
+        .lookup(
+            &LowerName::from_str("nas.lan.").unwrap(),
+            RecordType::A,
+            false,
+            Default::default(),
+        )
+        .wait()
+        .expect("lookup failed");
+
+    let records = lookup.iter().collect::<Vec<_>>();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].ttl(), 60);
+    assert_eq!(
+        *records[0].rdata().as_cname().unwrap(),
+        Name::from_str("router.lan.").unwrap()
+    );
+    assert_eq!(
+        *records[1].rdata().as_a().unwrap(),
+        Ipv4Addr::new(192, 168, 1, 1)
+    );
+}
+
+#[test]
+fn test_local_nodata() {
+    let authority = local_data();
+
+    let lookup = authority
+        .lookup(
+            &LowerName::from_str("www.example.com.").unwrap(),
+            RecordType::AAAA,
+            false,
+            Default::default(),
+        )
+        .wait()
+        .expect("lookup failed");
+
+    assert!(lookup.is_empty());
+}
+
+#[test]
+fn test_insert_and_remove() {
+    let mut authority = local_data();
+    let name = LowerName::from_str("www.example.com.").unwrap();
+
+    assert!(authority.get(&name).is_some());
+    assert_eq!(authority.remove(&name).map(|r| r.len()), Some(1));
+    assert!(authority.get(&name).is_none());
+}
@@ -3,11 +3,16 @@ extern crate trust_dns;
 extern crate trust_dns_server;
 
 use std::fs;
+use std::net::Ipv4Addr;
 use std::path::PathBuf;
 use std::str::FromStr;
 
-use trust_dns::rr::Name;
-use trust_dns_server::authority::ZoneType;
+use futures::Future;
+
+use trust_dns::op::{ResponseCode, UpdateBuilder};
+use trust_dns::rr::dnssec::SupportedAlgorithms;
+use trust_dns::rr::{LowerName, Name, RData, Record, RecordType};
+use trust_dns_server::authority::{Authority, ZoneType};
 use trust_dns_server::store::sqlite::{SqliteAuthority, SqliteConfig};
 
 #[macro_use]
@@ -70,4 +75,157 @@ basic_battery!(sqlite);
 #[cfg(feature = "dnssec")]
 dnssec_battery!(sqlite);
 #[cfg(feature = "dnssec")]
-dynamic_update!(sqlite_update);
\ No newline at end of file
+dynamic_update!(sqlite_update);
+
+/// An updatable authority which is not signed, so the updates need no signing keys
+fn sqlite_unsigned(test_name: &str) -> SqliteAuthority {
+    let journal_path = PathBuf::from("target/tests")
+        .join(module_path!())
+        .join(test_name)
+        .join("authority.jrnl");
+    fs::create_dir_all(journal_path.parent().unwrap()).ok();
+
+    // cleanup anything from previous test
+    fs::remove_file(&journal_path).ok();
+
+    let config = SqliteConfig {
+        zone_file_path: "tests/named_test_configs/example.com.zone".to_string(),
+        journal_file_path: journal_path.to_str().unwrap().to_string(),
+        allow_update: false,
+    };
+
+    SqliteAuthority::try_from_config(
+        Name::from_str("example.com.").unwrap(),
+        ZoneType::Master,
+        false,
+        false,
+        None,
+        &config,
+    ).expect("failed to load file")
+}
+
+fn lookup_addresses(authority: &SqliteAuthority, name: &str) -> Vec<Ipv4Addr> {
+    authority
+        .lookup(
+            &LowerName::from_str(name).unwrap(),
+            RecordType::A,
+            false,
+            SupportedAlgorithms::new(),
+        )
+        .wait()
+        .map(|lookup| lookup.iter().map(|r| *r.rdata().as_a().unwrap()).collect())
+        .unwrap_or_default()
+}
+
+fn a(name: &str, address: Ipv4Addr) -> Record {
+    Record::from_rdata(Name::from_str(name).unwrap(), 300, RData::A(address))
+}
+
+#[test]
+fn test_apply_update() {
+    let authority = sqlite_unsigned("test_apply_update");
+    let serial = authority.serial();
+
+    // swap the address of www and add a new name, all at once
+    let update = UpdateBuilder::new(Name::from_str("example.com.").unwrap())
+        .require_rrset(a("www.example.com.", Ipv4Addr::new(127, 0, 0, 1)))
+        .require_name_not_in_use(Name::from_str("new.example.com.").unwrap())
+        .delete_rrset(Name::from_str("www.example.com.").unwrap(), RecordType::A)
+        .add(a("www.example.com.", Ipv4Addr::new(127, 0, 0, 10)))
+        .add(a("new.example.com.", Ipv4Addr::new(127, 0, 0, 11)));
+    assert_eq!(authority.apply_update(&update), Ok(true));
+
+    assert_eq!(
+        lookup_addresses(&authority, "www.example.com."),
+        vec![Ipv4Addr::new(127, 0, 0, 10)]
+    );
+    assert_eq!(
+        lookup_addresses(&authority, "new.example.com."),
+        vec![Ipv4Addr::new(127, 0, 0, 11)]
+    );
+    assert!(authority.serial() > serial);
+
+    // the zone of the update must be the zone of the authority
+    let update = UpdateBuilder::new(Name::from_str("example.net.").unwrap());
+    assert_eq!(authority.apply_update(&update), Err(ResponseCode::NotAuth));
+}
+
+#[test]
+fn test_apply_update_is_atomic() {
+    let authority = sqlite_unsigned("test_apply_update_is_atomic");
+    let serial = authority.serial();
+
+    // the address of www is not the required one, nothing is applied
+    let update = UpdateBuilder::new(Name::from_str("example.com.").unwrap())
+        .add(a("new.example.com.", Ipv4Addr::new(127, 0, 0, 11)))
+        .require_rrset(a("www.example.com.", Ipv4Addr::new(127, 0, 0, 2)))
+        .delete_rrset(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+    assert_eq!(authority.apply_update(&update), Err(ResponseCode::NXRRSet));
+
+    let update = UpdateBuilder::new(Name::from_str("example.com.").unwrap())
+        .add(a("new.example.com.", Ipv4Addr::new(127, 0, 0, 11)))
+        .require_name_not_in_use(Name::from_str("www.example.com.").unwrap());
+    assert_eq!(authority.apply_update(&update), Err(ResponseCode::YXDomain));
+
+    assert_eq!(
+        lookup_addresses(&authority, "www.example.com."),
+        vec![Ipv4Addr::new(127, 0, 0, 1)]
+    );
+    assert!(lookup_addresses(&authority, "new.example.com.").is_empty());
+    assert_eq!(authority.serial(), serial);
+}
+
+#[cfg(feature = "dnssec-openssl")]
+#[test]
+fn test_apply_update_signs_changes() {
+    use std::sync::Arc;
+
+    use trust_dns::rr::rdata::{DNSSECRData, DNSSECRecordType};
+    use trust_dns::rr::RrKey;
+
+    let mut authority = sqlite(
+        "tests/named_test_configs/example.com.zone",
+        module_path!(),
+        "test_apply_update_signs_changes",
+    );
+    authority_battery::dnssec::add_signers(&mut authority);
+    let records = authority.records();
+
+    let update = UpdateBuilder::new(Name::from_str("example.com.").unwrap())
+        .add(a("new.example.com.", Ipv4Addr::new(127, 0, 0, 11)));
+    assert_eq!(authority.apply_update(&update), Ok(true));
+
+    // only the changed record sets are signed again, the others are the same
+    let new = Name::from_str("new.example.com.").unwrap();
+    let nsec = RecordType::DNSSEC(DNSSECRecordType::NSEC);
+    let updated = authority.records();
+    let changed = updated
+        .iter()
+        .filter(|(key, rrset)| match records.get(key) {
+            Some(old) => !Arc::ptr_eq(old, rrset),
+            None => true,
+        })
+        .map(|(key, rrset)| {
+            assert!(!rrset.rrsigs().is_empty(), "not signed: {:?}", key);
+            key.clone()
+        })
+        .collect::<Vec<RrKey>>();
+
+    // the new name, the nsec record before it and the soa
+    let previous_nsec = updated
+        .values()
+        .filter(|rrset| rrset.record_type() == nsec && *rrset.name() != new)
+        .find(|rrset| match rrset.records_without_rrsigs().next().map(Record::rdata) {
+            Some(RData::DNSSEC(DNSSECRData::NSEC(rdata))) => *rdata.next_domain_name() == new,
+            _ => false,
+        })
+        .expect("no nsec record before the new name");
+    let mut expected = vec![
+        RrKey::new(LowerName::from(&new), RecordType::A),
+        RrKey::new(LowerName::from(&new), nsec),
+        RrKey::new(previous_nsec.name().into(), nsec),
+        RrKey::new(authority.origin().clone(), RecordType::SOA),
+    ];
+    expected.sort();
+    assert_eq!(changed, expected);
+}
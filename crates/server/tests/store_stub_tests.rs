@@ -0,0 +1,148 @@
+#![cfg(feature = "trust-dns-resolver")]
+
+extern crate futures;
+extern crate tokio;
+extern crate trust_dns;
+extern crate trust_dns_resolver;
+extern crate trust_dns_server;
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use futures::future;
+use tokio::runtime::Runtime;
+
+use trust_dns::rr::rdata::SOA;
+use trust_dns::rr::{LowerName, Name, RData, Record, RecordType};
+use trust_dns_resolver::config::NameServerConfigGroup;
+use trust_dns_server::authority::{Authority, Catalog, LookupObject, ZoneType};
+use trust_dns_server::store::in_memory::InMemoryAuthority;
+use trust_dns_server::store::stub::{StubAuthority, StubConfig};
+use trust_dns_server::ServerFuture;
+
+/// A zone delegated to ns.example.com., with the address of www.example.com.
+fn zone(ns: Ipv4Addr, www: Ipv4Addr) -> InMemoryAuthority {
+    let origin = Name::from_str("example.com.").unwrap();
+    let ns_name = Name::from_str("ns.example.com.").unwrap();
+    let mut authority = InMemoryAuthority::empty(origin.clone(), ZoneType::Master, false);
+
+    authority.upsert(
+        Record::from_rdata(
+            origin.clone(),
+            3600,
+            RData::SOA(SOA::new(
+                ns_name.clone(),
+                Name::from_str("root.example.com.").unwrap(),
+                1,
+                7200,
+                3600,
+                1_209_600,
+                3600,
+            )),
+        ),
+        1,
+    );
+    authority.upsert(
+        Record::from_rdata(origin, 3600, RData::NS(ns_name.clone())),
+        1,
+    );
+    authority.upsert(Record::from_rdata(ns_name, 3600, RData::A(ns)), 1);
+    authority.upsert(
+        Record::from_rdata(
+            Name::from_str("www.example.com.").unwrap(),
+            0,
+            RData::A(www),
+        ),
+        1,
+    );
+
+    authority
+}
+
+fn serve(io_loop: &mut Runtime, socket: UdpSocket, authority: InMemoryAuthority) {
+    let mut catalog = Catalog::new();
+    catalog.upsert(authority.origin().clone(), Box::new(authority));
+
+    io_loop
+        .block_on(future::lazy(move || {
+            ServerFuture::new(catalog).register_socket_std(socket);
+            future::ok::<(), ()>(())
+        }))
+        .unwrap();
+}
+
+#[test]
+fn test_stub_queries_authoritative_name_servers() {
+    let mut io_loop = Runtime::new().unwrap();
+
+    // the master delegates the zone to the authoritative name server, on the same port
+    let master = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = master.local_addr().unwrap().port();
+    let authoritative = UdpSocket::bind(SocketAddr::new(
+        IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2)),
+        port,
+    ))
+    .unwrap();
+
+    serve(
+        &mut io_loop,
+        master,
+        zone(Ipv4Addr::new(127, 0, 0, 2), Ipv4Addr::new(10, 0, 0, 1)),
+    );
+    serve(
+        &mut io_loop,
+        authoritative,
+        zone(Ipv4Addr::new(127, 0, 0, 2), Ipv4Addr::new(10, 0, 0, 2)),
+    );
+
+    let config = StubConfig {
+        masters: NameServerConfigGroup::from_ips_clear(
+            &[IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1))],
+            port,
+        ),
+        port: Some(port),
+        refresh: Some(1),
+        options: None,
+    };
+
+    let (stub, bg) = StubAuthority::try_from_config(
+        Name::from_str("example.com.").unwrap(),
+        ZoneType::Stub,
+        &config,
+    )
+    .unwrap();
+    assert_eq!(stub.zone_type(), ZoneType::Stub);
+    io_loop.spawn(bg);
+
+    let www = LowerName::from_str("www.example.com.").unwrap();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let lookup = io_loop
+            .block_on(stub.lookup(&www, RecordType::A, false, Default::default()))
+            .expect("lookup failed");
+        let address = *lookup
+            .iter()
+            .next()
+            .expect("no addresses returned")
+            .rdata()
+            .as_a()
+            .expect("not an A record");
+
+        if address == Ipv4Addr::new(10, 0, 0, 2) {
+            break;
+        }
+
+        // until the name servers are learned, the master answers
+        assert_eq!(address, Ipv4Addr::new(10, 0, 0, 1));
+        assert!(Instant::now() < deadline, "name servers not learned");
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    // stub zones aren't signed, there are no NSEC records to deny the existence of names
+    let nsecs = io_loop
+        .block_on(stub.get_nsec_records(&www, true, Default::default()))
+        .expect("nsec lookup failed");
+    assert!(nsecs.is_empty());
+}
@@ -0,0 +1,210 @@
+extern crate futures;
+extern crate trust_dns;
+extern crate trust_dns_server;
+
+use std::net::Ipv6Addr;
+use std::str::FromStr;
+
+use futures::Future;
+
+use trust_dns::op::LowerQuery;
+use trust_dns::rr::{LowerName, Name, RData, RecordType};
+use trust_dns_server::authority::{AuthLookup, Authority, LookupError, ZoneType};
+use trust_dns_server::store::synth_ptr::{NameTemplate, SynthPtrAuthority, SynthPtrConfig};
+
+const REVERSE_ZONE: &str = "8.b.d.0.1.0.0.2.ip6.arpa.";
+const TEMPLATE: &str = "{ip}.dyn.example.com.";
+
+fn authority(zone: &str, reverse_zone: Option<&str>) -> SynthPtrAuthority {
+    SynthPtrAuthority::try_from_config(
+        Name::from_str(zone).unwrap(),
+        ZoneType::Master,
+        &SynthPtrConfig {
+            template: TEMPLATE.to_string(),
+            reverse_zone: reverse_zone.map(|z| Name::from_str(z).unwrap()),
+            ttl: Some(60),
+        },
+    )
+    .expect("failed to create authority")
+}
+
+fn lookup(
+    authority: &SynthPtrAuthority,
+    name: &str,
+    rtype: RecordType,
+) -> Result<AuthLookup, LookupError> {
+    authority
+        .lookup(
+            &LowerName::from_str(name).unwrap(),
+            rtype,
+            false,
+            Default::default(),
+        )
+        .wait()
+}
+
+fn rdatas(lookup: &AuthLookup) -> Vec<(Name, u32, RData)> {
+    lookup
+        .iter()
+        .map(|r| (r.name().clone(), r.ttl(), r.rdata().clone()))
+        .collect()
+}
+
+/// The name, without the checks of hostnames, e.g. for labels starting with -
+fn raw_name(name: &str) -> Name {
+    let labels = name.trim_end_matches('.').split('.').map(|l| l.as_bytes());
+    let mut name = Name::from_labels(labels).unwrap();
+    name.set_fqdn(true);
+    name
+}
+
+#[test]
+fn test_template() {
+    let template = NameTemplate::from_str(TEMPLATE).unwrap();
+    let names = [
+        ("2001:db8::1", "2001-db8--1.dyn.example.com."),
+        ("2001:db8:0:1:0:0:0:1", "2001-db8-0-1--1.dyn.example.com."),
+        (
+            "2001:db8:1:2:3:4:5:6",
+            "2001-db8-1-2-3-4-5-6.dyn.example.com.",
+        ),
+        ("::", "--.dyn.example.com."),
+        ("::ffff:192.0.2.1", "--ffff-c000-201.dyn.example.com."),
+        ("2001:db8::", "2001-db8--.dyn.example.com."),
+    ];
+
+    for &(address, name) in &names {
+        let address = Ipv6Addr::from_str(address).unwrap();
+        let name = raw_name(name);
+        assert_eq!(template.name_of(address), name);
+        assert_eq!(template.address_of(&name), Some(address));
+    }
+
+    // a single name for an address
+    let name = raw_name("2001-0db8--1.dyn.example.com.");
+    assert_eq!(template.address_of(&name), None);
+    let name = raw_name("2001-db8-0-0-0-0-0-1.dyn.example.com.");
+    assert_eq!(template.address_of(&name), None);
+    // not of the template
+    let name = raw_name("www.dyn.example.com.");
+    assert_eq!(template.address_of(&name), None);
+    let name = raw_name("2001-db8--1.example.com.");
+    assert_eq!(template.address_of(&name), None);
+
+    let template = NameTemplate::from_str("host-{ip}-v6.example.com.").unwrap();
+    let address = Ipv6Addr::from_str("2001:db8::1").unwrap();
+    let name = raw_name("host-2001-db8--1-v6.example.com.");
+    assert_eq!(template.name_of(address), name);
+    assert_eq!(template.address_of(&name), Some(address));
+
+    assert!(NameTemplate::from_str("dyn.example.com.").is_err());
+    assert!(NameTemplate::from_str("www.{ip}.example.com.").is_err());
+    assert!(NameTemplate::from_str("a_{ip}.example.com.").is_err());
+}
+
+#[test]
+fn test_config() {
+    let config = |template: &str, reverse_zone: Option<&str>| SynthPtrConfig {
+        template: template.to_string(),
+        reverse_zone: reverse_zone.map(|z| Name::from_str(z).unwrap()),
+        ttl: None,
+    };
+    let try_from_config = |zone: &str, config: &SynthPtrConfig| {
+        SynthPtrAuthority::try_from_config(Name::from_str(zone).unwrap(), ZoneType::Master, config)
+    };
+
+    assert!(try_from_config(REVERSE_ZONE, &config(TEMPLATE, None)).is_ok());
+    assert!(try_from_config("dyn.example.com.", &config(TEMPLATE, Some(REVERSE_ZONE))).is_ok());
+
+    // the reverse zone of the template zone is required
+    assert!(try_from_config("dyn.example.com.", &config(TEMPLATE, None)).is_err());
+    assert!(try_from_config("0.168.192.in-addr.arpa.", &config(TEMPLATE, None)).is_err());
+    assert!(try_from_config("example.org.", &config(TEMPLATE, Some(REVERSE_ZONE))).is_err());
+}
+
+#[test]
+fn test_ptr() {
+    let authority = authority(REVERSE_ZONE, None);
+    let name = "1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa.";
+
+    let ptr = lookup(&authority, name, RecordType::PTR).expect("lookup failed");
+    assert_eq!(
+        rdatas(&ptr),
+        vec![(
+            Name::from_str(name).unwrap(),
+            60,
+            RData::PTR(Name::from_str("2001-db8--1.dyn.example.com.").unwrap())
+        )]
+    );
+
+    let any = lookup(&authority, name, RecordType::ANY).expect("lookup failed");
+    assert_eq!(rdatas(&any), rdatas(&ptr));
+
+    let error = lookup(&authority, name, RecordType::TXT).expect_err("expected no records");
+    assert!(error.is_name_exists());
+
+    // the names above the addresses exist
+    let error = lookup(
+        &authority,
+        "0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa.",
+        RecordType::PTR,
+    )
+    .expect_err("expected no records");
+    assert!(error.is_name_exists());
+
+    // not addresses
+    let error = lookup(&authority, "www.8.b.d.0.1.0.0.2.ip6.arpa.", RecordType::PTR)
+        .expect_err("expected no records");
+    assert!(error.is_nx_domain());
+    let error = lookup(
+        &authority,
+        "g.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa.",
+        RecordType::PTR,
+    )
+    .expect_err("expected no records");
+    assert!(error.is_nx_domain());
+
+    // the records of the zone
+    let soa = lookup(&authority, REVERSE_ZONE, RecordType::SOA).expect("lookup failed");
+    assert_eq!(soa.iter().count(), 1);
+    let search = authority
+        .search(
+            &LowerQuery::query(trust_dns::op::Query::query(
+                Name::from_str(name).unwrap(),
+                RecordType::PTR,
+            )),
+            false,
+            Default::default(),
+        )
+        .wait()
+        .expect("search failed");
+    assert_eq!(rdatas(&search), rdatas(&ptr));
+}
+
+#[test]
+fn test_aaaa() {
+    let authority = authority("dyn.example.com.", Some(REVERSE_ZONE));
+
+    let aaaa = lookup(&authority, "2001-db8--1.dyn.example.com.", RecordType::AAAA)
+        .expect("lookup failed");
+    assert_eq!(
+        rdatas(&aaaa),
+        vec![(
+            Name::from_str("2001-db8--1.dyn.example.com.").unwrap(),
+            60,
+            RData::AAAA(Ipv6Addr::from_str("2001:db8::1").unwrap())
+        )]
+    );
+
+    let error = lookup(&authority, "2001-db8--1.dyn.example.com.", RecordType::A)
+        .expect_err("expected no records");
+    assert!(error.is_name_exists());
+
+    // only the addresses of the reverse zone
+    let error = lookup(&authority, "2001-db9--1.dyn.example.com.", RecordType::AAAA)
+        .expect_err("expected no records");
+    assert!(error.is_nx_domain());
+    let error = lookup(&authority, "www.dyn.example.com.", RecordType::AAAA)
+        .expect_err("expected no records");
+    assert!(error.is_nx_domain());
+}
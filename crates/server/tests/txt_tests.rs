@@ -10,6 +10,7 @@ use futures::future::Future;
 
 use trust_dns::proto::rr::rdata::tlsa::*;
 use trust_dns::rr::dnssec::*;
+use trust_dns::rr::rdata::{DNSSECRData, DNSSECRecordType};
 use trust_dns::rr::*;
 use trust_dns::serialize::txt::*;
 use trust_dns_server::authority::{Authority, ZoneType};
@@ -496,3 +497,228 @@ a       A       127.0.0.1
 
     assert!(InMemoryAuthority::new(origin, records, ZoneType::Master, false).is_ok());
 }
+
+#[test]
+fn test_escaped_character_strings_round_trip() {
+    let lexer = Lexer::new(
+        r###"
+@   IN  SOA     venera      action\.domains (
+                            20     ; SERIAL
+                            7200   ; REFRESH
+                            600    ; RETRY
+                            3600000; EXPIRE
+                            60)    ; MINIMUM
+
+quoted  TXT     "say \"hi\"; bye" "back\\slash" ; a comment
+unquoted TXT    semi\;colon with\ space \"quote\"
+octets  TXT     "\000\065\255" ( "in" "a list" )
+naptr   NAPTR   100 50 "s" "http+N2L+N2C+N2R" "!^(.*)$!\"\\1\"!" www.example.com.
+"###,
+    );
+
+    let (origin, records) = Parser::new()
+        .parse(lexer, Some(Name::from_str("isi.edu").unwrap()))
+        .expect("failed to parse");
+
+    let txt_data = |name: &str| -> Vec<Vec<u8>> {
+        let name = Name::from_str(name).unwrap();
+        let record_set = &records[&RrKey::new(name.into(), RecordType::TXT)];
+        match *record_set.records_without_rrsigs().next().unwrap().rdata() {
+            RData::TXT(ref txt) => txt.iter().map(|s| s.to_vec()).collect(),
+            _ => panic!("Not a TXT record!!!"), // valid panic, test code
+        }
+    };
+    assert_eq!(
+        txt_data("quoted.isi.edu."),
+        vec![b"say \"hi\"; bye".to_vec(), b"back\\slash".to_vec()]
+    );
+    assert_eq!(
+        txt_data("unquoted.isi.edu."),
+        vec![
+            b"semi;colon".to_vec(),
+            b"with space".to_vec(),
+            b"\"quote\"".to_vec(),
+        ]
+    );
+    assert_eq!(
+        txt_data("octets.isi.edu."),
+        vec![b"\x00A\xff".to_vec(), b"in".to_vec(), b"a list".to_vec()]
+    );
+
+    // write the records with Display and parse them again
+    let mut zone = String::new();
+    for record_set in records.values() {
+        for record in record_set.records_without_rrsigs() {
+            let rdata = match *record.rdata() {
+                RData::TXT(ref txt) => txt.to_string(),
+                RData::NAPTR(ref naptr) => naptr.to_string(),
+                _ => continue,
+            };
+            zone.push_str(&format!(
+                "{} {} IN {} {}\n",
+                record.name(),
+                record.ttl(),
+                record.rr_type(),
+                rdata
+            ));
+        }
+    }
+
+    let (_, reparsed) = Parser::new()
+        .parse(Lexer::new(&zone), Some(origin))
+        .expect("failed to parse Display output");
+    assert_eq!(reparsed.len(), 4);
+    for (key, record_set) in reparsed {
+        let original = records[&key].records_without_rrsigs().collect::<Vec<_>>();
+        assert_eq!(
+            record_set.records_without_rrsigs().collect::<Vec<_>>(),
+            original
+        );
+    }
+}
+
+#[test]
+fn test_record_reader() {
+    let zone = r###"
+$ORIGIN isi.edu.
+@   IN  SOA     venera      action\.domains (
+                            20     ; SERIAL (
+                            7200   ; REFRESH
+                            600    ; RETRY
+                            3600000; EXPIRE
+                            60)    ; MINIMUM
+
+        NS      a.isi.edu.
+a       A       26.3.0.103
+        TXT     "a quote ( over
+two lines" ; a comment )
+paren   TXT     \( "\"(" ( "in" ; a )
+                  "a list" )
+venera  A       10.1.0.52
+        A       128.9.0.32
+"###;
+
+    let records = RecordReader::new(zone.as_bytes(), None)
+        .collect::<Result<Vec<_>, _>>()
+        .expect("failed to read");
+
+    // the records are read in the order of the file
+    let types = records.iter().map(Record::rr_type).collect::<Vec<_>>();
+    assert_eq!(
+        types,
+        vec![
+            RecordType::SOA,
+            RecordType::NS,
+            RecordType::A,
+            RecordType::TXT,
+            RecordType::TXT,
+            RecordType::A,
+            RecordType::A,
+        ]
+    );
+    assert_eq!(
+        records[4].name(),
+        &Name::from_str("paren.isi.edu.").unwrap()
+    );
+    match *records[3].rdata() {
+        RData::TXT(ref txt) => assert_eq!(&*txt.txt_data()[0], &b"a quote ( over\ntwo lines"[..]),
+        _ => panic!("Not a TXT record!!!"), // valid panic, test code
+    }
+    match *records[4].rdata() {
+        RData::TXT(ref txt) => assert_eq!(txt.txt_data().len(), 4),
+        _ => panic!("Not a TXT record!!!"), // valid panic, test code
+    }
+
+    // the records are the same as those parsed from the whole file
+    let parsed = Parser::new()
+        .parse(Lexer::new(zone), None)
+        .expect("failed to parse");
+    let read = Parser::new()
+        .parse_reader(zone.as_bytes(), None)
+        .expect("failed to read");
+    assert_eq!(read, parsed);
+
+    // an unclosed list is an error at the end of the file
+    let mut reader = RecordReader::new(&b"$ORIGIN isi.edu.\na 60 IN TXT ( \"a\"\n"[..], None);
+    assert!(reader.next().unwrap().is_err());
+    assert!(reader.next().is_none());
+}
+
+#[test]
+fn test_pre_signed_zone() {
+    let zone = r###"
+$ORIGIN example.com.
+$TTL 3600
+@       IN  SOA     ns.example.com. root.example.com. (
+                            2019010101 7200 600 3600000 60 )
+        RRSIG   SOA 8 2 3600 20300101000000 20200101000000 2642 example.com. (
+                    AwEA AQ== )
+        DNSKEY  257 3 8 ( AwEAAQ== ) ; KSK
+        NSEC3PARAM 1 0 10 AABBCCDD
+www     RRSIG   A 8 3 3600 20300101000000 20200101000000 2642 example.com. AwEAAQ==
+        RRSIG   NSEC RSASHA256 3 3600 1893456000 1577836800 2642 example.com. AwEAAQ==
+        A       127.0.0.1
+        A       127.0.0.2
+        NSEC    example.com. A RRSIG NSEC
+sub     DS      60485 8 2 ( D4B7D520E7BB5F0F
+                            67674A0CCEB1E3E0 )
+"###;
+
+    let (origin, records) = Parser::new()
+        .parse(Lexer::new(zone), None)
+        .expect("failed to parse");
+    assert_eq!(origin, Name::from_str("example.com.").unwrap());
+
+    let www = Name::from_str("www.example.com.").unwrap();
+    let set = records
+        .get(&RrKey::new(www.clone().into(), RecordType::A))
+        .expect("A not found");
+    assert_eq!(set.records_without_rrsigs().count(), 2);
+
+    // the signature is attached to the set even though it preceded the records
+    assert_eq!(set.rrsigs().len(), 1);
+    match *set.rrsigs()[0].rdata() {
+        RData::DNSSEC(DNSSECRData::SIG(ref sig)) => {
+            assert_eq!(sig.type_covered(), RecordType::A);
+            assert_eq!(sig.key_tag(), 2642);
+            assert_eq!(sig.sig_expiration(), 1_893_456_000);
+        }
+        _ => panic!("Not an RRSIG record!!!"), // valid panic, test code
+    }
+
+    let nsec = records
+        .get(&RrKey::new(
+            www.clone().into(),
+            RecordType::DNSSEC(DNSSECRecordType::NSEC),
+        ))
+        .expect("NSEC not found");
+    assert_eq!(nsec.rrsigs().len(), 1);
+
+    let soa = records
+        .get(&RrKey::new(origin.clone().into(), RecordType::SOA))
+        .expect("SOA not found");
+    assert_eq!(soa.rrsigs().len(), 1);
+
+    // the RRSIGs are not sets of their own
+    assert!(records
+        .keys()
+        .all(|key| key.record_type != RecordType::DNSSEC(DNSSECRecordType::RRSIG)));
+
+    for record_type in &[DNSSECRecordType::DNSKEY, DNSSECRecordType::NSEC3PARAM] {
+        assert!(records.contains_key(&RrKey::new(
+            origin.clone().into(),
+            RecordType::DNSSEC(*record_type)
+        )));
+    }
+
+    let ds = records
+        .get(&RrKey::new(
+            Name::from_str("sub.example.com.").unwrap().into(),
+            RecordType::DNSSEC(DNSSECRecordType::DS),
+        ))
+        .expect("DS not found");
+    match *ds.records_without_rrsigs().next().unwrap().rdata() {
+        RData::DNSSEC(DNSSECRData::DS(ref ds)) => assert_eq!(ds.digest().len(), 16),
+        _ => panic!("Not a DS record!!!"), // valid panic, test code
+    }
+}
@@ -0,0 +1,264 @@
+extern crate futures;
+extern crate tokio;
+extern crate trust_dns;
+extern crate trust_dns_server;
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener};
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use futures::future;
+use tokio::runtime::Runtime;
+
+#[cfg(feature = "dnssec")]
+use trust_dns::proto::rr::dnssec::rdata::TsigAlgorithm;
+#[cfg(feature = "dnssec")]
+use trust_dns::rr::dnssec::TSigner;
+use trust_dns::rr::rdata::SOA;
+use trust_dns::rr::{LowerName, Name, RData, Record, RecordType};
+use trust_dns_server::authority::{refresh_secondary, Authority, Catalog, ZoneTransfer, ZoneType};
+#[cfg(feature = "dnssec")]
+use trust_dns_server::server::TsigKeyring;
+use trust_dns_server::store::in_memory::InMemoryAuthority;
+use trust_dns_server::ServerFuture;
+
+/// The master zone of example.com., with the address of www.example.com.
+fn zone(serial: u32, www: Ipv4Addr) -> InMemoryAuthority {
+    zone_with_expire(serial, www, 1_209_600)
+}
+
+fn zone_with_expire(serial: u32, www: Ipv4Addr, expire: i32) -> InMemoryAuthority {
+    let origin = Name::from_str("example.com.").unwrap();
+    let ns_name = Name::from_str("ns.example.com.").unwrap();
+    let mut authority = InMemoryAuthority::empty(origin.clone(), ZoneType::Master, true);
+
+    authority.upsert(
+        Record::from_rdata(
+            origin.clone(),
+            3600,
+            RData::SOA(SOA::new(
+                ns_name.clone(),
+                Name::from_str("root.example.com.").unwrap(),
+                serial,
+                1,
+                1,
+                expire,
+                3600,
+            )),
+        ),
+        serial,
+    );
+    authority.upsert(
+        Record::from_rdata(origin, 3600, RData::NS(ns_name.clone())),
+        serial,
+    );
+    authority.upsert(
+        Record::from_rdata(ns_name, 3600, RData::A(Ipv4Addr::new(127, 0, 0, 1))),
+        serial,
+    );
+    authority.upsert(
+        Record::from_rdata(
+            Name::from_str("www.example.com.").unwrap(),
+            3600,
+            RData::A(www),
+        ),
+        serial,
+    );
+
+    authority
+}
+
+/// Serves the zone over TCP, returning the catalog of the master and its address
+fn serve(io_loop: &mut Runtime, authority: InMemoryAuthority) -> (Catalog, SocketAddr) {
+    serve_with(io_loop, authority, |_| ())
+}
+
+fn serve_with<F: FnOnce(&mut ServerFuture<Catalog>)>(
+    io_loop: &mut Runtime,
+    authority: InMemoryAuthority,
+    configure: F,
+) -> (Catalog, SocketAddr) {
+    let mut catalog = Catalog::new();
+    catalog.upsert(authority.origin().clone(), Box::new(authority));
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let mut server = ServerFuture::new(catalog.clone());
+    configure(&mut server);
+    io_loop
+        .block_on(future::lazy(move || {
+            server.register_listener_std(listener, Duration::from_secs(5))
+        }))
+        .unwrap();
+
+    (catalog, addr)
+}
+
+fn transfer(master: SocketAddr, transfer_source: Option<IpAddr>) -> ZoneTransfer {
+    ZoneTransfer::new(
+        Name::from_str("example.com.").unwrap(),
+        vec![master],
+        transfer_source,
+        false,
+    )
+}
+
+fn run(
+    io_loop: &mut Runtime,
+    transfer: &ZoneTransfer,
+    serial: Option<u32>,
+) -> Result<Option<InMemoryAuthority>, String> {
+    io_loop.block_on(transfer.transfer(serial))
+}
+
+fn www(io_loop: &mut Runtime, authority: &InMemoryAuthority) -> Ipv4Addr {
+    let www = LowerName::from_str("www.example.com.").unwrap();
+    let lookup = io_loop
+        .block_on(authority.lookup(&www, RecordType::A, false, Default::default()))
+        .expect("lookup failed");
+
+    *lookup
+        .iter()
+        .next()
+        .expect("no addresses returned")
+        .rdata()
+        .as_a()
+        .expect("not an A record")
+}
+
+#[test]
+fn test_transfer_from_source() {
+    let mut io_loop = Runtime::new().unwrap();
+    let (_, master) = serve(&mut io_loop, zone(1, Ipv4Addr::new(10, 0, 0, 1)));
+
+    let transfer = transfer(master, Some(IpAddr::V4(Ipv4Addr::LOCALHOST)));
+    let authority = run(&mut io_loop, &transfer, None)
+        .expect("transfer failed")
+        .expect("zone not transferred");
+    assert_eq!(authority.zone_type(), ZoneType::Slave);
+    assert_eq!(www(&mut io_loop, &authority), Ipv4Addr::new(10, 0, 0, 1));
+
+    // the zone of the secondary is up to date
+    let up_to_date = run(&mut io_loop, &transfer, Some(1)).expect("transfer failed");
+    assert!(up_to_date.is_none());
+}
+
+#[test]
+fn test_transfer_source_not_local() {
+    let mut io_loop = Runtime::new().unwrap();
+    let (_, master) = serve(&mut io_loop, zone(1, Ipv4Addr::new(10, 0, 0, 1)));
+
+    // the connection can't be bound to an address of TEST-NET-1
+    let transfer = transfer(master, Some(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))));
+    assert!(run(&mut io_loop, &transfer, None).is_err());
+}
+
+#[test]
+fn test_refresh_secondary() {
+    let mut io_loop = Runtime::new().unwrap();
+    let (master_catalog, master) = serve(&mut io_loop, zone(1, Ipv4Addr::new(10, 0, 0, 1)));
+
+    let catalog = Catalog::new();
+    io_loop.spawn(refresh_secondary(
+        transfer(master, Some(IpAddr::V4(Ipv4Addr::LOCALHOST))),
+        catalog.clone(),
+    ));
+
+    let origin = LowerName::from_str("example.com.").unwrap();
+    let www = LowerName::from_str("www.example.com.").unwrap();
+    let mut expected = Ipv4Addr::new(10, 0, 0, 1);
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let address = catalog.find(&origin).and_then(|authority| {
+            let authority = authority.read().unwrap();
+            let lookup = io_loop
+                .block_on(authority.lookup(&www, RecordType::A, false, Default::default()))
+                .ok()?;
+            let address = lookup.iter().next()?.rdata().as_a().cloned();
+            address
+        });
+
+        if address == Some(expected) {
+            if expected == Ipv4Addr::new(10, 0, 0, 2) {
+                break;
+            }
+
+            // the master gets a newer serial, which is transferred on the next refresh
+            master_catalog.swap_zone(
+                origin.clone(),
+                Box::new(zone(2, Ipv4Addr::new(10, 0, 0, 2))),
+            );
+            expected = Ipv4Addr::new(10, 0, 0, 2);
+        }
+
+        assert!(Instant::now() < deadline, "zone not transferred");
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+#[test]
+fn test_secondary_expires() {
+    let mut io_loop = Runtime::new().unwrap();
+    let (master_catalog, master) = serve(
+        &mut io_loop,
+        zone_with_expire(1, Ipv4Addr::new(10, 0, 0, 1), 2),
+    );
+
+    let catalog = Catalog::new();
+    io_loop.spawn(refresh_secondary(transfer(master, None), catalog.clone()));
+
+    let origin = LowerName::from_str("example.com.").unwrap();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !catalog.contains(&origin) {
+        assert!(Instant::now() < deadline, "zone not transferred");
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    // the master stops serving the zone, the secondary can't refresh it until it expires
+    master_catalog.remove_zone(&origin);
+    let removed = Instant::now();
+    while catalog.contains(&origin) {
+        assert!(Instant::now() < deadline, "zone not expired");
+        thread::sleep(Duration::from_millis(100));
+    }
+    assert!(removed.elapsed() >= Duration::from_secs(1));
+}
+
+#[cfg(feature = "dnssec")]
+fn tsig_signer(key: &[u8]) -> TSigner {
+    TSigner::new(
+        key.to_vec(),
+        TsigAlgorithm::HmacSha256,
+        Name::from_str("transfer-key.example.com.").unwrap(),
+        300,
+    )
+    .unwrap()
+}
+
+#[cfg(feature = "dnssec")]
+#[test]
+fn test_tsig_signed_transfer() {
+    let mut io_loop = Runtime::new().unwrap();
+    let (_, master) = serve_with(
+        &mut io_loop,
+        zone(1, Ipv4Addr::new(10, 0, 0, 1)),
+        |server| {
+            let mut keyring = TsigKeyring::new();
+            keyring.add_key(tsig_signer(b"0123456789abcdef0123456789abcdef"));
+            server.set_tsig_keyring(keyring);
+        },
+    );
+
+    let signed =
+        transfer(master, None).with_tsig_signer(tsig_signer(b"0123456789abcdef0123456789abcdef"));
+    let authority = run(&mut io_loop, &signed, None)
+        .expect("transfer failed")
+        .expect("zone not transferred");
+    assert_eq!(www(&mut io_loop, &authority), Ipv4Addr::new(10, 0, 0, 1));
+
+    // the master doesn't verify the queries signed with another key
+    let other_key =
+        transfer(master, None).with_tsig_signer(tsig_signer(b"fedcba9876543210fedcba9876543210"));
+    assert!(run(&mut io_loop, &other_key, None).is_err());
+}
@@ -0,0 +1,10 @@
+#!/bin/bash -e
+
+set -x
+
+trust_dns_dir=$(dirname $0)/..
+cd ${trust_dns_dir:?}
+
+# Build the wire format for a target without std
+rustup target add thumbv7em-none-eabihf
+cargo build --manifest-path tests/no-std-tests/Cargo.toml --target thumbv7em-none-eabihf
@@ -0,0 +1,11 @@
+#!/bin/bash -e
+
+set -x
+
+trust_dns_dir=$(dirname $0)/..
+cd ${trust_dns_dir:?}
+
+# Check proto, with the fetch DNS-over-HTTPS client, for the browser and workers, the resolver
+#  does not build for wasm32 targets
+rustup target add wasm32-unknown-unknown
+cargo check --manifest-path crates/proto/Cargo.toml --target wasm32-unknown-unknown --features dns-over-https-fetch
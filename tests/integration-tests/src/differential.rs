@@ -0,0 +1,438 @@
+//! Differential testing of the decoding of messages against a reference rendering, e.g. by `dig`
+//!
+//! See `tests/test-data/differential/README.md` for the corpus.
+
+use std::fmt::{self, Display, Formatter};
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use trust_dns::op::{Message, MessageType, ResponseCode};
+use trust_dns::rr::{Name, Record};
+use trust_dns::serialize::txt::RecordReader;
+use trust_dns_proto::capture::{self, pcap, Direction};
+
+/// A field of the decoded message which differs from the reference
+#[derive(Debug, PartialEq)]
+pub struct Mismatch {
+    /// The field, e.g. `header.id` or `answer[0].ttl`
+    pub field: String,
+    /// The value of the decoded message
+    pub decoded: String,
+    /// The value of the reference
+    pub reference: String,
+}
+
+impl Display for Mismatch {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: decoded {}, reference {}",
+            self.field, self.decoded, self.reference
+        )
+    }
+}
+
+/// A message rendered by `dig`, with the header, EDNS and the records of each section
+#[derive(Debug, Default)]
+pub struct DigMessage {
+    header: Vec<(String, String)>,
+    edns: Vec<(String, String)>,
+    question: Vec<(String, String, String)>,
+    answer: String,
+    authority: String,
+    additional: String,
+}
+
+enum Section {
+    None,
+    Question,
+    Answer,
+    Authority,
+    Additional,
+}
+
+impl DigMessage {
+    /// Parses the output of `dig`, the default format with the comments of the sections
+    pub fn parse(text: &str) -> Self {
+        let mut dig = DigMessage::default();
+        let mut section = Section::None;
+
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(header) = strip(line, ";; ->>HEADER<<- ") {
+                // opcode: QUERY, status: NOERROR, id: 4660
+                for field in header.split(", ") {
+                    let mut pair = field.splitn(2, ": ");
+                    if let (Some(name), Some(value)) = (pair.next(), pair.next()) {
+                        dig.header.push((name.to_string(), value.to_string()));
+                    }
+                }
+                continue;
+            }
+
+            if let Some(flags) = strip(line, ";; flags:") {
+                // flags: qr rd ra; QUERY: 1, ANSWER: 1, AUTHORITY: 0, ADDITIONAL: 0
+                let mut parts = flags.splitn(2, ';');
+                let flags = parts.next().unwrap_or_default().trim();
+                dig.header.push(("flags".to_string(), flags.to_string()));
+                for count in parts.next().unwrap_or_default().split(',') {
+                    let mut pair = count.trim().splitn(2, ": ");
+                    if let (Some(name), Some(value)) = (pair.next(), pair.next()) {
+                        dig.header.push((name.to_lowercase(), value.to_string()));
+                    }
+                }
+                continue;
+            }
+
+            if let Some(edns) = strip(line, "; EDNS:") {
+                // EDNS: version: 0, flags: do; udp: 4096
+                for field in edns.split(|c| c == ',' || c == ';') {
+                    let mut pair = field.trim().splitn(2, ':');
+                    if let (Some(name), Some(value)) = (pair.next(), pair.next()) {
+                        dig.edns.push((name.to_string(), value.trim().to_string()));
+                    }
+                }
+                continue;
+            }
+
+            section = match line {
+                ";; QUESTION SECTION:" => Section::Question,
+                ";; ANSWER SECTION:" => Section::Answer,
+                ";; AUTHORITY SECTION:" => Section::Authority,
+                ";; ADDITIONAL SECTION:" => Section::Additional,
+                _ if line.starts_with(";;") => Section::None,
+                _ => {
+                    match section {
+                        Section::Question => {
+                            // ;www.example.com.		IN	A
+                            let mut fields = line.trim_start_matches(';').split_whitespace();
+                            if let (Some(name), Some(class), Some(rtype)) =
+                                (fields.next(), fields.next(), fields.next())
+                            {
+                                dig.question.push((
+                                    name.to_string(),
+                                    class.to_string(),
+                                    rtype.to_string(),
+                                ));
+                            }
+                        }
+                        Section::Answer => push_line(&mut dig.answer, line),
+                        Section::Authority => push_line(&mut dig.authority, line),
+                        Section::Additional => push_line(&mut dig.additional, line),
+                        Section::None => (),
+                    }
+                    continue;
+                }
+            };
+        }
+
+        dig
+    }
+
+    /// Compares the decoded message to the reference, returning the fields which differ
+    pub fn compare(&self, message: &Message) -> Vec<Mismatch> {
+        let mut mismatches = Vec::new();
+
+        for (name, reference) in &self.header {
+            let decoded = match name.as_str() {
+                "opcode" => format!("{:?}", message.op_code()).to_uppercase(),
+                "status" => status(message.response_code()),
+                "id" => message.id().to_string(),
+                "flags" => flags(message),
+                "query" => message.header().query_count().to_string(),
+                "answer" => message.header().answer_count().to_string(),
+                "authority" => message.header().name_server_count().to_string(),
+                "additional" => message.header().additional_count().to_string(),
+                _ => continue,
+            };
+            check(
+                &mut mismatches,
+                format!("header.{}", name),
+                decoded,
+                reference,
+            );
+        }
+
+        for (name, reference) in &self.edns {
+            let edns = message.edns();
+            let decoded = match name.as_str() {
+                "version" => edns.map(|edns| edns.version().to_string()),
+                "udp" => edns.map(|edns| edns.max_payload().to_string()),
+                "flags" => edns.map(|edns| if edns.dnssec_ok() { "do" } else { "" }.to_string()),
+                _ => continue,
+            };
+            check(
+                &mut mismatches,
+                format!("edns.{}", name),
+                decoded.unwrap_or_else(|| "<none>".to_string()),
+                reference,
+            );
+        }
+
+        let queries = message.queries();
+        for i in 0..queries.len().max(self.question.len()) {
+            let field = format!("question[{}]", i);
+            match (queries.get(i), self.question.get(i)) {
+                (Some(query), Some(&(ref name, ref class, ref rtype))) => {
+                    check(
+                        &mut mismatches,
+                        format!("{}.name", field),
+                        query.name().to_string(),
+                        name,
+                    );
+                    check(
+                        &mut mismatches,
+                        format!("{}.class", field),
+                        query.query_class().to_string(),
+                        class,
+                    );
+                    check(
+                        &mut mismatches,
+                        format!("{}.type", field),
+                        query.query_type().to_string(),
+                        rtype,
+                    );
+                }
+                (query, reference) => check(
+                    &mut mismatches,
+                    field,
+                    query.map_or_else(|| "<none>".to_string(), ToString::to_string),
+                    &reference.map_or_else(|| "<none>".to_string(), |q| format!("{:?}", q)),
+                ),
+            }
+        }
+
+        compare_records(&mut mismatches, "answer", message.answers(), &self.answer);
+        compare_records(
+            &mut mismatches,
+            "authority",
+            message.name_servers(),
+            &self.authority,
+        );
+        compare_records(
+            &mut mismatches,
+            "additional",
+            message.additionals(),
+            &self.additional,
+        );
+
+        mismatches
+    }
+}
+
+/// Decodes the last response of the capture, `*.txt` or `*.pcap`
+pub fn decode_response(capture: &Path) -> Result<Message, String> {
+    let file = File::open(capture).map_err(|e| format!("failed to open capture: {}", e))?;
+    let messages = match capture.extension().and_then(|ext| ext.to_str()) {
+        Some("pcap") => pcap::read_pcap(file),
+        _ => capture::read_captures(BufReader::new(file)),
+    }
+    .map_err(|e| format!("failed to read capture: {}", e))?;
+
+    let response = messages
+        .iter()
+        .rev()
+        .find(|captured| captured.direction() == Direction::Received)
+        .ok_or_else(|| "no response in capture".to_string())?;
+    response
+        .to_message()
+        .map_err(|e| format!("failed to decode {}: {}", response, e))
+}
+
+/// Compares the last response of the capture to its rendering by `dig`
+pub fn compare_case(capture: &Path, dig: &Path) -> Result<Vec<Mismatch>, String> {
+    let message = decode_response(capture)?;
+    let reference = fs::read_to_string(dig).map_err(|e| format!("failed to read dig: {}", e))?;
+    Ok(DigMessage::parse(&reference).compare(&message))
+}
+
+/// Compares every capture of the directory with a `<case>.dig` rendering
+///
+/// Returns the cases which were compared, and the failures, one line per mismatch.
+pub fn compare_corpus(dir: &Path) -> (Vec<PathBuf>, Vec<String>) {
+    let mut cases = Vec::new();
+    let mut failures = Vec::new();
+
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            failures.push(format!("{}: failed to read corpus: {}", dir.display(), e));
+            return (cases, failures);
+        }
+    };
+
+    let mut captures = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| match path.extension().and_then(|ext| ext.to_str()) {
+            Some("txt") | Some("pcap") => path.with_extension("dig").is_file(),
+            _ => false,
+        })
+        .collect::<Vec<_>>();
+    captures.sort();
+
+    for capture in captures {
+        match compare_case(&capture, &capture.with_extension("dig")) {
+            Ok(mismatches) => failures.extend(
+                mismatches
+                    .iter()
+                    .map(|mismatch| format!("{}: {}", capture.display(), mismatch)),
+            ),
+            Err(e) => failures.push(format!("{}: {}", capture.display(), e)),
+        }
+        cases.push(capture);
+    }
+
+    (cases, failures)
+}
+
+fn strip<'a>(line: &'a str, prefix: &str) -> Option<&'a str> {
+    if line.starts_with(prefix) {
+        Some(line[prefix.len()..].trim())
+    } else {
+        None
+    }
+}
+
+fn push_line(section: &mut String, line: &str) {
+    section.push_str(line);
+    section.push('\n');
+}
+
+fn check(mismatches: &mut Vec<Mismatch>, field: String, decoded: String, reference: &str) {
+    if decoded != reference {
+        mismatches.push(Mismatch {
+            field,
+            decoded,
+            reference: reference.to_string(),
+        });
+    }
+}
+
+/// The mnemonic of the response code, as printed by `dig`
+fn status(response_code: ResponseCode) -> String {
+    match response_code {
+        ResponseCode::NoError => "NOERROR".to_string(),
+        ResponseCode::FormErr => "FORMERR".to_string(),
+        ResponseCode::ServFail => "SERVFAIL".to_string(),
+        ResponseCode::NXDomain => "NXDOMAIN".to_string(),
+        ResponseCode::NotImp => "NOTIMP".to_string(),
+        ResponseCode::Refused => "REFUSED".to_string(),
+        ResponseCode::YXDomain => "YXDOMAIN".to_string(),
+        ResponseCode::YXRRSet => "YXRRSET".to_string(),
+        ResponseCode::NXRRSet => "NXRRSET".to_string(),
+        ResponseCode::NotAuth => "NOTAUTH".to_string(),
+        ResponseCode::NotZone => "NOTZONE".to_string(),
+        ResponseCode::BADVERS => "BADVERS".to_string(),
+        other => format!("RESERVED{}", u16::from(other)),
+    }
+}
+
+/// The flags of the header, in the order printed by `dig`
+fn flags(message: &Message) -> String {
+    let flags = [
+        ("qr", message.message_type() == MessageType::Response),
+        ("aa", message.authoritative()),
+        ("tc", message.truncated()),
+        ("rd", message.recursion_desired()),
+        ("ra", message.recursion_available()),
+        ("ad", message.authentic_data()),
+        ("cd", message.checking_disabled()),
+    ];
+
+    flags
+        .iter()
+        .filter(|&&(_, set)| set)
+        .map(|&(flag, _)| flag)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Compares the records of a section to the zone file lines of the reference
+fn compare_records(
+    mismatches: &mut Vec<Mismatch>,
+    section: &str,
+    records: &[Record],
+    reference: &str,
+) {
+    let reference_text = reference;
+    let parsed =
+        RecordReader::new(reference.as_bytes(), Some(Name::root())).collect::<Result<Vec<_>, _>>();
+    let reference = match parsed {
+        Ok(reference) => reference,
+        Err(e) => {
+            mismatches.push(Mismatch {
+                field: section.to_string(),
+                decoded: format!("{} records", records.len()),
+                reference: format!("failed to parse: {}", e),
+            });
+            return;
+        }
+    };
+
+    // dig renders one record per line, the TTL is read from its line as the parser of zone files
+    //  replaces the TTL of SOA records with their expire
+    let ttls = reference_text
+        .lines()
+        .map(|line| {
+            line.split_whitespace()
+                .nth(1)
+                .unwrap_or_default()
+                .to_string()
+        })
+        .collect::<Vec<_>>();
+
+    for i in 0..records.len().max(reference.len()) {
+        let field = format!("{}[{}]", section, i);
+        let (record, expected) = match (records.get(i), reference.get(i)) {
+            (Some(record), Some(expected)) => (record, expected),
+            (record, expected) => {
+                let none = || "<none>".to_string();
+                check(
+                    mismatches,
+                    field,
+                    record.map_or_else(none, |r| format!("{:?}", r)),
+                    &expected.map_or_else(none, |r| format!("{:?}", r)),
+                );
+                continue;
+            }
+        };
+
+        check(
+            mismatches,
+            format!("{}.name", field),
+            record.name().to_string(),
+            &expected.name().to_string(),
+        );
+        check(
+            mismatches,
+            format!("{}.ttl", field),
+            record.ttl().to_string(),
+            ttls.get(i).map_or("", String::as_str),
+        );
+        check(
+            mismatches,
+            format!("{}.class", field),
+            record.dns_class().to_string(),
+            &expected.dns_class().to_string(),
+        );
+        check(
+            mismatches,
+            format!("{}.type", field),
+            record.rr_type().to_string(),
+            &expected.rr_type().to_string(),
+        );
+        if record.rdata() != expected.rdata() {
+            mismatches.push(Mismatch {
+                field: format!("{}.rdata", field),
+                decoded: format!("{:?}", record.rdata()),
+                reference: format!("{:?}", expected.rdata()),
+            });
+        }
+    }
+}
@@ -36,9 +36,10 @@ use trust_dns_proto::xfer::{
 use trust_dns_proto::StreamHandle;
 
 use trust_dns_server::authority::{Catalog, MessageRequest, MessageResponse};
-use trust_dns_server::server::{Request, RequestHandler, ResponseHandler};
+use trust_dns_server::server::{Protocol, Request, RequestHandler, ResponseHandler};
 
 pub mod authority;
+pub mod differential;
 pub mod mock_client;
 pub mod tls_client_connection;
 
@@ -119,6 +120,41 @@ impl ResponseHandler for TestResponseHandler {
     }
 }
 
+/// Collects all the messages sent in response to a request, e.g. for zone transfers
+#[derive(Clone, Default)]
+pub struct TestMessagesResponseHandler {
+    messages: Arc<Mutex<Vec<Vec<u8>>>>,
+}
+
+impl TestMessagesResponseHandler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn into_messages(self) -> Vec<Message> {
+        self.messages
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|b| Message::from_vec(b).expect("could not decode message"))
+            .collect()
+    }
+}
+
+impl ResponseHandler for TestMessagesResponseHandler {
+    fn send_response(&self, response: MessageResponse) -> io::Result<()> {
+        let mut buf = Vec::with_capacity(512);
+        {
+            let mut encoder = BinEncoder::new(&mut buf);
+            response
+                .destructive_emit(&mut encoder)
+                .expect("could not encode");
+        }
+        self.messages.lock().unwrap().push(buf);
+        Ok(())
+    }
+}
+
 impl fmt::Display for TestClientStream {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         write!(formatter, "TestClientStream")
@@ -150,6 +186,8 @@ impl Stream for TestClientStream {
                 let request = Request {
                     message,
                     src: src_addr,
+                    protocol: Protocol::Tcp,
+                    tsig_key: None,
                 };
 
                 dbg!("catalog handling request");
@@ -0,0 +1,183 @@
+#[macro_use]
+extern crate lazy_static;
+extern crate tokio;
+extern crate trust_dns;
+extern crate trust_dns_proto;
+extern crate trust_dns_resolver;
+
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use tokio::runtime::Runtime;
+use trust_dns::client::{Client, SyncClient};
+use trust_dns::udp::UdpClientConnection;
+use trust_dns_proto::capture::{self, pcap, Capture, CapturedMessage, Direction};
+use trust_dns_proto::op::{Message, MessageBuilder, Query};
+use trust_dns_proto::rr::{DNSClass, Name, Record, RecordType};
+use trust_dns_proto::xfer::DnsHandle;
+use trust_dns_resolver::config::{NameServerConfig, Protocol, ResolverOpts};
+use trust_dns_resolver::name_server::NameServer;
+
+lazy_static! {
+    // the capture is set for the whole process, tests setting it can't run concurrently
+    static ref CAPTURE_LOCK: Mutex<()> = Mutex::new(());
+}
+
+fn read_capture_file(path: &Path) -> Vec<CapturedMessage> {
+    let file = File::open(path).expect("failed to open capture");
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("pcap") => pcap::read_pcap(file).expect("failed to read pcap"),
+        _ => capture::read_captures(BufReader::new(file)).expect("failed to read capture"),
+    }
+}
+
+/// Asserts that all the messages decode, and that all the responses answer a query
+fn replay(messages: &[CapturedMessage]) {
+    let mut queries = HashSet::new();
+    for captured in messages {
+        let message = captured
+            .to_message()
+            .unwrap_or_else(|e| panic!("failed to decode {}: {}", captured, e));
+
+        match captured.direction() {
+            Direction::Sent => {
+                queries.insert((captured.peer(), message.id()));
+            }
+            Direction::Received => assert!(
+                queries.contains(&(captured.peer(), message.id())),
+                "response without a query: {}",
+                captured
+            ),
+        }
+    }
+}
+
+#[test]
+fn test_replay_captures() {
+    let captures = Path::new(env!("CARGO_MANIFEST_DIR")).join("../test-data/captures");
+
+    let mut replayed = 0;
+    for entry in fs::read_dir(&captures).expect("failed to read the captures") {
+        let path = entry.expect("failed to read capture").path();
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("pcap") | Some("txt") => (),
+            _ => continue,
+        }
+
+        let messages = read_capture_file(&path);
+        assert!(!messages.is_empty(), "no messages in {}", path.display());
+        replay(&messages);
+        replayed += 1;
+    }
+
+    assert!(replayed > 0);
+}
+
+/// Answers all the queries with 127.0.0.1, until no query is received for a second
+fn answer_udp(socket: UdpSocket) {
+    socket
+        .set_read_timeout(Some(Duration::from_secs(1)))
+        .unwrap();
+
+    let mut buf = [0u8; 512];
+    while let Ok((len, src)) = socket.recv_from(&mut buf) {
+        let request = Message::from_vec(&buf[..len]).expect("failed to decode request");
+        let name = request.queries()[0].name().clone();
+
+        let response = MessageBuilder::response_to(&request)
+            .recursion_available(true)
+            .add_answer(Record::a(name, 300, Ipv4Addr::new(127, 0, 0, 1)))
+            .build()
+            .unwrap();
+        socket.send_to(&response.to_vec().unwrap(), src).unwrap();
+    }
+}
+
+fn spawn_server() -> SocketAddr {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = socket.local_addr().unwrap();
+    thread::spawn(move || answer_udp(socket));
+    addr
+}
+
+/// The messages exchanged with the server, other tests may be capturing at the same time
+fn captured_with(capture: &Capture, server: SocketAddr) -> Vec<CapturedMessage> {
+    capture
+        .messages()
+        .into_iter()
+        .filter(|message| message.peer() == server)
+        .collect()
+}
+
+#[test]
+fn test_capture_client() {
+    let _lock = CAPTURE_LOCK.lock().unwrap();
+    let server = spawn_server();
+    let capture = Capture::new();
+    capture::set_capture(Some(capture.clone()));
+
+    let client = SyncClient::new(UdpClientConnection::new(server).unwrap());
+    let name = Name::from_str("www.example.com.").unwrap();
+    let response = client
+        .query(&name, DNSClass::IN, RecordType::A)
+        .expect("query failed");
+    assert_eq!(response.answers().len(), 1);
+
+    capture::set_capture(None);
+    let messages = captured_with(&capture, server);
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[0].direction(), Direction::Sent);
+    assert_eq!(messages[1].direction(), Direction::Received);
+    assert_eq!(
+        messages[1].to_message().unwrap().answers(),
+        response.answers()
+    );
+
+    // the written capture replays to the same messages
+    let mut text = Vec::new();
+    capture.write_to(&mut text).unwrap();
+    let read = capture::read_captures(&text[..]).unwrap();
+    assert_eq!(read, capture.messages());
+    replay(&messages);
+}
+
+#[test]
+fn test_capture_resolver() {
+    let _lock = CAPTURE_LOCK.lock().unwrap();
+    let server = spawn_server();
+    let capture = Capture::new();
+    capture::set_capture(Some(capture.clone()));
+
+    // the name servers of the resolver, the lookups and their cache are above the capture
+    let mut name_server = NameServer::new(
+        NameServerConfig {
+            socket_addr: server,
+            protocol: Protocol::Udp,
+            tls_dns_name: None,
+        },
+        ResolverOpts::default(),
+    );
+    let name = Name::from_str("www.example.com.").unwrap();
+    let request = MessageBuilder::query(Query::query(name, RecordType::A))
+        .build()
+        .unwrap();
+
+    let mut runtime = Runtime::new().unwrap();
+    let response = runtime
+        .block_on(name_server.send(request))
+        .expect("query failed");
+    assert_eq!(response.answers().len(), 1);
+
+    capture::set_capture(None);
+    let messages = captured_with(&capture, server);
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[1].direction(), Direction::Received);
+    replay(&messages);
+}
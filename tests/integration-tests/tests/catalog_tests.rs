@@ -5,6 +5,7 @@ extern crate trust_dns_server;
 
 use std::net::*;
 use std::str::FromStr;
+use std::sync::{Arc, Mutex};
 
 use futures::Future;
 
@@ -13,7 +14,11 @@ use trust_dns::rr::rdata::*;
 use trust_dns::rr::*;
 use trust_dns::serialize::binary::{BinDecodable, BinEncodable};
 
-use trust_dns_server::authority::{Authority, Catalog, MessageRequest, ZoneType};
+use trust_dns_server::authority::{
+    Authority, Catalog, MessageRequest, MinimalResponses, SelectionContext, UpdateAudit,
+    ZoneChange, ZoneType,
+};
+use trust_dns_server::server::{CookieSecrets, Protocol, Request, RequestHandler};
 use trust_dns_server::store::in_memory::InMemoryAuthority;
 
 use trust_dns_integration::authority::create_example;
@@ -473,6 +478,64 @@ fn test_cname_additionals() {
     );
 }
 
+#[test]
+fn test_minimal_responses() {
+    let mut example = create_example();
+    let origin = example.origin().clone();
+    example.upsert(
+        Record::from_rdata(
+            origin.clone().into(),
+            86400,
+            RData::MX(MX::new(10, Name::from_str("www.example.com.").unwrap())),
+        ),
+        0,
+    );
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), Box::new(example));
+
+    let lookup = |catalog: &Catalog, name: &str, query_type: RecordType| {
+        let mut question: Message = Message::new();
+        question.add_query(Query::query(Name::from_str(name).unwrap(), query_type));
+        let question_req = MessageRequest::from_bytes(&question.to_bytes().unwrap()).unwrap();
+
+        let response_handler = TestResponseHandler::new();
+        catalog
+            .lookup(question_req, None, response_handler.clone())
+            .wait()
+            .unwrap();
+        response_handler.into_message().wait().unwrap()
+    };
+
+    // by default all the records are returned
+    let result = lookup(&catalog, "example.com.", RecordType::MX);
+    assert_eq!(result.answers().len(), 1);
+    assert_eq!(result.name_servers().len(), 2);
+    assert!(!result.additionals().is_empty());
+
+    catalog.set_minimal_responses(MinimalResponses::NoAuth);
+    let result = lookup(&catalog, "example.com.", RecordType::MX);
+    assert_eq!(result.answers().len(), 1);
+    assert!(result.name_servers().is_empty());
+    assert!(!result.additionals().is_empty());
+
+    catalog.set_zone_minimal_responses(origin.clone(), MinimalResponses::Yes);
+    let result = lookup(&catalog, "example.com.", RecordType::MX);
+    assert_eq!(result.answers().len(), 1);
+    assert!(result.name_servers().is_empty());
+    assert!(result.additionals().is_empty());
+
+    // the end of a CNAME chain answers the query
+    let result = lookup(&catalog, "alias.example.com.", RecordType::A);
+    assert_eq!(result.answers()[0].rr_type(), RecordType::CNAME);
+    assert!(!result.additionals().is_empty());
+
+    // negative responses are complete
+    let result = lookup(&catalog, "nx.example.com.", RecordType::A);
+    assert_eq!(result.response_code(), ResponseCode::NXDomain);
+    assert_eq!(result.name_servers()[0].rr_type(), RecordType::SOA);
+}
+
 #[test]
 fn test_multiple_cname_additionals() {
     let example = create_example();
@@ -529,3 +592,588 @@ fn test_multiple_cname_additionals() {
         &RData::A(Ipv4Addr::new(93, 184, 216, 34))
     );
 }
+
+#[test]
+fn test_answer_selector() {
+    let mut test = create_test();
+    let www_name: Name = Name::parse("www.test.com.", None).unwrap();
+    test.upsert(
+        Record::new()
+            .set_name(www_name.clone())
+            .set_ttl(86400)
+            .set_rr_type(RecordType::A)
+            .set_dns_class(DNSClass::IN)
+            .set_rdata(RData::A(Ipv4Addr::new(10, 0, 0, 1)))
+            .clone(),
+        0,
+    );
+    let test_origin = test.origin().clone();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(test_origin.clone(), Box::new(test));
+
+    // only return the addresses in the same /8 as the client, otherwise all addresses
+    catalog.set_answer_selector(
+        test_origin,
+        Arc::new(|context: &SelectionContext, answers: Vec<Record>| {
+            let client = match context.src() {
+                Some(SocketAddr::V4(src)) => src.ip().octets()[0],
+                _ => return answers,
+            };
+
+            let local = answers
+                .iter()
+                .filter(|r| r.rdata().as_a().map(|a| a.octets()[0]) == Some(client))
+                .cloned()
+                .collect::<Vec<_>>();
+
+            if local.is_empty() {
+                answers
+            } else {
+                local
+            }
+        }),
+    );
+
+    let mut question: Message = Message::new();
+    let mut query: Query = Query::new();
+    query.set_name(www_name.clone());
+    question.add_query(query);
+    let question_bytes = question.to_bytes().unwrap();
+
+    let lookup = |src: SocketAddr| {
+        let request = Request {
+            message: MessageRequest::from_bytes(&question_bytes).unwrap(),
+            src,
+            protocol: Protocol::Udp,
+            tsig_key: None,
+        };
+
+        let response_handler = TestResponseHandler::new();
+        catalog
+            .handle_request(request, response_handler.clone())
+            .wait()
+            .unwrap();
+        response_handler.into_message().wait().unwrap()
+    };
+
+    let result = lookup(SocketAddr::from(([10, 1, 2, 3], 53)));
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert_eq!(result.answers().len(), 1);
+    assert_eq!(
+        result.answers()[0].rdata(),
+        &RData::A(Ipv4Addr::new(10, 0, 0, 1))
+    );
+
+    let result = lookup(SocketAddr::from(([94, 1, 2, 3], 53)));
+    assert_eq!(result.answers().len(), 1);
+    assert_eq!(
+        result.answers()[0].rdata(),
+        &RData::A(Ipv4Addr::new(94, 184, 216, 34))
+    );
+
+    let result = lookup(SocketAddr::from(([192, 168, 0, 1], 53)));
+    assert_eq!(result.answers().len(), 2);
+
+    // lookups without a client address are passed through the selector as well
+    let response_handler = TestResponseHandler::new();
+    catalog
+        .lookup(
+            MessageRequest::from_bytes(&question_bytes).unwrap(),
+            None,
+            response_handler.clone(),
+        )
+        .wait()
+        .unwrap();
+    let result = response_handler.into_message().wait().unwrap();
+    assert_eq!(result.answers().len(), 2);
+}
+
+#[test]
+fn test_zone_swap_and_remove() {
+    let example = create_example();
+    let origin = example.origin().clone();
+
+    let catalog: Catalog = Catalog::new();
+    let changes = Arc::new(Mutex::new(Vec::new()));
+    let listener_changes = Arc::clone(&changes);
+    catalog.add_zone_listener(Arc::new(move |name: &LowerName, change: ZoneChange| {
+        listener_changes
+            .lock()
+            .unwrap()
+            .push((name.clone(), change))
+    }));
+
+    // the server holds a clone of the catalog, changes are made through another
+    let server_catalog = catalog.clone();
+
+    let mut question: Message = Message::new();
+    let mut query: Query = Query::new();
+    query.set_name(Name::parse("www.example.com.", None).unwrap());
+    question.add_query(query);
+    let question_bytes = question.to_bytes().unwrap();
+
+    let lookup = || {
+        let request = Request {
+            message: MessageRequest::from_bytes(&question_bytes).unwrap(),
+            src: SocketAddr::from(([127, 0, 0, 1], 53)),
+            protocol: Protocol::Udp,
+            tsig_key: None,
+        };
+
+        let response_handler = TestResponseHandler::new();
+        server_catalog
+            .handle_request(request, response_handler.clone())
+            .wait()
+            .unwrap();
+        response_handler.into_message().wait().unwrap()
+    };
+
+    assert_eq!(lookup().response_code(), ResponseCode::NXDomain);
+
+    assert!(catalog
+        .swap_zone(origin.clone(), Box::new(example))
+        .is_none());
+    let result = lookup();
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert_eq!(
+        result.answers()[0].rdata(),
+        &RData::A(Ipv4Addr::new(93, 184, 216, 34))
+    );
+
+    // replace the zone, a snapshot taken before still sees the previous authority
+    let previous = server_catalog.find(&origin).unwrap();
+    let mut replacement = create_example();
+    replacement.upsert(
+        Record::new()
+            .set_name(Name::parse("www.example.com.", None).unwrap())
+            .set_ttl(86400)
+            .set_rr_type(RecordType::A)
+            .set_dns_class(DNSClass::IN)
+            .set_rdata(RData::A(Ipv4Addr::new(192, 0, 2, 1)))
+            .clone(),
+        0,
+    );
+    assert!(catalog
+        .swap_zone(origin.clone(), Box::new(replacement))
+        .is_some());
+    assert!(!Arc::ptr_eq(
+        &previous,
+        &server_catalog.find(&origin).unwrap()
+    ));
+
+    let result = lookup();
+    assert_eq!(result.answers().len(), 2);
+    assert_eq!(catalog.zone_names(), vec![origin.clone()]);
+
+    assert!(catalog.remove_zone(&origin).is_some());
+    assert!(catalog.remove_zone(&origin).is_none());
+    assert!(!server_catalog.contains(&origin));
+    assert_eq!(lookup().response_code(), ResponseCode::NXDomain);
+
+    assert_eq!(
+        *changes.lock().unwrap(),
+        vec![
+            (origin.clone(), ZoneChange::Added),
+            (origin.clone(), ZoneChange::Replaced),
+            (origin, ZoneChange::Removed),
+        ]
+    );
+}
+
+#[test]
+fn test_update_audit() {
+    let example = create_example();
+    let origin = example.origin().clone();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin, Box::new(example));
+
+    let audits = Arc::new(Mutex::new(Vec::new()));
+    let sink_audits = Arc::clone(&audits);
+    catalog.add_update_audit_sink(Arc::new(move |audit: &UpdateAudit| {
+        sink_audits.lock().unwrap().push(audit.clone())
+    }));
+
+    let src = SocketAddr::from(([127, 0, 0, 1], 5353));
+    let update = |message: Message| {
+        let request = Request {
+            message: MessageRequest::from_bytes(&message.to_bytes().unwrap()).unwrap(),
+            src,
+            protocol: Protocol::Udp,
+            tsig_key: None,
+        };
+
+        let response_handler = TestResponseHandler::new();
+        catalog
+            .handle_request(request, response_handler.clone())
+            .wait()
+            .unwrap();
+        response_handler.into_message().wait().unwrap()
+    };
+
+    let address = |name: &str| {
+        Record::from_rdata(
+            Name::from_str(name).unwrap(),
+            300,
+            RData::A(Ipv4Addr::new(192, 0, 2, 1)),
+        )
+    };
+
+    // the in memory authority doesn't support updates
+    let mut message = UpdateBuilder::new(Name::from_str("example.com.").unwrap())
+        .add(address("www.example.com."))
+        .build();
+    message.set_id(10);
+    assert_eq!(update(message).response_code(), ResponseCode::NotImp);
+
+    let mut message = UpdateBuilder::new(Name::from_str("example.net.").unwrap())
+        .add(address("www.example.net."))
+        .build();
+    message.set_id(11);
+    assert_eq!(update(message).response_code(), ResponseCode::NXDomain);
+
+    let audits = audits.lock().unwrap();
+    assert_eq!(audits.len(), 2);
+
+    assert_eq!(audits[0].id(), 10);
+    assert_eq!(audits[0].response_code(), ResponseCode::NotImp);
+    assert!(!audits[0].is_accepted());
+    assert_eq!(
+        audits[0].zone(),
+        Some(&Name::from_str("example.com.").unwrap())
+    );
+    assert_eq!(audits[0].src(), Some(src));
+    assert!(audits[0].signer().is_none());
+    assert!(audits[0].diff().is_empty());
+    assert_eq!(
+        audits[0].to_string(),
+        "update 10 rejected: Not Implemented zone: example.com. src: 127.0.0.1:5353"
+    );
+
+    assert_eq!(audits[1].id(), 11);
+    assert_eq!(audits[1].response_code(), ResponseCode::NXDomain);
+    assert_eq!(
+        audits[1].zone(),
+        Some(&Name::from_str("example.net.").unwrap())
+    );
+}
+
+#[test]
+fn test_axfr_multiple_messages() {
+    let mut test = create_test();
+    test.set_allow_axfr(true);
+    let origin = test.origin().clone();
+    let serial = test.serial();
+
+    // enough records to require more than one message
+    for i in 0..2000u32 {
+        test.upsert(
+            Record::new()
+                .set_name(Name::parse(&format!("host-{}.test.com.", i), None).unwrap())
+                .set_ttl(86400)
+                .set_rr_type(RecordType::TXT)
+                .set_dns_class(DNSClass::IN)
+                .set_rdata(RData::TXT(TXT::new(vec![format!("host number {}", i)])))
+                .clone(),
+            serial,
+        );
+    }
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), Box::new(test));
+
+    let mut query: Query = Query::new();
+    query.set_name(origin.clone().into());
+    query.set_query_type(RecordType::AXFR);
+
+    let mut question: Message = Message::new();
+    question.set_id(1234);
+    question.add_query(query);
+    let question_bytes = question.to_bytes().unwrap();
+
+    let transfer = |protocol: Protocol| {
+        let request = Request {
+            message: MessageRequest::from_bytes(&question_bytes).unwrap(),
+            src: SocketAddr::from(([127, 0, 0, 1], 53)),
+            protocol,
+            tsig_key: None,
+        };
+
+        let response_handler = TestMessagesResponseHandler::new();
+        catalog
+            .handle_request(request, response_handler.clone())
+            .wait()
+            .unwrap();
+        response_handler.into_messages()
+    };
+
+    let messages = transfer(Protocol::Tcp);
+    assert!(messages.len() > 1);
+
+    let mut answers = Vec::new();
+    for (idx, message) in messages.iter().enumerate() {
+        assert_eq!(message.id(), 1234);
+        assert_eq!(message.response_code(), ResponseCode::NoError);
+        assert!(!message.truncated());
+        assert!(message.to_bytes().unwrap().len() <= 16_384);
+        assert_eq!(message.queries().len(), if idx == 0 { 1 } else { 0 });
+        answers.extend(message.answers().iter().cloned());
+    }
+
+    assert_eq!(answers.first().unwrap().rr_type(), RecordType::SOA);
+    assert_eq!(answers.last().unwrap().rr_type(), RecordType::SOA);
+    assert_eq!(
+        answers
+            .iter()
+            .filter(|r| r.rr_type() == RecordType::TXT)
+            .count(),
+        2000
+    );
+
+    // transfers are only allowed over TCP
+    let messages = transfer(Protocol::Udp);
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].response_code(), ResponseCode::Refused);
+    assert!(messages[0].answers().is_empty());
+}
+
+fn edns_request(edns: Edns) -> Message {
+    let mut query: Query = Query::new();
+    query.set_name(Name::from_str("www.example.com.").unwrap());
+    query.set_query_type(RecordType::A);
+
+    let mut question: Message = Message::new();
+    question.add_query(query);
+    question.set_edns(edns);
+
+    raw_request(&question)
+}
+
+#[test]
+fn test_edns_dnssec_ok_echoed() {
+    let mut edns = Edns::new();
+    edns.set_max_payload(4096);
+
+    let result = edns_request(edns.clone());
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert!(!result.edns().expect("no edns").dnssec_ok());
+
+    edns.set_dnssec_ok(true);
+    let result = edns_request(edns);
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert!(result.edns().expect("no edns").dnssec_ok());
+}
+
+#[test]
+fn test_edns_badvers() {
+    let mut edns = Edns::new();
+    edns.set_version(1);
+
+    let result = edns_request(edns);
+    let response_edns = result.edns().expect("no edns");
+
+    // BADVERS and BADSIG share the code 16
+    assert_eq!(u16::from(result.response_code()), 16);
+    assert_eq!(response_edns.rcode_high(), 1);
+    assert_eq!(response_edns.version(), 0);
+    assert!(result.answers().is_empty());
+}
+
+#[test]
+fn test_server_cookies() {
+    use trust_dns::rr::rdata::opt::{Cookie, EdnsCode, EdnsOption};
+
+    let example = create_example();
+    let origin = example.origin().clone();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin, Box::new(example));
+    catalog.set_cookie_secrets(Arc::new(CookieSecrets::random()));
+
+    let lookup = |cookie: Cookie| {
+        let mut edns = Edns::new();
+        edns.set_option(EdnsOption::Cookie(cookie));
+
+        let mut question: Message = Message::new();
+        question.add_query(Query::query(
+            Name::from_str("www.example.com.").unwrap(),
+            RecordType::A,
+        ));
+        question.set_edns(edns);
+
+        let request = Request {
+            message: MessageRequest::from_bytes(&question.to_bytes().unwrap()).unwrap(),
+            src: SocketAddr::from(([192, 0, 2, 1], 53)),
+            protocol: Protocol::Udp,
+            tsig_key: None,
+        };
+
+        let response_handler = TestResponseHandler::new();
+        catalog
+            .handle_request(request, response_handler.clone())
+            .wait()
+            .unwrap();
+        let response = response_handler.into_message().wait().unwrap();
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+
+        let edns = response.edns().expect("no edns");
+        match edns.option(EdnsCode::Cookie) {
+            Some(EdnsOption::Cookie(cookie)) => cookie.clone(),
+            option => panic!("unexpected cookie: {:?}", option),
+        }
+    };
+
+    // the server cookie is learnt with the first request, and returned as is while it's fresh
+    let cookie = lookup(Cookie::new([1; 8], vec![]).unwrap());
+    assert_eq!(cookie.client(), &[1; 8]);
+    assert_eq!(cookie.server().len(), 16);
+    assert_eq!(lookup(cookie.clone()), cookie);
+
+    // an invalid server cookie is replaced
+    let forged = Cookie::new([1; 8], vec![1; 16]).unwrap();
+    let cookie = lookup(forged.clone());
+    assert_ne!(cookie, forged);
+    assert_eq!(cookie.server().len(), 16);
+}
+
+fn raw_request(question: &Message) -> Message {
+    let example = create_example();
+    let origin = example.origin().clone();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), Box::new(example));
+
+    let request = Request {
+        message: MessageRequest::from_bytes(&question.to_bytes().unwrap()).unwrap(),
+        src: SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 53),
+        protocol: Protocol::Udp,
+        tsig_key: None,
+    };
+
+    let response_handler = TestResponseHandler::new();
+    catalog
+        .handle_request(request, response_handler.clone())
+        .wait()
+        .unwrap();
+    response_handler.into_message().wait().unwrap()
+}
+
+#[test]
+fn test_query_count_form_err() {
+    let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+
+    let mut question: Message = Message::new();
+    question.add_query(query.clone());
+    question.add_query(query.clone());
+    let result = raw_request(&question);
+    assert_eq!(result.response_code(), ResponseCode::FormErr);
+    assert_eq!(result.queries(), &[query.clone(), query.clone()]);
+    assert!(result.answers().is_empty());
+
+    let result = raw_request(&Message::new());
+    assert_eq!(result.response_code(), ResponseCode::FormErr);
+    assert!(result.queries().is_empty());
+
+    question.take_queries();
+    question.add_query(query);
+    let result = raw_request(&question);
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert!(!result.answers().is_empty());
+}
+
+#[test]
+fn test_op_code_not_imp() {
+    let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+
+    for op_code in &[
+        OpCode::Status,
+        OpCode::Notify,
+        OpCode::Unknown(1),
+        OpCode::Unknown(6),
+    ] {
+        let mut question: Message = Message::new();
+        question.set_op_code(*op_code);
+        question.add_query(query.clone());
+
+        let result = raw_request(&question);
+        assert_eq!(result.response_code(), ResponseCode::NotImp);
+        assert_eq!(result.op_code(), *op_code);
+        assert_eq!(result.queries().to_vec(), vec![query.clone()]);
+    }
+}
+
+#[cfg(feature = "dnssec")]
+fn chain_request(protocol: Protocol) -> Message {
+    use trust_dns::rr::rdata::opt::EdnsOption;
+    use trust_dns_integration::authority::create_secure_example;
+
+    let mut edns = Edns::new();
+    edns.set_dnssec_ok(true);
+    edns.set_option(EdnsOption::Chain(Name::root()));
+
+    let mut question: Message = Message::new();
+    question.add_query(Query::query(
+        Name::from_str("www.example.com.").unwrap(),
+        RecordType::A,
+    ));
+    question.set_edns(edns);
+
+    let example = create_secure_example();
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(example.origin().clone(), Box::new(example));
+
+    let request = Request {
+        message: MessageRequest::from_bytes(&question.to_bytes().unwrap()).unwrap(),
+        src: SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 53),
+        protocol,
+        tsig_key: None,
+    };
+
+    let response_handler = TestResponseHandler::new();
+    catalog
+        .handle_request(request, response_handler.clone())
+        .wait()
+        .unwrap();
+    response_handler.into_message().wait().unwrap()
+}
+
+#[test]
+#[cfg(feature = "dnssec")]
+fn test_edns_chain() {
+    use trust_dns::rr::rdata::opt::{EdnsCode, EdnsOption};
+    use trust_dns::rr::rdata::{DNSSECRData, DNSSECRecordType};
+
+    let dnskey = RecordType::DNSSEC(DNSSECRecordType::DNSKEY);
+
+    let response = chain_request(Protocol::Tcp);
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert!(!response.answers().is_empty());
+    assert_eq!(
+        response.edns().expect("no edns").option(EdnsCode::Chain),
+        Some(&EdnsOption::Chain(Name::root()))
+    );
+
+    // the zone keys and their signatures are in the authority section
+    let chain = response.name_servers();
+    assert!(chain
+        .iter()
+        .any(|r| r.rr_type() == dnskey && *r.name() == Name::from_str("example.com.").unwrap()));
+    assert!(chain.iter().any(|r| match r.rdata() {
+        RData::DNSSEC(DNSSECRData::SIG(sig)) => sig.type_covered() == dnskey,
+        _ => false,
+    }));
+
+    // not over UDP
+    let response = chain_request(Protocol::Udp);
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert!(response
+        .edns()
+        .expect("no edns")
+        .option(EdnsCode::Chain)
+        .is_none());
+    assert!(response
+        .name_servers()
+        .iter()
+        .all(|r| r.rr_type() != dnskey));
+}
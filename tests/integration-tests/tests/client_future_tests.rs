@@ -28,6 +28,8 @@ use trust_dns::client::{BasicClientHandle, ClientFuture, ClientHandle};
 use trust_dns::error::ClientErrorKind;
 use trust_dns::op::ResponseCode;
 #[cfg(feature = "dnssec")]
+use trust_dns::op::UpdateBuilder;
+#[cfg(feature = "dnssec")]
 use trust_dns::rr::dnssec::Signer;
 #[cfg(feature = "dnssec")]
 use trust_dns::rr::Record;
@@ -870,6 +872,66 @@ fn test_delete_all() {
     assert_eq!(result.answers().len(), 0);
 }
 
+#[cfg(feature = "dnssec")]
+#[test]
+fn test_update_builder() {
+    let mut io_loop = Runtime::new().unwrap();
+    let (bg, mut client, origin) = create_sig0_ready_client(&mut io_loop);
+
+    let name = Name::from_str("new.example.com").unwrap();
+    let record = Record::from_rdata(
+        name.clone(),
+        Duration::minutes(5).num_seconds() as u32,
+        RData::A(Ipv4Addr::new(100, 10, 100, 10)),
+    );
+
+    // the name isn't in use, the record is created
+    io_loop.spawn(bg);
+    let result = io_loop
+        .block_on(
+            client.update(
+                UpdateBuilder::new(origin.clone())
+                    .require_name_not_in_use(name.clone())
+                    .add(record.clone()),
+            ),
+        )
+        .expect("update failed");
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+
+    // the prerequisite doesn't hold anymore, nothing is changed
+    let result = io_loop
+        .block_on(
+            client.update(
+                UpdateBuilder::new(origin.clone())
+                    .require_absent(name.clone(), RecordType::A)
+                    .delete_rrset(name.clone(), RecordType::A),
+            ),
+        )
+        .expect("update failed");
+    assert_eq!(result.response_code(), ResponseCode::YXRRSet);
+
+    // replace the address of the existing rrset
+    let mut new_record = record.clone();
+    new_record.set_rdata(RData::A(Ipv4Addr::new(101, 11, 101, 11)));
+    let result = io_loop
+        .block_on(
+            client.update(
+                UpdateBuilder::new(origin.clone())
+                    .require_exists(name.clone(), RecordType::A)
+                    .delete_record(record.clone())
+                    .add(new_record.clone()),
+            ),
+        )
+        .expect("update failed");
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+
+    let result = io_loop
+        .block_on(client.query(name.clone(), DNSClass::IN, RecordType::A))
+        .expect("query failed");
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert_eq!(result.answers(), &[new_record]);
+}
+
 fn test_timeout_query<R>(mut client: BasicClientHandle<R>, mut io_loop: Runtime)
 where
     R: Future<Item = DnsResponse, Error = ProtoError> + 'static + Send,
@@ -0,0 +1,56 @@
+extern crate trust_dns_integration;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use trust_dns_integration::differential::{self, DigMessage};
+
+fn corpus() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../test-data/differential")
+}
+
+fn assert_corpus(dir: &Path) {
+    let (cases, failures) = differential::compare_corpus(dir);
+    assert!(!cases.is_empty(), "no cases in {}", dir.display());
+    assert!(
+        failures.is_empty(),
+        "{} mismatches:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn test_differential_corpus() {
+    assert_corpus(&corpus());
+}
+
+/// Compares a corpus outside of the repository, e.g. captured from a production resolver
+#[test]
+fn test_differential_external_corpus() {
+    match env::var_os("DIFFERENTIAL_CORPUS") {
+        Some(dir) => assert_corpus(Path::new(&dir)),
+        None => println!("DIFFERENTIAL_CORPUS is not set, skipping"),
+    }
+}
+
+#[test]
+fn test_differential_reports_mismatch() {
+    let dig = fs::read_to_string(corpus().join("example_a.dig")).unwrap();
+    let dig = dig.replace(
+        "www.example.com.\t300\tIN\tA",
+        "www.example.com.\t299\tIN\tA",
+    );
+
+    let message = differential::decode_response(&corpus().join("example_a.txt"))
+        .expect("failed to decode the response");
+    let mismatches = DigMessage::parse(&dig).compare(&message);
+
+    assert_eq!(mismatches.len(), 1, "{:?}", mismatches);
+    assert_eq!(mismatches[0].field, "answer[0].ttl");
+    assert_eq!(
+        mismatches[0].to_string(),
+        "answer[0].ttl: decoded 300, reference 299"
+    );
+}
@@ -0,0 +1,44 @@
+extern crate trust_dns;
+extern crate trust_dns_proto;
+
+use std::fs;
+use std::path::Path;
+
+/// Runs the fuzz target over all the inputs of its corpus, see crates/proto/fuzz/README.md
+fn run_corpus(target: &str, fuzz: fn(&[u8])) {
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../../crates/proto/fuzz/corpus")
+        .join(target);
+
+    let mut inputs = 0;
+    for entry in fs::read_dir(&corpus).expect("failed to read the corpus") {
+        let path = entry.expect("failed to read input").path();
+        let data = fs::read(&path).expect("failed to read input");
+
+        println!("{}", path.display());
+        fuzz(&data);
+        inputs += 1;
+    }
+
+    assert!(inputs > 0, "no inputs in {}", corpus.display());
+}
+
+#[test]
+fn test_message_corpus() {
+    run_corpus("message", trust_dns_proto::fuzzing::message);
+}
+
+#[test]
+fn test_name_corpus() {
+    run_corpus("name", trust_dns_proto::fuzzing::name);
+}
+
+#[test]
+fn test_zone_file_corpus() {
+    run_corpus("zone_file", trust_dns::fuzzing::zone_file);
+}
+
+#[test]
+fn test_dnssec_verify_corpus() {
+    run_corpus("dnssec_verify", trust_dns_proto::fuzzing::dnssec_verify);
+}
@@ -0,0 +1,148 @@
+extern crate futures;
+extern crate trust_dns;
+extern crate trust_dns_integration;
+extern crate trust_dns_server;
+
+use std::net::*;
+use std::sync::Arc;
+
+use futures::Future;
+
+use trust_dns::op::*;
+use trust_dns::rr::rdata::opt::EdnsOption;
+use trust_dns::rr::*;
+use trust_dns::serialize::binary::{BinDecodable, BinEncodable};
+
+use trust_dns_server::authority::geoip::{GeoIpDatabase, GeoIpSelector, GeoLocation};
+use trust_dns_server::authority::{Authority, Catalog, MessageRequest};
+use trust_dns_server::server::{Protocol, Request, RequestHandler};
+
+use trust_dns_integration::authority::create_example;
+use trust_dns_integration::*;
+
+/// Locates 10.0.0.0/8 in Germany, 11.0.0.0/8 in Japan, and everything else nowhere
+struct TestDatabase;
+
+impl GeoIpDatabase for TestDatabase {
+    fn locate(&self, address: IpAddr) -> Option<GeoLocation> {
+        let (country, continent) = match address {
+            IpAddr::V4(ip) if ip.octets()[0] == 10 => ("DE", "EU"),
+            IpAddr::V4(ip) if ip.octets()[0] == 11 => ("JP", "AS"),
+            _ => return None,
+        };
+
+        Some(GeoLocation {
+            country: Some(country.to_string()),
+            continent: Some(continent.to_string()),
+        })
+    }
+}
+
+fn create_catalog() -> Catalog {
+    let mut example = create_example();
+    let origin = example.origin().clone();
+    let www = Name::parse("www.example.com.", None).unwrap();
+
+    for ip in &[Ipv4Addr::new(192, 0, 2, 1), Ipv4Addr::new(198, 51, 100, 1)] {
+        example.upsert(Record::from_rdata(www.clone(), 86400, RData::A(*ip)), 0);
+    }
+
+    let mut selector = GeoIpSelector::new(Box::new(TestDatabase));
+    selector.add_group(
+        &www,
+        &["de".to_string(), "FR".to_string()],
+        &[IpAddr::from(Ipv4Addr::new(192, 0, 2, 1))],
+    );
+    selector.add_group(
+        &www,
+        &["AS".to_string()],
+        &[IpAddr::from(Ipv4Addr::new(198, 51, 100, 1))],
+    );
+
+    let mut catalog = Catalog::new();
+    catalog.upsert(origin.clone(), Box::new(example));
+    catalog.set_answer_selector(origin, Arc::new(selector));
+    catalog
+}
+
+fn lookup(catalog: &Catalog, src: IpAddr, client_subnet: Option<Vec<u8>>) -> Vec<RData> {
+    let mut question: Message = Message::new();
+    let mut query: Query = Query::new();
+    query.set_name(Name::parse("www.example.com.", None).unwrap());
+    question.add_query(query);
+
+    if let Some(client_subnet) = client_subnet {
+        let mut edns = Edns::new();
+        edns.set_option(EdnsOption::Unknown(8, client_subnet));
+        question.set_edns(edns);
+    }
+
+    let request = Request {
+        message: MessageRequest::from_bytes(&question.to_bytes().unwrap()).unwrap(),
+        src: SocketAddr::new(src, 53),
+        protocol: Protocol::Udp,
+        tsig_key: None,
+    };
+
+    let response_handler = TestResponseHandler::new();
+    catalog
+        .handle_request(request, response_handler.clone())
+        .wait()
+        .unwrap();
+    let result = response_handler.into_message().wait().unwrap();
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+
+    let mut answers = result
+        .answers()
+        .iter()
+        .map(|r| r.rdata().clone())
+        .collect::<Vec<_>>();
+    answers.sort();
+    answers
+}
+
+#[test]
+fn test_country_group() {
+    let catalog = create_catalog();
+
+    assert_eq!(
+        lookup(&catalog, IpAddr::from([10, 1, 2, 3]), None),
+        vec![RData::A(Ipv4Addr::new(192, 0, 2, 1))]
+    );
+}
+
+#[test]
+fn test_continent_group() {
+    let catalog = create_catalog();
+
+    assert_eq!(
+        lookup(&catalog, IpAddr::from([11, 1, 2, 3]), None),
+        vec![RData::A(Ipv4Addr::new(198, 51, 100, 1))]
+    );
+}
+
+#[test]
+fn test_no_group() {
+    let catalog = create_catalog();
+
+    // the addresses in no group are answered to clients without a group
+    assert_eq!(
+        lookup(&catalog, IpAddr::from([127, 0, 0, 1]), None),
+        vec![RData::A(Ipv4Addr::new(93, 184, 216, 34))]
+    );
+}
+
+#[test]
+fn test_client_subnet() {
+    let catalog = create_catalog();
+
+    // the client subnet, 11.1.2.0/24, takes precedence over the source address
+    assert_eq!(
+        lookup(
+            &catalog,
+            IpAddr::from([10, 1, 2, 3]),
+            Some(vec![0, 1, 24, 0, 11, 1, 2])
+        ),
+        vec![RData::A(Ipv4Addr::new(198, 51, 100, 1))]
+    );
+}
@@ -0,0 +1,86 @@
+extern crate trust_dns_resolver;
+extern crate trust_dns_server;
+
+use std::net::Ipv4Addr;
+use std::time::Duration;
+
+use trust_dns_resolver::config::ResolverOpts;
+use trust_dns_resolver::Resolver;
+use trust_dns_server::proto::op::{Query, ResponseCode};
+use trust_dns_server::proto::rr::{Name, RData, Record, RecordType};
+use trust_dns_server::server::Protocol;
+use trust_dns_server::testing::{MockResponse, MockServer};
+
+fn www() -> Name {
+    Name::from_ascii("www.example.com.").unwrap()
+}
+
+fn answers() -> MockResponse {
+    MockResponse::answers(vec![Record::from_rdata(
+        www(),
+        86400,
+        RData::A(Ipv4Addr::new(127, 0, 0, 1)),
+    )])
+}
+
+fn resolver_opts() -> ResolverOpts {
+    let mut options = ResolverOpts::default();
+    options.timeout = Duration::from_millis(200);
+    options
+}
+
+#[test]
+fn test_mock_server_truncated() {
+    let server = MockServer::new()
+        .respond(
+            Query::query(www(), RecordType::A),
+            answers().with_truncated(true),
+        )
+        .start()
+        .expect("failed to start the server");
+    let resolver = Resolver::new(server.resolver_config(), resolver_opts()).unwrap();
+
+    let lookup = resolver
+        .lookup("www.example.com.", RecordType::A)
+        .expect("lookup failed");
+    assert_eq!(
+        *lookup.iter().next().unwrap(),
+        RData::A(Ipv4Addr::new(127, 0, 0, 1))
+    );
+
+    let protocols = server
+        .requests()
+        .into_iter()
+        .map(|(protocol, _)| protocol)
+        .collect::<Vec<_>>();
+    assert_eq!(protocols, vec![Protocol::Udp, Protocol::Tcp]);
+}
+
+#[test]
+fn test_mock_server_dropped() {
+    let server = MockServer::new()
+        .respond(
+            Query::query(www(), RecordType::A),
+            answers().with_dropped(1),
+        )
+        .start()
+        .expect("failed to start the server");
+    let resolver = Resolver::new(server.resolver_config(), resolver_opts()).unwrap();
+
+    resolver
+        .lookup("www.example.com.", RecordType::A)
+        .expect("lookup failed");
+    assert!(server.requests().len() >= 2);
+}
+
+#[test]
+fn test_mock_server_response_code() {
+    let server = MockServer::new()
+        .with_default(MockResponse::response_code(ResponseCode::ServFail))
+        .start()
+        .expect("failed to start the server");
+    let resolver = Resolver::new(server.resolver_config(), resolver_opts()).unwrap();
+
+    assert!(resolver.lookup("www.example.com.", RecordType::A).is_err());
+    assert!(!server.requests().is_empty());
+}
@@ -11,6 +11,7 @@ use std::sync::{
     atomic::{AtomicIsize, Ordering},
     Arc,
 };
+use std::time::Duration;
 
 use futures::future::{self, Future, Loop};
 use tokio::runtime::current_thread::Runtime;
@@ -176,6 +177,72 @@ fn test_datagram_stream_upgrade() {
 
     let response = reactor.block_on(future).unwrap();
     assert_eq!(response.answers()[0], tcp_record);
+    assert!(response.tcp_fallback());
+}
+
+#[test]
+fn test_datagram_truncated_accept() {
+    // lookup to UDP should return truncated message
+    // the truncation strategy returns it instead of a lookup on TCP
+
+    let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+
+    let udp_record = v4_record(query.name().clone(), Ipv4Addr::new(127, 0, 0, 1));
+    let tcp_record = v4_record(query.name().clone(), Ipv4Addr::new(127, 0, 0, 2));
+
+    let mut udp_message = message(query.clone(), vec![udp_record.clone()], vec![], vec![]);
+    udp_message.as_mut().unwrap().set_truncated(true);
+
+    let tcp_message = message(query.clone(), vec![tcp_record], vec![], vec![]);
+
+    let mut reactor = Runtime::new().unwrap();
+
+    let mut options = ResolverOpts::default();
+    options.truncation_strategy = TruncationStrategy::Accept;
+
+    let udp_nameserver = mock_nameserver(vec![udp_message.map(Into::into)], options);
+    let tcp_nameserver = mock_nameserver(vec![tcp_message.map(Into::into)], options);
+
+    let mut pool = mock_nameserver_pool(vec![udp_nameserver], vec![tcp_nameserver], None, options);
+
+    let request = message(query, vec![], vec![], vec![]).unwrap();
+    let future = pool.send(request);
+
+    let response = reactor.block_on(future).unwrap();
+    assert_eq!(response.answers()[0], udp_record);
+    assert!(response.truncated());
+    assert!(!response.tcp_fallback());
+}
+
+#[test]
+fn test_datagram_truncated_retry() {
+    // lookup to UDP should return truncated message
+    // the truncation strategy fails the request instead of a lookup on TCP
+
+    let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+
+    let udp_record = v4_record(query.name().clone(), Ipv4Addr::new(127, 0, 0, 1));
+    let tcp_record = v4_record(query.name().clone(), Ipv4Addr::new(127, 0, 0, 2));
+
+    let mut udp_message = message(query.clone(), vec![udp_record], vec![], vec![]);
+    udp_message.as_mut().unwrap().set_truncated(true);
+
+    let tcp_message = message(query.clone(), vec![tcp_record], vec![], vec![]);
+
+    let mut reactor = Runtime::new().unwrap();
+
+    let mut options = ResolverOpts::default();
+    options.truncation_strategy = TruncationStrategy::Retry;
+
+    let udp_nameserver = mock_nameserver(vec![udp_message.map(Into::into)], options);
+    let tcp_nameserver = mock_nameserver(vec![tcp_message.map(Into::into)], options);
+
+    let mut pool = mock_nameserver_pool(vec![udp_nameserver], vec![tcp_nameserver], None, options);
+
+    let request = message(query, vec![], vec![], vec![]).unwrap();
+    let future = pool.send(request);
+
+    assert!(reactor.block_on(future).is_err());
 }
 
 #[test]
@@ -208,6 +275,7 @@ fn test_datagram_fails_to_stream() {
 
     let response = reactor.block_on(future).unwrap();
     assert_eq!(response.answers()[0], tcp_record);
+    assert!(response.tcp_fallback());
 }
 
 #[test]
@@ -542,3 +610,170 @@ fn test_concurrent_requests_0_conn() {
     let response = reactor.block_on(future).unwrap();
     assert_eq!(response.answers()[0], udp_record);
 }
+
+/// Counts the requests sent, the response to the first is optionally never received
+#[derive(Clone)]
+struct OnSendHedge {
+    sends: Arc<AtomicIsize>,
+    stall_first: bool,
+}
+
+impl OnSendHedge {
+    fn new(stall_first: bool) -> Self {
+        Self {
+            sends: Arc::new(AtomicIsize::new(0)),
+            stall_first,
+        }
+    }
+
+    fn sends(&self) -> isize {
+        self.sends.load(Ordering::Relaxed)
+    }
+}
+
+impl OnSend for OnSendHedge {
+    fn on_send(
+        &mut self,
+        response: Result<DnsResponse, ProtoError>,
+    ) -> Box<Future<Item = DnsResponse, Error = ProtoError> + Send> {
+        let sends = self.sends.fetch_add(1, Ordering::Relaxed);
+
+        if self.stall_first && sends == 0 {
+            Box::new(future::empty())
+        } else {
+            Box::new(future::result(response))
+        }
+    }
+}
+
+fn hedged_request(
+    options: ResolverOpts,
+    responses: Vec<ProtoResult<DnsResponse>>,
+    on_send: OnSendHedge,
+) -> ProtoResult<DnsResponse> {
+    let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+
+    let mut reactor = Runtime::new().unwrap();
+
+    let udp_nameservers = responses
+        .into_iter()
+        .map(|response| mock_nameserver_on_send(vec![response], options, on_send.clone()))
+        .collect();
+    let mut pool = mock_nameserver_pool_on_send(udp_nameservers, vec![], None, options, on_send);
+
+    let request = message(query, vec![], vec![], vec![]).unwrap();
+    reactor.block_on(pool.send(request))
+}
+
+fn hedged_response() -> ProtoResult<DnsResponse> {
+    let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+    let udp_record = v4_record(query.name().clone(), Ipv4Addr::new(127, 0, 0, 1));
+    message(query, vec![udp_record], vec![], vec![]).map(Into::into)
+}
+
+#[test]
+fn test_hedged_requests_slow_name_server() {
+    let mut options = ResolverOpts::default();
+    options.hedge_delay = Some(Duration::from_millis(10));
+
+    // the first request is never answered, the second is sent after the hedge delay
+    let on_send = OnSendHedge::new(true);
+    let response = hedged_request(
+        options,
+        vec![hedged_response(), hedged_response()],
+        on_send.clone(),
+    )
+    .unwrap();
+
+    assert_eq!(response.answers().len(), 1);
+    assert_eq!(on_send.sends(), 2);
+}
+
+#[test]
+fn test_hedged_requests_fast_name_server() {
+    let mut options = ResolverOpts::default();
+    options.hedge_delay = Some(Duration::from_secs(60));
+
+    // the first response is received before the hedge delay
+    let on_send = OnSendHedge::new(false);
+    let response = hedged_request(
+        options,
+        vec![hedged_response(), hedged_response()],
+        on_send.clone(),
+    )
+    .unwrap();
+
+    assert_eq!(response.answers().len(), 1);
+    assert_eq!(on_send.sends(), 1);
+}
+
+#[test]
+fn test_hedged_requests_failed_name_server() {
+    let mut options = ResolverOpts::default();
+    options.hedge_delay = Some(Duration::from_secs(60));
+
+    // a failed request is followed by the next, without waiting for the hedge delay
+    let on_send = OnSendHedge::new(false);
+    let response = hedged_request(
+        options,
+        vec![
+            error(ProtoError::from("failed")),
+            error(ProtoError::from("failed")),
+            hedged_response(),
+        ],
+        on_send.clone(),
+    );
+
+    assert!(response.is_ok(), "{:?}", response.err());
+
+    let on_send = OnSendHedge::new(false);
+    let response = hedged_request(
+        options,
+        vec![
+            error(ProtoError::from("first")),
+            error(ProtoError::from("second")),
+        ],
+        on_send.clone(),
+    );
+
+    assert!(response.is_err());
+    assert_eq!(on_send.sends(), 2);
+}
+
+#[test]
+fn test_name_server_down_and_up_events() {
+    use futures::Stream;
+    use trust_dns_resolver::ResolverEvent;
+
+    let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+    let mut reactor = Runtime::new().unwrap();
+
+    // the client reconnected after the failure answers empty messages
+    let mut name_server =
+        mock_nameserver(vec![error(ProtoError::from("failed"))], Default::default());
+    let events = name_server.events();
+
+    let request = message(query.clone(), vec![], vec![], vec![]).unwrap();
+    assert!(reactor.block_on(name_server.send(request.clone())).is_err());
+    assert!(reactor.block_on(name_server.send(request.clone())).is_ok());
+    assert!(reactor.block_on(name_server.send(request.clone())).is_ok());
+    drop(name_server);
+
+    let events = reactor.block_on(events.collect()).unwrap();
+    assert_eq!(events.len(), 2, "{:?}", events);
+    match events[0] {
+        ResolverEvent::NameServerDown {
+            protocol,
+            ref error,
+            ..
+        } => {
+            assert_eq!(protocol, Protocol::Udp);
+            assert_eq!(error.to_string(), "failed");
+        }
+        ref event => panic!("expected NameServerDown: {:?}", event),
+    }
+    match events[1] {
+        ResolverEvent::NameServerUp { protocol, .. } => assert_eq!(protocol, Protocol::Udp),
+        ref event => panic!("expected NameServerUp: {:?}", event),
+    }
+}
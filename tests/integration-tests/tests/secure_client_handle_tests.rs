@@ -1,6 +1,8 @@
 #![cfg(feature = "dnssec")]
 
+extern crate chrono;
 extern crate futures;
+extern crate openssl;
 extern crate tokio;
 extern crate trust_dns;
 extern crate trust_dns_integration;
@@ -9,6 +11,7 @@ extern crate trust_dns_server;
 
 use std::net::*;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 
 use tokio::runtime::current_thread::Runtime;
@@ -18,16 +21,17 @@ use trust_dns::client::{
 };
 use trust_dns::op::ResponseCode;
 use trust_dns::rr::dnssec::TrustAnchor;
+use trust_dns::rr::rdata::DNSSECRecordType;
 use trust_dns::rr::Name;
 use trust_dns::rr::{DNSClass, RData, RecordType};
 use trust_dns::tcp::TcpClientStream;
 
 use trust_dns_proto::SecureDnsHandle;
-use trust_dns_proto::xfer::DnsMultiplexerSerialResponse;
+use trust_dns_proto::xfer::{DnsHandle, DnsMultiplexerSerialResponse, DnsRequest};
 use trust_dns_proto::udp::{UdpClientStream, UdpResponse};
 use trust_dns_server::authority::{Authority, Catalog};
 
-use trust_dns_integration::authority::create_secure_example;
+use trust_dns_integration::authority::{create_example, create_secure_example};
 use trust_dns_integration::TestClientStream;
 
 #[test]
@@ -72,6 +76,159 @@ where
     }
 }
 
+/// Counts the requests sent through the handle
+#[derive(Clone)]
+struct CountingHandle<H: DnsHandle> {
+    handle: H,
+    sent: Arc<AtomicUsize>,
+}
+
+impl<H: DnsHandle> DnsHandle for CountingHandle<H> {
+    type Response = H::Response;
+
+    fn send<R: Into<DnsRequest>>(&mut self, request: R) -> Self::Response {
+        self.sent.fetch_add(1, Ordering::Relaxed);
+        self.handle.send(request)
+    }
+}
+
+/// Returns the number of requests sent to validate the query, with or without the CHAIN option
+fn chain_query_example(chain_query: bool) -> usize {
+    let authority = create_secure_example();
+    let public_key = authority
+        .secure_keys()
+        .first()
+        .expect("expected a key in the authority")
+        .key()
+        .to_public_key()
+        .expect("could not convert keypair to public_key");
+    let mut trust_anchor = TrustAnchor::new();
+    trust_anchor.insert_trust_anchor(&public_key);
+
+    let mut catalog = Catalog::new();
+    catalog.upsert(authority.origin().clone(), Box::new(authority));
+
+    let mut io_loop = Runtime::new().unwrap();
+    let (stream, sender) = TestClientStream::new(Arc::new(Mutex::new(catalog)));
+    let (bg, client) = ClientFuture::new(stream, Box::new(sender), None);
+    io_loop.spawn(bg);
+
+    let sent = Arc::new(AtomicUsize::new(0));
+    let client = CountingHandle {
+        handle: client,
+        sent: Arc::clone(&sent),
+    };
+    let mut client = SecureClientHandle::with_trust_anchor(client, trust_anchor);
+    if chain_query {
+        client = client.with_chain_query(Name::root());
+    }
+
+    let name = Name::from_str("www.example.com").unwrap();
+    let response = io_loop
+        .block_on(client.query(name.clone(), DNSClass::IN, RecordType::A))
+        .expect("query failed");
+
+    assert_eq!(response.answers().len(), 1);
+    assert_eq!(response.answers()[0].name(), &name);
+    // the chain is not part of the response
+    assert!(response
+        .name_servers()
+        .iter()
+        .all(|r| r.rr_type() != RecordType::DNSSEC(DNSSECRecordType::DNSKEY)));
+
+    sent.load(Ordering::Relaxed)
+}
+
+#[test]
+fn test_chain_query_example_nonet() {
+    // the DNSKEY of the zone is queried to validate the response, unless it is in the chain
+    assert!(chain_query_example(false) > 1);
+    assert_eq!(chain_query_example(true), 1);
+}
+
+/// Validates a query of a zone signed with two keys, the resolver trusting only the first one
+///
+/// With `colliding_key`, the zone also has a key with the tag and algorithm of the first one,
+///  which did not sign the zone, the signatures must also be verified with the other key.
+fn multiple_keys_example(colliding_key: bool) {
+    use chrono::Duration;
+    use openssl::rsa::Rsa;
+    use trust_dns::rr::dnssec::{Algorithm, KeyPair, PublicKey, PublicKeyBuf, Signer};
+    use trust_dns::rr::rdata::{DNSSECRData, DNSKEY};
+    use trust_dns::rr::Record;
+
+    let mut authority = create_example();
+    let origin = Name::from(authority.origin().clone());
+    let signers = (0..2)
+        .map(|_| {
+            let key = KeyPair::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+            let dnskey = key.to_dnskey(Algorithm::RSASHA256).unwrap();
+            Signer::dnssec(dnskey, key, origin.clone(), Duration::weeks(1))
+        })
+        .collect::<Vec<_>>();
+
+    let public_key = signers[0].key().to_public_key().unwrap();
+    let mut trust_anchor = TrustAnchor::new();
+    trust_anchor.insert_trust_anchor(&public_key);
+
+    if colliding_key {
+        // swapping bytes at even offsets keeps the checksum of the key tag
+        let mut colliding = public_key.public_bytes().to_vec();
+        let (i, j) = (10..colliding.len() - 2)
+            .step_by(2)
+            .map(|i| (i, i + 2))
+            .find(|&(i, j)| colliding[i] != colliding[j])
+            .unwrap();
+        colliding.swap(i, j);
+
+        let dnskey = DNSKEY::new(true, true, false, Algorithm::RSASHA256, colliding.clone());
+        assert_eq!(
+            dnskey.calculate_key_tag().unwrap(),
+            signers[0].calculate_key_tag().unwrap()
+        );
+        trust_anchor.insert_trust_anchor(&PublicKeyBuf::new(colliding));
+        authority.upsert(
+            Record::from_rdata(
+                origin.clone(),
+                86400,
+                RData::DNSSEC(DNSSECRData::DNSKEY(dnskey)),
+            ),
+            0,
+        );
+    }
+
+    for signer in signers {
+        authority.add_zone_signing_key(signer).unwrap();
+    }
+    authority.secure_zone().unwrap();
+
+    let mut catalog = Catalog::new();
+    catalog.upsert(authority.origin().clone(), Box::new(authority));
+
+    let mut io_loop = Runtime::new().unwrap();
+    let (stream, sender) = TestClientStream::new(Arc::new(Mutex::new(catalog)));
+    let (bg, client) = ClientFuture::new(stream, Box::new(sender), None);
+    io_loop.spawn(bg);
+    let mut client = SecureClientHandle::with_trust_anchor(client, trust_anchor);
+
+    let name = Name::from_str("www.example.com").unwrap();
+    let response = io_loop
+        .block_on(client.query(name.clone(), DNSClass::IN, RecordType::A))
+        .expect("query failed");
+    assert_eq!(response.answers().len(), 1);
+    assert_eq!(response.answers()[0].name(), &name);
+}
+
+#[test]
+fn test_multiple_keys_example_nonet() {
+    multiple_keys_example(false);
+}
+
+#[test]
+fn test_colliding_key_tags_example_nonet() {
+    multiple_keys_example(true);
+}
+
 #[test]
 fn test_nsec_query_example_nonet() {
     with_nonet(test_nsec_query_example);
@@ -6,6 +6,8 @@ extern crate tokio_tcp;
 extern crate tokio_timer;
 extern crate tokio_udp;
 extern crate trust_dns;
+#[cfg(feature = "dns-over-https")]
+extern crate trust_dns_https;
 extern crate trust_dns_integration;
 extern crate trust_dns_openssl;
 extern crate trust_dns_proto;
@@ -13,6 +15,7 @@ extern crate trust_dns_rustls;
 extern crate trust_dns_server;
 
 use std::io;
+use std::iter;
 use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
 use std::str::FromStr;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -30,11 +33,14 @@ use trust_dns::client::*;
 use trust_dns::op::*;
 use trust_dns::rr::*;
 use trust_dns::tcp::TcpClientConnection;
-use trust_dns::udp::UdpClientConnection;
+use trust_dns::udp::{UdpClientConnection, UdpMultiplexedClientConnection};
 use trust_dns_proto::error::ProtoError;
 use trust_dns_proto::xfer::DnsRequestSender;
 
-use trust_dns_server::authority::{Authority, Catalog};
+use trust_dns_server::authority::{Authority, Catalog, MessageResponseBuilder};
+#[cfg(feature = "dns-over-https")]
+use trust_dns_server::server::Health;
+use trust_dns_server::server::{Request, RequestHandler, ResponseHandler};
 use trust_dns_server::ServerFuture;
 
 use trust_dns_integration::authority::create_example;
@@ -69,6 +75,54 @@ fn test_server_www_udp() {
     server_thread.join().unwrap();;
 }
 
+#[test]
+fn test_server_www_udp_multiplexed() {
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 0));
+    let udp_socket = UdpSocket::bind(&addr).unwrap();
+
+    let ipaddr = udp_socket.local_addr().unwrap();
+    println!("udp_socket on port: {}", ipaddr);
+    let server_continue = Arc::new(AtomicBool::new(true));
+    let server_continue2 = server_continue.clone();
+
+    let server_thread = thread::Builder::new()
+        .name("test_server:udp:server".to_string())
+        .spawn(move || server_thread_udp(udp_socket, server_continue2))
+        .unwrap();
+
+    let client_thread = thread::Builder::new()
+        .name("test_server:udp:client".to_string())
+        .spawn(move || {
+            let conn = UdpMultiplexedClientConnection::new(ipaddr).unwrap();
+            client_thread_www(conn.clone());
+
+            // concurrent queries share the socket of the connection
+            let mut io_loop = Runtime::new().unwrap();
+            let (bg, client) = ClientFuture::connect(conn.new_stream(None));
+            io_loop.spawn(bg);
+
+            let name = Name::from_str("www.example.com.").unwrap();
+            let queries = (0..20)
+                .map(|_| client.clone().query(name.clone(), DNSClass::IN, RecordType::A))
+                .collect::<Vec<_>>();
+            let responses = io_loop.block_on(future::join_all(queries)).unwrap();
+            for response in responses {
+                assert_eq!(response.response_code(), ResponseCode::NoError);
+                assert_eq!(
+                    *response.answers()[0].rdata(),
+                    RData::A(Ipv4Addr::new(93, 184, 216, 34))
+                );
+            }
+        })
+        .unwrap();
+
+    let client_result = client_thread.join();
+
+    assert!(client_result.is_ok(), "client failed: {:?}", client_result);
+    server_continue.store(false, Ordering::Relaxed);
+    server_thread.join().unwrap();
+}
+
 #[test]
 fn test_server_www_tcp() {
     let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 0));
@@ -142,6 +196,339 @@ fn test_server_unknown_type() {
     server_thread.join().unwrap();;
 }
 
+#[test]
+fn test_server_form_err() {
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 0));
+    let udp_socket = UdpSocket::bind(&addr).unwrap();
+
+    let ipaddr = udp_socket.local_addr().unwrap();
+    println!("udp_socket on port: {}", ipaddr);
+    let server_continue = Arc::new(AtomicBool::new(true));
+    let server_continue2 = server_continue.clone();
+
+    let server_thread = thread::Builder::new()
+        .name("test_server:udp:server".to_string())
+        .spawn(move || server_thread_udp(udp_socket, server_continue2))
+        .unwrap();
+
+    // the header counts a query, which is missing
+    let request = [0x12, 0x34, 0x01, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0];
+
+    let client = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    client
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    client.send_to(&request, ipaddr).unwrap();
+
+    let mut buf = [0_u8; 512];
+    let (len, _) = client
+        .recv_from(&mut buf)
+        .expect("no response to malformed request");
+    let response = Message::from_vec(&buf[..len]).unwrap();
+
+    assert_eq!(response.id(), 0x1234);
+    assert_eq!(response.message_type(), MessageType::Response);
+    assert_eq!(response.response_code(), ResponseCode::FormErr);
+    assert!(response.queries().is_empty());
+
+    server_continue.store(false, Ordering::Relaxed);
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn test_server_request_deadline() {
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 0));
+    let udp_socket = UdpSocket::bind(&addr).unwrap();
+
+    let ipaddr = udp_socket.local_addr().unwrap();
+    println!("udp_socket on port: {}", ipaddr);
+    let server_continue = Arc::new(AtomicBool::new(true));
+    let server_continue2 = server_continue.clone();
+
+    let server_thread = thread::Builder::new()
+        .name("test_server:udp:server".to_string())
+        .spawn(move || {
+            let mut server = ServerFuture::new(StalledHandler);
+            server.set_request_deadline(Duration::from_millis(100));
+            server_thread_udp_with(server, udp_socket, server_continue2)
+        })
+        .unwrap();
+
+    let conn = UdpClientConnection::new(ipaddr).unwrap();
+    let client = SyncClient::new(conn);
+    let client_result = client
+        .query(
+            &Name::from_str("www.example.com.").unwrap(),
+            DNSClass::IN,
+            RecordType::A,
+        )
+        .expect("no response at the deadline");
+
+    assert_eq!(client_result.response_code(), ResponseCode::ServFail);
+    assert_eq!(
+        client_result.queries().first().unwrap().name(),
+        &Name::from_str("www.example.com.").unwrap()
+    );
+    assert!(client_result.answers().is_empty());
+
+    server_continue.store(false, Ordering::Relaxed);
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn test_server_udp_truncation() {
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 0));
+    let udp_socket = UdpSocket::bind(&addr).unwrap();
+
+    let ipaddr = udp_socket.local_addr().unwrap();
+    println!("udp_socket on port: {}", ipaddr);
+    let server_continue = Arc::new(AtomicBool::new(true));
+    let server_continue2 = server_continue.clone();
+
+    let server_thread = thread::Builder::new()
+        .name("test_server:udp:server".to_string())
+        .spawn(move || {
+            server_thread_udp_with(
+                ServerFuture::new(LargeHandler),
+                udp_socket,
+                server_continue2,
+            )
+        })
+        .unwrap();
+
+    let client = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    client
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let mut query = |max_payload: Option<u16>| {
+        let mut message = Message::new();
+        message.set_id(0x1234).add_query(Query::query(
+            Name::from_str("www.example.com.").unwrap(),
+            RecordType::A,
+        ));
+        if let Some(max_payload) = max_payload {
+            let mut edns = Edns::new();
+            edns.set_max_payload(max_payload);
+            message.set_edns(edns);
+        }
+        client.send_to(&message.to_vec().unwrap(), ipaddr).unwrap();
+
+        let mut buf = [0_u8; 4096];
+        let (len, _) = client.recv_from(&mut buf).expect("no response");
+        (len, Message::from_vec(&buf[..len]).unwrap())
+    };
+
+    // without EDNS, the response is truncated to 512 bytes
+    let (len, response) = query(None);
+    assert!(len <= 512, "response of {} bytes", len);
+    assert!(response.truncated());
+    assert!(!response.answers().is_empty());
+
+    // a larger payload is limited to the 1232 bytes which avoid fragmentation
+    let (len, response) = query(Some(4096));
+    assert!(len > 512 && len <= 1232, "response of {} bytes", len);
+    assert!(response.truncated());
+
+    server_continue.store(false, Ordering::Relaxed);
+    server_thread.join().unwrap();
+}
+
+#[test]
+#[cfg(feature = "dns-over-https")]
+fn test_server_www_http() {
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 0));
+    let http_listener = TcpListener::bind(&addr).unwrap();
+
+    let ipaddr = http_listener.local_addr().unwrap();
+    println!("http_listener on port: {}", ipaddr);
+    let server_continue = Arc::new(AtomicBool::new(true));
+    let server_continue2 = server_continue.clone();
+
+    let server_thread = thread::Builder::new()
+        .name("test_server:http:server".to_string())
+        .spawn(move || server_thread_http(http_listener, None, server_continue2))
+        .unwrap();
+
+    let mut message = Message::new();
+    message
+        .set_id(0x1234)
+        .add_query(Query::query(
+            Name::from_str("www.example.com.").unwrap(),
+            RecordType::A,
+        ))
+        .set_recursion_desired(true);
+    let message = message.to_vec().unwrap();
+    let request = trust_dns_https::request::new_get("ns.example.com", &message).unwrap();
+    let path_and_query = request.uri().path_and_query().unwrap().as_str();
+
+    // the GET request, and the same as POST request on the same connection
+    let (head, body) = http_exchange(
+        ipaddr,
+        &[
+            format!(
+                "GET {} HTTP/1.1\r\nHost: ns.example.com\r\nAccept: application/dns-message\r\n\r\n",
+                path_and_query
+            )
+            .into_bytes(),
+            [
+                format!(
+                    "POST /dns-query HTTP/1.1\r\nHost: ns.example.com\r\nContent-Type: application/dns-message\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    message.len()
+                )
+                .as_bytes(),
+                &message,
+            ]
+            .concat(),
+        ],
+    );
+
+    assert_eq!(head.len(), 2);
+    for (head, body) in head.iter().zip(body) {
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{}", head);
+        assert!(head.contains("content-type: application/dns-message\r\n"));
+        // the TTL of the answer
+        assert!(
+            head.contains("cache-control: max-age=86400\r\n"),
+            "{}",
+            head
+        );
+
+        let response = Message::from_vec(&body).unwrap();
+        assert_eq!(response.id(), 0x1234);
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert_eq!(
+            *response.answers()[0].rdata(),
+            RData::A(Ipv4Addr::new(93, 184, 216, 34))
+        );
+    }
+
+    let status_of = |request: String| {
+        let (head, _) = http_exchange(ipaddr, &[request.into_bytes()]);
+        head[0].lines().next().unwrap().to_string()
+    };
+
+    assert_eq!(
+        status_of(format!(
+            "GET {} HTTP/1.1\r\nHost: ns.example.com\r\nAccept: text/html\r\nConnection: close\r\n\r\n",
+            path_and_query
+        )),
+        "HTTP/1.1 406 Not Acceptable"
+    );
+    assert_eq!(
+        status_of(
+            "POST /dns-query HTTP/1.1\r\nHost: ns.example.com\r\nContent-Type: text/plain\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+        ),
+        "HTTP/1.1 415 Unsupported Media Type"
+    );
+    assert_eq!(
+        status_of(
+            "GET /dns-query?dns=*** HTTP/1.1\r\nHost: ns.example.com\r\nConnection: close\r\n\r\n"
+                .to_string()
+        ),
+        "HTTP/1.1 400 Bad Request"
+    );
+
+    server_continue.store(false, Ordering::Relaxed);
+    server_thread.join().unwrap();
+}
+
+#[test]
+#[cfg(feature = "dns-over-https")]
+fn test_server_health_http() {
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 0));
+    let http_listener = TcpListener::bind(&addr).unwrap();
+
+    let ipaddr = http_listener.local_addr().unwrap();
+    println!("http_listener on port: {}", ipaddr);
+    let server_continue = Arc::new(AtomicBool::new(true));
+    let server_continue2 = server_continue.clone();
+
+    let health = Arc::new(Health::new());
+    health.zone_loaded(LowerName::from_str("example.com.").unwrap(), None);
+    let health2 = health.clone();
+
+    let server_thread = thread::Builder::new()
+        .name("test_server:http:server".to_string())
+        .spawn(move || server_thread_http(http_listener, Some(health2), server_continue2))
+        .unwrap();
+
+    let get = |path: &str| {
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: ns.example.com\r\nConnection: close\r\n\r\n",
+            path
+        );
+        let (head, body) = http_exchange(ipaddr, &[request.into_bytes()]);
+        (
+            head[0].lines().next().unwrap().to_string(),
+            String::from_utf8(body[0].clone()).unwrap(),
+        )
+    };
+
+    assert_eq!(
+        get("/healthz"),
+        ("HTTP/1.1 200 OK".to_string(), "ok\n".to_string())
+    );
+    assert_eq!(
+        get("/readyz"),
+        (
+            "HTTP/1.1 200 OK".to_string(),
+            "ready\nzone example.com.: ok\n".to_string()
+        )
+    );
+
+    health.zone_loading(LowerName::from_str("example.net.").unwrap());
+    assert_eq!(
+        get("/readyz"),
+        (
+            "HTTP/1.1 503 Service Unavailable".to_string(),
+            "not ready\nzone example.com.: ok\nzone example.net.: loading\n".to_string()
+        )
+    );
+
+    server_continue.store(false, Ordering::Relaxed);
+    server_thread.join().unwrap();
+}
+
+/// Sends the requests on one connection, returning the heads and bodies of the responses
+#[cfg(feature = "dns-over-https")]
+fn http_exchange(ipaddr: SocketAddr, requests: &[Vec<u8>]) -> (Vec<String>, Vec<Vec<u8>>) {
+    use std::io::{Read, Write};
+
+    let mut stream = std::net::TcpStream::connect(ipaddr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    for request in requests {
+        stream.write_all(request).unwrap();
+    }
+
+    // the last request closes the connection
+    let mut responses = Vec::new();
+    stream.read_to_end(&mut responses).unwrap();
+
+    let mut heads = Vec::new();
+    let mut bodies = Vec::new();
+    let mut responses = &responses[..];
+    while !responses.is_empty() {
+        let head_len = responses
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .expect("incomplete response")
+            + 4;
+        let head = String::from_utf8(responses[..head_len].to_vec()).unwrap();
+        let body_len = head
+            .lines()
+            .find(|line| line.starts_with("content-length: "))
+            .map_or(0, |line| line["content-length: ".len()..].parse().unwrap());
+
+        bodies.push(responses[head_len..head_len + body_len].to_vec());
+        heads.push(head);
+        responses = &responses[head_len + body_len..];
+    }
+
+    (heads, bodies)
+}
+
 #[cfg(all(feature = "dns-over-openssl", not(feature = "dns-over-rustls")))]
 fn read_file(path: &str) -> Vec<u8> {
     use std::fs::File;
@@ -274,11 +661,69 @@ fn new_catalog() -> Catalog {
     catalog
 }
 
+/// Never finishes handling a request
+struct StalledHandler;
+
+impl RequestHandler for StalledHandler {
+    type ResponseFuture = future::Empty<(), ()>;
+
+    fn handle_request<R: ResponseHandler>(
+        &self,
+        _request: Request,
+        _response_handle: R,
+    ) -> Self::ResponseFuture {
+        future::empty()
+    }
+}
+
+/// Answers all requests with 200 A records, more than fit in any UDP response
+struct LargeHandler;
+
+impl RequestHandler for LargeHandler {
+    type ResponseFuture = future::FutureResult<(), ()>;
+
+    fn handle_request<R: ResponseHandler>(
+        &self,
+        request: Request,
+        response_handle: R,
+    ) -> Self::ResponseFuture {
+        let name = Name::from_str("www.example.com.").unwrap();
+        let answers = (0..200)
+            .map(|i| Record::from_rdata(name.clone(), 86400, RData::A(Ipv4Addr::new(10, 0, 0, i))))
+            .collect::<Vec<_>>();
+
+        let mut header = Header::new();
+        header
+            .set_id(request.message.id())
+            .set_message_type(MessageType::Response);
+        let answers: Box<Iterator<Item = &Record> + Send> = Box::new(answers.iter());
+        let empty = || Box::new(iter::empty()) as Box<Iterator<Item = &Record> + Send>;
+        let response = MessageResponseBuilder::new(Some(request.message.raw_queries())).build(
+            header,
+            answers,
+            empty(),
+            empty(),
+            empty(),
+        );
+
+        future::result(response_handle.send_response(response).map_err(|_| ()))
+    }
+}
+
 fn server_thread_udp(udp_socket: UdpSocket, server_continue: Arc<AtomicBool>) {
-    let catalog = new_catalog();
+    server_thread_udp_with(
+        ServerFuture::new(new_catalog()),
+        udp_socket,
+        server_continue,
+    )
+}
 
+fn server_thread_udp_with<T: RequestHandler>(
+    server: ServerFuture<T>,
+    udp_socket: UdpSocket,
+    server_continue: Arc<AtomicBool>,
+) {
     let mut io_loop = Runtime::new().unwrap();
-    let server = ServerFuture::new(catalog);
     io_loop
         .block_on::<Box<Future<Item = (), Error = ()> + Send>>(Box::new(future::lazy(|| {
             server.register_socket(udp_socket);
@@ -310,6 +755,37 @@ fn server_thread_tcp(tcp_listener: TcpListener, server_continue: Arc<AtomicBool>
     }
 }
 
+#[cfg(feature = "dns-over-https")]
+fn server_thread_http(
+    http_listener: TcpListener,
+    health: Option<Arc<Health>>,
+    server_continue: Arc<AtomicBool>,
+) {
+    let catalog = new_catalog();
+    let mut io_loop = Runtime::new().unwrap();
+    let mut server = ServerFuture::new(catalog);
+    if let Some(health) = health {
+        server.set_health(health);
+    }
+    io_loop
+        .block_on::<Box<Future<Item = (), Error = io::Error> + Send>>(Box::new(future::lazy(
+            || {
+                future::result(server.register_http_listener(
+                    http_listener,
+                    Duration::from_secs(30),
+                    "ns.example.com".to_string(),
+                ))
+            },
+        )))
+        .expect("http registration failed");
+
+    while server_continue.load(Ordering::Relaxed) {
+        io_loop
+            .block_on(Delay::new(Instant::now() + Duration::from_millis(10)))
+            .unwrap();
+    }
+}
+
 // FIXME: need a rustls option
 #[cfg(all(feature = "dns-over-openssl", not(feature = "dns-over-rustls")))]
 fn server_thread_tls(
@@ -0,0 +1,133 @@
+extern crate trust_dns_resolver;
+
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use trust_dns_resolver::config::{NameServerConfigGroup, ResolverConfig, ResolverOpts};
+use trust_dns_resolver::proto::op::{Message, MessageType, OpCode};
+use trust_dns_resolver::proto::rr::{RData, Record, RecordType};
+use trust_dns_resolver::testing::{Link, Simulation, VirtualNetwork};
+use trust_dns_resolver::AsyncResolver;
+
+fn addr() -> SocketAddr {
+    "10.0.0.1:53".parse().unwrap()
+}
+
+fn config() -> ResolverConfig {
+    ResolverConfig::from_parts(
+        None,
+        vec![],
+        NameServerConfigGroup::from_ips_clear(&[addr().ip()], addr().port()),
+    )
+}
+
+fn answer(request: &Message) -> Message {
+    let name = request.queries()[0].name().clone();
+    let mut response = Message::new();
+    response
+        .set_message_type(MessageType::Response)
+        .set_op_code(OpCode::Query)
+        .add_queries(request.queries().to_vec())
+        .add_answer(Record::from_rdata(
+            name,
+            60,
+            RData::A(Ipv4Addr::new(127, 0, 0, 1)),
+        ));
+    response
+}
+
+#[test]
+fn test_simulation_cache_expiry() {
+    let network = VirtualNetwork::new(1)
+        .with_server(addr(), answer)
+        .with_link(addr(), Link::new(Duration::from_millis(10)));
+    let (resolver, background) =
+        AsyncResolver::with_runtime(config(), ResolverOpts::default(), network.clone());
+
+    let mut simulation = Simulation::new();
+    simulation.spawn(background);
+
+    let lookup = simulation
+        .block_on(resolver.lookup("www.example.com.", RecordType::A))
+        .expect("lookup failed");
+    assert_eq!(
+        *lookup.iter().next().unwrap(),
+        RData::A(Ipv4Addr::new(127, 0, 0, 1))
+    );
+    assert_eq!(network.requests().len(), 1);
+
+    simulation.advance(Duration::from_secs(30));
+    simulation
+        .block_on(resolver.lookup("www.example.com.", RecordType::A))
+        .expect("lookup failed");
+    assert_eq!(network.requests().len(), 1);
+
+    simulation.advance(Duration::from_secs(31));
+    simulation
+        .block_on(resolver.lookup("www.example.com.", RecordType::A))
+        .expect("lookup failed");
+    assert_eq!(network.requests().len(), 2);
+}
+
+#[test]
+fn test_simulation_timeout() {
+    let network = VirtualNetwork::new(1)
+        .with_server(addr(), answer)
+        .with_link(addr(), Link::default().with_loss(1.0));
+    let mut options = ResolverOpts::default();
+    options.timeout = Duration::from_secs(10);
+    let (resolver, background) = AsyncResolver::with_runtime(config(), options, network.clone());
+
+    let mut simulation = Simulation::new();
+    simulation.spawn(background);
+
+    let start = Instant::now();
+    let virtual_start = simulation.now();
+    assert!(simulation
+        .block_on(resolver.lookup("www.example.com.", RecordType::A))
+        .is_err());
+
+    assert!(simulation.now() - virtual_start >= Duration::from_secs(10));
+    assert!(start.elapsed() < Duration::from_secs(10));
+    assert!(!network.requests().is_empty());
+}
+
+/// Runs lookups over a lossy link with jitter, returning the requests sent and the virtual time
+fn lossy_lookups(seed: u64) -> (usize, Duration, usize) {
+    let network = VirtualNetwork::new(seed)
+        .with_server(addr(), answer)
+        .with_link(
+            addr(),
+            Link::new(Duration::from_millis(20))
+                .with_jitter(Duration::from_millis(50))
+                .with_loss(0.3),
+        );
+    let mut options = ResolverOpts::default();
+    options.timeout = Duration::from_secs(1);
+    options.attempts = 4;
+    let (resolver, background) = AsyncResolver::with_runtime(config(), options, network.clone());
+
+    let mut simulation = Simulation::new();
+    simulation.spawn(background);
+
+    let start = simulation.now();
+    let found = (0..20)
+        .filter(|i| {
+            let name = format!("host-{}.example.com.", i);
+            simulation
+                .block_on(resolver.lookup(name.as_str(), RecordType::A))
+                .is_ok()
+        })
+        .count();
+
+    (network.requests().len(), simulation.now() - start, found)
+}
+
+#[test]
+fn test_simulation_deterministic() {
+    let first = lossy_lookups(42);
+    assert_eq!(first, lossy_lookups(42));
+
+    // some of the requests were lost, and retried
+    assert!(first.0 > 20);
+}
@@ -1043,10 +1043,10 @@ fn test_recovery() {
         .recover_with_journal(journal)
         .expect("recovery");
 
-    assert_eq!(
-        recovered_authority.records().len(),
-        authority.records().len()
-    );
+    let records = authority.records();
+    let recovered_records = recovered_authority.records();
+
+    assert_eq!(recovered_records.len(), records.len());
     assert!(recovered_authority
         .soa()
         .wait()
@@ -1054,25 +1054,20 @@ fn test_recovery() {
         .iter()
         .zip(authority.soa().wait().unwrap().iter())
         .all(|(r1, r2)| r1 == r2));
-    assert!(recovered_authority
-        .records()
-        .iter()
-        .all(|(rr_key, rr_set)| {
-            let other_rr_set = authority
-                .records()
-                .get(rr_key)
-                .unwrap_or_else(|| panic!("key doesn't exist: {:?}", rr_key));
-            rr_set
-                .records_without_rrsigs()
-                .zip(other_rr_set.records_without_rrsigs())
-                .all(|(record, other_record)| {
-                    record.ttl() == other_record.ttl() && record.rdata() == other_record.rdata()
-                })
-        },));
-
-    assert!(authority.records().iter().all(|(rr_key, rr_set)| {
-        let other_rr_set = recovered_authority
-            .records()
+    assert!(recovered_records.iter().all(|(rr_key, rr_set)| {
+        let other_rr_set = records
+            .get(rr_key)
+            .unwrap_or_else(|| panic!("key doesn't exist: {:?}", rr_key));
+        rr_set
+            .records_without_rrsigs()
+            .zip(other_rr_set.records_without_rrsigs())
+            .all(|(record, other_record)| {
+                record.ttl() == other_record.ttl() && record.rdata() == other_record.rdata()
+            })
+    },));
+
+    assert!(records.iter().all(|(rr_key, rr_set)| {
+        let other_rr_set = recovered_records
             .get(rr_key)
             .unwrap_or_else(|| panic!("key doesn't exist: {:?}", rr_key));
         rr_set
@@ -0,0 +1,224 @@
+#![cfg(feature = "dnssec")]
+
+extern crate futures;
+extern crate tokio;
+extern crate tokio_tcp;
+extern crate trust_dns;
+extern crate trust_dns_integration;
+extern crate trust_dns_proto;
+extern crate trust_dns_server;
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::{future, Stream};
+use tokio::runtime::current_thread::Runtime;
+use tokio_tcp::TcpListener;
+
+use trust_dns::client::{ClientFuture, ClientHandle};
+use trust_dns::op::ResponseCode;
+use trust_dns::rr::dnssec::TSigner;
+use trust_dns::rr::rdata::TXT;
+use trust_dns::rr::{DNSClass, Name, RData, Record, RecordType};
+use trust_dns_proto::rr::dnssec::rdata::TsigAlgorithm;
+use trust_dns_proto::tcp::TcpClientStream;
+use trust_dns_proto::xfer::DnsMultiplexer;
+
+use trust_dns_server::authority::{Authority, Catalog};
+use trust_dns_server::server::{IdentityQuotas, Quota, QuotaKind, TsigKeyring};
+use trust_dns_server::ServerFuture;
+
+use trust_dns_integration::authority::create_example;
+
+fn signer(key: &[u8]) -> TSigner {
+    TSigner::new(
+        key.to_vec(),
+        TsigAlgorithm::HmacSha256,
+        Name::from_ascii("transfer-key.example.com.").unwrap(),
+        300,
+    )
+    .unwrap()
+}
+
+/// Starts a server with a zone large enough to be transferred in multiple messages
+fn start_server(io_loop: &mut Runtime) -> SocketAddr {
+    start_server_with_quotas(io_loop, None)
+}
+
+fn start_server_with_quotas(
+    io_loop: &mut Runtime,
+    quotas: Option<Arc<IdentityQuotas>>,
+) -> SocketAddr {
+    let mut example = create_example();
+    example.set_allow_axfr(true);
+    let serial = example.serial();
+    for i in 0..2000u32 {
+        example.upsert(
+            Record::new()
+                .set_name(Name::parse(&format!("host-{}.example.com.", i), None).unwrap())
+                .set_ttl(86400)
+                .set_rr_type(RecordType::TXT)
+                .set_dns_class(DNSClass::IN)
+                .set_rdata(RData::TXT(TXT::new(vec![format!("host number {}", i)])))
+                .clone(),
+            serial,
+        );
+    }
+
+    let mut catalog = Catalog::new();
+    catalog.upsert(example.origin().clone(), Box::new(example));
+
+    let mut keyring = TsigKeyring::new();
+    keyring.add_key(signer(b"0123456789abcdef0123456789abcdef"));
+
+    let mut server = ServerFuture::new(catalog);
+    server.set_tsig_keyring(keyring);
+    if let Some(quotas) = quotas {
+        server.set_quotas(quotas);
+    }
+
+    let listener = TcpListener::bind(&([127, 0, 0, 1], 0).into()).unwrap();
+    let addr = listener.local_addr().unwrap();
+    io_loop
+        .block_on(future::lazy(move || {
+            server.register_listener(listener, Duration::from_secs(30))
+        }))
+        .expect("tcp registration failed");
+
+    addr
+}
+
+#[test]
+fn test_tsig_signed_axfr() {
+    let mut io_loop = Runtime::new().unwrap();
+    let addr = start_server(&mut io_loop);
+
+    let (stream, sender) = TcpClientStream::new(addr);
+    let multiplexer = DnsMultiplexer::new(
+        stream,
+        sender,
+        Some(Arc::new(signer(b"0123456789abcdef0123456789abcdef"))),
+    );
+    let (bg, mut client) = ClientFuture::connect(multiplexer);
+    io_loop.spawn(bg);
+
+    let response = io_loop
+        .block_on(client.zone_transfer(Name::from_ascii("example.com.").unwrap(), None))
+        .expect("transfer failed");
+
+    assert!(response.len() > 1);
+    let answers = response
+        .messages()
+        .flat_map(|message| message.answers().iter())
+        .collect::<Vec<_>>();
+    assert_eq!(answers.first().unwrap().rr_type(), RecordType::SOA);
+    assert_eq!(answers.last().unwrap().rr_type(), RecordType::SOA);
+    assert_eq!(
+        answers
+            .iter()
+            .filter(|r| r.name().to_string().starts_with("host-"))
+            .count(),
+        2000
+    );
+    assert!(response
+        .messages()
+        .all(|message| message.response_code() == ResponseCode::NoError));
+}
+
+#[test]
+fn test_tsig_signed_axfr_stream() {
+    let mut io_loop = Runtime::new().unwrap();
+    let addr = start_server(&mut io_loop);
+
+    let (stream, sender) = TcpClientStream::new(addr);
+    let multiplexer = DnsMultiplexer::new(
+        stream,
+        sender,
+        Some(Arc::new(signer(b"0123456789abcdef0123456789abcdef"))),
+    );
+    let (bg, mut client) = ClientFuture::connect(multiplexer);
+    io_loop.spawn(bg);
+
+    let answers = io_loop
+        .block_on(
+            client
+                .zone_transfer_stream(Name::from_ascii("example.com.").unwrap(), None)
+                .collect(),
+        )
+        .expect("transfer failed");
+
+    assert_eq!(answers.first().unwrap().rr_type(), RecordType::SOA);
+    assert_eq!(answers.last().unwrap().rr_type(), RecordType::SOA);
+    assert_eq!(
+        answers
+            .iter()
+            .filter(|r| r.name().to_string().starts_with("host-"))
+            .count(),
+        2000
+    );
+}
+
+#[test]
+fn test_tsig_axfr_bad_key() {
+    let mut io_loop = Runtime::new().unwrap();
+    let addr = start_server(&mut io_loop);
+
+    let (stream, sender) = TcpClientStream::new(addr);
+    let multiplexer = DnsMultiplexer::new(
+        stream,
+        sender,
+        Some(Arc::new(signer(b"not the key of the server"))),
+    );
+    let (bg, mut client) = ClientFuture::connect(multiplexer);
+    io_loop.spawn(bg);
+
+    // the server responds NOTAUTH with a BADSIG error, which isn't signed
+    let response =
+        io_loop.block_on(client.zone_transfer(Name::from_ascii("example.com.").unwrap(), None));
+    assert!(response.is_err());
+}
+
+#[test]
+fn test_tsig_query_quota() {
+    let key_name = Name::from_ascii("transfer-key.example.com.").unwrap();
+    let mut quotas = IdentityQuotas::new();
+    quotas.set_quota(
+        &key_name,
+        Quota {
+            queries_per_second: Some(1),
+            ..Quota::default()
+        },
+    );
+    let quotas = Arc::new(quotas);
+
+    let mut io_loop = Runtime::new().unwrap();
+    let addr = start_server_with_quotas(&mut io_loop, Some(Arc::clone(&quotas)));
+
+    let (stream, sender) = TcpClientStream::new(addr);
+    let multiplexer = DnsMultiplexer::new(
+        stream,
+        sender,
+        Some(Arc::new(signer(b"0123456789abcdef0123456789abcdef"))),
+    );
+    let (bg, mut client) = ClientFuture::connect(multiplexer);
+    io_loop.spawn(bg);
+
+    let name = Name::from_ascii("www.example.com.").unwrap();
+    let response = io_loop
+        .block_on(client.query(name.clone(), DNSClass::IN, RecordType::A))
+        .expect("query failed");
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert!(!response.answers().is_empty());
+
+    // the refusal is signed with the key of the request
+    let response = io_loop
+        .block_on(client.query(name, DNSClass::IN, RecordType::A))
+        .expect("query failed");
+    assert_eq!(response.response_code(), ResponseCode::Refused);
+    assert!(response.answers().is_empty());
+
+    let metrics = quotas.metrics(&key_name);
+    assert_eq!(metrics.allowed(QuotaKind::Query), 1);
+    assert_eq!(metrics.refused(QuotaKind::Query), 1);
+}
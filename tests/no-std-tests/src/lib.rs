@@ -0,0 +1,32 @@
+// Copyright 2015-2019 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Uses the wire format of trust-dns-proto from a `#![no_std]` crate, only `core` and `alloc`
+//!  are available to it.
+
+#![no_std]
+
+extern crate alloc;
+extern crate trust_dns_proto;
+
+use alloc::vec::Vec;
+
+use trust_dns_proto::error::ProtoResult;
+use trust_dns_proto::op::{Message, Query};
+use trust_dns_proto::rr::{Name, RecordType};
+
+/// Builds a query for `name` and encodes it to its wire format
+pub fn encode_query(name: &str, query_type: RecordType) -> ProtoResult<Vec<u8>> {
+    let mut message = Message::new();
+    message.add_query(Query::query(Name::from_ascii(name)?, query_type));
+    message.to_vec()
+}
+
+/// Decodes a message from its wire format
+pub fn decode_message(bytes: &[u8]) -> ProtoResult<Message> {
+    Message::from_vec(bytes)
+}